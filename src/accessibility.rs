@@ -0,0 +1,875 @@
+// Accessibility tree derived from the DOM and layout
+//
+// Roles, names and states follow the HTML to ARIA mappings for the
+// elements the engine supports. After a mutation the tree is rebuilt and
+// diffed against the previous one, so only changed nodes are sent to
+// AccessKit and on to screen readers.
+
+use crate::dom::Node;
+use crate::layout::{LayoutBox, Rect};
+use crate::ui::document_title;
+use accesskit::{Action, Affine, DefaultActionVerb, NodeBuilder, NodeClassSet, NodeId, Role, Tree, TreeUpdate};
+use std::collections::HashMap;
+
+/// Identifies a node in the accessibility tree
+///
+/// IDs are derived from the node's position in the DOM, so they stay the
+/// same across rebuilds while the document structure does.
+pub type AxNodeId = u64;
+
+/// The window node hosting the document in AccessKit updates
+pub const WINDOW_NODE_ID: AxNodeId = 0;
+/// The document (root) node
+pub const DOCUMENT_NODE_ID: AxNodeId = 1;
+
+/// Role of an accessibility node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AxRole {
+    Document,
+    Article,
+    Banner,
+    Button,
+    Cell,
+    CheckBox,
+    ComboBox,
+    ContentInfo,
+    Form,
+    GenericContainer,
+    Group,
+    Heading,
+    Image,
+    Link,
+    List,
+    ListItem,
+    Main,
+    Navigation,
+    Paragraph,
+    RadioButton,
+    Row,
+    Section,
+    StaticText,
+    Table,
+    TextInput,
+}
+
+impl AxRole {
+    /// Parse an ARIA `role` attribute value
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "document" => Some(AxRole::Document),
+            "article" => Some(AxRole::Article),
+            "banner" => Some(AxRole::Banner),
+            "button" => Some(AxRole::Button),
+            "cell" | "gridcell" => Some(AxRole::Cell),
+            "checkbox" | "switch" => Some(AxRole::CheckBox),
+            "combobox" | "listbox" => Some(AxRole::ComboBox),
+            "contentinfo" => Some(AxRole::ContentInfo),
+            "form" => Some(AxRole::Form),
+            "generic" | "none" | "presentation" => Some(AxRole::GenericContainer),
+            "group" => Some(AxRole::Group),
+            "heading" => Some(AxRole::Heading),
+            "img" | "image" => Some(AxRole::Image),
+            "link" => Some(AxRole::Link),
+            "list" => Some(AxRole::List),
+            "listitem" => Some(AxRole::ListItem),
+            "main" => Some(AxRole::Main),
+            "navigation" => Some(AxRole::Navigation),
+            "paragraph" => Some(AxRole::Paragraph),
+            "radio" => Some(AxRole::RadioButton),
+            "row" => Some(AxRole::Row),
+            "region" => Some(AxRole::Section),
+            "table" | "grid" => Some(AxRole::Table),
+            "textbox" | "searchbox" => Some(AxRole::TextInput),
+            _ => None,
+        }
+    }
+
+    /// ARIA name of the role
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AxRole::Document => "document",
+            AxRole::Article => "article",
+            AxRole::Banner => "banner",
+            AxRole::Button => "button",
+            AxRole::Cell => "cell",
+            AxRole::CheckBox => "checkbox",
+            AxRole::ComboBox => "combobox",
+            AxRole::ContentInfo => "contentinfo",
+            AxRole::Form => "form",
+            AxRole::GenericContainer => "generic",
+            AxRole::Group => "group",
+            AxRole::Heading => "heading",
+            AxRole::Image => "img",
+            AxRole::Link => "link",
+            AxRole::List => "list",
+            AxRole::ListItem => "listitem",
+            AxRole::Main => "main",
+            AxRole::Navigation => "navigation",
+            AxRole::Paragraph => "paragraph",
+            AxRole::RadioButton => "radio",
+            AxRole::Row => "row",
+            AxRole::Section => "region",
+            AxRole::StaticText => "text",
+            AxRole::Table => "table",
+            AxRole::TextInput => "textbox",
+        }
+    }
+
+    /// Is the role's name computed from its content (e.g. link text)
+    fn name_from_content(&self) -> bool {
+        matches!(
+            self,
+            AxRole::Button | AxRole::Cell | AxRole::Heading | AxRole::Link | AxRole::ListItem
+        )
+    }
+
+    /// Can the user activate or focus nodes with this role
+    fn is_interactive(&self) -> bool {
+        matches!(
+            self,
+            AxRole::Button
+                | AxRole::CheckBox
+                | AxRole::ComboBox
+                | AxRole::Link
+                | AxRole::RadioButton
+                | AxRole::TextInput
+        )
+    }
+
+    fn to_accesskit(self) -> Role {
+        match self {
+            AxRole::Document => Role::Document,
+            AxRole::Article => Role::Article,
+            AxRole::Banner => Role::Banner,
+            AxRole::Button => Role::Button,
+            AxRole::Cell => Role::Cell,
+            AxRole::CheckBox => Role::CheckBox,
+            AxRole::ComboBox => Role::ComboBox,
+            AxRole::ContentInfo => Role::ContentInfo,
+            AxRole::Form => Role::Form,
+            AxRole::GenericContainer => Role::GenericContainer,
+            AxRole::Group => Role::Group,
+            AxRole::Heading => Role::Heading,
+            AxRole::Image => Role::Image,
+            AxRole::Link => Role::Link,
+            AxRole::List => Role::List,
+            AxRole::ListItem => Role::ListItem,
+            AxRole::Main => Role::Main,
+            AxRole::Navigation => Role::Navigation,
+            AxRole::Paragraph => Role::Paragraph,
+            AxRole::RadioButton => Role::RadioButton,
+            AxRole::Row => Role::Row,
+            AxRole::Section => Role::Section,
+            AxRole::StaticText => Role::StaticText,
+            AxRole::Table => Role::Table,
+            AxRole::TextInput => Role::TextInput,
+        }
+    }
+}
+
+/// States exposed to assistive technology
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AxState {
+    pub focusable: bool,
+    pub disabled: bool,
+    pub required: bool,
+    /// Checked state of checkboxes and radio buttons
+    pub checked: Option<bool>,
+}
+
+/// A node in the accessibility tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct AxNode {
+    pub id: AxNodeId,
+    pub role: AxRole,
+    /// Accessible name (e.g. link text, `alt`, `aria-label`, `<label>`)
+    pub name: Option<String>,
+    /// Current value of form controls
+    pub value: Option<String>,
+    pub placeholder: Option<String>,
+    /// Link target, as written in the document
+    pub url: Option<String>,
+    /// Heading level
+    pub level: Option<usize>,
+    /// Border box in page coordinates, if the node was laid out
+    pub bounds: Option<Rect>,
+    pub state: AxState,
+    pub children: Vec<AxNodeId>,
+}
+
+impl AxNode {
+    fn new(id: AxNodeId, role: AxRole) -> Self {
+        Self {
+            id,
+            role,
+            name: None,
+            value: None,
+            placeholder: None,
+            url: None,
+            level: None,
+            bounds: None,
+            state: AxState::default(),
+            children: Vec::new(),
+        }
+    }
+}
+
+/// Accessibility tree for one document
+pub struct AccessibilityTree {
+    nodes: HashMap<AxNodeId, AxNode>,
+    focus: Option<AxNodeId>,
+    /// Window title shown by screen readers
+    window_title: String,
+    /// Device pixels per CSS pixel
+    scale_factor: f64,
+    /// Window position of the page's top-left corner, in CSS pixels
+    origin: (f32, f32),
+}
+
+impl AccessibilityTree {
+    /// Create a tree with an empty document
+    pub fn new() -> Self {
+        let mut nodes = HashMap::new();
+        nodes.insert(DOCUMENT_NODE_ID, AxNode::new(DOCUMENT_NODE_ID, AxRole::Document));
+        Self {
+            nodes,
+            focus: None,
+            window_title: String::new(),
+            scale_factor: 1.0,
+            origin: (0.0, 0.0),
+        }
+    }
+
+    /// Build a tree for a document, using layout (if given) for bounds
+    pub fn build(dom: &Node, layout: Option<&LayoutBox>) -> Self {
+        let mut tree = Self::new();
+        tree.update(dom, layout);
+        tree
+    }
+
+    /// Rebuild from the document after a mutation or relayout
+    ///
+    /// Returns the IDs of nodes that were added or changed, for an
+    /// incremental AccessKit update.
+    pub fn update(&mut self, dom: &Node, layout: Option<&LayoutBox>) -> Vec<AxNodeId> {
+        let mut builder = Builder {
+            nodes: HashMap::new(),
+            bounds: HashMap::new(),
+            labels: HashMap::new(),
+        };
+        if let Some(layout) = layout {
+            collect_bounds(layout, &mut builder.bounds);
+        }
+        collect_labels(dom, &mut builder.labels);
+
+        let mut document = AxNode::new(DOCUMENT_NODE_ID, AxRole::Document);
+        document.name = document_title(dom);
+        document.bounds = layout.map(|l| l.dimensions.border_box());
+        let mut path = vec![0];
+        builder.visit_children(dom, &mut path, &mut document.children, None);
+        builder.nodes.insert(DOCUMENT_NODE_ID, document);
+
+        let mut changed: Vec<AxNodeId> = builder
+            .nodes
+            .values()
+            .filter(|node| self.nodes.get(&node.id) != Some(node))
+            .map(|node| node.id)
+            .collect();
+        changed.sort_unstable();
+
+        self.nodes = builder.nodes;
+        if self.focus.is_some_and(|id| !self.nodes.contains_key(&id)) {
+            self.focus = None;
+        }
+        changed
+    }
+
+    /// Get a node
+    pub fn get(&self, id: AxNodeId) -> Option<&AxNode> {
+        self.nodes.get(&id)
+    }
+
+    /// The document node
+    pub fn root(&self) -> &AxNode {
+        &self.nodes[&DOCUMENT_NODE_ID]
+    }
+
+    /// Number of nodes, including the document
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Always false; the document node is always present
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Nodes with a role, in document order
+    pub fn find_by_role(&self, role: AxRole) -> Vec<&AxNode> {
+        let mut found = Vec::new();
+        self.walk(DOCUMENT_NODE_ID, &mut |node| {
+            if node.role == role {
+                found.push(node);
+            }
+        });
+        found
+    }
+
+    fn walk<'t>(&'t self, id: AxNodeId, f: &mut impl FnMut(&'t AxNode)) {
+        if let Some(node) = self.nodes.get(&id) {
+            f(node);
+            for child in &node.children {
+                self.walk(*child, f);
+            }
+        }
+    }
+
+    /// Node with keyboard focus, if any
+    pub fn focus(&self) -> Option<AxNodeId> {
+        self.focus
+    }
+
+    /// Move focus to a focusable node; returns false if it cannot take focus
+    pub fn set_focus(&mut self, id: AxNodeId) -> bool {
+        match self.nodes.get(&id) {
+            Some(node) if node.state.focusable => {
+                self.focus = Some(id);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Set the title of the window node; returns true if it changed
+    pub fn set_window_title(&mut self, title: &str) -> bool {
+        if self.window_title == title {
+            return false;
+        }
+        self.window_title = title.to_string();
+        true
+    }
+
+    /// Place the page in the window
+    ///
+    /// `origin` is where the page's top-left corner is in the window, in
+    /// CSS pixels (below the chrome, minus the scroll offset). Returns true
+    /// if anything changed.
+    pub fn set_viewport(&mut self, scale_factor: f64, origin: (f32, f32)) -> bool {
+        if self.scale_factor == scale_factor && self.origin == origin {
+            return false;
+        }
+        self.scale_factor = scale_factor;
+        self.origin = origin;
+        true
+    }
+
+    /// Full AccessKit update, for a new window or page
+    pub fn to_tree_update(&self) -> TreeUpdate {
+        let mut ids: Vec<AxNodeId> = self.nodes.keys().copied().collect();
+        ids.sort_unstable();
+        let mut update = self.tree_update_for(&ids);
+        update.tree = Some(Tree::new(NodeId(WINDOW_NODE_ID)));
+        update
+    }
+
+    /// AccessKit update with the given nodes
+    ///
+    /// The window and document nodes are always included, since they
+    /// carry the title, scroll offset and scale factor.
+    pub fn tree_update_for(&self, ids: &[AxNodeId]) -> TreeUpdate {
+        let mut classes = NodeClassSet::new();
+        let mut nodes = Vec::with_capacity(ids.len() + 2);
+
+        let mut window = NodeBuilder::new(Role::Window);
+        window.set_name(self.window_title.as_str());
+        window.set_children(vec![NodeId(DOCUMENT_NODE_ID)]);
+        window.set_transform(Affine::scale(self.scale_factor));
+        nodes.push((NodeId(WINDOW_NODE_ID), window.build(&mut classes)));
+
+        let mut ids = ids.to_vec();
+        if !ids.contains(&DOCUMENT_NODE_ID) {
+            ids.push(DOCUMENT_NODE_ID);
+        }
+        for id in ids {
+            if let Some(node) = self.nodes.get(&id) {
+                nodes.push((NodeId(id), self.build_node(node, &mut classes)));
+            }
+        }
+
+        TreeUpdate {
+            nodes,
+            tree: None,
+            focus: NodeId(self.focus.unwrap_or(DOCUMENT_NODE_ID)),
+        }
+    }
+
+    fn build_node(&self, node: &AxNode, classes: &mut NodeClassSet) -> accesskit::Node {
+        let mut builder = NodeBuilder::new(node.role.to_accesskit());
+        builder.set_children(node.children.iter().map(|id| NodeId(*id)).collect::<Vec<_>>());
+        if let Some(name) = &node.name {
+            builder.set_name(name.as_str());
+        }
+        if let Some(value) = &node.value {
+            builder.set_value(value.as_str());
+        }
+        if let Some(placeholder) = &node.placeholder {
+            builder.set_placeholder(placeholder.as_str());
+        }
+        if let Some(url) = &node.url {
+            builder.set_url(url.as_str());
+        }
+        if let Some(level) = node.level {
+            builder.set_hierarchical_level(level);
+        }
+        if let Some(rect) = node.bounds {
+            builder.set_bounds(accesskit::Rect {
+                x0: rect.x as f64,
+                y0: rect.y as f64,
+                x1: (rect.x + rect.width) as f64,
+                y1: (rect.y + rect.height) as f64,
+            });
+        }
+        if node.id == DOCUMENT_NODE_ID {
+            builder.set_transform(Affine::translate((self.origin.0 as f64, self.origin.1 as f64)));
+        }
+        if let Some(checked) = node.state.checked {
+            builder.set_checked(if checked { accesskit::Checked::True } else { accesskit::Checked::False });
+        }
+        if node.state.disabled {
+            builder.set_disabled();
+        }
+        if node.state.required {
+            builder.set_required();
+        }
+        if node.state.focusable && !node.state.disabled {
+            builder.add_action(Action::Focus);
+            builder.add_action(Action::Default);
+            builder.set_default_action_verb(match node.role {
+                AxRole::Link => DefaultActionVerb::Jump,
+                AxRole::CheckBox | AxRole::RadioButton => DefaultActionVerb::Check,
+                _ => DefaultActionVerb::Click,
+            });
+        }
+        builder.build(classes)
+    }
+}
+
+impl Default for AccessibilityTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Elements that never produce accessibility nodes
+const IGNORED_ELEMENTS: &[&str] = &[
+    "head", "script", "style", "meta", "link", "title", "template", "noscript", "base",
+];
+
+/// Elements whose children are exposed directly under their parent
+const TRANSPARENT_ELEMENTS: &[&str] = &["html", "body", "label", "tbody", "thead", "tfoot"];
+
+struct Builder {
+    nodes: HashMap<AxNodeId, AxNode>,
+    /// Layout bounds by DOM node address
+    bounds: HashMap<*const Node, Rect>,
+    /// `<label for>` text by control id
+    labels: HashMap<String, String>,
+}
+
+impl Builder {
+    /// Add nodes for a DOM node's children, appending their IDs to `children`
+    ///
+    /// `label` is the text of an enclosing `<label>`, which names the
+    /// control inside it.
+    fn visit_children(&mut self, node: &Node, path: &mut Vec<usize>, children: &mut Vec<AxNodeId>, label: Option<&str>) {
+        for (index, child) in node.children.iter().enumerate() {
+            path.push(index);
+            self.visit(child, path, children, label);
+            path.pop();
+        }
+    }
+
+    fn visit(&mut self, node: &Node, path: &mut Vec<usize>, siblings: &mut Vec<AxNodeId>, label: Option<&str>) {
+        if let Some(text) = node.text_content() {
+            let text = collapse_whitespace(text);
+            if !text.is_empty() {
+                let mut ax = AxNode::new(path_id(path), AxRole::StaticText);
+                ax.name = Some(text);
+                ax.bounds = self.bounds.get(&(node as *const Node)).copied();
+                siblings.push(ax.id);
+                self.nodes.insert(ax.id, ax);
+            }
+            return;
+        }
+
+        let Some(elem) = node.element_data() else {
+            return;
+        };
+        let tag = elem.tag_name.to_ascii_lowercase();
+        if IGNORED_ELEMENTS.contains(&tag.as_str())
+            || elem.attributes.contains_key("hidden")
+            || elem.get_attribute("aria-hidden") == Some("true")
+            || (tag == "input" && elem.get_attribute("type").is_some_and(|t| t.eq_ignore_ascii_case("hidden")))
+        {
+            return;
+        }
+
+        if TRANSPARENT_ELEMENTS.contains(&tag.as_str()) && elem.get_attribute("role").is_none() {
+            let label_text = (tag == "label").then(|| text_of(node));
+            self.visit_children(node, path, siblings, label_text.as_deref().or(label));
+            return;
+        }
+
+        let Some(role) = elem.get_attribute("role").and_then(AxRole::from_str).or_else(|| implicit_role(node)) else {
+            return;
+        };
+
+        let mut ax = AxNode::new(path_id(path), role);
+        ax.bounds = self.bounds.get(&(node as *const Node)).copied();
+        ax.state.focusable = role.is_interactive() || elem.get_attribute("tabindex").is_some_and(|t| t.trim() != "-1");
+        ax.state.disabled = elem.attributes.contains_key("disabled") || elem.get_attribute("aria-disabled") == Some("true");
+        ax.state.required = elem.attributes.contains_key("required") || elem.get_attribute("aria-required") == Some("true");
+        if matches!(role, AxRole::CheckBox | AxRole::RadioButton) {
+            ax.state.checked = Some(
+                elem.attributes.contains_key("checked") || elem.get_attribute("aria-checked") == Some("true"),
+            );
+        }
+        if role == AxRole::Heading {
+            ax.level = elem
+                .get_attribute("aria-level")
+                .and_then(|l| l.trim().parse().ok())
+                .or_else(|| tag.strip_prefix('h').and_then(|n| n.parse().ok()));
+        }
+        if role == AxRole::Link {
+            ax.url = elem.get_attribute("href").map(|h| h.trim().to_string());
+        }
+
+        // Form controls
+        match tag.as_str() {
+            "input" => {
+                let input_type = elem.get_attribute("type").unwrap_or("text").to_ascii_lowercase();
+                let value = elem.get_attribute("value").unwrap_or("");
+                match input_type.as_str() {
+                    "submit" | "button" | "reset" => {
+                        let fallback = if input_type == "reset" { "Reset" } else { "Submit" };
+                        ax.name = Some(if value.is_empty() { fallback.to_string() } else { value.to_string() });
+                    }
+                    "checkbox" | "radio" => {}
+                    "password" => ax.value = Some("\u{2022}".repeat(value.chars().count())),
+                    _ => ax.value = Some(value.to_string()),
+                }
+                ax.placeholder = elem.get_attribute("placeholder").map(str::to_string);
+            }
+            "textarea" => {
+                ax.value = Some(text_of(node));
+                ax.placeholder = elem.get_attribute("placeholder").map(str::to_string);
+            }
+            "select" => ax.value = selected_option(node),
+            _ => {}
+        }
+
+        // Accessible name, in order of precedence
+        let control_label = elem.id().and_then(|id| self.labels.get(id)).map(String::as_str).or(label);
+        let is_control = matches!(tag.as_str(), "input" | "textarea" | "select");
+        ax.name = non_empty(elem.get_attribute("aria-label"))
+            .or_else(|| if is_control { non_empty(control_label) } else { None })
+            .or_else(|| if tag == "img" { non_empty(elem.get_attribute("alt")) } else { None })
+            .or(ax.name.take())
+            .or_else(|| if role.name_from_content() { non_empty(Some(&text_of(node))) } else { None })
+            .or_else(|| non_empty(elem.get_attribute("title")));
+
+        // Content of controls and named-from-content nodes is already in
+        // their name or value; other nodes expose their children
+        if !is_control && !role.name_from_content() {
+            self.visit_children(node, path, &mut ax.children, label);
+        } else if role.name_from_content() {
+            // Keep nested elements (e.g. an image in a link) but not the text
+            let mut children = Vec::new();
+            for (index, child) in node.children.iter().enumerate() {
+                if child.element_data().is_some() {
+                    path.push(index);
+                    self.visit(child, path, &mut children, label);
+                    path.pop();
+                }
+            }
+            ax.children = children;
+        }
+
+        siblings.push(ax.id);
+        self.nodes.insert(ax.id, ax);
+    }
+}
+
+/// Implicit ARIA role of an HTML element, or `None` to skip it
+fn implicit_role(node: &Node) -> Option<AxRole> {
+    let elem = node.element_data()?;
+    let tag = elem.tag_name.to_ascii_lowercase();
+    Some(match tag.as_str() {
+        "a" if elem.attributes.contains_key("href") => AxRole::Link,
+        "article" => AxRole::Article,
+        "aside" | "section" => AxRole::Section,
+        "button" => AxRole::Button,
+        "fieldset" | "figure" | "details" => AxRole::Group,
+        "footer" => AxRole::ContentInfo,
+        "form" => AxRole::Form,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => AxRole::Heading,
+        "header" => AxRole::Banner,
+        // An empty alt marks the image as decorative
+        "img" if elem.get_attribute("alt") == Some("") => return None,
+        "img" => AxRole::Image,
+        "input" => match elem.get_attribute("type").unwrap_or("text").to_ascii_lowercase().as_str() {
+            "checkbox" => AxRole::CheckBox,
+            "radio" => AxRole::RadioButton,
+            "submit" | "button" | "reset" | "image" => AxRole::Button,
+            _ => AxRole::TextInput,
+        },
+        "li" => AxRole::ListItem,
+        "main" => AxRole::Main,
+        "nav" => AxRole::Navigation,
+        "ol" | "ul" | "menu" => AxRole::List,
+        "p" => AxRole::Paragraph,
+        "select" => AxRole::ComboBox,
+        "table" => AxRole::Table,
+        "td" | "th" => AxRole::Cell,
+        "textarea" => AxRole::TextInput,
+        "tr" => AxRole::Row,
+        _ => AxRole::GenericContainer,
+    })
+}
+
+/// Stable ID for the node at a DOM path (FNV-1a over the child indices)
+fn path_id(path: &[usize]) -> AxNodeId {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for index in path {
+        for byte in (*index as u64).to_le_bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+    // Keep clear of the window and document IDs
+    hash.max(DOCUMENT_NODE_ID + 1)
+}
+
+/// Layout bounds for every laid-out DOM node
+fn collect_bounds(layout: &LayoutBox, bounds: &mut HashMap<*const Node, Rect>) {
+    if let Some(styled) = layout.get_styled_node() {
+        bounds.entry(styled.node as *const Node).or_insert(layout.dimensions.border_box());
+    }
+    for child in &layout.children {
+        collect_bounds(child, bounds);
+    }
+}
+
+/// Text of every `<label for="...">`, by the id it labels
+fn collect_labels(node: &Node, labels: &mut HashMap<String, String>) {
+    if let Some(elem) = node.element_data() {
+        if elem.tag_name.eq_ignore_ascii_case("label") {
+            if let Some(target) = elem.get_attribute("for") {
+                labels.insert(target.to_string(), text_of(node));
+            }
+        }
+    }
+    for child in &node.children {
+        collect_labels(child, labels);
+    }
+}
+
+/// Text content of a node, with image alt text, whitespace collapsed
+fn text_of(node: &Node) -> String {
+    fn collect(node: &Node, out: &mut String) {
+        if let Some(text) = node.text_content() {
+            out.push_str(text);
+            out.push(' ');
+        } else if let Some(elem) = node.element_data() {
+            if elem.tag_name.eq_ignore_ascii_case("img") {
+                if let Some(alt) = elem.get_attribute("alt") {
+                    out.push_str(alt);
+                    out.push(' ');
+                }
+            }
+            for child in &node.children {
+                collect(child, out);
+            }
+        }
+    }
+    let mut out = String::new();
+    collect(node, &mut out);
+    collapse_whitespace(&out)
+}
+
+/// Text of a `<select>`'s selected option, or its first option
+fn selected_option(select: &Node) -> Option<String> {
+    fn options<'n>(node: &'n Node, out: &mut Vec<&'n Node>) {
+        for child in &node.children {
+            if child.element_data().is_some_and(|e| e.tag_name.eq_ignore_ascii_case("option")) {
+                out.push(child);
+            } else {
+                options(child, out);
+            }
+        }
+    }
+    let mut all = Vec::new();
+    options(select, &mut all);
+    let selected = all
+        .iter()
+        .find(|o| o.element_data().is_some_and(|e| e.attributes.contains_key("selected")))
+        .or(all.first())?;
+    Some(text_of(selected))
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn non_empty(text: Option<&str>) -> Option<String> {
+    text.map(collapse_whitespace).filter(|t| !t.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::CssParser;
+    use crate::html::HtmlParser;
+    use crate::layout::{layout_tree, Dimensions};
+    use crate::style::style_tree;
+
+    fn tree(html: &str) -> AccessibilityTree {
+        AccessibilityTree::build(&HtmlParser::parse(html), None)
+    }
+
+    #[test]
+    fn test_roles_and_names_from_markup() {
+        let tree = tree(
+            r#"<html><head><title>Docs</title><script>var x;</script></head><body>
+                <nav><a href="/home">Home <img src="h.png" alt="icon"></a></nav>
+                <h2>Intro</h2>
+                <p>Some <b>bold</b> text</p>
+                <img src="deco.png" alt="">
+                <button aria-label="Close dialog">X</button>
+            </body></html>"#,
+        );
+
+        assert_eq!(tree.root().name.as_deref(), Some("Docs"));
+        let link = tree.find_by_role(AxRole::Link)[0];
+        assert_eq!(link.name.as_deref(), Some("Home icon"));
+        assert_eq!(link.url.as_deref(), Some("/home"));
+        assert!(link.state.focusable);
+
+        let heading = tree.find_by_role(AxRole::Heading)[0];
+        assert_eq!((heading.name.as_deref(), heading.level), (Some("Intro"), Some(2)));
+
+        let button = tree.find_by_role(AxRole::Button)[0];
+        assert_eq!(button.name.as_deref(), Some("Close dialog"));
+
+        // Decorative images and scripts are left out
+        assert_eq!(tree.find_by_role(AxRole::Image).len(), 1);
+        let texts: Vec<_> = tree.find_by_role(AxRole::StaticText).iter().filter_map(|n| n.name.clone()).collect();
+        assert!(texts.contains(&"Some".to_string()));
+        assert!(!texts.iter().any(|t| t.contains("var x")));
+    }
+
+    #[test]
+    fn test_form_controls_expose_labels_values_and_states() {
+        let tree = tree(
+            r#"<form>
+                <label for="email">Email address</label>
+                <input id="email" type="email" value="a@example.com" required>
+                <label><input type="checkbox" checked> Remember me</label>
+                <input type="password" value="secret" aria-label="Password">
+                <select aria-label="Size"><option>S</option><option selected>M</option></select>
+                <input type="submit" disabled>
+                <input type="hidden" value="token">
+            </form>"#,
+        );
+
+        let inputs = tree.find_by_role(AxRole::TextInput);
+        assert_eq!(inputs.len(), 2);
+        assert_eq!(inputs[0].name.as_deref(), Some("Email address"));
+        assert_eq!(inputs[0].value.as_deref(), Some("a@example.com"));
+        assert!(inputs[0].state.required);
+        assert_eq!(inputs[1].value.as_deref(), Some("\u{2022}\u{2022}\u{2022}\u{2022}\u{2022}\u{2022}"));
+
+        let checkbox = tree.find_by_role(AxRole::CheckBox)[0];
+        assert_eq!(checkbox.name.as_deref(), Some("Remember me"));
+        assert_eq!(checkbox.state.checked, Some(true));
+
+        assert_eq!(tree.find_by_role(AxRole::ComboBox)[0].value.as_deref(), Some("M"));
+        let submit = tree.find_by_role(AxRole::Button)[0];
+        assert_eq!(submit.name.as_deref(), Some("Submit"));
+        assert!(submit.state.disabled);
+    }
+
+    #[test]
+    fn test_update_reports_only_changed_nodes() {
+        let mut tree = tree("<body><p>One</p><p>Two</p></body>");
+        let before = tree.len();
+
+        let changed = tree.update(&HtmlParser::parse("<body><p>One</p><p>Three</p></body>"), None);
+        assert_eq!(tree.len(), before);
+        // Only the second paragraph's text changed
+        assert_eq!(changed.len(), 1);
+        assert_eq!(tree.get(changed[0]).unwrap().name.as_deref(), Some("Three"));
+
+        let changed = tree.update(&HtmlParser::parse("<body><p>One</p></body>"), None);
+        // The document lost a child
+        assert_eq!(changed, vec![DOCUMENT_NODE_ID]);
+        assert_eq!(tree.find_by_role(AxRole::Paragraph).len(), 1);
+    }
+
+    #[test]
+    fn test_bounds_come_from_layout() {
+        let dom = HtmlParser::parse("<body><p>First</p><p>Second</p></body>");
+        let css = CssParser::parse("body { display: block; } p { display: block; height: 30px; }");
+        let styled = style_tree(&dom, &css);
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+        let layout = layout_tree(&styled, viewport);
+
+        let tree = AccessibilityTree::build(&dom, Some(&layout));
+        let paragraphs = tree.find_by_role(AxRole::Paragraph);
+        let (first, second) = (paragraphs[0].bounds.unwrap(), paragraphs[1].bounds.unwrap());
+        assert_eq!(first.height, 30.0);
+        assert_eq!(second.y, first.y + 30.0);
+    }
+
+    #[test]
+    fn test_accesskit_update_and_focus() {
+        let mut tree = tree(r#"<body><a href="/a">A</a><p>Text</p></body>"#);
+        assert!(tree.set_window_title("Example"));
+        assert!(!tree.set_window_title("Example"));
+        let link = tree.find_by_role(AxRole::Link)[0].id;
+        let paragraph = tree.find_by_role(AxRole::Paragraph)[0].id;
+
+        assert!(!tree.set_focus(paragraph));
+        assert!(tree.set_focus(link));
+
+        let update = tree.to_tree_update();
+        assert_eq!(update.tree.as_ref().map(|t| t.root), Some(NodeId(WINDOW_NODE_ID)));
+        assert_eq!(update.focus, NodeId(link));
+        assert_eq!(update.nodes.len(), tree.len() + 1);
+        let window = &update.nodes[0].1;
+        assert_eq!(window.role(), Role::Window);
+        assert_eq!(window.name(), Some("Example"));
+
+        // Scrolling only moves the document
+        assert!(tree.set_viewport(2.0, (0.0, 40.0)));
+        assert!(!tree.set_viewport(2.0, (0.0, 40.0)));
+        let update = tree.tree_update_for(&[]);
+        assert_eq!(update.nodes.len(), 2);
+        assert!(update.tree.is_none());
+    }
+
+    #[test]
+    fn test_role_attribute() {
+        assert_eq!(AxRole::from_str("Button"), Some(AxRole::Button));
+        assert_eq!(AxRole::from_str("searchbox"), Some(AxRole::TextInput));
+        assert_eq!(AxRole::from_str("bogus"), None);
+        assert_eq!(AxRole::Image.as_str(), "img");
+
+        let tree = tree(r#"<div role="navigation"><span role="button" tabindex="0">Go</span></div>"#);
+        assert_eq!(tree.find_by_role(AxRole::Navigation).len(), 1);
+        let button = tree.find_by_role(AxRole::Button)[0];
+        assert_eq!(button.name.as_deref(), Some("Go"));
+        assert!(button.state.focusable);
+    }
+}
@@ -1,8 +1,28 @@
 // CSS Animations and Transitions - Phase 7 Task 2
 
+use crate::compositor::Compositor;
+use crate::css::{self, Value};
+use crate::dom::Node;
+use crate::style::{PropertyMap, StyledNode};
+pub use crate::transform::Transform;
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
 
+/// Path to an element from the document, as child indices
+pub type ElementPath = Vec<usize>;
+
+/// Path from the document to one of its nodes
+pub fn element_path(document: &Node, node: &Node) -> Option<ElementPath> {
+    if std::ptr::eq(document, node) {
+        return Some(Vec::new());
+    }
+    document.children.iter().enumerate().find_map(|(i, child)| {
+        let mut path = element_path(child, node)?;
+        path.insert(0, i);
+        Some(path)
+    })
+}
+
 /// Animation timing function (easing)
 #[derive(Debug, Clone, PartialEq)]
 pub enum TimingFunction {
@@ -23,6 +43,42 @@ pub enum StepPosition {
 }
 
 impl TimingFunction {
+    /// Parse a CSS easing function, e.g. `ease-in` or `cubic-bezier(0.1, 0.7, 1, 0.1)`
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_ascii_lowercase();
+        match value.as_str() {
+            "linear" => return Some(TimingFunction::Linear),
+            "ease" => return Some(TimingFunction::Ease),
+            "ease-in" => return Some(TimingFunction::EaseIn),
+            "ease-out" => return Some(TimingFunction::EaseOut),
+            "ease-in-out" => return Some(TimingFunction::EaseInOut),
+            "step-start" => return Some(TimingFunction::Steps(1, StepPosition::Start)),
+            "step-end" => return Some(TimingFunction::Steps(1, StepPosition::End)),
+            _ => {}
+        }
+        
+        let (name, args) = value.strip_suffix(')')?.split_once('(')?;
+        let args: Vec<&str> = args.split(',').map(str::trim).collect();
+        match (name.trim(), args.as_slice()) {
+            ("cubic-bezier", [x1, y1, x2, y2]) => {
+                let (x1, y1, x2, y2) = (x1.parse().ok()?, y1.parse().ok()?, x2.parse().ok()?, y2.parse().ok()?);
+                // The curve has to be a function of time
+                let in_range = |x: f32| (0.0..=1.0).contains(&x);
+                (in_range(x1) && in_range(x2)).then_some(TimingFunction::CubicBezier(x1, y1, x2, y2))
+            }
+            ("steps", [steps, position @ ..]) if position.len() <= 1 => {
+                let steps = steps.parse().ok().filter(|steps| *steps > 0)?;
+                let position = match position.first().copied() {
+                    None | Some("end" | "jump-end") => StepPosition::End,
+                    Some("start" | "jump-start") => StepPosition::Start,
+                    Some(_) => return None,
+                };
+                Some(TimingFunction::Steps(steps, position))
+            }
+            _ => None,
+        }
+    }
+    
     /// Calculate progress value at time t (0.0 to 1.0)
     pub fn calculate(&self, t: f32) -> f32 {
         let t = t.clamp(0.0, 1.0);
@@ -46,17 +102,51 @@ impl TimingFunction {
         }
     }
     
-    /// Cubic bezier calculation (simplified Newton-Raphson method)
-    fn cubic_bezier(x1: f32, y1: f32, x2: f32, y2: f32, t: f32) -> f32 {
-        // Simplified: use approximation for performance
-        // In production, would use proper Newton-Raphson iteration
-        let t2 = t * t;
-        let t3 = t2 * t;
-        let mt = 1.0 - t;
-        let mt2 = mt * mt;
-        let mt3 = mt2 * mt;
+    /// Progress along a cubic bezier from (0, 0) to (1, 1) at time `x`
+    ///
+    /// The curve is parametric, so the parameter whose x is `x` is solved for
+    /// first: Newton-Raphson, falling back to bisection where the curve is too
+    /// flat for it to converge. The progress is the y at that parameter.
+    fn cubic_bezier(x1: f32, y1: f32, x2: f32, y2: f32, x: f32) -> f32 {
+        const EPSILON: f32 = 1e-6;
         
-        3.0 * y1 * mt2 * t + 3.0 * y2 * mt * t2 + t3
+        // x(t) and y(t) as polynomials in t
+        let (cx, cy) = (3.0 * x1, 3.0 * y1);
+        let (bx, by) = (3.0 * (x2 - x1) - cx, 3.0 * (y2 - y1) - cy);
+        let (ax, ay) = (1.0 - cx - bx, 1.0 - cy - by);
+        let sample_x = |t: f32| ((ax * t + bx) * t + cx) * t;
+        let sample_y = |t: f32| ((ay * t + by) * t + cy) * t;
+        let slope_x = |t: f32| (3.0 * ax * t + 2.0 * bx) * t + cx;
+        
+        let mut t = x;
+        for _ in 0..8 {
+            let error = sample_x(t) - x;
+            if error.abs() < EPSILON {
+                return sample_y(t);
+            }
+            let slope = slope_x(t);
+            if slope.abs() < EPSILON {
+                break;
+            }
+            t -= error / slope;
+        }
+        
+        // x(t) rises from 0 to 1 as t does, as x1 and x2 are within 0..=1
+        let (mut low, mut high) = (0.0f32, 1.0f32);
+        t = x;
+        for _ in 0..64 {
+            let error = sample_x(t) - x;
+            if error.abs() < EPSILON {
+                break;
+            }
+            if error > 0.0 {
+                high = t;
+            } else {
+                low = t;
+            }
+            t = (low + high) / 2.0;
+        }
+        sample_y(t)
     }
 }
 
@@ -76,28 +166,6 @@ pub enum AnimatableValue {
     Transform(Transform),
 }
 
-/// Transform types for animations
-#[derive(Debug, Clone, PartialEq)]
-pub struct Transform {
-    pub translate_x: f32,
-    pub translate_y: f32,
-    pub scale_x: f32,
-    pub scale_y: f32,
-    pub rotate: f32, // degrees
-}
-
-impl Default for Transform {
-    fn default() -> Self {
-        Self {
-            translate_x: 0.0,
-            translate_y: 0.0,
-            scale_x: 1.0,
-            scale_y: 1.0,
-            rotate: 0.0,
-        }
-    }
-}
-
 impl AnimatableValue {
     /// Interpolate between two values
     pub fn interpolate(&self, other: &Self, progress: f32) -> Option<Self> {
@@ -121,17 +189,56 @@ impl AnimatableValue {
                 Some(AnimatableValue::Percentage(a + (b - a) * progress))
             }
             (AnimatableValue::Transform(t1), AnimatableValue::Transform(t2)) => {
-                Some(AnimatableValue::Transform(Transform {
-                    translate_x: t1.translate_x + (t2.translate_x - t1.translate_x) * progress,
-                    translate_y: t1.translate_y + (t2.translate_y - t1.translate_y) * progress,
-                    scale_x: t1.scale_x + (t2.scale_x - t1.scale_x) * progress,
-                    scale_y: t1.scale_y + (t2.scale_y - t1.scale_y) * progress,
-                    rotate: t1.rotate + (t2.rotate - t1.rotate) * progress,
-                }))
+                Some(AnimatableValue::Transform(t1.interpolate(t2, progress)))
             }
             _ => None, // Type mismatch
         }
     }
+    
+    /// The value of a property's CSS value, for those that can be interpolated
+    pub fn from_css_value(property: &str, value: &Value) -> Option<Self> {
+        match value {
+            Value::Keyword(text) if property == "transform" => Transform::parse(text).map(AnimatableValue::Transform),
+            Value::Number(n) => Some(AnimatableValue::Number(*n)),
+            Value::Color(c) => Some(AnimatableValue::Color(c.r, c.g, c.b, c.a)),
+            Value::Length(px, css::Unit::Px) => Some(AnimatableValue::Length(*px)),
+            Value::Percentage(p) => Some(AnimatableValue::Percentage(*p)),
+            _ => None,
+        }
+    }
+    
+    /// The value as a CSS value, for those styles hold
+    pub fn to_css_value(&self) -> Option<Value> {
+        match self {
+            AnimatableValue::Number(n) => Some(Value::Number(*n)),
+            AnimatableValue::Color(r, g, b, a) => Some(Value::Color(css::Color { r: *r, g: *g, b: *b, a: *a })),
+            AnimatableValue::Length(px) => Some(Value::Length(*px, css::Unit::Px)),
+            AnimatableValue::Percentage(p) => Some(Value::Percentage(*p)),
+            AnimatableValue::Transform(t) => Some(Value::Keyword(t.to_string())),
+        }
+    }
+}
+
+/// What a frame has to redo when an animated property changes, least first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum StyleChange {
+    /// Only the element's compositor layer changes
+    Composite,
+    /// The element paints differently in the same place
+    Paint,
+    /// Boxes move or resize
+    Layout,
+}
+
+impl StyleChange {
+    /// What changing a property invalidates
+    pub fn of(property: &str) -> Self {
+        match property {
+            "opacity" | "transform" => StyleChange::Composite,
+            "color" | "background-color" | "border-color" | "outline-color" | "visibility" => StyleChange::Paint,
+            _ => StyleChange::Layout,
+        }
+    }
 }
 
 /// A keyframe in an animation
@@ -212,7 +319,7 @@ impl KeyframeAnimation {
 }
 
 /// CSS Transition definition
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Transition {
     /// Property name to transition
     pub property: String,
@@ -224,19 +331,165 @@ pub struct Transition {
     pub delay: Duration,
 }
 
+/// Parse a CSS time, e.g. `200ms` or `1.5s`
+pub fn parse_time(value: &str) -> Option<Duration> {
+    let value = value.trim().to_ascii_lowercase();
+    let millis = match value.strip_suffix("ms") {
+        Some(ms) => ms.parse::<f64>().ok()?,
+        None => value.strip_suffix('s')?.parse::<f64>().ok()? * 1000.0,
+    };
+    // Rounded to whole nanoseconds, so `0.1s` is exactly `100ms`
+    (millis >= 0.0 && millis.is_finite()).then(|| Duration::from_nanos((millis * 1e6).round() as u64))
+}
+
+/// Split a value at `separator`s outside parentheses, dropping empty parts
+fn split_outside_parens(value: &str, separator: impl Fn(char) -> bool) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in value.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            c if depth == 0 && separator(c) => {
+                parts.push(&value[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&value[start..]);
+    parts.into_iter().map(str::trim).filter(|part| !part.is_empty()).collect()
+}
+
+/// The transitions an element's `transition` properties ask for, one per property listed
+///
+/// The longhands replace the matching lists of the `transition` shorthand,
+/// and lists shorter than the list of properties repeat to match it.
+pub fn transitions_of(values: &PropertyMap) -> Vec<Transition> {
+    let keyword = |name: &str| match values.get(name) {
+        Some(Value::Keyword(keyword)) => Some(keyword.as_str()),
+        _ => None,
+    };
+    let longhands = ["transition-property", "transition-duration", "transition-timing-function", "transition-delay"];
+    if keyword("transition").is_none() && longhands.iter().all(|name| keyword(name).is_none()) {
+        return Vec::new();
+    }
+    
+    let (mut properties, mut durations, mut timing_functions, mut delays) =
+        (vec!["all".to_string()], vec![Duration::ZERO], vec![TimingFunction::Ease], vec![Duration::ZERO]);
+    if let Some(shorthand) = keyword("transition") {
+        (properties, durations, timing_functions, delays) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        for item in split_outside_parens(shorthand, |c| c == ',') {
+            let (mut property, mut duration, mut timing_function, mut delay) =
+                ("all".to_string(), None, TimingFunction::Ease, Duration::ZERO);
+            for component in split_outside_parens(item, char::is_whitespace) {
+                if let Some(time) = parse_time(component) {
+                    // The first time is the duration, the second the delay
+                    match duration {
+                        None => duration = Some(time),
+                        Some(_) => delay = time,
+                    }
+                } else if let Some(function) = TimingFunction::parse(component) {
+                    timing_function = function;
+                } else {
+                    property = component.to_ascii_lowercase();
+                }
+            }
+            properties.push(property);
+            durations.push(duration.unwrap_or(Duration::ZERO));
+            timing_functions.push(timing_function);
+            delays.push(delay);
+        }
+    }
+    let list = |name: &str| keyword(name).map(|value| split_outside_parens(value, |c| c == ','));
+    if let Some(list) = list("transition-property") {
+        properties = list.into_iter().map(str::to_ascii_lowercase).collect();
+    }
+    if let Some(list) = list("transition-duration").and_then(|l| l.into_iter().map(parse_time).collect()) {
+        durations = list;
+    }
+    if let Some(list) = list("transition-timing-function") {
+        if let Some(list) = list.into_iter().map(TimingFunction::parse).collect() {
+            timing_functions = list;
+        }
+    }
+    if let Some(list) = list("transition-delay").and_then(|l| l.into_iter().map(parse_time).collect()) {
+        delays = list;
+    }
+    if properties.iter().any(|property| property == "none") {
+        return Vec::new();
+    }
+    
+    let nth = |list: &[Duration], i: usize| list.get(i % list.len().max(1)).copied().unwrap_or(Duration::ZERO);
+    properties
+        .into_iter()
+        .enumerate()
+        .map(|(i, property)| Transition {
+            property,
+            duration: nth(&durations, i),
+            timing_function: timing_functions.get(i % timing_functions.len().max(1)).cloned().unwrap_or_default(),
+            delay: nth(&delays, i),
+        })
+        .collect()
+}
+
+/// Each element's specified values as of a style change, for the next
+/// change to see which values it changes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StyleSnapshot {
+    values: HashMap<ElementPath, PropertyMap>,
+}
+
+impl StyleSnapshot {
+    /// Create an empty snapshot
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Take the values of every element of a styled document
+    pub fn of(styled: &StyledNode) -> Self {
+        fn collect(styled: &StyledNode, path: &mut ElementPath, values: &mut HashMap<ElementPath, PropertyMap>) {
+            if styled.node.element_data().is_some() {
+                values.insert(path.clone(), styled.specified_values.clone());
+            }
+            for (i, child) in styled.children.iter().enumerate() {
+                path.push(i);
+                collect(child, path, values);
+                path.pop();
+            }
+        }
+        let mut values = HashMap::new();
+        collect(styled, &mut Vec::new(), &mut values);
+        Self { values }
+    }
+    
+    /// The values of an element
+    pub fn get(&self, element: &[usize]) -> Option<&PropertyMap> {
+        self.values.get(element)
+    }
+}
+
 /// Active animation instance
 #[derive(Debug, Clone)]
 pub struct ActiveAnimation {
     /// Animation name
     pub name: String,
+    /// Element animated
+    pub target: ElementPath,
     /// Duration
     pub duration: Duration,
     /// Start time
     pub start_time: Instant,
+    /// Time from the start before the first iteration begins
+    pub delay: Duration,
     /// Iteration count (0 = infinite)
     pub iteration_count: u32,
     /// Current iteration
     pub current_iteration: u32,
+    /// Has `animationstart` been fired
+    pub started: bool,
+    /// What moves the animation along
+    pub timeline: AnimationTimeline,
     /// Direction (normal or reverse)
     pub direction: AnimationDirection,
     /// Play state
@@ -245,6 +498,71 @@ pub struct ActiveAnimation {
     pub fill_mode: AnimationFillMode,
 }
 
+/// What an animation's progress follows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AnimationTimeline {
+    /// Time passing since the animation started
+    #[default]
+    Document,
+    /// A scroll container's offset
+    Scroll(ScrollTimeline),
+}
+
+/// A timeline running from a scroll container's start to the end of its
+/// scroll range, e.g. for progress bars and parallax
+///
+/// The animation's iterations, all of them if it repeats, stretch over the
+/// range; an infinite animation runs one. Delays are ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScrollTimeline {
+    /// Which of the container's offsets to follow
+    pub axis: ScrollAxis,
+}
+
+/// Direction a scroll timeline follows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollAxis {
+    /// Vertical scrolling
+    Block,
+    /// Horizontal scrolling
+    Inline,
+}
+
+/// How the engine lets time-driven animations and transitions run
+///
+/// Scroll-driven animations follow the user's own scrolling and are left alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MotionPolicy {
+    /// As the page asks
+    #[default]
+    Normal,
+    /// Jump to the end as soon as they start, filling and firing events as if they had run
+    Finish,
+    /// Do not run at all; values change without animating and no events fire
+    Disabled,
+}
+
+impl MotionPolicy {
+    /// Parse a policy name
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "normal" => Some(MotionPolicy::Normal),
+            "finish" => Some(MotionPolicy::Finish),
+            "disabled" => Some(MotionPolicy::Disabled),
+            _ => None,
+        }
+    }
+
+    /// Policy name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MotionPolicy::Normal => "normal",
+            MotionPolicy::Finish => "finish",
+            MotionPolicy::Disabled => "disabled",
+        }
+    }
+}
+
 /// Animation direction
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AnimationDirection {
@@ -273,47 +591,187 @@ pub enum AnimationFillMode {
 impl ActiveAnimation {
     /// Calculate current progress (0.0 to 1.0)
     pub fn current_progress(&self) -> f32 {
+        self.progress_at(Instant::now())
+    }
+    
+    /// Progress through the keyframes at `now` (0.0 to 1.0), in the animation's direction
+    pub fn progress_at(&self, now: Instant) -> f32 {
         if self.play_state == AnimationPlayState::Paused {
             return 0.0;
         }
         
-        let elapsed = self.start_time.elapsed();
-        let progress = elapsed.as_secs_f32() / self.duration.as_secs_f32();
+        let elapsed = self.iterations_at(now);
+        let iteration = self.iteration_at(now);
+        // Finished animations rest at the end of their last iteration
+        let progress = if self.iteration_count > 0 && elapsed >= self.iteration_count as f32 {
+            1.0
+        } else {
+            elapsed % 1.0
+        };
         
-        if progress >= 1.0 && self.iteration_count > 0 && self.current_iteration >= self.iteration_count {
-            return 1.0;
+        let reversed = match self.direction {
+            AnimationDirection::Normal => false,
+            AnimationDirection::Reverse => true,
+            AnimationDirection::Alternate => !iteration.is_multiple_of(2),
+            AnimationDirection::AlternateReverse => iteration.is_multiple_of(2),
+        };
+        if reversed {
+            1.0 - progress
+        } else {
+            progress
         }
-        
-        let progress = progress % 1.0;
-        
-        match self.direction {
-            AnimationDirection::Normal => progress,
-            AnimationDirection::Reverse => 1.0 - progress,
-            AnimationDirection::Alternate => {
-                if self.current_iteration % 2 == 0 {
-                    progress
-                } else {
-                    1.0 - progress
-                }
-            }
-            AnimationDirection::AlternateReverse => {
-                if self.current_iteration % 2 == 0 {
-                    1.0 - progress
-                } else {
-                    progress
-                }
-            }
+    }
+    
+    /// Iterations run by `now`, after the delay
+    fn iterations_at(&self, now: Instant) -> f32 {
+        now.saturating_duration_since(self.start_time + self.delay).as_secs_f32() / self.duration.as_secs_f32()
+    }
+    
+    /// Has the delay passed by `now`
+    pub fn has_begun_at(&self, now: Instant) -> bool {
+        now >= self.start_time + self.delay
+    }
+    
+    /// The iteration running at `now`, counting from 0
+    pub fn iteration_at(&self, now: Instant) -> u32 {
+        let iteration = self.iterations_at(now) as u32;
+        if self.iteration_count > 0 {
+            iteration.min(self.iteration_count - 1)
+        } else {
+            iteration
         }
     }
     
     /// Check if animation is complete
     pub fn is_complete(&self) -> bool {
+        self.is_complete_at(Instant::now())
+    }
+    
+    /// Has every iteration finished by `now`
+    pub fn is_complete_at(&self, now: Instant) -> bool {
         if self.iteration_count == 0 {
             return false; // Infinite
         }
         
-        let elapsed = self.start_time.elapsed();
-        elapsed >= self.duration * self.iteration_count
+        now.saturating_duration_since(self.start_time + self.delay) >= self.duration * self.iteration_count
+    }
+}
+
+/// Kind of DOM event animations and transitions fire
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationEventType {
+    /// An animation's delay has passed and its first iteration begun
+    AnimationStart,
+    /// An iteration ended and the next begun
+    AnimationIteration,
+    /// Every iteration has run
+    AnimationEnd,
+    /// A transition reached its final value
+    TransitionEnd,
+}
+
+impl AnimationEventType {
+    /// The event's type as scripts see it
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnimationEventType::AnimationStart => "animationstart",
+            AnimationEventType::AnimationIteration => "animationiteration",
+            AnimationEventType::AnimationEnd => "animationend",
+            AnimationEventType::TransitionEnd => "transitionend",
+        }
+    }
+}
+
+/// An event for the page to fire at an animated element
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationEvent {
+    pub event_type: AnimationEventType,
+    /// Element animated
+    pub target: ElementPath,
+    /// The animation's name, or the property transitioned
+    pub name: String,
+    /// Seconds the animation has run, not counting its delay
+    pub elapsed_time: f32,
+}
+
+/// Animated property values of each element for one frame
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AnimatedStyles {
+    values: HashMap<ElementPath, HashMap<String, AnimatableValue>>,
+}
+
+impl AnimatedStyles {
+    /// Create empty animated styles
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Are no elements animated
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+    
+    /// The animated values of an element
+    pub fn get(&self, element: &[usize]) -> Option<&HashMap<String, AnimatableValue>> {
+        self.values.get(element)
+    }
+    
+    /// Set an element's animated property
+    pub fn insert(&mut self, element: ElementPath, property: String, value: AnimatableValue) {
+        self.values.entry(element).or_default().insert(property, value);
+    }
+    
+    /// What has to be redone to go from `previous` to these values, if anything changed
+    pub fn change_from(&self, previous: &AnimatedStyles) -> Option<StyleChange> {
+        let changed = |from: &AnimatedStyles, to: &AnimatedStyles| -> Option<StyleChange> {
+            from.values
+                .iter()
+                .flat_map(|(element, values)| values.iter().map(move |value| (element, value)))
+                .filter(|(element, (property, value))| {
+                    to.values.get(*element).and_then(|values| values.get(*property)) != Some(*value)
+                })
+                .map(|(_, (property, _))| StyleChange::of(property))
+                .max()
+        };
+        changed(self, previous).max(changed(previous, self))
+    }
+    
+    /// Write the values into a styled document, over what the stylesheet gave
+    pub fn apply(&self, styled: &mut StyledNode) {
+        for (element, values) in &self.values {
+            let node = element.iter().try_fold(&mut *styled, |node, &i| node.children.get_mut(i));
+            let Some(node) = node else {
+                continue;
+            };
+            for (property, value) in values {
+                if let Some(value) = value.to_css_value() {
+                    node.specified_values.insert(property.clone(), value);
+                }
+            }
+        }
+    }
+    
+    /// Set the opacity and transform of animated elements' compositor layers
+    ///
+    /// Returns false if an element whose opacity or transform is animated
+    /// has no layer of its own, so its change has to be painted.
+    pub fn apply_to_layers(&self, layers: &mut Compositor) -> bool {
+        let mut composited = true;
+        for (element, values) in &self.values {
+            let layer = layers.element_layer(element).and_then(|id| layers.get_layer_mut(id));
+            let Some(layer) = layer else {
+                composited &= !values.keys().any(|property| StyleChange::of(property) == StyleChange::Composite);
+                continue;
+            };
+            // Tiles are reused; only how the layer is composited changes
+            if let Some(AnimatableValue::Number(opacity)) = values.get("opacity") {
+                layer.opacity = opacity.clamp(0.0, 1.0);
+            }
+            if let Some(AnimatableValue::Transform(transform)) = values.get("transform") {
+                layer.transform = transform.to_matrix_about(layer.transform_origin);
+            }
+        }
+        composited
     }
 }
 
@@ -323,26 +781,77 @@ pub struct AnimationManager {
     keyframe_animations: HashMap<String, KeyframeAnimation>,
     /// Active animations
     active_animations: Vec<ActiveAnimation>,
-    /// Active transitions
-    active_transitions: HashMap<String, (Instant, AnimatableValue, AnimatableValue, Transition)>,
+    /// Active transitions, by element and property
+    active_transitions: HashMap<(ElementPath, String), (Instant, AnimatableValue, AnimatableValue, Transition)>,
+    /// Final values of finished animations that fill forwards
+    filled: AnimatedStyles,
+    /// Events fired since they were last taken
+    events: Vec<AnimationEvent>,
+    /// How far the document has scrolled through its range, horizontally and vertically
+    scroll_progress: (f32, f32),
+    /// Has the scroll progress changed since the last sample
+    scrolled: bool,
+    /// Real time and animation time when the playback rate last changed
+    clock: (Instant, Instant),
+    /// How fast animation time passes, 1.0 being real time
+    playback_rate: f32,
+    /// What running animations is allowed to do
+    motion_policy: MotionPolicy,
 }
 
 impl AnimationManager {
     /// Create a new animation manager
     pub fn new() -> Self {
+        let now = Instant::now();
         Self {
             keyframe_animations: HashMap::new(),
             active_animations: Vec::new(),
             active_transitions: HashMap::new(),
+            filled: AnimatedStyles::new(),
+            events: Vec::new(),
+            scroll_progress: (0.0, 0.0),
+            scrolled: false,
+            clock: (now, now),
+            playback_rate: 1.0,
+            motion_policy: MotionPolicy::Normal,
         }
     }
     
+    /// Animation time at a real instant; animations start and are sampled in it
+    fn time_at(&self, now: Instant) -> Instant {
+        let (real, animation) = self.clock;
+        animation + now.saturating_duration_since(real).mul_f64(self.playback_rate as f64)
+    }
+    
+    /// Run animations faster or slower than real time, e.g. 0.1 to slow them 10x
+    pub fn set_playback_rate(&mut self, rate: f32) {
+        let rate = rate.max(0.0);
+        if rate != self.playback_rate {
+            let now = Instant::now();
+            self.clock = (now, self.time_at(now));
+            self.playback_rate = rate;
+        }
+    }
+    
+    pub fn playback_rate(&self) -> f32 {
+        self.playback_rate
+    }
+    
+    /// Let animations run, finish them at once, or stop them running
+    pub fn set_motion_policy(&mut self, policy: MotionPolicy) {
+        self.motion_policy = policy;
+    }
+    
+    pub fn motion_policy(&self) -> MotionPolicy {
+        self.motion_policy
+    }
+    
     /// Register a keyframe animation
     pub fn register_keyframe_animation(&mut self, animation: KeyframeAnimation) {
         self.keyframe_animations.insert(animation.name.clone(), animation);
     }
     
-    /// Start an animation
+    /// Start an animation on the document's root
     pub fn start_animation(
         &mut self,
         name: String,
@@ -350,6 +859,19 @@ impl AnimationManager {
         iteration_count: u32,
         direction: AnimationDirection,
         fill_mode: AnimationFillMode,
+    ) -> bool {
+        self.start_element_animation(Vec::new(), name, duration, iteration_count, direction, fill_mode)
+    }
+    
+    /// Start an animation on an element
+    pub fn start_element_animation(
+        &mut self,
+        target: ElementPath,
+        name: String,
+        duration: Duration,
+        iteration_count: u32,
+        direction: AnimationDirection,
+        fill_mode: AnimationFillMode,
     ) -> bool {
         if !self.keyframe_animations.contains_key(&name) {
             return false;
@@ -357,10 +879,14 @@ impl AnimationManager {
         
         self.active_animations.push(ActiveAnimation {
             name,
+            target,
             duration,
-            start_time: Instant::now(),
+            start_time: self.time_at(Instant::now()),
+            delay: Duration::ZERO,
             iteration_count,
             current_iteration: 0,
+            started: false,
+            timeline: AnimationTimeline::Document,
             direction,
             play_state: AnimationPlayState::Running,
             fill_mode,
@@ -369,7 +895,7 @@ impl AnimationManager {
         true
     }
     
-    /// Start a transition
+    /// Start a transition on the document's root
     pub fn start_transition(
         &mut self,
         property: String,
@@ -377,34 +903,175 @@ impl AnimationManager {
         to: AnimatableValue,
         transition: Transition,
     ) {
-        self.active_transitions.insert(property, (Instant::now(), from, to, transition));
+        self.start_element_transition(Vec::new(), property, from, to, transition);
+    }
+    
+    /// Start a transition on an element, replacing any running for the property
+    pub fn start_element_transition(
+        &mut self,
+        target: ElementPath,
+        property: String,
+        from: AnimatableValue,
+        to: AnimatableValue,
+        transition: Transition,
+    ) {
+        let start_time = self.time_at(Instant::now());
+        self.active_transitions.insert((target, property), (start_time, from, to, transition));
+    }
+    
+    /// Start the transitions a style change asks for
+    ///
+    /// Each element property that changed between `before` and `after`
+    /// transitions if the element's `transition` properties list it. A
+    /// transition starts from the value `current` shows, if the property
+    /// is being animated, so a change mid-transition carries on from there.
+    /// Elements new in `after` do not transition. Returns whether any
+    /// transition started.
+    pub fn start_style_transitions(
+        &mut self,
+        before: &StyleSnapshot,
+        after: &StyleSnapshot,
+        current: &AnimatedStyles,
+    ) -> bool {
+        let mut started = false;
+        for (element, values) in &after.values {
+            let Some(old_values) = before.get(element) else {
+                continue;
+            };
+            let transitions = transitions_of(values);
+            if transitions.is_empty() {
+                continue;
+            }
+            for (property, value) in values {
+                if property.starts_with("transition") || old_values.get(property) == Some(value) {
+                    continue;
+                }
+                // Later transitions in the list win
+                let transition = transitions.iter().rev().find(|t| t.property == *property || t.property == "all");
+                let Some(transition) = transition.filter(|t| !(t.duration + t.delay).is_zero()) else {
+                    continue;
+                };
+                let from = current
+                    .get(element)
+                    .and_then(|values| values.get(property))
+                    .cloned()
+                    .or_else(|| {
+                        let old = old_values.get(property)?;
+                        AnimatableValue::from_css_value(property, old)
+                    });
+                let (Some(from), Some(to)) = (from, AnimatableValue::from_css_value(property, value)) else {
+                    continue;
+                };
+                if from.interpolate(&to, 0.0).is_none() {
+                    continue;
+                }
+                let transition = Transition { property: property.clone(), ..transition.clone() };
+                self.start_element_transition(element.clone(), property.clone(), from, to, transition);
+                started = true;
+            }
+        }
+        started
     }
     
     /// Update animations and get current values
     pub fn update(&mut self) -> HashMap<String, AnimatableValue> {
-        let mut result = HashMap::new();
+        let styles = self.sample(Instant::now());
+        styles.values.into_values().flatten().collect()
+    }
+    
+    /// Advance animations to a frame's time and get each element's values
+    ///
+    /// Finished animations and transitions are dropped; animations that
+    /// fill forwards keep their final values until cleared, and those that
+    /// fill backwards show their first values during their delay. Events
+    /// for what happened since the last sample are queued for `take_events`.
+    /// The motion policy and playback rate apply to time-driven animations.
+    pub fn sample(&mut self, now: Instant) -> AnimatedStyles {
+        let now = self.time_at(now);
+        let policy = self.motion_policy;
+        let mut result = self.filled.clone();
         
         // Update animations
+        let keyframe_animations = &self.keyframe_animations;
+        let filled = &mut self.filled;
+        let events = &mut self.events;
+        let scroll_progress = self.scroll_progress;
+        self.scrolled = false;
         self.active_animations.retain_mut(|anim| {
-            if anim.is_complete() {
+            let Some(keyframe_anim) = keyframe_animations.get(&anim.name) else {
                 return false;
+            };
+            // Scroll-driven animations go wherever the offset puts them and never finish
+            if let AnimationTimeline::Scroll(timeline) = anim.timeline {
+                let progress = match timeline.axis {
+                    ScrollAxis::Inline => scroll_progress.0,
+                    ScrollAxis::Block => scroll_progress.1,
+                };
+                let active = anim.duration * anim.iteration_count;
+                let at = anim.start_time + anim.delay + active.mul_f32(progress.clamp(0.0, 1.0));
+                for (prop, val) in keyframe_anim.get_values_at(anim.progress_at(at)) {
+                    result.insert(anim.target.clone(), prop, val);
+                }
+                return true;
+            }
+            let now = match policy {
+                MotionPolicy::Normal => now,
+                MotionPolicy::Disabled => return false,
+                MotionPolicy::Finish => {
+                    // Infinite animations finish after one iteration
+                    anim.iteration_count = anim.iteration_count.max(1);
+                    now.max(anim.start_time + anim.delay + anim.duration * anim.iteration_count)
+                }
+            };
+            let values = keyframe_anim.get_values_at(anim.progress_at(now));
+            if !anim.has_begun_at(now) {
+                if matches!(anim.fill_mode, AnimationFillMode::Backwards | AnimationFillMode::Both) {
+                    for (prop, val) in values {
+                        result.insert(anim.target.clone(), prop, val);
+                    }
+                }
+                return true;
             }
             
-            if let Some(keyframe_anim) = self.keyframe_animations.get(&anim.name) {
-                let progress = anim.current_progress();
-                let values = keyframe_anim.get_values_at(progress);
-                
-                for (prop, val) in values {
-                    result.insert(prop, val);
+            let mut event = |event_type, elapsed: Duration| {
+                let (target, name) = (anim.target.clone(), anim.name.clone());
+                events.push(AnimationEvent { event_type, target, name, elapsed_time: elapsed.as_secs_f32() });
+            };
+            if !anim.started {
+                anim.started = true;
+                event(AnimationEventType::AnimationStart, Duration::ZERO);
+            }
+            let iteration = anim.iteration_at(now);
+            if iteration > anim.current_iteration {
+                anim.current_iteration = iteration;
+                event(AnimationEventType::AnimationIteration, anim.duration * iteration);
+            }
+            if anim.is_complete_at(now) {
+                event(AnimationEventType::AnimationEnd, anim.duration * anim.iteration_count);
+                if matches!(anim.fill_mode, AnimationFillMode::Forwards | AnimationFillMode::Both) {
+                    for (prop, val) in values {
+                        filled.insert(anim.target.clone(), prop.clone(), val.clone());
+                        result.insert(anim.target.clone(), prop, val);
+                    }
                 }
+                return false;
+            }
+            
+            for (prop, val) in values {
+                result.insert(anim.target.clone(), prop, val);
             }
             
             true
         });
         
         // Update transitions
-        self.active_transitions.retain(|prop, (start_time, from, to, transition)| {
-            let elapsed = start_time.elapsed();
+        let events = &mut self.events;
+        self.active_transitions.retain(|(target, prop), (start_time, from, to, transition)| {
+            let elapsed = match policy {
+                MotionPolicy::Normal => now.saturating_duration_since(*start_time),
+                MotionPolicy::Finish => transition.delay + transition.duration,
+                MotionPolicy::Disabled => return false,
+            };
             
             if elapsed < transition.delay {
                 return true; // Not started yet
@@ -412,14 +1079,20 @@ impl AnimationManager {
             
             let progress = (elapsed - transition.delay).as_secs_f32() / transition.duration.as_secs_f32();
             
-            if progress >= 1.0 {
-                result.insert(prop.clone(), to.clone());
+            if transition.duration.is_zero() || progress >= 1.0 {
+                result.insert(target.clone(), prop.clone(), to.clone());
+                events.push(AnimationEvent {
+                    event_type: AnimationEventType::TransitionEnd,
+                    target: target.clone(),
+                    name: prop.clone(),
+                    elapsed_time: transition.duration.as_secs_f32(),
+                });
                 return false; // Complete
             }
             
             let eased_progress = transition.timing_function.calculate(progress);
             if let Some(value) = from.interpolate(to, eased_progress) {
-                result.insert(prop.clone(), value);
+                result.insert(target.clone(), prop.clone(), value);
             }
             
             true
@@ -428,9 +1101,28 @@ impl AnimationManager {
         result
     }
     
+    /// Drain the events fired by samples since the last call, in order
+    pub fn take_events(&mut self) -> Vec<AnimationEvent> {
+        std::mem::take(&mut self.events)
+    }
+    
+    /// Set how far the document has scrolled through its range (0.0 to
+    /// 1.0), horizontally and vertically, for scroll-driven animations
+    pub fn set_scroll_progress(&mut self, x: f32, y: f32) {
+        if self.scroll_progress != (x, y) {
+            self.scroll_progress = (x, y);
+            self.scrolled = true;
+        }
+    }
+    
     /// Check if any animations are active
+    ///
+    /// Scroll-driven animations are only while the scroll progress has
+    /// changed since the last sample.
     pub fn has_active_animations(&self) -> bool {
-        !self.active_animations.is_empty() || !self.active_transitions.is_empty()
+        let scroll_driven = |anim: &ActiveAnimation| matches!(anim.timeline, AnimationTimeline::Scroll(_));
+        self.active_animations.iter().any(|anim| self.scrolled || !scroll_driven(anim))
+            || !self.active_transitions.is_empty()
     }
     
     /// Pause an animation by name
@@ -451,10 +1143,34 @@ impl AnimationManager {
         }
     }
     
+    /// Drive an animation by name from a timeline other than time passing
+    pub fn set_animation_timeline(&mut self, name: &str, timeline: AnimationTimeline) {
+        for anim in &mut self.active_animations {
+            if anim.name == name {
+                anim.timeline = timeline;
+                if matches!(timeline, AnimationTimeline::Scroll(_)) {
+                    // A scroll range holds a whole number of iterations
+                    anim.iteration_count = anim.iteration_count.max(1);
+                }
+            }
+        }
+    }
+    
+    /// Delay the first iteration of an animation by name, from its start
+    pub fn delay_animation(&mut self, name: &str, delay: Duration) {
+        for anim in &mut self.active_animations {
+            if anim.name == name {
+                anim.delay = delay;
+            }
+        }
+    }
+    
     /// Clear all animations
     pub fn clear(&mut self) {
         self.active_animations.clear();
         self.active_transitions.clear();
+        self.filled = AnimatedStyles::new();
+        self.events.clear();
     }
 }
 
@@ -485,6 +1201,28 @@ mod tests {
         assert_eq!(tf.calculate(1.0), 1.0);
     }
     
+    #[test]
+    fn test_timing_function_cubic_bezier() {
+        let cases = [
+            (TimingFunction::Ease, [0.0948, 0.4085, 0.8024, 0.9605, 0.9943]),
+            (TimingFunction::EaseIn, [0.0170, 0.0935, 0.3154, 0.6219, 0.8394]),
+            (TimingFunction::EaseOut, [0.1606, 0.3781, 0.6846, 0.9065, 0.9830]),
+            (TimingFunction::EaseInOut, [0.0197, 0.1292, 0.5, 0.8708, 0.9803]),
+            (TimingFunction::CubicBezier(0.1, 0.7, 1.0, 0.1), [0.2448, 0.3504, 0.4173, 0.4899, 0.6099]),
+            // Overshooting both ends, as y is not limited to 0..=1
+            (TimingFunction::CubicBezier(0.68, -0.55, 0.265, 1.55), [-0.0663, -0.0828, 0.6067, 1.0892, 1.0624]),
+            // Flat at both ends, where Newton-Raphson cannot take a step
+            (TimingFunction::CubicBezier(1.0, 0.0, 0.0, 1.0), [0.0038, 0.0297, 0.5, 0.9703, 0.9962]),
+        ];
+        for (tf, expected) in cases {
+            for (x, y) in [0.1, 0.25, 0.5, 0.75, 0.9].into_iter().zip(expected) {
+                let progress = tf.calculate(x);
+                assert!((progress - y).abs() < 2e-3, "{:?} at {}: {} != {}", tf, x, progress, y);
+            }
+            assert!(tf.calculate(0.0).abs() < 1e-5 && (tf.calculate(1.0) - 1.0).abs() < 1e-5);
+        }
+    }
+    
     #[test]
     fn test_animatable_value_interpolation() {
         let v1 = AnimatableValue::Number(0.0);
@@ -594,28 +1332,348 @@ mod tests {
     
     #[test]
     fn test_transform_interpolation() {
-        let t1 = AnimatableValue::Transform(Transform {
-            translate_x: 0.0,
-            translate_y: 0.0,
-            scale_x: 1.0,
-            scale_y: 1.0,
-            rotate: 0.0,
-        });
+        use crate::transform::TransformFunction;
         
-        let t2 = AnimatableValue::Transform(Transform {
-            translate_x: 100.0,
-            translate_y: 50.0,
-            scale_x: 2.0,
-            scale_y: 2.0,
-            rotate: 90.0,
-        });
+        let t1 = Transform::parse("translate(0px, 0px) scale(1) rotate(0deg)").unwrap();
+        let t1 = AnimatableValue::Transform(t1);
+        let t2 = Transform::parse("translate(100px, 50px) scale(2) rotate(90deg)").unwrap();
+        let t2 = AnimatableValue::from_css_value("transform", &Value::Keyword(t2.to_string())).unwrap();
         
         if let Some(AnimatableValue::Transform(t)) = t1.interpolate(&t2, 0.5) {
-            assert!((t.translate_x - 50.0).abs() < 0.01);
-            assert!((t.scale_x - 1.5).abs() < 0.01);
-            assert!((t.rotate - 45.0).abs() < 0.01);
+            assert_eq!(t.functions, [
+                TransformFunction::Translate(50.0, 25.0, 0.0),
+                TransformFunction::Scale(1.5, 1.5, 1.0),
+                TransformFunction::Rotate(0.0, 0.0, 1.0, 45.0),
+            ]);
         } else {
             panic!("Expected transform interpolation");
         }
     }
+    
+    fn fade_manager() -> AnimationManager {
+        let mut manager = AnimationManager::new();
+        let mut anim = KeyframeAnimation::new("fade".to_string());
+        for (offset, opacity) in [(0.0, 0.0), (1.0, 1.0)] {
+            let mut kf = Keyframe { offset, values: HashMap::new(), timing_function: TimingFunction::Linear };
+            kf.values.insert("opacity".to_string(), AnimatableValue::Number(opacity));
+            anim.add_keyframe(kf);
+        }
+        manager.register_keyframe_animation(anim);
+        manager
+    }
+    
+    #[test]
+    fn test_fill_modes_and_events() {
+        let mut manager = fade_manager();
+        let start = Instant::now();
+        assert!(manager.start_element_animation(
+            vec![0],
+            "fade".to_string(),
+            Duration::from_secs(1),
+            2,
+            AnimationDirection::Reverse,
+            AnimationFillMode::Backwards,
+        ));
+        manager.delay_animation("fade", Duration::from_millis(500));
+        let transition = Transition {
+            property: "width".to_string(),
+            duration: Duration::from_millis(400),
+            timing_function: TimingFunction::Linear,
+            delay: Duration::ZERO,
+        };
+        let (from, to) = (AnimatableValue::Length(0.0), AnimatableValue::Length(10.0));
+        manager.start_element_transition(vec![1], "width".to_string(), from, to, transition);
+        
+        let opacity = |styles: &AnimatedStyles| styles.get(&[0]).and_then(|v| v.get("opacity")).cloned();
+        let events = |manager: &mut AnimationManager| -> Vec<(AnimationEventType, String, f32)> {
+            let events = manager.take_events().into_iter();
+            events.map(|event| (event.event_type, event.name, event.elapsed_time)).collect()
+        };
+        // Filling backwards, the delay shows the first iteration's first value
+        assert_eq!(opacity(&manager.sample(start + Duration::from_millis(250))), Some(AnimatableValue::Number(1.0)));
+        assert!(events(&mut manager).is_empty());
+        
+        manager.sample(start + Duration::from_millis(700));
+        assert_eq!(events(&mut manager), [
+            (AnimationEventType::AnimationStart, "fade".to_string(), 0.0),
+            (AnimationEventType::TransitionEnd, "width".to_string(), 0.4),
+        ]);
+        manager.sample(start + Duration::from_millis(1700));
+        assert_eq!(events(&mut manager), [(AnimationEventType::AnimationIteration, "fade".to_string(), 1.0)]);
+        
+        // Not filling forwards, nothing is left once it ends
+        let done = manager.sample(start + Duration::from_millis(3000));
+        assert_eq!(events(&mut manager), [(AnimationEventType::AnimationEnd, "fade".to_string(), 2.0)]);
+        assert_eq!(opacity(&done), None);
+        assert!(!manager.has_active_animations());
+        assert_eq!(AnimationEventType::TransitionEnd.name(), "transitionend");
+    }
+    
+    #[test]
+    fn test_scroll_timeline() {
+        let mut manager = fade_manager();
+        assert!(manager.start_element_animation(
+            vec![0],
+            "fade".to_string(),
+            Duration::from_secs(1),
+            0,
+            AnimationDirection::Normal,
+            AnimationFillMode::None,
+        ));
+        let timeline = ScrollTimeline { axis: ScrollAxis::Block };
+        manager.set_animation_timeline("fade", AnimationTimeline::Scroll(timeline));
+        let opacity = |manager: &mut AnimationManager| match manager.sample(Instant::now()).get(&[0]) {
+            Some(values) => values.get("opacity").cloned(),
+            None => None,
+        };
+        
+        // Progress follows the offset, not the clock, both ways and up to the end
+        assert_eq!(opacity(&mut manager), Some(AnimatableValue::Number(0.0)));
+        for (y, expected) in [(0.25, 0.25), (1.0, 1.0), (0.5, 0.5)] {
+            manager.set_scroll_progress(0.9, y);
+            assert!(manager.has_active_animations());
+            assert_eq!(opacity(&mut manager), Some(AnimatableValue::Number(expected)));
+            // Nothing to do until the page scrolls again
+            assert!(!manager.has_active_animations());
+        }
+        assert!(manager.take_events().is_empty());
+    }
+    
+    #[test]
+    fn test_playback_rate_and_motion_policy() {
+        let mut manager = fade_manager();
+        manager.set_playback_rate(0.1);
+        let start = |manager: &mut AnimationManager| {
+            manager.start_element_animation(
+                vec![0],
+                "fade".to_string(),
+                Duration::from_secs(1),
+                0,
+                AnimationDirection::Normal,
+                AnimationFillMode::Forwards,
+            )
+        };
+        let opacity = |manager: &mut AnimationManager, at: Instant| match manager.sample(at).get(&[0]) {
+            Some(values) => values.get("opacity").cloned(),
+            None => None,
+        };
+        
+        // Five seconds at a tenth of the speed is half of the animation
+        assert!(start(&mut manager));
+        match opacity(&mut manager, Instant::now() + Duration::from_secs(5)) {
+            Some(AnimatableValue::Number(value)) => assert!((0.45..0.6).contains(&value), "{}", value),
+            other => panic!("unexpected opacity {:?}", other),
+        }
+        
+        // Finishing jumps infinite animations to the end of their first iteration
+        manager.set_motion_policy(MotionPolicy::Finish);
+        assert_eq!(opacity(&mut manager, Instant::now()), Some(AnimatableValue::Number(1.0)));
+        let ends = manager.take_events().into_iter().filter(|e| e.event_type == AnimationEventType::AnimationEnd);
+        assert_eq!(ends.count(), 1);
+        assert!(!manager.has_active_animations());
+        
+        // Disabled animations never apply
+        manager.clear();
+        manager.set_motion_policy(MotionPolicy::Disabled);
+        assert!(start(&mut manager));
+        assert_eq!(opacity(&mut manager, Instant::now()), None);
+        assert!(!manager.has_active_animations());
+    }
+    
+    #[test]
+    fn test_sample_elements() {
+        let mut manager = fade_manager();
+        let start = Instant::now();
+        assert!(manager.start_element_animation(
+            vec![0, 1],
+            "fade".to_string(),
+            Duration::from_secs(1),
+            2,
+            AnimationDirection::Alternate,
+            AnimationFillMode::Forwards,
+        ));
+        let transition = Transition {
+            property: "width".to_string(),
+            duration: Duration::from_secs(1),
+            timing_function: TimingFunction::Linear,
+            delay: Duration::ZERO,
+        };
+        manager.start_element_transition(
+            vec![0, 2],
+            "width".to_string(),
+            AnimatableValue::Length(100.0),
+            AnimatableValue::Length(200.0),
+            transition,
+        );
+        
+        let opacity = |styles: &AnimatedStyles| match styles.get(&[0, 1]).and_then(|v| v.get("opacity")) {
+            Some(AnimatableValue::Number(opacity)) => *opacity,
+            other => panic!("Expected opacity, got {:?}", other),
+        };
+        let quarter = manager.sample(start + Duration::from_millis(250));
+        assert!((opacity(&quarter) - 0.25).abs() < 0.05);
+        assert!(matches!(quarter.get(&[0, 2]).and_then(|v| v.get("width")),
+            Some(AnimatableValue::Length(width)) if (width - 125.0).abs() < 5.0));
+        
+        // The second iteration runs backwards, and the animation fills forwards once done
+        let second = manager.sample(start + Duration::from_millis(1250));
+        assert!((opacity(&second) - 0.75).abs() < 0.05);
+        assert_eq!(second.change_from(&quarter), Some(StyleChange::Layout));
+        let done = manager.sample(start + Duration::from_secs(3));
+        assert_eq!(opacity(&done), 0.0);
+        assert!(done.get(&[0, 2]).is_none());
+        assert!(!manager.has_active_animations());
+        assert_eq!(manager.sample(start + Duration::from_secs(4)), done);
+        manager.clear();
+        assert!(manager.sample(start + Duration::from_secs(5)).is_empty());
+    }
+    
+    #[test]
+    fn test_style_changes() {
+        let mut before = AnimatedStyles::new();
+        before.insert(vec![1], "opacity".to_string(), AnimatableValue::Number(0.5));
+        before.insert(vec![2], "color".to_string(), AnimatableValue::Color(0, 0, 0, 255));
+        assert_eq!(before.change_from(&before.clone()), None);
+        
+        let mut after = before.clone();
+        after.insert(vec![1], "opacity".to_string(), AnimatableValue::Number(0.6));
+        assert_eq!(after.change_from(&before), Some(StyleChange::Composite));
+        after.insert(vec![2], "color".to_string(), AnimatableValue::Color(9, 0, 0, 255));
+        assert_eq!(after.change_from(&before), Some(StyleChange::Paint));
+        // A property that stops animating goes back to its style
+        before.insert(vec![3], "height".to_string(), AnimatableValue::Length(10.0));
+        assert_eq!(after.change_from(&before), Some(StyleChange::Layout));
+    }
+    
+    #[test]
+    fn test_apply_animated_styles() {
+        use crate::css::CssParser;
+        use crate::html::HtmlParser;
+        use crate::layout::{layout_tree, Dimensions};
+        use crate::style::style_tree;
+        
+        let document = HtmlParser::parse("<html><body><p id=\"a\">A</p><p id=\"b\">B</p></body></html>");
+        let stylesheet = CssParser::parse("#a { will-change: opacity; } #b { width: 10px; }");
+        let (a, b) = (document.path_to_id("a").unwrap(), document.path_to_id("b").unwrap());
+        let mut styles = AnimatedStyles::new();
+        styles.insert(b.clone(), "width".to_string(), AnimatableValue::Length(50.0));
+        styles.insert(a.clone(), "opacity".to_string(), AnimatableValue::Number(0.25));
+        let transform = Transform::translate(20.0, 0.0);
+        styles.insert(a.clone(), "transform".to_string(), AnimatableValue::Transform(transform.clone()));
+        
+        let mut styled = style_tree(&document, &stylesheet);
+        styles.apply(&mut styled);
+        let node = b.iter().fold(&styled, |node, &i| &node.children[i]);
+        assert_eq!(node.value("width"), Some(&Value::Length(50.0, css::Unit::Px)));
+        let node = a.iter().fold(&styled, |node, &i| &node.children[i]);
+        assert_eq!(node.value("opacity"), Some(&Value::Number(0.25)));
+        assert_eq!(node.value("transform"), Some(&Value::Keyword(transform.to_string())));
+        
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+        let mut layers = Compositor::from_layout(&layout_tree(&styled, viewport), viewport.content);
+        assert!(styles.apply_to_layers(&mut layers));
+        let layer = layers.get_layer(layers.element_layer(&a).unwrap()).unwrap();
+        assert_eq!((layer.opacity, layer.transform.apply(0.0, 0.0)), (0.25, (20.0, 0.0)));
+        
+        // Without a layer of its own, an element's opacity has to be painted
+        styles.insert(b, "transform".to_string(), AnimatableValue::Transform(Transform::new()));
+        assert!(!styles.apply_to_layers(&mut layers));
+    }
+    
+    #[test]
+    fn test_parse_timing_functions_and_times() {
+        assert_eq!(TimingFunction::parse("EASE-IN"), Some(TimingFunction::EaseIn));
+        assert_eq!(TimingFunction::parse("step-start"), Some(TimingFunction::Steps(1, StepPosition::Start)));
+        assert_eq!(TimingFunction::parse("steps(4, jump-start)"), Some(TimingFunction::Steps(4, StepPosition::Start)));
+        assert_eq!(TimingFunction::parse("steps(3)"), Some(TimingFunction::Steps(3, StepPosition::End)));
+        assert_eq!(
+            TimingFunction::parse("cubic-bezier(0.1, -0.5, 1, 1.5)"),
+            Some(TimingFunction::CubicBezier(0.1, -0.5, 1.0, 1.5))
+        );
+        assert_eq!(TimingFunction::parse("cubic-bezier(1.1, 0, 1, 1)"), None);
+        assert_eq!(TimingFunction::parse("steps(0)"), None);
+        assert_eq!(TimingFunction::parse("bounce"), None);
+        
+        assert_eq!(parse_time("200ms"), Some(Duration::from_millis(200)));
+        assert_eq!(parse_time("1.5s"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_time("-1s"), None);
+        assert_eq!(parse_time("10px"), None);
+    }
+    
+    #[test]
+    fn test_transitions_of() {
+        let keyword = |value: &str| Value::Keyword(value.to_string());
+        let mut values = PropertyMap::new();
+        assert!(transitions_of(&values).is_empty());
+        
+        values.insert("transition".to_string(), keyword("opacity 0.5s ease-out 100ms, width 1s steps(2)"));
+        let transitions = transitions_of(&values);
+        assert_eq!(transitions, [
+            Transition {
+                property: "opacity".to_string(),
+                duration: Duration::from_millis(500),
+                timing_function: TimingFunction::EaseOut,
+                delay: Duration::from_millis(100),
+            },
+            Transition {
+                property: "width".to_string(),
+                duration: Duration::from_secs(1),
+                timing_function: TimingFunction::Steps(2, StepPosition::End),
+                delay: Duration::ZERO,
+            },
+        ]);
+        
+        // Longhands replace the shorthand's lists, which repeat to match the properties
+        values.insert("transition-property".to_string(), keyword("color, height, margin-top"));
+        values.insert("transition-duration".to_string(), keyword("1s, 2s"));
+        let transitions = transitions_of(&values);
+        let described: Vec<_> = transitions.iter().map(|t| (t.property.as_str(), t.duration.as_secs())).collect();
+        assert_eq!(described, [("color", 1), ("height", 2), ("margin-top", 1)]);
+        assert_eq!(transitions[2].delay, Duration::from_millis(100));
+        
+        values.insert("transition-property".to_string(), keyword("none"));
+        assert!(transitions_of(&values).is_empty());
+        let only_duration = PropertyMap::from([("transition-duration".to_string(), keyword("2s"))]);
+        assert_eq!(transitions_of(&only_duration)[0].property, "all");
+    }
+    
+    #[test]
+    fn test_start_style_transitions() {
+        use crate::css::CssParser;
+        use crate::html::HtmlParser;
+        use crate::style::style_tree;
+        
+        let document = HtmlParser::parse("<html><body><p id=\"a\">A</p><p id=\"b\">B</p></body></html>");
+        let before = CssParser::parse(
+            "p { width: 100px; height: 10px; opacity: 1; transition: width 1s linear, opacity 0s; } \
+             #b { transition: none; }",
+        );
+        let after = CssParser::parse(
+            "p { width: 200px; height: 20px; opacity: 0; transition: width 1s linear, opacity 0s; } \
+             #b { transition: none; }",
+        );
+        let before = StyleSnapshot::of(&style_tree(&document, &before));
+        let after = StyleSnapshot::of(&style_tree(&document, &after));
+        let (a, b) = (document.path_to_id("a").unwrap(), document.path_to_id("b").unwrap());
+        
+        let mut manager = AnimationManager::new();
+        assert!(!manager.start_style_transitions(&StyleSnapshot::new(), &after, &AnimatedStyles::new()));
+        assert!(manager.start_style_transitions(&before, &after, &AnimatedStyles::new()));
+        let start = Instant::now();
+        let styles = manager.sample(start + Duration::from_millis(500));
+        // Only the listed property with a duration transitions, and only where listed
+        let animated = styles.get(&a).unwrap();
+        assert_eq!(animated.keys().collect::<Vec<_>>(), ["width"]);
+        assert!(matches!(animated["width"], AnimatableValue::Length(width) if (width - 150.0).abs() < 5.0));
+        assert!(styles.get(&b).is_none());
+        
+        // Changing back mid-transition starts from where the transition got to
+        let mut current = AnimatedStyles::new();
+        current.insert(a.clone(), "width".to_string(), AnimatableValue::Length(150.0));
+        assert!(manager.start_style_transitions(&after, &before, &current));
+        let styles = manager.sample(Instant::now());
+        assert!(matches!(styles.get(&a).unwrap()["width"], AnimatableValue::Length(width) if width >= 140.0));
+        assert!(!manager.start_style_transitions(&before, &before, &current));
+    }
 }
@@ -1,82 +1,593 @@
 // Unified Browser Application - Phase 6
 use browser_engine::{
+    accessibility::{AccessibilityTree, AxRole},
+    crash::{self, CrashReport, CrashReporter, PipelinePhase},
+    memory::{format_bytes, MemoryMonitor, MemoryPressure, MemoryReport, MemorySource},
     html::HtmlParser,
-    css::CssParser,
-    style::style_tree,
-    layout::{layout_tree, Dimensions},
-    display::{build_display_list, DisplayCommand},
-    window::{Window, WindowConfig},
-    css::Color,
+    css::{CssParser, Declaration, MediaType, Stylesheet},
+    dom::{Document, Node},
+    style::{style_tree_with_defaults, PropertyMap, StyledNode},
+    layout::{inline::{EstimatedMetrics, TextMeasure}, layout_tree_with_images, Dimensions, LayoutBox},
+    layout::overflow::{scroll_containers, BoxScrolls, ScrollContainer},
+    layout::positioning::pin_fixed_boxes,
+    display::{build_display_list_scrolled, build_display_list_with_base, scroll_display_list, DisplayCommand, DisplayList},
+    window::{
+        css_size, BeginFrame, FramePhase, FrameTimer, IdleDeadline, IdleTaskQueue, ManagedEvent, ScrollAlign,
+        ScrollBehavior, WindowConfig, WindowControl, WindowKey, WindowManager,
+    },
+    renderer::{font_manager::{FontManager, FontMeasure}, image_cache::ImageCache},
     layout::Rect,
-    ui::BrowserUI,
-    navigation::NavigationHistory,
-    js::JsContext,
-    net::HttpClient,
-    devtools::{DevTools, DevToolsTab, NetworkRequestType},
+    ui::{
+        resolve_input, AddressBarAction, AuthAction, BookmarksBarHit, BrowserUI, FindAction, LinkHandler, NavCommand,
+        DevToolsAction, InspectedPage, PageSelection, PrintPreviewAction, SiteInfo, SiteInfoAction, Tab, TabCommand,
+        TabId, TabManager,
+    },
+    bookmarks::BookmarkManager,
+    history::{HistoryDatabase, TimeRange, VisitTransition},
+    reader::{extract_article, ReaderSettings},
+    preferences::Preferences,
+    content_filter::ContentFilter,
+    extensions::{Extension, ExtensionEvent, ExtensionHost, ExtensionStorage},
+    user_content::{RunAt, UserContent},
+    editing::{editing_host, position_at, Editor, InputEvent},
+    print::Page,
+    clipboard::Clipboard,
+    permissions::{PermissionManager, PermissionName, PermissionState, PromptAnswer, PromptId, RequestOutcome},
+    navigation::{document_base_url, resolve_href, element_rect_by_id, fragment_target, is_same_document},
+    storage::{CacheStorage, SiteDataTypes, StorageManager, WebStorage},
+    js::{DomMutation, EventType, MediaRequest, OpenDisposition, ScrollRequest, WindowOpenRequest},
+    net::{
+        is_mixed_content, AuthRequest, CacheMode, CachedResource, CancellationToken, ErrorPage, FetchEvent, FetchId, FetchRequest,
+        LoadEvent, NetError, RequestInterceptor, ResourceFetcher, ResourceLoader, ResourceType, SecurityState, Transfer,
+    },
+    devtools::{inspect_element, CdpServer, CdpTarget, DevTools, DevToolsTab, InspectedElement, NetworkRequestType},
+    js::{JsContext, JsError, JsValue},
+    multiprocess::{crash_page_html, ContentEvent, ContentSupervisor, GpuEvent, GpuFrame, GpuProcess, MessageBus, VideoLayer},
+    compositor::{changed_rects, Compositor},
+    animation::{AnimatedStyles, AnimationEvent, AnimationManager, ElementPath, StyleChange, StyleSnapshot},
+    web_fonts::FontFaceSet,
+    media::{Backends, MediaBackend, MediaElements, MediaError, MediaKind, RangeChunk},
+    observers::Rect as ClientRect,
+    performance::{NavigationTiming, TaskAttribution},
+    web_vitals::{
+        contentful_paints, largest_contentful_paint, layout_shift_score, ContentfulPaint, WebVitals, RECENT_INPUT,
+    },
+    headless::{HeadlessBrowser, HeadlessOptions, DEFAULT_TIMEOUT},
+    print::PrintOptions,
 };
-use winit::event::WindowEvent;
-use std::sync::{Arc, Mutex};
+use winit::dpi::PhysicalSize;
+use winit::event::{ElementState, MouseButton, MouseScrollDelta, TouchPhase, WindowEvent};
+use winit::keyboard::ModifiersState;
+use winit::window::CursorIcon;
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Pixels scrolled per mouse wheel notch
+const WHEEL_LINE_HEIGHT: f32 = 40.0;
 
 /// Browser application state
+///
+/// Caches, storage, bookmarks and history are shared by every window.
+/// The window handling the current event is in `window`; the others wait
+/// in `windows` until they receive an event.
 struct BrowserApp {
-    /// Browser UI (address bar, navigation buttons)
-    ui: BrowserUI,
-    /// Navigation history
-    history: NavigationHistory,
-    /// JavaScript context
-    js_context: JsContext,
-    /// HTTP client for loading pages
-    http_client: HttpClient,
+    /// State of the window handling the current event
+    window: WindowState,
+    /// Key of the window in `window`
+    window_key: WindowKey,
+    /// Every other open window
+    windows: HashMap<WindowKey, WindowState>,
+    /// Windows to open once the event has been handled
+    window_requests: Vec<NewWindow>,
+    /// `window.open` calls allowed by the popup blocker, not yet handled
+    script_opens: Vec<(url::Url, OpenDisposition)>,
+    /// Loads pages through the HTTP cache
+    resource_loader: ResourceLoader,
+    /// Fetches documents in the background, through the loader's cache and cookies
+    fetcher: ResourceFetcher,
+    /// Documents fetched for windows' navigations, until each window takes its own
+    fetched: HashMap<FetchId, Fetched>,
+    /// Ad-blocking rules from `filters.txt` next to the preferences
+    content_filter: Arc<ContentFilter>,
+    /// User stylesheets and scripts from the `user-content` directory next to the preferences
+    user_content: UserContent,
+    /// Installed extensions and their background scripts
+    extensions: ExtensionHost,
+    /// Demuxes and decodes the resources of <audio> and <video> elements
+    media_backend: Arc<dyn MediaBackend>,
+    /// Cancels the page load in flight (stop button)
+    load_cancel: Option<CancellationToken>,
     /// Developer tools
     devtools: DevTools,
-    /// Current page content
-    current_content: Option<PageContent>,
+    /// DevTools protocol server, if started with `--remote-debugging-port`
+    devtools_server: Option<CdpServer>,
+    /// Bookmarks shown in the bookmarks bar and offered as suggestions
+    bookmarks: BookmarkManager,
+    /// Visits across all tabs, ranked for address bar suggestions
+    history: HistoryDatabase,
+    /// Reading style for reader mode, shared by every tab
+    reader_settings: ReaderSettings,
+    /// User preferences and per-site overrides
+    preferences: Preferences,
+    /// File the preferences are saved to, if there is a config directory
+    preferences_path: Option<std::path::PathBuf>,
+    /// Browser storage; bookmarks and history are persisted to local storage
+    storage: StorageManager,
+    /// OS clipboard
+    clipboard: Clipboard,
+    /// Per-origin permission decisions and the prompts waiting for the user
+    permissions: PermissionManager,
+    /// Measures text for laying out pages, from the system's fonts when they load
+    text_metrics: Box<dyn TextMeasure>,
+    /// Permission requests of pages waiting on each prompt: the tab and the page's request id
+    permission_waiters: HashMap<PromptId, Vec<(TabId, u32)>>,
+    /// BroadcastChannel and `postMessage` traffic between the pages of all tabs
+    message_bus: MessageBus,
+    /// Site-isolated content processes hosting the tabs' pages
+    content: ContentSupervisor,
+    /// Decoded images of every window's pages, and those being fetched
+    images: ImageCache,
+    /// Image fetches in flight, by the image they fetch
+    image_fetches: HashMap<FetchId, url::Url>,
+    /// Reports of pages that panicked, kept in the profile
+    crashes: CrashReporter,
+    /// Tabs whose page panicked, showing the crash page until they navigate
+    crashed_pages: HashSet<TabId>,
+    /// Compares memory use with the budget and signals pressure
+    memory: MemoryMonitor,
+    /// Bytes of the GPU process's glyph atlas when memory was last checked
+    glyph_atlas_bytes: usize,
+    /// Keyboard modifiers currently held
+    modifiers: ModifiersState,
+    /// Work put off until a window has spare time before its next frame
+    idle_tasks: IdleTaskQueue<IdleWork>,
+}
+
+/// A document fetched from the network or the cache, or why it could not be
+type Fetched = Result<(CachedResource, Option<Transfer>), NetError>;
+
+/// A navigation whose document is being fetched in the background
+struct PendingLoad {
+    /// Tab the page is shown in once it arrives
+    tab: TabId,
+    url: url::Url,
+    fetch: FetchId,
+    /// The document's entry in the DevTools network log
+    network_request: usize,
+    transition: VisitTransition,
+    /// Stops the fetch (stop button, or another navigation)
+    cancel: CancellationToken,
+}
+
+/// Browser work that waits for idle time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IdleWork {
+    /// Drop cached responses no page has used for a while
+    EvictCache,
+    /// Look for an article in a tab's page, to offer reader mode
+    DetectArticle(TabId),
+    /// Fetch the web fonts a tab's page is waiting for
+    LoadFonts(TabId),
+    /// Fetch the next ranges of the media a tab's page is playing
+    LoadMedia(TabId),
+}
+
+/// Cached responses unused for this long are evicted in idle time
+const CACHE_IDLE_EVICTION: Duration = Duration::from_secs(30 * 60);
+/// Cached responses unused for this long are evicted under memory pressure
+const CACHE_PRESSURE_EVICTION: Duration = Duration::from_secs(60);
+/// Bytes of responses kept in the HTTP cache on disk
+const HTTP_DISK_CACHE_SIZE: usize = 256 * 1024 * 1024;
+/// Reader mode is offered this long after a load at the latest, however busy the page
+const ARTICLE_DETECTION_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// State owned by one top-level window
+struct WindowState {
+    /// Browser UI (address bar, navigation buttons)
+    ui: BrowserUI,
+    /// Open tabs, each with its own document, history, scroll and JS context
+    tabs: TabManager,
+    /// Link hover and activation state
+    link_handler: LinkHandler,
+    /// Text selected by dragging in the page
+    selection: PageSelection,
+    /// Time of the previous frame, for scroll animation
+    last_frame: Instant,
+    /// Latest touchpad scroll delta and when it arrived, for fling velocity
+    last_touchpad_scroll: Option<(f32, f32, Instant)>,
+    /// Rendered content for each tab
+    contents: HashMap<TabId, PageContent>,
     /// Loading state
     loading: bool,
+    /// Device pixels per CSS pixel for the monitor the window is on
+    scale_factor: f64,
+    /// Accessibility tree of the active tab, exposed to screen readers
+    accessibility: AccessibilityTree,
+    /// Has the active tab's document or layout changed since the tree was built
+    accessibility_dirty: bool,
+    /// Videos of the active tab whose backing store in the GPU process has their current picture
+    video_layers: HashSet<(TabId, ElementPath)>,
+    /// Navigation waiting on its document
+    pending_load: Option<PendingLoad>,
+}
+
+impl WindowState {
+    fn new(width: f32, height: f32, preferences: &Preferences) -> Self {
+        let mut ui = BrowserUI::new(width);
+        ui.set_tab_strip_visible(true);
+        ui.set_bookmarks_bar_visible(true);
+        ui.resize(width, height);
+        ui.address_bar.set_search_engine(preferences.search_engine.clone());
+        
+        Self {
+            ui,
+            tabs: TabManager::new(),
+            link_handler: LinkHandler::new(),
+            selection: PageSelection::new(),
+            last_frame: Instant::now(),
+            last_touchpad_scroll: None,
+            contents: HashMap::new(),
+            loading: false,
+            scale_factor: 1.0,
+            accessibility: AccessibilityTree::new(),
+            accessibility_dirty: true,
+            video_layers: HashSet::new(),
+            pending_load: None,
+        }
+    }
+}
+
+/// A window to open, with what to show in it
+struct NewWindow {
+    width: f32,
+    height: f32,
+    /// Tab moved from another window, with its rendered content
+    tab: Option<(Tab, Option<PageContent>)>,
+    /// Page to load; ignored when a tab is moved
+    url: Option<url::Url>,
 }
 
 /// Rendered page content
 struct PageContent {
-    backgrounds: Vec<(Rect, Color)>,
-    borders: Vec<(Rect, Color, (f32, f32, f32, f32))>,
+    /// What the page paints, in page coordinates
+    display_list: DisplayList,
+    /// Stylesheet applied to the document
+    stylesheet: Stylesheet,
+    /// Stylesheet as parsed, `@media` blocks included, for printing
+    source_stylesheet: Stylesheet,
+    /// Font defaults from the site's preferences
+    style_defaults: PropertyMap,
+    /// Compositor layers of the page as last laid out
+    layers: Compositor,
+    /// Has the page been drawn, so its paint timing is recorded
+    painted: bool,
+    /// Text and images as painted, for paint timing and layout shifts
+    contentful: Vec<ContentfulPaint>,
+    /// When the user last interacted with the page, if they have
+    last_input: Option<Instant>,
+    /// The page's Core Web Vitals so far
+    vitals: WebVitals,
+    /// CSS animations and transitions running on the page
+    animations: AnimationManager,
+    /// Animated values as of the last frame, applied over the stylesheet
+    animated: AnimatedStyles,
+    /// Elements' styles as of the last restyle, before animation, to start transitions from
+    computed: StyleSnapshot,
+    /// The page's `@font-face` fonts and how far they have loaded
+    fonts: FontFaceSet,
+    /// The page's <audio> and <video> elements and their players
+    media: MediaElements,
+    /// How far the page's scrollable boxes are scrolled
+    box_scrolls: BoxScrolls,
+    /// Boxes clipping their overflow as last laid out, for the wheel to find
+    scroll_containers: Vec<ScrollContainer>,
+    /// Why the document failed to load, if this is the page explaining it
+    error_page: Option<ErrorPage>,
+}
+
+impl PageContent {
+    /// Style a document the way this content was rendered
+    fn style<'a>(&'a self, dom: &'a Node) -> StyledNode<'a> {
+        let mut styled = style_tree_with_defaults(dom, &self.stylesheet, &self.style_defaults);
+        self.animated.apply(&mut styled);
+        styled
+    }
+}
+
+/// Note a resource a tab's page loads, keeping those that make it mixed content
+fn note_subresource(tab: &mut Tab, url: &url::Url, devtools: &mut DevTools) {
+    let mixed = tab.url().is_some_and(|page| is_mixed_content(page, url));
+    if mixed && !tab.insecure_content.contains(url) {
+        devtools.console.warn(format!("Mixed content: {} was loaded over an insecure connection", url));
+        tab.insecure_content.push(url.clone());
+    }
+}
+
+/// The active tab's document styled as it was rendered, with its content and the tab
+fn styled_active_page<'a>(
+    tabs: &'a TabManager,
+    contents: &'a HashMap<TabId, PageContent>,
+) -> Option<(StyledNode<'a>, &'a PageContent, &'a Tab)> {
+    let tab = tabs.active();
+    let content = contents.get(&tab.id())?;
+    Some((content.style(tab.tree()?), content, tab))
+}
+
+/// The active page as the DevTools panel inspects it
+fn inspected_page<'a>((styled, content, tab): &'a (StyledNode<'a>, &'a PageContent, &'a Tab)) -> InspectedPage<'a> {
+    InspectedPage {
+        styled,
+        stylesheet: &content.stylesheet,
+        layers: &content.layers,
+        vitals: &content.vitals,
+        performance: tab.js_context.performance(),
+    }
+}
+
+/// Compositor layers of a laid out page, with tiles at the window's resolution
+fn page_layers(layout_root: &LayoutBox, viewport: &Dimensions, scale_factor: f64) -> Compositor {
+    let mut layers = Compositor::from_layout(layout_root, viewport.content);
+    layers.set_scale_factor(scale_factor as f32);
+    layers
 }
 
 impl BrowserApp {
-    /// Create a new browser application
-    fn new(width: f32) -> Self {
+    /// Create a new browser application with its first window
+    fn new(window_key: WindowKey, width: f32, height: f32) -> Self {
+        let mut storage = StorageManager::new();
+        let bookmarks = BookmarkManager::load(storage.local_storage()).unwrap_or_else(|e| {
+            eprintln!("Failed to load bookmarks: {}", e);
+            BookmarkManager::new()
+        });
+        let history = HistoryDatabase::load(storage.local_storage()).unwrap_or_else(|e| {
+            eprintln!("Failed to load history: {}", e);
+            HistoryDatabase::new()
+        });
+        let reader_settings = ReaderSettings::load(storage.local_storage()).unwrap_or_else(|e| {
+            eprintln!("Failed to load reader settings: {}", e);
+            ReaderSettings::new()
+        });
+        let preferences_path = Preferences::default_path();
+        let preferences = preferences_path.as_deref().map_or_else(Preferences::new, |path| {
+            Preferences::load(path).unwrap_or_else(|e| {
+                eprintln!("Failed to load preferences: {}", e);
+                Preferences::new()
+            })
+        });
+        // Pages' localStorage and caches are kept next to the preferences file
+        if let Some(path) = preferences_path.as_deref().map(|p| p.with_file_name("local_storage.log")) {
+            match WebStorage::open(&path) {
+                Ok(web_storage) => storage.set_web_storage(web_storage),
+                Err(e) => eprintln!("Failed to open local storage: {}", e),
+            }
+        }
+        if let Some(dir) = preferences_path.as_deref().map(|p| p.with_file_name("cache_storage")) {
+            match CacheStorage::open(&dir) {
+                Ok(cache_storage) => storage.set_cache_storage(cache_storage),
+                Err(e) => eprintln!("Failed to open cache storage: {}", e),
+            }
+        }
+        let content_filter = preferences_path.as_deref().map_or_else(ContentFilter::new, |path| {
+            match ContentFilter::load(&path.with_file_name("filters.txt")) {
+                Ok(filter) => filter,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => ContentFilter::new(),
+                Err(e) => {
+                    eprintln!("Failed to load content filters: {}", e);
+                    ContentFilter::new()
+                }
+            }
+        });
+        let user_content = preferences_path.as_deref().map_or_else(UserContent::new, |path| {
+            match UserContent::load(&path.with_file_name("user-content")) {
+                Ok(content) => content,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => UserContent::new(),
+                Err(e) => {
+                    eprintln!("Failed to load user stylesheets and scripts: {}", e);
+                    UserContent::new()
+                }
+            }
+        });
+        let extensions = preferences_path.as_deref().map_or_else(ExtensionHost::new, load_extensions);
+        let permissions = preferences_path.as_deref().map_or_else(PermissionManager::new, |path| {
+            PermissionManager::load(&path.with_file_name("permissions.json")).unwrap_or_else(|e| {
+                eprintln!("Failed to load permissions: {}", e);
+                PermissionManager::new()
+            })
+        });
+        let text_metrics: Box<dyn TextMeasure> = match FontManager::new() {
+            Ok(fonts) => Box::new(FontMeasure::new(fonts)),
+            Err(e) => {
+                eprintln!("Failed to load fonts for layout: {}", e);
+                Box::new(EstimatedMetrics)
+            }
+        };
+        let crashes = preferences_path.as_deref().map_or_else(CrashReporter::new, |path| {
+            CrashReporter::open(&path.with_file_name("crash_reports")).unwrap_or_else(|e| {
+                eprintln!("Failed to open crash reports: {}", e);
+                CrashReporter::new()
+            })
+        });
+        let resource_loader = ResourceLoader::with_default_cache();
+        if let Some(dir) = preferences_path.as_deref().map(|p| p.with_file_name("http_cache")) {
+            if let Err(e) = resource_loader.open_disk_cache(&dir, HTTP_DISK_CACHE_SIZE) {
+                eprintln!("Failed to open HTTP cache: {}", e);
+            }
+        }
+        storage.set_network_data(Box::new(resource_loader.site_data()));
+        let fetcher = resource_loader.fetcher();
+        
         Self {
-            ui: BrowserUI::new(width),
-            history: NavigationHistory::new(),
-            js_context: JsContext::new(),
-            http_client: HttpClient::new(),
+            window: WindowState::new(width, height, &preferences),
+            window_key,
+            windows: HashMap::new(),
+            window_requests: Vec::new(),
+            script_opens: Vec::new(),
+            resource_loader,
+            fetcher,
+            fetched: HashMap::new(),
+            content_filter: Arc::new(content_filter),
+            user_content,
+            extensions,
+            media_backend: Arc::new(Backends::new()),
+            load_cancel: None,
             devtools: DevTools::new(),
-            current_content: None,
-            loading: false,
+            devtools_server: None,
+            bookmarks,
+            history,
+            reader_settings,
+            preferences,
+            preferences_path,
+            storage,
+            clipboard: Clipboard::system(),
+            permissions,
+            text_metrics,
+            permission_waiters: HashMap::new(),
+            message_bus: MessageBus::new(),
+            content: ContentSupervisor::new(),
+            images: ImageCache::with_default_size(),
+            image_fetches: HashMap::new(),
+            crashes,
+            crashed_pages: HashSet::new(),
+            memory: MemoryMonitor::default(),
+            glyph_atlas_bytes: 0,
+            modifiers: ModifiersState::empty(),
+            idle_tasks: IdleTaskQueue::new(),
         }
     }
-    
-    /// Navigate to a URL
+
+    /// Make a window's state current before handling its events
+    fn focus_window(&mut self, key: WindowKey) {
+        if key == self.window_key {
+            return;
+        }
+        if let Some(mut state) = self.windows.remove(&key) {
+            std::mem::swap(&mut self.window, &mut state);
+            self.windows.insert(self.window_key, state);
+            self.window_key = key;
+        }
+    }
+
+    /// Drop a closed window's state
+    fn close_window(&mut self, key: WindowKey) {
+        if key == self.window_key {
+            let Some(&next) = self.windows.keys().next() else {
+                return;
+            };
+            self.focus_window(next);
+        }
+        if let Some(state) = self.windows.remove(&key) {
+            for tab in state.tabs.tabs() {
+                self.content.close_tab(tab.id());
+            }
+        }
+    }
+
+    /// Open an empty window the size of the current one (Ctrl+N)
+    fn new_window(&mut self) {
+        let url = url::Url::parse("about:blank").ok();
+        self.window_requests.push(NewWindow {
+            width: self.window.ui.bounds.width,
+            height: self.window.ui.bounds.height,
+            tab: None,
+            url,
+        });
+    }
+
+    /// Move the active tab into a window of its own (Ctrl+Shift+M)
+    fn move_tab_to_new_window(&mut self) {
+        // A window's only tab is already on its own
+        if self.window.tabs.len() < 2 {
+            return;
+        }
+        let id = self.window.tabs.active_id();
+        let Some(tab) = self.window.tabs.take_tab(id) else {
+            return;
+        };
+        let content = self.window.contents.remove(&id);
+        self.devtools.console.info(format!("Moved tab to new window: {}", tab.title));
+        self.window_requests.push(NewWindow {
+            width: self.window.ui.bounds.width,
+            height: self.window.ui.bounds.height,
+            tab: Some((tab, content)),
+            url: None,
+        });
+        self.show_active_tab();
+    }
+
+    /// Windows to open once the current event has been handled
+    fn take_window_requests(&mut self) -> Vec<NewWindow> {
+        std::mem::take(&mut self.window_requests)
+    }
+
+    /// Set up the state for a window the window manager is opening
+    fn create_window(&mut self, key: WindowKey, request: NewWindow) {
+        let mut state = WindowState::new(request.width, request.height, &self.preferences);
+        let mut url = request.url;
+        if let Some((tab, content)) = request.tab {
+            let blank = state.tabs.active_id();
+            let id = state.tabs.adopt_tab(tab);
+            state.tabs.close_tab(blank);
+            match content {
+                Some(content) => {
+                    state.contents.insert(id, content);
+                }
+                None => url = state.tabs.active().url().cloned(),
+            }
+        }
+        
+        let opener = self.window_key;
+        self.windows.insert(key, state);
+        self.focus_window(key);
+        match url {
+            Some(url) => self.navigate(url.to_string()),
+            None => self.show_active_tab(),
+        }
+        self.focus_window(opener);
+    }
+
+    /// Open the pages scripts asked for with `window.open`
+    fn handle_script_opens(&mut self) {
+        for (url, disposition) in std::mem::take(&mut self.script_opens) {
+            match disposition {
+                OpenDisposition::CurrentTab => self.navigate(url.to_string()),
+                OpenDisposition::NewTab => {
+                    self.window.tabs.open_tab();
+                    self.navigate(url.to_string());
+                    self.show_active_tab();
+                }
+                OpenDisposition::NewWindow { width, height } => {
+                    self.window_requests.push(NewWindow {
+                        width: width.map_or(self.window.ui.bounds.width, |w| w as f32),
+                        height: height.map_or(self.window.ui.bounds.height, |h| h as f32 + self.window.ui.chrome_height),
+                        tab: None,
+                        url: Some(url),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Navigate to a URL by following a link
     fn navigate(&mut self, url_str: String) {
+        self.navigate_with(url_str, VisitTransition::Link);
+    }
+
+    /// Navigate to a URL, recording how the user got there in history
+    fn navigate_with(&mut self, url_str: String, transition: VisitTransition) {
         println!("Navigating to: {}", url_str);
-        self.loading = true;
-        self.ui.address_bar.set_loading(true);
-        
-        // Parse URL
-        let url = match url::Url::parse(&url_str) {
-            Ok(u) => u,
-            Err(_) => {
-                // Try adding http:// prefix
-                match url::Url::parse(&format!("http://{}", url_str)) {
-                    Ok(u) => u,
-                    Err(e) => {
-                        let error_msg = format!("Invalid URL: {}", e);
-                        eprintln!("{}", error_msg);
-                        self.devtools.console.error(error_msg);
-                        self.loading = false;
-                        self.ui.address_bar.set_loading(false);
-                        return;
-                    }
-                }
+        self.set_loading(true);
+        
+        // Parse URL, inferring https:// or falling back to a search
+        let url = match resolve_input(&url_str, self.window.ui.address_bar.search_engine()) {
+            Some(u) => u,
+            None => {
+                let error_msg = format!("Invalid URL: {}", url_str);
+                eprintln!("{}", error_msg);
+                self.devtools.console.error(error_msg);
+                self.set_loading(false);
+                return;
             }
         };
         
@@ -88,178 +599,2924 @@ impl BrowserApp {
         );
         self.devtools.console.info(format!("Navigating to: {}", url));
         
-        // Add to history
-        self.history.navigate_to(url.clone());
+        // Add to history, remembering where we were on the page being left
+        let previous = self.window.tabs.active().url().cloned();
+        self.save_scroll_position();
+        self.window.tabs.active_mut().history.navigate_to(url.clone());
+        
+        // A new page opens in the normal view
+        let same_document = previous.as_ref().is_some_and(|prev| is_same_document(prev, &url));
+        if !same_document {
+            self.window.tabs.active_mut().reader_mode = false;
+        }
+        
+        // Fragment change within the current document: scroll, don't reload
+        if same_document && url.fragment().is_some() && self.window.contents.contains_key(&self.window.tabs.active_id()) {
+            self.devtools.network.complete_request(req_idx, 200, 0, Some("text/html".to_string()));
+            self.scroll_to_fragment(url.fragment().unwrap_or(""), ScrollBehavior::Auto);
+            self.window.ui.address_bar.set_url(url.to_string());
+            self.set_loading(false);
+            return;
+        }
         
-        // Load the page
-        match self.load_page(&url, Some(req_idx)) {
+        // Load the page; documents from the network arrive in the background
+        self.assign_content_process(&url);
+        let crashed = self.content.is_crashed(self.window.tabs.active_id());
+        if matches!(url.scheme(), "http" | "https") && !crashed {
+            self.start_fetch(url, req_idx, transition);
+            return;
+        }
+        let result = self.load_page(&url, Some(req_idx), CacheMode::Default);
+        self.finish_navigation(&url, transition, result);
+    }
+
+    /// Start fetching a navigation's document, replacing any the window waits on
+    fn start_fetch(&mut self, url: url::Url, network_request: usize, transition: VisitTransition) {
+        if let Some(previous) = self.window.pending_load.take() {
+            previous.cancel.cancel();
+        }
+        self.load_event(LoadEvent::Started(url.clone()));
+        let site = self.preferences.for_site(&url);
+        self.fetcher.set_cookie_policy(site.cookie_policy);
+        self.fetcher.set_interceptor(Some(self.site_interceptor(site.content_filtering)));
+        let cancel = CancellationToken::new();
+        let fetch = self.fetcher.fetch(FetchRequest::new(url.clone(), ResourceType::Html).with_cancel(cancel.clone()));
+        let tab = self.window.tabs.active_id();
+        self.window.pending_load = Some(PendingLoad { tab, url, fetch, network_request, transition, cancel });
+    }
+
+    /// Collect finished background fetches and show the window's page if
+    /// its document arrived
+    ///
+    /// Returns whether a page was shown.
+    fn poll_fetches(&mut self) -> bool {
+        let windows = std::iter::once(&self.window).chain(self.windows.values());
+        let awaited: HashSet<FetchId> = windows.filter_map(|w| w.pending_load.as_ref()).map(|load| load.fetch).collect();
+        // Progress of other fetches and fetches nobody waits on any more are dropped
+        let mut images_loaded = false;
+        let mut images_decoded = false;
+        for event in self.fetcher.poll() {
+            match event {
+                FetchEvent::Finished { id, resource, transfer } if awaited.contains(&id) => {
+                    self.fetched.insert(id, Ok((*resource, transfer)));
+                }
+                FetchEvent::Failed { id, error } if awaited.contains(&id) => {
+                    self.fetched.insert(id, Err(error));
+                }
+                FetchEvent::Finished { id, resource, .. } => {
+                    if self.image_fetches.remove(&id).is_some() {
+                        match self.images.load_resource(&resource) {
+                            Ok(_) => images_decoded = true,
+                            Err(e) => {
+                                self.devtools.console.warn(format!("Could not decode image {}: {}", resource.url, e))
+                            }
+                        }
+                        images_loaded = true;
+                    }
+                }
+                FetchEvent::Failed { id, error } => {
+                    if let Some(url) = self.image_fetches.remove(&id) {
+                        self.devtools.console.warn(format!("Failed to load image {}: {}", url, error));
+                        self.images.fail(&url);
+                        images_loaded = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+        // Images without a CSS size take up room once their size is known
+        if images_decoded {
+            self.restyle_active_page();
+        }
+        let Some(fetch) = self.window.pending_load.as_ref().map(|load| load.fetch) else {
+            return images_loaded;
+        };
+        let (Some(fetched), Some(load)) = (self.fetched.remove(&fetch), self.window.pending_load.take()) else {
+            return images_loaded;
+        };
+        // Pages are rendered into the active tab; a tab left meanwhile keeps its page
+        if self.window.tabs.active_id() != load.tab {
+            self.devtools.network.complete_request(load.network_request, 0, 0, None);
+            self.devtools.console.info(format!("Dropped load of {} in a background tab", load.url));
+            self.window.ui.status_bar.progress_mut().finish();
+            self.set_loading(false);
+            return false;
+        }
+        let result = self.load_page_fetched(&load.url, Some(load.network_request), CacheMode::Default, Some(fetched));
+        self.finish_navigation(&load.url, load.transition, result);
+        true
+    }
+
+    /// Fetch the images the active page draws that are not loaded yet
+    ///
+    /// Until they are decoded, the renderer draws placeholders in their place.
+    fn request_images(&mut self) {
+        let Some(content) = self.window.contents.get(&self.window.tabs.active_id()) else {
+            return;
+        };
+        let tab = self.window.tabs.active_mut();
+        let page = tab.url().cloned();
+        for command in &content.display_list {
+            let DisplayCommand::Image { url, .. } = command else {
+                continue;
+            };
+            note_subresource(tab, url, &mut self.devtools);
+            if self.images.request(url) {
+                let mut request = FetchRequest::new(url.clone(), ResourceType::Image);
+                if let Some(ref page) = page {
+                    request = request.with_first_party(page.clone());
+                }
+                self.image_fetches.insert(self.fetcher.fetch(request), url.clone());
+            }
+        }
+    }
+
+    /// Memory held by every window's tabs and by the caches they share
+    fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::new();
+        report.add(MemorySource::Images, self.images.size());
+        report.add(MemorySource::GlyphAtlas, self.glyph_atlas_bytes);
+        report.add(MemorySource::HttpCache, self.resource_loader.cache_size());
+        for window in std::iter::once(&self.window).chain(self.windows.values()) {
+            for tab in window.tabs.tabs() {
+                let (id, name) = (tab.id(), tab.title.as_str());
+                let dom = tab.document.as_ref().map_or(0, |document| document.memory_bytes());
+                report.add_for_tab(id, name, MemorySource::Dom, dom);
+                let worlds = tab.extension_worlds.iter().map(|(_, world)| world.heap_size_estimate());
+                let scripts = tab.js_context.heap_size_estimate() + worlds.sum::<usize>();
+                report.add_for_tab(id, name, MemorySource::JsHeap, scripts);
+                if let Some(content) = window.contents.get(&id) {
+                    report.add_for_tab(id, name, MemorySource::CompositorTiles, content.layers.memory_bytes());
+                }
+            }
+        }
+        report
+    }
+
+    /// Check memory use when a check is due, shedding memory under pressure
+    ///
+    /// Caches that refill on demand are trimmed first; over budget,
+    /// background tabs are discarded too, largest first.
+    fn check_memory(&mut self, gpu: &GpuProcess) {
+        if !self.memory.due(Instant::now()) {
+            return;
+        }
+        self.glyph_atlas_bytes = gpu.glyph_atlas_bytes();
+        let report = self.memory_report();
+        let pressure = self.memory.assess(&report);
+        if pressure == MemoryPressure::None {
+            return;
+        }
+        self.devtools.console.warn(format!(
+            "Memory pressure {}: {} in use of {}",
+            pressure.as_str(),
+            format_bytes(report.total()),
+            format_bytes(self.memory.budget())
+        ));
+        self.images.evict_to(self.images.size() / 2);
+        self.resource_loader.evict_unused(CACHE_PRESSURE_EVICTION);
+        gpu.trim_caches();
+        if pressure < MemoryPressure::Critical {
+            return;
+        }
+        let mut needed = self.memory.relief_needed(&report);
+        for (tab, bytes) in report.tabs_by_size() {
+            if needed == 0 {
+                break;
+            }
+            if self.discard_tab(tab) {
+                needed = needed.saturating_sub(bytes);
+            }
+        }
+    }
+
+    /// Discard a background tab's page, in whichever window has it
+    ///
+    /// Returns false for active tabs and those with no page to discard.
+    fn discard_tab(&mut self, id: TabId) -> bool {
+        for window in std::iter::once(&mut self.window).chain(self.windows.values_mut()) {
+            if window.tabs.active_id() == id || !window.contents.contains_key(&id) {
+                continue;
+            }
+            let Some(tab) = window.tabs.tab_mut(id) else {
+                continue;
+            };
+            tab.discard();
+            self.devtools.console.info(format!("Discarded tab to save memory: {}", tab.title));
+            window.contents.remove(&id);
+            window.video_layers.retain(|(tab, _)| *tab != id);
+            self.content.close_tab(id);
+            return true;
+        }
+        false
+    }
+
+    /// Show a navigation's page once it has loaded, or report why it did not
+    fn finish_navigation(&mut self, url: &url::Url, transition: VisitTransition, result: Result<PageContent, String>) {
+        match result {
             Ok(content) => {
-                self.current_content = Some(content);
-                self.ui.address_bar.set_url(url.to_string());
-                self.loading = false;
-                self.ui.address_bar.set_loading(false);
-                println!("Page loaded successfully");
+                let failed = content.error_page.is_some();
+                self.window.contents.insert(self.window.tabs.active_id(), content);
+                self.window.ui.address_bar.set_url(url.to_string());
+                self.set_loading(false);
+                if failed {
+                    println!("Page failed to load");
+                } else {
+                    println!("Page loaded successfully");
+                    self.record_visit(url, transition);
+                }
+                if let Some(fragment) = url.fragment() {
+                    self.scroll_to_fragment(fragment, ScrollBehavior::Instant);
+                }
+                if self.window.ui.find_bar.is_open() {
+                    self.run_find();
+                }
             }
             Err(e) => {
                 eprintln!("Failed to load page: {}", e);
-                self.loading = false;
-                self.ui.address_bar.set_loading(false);
+                self.set_loading(false);
+            }
+        }
+    }
+
+    /// Request interceptor for a page's loads: the content filter, if the
+    /// site uses it, and the extensions' `webRequest` listeners
+    fn site_interceptor(&self, content_filtering: bool) -> Arc<dyn RequestInterceptor> {
+        let filter = self.content_filter.clone() as Arc<dyn RequestInterceptor>;
+        let interceptors: Vec<_> = content_filtering.then_some(filter).into_iter().chain([self.extensions.interceptor()]).collect();
+        Arc::new(interceptors)
+    }
+
+    /// Load and render a page
+    fn load_page(
+        &mut self,
+        url: &url::Url,
+        network_req_idx: Option<usize>,
+        cache_mode: CacheMode,
+    ) -> Result<PageContent, String> {
+        self.load_page_fetched(url, network_req_idx, cache_mode, None)
+    }
+
+    /// Render a page whose document may already have been fetched, loading it otherwise
+    ///
+    /// A panic while loading the page is reported, and the tab shows the
+    /// crash page in its place.
+    fn load_page_fetched(
+        &mut self,
+        url: &url::Url,
+        network_req_idx: Option<usize>,
+        cache_mode: CacheMode,
+        fetched: Option<Fetched>,
+    ) -> Result<PageContent, String> {
+        let loaded = crash::guard(Some(url), PipelinePhase::Load, || {
+            self.build_page(url, network_req_idx, cache_mode, fetched)
+        });
+        loaded.unwrap_or_else(|report| {
+            self.page_crashed(report);
+            self.build_page(url, None, cache_mode, None)
+        })
+    }
+
+    /// Load, parse, style, lay out and run the scripts of a page
+    fn build_page(
+        &mut self,
+        url: &url::Url,
+        network_req_idx: Option<usize>,
+        cache_mode: CacheMode,
+        fetched: Option<Fetched>,
+    ) -> Result<PageContent, String> {
+        self.window.ui.auth_bar.close();
+        // Handle special URLs
+        if url.as_str() == "about:blank" {
+            let dom = Node::element("html".to_string(), Default::default(), vec![]);
+            let tab = self.window.tabs.active_mut();
+            tab.set_document(dom, url);
+            tab.title = "New Tab".to_string();
+            tab.reader_available = false;
+            tab.reader_mode = false;
+            self.window.accessibility_dirty = true;
+            return Ok(PageContent {
+                display_list: vec![],
+                stylesheet: Stylesheet::new(vec![]),
+                source_stylesheet: Stylesheet::new(vec![]),
+                style_defaults: PropertyMap::new(),
+                layers: Compositor::default(),
+                painted: false,
+                contentful: Vec::new(),
+                last_input: None,
+                vitals: WebVitals::new(),
+                animations: AnimationManager::new(),
+                animated: AnimatedStyles::new(),
+                computed: StyleSnapshot::new(),
+                fonts: FontFaceSet::new(),
+                media: MediaElements::new(),
+                box_scrolls: BoxScrolls::new(),
+                scroll_containers: Vec::new(),
+                error_page: None,
+            });
+        }
+        
+        self.load_event(LoadEvent::Started(url.clone()));
+        let navigation_start = Instant::now();
+        
+        // Scripts, cookies, filtering and fonts follow the site's preferences
+        let site = self.preferences.for_site(url);
+        self.resource_loader.set_cookie_policy(site.cookie_policy);
+        self.resource_loader.set_interceptor(Some(self.site_interceptor(site.content_filtering)));
+        self.resource_loader.set_first_party(Some(url.clone()));
+        let style_defaults = site.style_defaults();
+        
+        // A tab whose content process or page pipeline crashed shows the crash page until reloaded
+        let tab_id = self.window.tabs.active_id();
+        let crashed = self.content.is_crashed(tab_id) || self.crashed_pages.contains(&tab_id);
+        
+        // A document that failed to load is replaced by a page saying why
+        let mut error_page = None;
+        // A server asking for credentials gets them from the sign-in bar, which
+        // loads the page again; until then its 401 response is shown
+        let mut auth_request = None;
+        // For demo purposes, use example HTML if it's a local file or special URL
+        let html_content = if crashed {
+            if let Some(idx) = network_req_idx {
+                self.devtools.network.complete_request(idx, 0, 0, None);
+            }
+            crash_page_html(url)
+        } else if url.scheme() == "http" || url.scheme() == "https" {
+            // Try to fetch from network, unless the document was fetched in the background
+            let loaded = fetched.unwrap_or_else(|| {
+                let cancel = CancellationToken::new();
+                self.load_cancel = Some(cancel.clone());
+                self.resource_loader.load_traced(url, cache_mode, Some(&cancel))
+            });
+            let fetched = loaded.and_then(|(resource, transfer)| {
+                let status = transfer.as_ref().map_or(200, |transfer| transfer.status);
+                error_page = ErrorPage::for_response(url, status, &resource.data);
+                auth_request = transfer.as_ref().and_then(|transfer| AuthRequest::for_transfer(url, transfer));
+                // Complete network request, with what went over the wire
+                if let Some(idx) = network_req_idx {
+                    let network = &mut self.devtools.network;
+                    let content_type = Some(resource.content_type.clone()).filter(|t| !t.is_empty());
+                    network.complete_request(idx, status, resource.data.len(), content_type);
+                    network.record_cache_use(idx, transfer.as_ref());
+                    if let Some(transfer) = transfer {
+                        network.record_transfer(idx, &transfer, &resource.data);
+                    }
+                }
+                resource.as_text()
+            });
+            self.load_cancel = None;
+            match fetched {
+                Ok(text) => error_page.as_ref().map_or(text, ErrorPage::to_html),
+                Err(NetError::Cancelled) => {
+                    if let Some(idx) = network_req_idx {
+                        self.devtools.network.complete_request(idx, 0, 0, None);
+                    }
+                    self.window.ui.status_bar.progress_mut().finish();
+                    return Err("Load stopped".to_string());
+                }
+                Err(e) => {
+                    let error_msg = format!("Network error: {}", e);
+                    eprintln!("{}", error_msg);
+                    self.devtools.console.error(error_msg);
+                    // Mark request as failed
+                    if let Some(idx) = network_req_idx {
+                        self.devtools.network.complete_request(idx, 0, 0, None);
+                    }
+                    error_page = ErrorPage::for_error(url, &e);
+                    error_page.as_ref().map_or_else(get_example_html, ErrorPage::to_html)
+                }
+            }
+        } else if url.as_str() == "about:memory" {
+            let html = self.memory_report().to_html(self.memory.budget());
+            if let Some(idx) = network_req_idx {
+                self.devtools.network.complete_request(idx, 200, html.len(), Some("text/html".to_string()));
+            }
+            html
+        } else {
+            // Use example HTML for testing
+            if let Some(idx) = network_req_idx {
+                let html = get_example_html();
+                self.devtools.network.complete_request(idx, 200, html.len(), Some("text/html".to_string()));
+            }
+            get_example_html()
+        };
+        
+        if let Some(request) = auth_request {
+            self.window.ui.auth_bar.open(request);
+        }
+        
+        // Parse HTML
+        let response_end = Instant::now();
+        crash::enter_phase(PipelinePhase::Parse);
+        let dom = HtmlParser::parse(&html_content);
+        let dom_interactive = Instant::now();
+        self.load_event(LoadEvent::DocumentLoaded);
+        // The demo stylesheet is built in, so there are no subresources to fetch
+        self.load_event(LoadEvent::SubresourcesDiscovered(0));
+        let base_url = document_base_url(&dom, url);
+        self.window.link_handler.set_base_url(base_url.clone());
+        
+        // Reader mode swaps in the extracted article and the reading style;
+        // otherwise whether to offer it is found in idle time
+        let article = self.window.tabs.active().reader_mode.then(|| extract_article(&dom)).flatten();
+        let reader_available = article.is_some();
+        let reader_mode = reader_available;
+        let (dom, css_content) = match article {
+            Some(article) if reader_mode => (article.to_document(), self.reader_settings.stylesheet()),
+            // Extract inline CSS or use default, hiding what the filter lists hide
+            _ if site.content_filtering => (dom, get_example_css() + &self.content_filter.hiding_css(url)),
+            _ => (dom, get_example_css()),
+        };
+        let mut source_stylesheet = CssParser::parse(&css_content);
+        source_stylesheet.append(self.user_content.stylesheet(url));
+        if !reader_mode {
+            source_stylesheet.append(self.extensions.stylesheet(url));
+        }
+        // Media queries see the user's preferences, such as reduced motion
+        let media_features = self.preferences.media_features();
+        let stylesheet = source_stylesheet.for_media_with(MediaType::Screen, &media_features);
+        // Text in web fonts waits for them from here, as font-display allows
+        let mut fonts = FontFaceSet::from_stylesheet(&source_stylesheet, &base_url);
+        fonts.start_loading(Instant::now());
+        
+        // Compute styles
+        let layout_start = Instant::now();
+        crash::enter_phase(PipelinePhase::Style);
+        let styled = style_tree_with_defaults(&dom, &stylesheet, &style_defaults);
+        let mut computed = StyleSnapshot::of(&styled);
+        
+        // Calculate layout
+        crash::enter_phase(PipelinePhase::Layout);
+        let layout_root = layout_tree_with_images(
+            &styled,
+            self.layout_viewport(),
+            &*self.text_metrics,
+            self.images.sizes(),
+            Some(&base_url),
+        );
+        
+        // Build display list
+        crash::enter_phase(PipelinePhase::Paint);
+        let mut display_list = build_display_list_with_base(&layout_root, &base_url);
+        let mut contentful = contentful_paints(&display_list);
+        let mut containers = scroll_containers(&layout_root, &BoxScrolls::new());
+        
+        let page_box = layout_root.dimensions.margin_box();
+        let viewport = self.layout_viewport();
+        let mut layers = page_layers(&layout_root, &viewport, self.window.scale_factor);
+        let behavior = ScrollBehavior::from_style(&styled);
+        let layout_end = Instant::now();
+        
+        // Hand the document to the active tab (this also resets its JS context)
+        let tab = self.window.tabs.active_mut();
+        tab.set_document(dom, &base_url);
+        self.devtools.dom_breakpoints.clear();
+        self.devtools.layers.select(None);
+        if let Some(previous) = tab.document_id.take() {
+            self.message_bus.unregister_document(previous);
+        }
+        tab.document_id = Some(self.message_bus.register_document(&base_url, tab.id(), None));
+        tab.reader_available = reader_available;
+        tab.reader_mode = reader_mode;
+        tab.js_context.set_enabled(site.javascript_enabled);
+        let _ = tab.js_context.set_time_origin(navigation_start);
+        let _ = tab.js_context.set_media_features(&media_features);
+        let _ = tab.js_context.set_font_faces(&fonts);
+        // Parsing and the first layout ran before the timeline began
+        let since_start = |at: Instant| at.duration_since(navigation_start).as_secs_f64() * 1000.0;
+        let performance = tab.js_context.performance_mut();
+        let (parse_start, parse_end) = (since_start(response_end), since_start(dom_interactive));
+        performance.record_task(parse_start, parse_end - parse_start, TaskAttribution::Parse);
+        let (layout_start, layout_end) = (since_start(layout_start), since_start(layout_end));
+        performance.record_task(layout_start, layout_end - layout_start, TaskAttribution::Layout);
+        tab.scroll.set_viewport_size(viewport.content.width, viewport.content.height);
+        tab.scroll.set_content_size(page_box.width, page_box.height);
+        tab.scroll.behavior = behavior;
+        let mut media = MediaElements::new();
+        if let Some(document) = tab.document.as_ref() {
+            let _ = tab.js_context.set_element_ids(document);
+            let _ = tab.js_context.set_console_document(document.tree());
+            media = MediaElements::from_document(document.tree(), &base_url, self.media_backend.clone());
+        }
+        let _ = tab.js_context.set_media_elements(&media, self.media_backend.as_ref(), Instant::now());
+        let _ = tab.js_context.set_permission_states(&self.permissions.states(&base_url));
+        // Videos of the previous page are no longer shown
+        let shown_tab = tab.id();
+        self.window.video_layers.retain(|(tab, _)| *tab != shown_tab);
+        
+        // Execute any JavaScript (simplified); reader mode shows no scripted content.
+        // The user's scripts run around the page's, as they ask
+        let scripts_enabled = site.javascript_enabled && !reader_mode && !crashed && error_page.is_none();
+        crash::enter_phase(PipelinePhase::Script);
+        let user_scripts = |run_at| match scripts_enabled {
+            true => self.user_content.scripts_at(url, run_at).into_iter().map(str::to_string).collect(),
+            false => Vec::new(),
+        };
+        let (start_scripts, end_scripts): (Vec<String>, Vec<String>) =
+            (user_scripts(RunAt::DocumentStart), user_scripts(RunAt::DocumentEnd));
+        let mut changed = false;
+        for script in start_scripts {
+            let result = self.window.tabs.active_mut().js_context.run_page_script(&script);
+            changed |= self.script_finished(result);
+        }
+        if scripts_enabled {
+            changed |= self.run_content_scripts(url, RunAt::DocumentStart);
+        }
+        if let Some(script) = extract_script(&html_content).filter(|_| scripts_enabled) {
+            self.devtools.console.log("Executing inline script".to_string());
+            let result = self.window.tabs.active_mut().js_context.run_page_script(&script);
+            // The page's observers see the rest of the page arrive
+            let tab = self.window.tabs.active_mut();
+            if let Some(document) = tab.document.as_ref() {
+                if let Err(e) = tab.js_context.record_parser_insertions(document) {
+                    self.devtools.console.error(format!("MutationObserver error: {}", e));
+                }
+            }
+            changed |= self.script_finished(result);
+        }
+        for script in end_scripts {
+            let result = self.window.tabs.active_mut().js_context.run_page_script(&script);
+            changed |= self.script_finished(result);
+        }
+        if scripts_enabled {
+            changed |= self.run_content_scripts(url, RunAt::DocumentEnd);
+        }
+        // Lay out again what the scripts changed
+        let document = self.window.tabs.active().tree().filter(|_| changed);
+        if let Some(document) = document {
+            let start = Instant::now();
+            crash::enter_phase(PipelinePhase::Style);
+            let styled = style_tree_with_defaults(document, &stylesheet, &style_defaults);
+            computed = StyleSnapshot::of(&styled);
+            let layout_root =
+                layout_tree_with_images(&styled, viewport, &*self.text_metrics, self.images.sizes(), Some(&base_url));
+            display_list = build_display_list_with_base(&layout_root, &base_url);
+            contentful = contentful_paints(&display_list);
+            containers = scroll_containers(&layout_root, &BoxScrolls::new());
+            layers = page_layers(&layout_root, &viewport, self.window.scale_factor);
+            let performance = self.window.tabs.active_mut().js_context.performance_mut();
+            performance.record_task_since(start, TaskAttribution::Layout);
+        }
+        // The whole page is painted afresh
+        self.devtools.layers.record_paints(&[page_box], Instant::now());
+        
+        let (response_end, dom_interactive) = (since_start(response_end), since_start(dom_interactive));
+        let loaded_at = Instant::now();
+        let loaded = since_start(loaded_at);
+        self.window.tabs.active_mut().js_context.performance_mut().set_navigation_timing(NavigationTiming {
+            response_start: response_end,
+            response_end,
+            dom_loading: response_end,
+            dom_interactive,
+            dom_content_loaded_event_start: loaded,
+            dom_content_loaded_event_end: loaded,
+            dom_complete: loaded,
+            load_event_start: loaded,
+            load_event_end: loaded,
+            ..Default::default()
+        });
+        self.deliver_performance_entries();
+        self.load_event(LoadEvent::Finished);
+        self.window.accessibility_dirty = true;
+        let tab_id = self.window.tabs.active_id();
+        if !reader_mode && !self.idle_tasks.contains(|work| *work == IdleWork::DetectArticle(tab_id)) {
+            self.idle_tasks.post_with_timeout(IdleWork::DetectArticle(tab_id), ARTICLE_DETECTION_TIMEOUT, loaded_at);
+        }
+        if !self.idle_tasks.contains(|work| *work == IdleWork::EvictCache) {
+            self.idle_tasks.post(IdleWork::EvictCache);
+        }
+        if fonts.is_loading() && !self.idle_tasks.contains(|work| *work == IdleWork::LoadFonts(tab_id)) {
+            self.idle_tasks.post(IdleWork::LoadFonts(tab_id));
+        }
+        if media.iter().any(|element| element.player.wants_fetch()) {
+            self.post_media_load(tab_id);
+        }
+        let mut animations = AnimationManager::new();
+        animations.set_motion_policy(self.preferences.animations);
+        Ok(PageContent {
+            display_list,
+            stylesheet,
+            source_stylesheet,
+            style_defaults,
+            layers,
+            painted: false,
+            contentful,
+            last_input: None,
+            vitals: WebVitals::new(),
+            animations,
+            animated: AnimatedStyles::new(),
+            computed,
+            fonts,
+            media,
+            box_scrolls: BoxScrolls::new(),
+            scroll_containers: containers,
+            error_page,
+        })
+    }
+
+    /// Feed a page load lifecycle event to the status bar's progress
+    fn load_event(&mut self, event: LoadEvent) {
+        self.window.ui.status_bar.progress_mut().handle(&event);
+    }
+
+    /// Host the active tab's next page in its site's content process
+    ///
+    /// This also recovers a tab whose process crashed.
+    fn assign_content_process(&mut self, url: &url::Url) {
+        let tab_id = self.window.tabs.active_id();
+        self.crashed_pages.remove(&tab_id);
+        if let Err(e) = self.content.assign_tab(tab_id, url) {
+            self.devtools.console.warn(format!("No content process for {}: {}", url, e));
+        }
+    }
+
+    /// Run commands from remote DevTools clients and send them the page's events
+    fn poll_devtools_server(&mut self) {
+        if let Some(mut server) = self.devtools_server.take() {
+            server.poll(self);
+            self.devtools_server = Some(server);
+        }
+    }
+
+    /// Handle replies and crashes from content processes
+    ///
+    /// Returns true if the active tab crashed and now shows the crash page.
+    fn poll_content_processes(&mut self) -> bool {
+        let mut active_crashed = false;
+        for event in self.content.poll() {
+            let ContentEvent::Crashed { site, tabs, .. } = event else {
+                continue;
+            };
+            self.devtools.console.error(format!("Content process for {} crashed", site));
+            // Background tabs render the crash page when they are shown again
+            for window in self.windows.values_mut() {
+                window.contents.retain(|id, _| !tabs.contains(id));
+            }
+            self.window.contents.retain(|id, _| !tabs.contains(id));
+            let active = self.window.tabs.active_id();
+            if tabs.contains(&active) {
+                self.show_crash_page();
+                active_crashed = true;
+            }
+        }
+        active_crashed
+    }
+
+    /// Replace the active tab's content with the crash page
+    fn show_crash_page(&mut self) {
+        let active = self.window.tabs.active_id();
+        let tab_url = self.window.tabs.active().url();
+        let Some(url) = self.content.tab_url(active).or(tab_url).cloned() else {
+            return;
+        };
+        if let Ok(content) = self.load_page(&url, None, CacheMode::Default) {
+            self.window.contents.insert(active, content);
+        }
+    }
+
+    /// Record a panic in the active tab's page; its page is the crash page until it navigates
+    fn page_crashed(&mut self, report: Box<CrashReport>) {
+        let at = report.url.as_deref().unwrap_or("a page");
+        self.devtools.console.error(format!("Page crashed during {} of {}: {}", report.phase.as_str(), at, report.message));
+        if let Err(e) = self.crashes.record(*report) {
+            eprintln!("Failed to save crash report: {}", e);
+        }
+        self.crashed_pages.insert(self.window.tabs.active_id());
+    }
+
+    /// Viewport used to lay out page content
+    fn layout_viewport(&self) -> Dimensions {
+        let page = self.window.ui.content_viewport();
+        let mut viewport = Dimensions::default();
+        viewport.content.width = page.width;
+        viewport.content.height = page.height;
+        viewport
+    }
+
+    /// Update link hover state and return the cursor to display
+    fn handle_mouse_move(&mut self, x: f32, y: f32) -> CursorIcon {
+        self.window.ui.input_handler.update_mouse_position(x, y);
+        if self.window.ui.devtools.is_picking() {
+            let over_page = !self.window.ui.contains_point(x, y) && !self.window.ui.devtools.contains_point(x, y);
+            let element = if over_page { self.element_at(x, y) } else { None };
+            self.window.ui.devtools.set_highlight(element);
+            if over_page {
+                return CursorIcon::Crosshair;
+            }
+        }
+        if self.window.ui.devtools.contains_point(x, y) {
+            self.window.ui.status_bar.set_link(None);
+            return CursorIcon::Default;
+        }
+        
+        self.guard_active_page(PipelinePhase::Input, |app| app.hover_page(x, y));
+        
+        // Preview the hovered link's target
+        let link = self.window.link_handler.hovered_url().map(|url| url.to_string());
+        self.window.ui.status_bar.set_link(link);
+        
+        self.window.link_handler.cursor()
+    }
+
+    /// Find the link under the pointer on the active page and fire
+    /// `mouseout` and `mouseover` as it changes
+    fn hover_page(&mut self, x: f32, y: f32) {
+        let viewport = self.layout_viewport();
+        let tab = self.window.tabs.active();
+        let change = match (self.window.contents.get(&tab.id()), tab.tree()) {
+            (Some(content), Some(dom)) => {
+                let styled = content.style(dom);
+                let mut layout_root = layout_tree_with_images(
+                    &styled,
+                    viewport,
+                    &*self.text_metrics,
+                    self.images.sizes(),
+                    self.window.link_handler.base_url(),
+                );
+                pin_fixed_boxes(&mut layout_root, 0.0, tab.scroll.offset_y);
+                self.window.link_handler.update_hover(&layout_root, x, y + tab.scroll.offset_y)
+            }
+            _ => None,
+        };
+        
+        if let Some(change) = change {
+            crash::enter_phase(PipelinePhase::Event);
+            let js_context = &mut self.window.tabs.active_mut().js_context;
+            if let Some(href) = change.left {
+                let _ = js_context.dispatch_event(EventType::MouseOut, href);
+            }
+            if let Some(href) = change.entered {
+                let _ = js_context.dispatch_event(EventType::MouseOver, href);
+            }
+        }
+    }
+
+    /// Follow a link under the pointer, if any
+    fn handle_click(&mut self, x: f32, y: f32) {
+        // The print preview covers the whole window
+        if self.window.ui.print_preview.is_open() {
+            return;
+        }
+        
+        let styled = styled_active_page(&self.window.tabs, &self.window.contents);
+        let page = styled.as_ref().map(inspected_page);
+        match self.window.ui.devtools.handle_click(x, y, &mut self.devtools, page) {
+            DevToolsAction::Ignored => {}
+            action => {
+                self.handle_devtools_action(action);
+                return;
+            }
+        }
+        
+        // Picking an element to inspect
+        if self.window.ui.devtools.is_picking() && !self.window.ui.contains_point(x, y) {
+            if let Some(element) = self.element_at(x, y) {
+                self.devtools.dom_inspector.reveal(element.path.clone());
+                self.devtools.set_active_tab(DevToolsTab::DomInspector);
+                self.set_inspected_element(&element.path);
+            }
+            self.window.ui.devtools.set_picking(false);
+            return;
+        }
+        
+        // The suggestion dropdown overlaps page content
+        let suggestion = self.window.ui.address_bar.suggestion_at(x, y).map(|s| s.url.clone());
+        if let Some(url) = suggestion {
+            self.window.ui.address_bar.set_focused(false);
+            self.navigate_with(url.to_string(), VisitTransition::Typed);
+            return;
+        }
+        
+        // The security indicator, and the site information panel it opens over the page
+        let site_info = self.site_info();
+        if self.window.ui.site_info.contains_point(x, y, site_info.as_ref()) {
+            if let Some(SiteInfoAction::ResetPermissions) = self.window.ui.site_info.handle_click(x, y, site_info.as_ref()) {
+                self.reset_site_permissions();
+            }
+            return;
+        }
+        self.window.ui.site_info.close();
+        
+        // The bookmarks bar, including an open folder's dropdown over the page
+        if self.window.ui.is_bookmarks_bar_visible() && self.window.ui.bookmarks_bar.contains_point(x, y, &self.bookmarks) {
+            if let Some(BookmarksBarHit::Bookmark(id)) = self.window.ui.bookmarks_bar.handle_click(x, y, &self.bookmarks) {
+                if let Some(url) = self.bookmarks.get(id).map(|b| b.url.to_string()) {
+                    self.navigate_with(url, VisitTransition::Bookmark);
+                }
+            }
+            return;
+        }
+        self.window.ui.bookmarks_bar.close_folder();
+        
+        if self.window.ui.auth_bar.contains_point(x, y) {
+            let action = self.window.ui.auth_bar.handle_click(x, y);
+            self.handle_auth_action(action);
+            return;
+        }
+        
+        let prompt = self.window.tabs.active().url().and_then(|url| self.permissions.pending_for(url));
+        if self.window.ui.permission_bar.contains_point(x, y, prompt) {
+            if let Some(answer) = self.window.ui.permission_bar.handle_click(x, y, prompt) {
+                self.answer_permission_prompt(answer);
+            }
+            return;
+        }
+        
+        if self.window.ui.star_button.contains_point(x, y) {
+            self.toggle_bookmark();
+            return;
+        }
+        
+        let tab = self.window.tabs.active();
+        if self.window.ui.reader_button.contains_point(x, y, tab.reader_available, tab.reader_mode) {
+            self.toggle_reader_mode();
+            return;
+        }
+        
+        let focus_address_bar = self.window.ui.address_bar.contains_point(x, y);
+        if focus_address_bar != self.window.ui.address_bar.is_focused() {
+            self.window.ui.address_bar.set_focused(focus_address_bar);
+        }
+        
+        if self.window.ui.contains_point(x, y) {
+            if let Some(command) = self.window.ui.navigation.click(x, y) {
+                self.handle_nav_command(command);
+                return;
+            }
+            if self.window.ui.tab_strip.handle_click(x, y, &mut self.window.tabs).is_some() {
+                self.show_active_tab();
+            }
+            return;
+        }
+        
+        // A crash in the page's part of the click leaves the crash page
+        let Some(target) = self.guard_active_page(PipelinePhase::Input, |app| app.click_page(x, y)) else {
+            return;
+        };
+        if let Some(url) = target {
+            if self.is_retry_link(&url) {
+                self.reload(CacheMode::Reload);
+            } else {
+                self.navigate(url.to_string());
+            }
+        } else if self.guard_active_page(PipelinePhase::Input, |app| app.focus_editable(x, y)) == Some(false) {
+            let offset_y = self.window.tabs.active().scroll.offset_y;
+            self.window.selection.start(x, y + offset_y);
+        }
+    }
+
+    /// Find the link a click on the active page lands on and fire `click`
+    /// at it, returning where it leads
+    fn click_page(&mut self, x: f32, y: f32) -> Option<url::Url> {
+        let viewport = self.layout_viewport();
+        let tab = self.window.tabs.active();
+        let target = self.window.contents.get(&tab.id()).zip(tab.tree()).and_then(|(content, dom)| {
+            let styled = content.style(dom);
+            let mut layout_root = layout_tree_with_images(
+                &styled,
+                viewport,
+                &*self.text_metrics,
+                self.images.sizes(),
+                self.window.link_handler.base_url(),
+            );
+            pin_fixed_boxes(&mut layout_root, 0.0, tab.scroll.offset_y);
+            self.window.link_handler.activate(&layout_root, x, y + tab.scroll.offset_y)
+        })?;
+        crash::enter_phase(PipelinePhase::Event);
+        let _ = self.window.tabs.active_mut().js_context.dispatch_event(EventType::Click, target.to_string());
+        // Click handlers run with user activation
+        self.service_script_requests(true);
+        Some(target)
+    }
+
+    /// Put the caret where a click in the page lands if that is editable,
+    /// blurring any region the active tab was editing otherwise
+    fn focus_editable(&mut self, x: f32, y: f32) -> bool {
+        let tab = self.window.tabs.active();
+        let offset_y = tab.scroll.offset_y;
+        let editor = self
+            .with_active_layout(|root| {
+                let document = tab.document.as_ref()?;
+                Editor::at(document, position_at(document, root, x, y + offset_y)?)
+            })
+            .flatten();
+        let focused = editor.is_some();
+        self.window.tabs.active_mut().editor = editor;
+        focused
+    }
+
+    /// Make an edit in the active tab's focused editable region, then lay
+    /// the page out again and fire `input` at the region
+    ///
+    /// Returns whether anything changed.
+    fn edit(&mut self, f: impl FnOnce(&mut Editor, &mut Document) -> Option<InputEvent>) -> bool {
+        let tab = self.window.tabs.active_mut();
+        let (Some(editor), Some(document)) = (tab.editor.as_mut(), tab.document.as_mut()) else {
+            return false;
+        };
+        let Some(event) = f(editor, document) else {
+            return false;
+        };
+        let host_id = document.element_data(editor.host()).and_then(|e| e.id()).map(str::to_string);
+        document.free_detached();
+        let _ = tab.js_context.set_element_ids(document);
+        let _ = tab.js_context.set_console_document(document.tree());
+        for (_, world) in &mut tab.extension_worlds {
+            let _ = world.set_element_ids(document);
+        }
+        crash::enter_phase(PipelinePhase::Event);
+        if let Err(e) = tab.js_context.dispatch_input_event(host_id.as_deref(), &event) {
+            self.devtools.console.error(format!("input event error: {}", e));
+        }
+        self.restyle_active_page();
+        true
+    }
+
+    /// Report how a script run during page load ended and carry out what it
+    /// asked for, returning whether it changed the document
+    fn script_finished(&mut self, result: Result<JsValue, JsError>) -> bool {
+        match result {
+            Ok(result) => self.devtools.console.debug(format!("Script result: {:?}", result)),
+            Err(e) => {
+                if !self.report_dom_breakpoint() {
+                    let error_msg = format!("JavaScript error: {}", e);
+                    eprintln!("{}", error_msg);
+                    self.devtools.console.error(error_msg);
+                }
+            }
+        }
+        self.service_script_requests(false)
+    }
+
+    /// Run the extensions' content scripts for a point of the page's load,
+    /// each extension in its own world on the active tab's document
+    ///
+    /// Returns whether the scripts changed the document.
+    fn run_content_scripts(&mut self, url: &url::Url, run_at: RunAt) -> bool {
+        let scripts: Vec<(String, String)> = self
+            .extensions
+            .content_scripts(url, run_at)
+            .into_iter()
+            .map(|(extension, source)| (extension.to_string(), source.to_string()))
+            .collect();
+        for (extension, source) in scripts {
+            let tab = self.window.tabs.active_mut();
+            if !tab.extension_worlds.iter().any(|(id, _)| *id == extension) {
+                let Some(mut world) = self.extensions.content_world(&extension) else {
+                    continue;
+                };
+                if let Some(document) = tab.document.as_ref() {
+                    let _ = world.set_element_ids(document);
+                }
+                tab.extension_worlds.push((extension.clone(), world));
+            }
+            let Some((_, world)) = tab.extension_worlds.iter_mut().find(|(id, _)| *id == extension) else {
+                continue;
+            };
+            if let Err(e) = world.run_page_script(&source) {
+                self.devtools.console.error(format!("Extension {} content script error: {}", extension, e));
+            }
+        }
+        self.poll_extensions();
+        self.apply_dom_mutations()
+    }
+
+    /// Answer the calls the extensions' content scripts made and carry out
+    /// the `tabs` calls of their background scripts
+    fn poll_extensions(&mut self) {
+        // Calls from content scripts, in every tab's worlds
+        let mut settled = Vec::new();
+        for tab in self.window.tabs.tabs_mut() {
+            let (tab_id, page) = (tab.id(), tab.url().cloned());
+            for (extension, world) in &mut tab.extension_worlds {
+                let calls = match world.take_extension_calls() {
+                    Ok(calls) => calls,
+                    Err(e) => {
+                        self.devtools.console.error(format!("Extension {} error: {}", extension, e));
+                        continue;
+                    }
+                };
+                for call in calls {
+                    let page = page.clone().unwrap_or_else(|| url::Url::parse("about:blank").expect("valid URL"));
+                    let call_id = call.id;
+                    if let Some(result) = self.extensions.content_call(extension, tab_id, &page, call) {
+                        settled.push((tab_id, extension.clone(), call_id, result));
+                    }
+                }
+            }
+        }
+        for event in self.extensions.take_events() {
+            match event {
+                ExtensionEvent::Call { extension, call } => {
+                    if let Some(result) = self.extension_tabs_call(&extension, &call.method, &call.args, call.id) {
+                        self.extensions.settle(&extension, call.id, result);
+                    }
+                }
+                ExtensionEvent::Reply { extension, tab, call, result } => settled.push((tab, extension, call, result)),
+                ExtensionEvent::Error { extension, message } => {
+                    self.devtools.console.error(format!("Extension {} error: {}", extension, message));
+                }
+            }
+        }
+        for (tab, extension, call, result) in settled {
+            let Some(world) = self.extension_world(tab, &extension) else {
+                continue;
+            };
+            if let Err(e) = world.settle_extension_call(call, result.as_ref().map_err(String::as_str)) {
+                self.devtools.console.error(format!("Extension {} error: {}", extension, e));
+            }
+        }
+    }
+
+    /// An extension's content script world in a tab
+    fn extension_world(&mut self, tab: TabId, extension: &str) -> Option<&mut JsContext> {
+        let tab = self.window.tabs.tab_mut(tab)?;
+        tab.extension_worlds.iter_mut().find(|(id, _)| id == extension).map(|(_, world)| world)
+    }
+
+    /// Carry out a background script's `tabs` call
+    ///
+    /// Returns the result to settle the call with, or `None` when it is
+    /// answered later, as a message to a content script is.
+    fn extension_tabs_call(&mut self, extension: &str, method: &str, args: &serde_json::Value, call: u32) -> Option<Result<serde_json::Value, String>> {
+        let can_see_tabs = self.extensions.extension(extension).is_some_and(|e| e.has_permission("tabs"));
+        let describe = |index: usize, tab: &Tab, active: bool| {
+            let mut info = serde_json::json!({ "id": tab.id(), "index": index, "active": active });
+            // Where a tab is is only told to extensions allowed to know
+            if can_see_tabs {
+                info["url"] = tab.url().map(|url| url.as_str()).into();
+                info["title"] = tab.title.as_str().into();
+            }
+            info
+        };
+        let tab_id = args["tabId"].as_u64();
+        let no_tab = || Err(format!("No tab with id: {}", tab_id.unwrap_or_default()));
+        Some(match method {
+            "tabs.query" => {
+                let active = args["query"]["active"].as_bool();
+                let active_id = self.window.tabs.active_id();
+                let tabs = self.window.tabs.tabs().iter().enumerate();
+                let matching = tabs.filter(|(_, tab)| active.is_none_or(|active| active == (tab.id() == active_id)));
+                Ok(matching.map(|(index, tab)| describe(index, tab, tab.id() == active_id)).collect())
+            }
+            "tabs.get" => {
+                let tabs = self.window.tabs.tabs();
+                match tabs.iter().position(|tab| Some(tab.id()) == tab_id) {
+                    Some(index) => Ok(describe(index, &tabs[index], tabs[index].id() == self.window.tabs.active_id())),
+                    None => no_tab(),
+                }
+            }
+            "tabs.create" => {
+                let previous = self.window.tabs.active_id();
+                self.window.tabs.open_tab();
+                let url = args["properties"]["url"].as_str().unwrap_or("about:blank");
+                self.navigate(url.to_string());
+                // A tab opened in the background leaves the active one shown
+                if args["properties"]["active"].as_bool() == Some(false) {
+                    self.window.tabs.activate(previous);
+                }
+                self.show_active_tab();
+                let index = self.window.tabs.active_index();
+                Ok(describe(index, self.window.tabs.active(), true))
+            }
+            "tabs.update" => {
+                let id = tab_id.unwrap_or(self.window.tabs.active_id());
+                if self.window.tabs.tab(id).is_none() {
+                    return Some(no_tab());
+                }
+                let url = args["properties"]["url"].as_str();
+                if args["properties"]["active"].as_bool() == Some(true) || url.is_some() {
+                    let previous = self.window.tabs.active_id();
+                    self.window.tabs.activate(id);
+                    if let Some(url) = url {
+                        self.navigate(url.to_string());
+                    }
+                    if args["properties"]["active"].as_bool() != Some(true) {
+                        self.window.tabs.activate(previous);
+                    }
+                    self.show_active_tab();
+                }
+                let tabs = self.window.tabs.tabs();
+                let index = tabs.iter().position(|tab| tab.id() == id)?;
+                Ok(describe(index, &tabs[index], id == self.window.tabs.active_id()))
+            }
+            "tabs.remove" => {
+                let ids: Vec<TabId> = match &args["tabIds"] {
+                    serde_json::Value::Array(ids) => ids.iter().filter_map(serde_json::Value::as_u64).collect(),
+                    id => id.as_u64().into_iter().collect(),
+                };
+                for id in ids {
+                    self.window.tabs.close_tab(id);
+                }
+                self.show_active_tab();
+                Ok(serde_json::Value::Null)
+            }
+            "tabs.sendMessage" => {
+                let sender = serde_json::json!({ "id": extension });
+                let message = args["message"].clone();
+                let world = tab_id.and_then(|tab| self.extension_world(tab, extension));
+                let Some(world) = world else {
+                    return Some(Err("Could not establish connection. Receiving end does not exist.".to_string()));
+                };
+                // The world's reply comes back as one of its calls
+                return match world.dispatch_extension_message(&message, &sender, call) {
+                    Ok(()) => None,
+                    Err(e) => Some(Err(e.to_string())),
+                };
+            }
+            method => Err(format!("{} is not supported", method)),
+        })
+    }
+
+    /// Apply permission, clipboard, `window.open`, media, element and scroll calls made by the active tab's scripts
+    ///
+    /// Returns whether the scripts changed the document.
+    fn service_script_requests(&mut self, user_activation: bool) -> bool {
+        self.report_dom_breakpoint();
+        self.service_permission_requests();
+        // The clipboard follows the page's clipboard permissions
+        if let Some(url) = self.window.tabs.active().url() {
+            self.clipboard.set_read_permission(self.permissions.state(url, PermissionName::ClipboardRead));
+            self.clipboard.set_write_permission(self.permissions.state(url, PermissionName::ClipboardWrite));
+        }
+        let js_context = &mut self.window.tabs.active_mut().js_context;
+        if let Err(e) = self.clipboard.service_js_requests(js_context, user_activation) {
+            self.devtools.console.error(format!("Clipboard error: {}", e));
+        }
+        
+        match self.window.tabs.active_mut().js_context.take_window_open_requests() {
+            Ok(requests) => {
+                for request in requests {
+                    self.queue_script_open(request, user_activation);
+                }
+            }
+            Err(e) => self.devtools.console.error(format!("window.open error: {}", e)),
+        }
+        
+        self.service_media_requests(Instant::now());
+        
+        match self.window.tabs.active_mut().js_context.take_edit_commands() {
+            Ok(commands) => {
+                for command in commands {
+                    self.edit(|editor, document| editor.exec_command(document, &command.command, command.value.as_deref()));
+                }
+            }
+            Err(e) => self.devtools.console.error(format!("execCommand error: {}", e)),
+        }
+        
+        // Elements scrolled into view may have just changed
+        let changed = self.apply_dom_mutations();
+        
+        let requests = match self.window.tabs.active_mut().js_context.take_scroll_requests() {
+            Ok(requests) => requests,
+            Err(e) => {
+                self.devtools.console.error(format!("Scroll error: {}", e));
+                return changed;
+            }
+        };
+        for request in requests {
+            match request {
+                ScrollRequest::IntoView { id, behavior, block } => {
+                    if let Some(Some(rect)) = self.with_active_layout(|root| element_rect_by_id(root, &id)) {
+                        self.window.tabs.active_mut().scroll.scroll_rect_into_view(rect, block, behavior);
+                    }
+                }
+                ScrollRequest::To { x, y, behavior } => {
+                    self.window.tabs.active_mut().scroll.scroll_to_with_behavior(x, y, behavior);
+                }
+                ScrollRequest::By { x, y, behavior } => {
+                    let scroll = &mut self.window.tabs.active_mut().scroll;
+                    let (target_x, target_y) = scroll.target();
+                    scroll.scroll_to_with_behavior(target_x + x, target_y + y, behavior);
+                }
+            }
+        }
+        
+        self.exchange_messages();
+        // Changes made by observer callbacks are reported at the next checkpoint
+        let changed = if self.deliver_mutation_records() { self.apply_dom_mutations() || changed } else { changed };
+        self.deliver_performance_entries();
+        changed
+    }
+
+    /// Settle the active page's permission requests that are decided; the rest wait on a prompt
+    fn service_permission_requests(&mut self) {
+        let tab = self.window.tabs.active_mut();
+        let requests = match tab.js_context.take_permission_requests() {
+            Ok(requests) => requests,
+            Err(e) => {
+                self.devtools.console.error(format!("Permissions error: {}", e));
+                return;
+            }
+        };
+        for request in requests {
+            let outcome = match tab.url() {
+                Some(url) => self.permissions.request(url, request.name),
+                None => RequestOutcome::Decided(PermissionState::Denied),
+            };
+            match outcome {
+                RequestOutcome::Decided(state) => {
+                    let _ = tab.js_context.settle_permission_request(request.id, state);
+                }
+                RequestOutcome::Pending(prompt) => {
+                    self.permission_waiters.entry(prompt).or_default().push((tab.id(), request.id));
+                }
+            }
+        }
+    }
+
+    /// Answer the prompt shown for the active page, settling every request waiting on it
+    fn answer_permission_prompt(&mut self, answer: PromptAnswer) {
+        let url = self.window.tabs.active().url();
+        let Some(id) = url.and_then(|url| self.permissions.pending_for(url)).map(|prompt| prompt.id) else {
+            return;
+        };
+        let Some((prompt, state)) = self.permissions.answer(id, answer) else {
+            return;
+        };
+        let waiters = self.permission_waiters.remove(&id).unwrap_or_default();
+        let tabs = self.window.tabs.tabs_mut().iter_mut();
+        for tab in tabs.chain(self.windows.values_mut().flat_map(|window| window.tabs.tabs_mut().iter_mut())) {
+            let Some(url) = tab.url().cloned() else {
+                continue;
+            };
+            let tab_id = tab.id();
+            for (_, request) in waiters.iter().filter(|(waiter, _)| *waiter == tab_id) {
+                let _ = tab.js_context.settle_permission_request(*request, state);
+            }
+            // Every page of the origin sees the new decision
+            if url.origin().ascii_serialization() == prompt.origin {
+                let _ = tab.js_context.set_permission_states(&self.permissions.states(&url));
+            }
+        }
+        self.save_permissions();
+        // Granting lets waiting calls, such as clipboard reads, go on
+        self.service_script_requests(false);
+    }
+
+    /// Forget the permission decisions for the active page's origin
+    fn reset_site_permissions(&mut self) {
+        let Some(url) = self.window.tabs.active().url().cloned() else {
+            return;
+        };
+        self.permissions.reset(&url);
+        let tabs = self.window.tabs.tabs_mut().iter_mut();
+        for tab in tabs.chain(self.windows.values_mut().flat_map(|window| window.tabs.tabs_mut().iter_mut())) {
+            let same_origin = tab.url().is_some_and(|page| page.origin() == url.origin());
+            if same_origin {
+                let states = self.permissions.states(&url);
+                let _ = tab.js_context.set_permission_states(&states);
+            }
+        }
+        self.devtools.console.info(format!("Reset permissions for {}", url.origin().ascii_serialization()));
+        self.save_permissions();
+    }
+
+    /// Keep the permission decisions next to the preferences
+    fn save_permissions(&mut self) {
+        if let Some(path) = self.preferences_path.as_deref().map(|p| p.with_file_name("permissions.json")) {
+            if let Err(e) = self.permissions.save(&path) {
+                self.devtools.console.error(format!("Failed to save permissions: {}", e));
+            }
+        }
+    }
+
+    /// What the site information panel shows about the active page
+    fn site_info(&self) -> Option<SiteInfo> {
+        let tab = self.window.tabs.active();
+        let url = tab.url()?;
+        let host = url.host_str().unwrap_or("");
+        let certificates = self.resource_loader.certificates();
+        let store = certificates.lock().ok()?;
+        let state = SecurityState::for_page(url, !tab.insecure_content.is_empty(), store.is_overridden(host));
+        let origin = url.origin();
+        let states = self.permissions.states(url);
+        Some(SiteInfo {
+            origin: if origin.is_tuple() { origin.ascii_serialization() } else { url.to_string() },
+            state,
+            certificate: store.get(host).filter(|_| state.has_certificate()).cloned(),
+            insecure_content: tab.insecure_content.iter().map(|url| url.to_string()).collect(),
+            permissions: PermissionName::ALL.iter().map(|&name| (name, states[&name])).collect(),
+        })
+    }
+
+    /// Call back the active page's MutationObservers, returning whether any was called
+    fn deliver_mutation_records(&mut self) -> bool {
+        match self.window.tabs.active_mut().js_context.deliver_mutation_records() {
+            Ok(delivered) => delivered > 0,
+            Err(e) => {
+                self.devtools.console.error(format!("MutationObserver error: {}", e));
+                false
+            }
+        }
+    }
+
+    /// Call back the active page's PerformanceObservers with new entries
+    fn deliver_performance_entries(&mut self) {
+        if let Err(e) = self.window.tabs.active_mut().js_context.deliver_performance_entries() {
+            self.devtools.console.error(format!("PerformanceObserver error: {}", e));
+        }
+    }
+
+    /// Record the active page's paint timing and largest contentful paint as drawn
+    fn record_paint_timing(&mut self) {
+        let viewport = self.visible_page_rect();
+        let tab = self.window.tabs.active_mut();
+        let Some(content) = self.window.contents.get_mut(&tab.id()) else {
+            return;
+        };
+        let performance = tab.js_context.performance_mut();
+        let mut recorded = false;
+        if !content.painted {
+            content.painted = true;
+            performance.add_paint("first-paint");
+            if !content.contentful.is_empty() {
+                performance.add_paint("first-contentful-paint");
+            }
+            recorded = true;
+        }
+        // Input ends the search for the largest contentful paint
+        if let Some((paint, size)) = largest_contentful_paint(&content.contentful, viewport) {
+            if content.last_input.is_none() {
+                let url = if paint.image { paint.content.clone() } else { String::new() };
+                recorded |= performance.add_largest_contentful_paint(size as f64, paint.label(), url);
+            }
+        }
+        if recorded {
+            content.vitals = WebVitals::from_performance(performance);
+            self.deliver_performance_entries();
+        }
+    }
+
+    /// Record how far the active page's content moved from `before` as a layout shift
+    fn record_layout_shift(&mut self, before: &[ContentfulPaint]) {
+        let viewport = self.visible_page_rect();
+        let tab = self.window.tabs.active_mut();
+        // Content moving before it was ever drawn shifts nothing the user saw
+        let Some(content) = self.window.contents.get_mut(&tab.id()).filter(|content| content.painted) else {
+            return;
+        };
+        let score = layout_shift_score(before, &content.contentful, viewport);
+        if score <= 0.0 {
+            return;
+        }
+        let since_input = content.last_input.map(|at| at.elapsed().as_secs_f64() * 1000.0);
+        let had_recent_input = since_input.is_some_and(|ms| ms < RECENT_INPUT);
+        let performance = tab.js_context.performance_mut();
+        performance.add_layout_shift(score, had_recent_input);
+        content.vitals = WebVitals::from_performance(performance);
+        self.deliver_performance_entries();
+    }
+
+    /// Note the user interacting with the active page
+    fn note_user_input(&mut self) {
+        if let Some(content) = self.window.contents.get_mut(&self.window.tabs.active_id()) {
+            content.last_input = Some(Instant::now());
+        }
+    }
+
+    /// The part of the active page in view, in page coordinates
+    fn visible_page_rect(&self) -> Rect {
+        let viewport = self.layout_viewport().content;
+        Rect { y: self.window.tabs.active().scroll.offset_y, ..viewport }
+    }
+
+    /// Make the element changes queued by the active tab's scripts to its document
+    ///
+    /// Returns whether the document changed; if it did the page is
+    /// restyled and DOM breakpoints follow the nodes they were set on.
+    fn apply_dom_mutations(&mut self) -> bool {
+        let tab = self.window.tabs.active_mut();
+        let mut mutations = match tab.js_context.take_dom_mutations() {
+            Ok(mutations) => mutations,
+            Err(e) => {
+                self.devtools.console.error(format!("DOM error: {}", e));
+                return false;
+            }
+        };
+        // Content scripts change the same document from their own worlds
+        for (extension, world) in &mut tab.extension_worlds {
+            match world.take_dom_mutations() {
+                Ok(changes) => mutations.extend(changes),
+                Err(e) => self.devtools.console.error(format!("Extension {} DOM error: {}", extension, e)),
+            }
+        }
+        let Some(document) = tab.document.as_mut() else {
+            return false;
+        };
+        let mut changed = false;
+        for mutation in &mutations {
+            if let Err(e) = tab.js_context.record_dom_mutation(document, mutation) {
+                self.devtools.console.error(format!("MutationObserver error: {}", e));
+            }
+            // DevTools keeps breakpoints by where the node was
+            let removed = match mutation {
+                DomMutation::Remove { node } => document.path(*node),
+                _ => None,
+            };
+            if mutation.apply(document).is_err() {
+                continue;
+            }
+            if let Some(path) = removed {
+                self.devtools.dom_breakpoints.node_removed(&path);
+            }
+            changed = true;
+        }
+        if changed {
+            // A script may have removed the text being edited, or its host
+            if tab.editor.as_ref().is_some_and(|editor| {
+                editing_host(document, editor.caret().node) != Some(editor.host()) || !document.is_connected(editor.host())
+            }) {
+                tab.editor = None;
+            }
+            document.free_detached();
+            let _ = tab.js_context.set_element_ids(document);
+            let _ = tab.js_context.set_console_document(document.tree());
+            for (_, world) in &mut tab.extension_worlds {
+                let _ = world.set_element_ids(document);
+            }
+            self.sync_dom_breakpoints();
+            let before = self.window.contents.get(&self.window.tabs.active_id()).map(|c| c.contentful.clone());
+            self.restyle_active_page();
+            self.record_layout_shift(&before.unwrap_or_default());
+        }
+        changed
+    }
+
+    /// Watch the active tab's document for changes DOM breakpoints stop on
+    fn sync_dom_breakpoints(&mut self) {
+        let tab = self.window.tabs.active_mut();
+        let watches =
+            tab.document.as_ref().map(|document| self.devtools.dom_breakpoints.watches(document)).unwrap_or_default();
+        if let Err(e) = tab.js_context.set_mutation_watches(&watches) {
+            self.devtools.console.error(format!("Cannot set DOM breakpoints: {}", e));
+        }
+    }
+
+    /// Show the node a DOM breakpoint stopped the active tab's script at
+    ///
+    /// Returns whether a script was stopped.
+    fn report_dom_breakpoint(&mut self) -> bool {
+        let pause = match self.window.tabs.active_mut().js_context.take_dom_breakpoint_pause() {
+            Ok(pause) => pause,
+            Err(e) => {
+                self.devtools.console.error(format!("DOM breakpoint error: {}", e));
+                return false;
+            }
+        };
+        let Some(pause) = pause else {
+            return false;
+        };
+        let breakpoint = self.devtools.dom_breakpoints.get(pause.breakpoint).cloned();
+        let kind = breakpoint.as_ref().map_or("DOM", |breakpoint| breakpoint.kind.label());
+        self.devtools.console.warn(format!(
+            "Script stopped on {} breakpoint before #{}.{}",
+            kind, pause.element_id, pause.call
+        ));
+        if let Some(breakpoint) = breakpoint {
+            self.devtools.dom_inspector.reveal(breakpoint.path.clone());
+            self.set_inspected_element(&breakpoint.path);
+        }
+        self.devtools.set_active_tab(DevToolsTab::DomInspector);
+        true
+    }
+
+    /// Route BroadcastChannel and `postMessage` traffic between the pages of every tab
+    ///
+    /// Handlers may post in turn, so a few rounds are run until the bus is
+    /// quiet. Documents of closed tabs are dropped from the bus.
+    fn exchange_messages(&mut self) {
+        const MAX_ROUNDS: usize = 4;
+        let bus = self.message_bus.clone();
+        let mut errors = Vec::new();
+        let mut live = HashSet::new();
+        
+        for _ in 0..MAX_ROUNDS {
+            let mut tabs: Vec<&mut Tab> = self.window.tabs.tabs_mut().iter_mut().collect();
+            for window in self.windows.values_mut() {
+                tabs.extend(window.tabs.tabs_mut().iter_mut());
+            }
+            
+            for tab in tabs.iter_mut() {
+                let Some(document) = tab.document_id else { continue };
+                live.insert(document);
+                if let Err(e) = tab.js_context.send_messages(&bus, document) {
+                    errors.push(e);
+                }
+            }
+            let mut delivered = 0;
+            for tab in tabs.iter_mut() {
+                let Some(document) = tab.document_id else { continue };
+                match tab.js_context.receive_messages(&bus, document) {
+                    Ok(count) => delivered += count,
+                    Err(e) => errors.push(e),
+                }
+            }
+            if delivered == 0 {
+                break;
+            }
+        }
+        
+        for document in bus.documents() {
+            if !live.contains(&document) {
+                bus.unregister_document(document);
+            }
+        }
+        for e in errors {
+            self.devtools.console.error(format!("Messaging error: {}", e));
+        }
+    }
+
+    /// Resolve a `window.open` call and queue it unless it is a blocked popup
+    ///
+    /// Pages may replace themselves at any time, but new tabs and windows
+    /// need user activation.
+    fn queue_script_open(&mut self, request: WindowOpenRequest, user_activation: bool) {
+        let url = if request.url.is_empty() {
+            url::Url::parse("about:blank")
+        } else {
+            match self.window.tabs.active().url() {
+                Some(base) => base.join(&request.url),
+                None => url::Url::parse(&request.url),
+            }
+        };
+        let Ok(url) = url else {
+            self.devtools.console.warn(format!("window.open: invalid URL {}", request.url));
+            return;
+        };
+        
+        if request.disposition != OpenDisposition::CurrentTab && !user_activation {
+            self.devtools.console.warn(format!("Blocked popup: {}", url));
+            return;
+        }
+        self.script_opens.push((url, request.disposition));
+    }
+
+    /// Bookmark the current page, or remove its bookmark
+    fn toggle_bookmark(&mut self) {
+        let tab = self.window.tabs.active();
+        let Some(url) = tab.url().cloned() else {
+            return;
+        };
+        
+        if self.bookmarks.remove(&url) {
+            self.devtools.console.info(format!("Removed bookmark: {}", url));
+        } else {
+            self.bookmarks.add(url.clone(), tab.title.clone());
+            self.devtools.console.info(format!("Bookmarked: {}", url));
+        }
+        self.save_bookmarks();
+    }
+
+    /// Update the loading indicators and navigation button states
+    fn set_loading(&mut self, loading: bool) {
+        self.window.loading = loading;
+        self.window.ui.address_bar.set_loading(loading);
+        self.window.ui.navigation.set_loading(loading);
+        let history = &self.window.tabs.active().history;
+        self.window.ui.navigation.set_history_state(history.can_go_back(), history.can_go_forward());
+    }
+
+    /// Run a navigation button or shortcut
+    fn handle_nav_command(&mut self, command: NavCommand) {
+        match command {
+            NavCommand::Back => {
+                if self.window.tabs.active().history.can_go_back() {
+                    self.go_back();
+                }
+            }
+            NavCommand::Forward => {
+                if self.window.tabs.active().history.can_go_forward() {
+                    self.go_forward();
+                }
+            }
+            NavCommand::Reload => self.reload(CacheMode::Revalidate),
+            NavCommand::HardReload => self.reload(CacheMode::Reload),
+            NavCommand::Stop => self.stop(),
+        }
+        self.set_loading(self.window.loading);
+    }
+
+    /// Reload the active tab, keeping its scroll position
+    ///
+    /// `Revalidate` checks cached copies with the server; `Reload`
+    /// bypasses the cache.
+    fn reload(&mut self, cache_mode: CacheMode) {
+        let Some(url) = self.window.tabs.active().url().cloned() else {
+            return;
+        };
+        self.devtools.console.info(format!("Reloading ({}): {}", cache_mode.as_str(), url));
+        let (x, y) = {
+            let scroll = &self.window.tabs.active().scroll;
+            (scroll.offset_x, scroll.offset_y)
+        };
+        
+        self.set_loading(true);
+        let req_idx = self.devtools.network.log_request(url.clone(), "GET".to_string(), NetworkRequestType::Document);
+        self.assign_content_process(&url);
+        match self.load_page(&url, Some(req_idx), cache_mode) {
+            Ok(content) => {
+                let failed = content.error_page.is_some();
+                self.window.contents.insert(self.window.tabs.active_id(), content);
+                self.window.tabs.active_mut().scroll.scroll_to(x, y);
+                if !failed {
+                    self.record_visit(&url, VisitTransition::Reload);
+                }
+                if self.window.ui.find_bar.is_open() {
+                    self.run_find();
+                }
+            }
+            Err(e) => self.devtools.console.warn(format!("Reload failed: {}", e)),
+        }
+        self.set_loading(false);
+    }
+
+    /// Abort the page load in flight
+    fn stop(&mut self) {
+        if let Some(cancel) = self.load_cancel.take() {
+            cancel.cancel();
+            self.devtools.console.info("Stopped loading".to_string());
+        }
+        // A document fetched in the background reports the stop when its fetch ends
+        if let Some(load) = &self.window.pending_load {
+            load.cancel.cancel();
+            self.devtools.console.info("Stopped loading".to_string());
+        }
+        self.window.ui.status_bar.progress_mut().finish();
+        self.set_loading(false);
+    }
+
+    /// Add a loaded page to the global history
+    fn record_visit(&mut self, url: &url::Url, transition: VisitTransition) {
+        if !matches!(url.scheme(), "http" | "https" | "file") {
+            return;
+        }
+        self.history.record_visit(url, transition);
+        self.history.set_title(url, &self.window.tabs.active().title);
+        self.save_history();
+    }
+
+    /// Remove history visits within a time range
+    fn clear_history(&mut self, range: TimeRange) {
+        let removed = self.history.clear_range(range);
+        self.devtools.console.info(format!("Cleared {} history entries", removed));
+        self.save_history();
+    }
+
+    /// Persist history to local storage
+    fn save_history(&mut self) {
+        if let Err(e) = self.history.save(self.storage.local_storage()) {
+            self.devtools.console.error(format!("Failed to save history: {}", e));
+        }
+    }
+
+    /// Persist bookmarks to local storage
+    fn save_bookmarks(&mut self) {
+        if let Err(e) = self.bookmarks.save(self.storage.local_storage()) {
+            self.devtools.console.error(format!("Failed to save bookmarks: {}", e));
+        }
+    }
+
+    /// Is the active tab's page bookmarked
+    fn is_bookmarked(&self) -> bool {
+        self.window.tabs.active().url().is_some_and(|url| self.bookmarks.is_bookmarked(url))
+    }
+
+    /// Remember the active tab's scroll offset in its current history entry
+    fn save_scroll_position(&mut self) {
+        let tab = self.window.tabs.active_mut();
+        let (x, y) = (tab.scroll.offset_x, tab.scroll.offset_y);
+        tab.history.save_scroll_position(x, y);
+    }
+
+    /// Scroll the active tab to the element a `#fragment` names
+    fn scroll_to_fragment(&mut self, fragment: &str, behavior: ScrollBehavior) {
+        match self.with_active_layout(|root| fragment_target(root, fragment)) {
+            Some(Some(rect)) => {
+                self.window.tabs.active_mut().scroll.scroll_rect_into_view(rect, ScrollAlign::Start, behavior);
+            }
+            _ => self.devtools.console.warn(format!("No element for fragment #{}", fragment)),
+        }
+    }
+
+    /// Mouse wheel or touchpad scroll over the page
+    fn handle_wheel(&mut self, delta: MouseScrollDelta, phase: TouchPhase) {
+        let (x, y) = self.window.ui.input_handler.mouse_position();
+        if self.window.ui.devtools.contains_point(x, y) {
+            let dy = match delta {
+                MouseScrollDelta::LineDelta(_, y) => -y * WHEEL_LINE_HEIGHT,
+                MouseScrollDelta::PixelDelta(position) => -position.to_logical::<f32>(self.window.scale_factor).y,
+            };
+            let styled = styled_active_page(&self.window.tabs, &self.window.contents);
+            let page = styled.as_ref().map(inspected_page);
+            self.window.ui.devtools.scroll_by(x, dy, &self.devtools, page);
+            return;
+        }
+        if self.scroll_box_at(x, y, delta) {
+            return;
+        }
+        let scroll = &mut self.window.tabs.active_mut().scroll;
+        match delta {
+            MouseScrollDelta::LineDelta(x, y) => {
+                scroll.wheel(-x * WHEEL_LINE_HEIGHT, -y * WHEEL_LINE_HEIGHT);
+            }
+            MouseScrollDelta::PixelDelta(position) => {
+                let position = position.to_logical::<f32>(self.window.scale_factor);
+                let (dx, dy) = (-position.x, -position.y);
+                match phase {
+                    TouchPhase::Ended => {
+                        // Keep moving with the velocity of the last movement
+                        if let Some((last_x, last_y, at)) = self.window.last_touchpad_scroll.take() {
+                            let elapsed = at.elapsed().as_secs_f32().max(1.0 / 120.0);
+                            if elapsed < 0.1 {
+                                scroll.fling(last_x / elapsed, last_y / elapsed);
+                            }
+                        }
+                    }
+                    _ => {
+                        scroll.touchpad_scroll(dx, dy);
+                        self.window.last_touchpad_scroll = Some((dx, dy, Instant::now()));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Is this the link of the active tab's error page back to the URL that failed
+    fn is_retry_link(&self, url: &url::Url) -> bool {
+        let tab = self.window.tabs.active();
+        let error_page = self.window.contents.get(&tab.id()).and_then(|content| content.error_page.as_ref());
+        error_page.is_some_and(|page| page.url == *url)
+    }
+
+    /// Scroll the innermost scrollable box under the pointer, if one can move
+    ///
+    /// Boxes scroll by the whole delta at once rather than smoothly, then
+    /// the page is restyled to paint them at their new offsets.
+    fn scroll_box_at(&mut self, x: f32, y: f32, delta: MouseScrollDelta) -> bool {
+        let (dx, dy) = match delta {
+            MouseScrollDelta::LineDelta(x, y) => (-x * WHEEL_LINE_HEIGHT, -y * WHEEL_LINE_HEIGHT),
+            MouseScrollDelta::PixelDelta(position) => {
+                let position = position.to_logical::<f32>(self.window.scale_factor);
+                (-position.x, -position.y)
+            }
+        };
+        let tab = self.window.tabs.active();
+        let page_y = y + tab.scroll.offset_y;
+        let Some(content) = self.window.contents.get_mut(&tab.id()) else {
+            return false;
+        };
+        if !content.box_scrolls.scroll_at(&content.scroll_containers, x, page_y, dx, dy) {
+            return false;
+        }
+        self.restyle_active_page();
+        true
+    }
+
+    /// Run a function over the active tab's layout tree
+    ///
+    /// Fixed boxes are where the page's scroll offset shows them, so points
+    /// in the page hit them.
+    fn with_active_layout<R>(&self, f: impl FnOnce(&LayoutBox) -> R) -> Option<R> {
+        let viewport = self.layout_viewport();
+        let tab = self.window.tabs.active();
+        let content = self.window.contents.get(&tab.id())?;
+        let styled = content.style(tab.tree()?);
+        let mut layout_root = layout_tree_with_images(
+            &styled,
+            viewport,
+            &*self.text_metrics,
+            self.images.sizes(),
+            self.window.link_handler.base_url(),
+        );
+        pin_fixed_boxes(&mut layout_root, 0.0, tab.scroll.offset_y);
+        Some(f(&layout_root))
+    }
+
+    /// Advance the active page's CSS animations to a frame begun at `frame_time`
+    ///
+    /// Opacity and transform go straight to the compositor layers of
+    /// elements that have them; anything else restyles the page.
+    fn tick_animations(&mut self, frame_time: Instant) {
+        let tab_id = self.window.tabs.active_id();
+        let Some(content) = self.window.contents.get_mut(&tab_id) else {
+            return;
+        };
+        // Scroll-driven animations follow the offset scrolling has just reached
+        let scroll = &self.window.tabs.active().scroll;
+        content.animations.set_scroll_progress(scroll.scroll_percentage_x(), scroll.scroll_percentage_y());
+        content.animations.set_playback_rate(self.devtools.layers.animation_rate());
+        if !content.animations.has_active_animations() {
+            return;
+        }
+        let animated = content.animations.sample(frame_time);
+        let events = content.animations.take_events();
+        let change = animated.change_from(&content.animated);
+        content.animated = animated;
+        let composited =
+            change == Some(StyleChange::Composite) && content.animated.apply_to_layers(&mut content.layers);
+        if change.is_some() && !composited {
+            self.restyle_active_page();
+        }
+        self.fire_animation_events(&events);
+    }
+    
+    /// Fire animation and transition events at the active page's elements
+    fn fire_animation_events(&mut self, events: &[AnimationEvent]) {
+        if events.is_empty() {
+            return;
+        }
+        self.guard_active_page(PipelinePhase::Event, |app| {
+            let tab = app.window.tabs.active_mut();
+            for event in events {
+                let document = tab.document.as_ref().map(Document::tree);
+                let element = document.and_then(|document| document.descendant(&event.target));
+                let id = element.and_then(|element| element.element_data()).and_then(|data| data.id());
+                if let Err(e) = tab.js_context.dispatch_animation_event(id, event) {
+                    app.devtools.console.error(format!("JavaScript error: {}", e));
+                }
+            }
+            app.service_script_requests(false);
+        });
+    }
+
+    /// Does the active page have CSS animations to draw in the next frame
+    fn has_active_animations(&self) -> bool {
+        let content = self.window.contents.get(&self.window.tabs.active_id());
+        content.is_some_and(|content| content.animations.has_active_animations())
+    }
+
+    /// Run the active page's animation frame callbacks for a frame begun at `frame_time`
+    fn run_animation_frames(&mut self, frame_time: Instant) {
+        if !self.window.tabs.active_mut().js_context.has_animation_frames() {
+            return;
+        }
+        self.guard_active_page(PipelinePhase::Timer, |app| {
+            if let Err(e) = app.window.tabs.active_mut().js_context.run_animation_frames(frame_time) {
+                app.devtools.console.error(format!("JavaScript error: {}", e));
+            }
+            app.service_script_requests(false);
+        });
+    }
+
+    /// Work out the active page's IntersectionObserver entries from its layout
+    fn update_intersections(&mut self) {
+        if !self.window.tabs.active_mut().js_context.has_intersection_observers() {
+            return;
+        }
+        let offset_y = self.window.tabs.active().scroll.offset_y;
+        let rects = self.with_active_layout(|root| {
+            let mut rects = HashMap::new();
+            collect_element_rects(root, &mut rects);
+            rects
+        });
+        let rects = rects.unwrap_or_default();
+        let rect_of = |id: &str| {
+            let rect: &Rect = rects.get(id)?;
+            Some(ClientRect::new(rect.x, rect.y - offset_y, rect.width, rect.height))
+        };
+        let viewport = self.layout_viewport().content;
+        let viewport = ClientRect::new(0.0, 0.0, viewport.width, viewport.height);
+        match self.window.tabs.active_mut().js_context.update_intersections(viewport, rect_of) {
+            Ok(0) => {}
+            Ok(_) => {
+                self.service_script_requests(false);
+            }
+            Err(e) => self.devtools.console.error(format!("IntersectionObserver error: {}", e)),
+        }
+    }
+
+    /// Run idle work and the active page's idle callbacks until `deadline`
+    ///
+    /// Without a deadline only work and callbacks whose timeout passed run.
+    /// Returns whether the window needs drawing again.
+    fn run_idle_tasks(&mut self, deadline: Option<IdleDeadline>) -> bool {
+        let mut changed = false;
+        while let Some((work, _)) = self.idle_tasks.next(deadline.as_ref(), Instant::now()) {
+            match work {
+                IdleWork::EvictCache => {
+                    let evicted = self.resource_loader.evict_unused(CACHE_IDLE_EVICTION);
+                    if evicted > 0 {
+                        self.devtools.console.debug(format!("Evicted {} unused cache entries", evicted));
+                    }
+                }
+                IdleWork::DetectArticle(tab_id) => changed |= self.detect_article(tab_id),
+                IdleWork::LoadFonts(tab_id) => changed |= self.load_fonts(tab_id),
+                IdleWork::LoadMedia(tab_id) => changed |= self.load_media(tab_id),
+            }
+        }
+        if !self.window.tabs.active_mut().js_context.has_idle_callbacks() {
+            return changed;
+        }
+        // A deadline already passed runs only the callbacks whose timeout passed
+        let deadline = deadline.map_or_else(Instant::now, |deadline| deadline.deadline);
+        let ran = self.guard_active_page(PipelinePhase::Timer, |app| {
+            match app.window.tabs.active_mut().js_context.run_idle_callbacks(deadline) {
+                Ok(0) => false,
+                Ok(_) => app.service_script_requests(false),
+                Err(e) => {
+                    app.devtools.console.error(format!("Idle callback error: {}", e));
+                    false
+                }
+            }
+        });
+        // The crash page needs drawing
+        ran.unwrap_or(true) || changed
+    }
+
+    /// Find whether a tab's page has an article to offer reader mode for,
+    /// returning whether the reader button changed
+    fn detect_article(&mut self, tab_id: TabId) -> bool {
+        let tab = match self.window.tabs.tab_mut(tab_id) {
+            Some(tab) => Some(tab),
+            None => self.windows.values_mut().find_map(|window| window.tabs.tab_mut(tab_id)),
+        };
+        let Some(tab) = tab.filter(|tab| !tab.reader_mode) else {
+            return false;
+        };
+        let reader_available = tab.tree().is_some_and(|document| extract_article(document).is_some());
+        let changed = tab.reader_available != reader_available;
+        tab.reader_available = reader_available;
+        changed
+    }
+
+    /// Fetch the web fonts a tab's page is waiting for, from the first source of each that loads
+    ///
+    /// Returns whether the page was laid out again for fonts that arrived.
+    fn load_fonts(&mut self, tab_id: TabId) -> bool {
+        let Some(content) = self.window.contents.get_mut(&tab_id) else {
+            return false;
+        };
+        let mut tab = self.window.tabs.tab_mut(tab_id);
+        for index in content.fonts.loading() {
+            let mut data = None;
+            for url in &content.fonts.faces()[index].sources {
+                if let Some(tab) = tab.as_deref_mut() {
+                    note_subresource(tab, url, &mut self.devtools);
+                }
+                let network = &mut self.devtools.network;
+                let request = network.log_request(url.clone(), "GET".to_string(), NetworkRequestType::Font);
+                match self.resource_loader.load_traced(url, CacheMode::Default, None) {
+                    Ok((resource, transfer)) => {
+                        let content_type = Some(resource.content_type.clone()).filter(|t| !t.is_empty());
+                        let status = transfer.as_ref().map_or(200, |transfer| transfer.status);
+                        network.complete_request(request, status, resource.data.len(), content_type);
+                        network.record_cache_use(request, transfer.as_ref());
+                        data = Some(resource.data);
+                        break;
+                    }
+                    Err(e) => {
+                        network.complete_request(request, 0, 0, None);
+                        self.devtools.console.warn(format!("Failed to load font {}: {}", url, e));
+                    }
+                }
+            }
+            content.fonts.finish_load(index, data, Instant::now());
+        }
+        if let Some(tab) = tab {
+            if let Err(e) = tab.js_context.set_font_faces(&content.fonts) {
+                self.devtools.console.error(format!("JavaScript error: {}", e));
+            }
+        }
+        self.tick_fonts(Instant::now())
+    }
+
+    /// Lay the active page out again if what its text is drawn with has
+    /// changed, as block periods end and web fonts arrive
+    fn tick_fonts(&mut self, now: Instant) -> bool {
+        let Some(content) = self.window.contents.get_mut(&self.window.tabs.active_id()) else {
+            return false;
+        };
+        if !content.fonts.update(now) {
+            return false;
+        }
+        self.restyle_active_page();
+        true
+    }
+
+    /// Is the active page's text waiting on a web font block period to end
+    fn has_font_timers(&self) -> bool {
+        let content = self.window.contents.get(&self.window.tabs.active_id());
+        content.is_some_and(|content| content.fonts.next_change(Instant::now()).is_some())
+    }
+
+    /// Fetch the next range of each of a tab's media resources that wants one
+    ///
+    /// Returns whether any arrived, so frames may have been decoded.
+    fn load_media(&mut self, tab_id: TabId) -> bool {
+        let Some(content) = self.window.contents.get_mut(&tab_id) else {
+            return false;
+        };
+        let mut fetched = false;
+        for element in content.media.iter_mut() {
+            let Some((url, range)) = element.player.next_fetch() else {
+                continue;
+            };
+            if let Some(tab) = self.window.tabs.tab_mut(tab_id) {
+                note_subresource(tab, &url, &mut self.devtools);
+            }
+            let network = &mut self.devtools.network;
+            let request = network.log_request(url.clone(), "GET".to_string(), NetworkRequestType::Media);
+            let result = match self.resource_loader.load_range(&url, range) {
+                Ok(response) if response.status < 400 => {
+                    let content_type = Some(response.content_type.clone()).filter(|t| !t.is_empty());
+                    network.complete_request(request, response.status, response.body.len(), content_type);
+                    Ok(RangeChunk::from_response(response))
+                }
+                Ok(response) => {
+                    network.complete_request(request, response.status, response.body.len(), None);
+                    Err(MediaError::Network(format!("HTTP {}", response.status)))
+                }
+                Err(e) => {
+                    network.complete_request(request, 0, 0, None);
+                    Err(MediaError::Network(e.to_string()))
+                }
+            };
+            if let Err(e) = &result {
+                self.devtools.console.warn(format!("Failed to load media {}: {}", url, e));
+            }
+            element.player.receive(&url, result, Instant::now());
+            fetched = true;
+        }
+        if content.media.iter().any(|element| element.player.wants_fetch()) {
+            self.post_media_load(tab_id);
+        }
+        fetched
+    }
+
+    /// Fetch more of a tab's media in idle time
+    fn post_media_load(&mut self, tab_id: TabId) {
+        if !self.idle_tasks.contains(|work| *work == IdleWork::LoadMedia(tab_id)) {
+            self.idle_tasks.post(IdleWork::LoadMedia(tab_id));
+        }
+    }
+
+    /// Apply `play()`, `pause()` and other calls the active page's scripts
+    /// made on its media elements
+    ///
+    /// Calls made before the page's content is ready wait in the queue.
+    fn service_media_requests(&mut self, now: Instant) {
+        let tab = self.window.tabs.active_mut();
+        let Some(content) = self.window.contents.get_mut(&tab.id()) else {
+            return;
+        };
+        let requests = match tab.js_context.take_media_requests() {
+            Ok(requests) => requests,
+            Err(e) => {
+                self.devtools.console.error(format!("Media error: {}", e));
+                return;
+            }
+        };
+        for request in requests {
+            let id = match &request {
+                MediaRequest::Play { id }
+                | MediaRequest::Pause { id }
+                | MediaRequest::Load { id }
+                | MediaRequest::Seek { id, .. }
+                | MediaRequest::SetVolume { id, .. }
+                | MediaRequest::SetLoop { id, .. } => id,
+            };
+            let Some(element) = content.media.get_mut(id) else {
+                continue;
+            };
+            let player = &mut element.player;
+            match request {
+                MediaRequest::Play { .. } => player.play(now),
+                MediaRequest::Pause { .. } => player.pause(now),
+                MediaRequest::Load { .. } => player.reload(),
+                MediaRequest::Seek { time, .. } => player.seek(time, now),
+                MediaRequest::SetVolume { volume, muted, .. } => {
+                    player.set_volume(volume);
+                    player.set_muted(muted);
+                }
+                MediaRequest::SetLoop { looping, .. } => player.set_loop(looping),
+            }
+        }
+        let tab_id = tab.id();
+        if content.media.iter().any(|element| element.player.wants_fetch()) {
+            self.post_media_load(tab_id);
+        }
+    }
+
+    /// Advance the active page's media to `now`, handing the events its
+    /// players fired to the page
+    fn tick_media(&mut self, now: Instant) {
+        self.service_media_requests(now);
+        let tab = self.window.tabs.active_mut();
+        let Some(content) = self.window.contents.get_mut(&tab.id()) else {
+            return;
+        };
+        for element in content.media.iter_mut() {
+            element.player.update(now);
+            let events = element.player.take_events();
+            let Some(id) = element.id.as_deref().filter(|_| !events.is_empty() || element.player.is_playing()) else {
+                continue;
+            };
+            if let Err(e) = tab.js_context.update_media_element(id, &element.player.state(now), &events) {
+                self.devtools.console.error(format!("JavaScript error: {}", e));
+            }
+        }
+        // Listeners may have called play() or pause()
+        self.service_media_requests(now);
+    }
+
+    /// Is media on the active page playing or waiting to
+    fn has_active_media(&self) -> bool {
+        let content = self.window.contents.get(&self.window.tabs.active_id());
+        content.is_some_and(|content| content.media.is_active())
+    }
+
+    /// Element of the active page under a window point, for the element picker
+    fn element_at(&self, x: f32, y: f32) -> Option<InspectedElement> {
+        let tab = self.window.tabs.active();
+        let document = tab.tree()?;
+        let offset_y = tab.scroll.offset_y;
+        self.with_active_layout(|root| inspect_element(document, root, x, y + offset_y)).flatten()
+    }
+
+    /// Point the console's `$0` at the node at a `DomInspector` path and
+    /// list the event listeners on it and its ancestors
+    fn set_inspected_element(&mut self, path: &[usize]) {
+        let tab = self.window.tabs.active_mut();
+        let document = tab.document.as_ref().map(Document::tree);
+        let node = document.and_then(|dom| self.devtools.dom_inspector.get_node_at_path(dom, path));
+        if let Err(e) = tab.js_context.set_inspected_element(node) {
+            self.devtools.console.error(format!("Cannot set $0: {}", e));
+        }
+        
+        let mut targets = vec!["window".to_string(), "document".to_string()];
+        if let Some(dom) = tab.tree() {
+            let ids = (0..=path.len()).filter_map(|depth| {
+                let node = self.devtools.dom_inspector.get_node_at_path(dom, &path[..depth])?;
+                node.element_data().and_then(|e| e.id()).map(|id| format!("#{}", id))
+            });
+            targets.extend(ids);
+        }
+        match tab.js_context.event_listeners(&targets) {
+            Ok(listeners) => self.devtools.dom_inspector.set_event_listeners(listeners),
+            Err(e) => self.devtools.console.error(format!("Cannot list event listeners: {}", e)),
+        }
+    }
+
+    /// Text of the current page selection
+    fn selected_page_text(&self) -> String {
+        self.with_active_layout(|root| self.window.selection.selected_text(root))
+            .unwrap_or_default()
+    }
+
+    /// Handle Ctrl+C / Ctrl+X / Ctrl+V; returns false for other keys
+    fn handle_clipboard_key(&mut self, key: &winit::keyboard::Key) -> bool {
+        use winit::keyboard::Key;
+        
+        let command = match key {
+            Key::Character(c) => c.to_lowercase(),
+            _ => return false,
+        };
+        let bar = &mut self.window.ui.address_bar;
+        let result = match (command.as_str(), bar.is_focused()) {
+            ("c", true) | ("x", true) => {
+                let text = bar.selected_text().unwrap_or(bar.url()).to_string();
+                let copied = self.clipboard.copy(&text);
+                if command == "x" && copied.is_ok() {
+                    if bar.selection().is_none() {
+                        bar.select_all();
+                    }
+                    bar.backspace();
+                }
+                copied
+            }
+            ("v", true) => self.clipboard.paste().map(|text| bar.insert_str(text.trim())),
+            ("c", false) | ("x", false) if self.window.tabs.active().editor.is_some() => {
+                let tab = self.window.tabs.active();
+                let text = tab.editor.as_ref().zip(tab.document.as_ref()).map(|(e, d)| e.selected_text(d));
+                let copied = self.clipboard.copy(&text.unwrap_or_default());
+                if command == "x" && copied.is_ok() {
+                    self.edit(|editor, document| if editor.is_collapsed() { None } else { editor.delete_backward(document) });
+                    self.service_script_requests(true);
+                }
+                copied
+            }
+            ("v", false) if self.window.tabs.active().editor.is_some() => self.clipboard.paste().map(|text| {
+                self.edit(|editor, document| editor.insert_text(document, &text));
+                self.service_script_requests(true);
+            }),
+            ("c", false) => {
+                let text = self.selected_page_text();
+                self.clipboard.copy(&text)
+            }
+            _ => return false,
+        };
+        
+        if let Err(e) = result {
+            self.devtools.console.warn(format!("Clipboard: {}", e));
+        }
+        true
+    }
+
+    /// Handle back navigation
+    fn go_back(&mut self) {
+        let previous = self.window.tabs.active().url().cloned();
+        self.save_scroll_position();
+        // Get URL before mutably borrowing self again
+        let entry = self.window.tabs.active_mut().history.go_back().map(|e| (e.url.clone(), e.scroll_position));
+        if let Some((url, scroll_position)) = entry {
+            self.devtools.console.info(format!("Back to: {}", url));
+            self.show_history_entry(url, scroll_position, previous);
+        }
+    }
+
+    /// Handle forward navigation
+    fn go_forward(&mut self) {
+        let previous = self.window.tabs.active().url().cloned();
+        self.save_scroll_position();
+        // Get URL before mutably borrowing self again
+        let entry = self.window.tabs.active_mut().history.go_forward().map(|e| (e.url.clone(), e.scroll_position));
+        if let Some((url, scroll_position)) = entry {
+            self.devtools.console.info(format!("Forward to: {}", url));
+            self.show_history_entry(url, scroll_position, previous);
+        }
+    }
+
+    /// Show a history entry and restore its scroll position
+    ///
+    /// Entries within the same document only scroll; others reload
+    /// without adding to history again.
+    fn show_history_entry(&mut self, url: url::Url, scroll_position: (f32, f32), previous: Option<url::Url>) {
+        let same_document = previous.is_some_and(|prev| is_same_document(&prev, &url));
+        if !same_document {
+            self.window.tabs.active_mut().reader_mode = false;
+        }
+        if !same_document || !self.window.contents.contains_key(&self.window.tabs.active_id()) {
+            self.assign_content_process(&url);
+            match self.load_page(&url, None, CacheMode::Default) {
+                Ok(content) => {
+                    self.window.contents.insert(self.window.tabs.active_id(), content);
+                }
+                Err(_) => return,
+            }
+        }
+        
+        let (x, y) = scroll_position;
+        self.window.tabs.active_mut().scroll.scroll_to(x, y);
+        self.window.ui.address_bar.set_url(url.to_string());
+    }
+
+    /// Bring the accessibility tree up to date and send changes to screen readers
+    fn update_accessibility(&mut self, control: &WindowControl, key: WindowKey) {
+        let viewport = self.layout_viewport();
+        let window = &mut self.window;
+        let tab = window.tabs.active();
+        let mut changed = window.accessibility.set_window_title(&tab.title);
+        
+        if window.accessibility_dirty {
+            window.accessibility_dirty = false;
+            let empty = Node::element("html".to_string(), Default::default(), vec![]);
+            let dom = tab.tree().unwrap_or(&empty);
+            let styled = window.contents.get(&tab.id()).map(|content| content.style(dom));
+            let base_url = window.link_handler.base_url();
+            let layout_root = styled.as_ref().map(|styled| {
+                layout_tree_with_images(styled, viewport, &*self.text_metrics, self.images.sizes(), base_url)
+            });
+            changed |= !window.accessibility.update(dom, layout_root.as_ref()).is_empty();
+        }
+        
+        // The page sits below the chrome, moved up by the scroll offset
+        let (x, y) = tab.scroll.apply_offset(0.0, 0.0);
+        let origin = (x, y + window.ui.chrome_height);
+        changed |= window.accessibility.set_viewport(window.scale_factor, origin);
+        
+        if changed {
+            // Always send the whole tree: a screen reader that starts
+            // listening later must not receive nodes without their parents
+            let tree = &window.accessibility;
+            control.update_accessibility(key, || tree.to_tree_update());
+        }
+    }
+
+    /// Carry out an action requested by a screen reader
+    fn handle_accessibility_action(&mut self, request: accesskit::ActionRequest) {
+        use accesskit::Action;
+        
+        let Some(node) = self.window.accessibility.get(request.target.0).cloned() else {
+            return;
+        };
+        match request.action {
+            Action::Focus => {
+                self.window.accessibility.set_focus(node.id);
+            }
+            Action::Default if node.role == AxRole::Link => {
+                let url = node.url.as_deref()
+                    .and_then(|href| resolve_href(self.window.link_handler.base_url()?, href));
+                if let Some(url) = url {
+                    self.navigate(url.to_string());
+                }
+            }
+            Action::Default => {
+                self.window.accessibility.set_focus(node.id);
+            }
+            Action::ScrollIntoView => {
+                if let Some(rect) = node.bounds {
+                    let scroll = &mut self.window.tabs.active_mut().scroll;
+                    scroll.scroll_rect_into_view(rect, ScrollAlign::Nearest, ScrollBehavior::Auto);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Allow or block JavaScript on the current page's site (Ctrl+Shift+J)
+    fn toggle_site_javascript(&mut self) {
+        let Some(url) = self.window.tabs.active().url().cloned() else {
+            return;
+        };
+        let enabled = !self.preferences.for_site(&url).javascript_enabled;
+        let global = self.preferences.javascript_enabled;
+        let changed = self.preferences.update_site_overrides(&url, |site| {
+            // Matching the global setting needs no override
+            site.javascript_enabled = (enabled != global).then_some(enabled);
+        });
+        if !changed {
+            return;
+        }
+        let state = if enabled { "allowed" } else { "blocked" };
+        self.devtools.console.info(format!("JavaScript {} on {}", state, url.origin().ascii_serialization()));
+        self.save_preferences();
+        self.render_active_page();
+    }
+
+    /// Turn content filtering on or off for the current page's site (Ctrl+Shift+A)
+    fn toggle_site_content_filtering(&mut self) {
+        let Some(url) = self.window.tabs.active().url().cloned() else {
+            return;
+        };
+        let enabled = !self.preferences.for_site(&url).content_filtering;
+        let global = self.preferences.content_filtering;
+        let changed = self.preferences.update_site_overrides(&url, |site| {
+            site.content_filtering = (enabled != global).then_some(enabled);
+        });
+        if !changed {
+            return;
+        }
+        let state = if enabled { "on" } else { "off" };
+        self.devtools.console.info(format!("Content filtering {} for {}", state, url.origin().ascii_serialization()));
+        self.save_preferences();
+        self.render_active_page();
+    }
+
+    /// Clear cookies, storage and cached files of the current page's site (Ctrl+Shift+Backspace)
+    fn clear_site_data(&mut self) {
+        let Some(url) = self.window.tabs.active().url().cloned() else {
+            return;
+        };
+        let freed = self.storage.usage_details(&url).total();
+        match self.storage.clear_site_data(&url, SiteDataTypes::all()) {
+            Ok(()) => {
+                let origin = url.origin().ascii_serialization();
+                self.devtools.console.info(format!("Cleared {} bytes of site data for {}", freed, origin));
+                self.render_active_page();
+            }
+            Err(e) => self.devtools.console.error(format!("Failed to clear site data: {}", e)),
+        }
+    }
+
+    /// Write the preferences file
+    fn save_preferences(&mut self) {
+        let Some(path) = self.preferences_path.as_deref() else {
+            return;
+        };
+        if let Err(e) = self.preferences.save(path) {
+            self.devtools.console.error(format!("Failed to save preferences: {}", e));
+        }
+    }
+
+    /// Switch the active tab into or out of reader mode
+    fn toggle_reader_mode(&mut self) {
+        // The page may not have been looked at for an article yet
+        let tab_id = self.window.tabs.active_id();
+        if self.idle_tasks.contains(|work| *work == IdleWork::DetectArticle(tab_id)) {
+            self.detect_article(tab_id);
+        }
+        let tab = self.window.tabs.active_mut();
+        if !tab.reader_available && !tab.reader_mode {
+            return;
+        }
+        tab.reader_mode = !tab.reader_mode;
+        self.render_active_page();
+    }
+
+    /// Apply a change to the reader settings, saving them and re-rendering
+    /// the page if it is in reader mode
+    fn update_reader_settings(&mut self, change: impl FnOnce(&mut ReaderSettings)) {
+        change(&mut self.reader_settings);
+        if let Err(e) = self.reader_settings.save(self.storage.local_storage()) {
+            self.devtools.console.error(format!("Failed to save reader settings: {}", e));
+        }
+        if self.window.tabs.active().reader_mode {
+            let scroll = self.window.tabs.active().scroll.target();
+            self.render_active_page();
+            self.window.tabs.active_mut().scroll.scroll_to(scroll.0, scroll.1);
+        }
+    }
+
+    /// Render the active tab's current page again, from the cache if possible
+    fn render_active_page(&mut self) {
+        if let Some(url) = self.window.tabs.active().url().cloned() {
+            if let Ok(content) = self.load_page(&url, None, CacheMode::Default) {
+                self.window.contents.insert(self.window.tabs.active_id(), content);
+            }
+        }
+    }
+
+    /// Show the print preview for the active tab (Ctrl+P)
+    fn open_print_preview(&mut self) {
+        self.window.ui.address_bar.set_focused(false);
+        self.window.ui.find_bar.close();
+        self.window.ui.print_preview.open();
+        self.paginate_for_print();
+    }
+
+    /// The active tab's document as it prints, with its site's fonts
+    fn printable_page(&self) -> Option<Page> {
+        let tab = self.window.tabs.active();
+        let content = self.window.contents.get(&tab.id())?;
+        let mut page = Page::new(tab.tree()?.clone(), content.source_stylesheet.clone())
+            .with_style_defaults(content.style_defaults.clone());
+        if let Some(url) = tab.url() {
+            page = page.with_url(url.clone());
+        }
+        Some(page)
+    }
+
+    /// Lay the active tab out with the preview's print options
+    fn paginate_for_print(&mut self) {
+        let Some(page) = self.printable_page() else {
+            self.window.ui.print_preview.set_pages(Vec::new());
+            return;
+        };
+        match page.paginate(self.window.ui.print_preview.options()) {
+            Ok(pages) => self.window.ui.print_preview.set_pages(pages),
+            Err(e) => self.devtools.console.warn(format!("Cannot print: {}", e)),
+        }
+    }
+
+    /// Route a key press to the open print preview
+    ///
+    /// Returns false if the preview is closed; while open it takes every key.
+    fn handle_print_preview_key(&mut self, key: &winit::keyboard::Key) -> bool {
+        if !self.window.ui.print_preview.is_open() {
+            return false;
+        }
+        match self.window.ui.print_preview.handle_key(key) {
+            PrintPreviewAction::OptionsChanged => self.paginate_for_print(),
+            PrintPreviewAction::Export => {
+                self.save_pdf();
+                self.window.ui.print_preview.close();
+            }
+            PrintPreviewAction::PageChanged | PrintPreviewAction::Closed | PrintPreviewAction::Ignored => {}
+        }
+        true
+    }
+
+    /// Save the active tab as a PDF in the working directory
+    fn save_pdf(&mut self) {
+        let Some(page) = self.printable_page() else {
+            return;
+        };
+        let pdf = match page.print_to_pdf(self.window.ui.print_preview.options()) {
+            Ok(pdf) => pdf,
+            Err(e) => {
+                self.devtools.console.error(format!("Cannot print: {}", e));
+                return;
+            }
+        };
+        let path = save_path(&page.title, "pdf");
+        match std::fs::write(&path, pdf) {
+            Ok(()) => {
+                println!("Saved {}", path.display());
+                self.devtools.console.info(format!("Saved PDF to {}", path.display()));
+            }
+            Err(e) => self.devtools.console.error(format!("Failed to save {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Save the requests listed in the network view as a HAR file in the
+    /// working directory
+    fn export_har(&mut self) {
+        let host = self.window.tabs.active().url().and_then(|url| url.host_str()).unwrap_or("network").to_string();
+        let path = save_path(&host, "har");
+        match std::fs::write(&path, self.devtools.network.export_har()) {
+            Ok(()) => self.devtools.console.info(format!("Saved network log to {}", path.display())),
+            Err(e) => self.devtools.console.error(format!("Failed to save {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Use a new device pixel ratio, laying the page out again in CSS pixels
+    fn set_scale_factor(&mut self, scale_factor: f64, size: PhysicalSize<u32>) {
+        self.window.scale_factor = scale_factor;
+        let (width, height) = css_size(size, scale_factor);
+        self.resize(width, height);
+    }
+
+    /// Handle window resize (in CSS pixels)
+    fn resize(&mut self, width: f32, height: f32) {
+        self.window.ui.resize(width, height);
+        
+        // Lay the current page out again for the new dimensions
+        if self.window.contents.contains_key(&self.window.tabs.active_id()) {
+            self.restyle_active_page();
+        } else {
+            self.render_active_page();
+        }
+    }
+
+    /// Style and lay out the active tab's document again, without reloading it
+    ///
+    /// A panic doing so is reported and the tab shows the crash page.
+    fn restyle_active_page(&mut self) {
+        self.guard_active_page(PipelinePhase::Style, Self::restyle_page);
+    }
+
+    /// Run work for the active tab's page, starting in `phase`
+    ///
+    /// A panic in it is reported and the tab shows the crash page; there is
+    /// no result then.
+    fn guard_active_page<T>(&mut self, phase: PipelinePhase, work: impl FnOnce(&mut Self) -> T) -> Option<T> {
+        let url = self.window.tabs.active().url().cloned();
+        match crash::guard(url.as_ref(), phase, || work(self)) {
+            Ok(result) => Some(result),
+            Err(report) => {
+                self.page_crashed(report);
+                self.show_crash_page();
+                None
+            }
+        }
+    }
+
+    fn restyle_page(&mut self) {
+        let viewport = self.layout_viewport();
+        let tab_id = self.window.tabs.active_id();
+        let tab = self.window.tabs.active_mut();
+        let (Some(content), Some(dom)) = (self.window.contents.get_mut(&tab_id), tab.tree()) else {
+            return;
+        };
+        let start = Instant::now();
+        let base_url = tab.url().map(|url| document_base_url(dom, url));
+        let (display_list, contentful, page_box, mut layers) = {
+            // Values the change gives transitions start over the values as they were
+            let mut styled = style_tree_with_defaults(dom, &content.stylesheet, &content.style_defaults);
+            let computed = StyleSnapshot::of(&styled);
+            if content.animations.start_style_transitions(&content.computed, &computed, &content.animated) {
+                content.animated = content.animations.sample(start);
+            }
+            content.computed = computed;
+            content.animated.apply(&mut styled);
+            crash::enter_phase(PipelinePhase::Layout);
+            let layout_root = layout_tree_with_images(
+                &styled,
+                viewport,
+                &*self.text_metrics,
+                self.images.sizes(),
+                base_url.as_ref(),
+            );
+            // Boxes stay scrolled as far as they still can be
+            let containers = scroll_containers(&layout_root, &content.box_scrolls);
+            content.box_scrolls.clamp(&containers);
+            content.scroll_containers = scroll_containers(&layout_root, &content.box_scrolls);
+            let display_list = build_display_list_scrolled(&layout_root, base_url.as_ref(), &content.box_scrolls);
+            let mut layers = page_layers(&layout_root, &viewport, self.window.scale_factor);
+            content.animated.apply_to_layers(&mut layers);
+            let contentful = contentful_paints(&display_list);
+            (display_list, contentful, layout_root.dimensions.margin_box(), layers)
+        };
+        tab.js_context.performance_mut().record_task_since(start, TaskAttribution::Layout);
+        // Only what painted differently needs repainting
+        let damage = changed_rects(&content.display_list, &display_list, DisplayCommand::rect);
+        // The new layers stay scrolled where the old ones were
+        let scrolled = *content.layers.viewport();
+        layers.scroll_to(scrolled.x, scrolled.y);
+        layers.clear_damage();
+        for rect in &damage {
+            layers.damage_region(*rect);
+        }
+        self.devtools.layers.record_paints(&damage, Instant::now());
+        content.display_list = display_list;
+        content.contentful = contentful;
+        content.layers = layers;
+        tab.scroll.set_viewport_size(viewport.content.width, viewport.content.height);
+        tab.scroll.set_content_size(page_box.width, page_box.height);
+        self.window.accessibility_dirty = true;
+    }
+
+    /// Change a declaration of the active page's stylesheet from DevTools and restyle the page
+    fn edit_stylesheet(&mut self, rule: usize, declaration: usize, edit: impl FnOnce(&mut Declaration)) {
+        let tab_id = self.window.tabs.active_id();
+        let content = self.window.contents.get_mut(&tab_id);
+        if let Some(declaration) = content.and_then(|content| content.stylesheet.declaration_mut(rule, declaration)) {
+            edit(declaration);
+            self.restyle_active_page();
+        }
+    }
+
+    /// Re-run the find-in-page search against the active tab
+    fn run_find(&mut self) {
+        let viewport = self.layout_viewport();
+        let tab = self.window.tabs.active();
+        if let (Some(content), Some(dom)) = (self.window.contents.get(&tab.id()), tab.tree()) {
+            let styled = content.style(dom);
+            let layout_root = layout_tree_with_images(
+                &styled,
+                viewport,
+                &*self.text_metrics,
+                self.images.sizes(),
+                self.window.link_handler.base_url(),
+            );
+            self.window.ui.find_bar.search(&layout_root);
+        }
+        self.window.ui.find_bar.scroll_into_view(&mut self.window.tabs.active_mut().scroll);
+    }
+
+    /// Route a key press to the sign-in bar
+    ///
+    /// Returns false if the bar is not shown.
+    fn handle_auth_key(&mut self, key: &winit::keyboard::Key) -> bool {
+        let action = self.window.ui.auth_bar.handle_key(key);
+        if action == AuthAction::Ignored {
+            return false;
+        }
+        self.handle_auth_action(action);
+        true
+    }
+
+    /// Keep the credentials given in the sign-in bar for the origin and
+    /// load the page again with them
+    fn handle_auth_action(&mut self, action: AuthAction) {
+        if let AuthAction::SignIn(request, credentials) = action {
+            if let Ok(mut auth) = self.resource_loader.auth_cache().lock() {
+                auth.store(&request.url, request.challenge, credentials);
+            }
+            self.reload(CacheMode::Reload);
+        }
+    }
+
+    /// Route a key press to the open find bar
+    ///
+    /// Returns false if the find bar did not handle the key.
+    fn handle_find_key(&mut self, key: &winit::keyboard::Key) -> bool {
+        let alt = self.modifiers.alt_key();
+        let shift = self.modifiers.shift_key();
+        match self.window.ui.find_bar.handle_key(key, alt, shift) {
+            FindAction::Ignored => false,
+            FindAction::QueryChanged => {
+                self.run_find();
+                true
+            }
+            FindAction::ActiveMatchChanged => {
+                self.window.ui.find_bar.scroll_into_view(&mut self.window.tabs.active_mut().scroll);
+                true
+            }
+            FindAction::Closed => true,
+        }
+    }
+
+    /// Route a key press to the DevTools console input
+    ///
+    /// Returns false if the panel did not handle the key.
+    fn handle_devtools_key(&mut self, key: &winit::keyboard::Key) -> bool {
+        match self.window.ui.devtools.handle_key(key) {
+            DevToolsAction::Ignored => false,
+            action => {
+                self.handle_devtools_action(action);
+                true
+            }
+        }
+    }
+
+    /// Act on input the DevTools panel handled
+    fn handle_devtools_action(&mut self, action: DevToolsAction) {
+        match action {
+            DevToolsAction::Evaluate(code) => {
+                self.devtools.console.log(format!("> {}", code));
+                match self.window.tabs.active_mut().js_context.evaluate_in_console(&code) {
+                    Ok(value) => self.devtools.console.log_value(value),
+                    Err(e) => self.devtools.console.error(e.to_string()),
+                }
+                self.service_script_requests(true);
+            }
+            DevToolsAction::Complete(input) => {
+                match self.window.tabs.active_mut().js_context.console_completions(&input) {
+                    Ok(candidates) => self.window.ui.devtools.complete(&candidates),
+                    Err(e) => self.devtools.console.error(e.to_string()),
+                }
+            }
+            DevToolsAction::ExpandObject(handle) => {
+                match self.window.tabs.active_mut().js_context.console_properties(handle) {
+                    Ok(properties) => self.devtools.console.expand(handle, properties),
+                    Err(e) => self.devtools.console.error(e.to_string()),
+                }
+            }
+            DevToolsAction::LayoutChanged => {
+                self.devtools.is_open = self.window.ui.devtools.is_open();
+                let (width, height) = (self.window.ui.bounds.width, self.window.ui.bounds.height);
+                self.resize(width, height);
+            }
+            DevToolsAction::NodeSelected(path) => self.set_inspected_element(&path),
+            DevToolsAction::ToggleDeclaration { rule, declaration } => {
+                self.edit_stylesheet(rule, declaration, |declaration| declaration.enabled = !declaration.enabled);
+            }
+            DevToolsAction::SetDeclarationValue { rule, declaration, value } => {
+                match CssParser::parse_property_value(&value) {
+                    Some(value) => self.edit_stylesheet(rule, declaration, |declaration| {
+                        declaration.value = value;
+                        declaration.enabled = true;
+                    }),
+                    None => self.devtools.console.warn(format!("Invalid property value: {}", value)),
+                }
             }
+            DevToolsAction::ExportHar => self.export_har(),
+            DevToolsAction::DomBreakpointsChanged => self.sync_dom_breakpoints(),
+            DevToolsAction::Handled | DevToolsAction::Ignored => {}
         }
     }
-    
-    /// Load and render a page
-    fn load_page(&mut self, url: &url::Url, network_req_idx: Option<usize>) -> Result<PageContent, String> {
-        // Handle special URLs
-        if url.as_str() == "about:blank" {
-            return Ok(PageContent {
-                backgrounds: vec![],
-                borders: vec![],
-            });
+
+    /// Route a key press to the focused address bar
+    ///
+    /// Returns false if the address bar did not handle the key.
+    fn handle_address_bar_key(&mut self, key: &winit::keyboard::Key) -> bool {
+        let ctrl = self.modifiers.control_key();
+        let shift = self.modifiers.shift_key();
+        match self.window.ui.address_bar.handle_key(key, ctrl, shift) {
+            AddressBarAction::Ignored => false,
+            AddressBarAction::Navigate(url) => {
+                self.navigate_with(url.to_string(), VisitTransition::Typed);
+                true
+            }
+            AddressBarAction::Cancel => {
+                // Restore the current page's URL
+                let url = self.window.tabs.active().url().map(|u| u.to_string()).unwrap_or_default();
+                self.window.ui.address_bar.set_url(url);
+                true
+            }
+            AddressBarAction::None => {
+                if !matches!(key, winit::keyboard::Key::Named(
+                    winit::keyboard::NamedKey::ArrowUp | winit::keyboard::NamedKey::ArrowDown
+                )) {
+                    self.window.ui.address_bar.update_suggestions(&self.history, &self.bookmarks);
+                }
+                true
+            }
         }
+    }
+
+    /// Typing in the page's focused editable region
+    fn handle_editing_key(&mut self, key: &winit::keyboard::Key) -> bool {
+        use winit::keyboard::{Key, NamedKey};
         
-        // For demo purposes, use example HTML if it's a local file or special URL
-        let html_content = if url.scheme() == "http" || url.scheme() == "https" {
-            // Try to fetch from network
-            match self.http_client.fetch_text(url) {
-                Ok(text) => {
-                    // Complete network request
-                    if let Some(idx) = network_req_idx {
-                        self.devtools.network.complete_request(idx, 200, text.len(), Some("text/html".to_string()));
-                    }
-                    text
-                }
-                Err(e) => {
-                    let error_msg = format!("Network error: {}", e);
-                    eprintln!("{}", error_msg);
-                    self.devtools.console.error(error_msg);
-                    // Mark request as failed
-                    if let Some(idx) = network_req_idx {
-                        self.devtools.network.complete_request(idx, 0, 0, None);
-                    }
-                    // Fallback to example content
-                    get_example_html()
-                }
+        if self.window.tabs.active().editor.is_none() {
+            return false;
+        }
+        let ctrl = self.modifiers.control_key();
+        let shift = self.modifiers.shift_key();
+        let handled = match key {
+            Key::Named(NamedKey::Escape) => {
+                self.window.tabs.active_mut().editor = None;
+                return true;
             }
-        } else {
-            // Use example HTML for testing
-            if let Some(idx) = network_req_idx {
-                let html = get_example_html();
-                self.devtools.network.complete_request(idx, 200, html.len(), Some("text/html".to_string()));
+            Key::Character(c) if ctrl && c.eq_ignore_ascii_case("b") => {
+                self.edit(|editor, document| editor.exec_command(document, "bold", None))
             }
-            get_example_html()
+            Key::Character(c) if ctrl && c.eq_ignore_ascii_case("i") => {
+                self.edit(|editor, document| editor.exec_command(document, "italic", None))
+            }
+            _ if ctrl => return false,
+            Key::Character(c) => self.edit(|editor, document| editor.insert_text(document, c)),
+            Key::Named(NamedKey::Space) => self.edit(|editor, document| editor.insert_text(document, " ")),
+            Key::Named(NamedKey::Enter) => self.edit(|editor, document| editor.insert_paragraph(document)),
+            Key::Named(NamedKey::Backspace) => self.edit(|editor, document| editor.delete_backward(document)),
+            Key::Named(NamedKey::Delete) => self.edit(|editor, document| editor.delete_forward(document)),
+            Key::Named(arrow @ (NamedKey::ArrowLeft | NamedKey::ArrowRight)) => {
+                let tab = self.window.tabs.active_mut();
+                if let (Some(editor), Some(document)) = (tab.editor.as_mut(), tab.document.as_ref()) {
+                    editor.move_caret(document, *arrow == NamedKey::ArrowRight, shift);
+                }
+                false
+            }
+            _ => return false,
         };
+        if handled {
+            // Input listeners run with user activation
+            self.service_script_requests(true);
+        }
+        true
+    }
+
+    /// Run a tab keyboard shortcut
+    fn handle_tab_command(&mut self, command: TabCommand) {
+        let previous = self.window.tabs.active_id();
+        self.window.tabs.execute(command);
         
-        // Parse HTML
-        let dom = HtmlParser::parse(&html_content);
-        
-        // Extract inline CSS or use default
-        let css_content = get_example_css();
-        let stylesheet = CssParser::parse(&css_content);
+        if command == TabCommand::NewTab {
+            self.navigate("about:blank".to_string());
+        }
+        if self.window.tabs.active_id() != previous {
+            self.show_active_tab();
+        }
+    }
+
+    /// Switch chrome and renderer state over to the active tab
+    fn show_active_tab(&mut self) {
+        // Drop rendered content and content processes for tabs that were closed
+        let tabs = &self.window.tabs;
+        let closed: Vec<TabId> = self.window.contents.keys().copied().filter(|id| tabs.tab(*id).is_none()).collect();
+        for id in closed {
+            self.window.contents.remove(&id);
+            self.content.close_tab(id);
+        }
         
-        // Compute styles
-        let styled = style_tree(&dom, &stylesheet);
+        self.window.accessibility_dirty = true;
+        // The sign-in bar asked for the page left; reloading that tab asks again
+        self.window.ui.auth_bar.close();
+        let tab = self.window.tabs.active();
+        let url = tab.url().cloned();
+        self.window.ui.address_bar.set_url(url.as_ref().map(|u| u.to_string()).unwrap_or_default());
         
-        // Calculate layout
-        let mut viewport = Dimensions::default();
-        viewport.content.width = self.ui.bounds.width;
-        viewport.content.height = self.ui.bounds.height - self.ui.chrome_height;
-        let layout_root = layout_tree(&styled, viewport);
+        self.window.link_handler = LinkHandler::new();
+        if let (Some(dom), Some(url)) = (tab.tree(), url.as_ref()) {
+            self.window.link_handler.set_base_url(document_base_url(dom, url));
+        }
         
-        // Build display list
-        let display_list = build_display_list(&layout_root);
+        if !self.window.contents.contains_key(&tab.id()) && self.content.is_crashed(tab.id()) {
+            self.show_crash_page();
+        } else if tab.discarded {
+            // Discarded under memory pressure; load the page again where it was
+            self.reload(CacheMode::Default);
+        } else if !self.window.contents.contains_key(&tab.id()) {
+            // Fresh tab from the tab strip or closing the last tab
+            let url = url.map(|u| u.to_string()).unwrap_or_else(|| "about:blank".to_string());
+            self.navigate(url);
+        }
         
-        // Extract render data
-        let (backgrounds, borders) = extract_render_data(&display_list);
+        self.devtools.console.info(format!("Switched to tab: {}", self.window.tabs.active().title));
         
-        // Execute any JavaScript (simplified)
-        if let Some(script) = extract_script(&html_content) {
-            self.devtools.console.log("Executing inline script".to_string());
-            match self.js_context.execute(&script) {
-                Ok(result) => {
-                    self.devtools.console.debug(format!("Script result: {:?}", result));
-                }
-                Err(e) => {
-                    let error_msg = format!("JavaScript error: {}", e);
-                    eprintln!("{}", error_msg);
-                    self.devtools.console.error(error_msg);
-                }
-            }
+        if self.window.ui.find_bar.is_open() {
+            self.run_find();
         }
-        
-        Ok(PageContent {
-            backgrounds,
-            borders,
-        })
     }
-    
-    /// Handle back navigation
-    fn go_back(&mut self) {
-        // Get URL before mutably borrowing self again
-        let url = self.history.go_back().map(|e| e.url.clone());
-        if let Some(url) = url {
-            let url_str = url.to_string();
-            self.devtools.console.info(format!("Back to: {}", url_str));
-            // Load without adding to history again
-            if let Ok(content) = self.load_page(&url, None) {
-                self.current_content = Some(content);
-                self.ui.address_bar.set_url(url_str);
+}
+
+/// Extensions installed next to the preferences file, each in a directory
+/// of `extensions`, with their storage in `extension-storage`
+fn load_extensions(preferences_path: &std::path::Path) -> ExtensionHost {
+    let storage = ExtensionStorage::open(&preferences_path.with_file_name("extension-storage")).unwrap_or_else(|e| {
+        eprintln!("Failed to open extension storage: {}", e);
+        ExtensionStorage::new()
+    });
+    let mut host = ExtensionHost::with_storage(storage);
+    let dirs = match std::fs::read_dir(preferences_path.with_file_name("extensions")) {
+        Ok(entries) => entries.filter_map(Result::ok).map(|entry| entry.path()).filter(|path| path.is_dir()),
+        Err(e) => {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                eprintln!("Failed to read extensions: {}", e);
             }
+            return host;
         }
-    }
-    
-    /// Handle forward navigation
-    fn go_forward(&mut self) {
-        // Get URL before mutably borrowing self again
-        let url = self.history.go_forward().map(|e| e.url.clone());
-        if let Some(url) = url {
-            let url_str = url.to_string();
-            self.devtools.console.info(format!("Forward to: {}", url_str));
-            // Load without adding to history again
-            if let Ok(content) = self.load_page(&url, None) {
-                self.current_content = Some(content);
-                self.ui.address_bar.set_url(url_str);
-            }
+    };
+    for dir in dirs {
+        match Extension::load(&dir) {
+            Ok(extension) => host.install(extension),
+            Err(e) => eprintln!("Failed to load extension {}: {}", dir.display(), e),
         }
     }
-    
-    /// Handle window resize
-    fn resize(&mut self, width: f32, height: f32) {
-        self.ui.resize(width, height);
-        
-        // Re-render current page with new dimensions
-        if let Some(entry) = self.history.current_entry() {
-            let url = entry.url.clone();
-            if let Ok(content) = self.load_page(&url, None) {
-                self.current_content = Some(content);
-            }
-        }
+    host
+}
+
+/// Unused file name in the working directory, from a page's title or host
+fn save_path(title: &str, extension: &str) -> std::path::PathBuf {
+    let stem: String = title
+        .chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.'))
+        .collect();
+    let stem = match stem.trim() {
+        "" => "page",
+        stem => stem,
+    };
+    let dir = std::env::current_dir().unwrap_or_default();
+    let mut path = dir.join(format!("{}.{}", stem, extension));
+    let mut copy = 1;
+    while path.exists() {
+        path = dir.join(format!("{} ({}).{}", stem, copy, extension));
+        copy += 1;
     }
+    path
 }
 
-/// Extract renderable data from display list
-fn extract_render_data(display_list: &[DisplayCommand]) -> (Vec<(Rect, Color)>, Vec<(Rect, Color, (f32, f32, f32, f32))>) {
-    let mut backgrounds = Vec::new();
-    let mut borders = Vec::new();
-    
-    for cmd in display_list {
-        match cmd {
-            DisplayCommand::SolidRect { color, rect } => {
-                backgrounds.push((*rect, *color));
-            }
-            DisplayCommand::Border { color, rect, widths } => {
-                borders.push((*rect, *color, *widths));
-            }
-            DisplayCommand::Text { .. } => {
-                // Text rendering handled by GPU text painter
-            }
-            DisplayCommand::Image { .. } => {
-                // Image rendering handled by GPU image painter
-            }
-        }
+/// Border boxes of the elements with an id, the first for each id
+fn collect_element_rects(layout: &LayoutBox, rects: &mut HashMap<String, Rect>) {
+    if let Some(id) = layout.get_styled_node().and_then(|s| s.node.element_data()).and_then(|e| e.id()) {
+        rects.entry(id.to_string()).or_insert_with(|| layout.dimensions.border_box());
+    }
+    for child in &layout.children {
+        collect_element_rects(child, rects);
     }
-    
-    (backgrounds, borders)
 }
 
 /// Extract JavaScript from HTML (simplified)
@@ -291,7 +3548,7 @@ fn get_example_html() -> String {
         <h1>Rust Browser Engine</h1>
         <p>Phase 6: Interactive Browser</p>
     </div>
-    
+
     <div class="content">
         <div class="feature-box">
             <h2>✓ HTML5 Parsing</h2>
@@ -306,7 +3563,7 @@ fn get_example_html() -> String {
             <p>Real JS engine integration</p>
         </div>
     </div>
-    
+
     <div id="footer">
         <p>Phase 6 Complete - Navigation, Forms, JavaScript</p>
     </div>
@@ -373,108 +3630,723 @@ p {
 }
 
 fn main() {
+    // Headless mode, as in Chrome: --headless [--screenshot] [--print-to-pdf] [--dump-dom] URL
+    if let Some(options) = HeadlessOptions::from_args(std::env::args().skip(1)) {
+        std::process::exit(run_headless(options));
+    }
+
     println!("=== Browser Engine: Phase 6 - Unified Browser ===\n");
-    
+
     let window_width = 1024.0;
     let window_height = 768.0;
-    
-    let app = Arc::new(Mutex::new(BrowserApp::new(window_width)));
-    
-    // Navigate to initial page
-    {
-        let mut app_lock = app.lock().unwrap();
-        app_lock.navigate("about:blank".to_string());
-    }
-    
+
     println!("Creating browser window...");
-    let window = Window::new(WindowConfig {
-        title: "Rust Browser Engine - Phase 6".to_string(),
-        width: window_width as u32,
-        height: window_height as u32,
-        resizable: true,
-    }).expect("Failed to create window");
-    
-    println!("✓ Browser window created");
+    let mut manager = WindowManager::new().expect("Failed to create event loop");
+    let first_window = manager.open_window(window_config(window_width, window_height));
+
+    let mut app = BrowserApp::new(first_window, window_width, window_height);
+
+    // Remote debugging, as in Chrome: --remote-debugging-port=9222
+    let debugging_port = std::env::args()
+        .find_map(|arg| arg.strip_prefix("--remote-debugging-port=").and_then(|port| port.parse::<u16>().ok()));
+    if let Some(port) = debugging_port {
+        match CdpServer::bind(("127.0.0.1", port)) {
+            Ok(server) => {
+                println!("DevTools listening on {}", server.websocket_url());
+                app.devtools_server = Some(server);
+            }
+            Err(e) => eprintln!("Failed to start the DevTools server: {}", e),
+        }
+    }
+
+    crash::install_panic_hook();
+
+    // Navigate to the home page
+    let homepage = app.preferences.homepage.clone();
+    app.navigate(homepage);
+
     println!("\nControls:");
-    println!("  - Type URL in address bar (Enter to navigate)");
+    println!("  - Click the address bar and type a URL or search (Enter to navigate)");
     println!("  - Alt+Left: Back");
     println!("  - Alt+Right: Forward");
+    println!("  - Ctrl+T / Ctrl+W: New / close tab");
+    println!("  - Ctrl+Tab / Ctrl+Shift+Tab: Next / previous tab");
+    println!("  - Ctrl+N: New window");
+    println!("  - Ctrl+Shift+M: Move tab to a new window");
+    println!("  - Ctrl+C / Ctrl+X / Ctrl+V: Copy / cut / paste");
+    println!("  - Ctrl+F: Find in page (Enter/Shift+Enter: next/previous, Alt+C: match case)");
+    println!("  - Ctrl+D: Bookmark page (or click the star)");
+    println!("  - Ctrl+Shift+B: Show / hide the bookmarks bar");
+    println!("  - Ctrl+Alt+R: Reader mode (or click the reader button)");
+    println!("  - Ctrl+Alt+= / Ctrl+Alt+- / Ctrl+Alt+T / Ctrl+Alt+F: Reader text size, theme and font");
+    println!("  - Ctrl+Shift+J: Allow / block JavaScript on the current site");
+    println!("  - Ctrl+P: Print preview (Enter: save as PDF; L/P/+/-/H/B: orientation, paper, scale, headers, backgrounds)");
+    println!("  - Ctrl+Shift+Delete: Clear the last hour of history");
+    println!("  - Ctrl+Shift+Backspace: Clear cookies, storage and cache for the current site");
+    println!("  - Arrows / PageUp / PageDown / Home / End / Space: Scroll");
     println!("  - F12: Toggle DevTools");
-    println!("  - Ctrl+R: Refresh");
-    println!("  - ESC: Exit\n");
-    
-    let app_for_loop = app.clone();
-    
+    println!("  - Ctrl+Shift+C: Pick an element to inspect (Esc to cancel)");
+    println!("  - F5 / Ctrl+R: Reload");
+    println!("  - Ctrl+F5 / Ctrl+Shift+R: Hard reload (bypass cache)");
+    println!("  - ESC: Stop loading, otherwise exit\n");
+
     // Run event loop
-    window.run_with_renderer(move |renderer, event| {
-        let mut app = app_for_loop.lock().unwrap();
-        
-        match event {
-            WindowEvent::RedrawRequested => {
-                // Render current page content
-                if let Some(ref content) = app.current_content {
-                    if let Err(e) = renderer.render_rects_and_borders(&content.backgrounds, &content.borders) {
-                        eprintln!("Render error: {}", e);
+    manager.run(move |control, key, gpu, event| {
+        let event = match event {
+            ManagedEvent::Opened => {
+                println!("✓ Browser window created");
+                app.focus_window(key);
+                if let Some(window) = control.window(key) {
+                    app.set_scale_factor(window.scale_factor(), window.inner_size());
+                    // Commands from DevTools clients are run when the window redraws
+                    if let Some(server) = app.devtools_server.as_ref().filter(|_| key == first_window) {
+                        let window = window.clone();
+                        server.set_waker(move || window.request_redraw());
                     }
                 }
+                return true;
             }
-            WindowEvent::Resized(size) => {
-                println!("Window resized: {}x{}", size.width, size.height);
-                renderer.resize(size.width, size.height);
-                app.resize(size.width as f32, size.height as f32);
+            ManagedEvent::Closed => {
+                app.close_window(key);
+                return true;
             }
-            WindowEvent::CloseRequested => {
-                println!("\nBrowser closing...");
-                return false;
+            ManagedEvent::Accessibility(request) => {
+                app.focus_window(key);
+                app.handle_accessibility_action(request);
+                app.update_accessibility(control, key);
+                control.request_redraw(key);
+                return true;
             }
-            WindowEvent::KeyboardInput { event, .. } => {
-                use winit::keyboard::{Key, NamedKey};
-                
-                if event.logical_key == Key::Named(NamedKey::Escape) {
-                    println!("\nESC pressed. Exiting...");
-                    return false;
-                }
-                
-                // F12: Toggle DevTools
-                if event.logical_key == Key::Named(NamedKey::F12) {
-                    app.devtools.toggle();
-                    if app.devtools.is_open {
-                        println!("\n=== Developer Tools ===");
-                        println!("Console: {} messages ({} errors, {} warnings)",
-                            app.devtools.console.count(),
-                            app.devtools.console.error_count(),
-                            app.devtools.console.warning_count());
-                        println!("Network: {} requests ({} failed, {} bytes total)",
-                            app.devtools.network.count(),
-                            app.devtools.network.failed_count(),
-                            app.devtools.network.total_size());
-                        
-                        // Print recent console messages
-                        println!("\nRecent Console Messages:");
-                        for msg in app.devtools.console.messages().iter().rev().take(5) {
-                            println!("  [{:?}] {}", msg.msg_type, msg.content);
-                        }
-                    } else {
-                        println!("\nDevTools closed.");
-                    }
+            ManagedEvent::Idle => {
+                // A crashed content process shows its crash page without waiting for a frame
+                app.focus_window(key);
+                poll_gpu(&mut app, gpu);
+                if app.poll_content_processes() | app.poll_fetches() {
+                    control.request_redraw(key);
                 }
-                
-                // Alt+Left: Back
-                if event.logical_key == Key::Named(NamedKey::ArrowLeft) {
-                    if app.history.can_go_back() {
-                        app.go_back();
-                    }
+                return true;
+            }
+            ManagedEvent::IdlePeriod(deadline) => {
+                app.focus_window(key);
+                if app.run_idle_tasks(Some(deadline)) {
+                    control.request_redraw(key);
                 }
-                
-                // Alt+Right: Forward
-                if event.logical_key == Key::Named(NamedKey::ArrowRight) {
-                    if app.history.can_go_forward() {
-                        app.go_forward();
+                request_idle_period(&mut app, control, key);
+                return true;
+            }
+            event @ (ManagedEvent::Window(_) | ManagedEvent::BeginFrame(_)) => event,
+        };
+        
+        app.focus_window(key);
+        let keep_open = match event {
+            ManagedEvent::BeginFrame(frame) => {
+                draw_frame(&mut app, control, key, gpu, frame);
+                true
+            }
+            ManagedEvent::Window(event) => handle_window_event(&mut app, control, key, gpu, event),
+            _ => true,
+        };
+        app.update_accessibility(control, key);
+        // Pages animating with requestAnimationFrame or CSS draw again on the
+        // next vsync, as do pages that just started observing intersections
+        let animating = app.has_active_animations() || app.has_font_timers() || app.has_active_media();
+        let js_context = &mut app.window.tabs.active_mut().js_context;
+        if animating || js_context.has_animation_frames() || js_context.has_pending_intersection_requests() {
+            control.request_redraw(key);
+        }
+        request_idle_period(&mut app, control, key);
+        
+        // Open the pages and windows asked for while handling the event
+        app.handle_script_opens();
+        for request in app.take_window_requests() {
+            // Requests are in CSS pixels, windows are created in device pixels
+            let scale = app.window.scale_factor as f32;
+            let new_key = control.open_window(window_config(request.width * scale, request.height * scale));
+            app.create_window(new_key, request);
+        }
+        keep_open
+    }).expect("Event loop error");
+}
+
+/// The active tab, as remote DevTools clients see it
+impl CdpTarget for BrowserApp {
+    fn url(&self) -> Option<url::Url> {
+        self.window.tabs.active().url().cloned()
+    }
+
+    fn title(&self) -> String {
+        self.window.tabs.active().title.clone()
+    }
+
+    fn document(&self) -> Option<&Node> {
+        self.window.tabs.active().tree()
+    }
+
+    fn navigate(&mut self, url: url::Url) -> Result<(), String> {
+        self.navigate_with(url.to_string(), VisitTransition::Typed);
+        Ok(())
+    }
+
+    fn reload(&mut self, ignore_cache: bool) {
+        self.reload(if ignore_cache { CacheMode::Reload } else { CacheMode::Revalidate });
+    }
+
+    fn evaluate(&mut self, expression: &str) -> Result<JsValue, String> {
+        self.window.tabs.active_mut().js_context.execute(expression).map_err(|e| e.to_string())
+    }
+
+    fn devtools(&self) -> &DevTools {
+        &self.devtools
+    }
+}
+
+/// Run without a window: load the page, write what the flags ask for, then
+/// serve DevTools clients if a port was given. Returns the exit code.
+fn run_headless(options: HeadlessOptions) -> i32 {
+    let mut browser = HeadlessBrowser::new(options.width, options.height);
+    if let Err(e) = browser.navigate(options.url.as_deref().unwrap_or("about:blank")) {
+        eprintln!("{}", e);
+        return 1;
+    }
+    if let Err(e) = browser.wait_for_load(DEFAULT_TIMEOUT) {
+        eprintln!("{}", e);
+    }
+
+    if options.dump_dom {
+        println!("{}", browser.content());
+    }
+    let mut status = 0;
+    if let Some(path) = &options.screenshot {
+        let written = browser
+            .screenshot_png()
+            .map_err(|e| e.to_string())
+            .and_then(|png| std::fs::write(path, png).map_err(|e| e.to_string()));
+        match written {
+            Ok(()) => println!("Written screenshot to {}", path.display()),
+            Err(e) => {
+                eprintln!("Failed to save the screenshot: {}", e);
+                status = 1;
+            }
+        }
+    }
+    if let Some(path) = &options.print_to_pdf {
+        let written = browser
+            .pdf(&PrintOptions::new())
+            .map_err(|e| e.to_string())
+            .and_then(|pdf| std::fs::write(path, pdf).map_err(|e| e.to_string()));
+        match written {
+            Ok(()) => println!("Written PDF to {}", path.display()),
+            Err(e) => {
+                eprintln!("Failed to save the PDF: {}", e);
+                status = 1;
+            }
+        }
+    }
+
+    let Some(port) = options.remote_debugging_port else {
+        return status;
+    };
+    let mut server = match CdpServer::bind(("127.0.0.1", port)) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("Failed to start the DevTools server: {}", e);
+            return 1;
+        }
+    };
+    println!("DevTools listening on {}", server.websocket_url());
+    loop {
+        server.poll(&mut browser);
+        if !browser.view_mut().tick(Instant::now()) {
+            std::thread::sleep(Duration::from_millis(16));
+        }
+    }
+}
+
+/// Configuration for a browser window
+fn window_config(width: f32, height: f32) -> WindowConfig {
+    WindowConfig {
+        title: "Rust Browser Engine - Phase 6".to_string(),
+        width: width as u32,
+        height: height as u32,
+        resizable: true,
+    }
+}
+
+/// Draw a frame of the window whose state is current, its work in phase order
+fn draw_frame(
+    app: &mut BrowserApp,
+    control: &mut WindowControl,
+    key: WindowKey,
+    gpu: &mut GpuProcess,
+    frame: BeginFrame,
+) {
+    let mut timer = FrameTimer::begin(frame);
+    
+    // Work that waited for the frame: crashed pages and DevTools commands
+    timer.enter(FramePhase::Input, Instant::now());
+    if app.poll_content_processes() {
+        control.request_redraw(key);
+    }
+    app.poll_devtools_server();
+    app.poll_extensions();
+    app.poll_fetches();
+    app.request_images();
+    poll_gpu(app, gpu);
+    app.check_memory(gpu);
+    
+    // Smooth scrolling, flings, CSS animations and the page's animation
+    // frame callbacks, all advanced to the frame's vsync
+    timer.enter(FramePhase::AnimationFrame, Instant::now());
+    let dt = frame.frame_time.saturating_duration_since(app.window.last_frame).as_secs_f32();
+    app.window.last_frame = app.window.last_frame.max(frame.frame_time);
+    if app.window.tabs.active_mut().scroll.tick(dt) {
+        control.request_redraw(key);
+    }
+    app.tick_animations(frame.frame_time);
+    app.tick_fonts(frame.frame_time);
+    app.tick_media(frame.frame_time);
+    app.run_animation_frames(frame.frame_time);
+    
+    // A scroll repaints only the tiles it brings into view
+    let offset_y = app.window.tabs.active().scroll.offset_y;
+    if let Some(content) = app.window.contents.get_mut(&app.window.tabs.active_id()) {
+        let exposed = content.layers.scroll_to(0.0, offset_y);
+        app.devtools.layers.record_paints(&exposed, Instant::now());
+    }
+    
+    // Styling and laying out the page and building its display list can
+    // panic; the tab then shows the crash page from the next frame. The
+    // chrome is painted either way, outside the guard, so its panics are
+    // not taken for the page's
+    let url = app.window.tabs.active().url().cloned();
+    let built = crash::guard(url.as_ref(), PipelinePhase::Style, || {
+        // The page is styled and laid out for the overlays drawn over it
+        timer.enter(FramePhase::Style, Instant::now());
+        let styled = styled_active_page(&app.window.tabs, &app.window.contents);
+        timer.enter(FramePhase::Layout, Instant::now());
+        crash::enter_phase(PipelinePhase::Layout);
+        let (selected, editing) = app
+            .with_active_layout(|root| {
+                let tab = app.window.tabs.active();
+                let editing = tab.editor.as_ref().zip(tab.document.as_ref());
+                let editing = editing.map(|(editor, document)| editor.paint(document, root, tab.scroll.offset_y));
+                let mut selected = app.window.selection.highlights(root);
+                selected.iter_mut().for_each(|command| command.translate(0.0, -tab.scroll.offset_y));
+                (selected, editing)
+            })
+            .unzip();
+    
+        // The active tab's page content
+        timer.enter(FramePhase::Paint, Instant::now());
+        crash::enter_phase(PipelinePhase::Paint);
+        let list = match app.window.contents.get(&app.window.tabs.active_id()) {
+            // Commands out of view are left out; fixed boxes do not scroll
+            Some(content) => scroll_display_list(content.display_list.clone(), *content.layers.viewport(), 0.0, offset_y),
+            None => Vec::new(),
+        };
+        (styled, selected, editing, list)
+    });
+    let ((styled, selected, editing, mut list), crashed) = match built {
+        Ok(page) => (page, None),
+        Err(report) => ((None, None, None, Vec::new()), Some(report)),
+    };
+
+    // The chrome over the page
+    let now = Instant::now();
+    list.extend(app.window.ui.navigation.paint());
+    list.extend(app.window.ui.star_button.paint(app.is_bookmarked()));
+    let tab = app.window.tabs.active();
+    list.extend(app.window.ui.reader_button.paint(tab.reader_available, tab.reader_mode));
+    let site_info = app.site_info();
+    if let Some(ref info) = site_info {
+        list.extend(app.window.ui.site_info.paint_icon(info.state));
+    }
+    if app.window.ui.is_bookmarks_bar_visible() {
+        list.extend(app.window.ui.bookmarks_bar.paint(&app.bookmarks));
+    }
+    list.extend(app.window.ui.tab_strip.paint(&app.window.tabs));
+    
+    // Find-in-page matches, the text selection and the find bar on top
+    let mut overlay = app.window.ui.find_bar.highlights(offset_y);
+    overlay.extend(selected.unwrap_or_default());
+    overlay.extend(editing.flatten().unwrap_or_default());
+    overlay.extend(app.window.ui.devtools.paint_highlight(offset_y));
+    if let Some(content) = app.window.contents.get(&app.window.tabs.active_id()) {
+        overlay.extend(app.devtools.layers.paint_overlays(&content.layers, offset_y, now));
+    }
+    // Repaints fade from the paint flashing overlay
+    if app.devtools.layers.is_flashing(now) {
+        control.request_redraw(key);
+    }
+    overlay.extend(app.window.ui.find_bar.paint());
+    let prompt = app.window.tabs.active().url().and_then(|url| app.permissions.pending_for(url));
+    overlay.extend(app.window.ui.permission_bar.paint(prompt));
+    overlay.extend(app.window.ui.auth_bar.paint());
+    if let Some(ref info) = site_info {
+        overlay.extend(app.window.ui.site_info.paint(info));
+    }
+    overlay.extend(app.window.ui.status_bar.paint());
+    overlay.extend(app.window.ui.bookmarks_bar.paint_menu(&app.bookmarks));
+    let page = styled.as_ref().map(inspected_page);
+    overlay.extend(app.window.ui.devtools.paint(&app.devtools, page));
+    overlay.extend(app.window.ui.print_preview.paint());
+    
+    // The GPU process paints the frame; if it crashes it is restarted and paints it again
+    timer.enter(FramePhase::Composite, Instant::now());
+    let videos = video_layers(app, offset_y);
+    let images = list
+        .iter()
+        .chain(&overlay)
+        .filter_map(|command| match command {
+            DisplayCommand::Image { url, .. } => app.images.shared(url),
+            _ => None,
+        })
+        .collect();
+    gpu.paint(GpuFrame { list, videos, overlay, images });
+    app.record_paint_timing();
+    if let Some(report) = crashed {
+        app.page_crashed(report);
+        app.show_crash_page();
+        control.request_redraw(key);
+    }
+    let report = timer.finish(Instant::now());
+    control.record_frame(&report);
+    app.devtools.frames.record_frame(frame.frame_time, report.work());
+    
+    // Observers hear where elements were drawn; what their callbacks
+    // change is drawn in the next frame
+    app.update_intersections();
+    
+    // Idle work whose timeout passed runs even when frames leave no time for it
+    if app.run_idle_tasks(None) {
+        control.request_redraw(key);
+    }
+}
+
+/// Collect what the GPU process needs to composite the active page's videos
+///
+/// New pictures are sent along with the layers, in window coordinates; the
+/// GPU process keeps showing the last picture of a video that has none.
+fn video_layers(app: &mut BrowserApp, offset_y: f32) -> Vec<VideoLayer> {
+    let tab_id = app.window.tabs.active_id();
+    let shown = &mut app.window.video_layers;
+    let Some(content) = app.window.contents.get_mut(&tab_id) else {
+        shown.clear();
+        return Vec::new();
+    };
+    let mut layers = Vec::new();
+    let mut keys = HashSet::new();
+    for element in content.media.iter_mut().filter(|element| element.kind == MediaKind::Video) {
+        let key = (tab_id, element.path.clone());
+        // A video not shown in the last frame lost its backing store, so its
+        // current picture is sent again
+        let picture = match element.player.take_frame() {
+            Some(frame) => Some(frame),
+            None if !shown.contains(&key) => element.player.current_frame().cloned(),
+            None => None,
+        };
+        let layer = content.layers.element_layer(&element.path).and_then(|id| content.layers.get_layer(id));
+        let Some(layer) = layer else {
+            continue;
+        };
+        if picture.is_none() && !shown.contains(&key) {
+            continue;
+        }
+        let rect = Rect { y: layer.bounds.y - offset_y, ..layer.bounds };
+        layers.push(VideoLayer { id: video_layer_id(&key), rect, picture });
+        keys.insert(key);
+    }
+    *shown = keys;
+    layers
+}
+
+/// Names a video's backing store in the GPU process
+fn video_layer_id(key: &(TabId, ElementPath)) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Collect the GPU process's events, noting when it had to be restarted
+fn poll_gpu(app: &mut BrowserApp, gpu: &mut GpuProcess) {
+    for event in gpu.poll() {
+        if let GpuEvent::ContextLost { backend } = event {
+            app.devtools.console.warn(format!("GPU process crashed; restarted painting with {:?}", backend));
+        }
+    }
+}
+
+/// Ask for an idle period for a window while there is idle work or the
+/// active page has idle callbacks waiting
+fn request_idle_period(app: &mut BrowserApp, control: &mut WindowControl, key: WindowKey) {
+    if !app.idle_tasks.is_empty() || app.window.tabs.active_mut().js_context.has_idle_callbacks() {
+        control.request_idle_period(key);
+    }
+}
+
+/// Handle an event for the window whose state is current
+///
+/// Returns false to close the window.
+fn handle_window_event(
+    app: &mut BrowserApp,
+    control: &mut WindowControl,
+    key: WindowKey,
+    gpu: &mut GpuProcess,
+    event: WindowEvent,
+) -> bool {
+    match event {
+        WindowEvent::Resized(size) => {
+            println!("Window resized: {}x{}", size.width, size.height);
+            gpu.resize(size.width, size.height, app.window.scale_factor as f32);
+            let (width, height) = css_size(size, app.window.scale_factor);
+            app.resize(width, height);
+        }
+        WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+            // The GPU process already uses the new ratio; a resize may follow
+            println!("Scale factor changed: {}", scale_factor);
+            let (width, height) = gpu.size();
+            app.set_scale_factor(scale_factor, PhysicalSize::new(width, height));
+        }
+        WindowEvent::CloseRequested => {
+            println!("\nWindow closing...");
+            return false;
+        }
+        WindowEvent::CursorMoved { position, .. } => {
+            let position = position.to_logical::<f32>(app.window.scale_factor);
+            let offset_y = app.window.tabs.active().scroll.offset_y;
+            app.window.selection.extend(position.x, position.y + offset_y);
+            let cursor = app.handle_mouse_move(position.x, position.y);
+            control.set_cursor_icon(key, cursor);
+            if app.window.ui.devtools.is_picking() {
+                control.request_redraw(key);
+            }
+        }
+        WindowEvent::MouseWheel { delta, phase, .. } => {
+            app.note_user_input();
+            app.handle_wheel(delta, phase);
+            control.request_redraw(key);
+        }
+        WindowEvent::ModifiersChanged(modifiers) => {
+            app.modifiers = modifiers.state();
+        }
+        WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Left, .. } => {
+            let (x, y) = app.window.ui.input_handler.mouse_position();
+            app.window.selection.clear();
+            app.note_user_input();
+            app.handle_click(x, y);
+        }
+        WindowEvent::MouseInput { state: ElementState::Released, button: MouseButton::Left, .. } => {
+            app.window.selection.end();
+        }
+        WindowEvent::KeyboardInput { event, .. } => {
+            use winit::keyboard::{Key, NamedKey};
+            
+            if event.state != ElementState::Pressed {
+                return true;
+            }
+            app.note_user_input();
+            
+            let ctrl = app.modifiers.control_key();
+            let shift = app.modifiers.shift_key();
+            
+            // The print preview is modal: it takes every key while open
+            if app.handle_print_preview_key(&event.logical_key) {
+                control.request_redraw(key);
+                return true;
+            }
+            
+            // Ctrl+T / Ctrl+W / Ctrl+Tab / Ctrl+1..9: Tabs
+            if let Some(command) = TabCommand::from_key(&event.logical_key, ctrl, shift) {
+                app.handle_tab_command(command);
+                control.request_redraw(key);
+                return true;
+            }
+            
+            // Ctrl+N: New window
+            if ctrl && !shift && matches!(&event.logical_key, Key::Character(c) if c.eq_ignore_ascii_case("n")) {
+                app.new_window();
+                return true;
+            }
+            
+            // Ctrl+Shift+M: Move tab to a new window
+            if ctrl && shift && matches!(&event.logical_key, Key::Character(c) if c.eq_ignore_ascii_case("m")) {
+                app.move_tab_to_new_window();
+                control.request_redraw(key);
+                return true;
+            }
+            
+            // Ctrl+P: Print preview
+            if ctrl && matches!(&event.logical_key, Key::Character(c) if c.eq_ignore_ascii_case("p")) {
+                app.open_print_preview();
+                control.request_redraw(key);
+                return true;
+            }
+            
+            // Ctrl+F: Find in page
+            if ctrl && matches!(&event.logical_key, Key::Character(c) if c.eq_ignore_ascii_case("f")) {
+                app.window.ui.address_bar.set_focused(false);
+                app.window.ui.devtools.set_focused(false);
+                app.window.ui.find_bar.open();
+                app.run_find();
+                control.request_redraw(key);
+                return true;
+            }
+            
+            // Ctrl+D: Bookmark page
+            if ctrl && matches!(&event.logical_key, Key::Character(c) if c.eq_ignore_ascii_case("d")) {
+                app.toggle_bookmark();
+                control.request_redraw(key);
+                return true;
+            }
+            
+            // Ctrl+Alt+R: Reader mode; Ctrl+Alt+=/-/T/F: reader text size, theme and font
+            if ctrl && app.modifiers.alt_key() {
+                let handled = match &event.logical_key {
+                    Key::Character(c) if c.eq_ignore_ascii_case("r") => {
+                        app.toggle_reader_mode();
+                        true
+                    }
+                    Key::Character(c) if c == "=" || c == "+" => {
+                        app.update_reader_settings(|settings| {
+                            settings.increase_font_size();
+                        });
+                        true
+                    }
+                    Key::Character(c) if c == "-" => {
+                        app.update_reader_settings(|settings| {
+                            settings.decrease_font_size();
+                        });
+                        true
                     }
+                    Key::Character(c) if c.eq_ignore_ascii_case("t") => {
+                        app.update_reader_settings(|settings| settings.theme = settings.theme.next());
+                        true
+                    }
+                    Key::Character(c) if c.eq_ignore_ascii_case("f") => {
+                        app.update_reader_settings(ReaderSettings::toggle_font);
+                        true
+                    }
+                    _ => false,
+                };
+                if handled {
+                    control.request_redraw(key);
+                    return true;
                 }
             }
-            _ => {}
+            
+            // Ctrl+Shift+J: Allow or block JavaScript on this site
+            if ctrl && shift && matches!(&event.logical_key, Key::Character(c) if c.eq_ignore_ascii_case("j")) {
+                app.toggle_site_javascript();
+                control.request_redraw(key);
+                return true;
+            }
+            
+            // Ctrl+Shift+A: Turn content filtering on or off for this site
+            if ctrl && shift && matches!(&event.logical_key, Key::Character(c) if c.eq_ignore_ascii_case("a")) {
+                app.toggle_site_content_filtering();
+                control.request_redraw(key);
+                return true;
+            }
+            
+            // Ctrl+Shift+B: Toggle the bookmarks bar
+            if ctrl && shift && matches!(&event.logical_key, Key::Character(c) if c.eq_ignore_ascii_case("b")) {
+                let visible = !app.window.ui.is_bookmarks_bar_visible();
+                app.window.ui.set_bookmarks_bar_visible(visible);
+                let size = (app.window.ui.bounds.width, app.window.ui.bounds.height);
+                app.resize(size.0, size.1);
+                control.request_redraw(key);
+                return true;
+            }
+            
+            // Ctrl+Shift+Backspace: Clear data for this site
+            if ctrl && shift && event.logical_key == Key::Named(NamedKey::Backspace) {
+                app.clear_site_data();
+                return true;
+            }
+            
+            // Ctrl+Shift+Delete: Clear recent history
+            if ctrl && shift && event.logical_key == Key::Named(NamedKey::Delete) {
+                app.clear_history(TimeRange::LastHour);
+                return true;
+            }
+            
+            // Ctrl+Shift+C: Pick an element to inspect
+            if ctrl && shift && matches!(&event.logical_key, Key::Character(c) if c.eq_ignore_ascii_case("c")) {
+                if !app.window.ui.devtools.is_open() {
+                    app.window.ui.devtools.toggle();
+                    app.handle_devtools_action(DevToolsAction::LayoutChanged);
+                }
+                let picking = app.window.ui.devtools.is_picking();
+                app.window.ui.devtools.set_picking(!picking);
+                control.request_redraw(key);
+                return true;
+            }
+            
+            // Ctrl+C / Ctrl+X / Ctrl+V: Clipboard
+            if ctrl && app.handle_clipboard_key(&event.logical_key) {
+                return true;
+            }
+            
+            // Typing in the DevTools console
+            if app.handle_devtools_key(&event.logical_key) {
+                control.request_redraw(key);
+                return true;
+            }
+            
+            // Typing in the sign-in bar
+            if app.handle_auth_key(&event.logical_key) {
+                control.request_redraw(key);
+                return true;
+            }
+            
+            // Typing in the find bar
+            if app.handle_find_key(&event.logical_key) {
+                control.request_redraw(key);
+                return true;
+            }
+            
+            // Typing in the focused address bar
+            if app.handle_address_bar_key(&event.logical_key) {
+                control.request_redraw(key);
+                return true;
+            }
+            
+            // Typing in a contenteditable region of the page
+            let editing = app.guard_active_page(PipelinePhase::Input, |app| app.handle_editing_key(&event.logical_key));
+            if editing.unwrap_or(true) {
+                control.request_redraw(key);
+                return true;
+            }
+            
+            // Alt+Left / Alt+Right: Back / forward, F5 / Ctrl+R: Reload,
+            // Ctrl+F5 / Ctrl+Shift+R: Hard reload, ESC while loading: Stop
+            let alt = app.modifiers.alt_key();
+            if let Some(command) = NavCommand::from_key(&event.logical_key, ctrl, shift, alt, app.window.loading) {
+                app.handle_nav_command(command);
+                control.request_redraw(key);
+                return true;
+            }
+            
+            if event.logical_key == Key::Named(NamedKey::Escape) {
+                println!("\nESC pressed. Exiting...");
+                control.exit();
+                return false;
+            }
+            
+            // F12: Toggle DevTools
+            if event.logical_key == Key::Named(NamedKey::F12) {
+                app.window.ui.devtools.toggle();
+                app.handle_devtools_action(DevToolsAction::LayoutChanged);
+                control.request_redraw(key);
+                return true;
+            }
+            
+            // Arrows, PageUp/PageDown, Home/End, Space: Scroll the page
+            if app.window.tabs.active_mut().scroll.handle_key(&event.logical_key, shift) {
+                control.request_redraw(key);
+            }
         }
-        true
-    }).expect("Event loop error");
+        _ => {}
+    }
+    true
 }
@@ -194,6 +194,13 @@ fn extract_render_data(display_list: &[DisplayCommand]) -> (Vec<(Rect, Color)>,
             DisplayCommand::Image { .. } => {
                 // Image rendering not yet implemented
             }
+            DisplayCommand::Highlight { color, rect } => {
+                backgrounds.push((*rect, *color));
+            }
+            DisplayCommand::PushClip { .. } | DisplayCommand::PopClip { .. } => {
+                // Clipping not yet implemented
+            }
+            DisplayCommand::PushFixed { .. } | DisplayCommand::PopFixed { .. } => {}
         }
     }
     
@@ -0,0 +1,601 @@
+// Bookmarks: folders, rename/remove, Netscape HTML import/export and
+// persistence through local storage
+
+use crate::storage::{LocalStorage, StorageError};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use url::Url;
+
+/// Local storage key the bookmarks are saved under
+pub const BOOKMARKS_STORAGE_KEY: &str = "browser.bookmarks";
+
+/// Identifies a bookmark or folder
+pub type BookmarkId = u64;
+
+/// Bookmark errors
+#[derive(Debug, Clone, PartialEq)]
+pub enum BookmarkError {
+    /// No bookmark or folder with this id
+    NotFound(BookmarkId),
+    /// A folder cannot be moved into itself or a descendant
+    InvalidFolder(BookmarkId),
+    /// Imported or stored data could not be read
+    Parse(String),
+    /// Saving to storage failed
+    Storage(StorageError),
+}
+
+impl std::fmt::Display for BookmarkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BookmarkError::NotFound(id) => write!(f, "No bookmark with id {}", id),
+            BookmarkError::InvalidFolder(id) => write!(f, "Invalid folder {}", id),
+            BookmarkError::Parse(msg) => write!(f, "Bookmark parse error: {}", msg),
+            BookmarkError::Storage(e) => write!(f, "Bookmark storage error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for BookmarkError {}
+
+/// A single bookmark
+#[derive(Debug, Clone)]
+pub struct Bookmark {
+    pub id: BookmarkId,
+    pub url: Url,
+    pub title: String,
+    /// Containing folder; `None` for the bookmarks bar
+    pub folder: Option<BookmarkId>,
+    pub created_at: SystemTime,
+}
+
+/// A bookmark folder
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookmarkFolder {
+    pub id: BookmarkId,
+    pub title: String,
+    /// Parent folder; `None` for the bookmarks bar
+    pub parent: Option<BookmarkId>,
+}
+
+/// An entry directly inside a folder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookmarkItem {
+    Bookmark(BookmarkId),
+    Folder(BookmarkId),
+}
+
+/// Bookmark store
+#[derive(Debug, Clone)]
+pub struct BookmarkManager {
+    bookmarks: Vec<Bookmark>,
+    folders: Vec<BookmarkFolder>,
+    next_id: BookmarkId,
+}
+
+impl BookmarkManager {
+    /// Create a new bookmark manager
+    pub fn new() -> Self {
+        Self {
+            bookmarks: Vec::new(),
+            folders: Vec::new(),
+            next_id: 1,
+        }
+    }
+
+    /// Add a bookmark to the bookmarks bar
+    ///
+    /// Returns false if the URL is already bookmarked.
+    pub fn add(&mut self, url: Url, title: String) -> bool {
+        // Check if already bookmarked
+        if self.is_bookmarked(&url) {
+            return false;
+        }
+        self.insert_bookmark(url, title, None, SystemTime::now());
+        true
+    }
+
+    /// Add a bookmark to a folder, returning its id
+    pub fn add_to_folder(
+        &mut self,
+        url: Url,
+        title: String,
+        folder: Option<BookmarkId>,
+    ) -> Result<BookmarkId, BookmarkError> {
+        self.check_folder(folder)?;
+        if let Some(existing) = self.find(&url) {
+            return Ok(existing.id);
+        }
+        Ok(self.insert_bookmark(url, title, folder, SystemTime::now()))
+    }
+
+    /// Create a folder, returning its id
+    pub fn create_folder(
+        &mut self,
+        title: String,
+        parent: Option<BookmarkId>,
+    ) -> Result<BookmarkId, BookmarkError> {
+        self.check_folder(parent)?;
+        let id = self.allocate_id();
+        self.folders.push(BookmarkFolder { id, title, parent });
+        Ok(id)
+    }
+
+    /// Remove a bookmark by URL
+    pub fn remove(&mut self, url: &Url) -> bool {
+        let len_before = self.bookmarks.len();
+        self.bookmarks.retain(|b| &b.url != url);
+        self.bookmarks.len() < len_before
+    }
+
+    /// Remove a bookmark, or a folder with everything in it
+    pub fn remove_item(&mut self, id: BookmarkId) -> bool {
+        if self.bookmarks.iter().any(|b| b.id == id) {
+            self.bookmarks.retain(|b| b.id != id);
+            return true;
+        }
+        if self.folder(id).is_none() {
+            return false;
+        }
+
+        let doomed: Vec<BookmarkId> = self
+            .folders
+            .iter()
+            .filter(|f| self.is_within(f.id, id))
+            .map(|f| f.id)
+            .collect();
+        self.bookmarks.retain(|b| !b.folder.is_some_and(|f| doomed.contains(&f)));
+        self.folders.retain(|f| !doomed.contains(&f.id));
+        true
+    }
+
+    /// Rename a bookmark or folder
+    pub fn rename(&mut self, id: BookmarkId, title: String) -> Result<(), BookmarkError> {
+        if let Some(bookmark) = self.bookmarks.iter_mut().find(|b| b.id == id) {
+            bookmark.title = title;
+        } else if let Some(folder) = self.folders.iter_mut().find(|f| f.id == id) {
+            folder.title = title;
+        } else {
+            return Err(BookmarkError::NotFound(id));
+        }
+        Ok(())
+    }
+
+    /// Move a bookmark or folder into another folder
+    pub fn move_item(&mut self, id: BookmarkId, folder: Option<BookmarkId>) -> Result<(), BookmarkError> {
+        self.check_folder(folder)?;
+        if let Some(bookmark) = self.bookmarks.iter_mut().find(|b| b.id == id) {
+            bookmark.folder = folder;
+            return Ok(());
+        }
+        if self.folder(id).is_none() {
+            return Err(BookmarkError::NotFound(id));
+        }
+        if folder.is_some_and(|target| self.is_within(target, id)) {
+            return Err(BookmarkError::InvalidFolder(id));
+        }
+        if let Some(f) = self.folders.iter_mut().find(|f| f.id == id) {
+            f.parent = folder;
+        }
+        Ok(())
+    }
+
+    /// Check if URL is bookmarked
+    pub fn is_bookmarked(&self, url: &Url) -> bool {
+        self.find(url).is_some()
+    }
+
+    /// Find the bookmark for a URL
+    pub fn find(&self, url: &Url) -> Option<&Bookmark> {
+        self.bookmarks.iter().find(|b| &b.url == url)
+    }
+
+    /// Get a bookmark by id
+    pub fn get(&self, id: BookmarkId) -> Option<&Bookmark> {
+        self.bookmarks.iter().find(|b| b.id == id)
+    }
+
+    /// Get a folder by id
+    pub fn folder(&self, id: BookmarkId) -> Option<&BookmarkFolder> {
+        self.folders.iter().find(|f| f.id == id)
+    }
+
+    /// Get all bookmarks
+    pub fn all(&self) -> &[Bookmark] {
+        &self.bookmarks
+    }
+
+    /// Get all folders
+    pub fn folders(&self) -> &[BookmarkFolder] {
+        &self.folders
+    }
+
+    /// Folders then bookmarks directly inside a folder (`None` for the bar)
+    pub fn children(&self, folder: Option<BookmarkId>) -> Vec<BookmarkItem> {
+        self.folders
+            .iter()
+            .filter(|f| f.parent == folder)
+            .map(|f| BookmarkItem::Folder(f.id))
+            .chain(
+                self.bookmarks
+                    .iter()
+                    .filter(|b| b.folder == folder)
+                    .map(|b| BookmarkItem::Bookmark(b.id)),
+            )
+            .collect()
+    }
+
+    /// Display title of a bookmark or folder
+    pub fn title(&self, item: BookmarkItem) -> Option<&str> {
+        match item {
+            BookmarkItem::Bookmark(id) => self.get(id).map(|b| b.title.as_str()),
+            BookmarkItem::Folder(id) => self.folder(id).map(|f| f.title.as_str()),
+        }
+    }
+
+    /// Clear all bookmarks
+    pub fn clear(&mut self) {
+        self.bookmarks.clear();
+        self.folders.clear();
+    }
+
+    /// Export as a Netscape bookmarks HTML file
+    pub fn export_html(&self) -> String {
+        let mut out = String::from(
+            "<!DOCTYPE NETSCAPE-Bookmark-file-1>\n\
+             <META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">\n\
+             <TITLE>Bookmarks</TITLE>\n\
+             <H1>Bookmarks</H1>\n",
+        );
+        self.export_folder(None, 0, &mut out);
+        out
+    }
+
+    fn export_folder(&self, folder: Option<BookmarkId>, depth: usize, out: &mut String) {
+        let indent = "    ".repeat(depth);
+        out.push_str(&format!("{}<DL><p>\n", indent));
+        for item in self.children(folder) {
+            match item {
+                BookmarkItem::Folder(id) => {
+                    let title = self.folder(id).map(|f| f.title.as_str()).unwrap_or("");
+                    out.push_str(&format!("{}    <DT><H3>{}</H3>\n", indent, escape_html(title)));
+                    self.export_folder(Some(id), depth + 1, out);
+                }
+                BookmarkItem::Bookmark(id) => {
+                    if let Some(b) = self.get(id) {
+                        out.push_str(&format!(
+                            "{}    <DT><A HREF=\"{}\" ADD_DATE=\"{}\">{}</A>\n",
+                            indent,
+                            escape_html(b.url.as_str()),
+                            unix_seconds(b.created_at),
+                            escape_html(&b.title)
+                        ));
+                    }
+                }
+            }
+        }
+        out.push_str(&format!("{}</DL><p>\n", indent));
+    }
+
+    /// Import a Netscape bookmarks HTML file into a folder
+    ///
+    /// Already bookmarked URLs and links that don't parse are skipped.
+    /// Returns the number of bookmarks added.
+    pub fn import_html(&mut self, html: &str, into: Option<BookmarkId>) -> Result<usize, BookmarkError> {
+        self.check_folder(into)?;
+        let token = Regex::new(
+            r#"(?is)<h3[^>]*>(.*?)</h3>|<a\s([^>]*)>(.*?)</a>|<dl[^>]*>|</dl>"#,
+        )
+        .map_err(|e| BookmarkError::Parse(e.to_string()))?;
+        let href = Regex::new(r#"(?i)\bhref\s*=\s*"([^"]*)""#).map_err(|e| BookmarkError::Parse(e.to_string()))?;
+        let add_date = Regex::new(r#"(?i)\badd_date\s*=\s*"(\d+)""#).map_err(|e| BookmarkError::Parse(e.to_string()))?;
+
+        // The top-level <DL> maps to `into`; each <H3> opens the next <DL>
+        let mut stack: Vec<Option<BookmarkId>> = Vec::new();
+        let mut pending_folder: Option<BookmarkId> = None;
+        let mut added = 0;
+
+        for caps in token.captures_iter(html) {
+            let tag = caps.get(0).map(|m| m.as_str()).unwrap_or("");
+            let current = stack.last().copied().unwrap_or(into);
+
+            if let Some(title) = caps.get(1) {
+                pending_folder = Some(self.create_folder(unescape_html(title.as_str().trim()), current)?);
+            } else if let Some(attrs) = caps.get(2) {
+                let url = href
+                    .captures(attrs.as_str())
+                    .and_then(|c| Url::parse(&unescape_html(&c[1])).ok());
+                let Some(url) = url else { continue };
+                if self.is_bookmarked(&url) {
+                    continue;
+                }
+                let created_at = add_date
+                    .captures(attrs.as_str())
+                    .and_then(|c| c[1].parse::<u64>().ok())
+                    .map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+                    .unwrap_or_else(SystemTime::now);
+                let title = unescape_html(caps.get(3).map(|m| m.as_str().trim()).unwrap_or(""));
+                self.insert_bookmark(url, title, current, created_at);
+                added += 1;
+            } else if tag.starts_with("</") {
+                stack.pop();
+            } else if stack.is_empty() {
+                stack.push(into);
+            } else {
+                stack.push(pending_folder.take().or(current));
+            }
+        }
+
+        Ok(added)
+    }
+
+    /// Save the bookmarks to local storage
+    pub fn save(&self, storage: &mut LocalStorage) -> Result<(), BookmarkError> {
+        let stored = StoredBookmarks {
+            folders: self
+                .folders
+                .iter()
+                .map(|f| StoredFolder { id: f.id, title: f.title.clone(), parent: f.parent })
+                .collect(),
+            bookmarks: self
+                .bookmarks
+                .iter()
+                .map(|b| StoredBookmark {
+                    id: b.id,
+                    url: b.url.to_string(),
+                    title: b.title.clone(),
+                    folder: b.folder,
+                    created_at: unix_seconds(b.created_at),
+                })
+                .collect(),
+        };
+        let json = serde_json::to_string(&stored).map_err(|e| BookmarkError::Parse(e.to_string()))?;
+        storage
+            .set_item(BOOKMARKS_STORAGE_KEY.to_string(), json)
+            .map_err(BookmarkError::Storage)
+    }
+
+    /// Load bookmarks saved to local storage
+    ///
+    /// Returns an empty store if nothing was saved.
+    pub fn load(storage: &LocalStorage) -> Result<Self, BookmarkError> {
+        let Some(json) = storage.get_item(BOOKMARKS_STORAGE_KEY) else {
+            return Ok(Self::new());
+        };
+        let stored: StoredBookmarks =
+            serde_json::from_str(&json).map_err(|e| BookmarkError::Parse(e.to_string()))?;
+
+        let mut manager = Self::new();
+        manager.folders = stored
+            .folders
+            .into_iter()
+            .map(|f| BookmarkFolder { id: f.id, title: f.title, parent: f.parent })
+            .collect();
+        manager.bookmarks = stored
+            .bookmarks
+            .into_iter()
+            .filter_map(|b| {
+                Some(Bookmark {
+                    id: b.id,
+                    url: Url::parse(&b.url).ok()?,
+                    title: b.title,
+                    folder: b.folder,
+                    created_at: UNIX_EPOCH + Duration::from_secs(b.created_at),
+                })
+            })
+            .collect();
+        manager.next_id = manager
+            .folders
+            .iter()
+            .map(|f| f.id)
+            .chain(manager.bookmarks.iter().map(|b| b.id))
+            .max()
+            .unwrap_or(0)
+            + 1;
+        Ok(manager)
+    }
+
+    fn allocate_id(&mut self) -> BookmarkId {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    fn insert_bookmark(
+        &mut self,
+        url: Url,
+        title: String,
+        folder: Option<BookmarkId>,
+        created_at: SystemTime,
+    ) -> BookmarkId {
+        let id = self.allocate_id();
+        self.bookmarks.push(Bookmark { id, url, title, folder, created_at });
+        id
+    }
+
+    fn check_folder(&self, folder: Option<BookmarkId>) -> Result<(), BookmarkError> {
+        match folder {
+            Some(id) if self.folder(id).is_none() => Err(BookmarkError::NotFound(id)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Is `folder` the same as or nested inside `ancestor`
+    fn is_within(&self, folder: BookmarkId, ancestor: BookmarkId) -> bool {
+        let mut current = Some(folder);
+        while let Some(id) = current {
+            if id == ancestor {
+                return true;
+            }
+            current = self.folder(id).and_then(|f| f.parent);
+        }
+        false
+    }
+}
+
+impl Default for BookmarkManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredBookmarks {
+    folders: Vec<StoredFolder>,
+    bookmarks: Vec<StoredBookmark>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredFolder {
+    id: BookmarkId,
+    title: String,
+    parent: Option<BookmarkId>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredBookmark {
+    id: BookmarkId,
+    url: String,
+    title: String,
+    folder: Option<BookmarkId>,
+    created_at: u64,
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn unescape_html(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_url(path: &str) -> Url {
+        Url::parse(&format!("http://example.com{}", path)).unwrap()
+    }
+
+    #[test]
+    fn test_bookmark_manager() {
+        let mut bookmarks = BookmarkManager::new();
+        
+        let url = test_url("/page1");
+        assert!(bookmarks.add(url.clone(), "Page 1".to_string()));
+        assert!(bookmarks.is_bookmarked(&url));
+
+        // Can't add duplicate
+        assert!(!bookmarks.add(url.clone(), "Page 1".to_string()));
+
+        assert_eq!(bookmarks.all().len(), 1);
+
+        // Remove bookmark
+        assert!(bookmarks.remove(&url));
+        assert!(!bookmarks.is_bookmarked(&url));
+        assert_eq!(bookmarks.all().len(), 0);
+    }
+
+    #[test]
+    fn test_folders_rename_and_remove() {
+        let mut bookmarks = BookmarkManager::new();
+        let news = bookmarks.create_folder("News".to_string(), None).unwrap();
+        let local = bookmarks.create_folder("Local".to_string(), Some(news)).unwrap();
+        let paper = bookmarks
+            .add_to_folder(test_url("/paper"), "Paper".to_string(), Some(local))
+            .unwrap();
+        bookmarks.add(test_url("/home"), "Home".to_string());
+
+        assert_eq!(bookmarks.children(None).len(), 2);
+        assert_eq!(bookmarks.children(Some(local)), vec![BookmarkItem::Bookmark(paper)]);
+
+        bookmarks.rename(paper, "Daily Paper".to_string()).unwrap();
+        assert_eq!(bookmarks.title(BookmarkItem::Bookmark(paper)), Some("Daily Paper"));
+        assert_eq!(bookmarks.rename(999, "x".to_string()), Err(BookmarkError::NotFound(999)));
+
+        // A folder can't be moved into its own subfolder
+        assert_eq!(bookmarks.move_item(news, Some(local)), Err(BookmarkError::InvalidFolder(news)));
+
+        assert!(bookmarks.remove_item(news));
+        assert!(bookmarks.folders().is_empty());
+        assert!(!bookmarks.is_bookmarked(&test_url("/paper")));
+        assert!(bookmarks.is_bookmarked(&test_url("/home")));
+    }
+
+    #[test]
+    fn test_html_round_trip() {
+        let mut bookmarks = BookmarkManager::new();
+        let dev = bookmarks.create_folder("Dev & Docs".to_string(), None).unwrap();
+        bookmarks
+            .add_to_folder(test_url("/rust?a=1&b=2"), "Rust <docs>".to_string(), Some(dev))
+            .unwrap();
+        bookmarks.add(test_url("/home"), "Home".to_string());
+
+        let html = bookmarks.export_html();
+        assert!(html.starts_with("<!DOCTYPE NETSCAPE-Bookmark-file-1>"));
+        assert!(html.contains("<H3>Dev &amp; Docs</H3>"));
+
+        let mut imported = BookmarkManager::new();
+        assert_eq!(imported.import_html(&html, None).unwrap(), 2);
+        let folder = &imported.folders()[0];
+        assert_eq!(folder.title, "Dev & Docs");
+        let rust = imported.find(&test_url("/rust?a=1&b=2")).unwrap();
+        assert_eq!(rust.title, "Rust <docs>");
+        assert_eq!(rust.folder, Some(folder.id));
+        assert_eq!(imported.find(&test_url("/home")).unwrap().folder, None);
+
+        // Importing again skips duplicates
+        assert_eq!(imported.import_html(&html, None).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_import_other_browser_export() {
+        let html = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<DL><p>
+    <DT><H3 ADD_DATE="1600000000" PERSONAL_TOOLBAR_FOLDER="true">Bookmarks bar</H3>
+    <DL><p>
+        <DT><A HREF="https://www.rust-lang.org/" ADD_DATE="1600000001" ICON="data:">Rust</A>
+    </DL><p>
+    <DT><a href="not a url">Broken</a>
+</DL><p>"#;
+        let mut bookmarks = BookmarkManager::new();
+        assert_eq!(bookmarks.import_html(html, None).unwrap(), 1);
+        let rust = &bookmarks.all()[0];
+        assert_eq!(rust.title, "Rust");
+        assert_eq!(unix_seconds(rust.created_at), 1600000001);
+        assert_eq!(bookmarks.folder(rust.folder.unwrap()).unwrap().title, "Bookmarks bar");
+    }
+
+    #[test]
+    fn test_persist_to_local_storage() {
+        let mut storage = LocalStorage::new();
+        assert!(BookmarkManager::load(&storage).unwrap().all().is_empty());
+
+        let mut bookmarks = BookmarkManager::new();
+        let folder = bookmarks.create_folder("Reading".to_string(), None).unwrap();
+        bookmarks
+            .add_to_folder(test_url("/article"), "Article".to_string(), Some(folder))
+            .unwrap();
+        bookmarks.save(&mut storage).unwrap();
+
+        let mut loaded = BookmarkManager::load(&storage).unwrap();
+        assert_eq!(loaded.folders(), bookmarks.folders());
+        assert_eq!(loaded.find(&test_url("/article")).unwrap().folder, Some(folder));
+
+        // New ids don't collide with loaded ones
+        let id = loaded.create_folder("More".to_string(), None).unwrap();
+        assert!(id > folder);
+    }
+}
@@ -1,5 +1,10 @@
 // Canvas 2D API - Phase 7 Task 3
 
+use crate::renderer::font_manager::FontManager;
+use crate::renderer::glyph_cache::{GlyphCache, GlyphInfo, GlyphKey};
+use std::collections::HashMap;
+use std::sync::Arc;
+
 /// Canvas element with 2D drawing context
 pub struct Canvas {
     /// Width in pixels
@@ -49,9 +54,193 @@ impl Canvas {
     
     /// Render context to pixels
     pub fn render(&mut self) {
-        if let Some(ctx) = &self.context {
-            self.pixels = ctx.get_image_data();
+        if let Some(ctx) = &mut self.context {
+            ctx.sync_pixels();
+            self.pixels = ctx.pixels().to_vec();
+        }
+    }
+    
+    /// Encode the canvas as a `data:` URL (`toDataURL`)
+    ///
+    /// Types other than PNG and JPEG fall back to PNG, and a canvas with no
+    /// pixels is `data:,`.
+    pub fn to_data_url(&mut self, mime_type: &str, quality: Option<f64>) -> String {
+        self.to_blob(mime_type, quality)
+            .map(|image| image.to_data_url())
+            .unwrap_or_else(|| "data:,".to_string())
+    }
+    
+    /// Encode the canvas as image bytes (`toBlob`), or `None` if it has no pixels
+    pub fn to_blob(&mut self, mime_type: &str, quality: Option<f64>) -> Option<EncodedImage> {
+        self.render();
+        EncodedImage::encode(&self.pixels, self.width, self.height, ImageEncoding::from_mime_type(mime_type), quality)
+            .ok()
+    }
+}
+
+/// Canvas that is not part of a document (`OffscreenCanvas`)
+///
+/// It owns its context outright, so it can be handed to a worker thread and
+/// drawn there, its frames sent back with `transfer_to_image_bitmap`.
+pub struct OffscreenCanvas {
+    /// Width in pixels
+    width: u32,
+    /// Height in pixels
+    height: u32,
+    /// 2D rendering context
+    context: Option<CanvasRenderingContext2D>,
+}
+
+impl OffscreenCanvas {
+    /// Create a new offscreen canvas
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height, context: None }
+    }
+    
+    /// Get 2D rendering context
+    pub fn get_context_2d(&mut self) -> &mut CanvasRenderingContext2D {
+        let (width, height) = (self.width, self.height);
+        self.context.get_or_insert_with(|| CanvasRenderingContext2D::new(width, height))
+    }
+    
+    /// Get width
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    
+    /// Get height
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+    
+    /// Resize, which resets the context's backing store and state
+    pub fn set_width(&mut self, width: u32) {
+        self.width = width;
+        self.reset_context();
+    }
+    
+    /// Resize, which resets the context's backing store and state
+    pub fn set_height(&mut self, height: u32) {
+        self.height = height;
+        self.reset_context();
+    }
+    
+    fn reset_context(&mut self) {
+        if let Some(ctx) = &mut self.context {
+            *ctx = CanvasRenderingContext2D::new(self.width, self.height);
+        }
+    }
+    
+    /// Take what has been drawn as a frame, leaving the canvas transparent black
+    pub fn transfer_to_image_bitmap(&mut self) -> Result<ImageData, CanvasError> {
+        let width = self.width;
+        let ctx = self
+            .context
+            .as_mut()
+            .ok_or_else(|| CanvasError::InvalidState("offscreen canvas has no context".to_string()))?;
+        ImageData::from_data(ctx.take_bitmap(), width, None)
+    }
+    
+    /// Encode the canvas as image bytes (`convertToBlob`)
+    ///
+    /// Types other than PNG and JPEG fall back to PNG.
+    pub fn convert_to_blob(&mut self, mime_type: &str, quality: Option<f64>) -> Result<EncodedImage, CanvasError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(CanvasError::IndexSize("offscreen canvas has no pixels".to_string()));
+        }
+        let encoding = ImageEncoding::from_mime_type(mime_type);
+        match &mut self.context {
+            Some(ctx) => {
+                ctx.sync_pixels();
+                EncodedImage::encode(ctx.pixels(), self.width, self.height, encoding, quality)
+            }
+            None => {
+                let blank = vec![0; self.width as usize * self.height as usize * 4];
+                EncodedImage::encode(&blank, self.width, self.height, encoding, quality)
+            }
+        }
+    }
+}
+
+/// Formats a canvas can be encoded in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageEncoding {
+    Png,
+    Jpeg,
+}
+
+impl ImageEncoding {
+    /// Pick the format for a MIME type, PNG unless it names one we encode
+    pub fn from_mime_type(mime_type: &str) -> Self {
+        match mime_type.trim().to_ascii_lowercase().as_str() {
+            "image/jpeg" => ImageEncoding::Jpeg,
+            _ => ImageEncoding::Png,
+        }
+    }
+    
+    /// The MIME type of the format
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            ImageEncoding::Png => "image/png",
+            ImageEncoding::Jpeg => "image/jpeg",
+        }
+    }
+}
+
+/// JPEG quality used when none in 0..=1 is given
+const DEFAULT_JPEG_QUALITY: f64 = 0.92;
+
+/// Canvas pixels encoded as an image file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodedImage {
+    /// Format of the bytes
+    pub encoding: ImageEncoding,
+    /// The encoded file
+    pub data: Vec<u8>,
+}
+
+impl EncodedImage {
+    /// Encode RGBA pixels; JPEG has no alpha, so they are composited onto black
+    pub fn encode(pixels: &[u8], width: u32, height: u32, encoding: ImageEncoding, quality: Option<f64>)
+        -> Result<Self, CanvasError>
+    {
+        use image::ImageEncoder;
+        
+        if width == 0 || height == 0 {
+            return Err(CanvasError::IndexSize("image has no pixels".to_string()));
         }
+        let mut data = Vec::new();
+        let result = match encoding {
+            ImageEncoding::Png => image::codecs::png::PngEncoder::new(&mut data)
+                .write_image(pixels, width, height, image::ColorType::Rgba8),
+            ImageEncoding::Jpeg => {
+                let quality = quality.filter(|q| (0.0..=1.0).contains(q)).unwrap_or(DEFAULT_JPEG_QUALITY);
+                let rgb: Vec<u8> = pixels
+                    .chunks_exact(4)
+                    .flat_map(|p| [0, 1, 2].map(|i| (p[i] as u32 * p[3] as u32 / 255) as u8))
+                    .collect();
+                image::codecs::jpeg::JpegEncoder::new_with_quality(&mut data, (quality * 100.0).round().max(1.0) as u8)
+                    .write_image(&rgb, width, height, image::ColorType::Rgb8)
+            }
+        };
+        result.map_err(|e| CanvasError::Encoding(e.to_string()))?;
+        Ok(Self { encoding, data })
+    }
+    
+    /// The MIME type of the bytes
+    pub fn mime_type(&self) -> &'static str {
+        self.encoding.mime_type()
+    }
+    
+    /// The image as a base64 `data:` URL
+    pub fn to_data_url(&self) -> String {
+        use base64::Engine;
+        
+        format!(
+            "data:{};base64,{}",
+            self.mime_type(),
+            base64::engine::general_purpose::STANDARD.encode(&self.data)
+        )
     }
 }
 
@@ -69,21 +258,172 @@ pub struct CanvasRenderingContext2D {
     state_stack: Vec<DrawingState>,
     /// Current state
     state: DrawingState,
+    /// Fonts and glyphs for text, loaded when text is first drawn or measured
+    text: Option<CanvasText>,
+    /// Drawing recorded for a GPU backing store, while the context is accelerated
+    commands: Option<CommandLog>,
+}
+
+/// Drawing recorded while a context is accelerated
+#[derive(Default)]
+struct CommandLog {
+    /// Commands the GPU has yet to take
+    pending: Vec<CanvasCommand>,
+    /// Commands not yet replayed into the CPU backing store
+    unsynced: Vec<CanvasCommand>,
+}
+
+/// Commands kept for the CPU backing store before they are replayed into it
+const MAX_UNSYNCED_COMMANDS: usize = 1024;
+
+/// Drawing recorded for a GPU backing store
+///
+/// Geometry is in canvas pixels, already transformed, and colors carry the
+/// global alpha they were drawn with.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CanvasCommand {
+    /// Fill closed polygons with a color by a fill rule
+    Fill { subpaths: Vec<Subpath>, rule: FillRule, color: Color },
+    /// Stretch an RGBA image over a parallelogram, corners clockwise from the image's top left
+    Image { pixels: Arc<[u8]>, width: u32, height: u32, corners: [(f32, f32); 4] },
+    /// Make a parallelogram transparent, corners in order around it
+    Clear { corners: [(f32, f32); 4] },
+}
+
+/// Fonts and rasterized glyphs for a context's text
+struct CanvasText {
+    font_manager: FontManager,
+    glyph_cache: GlyphCache,
+    /// Glyph cache ids of the families loaded so far
+    font_ids: HashMap<String, usize>,
+}
+
+/// Glyph placed along a line of text
+struct PlacedGlyph {
+    /// Pen position from the start of the line
+    pen_x: f32,
+    info: GlyphInfo,
+    bitmap: Vec<u8>,
+}
+
+/// A line of text laid out in a font, before alignment
+struct TextRun {
+    glyphs: Vec<PlacedGlyph>,
+    /// Advance of the whole line
+    width: f32,
+    /// Font ascent above and descent below the baseline, both positive
+    ascent: f32,
+    descent: f32,
+}
+
+impl TextRun {
+    /// Offset from the point text is drawn at to where the line starts on its alphabetic baseline
+    fn anchor(&self, align: TextAlign, baseline: TextBaseline) -> (f32, f32) {
+        // Text runs left to right, so start is left and end is right
+        let x = match align {
+            TextAlign::Start | TextAlign::Left => 0.0,
+            TextAlign::End | TextAlign::Right => -self.width,
+            TextAlign::Center => -self.width / 2.0,
+        };
+        let y = match baseline {
+            TextBaseline::Top => self.ascent,
+            TextBaseline::Hanging => self.ascent * 0.8,
+            TextBaseline::Middle => (self.ascent - self.descent) / 2.0,
+            TextBaseline::Alphabetic => 0.0,
+            TextBaseline::Ideographic | TextBaseline::Bottom => -self.descent,
+        };
+        (x, y)
+    }
+}
+
+/// Font given by the context's `font` property
+#[derive(Debug, Clone, PartialEq)]
+pub struct CanvasFont {
+    /// Size in pixels
+    pub size: f32,
+    /// First family of the family list
+    pub family: String,
+}
+
+impl CanvasFont {
+    /// Parse a CSS `font` shorthand, such as `bold 16px "Helvetica Neue", sans-serif`
+    ///
+    /// Relative sizes are taken against the 10px default. Returns `None`
+    /// for values the property would ignore.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut tokens = value.split_whitespace();
+        let size = loop {
+            let token = tokens.next()?;
+            // Line height after the size has no effect on canvas text
+            let size = token.split('/').next().unwrap_or(token);
+            if let Some(size) = Self::parse_size(size) {
+                break size;
+            }
+            let keyword = matches!(
+                token,
+                "normal" | "italic" | "oblique" | "small-caps" | "bold" | "bolder" | "lighter"
+            ) || token.parse::<u16>().is_ok_and(|weight| (1..=1000).contains(&weight));
+            if !keyword {
+                return None;
+            }
+        };
+        let families = tokens.collect::<Vec<_>>().join(" ");
+        let family = families.split(',').next()?.trim().trim_matches(|c| c == '"' || c == '\'');
+        if family.is_empty() {
+            return None;
+        }
+        Some(Self { size, family: family.to_string() })
+    }
+    
+    fn parse_size(value: &str) -> Option<f32> {
+        let units = [("px", 1.0), ("pt", 4.0 / 3.0), ("rem", 10.0), ("em", 10.0), ("%", 0.1)];
+        let (number, scale) = units.iter().find_map(|(unit, scale)| Some((value.strip_suffix(unit)?, *scale)))?;
+        let size = number.parse::<f32>().ok()? * scale;
+        (size.is_finite() && size >= 0.0).then_some(size)
+    }
+}
+
+/// Measurements of a line of text (`TextMetrics`)
+///
+/// Distances are from the point the text would be drawn at, for the
+/// context's alignment and baseline; bounding box sides count positive
+/// outwards.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TextMetrics {
+    pub width: f32,
+    pub actual_bounding_box_left: f32,
+    pub actual_bounding_box_right: f32,
+    pub actual_bounding_box_ascent: f32,
+    pub actual_bounding_box_descent: f32,
+    pub font_bounding_box_ascent: f32,
+    pub font_bounding_box_descent: f32,
 }
 
 /// Drawing state (saved/restored with save/restore)
 #[derive(Clone)]
 struct DrawingState {
     /// Fill style
-    fill_style: Color,
+    fill_style: FillStyle,
     /// Stroke style
-    stroke_style: Color,
+    stroke_style: FillStyle,
     /// Line width
     line_width: f32,
     /// Line cap
     line_cap: LineCap,
     /// Line join
     line_join: LineJoin,
+    /// Longest a miter join may be, in line widths
+    miter_limit: f32,
+    /// Lengths of alternating dashes and gaps, empty for solid lines
+    line_dash: Vec<f32>,
+    /// Distance into the dash pattern lines start at
+    line_dash_offset: f32,
+    /// Shadow color, transparent for no shadow
+    shadow_color: Color,
+    /// Shadow blur, twice the standard deviation of its Gaussian
+    shadow_blur: f32,
+    /// Shadow offset in canvas pixels, unaffected by the transform
+    shadow_offset: (f32, f32),
     /// Global alpha
     global_alpha: f32,
     /// Global composite operation
@@ -96,26 +436,59 @@ struct DrawingState {
     text_baseline: TextBaseline,
     /// Transform matrix
     transform: [f32; 6], // a, b, c, d, e, f
+    /// Coverage of each pixel by the clipping region, if drawing is clipped
+    clip: Option<Arc<[f32]>>,
 }
 
 impl Default for DrawingState {
     fn default() -> Self {
         Self {
-            fill_style: Color::rgba(0, 0, 0, 255),
-            stroke_style: Color::rgba(0, 0, 0, 255),
+            fill_style: FillStyle::Color(Color::rgba(0, 0, 0, 255)),
+            stroke_style: FillStyle::Color(Color::rgba(0, 0, 0, 255)),
             line_width: 1.0,
             line_cap: LineCap::Butt,
             line_join: LineJoin::Miter,
+            miter_limit: 10.0,
+            line_dash: Vec::new(),
+            line_dash_offset: 0.0,
+            shadow_color: Color::transparent(),
+            shadow_blur: 0.0,
+            shadow_offset: (0.0, 0.0),
             global_alpha: 1.0,
             global_composite_operation: CompositeOperation::SourceOver,
             font: "10px sans-serif".to_string(),
             text_align: TextAlign::Start,
             text_baseline: TextBaseline::Alphabetic,
-            transform: [1.0, 0.0, 0.0, 1.0, 0.0, 0.0], // Identity matrix
+            transform: IDENTITY, // Identity matrix
+            clip: None,
         }
     }
 }
 
+/// Transform that leaves points where they are
+const IDENTITY: [f32; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// Map a point through a transform matrix
+fn apply_transform(m: &[f32; 6], x: f32, y: f32) -> (f32, f32) {
+    (m[0] * x + m[2] * y + m[4], m[1] * x + m[3] * y + m[5])
+}
+
+/// Matrix undoing a transform, if it has one
+fn invert_transform(m: &[f32; 6]) -> Option<[f32; 6]> {
+    let det = m[0] * m[3] - m[1] * m[2];
+    if det == 0.0 || !det.is_finite() {
+        return None;
+    }
+    Some([
+        m[3] / det,
+        -m[1] / det,
+        -m[2] / det,
+        m[0] / det,
+        (m[2] * m[5] - m[3] * m[4]) / det,
+        (m[1] * m[4] - m[0] * m[5]) / det,
+    ])
+}
+
 /// Color representation
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Color {
@@ -135,8 +508,353 @@ impl Color {
     pub fn rgb(r: u8, g: u8, b: u8) -> Self {
         Self::rgba(r, g, b, 255)
     }
+    
+    /// Fully transparent black, painted where a style has no color
+    pub fn transparent() -> Self {
+        Self::rgba(0, 0, 0, 0)
+    }
+    
+    /// Color a fraction `t` of the way from this color to another
+    fn lerp(self, other: Color, t: f32) -> Self {
+        let mix = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * t).round() as u8;
+        Self::rgba(mix(self.r, other.r), mix(self.g, other.g), mix(self.b, other.b), mix(self.a, other.a))
+    }
+}
+
+/// What shapes are filled or stroked with
+#[derive(Debug, Clone, PartialEq)]
+pub enum FillStyle {
+    Color(Color),
+    Gradient(CanvasGradient),
+    Pattern(CanvasPattern),
+}
+
+impl FillStyle {
+    /// Color of the style at a point in canvas coordinates
+    pub fn color_at(&self, x: f32, y: f32) -> Color {
+        match self {
+            FillStyle::Color(color) => *color,
+            FillStyle::Gradient(gradient) => gradient.color_at(x, y),
+            FillStyle::Pattern(pattern) => pattern.color_at(x, y),
+        }
+    }
+}
+
+impl From<Color> for FillStyle {
+    fn from(color: Color) -> Self {
+        FillStyle::Color(color)
+    }
+}
+
+impl From<CanvasGradient> for FillStyle {
+    fn from(gradient: CanvasGradient) -> Self {
+        FillStyle::Gradient(gradient)
+    }
+}
+
+impl From<CanvasPattern> for FillStyle {
+    fn from(pattern: CanvasPattern) -> Self {
+        FillStyle::Pattern(pattern)
+    }
+}
+
+/// Geometry of a gradient
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GradientKind {
+    /// Along the line from (x0, y0) to (x1, y1)
+    Linear { x0: f32, y0: f32, x1: f32, y1: f32 },
+    /// Between the start circle and the end circle
+    Radial { x0: f32, y0: f32, r0: f32, x1: f32, y1: f32, r1: f32 },
+}
+
+/// Linear or radial gradient (`CanvasGradient`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CanvasGradient {
+    kind: GradientKind,
+    /// Color stops in offset order; stops at the same offset keep the order they were added in
+    stops: Vec<(f32, Color)>,
+}
+
+impl CanvasGradient {
+    /// Create a linear gradient
+    pub fn linear(x0: f32, y0: f32, x1: f32, y1: f32) -> Self {
+        Self { kind: GradientKind::Linear { x0, y0, x1, y1 }, stops: Vec::new() }
+    }
+    
+    /// Create a radial gradient; negative radii are an error
+    pub fn radial(x0: f32, y0: f32, r0: f32, x1: f32, y1: f32, r1: f32) -> Result<Self, CanvasError> {
+        if r0 < 0.0 || r1 < 0.0 {
+            return Err(CanvasError::IndexSize("radius is negative".to_string()));
+        }
+        Ok(Self { kind: GradientKind::Radial { x0, y0, r0, x1, y1, r1 }, stops: Vec::new() })
+    }
+    
+    /// Get the gradient's geometry
+    pub fn kind(&self) -> GradientKind {
+        self.kind
+    }
+    
+    /// Get the color stops, in offset order
+    pub fn stops(&self) -> &[(f32, Color)] {
+        &self.stops
+    }
+    
+    /// Add a color stop at an offset from 0 (start) to 1 (end)
+    pub fn add_color_stop(&mut self, offset: f32, color: Color) -> Result<(), CanvasError> {
+        if !(0.0..=1.0).contains(&offset) {
+            return Err(CanvasError::IndexSize(format!("color stop offset {} is outside 0 to 1", offset)));
+        }
+        let index = self.stops.partition_point(|(stop, _)| *stop <= offset);
+        self.stops.insert(index, (offset, color));
+        Ok(())
+    }
+    
+    /// Color of the gradient at a point
+    ///
+    /// Transparent black where the gradient is not defined: with no stops,
+    /// a zero-length line, identical circles, or outside a radial cone.
+    pub fn color_at(&self, x: f32, y: f32) -> Color {
+        match self.offset_at(x, y) {
+            Some(t) if !self.stops.is_empty() => self.color_at_offset(t),
+            _ => Color::transparent(),
+        }
+    }
+    
+    /// Offset along the gradient at a point, before clamping
+    fn offset_at(&self, x: f32, y: f32) -> Option<f32> {
+        match self.kind {
+            GradientKind::Linear { x0, y0, x1, y1 } => {
+                let (dx, dy) = (x1 - x0, y1 - y0);
+                let length_squared = dx * dx + dy * dy;
+                if length_squared == 0.0 {
+                    return None;
+                }
+                Some(((x - x0) * dx + (y - y0) * dy) / length_squared)
+            }
+            GradientKind::Radial { x0, y0, r0, x1, y1, r1 } => {
+                if x0 == x1 && y0 == y1 && r0 == r1 {
+                    return None;
+                }
+                // The largest ω whose circle, interpolated between the two, passes through the point
+                let (cdx, cdy, dr) = (x1 - x0, y1 - y0, r1 - r0);
+                let (px, py) = (x - x0, y - y0);
+                let a = cdx * cdx + cdy * cdy - dr * dr;
+                let b = px * cdx + py * cdy + r0 * dr;
+                let c = px * px + py * py - r0 * r0;
+                let radius_ok = |omega: f32| r0 + omega * dr >= 0.0;
+                if a.abs() < f32::EPSILON {
+                    let omega = c / (2.0 * b);
+                    return (b != 0.0 && radius_ok(omega)).then_some(omega);
+                }
+                let discriminant = b * b - a * c;
+                if discriminant < 0.0 {
+                    return None;
+                }
+                let root = discriminant.sqrt();
+                let (first, second) = ((b + root) / a, (b - root) / a);
+                [first.max(second), first.min(second)].into_iter().find(|omega| radius_ok(*omega))
+            }
+        }
+    }
+    
+    /// Color at an offset, clamped to the first and last stops
+    fn color_at_offset(&self, t: f32) -> Color {
+        let t = t.clamp(0.0, 1.0);
+        let after = self.stops.partition_point(|(offset, _)| *offset <= t);
+        match (after.checked_sub(1).map(|i| self.stops[i]), self.stops.get(after)) {
+            (Some((from, from_color)), Some(&(to, to_color))) if to > from => {
+                from_color.lerp(to_color, (t - from) / (to - from))
+            }
+            (Some((_, color)), _) => color,
+            (None, Some(&(_, color))) => color,
+            (None, None) => Color::transparent(),
+        }
+    }
+}
+
+/// How a pattern's image repeats (`createPattern`'s `repetition`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatternRepetition {
+    Repeat,
+    RepeatX,
+    RepeatY,
+    NoRepeat,
+}
+
+impl PatternRepetition {
+    /// Parse a repetition keyword; the empty string means `repeat`
+    pub fn parse(value: &str) -> Result<Self, CanvasError> {
+        match value {
+            "" | "repeat" => Ok(PatternRepetition::Repeat),
+            "repeat-x" => Ok(PatternRepetition::RepeatX),
+            "repeat-y" => Ok(PatternRepetition::RepeatY),
+            "no-repeat" => Ok(PatternRepetition::NoRepeat),
+            other => Err(CanvasError::Syntax(format!("unknown pattern repetition '{}'", other))),
+        }
+    }
+}
+
+/// Image tiled as a fill style (`CanvasPattern`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CanvasPattern {
+    width: u32,
+    height: u32,
+    /// RGBA pixels, shared by the copies of the style
+    pixels: Arc<[u8]>,
+    repetition: PatternRepetition,
+}
+
+impl CanvasPattern {
+    /// Create a pattern from RGBA pixels
+    ///
+    /// There is no pattern for an image with no pixels; pixel data shorter
+    /// than the image is an error.
+    pub fn new(pixels: &[u8], width: u32, height: u32, repetition: PatternRepetition)
+        -> Result<Option<Self>, CanvasError> {
+        if width == 0 || height == 0 {
+            return Ok(None);
+        }
+        let len = (width * height * 4) as usize;
+        if pixels.len() < len {
+            return Err(CanvasError::InvalidState(format!("image data is too short for {}x{}", width, height)));
+        }
+        Ok(Some(Self { width, height, pixels: pixels[..len].into(), repetition }))
+    }
+    
+    /// Get the repetition
+    pub fn repetition(&self) -> PatternRepetition {
+        self.repetition
+    }
+    
+    /// Color of the pattern at a point, its image's top left at the origin
+    pub fn color_at(&self, x: f32, y: f32) -> Color {
+        let (x, y) = (x.floor() as i64, y.floor() as i64);
+        let (width, height) = (self.width as i64, self.height as i64);
+        let (repeat_x, repeat_y) = match self.repetition {
+            PatternRepetition::Repeat => (true, true),
+            PatternRepetition::RepeatX => (true, false),
+            PatternRepetition::RepeatY => (false, true),
+            PatternRepetition::NoRepeat => (false, false),
+        };
+        let wrap = |value: i64, size: i64, repeat: bool| {
+            if repeat {
+                Some(value.rem_euclid(size))
+            } else {
+                (0..size).contains(&value).then_some(value)
+            }
+        };
+        let (Some(x), Some(y)) = (wrap(x, width, repeat_x), wrap(y, height, repeat_y)) else {
+            return Color::transparent();
+        };
+        let idx = ((y * width + x) * 4) as usize;
+        Color::rgba(self.pixels[idx], self.pixels[idx + 1], self.pixels[idx + 2], self.pixels[idx + 3])
+    }
+}
+
+/// Pixels copied out of or into a canvas (`ImageData`)
+///
+/// `data` holds unpremultiplied RGBA rows, top to bottom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageData {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl ImageData {
+    /// Create transparent black image data
+    pub fn new(width: u32, height: u32) -> Result<Self, CanvasError> {
+        if width == 0 || height == 0 {
+            return Err(CanvasError::IndexSize("image data has no pixels".to_string()));
+        }
+        let len = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|pixels| pixels.checked_mul(4))
+            .ok_or_else(|| CanvasError::IndexSize(format!("image data of {}x{} is too large", width, height)))?;
+        Ok(Self { width, height, data: vec![0; len] })
+    }
+    
+    /// Wrap RGBA pixels `width` wide, checking them against `height` if given
+    pub fn from_data(data: Vec<u8>, width: u32, height: Option<u32>) -> Result<Self, CanvasError> {
+        if data.is_empty() || !data.len().is_multiple_of(4) {
+            return Err(CanvasError::InvalidState("data length is not a nonzero multiple of 4".to_string()));
+        }
+        let row = width as usize * 4;
+        if row == 0 || !data.len().is_multiple_of(row) {
+            return Err(CanvasError::IndexSize(format!("data length is not a multiple of {} pixels", width)));
+        }
+        let rows = (data.len() / row) as u32;
+        if height.is_some_and(|height| height != rows) {
+            return Err(CanvasError::IndexSize(format!("data holds {} rows, not {:?}", rows, height)));
+        }
+        Ok(Self { width, height: rows, data })
+    }
+    
+    /// Get width
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    
+    /// Get height
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+    
+    /// Get the RGBA pixels
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+    
+    /// Get the RGBA pixels to change them
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+}
+
+/// A pixel as it reads back after being stored premultiplied by its alpha
+///
+/// Translucent colors lose precision and transparent ones lose their color.
+fn premultiplied_round_trip([r, g, b, a]: [u8; 4]) -> [u8; 4] {
+    match a {
+        0 => [0; 4],
+        255 => [r, g, b, a],
+        _ => {
+            let alpha = a as u32;
+            let round_trip = |c: u8| {
+                let premultiplied = (c as u32 * alpha + 127) / 255;
+                ((premultiplied * 255 + alpha / 2) / alpha).min(255) as u8
+            };
+            [round_trip(r), round_trip(g), round_trip(b), a]
+        }
+    }
+}
+
+/// Canvas errors, named after the DOM exceptions they stand for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CanvasError {
+    /// A value is out of range
+    IndexSize(String),
+    /// A keyword is not recognised
+    Syntax(String),
+    /// An image cannot be used
+    InvalidState(String),
+    /// Pixels cannot be encoded as an image
+    Encoding(String),
 }
 
+impl std::fmt::Display for CanvasError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CanvasError::IndexSize(msg) => write!(f, "IndexSizeError: {}", msg),
+            CanvasError::Syntax(msg) => write!(f, "SyntaxError: {}", msg),
+            CanvasError::InvalidState(msg) => write!(f, "InvalidStateError: {}", msg),
+            CanvasError::Encoding(msg) => write!(f, "EncodingError: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for CanvasError {}
+
 /// Line cap style
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LineCap {
@@ -145,6 +863,18 @@ pub enum LineCap {
     Square,
 }
 
+impl LineCap {
+    /// Parse a `lineCap` keyword
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "butt" => Some(LineCap::Butt),
+            "round" => Some(LineCap::Round),
+            "square" => Some(LineCap::Square),
+            _ => None,
+        }
+    }
+}
+
 /// Line join style
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LineJoin {
@@ -153,6 +883,18 @@ pub enum LineJoin {
     Bevel,
 }
 
+impl LineJoin {
+    /// Parse a `lineJoin` keyword
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "miter" => Some(LineJoin::Miter),
+            "round" => Some(LineJoin::Round),
+            "bevel" => Some(LineJoin::Bevel),
+            _ => None,
+        }
+    }
+}
+
 /// Composite operation
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CompositeOperation {
@@ -169,6 +911,58 @@ pub enum CompositeOperation {
     Xor,
 }
 
+impl CompositeOperation {
+    /// Parse a `globalCompositeOperation` keyword
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "source-over" => Some(CompositeOperation::SourceOver),
+            "source-in" => Some(CompositeOperation::SourceIn),
+            "source-out" => Some(CompositeOperation::SourceOut),
+            "source-atop" => Some(CompositeOperation::SourceAtop),
+            "destination-over" => Some(CompositeOperation::DestinationOver),
+            "destination-in" => Some(CompositeOperation::DestinationIn),
+            "destination-out" => Some(CompositeOperation::DestinationOut),
+            "destination-atop" => Some(CompositeOperation::DestinationAtop),
+            "lighter" => Some(CompositeOperation::Lighter),
+            "copy" => Some(CompositeOperation::Copy),
+            "xor" => Some(CompositeOperation::Xor),
+            _ => None,
+        }
+    }
+    
+    /// Composite premultiplied source and destination colors
+    fn apply(self, src: [f32; 4], dst: [f32; 4]) -> [f32; 4] {
+        let (sa, da) = (src[3], dst[3]);
+        // Porter-Duff: how much of the source and of the destination is kept
+        let (keep_src, keep_dst) = match self {
+            CompositeOperation::SourceOver => (1.0, 1.0 - sa),
+            CompositeOperation::SourceIn => (da, 0.0),
+            CompositeOperation::SourceOut => (1.0 - da, 0.0),
+            CompositeOperation::SourceAtop => (da, 1.0 - sa),
+            CompositeOperation::DestinationOver => (1.0 - da, 1.0),
+            CompositeOperation::DestinationIn => (0.0, sa),
+            CompositeOperation::DestinationOut => (0.0, 1.0 - sa),
+            CompositeOperation::DestinationAtop => (1.0 - da, sa),
+            CompositeOperation::Lighter => (1.0, 1.0),
+            CompositeOperation::Copy => (1.0, 0.0),
+            CompositeOperation::Xor => (1.0 - da, 1.0 - sa),
+        };
+        std::array::from_fn(|i| (src[i] * keep_src + dst[i] * keep_dst).min(1.0))
+    }
+    
+    /// Whether a transparent source leaves the destination as it is
+    fn keeps_unpainted(self) -> bool {
+        !matches!(
+            self,
+            CompositeOperation::SourceIn
+                | CompositeOperation::SourceOut
+                | CompositeOperation::DestinationIn
+                | CompositeOperation::DestinationAtop
+                | CompositeOperation::Copy
+        )
+    }
+}
+
 /// Text alignment
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TextAlign {
@@ -179,6 +973,20 @@ pub enum TextAlign {
     Center,
 }
 
+impl TextAlign {
+    /// Parse a `textAlign` keyword
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "start" => Some(TextAlign::Start),
+            "end" => Some(TextAlign::End),
+            "left" => Some(TextAlign::Left),
+            "right" => Some(TextAlign::Right),
+            "center" => Some(TextAlign::Center),
+            _ => None,
+        }
+    }
+}
+
 /// Text baseline
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TextBaseline {
@@ -190,6 +998,21 @@ pub enum TextBaseline {
     Bottom,
 }
 
+impl TextBaseline {
+    /// Parse a `textBaseline` keyword
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "top" => Some(TextBaseline::Top),
+            "hanging" => Some(TextBaseline::Hanging),
+            "middle" => Some(TextBaseline::Middle),
+            "alphabetic" => Some(TextBaseline::Alphabetic),
+            "ideographic" => Some(TextBaseline::Ideographic),
+            "bottom" => Some(TextBaseline::Bottom),
+            _ => None,
+        }
+    }
+}
+
 /// 2D path
 #[derive(Clone)]
 pub struct Path2D {
@@ -241,25 +1064,166 @@ impl Path2D {
     pub fn commands(&self) -> &[PathCommand] {
         &self.commands
     }
-}
-
-impl Default for Path2D {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-/// Path command
-#[derive(Debug, Clone, Copy)]
-pub enum PathCommand {
-    MoveTo { x: f32, y: f32 },
-    LineTo { x: f32, y: f32 },
-    QuadraticCurveTo { cpx: f32, cpy: f32, x: f32, y: f32 },
-    BezierCurveTo { cp1x: f32, cp1y: f32, cp2x: f32, cp2y: f32, x: f32, y: f32 },
-    Arc { x: f32, y: f32, radius: f32, start_angle: f32, end_angle: f32, anticlockwise: bool },
-    ClosePath,
-}
-
+    
+    /// Flatten the path into polylines, curves and arcs as line segments
+    pub fn subpaths(&self) -> Vec<Subpath> {
+        let mut subpaths: Vec<Subpath> = Vec::new();
+        let mut current: Option<Subpath> = None;
+        // Drawing with no current point starts at the command's first point
+        let from = |current: &Option<Subpath>, x: f32, y: f32| current.as_ref().map_or((x, y), Subpath::last);
+        for cmd in &self.commands {
+            match *cmd {
+                PathCommand::MoveTo { x, y } => {
+                    subpaths.extend(current.take());
+                    current = Some(Subpath::new(x, y));
+                }
+                PathCommand::LineTo { x, y } => {
+                    current.get_or_insert_with(|| Subpath::new(x, y)).points.push((x, y));
+                }
+                PathCommand::QuadraticCurveTo { cpx, cpy, x, y } => {
+                    let (x0, y0) = from(&current, cpx, cpy);
+                    let subpath = current.get_or_insert_with(|| Subpath::new(x0, y0));
+                    for i in 1..=CURVE_SEGMENTS {
+                        let t = i as f32 / CURVE_SEGMENTS as f32;
+                        let nt = 1.0 - t;
+                        subpath.points.push((
+                            nt * nt * x0 + 2.0 * nt * t * cpx + t * t * x,
+                            nt * nt * y0 + 2.0 * nt * t * cpy + t * t * y,
+                        ));
+                    }
+                }
+                PathCommand::BezierCurveTo { cp1x, cp1y, cp2x, cp2y, x, y } => {
+                    let (x0, y0) = from(&current, cp1x, cp1y);
+                    let subpath = current.get_or_insert_with(|| Subpath::new(x0, y0));
+                    for i in 1..=CURVE_SEGMENTS {
+                        let t = i as f32 / CURVE_SEGMENTS as f32;
+                        let nt = 1.0 - t;
+                        subpath.points.push((
+                            nt * nt * nt * x0 + 3.0 * nt * nt * t * cp1x + 3.0 * nt * t * t * cp2x + t * t * t * x,
+                            nt * nt * nt * y0 + 3.0 * nt * nt * t * cp1y + 3.0 * nt * t * t * cp2y + t * t * t * y,
+                        ));
+                    }
+                }
+                PathCommand::Arc { x, y, radius, start_angle, end_angle, anticlockwise } => {
+                    let points = arc_points(x, y, radius, start_angle, end_angle, anticlockwise, 1.0);
+                    // The arc joins the subpath with a line from its current point
+                    let subpath = current.get_or_insert_with(|| Subpath::new(points[0].0, points[0].1));
+                    subpath.points.extend(points);
+                }
+                PathCommand::ClosePath => {
+                    // The next subpath starts where this one did
+                    if let Some(mut subpath) = current.take() {
+                        subpath.closed = true;
+                        let start = subpath.points[0];
+                        subpaths.push(subpath);
+                        current = Some(Subpath::new(start.0, start.1));
+                    }
+                }
+            }
+        }
+        subpaths.extend(current);
+        subpaths
+    }
+}
+
+/// Line segments each curve is flattened into
+const CURVE_SEGMENTS: usize = 20;
+
+/// Points along an arc, from its start to its end
+///
+/// `scale` is how much the arc is magnified when drawn, so its segments
+/// stay about two pixels long.
+fn arc_points(
+    x: f32,
+    y: f32,
+    radius: f32,
+    start_angle: f32,
+    end_angle: f32,
+    anticlockwise: bool,
+    scale: f32,
+) -> Vec<(f32, f32)> {
+    // Sweeps of a full turn or more draw the whole circle
+    let full_turn = 2.0 * std::f32::consts::PI;
+    let sweep = if anticlockwise { start_angle - end_angle } else { end_angle - start_angle };
+    let sweep = if sweep >= full_turn { full_turn } else { sweep.rem_euclid(full_turn) };
+    let sweep = if anticlockwise { -sweep } else { sweep };
+    let steps = ((radius * scale * sweep.abs() / 2.0).ceil() as usize).clamp(1, 512);
+    (0..=steps)
+        .map(|i| start_angle + sweep * i as f32 / steps as f32)
+        .map(|angle| (x + radius * angle.cos(), y + radius * angle.sin()))
+        .collect()
+}
+
+/// Path flattened into connected points
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subpath {
+    /// Points joined by straight lines
+    pub points: Vec<(f32, f32)>,
+    /// Whether the last point joins back to the first
+    pub closed: bool,
+}
+
+impl Subpath {
+    fn new(x: f32, y: f32) -> Self {
+        Self { points: vec![(x, y)], closed: false }
+    }
+    
+    fn last(&self) -> (f32, f32) {
+        self.points[self.points.len() - 1]
+    }
+    
+    /// Line segments of the subpath, with the closing one if `close`
+    fn segments(&self, close: bool) -> impl Iterator<Item = ((f32, f32), (f32, f32))> + '_ {
+        let closing = (close && self.points.len() > 1).then(|| (self.last(), self.points[0]));
+        self.points.windows(2).map(|pair| (pair[0], pair[1])).chain(closing)
+    }
+}
+
+/// Which points are inside a path that crosses itself (`CanvasFillRule`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FillRule {
+    /// Inside where edges wind around the point a nonzero number of times
+    #[default]
+    NonZero,
+    /// Inside where a ray from the point crosses an odd number of edges
+    EvenOdd,
+}
+
+impl FillRule {
+    /// Parse a fill rule keyword
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "nonzero" => Some(FillRule::NonZero),
+            "evenodd" => Some(FillRule::EvenOdd),
+            _ => None,
+        }
+    }
+    
+    fn is_inside(self, winding: i32) -> bool {
+        match self {
+            FillRule::NonZero => winding != 0,
+            FillRule::EvenOdd => winding % 2 != 0,
+        }
+    }
+}
+
+impl Default for Path2D {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Path command
+#[derive(Debug, Clone, Copy)]
+pub enum PathCommand {
+    MoveTo { x: f32, y: f32 },
+    LineTo { x: f32, y: f32 },
+    QuadraticCurveTo { cpx: f32, cpy: f32, x: f32, y: f32 },
+    BezierCurveTo { cp1x: f32, cp1y: f32, cp2x: f32, cp2y: f32, x: f32, y: f32 },
+    Arc { x: f32, y: f32, radius: f32, start_angle: f32, end_angle: f32, anticlockwise: bool },
+    ClosePath,
+}
+
 impl CanvasRenderingContext2D {
     /// Create a new 2D context
     pub fn new(width: u32, height: u32) -> Self {
@@ -271,12 +1235,251 @@ impl CanvasRenderingContext2D {
             current_path: Path2D::new(),
             state_stack: Vec::new(),
             state: DrawingState::default(),
+            text: None,
+            commands: None,
         }
     }
     
-    /// Get image data
-    pub fn get_image_data(&self) -> Vec<u8> {
-        self.image_data.clone()
+    /// Get the whole backing store (RGBA)
+    ///
+    /// While the context is accelerated, drawing since the last
+    /// `sync_pixels` has yet to reach it.
+    pub fn pixels(&self) -> &[u8] {
+        &self.image_data
+    }
+    
+    // Acceleration
+    
+    /// Record drawing for a GPU backing store instead of rasterizing it
+    ///
+    /// Solid color fills, strokes, images and clears are recorded. Anything
+    /// else (gradients, patterns, clipping, shadows, composite operations
+    /// other than source-over, text, writing image data) moves the context
+    /// back to the CPU for good, catching the backing store up first.
+    pub fn set_accelerated(&mut self, accelerated: bool) {
+        if !accelerated {
+            self.sync_pixels();
+            self.commands = None;
+        } else if self.commands.is_none() {
+            // The GPU starts from what has been drawn so far
+            let corners = [(0.0, 0.0), (self.width as f32, 0.0), (self.width as f32, self.height as f32),
+                (0.0, self.height as f32)];
+            let base = CanvasCommand::Image {
+                pixels: self.image_data.clone().into(),
+                width: self.width,
+                height: self.height,
+                corners,
+            };
+            let pending = vec![CanvasCommand::Clear { corners }, base];
+            self.commands = Some(CommandLog { pending, unsynced: Vec::new() });
+        }
+    }
+    
+    /// Is drawing recorded for the GPU
+    pub fn is_accelerated(&self) -> bool {
+        self.commands.is_some()
+    }
+    
+    /// Take the drawing recorded since the GPU last took it, oldest first
+    pub fn take_commands(&mut self) -> Vec<CanvasCommand> {
+        self.commands.as_mut().map(|log| std::mem::take(&mut log.pending)).unwrap_or_default()
+    }
+    
+    /// Bring the CPU backing store up to date with recorded drawing
+    pub fn sync_pixels(&mut self) {
+        let Some(unsynced) = self.commands.as_mut().map(|log| std::mem::take(&mut log.unsynced)) else {
+            return;
+        };
+        // Recorded commands already carry the transform, alpha and clip they were drawn with
+        let state = std::mem::take(&mut self.state);
+        for command in &unsynced {
+            self.replay(command);
+        }
+        self.state = state;
+    }
+    
+    /// Take the backing store, leaving a transparent black one in its place
+    fn take_bitmap(&mut self) -> Vec<u8> {
+        self.sync_pixels();
+        let bitmap = std::mem::replace(&mut self.image_data, vec![0; (self.width * self.height * 4) as usize]);
+        let corners = [(0.0, 0.0), (self.width as f32, 0.0), (self.width as f32, self.height as f32),
+            (0.0, self.height as f32)];
+        self.record(CanvasCommand::Clear { corners });
+        bitmap
+    }
+    
+    /// Leave acceleration for drawing the GPU cannot do
+    fn demote(&mut self) {
+        if self.is_accelerated() {
+            self.set_accelerated(false);
+        }
+    }
+    
+    /// Can drawing be recorded, leaving acceleration if the state rules it out
+    fn can_record(&mut self) -> bool {
+        let composited = self.state.global_composite_operation != CompositeOperation::SourceOver;
+        if self.state.clip.is_some() || self.has_shadow() || composited {
+            self.demote();
+        }
+        self.is_accelerated()
+    }
+    
+    /// The color to record drawing in a style with, if it can be recorded
+    fn recorded_color(&mut self, style: &FillStyle) -> Option<Color> {
+        match style {
+            FillStyle::Color(color) if self.can_record() => {
+                Some(Color { a: (color.a as f32 * self.state.global_alpha) as u8, ..*color })
+            }
+            FillStyle::Color(_) => None,
+            _ => {
+                self.demote();
+                None
+            }
+        }
+    }
+    
+    /// Record a command, dropping those it paints over entirely
+    fn record(&mut self, command: CanvasCommand) {
+        let covers_canvas = match &command {
+            CanvasCommand::Clear { corners } => self.covers_canvas(corners),
+            CanvasCommand::Fill { subpaths, color, .. } => {
+                color.a == 255 && subpaths.len() == 1 && self.covers_canvas(&subpaths[0].points)
+            }
+            _ => false,
+        };
+        let Some(log) = self.commands.as_mut() else {
+            return;
+        };
+        if covers_canvas {
+            log.pending.clear();
+            log.unsynced.clear();
+        }
+        log.pending.push(command.clone());
+        log.unsynced.push(command);
+        if log.unsynced.len() > MAX_UNSYNCED_COMMANDS {
+            self.sync_pixels();
+        }
+    }
+    
+    /// Is a polygon a rectangle covering the whole canvas
+    fn covers_canvas(&self, points: &[(f32, f32)]) -> bool {
+        if points.len() != 4 {
+            return false;
+        }
+        let (left, top) = points.iter().fold((f32::INFINITY, f32::INFINITY), |(l, t), p| (l.min(p.0), t.min(p.1)));
+        let (right, bottom) = points.iter().fold((f32::NEG_INFINITY, f32::NEG_INFINITY), |(r, b), p| {
+            (r.max(p.0), b.max(p.1))
+        });
+        // Only the bounding rectangle itself has the bounding rectangle's area
+        let area = (0..4)
+            .map(|i| {
+                let ((x0, y0), (x1, y1)) = (points[i], points[(i + 1) % 4]);
+                x0 * y1 - x1 * y0
+            })
+            .sum::<f32>()
+            .abs()
+            / 2.0;
+        left <= 0.0
+            && top <= 0.0
+            && right >= self.width as f32
+            && bottom >= self.height as f32
+            && (area - (right - left) * (bottom - top)).abs() < 0.5
+    }
+    
+    /// Draw a recorded command into the CPU backing store
+    fn replay(&mut self, command: &CanvasCommand) {
+        match command {
+            CanvasCommand::Fill { subpaths, rule, color } => {
+                self.fill_polygons(subpaths, *rule, &FillStyle::Color(*color));
+            }
+            CanvasCommand::Image { pixels, width, height, corners } => {
+                self.draw_quad_image(pixels, *width, *height, corners);
+            }
+            CanvasCommand::Clear { corners } => self.clear_quad(corners),
+        }
+    }
+    
+    // Pixel access
+    
+    /// Create transparent black image data; negative sizes count back
+    pub fn create_image_data(&self, sw: i32, sh: i32) -> Result<ImageData, CanvasError> {
+        ImageData::new(sw.unsigned_abs(), sh.unsigned_abs())
+    }
+    
+    /// Copy a rectangle of the canvas out as image data
+    ///
+    /// A negative width or height counts back from `sx` or `sy`; pixels
+    /// outside the canvas are transparent black. Neither the transform nor
+    /// the clip applies.
+    pub fn get_image_data(&mut self, sx: i32, sy: i32, sw: i32, sh: i32) -> Result<ImageData, CanvasError> {
+        self.sync_pixels();
+        let (sx, sw) = if sw < 0 { (sx.saturating_add(sw), sw.unsigned_abs()) } else { (sx, sw as u32) };
+        let (sy, sh) = if sh < 0 { (sy.saturating_add(sh), sh.unsigned_abs()) } else { (sy, sh as u32) };
+        let mut image = ImageData::new(sw, sh)?;
+        for y in 0..sh {
+            for x in 0..sw {
+                let (cx, cy) = (sx as i64 + x as i64, sy as i64 + y as i64);
+                if cx < 0 || cy < 0 || cx >= self.width as i64 || cy >= self.height as i64 {
+                    continue;
+                }
+                let src = ((cy as u32 * self.width + cx as u32) * 4) as usize;
+                let dst = ((y * sw + x) * 4) as usize;
+                let pixel = &self.image_data[src..src + 4];
+                // A transparent pixel has no color to read back
+                if pixel[3] > 0 {
+                    image.data[dst..dst + 4].copy_from_slice(pixel);
+                }
+            }
+        }
+        Ok(image)
+    }
+    
+    /// Write image data into the canvas with its top left at (`dx`, `dy`)
+    pub fn put_image_data(&mut self, image: &ImageData, dx: i32, dy: i32) {
+        self.put_image_data_dirty(image, dx, dy, 0, 0, image.width as i32, image.height as i32);
+    }
+    
+    /// Write the dirty rectangle of image data into the canvas, offset by (`dx`, `dy`)
+    ///
+    /// Pixels replace what is there: neither the transform, the clip,
+    /// global alpha nor compositing applies. Colors come back from a later
+    /// `get_image_data` as a premultiplied backing store would keep them.
+    #[allow(clippy::too_many_arguments)]
+    pub fn put_image_data_dirty(
+        &mut self,
+        image: &ImageData,
+        dx: i32,
+        dy: i32,
+        dirty_x: i32,
+        dirty_y: i32,
+        dirty_width: i32,
+        dirty_height: i32,
+    ) {
+        // The dirty rectangle, made positive and limited to the image
+        let clamp = |start: i32, length: i32, size: u32| {
+            let (start, length) = (start as i64, length as i64);
+            let (start, length) = if length < 0 { (start + length, -length) } else { (start, length) };
+            let (start, length) = if start < 0 { (0, length + start) } else { (start, length) };
+            (start, length.min(size as i64 - start))
+        };
+        self.demote();
+        let (x0, width) = clamp(dirty_x, dirty_width, image.width);
+        let (y0, height) = clamp(dirty_y, dirty_height, image.height);
+        if width <= 0 || height <= 0 {
+            return;
+        }
+        for y in y0..y0 + height {
+            for x in x0..x0 + width {
+                let (cx, cy) = (dx as i64 + x, dy as i64 + y);
+                if cx < 0 || cy < 0 || cx >= self.width as i64 || cy >= self.height as i64 {
+                    continue;
+                }
+                let src = ((y * image.width as i64 + x) * 4) as usize;
+                let dst = ((cy as u32 * self.width + cx as u32) * 4) as usize;
+                let pixel = [image.data[src], image.data[src + 1], image.data[src + 2], image.data[src + 3]];
+                self.image_data[dst..dst + 4].copy_from_slice(&premultiplied_round_trip(pixel));
+            }
+        }
     }
     
     // State management
@@ -295,14 +1498,43 @@ impl CanvasRenderingContext2D {
     
     // Styling
     
-    /// Set fill style
-    pub fn set_fill_style(&mut self, color: Color) {
-        self.state.fill_style = color;
+    /// Set fill style: a color, gradient or pattern
+    pub fn set_fill_style(&mut self, style: impl Into<FillStyle>) {
+        self.state.fill_style = style.into();
+    }
+    
+    /// Set stroke style: a color, gradient or pattern
+    pub fn set_stroke_style(&mut self, style: impl Into<FillStyle>) {
+        self.state.stroke_style = style.into();
     }
     
-    /// Set stroke style
-    pub fn set_stroke_style(&mut self, color: Color) {
-        self.state.stroke_style = color;
+    /// Get fill style
+    pub fn fill_style(&self) -> &FillStyle {
+        &self.state.fill_style
+    }
+    
+    /// Get stroke style
+    pub fn stroke_style(&self) -> &FillStyle {
+        &self.state.stroke_style
+    }
+    
+    /// Create a linear gradient along a line, for use as a style
+    pub fn create_linear_gradient(&self, x0: f32, y0: f32, x1: f32, y1: f32) -> CanvasGradient {
+        CanvasGradient::linear(x0, y0, x1, y1)
+    }
+    
+    /// Create a radial gradient between two circles, for use as a style
+    pub fn create_radial_gradient(&self, x0: f32, y0: f32, r0: f32, x1: f32, y1: f32, r1: f32)
+        -> Result<CanvasGradient, CanvasError> {
+        CanvasGradient::radial(x0, y0, r0, x1, y1, r1)
+    }
+    
+    /// Create a pattern from an RGBA image, for use as a style
+    ///
+    /// Returns `None` for an image with no pixels.
+    pub fn create_pattern(&self, image_data: &[u8], width: u32, height: u32, repetition: &str)
+        -> Result<Option<CanvasPattern>, CanvasError> {
+        CanvasPattern::new(image_data, width, height, PatternRepetition::parse(repetition)?)
     }
     
     /// Set line width
@@ -315,6 +1547,117 @@ impl CanvasRenderingContext2D {
         self.state.global_alpha = alpha.clamp(0.0, 1.0);
     }
     
+    /// Set how later drawing combines with what is on the canvas
+    pub fn set_global_composite_operation(&mut self, operation: CompositeOperation) {
+        self.state.global_composite_operation = operation;
+    }
+    
+    /// Get the composite operation
+    pub fn global_composite_operation(&self) -> CompositeOperation {
+        self.state.global_composite_operation
+    }
+    
+    /// Set how open ends of stroked lines are drawn
+    pub fn set_line_cap(&mut self, cap: LineCap) {
+        self.state.line_cap = cap;
+    }
+    
+    /// Set how stroked lines meet at corners
+    pub fn set_line_join(&mut self, join: LineJoin) {
+        self.state.line_join = join;
+    }
+    
+    /// Set the longest miter, in line widths, before a join is beveled instead
+    pub fn set_miter_limit(&mut self, limit: f32) {
+        if limit > 0.0 && limit.is_finite() {
+            self.state.miter_limit = limit;
+        }
+    }
+    
+    /// Set dash and gap lengths for stroking; an odd list is repeated
+    ///
+    /// A list with a negative or non-finite length is ignored, and an empty
+    /// one makes lines solid again.
+    pub fn set_line_dash(&mut self, segments: &[f32]) {
+        if segments.iter().any(|length| *length < 0.0 || !length.is_finite()) {
+            return;
+        }
+        self.state.line_dash = segments.to_vec();
+        if !segments.len().is_multiple_of(2) {
+            self.state.line_dash.extend_from_slice(segments);
+        }
+    }
+    
+    /// Get the dash pattern
+    pub fn line_dash(&self) -> &[f32] {
+        &self.state.line_dash
+    }
+    
+    /// Set how far into the dash pattern lines start
+    pub fn set_line_dash_offset(&mut self, offset: f32) {
+        if offset.is_finite() {
+            self.state.line_dash_offset = offset;
+        }
+    }
+    
+    /// Set the shadow color; a transparent color draws no shadow
+    pub fn set_shadow_color(&mut self, color: Color) {
+        self.state.shadow_color = color;
+    }
+    
+    /// Set the shadow blur; negative values are ignored
+    pub fn set_shadow_blur(&mut self, blur: f32) {
+        if blur >= 0.0 && blur.is_finite() {
+            self.state.shadow_blur = blur;
+        }
+    }
+    
+    /// Set the shadow's horizontal offset
+    pub fn set_shadow_offset_x(&mut self, offset: f32) {
+        if offset.is_finite() {
+            self.state.shadow_offset.0 = offset;
+        }
+    }
+    
+    /// Set the shadow's vertical offset
+    pub fn set_shadow_offset_y(&mut self, offset: f32) {
+        if offset.is_finite() {
+            self.state.shadow_offset.1 = offset;
+        }
+    }
+    
+    /// Set the font from a CSS `font` shorthand; values that do not parse are ignored
+    pub fn set_font(&mut self, font: &str) {
+        if CanvasFont::parse(font).is_some() {
+            self.state.font = font.to_string();
+        }
+    }
+    
+    /// Get the font
+    pub fn font(&self) -> &str {
+        &self.state.font
+    }
+    
+    /// Set text alignment
+    pub fn set_text_align(&mut self, align: TextAlign) {
+        self.state.text_align = align;
+    }
+    
+    /// Get text alignment
+    pub fn text_align(&self) -> TextAlign {
+        self.state.text_align
+    }
+    
+    /// Set text baseline
+    pub fn set_text_baseline(&mut self, baseline: TextBaseline) {
+        self.state.text_baseline = baseline;
+    }
+    
+    /// Get text baseline
+    pub fn text_baseline(&self) -> TextBaseline {
+        self.state.text_baseline
+    }
+    
     // Path operations
     
     /// Begin a new path
@@ -322,29 +1665,52 @@ impl CanvasRenderingContext2D {
         self.current_path = Path2D::new();
     }
     
+    // Points are transformed as they are added, so the path keeps the
+    // transform in force when each part was drawn
+    
     /// Move to point
     pub fn move_to(&mut self, x: f32, y: f32) {
+        let (x, y) = self.transform_point(x, y);
         self.current_path.move_to(x, y);
     }
     
     /// Line to point
     pub fn line_to(&mut self, x: f32, y: f32) {
+        let (x, y) = self.transform_point(x, y);
         self.current_path.line_to(x, y);
     }
     
     /// Quadratic curve
     pub fn quadratic_curve_to(&mut self, cpx: f32, cpy: f32, x: f32, y: f32) {
+        let (cpx, cpy) = self.transform_point(cpx, cpy);
+        let (x, y) = self.transform_point(x, y);
         self.current_path.quadratic_curve_to(cpx, cpy, x, y);
     }
     
     /// Bezier curve
     pub fn bezier_curve_to(&mut self, cp1x: f32, cp1y: f32, cp2x: f32, cp2y: f32, x: f32, y: f32) {
+        let (cp1x, cp1y) = self.transform_point(cp1x, cp1y);
+        let (cp2x, cp2y) = self.transform_point(cp2x, cp2y);
+        let (x, y) = self.transform_point(x, y);
         self.current_path.bezier_curve_to(cp1x, cp1y, cp2x, cp2y, x, y);
     }
     
     /// Arc
+    ///
+    /// Flattened before it is transformed, as a scaled or skewed arc is no
+    /// longer circular.
     pub fn arc(&mut self, x: f32, y: f32, radius: f32, start_angle: f32, end_angle: f32, anticlockwise: bool) {
-        self.current_path.arc(x, y, radius, start_angle, end_angle, anticlockwise);
+        let m = self.state.transform;
+        let scale = (m[0] * m[3] - m[1] * m[2]).abs().sqrt();
+        let points = arc_points(x, y, radius, start_angle, end_angle, anticlockwise, scale);
+        for (i, (px, py)) in points.into_iter().enumerate() {
+            let (px, py) = apply_transform(&m, px, py);
+            if i == 0 && self.current_path.commands().is_empty() {
+                self.current_path.move_to(px, py);
+            } else {
+                self.current_path.line_to(px, py);
+            }
+        }
     }
     
     /// Close path
@@ -352,68 +1718,161 @@ impl CanvasRenderingContext2D {
         self.current_path.close_path();
     }
     
+    /// Restrict drawing to the current path, within any earlier clip, with the nonzero rule
+    pub fn clip(&mut self) {
+        self.clip_with_rule(FillRule::NonZero);
+    }
+    
+    /// Restrict drawing to the current path, within any earlier clip, with a fill rule
+    ///
+    /// `restore` lifts a clip set since the matching `save`.
+    pub fn clip_with_rule(&mut self, rule: FillRule) {
+        self.demote();
+        let mut clip = vec![0.0f32; (self.width * self.height) as usize];
+        let width = self.width;
+        rasterize(&self.current_path.subpaths(), rule, self.width, self.height, |x, y, amount| {
+            clip[(y * width + x) as usize] = amount.min(1.0);
+        });
+        if let Some(previous) = &self.state.clip {
+            clip.iter_mut().zip(previous.iter()).for_each(|(coverage, previous)| *coverage *= previous);
+        }
+        self.state.clip = Some(clip.into());
+    }
+    
+    // Transforms
+    
+    /// Move the origin
+    pub fn translate(&mut self, x: f32, y: f32) {
+        self.transform(1.0, 0.0, 0.0, 1.0, x, y);
+    }
+    
+    /// Rotate clockwise by an angle in radians
+    pub fn rotate(&mut self, angle: f32) {
+        let (sin, cos) = angle.sin_cos();
+        self.transform(cos, sin, -sin, cos, 0.0, 0.0);
+    }
+    
+    /// Scale horizontally and vertically
+    pub fn scale(&mut self, x: f32, y: f32) {
+        self.transform(x, 0.0, 0.0, y, 0.0, 0.0);
+    }
+    
+    /// Apply a transform after the current one; non-finite values are ignored
+    pub fn transform(&mut self, a: f32, b: f32, c: f32, d: f32, e: f32, f: f32) {
+        if ![a, b, c, d, e, f].iter().all(|value| value.is_finite()) {
+            return;
+        }
+        let [ma, mb, mc, md, me, mf] = self.state.transform;
+        self.state.transform = [
+            ma * a + mc * b,
+            mb * a + md * b,
+            ma * c + mc * d,
+            mb * c + md * d,
+            ma * e + mc * f + me,
+            mb * e + md * f + mf,
+        ];
+    }
+    
+    /// Replace the current transform; non-finite values are ignored
+    pub fn set_transform(&mut self, a: f32, b: f32, c: f32, d: f32, e: f32, f: f32) {
+        if [a, b, c, d, e, f].iter().all(|value| value.is_finite()) {
+            self.state.transform = [a, b, c, d, e, f];
+        }
+    }
+    
+    /// Go back to the identity transform
+    pub fn reset_transform(&mut self) {
+        self.state.transform = IDENTITY;
+    }
+    
+    /// Get the current transform as `[a, b, c, d, e, f]`
+    pub fn get_transform(&self) -> [f32; 6] {
+        self.state.transform
+    }
+    
     // Drawing operations
     
-    /// Fill current path
+    /// Fill current path with the nonzero rule
     pub fn fill(&mut self) {
-        self.rasterize_path(true);
+        self.fill_with_rule(FillRule::NonZero);
+    }
+    
+    /// Fill current path, its open subpaths closed, with a fill rule
+    pub fn fill_with_rule(&mut self, rule: FillRule) {
+        let style = self.state.fill_style.clone();
+        let subpaths = self.current_path.subpaths();
+        self.fill_subpaths(subpaths, rule, &style);
     }
     
     /// Stroke current path
     pub fn stroke(&mut self) {
-        self.rasterize_path(false);
+        let style = self.state.stroke_style.clone();
+        let outline = stroke_polygons(&self.current_path.subpaths(), &self.stroke_geometry());
+        self.fill_subpaths(outline, FillRule::NonZero, &style);
     }
     
     /// Fill rectangle
     pub fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
-        let color = self.state.fill_style;
-        self.draw_rect(x, y, width, height, color, true);
+        let style = self.state.fill_style.clone();
+        let rect = self.rect_subpath(x, y, width, height);
+        self.fill_subpaths(vec![rect], FillRule::NonZero, &style);
     }
     
     /// Stroke rectangle
     pub fn stroke_rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
-        let color = self.state.stroke_style;
-        self.draw_rect(x, y, width, height, color, false);
+        let style = self.state.stroke_style.clone();
+        let outline = stroke_polygons(&[self.rect_subpath(x, y, width, height)], &self.stroke_geometry());
+        self.fill_subpaths(outline, FillRule::NonZero, &style);
     }
     
-    /// Clear rectangle
+    /// Clear rectangle, to transparent, wherever it covers most of a pixel
     pub fn clear_rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
-        let x1 = x.max(0.0) as u32;
-        let y1 = y.max(0.0) as u32;
-        let x2 = ((x + width).min(self.width as f32)) as u32;
-        let y2 = ((y + height).min(self.height as f32)) as u32;
-        
-        for py in y1..y2 {
-            for px in x1..x2 {
-                self.set_pixel(px, py, Color::rgba(255, 255, 255, 0));
-            }
+        let points = self.rect_subpath(x, y, width, height).points;
+        let corners = [points[0], points[1], points[2], points[3]];
+        if self.can_record() {
+            self.record(CanvasCommand::Clear { corners });
+        } else {
+            self.clear_quad(&corners);
         }
     }
     
-    /// Fill text
+    /// Fill text in the context's font, aligned at (`x`, `y`)
     pub fn fill_text(&mut self, text: &str, x: f32, y: f32) {
-        // Simplified text rendering - just draw a placeholder for now
-        // In production, would use font rasterization library
-        let color = self.state.fill_style;
-        let char_width = 8.0;
-        let char_height = 12.0;
-        
-        for (i, _ch) in text.chars().enumerate() {
-            let char_x = x + (i as f32 * char_width);
-            self.draw_rect(char_x, y - char_height, char_width, char_height, color, true);
-        }
+        let style = self.state.fill_style.clone();
+        self.draw_text(text, x, y, &style, true);
     }
     
-    /// Stroke text
+    /// Stroke the outlines of text's glyphs, aligned at (`x`, `y`)
+    ///
+    /// Outlines are a pixel wide, whatever the line width.
     pub fn stroke_text(&mut self, text: &str, x: f32, y: f32) {
-        // Simplified text rendering
-        let color = self.state.stroke_style;
-        let char_width = 8.0;
-        let char_height = 12.0;
-        
-        for (i, _ch) in text.chars().enumerate() {
-            let char_x = x + (i as f32 * char_width);
-            self.draw_rect(char_x, y - char_height, char_width, char_height, color, false);
+        let style = self.state.stroke_style.clone();
+        self.draw_text(text, x, y, &style, false);
+    }
+    
+    /// Measure text in the context's font, for its alignment and baseline
+    pub fn measure_text(&mut self, text: &str) -> TextMetrics {
+        let Some(run) = self.layout_text(text) else {
+            return TextMetrics::default();
+        };
+        let (anchor_x, anchor_y) = run.anchor(self.state.text_align, self.state.text_baseline);
+        let inked = run.glyphs.iter().filter(|glyph| glyph.info.width > 0 && glyph.info.height > 0);
+        let (mut left, mut right, mut top, mut bottom) = (f32::INFINITY, f32::NEG_INFINITY, 0.0f32, 0.0f32);
+        for glyph in inked {
+            left = left.min(glyph.pen_x + glyph.info.bearing_x);
+            right = right.max(glyph.pen_x + glyph.info.bearing_x + glyph.info.width as f32);
+            top = top.max(glyph.info.bearing_y + glyph.info.height as f32);
+            bottom = bottom.min(glyph.info.bearing_y);
+        }
+        let (left, right) = if left <= right { (left, right) } else { (0.0, 0.0) };
+        TextMetrics {
+            width: run.width,
+            actual_bounding_box_left: -(left + anchor_x),
+            actual_bounding_box_right: right + anchor_x,
+            actual_bounding_box_ascent: top - anchor_y,
+            actual_bounding_box_descent: -bottom + anchor_y,
+            font_bounding_box_ascent: run.ascent - anchor_y,
+            font_bounding_box_descent: run.descent + anchor_y,
         }
     }
     
@@ -422,33 +1881,29 @@ impl CanvasRenderingContext2D {
     /// Draw image (simplified)
     pub fn draw_image(&mut self, image_data: &[u8], sx: f32, sy: f32, sw: f32, sh: f32, 
                       dx: f32, dy: f32, dw: f32, dh: f32) {
-        // Simplified: copy pixels with basic scaling
-        let sx = sx as u32;
-        let sy = sy as u32;
-        let sw = sw as u32;
-        let sh = sh as u32;
-        let dx = dx as u32;
-        let dy = dy as u32;
-        let dw = dw as u32;
-        let dh = dh as u32;
-        
-        for y in 0..dh {
-            for x in 0..dw {
-                let src_x = sx + (x * sw / dw.max(1));
-                let src_y = sy + (y * sh / dh.max(1));
-                let src_idx = ((src_y * sw + src_x) * 4) as usize;
-                
+        // The source rectangle, read from an image `sw` pixels wide
+        let (sx, sy, sw, sh) = (sx as u32, sy as u32, sw as u32, sh as u32);
+        let mut source = vec![0u8; (sw * sh * 4) as usize];
+        for y in 0..sh {
+            for x in 0..sw {
+                let src_idx = (((sy + y) * sw + sx + x) * 4) as usize;
+                let dst_idx = ((y * sw + x) * 4) as usize;
                 if src_idx + 3 < image_data.len() {
-                    let color = Color::rgba(
-                        image_data[src_idx],
-                        image_data[src_idx + 1],
-                        image_data[src_idx + 2],
-                        image_data[src_idx + 3],
-                    );
-                    self.set_pixel(dx + x, dy + y, color);
+                    source[dst_idx..dst_idx + 4].copy_from_slice(&image_data[src_idx..src_idx + 4]);
                 }
             }
         }
+        let points = self.rect_subpath(dx, dy, dw, dh).points;
+        let corners = [points[0], points[1], points[2], points[3]];
+        let alpha = self.state.global_alpha;
+        if self.can_record() {
+            if alpha < 1.0 {
+                source.chunks_exact_mut(4).for_each(|pixel| pixel[3] = (pixel[3] as f32 * alpha) as u8);
+            }
+            self.record(CanvasCommand::Image { pixels: source.into(), width: sw, height: sh, corners });
+        } else {
+            self.paint_with_effects(|ctx| ctx.draw_quad_image(&source, sw, sh, &corners));
+        }
     }
     
     // Helper methods
@@ -463,8 +1918,12 @@ impl CanvasRenderingContext2D {
             return;
         }
         
-        // Apply global alpha
-        let alpha = (color.a as f32 * self.state.global_alpha) as u8;
+        // Apply global alpha and the clip
+        let clip = self.state.clip.as_ref().map_or(1.0, |clip| clip[idx / 4]);
+        if clip == 0.0 {
+            return;
+        }
+        let alpha = (color.a as f32 * self.state.global_alpha * clip) as u8;
         
         // Basic alpha blending
         if alpha == 255 {
@@ -489,149 +1948,650 @@ impl CanvasRenderingContext2D {
         }
     }
     
-    fn draw_rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: Color, fill: bool) {
-        if fill {
-            let x1 = x.max(0.0) as u32;
-            let y1 = y.max(0.0) as u32;
-            let x2 = ((x + width).min(self.width as f32)) as u32;
-            let y2 = ((y + height).min(self.height as f32)) as u32;
-            
-            for py in y1..y2 {
-                for px in x1..x2 {
-                    self.set_pixel(px, py, color);
+    /// Lay out a line of text in the context's font, loading fonts on first use
+    ///
+    /// `None` if no font could be loaded.
+    fn layout_text(&mut self, text: &str) -> Option<TextRun> {
+        let font = CanvasFont::parse(&self.state.font)?;
+        if self.text.is_none() {
+            let font_manager = FontManager::new().ok()?;
+            let glyph_cache = GlyphCache::new(1024, 1024);
+            self.text = Some(CanvasText { font_manager, glyph_cache, font_ids: HashMap::new() });
+        }
+        let fonts = self.text.as_mut()?;
+        let face = fonts.font_manager.get_font(&font.family);
+        let font_id = match fonts.font_ids.get(&font.family) {
+            Some(&id) => id,
+            None => {
+                let id = fonts.glyph_cache.register_font(face.clone());
+                fonts.font_ids.insert(font.family.clone(), id);
+                id
+            }
+        };
+        let size = font.size.round().max(1.0) as u32;
+        let (ascent, descent) = face
+            .horizontal_line_metrics(size as f32)
+            .map_or((size as f32 * 0.8, size as f32 * 0.2), |metrics| (metrics.ascent, -metrics.descent));
+        // Bitmaps are copied out as glyphs are placed, so earlier lines' glyphs may be evicted
+        fonts.glyph_cache.begin_frame();
+        let mut glyphs = Vec::new();
+        let mut pen_x = 0.0;
+        for character in text.chars() {
+            let key = GlyphKey { character, size, font_id };
+            // A line too big for the atlas empties it and the glyph is rasterized again
+            let info = match fonts.glyph_cache.get_or_rasterize(key) {
+                Some(info) => info,
+                None => {
+                    fonts.glyph_cache.clear();
+                    fonts.glyph_cache.get_or_rasterize(key)?
+                }
+            };
+            let bitmap = fonts.glyph_cache.glyph_bitmap(&info);
+            glyphs.push(PlacedGlyph { pen_x, info, bitmap });
+            pen_x += info.advance;
+        }
+        Some(TextRun { glyphs, width: pen_x, ascent, descent })
+    }
+    
+    fn draw_text(&mut self, text: &str, x: f32, y: f32, style: &FillStyle, fill: bool) {
+        self.demote();
+        let Some(run) = self.layout_text(text) else {
+            return;
+        };
+        let (anchor_x, anchor_y) = run.anchor(self.state.text_align, self.state.text_baseline);
+        let (origin_x, baseline) = ((x + anchor_x).round(), (y + anchor_y).round());
+        self.paint_with_effects(|ctx| {
+            for glyph in &run.glyphs {
+                let (width, height) = (glyph.info.width, glyph.info.height);
+                if width == 0 || height == 0 {
+                    continue;
+                }
+                // Bitmaps hang from their top; bearings are from the baseline, upwards
+                let left = origin_x + (glyph.pen_x + glyph.info.bearing_x).round();
+                let top = baseline - glyph.info.bearing_y - height as f32;
+                if fill {
+                    ctx.draw_mask(&glyph.bitmap, width, height, left, top, style);
+                } else {
+                    let outline = glyph_outline(&glyph.bitmap, width, height);
+                    ctx.draw_mask(&outline, width + 2, height + 2, left - 1.0, top - 1.0, style);
                 }
             }
-        } else {
-            // Stroke: draw outline
-            let line_width = self.state.line_width as u32;
-            for i in 0..line_width {
-                self.draw_line(x + i as f32, y, x + width - i as f32, y, color);
-                self.draw_line(x + width, y + i as f32, x + width, y + height - i as f32, color);
-                self.draw_line(x + width - i as f32, y + height, x + i as f32, y + height, color);
-                self.draw_line(x, y + height - i as f32, x, y + i as f32, color);
+        });
+    }
+    
+    /// Paint a coverage mask placed at (`left`, `top`) in user space
+    ///
+    /// Canvas pixels are mapped back through the transform to the mask
+    /// pixel they fall in.
+    fn draw_mask(&mut self, mask: &[u8], width: u32, height: u32, left: f32, top: f32, style: &FillStyle) {
+        let Some(inverse) = invert_transform(&self.state.transform) else {
+            return;
+        };
+        let (x_range, y_range) = self.device_bounds(left, top, width as f32, height as f32);
+        for py in y_range {
+            for px in x_range.clone() {
+                let (u, v) = apply_transform(&inverse, px as f32 + 0.5, py as f32 + 0.5);
+                let (u, v) = ((u - left).floor(), (v - top).floor());
+                if u < 0.0 || v < 0.0 || u >= width as f32 || v >= height as f32 {
+                    continue;
+                }
+                let coverage = mask[(v as u32 * width + u as u32) as usize];
+                let color = self.style_color(px, py, style);
+                let alpha = (color.a as u32 * coverage as u32 + 127) / 255;
+                if alpha > 0 {
+                    self.set_pixel(px, py, Color { a: alpha as u8, ..color });
+                }
             }
         }
     }
     
-    fn draw_line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, color: Color) {
-        // Bresenham's line algorithm
-        let mut x1 = x1 as i32;
-        let mut y1 = y1 as i32;
-        let x2 = x2 as i32;
-        let y2 = y2 as i32;
-        
-        let dx = (x2 - x1).abs();
-        let dy = -(y2 - y1).abs();
-        let sx = if x1 < x2 { 1 } else { -1 };
-        let sy = if y1 < y2 { 1 } else { -1 };
-        let mut err = dx + dy;
-        
-        loop {
-            if x1 >= 0 && y1 >= 0 {
-                self.set_pixel(x1 as u32, y1 as u32, color);
+    /// Map a point through the current transform
+    fn transform_point(&self, x: f32, y: f32) -> (f32, f32) {
+        apply_transform(&self.state.transform, x, y)
+    }
+    
+    /// Canvas pixel columns and rows a transformed rectangle may touch
+    fn device_bounds(&self, x: f32, y: f32, width: f32, height: f32) -> (std::ops::Range<u32>, std::ops::Range<u32>) {
+        self.pixel_bounds(&self.rect_subpath(x, y, width, height).points)
+    }
+    
+    /// Canvas pixel columns and rows points in canvas pixels may touch
+    fn pixel_bounds(&self, corners: &[(f32, f32)]) -> (std::ops::Range<u32>, std::ops::Range<u32>) {
+        let (left, top) = corners.iter().fold((f32::INFINITY, f32::INFINITY), |(l, t), p| (l.min(p.0), t.min(p.1)));
+        let (right, bottom) = corners.iter().fold((0.0f32, 0.0f32), |(r, b), p| (r.max(p.0), b.max(p.1)));
+        (
+            left.max(0.0) as u32..(right.ceil() as u32).min(self.width),
+            top.max(0.0) as u32..(bottom.ceil() as u32).min(self.height),
+        )
+    }
+    
+    /// Closed outline of a rectangle, transformed
+    fn rect_subpath(&self, x: f32, y: f32, width: f32, height: f32) -> Subpath {
+        let corners = [(x, y), (x + width, y), (x + width, y + height), (x, y + height)];
+        Subpath { points: corners.iter().map(|&(x, y)| self.transform_point(x, y)).collect(), closed: true }
+    }
+    
+    /// The style's color at a pixel's center
+    ///
+    /// Gradients and patterns are in the coordinates of the current
+    /// transform, so the pixel is mapped back into them.
+    fn style_color(&self, x: u32, y: u32, style: &FillStyle) -> Color {
+        let (x, y) = (x as f32 + 0.5, y as f32 + 0.5);
+        match style {
+            FillStyle::Color(color) => *color,
+            _ if self.state.transform == IDENTITY => style.color_at(x, y),
+            _ => match invert_transform(&self.state.transform) {
+                Some(inverse) => {
+                    let (x, y) = apply_transform(&inverse, x, y);
+                    style.color_at(x, y)
+                }
+                None => Color::transparent(),
+            },
+        }
+    }
+    
+    /// Fill transformed polygons, or record the fill while accelerated
+    fn fill_subpaths(&mut self, subpaths: Vec<Subpath>, rule: FillRule, style: &FillStyle) {
+        match self.recorded_color(style) {
+            Some(color) => self.record(CanvasCommand::Fill { subpaths, rule, color }),
+            None => self.paint_with_effects(|ctx| ctx.fill_polygons(&subpaths, rule, style)),
+        }
+    }
+    
+    /// Width and dashes of strokes, scaled by the transform like the path
+    fn stroke_geometry(&self) -> StrokeGeometry {
+        let [a, b, c, d, ..] = self.state.transform;
+        let scale = (a * d - b * c).abs().sqrt();
+        StrokeGeometry {
+            width: self.state.line_width * scale,
+            cap: self.state.line_cap,
+            join: self.state.line_join,
+            miter_limit: self.state.miter_limit,
+            dash: self.state.line_dash.iter().map(|length| length * scale).collect(),
+            dash_offset: self.state.line_dash_offset * scale,
+        }
+    }
+    
+    /// Is a shadow drawn under shapes
+    fn has_shadow(&self) -> bool {
+        let (x, y) = self.state.shadow_offset;
+        self.state.shadow_color.a > 0 && (self.state.shadow_blur > 0.0 || x != 0.0 || y != 0.0)
+    }
+    
+    /// Draw with the shadow and composite operation
+    ///
+    /// Plain source-over drawing goes straight onto the canvas. Otherwise
+    /// `draw` paints into a transparent layer of its own; the layer's shadow
+    /// and then the layer are composited onto the whole canvas, with global
+    /// alpha and the clip.
+    fn paint_with_effects(&mut self, draw: impl Fn(&mut Self)) {
+        let shadowed = self.has_shadow();
+        if !shadowed && self.state.global_composite_operation == CompositeOperation::SourceOver {
+            draw(self);
+            return;
+        }
+        let state = self.state.clone();
+        self.state.global_alpha = 1.0;
+        self.state.clip = None;
+        self.state.shadow_color = Color::transparent();
+        self.state.global_composite_operation = CompositeOperation::SourceOver;
+        let canvas = std::mem::replace(&mut self.image_data, vec![0; (self.width * self.height * 4) as usize]);
+        draw(self);
+        let layer = std::mem::replace(&mut self.image_data, canvas);
+        self.state = state;
+        if shadowed {
+            let shadow = self.shadow_of(&layer);
+            self.composite_layer(&shadow);
+        }
+        self.composite_layer(&layer);
+    }
+    
+    /// The shadow a layer casts: its alpha, blurred, offset and in the shadow color
+    fn shadow_of(&self, layer: &[u8]) -> Vec<u8> {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let mut alpha: Vec<f32> = layer.chunks_exact(4).map(|pixel| pixel[3] as f32).collect();
+        blur(&mut alpha, width, height, self.state.shadow_blur / 2.0);
+        let (dx, dy) = (self.state.shadow_offset.0.round() as i64, self.state.shadow_offset.1.round() as i64);
+        let color = self.state.shadow_color;
+        let mut shadow = vec![0; layer.len()];
+        for y in 0..height as i64 {
+            for x in 0..width as i64 {
+                let (sx, sy) = (x - dx, y - dy);
+                if sx < 0 || sy < 0 || sx >= width as i64 || sy >= height as i64 {
+                    continue;
+                }
+                let coverage = alpha[sy as usize * width + sx as usize] / 255.0;
+                let idx = (y as usize * width + x as usize) * 4;
+                let a = (color.a as f32 * coverage).round() as u8;
+                shadow[idx..idx + 4].copy_from_slice(&[color.r, color.g, color.b, a]);
             }
-            
-            if x1 == x2 && y1 == y2 {
-                break;
+        }
+        shadow
+    }
+    
+    /// Composite a canvas-sized layer onto the canvas with the composite operation
+    fn composite_layer(&mut self, layer: &[u8]) {
+        let operation = self.state.global_composite_operation;
+        let premultiply = |pixel: &[u8], alpha: f32| {
+            let a = pixel[3] as f32 / 255.0 * alpha;
+            [pixel[0] as f32 / 255.0 * a, pixel[1] as f32 / 255.0 * a, pixel[2] as f32 / 255.0 * a, a]
+        };
+        for (i, source) in layer.chunks_exact(4).enumerate() {
+            let clip = self.state.clip.as_ref().map_or(1.0, |clip| clip[i]);
+            if clip == 0.0 || (source[3] == 0 && operation.keeps_unpainted()) {
+                continue;
             }
-            
-            let e2 = 2 * err;
-            if e2 >= dy {
-                err += dy;
-                x1 += sx;
+            let idx = i * 4;
+            let src = premultiply(source, self.state.global_alpha);
+            let dst = premultiply(&self.image_data[idx..idx + 4], 1.0);
+            let out = operation.apply(src, dst);
+            // Outside the clip the canvas keeps its color
+            let out: [f32; 4] = std::array::from_fn(|c| dst[c] + (out[c] - dst[c]) * clip);
+            let pixel = if out[3] > 0.0 {
+                let channel = |c: f32| (c / out[3] * 255.0).round().min(255.0) as u8;
+                [channel(out[0]), channel(out[1]), channel(out[2]), (out[3] * 255.0).round() as u8]
+            } else {
+                [0, 0, 0, 0]
+            };
+            self.image_data[idx..idx + 4].copy_from_slice(&pixel);
+        }
+    }
+    
+    /// Make transparent the pixels a transformed quad covers most of
+    fn clear_quad(&mut self, corners: &[(f32, f32); 4]) {
+        let quad = Subpath { points: corners.to_vec(), closed: true };
+        let mut cleared = Vec::new();
+        rasterize(&[quad], FillRule::NonZero, self.width, self.height, |px, py, amount| {
+            if amount >= 0.5 {
+                cleared.push((px, py));
             }
-            if e2 <= dx {
-                err += dx;
-                y1 += sy;
+        });
+        for (px, py) in cleared {
+            let idx = ((py * self.width + px) * 4) as usize;
+            let clipped = self.state.clip.as_ref().is_some_and(|clip| clip[idx / 4] < 0.5);
+            if !clipped {
+                self.image_data[idx..idx + 4].copy_from_slice(&[255, 255, 255, 0]);
             }
         }
     }
     
-    fn rasterize_path(&mut self, fill: bool) {
-        let color = if fill { self.state.fill_style } else { self.state.stroke_style };
-        let mut current_x = 0.0;
-        let mut current_y = 0.0;
-        
-        // Clone commands to avoid borrow checker issues
-        let commands = self.current_path.commands().to_vec();
-        
-        for cmd in &commands {
-            match cmd {
-                PathCommand::MoveTo { x, y } => {
-                    current_x = *x;
-                    current_y = *y;
-                }
-                PathCommand::LineTo { x, y } => {
-                    self.draw_line(current_x, current_y, *x, *y, color);
-                    current_x = *x;
-                    current_y = *y;
-                }
-                PathCommand::QuadraticCurveTo { cpx, cpy, x, y } => {
-                    // Simplified: approximate with line segments
-                    let steps = 20;
-                    for i in 0..=steps {
-                        let t = i as f32 / steps as f32;
-                        let nt = 1.0 - t;
-                        let qx = nt * nt * current_x + 2.0 * nt * t * cpx + t * t * x;
-                        let qy = nt * nt * current_y + 2.0 * nt * t * cpy + t * t * y;
-                        if i > 0 {
-                            self.draw_line(current_x, current_y, qx, qy, color);
-                        }
-                        current_x = qx;
-                        current_y = qy;
-                    }
-                }
-                PathCommand::BezierCurveTo { cp1x, cp1y, cp2x, cp2y, x, y } => {
-                    // Simplified: approximate with line segments
-                    let steps = 20;
-                    for i in 0..=steps {
-                        let t = i as f32 / steps as f32;
-                        let nt = 1.0 - t;
-                        let bx = nt * nt * nt * current_x + 
-                                3.0 * nt * nt * t * cp1x + 
-                                3.0 * nt * t * t * cp2x + 
-                                t * t * t * x;
-                        let by = nt * nt * nt * current_y + 
-                                3.0 * nt * nt * t * cp1y + 
-                                3.0 * nt * t * t * cp2y + 
-                                t * t * t * y;
-                        if i > 0 {
-                            self.draw_line(current_x, current_y, bx, by, color);
-                        }
-                        current_x = bx;
-                        current_y = by;
-                    }
+    /// Stretch an RGBA image over a parallelogram of canvas pixels
+    ///
+    /// `corners` run clockwise from the image's top left; each pixel center
+    /// inside takes the image pixel it falls in.
+    fn draw_quad_image(&mut self, pixels: &[u8], width: u32, height: u32, corners: &[(f32, f32); 4]) {
+        let [(x0, y0), (x1, y1), _, (x3, y3)] = *corners;
+        let (ux, uy, vx, vy) = (x1 - x0, y1 - y0, x3 - x0, y3 - y0);
+        let det = ux * vy - uy * vx;
+        if det == 0.0 || width == 0 || height == 0 {
+            return;
+        }
+        let (x_range, y_range) = self.pixel_bounds(corners);
+        for py in y_range {
+            for px in x_range.clone() {
+                let (dx, dy) = (px as f32 + 0.5 - x0, py as f32 + 0.5 - y0);
+                let u = (dx * vy - dy * vx) / det;
+                let v = (ux * dy - uy * dx) / det;
+                if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+                    continue;
                 }
-                PathCommand::Arc { x, y, radius, start_angle, end_angle, anticlockwise } => {
-                    // Draw arc with line segments
-                    let steps = 40;
-                    let angle_range = if *anticlockwise {
-                        start_angle - end_angle
-                    } else {
-                        end_angle - start_angle
-                    };
-                    
-                    for i in 0..=steps {
-                        let t = i as f32 / steps as f32;
-                        let angle = if *anticlockwise {
-                            start_angle - angle_range * t
-                        } else {
-                            start_angle + angle_range * t
-                        };
-                        let px = x + radius * angle.cos();
-                        let py = y + radius * angle.sin();
-                        if i > 0 {
-                            self.draw_line(current_x, current_y, px, py, color);
-                        }
-                        current_x = px;
-                        current_y = py;
-                    }
+                let (x, y) = (((u * width as f32) as u32).min(width - 1), ((v * height as f32) as u32).min(height - 1));
+                let idx = ((y * width + x) * 4) as usize;
+                let color = Color::rgba(pixels[idx], pixels[idx + 1], pixels[idx + 2], pixels[idx + 3]);
+                if color.a > 0 {
+                    self.set_pixel(px, py, color);
                 }
-                PathCommand::ClosePath => {
-                    // Would close to first point in subpath
+            }
+        }
+    }
+    
+    /// Fill closed polygons, already transformed, with anti-aliased edges
+    fn fill_polygons(&mut self, subpaths: &[Subpath], rule: FillRule, style: &FillStyle) {
+        let mut covered = Vec::new();
+        rasterize(subpaths, rule, self.width, self.height, |x, y, amount| covered.push((x, y, amount)));
+        for (x, y, amount) in covered {
+            let color = self.style_color(x, y, style);
+            let alpha = (color.a as f32 * amount.min(1.0)).round() as u8;
+            if alpha > 0 {
+                self.set_pixel(x, y, Color { a: alpha, ..color });
+            }
+        }
+    }
+}
+
+/// Scanline rasterize closed polygons, calling `cover` with each pixel they cover and by how much
+///
+/// Each pixel row is sampled along `FILL_SAMPLES` scanlines; a scanline
+/// covers each pixel by the exact width of its spans inside the polygons.
+fn rasterize(subpaths: &[Subpath], rule: FillRule, width: u32, height: u32, mut cover: impl FnMut(u32, u32, f32)) {
+    // Edges from top to bottom, with +1 for those drawn downwards
+    let mut edges: Vec<(f32, f32, f32, f32, i32)> = Vec::new();
+    for subpath in subpaths {
+        for ((x0, y0), (x1, y1)) in subpath.segments(true) {
+            if y0 < y1 {
+                edges.push((x0, y0, x1, y1, 1));
+            } else if y1 < y0 {
+                edges.push((x1, y1, x0, y0, -1));
+            }
+        }
+    }
+    if edges.is_empty() {
+        return;
+    }
+    let top = edges.iter().map(|edge| edge.1).fold(f32::INFINITY, f32::min).max(0.0) as u32;
+    let bottom = edges.iter().map(|edge| edge.3).fold(f32::NEG_INFINITY, f32::max).ceil().min(height as f32);
+    let mut coverage = vec![0.0f32; width as usize];
+    let mut crossings: Vec<(f32, i32)> = Vec::new();
+    for row in top..bottom.max(0.0) as u32 {
+        coverage.iter_mut().for_each(|c| *c = 0.0);
+        for sample in 0..FILL_SAMPLES {
+            let y = row as f32 + (sample as f32 + 0.5) / FILL_SAMPLES as f32;
+            crossings.clear();
+            crossings.extend(edges.iter().filter(|edge| edge.1 <= y && y < edge.3).map(|&(x0, y0, x1, y1, dir)| {
+                (x0 + (y - y0) / (y1 - y0) * (x1 - x0), dir)
+            }));
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let mut winding = 0;
+            for pair in crossings.windows(2) {
+                winding += pair[0].1;
+                if rule.is_inside(winding) {
+                    add_span_coverage(&mut coverage, pair[0].0, pair[1].0, 1.0 / FILL_SAMPLES as f32);
                 }
             }
         }
+        for (x, &amount) in coverage.iter().enumerate() {
+            if amount > 0.0 {
+                cover(x as u32, row, amount);
+            }
+        }
+    }
+}
+
+/// How a path is stroked, in canvas pixels
+struct StrokeGeometry {
+    width: f32,
+    cap: LineCap,
+    join: LineJoin,
+    miter_limit: f32,
+    /// Dash and gap lengths, empty for solid lines
+    dash: Vec<f32>,
+    dash_offset: f32,
+}
+
+/// Polygons covering the stroke of subpaths, to fill with the nonzero rule
+///
+/// Each segment becomes a rectangle, each corner a join and each open end
+/// a cap. All are wound the same way, so where they overlap they add up
+/// rather than cancel out.
+fn stroke_polygons(subpaths: &[Subpath], geometry: &StrokeGeometry) -> Vec<Subpath> {
+    if geometry.width <= 0.0 {
+        return Vec::new();
+    }
+    let mut polygons = Vec::new();
+    for subpath in subpaths {
+        if geometry.dash.iter().sum::<f32>() > 0.0 {
+            for dash in dash_subpath(subpath, &geometry.dash, geometry.dash_offset) {
+                stroke_subpath(&dash, geometry, &mut polygons);
+            }
+        } else {
+            stroke_subpath(subpath, geometry, &mut polygons);
+        }
+    }
+    polygons
+        .into_iter()
+        .map(|mut points| {
+            if signed_area(&points) < 0.0 {
+                points.reverse();
+            }
+            Subpath { points, closed: true }
+        })
+        .collect()
+}
+
+/// Add the polygons stroking one subpath
+fn stroke_subpath(subpath: &Subpath, geometry: &StrokeGeometry, polygons: &mut Vec<Vec<(f32, f32)>>) {
+    let half = geometry.width / 2.0;
+    let mut points = subpath.points.clone();
+    points.dedup();
+    if subpath.closed && points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    let (first, last) = (points[0], points[points.len() - 1]);
+    if points.len() == 1 {
+        // A subpath without length only shows its caps
+        match geometry.cap {
+            LineCap::Butt => {}
+            LineCap::Round => polygons.push(circle(first, half)),
+            LineCap::Square => polygons.push(cap_square(first, (1.0, 0.0), half)),
+        }
+        return;
+    }
+    let closing = subpath.closed.then_some((last, first));
+    let segments: Vec<_> = points.windows(2).map(|pair| (pair[0], pair[1])).chain(closing).collect();
+    for &(a, b) in &segments {
+        let (nx, ny) = scaled(normal(direction(a, b)), half);
+        polygons.push(vec![(a.0 + nx, a.1 + ny), (b.0 + nx, b.1 + ny), (b.0 - nx, b.1 - ny), (a.0 - nx, a.1 - ny)]);
+    }
+    let wrap = subpath.closed.then(|| (segments[segments.len() - 1], segments[0]));
+    for ((a, corner), (_, b)) in segments.windows(2).map(|pair| (pair[0], pair[1])).chain(wrap) {
+        polygons.extend(join(a, corner, b, geometry));
+    }
+    if !subpath.closed {
+        let (start, end) = (direction(points[1], first), direction(points[points.len() - 2], last));
+        for (point, outwards) in [(first, start), (last, end)] {
+            match geometry.cap {
+                LineCap::Butt => {}
+                LineCap::Round => polygons.push(circle(point, half)),
+                LineCap::Square => polygons.push(cap_square(point, outwards, half)),
+            }
+        }
+    }
+}
+
+/// The polygon joining a segment into `corner` to the segment out of it
+fn join(a: (f32, f32), corner: (f32, f32), b: (f32, f32), geometry: &StrokeGeometry) -> Option<Vec<(f32, f32)>> {
+    let half = geometry.width / 2.0;
+    if geometry.join == LineJoin::Round {
+        return Some(circle(corner, half));
+    }
+    let (d0, d1) = (direction(a, corner), direction(corner, b));
+    let cross = d0.0 * d1.1 - d0.1 * d1.0;
+    if cross.abs() < 1e-6 {
+        return None;
+    }
+    // The outer corners of the two segments, on the side the path turns away from
+    let side = if cross > 0.0 { -half } else { half };
+    let (n0, n1) = (scaled(normal(d0), side), scaled(normal(d1), side));
+    let (c0, c1) = ((corner.0 + n0.0, corner.1 + n0.1), (corner.0 + n1.0, corner.1 + n1.1));
+    // A miter is 1 / cos(turn / 2) line widths long
+    let cos_half_turn = ((1.0 + d0.0 * d1.0 + d0.1 * d1.1) / 2.0).sqrt();
+    if geometry.join == LineJoin::Miter && cos_half_turn > 0.0 && 1.0 / cos_half_turn <= geometry.miter_limit {
+        let bisector = direction((0.0, 0.0), (n0.0 + n1.0, n0.1 + n1.1));
+        let (tx, ty) = scaled(bisector, half / cos_half_turn);
+        return Some(vec![corner, c0, (corner.0 + tx, corner.1 + ty), c1]);
+    }
+    Some(vec![corner, c0, c1])
+}
+
+/// Square cap half a line width deep, past `point` in the direction `outwards`
+fn cap_square(point: (f32, f32), outwards: (f32, f32), half: f32) -> Vec<(f32, f32)> {
+    let (nx, ny) = scaled(normal(outwards), half);
+    let (ox, oy) = scaled(outwards, half);
+    vec![
+        (point.0 + nx, point.1 + ny),
+        (point.0 + nx + ox, point.1 + ny + oy),
+        (point.0 - nx + ox, point.1 - ny + oy),
+        (point.0 - nx, point.1 - ny),
+    ]
+}
+
+/// Polygon around a circle
+fn circle(center: (f32, f32), radius: f32) -> Vec<(f32, f32)> {
+    let steps = ((radius * std::f32::consts::PI).ceil() as usize).clamp(8, 512);
+    (0..steps)
+        .map(|i| i as f32 / steps as f32 * 2.0 * std::f32::consts::PI)
+        .map(|angle| (center.0 + radius * angle.cos(), center.1 + radius * angle.sin()))
+        .collect()
+}
+
+/// Unit vector from `a` towards `b`
+fn direction(a: (f32, f32), b: (f32, f32)) -> (f32, f32) {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length = (dx * dx + dy * dy).sqrt();
+    if length > 0.0 {
+        (dx / length, dy / length)
+    } else {
+        (1.0, 0.0)
+    }
+}
+
+/// A vector turned a quarter turn
+fn normal((x, y): (f32, f32)) -> (f32, f32) {
+    (-y, x)
+}
+
+fn scaled((x, y): (f32, f32), factor: f32) -> (f32, f32) {
+    (x * factor, y * factor)
+}
+
+/// Twice the area of a polygon, positive when wound clockwise on the canvas
+fn signed_area(points: &[(f32, f32)]) -> f32 {
+    let closing = points.last().zip(points.first()).map(|(&a, &b)| (a, b));
+    points.windows(2).map(|pair| (pair[0], pair[1])).chain(closing).map(|(a, b)| a.0 * b.1 - b.0 * a.1).sum()
+}
+
+/// Split a subpath into the open subpaths its dashes draw
+///
+/// The pattern starts `offset` into itself at the start of the subpath.
+fn dash_subpath(subpath: &Subpath, dash: &[f32], offset: f32) -> Vec<Subpath> {
+    let period: f32 = dash.iter().sum();
+    let (mut index, mut remaining) = (0, dash[0]);
+    let mut phase = offset.rem_euclid(period);
+    while phase > 0.0 {
+        if phase >= remaining {
+            phase -= remaining;
+            index = (index + 1) % dash.len();
+            remaining = dash[index];
+        } else {
+            remaining -= phase;
+            phase = 0.0;
+        }
+    }
+    // Even entries are dashes, odd ones gaps
+    let mut dashes = Vec::new();
+    let mut current = index.is_multiple_of(2).then(|| vec![subpath.points[0]]);
+    for (a, b) in subpath.segments(subpath.closed) {
+        let length = ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt();
+        let mut travelled = 0.0;
+        while length - travelled > remaining {
+            travelled += remaining;
+            let t = travelled / length;
+            let point = (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+            if let Some(mut points) = current.take() {
+                points.push(point);
+                dashes.push(Subpath { points, closed: false });
+            }
+            index = (index + 1) % dash.len();
+            remaining = dash[index];
+            if index.is_multiple_of(2) {
+                current = Some(vec![point]);
+            }
+        }
+        remaining -= length - travelled;
+        if let Some(points) = current.as_mut() {
+            points.push(b);
+        }
+    }
+    dashes.extend(current.map(|points| Subpath { points, closed: false }));
+    dashes
+}
+
+/// Blur a channel of a canvas-sized image, approximating a Gaussian with three box blurs
+fn blur(values: &mut [f32], width: usize, height: usize, sigma: f32) {
+    if sigma <= 0.0 {
+        return;
+    }
+    // Box sizes whose three passes add up to about the Gaussian's variance
+    let variance = 12.0 * sigma * sigma;
+    let ideal = (variance / 3.0 + 1.0).sqrt().floor() as i64;
+    let lower = if ideal % 2 == 0 { ideal - 1 } else { ideal }.max(1);
+    let excess = (3 * lower * lower + 12 * lower + 9) as f32 - variance;
+    let lower_passes = (excess / (4 * lower + 4) as f32).round() as i64;
+    for pass in 0..3 {
+        let size = if pass < lower_passes { lower } else { lower + 2 };
+        let radius = ((size - 1) / 2) as usize;
+        box_blur(values, height, width, |row, x| row * width + x, radius);
+        box_blur(values, width, height, |column, y| y * width + column, radius);
+    }
+}
+
+/// Average each value along its line with those within `radius`, counting beyond the ends as zero
+fn box_blur(values: &mut [f32], lines: usize, length: usize, index: impl Fn(usize, usize) -> usize, radius: usize) {
+    let mut line = vec![0.0; length];
+    let window = (2 * radius + 1) as f32;
+    for i in 0..lines {
+        for (j, value) in line.iter_mut().enumerate() {
+            *value = values[index(i, j)];
+        }
+        let mut sum: f32 = line[..radius.min(length)].iter().sum();
+        for j in 0..length {
+            if j + radius < length {
+                sum += line[j + radius];
+            }
+            if j > radius {
+                sum -= line[j - radius - 1];
+            }
+            values[index(i, j)] = sum / window;
+        }
+    }
+}
+
+/// Edge of a glyph's coverage, a pixel wide, on a mask one pixel larger all round
+///
+/// Each pixel keeps how much more it is covered than its least covered neighbour.
+fn glyph_outline(bitmap: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let (w, h) = (width as i64, height as i64);
+    let coverage = |x: i64, y: i64| {
+        if x < 0 || y < 0 || x >= w || y >= h {
+            0
+        } else {
+            bitmap[(y * w + x) as usize]
+        }
+    };
+    let mut outline = Vec::with_capacity(((w + 2) * (h + 2)) as usize);
+    for y in -1..=h {
+        for x in -1..=w {
+            let least = (-1..=1)
+                .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+                .map(|(dx, dy)| coverage(x + dx, y + dy))
+                .min()
+                .unwrap_or(0);
+            outline.push(coverage(x, y) - least);
+        }
+    }
+    outline
+}
+
+/// Scanlines sampled per pixel row when filling
+const FILL_SAMPLES: usize = 4;
+
+/// Add a horizontal span's share of each pixel it crosses, clipped to the row
+fn add_span_coverage(coverage: &mut [f32], x0: f32, x1: f32, weight: f32) {
+    let (x0, x1) = (x0.max(0.0), x1.min(coverage.len() as f32));
+    if x1 <= x0 {
+        return;
+    }
+    let (first, last) = (x0.floor() as usize, x1.floor() as usize);
+    if first == last {
+        coverage[first] += (x1 - x0) * weight;
+        return;
+    }
+    coverage[first] += (first as f32 + 1.0 - x0) * weight;
+    for c in &mut coverage[first + 1..last] {
+        *c += weight;
+    }
+    if last < coverage.len() {
+        coverage[last] += (x1 - last as f32) * weight;
     }
 }
 
@@ -663,8 +2623,8 @@ mod tests {
         ctx.fill_rect(10.0, 10.0, 20.0, 20.0);
         
         // Check that some pixels were set
-        let image_data = ctx.get_image_data();
-        assert!(image_data.len() > 0);
+        let image_data = ctx.get_image_data(0, 0, 100, 100).unwrap();
+        assert_eq!(&image_data.data()[(15 * 100 + 15) * 4..][..4], &[255, 0, 0, 255]);
     }
     
     #[test]
@@ -690,7 +2650,7 @@ mod tests {
         ctx.set_fill_style(Color::rgb(0, 255, 0));
         ctx.restore();
         
-        assert_eq!(ctx.state.fill_style, Color::rgb(255, 0, 0));
+        assert_eq!(ctx.state.fill_style, FillStyle::Color(Color::rgb(255, 0, 0)));
     }
     
     #[test]
@@ -729,6 +2689,66 @@ mod tests {
         let ctx = canvas.get_context_2d();
         
         ctx.fill_text("Hello", 10.0, 50.0);
+        let inked = |ctx: &CanvasRenderingContext2D, columns: std::ops::Range<usize>, rows: std::ops::Range<usize>| {
+            rows.flat_map(|y| columns.clone().map(move |x| (x, y))).any(|(x, y)| ctx.pixels()[(y * 200 + x) * 4] < 128)
+        };
+        assert!(inked(ctx, 10..60, 35..50));
+        assert!(!inked(ctx, 0..200, 51..100));
+        assert!(!inked(ctx, 0..10, 0..100));
+    }
+    
+    #[test]
+    fn test_text_alignment_and_metrics() {
+        let mut canvas = Canvas::new(200, 100);
+        let ctx = canvas.get_context_2d();
+        ctx.set_font("not a font");
+        assert_eq!(ctx.font(), "10px sans-serif");
+        ctx.set_font("bold 20px sans-serif");
+        assert_eq!(ctx.font(), "bold 20px sans-serif");
+        
+        let one = ctx.measure_text("Hi");
+        let two = ctx.measure_text("HiHi");
+        assert!(one.width > 0.0);
+        assert!((two.width - 2.0 * one.width).abs() < 0.01);
+        assert!(one.actual_bounding_box_ascent > 10.0);
+        assert!(one.actual_bounding_box_ascent <= one.font_bounding_box_ascent);
+        
+        // Right aligned text ends at x; top baseline text hangs below y
+        ctx.set_text_align(TextAlign::Right);
+        ctx.set_text_baseline(TextBaseline::Top);
+        let metrics = ctx.measure_text("Hi");
+        assert!((metrics.actual_bounding_box_left - one.width).abs() < 2.0);
+        assert!(metrics.actual_bounding_box_right <= 0.5);
+        assert!(metrics.actual_bounding_box_ascent <= 1.0);
+        assert!(metrics.actual_bounding_box_descent > 10.0);
+        ctx.fill_text("Hi", 150.0, 20.0);
+        let inked = |ctx: &CanvasRenderingContext2D, columns: std::ops::Range<usize>, rows: std::ops::Range<usize>| {
+            rows.flat_map(|y| columns.clone().map(move |x| (x, y))).any(|(x, y)| ctx.pixels()[(y * 200 + x) * 4] < 128)
+        };
+        assert!(inked(ctx, 120..150, 20..45));
+        assert!(!inked(ctx, 151..200, 0..100));
+        assert!(!inked(ctx, 0..200, 0..19));
+        
+        // Stroked text outlines its glyphs without filling them
+        let draw = |fill: bool| {
+            let mut canvas = Canvas::new(200, 100);
+            let ctx = canvas.get_context_2d();
+            ctx.set_font("bold 60px sans-serif");
+            if fill {
+                ctx.fill_text("H", 10.0, 80.0);
+            } else {
+                ctx.stroke_text("H", 10.0, 80.0);
+            }
+            ctx.pixels().iter().step_by(4).map(|&red| red < 128).collect::<Vec<_>>()
+        };
+        let (filled, outlined) = (draw(true), draw(false));
+        assert!(outlined.iter().any(|&inked| inked));
+        assert!(filled.iter().zip(&outlined).any(|(&filled, &outlined)| filled && !outlined));
+        
+        assert_eq!(CanvasFont::parse("italic 12pt \"Times New Roman\", serif"),
+            Some(CanvasFont { size: 16.0, family: "Times New Roman".to_string() }));
+        assert_eq!(CanvasFont::parse("2em/3 monospace").map(|font| font.size), Some(20.0));
+        assert_eq!(CanvasFont::parse("12px"), None);
     }
     
     #[test]
@@ -740,4 +2760,506 @@ mod tests {
         ctx.set_fill_style(Color::rgb(255, 0, 0));
         ctx.fill_rect(10.0, 10.0, 50.0, 50.0);
     }
+    
+    #[test]
+    fn test_fill_shapes() {
+        let mut canvas = Canvas::new(100, 100);
+        let ctx = canvas.get_context_2d();
+        let pixel = |data: &[u8], x: usize, y: usize| data[(y * 100 + x) * 4..][..4].to_vec();
+        ctx.set_fill_style(Color::rgb(0, 0, 0));
+        
+        // A circle is solid inside, blended at its edge and untouched outside
+        ctx.begin_path();
+        ctx.arc(50.0, 50.0, 20.0, 0.0, 2.0 * std::f32::consts::PI, false);
+        ctx.fill();
+        let data = ctx.pixels().to_vec();
+        assert_eq!(pixel(&data, 50, 50), vec![0, 0, 0, 255]);
+        assert_eq!(pixel(&data, 50, 31), vec![0, 0, 0, 255]);
+        assert_eq!(pixel(&data, 50, 25), vec![255, 255, 255, 255]);
+        let edge = pixel(&data, 64, 64)[0];
+        assert!(edge > 0 && edge < 255, "edge pixel {}", edge);
+        
+        // An open triangle is closed to fill it
+        let mut canvas = Canvas::new(100, 100);
+        let ctx = canvas.get_context_2d();
+        ctx.begin_path();
+        ctx.move_to(10.0, 10.0);
+        ctx.line_to(90.0, 10.0);
+        ctx.line_to(10.0, 90.0);
+        ctx.fill();
+        let data = ctx.pixels().to_vec();
+        assert_eq!(pixel(&data, 20, 20), vec![0, 0, 0, 255]);
+        assert_eq!(pixel(&data, 80, 80), vec![255, 255, 255, 255]);
+    }
+    
+    #[test]
+    fn test_fill_rules() {
+        // Two squares, one inside the other, both drawn clockwise
+        let square = |ctx: &mut CanvasRenderingContext2D, x: f32, size: f32| {
+            ctx.move_to(x, x);
+            ctx.line_to(x + size, x);
+            ctx.line_to(x + size, x + size);
+            ctx.line_to(x, x + size);
+            ctx.close_path();
+        };
+        let mut canvas = Canvas::new(100, 100);
+        let ctx = canvas.get_context_2d();
+        ctx.begin_path();
+        square(ctx, 10.0, 80.0);
+        square(ctx, 30.0, 40.0);
+        assert_eq!(ctx.current_path.subpaths().iter().filter(|subpath| subpath.closed).count(), 2);
+        ctx.fill_with_rule(FillRule::EvenOdd);
+        let data = ctx.pixels().to_vec();
+        assert_eq!(data[(50 * 100 + 50) * 4..][..4], [255, 255, 255, 255]);
+        assert_eq!(data[(20 * 100 + 20) * 4..][..4], [0, 0, 0, 255]);
+        
+        // Nonzero fills the hole; whole-pixel edges leave no partial coverage
+        ctx.fill();
+        let data = ctx.pixels().to_vec();
+        assert_eq!(data[(50 * 100 + 50) * 4..][..4], [0, 0, 0, 255]);
+        assert_eq!(data[(10 * 100 + 10) * 4..][..4], [0, 0, 0, 255]);
+        assert_eq!(data[(9 * 100 + 10) * 4..][..4], [255, 255, 255, 255]);
+        assert_eq!(data[(10 * 100 + 90) * 4..][..4], [255, 255, 255, 255]);
+        assert_eq!(FillRule::parse("evenodd"), Some(FillRule::EvenOdd));
+        assert_eq!(FillRule::parse("odd"), None);
+    }
+    
+    #[test]
+    fn test_transforms() {
+        let mut canvas = Canvas::new(100, 100);
+        let ctx = canvas.get_context_2d();
+        let pixel = |ctx: &CanvasRenderingContext2D, x: usize, y: usize| {
+            ctx.image_data[(y * 100 + x) * 4..][..4].to_vec()
+        };
+        let (black, white) = (vec![0, 0, 0, 255], vec![255, 255, 255, 255]);
+        
+        ctx.save();
+        ctx.translate(10.0, 10.0);
+        ctx.scale(2.0, 2.0);
+        assert_eq!(ctx.get_transform(), [2.0, 0.0, 0.0, 2.0, 10.0, 10.0]);
+        ctx.fill_rect(0.0, 0.0, 10.0, 10.0);
+        assert_eq!(pixel(ctx, 29, 29), black);
+        assert_eq!(pixel(ctx, 30, 30), white);
+        assert_eq!(pixel(ctx, 9, 9), white);
+        ctx.set_transform(f32::NAN, 0.0, 0.0, 1.0, 0.0, 0.0);
+        assert_eq!(ctx.get_transform(), [2.0, 0.0, 0.0, 2.0, 10.0, 10.0]);
+        ctx.restore();
+        assert_eq!(ctx.get_transform(), IDENTITY);
+        
+        // A quarter turn clockwise about (50, 0) lays the rectangle on its side
+        ctx.translate(50.0, 0.0);
+        ctx.rotate(std::f32::consts::FRAC_PI_2);
+        ctx.fill_rect(0.0, 0.0, 10.0, 20.0);
+        assert_eq!(pixel(ctx, 40, 5), black);
+        assert_eq!(pixel(ctx, 55, 5), white);
+        assert_eq!(pixel(ctx, 40, 15), white);
+        
+        // Paths keep the transform they were drawn with
+        ctx.reset_transform();
+        ctx.begin_path();
+        ctx.translate(50.0, 70.0);
+        ctx.arc(0.0, 0.0, 10.0, 0.0, 2.0 * std::f32::consts::PI, false);
+        ctx.reset_transform();
+        ctx.fill();
+        assert_eq!(pixel(ctx, 50, 70), black);
+        assert_eq!(pixel(ctx, 50, 85), white);
+    }
+    
+    #[test]
+    fn test_clip() {
+        let mut canvas = Canvas::new(100, 100);
+        let ctx = canvas.get_context_2d();
+        let pixel = |ctx: &CanvasRenderingContext2D, x: usize, y: usize| {
+            ctx.image_data[(y * 100 + x) * 4..][..4].to_vec()
+        };
+        let square = |ctx: &mut CanvasRenderingContext2D, from: f32, to: f32| {
+            ctx.begin_path();
+            ctx.move_to(from, from);
+            ctx.line_to(to, from);
+            ctx.line_to(to, to);
+            ctx.line_to(from, to);
+            ctx.close_path();
+        };
+        
+        ctx.save();
+        square(ctx, 10.0, 50.0);
+        ctx.clip();
+        ctx.set_fill_style(Color::rgb(255, 0, 0));
+        ctx.fill_rect(0.0, 0.0, 100.0, 100.0);
+        assert_eq!(pixel(ctx, 20, 20), vec![255, 0, 0, 255]);
+        assert_eq!(pixel(ctx, 60, 60), vec![255, 255, 255, 255]);
+        
+        // A second clip narrows the first; clearing is clipped too
+        square(ctx, 30.0, 70.0);
+        ctx.clip();
+        ctx.clear_rect(0.0, 0.0, 100.0, 100.0);
+        assert_eq!(pixel(ctx, 40, 40)[3], 0);
+        assert_eq!(pixel(ctx, 20, 20), vec![255, 0, 0, 255]);
+        assert_eq!(pixel(ctx, 60, 60), vec![255, 255, 255, 255]);
+        
+        // Restoring lifts the clip
+        ctx.restore();
+        ctx.set_fill_style(Color::rgb(0, 0, 255));
+        ctx.fill_rect(0.0, 0.0, 100.0, 100.0);
+        assert_eq!(pixel(ctx, 60, 60), vec![0, 0, 255, 255]);
+    }
+    
+    #[test]
+    fn test_image_data() {
+        let mut canvas = Canvas::new(10, 10);
+        let ctx = canvas.get_context_2d();
+        assert!(ctx.create_image_data(0, 5).is_err());
+        let mut image = ctx.create_image_data(-2, 2).unwrap();
+        assert_eq!((image.width(), image.height()), (2, 2));
+        assert!(image.data().iter().all(|&value| value == 0));
+        
+        // Opaque pixels round-trip; translucent ones as if stored premultiplied
+        image.data_mut()[..8].copy_from_slice(&[10, 20, 30, 255, 200, 100, 50, 2]);
+        image.data_mut()[8..12].copy_from_slice(&[255, 0, 0, 0]);
+        ctx.set_global_alpha(0.5);
+        ctx.translate(5.0, 5.0);
+        ctx.put_image_data(&image, 8, 8);
+        let read = ctx.get_image_data(8, 8, 3, 3).unwrap();
+        assert_eq!(&read.data()[..8], &[10, 20, 30, 255, 255, 128, 0, 2]);
+        // The pixels beyond the canvas edge are transparent black
+        assert_eq!(&read.data()[8..12], &[0, 0, 0, 0]);
+        assert_eq!(&read.data()[(2 * 3 + 2) * 4..][..4], &[0, 0, 0, 0]);
+        
+        // A negative size reads back from the corner; the dirty rectangle limits the write
+        let corner = ctx.get_image_data(9, 9, -1, -1).unwrap();
+        assert_eq!(corner.data(), &[10, 20, 30, 255]);
+        ctx.put_image_data_dirty(&image, 0, 0, 1, -5, 10, 6);
+        let written = ctx.get_image_data(0, 0, 2, 1).unwrap();
+        assert_eq!(written.data(), &[255, 255, 255, 255, 255, 128, 0, 2]);
+        
+        assert!(ImageData::from_data(vec![0; 12], 2, None).is_err());
+        assert!(ImageData::from_data(vec![0; 16], 2, Some(3)).is_err());
+        assert_eq!(ImageData::from_data(vec![0; 16], 2, Some(2)).unwrap().height(), 2);
+    }
+    
+    #[test]
+    fn test_gradients() {
+        let mut canvas = Canvas::new(100, 100);
+        let ctx = canvas.get_context_2d();
+        let pixel = |data: &[u8], x: usize, y: usize| data[(y * 100 + x) * 4..][..4].to_vec();
+        
+        let mut gradient = ctx.create_linear_gradient(0.0, 0.0, 100.0, 0.0);
+        assert!(gradient.add_color_stop(1.5, Color::rgb(0, 0, 0)).is_err());
+        gradient.add_color_stop(1.0, Color::rgb(0, 0, 255)).unwrap();
+        gradient.add_color_stop(0.0, Color::rgb(255, 0, 0)).unwrap();
+        assert_eq!(gradient.stops()[0], (0.0, Color::rgb(255, 0, 0)));
+        ctx.set_fill_style(gradient);
+        ctx.fill_rect(0.0, 0.0, 100.0, 100.0);
+        let data = ctx.pixels().to_vec();
+        assert_eq!(pixel(&data, 0, 50), vec![254, 0, 1, 255]);
+        assert_eq!(pixel(&data, 49, 0), vec![129, 0, 126, 255]);
+        assert_eq!(pixel(&data, 99, 99), vec![1, 0, 254, 255]);
+        
+        // Stops clamp beyond the ends; a zero-length gradient paints nothing
+        let mut gradient = CanvasGradient::linear(40.0, 0.0, 60.0, 0.0);
+        gradient.add_color_stop(0.0, Color::rgb(255, 255, 255)).unwrap();
+        gradient.add_color_stop(1.0, Color::rgb(0, 0, 0)).unwrap();
+        assert_eq!(gradient.color_at(0.0, 0.0), Color::rgb(255, 255, 255));
+        assert_eq!(gradient.color_at(90.0, 30.0), Color::rgb(0, 0, 0));
+        assert_eq!(CanvasGradient::linear(5.0, 5.0, 5.0, 5.0).color_at(5.0, 5.0), Color::transparent());
+        
+        // Concentric circles: the offset is the distance between the radii
+        assert!(ctx.create_radial_gradient(0.0, 0.0, -1.0, 0.0, 0.0, 10.0).is_err());
+        let mut radial = ctx.create_radial_gradient(50.0, 50.0, 10.0, 50.0, 50.0, 30.0).unwrap();
+        radial.add_color_stop(0.0, Color::rgb(0, 0, 0)).unwrap();
+        radial.add_color_stop(1.0, Color::rgb(200, 200, 200)).unwrap();
+        assert_eq!(radial.color_at(50.0, 50.0), Color::rgb(0, 0, 0));
+        assert_eq!(radial.color_at(70.0, 50.0), Color::rgb(100, 100, 100));
+        assert_eq!(radial.color_at(50.0, 95.0), Color::rgb(200, 200, 200));
+        
+        // A point outside the cone of two circles side by side is not painted
+        let mut cone = CanvasGradient::radial(20.0, 50.0, 5.0, 80.0, 50.0, 5.0).unwrap();
+        cone.add_color_stop(0.0, Color::rgb(255, 0, 0)).unwrap();
+        assert_eq!(cone.color_at(50.0, 52.0), Color::rgb(255, 0, 0));
+        assert_eq!(cone.color_at(50.0, 90.0), Color::transparent());
+    }
+    
+    #[test]
+    fn test_patterns() {
+        let mut canvas = Canvas::new(10, 10);
+        let ctx = canvas.get_context_2d();
+        // A 2x1 image: red then green
+        let image = [255, 0, 0, 255, 0, 255, 0, 255];
+        let unknown = CanvasError::Syntax("unknown pattern repetition 'tile'".to_string());
+        assert_eq!(ctx.create_pattern(&image, 2, 1, "tile"), Err(unknown));
+        assert_eq!(ctx.create_pattern(&image, 0, 1, "repeat"), Ok(None));
+        assert!(ctx.create_pattern(&image, 2, 2, "repeat").is_err());
+        
+        let pattern = ctx.create_pattern(&image, 2, 1, "").unwrap().unwrap();
+        assert_eq!(pattern.repetition(), PatternRepetition::Repeat);
+        assert_eq!(pattern.color_at(3.5, 7.5), Color::rgb(0, 255, 0));
+        assert_eq!(pattern.color_at(-0.5, 0.5), Color::rgb(0, 255, 0));
+        let row = ctx.create_pattern(&image, 2, 1, "repeat-x").unwrap().unwrap();
+        assert_eq!(row.color_at(4.5, 0.5), Color::rgb(255, 0, 0));
+        assert_eq!(row.color_at(4.5, 1.5), Color::transparent());
+        
+        ctx.set_stroke_style(pattern);
+        ctx.begin_path();
+        ctx.move_to(0.0, 5.5);
+        ctx.line_to(9.0, 5.5);
+        ctx.stroke();
+        let data = ctx.pixels().to_vec();
+        assert_eq!(data[(5 * 10 + 2) * 4..][..4], [255, 0, 0, 255]);
+        assert_eq!(data[(5 * 10 + 3) * 4..][..4], [0, 255, 0, 255]);
+        assert!(matches!(ctx.stroke_style(), FillStyle::Pattern(_)));
+    }
+    
+    #[test]
+    fn test_accelerated_drawing() {
+        // The same drawing, rasterized straight away and recorded for the GPU
+        let draw = |ctx: &mut CanvasRenderingContext2D| {
+            ctx.set_fill_style(Color::rgb(255, 0, 0));
+            ctx.fill_rect(10.0, 10.0, 30.0, 20.0);
+            ctx.translate(5.0, 5.0);
+            ctx.begin_path();
+            ctx.move_to(50.0, 10.0);
+            ctx.line_to(90.0, 10.0);
+            ctx.line_to(70.0, 50.0);
+            ctx.close_path();
+            ctx.fill_with_rule(FillRule::EvenOdd);
+            ctx.set_stroke_style(Color::rgba(0, 0, 255, 128));
+            ctx.stroke();
+            ctx.stroke_rect(5.0, 60.0, 40.0, 30.0);
+            ctx.draw_image(&[0, 255, 0, 255].repeat(4), 0.0, 0.0, 2.0, 2.0, 60.0, 60.0, 10.0, 10.0);
+            ctx.clear_rect(20.0, 15.0, 5.0, 5.0);
+        };
+        let mut cpu = CanvasRenderingContext2D::new(100, 100);
+        draw(&mut cpu);
+        let mut gpu = CanvasRenderingContext2D::new(100, 100);
+        gpu.set_accelerated(true);
+        draw(&mut gpu);
+        assert!(gpu.is_accelerated());
+        
+        // The GPU starts from the white canvas, then takes each command once
+        let commands = gpu.take_commands();
+        assert_eq!(commands.len(), 8);
+        assert!(matches!(commands[1], CanvasCommand::Image { width: 100, height: 100, .. }));
+        assert!(matches!(commands[3], CanvasCommand::Fill { rule: FillRule::EvenOdd, .. }));
+        assert!(matches!(commands[4], CanvasCommand::Fill { color: Color { a: 128, .. }, .. }));
+        assert!(matches!(commands[7], CanvasCommand::Clear { .. }));
+        assert!(gpu.take_commands().is_empty());
+        
+        // The CPU backing store catches up when it is read
+        assert_eq!(gpu.pixels()[(15 * 100 + 15) * 4..][..4], [255, 255, 255, 255]);
+        let expected = cpu.get_image_data(0, 0, 100, 100).unwrap();
+        assert_eq!(gpu.get_image_data(0, 0, 100, 100).unwrap(), expected);
+        
+        // Clearing the whole canvas leaves nothing before it to draw
+        gpu.reset_transform();
+        gpu.fill_rect(0.0, 0.0, 10.0, 10.0);
+        gpu.clear_rect(0.0, 0.0, 100.0, 100.0);
+        let corners = [(0.0, 0.0), (100.0, 0.0), (100.0, 100.0), (0.0, 100.0)];
+        assert_eq!(gpu.take_commands(), [CanvasCommand::Clear { corners }]);
+        
+        // A gradient cannot be recorded, so the context goes back to the CPU
+        let mut gradient = gpu.create_linear_gradient(0.0, 0.0, 10.0, 0.0);
+        gradient.add_color_stop(0.0, Color::rgb(0, 0, 0)).unwrap();
+        gpu.set_fill_style(gradient);
+        gpu.fill_rect(0.0, 0.0, 10.0, 10.0);
+        assert!(!gpu.is_accelerated());
+        assert!(gpu.take_commands().is_empty());
+        assert_eq!(gpu.pixels()[(5 * 100 + 5) * 4..][..4], [0, 0, 0, 255]);
+        assert_eq!(gpu.pixels()[(5 * 100 + 50) * 4..][..4], [255, 255, 255, 0]);
+    }
+    
+    #[test]
+    fn test_line_caps_joins_and_dashes() {
+        let covered = |ctx: &CanvasRenderingContext2D, x: u32, y: u32| ctx.pixels()[((y * 100 + x) * 4) as usize] == 0;
+        let line = |cap: LineCap| {
+            let mut ctx = CanvasRenderingContext2D::new(100, 100);
+            ctx.set_line_width(10.0);
+            ctx.set_line_cap(cap);
+            ctx.begin_path();
+            ctx.move_to(20.0, 50.0);
+            ctx.line_to(80.0, 50.0);
+            ctx.stroke();
+            ctx
+        };
+        let butt = line(LineCap::Butt);
+        assert!(covered(&butt, 50, 45) && covered(&butt, 50, 54) && !covered(&butt, 50, 56));
+        assert!(!covered(&butt, 17, 50));
+        let square = line(LineCap::Square);
+        assert!(covered(&square, 15, 45) && covered(&square, 84, 54));
+        let round = line(LineCap::Round);
+        assert!(covered(&round, 16, 50) && !covered(&round, 15, 45));
+        
+        // A sharp corner at (50, 20), its outside pointing up
+        let corner = |join: LineJoin, miter_limit: f32| {
+            let mut ctx = CanvasRenderingContext2D::new(100, 100);
+            ctx.set_line_width(10.0);
+            ctx.set_line_join(join);
+            ctx.set_miter_limit(miter_limit);
+            ctx.begin_path();
+            ctx.move_to(20.0, 80.0);
+            ctx.line_to(50.0, 20.0);
+            ctx.line_to(80.0, 80.0);
+            ctx.stroke();
+            ctx
+        };
+        let miter = corner(LineJoin::Miter, 10.0);
+        assert!(covered(&miter, 50, 11) && !covered(&miter, 50, 7));
+        let round = corner(LineJoin::Round, 10.0);
+        assert!(covered(&round, 50, 16) && !covered(&round, 50, 14));
+        // Past the miter limit, and with a bevel, the corner is cut off
+        for bevel in [corner(LineJoin::Bevel, 10.0), corner(LineJoin::Miter, 2.0)] {
+            assert!(covered(&bevel, 50, 18) && !covered(&bevel, 50, 16));
+        }
+        
+        let mut ctx = CanvasRenderingContext2D::new(100, 100);
+        ctx.set_line_dash(&[10.0]);
+        assert_eq!(ctx.line_dash(), [10.0, 10.0]);
+        ctx.set_line_dash(&[5.0, -1.0]);
+        assert_eq!(ctx.line_dash(), [10.0, 10.0]);
+        ctx.set_line_width(4.0);
+        ctx.set_line_dash_offset(5.0);
+        ctx.begin_path();
+        ctx.move_to(0.0, 50.0);
+        ctx.line_to(100.0, 50.0);
+        ctx.stroke();
+        assert!(covered(&ctx, 2, 50) && !covered(&ctx, 7, 50) && covered(&ctx, 17, 50) && !covered(&ctx, 27, 50));
+    }
+    
+    #[test]
+    fn test_shadows() {
+        let mut ctx = CanvasRenderingContext2D::new(100, 100);
+        ctx.set_fill_style(Color::rgb(255, 0, 0));
+        ctx.set_shadow_color(Color::rgb(0, 0, 255));
+        ctx.set_shadow_offset_x(10.0);
+        ctx.set_shadow_offset_y(10.0);
+        ctx.fill_rect(10.0, 10.0, 20.0, 20.0);
+        let pixel = |ctx: &CanvasRenderingContext2D, x: u32, y: u32| {
+            ctx.pixels()[((y * 100 + x) * 4) as usize..][..4].to_vec()
+        };
+        // The shape is drawn over its shadow
+        assert_eq!(pixel(&ctx, 15, 15), [255, 0, 0, 255]);
+        assert_eq!(pixel(&ctx, 35, 35), [0, 0, 255, 255]);
+        assert_eq!(pixel(&ctx, 45, 45), [255, 255, 255, 255]);
+        
+        // A blurred shadow fades out across its edge
+        let mut ctx = CanvasRenderingContext2D::new(100, 100);
+        ctx.set_shadow_color(Color::rgb(0, 0, 0));
+        ctx.set_shadow_blur(8.0);
+        ctx.set_shadow_offset_x(40.0);
+        ctx.fill_rect(10.0, 30.0, 20.0, 40.0);
+        let shade = |x: u32| pixel(&ctx, x, 50)[0];
+        assert!(shade(60) < 10);
+        assert!(shade(50) > shade(60) && shade(50) < 200);
+        assert!(shade(40) > shade(50) && shade(40) > 245);
+        assert_eq!(shade(95), 255);
+    }
+    
+    #[test]
+    fn test_composite_operations() {
+        assert_eq!(CompositeOperation::parse("destination-over"), Some(CompositeOperation::DestinationOver));
+        assert_eq!(CompositeOperation::parse("darken"), None);
+        let draw = |operation: CompositeOperation| {
+            let mut ctx = CanvasRenderingContext2D::new(100, 100);
+            ctx.clear_rect(0.0, 0.0, 100.0, 100.0);
+            ctx.set_fill_style(Color::rgb(255, 0, 0));
+            ctx.fill_rect(0.0, 0.0, 50.0, 50.0);
+            ctx.set_global_composite_operation(operation);
+            ctx.set_fill_style(Color::rgb(0, 0, 255));
+            ctx.fill_rect(25.0, 25.0, 50.0, 50.0);
+            // Only red, only blue, both and neither
+            [(10, 10), (60, 60), (30, 30), (90, 10)].map(|(x, y)| ctx.get_image_data(x, y, 1, 1).unwrap().data)
+        };
+        let (red, blue, none) = (vec![255, 0, 0, 255], vec![0, 0, 255, 255], vec![0, 0, 0, 0]);
+        let cases = [
+            (CompositeOperation::SourceOver, [&red, &blue, &blue, &none]),
+            (CompositeOperation::DestinationOver, [&red, &blue, &red, &none]),
+            (CompositeOperation::SourceIn, [&none, &none, &blue, &none]),
+            (CompositeOperation::SourceAtop, [&red, &none, &blue, &none]),
+            (CompositeOperation::DestinationOut, [&red, &none, &none, &none]),
+            (CompositeOperation::Xor, [&red, &blue, &none, &none]),
+            (CompositeOperation::Copy, [&none, &blue, &blue, &none]),
+        ];
+        for (operation, expected) in cases {
+            assert_eq!(draw(operation).iter().collect::<Vec<_>>(), expected, "{:?}", operation);
+        }
+        
+        // Lighter adds colors; the clip limits what an operation can clear
+        let [_, _, both, _] = draw(CompositeOperation::Lighter);
+        assert_eq!(both, [255, 0, 255, 255]);
+        let mut ctx = CanvasRenderingContext2D::new(100, 100);
+        ctx.begin_path();
+        ctx.move_to(0.0, 0.0);
+        ctx.line_to(50.0, 0.0);
+        ctx.line_to(50.0, 100.0);
+        ctx.line_to(0.0, 100.0);
+        ctx.clip();
+        ctx.set_global_composite_operation(CompositeOperation::Copy);
+        ctx.fill_rect(10.0, 10.0, 10.0, 10.0);
+        assert_eq!(ctx.get_image_data(30, 50, 1, 1).unwrap().data, [0, 0, 0, 0]);
+        assert_eq!(ctx.get_image_data(70, 50, 1, 1).unwrap().data, [255, 255, 255, 255]);
+    }
+    
+    #[test]
+    fn test_to_data_url_and_blob() {
+        let mut canvas = Canvas::new(4, 2);
+        let ctx = canvas.get_context_2d();
+        ctx.set_fill_style(Color::rgba(255, 0, 0, 128));
+        ctx.clear_rect(0.0, 0.0, 4.0, 2.0);
+        ctx.fill_rect(0.0, 0.0, 2.0, 2.0);
+        
+        let png = canvas.to_blob("image/png", None).unwrap();
+        assert_eq!(png.mime_type(), "image/png");
+        let decoded = image::load_from_memory(&png.data).unwrap().to_rgba8();
+        assert_eq!(decoded.dimensions(), (4, 2));
+        assert_eq!(decoded.as_raw(), canvas.pixels());
+        assert_eq!(decoded.get_pixel(3, 1).0[3], 0);
+        
+        // JPEG composites onto black; unknown types fall back to PNG
+        let jpeg = canvas.to_blob("IMAGE/JPEG", Some(1.0)).unwrap();
+        assert_eq!(jpeg.encoding, ImageEncoding::Jpeg);
+        let decoded = image::load_from_memory(&jpeg.data).unwrap().to_rgb8();
+        let [r, g, b] = decoded.get_pixel(0, 0).0;
+        assert!(r.abs_diff(128) < 8 && g < 8 && b < 8, "{:?}", (r, g, b));
+        assert_eq!(canvas.to_blob("image/bmp", None).unwrap().encoding, ImageEncoding::Png);
+        
+        let url = canvas.to_data_url("image/png", None);
+        assert!(url.starts_with("data:image/png;base64,iVBORw0KGgo"));
+        assert_eq!(Canvas::new(0, 10).to_data_url("image/png", None), "data:,");
+        assert!(Canvas::new(10, 0).to_blob("image/png", None).is_none());
+    }
+    
+    #[test]
+    fn test_offscreen_canvas() {
+        let mut offscreen = OffscreenCanvas::new(8, 8);
+        let frame = std::thread::spawn(move || {
+            offscreen.get_context_2d().set_fill_style(Color::rgb(0, 0, 255));
+            offscreen.get_context_2d().fill_rect(0.0, 0.0, 4.0, 8.0);
+            let frame = offscreen.transfer_to_image_bitmap().unwrap();
+            (frame, offscreen)
+        });
+        let (frame, mut offscreen) = frame.join().unwrap();
+        assert_eq!((frame.width(), frame.height()), (8, 8));
+        assert_eq!(&frame.data()[..4], [0, 0, 255, 255]);
+        assert_eq!(&frame.data()[7 * 4..8 * 4], [255, 255, 255, 255]);
+        
+        // The frame is taken; the state is kept
+        let ctx = offscreen.get_context_2d();
+        assert_eq!(ctx.get_image_data(0, 0, 1, 1).unwrap().data(), [0, 0, 0, 0]);
+        assert_eq!(ctx.fill_style(), &FillStyle::Color(Color::rgb(0, 0, 255)));
+        ctx.fill_rect(0.0, 0.0, 1.0, 1.0);
+        let png = offscreen.convert_to_blob("image/png", None).unwrap();
+        let decoded = image::load_from_memory(&png.data).unwrap().to_rgba8();
+        assert_eq!(decoded.get_pixel(0, 0).0, [0, 0, 255, 255]);
+        assert_eq!(decoded.get_pixel(1, 0).0, [0, 0, 0, 0]);
+        
+        // Resizing resets the context
+        offscreen.set_width(2);
+        assert_eq!(offscreen.get_context_2d().pixels().len(), 2 * 8 * 4);
+        assert_eq!(offscreen.get_context_2d().fill_style(), &FillStyle::Color(Color::rgb(0, 0, 0)));
+        offscreen.set_height(0);
+        assert!(matches!(offscreen.convert_to_blob("image/png", None), Err(CanvasError::IndexSize(_))));
+        assert!(matches!(
+            OffscreenCanvas::new(1, 1).transfer_to_image_bitmap(),
+            Err(CanvasError::InvalidState(_))
+        ));
+    }
 }
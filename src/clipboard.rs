@@ -0,0 +1,327 @@
+// Clipboard integration: OS clipboard access, form field cut/copy/paste,
+// and servicing of navigator.clipboard requests from scripts
+
+use crate::forms::{InputState, InputType, TextAreaState};
+use crate::js::{ClipboardRequestKind, JsContext, JsError};
+
+pub use crate::permissions::PermissionState;
+
+/// Clipboard errors
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClipboardError {
+    /// The OS clipboard could not be accessed
+    Unavailable(String),
+    /// The clipboard holds no text
+    Empty,
+    /// Access was blocked by permissions
+    PermissionDenied,
+    /// The control cannot be copied from or pasted into
+    NotAllowed,
+}
+
+impl std::fmt::Display for ClipboardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClipboardError::Unavailable(msg) => write!(f, "Clipboard unavailable: {}", msg),
+            ClipboardError::Empty => write!(f, "Clipboard is empty"),
+            ClipboardError::PermissionDenied => write!(f, "Clipboard permission denied"),
+            ClipboardError::NotAllowed => write!(f, "Clipboard operation not allowed"),
+        }
+    }
+}
+
+impl std::error::Error for ClipboardError {}
+
+/// Storage behind the clipboard
+pub trait ClipboardBackend: Send {
+    /// Read text from the clipboard
+    fn get_text(&mut self) -> Result<String, ClipboardError>;
+    /// Replace the clipboard contents with text
+    fn set_text(&mut self, text: &str) -> Result<(), ClipboardError>;
+}
+
+/// Clipboard kept in process memory (headless use and tests)
+#[derive(Debug, Clone, Default)]
+pub struct MemoryClipboard {
+    text: Option<String>,
+}
+
+impl ClipboardBackend for MemoryClipboard {
+    fn get_text(&mut self) -> Result<String, ClipboardError> {
+        self.text.clone().ok_or(ClipboardError::Empty)
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+        self.text = Some(text.to_string());
+        Ok(())
+    }
+}
+
+/// The OS clipboard
+pub struct SystemClipboard {
+    inner: arboard::Clipboard,
+}
+
+impl SystemClipboard {
+    /// Connect to the OS clipboard
+    pub fn new() -> Result<Self, ClipboardError> {
+        arboard::Clipboard::new()
+            .map(|inner| Self { inner })
+            .map_err(|e| ClipboardError::Unavailable(e.to_string()))
+    }
+}
+
+impl ClipboardBackend for SystemClipboard {
+    fn get_text(&mut self) -> Result<String, ClipboardError> {
+        self.inner.get_text().map_err(|e| match e {
+            arboard::Error::ContentNotAvailable => ClipboardError::Empty,
+            other => ClipboardError::Unavailable(other.to_string()),
+        })
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+        self.inner
+            .set_text(text.to_string())
+            .map_err(|e| ClipboardError::Unavailable(e.to_string()))
+    }
+}
+
+
+/// Browser clipboard with permission gating for scripts
+pub struct Clipboard {
+    backend: Box<dyn ClipboardBackend>,
+    read_permission: PermissionState,
+    write_permission: PermissionState,
+}
+
+impl Clipboard {
+    /// Create a clipboard over a backend
+    pub fn new(backend: Box<dyn ClipboardBackend>) -> Self {
+        Self {
+            backend,
+            read_permission: PermissionState::Prompt,
+            write_permission: PermissionState::Prompt,
+        }
+    }
+
+    /// Use the OS clipboard, falling back to memory if it is unavailable
+    pub fn system() -> Self {
+        match SystemClipboard::new() {
+            Ok(system) => Self::new(Box::new(system)),
+            Err(_) => Self::in_memory(),
+        }
+    }
+
+    /// Use a process-local clipboard
+    pub fn in_memory() -> Self {
+        Self::new(Box::new(MemoryClipboard::default()))
+    }
+
+    /// Set whether scripts may read the clipboard
+    pub fn set_read_permission(&mut self, state: PermissionState) {
+        self.read_permission = state;
+    }
+
+    /// Set whether scripts may write the clipboard
+    pub fn set_write_permission(&mut self, state: PermissionState) {
+        self.write_permission = state;
+    }
+
+    /// Copy text (e.g., the page or address bar selection)
+    pub fn copy(&mut self, text: &str) -> Result<(), ClipboardError> {
+        if text.is_empty() {
+            return Err(ClipboardError::Empty);
+        }
+        self.backend.set_text(text)
+    }
+
+    /// Read text for pasting
+    pub fn paste(&mut self) -> Result<String, ClipboardError> {
+        self.backend.get_text()
+    }
+
+    /// Copy an input's value; password fields cannot be copied
+    pub fn copy_input(&mut self, input: &InputState) -> Result<(), ClipboardError> {
+        if input.input_type == InputType::Password || !input.accepts_text() {
+            return Err(ClipboardError::NotAllowed);
+        }
+        self.copy(&input.value)
+    }
+
+    /// Copy an input's value and clear it
+    pub fn cut_input(&mut self, input: &mut InputState) -> Result<(), ClipboardError> {
+        if input.readonly || input.disabled {
+            return Err(ClipboardError::NotAllowed);
+        }
+        self.copy_input(input)?;
+        input.set_value(String::new());
+        Ok(())
+    }
+
+    /// Paste clipboard text at the end of an input
+    pub fn paste_into_input(&mut self, input: &mut InputState) -> Result<(), ClipboardError> {
+        let text = self.paste()?;
+        if input.insert_text(&text) {
+            Ok(())
+        } else {
+            Err(ClipboardError::NotAllowed)
+        }
+    }
+
+    /// Copy a textarea's value
+    pub fn copy_textarea(&mut self, textarea: &TextAreaState) -> Result<(), ClipboardError> {
+        self.copy(&textarea.value)
+    }
+
+    /// Copy a textarea's value and clear it
+    pub fn cut_textarea(&mut self, textarea: &mut TextAreaState) -> Result<(), ClipboardError> {
+        if textarea.readonly || textarea.disabled {
+            return Err(ClipboardError::NotAllowed);
+        }
+        self.copy_textarea(textarea)?;
+        textarea.set_value(String::new());
+        Ok(())
+    }
+
+    /// Paste clipboard text at the end of a textarea
+    pub fn paste_into_textarea(&mut self, textarea: &mut TextAreaState) -> Result<(), ClipboardError> {
+        let text = self.paste()?;
+        if textarea.insert_text(&text) {
+            Ok(())
+        } else {
+            Err(ClipboardError::NotAllowed)
+        }
+    }
+
+    /// Settle queued `navigator.clipboard` calls from a page's scripts
+    ///
+    /// `user_activation` is true while handling a user gesture (click or
+    /// key press), which lets writes through without a granted permission.
+    /// Returns the number of requests serviced.
+    pub fn service_js_requests(
+        &mut self,
+        ctx: &mut JsContext,
+        user_activation: bool,
+    ) -> Result<usize, JsError> {
+        let requests = ctx.take_clipboard_requests()?;
+
+        for request in &requests {
+            let result = match request.kind {
+                ClipboardRequestKind::ReadText => {
+                    if self.read_permission == PermissionState::Granted {
+                        self.paste().or_else(|e| match e {
+                            ClipboardError::Empty => Ok(String::new()),
+                            other => Err(other),
+                        }).map(Some)
+                    } else {
+                        Err(ClipboardError::PermissionDenied)
+                    }
+                }
+                ClipboardRequestKind::WriteText(ref text) => {
+                    let allowed = match self.write_permission {
+                        PermissionState::Granted => true,
+                        PermissionState::Prompt => user_activation,
+                        PermissionState::Denied => false,
+                    };
+                    if allowed {
+                        self.backend.set_text(text).map(|_| None)
+                    } else {
+                        Err(ClipboardError::PermissionDenied)
+                    }
+                }
+            };
+            ctx.settle_clipboard_request(request.id, result.map_err(|e| e.to_string()))?;
+        }
+
+        Ok(requests.len())
+    }
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self::in_memory()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::js::JsValue;
+
+    #[test]
+    fn test_copy_paste_text() {
+        let mut clipboard = Clipboard::in_memory();
+        assert_eq!(clipboard.paste(), Err(ClipboardError::Empty));
+        assert_eq!(clipboard.copy(""), Err(ClipboardError::Empty));
+
+        clipboard.copy("selected text").unwrap();
+        assert_eq!(clipboard.paste().unwrap(), "selected text");
+    }
+
+    #[test]
+    fn test_form_field_cut_copy_paste() {
+        let mut clipboard = Clipboard::in_memory();
+        let mut input = InputState {
+            value: "hello".to_string(),
+            ..Default::default()
+        };
+
+        clipboard.cut_input(&mut input).unwrap();
+        assert_eq!(input.value, "");
+
+        let mut other = InputState {
+            max_length: Some(3),
+            ..Default::default()
+        };
+        clipboard.copy("a\nbcdef").unwrap();
+        clipboard.paste_into_input(&mut other).unwrap();
+        assert_eq!(other.value, "abc");
+
+        let password = InputState {
+            input_type: InputType::Password,
+            value: "secret".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(clipboard.copy_input(&password), Err(ClipboardError::NotAllowed));
+
+        let mut textarea = TextAreaState::default();
+        clipboard.paste_into_textarea(&mut textarea).unwrap();
+        assert_eq!(textarea.value, "a\nbcdef");
+    }
+
+    #[test]
+    fn test_js_write_requires_activation_or_permission() {
+        let mut clipboard = Clipboard::in_memory();
+        let mut ctx = JsContext::new();
+        let script = "var result; navigator.clipboard.writeText('from page').then(
+            function () { result = 'ok'; }, function (e) { result = e.name; });";
+
+        ctx.execute(script).unwrap();
+        assert_eq!(clipboard.service_js_requests(&mut ctx, false).unwrap(), 1);
+        assert_eq!(ctx.execute("result").unwrap(), JsValue::String("NotAllowedError".to_string()));
+        assert_eq!(clipboard.paste(), Err(ClipboardError::Empty));
+
+        ctx.execute(script).unwrap();
+        clipboard.service_js_requests(&mut ctx, true).unwrap();
+        assert_eq!(ctx.execute("result").unwrap(), JsValue::String("ok".to_string()));
+        assert_eq!(clipboard.paste().unwrap(), "from page");
+    }
+
+    #[test]
+    fn test_js_read_requires_permission() {
+        let mut clipboard = Clipboard::in_memory();
+        clipboard.copy("clipboard contents").unwrap();
+        let mut ctx = JsContext::new();
+        let script = "var text; navigator.clipboard.readText().then(
+            function (t) { text = t; }, function (e) { text = e.name; });";
+
+        ctx.execute(script).unwrap();
+        clipboard.service_js_requests(&mut ctx, true).unwrap();
+        assert_eq!(ctx.execute("text").unwrap(), JsValue::String("NotAllowedError".to_string()));
+
+        clipboard.set_read_permission(PermissionState::Granted);
+        ctx.execute(script).unwrap();
+        clipboard.service_js_requests(&mut ctx, false).unwrap();
+        assert_eq!(ctx.execute("text").unwrap(), JsValue::String("clipboard contents".to_string()));
+    }
+}
@@ -1,8 +1,17 @@
 // Layer-Based Compositor - Phase 7
 // Implements tile-based rendering, damage tracking, and partial invalidation
+//
+// A page's layer tree is built from its layout: the document gets the root
+// layer and elements that are fixed, stacked with a z-index, translucent,
+// transformed or marked `will-change` get layers of their own.
 
-use crate::layout::Rect;
-use crate::css::Color;
+use crate::animation::{element_path, ElementPath};
+use crate::dom::Node;
+use crate::layout::{BoxType, LayoutBox, Rect};
+use crate::layout::positioning::{Position, PositionedElement};
+use crate::css::Value;
+use crate::style::StyledNode;
+use crate::transform::{self, Matrix4, Transform};
 use std::collections::HashSet;
 
 /// Tile size for rendering (256x256 pixels is a common choice)
@@ -19,8 +28,10 @@ pub struct Layer {
     pub z_index: i32,
     /// Opacity (0.0 to 1.0)
     pub opacity: f32,
-    /// Transform matrix (for future CSS transforms)
-    pub transform: Transform,
+    /// Transform matrix, in document coordinates
+    pub transform: Matrix4,
+    /// The point the element's `transform` turns about, in document coordinates
+    pub transform_origin: (f32, f32, f32),
     /// Whether this layer is visible
     pub visible: bool,
     /// Damaged regions that need repainting
@@ -31,48 +42,53 @@ pub struct Layer {
     pub parent_id: Option<LayerId>,
     /// Child layer IDs
     pub children: Vec<LayerId>,
+    /// What the layer paints, e.g. `#document` or `div#menu`
+    pub name: String,
+    /// Why the layer was created, for layers built from a layout
+    pub reason: Option<CompositingReason>,
+    /// The element the layer paints, for layers built from a layout
+    pub element: Option<ElementPath>,
 }
 
-/// Unique layer identifier
-pub type LayerId = u64;
-
-/// 2D transform matrix (simplified for now)
-#[derive(Debug, Clone, Copy)]
-pub struct Transform {
-    /// Translation X
-    pub translate_x: f32,
-    /// Translation Y
-    pub translate_y: f32,
-    /// Scale X
-    pub scale_x: f32,
-    /// Scale Y
-    pub scale_y: f32,
+/// Why an element is painted into a layer of its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompositingReason {
+    /// The document's root layer
+    Root,
+    /// `position: fixed`, which stays put while the page scrolls
+    FixedPosition,
+    /// A positioned element with a z-index
+    ZIndex,
+    /// `opacity` below 1
+    Opacity,
+    /// `will-change: transform` or `will-change: opacity`
+    WillChange,
+    /// A `transform` other than `none`
+    Transform,
+    /// A `<canvas>`, whose drawing is composited from its own backing store
+    Canvas,
+    /// A `<video>`, whose frames are composited from their own backing store
+    Video,
 }
 
-impl Default for Transform {
-    fn default() -> Self {
-        Self {
-            translate_x: 0.0,
-            translate_y: 0.0,
-            scale_x: 1.0,
-            scale_y: 1.0,
+impl CompositingReason {
+    /// Explanation as shown in the DevTools layers view
+    pub fn description(&self) -> &'static str {
+        match self {
+            CompositingReason::Root => "Root layer of the document",
+            CompositingReason::FixedPosition => "Has position: fixed",
+            CompositingReason::ZIndex => "Positioned with a z-index",
+            CompositingReason::Opacity => "Has opacity below 1",
+            CompositingReason::WillChange => "Has will-change",
+            CompositingReason::Transform => "Has a transform",
+            CompositingReason::Canvas => "Accelerated 2D canvas",
+            CompositingReason::Video => "Accelerated video",
         }
     }
 }
 
-impl Transform {
-    /// Create identity transform
-    pub fn identity() -> Self {
-        Self::default()
-    }
-    
-    /// Apply transform to a point
-    pub fn apply(&self, x: f32, y: f32) -> (f32, f32) {
-        let transformed_x = x * self.scale_x + self.translate_x;
-        let transformed_y = y * self.scale_y + self.translate_y;
-        (transformed_x, transformed_y)
-    }
-}
+/// Unique layer identifier
+pub type LayerId = u64;
 
 /// Tile coordinate (x, y in tile space)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -122,12 +138,16 @@ impl Layer {
             bounds,
             z_index: 0,
             opacity: 1.0,
-            transform: Transform::identity(),
+            transform: Matrix4::identity(),
+            transform_origin: (bounds.x + bounds.width / 2.0, bounds.y + bounds.height / 2.0, 0.0),
             visible: true,
             damaged_tiles: tiles.iter().map(|t| t.coord).collect(),
             tiles,
             parent_id: None,
             children: Vec::new(),
+            name: String::new(),
+            reason: None,
+            element: None,
         }
     }
     
@@ -154,7 +174,17 @@ impl Layer {
     }
     
     /// Mark a region as damaged (needs repainting)
+    ///
+    /// Only the part of the region inside the layer's bounds is damaged.
     pub fn damage(&mut self, rect: &Rect) {
+        let left = rect.x.max(self.bounds.x);
+        let top = rect.y.max(self.bounds.y);
+        let right = (rect.x + rect.width).min(self.bounds.x + self.bounds.width);
+        let bottom = (rect.y + rect.height).min(self.bounds.y + self.bounds.height);
+        if right <= left || bottom <= top {
+            return;
+        }
+        let rect = Rect { x: left, y: top, width: right - left, height: bottom - top };
         let start_coord = TileCoord::from_pixel(rect.x, rect.y);
         let end_coord = TileCoord::from_pixel(
             rect.x + rect.width,
@@ -173,6 +203,21 @@ impl Layer {
         self.damaged_tiles.iter().copied().collect()
     }
     
+    /// Does a tile need repainting
+    pub fn is_damaged(&self, coord: TileCoord) -> bool {
+        self.damaged_tiles.contains(&coord)
+    }
+    
+    /// Tiles covering the layer, row by row
+    pub fn tiles(&self) -> &[Tile] {
+        &self.tiles
+    }
+    
+    /// Bytes of RGBA backing store for the layer's tiles at a tile resolution
+    pub fn memory_bytes(&self, tile_resolution: u32) -> usize {
+        self.tiles.len() * tile_resolution as usize * tile_resolution as usize * 4
+    }
+    
     /// Mark tiles as rendered
     pub fn mark_tiles_rendered(&mut self, coords: &[TileCoord]) {
         for coord in coords {
@@ -189,12 +234,26 @@ impl Layer {
         self.damaged_tiles.clear();
     }
     
+    /// Mark every tile as needing to be rendered again
+    pub fn invalidate(&mut self) {
+        for tile in &mut self.tiles {
+            tile.rendered = false;
+            self.damaged_tiles.insert(tile.coord);
+        }
+    }
+    
+    /// Where the layer lands once transformed, as the rectangle holding it
+    pub fn transformed_bounds(&self) -> Rect {
+        self.transform.map_rect(self.bounds)
+    }
+    
     /// Check if layer intersects with viewport
     pub fn intersects_viewport(&self, viewport: &Rect) -> bool {
-        self.bounds.x < viewport.x + viewport.width
-            && self.bounds.x + self.bounds.width > viewport.x
-            && self.bounds.y < viewport.y + viewport.height
-            && self.bounds.y + self.bounds.height > viewport.y
+        let bounds = self.transformed_bounds();
+        bounds.x < viewport.x + viewport.width
+            && bounds.x + bounds.width > viewport.x
+            && bounds.y < viewport.y + viewport.height
+            && bounds.y + bounds.height > viewport.y
     }
     
     /// Get tiles visible in viewport
@@ -227,6 +286,8 @@ pub struct Compositor {
     root_layer_id: Option<LayerId>,
     /// Damaged regions in screen space
     screen_damage: Vec<Rect>,
+    /// Device pixels per CSS pixel
+    scale_factor: f32,
 }
 
 impl Compositor {
@@ -238,6 +299,54 @@ impl Compositor {
             viewport,
             root_layer_id: None,
             screen_damage: Vec::new(),
+            scale_factor: 1.0,
+        }
+    }
+    
+    /// Build the layer tree of a laid out page
+    ///
+    /// Layers are parented to the layer of their nearest composited
+    /// ancestor; a layer's z-index orders it among its siblings.
+    pub fn from_layout(layout_root: &LayoutBox, viewport: Rect) -> Self {
+        let mut compositor = Self::new(viewport);
+        let root = compositor.create_layer(layout_root.dimensions.margin_box());
+        if let Some(layer) = compositor.get_layer_mut(root) {
+            layer.name = "#document".to_string();
+            layer.reason = Some(CompositingReason::Root);
+            layer.element = Some(Vec::new());
+        }
+        let document = match layout_root.box_type {
+            BoxType::BlockNode(styled) | BoxType::InlineNode(styled) | BoxType::FlexNode(styled) => Some(styled.node),
+            BoxType::AnonymousBlock => None,
+        };
+        for child in &layout_root.children {
+            compositor.add_layout_layers(child, root, document);
+        }
+        compositor
+    }
+    
+    fn add_layout_layers(&mut self, layout_box: &LayoutBox, parent: LayerId, document: Option<&Node>) {
+        let mut parent = parent;
+        if let Some((reason, styled)) = compositing_reason(layout_box) {
+            let id = self.create_layer(layout_box.dimensions.border_box());
+            self.add_child(parent, id);
+            if let Some(layer) = self.get_layer_mut(id) {
+                layer.name = styled.node.element_data().map(|e| e.label()).unwrap_or_default();
+                layer.reason = Some(reason);
+                layer.element = document.and_then(|document| element_path(document, styled.node));
+                layer.z_index = PositionedElement::from_styled_node(styled).z_index;
+                if let Some(Value::Number(opacity)) = styled.value("opacity") {
+                    layer.opacity = opacity.clamp(0.0, 1.0);
+                }
+                layer.transform_origin = transform::element_origin(styled, layer.bounds);
+                if let Some(transform) = Transform::of(styled) {
+                    layer.transform = transform.to_matrix_about(layer.transform_origin);
+                }
+            }
+            parent = id;
+        }
+        for child in &layout_box.children {
+            self.add_layout_layers(child, parent, document);
         }
     }
     
@@ -262,6 +371,16 @@ impl Compositor {
         self.layers.iter().find(|l| l.id == id)
     }
     
+    /// The first layer created, holding the others
+    pub fn root_layer_id(&self) -> Option<LayerId> {
+        self.root_layer_id
+    }
+    
+    /// The layer an element paints into, if it has one of its own
+    pub fn element_layer(&self, element: &[usize]) -> Option<LayerId> {
+        self.layers.iter().find(|l| l.element.as_deref() == Some(element)).map(|l| l.id)
+    }
+    
     /// Get a mutable layer by ID
     pub fn get_layer_mut(&mut self, id: LayerId) -> Option<&mut Layer> {
         self.layers.iter_mut().find(|l| l.id == id)
@@ -364,6 +483,54 @@ impl Compositor {
         &self.viewport
     }
     
+    /// Move the viewport to a scroll offset, damaging the parts of the page
+    /// it newly shows
+    ///
+    /// What stays in view is only moved on screen, not repainted.
+    /// Fixed-position layers do not scroll, so they are not damaged.
+    /// Returns the newly exposed areas, in page coordinates.
+    pub fn scroll_to(&mut self, x: f32, y: f32) -> Vec<Rect> {
+        let old = self.viewport;
+        if old.x == x && old.y == y {
+            return Vec::new();
+        }
+        self.viewport = Rect { x, y, ..old };
+        let exposed = exposed_rects(&old, &self.viewport);
+        for rect in &exposed {
+            self.screen_damage.push(*rect);
+            for layer in &mut self.layers {
+                if layer.reason != Some(CompositingReason::FixedPosition) && layer.intersects_viewport(rect) {
+                    layer.damage(rect);
+                }
+            }
+        }
+        exposed
+    }
+    
+    /// Set the device pixel ratio tiles are rasterized at
+    ///
+    /// Tiles cover the same CSS pixel area at any ratio; a new ratio
+    /// invalidates every tile so it is re-rasterized at the new resolution.
+    pub fn set_scale_factor(&mut self, scale_factor: f32) {
+        if scale_factor <= 0.0 || scale_factor == self.scale_factor {
+            return;
+        }
+        self.scale_factor = scale_factor;
+        for layer in &mut self.layers {
+            layer.invalidate();
+        }
+    }
+    
+    /// Device pixels per CSS pixel
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+    
+    /// Width and height of a tile's backing texture in device pixels
+    pub fn tile_resolution(&self) -> u32 {
+        (TILE_SIZE as f32 * self.scale_factor).ceil() as u32
+    }
+    
     /// Get tiles that need rendering in current viewport
     pub fn get_tiles_to_render(&self) -> Vec<(LayerId, TileCoord, Rect)> {
         let mut tiles_to_render = Vec::new();
@@ -401,6 +568,12 @@ impl Compositor {
         self.layers.len()
     }
     
+    /// Bytes of tile backing store for every layer
+    pub fn memory_bytes(&self) -> usize {
+        let resolution = self.tile_resolution();
+        self.layers.iter().map(|layer| layer.memory_bytes(resolution)).sum()
+    }
+    
     /// Check if compositor has pending work
     pub fn has_pending_work(&self) -> bool {
         !self.screen_damage.is_empty() || 
@@ -408,6 +581,66 @@ impl Compositor {
     }
 }
 
+/// Parts of `new` outside `old`, for viewports of the same size
+fn exposed_rects(old: &Rect, new: &Rect) -> Vec<Rect> {
+    let (dx, dy) = (new.x - old.x, new.y - old.y);
+    if dx.abs() >= new.width || dy.abs() >= new.height {
+        return vec![*new];
+    }
+    let mut exposed = Vec::new();
+    if dy != 0.0 {
+        let y = if dy > 0.0 { new.y + new.height - dy } else { new.y };
+        exposed.push(Rect { x: new.x, y, width: new.width, height: dy.abs() });
+    }
+    if dx != 0.0 {
+        let x = if dx > 0.0 { new.x + new.width - dx } else { new.x };
+        exposed.push(Rect { x, y: new.y, width: dx.abs(), height: new.height });
+    }
+    exposed
+}
+
+/// Why a box gets a layer, with its styled node, if it does
+fn compositing_reason<'a>(layout_box: &LayoutBox<'a>) -> Option<(CompositingReason, &'a StyledNode<'a>)> {
+    let styled = match layout_box.box_type {
+        BoxType::BlockNode(styled) | BoxType::InlineNode(styled) | BoxType::FlexNode(styled) => styled,
+        BoxType::AnonymousBlock => return None,
+    };
+    let element = styled.node.element_data()?;
+    let keyword = |name: &str| match styled.value(name) {
+        Some(Value::Keyword(keyword)) => Some(keyword.as_str()),
+        _ => None,
+    };
+    let positioned = PositionedElement::from_styled_node(styled);
+    let reason = if positioned.position == Position::Fixed {
+        CompositingReason::FixedPosition
+    } else if positioned.is_positioned() && styled.value("z-index").is_some() && keyword("z-index") != Some("auto") {
+        CompositingReason::ZIndex
+    } else if matches!(styled.value("opacity"), Some(Value::Number(opacity)) if *opacity < 1.0) {
+        CompositingReason::Opacity
+    } else if matches!(keyword("will-change"), Some("transform" | "opacity")) {
+        CompositingReason::WillChange
+    } else if Transform::of(styled).is_some() {
+        CompositingReason::Transform
+    } else if element.tag_name == "canvas" {
+        CompositingReason::Canvas
+    } else if element.tag_name == "video" {
+        CompositingReason::Video
+    } else {
+        return None;
+    };
+    Some((reason, styled))
+}
+
+/// Areas painted differently by two frames, given what each painted
+///
+/// Items only one of the frames has are damage, in both their old and
+/// new places.
+pub fn changed_rects<T: PartialEq>(old: &[T], new: &[T], rect: impl Fn(&T) -> Rect) -> Vec<Rect> {
+    let removed = old.iter().filter(|item| !new.contains(item));
+    let added = new.iter().filter(|item| !old.contains(item));
+    removed.chain(added).map(rect).collect()
+}
+
 impl Default for Compositor {
     fn default() -> Self {
         Self::new(Rect {
@@ -477,6 +710,26 @@ mod tests {
         assert!(compositor.get_layer(layer_id).is_some());
     }
     
+    #[test]
+    fn test_scale_factor_change_rerenders_tiles() {
+        let mut compositor = Compositor::default();
+        let layer_id = compositor.create_layer(Rect { x: 0.0, y: 0.0, width: 512.0, height: 512.0 });
+        let tiles: Vec<TileCoord> = compositor.get_tiles_to_render().iter().map(|(_, c, _)| *c).collect();
+        compositor.mark_tiles_rendered(layer_id, &tiles);
+        assert!(!compositor.has_pending_work());
+        assert_eq!(compositor.tile_resolution(), 256);
+        
+        compositor.set_scale_factor(2.0);
+        assert_eq!(compositor.tile_resolution(), 512);
+        assert!(compositor.has_pending_work());
+        assert_eq!(compositor.get_tiles_to_render().len(), tiles.len());
+        
+        // Same ratio again is a no-op
+        compositor.mark_tiles_rendered(layer_id, &tiles);
+        compositor.set_scale_factor(2.0);
+        assert!(!compositor.has_pending_work());
+    }
+    
     #[test]
     fn test_compositor_layer_hierarchy() {
         let mut compositor = Compositor::default();
@@ -542,17 +795,112 @@ mod tests {
     
     #[test]
     fn test_transform_apply() {
-        let mut transform = Transform::identity();
-        transform.translate_x = 10.0;
-        transform.translate_y = 20.0;
-        transform.scale_x = 2.0;
-        transform.scale_y = 2.0;
+        let transform = Matrix4::translate(10.0, 20.0, 0.0).multiply(&Matrix4::scale(2.0, 2.0, 1.0));
         
         let (x, y) = transform.apply(5.0, 10.0);
         assert_eq!(x, 20.0); // 5 * 2 + 10
         assert_eq!(y, 40.0); // 10 * 2 + 20
     }
     
+    #[test]
+    fn test_layers_from_layout() {
+        use crate::css::CssParser;
+        use crate::html::HtmlParser;
+        use crate::layout::{layout_tree, Dimensions};
+        use crate::style::style_tree;
+        
+        let document = HtmlParser::parse(
+            "<html><body><div id=\"bar\"><p class=\"fade\">A</p></div><div id=\"pop\">B</div><p id=\"spin\">C</p>\
+             <canvas id=\"game\"></canvas><video id=\"clip\"></video></body></html>",
+        );
+        let stylesheet = CssParser::parse(
+            "#bar { position: fixed; height: 40px; } .fade { opacity: 0.5; } \
+             #pop { position: relative; z-index: 5; height: 600px; } \
+             #spin { display: block; transform: translateX(10px) rotate(90deg); transform-origin: left top; \
+             height: 20px; }",
+        );
+        let styled = style_tree(&document, &stylesheet);
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+        viewport.content.height = 600.0;
+        let layout_root = layout_tree(&styled, viewport);
+        let compositor = Compositor::from_layout(&layout_root, viewport.content);
+        
+        let root = compositor.get_layer(compositor.root_layer_id().unwrap()).unwrap();
+        assert_eq!((root.name.as_str(), root.reason), ("#document", Some(CompositingReason::Root)));
+        let children: Vec<&Layer> = root.children.iter().filter_map(|&id| compositor.get_layer(id)).collect();
+        let described: Vec<_> =
+            children.iter().map(|layer| (layer.name.as_str(), layer.reason, layer.z_index)).collect();
+        assert_eq!(described, [
+            ("div#bar", Some(CompositingReason::FixedPosition), 0),
+            ("div#pop", Some(CompositingReason::ZIndex), 5),
+            ("p#spin", Some(CompositingReason::Transform), 0),
+            ("canvas#game", Some(CompositingReason::Canvas), 0),
+            ("video#clip", Some(CompositingReason::Video), 0),
+        ]);
+        let fade = compositor.get_layer(children[0].children[0]).unwrap();
+        assert_eq!((fade.name.as_str(), fade.opacity), ("p.fade", 0.5));
+        let pop = document.path_to_id("pop").unwrap();
+        assert_eq!(children[1].element.as_ref(), Some(&pop));
+        assert_eq!(compositor.element_layer(&pop), Some(children[1].id));
+        assert_eq!(compositor.element_layer(&[]), Some(root.id));
+        // Turned a quarter about its top left corner, then moved right
+        let spin = children[2];
+        let bounds = spin.transformed_bounds();
+        assert_eq!((spin.transform_origin.0, spin.transform_origin.1), (spin.bounds.x, spin.bounds.y));
+        assert!((bounds.x - (spin.bounds.x - 10.0)).abs() < 1e-3 && (bounds.y - spin.bounds.y).abs() < 1e-3);
+        assert!((bounds.width - 20.0).abs() < 1e-3 && (bounds.height - spin.bounds.width).abs() < 1e-3);
+        let tiles: usize = compositor.layers_in_paint_order().iter().map(|layer| layer.tiles().len()).sum();
+        assert_eq!(compositor.memory_bytes(), tiles * 256 * 256 * 4);
+        
+        // 800 x 600 is 4 x 3 tiles of 256 x 256 RGBA pixels
+        let layer = Layer::new(9, Rect { x: 0.0, y: 0.0, width: 800.0, height: 600.0 });
+        assert_eq!(layer.memory_bytes(256), 12 * 256 * 256 * 4);
+    }
+    
+    #[test]
+    fn test_changed_rects_damage_layers() {
+        let rect = |x: f32| Rect { x, y: 0.0, width: 10.0, height: 10.0 };
+        let old = [(rect(0.0), 1), (rect(300.0), 2)];
+        let new = [(rect(0.0), 1), (rect(300.0), 3), (rect(600.0), 4)];
+        let changed = changed_rects(&old, &new, |(rect, _)| *rect);
+        assert_eq!(changed, [rect(300.0), rect(300.0), rect(600.0)]);
+        
+        let mut layer = Layer::new(1, Rect { x: 0.0, y: 0.0, width: 512.0, height: 256.0 });
+        layer.clear_damage();
+        for rect in &changed {
+            layer.damage(rect);
+        }
+        // Damage past the layer's bounds is ignored
+        assert_eq!(layer.damaged_tiles(), [TileCoord { x: 1, y: 0 }]);
+        assert!(layer.is_damaged(TileCoord { x: 1, y: 0 }) && !layer.is_damaged(TileCoord { x: 0, y: 0 }));
+    }
+    
+    #[test]
+    fn test_scroll_damages_exposed_tiles() {
+        let mut compositor = Compositor::new(Rect { x: 0.0, y: 0.0, width: 800.0, height: 600.0 });
+        let page = compositor.create_layer(Rect { x: 0.0, y: 0.0, width: 800.0, height: 3000.0 });
+        let header = compositor.create_layer(Rect { x: 0.0, y: 0.0, width: 800.0, height: 50.0 });
+        compositor.get_layer_mut(header).unwrap().reason = Some(CompositingReason::FixedPosition);
+        compositor.add_child(page, header);
+        compositor.clear_damage();
+
+        // Scrolling down 100px exposes a strip at the bottom of the viewport
+        let exposed = compositor.scroll_to(0.0, 100.0);
+        assert_eq!(exposed, vec![Rect { x: 0.0, y: 600.0, width: 800.0, height: 100.0 }]);
+        assert_eq!(compositor.viewport().y, 100.0);
+        let damaged = compositor.get_layer(page).unwrap().damaged_tiles();
+        assert!(!damaged.is_empty() && damaged.iter().all(|coord| coord.y == 2));
+        assert!(compositor.get_layer(header).unwrap().damaged_tiles().is_empty());
+
+        // Staying put damages nothing more; jumping past the viewport exposes all of it
+        compositor.clear_damage();
+        assert!(compositor.scroll_to(0.0, 100.0).is_empty());
+        assert!(!compositor.has_pending_work());
+        let exposed = compositor.scroll_to(0.0, 2000.0);
+        assert_eq!(exposed, vec![Rect { x: 0.0, y: 2000.0, width: 800.0, height: 600.0 }]);
+    }
+    
     #[test]
     fn test_viewport_intersection() {
         let bounds = Rect { x: 100.0, y: 100.0, width: 200.0, height: 200.0 };
@@ -0,0 +1,385 @@
+// Content filter - EasyList-style request blocking and element hiding
+//
+// Filter lists hold two kinds of rules. Network rules (`||ads.example^`,
+// `/banner/*$image,third-party`) block requests; the filter plugs into the
+// resource loader as its request interceptor. Cosmetic rules
+// (`example.com##.sponsored`) hide elements; the browser adds the
+// matching selectors to a page's style as `display: none` rules. Rules
+// starting with `@@` or using `#@#` are exceptions to the others. Rules
+// with options or selectors the filter does not support are skipped
+// rather than applied too broadly.
+
+use crate::net::{InterceptedRequest, RequestInterceptor, ResourceType};
+use regex::{Regex, RegexBuilder};
+use std::path::Path;
+use url::Url;
+
+/// Domains a rule is limited to, from `domain=a.com|~b.com` or `a.com,~b.com##`
+#[derive(Debug, Clone, Default)]
+struct Domains {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl Domains {
+    fn parse<'a>(list: impl Iterator<Item = &'a str>) -> Self {
+        let mut domains = Domains::default();
+        for domain in list.map(str::trim).filter(|d| !d.is_empty()) {
+            match domain.strip_prefix('~') {
+                Some(excluded) => domains.exclude.push(excluded.to_ascii_lowercase()),
+                None => domains.include.push(domain.to_ascii_lowercase()),
+            }
+        }
+        domains
+    }
+
+    /// Does the rule apply on a host (the host or a subdomain of a listed domain)
+    fn applies_to(&self, host: &str) -> bool {
+        let matches = |domain: &String| host == domain || host.ends_with(&format!(".{}", domain));
+        if self.exclude.iter().any(matches) {
+            return false;
+        }
+        self.include.is_empty() || self.include.iter().any(matches)
+    }
+}
+
+/// A rule that blocks, or exempts, requests
+#[derive(Debug, Clone)]
+struct NetworkRule {
+    pattern: Regex,
+    /// `Some(true)` for `$third-party`, `Some(false)` for `$~third-party`
+    third_party: Option<bool>,
+    /// Types the rule is limited to; empty for all
+    types: Vec<ResourceType>,
+    /// Types excluded with `$~type`
+    excluded_types: Vec<ResourceType>,
+    /// Sites of the pages the rule applies on
+    domains: Domains,
+}
+
+impl NetworkRule {
+    fn matches(&self, request: &InterceptedRequest<'_>) -> bool {
+        if self.third_party.is_some_and(|third_party| third_party != request.is_third_party()) {
+            return false;
+        }
+        if !self.types.is_empty() && !self.types.contains(&request.resource_type) {
+            return false;
+        }
+        if self.excluded_types.contains(&request.resource_type) {
+            return false;
+        }
+        let page_host = request.first_party.host_str().unwrap_or_default().to_ascii_lowercase();
+        self.domains.applies_to(&page_host) && self.pattern.is_match(request.url.as_str())
+    }
+}
+
+/// A rule that hides elements matching a selector
+#[derive(Debug, Clone)]
+struct CosmeticRule {
+    selector: String,
+    domains: Domains,
+}
+
+/// Parsed filter lists
+#[derive(Debug, Clone, Default)]
+pub struct ContentFilter {
+    blocking: Vec<NetworkRule>,
+    /// `@@` rules that let matching requests through
+    allowing: Vec<NetworkRule>,
+    hiding: Vec<CosmeticRule>,
+    /// `#@#` rules that keep elements visible on some sites
+    unhiding: Vec<CosmeticRule>,
+    /// Rules that were not understood
+    skipped: usize,
+}
+
+impl ContentFilter {
+    /// Create a filter with no rules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a filter from the text of a filter list
+    pub fn parse(list: &str) -> Self {
+        let mut filter = Self::new();
+        filter.add_list(list);
+        filter
+    }
+
+    /// Load a filter list file
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        std::fs::read_to_string(path).map(|list| Self::parse(&list))
+    }
+
+    /// Add the rules of a filter list, returning how many were added
+    pub fn add_list(&mut self, list: &str) -> usize {
+        let before = self.rule_count();
+        for line in list.lines() {
+            self.add_rule(line);
+        }
+        self.rule_count() - before
+    }
+
+    /// Add one rule; comments and blank lines are ignored
+    pub fn add_rule(&mut self, line: &str) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('!') || line.starts_with('[') {
+            return;
+        }
+        if let Some((domains, selector)) = line.split_once("#@#") {
+            if let Some(rule) = cosmetic_rule(domains, selector) {
+                self.unhiding.push(rule);
+                return;
+            }
+        } else if let Some((domains, selector)) = line.split_once("##") {
+            if let Some(rule) = cosmetic_rule(domains, selector) {
+                self.hiding.push(rule);
+                return;
+            }
+        } else if !line.contains("#?#") && !line.contains("#$#") {
+            let (rules, rule) = match line.strip_prefix("@@") {
+                Some(exception) => (&mut self.allowing, network_rule(exception)),
+                None => (&mut self.blocking, network_rule(line)),
+            };
+            if let Some(rule) = rule {
+                rules.push(rule);
+                return;
+            }
+        }
+        self.skipped += 1;
+    }
+
+    /// Number of rules in use
+    pub fn rule_count(&self) -> usize {
+        self.blocking.len() + self.allowing.len() + self.hiding.len() + self.unhiding.len()
+    }
+
+    /// Number of rules skipped because they were not understood
+    pub fn skipped_count(&self) -> usize {
+        self.skipped
+    }
+
+    /// Should a request be blocked
+    pub fn blocks(&self, request: &InterceptedRequest<'_>) -> bool {
+        self.blocking.iter().any(|rule| rule.matches(request))
+            && !self.allowing.iter().any(|rule| rule.matches(request))
+    }
+
+    /// Selectors of the elements to hide on a page
+    pub fn hidden_selectors(&self, page: &Url) -> Vec<&str> {
+        let host = page.host_str().unwrap_or_default().to_ascii_lowercase();
+        let unhidden = |selector: &str| {
+            self.unhiding.iter().any(|rule| rule.selector == selector && rule.domains.applies_to(&host))
+        };
+        let mut selectors: Vec<&str> = Vec::new();
+        for rule in &self.hiding {
+            let selector = rule.selector.as_str();
+            if rule.domains.applies_to(&host) && !unhidden(selector) && !selectors.contains(&selector) {
+                selectors.push(selector);
+            }
+        }
+        selectors
+    }
+
+    /// Style sheet hiding a page's filtered elements
+    pub fn hiding_css(&self, page: &Url) -> String {
+        self.hidden_selectors(page)
+            .iter()
+            .map(|selector| format!("{} {{ display: none; }}\n", selector))
+            .collect()
+    }
+}
+
+impl RequestInterceptor for ContentFilter {
+    fn should_block(&self, request: &InterceptedRequest<'_>) -> bool {
+        self.blocks(request)
+    }
+}
+
+/// Parse the two halves of a `domains##selector` rule
+fn cosmetic_rule(domains: &str, selector: &str) -> Option<CosmeticRule> {
+    let selector = selector.trim();
+    // The style engine knows type, class and id selectors and combinators;
+    // it would read `div:has(.ad)` as `div` and hide every div, and braces
+    // would let a rule add style instead of hiding
+    let supported = |c: char| c.is_alphanumeric() || "-_.#*>+~, ".contains(c);
+    if selector.is_empty() || !selector.chars().all(supported) {
+        return None;
+    }
+    Some(CosmeticRule { selector: selector.to_string(), domains: Domains::parse(domains.split(',')) })
+}
+
+/// Parse a network rule, without its `@@`
+fn network_rule(line: &str) -> Option<NetworkRule> {
+    let is_regex = line.len() > 1 && line.starts_with('/') && line.ends_with('/');
+    let (pattern, options) = match line.rfind('$') {
+        Some(at) if !is_regex => (&line[..at], Some(&line[at + 1..])),
+        _ => (line, None),
+    };
+    let mut rule = NetworkRule {
+        pattern: Regex::new("").ok()?,
+        third_party: None,
+        types: Vec::new(),
+        excluded_types: Vec::new(),
+        domains: Domains::default(),
+    };
+    let mut match_case = false;
+    for option in options.into_iter().flat_map(|options| options.split(',')) {
+        let (negated, name) = match option.trim().strip_prefix('~') {
+            Some(name) => (true, name),
+            None => (false, option.trim()),
+        };
+        match name {
+            "third-party" => rule.third_party = Some(!negated),
+            "match-case" => match_case = true,
+            _ if name.starts_with("domain=") => {
+                rule.domains = Domains::parse(name["domain=".len()..].split('|'));
+            }
+            _ => {
+                let resource_type = option_type(name)?;
+                let types = if negated { &mut rule.excluded_types } else { &mut rule.types };
+                types.push(resource_type);
+            }
+        }
+    }
+    rule.pattern = pattern_regex(pattern, match_case)?;
+    Some(rule)
+}
+
+/// Resource type a `$type` option names
+fn option_type(name: &str) -> Option<ResourceType> {
+    match name {
+        "document" | "subdocument" => Some(ResourceType::Html),
+        "stylesheet" => Some(ResourceType::Css),
+        "image" => Some(ResourceType::Image),
+        "font" => Some(ResourceType::Font),
+        "script" | "xmlhttprequest" | "media" | "object" | "websocket" | "ping" | "other" => {
+            Some(ResourceType::Other)
+        }
+        _ => None,
+    }
+}
+
+/// Compile a filter pattern into a regular expression over the whole URL
+///
+/// `||` anchors at the start of the host or a subdomain, `|` at either
+/// end of the URL, `^` matches a separator or the end and `*` anything.
+fn pattern_regex(pattern: &str, match_case: bool) -> Option<Regex> {
+    let source = if pattern.len() > 1 && pattern.starts_with('/') && pattern.ends_with('/') {
+        pattern[1..pattern.len() - 1].to_string()
+    } else {
+        let mut source = String::new();
+        let mut rest = pattern;
+        if let Some(host) = rest.strip_prefix("||") {
+            source.push_str(r"^[a-z][a-z0-9+.-]*://([^/?#]*\.)?");
+            rest = host;
+        } else if let Some(start) = rest.strip_prefix('|') {
+            source.push('^');
+            rest = start;
+        }
+        let anchored_end = rest.ends_with('|');
+        let rest = rest.strip_suffix('|').unwrap_or(rest);
+        for c in rest.chars() {
+            match c {
+                '*' => source.push_str(".*"),
+                '^' => source.push_str(r"(?:[^\w.%-]|$)"),
+                c => source.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+            }
+        }
+        if anchored_end {
+            source.push('$');
+        }
+        source
+    };
+    RegexBuilder::new(&source).case_insensitive(!match_case).build().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    fn blocks(filter: &ContentFilter, request: &str, page: &str) -> bool {
+        let request = url(request);
+        let page = url(page);
+        filter.blocks(&InterceptedRequest {
+            url: &request,
+            first_party: &page,
+            resource_type: ResourceType::from_extension(&request),
+        })
+    }
+
+    #[test]
+    fn test_network_rules() {
+        let filter = ContentFilter::parse(
+            "! Comment\n\
+             [Adblock Plus 2.0]\n\
+             ||ads.example.com^\n\
+             /banner/*.gif\n\
+             |http://plain.example/\n\
+             /tracker\\d+\\.js/\n\
+             @@||ads.example.com/allowed/\n",
+        );
+        assert_eq!(filter.rule_count(), 5);
+        let page = "https://news.example.org/";
+
+        assert!(blocks(&filter, "https://ads.example.com/x.js", page));
+        assert!(blocks(&filter, "https://cdn.ads.example.com/x.js", page));
+        assert!(!blocks(&filter, "https://ads.example.community/x.js", page));
+        assert!(!blocks(&filter, "https://notads.example.com/x.js", page));
+        assert!(blocks(&filter, "https://img.example.org/banner/top.gif", page));
+        assert!(blocks(&filter, "http://plain.example/page", page));
+        assert!(!blocks(&filter, "https://plain.example/page", page));
+        assert!(blocks(&filter, "https://example.org/tracker42.js", page));
+
+        // Exceptions win over blocking rules
+        assert!(!blocks(&filter, "https://ads.example.com/allowed/x.js", page));
+    }
+
+    #[test]
+    fn test_rule_options() {
+        let filter = ContentFilter::parse(
+            "||cdn.example.net^$third-party,image\n\
+             /pixel.$domain=shop.example|~help.shop.example\n\
+             ||fonts.example^$~font\n\
+             ||popups.example^$popup\n",
+        );
+        // `$popup` is not supported, so its rule is skipped
+        assert_eq!((filter.rule_count(), filter.skipped_count()), (3, 1));
+
+        let image = "https://cdn.example.net/a.png";
+        assert!(blocks(&filter, image, "https://news.example.org/"));
+        assert!(!blocks(&filter, image, "https://www.example.net/"));
+        assert!(!blocks(&filter, "https://cdn.example.net/a.css", "https://news.example.org/"));
+
+        assert!(blocks(&filter, "https://t.example/pixel.gif", "https://www.shop.example/"));
+        assert!(!blocks(&filter, "https://t.example/pixel.gif", "https://help.shop.example/"));
+        assert!(!blocks(&filter, "https://t.example/pixel.gif", "https://other.example/"));
+
+        assert!(blocks(&filter, "https://fonts.example/f.css", "https://a.example/"));
+        assert!(!blocks(&filter, "https://fonts.example/f.woff2", "https://a.example/"));
+    }
+
+    #[test]
+    fn test_element_hiding() {
+        let filter = ContentFilter::parse(
+            "##.ad-banner\n\
+             example.com,~forum.example.com##.sponsored\n\
+             news.example.com#@#.ad-banner\n\
+             ##.bad { color: red }\n\
+             ##a[href*=\"ads\"]\n\
+             example.com#?#div:has(> .ad)\n",
+        );
+        assert_eq!(filter.skipped_count(), 3);
+
+        let page = url("https://www.example.com/");
+        assert_eq!(filter.hidden_selectors(&page), vec![".ad-banner", ".sponsored"]);
+        assert_eq!(filter.hiding_css(&page), ".ad-banner { display: none; }\n.sponsored { display: none; }\n");
+        assert_eq!(filter.hidden_selectors(&url("https://forum.example.com/")), vec![".ad-banner"]);
+        assert_eq!(filter.hidden_selectors(&url("https://news.example.com/")), vec![".sponsored"]);
+        assert_eq!(filter.hidden_selectors(&url("https://other.org/")), vec![".ad-banner"]);
+    }
+}
@@ -0,0 +1,292 @@
+// Crash reporting - panics in one page's pipeline are caught and recorded
+//
+// Work for a page (parsing, style, layout, scripts) runs under `guard`,
+// which catches a panic so the tab can show an error page while the rest of
+// the browser carries on. Each crash is recorded as a report in the spirit
+// of a minidump: the page's URL, the phase the pipeline was in, the panic
+// message and where it was raised, the thread and a backtrace. Reports are
+// written to a directory as JSON, one file per crash, and read back at start.
+
+use std::cell::{Cell, RefCell};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Step of a page's pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PipelinePhase {
+    /// Fetching the document and its subresources
+    Load,
+    Parse,
+    Style,
+    Layout,
+    Script,
+    /// Running the page's handlers for an input, animation or other event
+    Event,
+    /// Running the page's timers, animation frame and idle callbacks
+    Timer,
+    /// Hit testing and editing the page for a click or key press
+    Input,
+    Paint,
+}
+
+impl PipelinePhase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PipelinePhase::Load => "load",
+            PipelinePhase::Parse => "parse",
+            PipelinePhase::Style => "style",
+            PipelinePhase::Layout => "layout",
+            PipelinePhase::Script => "script",
+            PipelinePhase::Event => "event",
+            PipelinePhase::Timer => "timer",
+            PipelinePhase::Input => "input",
+            PipelinePhase::Paint => "paint",
+        }
+    }
+}
+
+/// What is known about a crash
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CrashReport {
+    /// Unique per report, also its file name
+    pub id: String,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    /// Page the pipeline was working on
+    pub url: Option<String>,
+    /// Phase the pipeline was in when it panicked
+    pub phase: PipelinePhase,
+    /// The panic message
+    pub message: String,
+    /// `file:line:column` the panic was raised at, if the hook saw it
+    pub location: Option<String>,
+    pub thread: String,
+    /// Captured by the panic hook; empty without it
+    pub backtrace: String,
+}
+
+/// The panic seen by the hook, waiting for `guard` to report it
+struct PanicDetails {
+    message: String,
+    location: Option<String>,
+    backtrace: String,
+}
+
+thread_local! {
+    /// Phase of the guarded work on this thread, if any
+    static PHASE: Cell<Option<PipelinePhase>> = const { Cell::new(None) };
+    /// Page the guarded work is for
+    static PAGE: RefCell<Option<String>> = const { RefCell::new(None) };
+    static LAST_PANIC: RefCell<Option<PanicDetails>> = const { RefCell::new(None) };
+}
+
+static HOOK: Once = Once::new();
+
+/// Install a panic hook that captures backtraces of guarded panics
+///
+/// A guarded panic is noted in one line on stderr, with its phase, page and
+/// message. Panics elsewhere go to the hook that was installed before.
+pub fn install_panic_hook() {
+    HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let Some(phase) = PHASE.with(Cell::get) else {
+                previous(info);
+                return;
+            };
+            let message = payload_message(info.payload());
+            eprintln!("{}", PAGE.with(|page| crash_line(phase, page.borrow().as_deref(), &message)));
+            let details = PanicDetails {
+                message,
+                location: info.location().map(|at| format!("{}:{}:{}", at.file(), at.line(), at.column())),
+                backtrace: std::backtrace::Backtrace::force_capture().to_string(),
+            };
+            LAST_PANIC.with(|last| *last.borrow_mut() = Some(details));
+        }));
+    });
+}
+
+/// Note the phase guarded work has reached, for its crash report
+pub fn enter_phase(phase: PipelinePhase) {
+    PHASE.with(|current| {
+        if current.get().is_some() {
+            current.set(Some(phase));
+        }
+    });
+}
+
+/// Run a page's work, turning a panic in it into a crash report
+///
+/// The work starts in `phase` and moves on with `enter_phase`.
+pub fn guard<T>(url: Option<&Url>, phase: PipelinePhase, work: impl FnOnce() -> T) -> Result<T, Box<CrashReport>> {
+    let outer = PHASE.with(|current| current.replace(Some(phase)));
+    let outer_page = PAGE.with(|page| page.replace(url.map(Url::to_string)));
+    let result = panic::catch_unwind(AssertUnwindSafe(work));
+    let phase = PHASE.with(|current| current.replace(outer)).unwrap_or(phase);
+    PAGE.with(|page| *page.borrow_mut() = outer_page);
+    result.map_err(|payload| {
+        let details = LAST_PANIC.with(|last| last.borrow_mut().take());
+        let (message, location, backtrace) = match details {
+            Some(details) => (details.message, details.location, details.backtrace),
+            None => (payload_message(payload.as_ref()), None, String::new()),
+        };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        Box::new(CrashReport {
+            id: format!("{}-{:09}", now.as_secs(), now.subsec_nanos()),
+            timestamp: now.as_secs(),
+            url: url.map(Url::to_string),
+            phase,
+            message,
+            location,
+            thread: std::thread::current().name().unwrap_or("unnamed").to_string(),
+            backtrace,
+        })
+    })
+}
+
+/// The line the panic hook notes a guarded panic with
+fn crash_line(phase: PipelinePhase, page: Option<&str>, message: &str) -> String {
+    format!("Page crashed in {}: {}: {}", phase.as_str(), page.unwrap_or("no page"), message)
+}
+
+/// Text of a panic's payload
+fn payload_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// Crash reports of this and earlier sessions
+pub struct CrashReporter {
+    /// Directory reports are written to, if they are kept
+    dir: Option<PathBuf>,
+    /// Oldest first
+    reports: Vec<CrashReport>,
+}
+
+impl CrashReporter {
+    /// Keep reports in memory only
+    pub fn new() -> Self {
+        Self { dir: None, reports: Vec::new() }
+    }
+
+    /// Keep reports in a directory, reading those already there
+    pub fn open(dir: &Path) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let mut reports = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                // Reports that no longer parse are left for whoever wrote them
+                if let Ok(report) = serde_json::from_slice(&std::fs::read(&path)?) {
+                    reports.push(report);
+                }
+            }
+        }
+        reports.sort_by(|a: &CrashReport, b| a.id.cmp(&b.id));
+        Ok(Self { dir: Some(dir.to_path_buf()), reports })
+    }
+
+    /// Record a crash, writing it out if reports are kept on disk
+    pub fn record(&mut self, report: CrashReport) -> std::io::Result<()> {
+        let written = match &self.dir {
+            Some(dir) => {
+                let json = serde_json::to_vec_pretty(&report).map_err(std::io::Error::other)?;
+                std::fs::write(dir.join(format!("{}.json", report.id)), json)
+            }
+            None => Ok(()),
+        };
+        self.reports.push(report);
+        written
+    }
+
+    /// Reports, oldest first
+    pub fn reports(&self) -> &[CrashReport] {
+        &self.reports
+    }
+
+    /// Forget all reports, deleting their files
+    pub fn clear(&mut self) -> std::io::Result<()> {
+        if let Some(dir) = &self.dir {
+            for report in &self.reports {
+                let path = dir.join(format!("{}.json", report.id));
+                if path.exists() {
+                    std::fs::remove_file(path)?;
+                }
+            }
+        }
+        self.reports.clear();
+        Ok(())
+    }
+}
+
+impl Default for CrashReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guard_reports_panic_with_phase() {
+        install_panic_hook();
+        let url = Url::parse("https://example.com/page").unwrap();
+
+        assert_eq!(guard(Some(&url), PipelinePhase::Parse, || 42), Ok(42));
+
+        let report = guard(Some(&url), PipelinePhase::Parse, || {
+            enter_phase(PipelinePhase::Layout);
+            panic!("box without a parent");
+        })
+        .unwrap_err();
+        assert_eq!(report.phase, PipelinePhase::Layout);
+        assert_eq!(report.message, "box without a parent");
+        assert_eq!(report.url.as_deref(), Some("https://example.com/page"));
+        assert!(report.location.unwrap().contains("crash.rs"));
+
+        // A guard inside another reports its own phase and hands back the outer one
+        let outer = guard(Some(&url), PipelinePhase::Timer, || {
+            let inner = guard(Some(&url), PipelinePhase::Event, || panic!("handler")).unwrap_err();
+            (inner.phase, PHASE.with(Cell::get))
+        });
+        assert_eq!(outer, Ok((PipelinePhase::Event, Some(PipelinePhase::Timer))));
+        assert!(PAGE.with(|page| page.borrow().is_none()));
+        assert_eq!(
+            crash_line(PipelinePhase::Layout, Some("https://example.com/page"), "box without a parent"),
+            "Page crashed in layout: https://example.com/page: box without a parent"
+        );
+
+        // Outside a guard, phases are not tracked
+        enter_phase(PipelinePhase::Script);
+        assert!(PHASE.with(Cell::get).is_none());
+    }
+
+    #[test]
+    fn test_reports_persist() {
+        let dir = std::env::temp_dir().join(format!("crash_reports_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let report = guard(None, PipelinePhase::Script, || panic!("{}", "out of stack")).unwrap_err();
+        let mut reporter = CrashReporter::open(&dir).unwrap();
+        reporter.record(*report.clone()).unwrap();
+
+        let reopened = CrashReporter::open(&dir).unwrap();
+        assert_eq!(reopened.reports(), &[*report]);
+
+        reporter.clear().unwrap();
+        assert!(CrashReporter::open(&dir).unwrap().reports().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,10 +1,127 @@
-use cssparser::{Parser, ParserInput, Token};
+use cssparser::{Parser, ParserInput, ToCss, Token};
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 /// A CSS stylesheet containing multiple rules
 #[derive(Debug, Clone)]
 pub struct Stylesheet {
     pub rules: Vec<Rule>,
+    /// `@media` blocks, kept aside until a medium is chosen
+    pub media_rules: Vec<MediaRule>,
+    /// `@font-face` rules, in source order
+    pub font_faces: Vec<FontFaceRule>,
+}
+
+/// An `@font-face` rule's descriptors, kept as their source text
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FontFaceRule {
+    pub descriptors: Vec<(String, String)>,
+}
+
+impl FontFaceRule {
+    /// Source text of a descriptor; the last one given wins
+    pub fn descriptor(&self, name: &str) -> Option<&str> {
+        self.descriptors.iter().rev().find(|(n, _)| n == name).map(|(_, value)| value.as_str())
+    }
+}
+
+/// An `@media` block and the rules it guards
+#[derive(Debug, Clone)]
+pub struct MediaRule {
+    pub query: MediaQuery,
+    pub rules: Vec<Rule>,
+    /// Number of unconditional rules before the block, to keep source order
+    pub position: usize,
+}
+
+/// Output medium a stylesheet is applied for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaType {
+    Screen,
+    Print,
+}
+
+impl MediaType {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "screen" => Some(MediaType::Screen),
+            "print" => Some(MediaType::Print),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MediaType::Screen => "screen",
+            MediaType::Print => "print",
+        }
+    }
+}
+
+/// A comma-separated media query list; it matches if any query does
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaQuery {
+    pub queries: Vec<MediaQueryItem>,
+}
+
+/// One query of a list, e.g. `not print` or `screen and (max-width: 600px)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaQueryItem {
+    pub negated: bool,
+    /// Media type name; `all` when the query only has features
+    pub media_type: String,
+    /// Feature expressions, all of which must hold
+    pub features: Vec<MediaFeature>,
+}
+
+/// A media feature expression, e.g. `(prefers-reduced-motion: reduce)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaFeature {
+    pub name: String,
+    /// `None` for the boolean form, e.g. `(prefers-reduced-motion)`
+    pub value: Option<String>,
+}
+
+impl MediaFeature {
+    /// Does the feature hold, if it is one that is evaluated
+    pub fn evaluate(&self, features: &MediaFeatures) -> Option<bool> {
+        match (self.name.as_str(), self.value.as_deref()) {
+            ("prefers-reduced-motion", None | Some("reduce")) => Some(features.reduced_motion),
+            ("prefers-reduced-motion", Some("no-preference")) => Some(!features.reduced_motion),
+            _ => None,
+        }
+    }
+}
+
+/// User settings media features are evaluated against
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MediaFeatures {
+    /// The user asks for less motion (`prefers-reduced-motion: reduce`)
+    pub reduced_motion: bool,
+}
+
+impl MediaQuery {
+    /// Check whether the query list applies to a medium, with no user preferences
+    pub fn matches(&self, media: MediaType) -> bool {
+        self.evaluate(media, &MediaFeatures::default())
+    }
+
+    /// Check whether the query list applies to a medium and user settings
+    ///
+    /// Only `prefers-reduced-motion` is evaluated; queries with other
+    /// features never match, negated or not.
+    pub fn evaluate(&self, media: MediaType, features: &MediaFeatures) -> bool {
+        self.queries.iter().any(|query| {
+            let mut matched = query.media_type == "all" || query.media_type == media.as_str();
+            for feature in &query.features {
+                match feature.evaluate(features) {
+                    Some(holds) => matched &= holds,
+                    None => return false,
+                }
+            }
+            matched != query.negated
+        })
+    }
 }
 
 /// A CSS rule with selectors and declarations
@@ -12,20 +129,78 @@ pub struct Stylesheet {
 pub struct Rule {
     pub selectors: Vec<Selector>,
     pub declarations: Vec<Declaration>,
+    /// Who wrote the rule, which decides the cascade before specificity
+    pub origin: Origin,
+}
+
+/// Cascade origin of a rule, in the order the cascade applies them
+///
+/// Rules of a later origin beat those of an earlier one whatever their
+/// specificity: the page's own styles override the user's, and the user's
+/// override the browser's defaults.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Origin {
+    UserAgent,
+    User,
+    #[default]
+    Author,
 }
 
 /// A CSS selector (simplified)
 #[derive(Debug, Clone, PartialEq)]
 pub enum Selector {
     Simple(SimpleSelector),
+    /// Simple selectors joined by combinators, e.g. `ul > li`
+    ///
+    /// `subject` is matched against the element; `parts`, left to right and
+    /// each with the combinator after it, against its ancestors and siblings.
+    Complex {
+        parts: Vec<(SimpleSelector, Combinator)>,
+        subject: SimpleSelector,
+    },
+}
+
+/// How the elements matched by two simple selectors are related
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Combinator {
+    /// `a b`: b is inside a
+    Descendant,
+    /// `a > b`: b is a child of a
+    Child,
+    /// `a + b`: b comes right after a
+    NextSibling,
+    /// `a ~ b`: b comes after a
+    SubsequentSibling,
+}
+
+impl Combinator {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Combinator::Descendant => " ",
+            Combinator::Child => " > ",
+            Combinator::NextSibling => " + ",
+            Combinator::SubsequentSibling => " ~ ",
+        }
+    }
+}
+
+impl Selector {
+    /// The simple selector the element itself must match
+    pub fn subject(&self) -> &SimpleSelector {
+        match self {
+            Selector::Simple(simple) => simple,
+            Selector::Complex { subject, .. } => subject,
+        }
+    }
 }
 
-/// A simple selector (tag, class, or id)
+/// A simple selector (tag, class, id, or pseudo-class)
 #[derive(Debug, Clone, PartialEq)]
 pub struct SimpleSelector {
     pub tag_name: Option<String>,
     pub id: Option<String>,
     pub classes: Vec<String>,
+    pub pseudo_classes: Vec<String>,
 }
 
 /// A CSS declaration (property: value)
@@ -33,6 +208,8 @@ pub struct SimpleSelector {
 pub struct Declaration {
     pub name: String,
     pub value: Value,
+    /// Switched off declarations stay in the rule but are not applied
+    pub enabled: bool,
 }
 
 /// CSS property values
@@ -43,6 +220,11 @@ pub enum Value {
     Color(Color),
     Number(f32),
     Percentage(f32),
+    /// A function kept for later, e.g. `url(a.png)` or a `calc()` with percentages
+    Function(String, Vec<Value>),
+    /// Several space-separated components, e.g. `0 auto`; commas and
+    /// slashes between them are kept as one-character keywords
+    List(Vec<Value>),
 }
 
 /// CSS length units
@@ -55,7 +237,7 @@ pub enum Unit {
 }
 
 /// RGBA color
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -77,9 +259,195 @@ impl Color {
     }
 }
 
+impl Value {
+    /// The value in pixels, with percentages of `percent_base` and `calc()` evaluated
+    ///
+    /// Keywords, relative lengths, and percentages with no base have none.
+    pub fn resolve_length(&self, percent_base: Option<f32>) -> Option<f32> {
+        match self {
+            Value::Length(length, Unit::Px) => Some(*length),
+            Value::Number(number) => Some(*number),
+            Value::Length(percent, Unit::Percent) | Value::Percentage(percent) => {
+                percent_base.map(|base| base * percent / 100.0)
+            }
+            Value::Function(name, arguments) if name == "calc" => evaluate_calc(arguments, percent_base),
+            _ => None,
+        }
+    }
+
+    /// The items of a comma-separated list; a lone value is a list of one
+    pub fn comma_separated(&self) -> Vec<Value> {
+        let Value::List(components) = self else {
+            return vec![self.clone()];
+        };
+        components
+            .split(|component| matches!(component, Value::Keyword(k) if k == ","))
+            .map(|item| match item {
+                [single] => single.clone(),
+                _ => Value::List(item.to_vec()),
+            })
+            .collect()
+    }
+
+    /// The first family of a `font-family` list, e.g. `Open Sans` from `"Open Sans", serif`
+    pub fn first_font_family(&self) -> Option<String> {
+        match self.comma_separated().into_iter().next()? {
+            family @ (Value::Keyword(_) | Value::List(_)) => Some(family.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// Evaluate the components of a `calc()`: `*` and `/` bind tighter than `+` and `-`
+///
+/// The result must be a length; a bare number counts as one, as it does outside `calc()`.
+fn evaluate_calc(components: &[Value], percent_base: Option<f32>) -> Option<f32> {
+    evaluate_calc_term(components, percent_base).map(|(value, _)| value)
+}
+
+/// A `calc()` expression's value and whether it is a length rather than a number
+///
+/// Terms added or subtracted must agree, two lengths cannot be multiplied,
+/// and nothing can be divided by a length.
+fn evaluate_calc_term(components: &[Value], percent_base: Option<f32>) -> Option<(f32, bool)> {
+    let mut sum: Option<(f32, bool)> = None;
+    let mut sign = 1.0;
+    let mut product: Option<(f32, bool)> = None;
+    // Pending `*` (true) or `/` (false) waiting for its right operand
+    let mut operator: Option<bool> = None;
+    let add = |sum: Option<(f32, bool)>, sign: f32, (value, is_length): (f32, bool)| match sum {
+        None => Some((sign * value, is_length)),
+        Some((total, total_is_length)) if total_is_length == is_length => Some((total + sign * value, is_length)),
+        Some(_) => None,
+    };
+    for component in components {
+        match component {
+            Value::Keyword(op) if op == "+" || op == "-" => {
+                if operator.is_some() {
+                    return None;
+                }
+                sum = Some(add(sum, sign, product.take()?)?);
+                sign = if op == "+" { 1.0 } else { -1.0 };
+            }
+            Value::Keyword(op) if op == "*" || op == "/" => {
+                if product.is_none() || operator.is_some() {
+                    return None;
+                }
+                operator = Some(op == "*");
+            }
+            operand => {
+                let (value, is_length) = match operand {
+                    Value::Number(number) => (*number, false),
+                    Value::Function(name, arguments) if name == "calc" => {
+                        evaluate_calc_term(arguments, percent_base)?
+                    }
+                    length => (length.resolve_length(percent_base)?, true),
+                };
+                product = Some(match (product, operator.take()) {
+                    (None, None) => (value, is_length),
+                    (Some((left, left_is_length)), Some(true)) if !(left_is_length && is_length) => {
+                        (left * value, left_is_length || is_length)
+                    }
+                    (Some((left, left_is_length)), Some(false)) if !is_length && value != 0.0 => {
+                        (left / value, left_is_length)
+                    }
+                    _ => return None,
+                });
+            }
+        }
+    }
+    if operator.is_some() {
+        return None;
+    }
+    add(sum, sign, product?)
+}
+
+/// The color of `rgb()` or `rgba()` arguments, in comma or space syntax
+fn rgb_color(arguments: &[Value]) -> Option<Color> {
+    let numbers: Vec<&Value> = arguments
+        .iter()
+        .filter(|argument| !matches!(argument, Value::Keyword(k) if k == "," || k == "/"))
+        .collect();
+    if !(3..=4).contains(&numbers.len()) {
+        return None;
+    }
+    let channel = |value: &Value| match value {
+        Value::Number(n) => Some(n.clamp(0.0, 255.0).round() as u8),
+        Value::Percentage(p) => Some((p.clamp(0.0, 100.0) * 2.55).round() as u8),
+        _ => None,
+    };
+    let alpha = match numbers.get(3) {
+        None => 255,
+        Some(Value::Number(a)) => (a.clamp(0.0, 1.0) * 255.0).round() as u8,
+        Some(Value::Percentage(p)) => (p.clamp(0.0, 100.0) * 2.55).round() as u8,
+        Some(_) => return None,
+    };
+    Some(Color::new(channel(numbers[0])?, channel(numbers[1])?, channel(numbers[2])?, alpha))
+}
+
+/// Write components space-separated, with commas against the item before them
+fn write_components(f: &mut fmt::Formatter, components: &[Value]) -> fmt::Result {
+    for (index, component) in components.iter().enumerate() {
+        if index > 0 && !matches!(component, Value::Keyword(k) if k == ",") {
+            write!(f, " ")?;
+        }
+        write!(f, "{}", component)?;
+    }
+    Ok(())
+}
+
 impl Stylesheet {
     pub fn new(rules: Vec<Rule>) -> Self {
-        Stylesheet { rules }
+        Stylesheet { rules, media_rules: Vec::new(), font_faces: Vec::new() }
+    }
+
+    /// Append another stylesheet's rules, `@media` blocks and `@font-face` rules included
+    pub fn append(&mut self, other: Stylesheet) {
+        let offset = self.rules.len();
+        self.rules.extend(other.rules);
+        self.font_faces.extend(other.font_faces);
+        self.media_rules.extend(other.media_rules.into_iter().map(|mut media_rule| {
+            media_rule.position += offset;
+            media_rule
+        }));
+    }
+
+    /// The same stylesheet with every rule, `@media` ones included, from an origin
+    pub fn with_origin(mut self, origin: Origin) -> Self {
+        let media_rules = self.media_rules.iter_mut().flat_map(|media_rule| media_rule.rules.iter_mut());
+        for rule in self.rules.iter_mut().chain(media_rules) {
+            rule.origin = origin;
+        }
+        self
+    }
+
+    /// A declaration of a rule, to change it in place
+    pub fn declaration_mut(&mut self, rule: usize, declaration: usize) -> Option<&mut Declaration> {
+        self.rules.get_mut(rule)?.declarations.get_mut(declaration)
+    }
+
+    /// Flatten the `@media` blocks matching a medium into plain rules
+    ///
+    /// Rules keep their source order so the cascade is unchanged.
+    pub fn for_media(&self, media: MediaType) -> Stylesheet {
+        self.for_media_with(media, &MediaFeatures::default())
+    }
+
+    /// Flatten the `@media` blocks matching a medium and user settings into plain rules
+    pub fn for_media_with(&self, media: MediaType, features: &MediaFeatures) -> Stylesheet {
+        let mut rules = Vec::new();
+        let mut media_rules = self.media_rules.iter().peekable();
+        for index in 0..=self.rules.len() {
+            while let Some(media_rule) = media_rules.next_if(|m| m.position <= index) {
+                if media_rule.query.evaluate(media, features) {
+                    rules.extend(media_rule.rules.iter().cloned());
+                }
+            }
+            if let Some(rule) = self.rules.get(index) {
+                rules.push(rule.clone());
+            }
+        }
+        Stylesheet { rules, media_rules: Vec::new(), font_faces: self.font_faces.clone() }
     }
 }
 
@@ -94,15 +462,68 @@ impl fmt::Display for Value {
             Value::Color(c) => write!(f, "rgba({}, {}, {}, {})", c.r, c.g, c.b, c.a),
             Value::Number(n) => write!(f, "{}", n),
             Value::Percentage(n) => write!(f, "{}%", n),
+            Value::Function(name, arguments) => {
+                write!(f, "{}(", name)?;
+                write_components(f, arguments)?;
+                write!(f, ")")
+            }
+            Value::List(components) => write_components(f, components),
         }
     }
 }
 
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Selector::Simple(simple) => write!(f, "{}", simple),
+            Selector::Complex { parts, subject } => {
+                for (simple, combinator) in parts {
+                    write!(f, "{}{}", simple, combinator.as_str())?;
+                }
+                write!(f, "{}", subject)
+            }
+        }
+    }
+}
+
+impl fmt::Display for SimpleSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let simple = self;
+        if simple.tag_name.is_none() && simple.id.is_none() && simple.classes.is_empty() {
+            write!(f, "*")?;
+        }
+        if let Some(tag) = &simple.tag_name {
+            write!(f, "{}", tag)?;
+        }
+        if let Some(id) = &simple.id {
+            write!(f, "#{}", id)?;
+        }
+        for class in &simple.classes {
+            write!(f, ".{}", class)?;
+        }
+        for pseudo in &simple.pseudo_classes {
+            write!(f, ":{}", pseudo)?;
+        }
+        Ok(())
+    }
+}
+
 /// Calculate specificity for selector matching priority
 pub fn specificity(selector: &Selector) -> Specificity {
-    let Selector::Simple(ref simple) = selector;
+    match selector {
+        Selector::Simple(simple) => simple_specificity(simple),
+        Selector::Complex { parts, subject } => {
+            parts.iter().map(|(simple, _)| simple_specificity(simple)).fold(
+                simple_specificity(subject),
+                |Specificity(id, class, tag), part| Specificity(id + part.0, class + part.1, tag + part.2),
+            )
+        }
+    }
+}
+
+fn simple_specificity(simple: &SimpleSelector) -> Specificity {
     let id = if simple.id.is_some() { 1 } else { 0 };
-    let class = simple.classes.len();
+    let class = simple.classes.len() + simple.pseudo_classes.len();
     let tag = if simple.tag_name.is_some() { 1 } else { 0 };
     
     Specificity(id, class, tag)
@@ -111,6 +532,26 @@ pub fn specificity(selector: &Selector) -> Specificity {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Specificity(usize, usize, usize);
 
+impl fmt::Display for Specificity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({},{},{})", self.0, self.1, self.2)
+    }
+}
+
+/// Properties whose values are lists of several components, kept as their
+/// source text in a keyword for the code that uses them to parse
+const RAW_VALUE_PROPERTIES: &[&str] = &[
+    "transition",
+    "transition-property",
+    "transition-duration",
+    "transition-timing-function",
+    "transition-delay",
+    "transform",
+    "transform-origin",
+    "text-decoration",
+    "text-decoration-line",
+];
+
 /// CSS Parser
 pub struct CssParser;
 
@@ -120,15 +561,30 @@ impl CssParser {
         let mut input = ParserInput::new(source);
         let mut parser = Parser::new(&mut input);
         let mut rules = Vec::new();
+        let mut media_rules = Vec::new();
+        let mut font_faces = Vec::new();
 
         while parser.is_exhausted() == false {
             // Skip whitespace and comments
             let _ = parser.skip_whitespace();
-            
+
             if parser.is_exhausted() {
                 break;
             }
 
+            if let Ok(query) = parser.try_parse(Self::parse_media_prelude) {
+                let nested = parser.parse_nested_block(|parser| {
+                    Ok::<Vec<Rule>, cssparser::ParseError<()>>(Self::parse_rules(parser))
+                }).unwrap_or_default();
+                media_rules.push(MediaRule { query, rules: nested, position: rules.len() });
+                continue;
+            }
+
+            if let Ok(font_face) = parser.try_parse(Self::parse_font_face) {
+                font_faces.push(font_face);
+                continue;
+            }
+
             if let Ok(rule) = Self::parse_rule(&mut parser) {
                 rules.push(rule);
             } else {
@@ -137,7 +593,123 @@ impl CssParser {
             }
         }
 
-        Stylesheet::new(rules)
+        Stylesheet { rules, media_rules, font_faces }
+    }
+
+    /// Parse the plain rules inside a block (nested at-rules are skipped)
+    fn parse_rules(parser: &mut Parser) -> Vec<Rule> {
+        let mut rules = Vec::new();
+
+        while !parser.is_exhausted() {
+            // Skip whitespace and comments
+            parser.skip_whitespace();
+
+            if parser.is_exhausted() {
+                break;
+            }
+
+            if let Ok(rule) = Self::parse_rule(parser) {
+                rules.push(rule);
+            } else {
+                // Skip to next rule on error
+                let _ = Self::skip_to_next_rule(parser);
+            }
+        }
+
+        rules
+    }
+
+    /// Parse a single property value, e.g. `12px`, `#fff` or `1px solid`
+    pub fn parse_property_value(source: &str) -> Option<Value> {
+        let mut input = ParserInput::new(source);
+        let mut parser = Parser::new(&mut input);
+        let value = Self::parse_value(&mut parser).ok()?;
+        parser.skip_whitespace();
+        parser.is_exhausted().then_some(value)
+    }
+
+    /// Parse `@media <query list>` up to and including the opening brace
+    fn parse_media_prelude<'i>(parser: &mut Parser<'i, '_>) -> Result<MediaQuery, cssparser::ParseError<'i, ()>> {
+        let location = parser.current_source_location();
+        match parser.next()? {
+            Token::AtKeyword(name) if name.eq_ignore_ascii_case("media") => {}
+            _ => return Err(location.new_custom_error(())),
+        }
+
+        let mut queries = Vec::new();
+        let mut current: Option<MediaQueryItem> = None;
+        loop {
+            let token = parser.next()?.clone();
+            match token {
+                Token::CurlyBracketBlock => break,
+                Token::Comma => queries.extend(current.take()),
+                Token::Ident(ident) => {
+                    let ident = ident.to_ascii_lowercase();
+                    let query = current.get_or_insert(MediaQueryItem {
+                        negated: false,
+                        media_type: "all".to_string(),
+                        features: Vec::new(),
+                    });
+                    match ident.as_str() {
+                        "not" => query.negated = true,
+                        "only" | "and" => {}
+                        _ => query.media_type = ident,
+                    }
+                }
+                Token::ParenthesisBlock => {
+                    let feature = parser.parse_nested_block(|parser| {
+                        let name = parser.expect_ident()?.to_ascii_lowercase();
+                        let value = if parser.try_parse(|parser| parser.expect_colon()).is_ok() {
+                            let start = parser.position();
+                            while parser.next().is_ok() {}
+                            Some(parser.slice_from(start).trim().to_ascii_lowercase())
+                        } else {
+                            None
+                        };
+                        Ok::<MediaFeature, cssparser::ParseError<()>>(MediaFeature { name, value })
+                    });
+                    let query = current.get_or_insert(MediaQueryItem {
+                        negated: false,
+                        media_type: "all".to_string(),
+                        features: Vec::new(),
+                    });
+                    // An expression that cannot be read is a feature that is never evaluated
+                    query.features.push(feature.unwrap_or(MediaFeature { name: String::new(), value: None }));
+                }
+                _ => {}
+            }
+        }
+        queries.extend(current);
+
+        Ok(MediaQuery { queries })
+    }
+
+    /// Parse an `@font-face` block, keeping each descriptor's source text
+    fn parse_font_face<'i>(parser: &mut Parser<'i, '_>) -> Result<FontFaceRule, cssparser::ParseError<'i, ()>> {
+        let location = parser.current_source_location();
+        match parser.next()? {
+            Token::AtKeyword(name) if name.eq_ignore_ascii_case("font-face") => {}
+            _ => return Err(location.new_custom_error(())),
+        }
+        parser.expect_curly_bracket_block()?;
+
+        parser.parse_nested_block(|parser| {
+            let mut descriptors = Vec::new();
+            while !parser.is_exhausted() {
+                let name = parser.try_parse(|parser| {
+                    let name = parser.expect_ident()?.to_ascii_lowercase();
+                    parser.expect_colon()?;
+                    Ok::<String, cssparser::ParseError<()>>(name)
+                });
+                // Values of unreadable descriptors are skipped up to their semicolon
+                let value = Self::parse_raw_value(parser);
+                if let (Ok(name), Ok(Value::Keyword(value))) = (name, value) {
+                    descriptors.push((name, value));
+                }
+                let _ = parser.try_parse(|p| p.expect_semicolon());
+            }
+            Ok(FontFaceRule { descriptors })
+        })
     }
 
     fn parse_rule(parser: &mut Parser) -> Result<Rule, ()> {
@@ -150,17 +722,16 @@ impl CssParser {
             Ok::<Vec<Declaration>, cssparser::ParseError<()>>(Self::parse_declarations(parser))
         }).unwrap_or_default();
 
-        Ok(Rule { selectors, declarations })
+        Ok(Rule { selectors, declarations, origin: Origin::Author })
     }
 
+    /// Parse a comma separated selector list; one selector that cannot be read spoils the list
     fn parse_selectors(parser: &mut Parser) -> Result<Vec<Selector>, ()> {
         let mut selectors = Vec::new();
         
         loop {
             parser.skip_whitespace();
-            if let Ok(selector) = Self::parse_simple_selector(parser) {
-                selectors.push(Selector::Simple(selector));
-            }
+            selectors.push(Self::parse_complex_selector(parser)?);
 
             parser.skip_whitespace();
             
@@ -170,46 +741,94 @@ impl CssParser {
             }
         }
 
-        if selectors.is_empty() {
-            Err(())
+        Ok(selectors)
+    }
+
+    /// Parse simple selectors joined by combinators
+    fn parse_complex_selector(parser: &mut Parser) -> Result<Selector, ()> {
+        let mut parts = Vec::new();
+        let mut subject = Self::parse_simple_selector(parser)?;
+
+        loop {
+            let mut spaced = false;
+            let combinator = loop {
+                let before = parser.state();
+                match parser.next_including_whitespace() {
+                    Ok(Token::WhiteSpace(_)) => spaced = true,
+                    Ok(Token::Delim('>')) => break Some(Combinator::Child),
+                    Ok(Token::Delim('+')) => break Some(Combinator::NextSibling),
+                    Ok(Token::Delim('~')) => break Some(Combinator::SubsequentSibling),
+                    Ok(Token::Comma | Token::CurlyBracketBlock) | Err(_) => {
+                        parser.reset(&before);
+                        break None;
+                    }
+                    Ok(_) => {
+                        parser.reset(&before);
+                        break spaced.then_some(Combinator::Descendant);
+                    }
+                }
+            };
+            let Some(combinator) = combinator else {
+                break;
+            };
+            parser.skip_whitespace();
+            let next = Self::parse_simple_selector(parser)?;
+            parts.push((std::mem::replace(&mut subject, next), combinator));
+        }
+
+        if parts.is_empty() {
+            Ok(Selector::Simple(subject))
         } else {
-            Ok(selectors)
+            Ok(Selector::Complex { parts, subject })
         }
     }
 
+    /// Parse a tag, id, classes and pseudo-classes written together
     fn parse_simple_selector(parser: &mut Parser) -> Result<SimpleSelector, ()> {
         let mut selector = SimpleSelector {
             tag_name: None,
             id: None,
             classes: Vec::new(),
+            pseudo_classes: Vec::new(),
         };
+        let mut empty = true;
 
-        parser.skip_whitespace();
-
-        while let Ok(token) = parser.next_including_whitespace() {
-            match token {
-                Token::Ident(name) => {
+        loop {
+            let before = parser.state();
+            match parser.next_including_whitespace() {
+                Ok(Token::Ident(name)) => {
                     selector.tag_name = Some(name.to_string());
                 }
-                Token::IDHash(id) => {
+                Ok(Token::IDHash(id)) => {
                     selector.id = Some(id.to_string());
                 }
-                Token::Delim('.') => {
+                Ok(Token::Delim('.')) => {
                     if let Ok(Token::Ident(class)) = parser.next() {
                         selector.classes.push(class.to_string());
                     }
                 }
-                Token::Delim('*') => {
+                Ok(Token::Colon) => {
+                    if let Ok(Token::Ident(name)) = parser.next_including_whitespace() {
+                        selector.pseudo_classes.push(name.to_ascii_lowercase());
+                    }
+                }
+                Ok(Token::Delim('*')) => {
                     // Universal selector
                 }
-                Token::WhiteSpace(_) | Token::CurlyBracketBlock => {
+                // Whitespace, combinators, commas and the rule's block end the selector
+                _ => {
+                    parser.reset(&before);
                     break;
                 }
-                _ => break,
             }
+            empty = false;
         }
 
-        Ok(selector)
+        if empty {
+            Err(())
+        } else {
+            Ok(selector)
+        }
     }
 
     fn parse_declarations(parser: &mut Parser) -> Vec<Declaration> {
@@ -238,25 +857,77 @@ impl CssParser {
         parser.expect_colon().map_err(|_| ())?;
         parser.skip_whitespace();
 
-        let value = Self::parse_value(parser)?;
+        let value = if RAW_VALUE_PROPERTIES.contains(&name.as_str()) {
+            Self::parse_raw_value(parser)?
+        } else {
+            Self::parse_value(parser)?
+        };
+
+        Ok(Declaration { name, value, enabled: true })
+    }
 
-        Ok(Declaration { name, value })
+    /// Take the source text of a value, up to the end of its declaration
+    fn parse_raw_value(parser: &mut Parser) -> Result<Value, ()> {
+        let start = parser.position();
+        loop {
+            let state = parser.state();
+            match parser.next() {
+                Ok(Token::Semicolon) => {
+                    parser.reset(&state);
+                    break;
+                }
+                // A block left unread at the end would be cut from the slice
+                Ok(Token::Function(_) | Token::ParenthesisBlock | Token::SquareBracketBlock) => {
+                    let _ = parser.parse_nested_block(|_| Ok::<(), cssparser::ParseError<()>>(()));
+                }
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+        let text = parser.slice_from(start).trim();
+        if text.is_empty() {
+            return Err(());
+        }
+        Ok(Value::Keyword(text.to_string()))
     }
 
+    /// Parse a value's components up to the end of its declaration
+    ///
+    /// A lone component is the value itself; several make a `Value::List`.
     fn parse_value(parser: &mut Parser) -> Result<Value, ()> {
-        parser.skip_whitespace();
-        
-        let token = parser.next().map_err(|_| ())?;
-        
+        let mut components = Vec::new();
+        loop {
+            parser.skip_whitespace();
+            let state = parser.state();
+            let token = match parser.next() {
+                // `!important` is left for the declaration list to skip
+                Ok(Token::Semicolon | Token::Delim('!')) => {
+                    parser.reset(&state);
+                    break;
+                }
+                Ok(token) => token.clone(),
+                Err(_) => break,
+            };
+            components.push(Self::parse_component(parser, token, false)?);
+        }
+        match components.len() {
+            0 => Err(()),
+            1 => Ok(components.remove(0)),
+            _ => Ok(Value::List(components)),
+        }
+    }
+
+    /// Parse one component value; math operators are only read inside `calc()`
+    fn parse_component(parser: &mut Parser, token: Token, in_calc: bool) -> Result<Value, ()> {
         match token {
-            Token::Ident(keyword) => {
+            Token::Ident(keyword) | Token::QuotedString(keyword) => {
                 Ok(Value::Keyword(keyword.to_string()))
             }
             Token::Number { value, .. } => {
-                Ok(Value::Number(*value))
+                Ok(Value::Number(value))
             }
             Token::Percentage { unit_value, .. } => {
-                Ok(Value::Percentage(*unit_value * 100.0))
+                Ok(Value::Percentage(unit_value * 100.0))
             }
             Token::Dimension { value, unit, .. } => {
                 let unit = match unit.as_ref() {
@@ -266,16 +937,107 @@ impl CssParser {
                     "%" => Unit::Percent,
                     _ => return Err(()),
                 };
-                Ok(Value::Length(*value, unit))
+                Ok(Value::Length(value, unit))
             }
             Token::Hash(hex) | Token::IDHash(hex) => {
                 Self::parse_hex_color(hex.as_ref())
             }
+            Token::UnquotedUrl(url) => {
+                Ok(Value::Function("url".to_string(), vec![Value::Keyword(url.to_string())]))
+            }
+            Token::Comma => Ok(Value::Keyword(",".to_string())),
+            Token::Delim('/') => Ok(Value::Keyword("/".to_string())),
+            Token::Delim(op @ ('+' | '-' | '*')) if in_calc => Ok(Value::Keyword(op.to_string())),
+            // Parentheses group a sub-expression, evaluated as a nested `calc()`
+            Token::ParenthesisBlock if in_calc => {
+                Ok(Value::Function("calc".to_string(), Self::parse_arguments(parser, true)?))
+            }
+            Token::Function(name) => {
+                let name = name.to_ascii_lowercase();
+                let arguments = match name.as_str() {
+                    "rgb" | "rgba" | "url" => Self::parse_arguments(parser, false)?,
+                    "calc" => Self::parse_arguments(parser, true)?,
+                    _ => Self::parse_other_arguments(parser)?,
+                };
+                Self::parse_function(name, arguments)
+            }
             _ => Err(()),
         }
     }
 
-    fn parse_hex_color(hex: &str) -> Result<Value, ()> {
+    /// Parse the components inside a function or parenthesis block
+    fn parse_arguments(parser: &mut Parser, in_calc: bool) -> Result<Vec<Value>, ()> {
+        parser
+            .parse_nested_block(|parser| {
+                let mut arguments = Vec::new();
+                loop {
+                    parser.skip_whitespace();
+                    let token = match parser.next() {
+                        Ok(token) => token.clone(),
+                        Err(_) => break,
+                    };
+                    let argument = Self::parse_component(parser, token, in_calc)
+                        .map_err(|_| parser.new_custom_error::<(), ()>(()))?;
+                    arguments.push(argument);
+                }
+                Ok::<Vec<Value>, cssparser::ParseError<()>>(arguments)
+            })
+            .map_err(|_| ())
+    }
+
+    /// Parse the arguments of a function this parser does not evaluate, such
+    /// as `var()` or `linear-gradient()`
+    ///
+    /// Tokens with no value of their own, like `45deg`, are kept as written.
+    fn parse_other_arguments(parser: &mut Parser) -> Result<Vec<Value>, ()> {
+        parser
+            .parse_nested_block(|parser| {
+                let mut arguments = Vec::new();
+                loop {
+                    parser.skip_whitespace();
+                    let token = match parser.next() {
+                        Ok(token) => token.clone(),
+                        Err(_) => break,
+                    };
+                    let is_block = matches!(
+                        token,
+                        Token::Function(_) | Token::ParenthesisBlock | Token::SquareBracketBlock | Token::CurlyBracketBlock
+                    );
+                    let argument = match Self::parse_component(parser, token.clone(), true) {
+                        Ok(argument) => argument,
+                        Err(()) if !is_block => Value::Keyword(token.to_css_string()),
+                        Err(()) => return Err(parser.new_custom_error::<(), ()>(())),
+                    };
+                    arguments.push(argument);
+                }
+                Ok::<Vec<Value>, cssparser::ParseError<()>>(arguments)
+            })
+            .map_err(|_| ())
+    }
+
+    /// Build a function's value: colors for `rgb()`, `calc()` folded to a
+    /// length when it has no percentages to wait for, and any other function
+    /// kept as written
+    fn parse_function(name: String, arguments: Vec<Value>) -> Result<Value, ()> {
+        match name.as_str() {
+            "rgb" | "rgba" => rgb_color(&arguments).map(Value::Color).ok_or(()),
+            "url" => match arguments.as_slice() {
+                [Value::Keyword(_)] => Ok(Value::Function(name, arguments)),
+                _ => Err(()),
+            },
+            "calc" => {
+                if let Some(px) = evaluate_calc(&arguments, None) {
+                    return Ok(Value::Length(px, Unit::Px));
+                }
+                // Reject what would not evaluate whatever the percentages are of
+                evaluate_calc(&arguments, Some(100.0)).ok_or(())?;
+                Ok(Value::Function(name, arguments))
+            }
+            _ => Ok(Value::Function(name, arguments)),
+        }
+    }
+
+    pub(crate) fn parse_hex_color(hex: &str) -> Result<Value, ()> {
         let hex = hex.trim_start_matches('#');
         
         let (r, g, b) = match hex.len() {
@@ -336,9 +1098,196 @@ mod tests {
             tag_name: Some("div".to_string()),
             id: Some("main".to_string()),
             classes: vec!["container".to_string()],
+            pseudo_classes: Vec::new(),
         });
         
         let spec = specificity(&selector);
         assert_eq!(spec, Specificity(1, 1, 1));
     }
+
+    #[test]
+    fn test_parse_combinators() {
+        let stylesheet = CssParser::parse(
+            ".container p, ul>li { color: red; } h1 + p ~ div.note { color: red; } a > { color: red; } \
+             em { color: red; }",
+        );
+        let selectors: Vec<String> =
+            stylesheet.rules.iter().flat_map(|rule| &rule.selectors).map(|s| s.to_string()).collect();
+        // A combinator with nothing after it spoils its rule
+        assert_eq!(selectors, [".container p", "ul > li", "h1 + p ~ div.note", "em"]);
+        let Selector::Complex { parts, subject } = &stylesheet.rules[1].selectors[0] else {
+            panic!("expected a complex selector");
+        };
+        let combinators: Vec<Combinator> = parts.iter().map(|(_, combinator)| *combinator).collect();
+        assert_eq!(combinators, [Combinator::NextSibling, Combinator::SubsequentSibling]);
+        assert_eq!(subject.classes, ["note"]);
+        assert_eq!(specificity(&stylesheet.rules[1].selectors[0]), Specificity(0, 1, 3));
+    }
+
+    #[test]
+    fn test_parse_pseudo_class() {
+        let stylesheet = CssParser::parse("input:invalid { border-color: #ff0000; }");
+        assert_eq!(stylesheet.rules.len(), 1);
+
+        let simple = stylesheet.rules[0].selectors[0].subject();
+        assert_eq!(simple.tag_name.as_deref(), Some("input"));
+        assert_eq!(simple.pseudo_classes, vec!["invalid".to_string()]);
+        assert_eq!(specificity(&stylesheet.rules[0].selectors[0]), Specificity(0, 1, 1));
+    }
+
+    #[test]
+    fn test_parse_media_rules() {
+        let css = "p { color: black; } @media print { p { color: red; } .nav { display: none; } } h1 { color: blue; }";
+        let stylesheet = CssParser::parse(css);
+
+        assert_eq!(stylesheet.rules.len(), 2);
+        assert_eq!(stylesheet.media_rules.len(), 1);
+        assert_eq!(stylesheet.media_rules[0].position, 1);
+        assert_eq!(stylesheet.media_rules[0].rules.len(), 2);
+        assert!(stylesheet.media_rules[0].query.matches(MediaType::Print));
+        assert!(!stylesheet.media_rules[0].query.matches(MediaType::Screen));
+
+        // The print rules land between the rules they were written between
+        let print = stylesheet.for_media(MediaType::Print);
+        assert_eq!(print.rules.len(), 4);
+        assert_eq!(print.rules[1].declarations[0].value, Value::Keyword("red".to_string()));
+        assert_eq!(stylesheet.for_media(MediaType::Screen).rules.len(), 2);
+    }
+
+    #[test]
+    fn test_media_query_list() {
+        let css = "@media screen, print { a { color: red; } } \
+                   @media not print { b { color: red; } } \
+                   @media screen and (max-width: 600px) { i { color: red; } }";
+        let stylesheet = CssParser::parse(css);
+        let queries: Vec<&MediaQuery> = stylesheet.media_rules.iter().map(|m| &m.query).collect();
+
+        assert_eq!(queries.len(), 3);
+        assert!(queries[0].matches(MediaType::Screen) && queries[0].matches(MediaType::Print));
+        assert!(queries[1].matches(MediaType::Screen) && !queries[1].matches(MediaType::Print));
+        // Features other than prefers-reduced-motion are not evaluated
+        assert!(!queries[2].matches(MediaType::Screen));
+    }
+
+    #[test]
+    fn test_prefers_reduced_motion() {
+        let css = "a { color: red; } \
+                   @media (prefers-reduced-motion: reduce) { b { color: red; } } \
+                   @media screen and (prefers-reduced-motion: no-preference) { i { color: red; } } \
+                   @media (prefers-reduced-motion) { u { color: red; } } \
+                   @media not screen and (hover) { s { color: red; } }";
+        let stylesheet = CssParser::parse(css);
+        assert_eq!(stylesheet.media_rules[0].query.queries[0].features, [MediaFeature {
+            name: "prefers-reduced-motion".to_string(),
+            value: Some("reduce".to_string()),
+        }]);
+
+        let tags = |features: MediaFeatures| -> Vec<String> {
+            let stylesheet = stylesheet.for_media_with(MediaType::Screen, &features);
+            stylesheet.rules.iter().filter_map(|rule| rule.selectors[0].subject().tag_name.clone()).collect()
+        };
+        assert_eq!(tags(MediaFeatures::default()), ["a", "i"]);
+        assert_eq!(tags(MediaFeatures { reduced_motion: true }), ["a", "b", "u"]);
+        assert!(!stylesheet.media_rules[1].query.evaluate(MediaType::Print, &MediaFeatures::default()));
+    }
+
+    #[test]
+    fn test_font_face_rules() {
+        let css = "@font-face { font-family: \"Open Sans\"; src: url(a.woff2) format(\"woff2\"), local(Arial); \
+                   font-display: swap; } p { color: red; }";
+        let stylesheet = CssParser::parse(css);
+        assert_eq!(stylesheet.rules.len(), 1);
+        let face = &stylesheet.font_faces[0];
+        assert_eq!(face.descriptor("font-family"), Some("\"Open Sans\""));
+        assert_eq!(face.descriptor("src"), Some("url(a.woff2) format(\"woff2\"), local(Arial)"));
+        assert_eq!(face.descriptor("font-display"), Some("swap"));
+        assert_eq!(stylesheet.for_media(MediaType::Screen).font_faces.len(), 1);
+    }
+
+    #[test]
+    fn test_append_keeps_media_positions() {
+        let mut stylesheet = CssParser::parse("a { color: red; } b { color: red; }");
+        stylesheet.append(CssParser::parse("@media print { i { color: red; } }"));
+
+        assert_eq!(stylesheet.media_rules[0].position, 2);
+        assert_eq!(stylesheet.for_media(MediaType::Print).rules.len(), 3);
+    }
+
+    #[test]
+    fn test_property_values_and_selector_text() {
+        assert_eq!(CssParser::parse_property_value(" 12px "), Some(Value::Length(12.0, Unit::Px)));
+        assert_eq!(CssParser::parse_property_value("#fff"), Some(Value::Color(Color::white())));
+        assert_eq!(
+            CssParser::parse_property_value("12px solid"),
+            Some(Value::List(vec![Value::Length(12.0, Unit::Px), Value::Keyword("solid".to_string())]))
+        );
+        assert_eq!(CssParser::parse_property_value("12vw"), None);
+        assert_eq!(CssParser::parse_property_value(""), None);
+
+        let stylesheet = CssParser::parse("div#main.card:valid { margin: 0; } .a { margin: 0; } * { color: red; }");
+        let selectors: Vec<String> =
+            stylesheet.rules.iter().flat_map(|rule| &rule.selectors).map(|s| s.to_string()).collect();
+        assert_eq!(selectors, ["div#main.card:valid", ".a", "*"]);
+        assert_eq!(specificity(&stylesheet.rules[0].selectors[0]).to_string(), "(1,2,1)");
+    }
+
+    #[test]
+    fn test_functional_and_multi_token_values() {
+        let value = |source: &str| CssParser::parse_property_value(source);
+        assert_eq!(value("rgb(255, 0, 0)"), Some(Value::Color(Color::new(255, 0, 0, 255))));
+        assert_eq!(value("rgba(0, 0, 255, 0.5)"), Some(Value::Color(Color::new(0, 0, 255, 128))));
+        assert_eq!(value("rgb(100% 0% 0% / 50%)"), Some(Value::Color(Color::new(255, 0, 0, 128))));
+        assert_eq!(value("rgb(1, 2)"), None);
+        assert_eq!(value("url(a.png)"), Some(Value::Function("url".to_string(), vec![Value::Keyword("a.png".to_string())])));
+        assert_eq!(value("url(\"b c.png\")").unwrap().to_string(), "url(b c.png)");
+        assert_eq!(value("linear-gradient(45deg, red, blue)").unwrap().to_string(), "linear-gradient(45deg, red, blue)");
+        assert_eq!(
+            value("blur(2px)"),
+            Some(Value::Function("blur".to_string(), vec![Value::Length(2.0, Unit::Px)]))
+        );
+        assert_eq!(value("var(--gap)").unwrap().resolve_length(Some(100.0)), None);
+        assert!(matches!(value("min(10px, 5%)"), Some(Value::Function(name, _)) if name == "min"));
+
+        assert_eq!(value("calc(10px + 2 * (3px + 2px))"), Some(Value::Length(20.0, Unit::Px)));
+        let calc = value("calc(100% - 20px)").unwrap();
+        assert_eq!(calc.to_string(), "calc(100% - 20px)");
+        assert_eq!(calc.resolve_length(Some(300.0)), Some(280.0));
+        assert_eq!(calc.resolve_length(None), None);
+        assert_eq!(value("calc(10px +)"), None);
+        assert_eq!(value("calc(1px / 0)"), None);
+        assert_eq!(value("calc(10px + 2)"), None);
+        assert_eq!(value("calc(2px * 3px)"), None);
+        assert_eq!(value("calc(10px / 2px)"), None);
+        assert_eq!(value("calc(100% - 2)"), None);
+        assert_eq!(value("calc((1 + 2) * 3px)"), Some(Value::Length(9.0, Unit::Px)));
+        assert_eq!(value("calc(10px / 2 - 1px)"), Some(Value::Length(4.0, Unit::Px)));
+
+        let family = value("\"Open Sans\", Georgia, serif").unwrap();
+        assert_eq!(family.to_string(), "Open Sans, Georgia, serif");
+        assert_eq!(family.first_font_family().as_deref(), Some("Open Sans"));
+        assert_eq!(value("Times New Roman, serif").unwrap().first_font_family().as_deref(), Some("Times New Roman"));
+
+        let stylesheet = CssParser::parse("p { margin: 0 auto !important; color: rgb(0, 128, 0); }");
+        let declarations = &stylesheet.rules[0].declarations;
+        assert_eq!(declarations[0].value, Value::List(vec![Value::Number(0.0), Value::Keyword("auto".to_string())]));
+        assert_eq!(declarations[1].value, Value::Color(Color::new(0, 128, 0, 255)));
+    }
+
+    #[test]
+    fn test_raw_transition_values() {
+        let stylesheet = CssParser::parse(
+            "a { transition: opacity 0.3s cubic-bezier(0.1, 0.7, 1.0, 0.1), width 1s; color: red; } \
+             b { transition-duration: 200ms, 1s; transform: rotate(1deg) scale(2) }",
+        );
+        let values: Vec<(&str, &Value)> = stylesheet.rules.iter()
+            .flat_map(|rule| &rule.declarations)
+            .map(|declaration| (declaration.name.as_str(), &declaration.value))
+            .collect();
+        assert_eq!(values, [
+            ("transition", &Value::Keyword("opacity 0.3s cubic-bezier(0.1, 0.7, 1.0, 0.1), width 1s".to_string())),
+            ("color", &Value::Keyword("red".to_string())),
+            ("transition-duration", &Value::Keyword("200ms, 1s".to_string())),
+            ("transform", &Value::Keyword("rotate(1deg) scale(2)".to_string())),
+        ]);
+    }
 }
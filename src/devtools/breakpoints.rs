@@ -0,0 +1,173 @@
+// DOM breakpoints - stop scripts that change a chosen element
+//
+// Breakpoints are kept by the element's path in the document and turned
+// into watches on element nodes for the script runtime. Scripts reach
+// elements through `document.getElementById`, so elements without an id,
+// and descendants without one, cannot be changed by scripts and set no watch.
+
+use crate::dom::{Document, NodeId};
+use crate::js::MutationWatch;
+use crate::observers::MutationType;
+
+/// What a DOM breakpoint stops on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DomBreakpointKind {
+    /// An element inside the node is removed
+    SubtreeModified,
+    /// An attribute of the node is set or removed
+    AttributeModified,
+    /// The node itself is removed
+    NodeRemoved,
+}
+
+impl DomBreakpointKind {
+    pub const ALL: [DomBreakpointKind; 3] =
+        [DomBreakpointKind::SubtreeModified, DomBreakpointKind::AttributeModified, DomBreakpointKind::NodeRemoved];
+
+    /// Name as shown in the Elements view's context menu
+    pub fn label(&self) -> &'static str {
+        match self {
+            DomBreakpointKind::SubtreeModified => "Subtree modifications",
+            DomBreakpointKind::AttributeModified => "Attribute modifications",
+            DomBreakpointKind::NodeRemoved => "Node removal",
+        }
+    }
+}
+
+/// A breakpoint on a node of the document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DomBreakpoint {
+    /// Path of child indices from the document root
+    pub path: Vec<usize>,
+    pub kind: DomBreakpointKind,
+}
+
+/// The DOM breakpoints set in DevTools
+#[derive(Debug, Clone, Default)]
+pub struct DomBreakpoints {
+    breakpoints: Vec<DomBreakpoint>,
+}
+
+impl DomBreakpoints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set or clear a breakpoint, returning whether it is now set
+    pub fn toggle(&mut self, path: &[usize], kind: DomBreakpointKind) -> bool {
+        if let Some(index) = self.breakpoints.iter().position(|b| b.path == path && b.kind == kind) {
+            self.breakpoints.remove(index);
+            false
+        } else {
+            self.breakpoints.push(DomBreakpoint { path: path.to_vec(), kind });
+            true
+        }
+    }
+
+    pub fn is_set(&self, path: &[usize], kind: DomBreakpointKind) -> bool {
+        self.breakpoints.iter().any(|b| b.path == path && b.kind == kind)
+    }
+
+    pub fn breakpoints(&self) -> &[DomBreakpoint] {
+        &self.breakpoints
+    }
+
+    pub fn get(&self, index: usize) -> Option<&DomBreakpoint> {
+        self.breakpoints.get(index)
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// Keep paths pointing at the same nodes after the node at `path` is removed
+    ///
+    /// Breakpoints inside the removed node go with it and later siblings
+    /// move up by one.
+    pub fn node_removed(&mut self, path: &[usize]) {
+        let Some((&index, parent)) = path.split_last() else {
+            return;
+        };
+        self.breakpoints.retain(|b| !b.path.starts_with(path));
+        for breakpoint in &mut self.breakpoints {
+            if breakpoint.path.len() > parent.len()
+                && breakpoint.path.starts_with(parent)
+                && breakpoint.path[parent.len()] > index
+            {
+                breakpoint.path[parent.len()] -= 1;
+            }
+        }
+    }
+
+    /// Watches for the script runtime, on the document's elements with an id
+    pub fn watches(&self, document: &Document) -> Vec<MutationWatch> {
+        let has_id = |node: &NodeId| document.element_data(*node).and_then(|e| e.id()).is_some();
+        let mut watches = Vec::new();
+        for (index, breakpoint) in self.breakpoints.iter().enumerate() {
+            let Some(node) = document.node_at_path(&breakpoint.path) else {
+                continue;
+            };
+            let watch = |node, mutation| MutationWatch { node, mutation, breakpoint: index };
+            match breakpoint.kind {
+                DomBreakpointKind::AttributeModified => {
+                    watches.extend(Some(node).filter(has_id).map(|node| watch(node, MutationType::Attributes)));
+                }
+                DomBreakpointKind::NodeRemoved => {
+                    watches.extend(Some(node).filter(has_id).map(|node| watch(node, MutationType::ChildList)));
+                }
+                DomBreakpointKind::SubtreeModified => {
+                    let inside = document.descendants(node).into_iter().skip(1).filter(has_id);
+                    watches.extend(inside.map(|node| watch(node, MutationType::ChildList)));
+                }
+            }
+        }
+        watches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html::HtmlParser;
+
+    #[test]
+    fn test_watches_follow_breakpoints() {
+        let document = Document::new(HtmlParser::parse(
+            "<html><body><ul id=\"list\"><li id=\"one\">1</li><li>2</li><li id=\"three\">3</li></ul></body></html>",
+        ));
+        let element = |id| document.get_element_by_id(id).unwrap();
+        let list = document.path(element("list")).unwrap();
+        let mut breakpoints = DomBreakpoints::new();
+        assert!(breakpoints.toggle(&list, DomBreakpointKind::SubtreeModified));
+        assert!(breakpoints.toggle(&list, DomBreakpointKind::AttributeModified));
+        assert!(breakpoints.is_set(&list, DomBreakpointKind::SubtreeModified));
+
+        let watches = breakpoints.watches(&document);
+        let described: Vec<_> = watches.iter().map(|w| (w.node, w.mutation, w.breakpoint)).collect();
+        assert_eq!(described, [
+            (element("one"), MutationType::ChildList, 0),
+            (element("three"), MutationType::ChildList, 0),
+            (element("list"), MutationType::Attributes, 1),
+        ]);
+
+        assert!(!breakpoints.toggle(&list, DomBreakpointKind::SubtreeModified));
+        assert_eq!(breakpoints.breakpoints().len(), 1);
+        assert_eq!(breakpoints.get(0).unwrap().kind, DomBreakpointKind::AttributeModified);
+    }
+
+    #[test]
+    fn test_node_removed_shifts_paths() {
+        let mut breakpoints = DomBreakpoints::new();
+        breakpoints.toggle(&[1, 0], DomBreakpointKind::NodeRemoved);
+        breakpoints.toggle(&[1, 0, 2], DomBreakpointKind::AttributeModified);
+        breakpoints.toggle(&[1, 2, 1], DomBreakpointKind::AttributeModified);
+        breakpoints.toggle(&[1, 1], DomBreakpointKind::NodeRemoved);
+
+        breakpoints.node_removed(&[1, 0]);
+        let paths: Vec<_> = breakpoints.breakpoints().iter().map(|b| b.path.clone()).collect();
+        assert_eq!(paths, [vec![1, 1, 1], vec![1, 0]]);
+
+        breakpoints.node_removed(&[]);
+        assert_eq!(breakpoints.breakpoints().len(), 2);
+    }
+}
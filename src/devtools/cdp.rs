@@ -0,0 +1,1323 @@
+// Chrome DevTools Protocol server
+//
+// Lets external tools - the Chrome inspector, Puppeteer-style automation -
+// attach to the engine. A listener thread serves the `/json` discovery
+// endpoints and carries protocol messages over WebSockets; the commands are
+// run on the embedder's thread when it calls `CdpServer::poll`, since that
+// is where the page lives. The DOM, Page, Network, Runtime and Log domains
+// are supported, plus what clients need to find the page (Browser, Target).
+
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde_json::{json, Value};
+use url::Url;
+
+use super::{Console, ConsoleMessage, ConsoleMessageType, DevTools, NetworkRequest};
+use crate::css::{CssParser, Selector};
+use crate::dom::{Node, NodeType};
+use crate::engine::Screenshot;
+use crate::js::JsValue;
+use crate::print::{Margins, PaperSize, PrintOptions, CSS_PX_PER_INCH};
+use crate::style::matches_in;
+use crate::websocket::{accept_upgrade, CloseCode, ServerCodec, WebSocketMessage};
+
+/// Protocol version reported to clients
+const PROTOCOL_VERSION: &str = "1.3";
+
+/// Product reported by `Browser.getVersion` and `/json/version`
+const PRODUCT: &str = "BrowserEngine/0.1.0";
+
+/// The one page target; its main frame has the same ID, as in Chrome
+const TARGET_ID: &str = "page";
+
+/// Session handed out by `Target.attachToTarget`
+const SESSION_ID: &str = "page-session";
+
+/// The only execution context: the page's main world
+const EXECUTION_CONTEXT_ID: i64 = 1;
+
+/// How often server threads check for outgoing messages and shutdown
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Longest HTTP request head accepted
+const MAX_REQUEST_HEAD: usize = 16 * 1024;
+
+/// Elements serialized without an end tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr",
+];
+
+/// The page a DevTools client inspects, provided by the embedder
+pub trait CdpTarget {
+    /// URL of the page, if one is loaded
+    fn url(&self) -> Option<Url>;
+
+    /// Title of the page
+    fn title(&self) -> String;
+
+    /// The page's document
+    fn document(&self) -> Option<&Node>;
+
+    /// Load a URL; the page has loaded when this returns
+    fn navigate(&mut self, url: Url) -> Result<(), String>;
+
+    /// Reload the page
+    fn reload(&mut self, ignore_cache: bool);
+
+    /// Run a script in the page
+    fn evaluate(&mut self, expression: &str) -> Result<JsValue, String>;
+
+    /// Console and network logs
+    fn devtools(&self) -> &DevTools;
+
+    /// Paint the visible part of the page
+    fn capture_screenshot(&mut self) -> Result<Screenshot, String> {
+        Err("Screenshots are not supported".to_string())
+    }
+
+    /// Lay the page out on paper as a PDF file
+    fn print_to_pdf(&mut self, _options: &PrintOptions) -> Result<Vec<u8>, String> {
+        Err("Printing is not supported".to_string())
+    }
+}
+
+/// A protocol error, sent back in place of a result
+struct CdpError {
+    code: i64,
+    message: String,
+}
+
+impl CdpError {
+    fn method_not_found(method: &str) -> Self {
+        Self { code: -32601, message: format!("'{}' wasn't found", method) }
+    }
+
+    fn invalid_params(message: &str) -> Self {
+        Self { code: -32602, message: message.to_string() }
+    }
+
+    fn server(message: &str) -> Self {
+        Self { code: -32000, message: message.to_string() }
+    }
+}
+
+type CdpResult = Result<Value, CdpError>;
+
+/// Protocol state of one client: enabled domains, node IDs handed out,
+/// and how far it has been told about the logs
+#[derive(Debug, Default)]
+pub struct CdpSession {
+    /// Set once the client attached through `Target.attachToTarget`
+    session_id: Option<String>,
+    page: bool,
+    dom: bool,
+    network: bool,
+    runtime: bool,
+    log: bool,
+    /// Next console message to report in each domain, counted over all
+    /// messages ever logged
+    runtime_cursor: usize,
+    log_cursor: usize,
+    /// Next network request to announce, counted the same way
+    network_cursor: usize,
+    /// Requests announced whose response was not reported yet
+    pending_requests: Vec<usize>,
+    /// URL of the page the client was last told about
+    page_url: Option<Url>,
+    /// Page loads so far, for loader IDs
+    loads: u64,
+    /// Paths of the nodes handed out, indexed by node ID - 1
+    ///
+    /// The document is `[]` and its root element `[0]`.
+    nodes: Vec<Vec<usize>>,
+}
+
+impl CdpSession {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run one protocol message; returns the response and any events it caused
+    pub fn handle(&mut self, message: &str, target: &mut dyn CdpTarget) -> Vec<String> {
+        let Ok(request) = serde_json::from_str::<Value>(message) else {
+            return vec![json!({"error": {"code": -32700, "message": "Message must be a valid JSON"}}).to_string()];
+        };
+        let Some(id) = request["id"].as_i64() else {
+            return vec![
+                json!({"error": {"code": -32600, "message": "Message must have integer 'id' property"}}).to_string(),
+            ];
+        };
+        let method = request["method"].as_str().unwrap_or("");
+        let params = request.get("params").cloned().unwrap_or_else(|| json!({}));
+
+        let mut events = Vec::new();
+        let mut response = match self.dispatch(method, &params, target, &mut events) {
+            Ok(result) => json!({"id": id, "result": result}),
+            Err(e) => json!({"id": id, "error": {"code": e.code, "message": e.message}}),
+        };
+        if let Some(session_id) = request.get("sessionId") {
+            response["sessionId"] = session_id.clone();
+        }
+        let mut messages = vec![response.to_string()];
+        messages.extend(events.into_iter().map(|event| self.event_message(event)));
+        messages
+    }
+
+    /// Events for what changed in the page since the last call
+    pub fn events(&mut self, target: &dyn CdpTarget) -> Vec<String> {
+        let mut events = Vec::new();
+        if target.url() != self.page_url {
+            self.page_loaded(target, &mut events);
+        }
+        let devtools = target.devtools();
+        if self.runtime {
+            for message in new_messages(&devtools.console, &mut self.runtime_cursor) {
+                events.push(console_api_called(message));
+            }
+        }
+        if self.log {
+            for message in new_messages(&devtools.console, &mut self.log_cursor) {
+                events.push(log_entry_added(message));
+            }
+        }
+        if self.network {
+            self.network_events(target, &mut events);
+        }
+        events.into_iter().map(|event| self.event_message(event)).collect()
+    }
+
+    fn dispatch(
+        &mut self,
+        method: &str,
+        params: &Value,
+        target: &mut dyn CdpTarget,
+        events: &mut Vec<Value>,
+    ) -> CdpResult {
+        let console = &target.devtools().console;
+        let kept_messages = console.logged_count() - console.count();
+        match method {
+            "Browser.getVersion" => Ok(json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "product": PRODUCT,
+                "revision": "",
+                "userAgent": PRODUCT,
+                "jsVersion": "",
+            })),
+            "Target.getTargets" => Ok(json!({"targetInfos": [target_info(&*target)]})),
+            "Target.getTargetInfo" => Ok(json!({"targetInfo": target_info(&*target)})),
+            "Target.setDiscoverTargets" | "Target.setAutoAttach" => Ok(json!({})),
+            "Target.attachToTarget" => {
+                if params["targetId"].as_str() != Some(TARGET_ID) {
+                    return Err(CdpError::invalid_params("No target with given id found"));
+                }
+                self.session_id = Some(SESSION_ID.to_string());
+                Ok(json!({"sessionId": SESSION_ID}))
+            }
+
+            "Page.enable" => {
+                self.page = true;
+                self.page_url = target.url();
+                Ok(json!({}))
+            }
+            "Page.disable" => {
+                self.page = false;
+                Ok(json!({}))
+            }
+            "Page.getFrameTree" => Ok(json!({"frameTree": {"frame": self.frame(&*target)}})),
+            "Page.navigate" => {
+                let url = params["url"].as_str().ok_or_else(|| CdpError::invalid_params("url: string value expected"))?;
+                let url = Url::parse(url).map_err(|_| CdpError::server("Cannot navigate to invalid URL"))?;
+                let mut result = json!({"frameId": TARGET_ID});
+                if let Err(e) = target.navigate(url) {
+                    result["errorText"] = json!(e);
+                }
+                self.page_loaded(&*target, events);
+                result["loaderId"] = json!(self.loader_id());
+                Ok(result)
+            }
+            "Page.reload" => {
+                target.reload(params["ignoreCache"].as_bool().unwrap_or(false));
+                self.page_loaded(&*target, events);
+                Ok(json!({}))
+            }
+            "Page.captureScreenshot" => {
+                if !matches!(params["format"].as_str(), None | Some("png")) {
+                    return Err(CdpError::invalid_params("format: only png is supported"));
+                }
+                let screenshot = target.capture_screenshot().map_err(|e| CdpError::server(&e))?;
+                let png = screenshot.to_png().map_err(|e| CdpError::server(&e.to_string()))?;
+                Ok(json!({"data": BASE64.encode(png)}))
+            }
+            "Page.printToPDF" => {
+                let pdf = target.print_to_pdf(&print_options(params)).map_err(|e| CdpError::server(&e))?;
+                Ok(json!({"data": BASE64.encode(pdf)}))
+            }
+
+            "DOM.enable" => {
+                self.dom = true;
+                Ok(json!({}))
+            }
+            "DOM.disable" => {
+                self.dom = false;
+                Ok(json!({}))
+            }
+            "DOM.getDocument" => {
+                let depth = params["depth"].as_i64().unwrap_or(1);
+                self.nodes.clear();
+                let root = self.document_json(&*target, depth);
+                Ok(json!({"root": root}))
+            }
+            "DOM.describeNode" => {
+                let path = self.node_path(params)?;
+                let document = target.document().ok_or_else(|| CdpError::server("Document is not available"))?;
+                let node = match lookup(document, &path) {
+                    Some(node) => self.node_json(node, path, 0),
+                    None => self.document_json(&*target, 0),
+                };
+                Ok(json!({"node": node}))
+            }
+            "DOM.querySelector" | "DOM.querySelectorAll" => {
+                let path = self.node_path(params)?;
+                let selector = params["selector"]
+                    .as_str()
+                    .ok_or_else(|| CdpError::invalid_params("selector: string value expected"))?;
+                let selectors = parse_selectors(selector).ok_or_else(|| CdpError::server("DOM Error while querying"))?;
+                let document = target.document().ok_or_else(|| CdpError::server("Document is not available"))?;
+                let found = match lookup(document, &path) {
+                    Some(node) => {
+                        let mut found = Vec::new();
+                        let mut ancestors = document.ancestors(&path[1..]);
+                        ancestors.push(node);
+                        collect_matches(node, &mut ancestors, &mut path.clone(), &selectors, &mut found);
+                        found
+                    }
+                    None => document_matches(document, &selectors),
+                };
+                if method == "DOM.querySelector" {
+                    let node_id = found.into_iter().next().map_or(0, |path| self.node_id(path));
+                    Ok(json!({"nodeId": node_id}))
+                } else {
+                    let node_ids: Vec<i64> = found.into_iter().map(|path| self.node_id(path)).collect();
+                    Ok(json!({"nodeIds": node_ids}))
+                }
+            }
+            "DOM.getOuterHTML" => {
+                let path = self.node_path(params)?;
+                let document = target.document().ok_or_else(|| CdpError::server("Document is not available"))?;
+                let mut html = String::new();
+                outer_html(lookup(document, &path).unwrap_or(document), &mut html);
+                Ok(json!({"outerHTML": html}))
+            }
+
+            "Network.enable" => {
+                // Only requests made from now on are reported, as in Chrome
+                self.network = true;
+                self.network_cursor = target.devtools().network.logged_count();
+                self.pending_requests.clear();
+                Ok(json!({}))
+            }
+            "Network.disable" => {
+                self.network = false;
+                Ok(json!({}))
+            }
+
+            "Runtime.enable" => {
+                // Messages logged before are replayed
+                self.runtime = true;
+                self.runtime_cursor = kept_messages;
+                let origin = target.url().map(|url| url.origin().ascii_serialization()).unwrap_or_default();
+                events.push(json!({
+                    "method": "Runtime.executionContextCreated",
+                    "params": {"context": {
+                        "id": EXECUTION_CONTEXT_ID,
+                        "origin": origin,
+                        "name": "",
+                        "auxData": {"isDefault": true, "type": "default", "frameId": TARGET_ID},
+                    }},
+                }));
+                Ok(json!({}))
+            }
+            "Runtime.disable" => {
+                self.runtime = false;
+                Ok(json!({}))
+            }
+            "Runtime.runIfWaitingForDebugger" => Ok(json!({})),
+            "Runtime.evaluate" => {
+                let expression = params["expression"]
+                    .as_str()
+                    .ok_or_else(|| CdpError::invalid_params("expression: string value expected"))?;
+                Ok(evaluation_result(target.evaluate(expression), params["returnByValue"].as_bool().unwrap_or(false)))
+            }
+            "Runtime.callFunctionOn" => {
+                let function = params["functionDeclaration"]
+                    .as_str()
+                    .ok_or_else(|| CdpError::invalid_params("functionDeclaration: string value expected"))?;
+                // Only arguments passed by value are supported: there are no remote object handles
+                let arguments: Vec<String> = params["arguments"]
+                    .as_array()
+                    .map(|args| args.iter().map(|arg| arg.get("value").unwrap_or(&Value::Null).to_string()).collect())
+                    .unwrap_or_default();
+                let expression = format!("({})({})", function, arguments.join(", "));
+                Ok(evaluation_result(target.evaluate(&expression), params["returnByValue"].as_bool().unwrap_or(false)))
+            }
+
+            "Log.enable" => {
+                self.log = true;
+                self.log_cursor = kept_messages;
+                Ok(json!({}))
+            }
+            "Log.disable" => {
+                self.log = false;
+                Ok(json!({}))
+            }
+            "Log.clear" => {
+                self.log_cursor = console.logged_count();
+                Ok(json!({}))
+            }
+
+            _ => Err(CdpError::method_not_found(method)),
+        }
+    }
+
+    /// A new document is in the page
+    fn page_loaded(&mut self, target: &dyn CdpTarget, events: &mut Vec<Value>) {
+        self.page_url = target.url();
+        self.loads += 1;
+        self.nodes.clear();
+        if self.page {
+            let timestamp = seconds(SystemTime::now());
+            events.push(json!({"method": "Page.frameNavigated", "params": {"frame": self.frame(target)}}));
+            events.push(json!({"method": "Page.domContentEventFired", "params": {"timestamp": timestamp}}));
+            events.push(json!({"method": "Page.loadEventFired", "params": {"timestamp": timestamp}}));
+        }
+        if self.dom {
+            events.push(json!({"method": "DOM.documentUpdated", "params": {}}));
+        }
+    }
+
+    /// Announce new requests and report the ones that finished
+    fn network_events(&mut self, target: &dyn CdpTarget, events: &mut Vec<Value>) {
+        let network = &target.devtools().network;
+        let first_kept = network.logged_count() - network.count();
+        let request = |id: usize| id.checked_sub(first_kept).and_then(|i| network.requests().get(i));
+
+        for id in self.network_cursor.max(first_kept)..network.logged_count() {
+            if let Some(request) = request(id) {
+                events.push(request_will_be_sent(id, request, &self.loader_id(), self.page_url.as_ref()));
+                self.pending_requests.push(id);
+            }
+        }
+        self.network_cursor = network.logged_count();
+
+        let loader_id = self.loader_id();
+        self.pending_requests.retain(|&id| {
+            let Some(request) = request(id) else {
+                // Dropped from the log before it finished
+                return false;
+            };
+            if request.completed_at.is_none() {
+                return true;
+            }
+            events.extend(request_finished(id, request, &loader_id));
+            false
+        });
+    }
+
+    /// Wrap an event, addressing it to the client's session
+    fn event_message(&self, mut event: Value) -> String {
+        if let Some(session_id) = &self.session_id {
+            event["sessionId"] = json!(session_id);
+        }
+        event.to_string()
+    }
+
+    fn loader_id(&self) -> String {
+        format!("loader-{}", self.loads)
+    }
+
+    fn frame(&self, target: &dyn CdpTarget) -> Value {
+        let url = target.url();
+        json!({
+            "id": TARGET_ID,
+            "loaderId": self.loader_id(),
+            "url": url.as_ref().map_or("about:blank", Url::as_str),
+            "securityOrigin": url.as_ref().map(|url| url.origin().ascii_serialization()).unwrap_or_default(),
+            "mimeType": "text/html",
+        })
+    }
+
+    /// The document node, with its children down to `depth` (-1 for all)
+    fn document_json(&mut self, target: &dyn CdpTarget, depth: i64) -> Value {
+        let url = target.url().map(String::from).unwrap_or_default();
+        let mut json = json!({
+            "nodeId": self.node_id(Vec::new()),
+            "backendNodeId": 1,
+            "nodeType": 9,
+            "nodeName": "#document",
+            "localName": "",
+            "nodeValue": "",
+            "documentURL": url,
+            "baseURL": url,
+            "childNodeCount": target.document().map_or(0, |_| 1),
+        });
+        if let (Some(document), true) = (target.document(), depth != 0) {
+            json["children"] = json!([self.node_json(document, vec![0], depth - 1)]);
+        }
+        json
+    }
+
+    fn node_json(&mut self, node: &Node, path: Vec<usize>, depth: i64) -> Value {
+        let node_id = self.node_id(path.clone());
+        let mut json = match &node.node_type {
+            NodeType::Element(data) => {
+                let mut attributes: Vec<(&String, &String)> = data.attributes.iter().collect();
+                attributes.sort();
+                let attributes: Vec<&String> = attributes.into_iter().flat_map(|(name, value)| [name, value]).collect();
+                json!({
+                    "nodeType": 1,
+                    "nodeName": data.tag_name.to_ascii_uppercase(),
+                    "localName": data.tag_name,
+                    "nodeValue": "",
+                    "attributes": attributes,
+                })
+            }
+            NodeType::Text(text) => json!({"nodeType": 3, "nodeName": "#text", "localName": "", "nodeValue": text}),
+            NodeType::Comment(text) => {
+                json!({"nodeType": 8, "nodeName": "#comment", "localName": "", "nodeValue": text})
+            }
+        };
+        json["nodeId"] = json!(node_id);
+        json["backendNodeId"] = json!(node_id);
+        json["childNodeCount"] = json!(node.children.len());
+        if depth != 0 && !node.children.is_empty() {
+            let children: Vec<Value> = node
+                .children
+                .iter()
+                .enumerate()
+                .map(|(i, child)| {
+                    let mut child_path = path.clone();
+                    child_path.push(i);
+                    self.node_json(child, child_path, depth - 1)
+                })
+                .collect();
+            json["children"] = json!(children);
+        }
+        json
+    }
+
+    /// ID for the node at `path`, handing out a new one if needed
+    fn node_id(&mut self, path: Vec<usize>) -> i64 {
+        let index = match self.nodes.iter().position(|known| *known == path) {
+            Some(index) => index,
+            None => {
+                self.nodes.push(path);
+                self.nodes.len() - 1
+            }
+        };
+        index as i64 + 1
+    }
+
+    /// Path of the node named by the `nodeId` parameter
+    fn node_path(&self, params: &Value) -> Result<Vec<usize>, CdpError> {
+        params["nodeId"]
+            .as_i64()
+            .and_then(|id| usize::try_from(id - 1).ok())
+            .and_then(|index| self.nodes.get(index))
+            .cloned()
+            .ok_or_else(|| CdpError::server("Could not find node with given id"))
+    }
+}
+
+/// Owner of the discovery endpoints and the WebSocket clients
+pub struct CdpServer {
+    shared: Arc<Shared>,
+    events: Receiver<ServerEvent>,
+    clients: HashMap<u64, Client>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// State the server threads share with the embedder's thread
+struct Shared {
+    address: SocketAddr,
+    /// URL and title of the page, for `/json/list`
+    page: Mutex<(String, String)>,
+    /// Called when a message arrives, so the embedder polls
+    waker: Mutex<Option<Box<dyn Fn() + Send>>>,
+    stop: AtomicBool,
+    next_client: AtomicU64,
+}
+
+impl Shared {
+    fn wake(&self) {
+        if let Ok(waker) = self.waker.lock() {
+            if let Some(waker) = waker.as_ref() {
+                waker();
+            }
+        }
+    }
+}
+
+/// From a client's thread to the embedder's
+enum ServerEvent {
+    Opened(u64, Sender<String>),
+    Message(u64, String),
+    Closed(u64),
+}
+
+/// A connected WebSocket client
+struct Client {
+    outbox: Sender<String>,
+    session: CdpSession,
+}
+
+impl CdpServer {
+    /// Start listening, e.g. on `127.0.0.1:9222`
+    pub fn bind(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        let shared = Arc::new(Shared {
+            address: listener.local_addr()?,
+            page: Mutex::new((String::new(), String::new())),
+            waker: Mutex::new(None),
+            stop: AtomicBool::new(false),
+            next_client: AtomicU64::new(1),
+        });
+        let (events_tx, events) = mpsc::channel();
+        let thread_shared = Arc::clone(&shared);
+        let thread = std::thread::Builder::new()
+            .name("devtools-server".to_string())
+            .spawn(move || run_listener(listener, thread_shared, events_tx))?;
+        Ok(Self { shared, events, clients: HashMap::new(), thread: Some(thread) })
+    }
+
+    /// Address the server listens on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.shared.address
+    }
+
+    /// WebSocket URL clients attach to the page with
+    pub fn websocket_url(&self) -> String {
+        websocket_url(self.shared.address)
+    }
+
+    /// Call `waker` from the server's threads when a client sends a message
+    pub fn set_waker(&self, waker: impl Fn() + Send + 'static) {
+        if let Ok(mut slot) = self.shared.waker.lock() {
+            *slot = Some(Box::new(waker));
+        }
+    }
+
+    /// Number of attached clients
+    pub fn client_count(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Run the commands clients sent and send them the page's events
+    pub fn poll(&mut self, target: &mut dyn CdpTarget) {
+        if let Ok(mut page) = self.shared.page.lock() {
+            *page = (target.url().map(String::from).unwrap_or_default(), target.title());
+        }
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                ServerEvent::Opened(id, outbox) => {
+                    self.clients.insert(id, Client { outbox, session: CdpSession::new() });
+                }
+                ServerEvent::Message(id, message) => {
+                    if let Some(client) = self.clients.get_mut(&id) {
+                        for reply in client.session.handle(&message, target) {
+                            let _ = client.outbox.send(reply);
+                        }
+                    }
+                }
+                ServerEvent::Closed(id) => {
+                    self.clients.remove(&id);
+                }
+            }
+        }
+        for client in self.clients.values_mut() {
+            for event in client.session.events(target) {
+                let _ = client.outbox.send(event);
+            }
+        }
+    }
+
+    /// Disconnect every client and stop listening
+    pub fn shutdown(&mut self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        self.clients.clear();
+    }
+}
+
+impl Drop for CdpServer {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Accept connections until the server stops
+fn run_listener(listener: TcpListener, shared: Arc<Shared>, events: Sender<ServerEvent>) {
+    while !shared.stop.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let id = shared.next_client.fetch_add(1, Ordering::Relaxed);
+                let (shared, events) = (Arc::clone(&shared), events.clone());
+                let _ = std::thread::Builder::new()
+                    .name("devtools-client".to_string())
+                    .spawn(move || serve_connection(stream, id, &shared, &events));
+            }
+            Err(_) => std::thread::sleep(POLL_INTERVAL),
+        }
+    }
+}
+
+/// Answer one HTTP request, staying on as a WebSocket if it upgrades
+fn serve_connection(mut stream: TcpStream, id: u64, shared: &Shared, events: &Sender<ServerEvent>) {
+    if stream.set_nonblocking(false).is_err() || stream.set_read_timeout(Some(POLL_INTERVAL)).is_err() {
+        return;
+    }
+    let Some((head, rest)) = read_head(&mut stream, shared) else {
+        return;
+    };
+    let path = head.split(' ').nth(1).unwrap_or("");
+    match path {
+        "/json/version" => {
+            let version = json!({
+                "Browser": PRODUCT,
+                "Protocol-Version": PROTOCOL_VERSION,
+                "User-Agent": PRODUCT,
+                "webSocketDebuggerUrl": websocket_url(shared.address),
+            });
+            let _ = respond(&mut stream, "200 OK", &version.to_string());
+        }
+        "/json" | "/json/list" => {
+            let (url, title) = shared.page.lock().map(|page| page.clone()).unwrap_or_default();
+            let list = json!([{
+                "id": TARGET_ID,
+                "type": "page",
+                "title": title,
+                "url": url,
+                "webSocketDebuggerUrl": websocket_url(shared.address),
+                "devtoolsFrontendUrl": format!(
+                    "devtools://devtools/bundled/inspector.html?ws={}/devtools/page/{}",
+                    shared.address, TARGET_ID
+                ),
+            }]);
+            let _ = respond(&mut stream, "200 OK", &list.to_string());
+        }
+        path if path.starts_with("/devtools/") => match accept_upgrade(&head) {
+            Ok(upgrade) if stream.write_all(upgrade.response.as_bytes()).is_ok() => {
+                serve_websocket(stream, rest, id, shared, events);
+            }
+            Ok(_) => {}
+            Err(e) => {
+                let _ = respond(&mut stream, "400 Bad Request", &e.to_string());
+            }
+        },
+        _ => {
+            let _ = respond(&mut stream, "404 Not Found", "");
+        }
+    }
+}
+
+/// Carry protocol messages between a client and the embedder's thread
+fn serve_websocket(mut stream: TcpStream, received: Vec<u8>, id: u64, shared: &Shared, events: &Sender<ServerEvent>) {
+    let (outbox, replies) = mpsc::channel::<String>();
+    if events.send(ServerEvent::Opened(id, outbox)).is_err() {
+        return;
+    }
+    let mut codec = ServerCodec::new();
+    let mut buf = received;
+    let mut chunk = [0u8; 8192];
+    'connection: while !shared.stop.load(Ordering::Relaxed) {
+        let messages = match codec.receive_bytes(&buf) {
+            Ok(messages) => messages,
+            Err(e) => {
+                let _ = stream.write_all(&ServerCodec::close(e.close_code()));
+                break;
+            }
+        };
+        buf.clear();
+        for message in messages {
+            match message {
+                WebSocketMessage::Text(text) => {
+                    if events.send(ServerEvent::Message(id, text)).is_err() {
+                        break 'connection;
+                    }
+                    shared.wake();
+                }
+                WebSocketMessage::Ping(payload) => {
+                    if stream.write_all(&ServerCodec::pong(payload)).is_err() {
+                        break 'connection;
+                    }
+                }
+                WebSocketMessage::Close(..) => {
+                    let _ = stream.write_all(&ServerCodec::close(CloseCode::Normal));
+                    break 'connection;
+                }
+                WebSocketMessage::Binary(_) | WebSocketMessage::Pong(_) => {}
+            }
+        }
+
+        for reply in replies.try_iter() {
+            if stream.write_all(&ServerCodec::text(&reply)).is_err() {
+                break 'connection;
+            }
+        }
+
+        match stream.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(_) => break,
+        }
+    }
+    let _ = events.send(ServerEvent::Closed(id));
+    shared.wake();
+}
+
+/// Read an HTTP request head; returns it and any bytes read past it
+fn read_head(stream: &mut TcpStream, shared: &Shared) -> Option<(String, Vec<u8>)> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    loop {
+        if let Some(end) = buf.windows(4).position(|window| window == b"\r\n\r\n") {
+            let rest = buf.split_off(end + 4);
+            return String::from_utf8(buf).ok().map(|head| (head, rest));
+        }
+        if buf.len() > MAX_REQUEST_HEAD || shared.stop.load(Ordering::Relaxed) {
+            return None;
+        }
+        match stream.read(&mut chunk) {
+            Ok(0) => return None,
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(_) => return None,
+        }
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: &str, body: &str) -> io::Result<()> {
+    let content_type = if body.starts_with(['{', '[']) { "application/json" } else { "text/plain" };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}; charset=UTF-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+fn websocket_url(address: SocketAddr) -> String {
+    format!("ws://{}/devtools/page/{}", address, TARGET_ID)
+}
+
+fn target_info(target: &dyn CdpTarget) -> Value {
+    json!({
+        "targetId": TARGET_ID,
+        "type": "page",
+        "title": target.title(),
+        "url": target.url().map(String::from).unwrap_or_default(),
+        "attached": true,
+    })
+}
+
+/// Seconds since the epoch, the protocol's timestamps
+fn seconds(time: SystemTime) -> f64 {
+    time.duration_since(UNIX_EPOCH).map_or(0.0, |elapsed| elapsed.as_secs_f64())
+}
+
+/// Console messages logged since `cursor`, moving it past them
+fn new_messages<'a>(console: &'a Console, cursor: &mut usize) -> &'a [ConsoleMessage] {
+    let first_kept = console.logged_count() - console.count();
+    let start = (*cursor).max(first_kept) - first_kept;
+    *cursor = console.logged_count();
+    &console.messages()[start..]
+}
+
+fn console_api_called(message: &ConsoleMessage) -> Value {
+    let kind = match message.msg_type {
+        ConsoleMessageType::Log => "log",
+        ConsoleMessageType::Info => "info",
+        ConsoleMessageType::Warn => "warning",
+        ConsoleMessageType::Error => "error",
+        ConsoleMessageType::Debug => "debug",
+    };
+    json!({
+        "method": "Runtime.consoleAPICalled",
+        "params": {
+            "type": kind,
+            "args": [{"type": "string", "value": message.content}],
+            "executionContextId": EXECUTION_CONTEXT_ID,
+            "timestamp": seconds(message.timestamp) * 1000.0,
+        },
+    })
+}
+
+fn log_entry_added(message: &ConsoleMessage) -> Value {
+    let level = match message.msg_type {
+        ConsoleMessageType::Log | ConsoleMessageType::Info => "info",
+        ConsoleMessageType::Warn => "warning",
+        ConsoleMessageType::Error => "error",
+        ConsoleMessageType::Debug => "verbose",
+    };
+    let mut entry = json!({
+        "source": "javascript",
+        "level": level,
+        "text": message.content,
+        "timestamp": seconds(message.timestamp) * 1000.0,
+    });
+    if let Some(source) = &message.source {
+        entry["url"] = json!(source);
+    }
+    json!({"method": "Log.entryAdded", "params": {"entry": entry}})
+}
+
+fn request_will_be_sent(id: usize, request: &NetworkRequest, loader_id: &str, page_url: Option<&Url>) -> Value {
+    let started = seconds(request.started_at);
+    json!({
+        "method": "Network.requestWillBeSent",
+        "params": {
+            "requestId": id.to_string(),
+            "loaderId": loader_id,
+            "documentURL": page_url.map_or(request.url.as_str(), Url::as_str),
+            "request": {
+                "url": request.url.as_str(),
+                "method": request.method,
+                "headers": header_object(&request.request_headers),
+            },
+            "timestamp": started,
+            "wallTime": started,
+            "initiator": {"type": "other"},
+            "type": format!("{:?}", request.request_type),
+            "frameId": TARGET_ID,
+        },
+    })
+}
+
+/// Headers as a CDP `Headers` object; repeated ones are joined by newlines
+fn header_object(headers: &[(String, String)]) -> Value {
+    let mut object = serde_json::Map::new();
+    for (name, value) in headers {
+        match object.get_mut(name) {
+            Some(Value::String(joined)) => {
+                joined.push('\n');
+                joined.push_str(value);
+            }
+            _ => {
+                object.insert(name.clone(), json!(value));
+            }
+        }
+    }
+    Value::Object(object)
+}
+
+/// `responseReceived` and `loadingFinished`, or `loadingFailed` for a
+/// request that got no response
+fn request_finished(id: usize, request: &NetworkRequest, loader_id: &str) -> Vec<Value> {
+    let timestamp = request.completed_at.map_or(0.0, seconds);
+    let resource_type = format!("{:?}", request.request_type);
+    let size = request.size.unwrap_or(0);
+    match request.status {
+        Some(status) if status > 0 => vec![
+            json!({
+                "method": "Network.responseReceived",
+                "params": {
+                    "requestId": id.to_string(),
+                    "loaderId": loader_id,
+                    "timestamp": timestamp,
+                    "type": resource_type,
+                    "frameId": TARGET_ID,
+                    "response": {
+                        "url": request.url.as_str(),
+                        "status": status,
+                        "statusText": "",
+                        "headers": header_object(&request.response_headers),
+                        "mimeType": request.content_type.as_deref().unwrap_or(""),
+                        "encodedDataLength": size,
+                    },
+                },
+            }),
+            json!({
+                "method": "Network.loadingFinished",
+                "params": {"requestId": id.to_string(), "timestamp": timestamp, "encodedDataLength": size},
+            }),
+        ],
+        _ => vec![json!({
+            "method": "Network.loadingFailed",
+            "params": {
+                "requestId": id.to_string(),
+                "timestamp": timestamp,
+                "type": resource_type,
+                "errorText": "net::ERR_FAILED",
+            },
+        })],
+    }
+}
+
+/// Result of `Runtime.evaluate` for a script's outcome
+fn evaluation_result(outcome: Result<JsValue, String>, by_value: bool) -> Value {
+    match outcome {
+        Ok(value) => json!({"result": remote_object(&value, by_value)}),
+        Err(message) => json!({
+            "result": {"type": "object", "subtype": "error", "className": "Error", "description": message},
+            "exceptionDetails": {
+                "exceptionId": 1,
+                "text": "Uncaught",
+                "lineNumber": 0,
+                "columnNumber": 0,
+                "exception": {"type": "object", "subtype": "error", "className": "Error", "description": message},
+            },
+        }),
+    }
+}
+
+/// A script value as a protocol `RemoteObject`
+///
+/// There are no handles to objects, so objects always carry their value.
+fn remote_object(value: &JsValue, by_value: bool) -> Value {
+    match value {
+        JsValue::Undefined => json!({"type": "undefined"}),
+        JsValue::Null => json!({"type": "object", "subtype": "null", "value": null}),
+        JsValue::Boolean(b) => json!({"type": "boolean", "value": b}),
+        JsValue::Number(n) if n.is_finite() => json!({"type": "number", "value": n, "description": value.to_string()}),
+        JsValue::Number(_) => {
+            json!({"type": "number", "unserializableValue": value.to_string(), "description": value.to_string()})
+        }
+        JsValue::String(s) => json!({"type": "string", "value": s}),
+        JsValue::Array(items) => json!({
+            "type": "object",
+            "subtype": "array",
+            "className": "Array",
+            "description": format!("Array({})", items.len()),
+            "value": to_json(value),
+        }),
+        JsValue::Object(_) => {
+            let mut object = json!({"type": "object", "className": "Object", "description": "Object"});
+            if by_value {
+                object["value"] = to_json(value);
+            }
+            object
+        }
+        JsValue::Function(code) => json!({"type": "function", "className": "Function", "description": code}),
+    }
+}
+
+fn to_json(value: &JsValue) -> Value {
+    match value {
+        JsValue::Undefined | JsValue::Null | JsValue::Function(_) => Value::Null,
+        JsValue::Boolean(b) => json!(b),
+        JsValue::Number(n) => json!(n),
+        JsValue::String(s) => json!(s),
+        JsValue::Array(items) => Value::Array(items.iter().map(to_json).collect()),
+        JsValue::Object(fields) => Value::Object(fields.iter().map(|(k, v)| (k.clone(), to_json(v))).collect()),
+    }
+}
+
+/// The node at `path` below the document; `None` for the document itself
+fn lookup<'a>(document: &'a Node, path: &[usize]) -> Option<&'a Node> {
+    let (&first, rest) = path.split_first()?;
+    if first != 0 {
+        return None;
+    }
+    rest.iter().try_fold(document, |node, &i| node.children.get(i))
+}
+
+/// Parse a selector list as `querySelector` takes it; `None` if it is invalid
+pub(crate) fn parse_selectors(selector: &str) -> Option<Vec<Selector>> {
+    let rule = CssParser::parse(&format!("{} {{}}", selector)).rules.into_iter().next()?;
+    (!rule.selectors.is_empty()).then_some(rule.selectors)
+}
+
+/// Paths of the nodes of a document matching any of `selectors`, the
+/// document itself first, in document order
+///
+/// Paths start with 0 for the document, as node paths do in the protocol.
+pub(crate) fn document_matches(document: &Node, selectors: &[Selector]) -> Vec<Vec<usize>> {
+    let mut found = Vec::new();
+    if selectors.iter().any(|s| matches_in(document, &[], s)) {
+        found.push(vec![0]);
+    }
+    collect_matches(document, &mut vec![document], &mut vec![0], selectors, &mut found);
+    found
+}
+
+/// Paths of the elements below `node` matching any of `selectors`, in
+/// document order
+///
+/// `ancestors` runs from the root down to `node` itself.
+fn collect_matches<'n>(
+    node: &'n Node,
+    ancestors: &mut Vec<&'n Node>,
+    path: &mut Vec<usize>,
+    selectors: &[Selector],
+    found: &mut Vec<Vec<usize>>,
+) {
+    for (i, child) in node.children.iter().enumerate() {
+        path.push(i);
+        if selectors.iter().any(|s| matches_in(child, ancestors, s)) {
+            found.push(path.clone());
+        }
+        ancestors.push(child);
+        collect_matches(child, ancestors, path, selectors, found);
+        ancestors.pop();
+        path.pop();
+    }
+}
+
+/// Print options from `Page.printToPDF` parameters, which measure paper in inches
+fn print_options(params: &Value) -> PrintOptions {
+    let inches = |name: &str, default: f64| params[name].as_f64().unwrap_or(default) as f32 * CSS_PX_PER_INCH;
+    PrintOptions {
+        paper: PaperSize::Custom { width: inches("paperWidth", 8.5), height: inches("paperHeight", 11.0) },
+        landscape: params["landscape"].as_bool().unwrap_or(false),
+        margins: Margins {
+            top: inches("marginTop", 0.4),
+            right: inches("marginRight", 0.4),
+            bottom: inches("marginBottom", 0.4),
+            left: inches("marginLeft", 0.4),
+        },
+        scale: params["scale"].as_f64().unwrap_or(1.0) as f32,
+        header_footer: params["displayHeaderFooter"].as_bool().unwrap_or(false),
+        background_graphics: params["printBackground"].as_bool().unwrap_or(false),
+    }
+}
+
+/// Serialize a node and what it contains as HTML
+pub(crate) fn outer_html(node: &Node, html: &mut String) {
+    match &node.node_type {
+        NodeType::Element(data) => {
+            html.push('<');
+            html.push_str(&data.tag_name);
+            let mut attributes: Vec<_> = data.attributes.iter().collect();
+            attributes.sort();
+            for (name, value) in attributes {
+                html.push_str(&format!(" {}=\"{}\"", name, value.replace('&', "&amp;").replace('"', "&quot;")));
+            }
+            html.push('>');
+            if VOID_ELEMENTS.contains(&data.tag_name.as_str()) {
+                return;
+            }
+            for child in &node.children {
+                outer_html(child, html);
+            }
+            html.push_str(&format!("</{}>", data.tag_name));
+        }
+        NodeType::Text(text) => {
+            html.push_str(&text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;"));
+        }
+        NodeType::Comment(text) => html.push_str(&format!("<!--{}-->", text)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devtools::NetworkRequestType;
+    use crate::html::HtmlParser;
+    use crate::js::JsRuntime;
+    use std::time::Instant;
+
+    struct TestPage {
+        url: Option<Url>,
+        document: Option<Node>,
+        runtime: JsRuntime,
+        devtools: DevTools,
+    }
+
+    impl TestPage {
+        fn new() -> Self {
+            Self { url: None, document: None, runtime: JsRuntime::new(), devtools: DevTools::new() }
+        }
+    }
+
+    impl CdpTarget for TestPage {
+        fn url(&self) -> Option<Url> {
+            self.url.clone()
+        }
+
+        fn title(&self) -> String {
+            "Test".to_string()
+        }
+
+        fn document(&self) -> Option<&Node> {
+            self.document.as_ref()
+        }
+
+        fn navigate(&mut self, url: Url) -> Result<(), String> {
+            let idx = self.devtools.network.log_request(url.clone(), "GET".to_string(), NetworkRequestType::Document);
+            self.devtools.network.complete_request(idx, 200, 42, Some("text/html".to_string()));
+            self.document = Some(HtmlParser::parse(
+                "<html><body><p class=\"a\">One</p><div><p id=\"two\">Two &amp; more</p><br></div></body></html>",
+            ));
+            self.url = Some(url);
+            Ok(())
+        }
+
+        fn reload(&mut self, _: bool) {}
+
+        fn evaluate(&mut self, expression: &str) -> Result<JsValue, String> {
+            self.runtime.execute(expression).map_err(|e| e.to_string())
+        }
+
+        fn devtools(&self) -> &DevTools {
+            &self.devtools
+        }
+    }
+
+    /// Send a command and return the parsed replies
+    fn call(session: &mut CdpSession, page: &mut TestPage, id: i64, method: &str, params: Value) -> Vec<Value> {
+        let message = json!({"id": id, "method": method, "params": params}).to_string();
+        session.handle(&message, page).iter().map(|reply| serde_json::from_str(reply).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_page_dom_and_runtime() {
+        let mut page = TestPage::new();
+        let mut session = CdpSession::new();
+        call(&mut session, &mut page, 1, "Page.enable", json!({}));
+        call(&mut session, &mut page, 2, "Network.enable", json!({}));
+        let replies = call(&mut session, &mut page, 3, "Page.navigate", json!({"url": "https://example.com/"}));
+        assert_eq!(replies[0]["id"], 3);
+        assert_eq!(replies[0]["result"]["frameId"], TARGET_ID);
+        let methods: Vec<&str> = replies[1..].iter().map(|event| event["method"].as_str().unwrap()).collect();
+        assert_eq!(methods, ["Page.frameNavigated", "Page.domContentEventFired", "Page.loadEventFired"]);
+        assert_eq!(replies[1]["params"]["frame"]["url"], "https://example.com/");
+
+        // The load shows up in the Network domain
+        let events: Vec<Value> = session.events(&page).iter().map(|e| serde_json::from_str(e).unwrap()).collect();
+        let methods: Vec<&str> = events.iter().map(|event| event["method"].as_str().unwrap()).collect();
+        assert_eq!(methods, ["Network.requestWillBeSent", "Network.responseReceived", "Network.loadingFinished"]);
+        assert_eq!(events[1]["params"]["response"]["status"], 200);
+        assert!(session.events(&page).is_empty());
+
+        let replies = call(&mut session, &mut page, 4, "DOM.getDocument", json!({"depth": -1}));
+        let root = &replies[0]["result"]["root"];
+        assert_eq!(root["nodeName"], "#document");
+        assert_eq!(root["children"][0]["nodeName"], "HTML");
+        let document_id = root["nodeId"].clone();
+
+        let params = json!({"nodeId": document_id, "selector": "p"});
+        let replies = call(&mut session, &mut page, 5, "DOM.querySelectorAll", params);
+        let node_ids = replies[0]["result"]["nodeIds"].as_array().unwrap().clone();
+        assert_eq!(node_ids.len(), 2);
+        let params = json!({"nodeId": document_id, "selector": "#two"});
+        let replies = call(&mut session, &mut page, 6, "DOM.querySelector", params);
+        assert_eq!(replies[0]["result"]["nodeId"], node_ids[1]);
+        let replies = call(&mut session, &mut page, 7, "DOM.getOuterHTML", json!({"nodeId": node_ids[1]}));
+        assert_eq!(replies[0]["result"]["outerHTML"], "<p id=\"two\">Two &amp; more</p>");
+        let replies = call(&mut session, &mut page, 8, "DOM.getOuterHTML", json!({"nodeId": 999}));
+        assert_eq!(replies[0]["error"]["code"], -32000);
+
+        let replies = call(&mut session, &mut page, 9, "Runtime.evaluate", json!({"expression": "[1, 2].length + 40"}));
+        assert_eq!(replies[0]["result"]["result"]["value"], 42.0);
+        let params = json!({"expression": "throw new Error('x')"});
+        let replies = call(&mut session, &mut page, 10, "Runtime.evaluate", params);
+        assert!(replies[0]["result"]["exceptionDetails"].is_object());
+        let replies = call(
+            &mut session,
+            &mut page,
+            11,
+            "Runtime.callFunctionOn",
+            json!({"functionDeclaration": "(a, b) => a + b", "arguments": [{"value": 2}, {"value": 3}]}),
+        );
+        assert_eq!(replies[0]["result"]["result"]["value"], 5.0);
+
+        let replies = call(&mut session, &mut page, 12, "Debugger.pause", json!({}));
+        assert_eq!(replies[0]["error"]["code"], -32601);
+        assert_eq!(session.handle("not json", &mut page).len(), 1);
+    }
+
+    #[test]
+    fn test_console_events() {
+        let mut page = TestPage::new();
+        let mut session = CdpSession::new();
+        page.devtools.console.log("before".to_string());
+        let replies = call(&mut session, &mut page, 1, "Runtime.enable", json!({}));
+        assert_eq!(replies[1]["method"], "Runtime.executionContextCreated");
+        call(&mut session, &mut page, 2, "Log.enable", json!({}));
+        page.devtools.console.warn("after".to_string());
+
+        // Messages from before enabling are replayed
+        let events: Vec<Value> = session.events(&page).iter().map(|e| serde_json::from_str(e).unwrap()).collect();
+        let summary: Vec<(&str, &str)> = events
+            .iter()
+            .map(|event| {
+                let text = event["params"]["args"][0]["value"].as_str().or(event["params"]["entry"]["text"].as_str());
+                (event["method"].as_str().unwrap(), text.unwrap())
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("Runtime.consoleAPICalled", "before"),
+                ("Runtime.consoleAPICalled", "after"),
+                ("Log.entryAdded", "before"),
+                ("Log.entryAdded", "after"),
+            ]
+        );
+        assert_eq!(events[1]["params"]["type"], "warning");
+    }
+
+    #[test]
+    fn test_server_over_websocket() {
+        let mut server = CdpServer::bind("127.0.0.1:0").unwrap();
+        let mut page = TestPage::new();
+        page.navigate(Url::parse("https://example.com/").unwrap()).unwrap();
+        server.poll(&mut page);
+
+        let mut http = TcpStream::connect(server.local_addr()).unwrap();
+        http.write_all(b"GET /json/list HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut listing = String::new();
+        http.read_to_string(&mut listing).unwrap();
+        assert!(listing.starts_with("HTTP/1.1 200 OK"));
+        assert!(listing.contains(&server.websocket_url()));
+        assert!(listing.contains("https://example.com/"));
+
+        let mut ws = TcpStream::connect(server.local_addr()).unwrap();
+        write!(
+            ws,
+            "GET /devtools/page/{} HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            TARGET_ID
+        )
+        .unwrap();
+        let message = br#"{"id":1,"method":"Browser.getVersion"}"#;
+        // A masked text frame; the zero mask leaves the payload as is
+        let mut frame = vec![0x81, 0x80 | message.len() as u8, 0, 0, 0, 0];
+        frame.extend_from_slice(message);
+        ws.write_all(&frame).unwrap();
+
+        ws.set_read_timeout(Some(Duration::from_millis(10))).unwrap();
+        let mut received = Vec::new();
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let reply = loop {
+            assert!(Instant::now() < deadline, "no reply");
+            server.poll(&mut page);
+            let mut chunk = [0u8; 1024];
+            if let Ok(n) = ws.read(&mut chunk) {
+                received.extend_from_slice(&chunk[..n]);
+            }
+            let text = String::from_utf8_lossy(&received);
+            if let Some(start) = text.find("{\"id\"") {
+                break text[start..].to_string();
+            }
+        };
+        assert!(String::from_utf8_lossy(&received).starts_with("HTTP/1.1 101 Switching Protocols"));
+        let reply: Value = serde_json::from_str(&reply).unwrap();
+        assert_eq!(reply["result"]["protocolVersion"], PROTOCOL_VERSION);
+        assert_eq!(server.client_count(), 1);
+    }
+}
@@ -0,0 +1,127 @@
+// Frame timeline - how long the browser took to produce each frame
+//
+// The embedder records every frame it draws; the performance view graphs
+// the most recent ones against the 60fps budget so jank stands out.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Frames kept for the graph
+const MAX_FRAMES: usize = 120;
+/// Time to draw a frame at 60fps
+pub const FRAME_BUDGET: Duration = Duration::from_micros(16_667);
+/// Frames taking this long are janky: the page visibly stutters
+pub const JANK_THRESHOLD: Duration = Duration::from_millis(50);
+
+/// A frame the browser drew
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    pub start: Instant,
+    /// Time spent producing the frame
+    pub duration: Duration,
+    /// Time since the previous frame began, if it was recent
+    pub interval: Option<Duration>,
+}
+
+impl Frame {
+    pub fn is_janky(&self) -> bool {
+        self.duration >= JANK_THRESHOLD
+    }
+}
+
+/// The most recent frames, oldest first
+#[derive(Debug, Clone, Default)]
+pub struct FrameTimeline {
+    frames: VecDeque<Frame>,
+}
+
+impl FrameTimeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a frame begun at `start` that took `duration` to draw
+    pub fn record_frame(&mut self, start: Instant, duration: Duration) {
+        // Frames after an idle spell are not late, just not needed
+        let interval = self
+            .frames
+            .back()
+            .map(|previous| start.duration_since(previous.start))
+            .filter(|interval| *interval < JANK_THRESHOLD * 4);
+        if self.frames.len() == MAX_FRAMES {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(Frame { start, duration, interval });
+    }
+
+    pub fn frames(&self) -> impl Iterator<Item = &Frame> {
+        self.frames.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Mean time to draw a frame
+    pub fn average(&self) -> Duration {
+        match self.frames.len() {
+            0 => Duration::ZERO,
+            n => self.frames.iter().map(|frame| frame.duration).sum::<Duration>() / n as u32,
+        }
+    }
+
+    /// Frames per second while frames came back to back, if they did
+    pub fn frame_rate(&self) -> Option<f64> {
+        let intervals: Vec<Duration> = self.frames.iter().filter_map(|frame| frame.interval).collect();
+        let total = intervals.iter().sum::<Duration>().as_secs_f64();
+        (total > 0.0).then(|| intervals.len() as f64 / total)
+    }
+
+    pub fn janky_count(&self) -> usize {
+        self.frames.iter().filter(|frame| frame.is_janky()).count()
+    }
+
+    /// Frames over the 60fps budget
+    pub fn over_budget_count(&self) -> usize {
+        self.frames.iter().filter(|frame| frame.duration > FRAME_BUDGET).count()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_timeline() {
+        let mut timeline = FrameTimeline::new();
+        assert_eq!(timeline.frame_rate(), None);
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        timeline.record_frame(start, ms(4));
+        timeline.record_frame(start + ms(20), ms(10));
+        timeline.record_frame(start + ms(40), ms(70));
+        // After an idle second the frame is not counted as late
+        timeline.record_frame(start + ms(1040), ms(4));
+
+        let intervals: Vec<_> = timeline.frames().map(|frame| frame.interval).collect();
+        assert_eq!(intervals, [None, Some(ms(20)), Some(ms(20)), None]);
+        assert_eq!(timeline.frame_rate(), Some(50.0));
+        assert_eq!(timeline.average(), ms(22));
+        assert_eq!(timeline.janky_count(), 1);
+        assert_eq!(timeline.over_budget_count(), 1);
+
+        for i in 0..MAX_FRAMES as u64 {
+            timeline.record_frame(start + ms(2000 + i * 16), ms(2));
+        }
+        assert_eq!(timeline.len(), MAX_FRAMES);
+        assert_eq!(timeline.janky_count(), 0);
+    }
+}
@@ -0,0 +1,211 @@
+// HAR export - network captures in the HTTP Archive 1.2 format
+//
+// HAR files open in other browsers' DevTools and in standalone viewers.
+// Phases the blocking client cannot tell apart (DNS, connecting, TLS) are
+// reported as unknown, -1, and counted in the wait. Chrome's
+// `_resourceType` and `_webSocketMessages` extensions are filled in too.
+
+use base64::Engine;
+use serde_json::{json, Value};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{CapturedBody, NetworkRequest};
+use crate::websocket::FrameDirection;
+
+/// The `log` object of a HAR file, holding one entry per request
+pub fn har_log<'a>(requests: impl Iterator<Item = &'a NetworkRequest>) -> Value {
+    json!({
+        "log": {
+            "version": "1.2",
+            "creator": {"name": "BrowserEngine", "version": env!("CARGO_PKG_VERSION")},
+            "pages": [],
+            "entries": requests.map(entry).collect::<Vec<_>>(),
+        }
+    })
+}
+
+fn entry(request: &NetworkRequest) -> Value {
+    let total = request.duration_ms.map_or(0.0, |ms| ms as f64);
+    let timings = match request.timing {
+        Some(timing) => json!({
+            "blocked": (total - timing.wait_ms - timing.receive_ms).max(0.0),
+            "dns": -1,
+            "connect": -1,
+            "ssl": -1,
+            "send": 0,
+            "wait": timing.wait_ms,
+            "receive": timing.receive_ms,
+        }),
+        None => json!({"blocked": -1, "dns": -1, "connect": -1, "ssl": -1, "send": 0, "wait": total, "receive": 0}),
+    };
+    let query: Vec<Value> =
+        request.url.query_pairs().map(|(name, value)| json!({"name": name, "value": value})).collect();
+    let mime_type = request.content_type.clone().unwrap_or_default();
+
+    let mut request_json = json!({
+        "method": request.method,
+        "url": request.url.as_str(),
+        "httpVersion": "HTTP/1.1",
+        "cookies": [],
+        "headers": headers(&request.request_headers),
+        "queryString": query,
+        "headersSize": -1,
+        "bodySize": request.request_body.as_ref().map_or(0, |body| body.size as i64),
+    });
+    if let Some(body) = &request.request_body {
+        let mime_type = header(&request.request_headers, "content-type").unwrap_or_default();
+        request_json["postData"] = json!({"mimeType": mime_type, "text": body_text(body).0});
+    }
+
+    let mut content = json!({"size": request.size.unwrap_or(0), "mimeType": mime_type});
+    if let Some(body) = &request.response_body {
+        let (text, encoding) = body_text(body);
+        content["text"] = json!(text);
+        if let Some(encoding) = encoding {
+            content["encoding"] = json!(encoding);
+        }
+        if body.is_truncated() {
+            content["comment"] = json!(format!("First {} of {} bytes", body.data.len(), body.size));
+        }
+    }
+
+    let mut entry = json!({
+        "startedDateTime": iso_8601(request.started_at),
+        "time": total,
+        "request": request_json,
+        "response": {
+            "status": request.status.unwrap_or(0),
+            "statusText": "",
+            "httpVersion": "HTTP/1.1",
+            "cookies": [],
+            "headers": headers(&request.response_headers),
+            "content": content,
+            "redirectURL": header(&request.response_headers, "location").unwrap_or_default(),
+            "headersSize": -1,
+            "bodySize": request.size.map_or(-1, |size| size as i64),
+        },
+        "cache": {},
+        "timings": timings,
+        "_resourceType": format!("{:?}", request.request_type).to_lowercase(),
+    });
+    if let Some(ws) = &request.websocket {
+        let messages: Vec<Value> = ws
+            .frames
+            .iter()
+            .map(|frame| {
+                let direction = match frame.direction {
+                    FrameDirection::Sent => "send",
+                    FrameDirection::Received => "receive",
+                };
+                let time = seconds(frame.timestamp);
+                json!({"type": direction, "time": time, "opcode": frame.opcode, "data": frame.preview})
+            })
+            .collect();
+        entry["_webSocketMessages"] = json!(messages);
+    }
+    entry
+}
+
+fn headers(headers: &[(String, String)]) -> Vec<Value> {
+    headers.iter().map(|(name, value)| json!({"name": name, "value": value})).collect()
+}
+
+fn header(headers: &[(String, String)], name: &str) -> Option<String> {
+    headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, value)| value.clone())
+}
+
+/// A body as HAR text: text as is, binary data in base64
+fn body_text(body: &CapturedBody) -> (String, Option<&'static str>) {
+    match body.text() {
+        Some(text) => (text.to_string(), None),
+        None => (base64::engine::general_purpose::STANDARD.encode(&body.data), Some("base64")),
+    }
+}
+
+fn seconds(time: SystemTime) -> f64 {
+    time.duration_since(UNIX_EPOCH).map_or(0.0, |d| d.as_secs_f64())
+}
+
+/// `2024-05-01T12:30:00.250Z`
+fn iso_8601(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, rest) = (secs / 86_400, secs % 86_400);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devtools::{NetworkFilter, NetworkRequestType, NetworkTab};
+    use crate::net::{RequestTiming, Transfer};
+    use std::time::Duration;
+    use url::Url;
+
+    #[test]
+    fn test_har_entries() {
+        let mut network = NetworkTab::new();
+        let url = Url::parse("https://example.com/search?q=rust&page=2").unwrap();
+        let idx = network.log_request(url, "GET".to_string(), NetworkRequestType::Document);
+        network.complete_request(idx, 200, 11, Some("text/html".to_string()));
+        let transfer = Transfer {
+            status: 200,
+            request_headers: vec![("user-agent".to_string(), "BrowserEngine/0.1.0".to_string())],
+            response_headers: vec![("content-type".to_string(), "text/html".to_string())],
+            timing: RequestTiming { wait_ms: 20.0, receive_ms: 5.0 },
+            ..Transfer::default()
+        };
+        network.record_transfer(idx, &transfer, b"<p>Hi</p>\n\n");
+        let image = network.log_request(
+            Url::parse("https://example.com/a.png").unwrap(),
+            "GET".to_string(),
+            NetworkRequestType::Image,
+        );
+        network.record_transfer(image, &transfer, &[0x89, b'P', b'N', b'G', 0xff]);
+
+        let har: Value = serde_json::from_str(&network.export_har()).unwrap();
+        assert_eq!(har["log"]["version"], "1.2");
+        let entries = har["log"]["entries"].as_array().unwrap();
+        assert_eq!(entries.len(), 2);
+        let page = &entries[0];
+        assert_eq!(page["request"]["queryString"][0], json!({"name": "q", "value": "rust"}));
+        assert_eq!(page["request"]["headers"][0]["name"], "user-agent");
+        assert_eq!(page["response"]["status"], 200);
+        assert_eq!(page["response"]["content"]["text"], "<p>Hi</p>\n\n");
+        assert_eq!(page["timings"]["wait"], 20.0);
+        assert_eq!(page["_resourceType"], "document");
+        assert_eq!(entries[1]["response"]["content"]["encoding"], "base64");
+        assert_eq!(entries[1]["response"]["status"], 0);
+
+        // Only filtered requests are exported
+        network.set_filter(NetworkFilter { types: vec![NetworkRequestType::Image], ..Default::default() });
+        let har: Value = serde_json::from_str(&network.export_har()).unwrap();
+        assert_eq!(har["log"]["entries"][0]["request"]["url"], "https://example.com/a.png");
+    }
+
+    #[test]
+    fn test_iso_8601() {
+        assert_eq!(iso_8601(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        let time = UNIX_EPOCH + Duration::from_millis(1_709_210_096_789);
+        assert_eq!(iso_8601(time), "2024-02-29T12:34:56.789Z");
+    }
+}
@@ -0,0 +1,144 @@
+// Layers view - the page's compositor layers and rendering overlays
+//
+// The layer tree belongs to the page and is rebuilt when it is laid out;
+// DevTools keeps which layer is selected and the overlay switches. Areas
+// repainted are remembered for a moment so paint flashing can tint them.
+
+use std::time::{Duration, Instant};
+
+use crate::compositor::{Compositor, Layer, LayerId};
+use crate::css::Color;
+use crate::display::{DisplayCommand, DisplayList};
+use crate::layout::Rect;
+
+/// Speed animations play at while slowed for debugging
+const SLOW_ANIMATION_RATE: f32 = 0.1;
+/// How long a repainted area stays tinted
+const PAINT_FLASH: Duration = Duration::from_millis(1000);
+const PAINT_FLASH_COLOR: Color = Color { r: 0, g: 200, b: 80, a: 110 };
+const LAYER_BORDER: Color = Color { r: 255, g: 152, b: 0, a: 255 };
+const TILE_BORDER: Color = Color { r: 0, g: 160, b: 230, a: 140 };
+
+/// State of the layers view and its overlays
+#[derive(Debug, Clone, Default)]
+pub struct LayersView {
+    selected: Option<LayerId>,
+    /// Tint areas of the page as they are repainted
+    pub show_paint_rects: bool,
+    /// Outline layers and their tiles over the page
+    pub show_layer_borders: bool,
+    /// Play the page's animations at a tenth of their speed
+    pub slow_animations: bool,
+    /// Areas repainted and when, newest last
+    paints: Vec<(Rect, Instant)>,
+}
+
+impl LayersView {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn select(&mut self, layer: Option<LayerId>) {
+        self.selected = layer;
+    }
+
+    pub fn selected(&self) -> Option<LayerId> {
+        self.selected
+    }
+
+    /// Rate the page's animations should play at
+    pub fn animation_rate(&self) -> f32 {
+        if self.slow_animations {
+            SLOW_ANIMATION_RATE
+        } else {
+            1.0
+        }
+    }
+
+    /// Remember areas of the page that were just repainted
+    pub fn record_paints(&mut self, rects: &[Rect], now: Instant) {
+        self.paints.retain(|(_, at)| now.duration_since(*at) < PAINT_FLASH);
+        self.paints.extend(rects.iter().map(|rect| (*rect, now)));
+    }
+
+    /// Are repaints still tinted, so the page needs drawing again as they fade
+    pub fn is_flashing(&self, now: Instant) -> bool {
+        self.show_paint_rects && self.paints.iter().any(|(_, at)| now.duration_since(*at) < PAINT_FLASH)
+    }
+
+    /// Repaint tints and layer borders for a page scrolled by `offset_y`
+    pub fn paint_overlays(&self, layers: &Compositor, offset_y: f32, now: Instant) -> DisplayList {
+        let shift = |rect: Rect| Rect { y: rect.y - offset_y, ..rect };
+        let mut list = Vec::new();
+        if self.show_layer_borders {
+            for layer in layers.layers_in_paint_order() {
+                for rect in tile_rects(layer) {
+                    let widths = (1.0, 1.0, 1.0, 1.0);
+                    list.push(DisplayCommand::Border { color: TILE_BORDER, rect: shift(rect), widths });
+                }
+                let rect = shift(layer.bounds);
+                list.push(DisplayCommand::Border { color: LAYER_BORDER, rect, widths: (2.0, 2.0, 2.0, 2.0) });
+            }
+        }
+        if self.show_paint_rects {
+            for (rect, at) in &self.paints {
+                let age = now.duration_since(*at).as_secs_f32() / PAINT_FLASH.as_secs_f32();
+                if age < 1.0 {
+                    let a = (PAINT_FLASH_COLOR.a as f32 * (1.0 - age)) as u8;
+                    let color = Color { a, ..PAINT_FLASH_COLOR };
+                    list.push(DisplayCommand::Highlight { color, rect: shift(*rect) });
+                }
+            }
+        }
+        list
+    }
+}
+
+/// The parts of a layer's tiles inside its bounds, in page coordinates
+pub fn tile_rects(layer: &Layer) -> impl Iterator<Item = Rect> + '_ {
+    let bounds = layer.bounds;
+    layer.tiles().iter().filter_map(move |tile| {
+        let tile = tile.coord.to_rect();
+        let x = tile.x.max(bounds.x);
+        let y = tile.y.max(bounds.y);
+        let right = (tile.x + tile.width).min(bounds.x + bounds.width);
+        let bottom = (tile.y + tile.height).min(bounds.y + bounds.height);
+        (right > x && bottom > y).then_some(Rect { x, y, width: right - x, height: bottom - y })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overlays() {
+        let mut layers = Compositor::default();
+        layers.create_layer(Rect { x: 0.0, y: 0.0, width: 300.0, height: 100.0 });
+        let mut view = LayersView::new();
+        let start = Instant::now();
+        view.record_paints(&[Rect { x: 10.0, y: 50.0, width: 20.0, height: 20.0 }], start);
+        assert!(view.paint_overlays(&layers, 0.0, start).is_empty());
+        assert!(!view.is_flashing(start));
+
+        view.show_layer_borders = true;
+        view.show_paint_rects = true;
+        let overlays = view.paint_overlays(&layers, 30.0, start + Duration::from_millis(500));
+        let rects: Vec<Rect> = overlays.iter().map(|command| command.rect()).collect();
+        // Two tiles cut to the layer, the layer, then the repainted area
+        assert_eq!(rects, [
+            Rect { x: 0.0, y: -30.0, width: 256.0, height: 100.0 },
+            Rect { x: 256.0, y: -30.0, width: 44.0, height: 100.0 },
+            Rect { x: 0.0, y: -30.0, width: 300.0, height: 100.0 },
+            Rect { x: 10.0, y: 20.0, width: 20.0, height: 20.0 },
+        ]);
+        assert!(matches!(overlays[3], DisplayCommand::Highlight { color, .. } if color.a == 55));
+
+        // Repaints fade out and are dropped
+        let later = start + PAINT_FLASH;
+        assert!(!view.is_flashing(later));
+        assert_eq!(view.paint_overlays(&layers, 0.0, later).len(), 3);
+        view.record_paints(&[], later);
+        assert!(view.paints.is_empty());
+    }
+}
@@ -0,0 +1,1318 @@
+// Developer Tools - Console, DOM Inspector, Network Tab, Layers, Performance, Lighthouse
+
+mod breakpoints;
+mod cdp;
+mod frames;
+mod har;
+mod layers;
+mod styles;
+
+pub use breakpoints::{DomBreakpoint, DomBreakpointKind, DomBreakpoints};
+pub use cdp::{CdpServer, CdpSession, CdpTarget};
+pub(crate) use cdp::{document_matches, outer_html, parse_selectors};
+pub use frames::{Frame, FrameTimeline, FRAME_BUDGET, JANK_THRESHOLD};
+pub use layers::{tile_rects, LayersView};
+pub use styles::{computed_style, matched_rules, styled_node_at_path, MatchedDeclaration, MatchedRule};
+
+use crate::dom::Node;
+use crate::js::{ConsoleProperty, ConsoleValue, EventListenerInfo};
+use crate::layout::{Dimensions, LayoutBox};
+use crate::net::{CacheStats, RequestTiming, Transfer};
+use crate::websocket::{FrameDirection, FrameInfo};
+use std::collections::HashMap;
+use std::time::SystemTime;
+use url::Url;
+
+/// Developer tools state
+pub struct DevTools {
+    /// Console log messages
+    pub console: Console,
+    /// DOM inspector state
+    pub dom_inspector: DomInspector,
+    /// Network activity log
+    pub network: NetworkTab,
+    /// Breakpoints on changes to the document
+    pub dom_breakpoints: DomBreakpoints,
+    /// Compositor layers view and rendering overlays
+    pub layers: LayersView,
+    /// Recent frames, for the performance view
+    pub frames: FrameTimeline,
+    /// Is devtools panel open
+    pub is_open: bool,
+    /// Current active tab
+    pub active_tab: DevToolsTab,
+}
+
+/// Active developer tools tab
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DevToolsTab {
+    Console,
+    DomInspector,
+    Network,
+    Layers,
+    /// Frame times and long tasks
+    Performance,
+    /// Core Web Vitals summary
+    Lighthouse,
+}
+
+impl DevTools {
+    /// Create new developer tools
+    pub fn new() -> Self {
+        Self {
+            console: Console::new(),
+            dom_inspector: DomInspector::new(),
+            network: NetworkTab::new(),
+            dom_breakpoints: DomBreakpoints::new(),
+            layers: LayersView::new(),
+            frames: FrameTimeline::new(),
+            is_open: false,
+            active_tab: DevToolsTab::Console,
+        }
+    }
+    
+    /// Toggle devtools panel
+    pub fn toggle(&mut self) {
+        self.is_open = !self.is_open;
+    }
+    
+    /// Set active tab
+    pub fn set_active_tab(&mut self, tab: DevToolsTab) {
+        self.active_tab = tab;
+    }
+    
+    /// Clear all devtools data
+    pub fn clear_all(&mut self) {
+        self.console.clear();
+        self.network.clear();
+        self.frames.clear();
+    }
+}
+
+impl Default for DevTools {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Console for logging JavaScript output and errors
+pub struct Console {
+    /// Console messages
+    messages: Vec<ConsoleMessage>,
+    /// Maximum messages to keep
+    max_messages: usize,
+    /// Messages ever logged, including dropped ones
+    logged: usize,
+    /// Properties of the logged objects that are expanded, by handle
+    expanded: HashMap<u32, Vec<ConsoleProperty>>,
+}
+
+/// Console message types
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConsoleMessageType {
+    Log,
+    Info,
+    Warn,
+    Error,
+    Debug,
+}
+
+/// A console message
+#[derive(Debug, Clone)]
+pub struct ConsoleMessage {
+    /// Message type
+    pub msg_type: ConsoleMessageType,
+    /// Message content
+    pub content: String,
+    /// Timestamp
+    pub timestamp: SystemTime,
+    /// Source file/location (if available)
+    pub source: Option<String>,
+    /// Handle of a logged object, whose properties can be listed
+    pub object: Option<u32>,
+}
+
+impl Console {
+    /// Create a new console
+    pub fn new() -> Self {
+        Self {
+            messages: Vec::new(),
+            max_messages: 1000,
+            logged: 0,
+            expanded: HashMap::new(),
+        }
+    }
+    
+    /// Log a message
+    pub fn log(&mut self, content: String) {
+        self.add_message(ConsoleMessageType::Log, content, None);
+    }
+    
+    /// Log an info message
+    pub fn info(&mut self, content: String) {
+        self.add_message(ConsoleMessageType::Info, content, None);
+    }
+    
+    /// Log a warning
+    pub fn warn(&mut self, content: String) {
+        self.add_message(ConsoleMessageType::Warn, content, None);
+    }
+    
+    /// Log an error
+    pub fn error(&mut self, content: String) {
+        self.add_message(ConsoleMessageType::Error, content, None);
+    }
+    
+    /// Log a debug message
+    pub fn debug(&mut self, content: String) {
+        self.add_message(ConsoleMessageType::Debug, content, None);
+    }
+    
+    /// Log the result of a console evaluation; objects can be expanded
+    pub fn log_value(&mut self, value: ConsoleValue) {
+        self.push(ConsoleMessage {
+            msg_type: ConsoleMessageType::Log,
+            content: value.preview,
+            timestamp: SystemTime::now(),
+            source: None,
+            object: value.handle,
+        });
+    }
+    
+    /// Add a message with source
+    pub fn add_message(&mut self, msg_type: ConsoleMessageType, content: String, source: Option<String>) {
+        self.push(ConsoleMessage {
+            msg_type,
+            content,
+            timestamp: SystemTime::now(),
+            source,
+            object: None,
+        });
+    }
+    
+    fn push(&mut self, message: ConsoleMessage) {
+        self.messages.push(message);
+        self.logged += 1;
+        
+        // Maintain max size
+        if self.messages.len() > self.max_messages {
+            self.messages.remove(0);
+        }
+    }
+    
+    /// Get all messages
+    pub fn messages(&self) -> &[ConsoleMessage] {
+        &self.messages
+    }
+    
+    /// Get messages by type
+    pub fn messages_by_type(&self, msg_type: ConsoleMessageType) -> Vec<&ConsoleMessage> {
+        self.messages
+            .iter()
+            .filter(|m| m.msg_type == msg_type)
+            .collect()
+    }
+    
+    /// Clear all messages
+    pub fn clear(&mut self) {
+        self.messages.clear();
+        self.expanded.clear();
+    }
+    
+    /// Show the properties of a logged object
+    pub fn expand(&mut self, handle: u32, properties: Vec<ConsoleProperty>) {
+        self.expanded.insert(handle, properties);
+    }
+    
+    /// Hide the properties of a logged object
+    pub fn collapse(&mut self, handle: u32) {
+        self.expanded.remove(&handle);
+    }
+    
+    /// Properties of a logged object, if it is expanded
+    pub fn expanded_properties(&self, handle: u32) -> Option<&[ConsoleProperty]> {
+        self.expanded.get(&handle).map(Vec::as_slice)
+    }
+    
+    /// Get message count
+    pub fn count(&self) -> usize {
+        self.messages.len()
+    }
+    
+    /// Messages ever logged; the kept ones are the last `count()` of them
+    pub fn logged_count(&self) -> usize {
+        self.logged
+    }
+    
+    /// Get error count
+    pub fn error_count(&self) -> usize {
+        self.messages.iter().filter(|m| m.msg_type == ConsoleMessageType::Error).count()
+    }
+    
+    /// Get warning count
+    pub fn warning_count(&self) -> usize {
+        self.messages.iter().filter(|m| m.msg_type == ConsoleMessageType::Warn).count()
+    }
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// DOM inspector for viewing and inspecting the DOM tree
+pub struct DomInspector {
+    /// Currently selected node (path from root)
+    selected_node_path: Vec<usize>,
+    /// Expanded nodes (paths)
+    expanded_nodes: Vec<Vec<usize>>,
+    /// Show text nodes
+    show_text_nodes: bool,
+    /// Show comments
+    show_comments: bool,
+    /// Event listeners on the selected node and its ancestors
+    event_listeners: Vec<EventListenerInfo>,
+}
+
+impl DomInspector {
+    /// Create a new DOM inspector
+    pub fn new() -> Self {
+        Self {
+            selected_node_path: vec![],
+            expanded_nodes: vec![vec![]],  // Root is expanded by default
+            show_text_nodes: true,
+            show_comments: false,
+            event_listeners: Vec::new(),
+        }
+    }
+    
+    /// Select a node by path
+    pub fn select_node(&mut self, path: Vec<usize>) {
+        self.selected_node_path = path;
+    }
+    
+    /// Get selected node path
+    pub fn selected_path(&self) -> &[usize] {
+        &self.selected_node_path
+    }
+    
+    /// Replace the listeners shown for the selected node
+    pub fn set_event_listeners(&mut self, listeners: Vec<EventListenerInfo>) {
+        self.event_listeners = listeners;
+    }
+    
+    /// Event listeners on the selected node and its ancestors
+    pub fn event_listeners(&self) -> &[EventListenerInfo] {
+        &self.event_listeners
+    }
+    
+    /// Select a node and expand its ancestors so it shows in the tree
+    pub fn reveal(&mut self, path: Vec<usize>) {
+        for depth in 0..path.len() {
+            if !self.is_expanded(&path[..depth]) {
+                self.expanded_nodes.push(path[..depth].to_vec());
+            }
+        }
+        self.selected_node_path = path;
+    }
+    
+    /// Toggle node expansion
+    pub fn toggle_node(&mut self, path: Vec<usize>) {
+        if let Some(idx) = self.expanded_nodes.iter().position(|p| p == &path) {
+            self.expanded_nodes.remove(idx);
+        } else {
+            self.expanded_nodes.push(path);
+        }
+    }
+    
+    /// Check if node is expanded
+    pub fn is_expanded(&self, path: &[usize]) -> bool {
+        self.expanded_nodes.iter().any(|p| p == path)
+    }
+    
+    /// Get expanded nodes
+    pub fn expanded_nodes(&self) -> &[Vec<usize>] {
+        &self.expanded_nodes
+    }
+    
+    /// Collapse all nodes
+    pub fn collapse_all(&mut self) {
+        self.expanded_nodes.clear();
+        self.expanded_nodes.push(vec![]);  // Keep root expanded
+    }
+    
+    /// Expand all nodes (up to a depth limit)
+    pub fn expand_all(&mut self, dom: &Node, max_depth: usize) {
+        self.expanded_nodes.clear();
+        self.expand_recursive(dom, vec![], 0, max_depth);
+    }
+    
+    fn expand_recursive(&mut self, node: &Node, path: Vec<usize>, depth: usize, max_depth: usize) {
+        if depth >= max_depth {
+            return;
+        }
+        
+        self.expanded_nodes.push(path.clone());
+        
+        for (i, _child) in node.children.iter().enumerate() {
+            let mut child_path = path.clone();
+            child_path.push(i);
+            self.expand_recursive(&node.children[i], child_path, depth + 1, max_depth);
+        }
+    }
+    
+    /// Toggle showing text nodes
+    pub fn toggle_text_nodes(&mut self) {
+        self.show_text_nodes = !self.show_text_nodes;
+    }
+    
+    /// Toggle showing comments
+    pub fn toggle_comments(&mut self) {
+        self.show_comments = !self.show_comments;
+    }
+    
+    /// Check if text nodes are shown
+    pub fn shows_text_nodes(&self) -> bool {
+        self.show_text_nodes
+    }
+    
+    /// Check if comments are shown
+    pub fn shows_comments(&self) -> bool {
+        self.show_comments
+    }
+    
+    /// Get node at path
+    pub fn get_node_at_path<'a>(&self, root: &'a Node, path: &[usize]) -> Option<&'a Node> {
+        let mut current = root;
+        for &idx in path {
+            current = current.children.get(idx)?;
+        }
+        Some(current)
+    }
+}
+
+impl Default for DomInspector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An element under the pointer in inspect mode
+#[derive(Debug, Clone)]
+pub struct InspectedElement {
+    /// Path from the root element, as used by `DomInspector`
+    pub path: Vec<usize>,
+    /// The element's box, in page coordinates
+    pub dimensions: Dimensions,
+    /// Tag, id and classes, e.g. `div#main.card`
+    pub label: String,
+}
+
+/// Find the innermost element whose box is under a point
+///
+/// `layout_root` must be laid out from `document`. Text has no box of its
+/// own to inspect, so pointing at it picks its element.
+pub fn inspect_element(document: &Node, layout_root: &LayoutBox, x: f32, y: f32) -> Option<InspectedElement> {
+    layout_root.hit_test(x, y).into_iter().rev().find_map(|layout_box| {
+        let node = layout_box.get_styled_node()?.node;
+        let label = node.element_data()?.label();
+        Some(InspectedElement { path: node_path(document, node)?, dimensions: layout_box.dimensions, label })
+    })
+}
+
+/// Path from `root` to `node`, found by identity
+fn node_path(root: &Node, node: &Node) -> Option<Vec<usize>> {
+    if std::ptr::eq(root, node) {
+        return Some(Vec::new());
+    }
+    root.children.iter().enumerate().find_map(|(i, child)| {
+        let mut path = node_path(child, node)?;
+        path.insert(0, i);
+        Some(path)
+    })
+}
+
+/// Network activity logger
+pub struct NetworkTab {
+    /// Network requests
+    requests: Vec<NetworkRequest>,
+    /// Maximum requests to keep
+    max_requests: usize,
+    /// Requests ever logged, including dropped ones
+    logged: usize,
+    /// Which requests the network view lists
+    filter: NetworkFilter,
+}
+
+/// Which requests the network view lists
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetworkFilter {
+    /// Types listed; every type when empty
+    pub types: Vec<NetworkRequestType>,
+    pub status: StatusFilter,
+    /// Only requests to this host and its subdomains
+    pub domain: Option<String>,
+}
+
+/// Requests listed by how they ended
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StatusFilter {
+    #[default]
+    Any,
+    /// Completed with a status below 400
+    Successful,
+    /// Failed or completed with an error status
+    Failed,
+    /// Still waiting for a response
+    Pending,
+}
+
+impl NetworkFilter {
+    /// Does a request pass the filter
+    pub fn matches(&self, request: &NetworkRequest) -> bool {
+        let type_matches = self.types.is_empty() || self.types.contains(&request.request_type);
+        let status_matches = match self.status {
+            StatusFilter::Any => true,
+            StatusFilter::Successful => request.completed_at.is_some() && !request.is_failed(),
+            StatusFilter::Failed => request.is_failed(),
+            StatusFilter::Pending => request.completed_at.is_none(),
+        };
+        let domain_matches = self.domain.as_deref().is_none_or(|domain| {
+            let host = request.url.host_str().unwrap_or("").to_ascii_lowercase();
+            let domain = domain.to_ascii_lowercase();
+            host == domain || host.ends_with(&format!(".{}", domain))
+        });
+        type_matches && status_matches && domain_matches
+    }
+
+    /// Does the filter list every request
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Network request record
+#[derive(Debug, Clone)]
+pub struct NetworkRequest {
+    /// Request URL
+    pub url: Url,
+    /// HTTP method
+    pub method: String,
+    /// Status code (if completed)
+    pub status: Option<u16>,
+    /// Response size in bytes
+    pub size: Option<usize>,
+    /// Request duration in milliseconds
+    pub duration_ms: Option<u64>,
+    /// Request start time
+    pub started_at: SystemTime,
+    /// Request end time
+    pub completed_at: Option<SystemTime>,
+    /// Content type
+    pub content_type: Option<String>,
+    /// Request type (Document, Stylesheet, Script, Image, etc.)
+    pub request_type: NetworkRequestType,
+    /// Connection and frames, for WebSocket requests
+    pub websocket: Option<WebSocketDetails>,
+    /// Headers sent, once the request went out
+    pub request_headers: Vec<(String, String)>,
+    /// Headers received
+    pub response_headers: Vec<(String, String)>,
+    /// Phases of the request, when it went over the network
+    pub timing: Option<RequestTiming>,
+    /// Body sent, for requests that have one
+    pub request_body: Option<CapturedBody>,
+    /// Body received
+    pub response_body: Option<CapturedBody>,
+    /// How the HTTP cache served the request, for loads through it
+    pub cache: Option<CacheUse>,
+}
+
+/// How the HTTP cache served a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheUse {
+    /// Served from the cache without a request
+    Hit,
+    /// The server confirmed the cached copy with `304 Not Modified`
+    Revalidated,
+    /// Fetched in full from the network
+    Miss,
+}
+
+impl CacheUse {
+    /// How a load was served, from the transfer it made; none means a hit
+    pub fn of(transfer: Option<&Transfer>) -> Self {
+        match transfer {
+            None => CacheUse::Hit,
+            Some(transfer) if transfer.status == 304 => CacheUse::Revalidated,
+            Some(_) => CacheUse::Miss,
+        }
+    }
+}
+
+/// Bytes of each body kept in the network log
+const MAX_CAPTURED_BODY: usize = 256 * 1024;
+
+/// Lines of a text body shown in a request's details
+const BODY_PREVIEW_LINES: usize = 200;
+
+/// A request or response body, cut off after `MAX_CAPTURED_BODY` bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedBody {
+    /// The start of the body
+    pub data: Vec<u8>,
+    /// Size of the whole body
+    pub size: usize,
+}
+
+impl CapturedBody {
+    /// Keep the start of a body
+    pub fn capture(body: &[u8]) -> Self {
+        Self {
+            data: body[..body.len().min(MAX_CAPTURED_BODY)].to_vec(),
+            size: body.len(),
+        }
+    }
+
+    /// Was the end of the body left out
+    pub fn is_truncated(&self) -> bool {
+        self.data.len() < self.size
+    }
+
+    /// The kept bytes as text, or `None` for binary data
+    ///
+    /// A character cut in half where the body was truncated is dropped.
+    pub fn text(&self) -> Option<&str> {
+        match std::str::from_utf8(&self.data) {
+            Ok(text) => Some(text),
+            Err(e) if e.error_len().is_none() && self.is_truncated() => {
+                std::str::from_utf8(&self.data[..e.valid_up_to()]).ok()
+            }
+            Err(_) => None,
+        }
+    }
+
+    /// Lines of the body's preview
+    fn preview_lines(&self, lines: &mut Vec<String>) {
+        match self.text() {
+            Some(text) => {
+                lines.extend(text.lines().take(BODY_PREVIEW_LINES).map(|line| format!("  {}", line)));
+                if text.lines().count() > BODY_PREVIEW_LINES || self.is_truncated() {
+                    lines.push("  \u{2026}".to_string());
+                }
+            }
+            None => lines.push(format!("  ({} B of binary data)", self.size)),
+        }
+    }
+}
+
+impl NetworkRequest {
+    /// Did the request end without a successful response
+    pub fn is_failed(&self) -> bool {
+        self.completed_at.is_some() && self.status.is_none_or(|status| status == 0 || status >= 400)
+    }
+
+    /// Lines of the request's detail view
+    ///
+    /// WebSocket requests list their frames, oldest first.
+    pub fn detail_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("{} {}", self.method, self.url)];
+        match self.status {
+            Some(status) => lines.push(format!("Status: {}", status)),
+            None if self.completed_at.is_some() => lines.push("Status: (failed)".to_string()),
+            None => lines.push("Status: (pending)".to_string()),
+        }
+        if let Some(ref content_type) = self.content_type {
+            lines.push(format!("Content-Type: {}", content_type));
+        }
+        if let Some(ms) = self.duration_ms {
+            lines.push(format!("Time: {} ms", ms));
+        }
+        if self.websocket.is_none() {
+            lines.push(format!("Size: {} B", self.size.unwrap_or(0)));
+        }
+        match self.cache {
+            Some(CacheUse::Hit) => lines.push("Cache: hit".to_string()),
+            Some(CacheUse::Revalidated) => lines.push("Cache: revalidated (304 Not Modified)".to_string()),
+            Some(CacheUse::Miss) => lines.push("Cache: miss".to_string()),
+            None => {}
+        }
+
+        if let Some(timing) = self.timing {
+            let total = self.duration_ms.map_or(0.0, |ms| ms as f64);
+            lines.push(String::new());
+            lines.push("Timing".to_string());
+            lines.push(format!("  Queueing: {:.1} ms", (total - timing.wait_ms - timing.receive_ms).max(0.0)));
+            lines.push(format!("  Waiting for response: {:.1} ms", timing.wait_ms));
+            lines.push(format!("  Content download: {:.1} ms", timing.receive_ms));
+        }
+        let headers = [("Response Headers", &self.response_headers), ("Request Headers", &self.request_headers)];
+        for (title, headers) in headers {
+            if !headers.is_empty() {
+                lines.push(String::new());
+                lines.push(format!("{} ({})", title, headers.len()));
+                lines.extend(headers.iter().map(|(name, value)| format!("  {}: {}", name, value)));
+            }
+        }
+        for (title, body) in [("Request Body", &self.request_body), ("Response", &self.response_body)] {
+            if let Some(body) = body {
+                lines.push(String::new());
+                lines.push(format!("{} ({} B)", title, body.size));
+                body.preview_lines(&mut lines);
+            }
+        }
+
+        let Some(ref ws) = self.websocket else {
+            return lines;
+        };
+        lines.push(String::new());
+        if let Some(ref protocol) = ws.protocol {
+            lines.push(format!("Protocol: {}", protocol));
+        }
+        lines.push(format!("Sent: {} B, received: {} B", ws.bytes_sent, ws.bytes_received));
+        if let Some(code) = ws.close_code {
+            lines.push(format!("Closed: {}", code));
+        }
+        if ws.dropped_frames > 0 {
+            lines.push(format!("({} earlier frames not shown)", ws.dropped_frames));
+        }
+        for frame in &ws.frames {
+            let arrow = match frame.direction {
+                FrameDirection::Sent => "↑",
+                FrameDirection::Received => "↓",
+            };
+            lines.push(format!("{} {:<12} {:>7} B  {}", arrow, frame.opcode_name(), frame.length, frame.preview));
+        }
+        lines
+    }
+}
+
+/// Frames kept per WebSocket connection
+const MAX_WEBSOCKET_FRAMES: usize = 1000;
+
+/// Characters of payload shown for each frame
+const FRAME_PREVIEW_CHARS: usize = 100;
+
+/// A WebSocket connection's activity
+#[derive(Debug, Clone, Default)]
+pub struct WebSocketDetails {
+    /// Subprotocol the server selected
+    pub protocol: Option<String>,
+    /// Close code, once the connection closed
+    pub close_code: Option<u16>,
+    /// Frames, up to `MAX_WEBSOCKET_FRAMES` most recent
+    pub frames: Vec<WebSocketFrame>,
+    /// Frames dropped to stay under the limit
+    pub dropped_frames: usize,
+    /// Payload bytes sent
+    pub bytes_sent: usize,
+    /// Payload bytes received
+    pub bytes_received: usize,
+}
+
+/// One WebSocket frame in the network log
+#[derive(Debug, Clone)]
+pub struct WebSocketFrame {
+    pub direction: FrameDirection,
+    pub opcode: u8,
+    /// Whether this is the final fragment of its message
+    pub fin: bool,
+    /// Payload length in bytes
+    pub length: usize,
+    /// Readable summary of the payload
+    pub preview: String,
+    pub timestamp: SystemTime,
+}
+
+impl WebSocketFrame {
+    /// Build a log entry from a frame recorded by the socket
+    pub fn from_info(info: &FrameInfo) -> Self {
+        Self {
+            direction: info.direction,
+            opcode: info.opcode,
+            fin: info.fin,
+            length: info.length,
+            preview: frame_preview(info),
+            timestamp: SystemTime::now(),
+        }
+    }
+
+    /// Name of the frame's opcode
+    pub fn opcode_name(&self) -> &'static str {
+        match self.opcode {
+            0x0 => "continuation",
+            0x1 => "text",
+            0x2 => "binary",
+            0x8 => "close",
+            0x9 => "ping",
+            0xA => "pong",
+            _ => "unknown",
+        }
+    }
+}
+
+/// Summarize a payload: text as text, close frames by code, the rest as hex
+fn frame_preview(info: &FrameInfo) -> String {
+    let truncated = info.length > info.preview.len();
+    match info.opcode {
+        0x0 | 0x1 => {
+            let text = String::from_utf8_lossy(&info.preview);
+            let mut preview: String = text.chars().take(FRAME_PREVIEW_CHARS).collect();
+            if truncated || text.chars().count() > FRAME_PREVIEW_CHARS {
+                preview.push('…');
+            }
+            preview
+        }
+        0x8 if info.preview.len() >= 2 => {
+            let code = u16::from_be_bytes([info.preview[0], info.preview[1]]);
+            let reason = String::from_utf8_lossy(&info.preview[2..]);
+            if reason.is_empty() {
+                code.to_string()
+            } else {
+                format!("{} {}", code, reason)
+            }
+        }
+        _ => {
+            let hex: Vec<String> = info.preview.iter().take(16).map(|b| format!("{:02x}", b)).collect();
+            let mut preview = hex.join(" ");
+            if info.length > 16 {
+                preview.push_str(" …");
+            }
+            preview
+        }
+    }
+}
+
+/// Type of network request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkRequestType {
+    Document,
+    Stylesheet,
+    Script,
+    Image,
+    Font,
+    Media,
+    XHR,
+    Fetch,
+    WebSocket,
+    Other,
+}
+
+impl NetworkTab {
+    /// Create a new network tab
+    pub fn new() -> Self {
+        Self {
+            requests: Vec::new(),
+            max_requests: 500,
+            logged: 0,
+            filter: NetworkFilter::default(),
+        }
+    }
+    
+    /// Log a new request
+    pub fn log_request(&mut self, url: Url, method: String, request_type: NetworkRequestType) -> usize {
+        let request = NetworkRequest {
+            url,
+            method,
+            status: None,
+            size: None,
+            duration_ms: None,
+            started_at: SystemTime::now(),
+            completed_at: None,
+            content_type: None,
+            websocket: (request_type == NetworkRequestType::WebSocket).then(WebSocketDetails::default),
+            request_type,
+            request_headers: Vec::new(),
+            response_headers: Vec::new(),
+            timing: None,
+            request_body: None,
+            response_body: None,
+            cache: None,
+        };
+        
+        self.requests.push(request);
+        self.logged += 1;
+        
+        // Maintain max size
+        if self.requests.len() > self.max_requests {
+            self.requests.remove(0);
+            // After removal, the index is max_requests - 1
+            return self.max_requests - 1;
+        }
+        
+        self.requests.len() - 1
+    }
+    
+    /// Complete a request with response data
+    pub fn complete_request(&mut self, idx: usize, status: u16, size: usize, content_type: Option<String>) {
+        if let Some(request) = self.requests.get_mut(idx) {
+            request.status = Some(status);
+            request.size = Some(size);
+            request.content_type = content_type;
+            request.completed_at = Some(SystemTime::now());
+            
+            // Calculate duration
+            if let Ok(duration) = request.completed_at.unwrap().duration_since(request.started_at) {
+                request.duration_ms = Some(duration.as_millis() as u64);
+            }
+        }
+    }
+    
+    /// Record the body sent with a request
+    pub fn set_request_body(&mut self, idx: usize, body: &[u8]) {
+        if let Some(request) = self.requests.get_mut(idx) {
+            request.request_body = Some(CapturedBody::capture(body));
+        }
+    }
+    
+    /// Record the headers, timing and body of a request's network transfer
+    pub fn record_transfer(&mut self, idx: usize, transfer: &Transfer, body: &[u8]) {
+        if let Some(request) = self.requests.get_mut(idx) {
+            request.request_headers = transfer.request_headers.clone();
+            request.response_headers = transfer.response_headers.clone();
+            request.timing = Some(transfer.timing);
+            request.response_body = Some(CapturedBody::capture(body));
+        }
+    }
+    
+    /// Record how the HTTP cache served a request, from its transfer
+    pub fn record_cache_use(&mut self, idx: usize, transfer: Option<&Transfer>) {
+        if let Some(request) = self.requests.get_mut(idx) {
+            request.cache = Some(CacheUse::of(transfer));
+        }
+    }
+    
+    /// Log a WebSocket connection being opened
+    pub fn log_websocket(&mut self, url: Url) -> usize {
+        self.log_request(url, "GET".to_string(), NetworkRequestType::WebSocket)
+    }
+    
+    /// The opening handshake succeeded (101 Switching Protocols)
+    pub fn websocket_opened(&mut self, idx: usize, protocol: Option<String>) {
+        self.complete_request(idx, 101, 0, None);
+        if let Some(ws) = self.websocket_mut(idx) {
+            ws.protocol = protocol;
+        }
+    }
+    
+    /// Record frames sent or received on a WebSocket connection
+    pub fn log_websocket_frames(&mut self, idx: usize, frames: &[FrameInfo]) {
+        let Some(request) = self.requests.get_mut(idx) else {
+            return;
+        };
+        let Some(ws) = request.websocket.as_mut() else {
+            return;
+        };
+        for info in frames {
+            match info.direction {
+                FrameDirection::Sent => ws.bytes_sent += info.length,
+                FrameDirection::Received => ws.bytes_received += info.length,
+            }
+            ws.frames.push(WebSocketFrame::from_info(info));
+        }
+        if ws.frames.len() > MAX_WEBSOCKET_FRAMES {
+            let excess = ws.frames.len() - MAX_WEBSOCKET_FRAMES;
+            ws.frames.drain(..excess);
+            ws.dropped_frames += excess;
+        }
+        request.size = Some(ws.bytes_sent + ws.bytes_received);
+    }
+    
+    /// The connection closed, with the close code if there was one
+    pub fn websocket_closed(&mut self, idx: usize, code: Option<u16>) {
+        if let Some(ws) = self.websocket_mut(idx) {
+            ws.close_code = code;
+        }
+    }
+    
+    /// Connection details of a WebSocket request
+    pub fn websocket(&self, idx: usize) -> Option<&WebSocketDetails> {
+        self.requests.get(idx)?.websocket.as_ref()
+    }
+    
+    fn websocket_mut(&mut self, idx: usize) -> Option<&mut WebSocketDetails> {
+        self.requests.get_mut(idx)?.websocket.as_mut()
+    }
+    
+    /// Get all requests
+    pub fn requests(&self) -> &[NetworkRequest] {
+        &self.requests
+    }
+    
+    /// Get requests by type
+    pub fn requests_by_type(&self, request_type: NetworkRequestType) -> Vec<&NetworkRequest> {
+        self.requests
+            .iter()
+            .filter(|r| r.request_type == request_type)
+            .collect()
+    }
+    
+    /// Get total requests count
+    pub fn count(&self) -> usize {
+        self.requests.len()
+    }
+    
+    /// Requests ever logged; the kept ones are the last `count()` of them
+    pub fn logged_count(&self) -> usize {
+        self.logged
+    }
+    
+    /// Get total transferred bytes
+    pub fn total_size(&self) -> usize {
+        self.requests.iter().filter_map(|r| r.size).sum()
+    }
+    
+    /// Cache hits, revalidations and misses among the kept requests
+    pub fn cache_stats(&self) -> CacheStats {
+        let mut stats = CacheStats::default();
+        for request in &self.requests {
+            match request.cache {
+                Some(CacheUse::Hit) => stats.hits += 1,
+                Some(CacheUse::Revalidated) => stats.revalidated += 1,
+                Some(CacheUse::Miss) => stats.misses += 1,
+                None => {}
+            }
+        }
+        stats
+    }
+    
+    /// Get failed requests count (status >= 400, or no response)
+    pub fn failed_count(&self) -> usize {
+        self.requests.iter().filter(|r| r.is_failed()).count()
+    }
+    
+    /// Which requests the network view lists
+    pub fn filter(&self) -> &NetworkFilter {
+        &self.filter
+    }
+    
+    /// Change which requests the network view lists
+    pub fn set_filter(&mut self, filter: NetworkFilter) {
+        self.filter = filter;
+    }
+    
+    /// Requests passing the filter, with their indices in `requests()`
+    pub fn filtered_requests(&self) -> Vec<(usize, &NetworkRequest)> {
+        self.requests.iter().enumerate().filter(|(_, r)| self.filter.matches(r)).collect()
+    }
+    
+    /// The requests passing the filter as an HTTP Archive (HAR 1.2) for sharing
+    pub fn export_har(&self) -> String {
+        let requests = self.filtered_requests().into_iter().map(|(_, request)| request);
+        serde_json::to_string_pretty(&har::har_log(requests)).unwrap_or_default()
+    }
+    
+    /// Clear all requests
+    pub fn clear(&mut self) {
+        self.requests.clear();
+    }
+}
+
+impl Default for NetworkTab {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_devtools_creation() {
+        let devtools = DevTools::new();
+        assert!(!devtools.is_open);
+        assert_eq!(devtools.active_tab, DevToolsTab::Console);
+    }
+    
+    #[test]
+    fn test_devtools_toggle() {
+        let mut devtools = DevTools::new();
+        devtools.toggle();
+        assert!(devtools.is_open);
+        devtools.toggle();
+        assert!(!devtools.is_open);
+    }
+    
+    #[test]
+    fn test_console_logging() {
+        let mut console = Console::new();
+        console.log("Test message".to_string());
+        console.error("Error message".to_string());
+        
+        assert_eq!(console.count(), 2);
+        assert_eq!(console.error_count(), 1);
+        assert_eq!(console.warning_count(), 0);
+    }
+    
+    #[test]
+    fn test_console_clear() {
+        let mut console = Console::new();
+        console.log("Message 1".to_string());
+        console.log("Message 2".to_string());
+        assert_eq!(console.count(), 2);
+        
+        console.clear();
+        assert_eq!(console.count(), 0);
+    }
+    
+    #[test]
+    fn test_console_filter_by_type() {
+        let mut console = Console::new();
+        console.log("Log 1".to_string());
+        console.error("Error 1".to_string());
+        console.warn("Warn 1".to_string());
+        console.error("Error 2".to_string());
+        
+        let errors = console.messages_by_type(ConsoleMessageType::Error);
+        assert_eq!(errors.len(), 2);
+        
+        let warnings = console.messages_by_type(ConsoleMessageType::Warn);
+        assert_eq!(warnings.len(), 1);
+    }
+    
+    #[test]
+    fn test_dom_inspector_selection() {
+        let mut inspector = DomInspector::new();
+        inspector.select_node(vec![0, 1, 2]);
+        assert_eq!(inspector.selected_path(), &[0, 1, 2]);
+    }
+    
+    #[test]
+    fn test_dom_inspector_expansion() {
+        let mut inspector = DomInspector::new();
+        let path = vec![0, 1];
+        
+        assert!(!inspector.is_expanded(&path));
+        inspector.toggle_node(path.clone());
+        assert!(inspector.is_expanded(&path));
+        inspector.toggle_node(path.clone());
+        assert!(!inspector.is_expanded(&path));
+    }
+    
+    #[test]
+    fn test_dom_inspector_collapse_all() {
+        let mut inspector = DomInspector::new();
+        inspector.toggle_node(vec![0]);
+        inspector.toggle_node(vec![1]);
+        inspector.collapse_all();
+        
+        // Only root should be expanded
+        assert_eq!(inspector.expanded_nodes().len(), 1);
+        assert_eq!(&inspector.expanded_nodes()[0], &Vec::<usize>::new());
+    }
+    
+    #[test]
+    fn test_inspect_element() {
+        use crate::css::CssParser;
+        use crate::html::HtmlParser;
+        use crate::layout::layout_tree;
+        use crate::style::style_tree;
+        
+        let document = HtmlParser::parse("<html><body><div id=\"a\" class=\"x y\"><p>Hi</p></div></body></html>");
+        let stylesheet = CssParser::parse("div { padding: 10px; } p { height: 20px; }");
+        let styled = style_tree(&document, &stylesheet);
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+        let layout = layout_tree(&styled, viewport);
+        
+        // The padding belongs to the div, the text to its paragraph
+        let div = inspect_element(&document, &layout, 5.0, 5.0).unwrap();
+        assert_eq!(div.label, "div#a.x.y");
+        assert_eq!(div.path, vec![1, 0]);
+        assert_eq!(div.dimensions.padding.left, 10.0);
+        let p = inspect_element(&document, &layout, 15.0, 15.0).unwrap();
+        assert_eq!((p.label.as_str(), p.path.as_slice()), ("p", &[1, 0, 0][..]));
+        
+        let mut inspector = DomInspector::new();
+        inspector.reveal(p.path.clone());
+        assert_eq!(inspector.selected_path(), &[1, 0, 0]);
+        assert!(inspector.is_expanded(&[]) && inspector.is_expanded(&[1]) && inspector.is_expanded(&[1, 0]));
+        assert!(!inspector.is_expanded(&[1, 0, 0]));
+    }
+    
+    #[test]
+    fn test_network_tab_logging() {
+        let mut network = NetworkTab::new();
+        let url = Url::parse("https://example.com").unwrap();
+        
+        let idx = network.log_request(url.clone(), "GET".to_string(), NetworkRequestType::Document);
+        assert_eq!(network.count(), 1);
+        
+        network.complete_request(idx, 200, 1024, Some("text/html".to_string()));
+        assert_eq!(network.requests()[0].status, Some(200));
+        assert_eq!(network.requests()[0].size, Some(1024));
+    }
+    
+    #[test]
+    fn test_network_tab_total_size() {
+        let mut network = NetworkTab::new();
+        
+        let url1 = Url::parse("https://example.com/page1").unwrap();
+        let url2 = Url::parse("https://example.com/page2").unwrap();
+        
+        let idx1 = network.log_request(url1, "GET".to_string(), NetworkRequestType::Document);
+        let idx2 = network.log_request(url2, "GET".to_string(), NetworkRequestType::Stylesheet);
+        
+        network.complete_request(idx1, 200, 1000, None);
+        network.complete_request(idx2, 200, 500, None);
+        
+        assert_eq!(network.total_size(), 1500);
+    }
+    
+    #[test]
+    fn test_network_tab_failed_count() {
+        let mut network = NetworkTab::new();
+        
+        let url1 = Url::parse("https://example.com/ok").unwrap();
+        let url2 = Url::parse("https://example.com/notfound").unwrap();
+        
+        let idx1 = network.log_request(url1, "GET".to_string(), NetworkRequestType::Document);
+        let idx2 = network.log_request(url2, "GET".to_string(), NetworkRequestType::Document);
+        
+        network.complete_request(idx1, 200, 1000, None);
+        network.complete_request(idx2, 404, 100, None);
+        
+        assert_eq!(network.failed_count(), 1);
+    }
+    
+    #[test]
+    fn test_network_tab_filter_by_type() {
+        let mut network = NetworkTab::new();
+        
+        let url1 = Url::parse("https://example.com/page").unwrap();
+        let url2 = Url::parse("https://example.com/style.css").unwrap();
+        let url3 = Url::parse("https://example.com/script.js").unwrap();
+        
+        network.log_request(url1, "GET".to_string(), NetworkRequestType::Document);
+        network.log_request(url2, "GET".to_string(), NetworkRequestType::Stylesheet);
+        network.log_request(url3, "GET".to_string(), NetworkRequestType::Script);
+        
+        let stylesheets = network.requests_by_type(NetworkRequestType::Stylesheet);
+        assert_eq!(stylesheets.len(), 1);
+    }
+    
+    #[test]
+    fn test_network_filter() {
+        let mut network = NetworkTab::new();
+        let log = |network: &mut NetworkTab, url: &str, request_type| {
+            network.log_request(Url::parse(url).unwrap(), "GET".to_string(), request_type)
+        };
+        let page = log(&mut network, "https://example.com/", NetworkRequestType::Document);
+        let style = log(&mut network, "https://cdn.example.com/site.css", NetworkRequestType::Stylesheet);
+        let api = log(&mut network, "https://api.other.org/data", NetworkRequestType::Fetch);
+        log(&mut network, "https://notexample.com/x.js", NetworkRequestType::Script);
+        network.complete_request(page, 200, 100, None);
+        network.complete_request(style, 404, 0, None);
+        network.complete_request(api, 0, 0, None);
+
+        let listed = |network: &NetworkTab| -> Vec<usize> {
+            network.filtered_requests().into_iter().map(|(i, _)| i).collect()
+        };
+        assert_eq!(listed(&network), [0, 1, 2, 3]);
+        network.set_filter(NetworkFilter { status: StatusFilter::Failed, ..Default::default() });
+        assert_eq!(listed(&network), [1, 2]);
+        network.set_filter(NetworkFilter { status: StatusFilter::Pending, ..Default::default() });
+        assert_eq!(listed(&network), [3]);
+        network.set_filter(NetworkFilter { domain: Some("Example.com".to_string()), ..Default::default() });
+        assert_eq!(listed(&network), [0, 1]);
+        let types = vec![NetworkRequestType::XHR, NetworkRequestType::Fetch, NetworkRequestType::Document];
+        network.set_filter(NetworkFilter { types, status: StatusFilter::Successful, domain: None });
+        assert_eq!(listed(&network), [0]);
+        assert!(!network.filter().is_empty());
+        assert_eq!(network.failed_count(), 2);
+    }
+
+    #[test]
+    fn test_captured_bodies() {
+        // A character cut in half at the end is left out of the text
+        let body = CapturedBody::capture(format!("a{}", "é".repeat(MAX_CAPTURED_BODY)).as_bytes());
+        assert!(body.is_truncated());
+        assert_eq!(body.data.len(), MAX_CAPTURED_BODY);
+        assert_eq!(body.text().unwrap().len(), MAX_CAPTURED_BODY - 1);
+        assert!(CapturedBody::capture(&[0xff, 0xfe]).text().is_none());
+
+        let mut network = NetworkTab::new();
+        let url = Url::parse("https://example.com/").unwrap();
+        let idx = network.log_request(url, "POST".to_string(), NetworkRequestType::Fetch);
+        network.set_request_body(idx, b"name=value");
+        let transfer = Transfer {
+            status: 201,
+            request_headers: vec![("accept".to_string(), "*/*".to_string())],
+            response_headers: vec![("server".to_string(), "test".to_string())],
+            timing: RequestTiming { wait_ms: 12.0, receive_ms: 3.0 },
+            ..Transfer::default()
+        };
+        network.complete_request(idx, 201, 8, Some("text/plain".to_string()));
+        network.record_transfer(idx, &transfer, b"created\n");
+        let lines = network.requests()[idx].detail_lines();
+        let expected = [
+            "Response Headers (1)",
+            "  server: test",
+            "  accept: */*",
+            "  name=value",
+            "Response (8 B)",
+            "  created",
+            "  Waiting for response: 12.0 ms",
+        ];
+        for expected in expected {
+            assert!(lines.contains(&expected.to_string()), "missing {:?} in {:?}", expected, lines);
+        }
+    }
+
+    #[test]
+    fn test_network_tab_clear() {
+        let mut network = NetworkTab::new();
+        let url = Url::parse("https://example.com").unwrap();
+        
+        network.log_request(url, "GET".to_string(), NetworkRequestType::Document);
+        assert_eq!(network.count(), 1);
+        
+        network.clear();
+        assert_eq!(network.count(), 0);
+    }
+    
+    #[test]
+    fn test_network_tab_websocket_frames() {
+        let mut network = NetworkTab::new();
+        let idx = network.log_websocket(Url::parse("wss://example.com/chat").unwrap());
+        network.websocket_opened(idx, Some("chat".to_string()));
+        
+        let frame = |direction, opcode, payload: &[u8], length| FrameInfo {
+            direction,
+            opcode,
+            fin: true,
+            length,
+            preview: payload.to_vec(),
+        };
+        network.log_websocket_frames(idx, &[
+            frame(FrameDirection::Sent, 0x1, b"hello", 5),
+            frame(FrameDirection::Received, 0x2, &[0xde, 0xad, 0xbe, 0xef], 4),
+            frame(FrameDirection::Received, 0x8, b"\x03\xe8bye", 5),
+        ]);
+        network.websocket_closed(idx, Some(1000));
+        
+        let ws = network.websocket(idx).unwrap();
+        assert_eq!(ws.bytes_sent, 5);
+        assert_eq!(ws.bytes_received, 9);
+        let previews: Vec<(&str, &str)> = ws.frames.iter().map(|f| (f.opcode_name(), f.preview.as_str())).collect();
+        assert_eq!(previews, vec![("text", "hello"), ("binary", "de ad be ef"), ("close", "1000 bye")]);
+        
+        let request = &network.requests()[idx];
+        assert_eq!(request.status, Some(101));
+        let lines = request.detail_lines();
+        assert!(lines.contains(&"Protocol: chat".to_string()));
+        assert!(lines.contains(&"Closed: 1000".to_string()));
+        assert!(lines.iter().any(|l| l.starts_with("↑ text") && l.ends_with("hello")));
+        
+        // Only the most recent frames are kept
+        let many = vec![frame(FrameDirection::Sent, 0x9, b"", 0); MAX_WEBSOCKET_FRAMES];
+        network.log_websocket_frames(idx, &many);
+        let ws = network.websocket(idx).unwrap();
+        assert_eq!(ws.frames.len(), MAX_WEBSOCKET_FRAMES);
+        assert_eq!(ws.dropped_frames, 3);
+        let page = network.log_request(Url::parse("https://example.com/").unwrap(), "GET".to_string(), NetworkRequestType::Document);
+        assert!(network.websocket(page).is_none());
+    }
+}
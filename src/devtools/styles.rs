@@ -0,0 +1,131 @@
+// Styles pane model - matched rules and computed values of an element
+//
+// Rules are listed the way the cascade applies them, the winning rule
+// first. A declaration that another one of the same property beats is
+// marked overridden; switched off declarations never win.
+
+use std::collections::HashMap;
+
+use crate::css::{specificity, Origin, Specificity, Stylesheet};
+use crate::dom::Node;
+use crate::style::{matches_in, StyledNode};
+
+/// A stylesheet rule that matches the inspected element
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchedRule {
+    /// Index of the rule in the stylesheet
+    pub index: usize,
+    /// The rule's selector that matched
+    pub selector: String,
+    pub specificity: Specificity,
+    /// Whether the browser, the user or the page wrote the rule
+    pub origin: Origin,
+    pub declarations: Vec<MatchedDeclaration>,
+}
+
+/// A declaration of a matched rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchedDeclaration {
+    pub name: String,
+    pub value: String,
+    pub enabled: bool,
+    /// Another declaration of the property wins the cascade
+    pub overridden: bool,
+}
+
+/// Rules matching an element, the one applied last first
+///
+/// `ancestors` are the element's ancestors from the root down to its parent.
+pub fn matched_rules(element: &Node, ancestors: &[&Node], stylesheet: &Stylesheet) -> Vec<MatchedRule> {
+    let mut rules: Vec<MatchedRule> = stylesheet
+        .rules
+        .iter()
+        .enumerate()
+        .filter_map(|(index, rule)| {
+            let selector = rule.selectors.iter().find(|selector| matches_in(element, ancestors, selector))?;
+            Some(MatchedRule {
+                index,
+                selector: selector.to_string(),
+                specificity: specificity(selector),
+                origin: rule.origin,
+                declarations: rule
+                    .declarations
+                    .iter()
+                    .map(|declaration| MatchedDeclaration {
+                        name: declaration.name.clone(),
+                        value: declaration.value.to_string(),
+                        enabled: declaration.enabled,
+                        overridden: false,
+                    })
+                    .collect(),
+            })
+        })
+        .collect();
+    // Same order as the cascade: by origin, specificity, then source order
+    rules.sort_by_key(|rule| (rule.origin, rule.specificity));
+
+    let mut winners = HashMap::new();
+    for (i, rule) in rules.iter().enumerate() {
+        for (j, declaration) in rule.declarations.iter().enumerate().filter(|(_, d)| d.enabled) {
+            winners.insert(declaration.name.clone(), (i, j));
+        }
+    }
+    for (i, rule) in rules.iter_mut().enumerate() {
+        for (j, declaration) in rule.declarations.iter_mut().enumerate() {
+            declaration.overridden = declaration.enabled && winners.get(&declaration.name) != Some(&(i, j));
+        }
+    }
+    rules.reverse();
+    rules
+}
+
+/// Values applied to a styled node, inherited ones included, by property name
+pub fn computed_style(styled: &StyledNode) -> Vec<(String, String)> {
+    let mut values: Vec<(String, String)> =
+        styled.specified_values.iter().map(|(name, value)| (name.clone(), value.to_string())).collect();
+    values.sort();
+    values
+}
+
+/// The styled node at a `DomInspector` path
+pub fn styled_node_at_path<'a, 'b>(root: &'b StyledNode<'a>, path: &[usize]) -> Option<&'b StyledNode<'a>> {
+    path.iter().try_fold(root, |node, &i| node.children.get(i))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::CssParser;
+    use crate::html::HtmlParser;
+    use crate::style::style_tree;
+
+    #[test]
+    fn test_matched_rules_in_cascade_order() {
+        let document = HtmlParser::parse("<html><body><p id=\"a\" class=\"note\">Hi</p></body></html>");
+        let mut stylesheet = CssParser::parse(
+            "p { color: red; margin: 4px; } #a { color: blue; } \
+             .note { margin: 8px; padding: 2px; } h1 { color: red; }",
+        );
+        let p = &document.children[1].children[0];
+        let rules = matched_rules(p, &document.ancestors(&[1, 0]), &stylesheet);
+        let selectors: Vec<(&str, String)> =
+            rules.iter().map(|rule| (rule.selector.as_str(), rule.specificity.to_string())).collect();
+        let expected = [("#a", "(1,0,0)"), (".note", "(0,1,0)"), ("p", "(0,0,1)")].map(|(s, n)| (s, n.to_string()));
+        assert_eq!(selectors, expected);
+        let overridden: Vec<bool> = rules[2].declarations.iter().map(|d| d.overridden).collect();
+        assert_eq!(overridden, [true, true]);
+        assert!(!rules[0].declarations[0].overridden);
+
+        // Switching off the winner lets the next rule through
+        stylesheet.declaration_mut(1, 0).unwrap().enabled = false;
+        let rules = matched_rules(p, &document.ancestors(&[1, 0]), &stylesheet);
+        assert!(!rules[0].declarations[0].enabled && !rules[0].declarations[0].overridden);
+        assert!(!rules[2].declarations[0].overridden);
+
+        let styled = style_tree(&document, &stylesheet);
+        let computed = computed_style(styled_node_at_path(&styled, &[1, 0]).unwrap());
+        assert!(computed.contains(&("color".to_string(), "red".to_string())));
+        assert!(computed.contains(&("margin".to_string(), "8px".to_string())));
+        assert!(styled_node_at_path(&styled, &[1, 5]).is_none());
+    }
+}
@@ -1,12 +1,19 @@
+use crate::animation::element_path;
 use crate::css::{Color, Value};
-use crate::layout::{LayoutBox, Rect};
+use crate::dom::Node;
+use crate::layout::overflow::{BoxScrolls, Overflow, ScrollContainer};
+use crate::layout::positioning;
+use crate::layout::{Dimensions, LayoutBox, Rect};
+use crate::text_style::{TextDecoration, TextStyle, TextTransform};
+use crate::transform::{self, Matrix4, Transform};
+use serde::{Deserialize, Serialize};
 use url::Url;
 
 /// A display list is a list of graphics operations to perform
 pub type DisplayList = Vec<DisplayCommand>;
 
 /// A single graphics operation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum DisplayCommand {
     /// Fill a solid rectangle
     SolidRect {
@@ -27,23 +34,213 @@ pub enum DisplayCommand {
         color: Color,
         font_family: String,
         font_size: f32,
+        /// Spacing and decoration lines
+        style: TextStyle,
     },
     /// Draw an image
     Image {
         url: Url,
         rect: Rect,
     },
+    /// Tint a rectangle over existing content (e.g., find-in-page matches)
+    Highlight {
+        color: Color,
+        rect: Rect,
+    },
+    /// Clip the commands up to the matching `PopClip` to a rectangle, on
+    /// top of any clip already in force
+    PushClip {
+        rect: Rect,
+    },
+    /// End the clip of the matching `PushClip`, which has the same rectangle
+    PopClip {
+        rect: Rect,
+    },
+    /// Begin the commands of a fixed box, up to the matching `PopFixed`,
+    /// which stay where they are in the window as the page scrolls under them
+    PushFixed {
+        rect: Rect,
+    },
+    /// End the commands of the matching `PushFixed`, which has the same rectangle
+    PopFixed {
+        rect: Rect,
+    },
+}
+
+impl DisplayCommand {
+    /// Area the command paints
+    pub fn rect(&self) -> Rect {
+        match self {
+            DisplayCommand::SolidRect { rect, .. } => *rect,
+            DisplayCommand::Border { rect, .. } => *rect,
+            DisplayCommand::Text { rect, .. } => *rect,
+            DisplayCommand::Image { rect, .. } => *rect,
+            DisplayCommand::Highlight { rect, .. } => *rect,
+            DisplayCommand::PushClip { rect } | DisplayCommand::PopClip { rect } => *rect,
+            DisplayCommand::PushFixed { rect } | DisplayCommand::PopFixed { rect } => *rect,
+        }
+    }
+
+    /// Whether the command only marks where a clip or fixed box begins or ends
+    pub fn is_marker(&self) -> bool {
+        matches!(
+            self,
+            DisplayCommand::PushClip { .. }
+                | DisplayCommand::PopClip { .. }
+                | DisplayCommand::PushFixed { .. }
+                | DisplayCommand::PopFixed { .. }
+        )
+    }
+
+    /// Move the command by `dx`, `dy`, as when the page scrolls under it
+    pub fn translate(&mut self, dx: f32, dy: f32) {
+        let (DisplayCommand::SolidRect { rect, .. }
+        | DisplayCommand::Border { rect, .. }
+        | DisplayCommand::Text { rect, .. }
+        | DisplayCommand::Image { rect, .. }
+        | DisplayCommand::Highlight { rect, .. }
+        | DisplayCommand::PushClip { rect }
+        | DisplayCommand::PopClip { rect }
+        | DisplayCommand::PushFixed { rect }
+        | DisplayCommand::PopFixed { rect }) = self;
+        rect.x += dx;
+        rect.y += dy;
+    }
+}
+
+/// Damage rectangles past which they are merged into one
+const MAX_DAMAGE_RECTS: usize = 16;
+
+/// Areas that must be repainted to go from `old` to `new`
+///
+/// Commands are compared by position; both the old and the new area of a
+/// changed command are damaged.
+pub fn damage(old: &[DisplayCommand], new: &[DisplayCommand]) -> Vec<Rect> {
+    let mut rects = Vec::new();
+    for i in 0..old.len().max(new.len()) {
+        let (before, after) = (old.get(i), new.get(i));
+        if before == after {
+            continue;
+        }
+        for command in [before, after].into_iter().flatten() {
+            let rect = command.rect();
+            if rect.width > 0.0 && rect.height > 0.0 && !rects.contains(&rect) {
+                rects.push(rect);
+            }
+        }
+    }
+    if rects.len() > MAX_DAMAGE_RECTS {
+        return vec![bounding_rect(&rects)];
+    }
+    rects
+}
+
+/// Smallest rectangle containing all of `rects`
+fn bounding_rect(rects: &[Rect]) -> Rect {
+    let left = rects.iter().map(|r| r.x).fold(f32::INFINITY, f32::min);
+    let top = rects.iter().map(|r| r.y).fold(f32::INFINITY, f32::min);
+    let right = rects.iter().map(|r| r.x + r.width).fold(f32::NEG_INFINITY, f32::max);
+    let bottom = rects.iter().map(|r| r.y + r.height).fold(f32::NEG_INFINITY, f32::max);
+    Rect { x: left, y: top, width: right - left, height: bottom - top }
 }
 
 /// Build a display list from a layout tree
+///
+/// Images whose `src` is a relative URL are left out; see `build_display_list_with_base`.
 pub fn build_display_list(layout_root: &LayoutBox) -> DisplayList {
+    build_display_list_scrolled(layout_root, None, &BoxScrolls::default())
+}
+
+/// Build a display list from a layout tree, resolving image URLs against the document's base URL
+pub fn build_display_list_with_base(layout_root: &LayoutBox, base_url: &Url) -> DisplayList {
+    build_display_list_scrolled(layout_root, Some(base_url), &BoxScrolls::default())
+}
+
+/// Build a display list from a layout tree, with each scrollable box's
+/// content moved by how far `scrolls` has it scrolled
+pub fn build_display_list_scrolled(layout_root: &LayoutBox, base_url: Option<&Url>, scrolls: &BoxScrolls) -> DisplayList {
+    let page = Page {
+        base_url,
+        document: layout_root.get_styled_node().map(|styled| styled.node),
+        scrolls,
+    };
     let mut list = Vec::new();
-    render_layout_box(&mut list, layout_root);
+    render_layout_box(&mut list, layout_root, &[], &page);
     list
 }
 
+/// What every box of a page is painted with
+struct Page<'a> {
+    /// What relative image URLs are resolved against
+    base_url: Option<&'a Url>,
+    /// Node scroll offsets are keyed by paths from
+    document: Option<&'a Node>,
+    scrolls: &'a BoxScrolls,
+}
+
 /// Render a layout box and its descendants into the display list
-fn render_layout_box(list: &mut DisplayList, layout_box: &LayoutBox) {
+///
+/// A box with a `transform` has what it and its descendants paint mapped
+/// through it. Commands only paint axis-aligned rectangles, so a rotated or
+/// skewed box paints the rectangle holding it. `decorations` are the lines
+/// ancestors draw through their text, which the box's text gets too. What a
+/// fixed box paints is marked, so that it is not scrolled with the page.
+fn render_layout_box(list: &mut DisplayList, layout_box: &LayoutBox, decorations: &[TextDecoration], page: &Page) {
+    let fixed = positioning::is_fixed(layout_box).then(|| layout_box.dimensions.margin_box());
+    if let Some(rect) = fixed {
+        list.push(DisplayCommand::PushFixed { rect });
+    }
+    let start = list.len();
+    render_box_contents(list, layout_box, decorations, page);
+    if let Some(matrix) = box_transform(layout_box) {
+        for command in &mut list[start..] {
+            transform_command(command, &matrix);
+        }
+    }
+    if let Some(rect) = fixed {
+        list.push(DisplayCommand::PopFixed { rect });
+    }
+}
+
+/// The matrix a box paints with, about its `transform-origin`
+fn box_transform(layout_box: &LayoutBox) -> Option<Matrix4> {
+    let styled = layout_box.get_styled_node()?;
+    let transform = Transform::of(styled).filter(|transform| !transform.is_identity())?;
+    let origin = transform::element_origin(styled, layout_box.dimensions.border_box());
+    Some(transform.to_matrix_about(origin))
+}
+
+/// Move a command to where a transform puts it, scaling border widths and
+/// font sizes along with the box
+fn transform_command(command: &mut DisplayCommand, matrix: &Matrix4) {
+    let m = &matrix.m;
+    let scale_x = m[0][0].hypot(m[1][0]);
+    let scale_y = m[0][1].hypot(m[1][1]);
+    match command {
+        DisplayCommand::SolidRect { rect, .. }
+        | DisplayCommand::Image { rect, .. }
+        | DisplayCommand::Highlight { rect, .. }
+        | DisplayCommand::PushClip { rect }
+        | DisplayCommand::PopClip { rect }
+        | DisplayCommand::PushFixed { rect }
+        | DisplayCommand::PopFixed { rect } => *rect = matrix.map_rect(*rect),
+        DisplayCommand::Border { rect, widths, .. } => {
+            *rect = matrix.map_rect(*rect);
+            *widths = (widths.0 * scale_x, widths.1 * scale_x, widths.2 * scale_y, widths.3 * scale_y);
+        }
+        DisplayCommand::Text { rect, font_size, style, .. } => {
+            *rect = matrix.map_rect(*rect);
+            *font_size *= scale_y;
+            style.scale(scale_y);
+        }
+    }
+}
+
+/// Render a layout box's own painting, then its children
+///
+/// A box that clips its overflow clips its children to its padding box,
+/// moved by how far the box is scrolled, with its scrollbar thumbs on top.
+fn render_box_contents(list: &mut DisplayList, layout_box: &LayoutBox, decorations: &[TextDecoration], page: &Page) {
     // Render the box's background first
     render_background(list, layout_box);
     
@@ -51,17 +248,51 @@ fn render_layout_box(list: &mut DisplayList, layout_box: &LayoutBox) {
     render_borders(list, layout_box);
     
     // Render images if this is an img element
-    render_image(list, layout_box);
+    render_image(list, layout_box, page.base_url);
     
     // Render text content if present
-    render_text(list, layout_box);
+    render_text(list, layout_box, decorations);
     
-    // Recursively render children
+    // Recursively render children, under this box's decorations as well as its ancestors'
+    let mut decorations = decorations.to_vec();
+    if let Some(styled) = layout_box.get_styled_node() {
+        decorations.extend(TextDecoration::of(styled));
+    }
+    let overflow = Overflow::of(layout_box);
+    let clip = layout_box.dimensions.padding_box();
+    if overflow.clips() {
+        list.push(DisplayCommand::PushClip { rect: clip });
+    }
+    let start = list.len();
     for child in &layout_box.children {
-        render_layout_box(list, child);
+        render_layout_box(list, child, &decorations, page);
+    }
+    if overflow.scrolls() {
+        render_scrolled(list, start, layout_box, page);
+    }
+    if overflow.clips() {
+        list.push(DisplayCommand::PopClip { rect: clip });
     }
 }
 
+/// Move what a scrollable box's children painted from `start` on by how
+/// far it is scrolled, and add its scrollbar thumbs
+fn render_scrolled(list: &mut DisplayList, start: usize, layout_box: &LayoutBox, page: &Page) {
+    let element = page
+        .document
+        .zip(layout_box.get_styled_node())
+        .and_then(|(document, styled)| element_path(document, styled.node));
+    let Some(container) = element.and_then(|element| ScrollContainer::of(layout_box, element)) else {
+        return;
+    };
+    let (x, y) = page.scrolls.offset(&container.element);
+    for command in &mut list[start..] {
+        command.translate(-x, -y);
+    }
+    let thumbs = container.scrollbar_thumbs((x, y));
+    list.extend(thumbs.into_iter().map(|rect| DisplayCommand::SolidRect { color: SCROLLBAR_THUMB, rect }));
+}
+
 /// Render the background of a layout box
 fn render_background(list: &mut DisplayList, layout_box: &LayoutBox) {
     // Get the background color from the styled node
@@ -91,14 +322,18 @@ fn render_borders(list: &mut DisplayList, layout_box: &LayoutBox) {
 }
 
 /// Render image element
-fn render_image(list: &mut DisplayList, layout_box: &LayoutBox) {
+fn render_image(list: &mut DisplayList, layout_box: &LayoutBox, base_url: Option<&Url>) {
     if let Some(style_node) = layout_box.get_styled_node() {
         if let Some(elem) = style_node.node.element_data() {
             // Check if this is an img element
             if elem.tag_name.to_lowercase() == "img" {
                 if let Some(src) = elem.attributes.get("src") {
-                    // Parse URL from src attribute
-                    if let Ok(url) = Url::parse(src) {
+                    // Parse URL from src attribute, relative to the base URL if there is one
+                    let url = match base_url {
+                        Some(base) => base.join(src),
+                        None => Url::parse(src),
+                    };
+                    if let Ok(url) = url {
                         list.push(DisplayCommand::Image {
                             url,
                             rect: layout_box.dimensions.content,
@@ -111,7 +346,7 @@ fn render_image(list: &mut DisplayList, layout_box: &LayoutBox) {
 }
 
 /// Render text content of a layout box
-fn render_text(list: &mut DisplayList, layout_box: &LayoutBox) {
+fn render_text(list: &mut DisplayList, layout_box: &LayoutBox, decorations: &[TextDecoration]) {
     // Get the styled node
     if let Some(style_node) = layout_box.get_styled_node() {
         // Check if this is a text node
@@ -123,10 +358,7 @@ fn render_text(list: &mut DisplayList, layout_box: &LayoutBox) {
                 // Get font properties
                 let font_family = style_node
                     .value("font-family")
-                    .and_then(|v| match v {
-                        Value::Keyword(s) => Some(s.clone()),
-                        _ => None,
-                    })
+                    .and_then(Value::first_font_family)
                     .unwrap_or_else(|| "sans-serif".to_string());
                 
                 let font_size = style_node
@@ -137,13 +369,29 @@ fn render_text(list: &mut DisplayList, layout_box: &LayoutBox) {
                     })
                     .unwrap_or(16.0); // Default to 16px
                 
-                list.push(DisplayCommand::Text {
-                    text: text.to_string(),
-                    rect: layout_box.dimensions.content,
-                    color,
-                    font_family,
-                    font_size,
-                });
+                let style = TextStyle::of(style_node, decorations);
+                if layout_box.fragments.is_empty() {
+                    list.push(DisplayCommand::Text {
+                        text: TextTransform::of(style_node).apply(text),
+                        rect: layout_box.dimensions.content,
+                        color,
+                        font_family,
+                        font_size,
+                        style,
+                    });
+                    return;
+                }
+                // One command per line the text was broken onto
+                for fragment in &layout_box.fragments {
+                    list.push(DisplayCommand::Text {
+                        text: fragment.text.clone(),
+                        rect: fragment.rect,
+                        color,
+                        font_family: font_family.clone(),
+                        font_size,
+                        style: style.clone(),
+                    });
+                }
             }
         }
     }
@@ -160,6 +408,55 @@ fn get_color(layout_box: &LayoutBox, property: &str) -> Option<Color> {
         })
 }
 
+/// Colour of the thumbs drawn over scrollable boxes
+const SCROLLBAR_THUMB: Color = Color { r: 0, g: 0, b: 0, a: 110 };
+
+/// Box-model overlay tints used by the element inspector
+const MARGIN_HIGHLIGHT: Color = Color { r: 246, g: 178, b: 107, a: 102 };
+const BORDER_HIGHLIGHT: Color = Color { r: 255, g: 229, b: 153, a: 102 };
+const PADDING_HIGHLIGHT: Color = Color { r: 147, g: 196, b: 125, a: 140 };
+const CONTENT_HIGHLIGHT: Color = Color { r: 111, g: 168, b: 220, a: 166 };
+
+/// Inspector overlay for a box: its margin, border, padding and content
+/// areas tinted orange, yellow, green and blue
+///
+/// Each edge area is tinted as strips around the area inside it, so the
+/// tints do not stack. `offset_y` is subtracted from positions (page scroll).
+pub fn box_model_highlight(dimensions: &Dimensions, offset_y: f32) -> DisplayList {
+    let shift = |rect: Rect| Rect { y: rect.y - offset_y, ..rect };
+    let content = shift(dimensions.content);
+    let padding = shift(dimensions.padding_box());
+    let border = shift(dimensions.border_box());
+    let margin = shift(dimensions.margin_box());
+
+    let mut list = Vec::new();
+    push_ring(&mut list, margin, border, MARGIN_HIGHLIGHT);
+    push_ring(&mut list, border, padding, BORDER_HIGHLIGHT);
+    push_ring(&mut list, padding, content, PADDING_HIGHLIGHT);
+    list.push(DisplayCommand::Highlight { color: CONTENT_HIGHLIGHT, rect: content });
+    list.retain(|command| command.rect().width > 0.0 && command.rect().height > 0.0);
+    list
+}
+
+/// Highlight the part of `outer` around `inner`: top and bottom strips,
+/// then the left and right ones between them
+fn push_ring(list: &mut DisplayList, outer: Rect, inner: Rect, color: Color) {
+    let top = inner.y - outer.y;
+    let bottom = outer.y + outer.height - inner.y - inner.height;
+    let strips = [
+        Rect { height: top, ..outer },
+        Rect { y: inner.y + inner.height, height: bottom, ..outer },
+        Rect { y: inner.y, width: inner.x - outer.x, height: inner.height, ..outer },
+        Rect {
+            x: inner.x + inner.width,
+            y: inner.y,
+            width: outer.x + outer.width - inner.x - inner.width,
+            height: inner.height,
+        },
+    ];
+    list.extend(strips.into_iter().map(|rect| DisplayCommand::Highlight { color, rect }));
+}
+
 /// Optimize display list by removing occluded items
 /// 
 /// This is a simple optimization that removes items completely covered by opaque items
@@ -170,18 +467,38 @@ pub fn optimize_display_list(list: DisplayList) -> DisplayList {
 }
 
 /// Cull display items outside the viewport
+///
+/// Clips and fixed box markers are kept whatever their rectangle, so
+/// every one still in the list is ended.
 pub fn cull_display_list(list: DisplayList, viewport: Rect) -> DisplayList {
+    list.into_iter().filter(|item| item.is_marker() || rectangles_intersect(&item.rect(), &viewport)).collect()
+}
+
+/// A page's display list as seen scrolled to `offset_x`, `offset_y`,
+/// culled to `viewport`, the visible part of the page
+///
+/// Commands move up and left by the scroll offset, except those of fixed
+/// boxes: these were laid out against the unscrolled viewport and stay
+/// where they are, culled to the window rather than the page.
+pub fn scroll_display_list(list: DisplayList, viewport: Rect, offset_x: f32, offset_y: f32) -> DisplayList {
+    let window = Rect { x: viewport.x - offset_x, y: viewport.y - offset_y, ..viewport };
+    let mut fixed_depth = 0usize;
     list.into_iter()
-        .filter(|item| {
-            let rect = match item {
-                DisplayCommand::SolidRect { rect, .. } => rect,
-                DisplayCommand::Border { rect, .. } => rect,
-                DisplayCommand::Text { rect, .. } => rect,
-                DisplayCommand::Image { rect, .. } => rect,
-            };
-            
-            // Check if rectangles intersect
-            rectangles_intersect(rect, &viewport)
+        .filter_map(|mut command| {
+            match command {
+                DisplayCommand::PushFixed { .. } => fixed_depth += 1,
+                DisplayCommand::PopFixed { .. } => fixed_depth = fixed_depth.saturating_sub(1),
+                _ => {}
+            }
+            let fixed = fixed_depth > 0 || matches!(command, DisplayCommand::PopFixed { .. });
+            let visible = if fixed { window } else { viewport };
+            if !command.is_marker() && !rectangles_intersect(&command.rect(), &visible) {
+                return None;
+            }
+            if !fixed {
+                command.translate(-offset_x, -offset_y);
+            }
+            Some(command)
         })
         .collect()
 }
@@ -200,7 +517,8 @@ mod tests {
     use crate::css::{CssParser, Unit};
     use crate::dom::Node;
     use crate::style::style_tree;
-    use crate::layout::{layout_tree, Dimensions};
+    use crate::layout::{layout_tree, EdgeSizes};
+    use crate::text_style::DecorationLine;
     use std::collections::HashMap;
 
     #[test]
@@ -278,4 +596,208 @@ mod tests {
         let has_image = display_list.iter().any(|cmd| matches!(cmd, DisplayCommand::Image { .. }));
         assert!(has_image);
     }
+
+    #[test]
+    fn test_relative_image_url_resolves_against_base() {
+        let mut attrs = HashMap::new();
+        attrs.insert("src".to_string(), "images/logo.png".to_string());
+        let node = Node::element("img".to_string(), attrs, vec![]);
+        let stylesheet = CssParser::parse("img { width: 10px; height: 10px; }");
+        let styled = style_tree(&node, &stylesheet);
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+        let layout = layout_tree(&styled, viewport);
+
+        assert!(build_display_list(&layout).is_empty());
+        let base = Url::parse("https://example.com/docs/page.html").unwrap();
+        let list = build_display_list_with_base(&layout, &base);
+        assert!(matches!(
+            &list[..],
+            [DisplayCommand::Image { url, .. }] if url.as_str() == "https://example.com/docs/images/logo.png"
+        ));
+    }
+    
+    #[test]
+    fn test_box_model_highlight() {
+        let dimensions = Dimensions {
+            content: Rect { x: 20.0, y: 120.0, width: 100.0, height: 50.0 },
+            padding: EdgeSizes { left: 5.0, ..EdgeSizes::default() },
+            border: EdgeSizes { top: 2.0, ..EdgeSizes::default() },
+            margin: EdgeSizes { bottom: 10.0, ..EdgeSizes::default() },
+        };
+
+        let list = box_model_highlight(&dimensions, 100.0);
+        let tinted = |color: Color| -> Vec<Rect> {
+            list.iter()
+                .filter_map(|command| match command {
+                    DisplayCommand::Highlight { color: c, rect } if *c == color => Some(*rect),
+                    _ => None,
+                })
+                .collect()
+        };
+        // Only edges with a size are tinted, each as its own strip
+        assert_eq!(tinted(CONTENT_HIGHLIGHT), vec![Rect { x: 20.0, y: 20.0, width: 100.0, height: 50.0 }]);
+        assert_eq!(tinted(PADDING_HIGHLIGHT), vec![Rect { x: 15.0, y: 20.0, width: 5.0, height: 50.0 }]);
+        assert_eq!(tinted(BORDER_HIGHLIGHT), vec![Rect { x: 15.0, y: 18.0, width: 105.0, height: 2.0 }]);
+        assert_eq!(tinted(MARGIN_HIGHLIGHT), vec![Rect { x: 15.0, y: 70.0, width: 105.0, height: 10.0 }]);
+    }
+
+    #[test]
+    fn test_transformed_box() {
+        let css = "div { background-color: #ff0000; border-color: #000000; border-width: 2px; width: 100px; \
+                   height: 50px; transform: translate(10px, 5px) scale(2); transform-origin: left top; }";
+        let stylesheet = CssParser::parse(css);
+        let node = Node::element("div".to_string(), HashMap::new(), vec![Node::text("Hi".to_string())]);
+        let styled = style_tree(&node, &stylesheet);
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+        let list = build_display_list(&layout_tree(&styled, viewport));
+        
+        // Scaled about the top left corner, then moved
+        let scaled = Rect { x: 10.0, y: 5.0, width: 208.0, height: 108.0 };
+        assert_eq!(list[0], DisplayCommand::SolidRect { color: Color::new(255, 0, 0, 255), rect: scaled });
+        assert!(matches!(list[1], DisplayCommand::Border { rect, widths: (4.0, 4.0, 4.0, 4.0), .. } if rect == scaled));
+        let DisplayCommand::Text { rect, font_size, .. } = &list[2] else {
+            panic!("expected text, got {:?}", list[2]);
+        };
+        assert_eq!(*font_size, 32.0);
+        assert!(rect.x >= scaled.x && rect.x + rect.width <= scaled.x + scaled.width);
+    }
+
+    #[test]
+    fn test_scrolled_box_clips_and_moves_children() {
+        use crate::layout::overflow::scroll_containers;
+
+        let css = "div { display: block; } #list { overflow: auto; height: 100px; padding: 5px; } \
+                   #item { height: 300px; background-color: #ff0000; }";
+        let stylesheet = CssParser::parse(css);
+        let id = |id: &str| HashMap::from([("id".to_string(), id.to_string())]);
+        let item = Node::element("div".to_string(), id("item"), vec![]);
+        let node = Node::element("div".to_string(), id("list"), vec![item]);
+        let styled = style_tree(&node, &stylesheet);
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+        let layout = layout_tree(&styled, viewport);
+
+        let mut scrolls = BoxScrolls::default();
+        assert!(scrolls.scroll_at(&scroll_containers(&layout, &scrolls), 10.0, 10.0, 0.0, 40.0));
+        let list = build_display_list_scrolled(&layout, None, &scrolls);
+
+        let clip = Rect { x: 0.0, y: 0.0, width: 800.0, height: 110.0 };
+        assert_eq!(list[0], DisplayCommand::PushClip { rect: clip });
+        assert!(matches!(list[1], DisplayCommand::SolidRect { rect, .. } if rect.y == 5.0 - 40.0));
+        // Thumb along the right edge, then the clip ends
+        assert!(matches!(list[2], DisplayCommand::SolidRect { color, rect } if color == SCROLLBAR_THUMB && rect.x > 790.0));
+        assert_eq!(list[3], DisplayCommand::PopClip { rect: clip });
+
+        // Culling keeps the clip even when it lies outside the viewport
+        let culled = cull_display_list(list, Rect { x: 0.0, y: 500.0, width: 800.0, height: 100.0 });
+        assert_eq!(culled.len(), 2);
+    }
+
+    #[test]
+    fn test_fixed_box_stays_put_when_scrolled() {
+        use crate::layout::positioning::pin_fixed_boxes;
+        use crate::ui::link_at;
+
+        let css = "div, a { display: block; } #page { height: 2000px; background-color: #ffffff; } \
+                   #bar { position: fixed; top: 0px; left: 0px; width: 800px; height: 40px; background-color: #000000; } \
+                   a { height: 40px; }";
+        let stylesheet = CssParser::parse(css);
+        let id = |id: &str| HashMap::from([("id".to_string(), id.to_string())]);
+        let link = Node::element("a".to_string(), HashMap::from([("href".to_string(), "/home".to_string())]), vec![]);
+        let bar = Node::element("div".to_string(), id("bar"), vec![link]);
+        let page = Node::element("div".to_string(), id("page"), vec![]);
+        let root = Node::element("div".to_string(), HashMap::new(), vec![page, bar]);
+        let styled = style_tree(&root, &stylesheet);
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+        viewport.content.height = 600.0;
+        let mut layout = layout_tree(&styled, viewport);
+        let list = build_display_list(&layout);
+        let bar_color = Color::new(0, 0, 0, 255);
+
+        // Scrolled well past where the bar was laid out, it is still drawn at the top
+        let seen = Rect { x: 0.0, y: 1000.0, width: 800.0, height: 600.0 };
+        let scrolled = scroll_display_list(list, seen, 0.0, 1000.0);
+        let bar_rect = scrolled.iter().find_map(|command| match command {
+            DisplayCommand::SolidRect { color, rect } if *color == bar_color => Some(*rect),
+            _ => None,
+        });
+        assert_eq!(bar_rect, Some(Rect { x: 0.0, y: 0.0, width: 800.0, height: 40.0 }));
+        // The page under it moves up by the scroll
+        assert!(scrolled
+            .iter()
+            .any(|command| matches!(command, DisplayCommand::SolidRect { rect, .. } if rect.y == -1000.0)));
+
+        // A click at the top of the window lands on the bar's link
+        assert_eq!(link_at(&layout, 10.0, 1020.0), None);
+        pin_fixed_boxes(&mut layout, 0.0, 1000.0);
+        assert_eq!(link_at(&layout, 10.0, 1020.0).as_deref(), Some("/home"));
+    }
+
+    #[test]
+    fn test_text_styles() {
+        let css = "div { text-decoration: underline #ff0000; text-transform: uppercase; letter-spacing: 2px; } \
+                   span { text-decoration: line-through; display: block; }";
+        let stylesheet = CssParser::parse(css);
+        let span = Node::element("span".to_string(), HashMap::new(), vec![Node::text("inner".to_string())]);
+        let node = Node::element("div".to_string(), HashMap::new(), vec![Node::text("outer".to_string()), span]);
+        let styled = style_tree(&node, &stylesheet);
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+        let list = build_display_list(&layout_tree(&styled, viewport));
+        let texts: Vec<(&str, &TextStyle)> = list
+            .iter()
+            .filter_map(|command| match command {
+                DisplayCommand::Text { text, style, .. } => Some((text.as_str(), style)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(texts.len(), 2);
+        assert_eq!(texts[0].0, "OUTER");
+        assert_eq!(texts[0].1.letter_spacing, 2.0);
+        let lines = |style: &TextStyle| style.decorations.iter().map(|d| d.line).collect::<Vec<_>>();
+        assert_eq!(lines(texts[0].1), vec![DecorationLine::Underline]);
+        assert_eq!(texts[0].1.decorations[0].color, Color::new(255, 0, 0, 255));
+        // The span's text keeps the div's underline and adds its own line through
+        assert_eq!(texts[1].0, "INNER");
+        assert_eq!(lines(texts[1].1), vec![DecorationLine::Underline, DecorationLine::LineThrough]);
+    }
+
+    #[test]
+    fn test_wrapped_text_draws_each_line() {
+        let css = CssParser::parse("div { display: block; width: 60px; font-size: 10px; line-height: 12px; }");
+        let node = Node::element("div".to_string(), HashMap::new(), vec![Node::text("wrap this text".to_string())]);
+        let styled = style_tree(&node, &css);
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+        let layout = layout_tree(&styled, viewport);
+
+        let display_list = build_display_list(&layout);
+        let lines: Vec<(&str, f32)> = display_list
+            .iter()
+            .filter_map(|command| match command {
+                DisplayCommand::Text { text, rect, .. } => Some((text.as_str(), rect.y)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(lines, vec![("wrap this", 1.0), ("text", 13.0)]);
+    }
+
+    #[test]
+    fn test_damage() {
+        let rect = |x: f32| Rect { x, y: 0.0, width: 10.0, height: 10.0 };
+        let solid = |x: f32, r: u8| DisplayCommand::SolidRect { color: Color::new(r, 0, 0, 255), rect: rect(x) };
+        let old = vec![solid(0.0, 255), solid(20.0, 255), solid(40.0, 255)];
+        
+        assert!(damage(&old, &old).is_empty());
+        // A recolored item damages its area; a moved one both areas; a removed one its old area
+        let new = vec![solid(0.0, 128), solid(30.0, 255)];
+        assert_eq!(damage(&old, &new), vec![rect(0.0), rect(20.0), rect(30.0), rect(40.0)]);
+        
+        // Many changes collapse into their bounding box
+        let many: Vec<DisplayCommand> = (0..20).map(|i| solid(i as f32 * 10.0, 1)).collect();
+        assert_eq!(damage(&[], &many), vec![Rect { x: 0.0, y: 0.0, width: 200.0, height: 10.0 }]);
+    }
 }
@@ -0,0 +1,595 @@
+// Retained DOM - nodes in an arena, addressed by stable ids
+//
+// A `Document` owns every node it has created in one vector and links
+// them with parent and sibling ids, so a node can be found, changed or
+// moved without rebuilding the tree around it. Removing a node only
+// detaches it, and it can be inserted again, as in the web DOM, until the
+// host calls `free_detached()` at the end of a batch of changes. That drops
+// every detached node and puts its slot on a free list; each slot counts how
+// often it was reused, so an old id for it is not found rather than naming
+// the new node. Code that still walks an owned `Node` tree (style, layout)
+// reads a snapshot from `tree()`, which is rebuilt after the document changes.
+
+use super::{AttrMap, ElementData, Node, NodeType};
+use serde::{Deserialize, Serialize};
+use std::cell::OnceCell;
+
+/// Stable id of a node in a `Document`
+///
+/// Ids are sent to scripts as plain numbers, which is how element handles
+/// name their node: the slot's index in the low 32 bits and its generation
+/// above them, kept small enough to be exact in a JavaScript number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct NodeId(u64);
+
+/// Generations a slot goes through before it is retired rather than reused
+const MAX_GENERATION: u32 = (1 << 21) - 1;
+
+impl NodeId {
+    fn new(index: usize, generation: u32) -> Self {
+        Self(u64::from(generation) << 32 | index as u64)
+    }
+
+    /// Position of the node in its document's arena
+    pub fn index(self) -> usize {
+        (self.0 & 0xffff_ffff) as usize
+    }
+
+    fn generation(self) -> u32 {
+        (self.0 >> 32) as u32
+    }
+
+    /// The id as scripts see it
+    pub fn to_u64(self) -> u64 {
+        self.0
+    }
+}
+
+/// Why a DOM operation failed, named after the DOMException it raises
+#[derive(Debug, Clone, PartialEq)]
+pub enum DomError {
+    /// A node is not in this document, or not where the operation expects it
+    NotFound,
+    /// The change would make a node its own ancestor, or give a text node children
+    HierarchyRequest,
+    /// The node is not of the type the operation needs (an element, or text)
+    InvalidNodeType,
+}
+
+impl std::fmt::Display for DomError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DomError::NotFound => write!(f, "NotFoundError: the node was not found"),
+            DomError::HierarchyRequest => write!(f, "HierarchyRequestError: the node cannot be inserted there"),
+            DomError::InvalidNodeType => write!(f, "InvalidNodeTypeError: the node is of the wrong type"),
+        }
+    }
+}
+
+impl std::error::Error for DomError {}
+
+/// A node and its links
+#[derive(Debug, Clone)]
+struct Slot {
+    node_type: NodeType,
+    /// Times the slot has been freed
+    generation: u32,
+    parent: Option<NodeId>,
+    first_child: Option<NodeId>,
+    last_child: Option<NodeId>,
+    previous_sibling: Option<NodeId>,
+    next_sibling: Option<NodeId>,
+}
+
+impl Slot {
+    fn new(node_type: NodeType) -> Self {
+        Self {
+            node_type,
+            generation: 0,
+            parent: None,
+            first_child: None,
+            last_child: None,
+            previous_sibling: None,
+            next_sibling: None,
+        }
+    }
+}
+
+/// A document whose nodes can be changed in place
+#[derive(Debug, Clone)]
+pub struct Document {
+    nodes: Vec<Slot>,
+    /// Slots of freed nodes, to reuse
+    free: Vec<usize>,
+    root: NodeId,
+    /// Owned tree of the connected nodes, built on first use after a change
+    snapshot: OnceCell<Node>,
+}
+
+impl Document {
+    /// Create a document from a parsed tree, whose root becomes the document's root
+    pub fn new(tree: Node) -> Self {
+        let mut document = Self { nodes: Vec::new(), free: Vec::new(), root: NodeId::new(0, 0), snapshot: OnceCell::new() };
+        document.root = document.adopt(&tree);
+        // The tree is already what a snapshot would build
+        let _ = document.snapshot.set(tree);
+        document
+    }
+
+    /// Add a tree's nodes, unattached, returning the id of its root
+    fn adopt(&mut self, tree: &Node) -> NodeId {
+        let id = self.push(tree.node_type.clone());
+        for child in &tree.children {
+            let child = self.adopt(child);
+            self.link(id, child, None);
+        }
+        id
+    }
+
+    fn push(&mut self, node_type: NodeType) -> NodeId {
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.nodes[index];
+            slot.node_type = node_type;
+            return NodeId::new(index, slot.generation);
+        }
+        self.nodes.push(Slot::new(node_type));
+        NodeId::new(self.nodes.len() - 1, 0)
+    }
+
+    /// The root node (normally `<html>`)
+    pub fn root(&self) -> NodeId {
+        self.root
+    }
+
+    /// Number of nodes the document holds, attached or not
+    pub fn len(&self) -> usize {
+        self.nodes.len() - self.free.len()
+    }
+
+    /// Does the document have no nodes (never true: it has a root)
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    fn slot(&self, id: NodeId) -> Option<&Slot> {
+        self.nodes.get(id.index()).filter(|slot| slot.generation == id.generation())
+    }
+
+    fn slot_mut(&mut self, id: NodeId) -> Option<&mut Slot> {
+        self.nodes.get_mut(id.index()).filter(|slot| slot.generation == id.generation())
+    }
+
+    /// Create an unattached element
+    pub fn create_element(&mut self, tag_name: &str, attributes: AttrMap) -> NodeId {
+        self.push(NodeType::Element(ElementData { tag_name: tag_name.to_string(), attributes }))
+    }
+
+    /// Create an unattached text node
+    pub fn create_text(&mut self, data: &str) -> NodeId {
+        self.push(NodeType::Text(data.to_string()))
+    }
+
+    /// Create an unattached comment
+    pub fn create_comment(&mut self, data: &str) -> NodeId {
+        self.push(NodeType::Comment(data.to_string()))
+    }
+
+    /// What a node is, or `None` for an id from another document or a freed node
+    pub fn node_type(&self, id: NodeId) -> Option<&NodeType> {
+        self.slot(id).map(|slot| &slot.node_type)
+    }
+
+    /// A node's element data, if it is an element
+    pub fn element_data(&self, id: NodeId) -> Option<&ElementData> {
+        match self.node_type(id)? {
+            NodeType::Element(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    /// A node's text, if it is a text node
+    pub fn text_content(&self, id: NodeId) -> Option<&str> {
+        match self.node_type(id)? {
+            NodeType::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.slot(id)?.parent
+    }
+
+    pub fn first_child(&self, id: NodeId) -> Option<NodeId> {
+        self.slot(id)?.first_child
+    }
+
+    pub fn last_child(&self, id: NodeId) -> Option<NodeId> {
+        self.slot(id)?.last_child
+    }
+
+    pub fn next_sibling(&self, id: NodeId) -> Option<NodeId> {
+        self.slot(id)?.next_sibling
+    }
+
+    pub fn previous_sibling(&self, id: NodeId) -> Option<NodeId> {
+        self.slot(id)?.previous_sibling
+    }
+
+    /// A node's children, in order
+    pub fn children(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::successors(self.first_child(id), move |&child| self.next_sibling(child))
+    }
+
+    /// A node's ancestors, nearest first
+    pub fn ancestors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::successors(self.parent(id), move |&node| self.parent(node))
+    }
+
+    /// A node and everything under it, in document order
+    pub fn descendants(&self, id: NodeId) -> Vec<NodeId> {
+        let mut nodes = Vec::new();
+        let mut stack = vec![id];
+        while let Some(node) = stack.pop() {
+            nodes.push(node);
+            let start = stack.len();
+            stack.extend(self.children(node));
+            stack[start..].reverse();
+        }
+        nodes
+    }
+
+    /// Is the node in the document's tree (not created or removed and left detached)
+    pub fn is_connected(&self, id: NodeId) -> bool {
+        id == self.root || self.ancestors(id).last() == Some(self.root)
+    }
+
+    /// First connected element with an id attribute, in document order
+    pub fn get_element_by_id(&self, element_id: &str) -> Option<NodeId> {
+        self.descendants(self.root)
+            .into_iter()
+            .find(|&node| self.element_data(node).and_then(ElementData::id) == Some(element_id))
+    }
+
+    /// Child indices from the root to a connected node
+    pub fn path(&self, id: NodeId) -> Option<Vec<usize>> {
+        if !self.is_connected(id) {
+            return None;
+        }
+        let mut path = Vec::new();
+        let mut node = id;
+        while let Some(parent) = self.parent(node) {
+            path.push(self.children(parent).position(|child| child == node)?);
+            node = parent;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// The node at a path of child indices from the root
+    pub fn node_at_path(&self, path: &[usize]) -> Option<NodeId> {
+        path.iter().try_fold(self.root, |node, &i| self.children(node).nth(i))
+    }
+
+    /// Add a node as the last child of `parent`, moving it from where it was
+    pub fn append_child(&mut self, parent: NodeId, child: NodeId) -> Result<(), DomError> {
+        self.insert_before(parent, child, None)
+    }
+
+    /// Add a node to `parent` before `reference`, or last for `None`,
+    /// moving it from where it was
+    pub fn insert_before(&mut self, parent: NodeId, child: NodeId, reference: Option<NodeId>) -> Result<(), DomError> {
+        let parent_type = self.node_type(parent).ok_or(DomError::NotFound)?;
+        self.slot(child).ok_or(DomError::NotFound)?;
+        if !matches!(parent_type, NodeType::Element(_)) {
+            return Err(DomError::HierarchyRequest);
+        }
+        if child == self.root || child == parent || self.ancestors(parent).any(|node| node == child) {
+            return Err(DomError::HierarchyRequest);
+        }
+        if let Some(reference) = reference {
+            if self.parent(reference) != Some(parent) {
+                return Err(DomError::NotFound);
+            }
+            if reference == child {
+                return Ok(());
+            }
+        }
+        self.unlink(child);
+        self.link(parent, child, reference);
+        self.changed();
+        Ok(())
+    }
+
+    /// Detach a child from `parent`; it keeps its id and children
+    pub fn remove_child(&mut self, parent: NodeId, child: NodeId) -> Result<(), DomError> {
+        if self.parent(child) != Some(parent) || self.slot(parent).is_none() {
+            return Err(DomError::NotFound);
+        }
+        self.unlink(child);
+        self.changed();
+        Ok(())
+    }
+
+    /// Set an element's attribute
+    pub fn set_attribute(&mut self, id: NodeId, name: &str, value: &str) -> Result<(), DomError> {
+        self.attributes_mut(id)?.insert(name.to_string(), value.to_string());
+        self.changed();
+        Ok(())
+    }
+
+    /// Remove an element's attribute, returning its old value
+    pub fn remove_attribute(&mut self, id: NodeId, name: &str) -> Result<Option<String>, DomError> {
+        let old = self.attributes_mut(id)?.remove(name);
+        if old.is_some() {
+            self.changed();
+        }
+        Ok(old)
+    }
+
+    /// Replace a text node's text
+    pub fn set_text(&mut self, id: NodeId, data: &str) -> Result<(), DomError> {
+        match &mut self.slot_mut(id).ok_or(DomError::NotFound)?.node_type {
+            NodeType::Text(text) => *text = data.to_string(),
+            _ => return Err(DomError::InvalidNodeType),
+        }
+        self.changed();
+        Ok(())
+    }
+
+    fn attributes_mut(&mut self, id: NodeId) -> Result<&mut AttrMap, DomError> {
+        match &mut self.slot_mut(id).ok_or(DomError::NotFound)?.node_type {
+            NodeType::Element(data) => Ok(&mut data.attributes),
+            _ => Err(DomError::InvalidNodeType),
+        }
+    }
+
+    /// Owned copy of a node and everything under it
+    pub fn to_node(&self, id: NodeId) -> Option<Node> {
+        let node_type = self.node_type(id)?.clone();
+        let children = self.children(id).filter_map(|child| self.to_node(child)).collect();
+        Some(Node { node_type, children })
+    }
+
+    /// The connected nodes as an owned tree, for code that walks `Node`s
+    pub fn tree(&self) -> &Node {
+        self.snapshot.get_or_init(|| self.to_node(self.root).expect("the root is always in the arena"))
+    }
+
+    /// Drop every node not in the document's tree, freeing its slot
+    ///
+    /// Hosts call this once a batch of changes is made, since a node removed
+    /// in one step of a batch may be inserted again in the next. Ids of the
+    /// freed nodes are not found from then on. Returns how many were freed.
+    pub fn free_detached(&mut self) -> usize {
+        let mut keep = vec![false; self.nodes.len()];
+        for node in self.descendants(self.root) {
+            keep[node.index()] = true;
+        }
+        for &index in &self.free {
+            keep[index] = true;
+        }
+        let mut freed = 0;
+        for (index, slot) in self.nodes.iter_mut().enumerate() {
+            if keep[index] || slot.generation == MAX_GENERATION {
+                continue;
+            }
+            *slot = Slot { generation: slot.generation + 1, ..Slot::new(NodeType::Comment(String::new())) };
+            // A slot out of generations is left empty instead of risking an old id naming a new node
+            if slot.generation < MAX_GENERATION {
+                self.free.push(index);
+            }
+            freed += 1;
+        }
+        freed
+    }
+
+    /// Estimated bytes the arena and its snapshot hold
+    ///
+    /// Counts each node's slot and the strings it owns; a snapshot, when
+    /// built, holds a copy of the connected nodes.
+    pub fn memory_bytes(&self) -> usize {
+        let arena: usize = self.nodes.iter().map(|slot| size_of::<Slot>() + node_type_bytes(&slot.node_type)).sum();
+        let snapshot = self.snapshot.get().map_or(0, tree_bytes);
+        arena + snapshot
+    }
+
+    /// Drop the snapshot after a change
+    fn changed(&mut self) {
+        self.snapshot.take();
+    }
+
+    /// Attach an unattached node to `parent`, before `reference` or last
+    fn link(&mut self, parent: NodeId, child: NodeId, reference: Option<NodeId>) {
+        let previous = match reference {
+            Some(reference) => self.nodes[reference.index()].previous_sibling,
+            None => self.nodes[parent.index()].last_child,
+        };
+        let slot = &mut self.nodes[child.index()];
+        slot.parent = Some(parent);
+        slot.previous_sibling = previous;
+        slot.next_sibling = reference;
+        match previous {
+            Some(previous) => self.nodes[previous.index()].next_sibling = Some(child),
+            None => self.nodes[parent.index()].first_child = Some(child),
+        }
+        match reference {
+            Some(reference) => self.nodes[reference.index()].previous_sibling = Some(child),
+            None => self.nodes[parent.index()].last_child = Some(child),
+        }
+    }
+
+    /// Take a node out of its parent's children, if it has a parent
+    fn unlink(&mut self, id: NodeId) {
+        let slot = &mut self.nodes[id.index()];
+        let (previous, next) = (slot.previous_sibling.take(), slot.next_sibling.take());
+        let Some(parent) = slot.parent.take() else {
+            return;
+        };
+        match previous {
+            Some(previous) => self.nodes[previous.index()].next_sibling = next,
+            None => self.nodes[parent.index()].first_child = next,
+        }
+        match next {
+            Some(next) => self.nodes[next.index()].previous_sibling = previous,
+            None => self.nodes[parent.index()].last_child = previous,
+        }
+    }
+}
+
+impl From<Node> for Document {
+    fn from(tree: Node) -> Self {
+        Self::new(tree)
+    }
+}
+
+/// Heap bytes a node's strings take
+fn node_type_bytes(node_type: &NodeType) -> usize {
+    match node_type {
+        NodeType::Element(data) => {
+            data.tag_name.capacity()
+                + data.attributes.iter().map(|(name, value)| name.capacity() + value.capacity()).sum::<usize>()
+                + data.attributes.capacity() * size_of::<(String, String)>()
+        }
+        NodeType::Text(text) | NodeType::Comment(text) => text.capacity(),
+    }
+}
+
+/// Bytes an owned tree takes
+fn tree_bytes(node: &Node) -> usize {
+    size_of::<Node>() + node_type_bytes(&node.node_type) + node.children.iter().map(tree_bytes).sum::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html::HtmlParser;
+
+    fn document() -> Document {
+        Document::new(HtmlParser::parse(
+            "<html><body><ul id=\"list\"><li id=\"a\">A</li><li id=\"b\">B</li></ul><p id=\"p\">P</p></body></html>",
+        ))
+    }
+
+    fn ids(document: &Document, parent: NodeId) -> Vec<String> {
+        document
+            .children(parent)
+            .filter_map(|child| document.element_data(child).and_then(ElementData::id).map(str::to_string))
+            .collect()
+    }
+
+    #[test]
+    fn test_links_and_paths() {
+        let document = document();
+        let list = document.get_element_by_id("list").unwrap();
+        let (a, b) = (document.get_element_by_id("a").unwrap(), document.get_element_by_id("b").unwrap());
+        assert_eq!(document.parent(a), Some(list));
+        assert_eq!((document.next_sibling(a), document.previous_sibling(b)), (Some(b), Some(a)));
+        assert_eq!((document.first_child(list), document.last_child(list)), (Some(a), Some(b)));
+        assert_eq!(document.ancestors(a).last(), Some(document.root()));
+        assert_eq!(document.text_content(document.first_child(b).unwrap()), Some("B"));
+
+        let path = document.path(b).unwrap();
+        assert_eq!(document.tree().descendant(&path).and_then(Node::element_data).and_then(|e| e.id()), Some("b"));
+        assert_eq!(document.node_at_path(&path), Some(b));
+        assert_eq!(document.descendants(list).len(), 5);
+    }
+
+    #[test]
+    fn test_mutations_keep_ids() {
+        let mut document = document();
+        let list = document.get_element_by_id("list").unwrap();
+        let (a, b, p) = (
+            document.get_element_by_id("a").unwrap(),
+            document.get_element_by_id("b").unwrap(),
+            document.get_element_by_id("p").unwrap(),
+        );
+
+        let c = document.create_element("li", AttrMap::new());
+        document.set_attribute(c, "id", "c").unwrap();
+        document.insert_before(list, c, Some(b)).unwrap();
+        assert_eq!(ids(&document, list), ["a", "c", "b"]);
+
+        // Appending a connected node moves it
+        document.append_child(list, a).unwrap();
+        assert_eq!(ids(&document, list), ["c", "b", "a"]);
+
+        document.remove_child(list, b).unwrap();
+        assert!(!document.is_connected(b));
+        assert_eq!(document.path(b), None);
+        assert_eq!(document.get_element_by_id("b"), None);
+        assert_eq!(document.remove_child(list, b), Err(DomError::NotFound));
+
+        // A removed node keeps its id and can go back in
+        document.append_child(p, b).unwrap();
+        assert_eq!(document.get_element_by_id("b"), Some(b));
+        assert_eq!(document.remove_attribute(a, "id"), Ok(Some("a".to_string())));
+        assert_eq!(document.get_element_by_id("a"), None);
+
+        // The snapshot follows the changes
+        let tree = document.tree();
+        let list_node = tree.descendant(&document.path(list).unwrap()).unwrap();
+        assert_eq!(list_node.children.len(), 2);
+    }
+
+    #[test]
+    fn test_hierarchy_errors() {
+        let mut document = document();
+        let list = document.get_element_by_id("list").unwrap();
+        let a = document.get_element_by_id("a").unwrap();
+        let text = document.first_child(a).unwrap();
+        assert_eq!(document.append_child(a, list), Err(DomError::HierarchyRequest));
+        assert_eq!(document.append_child(list, list), Err(DomError::HierarchyRequest));
+        assert_eq!(document.append_child(text, list), Err(DomError::HierarchyRequest));
+        assert_eq!(document.append_child(list, document.root()), Err(DomError::HierarchyRequest));
+        assert_eq!(document.insert_before(list, text, Some(text)), Err(DomError::NotFound));
+        assert_eq!(document.set_attribute(text, "id", "x"), Err(DomError::InvalidNodeType));
+        assert_eq!(document.set_text(list, "x"), Err(DomError::InvalidNodeType));
+        document.set_text(text, "changed").unwrap();
+        assert_eq!(document.tree().descendant(&document.path(text).unwrap()).unwrap().text_content(), Some("changed"));
+
+        let other = Document::new(Node::text("x".to_string()));
+        let stray = NodeId::new(other.len() + document.len(), 0);
+        assert_eq!(document.append_child(list, stray), Err(DomError::NotFound));
+    }
+
+    #[test]
+    fn test_free_detached_reuses_slots() {
+        let mut document = document();
+        let list = document.get_element_by_id("list").unwrap();
+        let a = document.get_element_by_id("a").unwrap();
+        let text = document.first_child(a).unwrap();
+        let len = document.len();
+        document.remove_child(list, a).unwrap();
+        assert_eq!(document.free_detached(), 2);
+        assert_eq!(document.len(), len - 2);
+        assert!(document.node_type(a).is_none());
+        assert_eq!(document.append_child(list, a), Err(DomError::NotFound));
+        assert_eq!(document.free_detached(), 0);
+
+        // A new node takes a freed slot, and the old ids do not name it
+        let c = document.create_element("li", AttrMap::new());
+        assert!(c.index() == a.index() || c.index() == text.index());
+        assert!(c != a && c != text);
+        assert_eq!(document.set_text(text, "x"), Err(DomError::NotFound));
+        document.append_child(list, c).unwrap();
+        assert_eq!(document.children(list).collect::<Vec<_>>(), [document.get_element_by_id("b").unwrap(), c]);
+        assert_eq!(document.len(), len - 1);
+    }
+
+    #[test]
+    fn test_memory_bytes_count_text() {
+        let mut document = document();
+        let p = document.get_element_by_id("p").unwrap();
+        let empty = document.create_text("");
+        document.append_child(p, empty).unwrap();
+        let before = document.memory_bytes();
+        let text = document.create_text(&"x".repeat(1000));
+        document.append_child(p, text).unwrap();
+        let arena = document.memory_bytes();
+        assert!(arena >= before + 1000);
+        // The snapshot copies the text again
+        document.tree();
+        assert!(document.memory_bytes() >= arena + 1000);
+    }
+}
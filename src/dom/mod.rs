@@ -1,5 +1,9 @@
+mod document;
+
 use std::collections::HashMap;
 
+pub use document::{Document, DomError, NodeId};
+
 /// Represents a node in the DOM tree
 #[derive(Debug, Clone)]
 pub enum NodeType {
@@ -64,6 +68,52 @@ impl Node {
             _ => None,
         }
     }
+
+    /// Get the element data mutably if this is an element node
+    pub fn element_data_mut(&mut self) -> Option<&mut ElementData> {
+        match &mut self.node_type {
+            NodeType::Element(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    /// Path of child indices to the first element with an id, in document order
+    pub fn path_to_id(&self, id: &str) -> Option<Vec<usize>> {
+        if self.element_data().and_then(|e| e.id()) == Some(id) {
+            return Some(Vec::new());
+        }
+        self.children.iter().enumerate().find_map(|(i, child)| {
+            let mut path = child.path_to_id(id)?;
+            path.insert(0, i);
+            Some(path)
+        })
+    }
+
+    /// The node at a path of child indices
+    pub fn descendant(&self, path: &[usize]) -> Option<&Node> {
+        path.iter().try_fold(self, |node, &i| node.children.get(i))
+    }
+
+    /// Nodes from this one down to the parent of the node at a path of child indices
+    ///
+    /// Stops early if the path leaves the tree.
+    pub fn ancestors(&self, path: &[usize]) -> Vec<&Node> {
+        let mut ancestors = Vec::new();
+        let mut node = self;
+        for &i in path {
+            ancestors.push(node);
+            match node.children.get(i) {
+                Some(child) => node = child,
+                None => break,
+            }
+        }
+        ancestors
+    }
+
+    /// The node at a path of child indices, to change
+    pub fn descendant_mut(&mut self, path: &[usize]) -> Option<&mut Node> {
+        path.iter().try_fold(self, |node, &i| node.children.get_mut(i))
+    }
 }
 
 impl ElementData {
@@ -83,6 +133,20 @@ impl ElementData {
             .map(|s| s.split_whitespace().collect())
             .unwrap_or_default()
     }
+
+    /// Tag, id and classes, e.g. `div#main.card`
+    pub fn label(&self) -> String {
+        let mut label = self.tag_name.clone();
+        if let Some(id) = self.id() {
+            label.push('#');
+            label.push_str(id);
+        }
+        for class in self.classes() {
+            label.push('.');
+            label.push_str(class);
+        }
+        label
+    }
 }
 
 #[cfg(test)]
@@ -108,5 +172,23 @@ mod tests {
         assert_eq!(elem_data.tag_name, "div");
         assert_eq!(elem_data.id(), Some("main"));
         assert_eq!(elem_data.classes(), vec!["container", "active"]);
+        assert_eq!(elem_data.label(), "div#main.container.active");
+    }
+
+    #[test]
+    fn test_path_to_id() {
+        let mut attrs = HashMap::new();
+        attrs.insert("id".to_string(), "target".to_string());
+        let mut root = Node::element("div".to_string(), HashMap::new(), vec![
+            Node::text("a".to_string()),
+            Node::element("p".to_string(), HashMap::new(), vec![Node::element("b".to_string(), attrs, vec![])]),
+        ]);
+        assert_eq!(root.path_to_id("target"), Some(vec![1, 0]));
+        assert_eq!(root.path_to_id("missing"), None);
+
+        let target = root.descendant_mut(&[1, 0]).unwrap();
+        target.element_data_mut().unwrap().attributes.insert("class".to_string(), "on".to_string());
+        assert_eq!(root.children[1].children[0].element_data().unwrap().classes(), ["on"]);
+        assert!(root.descendant_mut(&[0, 0]).is_none());
     }
 }
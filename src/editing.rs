@@ -0,0 +1,885 @@
+// Rich-text editing of contenteditable regions
+//
+// An element whose `contenteditable` is "", "true" or "plaintext-only" is
+// an editing host: it and everything under it can be edited, except below
+// an element that sets "false". The caret and selection are DOM positions
+// that may sit in different elements, and edits change the `Document` in
+// place the way a script's DOM calls do. Each edit returns the
+// `InputEvent` the page's `input` listeners are given.
+
+use crate::css::Color;
+use crate::display::{DisplayCommand, DisplayList};
+use crate::dom::{AttrMap, Document, Node, NodeId};
+use crate::layout::{LayoutBox, Rect};
+
+const CARET_COLOR: Color = Color { r: 0, g: 0, b: 0, a: 255 };
+const SELECTION_HIGHLIGHT: Color = Color { r: 51, g: 144, b: 255, a: 90 };
+
+/// Height of the caret in an element with no text to measure
+const EMPTY_LINE_HEIGHT: f32 = 16.0;
+
+/// Elements edits treat as paragraphs: Enter splits them and deleting
+/// across one joins it to its neighbour
+const BLOCK_TAGS: &[&str] = &[
+    "address", "article", "aside", "blockquote", "dd", "div", "dt", "footer", "h1", "h2", "h3", "h4", "h5", "h6",
+    "header", "li", "main", "nav", "p", "pre", "section",
+];
+
+/// A point in the document: a character offset in a text node, or a child
+/// index in an element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub node: NodeId,
+    pub offset: usize,
+}
+
+impl Position {
+    pub fn new(node: NodeId, offset: usize) -> Self {
+        Self { node, offset }
+    }
+}
+
+/// What an edit did, as the page's `InputEvent` describes it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputEvent {
+    /// `inputType`, e.g. `insertText` or `formatBold`
+    pub input_type: &'static str,
+    /// The text inserted, for insertions
+    pub data: Option<String>,
+}
+
+impl InputEvent {
+    fn new(input_type: &'static str) -> Self {
+        Self { input_type, data: None }
+    }
+}
+
+/// `Some(true)` or `Some(false)` if a node sets `contenteditable`, `None`
+/// if it inherits
+fn editable_attribute(document: &Document, node: NodeId) -> Option<bool> {
+    let value = document.element_data(node)?.get_attribute("contenteditable")?;
+    match value.to_ascii_lowercase().as_str() {
+        "" | "true" | "plaintext-only" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// The editing host a node is in: the outermost element of its editable region
+pub fn editing_host(document: &Document, node: NodeId) -> Option<NodeId> {
+    let mut host = None;
+    for id in std::iter::once(node).chain(document.ancestors(node)) {
+        match editable_attribute(document, id) {
+            Some(true) => host = Some(id),
+            Some(false) => break,
+            None => {}
+        }
+    }
+    host
+}
+
+/// Can the user edit a node
+pub fn is_editable(document: &Document, node: NodeId) -> bool {
+    editing_host(document, node).is_some()
+}
+
+fn char_len(document: &Document, node: NodeId) -> usize {
+    document.text_content(node).map_or(0, |text| text.chars().count())
+}
+
+fn is_block(document: &Document, node: NodeId) -> bool {
+    document.element_data(node).is_some_and(|e| BLOCK_TAGS.contains(&e.tag_name.to_ascii_lowercase().as_str()))
+}
+
+/// Replace the characters `from..to` of a text node with `text`
+fn splice(document: &mut Document, node: NodeId, from: usize, to: usize, text: &str) {
+    let Some(old) = document.text_content(node) else {
+        return;
+    };
+    let chars: Vec<char> = old.chars().collect();
+    let from = from.min(chars.len());
+    let to = to.clamp(from, chars.len());
+    let new: String = chars[..from].iter().copied().chain(text.chars()).chain(chars[to..].iter().copied()).collect();
+    let _ = document.set_text(node, &new);
+}
+
+/// Move the text of a text node from `offset` on into a new text node after
+/// it, returning the new node
+fn split_text(document: &mut Document, node: NodeId, offset: usize) -> Option<NodeId> {
+    let text = document.text_content(node)?;
+    let rest: String = text.chars().skip(offset).collect();
+    let parent = document.parent(node)?;
+    let after = document.create_text(&rest);
+    document.insert_before(parent, after, document.next_sibling(node)).ok()?;
+    splice(document, node, offset, usize::MAX, "");
+    Some(after)
+}
+
+/// Replace an element with its children
+fn unwrap(document: &mut Document, element: NodeId) {
+    let Some(parent) = document.parent(element) else {
+        return;
+    };
+    while let Some(child) = document.first_child(element) {
+        if document.insert_before(parent, child, Some(element)).is_err() {
+            return;
+        }
+    }
+    let _ = document.remove_child(parent, element);
+}
+
+/// The caret and selection in one editing host, and the edits made at them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Editor {
+    host: NodeId,
+    anchor: Position,
+    focus: Position,
+}
+
+impl Editor {
+    /// Edit the region `node` is in, with the caret at its end
+    pub fn new(document: &Document, node: NodeId) -> Option<Self> {
+        let host = editing_host(document, node)?;
+        let end = match Self::text_nodes_in(document, host).last() {
+            Some(&text) => Position::new(text, char_len(document, text)),
+            None => Position::new(host, document.children(host).count()),
+        };
+        Some(Self { host, anchor: end, focus: end })
+    }
+
+    /// Edit the region a position is in, with the caret there
+    pub fn at(document: &Document, position: Position) -> Option<Self> {
+        let host = editing_host(document, position.node)?;
+        let position = normalize(document, position);
+        Some(Self { host, anchor: position, focus: position })
+    }
+
+    /// The editing host
+    pub fn host(&self) -> NodeId {
+        self.host
+    }
+
+    /// Where the caret is: the end of the selection that moves
+    pub fn caret(&self) -> Position {
+        self.focus
+    }
+
+    /// Is the selection empty
+    pub fn is_collapsed(&self) -> bool {
+        self.anchor == self.focus
+    }
+
+    /// The selection's ends, in document order
+    pub fn selection(&self, document: &Document) -> (Position, Position) {
+        if order_key(document, self.host, self.anchor) <= order_key(document, self.host, self.focus) {
+            (self.anchor, self.focus)
+        } else {
+            (self.focus, self.anchor)
+        }
+    }
+
+    /// Put the caret at a position in the host; false if it is outside
+    pub fn set_caret(&mut self, document: &Document, position: Position) -> bool {
+        if editing_host(document, position.node) != Some(self.host) {
+            return false;
+        }
+        self.collapse(normalize(document, position));
+        true
+    }
+
+    /// Move the caret to a position in the host, keeping the selection's
+    /// other end; false if it is outside
+    pub fn extend_to(&mut self, document: &Document, position: Position) -> bool {
+        if editing_host(document, position.node) != Some(self.host) {
+            return false;
+        }
+        self.focus = normalize(document, position);
+        true
+    }
+
+    fn collapse(&mut self, position: Position) {
+        self.anchor = position;
+        self.focus = position;
+    }
+
+    /// Editable text nodes of a host, in document order
+    fn text_nodes_in(document: &Document, host: NodeId) -> Vec<NodeId> {
+        document
+            .descendants(host)
+            .into_iter()
+            .filter(|&node| document.text_content(node).is_some() && editing_host(document, node) == Some(host))
+            .collect()
+    }
+
+    fn text_nodes(&self, document: &Document) -> Vec<NodeId> {
+        Self::text_nodes_in(document, self.host)
+    }
+
+    /// The paragraph a node is in, or the host if it is in none
+    fn block_of(&self, document: &Document, node: NodeId) -> NodeId {
+        std::iter::once(node)
+            .chain(document.ancestors(node))
+            .take_while(|&id| id != self.host)
+            .find(|&id| is_block(document, id))
+            .unwrap_or(self.host)
+    }
+
+    /// Move the caret one character, across elements; `extend` moves only
+    /// the caret end of the selection
+    pub fn move_caret(&mut self, document: &Document, forward: bool, extend: bool) {
+        if !extend && !self.is_collapsed() {
+            let (start, end) = self.selection(document);
+            self.collapse(if forward { end } else { start });
+            return;
+        }
+        let caret = normalize(document, self.focus);
+        let moved = match document.text_content(caret.node) {
+            Some(_) if forward && caret.offset < char_len(document, caret.node) => {
+                Position::new(caret.node, caret.offset + 1)
+            }
+            Some(_) if !forward && caret.offset > 0 => Position::new(caret.node, caret.offset - 1),
+            Some(_) => {
+                let texts = self.text_nodes(document);
+                let index = texts.iter().position(|&text| text == caret.node);
+                let neighbour = index.and_then(|i| if forward { texts.get(i + 1) } else { i.checked_sub(1).map(|i| &texts[i]) });
+                match neighbour {
+                    // Moving into the next paragraph is a step of its own
+                    Some(&next) => {
+                        let same_block = self.block_of(document, next) == self.block_of(document, caret.node);
+                        let len = char_len(document, next);
+                        let offset = match (forward, same_block) {
+                            (true, true) => len.min(1),
+                            (true, false) => 0,
+                            (false, true) => len.saturating_sub(1),
+                            (false, false) => len,
+                        };
+                        Position::new(next, offset)
+                    }
+                    None => caret,
+                }
+            }
+            None => caret,
+        };
+        self.focus = moved;
+        if !extend {
+            self.anchor = moved;
+        }
+    }
+
+    /// The selected text, a line for each paragraph
+    pub fn selected_text(&self, document: &Document) -> String {
+        let mut text = String::new();
+        let mut block = None;
+        for (node, from, to) in self.selected_ranges(document) {
+            let node_block = self.block_of(document, node);
+            if block.is_some_and(|block| block != node_block) {
+                text.push('\n');
+            }
+            block = Some(node_block);
+            text.extend(document.text_content(node).unwrap_or("").chars().skip(from).take(to - from));
+        }
+        text
+    }
+
+    /// The selected characters of each text node, in document order
+    fn selected_ranges(&self, document: &Document) -> Vec<(NodeId, usize, usize)> {
+        let (start, end) = self.selection(document);
+        let (start_key, end_key) = (order_key(document, self.host, start), order_key(document, self.host, end));
+        self.text_nodes(document)
+            .into_iter()
+            .filter_map(|node| {
+                let len = char_len(document, node);
+                let from = if node == start.node { start.offset } else { 0 };
+                let to = if node == end.node { end.offset } else { len };
+                let inside = order_key(document, self.host, Position::new(node, len)) >= start_key
+                    && order_key(document, self.host, Position::new(node, 0)) <= end_key;
+                (inside && from < to).then_some((node, from, to.min(len)))
+            })
+            .collect()
+    }
+
+    /// Delete the selected content, joining the paragraphs at its ends;
+    /// false if the selection is empty
+    fn delete_selection(&mut self, document: &mut Document) -> bool {
+        let (start, end) = self.selection(document);
+        if start == end {
+            return false;
+        }
+        if start.node == end.node {
+            splice(document, start.node, start.offset, end.offset, "");
+            self.collapse(start);
+            return true;
+        }
+
+        // Nodes wholly between the ends go, outermost first
+        let order = document.descendants(self.host);
+        let index = |node| order.iter().position(|&n| n == node);
+        let (Some(first), Some(last)) = (index(start.node), index(end.node)) else {
+            return false;
+        };
+        let end_ancestors: Vec<NodeId> = document.ancestors(end.node).collect();
+        let mut removed: Vec<NodeId> = Vec::new();
+        for &node in &order[first + 1..last] {
+            let in_removed = document.ancestors(node).any(|ancestor| removed.contains(&ancestor));
+            if !in_removed && !end_ancestors.contains(&node) {
+                removed.push(node);
+            }
+        }
+        for node in removed {
+            if let Some(parent) = document.parent(node) {
+                let _ = document.remove_child(parent, node);
+            }
+        }
+        splice(document, start.node, start.offset, usize::MAX, "");
+        splice(document, end.node, 0, end.offset, "");
+
+        let (start_block, end_block) = (self.block_of(document, start.node), self.block_of(document, end.node));
+        if start_block != end_block {
+            self.join(document, end.node, start_block);
+        }
+        self.collapse(start);
+        true
+    }
+
+    /// Move the run of inline content `node` is in to the end of `into`,
+    /// removing the paragraph it leaves if that is now empty
+    fn join(&self, document: &mut Document, node: NodeId, into: NodeId) {
+        let from = self.block_of(document, node);
+        if from == into || document.ancestors(from).any(|ancestor| ancestor == into) {
+            return;
+        }
+        // The child of `from` holding `node`, and the inline siblings after it
+        let Some(mut child) = std::iter::once(node).chain(document.ancestors(node)).find(|&n| document.parent(n) == Some(from))
+        else {
+            return;
+        };
+        while let Some(previous) = document.previous_sibling(child).filter(|&p| !is_block(document, p)) {
+            child = previous;
+        }
+        let mut next = Some(child);
+        while let Some(node) = next.filter(|&n| !is_block(document, n)) {
+            next = document.next_sibling(node);
+            if document.append_child(into, node).is_err() {
+                return;
+            }
+        }
+        if from != self.host && document.first_child(from).is_none() {
+            if let Some(parent) = document.parent(from) {
+                let _ = document.remove_child(parent, from);
+            }
+        }
+    }
+
+    /// The caret as a text position, adding an empty text node where it
+    /// is between elements
+    fn text_caret(&mut self, document: &mut Document) -> Option<Position> {
+        let caret = normalize(document, self.focus);
+        if document.text_content(caret.node).is_some() {
+            self.collapse(caret);
+            return Some(caret);
+        }
+        let text = document.create_text("");
+        let reference = document.children(caret.node).nth(caret.offset);
+        document.insert_before(caret.node, text, reference).ok()?;
+        self.collapse(Position::new(text, 0));
+        Some(self.focus)
+    }
+
+    /// Type text at the caret, replacing the selection
+    pub fn insert_text(&mut self, document: &mut Document, text: &str) -> Option<InputEvent> {
+        if text.is_empty() {
+            return None;
+        }
+        self.delete_selection(document);
+        let caret = self.text_caret(document)?;
+        splice(document, caret.node, caret.offset, caret.offset, text);
+        self.collapse(Position::new(caret.node, caret.offset + text.chars().count()));
+        Some(InputEvent { input_type: "insertText", data: Some(text.to_string()) })
+    }
+
+    /// Backspace: delete the selection, or the character before the caret,
+    /// joining paragraphs at the start of one
+    pub fn delete_backward(&mut self, document: &mut Document) -> Option<InputEvent> {
+        let event = InputEvent::new("deleteContentBackward");
+        if self.delete_selection(document) {
+            return Some(event);
+        }
+        let caret = normalize(document, self.focus);
+        document.text_content(caret.node)?;
+        if caret.offset > 0 {
+            splice(document, caret.node, caret.offset - 1, caret.offset, "");
+            self.collapse(Position::new(caret.node, caret.offset - 1));
+            return Some(event);
+        }
+        loop {
+            let texts = self.text_nodes(document);
+            let index = texts.iter().position(|&text| text == caret.node)?;
+            let previous = texts[..index].last().copied()?;
+            let block = self.block_of(document, caret.node);
+            if self.block_of(document, previous) != block {
+                self.join(document, caret.node, self.block_of(document, previous));
+                return Some(event);
+            }
+            let len = char_len(document, previous);
+            if len > 0 {
+                splice(document, previous, len - 1, len, "");
+                return Some(event);
+            }
+            // Empty text before the caret deletes nothing visible, so goes on
+            let parent = document.parent(previous)?;
+            document.remove_child(parent, previous).ok()?;
+        }
+    }
+
+    /// Delete: delete the selection, or the character after the caret,
+    /// joining the next paragraph at the end of one
+    pub fn delete_forward(&mut self, document: &mut Document) -> Option<InputEvent> {
+        let event = InputEvent::new("deleteContentForward");
+        if self.delete_selection(document) {
+            return Some(event);
+        }
+        let caret = normalize(document, self.focus);
+        document.text_content(caret.node)?;
+        if caret.offset < char_len(document, caret.node) {
+            splice(document, caret.node, caret.offset, caret.offset + 1, "");
+            return Some(event);
+        }
+        loop {
+            let texts = self.text_nodes(document);
+            let index = texts.iter().position(|&text| text == caret.node)?;
+            let next = texts.get(index + 1).copied()?;
+            let block = self.block_of(document, caret.node);
+            if self.block_of(document, next) != block {
+                self.join(document, next, block);
+                return Some(event);
+            }
+            if char_len(document, next) > 0 {
+                splice(document, next, 0, 1, "");
+                return Some(event);
+            }
+            let parent = document.parent(next)?;
+            document.remove_child(parent, next).ok()?;
+        }
+    }
+
+    /// Enter: split the paragraph at the caret, moving the caret to the new one
+    pub fn insert_paragraph(&mut self, document: &mut Document) -> Option<InputEvent> {
+        self.delete_selection(document);
+        let caret = self.text_caret(document)?;
+        let mut block = self.block_of(document, caret.node);
+        if block == self.host {
+            // Text straight in the host becomes a paragraph first, as in browsers
+            let wrapper = document.create_element("div", AttrMap::new());
+            document.insert_before(self.host, wrapper, Some(caret.node)).ok()?;
+            document.append_child(wrapper, caret.node).ok()?;
+            self.join(document, caret.node, wrapper);
+            let mut previous = document.previous_sibling(wrapper);
+            while let Some(node) = previous.filter(|&n| !is_block(document, n)) {
+                previous = document.previous_sibling(node);
+                document.insert_before(wrapper, node, document.first_child(wrapper)).ok()?;
+            }
+            block = wrapper;
+        }
+
+        // Split each element from the text up to the paragraph, moving what
+        // follows the caret into copies of them
+        let after = split_text(document, caret.node, caret.offset)?;
+        let mut child = caret.node;
+        loop {
+            let parent = document.parent(child)?;
+            let mut attributes = document.element_data(parent)?.attributes.clone();
+            attributes.remove("id");
+            let tag = document.element_data(parent)?.tag_name.clone();
+            let copy = document.create_element(&tag, attributes);
+            let mut next = document.next_sibling(child);
+            while let Some(node) = next {
+                next = document.next_sibling(node);
+                document.append_child(copy, node).ok()?;
+            }
+            let grandparent = document.parent(parent)?;
+            document.insert_before(grandparent, copy, document.next_sibling(parent)).ok()?;
+            if parent == block {
+                break;
+            }
+            child = parent;
+        }
+        self.collapse(Position::new(after, 0));
+        Some(InputEvent::new("insertParagraph"))
+    }
+
+    /// Bold or italicize the selection, or undo it if all of it already is
+    fn toggle_format(&mut self, document: &mut Document, tags: &[&str], input_type: &'static str) -> Option<InputEvent> {
+        let ranges = self.selected_ranges(document);
+        if ranges.is_empty() {
+            return None;
+        }
+        let host = self.host;
+        let formatting = |document: &Document, node: NodeId| {
+            document.ancestors(node).take_while(|&id| id != host).find(|&id| {
+                document.element_data(id).is_some_and(|e| tags.contains(&e.tag_name.to_ascii_lowercase().as_str()))
+            })
+        };
+        if ranges.iter().all(|&(node, ..)| formatting(document, node).is_some()) {
+            for &(node, ..) in &ranges {
+                while let Some(element) = formatting(document, node) {
+                    unwrap(document, element);
+                }
+            }
+            return Some(InputEvent::new(input_type));
+        }
+
+        let mut wrapped = Vec::new();
+        for (node, from, to) in ranges {
+            if to < char_len(document, node) {
+                split_text(document, node, to)?;
+            }
+            let selected = if from > 0 { split_text(document, node, from)? } else { node };
+            if formatting(document, selected).is_none() {
+                let parent = document.parent(selected)?;
+                let element = document.create_element(tags[0], AttrMap::new());
+                document.insert_before(parent, element, Some(selected)).ok()?;
+                document.append_child(element, selected).ok()?;
+            }
+            wrapped.push(selected);
+        }
+        let (first, last) = (*wrapped.first()?, *wrapped.last()?);
+        self.anchor = Position::new(first, 0);
+        self.focus = Position::new(last, char_len(document, last));
+        Some(InputEvent::new(input_type))
+    }
+
+    /// Run a `document.execCommand` command; `None` if it is unsupported
+    /// or changed nothing
+    pub fn exec_command(&mut self, document: &mut Document, command: &str, value: Option<&str>) -> Option<InputEvent> {
+        match command.to_ascii_lowercase().as_str() {
+            "bold" => self.toggle_format(document, &["b", "strong"], "formatBold"),
+            "italic" => self.toggle_format(document, &["i", "em"], "formatItalic"),
+            "inserttext" => self.insert_text(document, value.unwrap_or("")),
+            "delete" => self.delete_backward(document),
+            "forwarddelete" => self.delete_forward(document),
+            "insertparagraph" => self.insert_paragraph(document),
+            _ => None,
+        }
+    }
+
+    /// Selection highlight and caret over a page laid out from `document`,
+    /// scrolled by `offset_y`
+    pub fn paint(&self, document: &Document, layout_root: &LayoutBox, offset_y: f32) -> DisplayList {
+        let mut list = DisplayList::new();
+        for (node, from, to) in self.selected_ranges(document) {
+            let (Some(start), Some(end)) = (
+                caret_rect(document, layout_root, Position::new(node, from)),
+                caret_rect(document, layout_root, Position::new(node, to)),
+            ) else {
+                continue;
+            };
+            let rects = if start.y == end.y {
+                vec![Rect { width: end.x - start.x, ..start }]
+            } else {
+                // Across lines the whole of the node's text is highlighted
+                text_box(document, layout_root, node).map_or_else(Vec::new, |b| b.fragments.iter().map(|f| f.rect).collect())
+            };
+            for rect in rects {
+                list.push(DisplayCommand::Highlight { color: SELECTION_HIGHLIGHT, rect: Rect { y: rect.y - offset_y, ..rect } });
+            }
+        }
+        if let Some(rect) = caret_rect(document, layout_root, self.focus) {
+            list.push(DisplayCommand::SolidRect { color: CARET_COLOR, rect: Rect { y: rect.y - offset_y, ..rect } });
+        }
+        list
+    }
+}
+
+/// A position in a text node where one is next to an element position
+fn normalize(document: &Document, position: Position) -> Position {
+    if document.element_data(position.node).is_none() {
+        return position;
+    }
+    let mut children = document.children(position.node).skip(position.offset.saturating_sub(1));
+    let before = if position.offset > 0 { children.next() } else { None };
+    let after = children.next();
+    match (after, before) {
+        (Some(text), _) if document.text_content(text).is_some() => Position::new(text, 0),
+        (_, Some(text)) if document.text_content(text).is_some() => Position::new(text, char_len(document, text)),
+        _ => position,
+    }
+}
+
+/// Sort key of a position: the node's place in document order, then the offset
+fn order_key(document: &Document, host: NodeId, position: Position) -> (usize, usize) {
+    let order = document.descendants(host);
+    let index = |node: NodeId| order.iter().position(|&n| n == node).unwrap_or(order.len());
+    if document.element_data(position.node).is_none() {
+        return (index(position.node), position.offset);
+    }
+    match document.children(position.node).nth(position.offset) {
+        Some(child) => (index(child), 0),
+        // After the element's last descendant
+        None => (document.descendants(position.node).last().map_or(order.len(), |&last| index(last)), usize::MAX),
+    }
+}
+
+/// Path from `root` to `node`, found by identity
+fn path_of(root: &Node, node: &Node) -> Option<Vec<usize>> {
+    if std::ptr::eq(root, node) {
+        return Some(Vec::new());
+    }
+    root.children.iter().enumerate().find_map(|(i, child)| {
+        let mut path = path_of(child, node)?;
+        path.insert(0, i);
+        Some(path)
+    })
+}
+
+/// The layout box of a node of `document`
+fn layout_box_of<'b, 'a>(document: &Document, layout_root: &'b LayoutBox<'a>, node: NodeId) -> Option<&'b LayoutBox<'a>> {
+    let target = document.tree().descendant(&document.path(node)?)?;
+    find_box(layout_root, &|layout_box| layout_box.get_styled_node().is_some_and(|s| std::ptr::eq(s.node, target)))
+}
+
+fn text_box<'b, 'a>(document: &Document, layout_root: &'b LayoutBox<'a>, node: NodeId) -> Option<&'b LayoutBox<'a>> {
+    layout_box_of(document, layout_root, node).filter(|b| !b.fragments.is_empty())
+}
+
+fn find_box<'b, 'a>(layout_box: &'b LayoutBox<'a>, matches: &dyn Fn(&LayoutBox<'a>) -> bool) -> Option<&'b LayoutBox<'a>> {
+    if matches(layout_box) {
+        return Some(layout_box);
+    }
+    layout_box.children.iter().find_map(|child| find_box(child, matches))
+}
+
+/// Where the caret at a position is drawn, in page coordinates
+pub fn caret_rect(document: &Document, layout_root: &LayoutBox, position: Position) -> Option<Rect> {
+    if let Some(text) = text_box(document, layout_root, position.node) {
+        let mut remaining = position.offset;
+        for fragment in &text.fragments {
+            let chars = fragment.text.chars().count();
+            if remaining <= chars {
+                let x = fragment.rect.x + fragment.rect.width * remaining as f32 / chars.max(1) as f32;
+                return Some(Rect { x, width: 1.0, ..fragment.rect });
+            }
+            remaining -= chars;
+        }
+        let last = text.fragments.last()?.rect;
+        return Some(Rect { x: last.x + last.width, width: 1.0, ..last });
+    }
+    // No text to measure: the start of the element, or of the text's parent
+    let element = if document.element_data(position.node).is_some() { position.node } else { document.parent(position.node)? };
+    let content = layout_box_of(document, layout_root, element)?.dimensions.content;
+    Some(Rect { x: content.x, y: content.y, width: 1.0, height: EMPTY_LINE_HEIGHT })
+}
+
+/// The position under a point of a page laid out from `document`: in the
+/// text there, or at the end of the element there
+pub fn position_at(document: &Document, layout_root: &LayoutBox, x: f32, y: f32) -> Option<Position> {
+    let mut texts = Vec::new();
+    collect_text_boxes(layout_root, &mut texts);
+    // The fragment under the point, or else the nearest on its line
+    let on_line = texts.iter().flat_map(|b| b.fragments.iter().enumerate().map(move |(i, f)| (*b, i, f))).filter(|(_, _, f)| {
+        y >= f.rect.y && y < f.rect.y + f.rect.height
+    });
+    let distance = |rect: &Rect| if x < rect.x { rect.x - x } else { (x - rect.x - rect.width).max(0.0) };
+    let hit = on_line.min_by(|(_, _, a), (_, _, b)| distance(&a.rect).total_cmp(&distance(&b.rect)));
+    if let Some((text_box, index, fragment)) = hit {
+        let node = document.node_at_path(&path_of(document.tree(), text_box.get_styled_node()?.node)?)?;
+        let before: usize = text_box.fragments[..index].iter().map(|f| f.text.chars().count()).sum();
+        let chars = fragment.text.chars().count();
+        let along = ((x - fragment.rect.x) / fragment.rect.width.max(1.0)).clamp(0.0, 1.0);
+        let offset = before + (along * chars as f32).round() as usize;
+        return Some(Position::new(node, offset.min(char_len(document, node))));
+    }
+
+    let element = layout_root.hit_test(x, y).into_iter().rev().find_map(|b| {
+        let node = b.get_styled_node()?.node;
+        node.element_data()?;
+        document.node_at_path(&path_of(document.tree(), node)?)
+    })?;
+    let end = document.descendants(element).into_iter().rev().find(|&n| document.text_content(n).is_some());
+    Some(match end {
+        Some(text) => Position::new(text, char_len(document, text)),
+        None => Position::new(element, document.children(element).count()),
+    })
+}
+
+fn collect_text_boxes<'b, 'a>(layout_box: &'b LayoutBox<'a>, out: &mut Vec<&'b LayoutBox<'a>>) {
+    if !layout_box.fragments.is_empty() && layout_box.get_styled_node().is_some_and(|s| s.node.text_content().is_some()) {
+        out.push(layout_box);
+    }
+    for child in &layout_box.children {
+        collect_text_boxes(child, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::Stylesheet;
+    use crate::html::HtmlParser;
+    use crate::layout::{layout_tree, Dimensions};
+    use crate::style::style_tree;
+
+    fn document(html: &str) -> Document {
+        Document::new(HtmlParser::parse(html))
+    }
+
+    /// The host's content as HTML
+    fn html(document: &Document, host: NodeId) -> String {
+        fn write(document: &Document, node: NodeId, out: &mut String) {
+            if let Some(text) = document.text_content(node) {
+                out.push_str(text);
+                return;
+            }
+            let tag = document.element_data(node).map(|e| e.tag_name.clone()).unwrap_or_default();
+            out.push_str(&format!("<{}>", tag));
+            for child in document.children(node) {
+                write(document, child, out);
+            }
+            out.push_str(&format!("</{}>", tag));
+        }
+        let mut out = String::new();
+        for child in document.children(host) {
+            write(document, child, &mut out);
+        }
+        out
+    }
+
+    fn text(document: &Document, id: &str) -> NodeId {
+        let element = document.get_element_by_id(id).unwrap();
+        document.descendants(element).into_iter().find(|&n| document.text_content(n).is_some()).unwrap()
+    }
+
+    #[test]
+    fn test_editing_host() {
+        let document = document(
+            "<div id=\"host\" contenteditable><p id=\"a\">Hi</p><span id=\"b\" contenteditable=\"false\">no</span></div><p id=\"c\">x</p>",
+        );
+        let host = document.get_element_by_id("host").unwrap();
+        assert_eq!(editing_host(&document, text(&document, "a")), Some(host));
+        assert!(!is_editable(&document, text(&document, "b")));
+        assert!(!is_editable(&document, text(&document, "c")));
+        assert!(Editor::new(&document, text(&document, "c")).is_none());
+        let editor = Editor::new(&document, host).unwrap();
+        assert_eq!(editor.caret(), Position::new(text(&document, "a"), 2));
+    }
+
+    #[test]
+    fn test_typing_and_deleting() {
+        let mut document = document("<div id=\"host\" contenteditable=\"true\"><p id=\"a\">Hello</p></div>");
+        let host = document.get_element_by_id("host").unwrap();
+        let hello = text(&document, "a");
+        let mut editor = Editor::at(&document, Position::new(hello, 5)).unwrap();
+        let event = editor.insert_text(&mut document, " world").unwrap();
+        assert_eq!((event.input_type, event.data.as_deref()), ("insertText", Some(" world")));
+        assert_eq!(editor.delete_backward(&mut document).unwrap().input_type, "deleteContentBackward");
+        assert_eq!(html(&document, host), "<p>Hello worl</p>");
+
+        // A selection is replaced by what is typed
+        editor.set_caret(&document, Position::new(hello, 0));
+        editor.extend_to(&document, Position::new(hello, 5));
+        assert_eq!(editor.selected_text(&document), "Hello");
+        editor.insert_text(&mut document, "Bye");
+        assert_eq!(html(&document, host), "<p>Bye worl</p>");
+        assert_eq!(editor.caret(), Position::new(hello, 3));
+        editor.delete_forward(&mut document);
+        assert_eq!(html(&document, host), "<p>Byeworl</p>");
+    }
+
+    #[test]
+    fn test_paragraphs() {
+        let mut document = document("<div id=\"host\" contenteditable><p id=\"a\">One <b>two</b> three</p></div>");
+        let host = document.get_element_by_id("host").unwrap();
+        let two = document.descendants(host).into_iter().filter(|&n| document.text_content(n).is_some()).nth(1).unwrap();
+
+        // Enter in the middle of bold text splits the bold element too
+        let mut editor = Editor::at(&document, Position::new(two, 1)).unwrap();
+        assert_eq!(editor.insert_paragraph(&mut document).unwrap().input_type, "insertParagraph");
+        assert_eq!(html(&document, host), "<p>One <b>t</b></p><p><b>wo</b> three</p>");
+        editor.insert_text(&mut document, "T");
+        assert_eq!(html(&document, host), "<p>One <b>t</b></p><p><b>Two</b> three</p>");
+
+        // Backspace at the start of a paragraph joins it to the one before
+        editor.move_caret(&document, false, false);
+        editor.delete_backward(&mut document);
+        assert_eq!(html(&document, host), "<p>One <b>t</b><b>Two</b> three</p>");
+
+        // Text straight in the host is put in a paragraph before splitting
+        let mut document = document_with_plain_host();
+        let host = document.get_element_by_id("host").unwrap();
+        let mut editor = Editor::new(&document, host).unwrap();
+        editor.insert_paragraph(&mut document);
+        editor.insert_text(&mut document, "next");
+        assert_eq!(html(&document, host), "<div>plain</div><div>next</div>");
+    }
+
+    fn document_with_plain_host() -> Document {
+        document("<div id=\"host\" contenteditable>plain</div>")
+    }
+
+    #[test]
+    fn test_selection_across_elements() {
+        let mut document = document("<div id=\"host\" contenteditable><p id=\"a\">First</p><p id=\"b\">Second</p></div>");
+        let host = document.get_element_by_id("host").unwrap();
+        let (first, second) = (text(&document, "a"), text(&document, "b"));
+        let mut editor = Editor::at(&document, Position::new(first, 5)).unwrap();
+
+        // Arrow keys step over the paragraph boundary, and shift extends
+        editor.move_caret(&document, true, false);
+        assert_eq!(editor.caret(), Position::new(second, 0));
+        editor.set_caret(&document, Position::new(first, 2));
+        for _ in 0..7 {
+            editor.move_caret(&document, true, true);
+        }
+        assert_eq!(editor.caret(), Position::new(second, 3));
+        assert_eq!(editor.selected_text(&document), "rst\nSec");
+
+        editor.delete_backward(&mut document);
+        assert_eq!(html(&document, host), "<p>Fiond</p>");
+        assert_eq!(editor.caret(), Position::new(first, 2));
+        assert!(editor.is_collapsed());
+    }
+
+    #[test]
+    fn test_exec_command_formatting() {
+        let mut document = document("<div id=\"host\" contenteditable><p id=\"a\">make bold</p></div>");
+        let host = document.get_element_by_id("host").unwrap();
+        let words = text(&document, "a");
+        let mut editor = Editor::at(&document, Position::new(words, 5)).unwrap();
+        assert_eq!(editor.exec_command(&mut document, "bold", None), None);
+
+        editor.extend_to(&document, Position::new(words, 9));
+        let event = editor.exec_command(&mut document, "Bold", None).unwrap();
+        assert_eq!(event.input_type, "formatBold");
+        assert_eq!(html(&document, host), "<p>make <b>bold</b></p>");
+        assert_eq!(editor.selected_text(&document), "bold");
+
+        editor.exec_command(&mut document, "italic", None);
+        assert_eq!(html(&document, host), "<p>make <b><i>bold</i></b></p>");
+        editor.exec_command(&mut document, "bold", None);
+        assert_eq!(html(&document, host), "<p>make <i>bold</i></p>");
+
+        editor.move_caret(&document, true, false);
+        editor.exec_command(&mut document, "insertText", Some("!"));
+        assert_eq!(html(&document, host), "<p>make <i>bold!</i></p>");
+        assert_eq!(editor.exec_command(&mut document, "justifyCenter", None), None);
+    }
+
+    #[test]
+    fn test_caret_from_layout() {
+        let document = document("<div id=\"host\" contenteditable><p id=\"a\">Hello</p></div>");
+        let stylesheet = Stylesheet::new(Vec::new());
+        let styled = style_tree(document.tree(), &stylesheet);
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+        let root = layout_tree(&styled, viewport);
+        let hello = text(&document, "a");
+
+        let start = caret_rect(&document, &root, Position::new(hello, 0)).unwrap();
+        let end = caret_rect(&document, &root, Position::new(hello, 5)).unwrap();
+        assert!(end.x > start.x && end.y == start.y);
+        let middle = position_at(&document, &root, (start.x + end.x) / 2.0, start.y + 1.0).unwrap();
+        assert_eq!(middle.node, hello);
+        assert!((1..=4).contains(&middle.offset));
+        let editor = Editor::at(&document, middle).unwrap();
+        assert!(editor.paint(&document, &root, 0.0).iter().any(|c| matches!(c, DisplayCommand::SolidRect { .. })));
+    }
+}
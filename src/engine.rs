@@ -0,0 +1,568 @@
+// Embedding API - a whole page pipeline behind one type
+//
+// A WebView owns what a browser tab needs to show a page: it loads the
+// document and its stylesheets, runs its scripts, styles and lays it out,
+// and keeps the display list to paint. Input is hit tested against the
+// layout and dispatched to the page's scripts; following a link loads the
+// new page. Embedders hear about title, URL and favicon changes through
+// callbacks and take screenshots with the software rasterizer, so no
+// window or GPU is needed.
+
+use crate::css::{CssParser, MediaType, Origin, Stylesheet};
+use crate::display::{build_display_list, scroll_display_list, DisplayList};
+use crate::dom::{Document, Node};
+use crate::js::{EventType, JsContext, JsError, JsValue};
+use crate::layout::inline::{EstimatedMetrics, TextMeasure};
+use crate::layout::{layout_tree_with_metrics, Dimensions, LayoutBox, Rect};
+use crate::navigation::{document_base_url, resolve_href};
+use crate::net::{CredentialPrompt, LoadedPage, NetError, PageLoader};
+use crate::print::{Page, PrintError, PrintOptions};
+use crate::renderer::software::SoftwareRasterizer;
+use crate::style::style_tree;
+use crate::ui::{document_title, favicon_url, link_at};
+use crate::user_content::{RunAt, UserContent};
+use crate::window::ScrollState;
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// Longest idle period `tick` gives idle callbacks
+const IDLE_PERIOD: Duration = Duration::from_millis(50);
+
+/// Styles every page starts from, before its own
+const USER_AGENT_CSS: &str = "
+    head, title, script, style, link, meta { display: none; }
+    html, body, div, p, h1, h2, h3, h4, h5, h6, ul, ol, li, section, article, header, footer, nav, main,
+    aside, blockquote, pre, form, table, tr, figure { display: block; }
+    body { margin: 8px; }
+";
+
+/// Something embedders may want to show about the page
+#[derive(Debug, Clone, PartialEq)]
+pub enum WebViewEvent {
+    /// A new page was committed
+    UrlChanged(Url),
+    /// The page's `<title>` changed
+    TitleChanged(String),
+    /// The page's icon changed, or it no longer has one
+    FaviconChanged(Option<Url>),
+    /// The page loaded and its scripts ran
+    LoadFinished,
+}
+
+/// Input sent to the page, in CSS pixels from the top left of the view
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputEvent {
+    MouseMove { x: f32, y: f32 },
+    MouseDown { x: f32, y: f32 },
+    MouseUp { x: f32, y: f32 },
+    /// A press and release; follows the link under the point
+    Click { x: f32, y: f32 },
+    /// Scroll the page by a distance
+    Scroll { delta_x: f32, delta_y: f32 },
+    /// A key, named as in `KeyboardEvent.key`
+    KeyDown(String),
+    KeyUp(String),
+}
+
+/// A painted view, row by row, four RGBA bytes per pixel
+#[derive(Debug, Clone, PartialEq)]
+pub struct Screenshot {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Screenshot {
+    /// Encode the pixels as a PNG file
+    pub fn to_png(&self) -> Result<Vec<u8>, image::ImageError> {
+        let mut png = Vec::new();
+        if let Some(image) = image::RgbaImage::from_raw(self.width, self.height, self.pixels.clone()) {
+            image.write_to(&mut Cursor::new(&mut png), image::ImageOutputFormat::Png)?;
+        }
+        Ok(png)
+    }
+}
+
+/// Callback told about page changes
+pub type WebViewListener = Box<dyn FnMut(&WebViewEvent)>;
+
+/// A page and everything needed to load, lay out, script and paint it
+pub struct WebView {
+    loader: PageLoader,
+    /// Size of the view in CSS pixels
+    width: f32,
+    height: f32,
+    /// Device pixels per CSS pixel for screenshots
+    scale_factor: f32,
+    url: Option<Url>,
+    /// URL links and scripts resolve against
+    base_url: Option<Url>,
+    title: String,
+    favicon: Option<Url>,
+    document: Option<Document>,
+    /// HTML the page was loaded from, when it did not come from the network
+    source: Option<String>,
+    /// User agent, user and page styles, for every medium
+    page_stylesheet: Stylesheet,
+    /// The rules of `page_stylesheet` that apply on screen
+    stylesheet: Stylesheet,
+    js_context: JsContext,
+    scroll: ScrollState,
+    text_metrics: Box<dyn TextMeasure>,
+    display_list: DisplayList,
+    listeners: Vec<WebViewListener>,
+    /// Stylesheets and scripts added to the pages they match
+    user_content: UserContent,
+}
+
+impl WebView {
+    /// Create an empty view of a size in CSS pixels
+    pub fn new(width: f32, height: f32) -> Self {
+        Self {
+            loader: PageLoader::new(),
+            width,
+            height,
+            scale_factor: 1.0,
+            url: None,
+            base_url: None,
+            title: String::new(),
+            favicon: None,
+            document: None,
+            source: None,
+            page_stylesheet: Stylesheet::new(Vec::new()),
+            stylesheet: Stylesheet::new(Vec::new()),
+            js_context: JsContext::new(),
+            scroll: ScrollState::new(width, height),
+            text_metrics: Box::new(EstimatedMetrics),
+            display_list: Vec::new(),
+            listeners: Vec::new(),
+            user_content: UserContent::new(),
+        }
+    }
+
+    /// Measure text with real fonts instead of estimates
+    pub fn set_text_measure(&mut self, measure: Box<dyn TextMeasure>) {
+        self.text_metrics = measure;
+        self.relayout();
+    }
+
+    /// Set the device pixel ratio screenshots are taken at
+    pub fn set_scale_factor(&mut self, scale_factor: f32) {
+        if scale_factor > 0.0 {
+            self.scale_factor = scale_factor;
+        }
+    }
+
+    /// User stylesheets and scripts, which apply from the next page load
+    pub fn user_content_mut(&mut self) -> &mut UserContent {
+        &mut self.user_content
+    }
+
+    /// Be told about title, URL and favicon changes
+    pub fn on_event(&mut self, listener: impl FnMut(&WebViewEvent) + 'static) {
+        self.listeners.push(Box::new(listener));
+    }
+
+    /// Answer servers asking for a username and password
+    ///
+    /// Credentials that work are sent to the origin again for the life of
+    /// the view. Without a prompt, a `401 Unauthorized` page is shown as is.
+    pub fn set_credential_prompt(&mut self, prompt: impl CredentialPrompt + 'static) {
+        self.loader.set_credential_prompt(Some(Arc::new(prompt)));
+    }
+
+    /// Load a page from the network
+    pub fn load_url(&mut self, url: &Url) -> Result<(), NetError> {
+        if url.as_str() == "about:blank" {
+            return self.load_html("", url);
+        }
+        let page = self.loader.load_page(url)?;
+        self.source = None;
+        self.commit(page);
+        Ok(())
+    }
+
+    /// Load a page from HTML, resolving its links and stylesheets against `url`
+    pub fn load_html(&mut self, html: &str, url: &Url) -> Result<(), NetError> {
+        let page = self.loader.load_html(html, url)?;
+        self.source = Some(html.to_string());
+        self.commit(page);
+        Ok(())
+    }
+
+    /// Load the page shown again, from the HTML it was given if it came from no network
+    pub fn reload(&mut self) -> Result<(), NetError> {
+        let Some(url) = self.url.clone() else {
+            return Ok(());
+        };
+        match self.source.take() {
+            Some(html) => self.load_html(&html, &url),
+            None => self.load_url(&url),
+        }
+    }
+
+    /// Change the size of the view, laying the page out again
+    pub fn resize(&mut self, width: f32, height: f32) {
+        self.width = width;
+        self.height = height;
+        self.scroll.set_viewport_size(width, height);
+        self.relayout();
+        let _ = self.js_context.dispatch_event(EventType::Resize, String::new());
+        self.apply_dom_mutations();
+    }
+
+    /// Send input to the page
+    ///
+    /// A click on a link loads its target, which can fail like `load_url`.
+    pub fn send_input(&mut self, event: InputEvent) -> Result<(), NetError> {
+        let (event_type, target) = match &event {
+            InputEvent::MouseMove { x, y } => (EventType::MouseMove, self.target_at(*x, *y)),
+            InputEvent::MouseDown { x, y } => (EventType::MouseDown, self.target_at(*x, *y)),
+            InputEvent::MouseUp { x, y } => (EventType::MouseUp, self.target_at(*x, *y)),
+            InputEvent::Click { x, y } => (EventType::Click, self.target_at(*x, *y)),
+            InputEvent::Scroll { delta_x, delta_y } => {
+                self.scroll.scroll_by(*delta_x, *delta_y);
+                (EventType::Scroll, String::new())
+            }
+            InputEvent::KeyDown(key) => (EventType::KeyDown, key.clone()),
+            InputEvent::KeyUp(key) => (EventType::KeyUp, key.clone()),
+        };
+        let _ = self.js_context.dispatch_event(event_type, target);
+        self.apply_dom_mutations();
+
+        if let InputEvent::Click { x, y } = event {
+            if let Some(url) = self.link_at(x, y) {
+                return self.load_url(&url);
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the page's animation frame callbacks for a frame at `frame_time`,
+    /// then its idle callbacks if the frame left time for them
+    ///
+    /// Returns whether any callback ran.
+    pub fn tick(&mut self, frame_time: Instant) -> bool {
+        let mut ran = 0;
+        if self.js_context.has_animation_frames() {
+            ran += self.js_context.run_animation_frames(frame_time).unwrap_or(0);
+        }
+        if self.js_context.has_idle_callbacks() {
+            ran += self.js_context.run_idle_callbacks(Instant::now() + IDLE_PERIOD).unwrap_or(0);
+        }
+        self.apply_dom_mutations();
+        ran > 0
+    }
+
+    /// Whether the page's scripts are waiting for a frame or idle time
+    pub fn has_pending_callbacks(&mut self) -> bool {
+        self.js_context.has_animation_frames() || self.js_context.has_idle_callbacks()
+    }
+
+    /// Run script in the page, returning its completion value
+    pub fn evaluate_js(&mut self, code: &str) -> Result<JsValue, JsError> {
+        let result = self.js_context.execute(code);
+        self.apply_dom_mutations();
+        result
+    }
+
+    /// Paint the visible part of the page
+    ///
+    /// The software rasterizer draws boxes and borders; text and images are
+    /// left out.
+    pub fn screenshot(&self) -> Screenshot {
+        let width = (self.width * self.scale_factor).round().max(0.0) as u32;
+        let height = (self.height * self.scale_factor).round().max(0.0) as u32;
+        let mut raster = SoftwareRasterizer::new(width, height);
+        raster.set_scale_factor(self.scale_factor);
+        let viewport = Rect { x: self.scroll.offset_x, y: self.scroll.offset_y, width: self.width, height: self.height };
+        let scrolled = scroll_display_list(self.display_list.clone(), viewport, self.scroll.offset_x, self.scroll.offset_y);
+        raster.paint(&scrolled);
+        Screenshot { width, height, pixels: raster.pixels().to_vec() }
+    }
+
+    /// Lay the page out on paper and encode it as a PDF file
+    pub fn print_to_pdf(&self, options: &PrintOptions) -> Result<Vec<u8>, PrintError> {
+        let document = self
+            .document()
+            .cloned()
+            .unwrap_or_else(|| Node::element("html".to_string(), Default::default(), Vec::new()));
+        let mut page = Page::new(document, self.page_stylesheet.clone());
+        if let Some(url) = &self.url {
+            page = page.with_url(url.clone());
+        }
+        page.print_to_pdf(options)
+    }
+
+    /// URL of the page shown
+    pub fn url(&self) -> Option<&Url> {
+        self.url.as_ref()
+    }
+
+    /// Title of the page, or its URL if it has none
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Icon of the page
+    pub fn favicon(&self) -> Option<&Url> {
+        self.favicon.as_ref()
+    }
+
+    /// The page's document
+    pub fn document(&self) -> Option<&Node> {
+        self.document.as_ref().map(Document::tree)
+    }
+
+    /// The page's retained document, whose nodes keep their ids across changes
+    pub fn dom(&self) -> Option<&Document> {
+        self.document.as_ref()
+    }
+
+    /// What painting the page draws, in page coordinates
+    pub fn display_list(&self) -> &DisplayList {
+        &self.display_list
+    }
+
+    /// How far the page is scrolled, in CSS pixels
+    pub fn scroll_offset(&self) -> (f32, f32) {
+        (self.scroll.offset_x, self.scroll.offset_y)
+    }
+
+    /// Show a loaded page: lay it out, run its scripts and report what changed
+    fn commit(&mut self, page: LoadedPage) {
+        let LoadedPage { url, dom, stylesheets, .. } = page;
+        let base_url = document_base_url(&dom, &url);
+        let mut stylesheet = CssParser::parse(USER_AGENT_CSS).with_origin(Origin::UserAgent);
+        stylesheet.append(self.user_content.stylesheet(&url));
+        for sheet in stylesheets {
+            stylesheet.append(sheet);
+        }
+        self.stylesheet = stylesheet.for_media(MediaType::Screen);
+        self.page_stylesheet = stylesheet;
+        let title = document_title(&dom).unwrap_or_else(|| url.to_string());
+        let favicon = favicon_url(&dom, &base_url);
+        let mut scripts: Vec<String> = self.user_scripts(&url, RunAt::DocumentStart);
+        scripts.extend(self.page_scripts(&dom, &base_url));
+        scripts.extend(self.user_scripts(&url, RunAt::DocumentEnd));
+
+        let document = Document::new(dom);
+        self.js_context = JsContext::new();
+        let _ = self.js_context.set_element_ids(&document);
+        self.document = Some(document);
+        self.scroll = ScrollState::new(self.width, self.height);
+        self.relayout();
+
+        self.url = Some(url.clone());
+        self.base_url = Some(base_url);
+        self.emit(WebViewEvent::UrlChanged(url));
+        if title != self.title {
+            self.title = title.clone();
+            self.emit(WebViewEvent::TitleChanged(title));
+        }
+        if favicon != self.favicon {
+            self.favicon = favicon.clone();
+            self.emit(WebViewEvent::FaviconChanged(favicon));
+        }
+
+        for script in scripts {
+            if let Err(e) = self.js_context.run_page_script(&script) {
+                eprintln!("JavaScript error: {}", e);
+            }
+        }
+        let _ = self.js_context.dispatch_event(EventType::DOMContentLoaded, String::new());
+        let _ = self.js_context.dispatch_event(EventType::Load, String::new());
+        self.apply_dom_mutations();
+        self.emit(WebViewEvent::LoadFinished);
+    }
+
+    /// Source of the page's scripts in document order, fetching external ones
+    fn page_scripts(&self, dom: &Node, base_url: &Url) -> Vec<String> {
+        let mut scripts = Vec::new();
+        collect_scripts(dom, &mut |src, text| match src {
+            Some(src) => match base_url.join(src).map(|url| self.loader.resource_loader().load_text(&url)) {
+                Ok(Ok(script)) => scripts.push(script),
+                Ok(Err(e)) => eprintln!("Failed to load script {}: {}", src, e),
+                Err(e) => eprintln!("Bad script URL {}: {}", src, e),
+            },
+            None => scripts.push(text),
+        });
+        scripts
+    }
+
+    /// Source of the user scripts to run in a page at a point of its load
+    fn user_scripts(&self, url: &Url, run_at: RunAt) -> Vec<String> {
+        self.user_content.scripts_at(url, run_at).into_iter().map(str::to_string).collect()
+    }
+
+    /// Make the changes scripts made to the document, relaying out if any took
+    fn apply_dom_mutations(&mut self) {
+        let Ok(mutations) = self.js_context.take_dom_mutations() else {
+            return;
+        };
+        let Some(document) = self.document.as_mut() else {
+            return;
+        };
+        let mut changed = false;
+        for mutation in &mutations {
+            changed |= mutation.apply(document).is_ok();
+        }
+        if changed {
+            document.free_detached();
+            let _ = self.js_context.set_element_ids(document);
+            self.relayout();
+        }
+    }
+
+    /// Style and lay out the document again, rebuilding the display list
+    fn relayout(&mut self) {
+        let viewport = self.viewport();
+        let Some(document) = self.document.as_ref().map(Document::tree) else {
+            self.display_list.clear();
+            return;
+        };
+        let styled = style_tree(document, &self.stylesheet);
+        let layout_root = layout_tree_with_metrics(&styled, viewport, &*self.text_metrics);
+        let page_box = layout_root.dimensions.margin_box();
+        self.scroll.set_content_size(page_box.width, page_box.height);
+        self.display_list = build_display_list(&layout_root);
+    }
+
+    fn viewport(&self) -> Dimensions {
+        Dimensions {
+            content: Rect { x: 0.0, y: 0.0, width: self.width, height: self.height },
+            ..Dimensions::default()
+        }
+    }
+
+    /// Run a function over the page's layout
+    fn with_layout<R>(&self, f: impl FnOnce(&LayoutBox) -> R) -> Option<R> {
+        let document = self.document()?;
+        let styled = style_tree(document, &self.stylesheet);
+        let layout_root = layout_tree_with_metrics(&styled, self.viewport(), &*self.text_metrics);
+        Some(f(&layout_root))
+    }
+
+    /// Id of the innermost element with one under a point in the view, for events
+    fn target_at(&self, x: f32, y: f32) -> String {
+        let (page_x, page_y) = (x + self.scroll.offset_x, y + self.scroll.offset_y);
+        self.with_layout(|root| {
+            root.hit_test(page_x, page_y)
+                .iter()
+                .rev()
+                .find_map(|layout_box| layout_box.get_styled_node()?.node.element_data()?.id().map(str::to_string))
+        })
+        .flatten()
+        .unwrap_or_default()
+    }
+
+    /// Where the link under a point in the view goes
+    fn link_at(&self, x: f32, y: f32) -> Option<Url> {
+        let (page_x, page_y) = (x + self.scroll.offset_x, y + self.scroll.offset_y);
+        let href = self.with_layout(|root| link_at(root, page_x, page_y)).flatten()?;
+        resolve_href(self.base_url.as_ref()?, &href)
+    }
+
+    fn emit(&mut self, event: WebViewEvent) {
+        for listener in &mut self.listeners {
+            listener(&event);
+        }
+    }
+}
+
+/// Visit the `<script>` elements of a document: their `src`, or their text if they have none
+fn collect_scripts(node: &Node, visit: &mut dyn FnMut(Option<&str>, String)) {
+    if let Some(element) = node.element_data() {
+        if element.tag_name.eq_ignore_ascii_case("script") {
+            let text: String = node.children.iter().filter_map(|child| child.text_content()).collect();
+            match element.get_attribute("src") {
+                Some(src) => visit(Some(src), text),
+                None if !text.trim().is_empty() => visit(None, text),
+                None => {}
+            }
+            return;
+        }
+    }
+    for child in &node.children {
+        collect_scripts(child, visit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user_content::{MatchPattern, UserScript, UserStyleSheet};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    const PAGE: &str = r#"<html><head><title>First</title><link rel="icon" href="/icon.png">
+        <style>#box { background-color: #ff0000; height: 40px; } a { display: block; height: 20px; }</style>
+        </head><body><div id="box"></div><a id="next" href="about:blank">Next</a>
+        <script>document.getElementById('box').setAttribute('title', 'x');</script></body></html>"#;
+
+    #[test]
+    fn test_load_html_reports_changes_and_paints() {
+        let mut view = WebView::new(200.0, 100.0);
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let seen = events.clone();
+        view.on_event(move |event| seen.borrow_mut().push(event.clone()));
+
+        let url = Url::parse("https://example.test/page.html").unwrap();
+        view.load_html(PAGE, &url).unwrap();
+        assert_eq!(view.title(), "First");
+        assert_eq!(view.favicon().map(Url::as_str), Some("https://example.test/icon.png"));
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                WebViewEvent::UrlChanged(url.clone()),
+                WebViewEvent::TitleChanged("First".to_string()),
+                WebViewEvent::FaviconChanged(view.favicon().cloned()),
+                WebViewEvent::LoadFinished,
+            ]
+        );
+
+        // The script ran against the document
+        let document = view.document().unwrap();
+        let element = document.path_to_id("box").and_then(|path| document.descendant(&path));
+        assert_eq!(element.and_then(|e| e.element_data()?.get_attribute("title")), Some("x"));
+        assert_eq!(view.evaluate_js("1 + 2").unwrap(), JsValue::Number(3.0));
+
+        // The red box sits inside the body's margin
+        let shot = view.screenshot();
+        assert_eq!((shot.width, shot.height), (200, 100));
+        let pixel = |x: usize, y: usize| &shot.pixels[(y * 200 + x) * 4..(y * 200 + x) * 4 + 4];
+        assert_eq!(pixel(20, 20), &[255, 0, 0, 255]);
+        assert_eq!(pixel(2, 2), &[255, 255, 255, 255]);
+
+        // Clicking the link loads its target
+        events.borrow_mut().clear();
+        view.send_input(InputEvent::Click { x: 20.0, y: 55.0 }).unwrap();
+        assert_eq!(view.url().map(Url::as_str), Some("about:blank"));
+        assert_eq!(events.borrow()[0], WebViewEvent::UrlChanged(Url::parse("about:blank").unwrap()));
+        assert!(events.borrow().contains(&WebViewEvent::FaviconChanged(None)));
+    }
+
+    #[test]
+    fn test_user_content_applies_to_matching_pages() {
+        let mut view = WebView::new(100.0, 100.0);
+        let content = view.user_content_mut();
+        content.add_stylesheet(UserStyleSheet::new("box", "div { background-color: #00ff00; width: 10px; }"));
+        content.add_script(UserScript::new("start", "var order = ['start'];", RunAt::DocumentStart));
+        let elsewhere = MatchPattern::parse("https://other.test/*").unwrap();
+        content.add_script(UserScript::new("skipped", "order.push('skipped');", RunAt::DocumentEnd).with_match(elsewhere));
+        content.add_script(UserScript::new("end", "order.push('end');", RunAt::DocumentEnd));
+
+        let html = "<html><head><style>div { background-color: #ff0000; height: 20px; }</style></head>\
+                    <body><div></div><script>order.push('page');</script></body></html>";
+        view.load_html(html, &Url::parse("https://example.test/").unwrap()).unwrap();
+        assert_eq!(view.evaluate_js("order.join()").unwrap(), JsValue::String("start,page,end".to_string()));
+
+        // The page's color beats the user's; the width only the user set applies
+        let shot = view.screenshot();
+        let pixel = |x: usize, y: usize| &shot.pixels[(y * 100 + x) * 4..(y * 100 + x) * 4 + 4];
+        assert_eq!(pixel(12, 12), &[255, 0, 0, 255]);
+        assert_eq!(pixel(30, 12), &[255, 255, 255, 255]);
+    }
+}
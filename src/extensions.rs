@@ -0,0 +1,814 @@
+// Extensions - a small WebExtension host
+//
+// An extension is a directory with a `manifest.json`. Its background
+// scripts run on a thread of their own, in a script context that shares
+// nothing with pages or other extensions; they reach the browser through
+// the `chrome` namespace. The thread answers `storage` calls itself and
+// passes `tabs` calls to the browser as events, which the browser settles.
+// Content scripts run in the pages they match, each extension in an
+// isolated world: a script context of its own that sees the page's
+// document but not the page's globals. The host is also a request
+// interceptor: a request matching a `webRequest.onBeforeRequest`
+// listener's filter, on a site the extension has host permission for, is
+// shown to the background thread, and a blocking listener may cancel it.
+
+use crate::css::{CssParser, Stylesheet};
+use crate::js::{ExtensionCall, ExtensionWorld, JsContext};
+use crate::net::{InterceptedRequest, RequestInterceptor, ResourceType};
+use crate::ui::TabId;
+use crate::user_content::{MatchPattern, RunAt, UserScript, UserStyleSheet};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use url::Url;
+
+/// Longest a blocking `webRequest` listener may hold up a request; the
+/// request goes ahead if it has not answered by then
+const BLOCKING_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Error for messages nobody listens to, as Chrome words it
+const NO_RECEIVER: &str = "Could not establish connection. Receiving end does not exist.";
+
+/// Identifies an extension; the name of its directory
+pub type ExtensionId = String;
+
+/// The parts of `manifest.json` the host reads
+#[derive(Debug, Clone, Deserialize)]
+pub struct Manifest {
+    pub manifest_version: u32,
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    /// API permissions, and in version 2 host permissions too
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    /// Sites the extension may see requests to, in version 3
+    #[serde(default)]
+    pub host_permissions: Vec<String>,
+    #[serde(default)]
+    pub background: Option<BackgroundManifest>,
+    #[serde(default)]
+    pub content_scripts: Vec<ContentScriptManifest>,
+}
+
+/// Scripts run in the extension's background context
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BackgroundManifest {
+    #[serde(default)]
+    pub scripts: Vec<String>,
+    /// Version 3's service worker, run like a background script
+    pub service_worker: Option<String>,
+}
+
+/// Scripts and stylesheets added to the pages some patterns match
+#[derive(Debug, Clone, Deserialize)]
+pub struct ContentScriptManifest {
+    pub matches: Vec<String>,
+    #[serde(default)]
+    pub exclude_matches: Vec<String>,
+    #[serde(default)]
+    pub js: Vec<String>,
+    #[serde(default)]
+    pub css: Vec<String>,
+    /// `document_start`, `document_end` or `document_idle`
+    pub run_at: Option<String>,
+}
+
+/// Why an extension could not be loaded
+#[derive(Debug)]
+pub enum ExtensionError {
+    /// A file the extension needs could not be read
+    File { path: String, error: std::io::Error },
+    Manifest(serde_json::Error),
+    UnsupportedManifestVersion(u32),
+}
+
+impl fmt::Display for ExtensionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtensionError::File { path, error } => write!(f, "cannot read {}: {}", path, error),
+            ExtensionError::Manifest(e) => write!(f, "invalid manifest: {}", e),
+            ExtensionError::UnsupportedManifestVersion(version) => {
+                write!(f, "manifest version {} is not supported", version)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExtensionError {}
+
+/// A loaded extension
+#[derive(Debug, Clone)]
+pub struct Extension {
+    pub id: ExtensionId,
+    pub manifest: Manifest,
+    /// The manifest as written, for `runtime.getManifest()`
+    manifest_json: Value,
+    /// Source of the background scripts, in manifest order
+    background_scripts: Vec<String>,
+    content_scripts: Vec<UserScript>,
+    content_styles: Vec<UserStyleSheet>,
+    /// Sites the extension may see requests to
+    host_patterns: Vec<MatchPattern>,
+}
+
+impl Extension {
+    /// Load an extension from its directory
+    pub fn load(dir: &Path) -> Result<Self, ExtensionError> {
+        let id = dir.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        Self::parse(&id, |path| std::fs::read_to_string(dir.join(path)))
+    }
+
+    /// Read an extension whose files, `manifest.json` among them, come from `read`
+    pub fn parse(id: &str, read: impl Fn(&str) -> std::io::Result<String>) -> Result<Self, ExtensionError> {
+        let read_file = |path: &str| read(path).map_err(|error| ExtensionError::File { path: path.to_string(), error });
+        let manifest_json: Value = serde_json::from_str(&read_file("manifest.json")?).map_err(ExtensionError::Manifest)?;
+        let manifest: Manifest = serde_json::from_value(manifest_json.clone()).map_err(ExtensionError::Manifest)?;
+        if !matches!(manifest.manifest_version, 2 | 3) {
+            return Err(ExtensionError::UnsupportedManifestVersion(manifest.manifest_version));
+        }
+
+        let background = manifest.background.clone().unwrap_or_default();
+        let background_scripts =
+            background.scripts.iter().chain(&background.service_worker).map(|path| read_file(path)).collect::<Result<_, _>>()?;
+
+        let mut content_scripts = Vec::new();
+        let mut content_styles = Vec::new();
+        for entry in &manifest.content_scripts {
+            let matches: Vec<MatchPattern> = entry.matches.iter().filter_map(|p| MatchPattern::parse(p)).collect();
+            let excludes: Vec<MatchPattern> = entry.exclude_matches.iter().filter_map(|p| MatchPattern::parse(p)).collect();
+            // Without a pattern it understands the entry would run everywhere
+            if matches.is_empty() {
+                continue;
+            }
+            let run_at = entry.run_at.as_deref().and_then(|run_at| RunAt::parse(&run_at.replace('_', "-")));
+            for path in &entry.js {
+                let script = UserScript::new(id, &read_file(path)?, run_at.unwrap_or_default());
+                let script = matches.iter().cloned().fold(script, UserScript::with_match);
+                content_scripts.push(excludes.iter().cloned().fold(script, UserScript::with_exclude_match));
+            }
+            for path in &entry.css {
+                let sheet = UserStyleSheet::new(id, &read_file(path)?);
+                let sheet = matches.iter().cloned().fold(sheet, UserStyleSheet::with_match);
+                content_styles.push(excludes.iter().cloned().fold(sheet, UserStyleSheet::with_exclude_match));
+            }
+        }
+
+        // API permission names are not patterns and drop out here
+        let host_patterns = manifest.permissions.iter().chain(&manifest.host_permissions).filter_map(|p| MatchPattern::parse(p)).collect();
+        Ok(Self {
+            id: id.to_string(),
+            manifest,
+            manifest_json,
+            background_scripts,
+            content_scripts,
+            content_styles,
+            host_patterns,
+        })
+    }
+
+    /// Was an API permission, e.g. `storage`, asked for
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.manifest.permissions.iter().any(|p| p == permission)
+    }
+
+    /// May the extension see requests to a URL
+    pub fn has_host_permission(&self, url: &Url) -> bool {
+        self.host_patterns.iter().any(|pattern| pattern.matches(url))
+    }
+
+    /// The manifest as written
+    pub fn manifest_json(&self) -> &Value {
+        &self.manifest_json
+    }
+}
+
+/// The extensions' `storage.local` areas, kept on disk when given a directory
+#[derive(Debug, Default)]
+pub struct ExtensionStorage {
+    areas: HashMap<ExtensionId, Map<String, Value>>,
+    /// Holds one `<id>.json` file per extension
+    dir: Option<PathBuf>,
+}
+
+impl ExtensionStorage {
+    /// Storage kept in memory only
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Storage kept in a directory, created if missing
+    pub fn open(dir: &Path) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        Ok(Self { areas: HashMap::new(), dir: Some(dir.to_path_buf()) })
+    }
+
+    fn area(&mut self, extension: &str) -> &mut Map<String, Value> {
+        let dir = self.dir.as_ref();
+        self.areas.entry(extension.to_string()).or_insert_with(|| {
+            let file = dir.map(|dir| dir.join(format!("{}.json", extension)));
+            let stored = file.and_then(|file| std::fs::read_to_string(file).ok());
+            stored.and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default()
+        })
+    }
+
+    fn save(&self, extension: &str) -> Result<(), String> {
+        let (Some(dir), Some(area)) = (&self.dir, self.areas.get(extension)) else {
+            return Ok(());
+        };
+        let json = serde_json::to_string(area).map_err(|e| e.to_string())?;
+        std::fs::write(dir.join(format!("{}.json", extension)), json).map_err(|e| e.to_string())
+    }
+
+    /// Answer a `storage.get`, `set`, `remove` or `clear` call
+    pub fn call(&mut self, extension: &str, method: &str, args: &Value) -> Result<Value, String> {
+        let area = self.area(extension);
+        let names = |keys: &Value| -> Vec<String> {
+            match keys {
+                Value::String(key) => vec![key.clone()],
+                Value::Array(keys) => keys.iter().filter_map(Value::as_str).map(str::to_string).collect(),
+                _ => Vec::new(),
+            }
+        };
+        match method {
+            "storage.get" => {
+                let keys = &args["keys"];
+                let items: Map<String, Value> = match keys {
+                    Value::Null => area.clone(),
+                    // Keys with defaults for those not stored
+                    Value::Object(defaults) => defaults
+                        .iter()
+                        .map(|(key, default)| (key.clone(), area.get(key).unwrap_or(default).clone()))
+                        .collect(),
+                    keys => names(keys).into_iter().filter_map(|key| Some((key.clone(), area.get(&key)?.clone()))).collect(),
+                };
+                return Ok(Value::Object(items));
+            }
+            "storage.set" => {
+                let Value::Object(items) = &args["items"] else {
+                    return Err("storage.set takes an object of items".to_string());
+                };
+                area.extend(items.clone());
+            }
+            "storage.remove" => {
+                for key in names(&args["keys"]) {
+                    area.remove(&key);
+                }
+            }
+            "storage.clear" => area.clear(),
+            _ => return Err(format!("{} is not supported", method)),
+        }
+        self.save(extension)?;
+        Ok(Value::Null)
+    }
+}
+
+/// Something the browser should act on for an extension
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExtensionEvent {
+    /// A background script called a `tabs` method; answer with `ExtensionHost::settle`
+    Call { extension: ExtensionId, call: ExtensionCall },
+    /// A background script answered a content script's `runtime.sendMessage`;
+    /// settle the content script's call with it
+    Reply { extension: ExtensionId, tab: TabId, call: u32, result: Result<Value, String> },
+    /// A background script threw
+    Error { extension: ExtensionId, message: String },
+}
+
+/// A `webRequest.onBeforeRequest` listener and the requests it wants to see
+#[derive(Debug, Clone)]
+struct RequestFilter {
+    extension: ExtensionId,
+    listener: u32,
+    urls: Vec<MatchPattern>,
+    /// Request types, e.g. `image`; empty for all
+    types: Vec<String>,
+    blocking: bool,
+    /// The extension's host permissions
+    hosts: Vec<MatchPattern>,
+}
+
+impl RequestFilter {
+    fn matches(&self, url: &Url, request_type: &str) -> bool {
+        (self.types.is_empty() || self.types.iter().any(|t| t == request_type))
+            && self.urls.iter().any(|pattern| pattern.matches(url))
+            && self.hosts.iter().any(|pattern| pattern.matches(url))
+    }
+}
+
+/// Work for a background thread
+enum Command {
+    WebRequest { listener: u32, details: Value, reply: Option<Sender<bool>> },
+    /// The browser's answer to a `tabs` call
+    Settle { call: u32, result: Result<Value, String> },
+    /// A content script's message, answered to its call in its tab
+    Message { message: Value, sender: Value, tab: TabId, call: u32 },
+    Shutdown,
+}
+
+/// What the host shares with its request interceptor and background threads
+#[derive(Default)]
+struct Shared {
+    filters: RwLock<Vec<RequestFilter>>,
+    backgrounds: Mutex<HashMap<ExtensionId, Sender<Command>>>,
+}
+
+impl Shared {
+    fn send(&self, extension: &str, command: Command) -> bool {
+        let backgrounds = self.backgrounds.lock().unwrap_or_else(|e| e.into_inner());
+        backgrounds.get(extension).is_some_and(|background| background.send(command).is_ok())
+    }
+}
+
+/// Runs `webRequest` listeners from the resource loader
+struct WebRequestInterceptor(Arc<Shared>);
+
+impl RequestInterceptor for WebRequestInterceptor {
+    fn should_block(&self, request: &InterceptedRequest<'_>) -> bool {
+        let request_type = match request.resource_type {
+            ResourceType::Html if request.url == request.first_party => "main_frame",
+            ResourceType::Html => "sub_frame",
+            ResourceType::Css => "stylesheet",
+            ResourceType::Image => "image",
+            ResourceType::Font => "font",
+            ResourceType::Other => "other",
+        };
+        // Listeners run without the lock held, so they may add or remove filters
+        let filters: Vec<RequestFilter> = {
+            let filters = self.0.filters.read().unwrap_or_else(|e| e.into_inner());
+            filters.iter().filter(|filter| filter.matches(request.url, request_type)).cloned().collect()
+        };
+        let details = json!({
+            "url": request.url.as_str(),
+            "method": "GET",
+            "type": request_type,
+            "initiator": request.first_party.origin().ascii_serialization(),
+            "thirdParty": request.is_third_party(),
+        });
+        for filter in filters {
+            let (reply, answer) = match filter.blocking {
+                true => {
+                    let (reply, answer) = mpsc::channel();
+                    (Some(reply), Some(answer))
+                }
+                false => (None, None),
+            };
+            let command = Command::WebRequest { listener: filter.listener, details: details.clone(), reply };
+            if !self.0.send(&filter.extension, command) {
+                continue;
+            }
+            if answer.is_some_and(|answer| answer.recv_timeout(BLOCKING_TIMEOUT) == Ok(true)) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// An extension's background context, on its own thread
+struct BackgroundWorker {
+    extension: Extension,
+    context: JsContext,
+    shared: Arc<Shared>,
+    storage: Arc<Mutex<ExtensionStorage>>,
+    events: Sender<ExtensionEvent>,
+    /// Content script calls waiting on an answer to their message, by reply id
+    replies: HashMap<u32, (TabId, u32)>,
+    next_reply: u32,
+}
+
+impl BackgroundWorker {
+    /// Start the background scripts and serve commands until shut down
+    fn run(
+        extension: Extension,
+        shared: Arc<Shared>,
+        storage: Arc<Mutex<ExtensionStorage>>,
+        events: Sender<ExtensionEvent>,
+        commands: Receiver<Command>,
+    ) {
+        let (id, manifest) = (extension.id.clone(), extension.manifest_json.clone());
+        let mut worker = Self { extension, context: JsContext::new(), shared, storage, events, replies: HashMap::new(), next_reply: 1 };
+        worker.serve(&id, &manifest, commands);
+    }
+
+    fn serve(&mut self, id: &str, manifest: &Value, commands: Receiver<Command>) {
+        if let Err(e) = self.context.install_extension_api(id, manifest, ExtensionWorld::Background) {
+            self.error(e.to_string());
+            return;
+        }
+        for source in self.extension.background_scripts.clone() {
+            if let Err(e) = self.context.run_page_script(&source) {
+                self.error(e.to_string());
+            }
+        }
+        self.service_calls();
+        while let Ok(command) = commands.recv() {
+            match command {
+                Command::WebRequest { listener, details, reply } => {
+                    let cancel = self.context.dispatch_web_request(listener, &details).unwrap_or_else(|e| {
+                        self.error(e.to_string());
+                        false
+                    });
+                    if let Some(reply) = reply {
+                        let _ = reply.send(cancel);
+                    }
+                }
+                Command::Settle { call, result } => {
+                    if let Err(e) = self.context.settle_extension_call(call, result.as_ref().map_err(String::as_str)) {
+                        self.error(e.to_string());
+                    }
+                }
+                Command::Message { message, sender, tab, call } => {
+                    let reply = self.next_reply;
+                    self.next_reply += 1;
+                    self.replies.insert(reply, (tab, call));
+                    if let Err(e) = self.context.dispatch_extension_message(&message, &sender, reply) {
+                        self.error(e.to_string());
+                    }
+                }
+                Command::Shutdown => break,
+            }
+            self.service_calls();
+        }
+    }
+
+    fn error(&self, message: String) {
+        let _ = self.events.send(ExtensionEvent::Error { extension: self.extension.id.clone(), message });
+    }
+
+    fn settle(&mut self, call: u32, result: Result<Value, String>) {
+        if let Err(e) = self.context.settle_extension_call(call, result.as_ref().map_err(String::as_str)) {
+            self.error(e.to_string());
+        }
+    }
+
+    /// Answer or pass on the calls the scripts made, until settling them makes no more
+    fn service_calls(&mut self) {
+        loop {
+            let calls = match self.context.take_extension_calls() {
+                Ok(calls) if !calls.is_empty() => calls,
+                Ok(_) => return,
+                Err(e) => return self.error(e.to_string()),
+            };
+            for call in calls {
+                self.service_call(call);
+            }
+        }
+    }
+
+    fn service_call(&mut self, call: ExtensionCall) {
+        let id = self.extension.id.clone();
+        match call.method.as_str() {
+            method if method.starts_with("storage.") => {
+                let result = match self.extension.has_permission("storage") {
+                    true => self.storage.lock().unwrap_or_else(|e| e.into_inner()).call(&id, method, &call.args),
+                    false => Err("The \"storage\" permission is required".to_string()),
+                };
+                self.settle(call.id, result);
+            }
+            "webRequest.addListener" => {
+                let blocking = call.args["blocking"].as_bool().unwrap_or(false);
+                let allowed = self.extension.has_permission("webRequest")
+                    && (!blocking || self.extension.has_permission("webRequestBlocking"));
+                if !allowed {
+                    return self.error("webRequest listeners need the \"webRequest\" and, to block, \"webRequestBlocking\" permissions".to_string());
+                }
+                let strings = |value: &Value| -> Vec<String> {
+                    value.as_array().into_iter().flatten().filter_map(Value::as_str).map(str::to_string).collect()
+                };
+                let filter = RequestFilter {
+                    extension: id,
+                    listener: call.args["listener"].as_u64().unwrap_or_default() as u32,
+                    urls: strings(&call.args["urls"]).iter().filter_map(|p| MatchPattern::parse(p)).collect(),
+                    types: strings(&call.args["types"]),
+                    blocking,
+                    hosts: self.extension.host_patterns.clone(),
+                };
+                self.shared.filters.write().unwrap_or_else(|e| e.into_inner()).push(filter);
+            }
+            "webRequest.removeListener" => {
+                let listener = call.args["listener"].as_u64().unwrap_or_default() as u32;
+                let mut filters = self.shared.filters.write().unwrap_or_else(|e| e.into_inner());
+                filters.retain(|filter| filter.extension != id || filter.listener != listener);
+            }
+            "runtime.reply" => {
+                let reply = call.args["reply"].as_u64().unwrap_or_default() as u32;
+                let Some((tab, content_call)) = self.replies.remove(&reply) else {
+                    return;
+                };
+                let result = match call.args["error"].as_str() {
+                    Some(error) => Err(error.to_string()),
+                    None => Ok(call.args["value"].clone()),
+                };
+                let _ = self.events.send(ExtensionEvent::Reply { extension: id, tab, call: content_call, result });
+            }
+            // There are no other extension pages to talk to
+            "runtime.sendMessage" => self.settle(call.id, Err(NO_RECEIVER.to_string())),
+            method if method.starts_with("tabs.") => {
+                let _ = self.events.send(ExtensionEvent::Call { extension: id, call });
+            }
+            method => {
+                let error = format!("{} is not supported", method);
+                self.settle(call.id, Err(error));
+            }
+        }
+    }
+}
+
+/// The installed extensions and their background threads
+pub struct ExtensionHost {
+    extensions: Vec<Extension>,
+    threads: HashMap<ExtensionId, JoinHandle<()>>,
+    shared: Arc<Shared>,
+    storage: Arc<Mutex<ExtensionStorage>>,
+    events: Receiver<ExtensionEvent>,
+    event_sender: Sender<ExtensionEvent>,
+}
+
+impl ExtensionHost {
+    /// Create a host whose extensions' storage is kept in memory
+    pub fn new() -> Self {
+        Self::with_storage(ExtensionStorage::new())
+    }
+
+    pub fn with_storage(storage: ExtensionStorage) -> Self {
+        let (event_sender, events) = mpsc::channel();
+        Self {
+            extensions: Vec::new(),
+            threads: HashMap::new(),
+            shared: Arc::new(Shared::default()),
+            storage: Arc::new(Mutex::new(storage)),
+            events,
+            event_sender,
+        }
+    }
+
+    /// Install an extension, replacing one with its id, and start its background scripts
+    pub fn install(&mut self, extension: Extension) {
+        self.uninstall(&extension.id);
+        if !extension.background_scripts.is_empty() {
+            let (commands, receiver) = mpsc::channel();
+            let (background, shared, storage, events) =
+                (extension.clone(), self.shared.clone(), self.storage.clone(), self.event_sender.clone());
+            // The context is made on the thread, as script contexts cannot move between threads
+            let spawned = std::thread::Builder::new().name(format!("extension {}", extension.id)).spawn(move || {
+                BackgroundWorker::run(background, shared, storage, events, receiver);
+            });
+            match spawned {
+                Ok(thread) => {
+                    self.threads.insert(extension.id.clone(), thread);
+                    self.shared.backgrounds.lock().unwrap_or_else(|e| e.into_inner()).insert(extension.id.clone(), commands);
+                }
+                Err(e) => {
+                    let _ = self.event_sender.send(ExtensionEvent::Error { extension: extension.id.clone(), message: e.to_string() });
+                }
+            }
+        }
+        self.extensions.push(extension);
+    }
+
+    /// Remove an extension and stop its background scripts, returning whether it was installed
+    pub fn uninstall(&mut self, id: &str) -> bool {
+        self.shared.filters.write().unwrap_or_else(|e| e.into_inner()).retain(|filter| filter.extension != id);
+        if self.shared.send(id, Command::Shutdown) {
+            self.shared.backgrounds.lock().unwrap_or_else(|e| e.into_inner()).remove(id);
+        }
+        if let Some(thread) = self.threads.remove(id) {
+            let _ = thread.join();
+        }
+        let before = self.extensions.len();
+        self.extensions.retain(|extension| extension.id != id);
+        self.extensions.len() != before
+    }
+
+    pub fn extensions(&self) -> &[Extension] {
+        &self.extensions
+    }
+
+    pub fn extension(&self, id: &str) -> Option<&Extension> {
+        self.extensions.iter().find(|extension| extension.id == id)
+    }
+
+    /// The request interceptor that runs the extensions' `webRequest` listeners
+    pub fn interceptor(&self) -> Arc<dyn RequestInterceptor> {
+        Arc::new(WebRequestInterceptor(self.shared.clone()))
+    }
+
+    /// Content scripts to run in a page at a point of its load, with their extension
+    pub fn content_scripts(&self, url: &Url, run_at: RunAt) -> Vec<(&str, &str)> {
+        self.extensions
+            .iter()
+            .flat_map(|extension| extension.content_scripts.iter().map(move |script| (extension, script)))
+            .filter(|(_, script)| script.run_at == run_at && script.applies_to(url))
+            .map(|(extension, script)| (extension.id.as_str(), script.source.as_str()))
+            .collect()
+    }
+
+    /// The content stylesheets applying to a page, parsed; they are author
+    /// styles, added after the page's own
+    pub fn stylesheet(&self, url: &Url) -> Stylesheet {
+        let mut stylesheet = Stylesheet::new(Vec::new());
+        let sheets = self.extensions.iter().flat_map(|extension| &extension.content_styles);
+        for sheet in sheets.filter(|sheet| sheet.applies_to(url)) {
+            stylesheet.append(CssParser::parse(&sheet.css));
+        }
+        stylesheet
+    }
+
+    /// A new isolated world for an extension's content scripts
+    pub fn content_world(&self, extension: &str) -> Option<JsContext> {
+        let extension = self.extension(extension)?;
+        let mut world = JsContext::new();
+        world.install_extension_api(&extension.id, &extension.manifest_json, ExtensionWorld::Content).ok()?;
+        Some(world)
+    }
+
+    /// Handle a call a content script made from a page
+    ///
+    /// Returns the result to settle the call with now; a message to the
+    /// background scripts is answered later by an `ExtensionEvent::Reply`.
+    pub fn content_call(&mut self, extension: &str, tab: TabId, page: &Url, call: ExtensionCall) -> Option<Result<Value, String>> {
+        let has_storage = self.extension(extension)?.has_permission("storage");
+        match call.method.as_str() {
+            method if method.starts_with("storage.") => Some(match has_storage {
+                true => self.storage.lock().unwrap_or_else(|e| e.into_inner()).call(extension, method, &call.args),
+                false => Err("The \"storage\" permission is required".to_string()),
+            }),
+            "runtime.sendMessage" => {
+                let sender = json!({ "id": extension, "url": page.as_str(), "tab": { "id": tab, "url": page.as_str() } });
+                let message = Command::Message { message: call.args["message"].clone(), sender, tab, call: call.id };
+                (!self.shared.send(extension, message)).then(|| Err(NO_RECEIVER.to_string()))
+            }
+            // A content script answering a background script's `tabs.sendMessage`
+            "runtime.reply" => {
+                let reply = call.args["reply"].as_u64().unwrap_or_default() as u32;
+                let result = match call.args["error"].as_str() {
+                    Some(error) => Err(error.to_string()),
+                    None => Ok(call.args["value"].clone()),
+                };
+                self.settle(extension, reply, result);
+                None
+            }
+            method => Some(Err(format!("{} is not supported", method))),
+        }
+    }
+
+    /// Answer a background script's call
+    pub fn settle(&self, extension: &str, call: u32, result: Result<Value, String>) {
+        self.shared.send(extension, Command::Settle { call, result });
+    }
+
+    /// Drain what the background scripts want from the browser
+    pub fn take_events(&self) -> Vec<ExtensionEvent> {
+        self.events.try_iter().collect()
+    }
+}
+
+impl Default for ExtensionHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for ExtensionHost {
+    fn drop(&mut self) {
+        let ids: Vec<ExtensionId> = self.extensions.iter().map(|extension| extension.id.clone()).collect();
+        for id in ids {
+            self.uninstall(&id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn extension(id: &str, manifest: &str, files: &[(&str, &str)]) -> Result<Extension, ExtensionError> {
+        let files: HashMap<String, String> = files.iter().map(|(name, text)| (name.to_string(), text.to_string())).collect();
+        let manifest = manifest.to_string();
+        Extension::parse(id, move |path| match path {
+            "manifest.json" => Ok(manifest.clone()),
+            path => files.get(path).cloned().ok_or_else(|| std::io::ErrorKind::NotFound.into()),
+        })
+    }
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_manifest_and_content_scripts() {
+        let privacy = extension(
+            "privacy",
+            r#"{ "manifest_version": 2, "name": "Privacy", "version": "1.0",
+                 "permissions": ["storage", "https://*.example.com/*"],
+                 "content_scripts": [
+                     { "matches": ["https://*.example.com/*"], "exclude_matches": ["https://example.com/admin/*"],
+                       "js": ["start.js"], "css": ["hide.css"], "run_at": "document_start" },
+                     { "matches": ["<all_urls>"], "js": ["end.js"] },
+                     { "matches": ["not a pattern"], "js": ["end.js"] }
+                 ] }"#,
+            &[("start.js", "1"), ("end.js", "2"), ("hide.css", ".ad { display: none; }")],
+        )
+        .unwrap();
+        assert_eq!(privacy.manifest.name, "Privacy");
+        assert!(privacy.has_permission("storage") && !privacy.has_permission("tabs"));
+        assert!(privacy.has_host_permission(&url("https://www.example.com/")));
+        assert!(!privacy.has_host_permission(&url("https://other.org/")));
+
+        let mut host = ExtensionHost::new();
+        host.install(privacy);
+        let page = url("https://www.example.com/news");
+        assert_eq!(host.content_scripts(&page, RunAt::DocumentStart), [("privacy", "1")]);
+        assert_eq!(host.content_scripts(&page, RunAt::DocumentEnd), [("privacy", "2")]);
+        assert!(host.content_scripts(&url("https://example.com/admin/x"), RunAt::DocumentStart).is_empty());
+        assert_eq!(host.content_scripts(&url("https://other.org/"), RunAt::DocumentEnd).len(), 1);
+        assert_eq!(host.stylesheet(&page).rules.len(), 1);
+        assert!(host.stylesheet(&url("https://other.org/")).rules.is_empty());
+
+        assert!(host.uninstall("privacy"));
+        assert!(host.extensions().is_empty());
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let missing = extension("a", r#"{ "manifest_version": 3, "name": "A", "version": "1",
+            "background": { "service_worker": "sw.js" } }"#, &[]);
+        assert!(matches!(missing, Err(ExtensionError::File { ref path, .. }) if path == "sw.js"));
+        let old = extension("b", r#"{ "manifest_version": 1, "name": "B", "version": "1" }"#, &[]);
+        assert!(matches!(old, Err(ExtensionError::UnsupportedManifestVersion(1))));
+        assert!(matches!(extension("c", "{", &[]), Err(ExtensionError::Manifest(_))));
+    }
+
+    #[test]
+    fn test_storage_calls() {
+        let mut storage = ExtensionStorage::new();
+        let set = storage.call("a", "storage.set", &json!({ "items": { "x": 1, "y": [2] } }));
+        assert_eq!(set, Ok(Value::Null));
+        assert_eq!(storage.call("a", "storage.get", &json!({ "keys": "x" })), Ok(json!({ "x": 1 })));
+        assert_eq!(storage.call("a", "storage.get", &json!({ "keys": null })), Ok(json!({ "x": 1, "y": [2] })));
+        assert_eq!(
+            storage.call("a", "storage.get", &json!({ "keys": { "x": 0, "z": "default" } })),
+            Ok(json!({ "x": 1, "z": "default" }))
+        );
+        // Areas are per extension
+        assert_eq!(storage.call("b", "storage.get", &json!({ "keys": ["x"] })), Ok(json!({})));
+        storage.call("a", "storage.remove", &json!({ "keys": ["x"] })).unwrap();
+        assert_eq!(storage.call("a", "storage.get", &json!({ "keys": ["x", "y"] })), Ok(json!({ "y": [2] })));
+        storage.call("a", "storage.clear", &json!({})).unwrap();
+        assert_eq!(storage.call("a", "storage.get", &json!({ "keys": null })), Ok(json!({})));
+        assert!(storage.call("a", "storage.set", &json!({ "items": 3 })).is_err());
+    }
+
+    #[test]
+    fn test_background_blocks_requests_and_calls_tabs() {
+        let blocker = extension(
+            "blocker",
+            r#"{ "manifest_version": 2, "name": "Blocker", "version": "1",
+                 "permissions": ["webRequest", "webRequestBlocking", "<all_urls>"],
+                 "background": { "scripts": ["background.js"] } }"#,
+            &[(
+                "background.js",
+                "chrome.webRequest.onBeforeRequest.addListener(function (details) {
+                     return { cancel: details.url.indexOf('tracker') >= 0 };
+                 }, { urls: ['<all_urls>'], types: ['image'] }, ['blocking']);
+                 chrome.tabs.query({ active: true });",
+            )],
+        )
+        .unwrap();
+        let mut host = ExtensionHost::new();
+        host.install(blocker);
+
+        // Wait for the background script to register its listener
+        let start = Instant::now();
+        let call = loop {
+            if let Some(ExtensionEvent::Call { call, .. }) = host.take_events().into_iter().next() {
+                break call;
+            }
+            assert!(start.elapsed() < Duration::from_secs(5), "the background script did not start");
+            std::thread::sleep(Duration::from_millis(5));
+        };
+        assert_eq!(call.method, "tabs.query");
+
+        let interceptor = host.interceptor();
+        let page = url("https://news.example/");
+        let blocks = |request: &str, resource_type| {
+            let request = url(request);
+            interceptor.should_block(&InterceptedRequest { url: &request, first_party: &page, resource_type })
+        };
+        assert!(blocks("https://cdn.example/tracker.png", ResourceType::Image));
+        assert!(!blocks("https://cdn.example/logo.png", ResourceType::Image));
+        assert!(!blocks("https://cdn.example/tracker.css", ResourceType::Css));
+
+        host.uninstall("blocker");
+        assert!(!blocks("https://cdn.example/tracker.png", ResourceType::Image));
+    }
+}
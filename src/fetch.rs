@@ -139,6 +139,33 @@ pub enum Method {
     Options,
 }
 
+impl Method {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_ascii_uppercase().as_str() {
+            "GET" => Some(Method::Get),
+            "POST" => Some(Method::Post),
+            "PUT" => Some(Method::Put),
+            "DELETE" => Some(Method::Delete),
+            "PATCH" => Some(Method::Patch),
+            "HEAD" => Some(Method::Head),
+            "OPTIONS" => Some(Method::Options),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Method::Get => "GET",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+            Method::Patch => "PATCH",
+            Method::Head => "HEAD",
+            Method::Options => "OPTIONS",
+        }
+    }
+}
+
 /// Request mode
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RequestMode {
@@ -241,6 +268,16 @@ impl Response {
         Ok(self.body.clone())
     }
     
+    /// Body bytes, without consuming the body
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+    
+    /// Has the body been read
+    pub fn body_used(&self) -> bool {
+        self.body_used
+    }
+    
     /// Clone the response
     pub fn clone_response(&self) -> Result<Self, FetchError> {
         if self.body_used {
@@ -1,7 +1,10 @@
 // HTML Forms and Input Handling
 
-use crate::dom::{Node, NodeType};
+use crate::dom::{ElementData, Node, NodeType};
+use crate::js::{EventType, JsContext, JsError};
+use regex::Regex;
 use std::collections::HashMap;
+use std::sync::OnceLock;
 
 /// Form input types
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -9,6 +12,7 @@ pub enum InputType {
     Text,
     Password,
     Email,
+    Url,
     Number,
     Checkbox,
     Radio,
@@ -23,6 +27,7 @@ impl InputType {
         match s.to_lowercase().as_str() {
             "password" => InputType::Password,
             "email" => InputType::Email,
+            "url" => InputType::Url,
             "number" => InputType::Number,
             "checkbox" => InputType::Checkbox,
             "radio" => InputType::Radio,
@@ -44,6 +49,15 @@ pub struct InputState {
     pub readonly: bool,
     pub placeholder: Option<String>,
     pub max_length: Option<usize>,
+    pub required: bool,
+    /// Regular expression the whole value must match
+    pub pattern: Option<String>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Allowed value granularity (`None` means any value is allowed)
+    pub step: Option<f64>,
+    /// Message set via `setCustomValidity()`
+    pub custom_validity: Option<String>,
 }
 
 impl Default for InputState {
@@ -56,10 +70,43 @@ impl Default for InputState {
             readonly: false,
             placeholder: None,
             max_length: None,
+            required: false,
+            pattern: None,
+            min: None,
+            max: None,
+            step: None,
+            custom_validity: None,
         }
     }
 }
 
+/// Validity flags for a form control (mirrors the DOM `ValidityState`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ValidityState {
+    pub value_missing: bool,
+    pub type_mismatch: bool,
+    pub pattern_mismatch: bool,
+    pub range_underflow: bool,
+    pub range_overflow: bool,
+    pub step_mismatch: bool,
+    pub bad_input: bool,
+    pub custom_error: bool,
+}
+
+impl ValidityState {
+    /// True if no constraint is violated
+    pub fn valid(&self) -> bool {
+        *self == ValidityState::default()
+    }
+}
+
+/// A control that failed constraint validation
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidControl {
+    pub name: String,
+    pub message: String,
+}
+
 impl InputState {
     /// Create from DOM attributes
     pub fn from_attributes(attrs: &HashMap<String, String>) -> Self {
@@ -76,6 +123,18 @@ impl InputState {
         let max_length = attrs
             .get("maxlength")
             .and_then(|s| s.parse().ok());
+        let required = attrs.contains_key("required");
+        let pattern = attrs.get("pattern").cloned();
+        let min = attrs.get("min").and_then(|s| s.trim().parse().ok());
+        let max = attrs.get("max").and_then(|s| s.trim().parse().ok());
+
+        // Number inputs default to a step of 1; "any" disables step checking
+        let step = match attrs.get("step").map(|s| s.trim()) {
+            Some(s) if s.eq_ignore_ascii_case("any") => None,
+            Some(s) => s.parse().ok().filter(|v: &f64| *v > 0.0).or(Some(1.0)),
+            None if input_type == InputType::Number => Some(1.0),
+            None => None,
+        };
 
         Self {
             input_type,
@@ -85,6 +144,12 @@ impl InputState {
             readonly,
             placeholder,
             max_length,
+            required,
+            pattern,
+            min,
+            max,
+            step,
+            custom_validity: None,
         }
     }
 
@@ -104,6 +169,25 @@ impl InputState {
         true
     }
 
+    /// Does this control accept typed or pasted text
+    pub fn accepts_text(&self) -> bool {
+        matches!(
+            self.input_type,
+            InputType::Text | InputType::Password | InputType::Email | InputType::Url | InputType::Number
+        )
+    }
+
+    /// Append text at the end of the value (e.g., paste)
+    ///
+    /// Line breaks are stripped since inputs are single-line.
+    pub fn insert_text(&mut self, text: &str) -> bool {
+        if !self.accepts_text() {
+            return false;
+        }
+        let text: String = text.chars().filter(|c| *c != '\n' && *c != '\r').collect();
+        self.set_value(format!("{}{}", self.value, text))
+    }
+
     /// Toggle checked state (for checkbox/radio)
     pub fn toggle_checked(&mut self) -> bool {
         if self.disabled {
@@ -122,6 +206,328 @@ impl InputState {
             _ => false,
         }
     }
+
+    /// Set a custom validation message; an empty message clears it
+    pub fn set_custom_validity(&mut self, message: &str) {
+        self.custom_validity = if message.is_empty() {
+            None
+        } else {
+            Some(message.to_string())
+        };
+    }
+
+    /// Check if the control is subject to constraint validation
+    pub fn will_validate(&self) -> bool {
+        !self.disabled
+            && !self.readonly
+            && !matches!(
+                self.input_type,
+                InputType::Hidden | InputType::Submit | InputType::Button
+            )
+    }
+
+    /// Evaluate all constraints against the current value
+    pub fn validity(&self) -> ValidityState {
+        let mut validity = ValidityState::default();
+        if !self.will_validate() {
+            return validity;
+        }
+
+        validity.custom_error = self.custom_validity.is_some();
+
+        if matches!(self.input_type, InputType::Checkbox | InputType::Radio) {
+            validity.value_missing = self.required && !self.checked;
+            return validity;
+        }
+
+        if self.value.is_empty() {
+            validity.value_missing = self.required;
+            return validity;
+        }
+
+        match self.input_type {
+            InputType::Email => validity.type_mismatch = !is_valid_email(&self.value),
+            InputType::Url => validity.type_mismatch = url::Url::parse(&self.value).is_err(),
+            InputType::Number => match self.value.trim().parse::<f64>() {
+                Ok(number) if number.is_finite() => {
+                    validity.range_underflow = self.min.is_some_and(|min| number < min);
+                    validity.range_overflow = self.max.is_some_and(|max| number > max);
+                    validity.step_mismatch = self.step.is_some_and(|step| {
+                        let steps = (number - self.min.unwrap_or(0.0)) / step;
+                        (steps - steps.round()).abs() > 1e-9
+                    });
+                }
+                _ => validity.bad_input = true,
+            },
+            _ => {}
+        }
+
+        if let Some(ref pattern) = self.pattern {
+            // Patterns must match the entire value; invalid patterns are ignored
+            if let Ok(re) = Regex::new(&format!("^(?:{})$", pattern)) {
+                validity.pattern_mismatch = !re.is_match(&self.value);
+            }
+        }
+
+        validity
+    }
+
+    /// Equivalent of `checkValidity()`
+    pub fn check_validity(&self) -> bool {
+        self.validity().valid()
+    }
+
+    /// Equivalent of `validationMessage`; empty when the control is valid
+    pub fn validation_message(&self) -> String {
+        let validity = self.validity();
+
+        if validity.custom_error {
+            return self.custom_validity.clone().unwrap_or_default();
+        }
+        if validity.value_missing {
+            return match self.input_type {
+                InputType::Checkbox => "Please check this box if you want to proceed.".to_string(),
+                InputType::Radio => "Please select one of these options.".to_string(),
+                _ => "Please fill out this field.".to_string(),
+            };
+        }
+        if validity.type_mismatch {
+            return match self.input_type {
+                InputType::Email => "Please enter an email address.".to_string(),
+                _ => "Please enter a URL.".to_string(),
+            };
+        }
+        if validity.bad_input {
+            return "Please enter a number.".to_string();
+        }
+        if validity.range_underflow {
+            return format!("Value must be greater than or equal to {}.", self.min.unwrap_or_default());
+        }
+        if validity.range_overflow {
+            return format!("Value must be less than or equal to {}.", self.max.unwrap_or_default());
+        }
+        if validity.step_mismatch {
+            return "Please enter a valid value.".to_string();
+        }
+        if validity.pattern_mismatch {
+            return "Please match the requested format.".to_string();
+        }
+
+        String::new()
+    }
+
+    /// Write the current state back onto the element's attributes
+    pub fn sync_to_element(&self, elem: &mut ElementData) {
+        elem.attributes.insert("value".to_string(), self.value.clone());
+
+        if matches!(self.input_type, InputType::Checkbox | InputType::Radio) {
+            if self.checked {
+                elem.attributes.insert("checked".to_string(), String::new());
+            } else {
+                elem.attributes.remove("checked");
+            }
+        }
+    }
+}
+
+/// A group of radio buttons sharing the same name
+///
+/// At most one member of a group can be checked at a time.
+#[derive(Debug, Clone, Default)]
+pub struct RadioGroup {
+    pub name: String,
+    pub members: Vec<InputState>,
+}
+
+impl RadioGroup {
+    /// Create an empty radio group
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            members: Vec::new(),
+        }
+    }
+
+    /// Add a radio button to the group, returning its index
+    ///
+    /// If the new member is checked, any previously checked member is cleared.
+    pub fn add(&mut self, input: InputState) -> usize {
+        if input.checked {
+            for member in &mut self.members {
+                member.checked = false;
+            }
+        }
+        self.members.push(input);
+        self.members.len() - 1
+    }
+
+    /// Check the member at `index`, unchecking all others
+    pub fn check(&mut self, index: usize) -> bool {
+        match self.members.get(index) {
+            Some(member) if !member.disabled => {}
+            _ => return false,
+        }
+
+        for (i, member) in self.members.iter_mut().enumerate() {
+            member.checked = i == index;
+        }
+        true
+    }
+
+    /// Index of the currently checked member
+    pub fn checked_index(&self) -> Option<usize> {
+        self.members.iter().position(|m| m.checked)
+    }
+
+    /// Value of the currently checked member
+    pub fn value(&self) -> Option<&str> {
+        self.checked_index().map(|i| self.members[i].value.as_str())
+    }
+
+    /// Clear the checked state of every member
+    pub fn clear(&mut self) {
+        for member in &mut self.members {
+            member.checked = false;
+        }
+    }
+}
+
+/// A single `<option>` inside a `<select>`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectOption {
+    pub value: String,
+    pub label: String,
+    pub disabled: bool,
+}
+
+/// Select element state
+#[derive(Debug, Clone, Default)]
+pub struct SelectState {
+    pub options: Vec<SelectOption>,
+    pub selected_index: Option<usize>,
+    pub disabled: bool,
+}
+
+impl SelectState {
+    /// Create from a `<select>` node and its `<option>` children
+    pub fn from_node(node: &Node) -> Self {
+        let disabled = node
+            .element_data()
+            .map(|e| e.attributes.contains_key("disabled"))
+            .unwrap_or(false);
+
+        let mut options = Vec::new();
+        let mut selected_index = None;
+
+        for child in &node.children {
+            let elem = match child.element_data() {
+                Some(elem) if elem.tag_name == "option" => elem,
+                _ => continue,
+            };
+
+            let label = text_of(child).trim().to_string();
+            let value = elem
+                .get_attribute("value")
+                .map(|v| v.to_string())
+                .unwrap_or_else(|| label.clone());
+
+            if elem.attributes.contains_key("selected") {
+                selected_index = Some(options.len());
+            }
+
+            options.push(SelectOption {
+                value,
+                label,
+                disabled: elem.attributes.contains_key("disabled"),
+            });
+        }
+
+        // Without an explicit selection the first enabled option is shown
+        if selected_index.is_none() {
+            selected_index = options.iter().position(|o| !o.disabled);
+        }
+
+        Self {
+            options,
+            selected_index,
+            disabled,
+        }
+    }
+
+    /// Select the option at `index`
+    pub fn select(&mut self, index: usize) -> bool {
+        if self.disabled {
+            return false;
+        }
+
+        match self.options.get(index) {
+            Some(option) if !option.disabled => {
+                self.selected_index = Some(index);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Get the currently selected option
+    pub fn selected(&self) -> Option<&SelectOption> {
+        self.selected_index.and_then(|i| self.options.get(i))
+    }
+
+    /// Value of the currently selected option
+    pub fn value(&self) -> Option<&str> {
+        self.selected().map(|o| o.value.as_str())
+    }
+
+    /// Write the selection back onto the `<option>` children of a `<select>` node
+    pub fn sync_to_node(&self, node: &mut Node) {
+        let mut index = 0;
+        for child in &mut node.children {
+            if let NodeType::Element(ref mut elem) = child.node_type {
+                if elem.tag_name != "option" {
+                    continue;
+                }
+                if Some(index) == self.selected_index {
+                    elem.attributes.insert("selected".to_string(), String::new());
+                } else {
+                    elem.attributes.remove("selected");
+                }
+                index += 1;
+            }
+        }
+    }
+}
+
+/// Check a value against the HTML "valid e-mail address" production
+fn is_valid_email(value: &str) -> bool {
+    static EMAIL: OnceLock<Regex> = OnceLock::new();
+    EMAIL
+        .get_or_init(|| {
+            Regex::new(
+                r"^[a-zA-Z0-9.!#$%&'*+/=?^_`{|}~-]+@[a-zA-Z0-9](?:[a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?(?:\.[a-zA-Z0-9](?:[a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?)*$",
+            )
+            .unwrap()
+        })
+        .is_match(value)
+}
+
+/// Check whether an element matches `:valid` / `:invalid`
+///
+/// Returns `None` for elements that are not form controls, so neither
+/// pseudo-class applies to them.
+pub fn element_validity(elem: &ElementData) -> Option<bool> {
+    match elem.tag_name.as_str() {
+        "input" => Some(InputState::from_attributes(&elem.attributes).check_validity()),
+        _ => None,
+    }
+}
+
+/// Concatenate the text content of a node's descendants
+fn text_of(node: &Node) -> String {
+    match &node.node_type {
+        NodeType::Text(text) => text.clone(),
+        _ => node.children.iter().map(text_of).collect(),
+    }
 }
 
 /// Textarea element state
@@ -134,6 +540,7 @@ pub struct TextAreaState {
     pub rows: usize,
     pub cols: usize,
     pub max_length: Option<usize>,
+    pub required: bool,
 }
 
 impl Default for TextAreaState {
@@ -146,6 +553,7 @@ impl Default for TextAreaState {
             rows: 2,
             cols: 20,
             max_length: None,
+            required: false,
         }
     }
 }
@@ -167,6 +575,7 @@ impl TextAreaState {
             .unwrap_or(20)
             .clamp(1, 1000); // Limit to reasonable range
         let max_length = attrs.get("maxlength").and_then(|s| s.parse().ok());
+        let required = attrs.contains_key("required");
 
         Self {
             value: String::new(),
@@ -176,6 +585,21 @@ impl TextAreaState {
             rows,
             cols,
             max_length,
+            required,
+        }
+    }
+
+    /// Equivalent of `checkValidity()`
+    pub fn check_validity(&self) -> bool {
+        self.validation_message().is_empty()
+    }
+
+    /// Equivalent of `validationMessage`; empty when the control is valid
+    pub fn validation_message(&self) -> String {
+        if self.required && !self.disabled && !self.readonly && self.value.is_empty() {
+            "Please fill out this field.".to_string()
+        } else {
+            String::new()
         }
     }
 
@@ -194,6 +618,12 @@ impl TextAreaState {
         self.value = value;
         true
     }
+
+    /// Append text at the end of the value (e.g., paste)
+    pub fn insert_text(&mut self, text: &str) -> bool {
+        let text = text.replace("\r\n", "\n");
+        self.set_value(format!("{}{}", self.value, text))
+    }
 }
 
 /// Form element state
@@ -201,8 +631,12 @@ impl TextAreaState {
 pub struct FormState {
     pub action: Option<String>,
     pub method: String,
+    /// Skip constraint validation on submit (`novalidate` attribute)
+    pub no_validate: bool,
     pub inputs: HashMap<String, InputState>,
     pub textareas: HashMap<String, TextAreaState>,
+    pub radio_groups: HashMap<String, RadioGroup>,
+    pub selects: HashMap<String, SelectState>,
 }
 
 impl Default for FormState {
@@ -210,8 +644,11 @@ impl Default for FormState {
         Self {
             action: None,
             method: "GET".to_string(),
+            no_validate: false,
             inputs: HashMap::new(),
             textareas: HashMap::new(),
+            radio_groups: HashMap::new(),
+            selects: HashMap::new(),
         }
     }
 }
@@ -225,14 +662,35 @@ impl FormState {
             .map(|m| m.to_uppercase())
             .unwrap_or_else(|| "GET".to_string());
 
+        let no_validate = attrs.contains_key("novalidate");
+
         Self {
             action,
             method,
+            no_validate,
             inputs: HashMap::new(),
             textareas: HashMap::new(),
+            radio_groups: HashMap::new(),
+            selects: HashMap::new(),
         }
     }
 
+    /// Add a radio button to the group with the given name, creating the group if needed
+    pub fn add_radio(&mut self, name: &str, input: InputState) -> usize {
+        self.radio_groups
+            .entry(name.to_string())
+            .or_insert_with(|| RadioGroup::new(name.to_string()))
+            .add(input)
+    }
+
+    /// Check a radio button, unchecking the rest of its group
+    pub fn check_radio(&mut self, name: &str, index: usize) -> bool {
+        self.radio_groups
+            .get_mut(name)
+            .map(|group| group.check(index))
+            .unwrap_or(false)
+    }
+
     /// Collect form data for submission
     pub fn collect_data(&self) -> HashMap<String, String> {
         let mut data = HashMap::new();
@@ -259,9 +717,97 @@ impl FormState {
             data.insert(name.clone(), textarea.value.clone());
         }
 
+        // Collect the checked member of each radio group
+        for (name, group) in &self.radio_groups {
+            if let Some(value) = group.value() {
+                data.insert(name.clone(), value.to_string());
+            }
+        }
+
+        // Collect select values
+        for (name, select) in &self.selects {
+            if let Some(value) = select.value() {
+                data.insert(name.clone(), value.to_string());
+            }
+        }
+
         data
     }
 
+    /// Collect every control that fails constraint validation, ordered by name
+    pub fn invalid_controls(&self) -> Vec<InvalidControl> {
+        let mut invalid = Vec::new();
+
+        for (name, input) in &self.inputs {
+            if !input.check_validity() {
+                invalid.push(InvalidControl {
+                    name: name.clone(),
+                    message: input.validation_message(),
+                });
+            }
+        }
+
+        for (name, textarea) in &self.textareas {
+            if !textarea.check_validity() {
+                invalid.push(InvalidControl {
+                    name: name.clone(),
+                    message: textarea.validation_message(),
+                });
+            }
+        }
+
+        // A required radio group is satisfied by any checked member
+        for (name, group) in &self.radio_groups {
+            let required = group.members.iter().any(|m| m.required && m.will_validate());
+            if required && group.checked_index().is_none() {
+                invalid.push(InvalidControl {
+                    name: name.clone(),
+                    message: "Please select one of these options.".to_string(),
+                });
+            }
+        }
+
+        invalid.sort_by(|a, b| a.name.cmp(&b.name));
+        invalid
+    }
+
+    /// Equivalent of `form.checkValidity()`
+    pub fn check_validity(&self) -> bool {
+        self.invalid_controls().is_empty()
+    }
+
+    /// Validate and collect the form data set for submission
+    ///
+    /// Submission is blocked when any control is invalid, unless the form
+    /// has the `novalidate` attribute.
+    pub fn submit(&self) -> Result<HashMap<String, String>, Vec<InvalidControl>> {
+        if !self.no_validate {
+            let invalid = self.invalid_controls();
+            if !invalid.is_empty() {
+                return Err(invalid);
+            }
+        }
+        Ok(self.collect_data())
+    }
+
+    /// Submit the form, dispatching `invalid` events for each failing
+    /// control or a `submit` event when the form is valid
+    pub fn request_submit(
+        &self,
+        js: &mut JsContext,
+    ) -> Result<Result<HashMap<String, String>, Vec<InvalidControl>>, JsError> {
+        let result = self.submit();
+        match &result {
+            Ok(_) => js.dispatch_event(EventType::Submit, String::new())?,
+            Err(invalid) => {
+                for control in invalid {
+                    js.dispatch_event(EventType::Invalid, control.name.clone())?;
+                }
+            }
+        }
+        Ok(result)
+    }
+
     /// Reset form to initial state
     pub fn reset(&mut self) {
         for input in self.inputs.values_mut() {
@@ -274,6 +820,14 @@ impl FormState {
         for textarea in self.textareas.values_mut() {
             textarea.value.clear();
         }
+
+        for group in self.radio_groups.values_mut() {
+            group.clear();
+        }
+
+        for select in self.selects.values_mut() {
+            select.selected_index = select.options.iter().position(|o| !o.disabled);
+        }
     }
 }
 
@@ -475,6 +1029,250 @@ mod tests {
         assert_eq!(form.inputs.get("field").unwrap().value, "");
     }
 
+    #[test]
+    fn test_input_sync_to_element() {
+        let mut elem = ElementData {
+            tag_name: "input".to_string(),
+            attributes: HashMap::new(),
+        };
+        let mut checkbox = InputState {
+            input_type: InputType::Checkbox,
+            value: "on".to_string(),
+            ..Default::default()
+        };
+
+        checkbox.toggle_checked();
+        checkbox.sync_to_element(&mut elem);
+        assert!(elem.attributes.contains_key("checked"));
+
+        checkbox.toggle_checked();
+        checkbox.sync_to_element(&mut elem);
+        assert!(!elem.attributes.contains_key("checked"));
+    }
+
+    #[test]
+    fn test_radio_group_exclusive() {
+        let mut form = FormState::default();
+        for value in ["small", "medium", "large"] {
+            let radio = InputState {
+                input_type: InputType::Radio,
+                value: value.to_string(),
+                ..Default::default()
+            };
+            form.add_radio("size", radio);
+        }
+
+        assert!(form.check_radio("size", 0));
+        assert!(form.check_radio("size", 2));
+
+        let group = &form.radio_groups["size"];
+        assert_eq!(group.checked_index(), Some(2));
+        assert_eq!(group.members.iter().filter(|m| m.checked).count(), 1);
+        assert_eq!(form.collect_data().get("size"), Some(&"large".to_string()));
+
+        // Unknown group or index
+        assert!(!form.check_radio("color", 0));
+        assert!(!form.check_radio("size", 5));
+    }
+
+    #[test]
+    fn test_select_from_node() {
+        let mut selected = HashMap::new();
+        selected.insert("value".to_string(), "b".to_string());
+        selected.insert("selected".to_string(), String::new());
+
+        let mut select = Node::element(
+            "select".to_string(),
+            HashMap::new(),
+            vec![
+                Node::element("option".to_string(), HashMap::new(), vec![Node::text("Alpha".to_string())]),
+                Node::element("option".to_string(), selected, vec![Node::text("Beta".to_string())]),
+            ],
+        );
+
+        let mut state = SelectState::from_node(&select);
+        assert_eq!(state.options.len(), 2);
+        assert_eq!(state.options[0].value, "Alpha");
+        assert_eq!(state.value(), Some("b"));
+
+        assert!(state.select(0));
+        state.sync_to_node(&mut select);
+        let first = select.children[0].element_data().unwrap();
+        let second = select.children[1].element_data().unwrap();
+        assert!(first.attributes.contains_key("selected"));
+        assert!(!second.attributes.contains_key("selected"));
+    }
+
+    #[test]
+    fn test_select_disabled_option() {
+        let mut state = SelectState {
+            options: vec![
+                SelectOption { value: "a".to_string(), label: "A".to_string(), disabled: false },
+                SelectOption { value: "b".to_string(), label: "B".to_string(), disabled: true },
+            ],
+            selected_index: Some(0),
+            disabled: false,
+        };
+
+        assert!(!state.select(1));
+        assert_eq!(state.value(), Some("a"));
+    }
+
+    #[test]
+    fn test_required_validation() {
+        let mut attrs = HashMap::new();
+        attrs.insert("required".to_string(), String::new());
+        let mut input = InputState::from_attributes(&attrs);
+
+        assert!(input.validity().value_missing);
+        assert_eq!(input.validation_message(), "Please fill out this field.");
+
+        input.set_value("hello".to_string());
+        assert!(input.check_validity());
+        assert_eq!(input.validation_message(), "");
+
+        // Disabled controls are barred from validation
+        input.value.clear();
+        input.disabled = true;
+        assert!(input.check_validity());
+    }
+
+    #[test]
+    fn test_type_validation() {
+        let mut email = InputState {
+            input_type: InputType::Email,
+            value: "not-an-email".to_string(),
+            ..Default::default()
+        };
+        assert!(email.validity().type_mismatch);
+        email.value = "user@example.com".to_string();
+        assert!(email.check_validity());
+
+        let mut url = InputState {
+            input_type: InputType::Url,
+            value: "example dot com".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(url.validation_message(), "Please enter a URL.");
+        url.value = "https://example.com/".to_string();
+        assert!(url.check_validity());
+    }
+
+    #[test]
+    fn test_number_range_and_step() {
+        let mut attrs = HashMap::new();
+        attrs.insert("type".to_string(), "number".to_string());
+        attrs.insert("min".to_string(), "2".to_string());
+        attrs.insert("max".to_string(), "10".to_string());
+        attrs.insert("step".to_string(), "2".to_string());
+        let mut input = InputState::from_attributes(&attrs);
+
+        input.value = "1".to_string();
+        assert!(input.validity().range_underflow);
+        input.value = "12".to_string();
+        assert!(input.validity().range_overflow);
+        input.value = "5".to_string();
+        assert!(input.validity().step_mismatch);
+        input.value = "6".to_string();
+        assert!(input.check_validity());
+        input.value = "abc".to_string();
+        assert!(input.validity().bad_input);
+
+        input.step = None;
+        input.value = "5.5".to_string();
+        assert!(input.check_validity());
+    }
+
+    #[test]
+    fn test_pattern_validation() {
+        let mut input = InputState {
+            pattern: Some("[0-9]{3}".to_string()),
+            value: "1234".to_string(),
+            ..Default::default()
+        };
+        // Pattern is anchored to the whole value
+        assert!(input.validity().pattern_mismatch);
+        input.value = "123".to_string();
+        assert!(input.check_validity());
+    }
+
+    #[test]
+    fn test_custom_validity() {
+        let mut input = InputState::default();
+        input.set_custom_validity("Username taken");
+        assert!(input.validity().custom_error);
+        assert_eq!(input.validation_message(), "Username taken");
+        input.set_custom_validity("");
+        assert!(input.check_validity());
+    }
+
+    #[test]
+    fn test_submit_blocked_when_invalid() {
+        let mut form = FormState::default();
+        form.inputs.insert(
+            "email".to_string(),
+            InputState {
+                input_type: InputType::Email,
+                required: true,
+                ..Default::default()
+            },
+        );
+        form.add_radio(
+            "plan",
+            InputState {
+                input_type: InputType::Radio,
+                required: true,
+                value: "basic".to_string(),
+                ..Default::default()
+            },
+        );
+
+        let invalid = form.submit().unwrap_err();
+        assert_eq!(invalid.len(), 2);
+        assert_eq!(invalid[0].name, "email");
+        assert_eq!(invalid[1].name, "plan");
+
+        form.no_validate = true;
+        assert!(form.submit().is_ok());
+    }
+
+    #[test]
+    fn test_request_submit_dispatches_events() {
+        let mut js = JsContext::new();
+        js.add_event_listener(EventType::Invalid, "var invalidFired = true;".to_string())
+            .unwrap();
+
+        let mut form = FormState::default();
+        form.inputs.insert(
+            "name".to_string(),
+            InputState {
+                required: true,
+                ..Default::default()
+            },
+        );
+
+        let result = form.request_submit(&mut js).unwrap();
+        assert!(result.is_err());
+        assert!(js.execute("invalidFired").is_ok());
+    }
+
+    #[test]
+    fn test_element_validity() {
+        let mut attrs = HashMap::new();
+        attrs.insert("required".to_string(), String::new());
+        let mut elem = ElementData {
+            tag_name: "input".to_string(),
+            attributes: attrs,
+        };
+        assert_eq!(element_validity(&elem), Some(false));
+
+        elem.attributes.insert("value".to_string(), "filled".to_string());
+        assert_eq!(element_validity(&elem), Some(true));
+
+        elem.tag_name = "div".to_string();
+        assert_eq!(element_validity(&elem), None);
+    }
+
     #[test]
     fn test_focus_manager() {
         let mut focus = FocusManager::new();
@@ -0,0 +1,361 @@
+// Headless mode - pages driven from code, with no window
+//
+// A HeadlessBrowser renders offscreen through a WebView, so it runs where
+// there is no display or GPU: CI rendering tests, scraping, batch printing.
+// Callers navigate, wait for the page to settle or for an element to show
+// up, run script, and capture the page as a PNG or PDF. The browser is also
+// a DevTools target, so a remote protocol client drives the same page with
+// the same operations, and `HeadlessOptions` reads Chrome's command line
+// flags for running it from the shell.
+
+use std::fmt;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use url::Url;
+
+use crate::devtools::{document_matches, outer_html, parse_selectors, CdpTarget, DevTools};
+use crate::dom::Node;
+use crate::engine::{Screenshot, WebView};
+use crate::js::{JsError, JsValue};
+use crate::net::NetError;
+use crate::print::{PrintError, PrintOptions};
+
+/// Size of the view when none is given, in CSS pixels
+pub const DEFAULT_WIDTH: f32 = 800.0;
+pub const DEFAULT_HEIGHT: f32 = 600.0;
+
+/// How long waits last when none is given
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Time between frames while waiting
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Why an automation call failed
+#[derive(Debug)]
+pub enum HeadlessError {
+    InvalidUrl(String),
+    /// The page could not be loaded
+    Load(NetError),
+    /// No page has been loaded yet
+    NoPage,
+    InvalidSelector(String),
+    /// What was waited for did not happen in time
+    Timeout(String),
+    Script(JsError),
+    Print(PrintError),
+    /// The screenshot could not be encoded
+    Encode(String),
+}
+
+impl fmt::Display for HeadlessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeadlessError::InvalidUrl(url) => write!(f, "Invalid URL: {}", url),
+            HeadlessError::Load(e) => write!(f, "Failed to load the page: {}", e),
+            HeadlessError::NoPage => write!(f, "No page is loaded"),
+            HeadlessError::InvalidSelector(selector) => write!(f, "Invalid selector: {}", selector),
+            HeadlessError::Timeout(what) => write!(f, "Timed out waiting for {}", what),
+            HeadlessError::Script(e) => write!(f, "Script error: {}", e),
+            HeadlessError::Print(e) => write!(f, "Printing failed: {}", e),
+            HeadlessError::Encode(e) => write!(f, "Failed to encode the screenshot: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for HeadlessError {}
+
+/// A page with no window, driven by automation calls
+pub struct HeadlessBrowser {
+    view: WebView,
+    /// Console messages for DevTools clients: load and script failures
+    devtools: DevTools,
+}
+
+impl HeadlessBrowser {
+    /// Create a browser whose view is a size in CSS pixels
+    pub fn new(width: f32, height: f32) -> Self {
+        Self { view: WebView::new(width, height), devtools: DevTools::new() }
+    }
+
+    /// The view pages are loaded and painted in
+    pub fn view(&self) -> &WebView {
+        &self.view
+    }
+
+    pub fn view_mut(&mut self) -> &mut WebView {
+        &mut self.view
+    }
+
+    /// Load a URL; its scripts have run when this returns
+    pub fn navigate(&mut self, url: &str) -> Result<(), HeadlessError> {
+        let url = Url::parse(url).map_err(|_| HeadlessError::InvalidUrl(url.to_string()))?;
+        self.view.load_url(&url).map_err(|e| self.load_failed(e))
+    }
+
+    /// Load a page from HTML, resolving its links against `url`
+    pub fn load_html(&mut self, html: &str, url: &str) -> Result<(), HeadlessError> {
+        let url = Url::parse(url).map_err(|_| HeadlessError::InvalidUrl(url.to_string()))?;
+        self.view.load_html(html, &url).map_err(|e| self.load_failed(e))
+    }
+
+    /// Wait until the page has settled: the callbacks its scripts left
+    /// waiting for a frame or idle time have run
+    pub fn wait_for_load(&mut self, timeout: Duration) -> Result<(), HeadlessError> {
+        if self.view.url().is_none() {
+            return Err(HeadlessError::NoPage);
+        }
+        let deadline = Instant::now() + timeout;
+        while self.view.has_pending_callbacks() {
+            if Instant::now() >= deadline {
+                return Err(HeadlessError::Timeout("the page to load".to_string()));
+            }
+            self.next_frame();
+        }
+        Ok(())
+    }
+
+    /// Wait until an element matches a selector, running frames meanwhile
+    ///
+    /// Returns the path of child indices from the document to the first
+    /// element matching, for `Node::descendant`.
+    pub fn wait_for_selector(&mut self, selector: &str, timeout: Duration) -> Result<Vec<usize>, HeadlessError> {
+        let selectors = parse_selectors(selector).ok_or_else(|| HeadlessError::InvalidSelector(selector.to_string()))?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            let document = self.view.document().ok_or(HeadlessError::NoPage)?;
+            if let Some(path) = document_matches(document, &selectors).into_iter().next() {
+                return Ok(path[1..].to_vec());
+            }
+            if Instant::now() >= deadline {
+                return Err(HeadlessError::Timeout(format!("'{}'", selector)));
+            }
+            self.next_frame();
+        }
+    }
+
+    /// Run script in the page, returning its completion value
+    pub fn evaluate(&mut self, code: &str) -> Result<JsValue, HeadlessError> {
+        self.view.evaluate_js(code).map_err(HeadlessError::Script)
+    }
+
+    /// Paint the visible part of the page
+    pub fn screenshot(&self) -> Screenshot {
+        self.view.screenshot()
+    }
+
+    /// Paint the visible part of the page as a PNG file
+    pub fn screenshot_png(&self) -> Result<Vec<u8>, HeadlessError> {
+        self.view.screenshot().to_png().map_err(|e| HeadlessError::Encode(e.to_string()))
+    }
+
+    /// Lay the page out on paper as a PDF file
+    pub fn pdf(&self, options: &PrintOptions) -> Result<Vec<u8>, HeadlessError> {
+        self.view.print_to_pdf(options).map_err(HeadlessError::Print)
+    }
+
+    /// The document serialized as HTML, with the changes scripts made
+    pub fn content(&self) -> String {
+        let mut html = String::new();
+        if let Some(document) = self.view.document() {
+            outer_html(document, &mut html);
+        }
+        html
+    }
+
+    /// Run a frame's callbacks, or wait a frame if there were none
+    fn next_frame(&mut self) {
+        if !self.view.tick(Instant::now()) {
+            std::thread::sleep(FRAME_INTERVAL);
+        }
+    }
+
+    fn load_failed(&mut self, error: NetError) -> HeadlessError {
+        self.devtools.console.error(format!("Failed to load the page: {}", error));
+        HeadlessError::Load(error)
+    }
+}
+
+impl Default for HeadlessBrowser {
+    fn default() -> Self {
+        Self::new(DEFAULT_WIDTH, DEFAULT_HEIGHT)
+    }
+}
+
+/// The page, as remote DevTools clients see it
+impl CdpTarget for HeadlessBrowser {
+    fn url(&self) -> Option<Url> {
+        self.view.url().cloned()
+    }
+
+    fn title(&self) -> String {
+        self.view.title().to_string()
+    }
+
+    fn document(&self) -> Option<&Node> {
+        self.view.document()
+    }
+
+    fn navigate(&mut self, url: Url) -> Result<(), String> {
+        self.view.load_url(&url).map_err(|e| self.load_failed(e).to_string())
+    }
+
+    fn reload(&mut self, _ignore_cache: bool) {
+        if let Err(e) = self.view.reload() {
+            self.load_failed(e);
+        }
+    }
+
+    fn evaluate(&mut self, expression: &str) -> Result<JsValue, String> {
+        self.view.evaluate_js(expression).map_err(|e| e.to_string())
+    }
+
+    fn devtools(&self) -> &DevTools {
+        &self.devtools
+    }
+
+    fn capture_screenshot(&mut self) -> Result<Screenshot, String> {
+        Ok(self.view.screenshot())
+    }
+
+    fn print_to_pdf(&mut self, options: &PrintOptions) -> Result<Vec<u8>, String> {
+        self.view.print_to_pdf(options).map_err(|e| e.to_string())
+    }
+}
+
+/// What to do when run from the command line with `--headless`
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadlessOptions {
+    /// Page to load first
+    pub url: Option<String>,
+    pub width: f32,
+    pub height: f32,
+    /// Where to save a PNG of the page
+    pub screenshot: Option<PathBuf>,
+    /// Where to save the page printed as a PDF
+    pub print_to_pdf: Option<PathBuf>,
+    /// Print the document's HTML to stdout
+    pub dump_dom: bool,
+    /// Serve the page to DevTools clients on this port until killed
+    pub remote_debugging_port: Option<u16>,
+}
+
+impl HeadlessOptions {
+    /// Read Chrome's flags: `--headless`, `--screenshot[=file]`,
+    /// `--print-to-pdf[=file]`, `--dump-dom`, `--window-size=W,H`,
+    /// `--remote-debugging-port=N` and the URL
+    ///
+    /// `None` when `--headless` is not among them.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Option<Self> {
+        let mut headless = false;
+        let mut options = Self {
+            url: None,
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            screenshot: None,
+            print_to_pdf: None,
+            dump_dom: false,
+            remote_debugging_port: None,
+        };
+        for arg in args {
+            let (flag, value) = match arg.split_once('=') {
+                Some((flag, value)) if arg.starts_with("--") => (flag, Some(value)),
+                _ => (arg.as_str(), None),
+            };
+            match flag {
+                "--headless" => headless = true,
+                "--screenshot" => options.screenshot = Some(PathBuf::from(value.unwrap_or("screenshot.png"))),
+                "--print-to-pdf" => options.print_to_pdf = Some(PathBuf::from(value.unwrap_or("output.pdf"))),
+                "--dump-dom" => options.dump_dom = true,
+                "--window-size" => {
+                    let size = value.and_then(|v| v.split_once(','));
+                    if let Some((Ok(width), Ok(height))) = size.map(|(w, h)| (w.trim().parse(), h.trim().parse())) {
+                        options.width = width;
+                        options.height = height;
+                    }
+                }
+                "--remote-debugging-port" => options.remote_debugging_port = value.and_then(|v| v.parse().ok()),
+                _ if !flag.starts_with('-') => options.url = Some(arg.clone()),
+                _ => {}
+            }
+        }
+        headless.then_some(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devtools::CdpSession;
+    use serde_json::{json, Value};
+
+    const PAGE: &str = r#"<html><head><title>Report</title>
+        <style>#chart { background-color: #0000ff; height: 30px; }</style></head>
+        <body><div id="chart"></div>
+        <script>
+            requestAnimationFrame(function () {
+                document.getElementById('chart').setAttribute('class', 'ready');
+            });
+        </script></body></html>"#;
+
+    #[test]
+    fn test_wait_capture_and_evaluate() {
+        let mut browser = HeadlessBrowser::new(100.0, 60.0);
+        assert!(matches!(browser.wait_for_load(DEFAULT_TIMEOUT), Err(HeadlessError::NoPage)));
+        browser.load_html(PAGE, "https://example.test/report").unwrap();
+
+        // The class the frame callback sets shows up once frames run
+        let path = browser.wait_for_selector("body > div.ready", Duration::from_secs(5)).unwrap();
+        let document = browser.view().document().unwrap();
+        let element = document.descendant(&path).and_then(Node::element_data).unwrap();
+        assert_eq!(element.id(), Some("chart"));
+        browser.wait_for_load(Duration::from_secs(5)).unwrap();
+        assert!(browser.content().contains("class=\"ready\""));
+
+        let timeout = browser.wait_for_selector("#missing", Duration::from_millis(20));
+        assert!(matches!(timeout, Err(HeadlessError::Timeout(_))));
+        assert!(matches!(browser.wait_for_selector("p >", DEFAULT_TIMEOUT), Err(HeadlessError::InvalidSelector(_))));
+
+        assert_eq!(browser.evaluate("[1, 2].length + 40").unwrap(), JsValue::Number(42.0));
+        let shot = browser.screenshot();
+        assert_eq!(&shot.pixels[(20 * 100 + 20) * 4..(20 * 100 + 20) * 4 + 4], &[0, 0, 255, 255]);
+        assert!(browser.screenshot_png().unwrap().starts_with(b"\x89PNG"));
+        assert!(browser.pdf(&PrintOptions::new()).unwrap().starts_with(b"%PDF"));
+    }
+
+    #[test]
+    fn test_devtools_clients_capture_the_page() {
+        let mut browser = HeadlessBrowser::new(100.0, 60.0);
+        browser.load_html(PAGE, "https://example.test/report").unwrap();
+        let mut session = CdpSession::new();
+        let mut call = |id: i64, method: &str, params: Value| -> Value {
+            let message = json!({"id": id, "method": method, "params": params}).to_string();
+            serde_json::from_str(&session.handle(&message, &mut browser)[0]).unwrap()
+        };
+
+        let reply = call(1, "Page.captureScreenshot", json!({}));
+        assert!(reply["result"]["data"].as_str().unwrap().starts_with("iVBORw0KGgo"));
+        let reply = call(2, "Page.printToPDF", json!({"landscape": true}));
+        assert!(reply["result"]["data"].as_str().unwrap().starts_with("JVBERi"));
+        let reply = call(3, "Page.captureScreenshot", json!({"format": "webp"}));
+        assert_eq!(reply["error"]["code"], -32602);
+    }
+
+    #[test]
+    fn test_options_from_args() {
+        let args = ["--headless", "--screenshot", "--window-size=320,240", "https://example.com/"];
+        let options = HeadlessOptions::from_args(args.iter().map(|a| a.to_string())).unwrap();
+        assert_eq!(options.url.as_deref(), Some("https://example.com/"));
+        assert_eq!((options.width, options.height), (320.0, 240.0));
+        assert_eq!(options.screenshot, Some(PathBuf::from("screenshot.png")));
+        assert_eq!(options.print_to_pdf, None);
+
+        let args = ["--print-to-pdf=out/page.pdf", "--headless", "--dump-dom", "--remote-debugging-port=9222"];
+        let options = HeadlessOptions::from_args(args.iter().map(|a| a.to_string())).unwrap();
+        assert_eq!(options.print_to_pdf, Some(PathBuf::from("out/page.pdf")));
+        assert!(options.dump_dom);
+        assert_eq!(options.remote_debugging_port, Some(9222));
+
+        assert_eq!(HeadlessOptions::from_args(vec!["https://example.com/".to_string()]), None);
+    }
+}
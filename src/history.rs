@@ -0,0 +1,508 @@
+// Global browsing history: visit records shared by all tabs, frecency
+// ranking for address bar autocomplete, and clearing by time range
+
+use crate::storage::{LocalStorage, StorageError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use url::Url;
+
+/// Local storage key the history is saved under
+pub const HISTORY_STORAGE_KEY: &str = "browser.history";
+
+/// Number of recent visits kept per URL for frecency scoring
+const MAX_VISIT_SAMPLES: usize = 10;
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// History errors
+#[derive(Debug, Clone, PartialEq)]
+pub enum HistoryError {
+    /// Stored data could not be read
+    Parse(String),
+    /// Saving to storage failed
+    Storage(StorageError),
+}
+
+impl std::fmt::Display for HistoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HistoryError::Parse(msg) => write!(f, "History parse error: {}", msg),
+            HistoryError::Storage(e) => write!(f, "History storage error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for HistoryError {}
+
+/// How the user arrived at a page
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VisitTransition {
+    /// Followed a link
+    Link,
+    /// Typed or picked in the address bar
+    Typed,
+    /// Opened from a bookmark
+    Bookmark,
+    /// Reloaded the page
+    Reload,
+}
+
+impl VisitTransition {
+    /// Frecency weight of a visit of this kind
+    fn bonus(self) -> f32 {
+        match self {
+            VisitTransition::Typed => 2.0,
+            VisitTransition::Bookmark => 1.4,
+            VisitTransition::Link => 1.0,
+            VisitTransition::Reload => 0.0,
+        }
+    }
+}
+
+/// Everything recorded about one URL
+#[derive(Debug, Clone, PartialEq)]
+pub struct VisitRecord {
+    pub url: Url,
+    pub title: Option<String>,
+    /// Total number of visits
+    pub visit_count: u32,
+    /// Visits that came from the address bar
+    pub typed_count: u32,
+    pub first_visit: SystemTime,
+    pub last_visit: SystemTime,
+    /// Most recent visits, oldest first
+    visits: Vec<(SystemTime, VisitTransition)>,
+}
+
+impl VisitRecord {
+    /// Frecency score: recent, frequent and typed visits rank higher
+    pub fn frecency(&self, now: SystemTime) -> f32 {
+        if self.visits.is_empty() {
+            return 0.0;
+        }
+        let sampled: f32 = self
+            .visits
+            .iter()
+            .map(|(time, transition)| {
+                let age = now.duration_since(*time).unwrap_or_default().as_secs();
+                let recency = match age {
+                    a if a <= 4 * DAY => 100.0,
+                    a if a <= 14 * DAY => 70.0,
+                    a if a <= 31 * DAY => 50.0,
+                    a if a <= 90 * DAY => 30.0,
+                    _ => 10.0,
+                };
+                recency * transition.bonus()
+            })
+            .sum();
+        // Scale the sample up to the full visit count
+        sampled * self.visit_count as f32 / self.visits.len() as f32
+    }
+}
+
+/// Period of history to clear
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeRange {
+    LastHour,
+    LastDay,
+    LastWeek,
+    LastFourWeeks,
+    AllTime,
+    /// Visits at or after the first time and before the second
+    Between(SystemTime, SystemTime),
+}
+
+impl TimeRange {
+    /// Start and end of the range relative to `now`
+    pub fn bounds(&self, now: SystemTime) -> (SystemTime, SystemTime) {
+        let ago = |secs: u64| now.checked_sub(Duration::from_secs(secs)).unwrap_or(UNIX_EPOCH);
+        // Include visits stamped at `now`
+        let end = now + Duration::from_secs(1);
+        match *self {
+            TimeRange::LastHour => (ago(60 * 60), end),
+            TimeRange::LastDay => (ago(DAY), end),
+            TimeRange::LastWeek => (ago(7 * DAY), end),
+            TimeRange::LastFourWeeks => (ago(28 * DAY), end),
+            TimeRange::AllTime => (UNIX_EPOCH, end),
+            TimeRange::Between(start, end) => (start, end),
+        }
+    }
+}
+
+/// Browsing history shared by all tabs
+#[derive(Debug, Clone)]
+pub struct HistoryDatabase {
+    records: HashMap<String, VisitRecord>,
+}
+
+impl HistoryDatabase {
+    /// Create an empty history
+    pub fn new() -> Self {
+        Self {
+            records: HashMap::new(),
+        }
+    }
+
+    /// Record a visit now
+    pub fn record_visit(&mut self, url: &Url, transition: VisitTransition) -> &VisitRecord {
+        self.record_visit_at(url, transition, SystemTime::now())
+    }
+
+    /// Record a visit at a given time
+    pub fn record_visit_at(&mut self, url: &Url, transition: VisitTransition, time: SystemTime) -> &VisitRecord {
+        let record = self.records.entry(Self::key(url)).or_insert_with(|| VisitRecord {
+            url: url.clone(),
+            title: None,
+            visit_count: 0,
+            typed_count: 0,
+            first_visit: time,
+            last_visit: time,
+            visits: Vec::new(),
+        });
+
+        record.visit_count += 1;
+        if transition == VisitTransition::Typed {
+            record.typed_count += 1;
+        }
+        record.first_visit = record.first_visit.min(time);
+        record.last_visit = record.last_visit.max(time);
+        record.visits.push((time, transition));
+        record.visits.sort_by_key(|(t, _)| *t);
+        if record.visits.len() > MAX_VISIT_SAMPLES {
+            record.visits.remove(0);
+        }
+        record
+    }
+
+    /// Update the title recorded for a URL
+    pub fn set_title(&mut self, url: &Url, title: &str) {
+        if let Some(record) = self.records.get_mut(&Self::key(url)) {
+            record.title = if title.is_empty() { None } else { Some(title.to_string()) };
+        }
+    }
+
+    /// Get the record for a URL
+    pub fn get(&self, url: &Url) -> Option<&VisitRecord> {
+        self.records.get(&Self::key(url))
+    }
+
+    /// Number of URLs in history
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Is the history empty
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Most recently visited URLs, newest first
+    pub fn recent(&self, limit: usize) -> Vec<&VisitRecord> {
+        let mut records: Vec<&VisitRecord> = self.records.values().collect();
+        records.sort_by_key(|r| std::cmp::Reverse(r.last_visit));
+        records.truncate(limit);
+        records
+    }
+
+    /// Highest-frecency URLs
+    pub fn most_frecent(&self, limit: usize) -> Vec<&VisitRecord> {
+        self.ranked(self.records.values().collect(), limit)
+    }
+
+    /// URLs starting with typed text, ignoring scheme and `www.`
+    pub fn query_prefix(&self, prefix: &str, limit: usize) -> Vec<&VisitRecord> {
+        let prefix = prefix.trim().to_lowercase();
+        if prefix.is_empty() {
+            return Vec::new();
+        }
+        let matching = self
+            .records
+            .values()
+            .filter(|r| Self::matches_prefix(&r.url, &prefix))
+            .collect();
+        self.ranked(matching, limit)
+    }
+
+    /// URLs or titles containing typed text; prefix matches rank first
+    pub fn search(&self, text: &str, limit: usize) -> Vec<&VisitRecord> {
+        let text = text.trim().to_lowercase();
+        if text.is_empty() {
+            return Vec::new();
+        }
+        let (mut prefixed, contained): (Vec<&VisitRecord>, Vec<&VisitRecord>) = self
+            .records
+            .values()
+            .filter(|r| {
+                r.url.as_str().to_lowercase().contains(&text)
+                    || r.title.as_ref().is_some_and(|t| t.to_lowercase().contains(&text))
+            })
+            .partition(|r| Self::matches_prefix(&r.url, &text));
+
+        prefixed = self.ranked(prefixed, limit);
+        let rest = limit.saturating_sub(prefixed.len());
+        prefixed.extend(self.ranked(contained, rest));
+        prefixed
+    }
+
+    /// Inline completion for typed text: the host (or URL) of the best prefix match
+    ///
+    /// Only URLs that were typed before are completed.
+    pub fn inline_completion(&self, prefix: &str) -> Option<String> {
+        let typed = prefix.trim().to_lowercase();
+        let best = self
+            .query_prefix(&typed, usize::MAX)
+            .into_iter()
+            .find(|r| r.typed_count > 0)?;
+        let stripped = strip_url(&best.url);
+        let host = stripped.split('/').next().unwrap_or(&stripped);
+        let completion = if host.len() >= typed.len() && host.starts_with(&typed) { host } else { &stripped };
+        Some(format!("{}/", completion.trim_end_matches('/')))
+    }
+
+    /// Remove one URL from history
+    pub fn remove(&mut self, url: &Url) -> bool {
+        self.records.remove(&Self::key(url)).is_some()
+    }
+
+    /// Remove visits within a time range
+    ///
+    /// URLs left without any sampled visit are dropped entirely. Returns
+    /// the number of URLs removed.
+    pub fn clear_range(&mut self, range: TimeRange) -> usize {
+        let (start, end) = range.bounds(SystemTime::now());
+        let before = self.records.len();
+        self.records.retain(|_, record| {
+            let kept = record.visits.len();
+            record.visits.retain(|(time, _)| *time < start || *time >= end);
+            let removed = (kept - record.visits.len()) as u32;
+            record.visit_count = record.visit_count.saturating_sub(removed);
+            match (record.visits.first(), record.visits.last()) {
+                (Some(first), Some(last)) if record.visit_count > 0 => {
+                    if record.first_visit >= start && record.first_visit < end {
+                        record.first_visit = first.0;
+                    }
+                    record.last_visit = last.0;
+                    true
+                }
+                _ => false,
+            }
+        });
+        before - self.records.len()
+    }
+
+    /// Remove all history
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+
+    /// Save the history to local storage
+    pub fn save(&self, storage: &mut LocalStorage) -> Result<(), HistoryError> {
+        let stored: Vec<StoredRecord> = self
+            .records
+            .values()
+            .map(|r| StoredRecord {
+                url: r.url.to_string(),
+                title: r.title.clone(),
+                visit_count: r.visit_count,
+                typed_count: r.typed_count,
+                first_visit: unix_millis(r.first_visit),
+                visits: r.visits.iter().map(|(t, kind)| (unix_millis(*t), *kind)).collect(),
+            })
+            .collect();
+        let json = serde_json::to_string(&stored).map_err(|e| HistoryError::Parse(e.to_string()))?;
+        storage
+            .set_item(HISTORY_STORAGE_KEY.to_string(), json)
+            .map_err(HistoryError::Storage)
+    }
+
+    /// Load history saved to local storage
+    ///
+    /// Returns an empty history if nothing was saved.
+    pub fn load(storage: &LocalStorage) -> Result<Self, HistoryError> {
+        let Some(json) = storage.get_item(HISTORY_STORAGE_KEY) else {
+            return Ok(Self::new());
+        };
+        let stored: Vec<StoredRecord> =
+            serde_json::from_str(&json).map_err(|e| HistoryError::Parse(e.to_string()))?;
+
+        let mut history = Self::new();
+        for record in stored {
+            let Ok(url) = Url::parse(&record.url) else { continue };
+            let visits: Vec<(SystemTime, VisitTransition)> =
+                record.visits.into_iter().map(|(t, kind)| (from_unix_millis(t), kind)).collect();
+            let Some(last_visit) = visits.last().map(|(t, _)| *t) else { continue };
+            history.records.insert(
+                Self::key(&url),
+                VisitRecord {
+                    url,
+                    title: record.title,
+                    visit_count: record.visit_count,
+                    typed_count: record.typed_count,
+                    first_visit: from_unix_millis(record.first_visit),
+                    last_visit,
+                    visits,
+                },
+            );
+        }
+        Ok(history)
+    }
+
+    fn ranked<'a>(&self, mut records: Vec<&'a VisitRecord>, limit: usize) -> Vec<&'a VisitRecord> {
+        let now = SystemTime::now();
+        records.sort_by(|a, b| {
+            b.frecency(now)
+                .total_cmp(&a.frecency(now))
+                .then_with(|| b.last_visit.cmp(&a.last_visit))
+        });
+        records.truncate(limit);
+        records
+    }
+
+    fn matches_prefix(url: &Url, prefix: &str) -> bool {
+        url.as_str().to_lowercase().starts_with(prefix) || strip_url(url).starts_with(prefix)
+    }
+
+    /// URLs differing only in fragment share a record
+    fn key(url: &Url) -> String {
+        let mut url = url.clone();
+        url.set_fragment(None);
+        url.to_string()
+    }
+}
+
+impl Default for HistoryDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredRecord {
+    url: String,
+    title: Option<String>,
+    visit_count: u32,
+    typed_count: u32,
+    first_visit: u64,
+    visits: Vec<(u64, VisitTransition)>,
+}
+
+/// Lowercased URL without scheme or leading `www.`
+fn strip_url(url: &Url) -> String {
+    let text = url.as_str().to_lowercase();
+    let text = text.split_once("://").map(|(_, rest)| rest).unwrap_or(&text);
+    text.strip_prefix("www.").unwrap_or(text).to_string()
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+fn from_unix_millis(millis: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    fn days_ago(days: u64) -> SystemTime {
+        SystemTime::now() - Duration::from_secs(days * DAY)
+    }
+
+    #[test]
+    fn test_record_visits() {
+        let mut history = HistoryDatabase::new();
+        history.record_visit(&url("https://example.com/a"), VisitTransition::Typed);
+        history.record_visit(&url("https://example.com/a#section"), VisitTransition::Link);
+        history.set_title(&url("https://example.com/a"), "Page A");
+
+        assert_eq!(history.len(), 1);
+        let record = history.get(&url("https://example.com/a")).unwrap();
+        assert_eq!(record.visit_count, 2);
+        assert_eq!(record.typed_count, 1);
+        assert_eq!(record.title.as_deref(), Some("Page A"));
+        assert!(record.last_visit >= record.first_visit);
+    }
+
+    #[test]
+    fn test_frecency_prefers_recent_typed_visits() {
+        let mut history = HistoryDatabase::new();
+        for _ in 0..3 {
+            history.record_visit_at(&url("https://old.example.com/"), VisitTransition::Link, days_ago(100));
+        }
+        history.record_visit_at(&url("https://typed.example.com/"), VisitTransition::Typed, days_ago(1));
+        history.record_visit_at(&url("https://link.example.com/"), VisitTransition::Link, days_ago(1));
+
+        let ranked: Vec<&str> = history.most_frecent(3).iter().map(|r| r.url.as_str()).collect();
+        assert_eq!(
+            ranked,
+            vec!["https://typed.example.com/", "https://link.example.com/", "https://old.example.com/"]
+        );
+        let recent = history.recent(1);
+        assert_ne!(recent[0].url.as_str(), "https://old.example.com/");
+    }
+
+    #[test]
+    fn test_prefix_query_and_search() {
+        let mut history = HistoryDatabase::new();
+        history.record_visit(&url("https://www.rust-lang.org/learn"), VisitTransition::Typed);
+        history.record_visit(&url("https://docs.rs/"), VisitTransition::Link);
+        history.record_visit(&url("https://docs.rs/"), VisitTransition::Link);
+        history.set_title(&url("https://docs.rs/"), "Docs.rs - Rust documentation");
+        history.record_visit(&url("https://example.com/rust"), VisitTransition::Link);
+
+        let prefixed = history.query_prefix("rust", 10);
+        assert_eq!(prefixed.len(), 1);
+        assert_eq!(prefixed[0].url.as_str(), "https://www.rust-lang.org/learn");
+        assert_eq!(history.query_prefix("https://docs", 10).len(), 1);
+
+        // Prefix matches first, then title and URL substring matches
+        let found = history.search("rust", 10);
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[0].url.as_str(), "https://www.rust-lang.org/learn");
+
+        assert_eq!(history.inline_completion("ru").as_deref(), Some("rust-lang.org/"));
+        // Never typed, so never completed
+        assert_eq!(history.inline_completion("docs"), None);
+    }
+
+    #[test]
+    fn test_clear_range() {
+        let mut history = HistoryDatabase::new();
+        history.record_visit_at(&url("https://old.example.com/"), VisitTransition::Link, days_ago(10));
+        history.record_visit_at(&url("https://both.example.com/"), VisitTransition::Link, days_ago(10));
+        history.record_visit(&url("https://both.example.com/"), VisitTransition::Link);
+        history.record_visit(&url("https://new.example.com/"), VisitTransition::Link);
+
+        assert_eq!(history.clear_range(TimeRange::LastDay), 1);
+        assert!(history.get(&url("https://new.example.com/")).is_none());
+        let both = history.get(&url("https://both.example.com/")).unwrap();
+        assert_eq!(both.visit_count, 1);
+        assert!(both.last_visit < days_ago(9));
+
+        assert_eq!(history.clear_range(TimeRange::AllTime), 2);
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn test_persist_to_local_storage() {
+        let mut storage = LocalStorage::new();
+        assert!(HistoryDatabase::load(&storage).unwrap().is_empty());
+
+        let mut history = HistoryDatabase::new();
+        history.record_visit(&url("https://example.com/"), VisitTransition::Typed);
+        history.set_title(&url("https://example.com/"), "Example");
+        history.save(&mut storage).unwrap();
+
+        let loaded = HistoryDatabase::load(&storage).unwrap();
+        let record = loaded.get(&url("https://example.com/")).unwrap();
+        assert_eq!(record.typed_count, 1);
+        assert_eq!(record.title.as_deref(), Some("Example"));
+        assert_eq!(unix_millis(record.last_visit), unix_millis(history.get(&url("https://example.com/")).unwrap().last_visit));
+    }
+}
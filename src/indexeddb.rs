@@ -1,4 +1,6 @@
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 use std::sync::{Arc, Mutex};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
@@ -11,6 +13,8 @@ pub enum IDBError {
     ConstraintError(String),
     VersionError(String),
     DataError(String),
+    SyntaxError(String),
+    InvalidAccessError(String),
     TransactionInactiveError,
     ReadOnlyError,
     AbortError,
@@ -25,6 +29,8 @@ impl std::fmt::Display for IDBError {
             IDBError::ConstraintError(msg) => write!(f, "ConstraintError: {}", msg),
             IDBError::VersionError(msg) => write!(f, "VersionError: {}", msg),
             IDBError::DataError(msg) => write!(f, "DataError: {}", msg),
+            IDBError::SyntaxError(msg) => write!(f, "SyntaxError: {}", msg),
+            IDBError::InvalidAccessError(msg) => write!(f, "InvalidAccessError: {}", msg),
             IDBError::TransactionInactiveError => write!(f, "TransactionInactiveError"),
             IDBError::ReadOnlyError => write!(f, "ReadOnlyError"),
             IDBError::AbortError => write!(f, "AbortError"),
@@ -35,21 +41,70 @@ impl std::fmt::Display for IDBError {
 
 impl std::error::Error for IDBError {}
 
+/// Largest key the key generator hands out (2^53)
+const MAX_GENERATED_KEY: i64 = 1 << 53;
+
 /// Key for IndexedDB records
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+///
+/// Keys of different types sort Number < Date < String < Array, as in the
+/// Indexed DB spec; equality and hashing agree with that ordering.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum IDBKey {
-    Number(i64),
+    Number(f64),
     String(String),
     Date(i64), // Unix timestamp
     Array(Vec<IDBKey>),
 }
 
 impl IDBKey {
-    pub fn compare(&self, other: &IDBKey) -> std::cmp::Ordering {
-        use std::cmp::Ordering;
+    /// Convert a stored value to a key
+    ///
+    /// Only numbers, strings and arrays of valid keys are keys.
+    pub fn from_json(value: &JsonValue) -> Result<IDBKey, IDBError> {
+        match value {
+            JsonValue::Number(n) => n
+                .as_f64()
+                .map(IDBKey::Number)
+                .ok_or_else(|| IDBError::DataError("Number is not a valid key".to_string())),
+            JsonValue::String(s) => Ok(IDBKey::String(s.clone())),
+            JsonValue::Array(items) => items.iter().map(IDBKey::from_json).collect::<Result<_, _>>().map(IDBKey::Array),
+            other => Err(IDBError::DataError(format!("{} is not a valid key", other))),
+        }
+    }
+
+    /// The key as a value, for storing it inside a record
+    pub fn to_json(&self) -> JsonValue {
+        match self {
+            IDBKey::Number(n) => serde_json::json!(n),
+            IDBKey::String(s) => JsonValue::String(s.clone()),
+            IDBKey::Date(ms) => serde_json::json!(ms),
+            IDBKey::Array(keys) => JsonValue::Array(keys.iter().map(IDBKey::to_json).collect()),
+        }
+    }
+
+    /// NaN is not a key, nor is an array containing one
+    pub fn is_valid(&self) -> bool {
+        match self {
+            IDBKey::Number(n) => !n.is_nan(),
+            IDBKey::Array(keys) => keys.iter().all(IDBKey::is_valid),
+            _ => true,
+        }
+    }
+
+    fn type_rank(&self) -> u8 {
+        match self {
+            IDBKey::Number(_) => 0,
+            IDBKey::Date(_) => 1,
+            IDBKey::String(_) => 2,
+            IDBKey::Array(_) => 3,
+        }
+    }
+
+    pub fn compare(&self, other: &IDBKey) -> Ordering {
         match (self, other) {
-            (IDBKey::Number(a), IDBKey::Number(b)) => a.cmp(b),
-            (IDBKey::String(a), IDBKey::String(b)) => a.cmp(b),
+            (IDBKey::Number(a), IDBKey::Number(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+            // Strings compare by UTF-16 code units, like JavaScript
+            (IDBKey::String(a), IDBKey::String(b)) => a.encode_utf16().cmp(b.encode_utf16()),
             (IDBKey::Date(a), IDBKey::Date(b)) => a.cmp(b),
             (IDBKey::Array(a), IDBKey::Array(b)) => {
                 // Compare element by element
@@ -61,11 +116,151 @@ impl IDBKey {
                 }
                 a.len().cmp(&b.len())
             }
-            _ => Ordering::Equal,
+            _ => self.type_rank().cmp(&other.type_rank()),
         }
     }
 }
 
+impl PartialEq for IDBKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.compare(other) == Ordering::Equal
+    }
+}
+
+impl Eq for IDBKey {}
+
+impl PartialOrd for IDBKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for IDBKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.compare(other)
+    }
+}
+
+impl Hash for IDBKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.type_rank().hash(state);
+        match self {
+            // 0.0 and -0.0 are the same key
+            IDBKey::Number(n) => (if *n == 0.0 { 0.0f64 } else { *n }).to_bits().hash(state),
+            IDBKey::String(s) => s.hash(state),
+            IDBKey::Date(ms) => ms.hash(state),
+            IDBKey::Array(keys) => keys.hash(state),
+        }
+    }
+}
+
+/// Where a record's key is found inside its value
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IDBKeyPath {
+    /// Property names separated by dots, e.g. "a.b"; "" is the value itself
+    String(String),
+    /// Several paths whose keys make up a compound (array) key
+    Array(Vec<String>),
+}
+
+impl IDBKeyPath {
+    /// Paths are identifiers separated by dots; arrays must not be empty
+    pub fn is_valid(&self) -> bool {
+        match self {
+            IDBKeyPath::String(path) => is_valid_path(path),
+            IDBKeyPath::Array(paths) => !paths.is_empty() && paths.iter().all(|p| is_valid_path(p)),
+        }
+    }
+
+    /// Evaluate the path on a value; `None` if a property is missing
+    pub fn evaluate(&self, value: &JsonValue) -> Option<JsonValue> {
+        match self {
+            IDBKeyPath::String(path) => evaluate_path(value, path),
+            IDBKeyPath::Array(paths) => paths
+                .iter()
+                .map(|path| evaluate_path(value, path))
+                .collect::<Option<Vec<_>>>()
+                .map(JsonValue::Array),
+        }
+    }
+
+    /// Extract a key from a value
+    ///
+    /// `Ok(None)` if the path yields nothing; a `DataError` if it yields
+    /// something that is not a valid key.
+    pub fn extract_key(&self, value: &JsonValue) -> Result<Option<IDBKey>, IDBError> {
+        self.evaluate(value).map(|found| IDBKey::from_json(&found)).transpose()
+    }
+
+    /// Store a generated key in a value at this path, creating objects on the way
+    fn inject(&self, value: &mut JsonValue, key: &IDBKey) -> Result<(), IDBError> {
+        let IDBKeyPath::String(path) = self else {
+            return Err(IDBError::DataError("Cannot inject a key at an array key path".to_string()));
+        };
+        let mut parts: Vec<&str> = path.split('.').collect();
+        let last = parts.pop().filter(|p| !p.is_empty());
+        let mut current = value;
+        for part in parts {
+            let JsonValue::Object(object) = current else {
+                return Err(IDBError::DataError(format!("Cannot inject a key at '{}'", path)));
+            };
+            current = object.entry(part).or_insert_with(|| JsonValue::Object(Default::default()));
+        }
+        match (current, last) {
+            (JsonValue::Object(object), Some(last)) => {
+                object.insert(last.to_string(), key.to_json());
+                Ok(())
+            }
+            _ => Err(IDBError::DataError(format!("Cannot inject a key at '{}'", path))),
+        }
+    }
+}
+
+impl From<&str> for IDBKeyPath {
+    fn from(path: &str) -> Self {
+        IDBKeyPath::String(path.to_string())
+    }
+}
+
+impl From<String> for IDBKeyPath {
+    fn from(path: String) -> Self {
+        IDBKeyPath::String(path)
+    }
+}
+
+impl From<Vec<String>> for IDBKeyPath {
+    fn from(paths: Vec<String>) -> Self {
+        IDBKeyPath::Array(paths)
+    }
+}
+
+/// "" or identifiers separated by dots
+fn is_valid_path(path: &str) -> bool {
+    path.is_empty()
+        || path.split('.').all(|identifier| {
+            let mut chars = identifier.chars();
+            chars.next().is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+                && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+        })
+}
+
+/// Follow a dotted path; strings and arrays also have a `length`
+fn evaluate_path(value: &JsonValue, path: &str) -> Option<JsonValue> {
+    if path.is_empty() {
+        return Some(value.clone());
+    }
+    let mut current = value.clone();
+    for identifier in path.split('.') {
+        current = match (&current, identifier) {
+            (JsonValue::String(s), "length") => serde_json::json!(s.encode_utf16().count()),
+            (JsonValue::Array(items), "length") => serde_json::json!(items.len()),
+            (JsonValue::Object(object), _) => object.get(identifier)?.clone(),
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
 /// Key range for queries
 #[derive(Debug, Clone)]
 pub struct IDBKeyRange {
@@ -165,7 +360,7 @@ impl Default for IDBIndexParameters {
 /// Object store parameters
 #[derive(Debug, Clone)]
 pub struct IDBObjectStoreParameters {
-    pub key_path: Option<String>,
+    pub key_path: Option<IDBKeyPath>,
     pub auto_increment: bool,
 }
 
@@ -182,7 +377,7 @@ impl Default for IDBObjectStoreParameters {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct IndexData {
     name: String,
-    key_path: String,
+    key_path: IDBKeyPath,
     unique: bool,
     multi_entry: bool,
     /// Map from index key to primary keys
@@ -193,13 +388,16 @@ struct IndexData {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IDBObjectStore {
     name: String,
-    key_path: Option<String>,
+    key_path: Option<IDBKeyPath>,
     auto_increment: bool,
     auto_increment_counter: i64,
     /// Primary key -> value mapping
     records: HashMap<IDBKey, JsonValue>,
     /// Index name -> index data
     indexes: HashMap<String, IndexData>,
+    /// Bumped by each committed transaction that wrote to the store
+    #[serde(default)]
+    revision: u64,
 }
 
 impl IDBObjectStore {
@@ -211,6 +409,7 @@ impl IDBObjectStore {
             auto_increment_counter: 0,
             records: HashMap::new(),
             indexes: HashMap::new(),
+            revision: 0,
         }
     }
 
@@ -218,8 +417,8 @@ impl IDBObjectStore {
         &self.name
     }
 
-    pub fn key_path(&self) -> Option<&str> {
-        self.key_path.as_deref()
+    pub fn key_path(&self) -> Option<&IDBKeyPath> {
+        self.key_path.as_ref()
     }
 
     pub fn auto_increment(&self) -> bool {
@@ -230,8 +429,8 @@ impl IDBObjectStore {
         self.indexes.keys().cloned().collect()
     }
 
-    pub fn add(&mut self, value: JsonValue, key: Option<IDBKey>) -> Result<IDBKey, IDBError> {
-        let final_key = self.resolve_key(key, &value)?;
+    pub fn add(&mut self, mut value: JsonValue, key: Option<IDBKey>) -> Result<IDBKey, IDBError> {
+        let final_key = self.resolve_key(key, &mut value)?;
         
         if self.records.contains_key(&final_key) {
             return Err(IDBError::ConstraintError("Key already exists".to_string()));
@@ -243,8 +442,8 @@ impl IDBObjectStore {
         Ok(final_key)
     }
 
-    pub fn put(&mut self, value: JsonValue, key: Option<IDBKey>) -> Result<IDBKey, IDBError> {
-        let final_key = self.resolve_key(key, &value)?;
+    pub fn put(&mut self, mut value: JsonValue, key: Option<IDBKey>) -> Result<IDBKey, IDBError> {
+        let final_key = self.resolve_key(key, &mut value)?;
         
         // Remove from indexes if updating
         if self.records.contains_key(&final_key) {
@@ -314,10 +513,17 @@ impl IDBObjectStore {
         keys
     }
 
-    pub fn create_index(&mut self, name: String, key_path: String, params: IDBIndexParameters) -> Result<(), IDBError> {
+    pub fn create_index(&mut self, name: String, key_path: impl Into<IDBKeyPath>, params: IDBIndexParameters) -> Result<(), IDBError> {
         if self.indexes.contains_key(&name) {
             return Err(IDBError::ConstraintError("Index already exists".to_string()));
         }
+        let key_path = key_path.into();
+        if !key_path.is_valid() {
+            return Err(IDBError::SyntaxError(format!("Invalid key path {:?}", key_path)));
+        }
+        if params.multi_entry && matches!(key_path, IDBKeyPath::Array(_)) {
+            return Err(IDBError::InvalidAccessError("multiEntry index with an array key path".to_string()));
+        }
         
         let mut index = IndexData {
             name: name.clone(),
@@ -329,7 +535,7 @@ impl IDBObjectStore {
         
         // Build index from existing records
         for (primary_key, value) in &self.records {
-            if let Some(index_key) = self.extract_index_key(value, &index.key_path) {
+            for index_key in index.keys_for(value) {
                 index.entries.entry(index_key)
                     .or_insert_with(Vec::new)
                     .push(primary_key.clone());
@@ -346,37 +552,74 @@ impl IDBObjectStore {
         Ok(())
     }
 
-    pub fn index(&self, name: &str) -> Result<&IndexData, IDBError> {
-        self.indexes.get(name)
-            .ok_or_else(|| IDBError::NotFoundError("Index not found".to_string()))
+    pub fn index(&self, name: &str) -> Result<IDBIndex<'_>, IDBError> {
+        let data = self.indexes.get(name)
+            .ok_or_else(|| IDBError::NotFoundError("Index not found".to_string()))?;
+        Ok(IDBIndex { store: self, data })
     }
 
-    fn resolve_key(&mut self, key: Option<IDBKey>, _value: &JsonValue) -> Result<IDBKey, IDBError> {
-        if let Some(k) = key {
-            Ok(k)
-        } else if self.auto_increment {
-            self.auto_increment_counter += 1;
-            Ok(IDBKey::Number(self.auto_increment_counter))
-        } else if self.key_path.is_some() {
-            // In real implementation, extract from value using key_path
-            Err(IDBError::DataError("Key path not implemented".to_string()))
-        } else {
-            Err(IDBError::DataError("No key provided".to_string()))
+    /// Cursor over the records in `range`, in key order
+    pub fn open_cursor(&self, range: Option<&IDBKeyRange>, direction: IDBCursorDirection) -> IDBCursor {
+        let records = self.records.iter()
+            .filter(|(key, _)| range.is_none_or(|r| r.includes(key)))
+            .map(|(key, value)| CursorRecord {
+                key: key.clone(),
+                primary_key: key.clone(),
+                value: value.clone(),
+            })
+            .collect();
+        IDBCursor::from_records(records, direction, Some(IDBCursorSource::Store(self.name.clone())))
+    }
+
+    /// Work out a record's key: given, found at the key path, or generated
+    ///
+    /// A generated key is also written into the value at the key path.
+    fn resolve_key(&mut self, key: Option<IDBKey>, value: &mut JsonValue) -> Result<IDBKey, IDBError> {
+        let key = match (&self.key_path, key) {
+            (Some(_), Some(_)) => {
+                return Err(IDBError::DataError("Key given for a store with a key path".to_string()));
+            }
+            (Some(key_path), None) => match key_path.extract_key(value)? {
+                Some(key) => key,
+                None if self.auto_increment => {
+                    let key = self.next_generated_key()?;
+                    key_path.inject(value, &key)?;
+                    self.auto_increment_counter += 1;
+                    return Ok(key);
+                }
+                None => return Err(IDBError::DataError("Key path yielded no value".to_string())),
+            },
+            (None, Some(key)) => key,
+            (None, None) if self.auto_increment => {
+                let key = self.next_generated_key()?;
+                self.auto_increment_counter += 1;
+                return Ok(key);
+            }
+            (None, None) => return Err(IDBError::DataError("No key provided".to_string())),
+        };
+
+        if !key.is_valid() {
+            return Err(IDBError::DataError("Invalid key".to_string()));
+        }
+        // Explicit numeric keys move the generator past them
+        if let (true, IDBKey::Number(n)) = (self.auto_increment, &key) {
+            if *n >= self.auto_increment_counter as f64 {
+                self.auto_increment_counter = n.floor().min(MAX_GENERATED_KEY as f64) as i64;
+            }
+        }
+        Ok(key)
+    }
+
+    fn next_generated_key(&self) -> Result<IDBKey, IDBError> {
+        if self.auto_increment_counter >= MAX_GENERATED_KEY {
+            return Err(IDBError::ConstraintError("Key generator exhausted".to_string()));
         }
+        Ok(IDBKey::Number((self.auto_increment_counter + 1) as f64))
     }
 
     fn update_indexes(&mut self, key: &IDBKey, value: &JsonValue) -> Result<(), IDBError> {
-        // Collect index updates to avoid borrowing issues
-        let updates: Vec<_> = self.indexes.iter()
-            .filter_map(|(name, index)| {
-                self.extract_index_key(value, &index.key_path)
-                    .map(|index_key| (name.clone(), index_key))
-            })
-            .collect();
-        
-        // Apply updates
-        for (name, index_key) in updates {
-            if let Some(index) = self.indexes.get_mut(&name) {
+        for index in self.indexes.values_mut() {
+            for index_key in index.keys_for(value) {
                 index.entries.entry(index_key)
                     .or_insert_with(Vec::new)
                     .push(key.clone());
@@ -393,47 +636,194 @@ impl IDBObjectStore {
         }
     }
 
-    fn extract_index_key(&self, value: &JsonValue, key_path: &str) -> Option<IDBKey> {
-        // Simplified: extract from top-level property
-        if let JsonValue::Object(obj) = value {
-            if let Some(JsonValue::Number(n)) = obj.get(key_path) {
-                return n.as_i64().map(IDBKey::Number);
-            } else if let Some(JsonValue::String(s)) = obj.get(key_path) {
-                return Some(IDBKey::String(s.clone()));
+}
+
+impl IndexData {
+    /// Index keys for a record; records without a valid key are not indexed
+    ///
+    /// A multiEntry index adds one entry per distinct valid array element.
+    fn keys_for(&self, value: &JsonValue) -> Vec<IDBKey> {
+        let Some(found) = self.key_path.evaluate(value) else {
+            return Vec::new();
+        };
+        match (&found, self.multi_entry) {
+            (JsonValue::Array(items), true) => {
+                let mut keys: Vec<IDBKey> = items.iter().filter_map(|item| IDBKey::from_json(item).ok()).collect();
+                keys.sort();
+                keys.dedup();
+                keys
             }
+            _ => IDBKey::from_json(&found).into_iter().collect(),
+        }
+    }
+}
+
+/// Read access to an index, resolving index keys to the store's records
+#[derive(Debug, Clone, Copy)]
+pub struct IDBIndex<'a> {
+    store: &'a IDBObjectStore,
+    data: &'a IndexData,
+}
+
+impl<'a> IDBIndex<'a> {
+    pub fn name(&self) -> &str {
+        &self.data.name
+    }
+
+    pub fn key_path(&self) -> &IDBKeyPath {
+        &self.data.key_path
+    }
+
+    pub fn unique(&self) -> bool {
+        self.data.unique
+    }
+
+    pub fn multi_entry(&self) -> bool {
+        self.data.multi_entry
+    }
+
+    /// (index key, primary key) pairs in `range`, ordered by index key then primary key
+    fn entries(&self, range: Option<&IDBKeyRange>) -> Vec<(IDBKey, IDBKey)> {
+        let mut entries: Vec<_> = self.data.entries.iter()
+            .filter(|(index_key, _)| range.is_none_or(|r| r.includes(index_key)))
+            .flat_map(|(index_key, primary_keys)| {
+                primary_keys.iter().map(move |primary_key| (index_key.clone(), primary_key.clone()))
+            })
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    /// First record (by primary key) whose index key is `key`
+    pub fn get(&self, key: &IDBKey) -> Option<&'a JsonValue> {
+        let primary_key = self.get_key(key)?;
+        self.store.records.get(&primary_key)
+    }
+
+    /// Primary key of the first record whose index key is `key`
+    pub fn get_key(&self, key: &IDBKey) -> Option<IDBKey> {
+        self.data.entries.get(key)?.iter().min().cloned()
+    }
+
+    /// Records in `range` as (primary key, value), in index order
+    pub fn get_all(&self, range: Option<&IDBKeyRange>, count: Option<usize>) -> Vec<(IDBKey, JsonValue)> {
+        self.get_all_keys(range, count)
+            .into_iter()
+            .filter_map(|primary_key| {
+                let value = self.store.records.get(&primary_key)?.clone();
+                Some((primary_key, value))
+            })
+            .collect()
+    }
+
+    /// Primary keys of the records in `range`, in index order
+    pub fn get_all_keys(&self, range: Option<&IDBKeyRange>, count: Option<usize>) -> Vec<IDBKey> {
+        let mut keys: Vec<_> = self.entries(range).into_iter().map(|(_, primary_key)| primary_key).collect();
+        if let Some(max_count) = count {
+            keys.truncate(max_count);
         }
-        None
+        keys
+    }
+
+    /// Number of index entries in `range`
+    pub fn count(&self, range: Option<&IDBKeyRange>) -> usize {
+        self.data.entries.iter()
+            .filter(|(index_key, _)| range.is_none_or(|r| r.includes(index_key)))
+            .map(|(_, primary_keys)| primary_keys.len())
+            .sum()
+    }
+
+    /// Cursor over the records in `range`; its key is the index key
+    pub fn open_cursor(&self, range: Option<&IDBKeyRange>, direction: IDBCursorDirection) -> IDBCursor {
+        let records = self.entries(range)
+            .into_iter()
+            .filter_map(|(key, primary_key)| {
+                let value = self.store.records.get(&primary_key)?.clone();
+                Some(CursorRecord { key, primary_key, value })
+            })
+            .collect();
+        let source = IDBCursorSource::Index {
+            store: self.store.name.clone(),
+            index: self.data.name.clone(),
+        };
+        IDBCursor::from_records(records, direction, Some(source))
     }
 }
 
+/// What a cursor iterates over
+#[derive(Debug, Clone, PartialEq)]
+pub enum IDBCursorSource {
+    Store(String),
+    Index { store: String, index: String },
+}
+
+/// One position of a cursor
+#[derive(Debug, Clone)]
+struct CursorRecord {
+    /// Store key, or index key for an index cursor
+    key: IDBKey,
+    primary_key: IDBKey,
+    value: JsonValue,
+}
+
 /// Cursor for iterating records
+///
+/// Iterates a snapshot taken when it was opened. `update` and `delete`
+/// write through a transaction and keep the snapshot in step.
 #[derive(Debug)]
 pub struct IDBCursor {
-    keys: Vec<IDBKey>,
+    records: Vec<CursorRecord>,
     current_index: usize,
     direction: IDBCursorDirection,
+    source: Option<IDBCursorSource>,
 }
 
 impl IDBCursor {
-    pub fn new(mut keys: Vec<IDBKey>, direction: IDBCursorDirection) -> Self {
-        keys.sort_by(|a, b| a.compare(b));
-        
-        match direction {
-            IDBCursorDirection::Prev | IDBCursorDirection::PrevUnique => {
-                keys.reverse();
-            }
-            _ => {}
+    /// Key-only cursor over a list of keys
+    pub fn new(keys: Vec<IDBKey>, direction: IDBCursorDirection) -> Self {
+        let records = keys.into_iter()
+            .map(|key| CursorRecord { primary_key: key.clone(), key, value: JsonValue::Null })
+            .collect();
+        Self::from_records(records, direction, None)
+    }
+
+    fn from_records(mut records: Vec<CursorRecord>, direction: IDBCursorDirection, source: Option<IDBCursorSource>) -> Self {
+        records.sort_by(|a, b| a.key.cmp(&b.key).then_with(|| a.primary_key.cmp(&b.primary_key)));
+
+        // Unique directions keep the lowest primary key of each key, in both directions
+        if matches!(direction, IDBCursorDirection::NextUnique | IDBCursorDirection::PrevUnique) {
+            records.dedup_by(|later, first| later.key == first.key);
         }
-        
+        if matches!(direction, IDBCursorDirection::Prev | IDBCursorDirection::PrevUnique) {
+            records.reverse();
+        }
+
         Self {
-            keys,
+            records,
             current_index: 0,
             direction,
+            source,
         }
     }
 
     pub fn key(&self) -> Option<&IDBKey> {
-        self.keys.get(self.current_index)
+        self.current().map(|record| &record.key)
+    }
+
+    pub fn primary_key(&self) -> Option<&IDBKey> {
+        self.current().map(|record| &record.primary_key)
+    }
+
+    pub fn value(&self) -> Option<&JsonValue> {
+        self.current().map(|record| &record.value)
+    }
+
+    pub fn direction(&self) -> IDBCursorDirection {
+        self.direction
+    }
+
+    pub fn source(&self) -> Option<&IDBCursorSource> {
+        self.source.as_ref()
     }
 
     pub fn advance(&mut self, count: usize) {
@@ -444,26 +834,123 @@ impl IDBCursor {
         self.current_index += 1;
     }
 
+    /// Move to the next position whose key is at or past `key` in the cursor's direction
+    pub fn continue_to(&mut self, key: &IDBKey) {
+        let forward = matches!(self.direction, IDBCursorDirection::Next | IDBCursorDirection::NextUnique);
+        self.current_index += 1;
+        while let Some(record) = self.current() {
+            let reached = if forward { record.key >= *key } else { record.key <= *key };
+            if reached {
+                break;
+            }
+            self.current_index += 1;
+        }
+    }
+
     pub fn has_value(&self) -> bool {
-        self.current_index < self.keys.len()
+        self.current_index < self.records.len()
+    }
+
+    /// Replace the current record's value in the transaction
+    ///
+    /// For stores with a key path the new value must keep the same key.
+    pub fn update(&mut self, transaction: &mut IDBTransaction, value: JsonValue) -> Result<IDBKey, IDBError> {
+        let store_name = self.store_name()?.to_string();
+        let primary_key = self.primary_key()
+            .cloned()
+            .ok_or_else(|| IDBError::InvalidStateError("Cursor is past the end".to_string()))?;
+
+        let key = match transaction.object_store(&store_name)?.key_path() {
+            Some(key_path) => {
+                if key_path.extract_key(&value)?.as_ref() != Some(&primary_key) {
+                    return Err(IDBError::DataError("Value's key differs from the cursor's primary key".to_string()));
+                }
+                None
+            }
+            None => Some(primary_key),
+        };
+        let key = transaction.put(&store_name, value.clone(), key)?;
+
+        if let Some(record) = self.records.get_mut(self.current_index) {
+            record.value = value;
+        }
+        Ok(key)
+    }
+
+    /// Delete the current record in the transaction
+    pub fn delete(&mut self, transaction: &mut IDBTransaction) -> Result<(), IDBError> {
+        let store_name = self.store_name()?.to_string();
+        let primary_key = self.primary_key()
+            .cloned()
+            .ok_or_else(|| IDBError::InvalidStateError("Cursor is past the end".to_string()))?;
+        transaction.delete(&store_name, &primary_key)
+    }
+
+    fn current(&self) -> Option<&CursorRecord> {
+        self.records.get(self.current_index)
+    }
+
+    fn store_name(&self) -> Result<&str, IDBError> {
+        match &self.source {
+            Some(IDBCursorSource::Store(store)) | Some(IDBCursorSource::Index { store, .. }) => Ok(store),
+            None => Err(IDBError::InvalidStateError("Cursor is not over a store".to_string())),
+        }
     }
 }
 
+/// Lifecycle of a transaction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IDBTransactionState {
+    Active,
+    Committed,
+    Aborted,
+}
+
+/// Transactions running against a database, shared with its transactions
+#[derive(Debug, Default)]
+struct TransactionRegistry {
+    next_id: u64,
+    running: HashMap<u64, IDBTransactionMode>,
+}
+
 /// Transaction
+///
+/// Works on its own copies of the stores in its scope: writes are buffered
+/// there, become visible to the database all at once on `commit`, and are
+/// dropped on `abort` or when the transaction is dropped uncommitted.
 pub struct IDBTransaction {
+    id: u64,
     mode: IDBTransactionMode,
     store_names: Vec<String>,
-    active: bool,
-    aborted: bool,
+    state: IDBTransactionState,
+    /// Working copies of the scoped stores
+    stores: HashMap<String, IDBObjectStore>,
+    /// Revision of each store when it was copied, to detect conflicting commits
+    base_revisions: HashMap<String, u64>,
+    /// Stores written to
+    dirty: HashSet<String>,
+    /// Version the database moves to (version change transactions only)
+    new_version: Option<u64>,
+    registry: Arc<Mutex<TransactionRegistry>>,
 }
 
 impl IDBTransaction {
-    pub fn new(store_names: Vec<String>, mode: IDBTransactionMode) -> Self {
+    fn new(db: &IDBDatabase, id: u64, store_names: Vec<String>, mode: IDBTransactionMode) -> Self {
+        let stores: HashMap<String, IDBObjectStore> = store_names
+            .iter()
+            .filter_map(|name| db.object_stores.get(name).map(|store| (name.clone(), store.clone())))
+            .collect();
+        let base_revisions = stores.iter().map(|(name, store)| (name.clone(), store.revision)).collect();
         Self {
+            id,
             mode,
             store_names,
-            active: true,
-            aborted: false,
+            state: IDBTransactionState::Active,
+            stores,
+            base_revisions,
+            dirty: HashSet::new(),
+            new_version: None,
+            registry: db.transactions.clone(),
         }
     }
 
@@ -475,17 +962,154 @@ impl IDBTransaction {
         &self.store_names
     }
 
+    pub fn state(&self) -> IDBTransactionState {
+        self.state
+    }
+
     pub fn is_active(&self) -> bool {
-        self.active && !self.aborted
+        self.state == IDBTransactionState::Active
+    }
+
+    /// Read a store in scope, including this transaction's own writes
+    pub fn object_store(&self, name: &str) -> Result<&IDBObjectStore, IDBError> {
+        self.check_active()?;
+        self.stores.get(name)
+            .ok_or_else(|| IDBError::NotFoundError(format!("Store '{}' is not in the transaction's scope", name)))
+    }
+
+    pub fn add(&mut self, store: &str, value: JsonValue, key: Option<IDBKey>) -> Result<IDBKey, IDBError> {
+        self.writable_store(store)?.add(value, key)
+    }
+
+    pub fn put(&mut self, store: &str, value: JsonValue, key: Option<IDBKey>) -> Result<IDBKey, IDBError> {
+        self.writable_store(store)?.put(value, key)
+    }
+
+    pub fn delete(&mut self, store: &str, key: &IDBKey) -> Result<(), IDBError> {
+        self.writable_store(store)?.delete(key)
+    }
+
+    pub fn clear(&mut self, store: &str) -> Result<(), IDBError> {
+        self.writable_store(store)?.clear()
+    }
+
+    /// Create a store (version change transactions only)
+    pub fn create_object_store(&mut self, name: String, params: IDBObjectStoreParameters) -> Result<(), IDBError> {
+        self.check_version_change()?;
+        if self.stores.contains_key(&name) {
+            return Err(IDBError::ConstraintError("Object store already exists".to_string()));
+        }
+        validate_store_parameters(&params)?;
+        self.stores.insert(name.clone(), IDBObjectStore::new(name.clone(), params));
+        self.store_names.push(name);
+        Ok(())
     }
 
+    /// Delete a store (version change transactions only)
+    pub fn delete_object_store(&mut self, name: &str) -> Result<(), IDBError> {
+        self.check_version_change()?;
+        self.stores.remove(name)
+            .ok_or_else(|| IDBError::NotFoundError("Object store not found".to_string()))?;
+        self.store_names.retain(|n| n != name);
+        Ok(())
+    }
+
+    /// Create an index (version change transactions only)
+    pub fn create_index(&mut self, store: &str, name: String, key_path: impl Into<IDBKeyPath>, params: IDBIndexParameters) -> Result<(), IDBError> {
+        self.check_version_change()?;
+        self.writable_store(store)?.create_index(name, key_path, params)
+    }
+
+    /// Delete an index (version change transactions only)
+    pub fn delete_index(&mut self, store: &str, name: &str) -> Result<(), IDBError> {
+        self.check_version_change()?;
+        self.writable_store(store)?.delete_index(name)
+    }
+
+    /// Discard every buffered write
     pub fn abort(&mut self) {
-        self.aborted = true;
-        self.active = false;
+        if self.is_active() {
+            self.state = IDBTransactionState::Aborted;
+            self.stores.clear();
+            self.finish();
+        }
     }
 
-    pub fn commit(&mut self) {
-        self.active = false;
+    /// Apply the buffered writes to the database, all or nothing
+    ///
+    /// Fails with `AbortError`, discarding the writes, if another
+    /// transaction committed to one of the written stores since this one
+    /// started.
+    pub fn commit(&mut self, db: &mut IDBDatabase) -> Result<(), IDBError> {
+        self.check_active()?;
+        if !Arc::ptr_eq(&self.registry, &db.transactions) {
+            return Err(IDBError::InvalidStateError("Transaction belongs to another database".to_string()));
+        }
+
+        if let Some(version) = self.new_version {
+            // Exclusive: nothing else ran, so the copies replace every store
+            db.object_stores = std::mem::take(&mut self.stores);
+            db.version = version;
+        } else {
+            let conflict = self.dirty.iter().any(|name| {
+                db.object_stores.get(name).map(|store| store.revision) != self.base_revisions.get(name).copied()
+            });
+            if conflict {
+                self.abort();
+                return Err(IDBError::AbortError);
+            }
+            for name in self.dirty.drain() {
+                if let Some(mut store) = self.stores.remove(&name) {
+                    store.revision += 1;
+                    db.object_stores.insert(name, store);
+                }
+            }
+        }
+
+        self.state = IDBTransactionState::Committed;
+        self.stores.clear();
+        self.finish();
+        Ok(())
+    }
+
+    fn check_active(&self) -> Result<(), IDBError> {
+        match self.state {
+            IDBTransactionState::Active => Ok(()),
+            _ => Err(IDBError::TransactionInactiveError),
+        }
+    }
+
+    fn check_version_change(&self) -> Result<(), IDBError> {
+        self.check_active()?;
+        if self.mode != IDBTransactionMode::VersionChange {
+            return Err(IDBError::InvalidStateError("Schema changes need a version change transaction".to_string()));
+        }
+        Ok(())
+    }
+
+    fn writable_store(&mut self, name: &str) -> Result<&mut IDBObjectStore, IDBError> {
+        self.check_active()?;
+        if self.mode == IDBTransactionMode::ReadOnly {
+            return Err(IDBError::ReadOnlyError);
+        }
+        let store = self.stores.get_mut(name)
+            .ok_or_else(|| IDBError::NotFoundError(format!("Store '{}' is not in the transaction's scope", name)))?;
+        self.dirty.insert(name.to_string());
+        Ok(store)
+    }
+
+    /// Leave the database's list of running transactions
+    fn finish(&mut self) {
+        if let Ok(mut registry) = self.registry.lock() {
+            registry.running.remove(&self.id);
+        }
+    }
+}
+
+impl Drop for IDBTransaction {
+    fn drop(&mut self) {
+        // Never committed: the writes are lost, as on abort
+        self.abort();
     }
 }
 
@@ -495,6 +1119,8 @@ pub struct IDBDatabase {
     name: String,
     version: u64,
     object_stores: HashMap<String, IDBObjectStore>,
+    #[serde(skip)]
+    transactions: Arc<Mutex<TransactionRegistry>>,
 }
 
 impl IDBDatabase {
@@ -503,6 +1129,7 @@ impl IDBDatabase {
             name,
             version,
             object_stores: HashMap::new(),
+            transactions: Arc::default(),
         }
     }
 
@@ -522,6 +1149,7 @@ impl IDBDatabase {
         if self.object_stores.contains_key(&name) {
             return Err(IDBError::ConstraintError("Object store already exists".to_string()));
         }
+        validate_store_parameters(&params)?;
         
         let store = IDBObjectStore::new(name.clone(), params);
         self.object_stores.insert(name, store);
@@ -539,12 +1167,14 @@ impl IDBDatabase {
             .ok_or_else(|| IDBError::NotFoundError("Object store not found".to_string()))
     }
 
-    pub fn object_store_mut(&mut self, name: &str) -> Result<&mut IDBObjectStore, IDBError> {
-        self.object_stores.get_mut(name)
-            .ok_or_else(|| IDBError::NotFoundError("Object store not found".to_string()))
-    }
-
+    /// Start a read-only or read-write transaction over some stores
+    ///
+    /// Version change transactions come from `upgrade`; none can start
+    /// while one is running.
     pub fn transaction(&self, store_names: Vec<String>, mode: IDBTransactionMode) -> Result<IDBTransaction, IDBError> {
+        if mode == IDBTransactionMode::VersionChange {
+            return Err(IDBError::InvalidAccessError("Version change transactions are started by upgrade".to_string()));
+        }
         // Verify all stores exist
         for name in &store_names {
             if !self.object_stores.contains_key(name) {
@@ -552,8 +1182,62 @@ impl IDBDatabase {
             }
         }
         
-        Ok(IDBTransaction::new(store_names, mode))
+        let id = self.register(mode)?;
+        Ok(IDBTransaction::new(self, id, store_names, mode))
+    }
+
+    /// Start the version change transaction moving the database to `version`
+    ///
+    /// It covers every store and waits for no one: it fails while any other
+    /// transaction is running, and blocks new ones until it finishes.
+    pub fn upgrade(&self, version: u64) -> Result<IDBTransaction, IDBError> {
+        if version <= self.version {
+            return Err(IDBError::VersionError("Requested version is not higher than current".to_string()));
+        }
+        let id = self.register(IDBTransactionMode::VersionChange)?;
+        let mut transaction = IDBTransaction::new(self, id, self.object_store_names(), IDBTransactionMode::VersionChange);
+        transaction.new_version = Some(version);
+        Ok(transaction)
+    }
+
+    /// Number of transactions started and not yet finished
+    pub fn running_transactions(&self) -> usize {
+        self.transactions.lock().map_or(0, |registry| registry.running.len())
+    }
+
+    fn register(&self, mode: IDBTransactionMode) -> Result<u64, IDBError> {
+        let mut registry = self.transactions.lock()
+            .map_err(|_| IDBError::UnknownError("Transaction registry poisoned".to_string()))?;
+        let version_change_running = registry.running.values().any(|m| *m == IDBTransactionMode::VersionChange);
+        let blocked = match mode {
+            IDBTransactionMode::VersionChange => !registry.running.is_empty(),
+            _ => version_change_running,
+        };
+        if blocked {
+            return Err(IDBError::InvalidStateError("A version change transaction must run alone".to_string()));
+        }
+        registry.next_id += 1;
+        let id = registry.next_id;
+        registry.running.insert(id, mode);
+        Ok(id)
+    }
+}
+
+/// Key path rules for a new store
+fn validate_store_parameters(params: &IDBObjectStoreParameters) -> Result<(), IDBError> {
+    if let Some(key_path) = &params.key_path {
+        if !key_path.is_valid() {
+            return Err(IDBError::SyntaxError(format!("Invalid key path {:?}", key_path)));
+        }
+        let generated_unstorable = matches!(key_path, IDBKeyPath::Array(_))
+            || *key_path == IDBKeyPath::String(String::new());
+        if params.auto_increment && generated_unstorable {
+            return Err(IDBError::InvalidAccessError(
+                "autoIncrement needs a non-empty, non-array key path".to_string(),
+            ));
+        }
     }
+    Ok(())
 }
 
 /// Factory for creating/opening databases
@@ -597,6 +1281,14 @@ impl IDBFactory {
         let databases = self.databases.lock().unwrap();
         databases.keys().cloned().collect()
     }
+
+    /// Approximate bytes stored, as the databases' serialized size
+    pub fn size(&self) -> usize {
+        let databases = self.databases.lock().unwrap();
+        databases.values()
+            .map(|db| serde_json::to_vec(db).map_or(0, |bytes| bytes.len()))
+            .sum()
+    }
 }
 
 impl Default for IDBFactory {
@@ -611,8 +1303,8 @@ mod tests {
 
     #[test]
     fn test_idb_key_compare() {
-        let key1 = IDBKey::Number(1);
-        let key2 = IDBKey::Number(2);
+        let key1 = IDBKey::Number(1.0);
+        let key2 = IDBKey::Number(2.0);
         assert_eq!(key1.compare(&key2), std::cmp::Ordering::Less);
         assert_eq!(key2.compare(&key1), std::cmp::Ordering::Greater);
         assert_eq!(key1.compare(&key1), std::cmp::Ordering::Equal);
@@ -621,17 +1313,17 @@ mod tests {
     #[test]
     fn test_idb_key_range() {
         let range = IDBKeyRange::bound(
-            IDBKey::Number(1),
-            IDBKey::Number(10),
+            IDBKey::Number(1.0),
+            IDBKey::Number(10.0),
             false,
             false,
         );
         
-        assert!(range.includes(&IDBKey::Number(1)));
-        assert!(range.includes(&IDBKey::Number(5)));
-        assert!(range.includes(&IDBKey::Number(10)));
-        assert!(!range.includes(&IDBKey::Number(0)));
-        assert!(!range.includes(&IDBKey::Number(11)));
+        assert!(range.includes(&IDBKey::Number(1.0)));
+        assert!(range.includes(&IDBKey::Number(5.0)));
+        assert!(range.includes(&IDBKey::Number(10.0)));
+        assert!(!range.includes(&IDBKey::Number(0.0)));
+        assert!(!range.includes(&IDBKey::Number(11.0)));
     }
 
     #[test]
@@ -671,7 +1363,7 @@ mod tests {
         
         let value = serde_json::json!({"name": "test"});
         let key = store.add(value, None).unwrap();
-        assert_eq!(key, IDBKey::Number(1));
+        assert_eq!(key, IDBKey::Number(1.0));
         assert_eq!(store.count(), 1);
     }
 
@@ -729,14 +1421,14 @@ mod tests {
         };
         let mut store = IDBObjectStore::new("test".to_string(), params);
         
-        store.put(serde_json::json!({"v": 1}), Some(IDBKey::Number(1))).unwrap();
-        store.put(serde_json::json!({"v": 2}), Some(IDBKey::Number(2))).unwrap();
-        store.put(serde_json::json!({"v": 3}), Some(IDBKey::Number(3))).unwrap();
+        store.put(serde_json::json!({"v": 1}), Some(IDBKey::Number(1.0))).unwrap();
+        store.put(serde_json::json!({"v": 2}), Some(IDBKey::Number(2.0))).unwrap();
+        store.put(serde_json::json!({"v": 3}), Some(IDBKey::Number(3.0))).unwrap();
         
         let all = store.get_all(None, None);
         assert_eq!(all.len(), 3);
         
-        let range = IDBKeyRange::bound(IDBKey::Number(1), IDBKey::Number(2), false, false);
+        let range = IDBKeyRange::bound(IDBKey::Number(1.0), IDBKey::Number(2.0), false, false);
         let filtered = store.get_all(Some(&range), None);
         assert_eq!(filtered.len(), 2);
     }
@@ -761,19 +1453,19 @@ mod tests {
     #[test]
     fn test_cursor() {
         let keys = vec![
-            IDBKey::Number(1),
-            IDBKey::Number(2),
-            IDBKey::Number(3),
+            IDBKey::Number(1.0),
+            IDBKey::Number(2.0),
+            IDBKey::Number(3.0),
         ];
         
         let mut cursor = IDBCursor::new(keys, IDBCursorDirection::Next);
-        assert_eq!(cursor.key(), Some(&IDBKey::Number(1)));
+        assert_eq!(cursor.key(), Some(&IDBKey::Number(1.0)));
         
         cursor.continue_cursor();
-        assert_eq!(cursor.key(), Some(&IDBKey::Number(2)));
+        assert_eq!(cursor.key(), Some(&IDBKey::Number(2.0)));
         
         cursor.advance(1);
-        assert_eq!(cursor.key(), Some(&IDBKey::Number(3)));
+        assert_eq!(cursor.key(), Some(&IDBKey::Number(3.0)));
         
         cursor.continue_cursor();
         assert!(!cursor.has_value());
@@ -796,4 +1488,333 @@ mod tests {
         assert_eq!(tx.mode(), IDBTransactionMode::ReadOnly);
         assert!(tx.is_active());
     }
+
+    #[test]
+    fn test_key_ordering_across_types() {
+        let mut keys = vec![
+            IDBKey::Array(vec![IDBKey::Number(1.0)]),
+            IDBKey::String("b".to_string()),
+            IDBKey::Date(0),
+            IDBKey::Number(2.5),
+            IDBKey::String("a".to_string()),
+            IDBKey::Number(-1.0),
+            IDBKey::Array(vec![]),
+        ];
+        keys.sort();
+        assert_eq!(keys, vec![
+            IDBKey::Number(-1.0),
+            IDBKey::Number(2.5),
+            IDBKey::Date(0),
+            IDBKey::String("a".to_string()),
+            IDBKey::String("b".to_string()),
+            IDBKey::Array(vec![]),
+            IDBKey::Array(vec![IDBKey::Number(1.0)]),
+        ]);
+        assert_eq!(IDBKey::Number(0.0), IDBKey::Number(-0.0));
+
+        // Only numbers, strings and arrays of those are keys
+        assert!(IDBKey::from_json(&serde_json::json!([1, "a", [2]])).is_ok());
+        assert!(IDBKey::from_json(&serde_json::json!(true)).is_err());
+        assert!(IDBKey::from_json(&serde_json::json!([1, null])).is_err());
+        assert!(!IDBKey::Number(f64::NAN).is_valid());
+    }
+
+    #[test]
+    fn test_key_path_extraction() {
+        let value = serde_json::json!({"id": 7, "name": {"first": "Ada", "last": "Lovelace"}, "tags": ["x"]});
+        let nested = IDBKeyPath::from("name.first");
+        assert_eq!(nested.extract_key(&value).unwrap(), Some(IDBKey::String("Ada".to_string())));
+        assert_eq!(IDBKeyPath::from("name.middle").extract_key(&value).unwrap(), None);
+        assert_eq!(IDBKeyPath::from("name.last.length").extract_key(&value).unwrap(), Some(IDBKey::Number(8.0)));
+        // The path yields a value that is not a key
+        assert!(IDBKeyPath::from("name").extract_key(&value).is_err());
+
+        let compound = IDBKeyPath::from(vec!["name.last".to_string(), "id".to_string()]);
+        assert_eq!(
+            compound.extract_key(&value).unwrap(),
+            Some(IDBKey::Array(vec![IDBKey::String("Lovelace".to_string()), IDBKey::Number(7.0)]))
+        );
+
+        assert!(IDBKeyPath::from("").is_valid());
+        assert!(!IDBKeyPath::from("a..b").is_valid());
+        assert!(!IDBKeyPath::from("1a").is_valid());
+        assert!(!IDBKeyPath::Array(vec![]).is_valid());
+    }
+
+    #[test]
+    fn test_store_with_key_path() {
+        let params = IDBObjectStoreParameters {
+            key_path: Some(vec!["last".to_string(), "first".to_string()].into()),
+            auto_increment: false,
+        };
+        let mut store = IDBObjectStore::new("people".to_string(), params);
+
+        let key = store.add(serde_json::json!({"first": "Ada", "last": "Lovelace"}), None).unwrap();
+        assert_eq!(key, IDBKey::Array(vec![IDBKey::String("Lovelace".to_string()), IDBKey::String("Ada".to_string())]));
+        assert!(store.add(serde_json::json!({"first": "Ada", "last": "Lovelace"}), None).is_err());
+        assert!(matches!(store.add(serde_json::json!({"first": "Ada"}), None), Err(IDBError::DataError(_))));
+        // Stores with a key path take no explicit key
+        assert!(matches!(
+            store.put(serde_json::json!({"first": "A", "last": "B"}), Some(IDBKey::Number(1.0))),
+            Err(IDBError::DataError(_))
+        ));
+    }
+
+    #[test]
+    fn test_auto_increment_injects_key() {
+        let params = IDBObjectStoreParameters {
+            key_path: Some("meta.id".into()),
+            auto_increment: true,
+        };
+        let mut store = IDBObjectStore::new("items".to_string(), params);
+
+        let key = store.add(serde_json::json!({"title": "first"}), None).unwrap();
+        assert_eq!(key, IDBKey::Number(1.0));
+        assert_eq!(store.get(&key).unwrap()["meta"]["id"], serde_json::json!(1.0));
+
+        // An explicit numeric key moves the generator past it
+        store.add(serde_json::json!({"meta": {"id": 10}}), None).unwrap();
+        assert_eq!(store.add(serde_json::json!({}), None).unwrap(), IDBKey::Number(11.0));
+
+        // Nowhere to put the key
+        assert!(store.add(serde_json::json!("text"), None).is_err());
+    }
+
+    #[test]
+    fn test_index_key_paths() {
+        let mut store = IDBObjectStore::new("test".to_string(), IDBObjectStoreParameters::default());
+        store.put(serde_json::json!({"a": {"b": "x"}, "tags": ["t1", "t2", "t1"]}), Some(IDBKey::Number(1.0))).unwrap();
+        store.put(serde_json::json!({"a": {"b": true}, "tags": "t3"}), Some(IDBKey::Number(2.0))).unwrap();
+
+        store.create_index("ab".to_string(), "a.b", IDBIndexParameters::default()).unwrap();
+        let tags = IDBIndexParameters { unique: false, multi_entry: true };
+        store.create_index("tags".to_string(), "tags", tags).unwrap();
+
+        // Records whose value at the path is not a key are left out
+        let ab = store.index("ab").unwrap();
+        assert_eq!(ab.count(None), 1);
+        assert_eq!(ab.get_key(&IDBKey::String("x".to_string())), Some(IDBKey::Number(1.0)));
+
+        let tags = store.index("tags").unwrap();
+        assert_eq!(tags.count(None), 3);
+        assert_eq!(tags.get_all_keys(Some(&IDBKeyRange::only(IDBKey::String("t1".to_string()))), None), vec![IDBKey::Number(1.0)]);
+
+        let invalid = store.create_index("bad".to_string(), "a b", IDBIndexParameters::default());
+        assert!(matches!(invalid, Err(IDBError::SyntaxError(_))));
+        let array_multi = IDBIndexParameters { unique: false, multi_entry: true };
+        let invalid = store.create_index("multi".to_string(), vec!["a".to_string()], array_multi);
+        assert!(matches!(invalid, Err(IDBError::InvalidAccessError(_))));
+    }
+
+    #[test]
+    fn test_create_object_store_validates_key_path() {
+        let mut db = IDBDatabase::new("testdb".to_string(), 1);
+        let params = IDBObjectStoreParameters { key_path: Some("".into()), auto_increment: true };
+        assert!(matches!(
+            db.create_object_store("a".to_string(), params),
+            Err(IDBError::InvalidAccessError(_))
+        ));
+        let params = IDBObjectStoreParameters { key_path: Some("a.".into()), auto_increment: false };
+        assert!(matches!(db.create_object_store("b".to_string(), params), Err(IDBError::SyntaxError(_))));
+    }
+
+    fn database_with_store() -> IDBDatabase {
+        let mut db = IDBDatabase::new("testdb".to_string(), 1);
+        db.create_object_store("store1".to_string(), IDBObjectStoreParameters::default()).unwrap();
+        db
+    }
+
+    #[test]
+    fn test_transaction_buffers_until_commit() {
+        let mut db = database_with_store();
+        let key = IDBKey::String("k".to_string());
+        let mut tx = db.transaction(vec!["store1".to_string()], IDBTransactionMode::ReadWrite).unwrap();
+
+        tx.put("store1", serde_json::json!({"v": 1}), Some(key.clone())).unwrap();
+        assert!(tx.object_store("store1").unwrap().get(&key).is_some());
+        assert!(db.object_store("store1").unwrap().get(&key).is_none());
+
+        tx.commit(&mut db).unwrap();
+        assert_eq!(tx.state(), IDBTransactionState::Committed);
+        assert!(db.object_store("store1").unwrap().get(&key).is_some());
+        assert_eq!(db.running_transactions(), 0);
+        assert_eq!(
+            tx.put("store1", serde_json::json!({}), Some(key)),
+            Err(IDBError::TransactionInactiveError)
+        );
+    }
+
+    #[test]
+    fn test_transaction_abort_rolls_back() {
+        let mut db = database_with_store();
+        let key = IDBKey::Number(1.0);
+        {
+            let mut tx = db.transaction(vec!["store1".to_string()], IDBTransactionMode::ReadWrite).unwrap();
+            tx.put("store1", serde_json::json!("a"), Some(key.clone())).unwrap();
+            tx.abort();
+            assert_eq!(tx.commit(&mut db), Err(IDBError::TransactionInactiveError));
+        }
+        {
+            // Dropped without committing
+            let mut tx = db.transaction(vec!["store1".to_string()], IDBTransactionMode::ReadWrite).unwrap();
+            tx.put("store1", serde_json::json!("b"), Some(key.clone())).unwrap();
+            assert_eq!(db.running_transactions(), 1);
+        }
+        assert_eq!(db.running_transactions(), 0);
+        assert_eq!(db.object_store("store1").unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_read_only_transaction_rejects_writes() {
+        let db = database_with_store();
+        let mut tx = db.transaction(vec!["store1".to_string()], IDBTransactionMode::ReadOnly).unwrap();
+        assert_eq!(tx.put("store1", serde_json::json!(1), Some(IDBKey::Number(1.0))), Err(IDBError::ReadOnlyError));
+        assert_eq!(tx.clear("store1"), Err(IDBError::ReadOnlyError));
+        assert!(matches!(tx.object_store("other"), Err(IDBError::NotFoundError(_))));
+        assert!(matches!(
+            tx.create_object_store("s".to_string(), IDBObjectStoreParameters::default()),
+            Err(IDBError::InvalidStateError(_))
+        ));
+    }
+
+    #[test]
+    fn test_conflicting_commit_aborts() {
+        let mut db = database_with_store();
+        let names = vec!["store1".to_string()];
+        let mut first = db.transaction(names.clone(), IDBTransactionMode::ReadWrite).unwrap();
+        let mut second = db.transaction(names, IDBTransactionMode::ReadWrite).unwrap();
+
+        first.put("store1", serde_json::json!(1), Some(IDBKey::Number(1.0))).unwrap();
+        second.put("store1", serde_json::json!(2), Some(IDBKey::Number(2.0))).unwrap();
+        first.commit(&mut db).unwrap();
+
+        // Committing would overwrite the first transaction's writes
+        assert_eq!(second.commit(&mut db), Err(IDBError::AbortError));
+        assert_eq!(second.state(), IDBTransactionState::Aborted);
+        assert_eq!(db.object_store("store1").unwrap().get_all_keys(None, None), vec![IDBKey::Number(1.0)]);
+    }
+
+    #[test]
+    fn test_version_change_is_exclusive() {
+        let mut db = database_with_store();
+        let reader = db.transaction(vec!["store1".to_string()], IDBTransactionMode::ReadOnly).unwrap();
+        assert!(matches!(db.upgrade(2), Err(IDBError::InvalidStateError(_))));
+        drop(reader);
+        assert!(matches!(db.upgrade(1), Err(IDBError::VersionError(_))));
+        assert!(matches!(
+            db.transaction(vec![], IDBTransactionMode::VersionChange),
+            Err(IDBError::InvalidAccessError(_))
+        ));
+
+        let mut upgrade = db.upgrade(2).unwrap();
+        assert!(matches!(
+            db.transaction(vec!["store1".to_string()], IDBTransactionMode::ReadOnly),
+            Err(IDBError::InvalidStateError(_))
+        ));
+        upgrade.create_object_store("people".to_string(), IDBObjectStoreParameters::default()).unwrap();
+        upgrade.create_index("people", "byName".to_string(), "name", IDBIndexParameters::default()).unwrap();
+        upgrade.delete_object_store("store1").unwrap();
+        assert_eq!(db.version(), 1);
+
+        upgrade.commit(&mut db).unwrap();
+        assert_eq!(db.version(), 2);
+        assert_eq!(db.object_store_names(), vec!["people".to_string()]);
+        assert!(db.object_store("people").unwrap().index("byName").is_ok());
+        assert!(db.transaction(vec!["people".to_string()], IDBTransactionMode::ReadWrite).is_ok());
+    }
+
+    fn people_database() -> IDBDatabase {
+        let mut db = IDBDatabase::new("people".to_string(), 1);
+        let mut upgrade = db.upgrade(2).unwrap();
+        let params = IDBObjectStoreParameters { key_path: Some("id".into()), auto_increment: false };
+        upgrade.create_object_store("people".to_string(), params).unwrap();
+        upgrade.create_index("people", "byCity".to_string(), "city", IDBIndexParameters::default()).unwrap();
+        for (id, city) in [(1, "Oslo"), (2, "Bergen"), (3, "Oslo"), (4, "Tromsø")] {
+            upgrade.add("people", serde_json::json!({"id": id, "city": city}), None).unwrap();
+        }
+        upgrade.commit(&mut db).unwrap();
+        db
+    }
+
+    fn city(name: &str) -> IDBKey {
+        IDBKey::String(name.to_string())
+    }
+
+    #[test]
+    fn test_index_queries_return_records() {
+        let db = people_database();
+        let by_city = db.object_store("people").unwrap().index("byCity").unwrap();
+
+        assert_eq!(by_city.get(&city("Oslo")).unwrap()["id"], serde_json::json!(1));
+        assert_eq!(by_city.get_key(&city("Bergen")), Some(IDBKey::Number(2.0)));
+        assert!(by_city.get(&city("Paris")).is_none());
+
+        let oslo = by_city.get_all(Some(&IDBKeyRange::only(city("Oslo"))), None);
+        assert_eq!(oslo.iter().map(|(key, _)| key.clone()).collect::<Vec<_>>(), vec![IDBKey::Number(1.0), IDBKey::Number(3.0)]);
+        // Index order: Bergen, Oslo, Oslo, Tromsø
+        assert_eq!(by_city.get_all_keys(None, Some(2)), vec![IDBKey::Number(2.0), IDBKey::Number(1.0)]);
+        assert_eq!(by_city.count(Some(&IDBKeyRange::lower_bound(city("O"), false))), 3);
+    }
+
+    #[test]
+    fn test_cursor_directions_and_ranges() {
+        let db = people_database();
+        let store = db.object_store("people").unwrap();
+        let by_city = store.index("byCity").unwrap();
+        let primary_keys = |mut cursor: IDBCursor| {
+            let mut keys = Vec::new();
+            while let Some(key) = cursor.primary_key() {
+                keys.push(key.clone());
+                cursor.continue_cursor();
+            }
+            keys
+        };
+        let numbers = |ids: &[i32]| ids.iter().map(|&id| IDBKey::Number(id as f64)).collect::<Vec<_>>();
+
+        assert_eq!(primary_keys(by_city.open_cursor(None, IDBCursorDirection::Next)), numbers(&[2, 1, 3, 4]));
+        assert_eq!(primary_keys(by_city.open_cursor(None, IDBCursorDirection::Prev)), numbers(&[4, 3, 1, 2]));
+        assert_eq!(primary_keys(by_city.open_cursor(None, IDBCursorDirection::NextUnique)), numbers(&[2, 1, 4]));
+        // Unique in reverse still yields the lowest primary key of each key
+        assert_eq!(primary_keys(by_city.open_cursor(None, IDBCursorDirection::PrevUnique)), numbers(&[4, 1, 2]));
+
+        let range = IDBKeyRange::bound(IDBKey::Number(2.0), IDBKey::Number(4.0), false, true);
+        let mut cursor = store.open_cursor(Some(&range), IDBCursorDirection::Next);
+        assert_eq!(cursor.value().unwrap()["city"], serde_json::json!("Bergen"));
+        assert_eq!(cursor.source(), Some(&IDBCursorSource::Store("people".to_string())));
+        cursor.continue_to(&IDBKey::Number(3.0));
+        assert_eq!(cursor.key(), Some(&IDBKey::Number(3.0)));
+        cursor.continue_cursor();
+        assert!(!cursor.has_value());
+    }
+
+    #[test]
+    fn test_cursor_update_and_delete() {
+        let mut db = people_database();
+        let mut tx = db.transaction(vec!["people".to_string()], IDBTransactionMode::ReadWrite).unwrap();
+        let range = IDBKeyRange::only(city("Oslo"));
+        let mut cursor = tx.object_store("people").unwrap().index("byCity").unwrap()
+            .open_cursor(Some(&range), IDBCursorDirection::Next);
+
+        // The key at the store's key path cannot change
+        let moved = serde_json::json!({"id": 9, "city": "Oslo"});
+        assert!(matches!(cursor.update(&mut tx, moved), Err(IDBError::DataError(_))));
+
+        let renamed = serde_json::json!({"id": 1, "city": "Stavanger"});
+        assert_eq!(cursor.update(&mut tx, renamed.clone()), Ok(IDBKey::Number(1.0)));
+        assert_eq!(cursor.value(), Some(&renamed));
+        cursor.continue_cursor();
+        cursor.delete(&mut tx).unwrap();
+        tx.commit(&mut db).unwrap();
+
+        let store = db.object_store("people").unwrap();
+        assert!(store.get(&IDBKey::Number(3.0)).is_none());
+        let by_city = store.index("byCity").unwrap();
+        assert_eq!(by_city.count(Some(&range)), 0);
+        assert_eq!(by_city.get_key(&city("Stavanger")), Some(IDBKey::Number(1.0)));
+
+        // Read-only transactions cannot write through a cursor
+        let mut reader = db.transaction(vec!["people".to_string()], IDBTransactionMode::ReadOnly).unwrap();
+        let mut cursor = reader.object_store("people").unwrap().open_cursor(None, IDBCursorDirection::Next);
+        assert_eq!(cursor.delete(&mut reader), Err(IDBError::ReadOnlyError));
+    }
 }
@@ -0,0 +1,102 @@
+// requestAnimationFrame binding
+//
+// Callbacks wait in the page until the host begins a frame, then run
+// together with the frame's timestamp. Callbacks requested while a frame's
+// callbacks run wait for the next frame.
+
+use super::runtime::{JsError, JsRuntime, JsValue};
+
+/// Script installed into every context to provide `requestAnimationFrame`
+const ANIMATION_FRAME_SHIM: &str = r#"
+(function (global) {
+    var callbacks = [];
+    var running = [];
+    var nextHandle = 1;
+
+    global.requestAnimationFrame = function (callback) {
+        if (typeof callback !== "function") {
+            throw new TypeError("requestAnimationFrame: callback is not a function");
+        }
+        var handle = nextHandle++;
+        callbacks.push({ handle: handle, callback: callback });
+        return handle;
+    };
+    global.cancelAnimationFrame = function (handle) {
+        var keep = function (entry) { return entry.handle !== handle; };
+        callbacks = callbacks.filter(keep);
+        running = running.filter(keep);
+    };
+
+    global.__animationFramesPending = function () {
+        return callbacks.length > 0;
+    };
+    global.__animationFramesRun = function (timestamp) {
+        running = callbacks;
+        callbacks = [];
+        var ran = 0;
+        while (running.length > 0) {
+            var entry = running.shift();
+            ran++;
+            // One callback throwing does not stop the others
+            try {
+                entry.callback.call(global, timestamp);
+            } catch (e) {
+                if (global.console && console.error) {
+                    console.error(e);
+                }
+            }
+        }
+        return ran;
+    };
+})(globalThis);
+"#;
+
+/// Install the animation frame shim into a runtime
+pub(super) fn install(runtime: &mut JsRuntime) -> Result<(), JsError> {
+    runtime.execute(ANIMATION_FRAME_SHIM).map(|_| ())
+}
+
+/// Are callbacks waiting for the next frame
+pub(super) fn is_pending(runtime: &mut JsRuntime) -> Result<bool, JsError> {
+    Ok(runtime.execute("__animationFramesPending()")?.to_bool())
+}
+
+/// Run the callbacks waiting for a frame at `timestamp`, returning how many ran
+pub(super) fn run(runtime: &mut JsRuntime, timestamp: f64) -> Result<usize, JsError> {
+    match runtime.execute(&format!("__animationFramesRun({})", timestamp))? {
+        JsValue::Number(ran) => Ok(ran as usize),
+        other => Err(JsError::TypeError(format!("unexpected animation frame count: {:?}", other))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_callbacks_run_once_per_frame() {
+        let mut runtime = JsRuntime::new();
+        install(&mut runtime).unwrap();
+        assert!(!is_pending(&mut runtime).unwrap());
+        runtime
+            .execute(
+                "var times = [];
+                 function tick(t) { times.push(t); if (times.length < 2) requestAnimationFrame(tick); }
+                 requestAnimationFrame(tick);
+                 var cancelled = requestAnimationFrame(function () { times.push('cancelled'); });
+                 requestAnimationFrame(function () { throw new Error('oops'); });
+                 cancelAnimationFrame(cancelled);",
+            )
+            .unwrap();
+        assert!(is_pending(&mut runtime).unwrap());
+
+        // A callback asking for another frame waits for it
+        assert_eq!(run(&mut runtime, 16.5).unwrap(), 2);
+        assert_eq!(runtime.execute("times.join(',')").unwrap(), JsValue::String("16.5".to_string()));
+        assert!(is_pending(&mut runtime).unwrap());
+        assert_eq!(run(&mut runtime, 33.0).unwrap(), 1);
+        assert_eq!(runtime.execute("times.join(',')").unwrap(), JsValue::String("16.5,33".to_string()));
+        assert!(!is_pending(&mut runtime).unwrap());
+        assert_eq!(run(&mut runtime, 50.0).unwrap(), 0);
+    }
+}
@@ -0,0 +1,170 @@
+// navigator.clipboard binding
+//
+// Scripts get promise-returning readText()/writeText(). Calls are queued
+// in the page and serviced later by the host, which applies permission
+// checks and settles the promises.
+
+use super::runtime::{JsError, JsRuntime, JsValue};
+use serde::Deserialize;
+
+/// Script installed into every context to provide `navigator.clipboard`
+const CLIPBOARD_SHIM: &str = r#"
+(function (global) {
+    var pending = {};
+    var queue = [];
+    var nextId = 1;
+
+    function request(kind, text) {
+        return new Promise(function (resolve, reject) {
+            var id = nextId++;
+            pending[id] = { resolve: resolve, reject: reject };
+            queue.push({ id: id, kind: kind, text: text });
+        });
+    }
+
+    global.navigator = global.navigator || {};
+    global.navigator.clipboard = {
+        readText: function () { return request("read", null); },
+        writeText: function (text) { return request("write", String(text)); }
+    };
+
+    global.__clipboardTake = function () {
+        var taken = queue;
+        queue = [];
+        return JSON.stringify(taken);
+    };
+
+    global.__clipboardSettle = function (id, ok, value) {
+        var p = pending[id];
+        if (!p) {
+            return false;
+        }
+        delete pending[id];
+        if (ok) {
+            p.resolve(value);
+        } else {
+            var error = new Error(value);
+            error.name = "NotAllowedError";
+            p.reject(error);
+        }
+        return true;
+    };
+})(globalThis);
+"#;
+
+/// What a script asked the clipboard to do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClipboardRequestKind {
+    /// `navigator.clipboard.readText()`
+    ReadText,
+    /// `navigator.clipboard.writeText(text)`
+    WriteText(String),
+}
+
+/// A pending `navigator.clipboard` call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClipboardRequest {
+    /// Identifies the promise to settle
+    pub id: u32,
+    pub kind: ClipboardRequestKind,
+}
+
+#[derive(Deserialize)]
+struct RawRequest {
+    id: u32,
+    kind: String,
+    text: Option<String>,
+}
+
+/// Install the `navigator.clipboard` shim into a runtime
+pub(super) fn install(runtime: &mut JsRuntime) -> Result<(), JsError> {
+    runtime.execute(CLIPBOARD_SHIM).map(|_| ())
+}
+
+/// Drain clipboard calls queued by scripts
+pub(super) fn take_requests(runtime: &mut JsRuntime) -> Result<Vec<ClipboardRequest>, JsError> {
+    let json = match runtime.execute("__clipboardTake()")? {
+        JsValue::String(json) => json,
+        other => return Err(JsError::TypeError(format!("unexpected clipboard queue: {:?}", other))),
+    };
+
+    let raw: Vec<RawRequest> =
+        serde_json::from_str(&json).map_err(|e| JsError::RuntimeError(e.to_string()))?;
+
+    Ok(raw
+        .into_iter()
+        .filter_map(|r| {
+            let kind = match r.kind.as_str() {
+                "read" => ClipboardRequestKind::ReadText,
+                "write" => ClipboardRequestKind::WriteText(r.text.unwrap_or_default()),
+                _ => return None,
+            };
+            Some(ClipboardRequest { id: r.id, kind })
+        })
+        .collect())
+}
+
+/// Resolve (`Ok`) or reject (`Err`, as `NotAllowedError`) a pending call
+pub(super) fn settle(
+    runtime: &mut JsRuntime,
+    id: u32,
+    result: Result<Option<String>, String>,
+) -> Result<(), JsError> {
+    let (ok, value) = match result {
+        Ok(Some(text)) => (true, quote(&text)),
+        Ok(None) => (true, "undefined".to_string()),
+        Err(message) => (false, quote(&message)),
+    };
+    runtime
+        .execute(&format!("__clipboardSettle({}, {}, {})", id, ok, value))
+        .map(|_| ())
+}
+
+/// Encode a string as a JavaScript string literal
+fn quote(text: &str) -> String {
+    serde_json::to_string(text).unwrap_or_else(|_| "\"\"".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_are_queued_and_drained() {
+        let mut runtime = JsRuntime::new();
+        install(&mut runtime).unwrap();
+
+        runtime
+            .execute("navigator.clipboard.writeText('hello'); navigator.clipboard.readText();")
+            .unwrap();
+
+        let requests = take_requests(&mut runtime).unwrap();
+        assert_eq!(
+            requests,
+            vec![
+                ClipboardRequest { id: 1, kind: ClipboardRequestKind::WriteText("hello".to_string()) },
+                ClipboardRequest { id: 2, kind: ClipboardRequestKind::ReadText },
+            ]
+        );
+        assert!(take_requests(&mut runtime).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_settle_resolves_and_rejects() {
+        let mut runtime = JsRuntime::new();
+        install(&mut runtime).unwrap();
+        runtime
+            .execute(
+                "var text, error;
+                 navigator.clipboard.readText().then(function (t) { text = t; });
+                 navigator.clipboard.readText().catch(function (e) { error = e.name; });",
+            )
+            .unwrap();
+
+        settle(&mut runtime, 1, Ok(Some("quoted \"text\"\n".to_string()))).unwrap();
+        settle(&mut runtime, 2, Err("denied".to_string())).unwrap();
+
+        assert_eq!(runtime.get_global("text"), Some(JsValue::String("quoted \"text\"\n".to_string())));
+        assert_eq!(runtime.get_global("error"), Some(JsValue::String("NotAllowedError".to_string())));
+    }
+}
@@ -0,0 +1,240 @@
+// Evaluation for the DevTools console
+//
+// Lines typed into the console run at global scope, like a script, so
+// their `var`s and functions stay defined. Results are kept on the script
+// side and referred to by handle: the console shows a one-line preview and
+// lists an object's properties when it is expanded. `$_` is the last result.
+
+use super::runtime::{JsError, JsRuntime, JsValue};
+use serde::Deserialize;
+use serde_json::json;
+
+/// Script installed into every context to support console evaluation
+const CONSOLE_SHIM: &str = r##"
+(function (global) {
+    var values = [];
+    var MAX_PREVIEW = 100;
+
+    function isElement(value) {
+        return value.nodeType === 1 && typeof value.localName === "string";
+    }
+
+    function elementPreview(element) {
+        var text = element.localName;
+        if (element.id) {
+            text += "#" + element.id;
+        }
+        if (element.className) {
+            text += "." + element.className.trim().split(/\s+/).join(".");
+        }
+        return text;
+    }
+
+    // One-line text for a value; nested objects are only summarized
+    function preview(value, nested) {
+        switch (typeof value) {
+            case "string":
+                return JSON.stringify(value);
+            case "function":
+                return "ƒ " + (value.name || "anonymous") + "()";
+            case "symbol":
+            case "bigint":
+                return value.toString() + (typeof value === "bigint" ? "n" : "");
+            case "object":
+                if (value === null) {
+                    return "null";
+                }
+                break;
+            default:
+                return String(value);
+        }
+        if (isElement(value)) {
+            return elementPreview(value);
+        }
+        if (value instanceof Error) {
+            return value.name + ": " + value.message;
+        }
+        if (Array.isArray(value)) {
+            if (nested) {
+                return "Array(" + value.length + ")";
+            }
+            return "(" + value.length + ") [" + shorten(value.map(function (item) {
+                return preview(item, true);
+            })) + "]";
+        }
+        if (nested) {
+            return "{…}";
+        }
+        return "{" + shorten(Object.keys(value).map(function (key) {
+            return key + ": " + preview(value[key], true);
+        })) + "}";
+    }
+
+    function shorten(items) {
+        var text = "";
+        for (var i = 0; i < items.length; i++) {
+            var next = (i ? ", " : "") + items[i];
+            if (text.length + next.length > MAX_PREVIEW) {
+                return text + ", …";
+            }
+            text += next;
+        }
+        return text;
+    }
+
+    function describe(value) {
+        var handle = null;
+        if (value !== null && typeof value === "object" || typeof value === "function") {
+            handle = values.length;
+            values.push(value);
+        }
+        return { preview: preview(value, false), handle: handle };
+    }
+
+    global.__consoleEvaluate = function (code) {
+        var value = (0, eval)(code);
+        global.$_ = value;
+        return JSON.stringify(describe(value));
+    };
+
+    global.__consoleProperties = function (handle) {
+        var value = values[handle];
+        var names = value === undefined ? [] : Object.getOwnPropertyNames(value);
+        return JSON.stringify(names.map(function (name) {
+            var property;
+            try {
+                property = describe(value[name]);
+            } catch (e) {
+                property = { preview: "(" + preview(e, true) + ")", handle: null };
+            }
+            return { name: name, value: property };
+        }));
+    };
+
+    // Property names of the object before the last `.`, or global names
+    global.__consoleCompletions = function (path, prefix) {
+        var target = global;
+        if (path) {
+            target = path.split(".").reduce(function (object, name) {
+                return object === undefined || object === null ? undefined : object[name];
+            }, global);
+        }
+        var names = {};
+        for (var object = target; object !== undefined && object !== null; object = Object.getPrototypeOf(object)) {
+            Object.getOwnPropertyNames(object).forEach(function (name) {
+                names[name] = true;
+            });
+        }
+        return JSON.stringify(Object.keys(names).filter(function (name) {
+            return name.indexOf(prefix) === 0 && name.indexOf("__") !== 0 && /^[A-Za-z_$][\w$]*$/.test(name);
+        }).sort());
+    };
+})(globalThis);
+"##;
+
+/// A value as the console shows it
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ConsoleValue {
+    /// One line, e.g. `{a: 1, b: "x"}` or `(2) [1, 2]`
+    pub preview: String,
+    /// Handle for listing an object's properties; `None` for primitives
+    pub handle: Option<u32>,
+}
+
+/// A property of an object shown in the console
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ConsoleProperty {
+    pub name: String,
+    pub value: ConsoleValue,
+}
+
+/// Install the console shim into a runtime
+pub(super) fn install(runtime: &mut JsRuntime) -> Result<(), JsError> {
+    runtime.execute(CONSOLE_SHIM).map(|_| ())
+}
+
+/// Run a line typed into the console
+pub(super) fn evaluate(runtime: &mut JsRuntime, code: &str) -> Result<ConsoleValue, JsError> {
+    parse(runtime.execute(&format!("__consoleEvaluate({})", json!(code)))?)
+}
+
+/// Own properties of an object the console showed
+pub(super) fn properties(runtime: &mut JsRuntime, handle: u32) -> Result<Vec<ConsoleProperty>, JsError> {
+    parse(runtime.execute(&format!("__consoleProperties({})", handle))?)
+}
+
+/// Names that can complete the identifier at the end of `input`
+///
+/// After a dotted path (`document.bo`) they are the path's properties,
+/// otherwise global names. Only plain paths are looked up, so completing
+/// never runs a call.
+pub(super) fn completions(runtime: &mut JsRuntime, input: &str) -> Result<Vec<String>, JsError> {
+    let start = input
+        .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$' || c == '.'))
+        .map_or(0, |i| i + 1);
+    let expression = &input[start..];
+    let (path, prefix) = expression.rsplit_once('.').unwrap_or(("", expression));
+    let plain_path = path.split('.').all(|name| {
+        name.chars().next().is_some_and(|c| !c.is_ascii_digit())
+            && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+    });
+    if expression.contains('.') && !plain_path {
+        return Ok(Vec::new());
+    }
+    parse(runtime.execute(&format!("__consoleCompletions({}, {})", json!(path), json!(prefix)))?)
+}
+
+fn parse<T: for<'de> Deserialize<'de>>(result: JsValue) -> Result<T, JsError> {
+    match result {
+        JsValue::String(json) => serde_json::from_str(&json).map_err(|e| JsError::RuntimeError(e.to_string())),
+        other => Err(JsError::TypeError(format!("unexpected console result: {:?}", other))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runtime() -> JsRuntime {
+        let mut runtime = JsRuntime::new();
+        install(&mut runtime).unwrap();
+        runtime
+    }
+
+    #[test]
+    fn test_previews_and_properties() {
+        let mut runtime = runtime();
+        let preview = |runtime: &mut JsRuntime, code: &str| evaluate(runtime, code).unwrap().preview;
+        assert_eq!(preview(&mut runtime, "1 + 1"), "2");
+        assert_eq!(preview(&mut runtime, "'hi'"), "\"hi\"");
+        assert_eq!(preview(&mut runtime, "[1, 'a', [2]]"), "(3) [1, \"a\", Array(1)]");
+        assert_eq!(preview(&mut runtime, "({ a: 1, b: { c: 2 } })"), "{a: 1, b: {\u{2026}}}");
+        assert_eq!(preview(&mut runtime, "function add(a, b) { return a + b; }; add"), "\u{192} add()");
+        // Lines run at global scope and `$_` is the last result
+        assert_eq!(preview(&mut runtime, "var kept = 5"), "undefined");
+        assert_eq!(preview(&mut runtime, "kept * 2"), "10");
+        assert_eq!(preview(&mut runtime, "$_ + 1"), "11");
+
+        let object = evaluate(&mut runtime, "({ a: 1, b: { c: 2 } })").unwrap();
+        assert_eq!(evaluate(&mut runtime, "2").unwrap().handle, None);
+        let properties = properties(&mut runtime, object.handle.unwrap()).unwrap();
+        assert_eq!(properties[0], ConsoleProperty {
+            name: "a".to_string(),
+            value: ConsoleValue { preview: "1".to_string(), handle: None },
+        });
+        let nested = properties[1].value.handle.unwrap();
+        assert_eq!(super::properties(&mut runtime, nested).unwrap()[0].value.preview, "2");
+        assert!(evaluate(&mut runtime, "missing()").is_err());
+    }
+
+    #[test]
+    fn test_completions() {
+        let mut runtime = runtime();
+        runtime.execute("var config = { debug: true, depth: 3, name: 'x' }").unwrap();
+        assert_eq!(completions(&mut runtime, "conf").unwrap(), ["config"]);
+        assert_eq!(completions(&mut runtime, "1 + config.de").unwrap(), ["debug", "depth"]);
+        assert!(completions(&mut runtime, "Math.ma").unwrap().contains(&"max".to_string()));
+        assert!(completions(&mut runtime, "f().x").unwrap().is_empty());
+        assert!(completions(&mut runtime, "__console").unwrap().is_empty());
+    }
+}
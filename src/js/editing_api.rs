@@ -0,0 +1,86 @@
+// document.execCommand() and document.queryCommandSupported()
+//
+// Commands are queued for the host, which runs them at the caret of the
+// focused contenteditable region and then fires `input` at it. execCommand
+// answers at once whether the command is one the host runs, as browsers
+// answer whether it is enabled.
+
+use super::runtime::{JsError, JsRuntime, JsValue};
+use serde::Deserialize;
+
+/// Script installed into every context to provide the editing commands
+const EDITING_SHIM: &str = r#"
+(function (global) {
+    var queue = [];
+    var supported = ["bold", "italic", "inserttext", "delete", "forwarddelete", "insertparagraph"];
+
+    function isSupported(command) {
+        return supported.indexOf(String(command).toLowerCase()) >= 0;
+    }
+
+    global.document = global.document || {};
+    global.document.execCommand = function (command, showUI, value) {
+        if (!isSupported(command)) {
+            return false;
+        }
+        queue.push({ command: String(command), value: value === undefined || value === null ? null : String(value) });
+        return true;
+    };
+    global.document.queryCommandSupported = isSupported;
+
+    global.__editingTake = function () {
+        var taken = queue;
+        queue = [];
+        return JSON.stringify(taken);
+    };
+})(globalThis);
+"#;
+
+/// A `document.execCommand` call
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct EditCommand {
+    /// Command name as the script gave it, e.g. `bold` or `insertText`
+    pub command: String,
+    /// The value argument, for `insertText`
+    pub value: Option<String>,
+}
+
+/// Install the editing command shim into a runtime
+pub(super) fn install(runtime: &mut JsRuntime) -> Result<(), JsError> {
+    runtime.execute(EDITING_SHIM).map(|_| ())
+}
+
+/// Drain `document.execCommand` calls queued by scripts
+pub(super) fn take_commands(runtime: &mut JsRuntime) -> Result<Vec<EditCommand>, JsError> {
+    match runtime.execute("__editingTake()")? {
+        JsValue::String(json) => serde_json::from_str(&json).map_err(|e| JsError::RuntimeError(e.to_string())),
+        other => Err(JsError::TypeError(format!("unexpected editing queue: {:?}", other))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exec_command_is_queued() {
+        let mut runtime = JsRuntime::new();
+        install(&mut runtime).unwrap();
+
+        let result = runtime
+            .execute(
+                "[document.execCommand('bold'), document.execCommand('insertText', false, 'hi'),
+                  document.execCommand('justifyCenter'), document.queryCommandSupported('Italic')].join()",
+            )
+            .unwrap();
+        assert_eq!(result, JsValue::String("true,true,false,true".to_string()));
+        assert_eq!(
+            take_commands(&mut runtime).unwrap(),
+            vec![
+                EditCommand { command: "bold".to_string(), value: None },
+                EditCommand { command: "insertText".to_string(), value: Some("hi".to_string()) },
+            ]
+        );
+        assert!(take_commands(&mut runtime).unwrap().is_empty());
+    }
+}
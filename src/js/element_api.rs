@@ -0,0 +1,506 @@
+// Element changes and event listeners: setAttribute(), remove(), addEventListener()
+//
+// Elements from `document.getElementById` can change their attributes and
+// remove themselves. The changes are queued with the element's `NodeId` and
+// made to the document by the host after the script runs. Listeners added to elements, `document`
+// and `window` are recorded so DevTools can list them; the runtime does not
+// keep function source, so listeners are found in the page's scripts by
+// their name or the `addEventListener` call that added them. The host fires
+// events at elements through the same listeners, bubbling them up to
+// `document` and `window`.
+//
+// DOM breakpoints watch element nodes for changes. The runtime cannot suspend a
+// script part way through, so a change to a watched element records where
+// the script stopped and throws, leaving the document as it was before.
+
+use super::runtime::{JsError, JsRuntime, JsValue};
+use crate::dom::{Document, DomError, NodeId};
+use crate::observers::MutationType;
+use serde::Deserialize;
+use serde_json::json;
+
+/// Script installed into every context to provide element changes and listeners
+const ELEMENT_SHIM: &str = r##"
+(function (global) {
+    var mutations = [];
+    var watches = [];
+    var pause = null;
+    var listeners = {};
+
+    function checkWatches(node, id, mutation, call) {
+        for (var i = 0; i < watches.length; i++) {
+            if (watches[i].node === node && watches[i].mutation === mutation) {
+                pause = { breakpoint: watches[i].breakpoint, id: id, call: call };
+                var error = new Error("Paused on DOM breakpoint: " + call);
+                error.name = "DOMBreakpoint";
+                throw error;
+            }
+        }
+    }
+
+    function listenerOptions(options) {
+        if (typeof options !== "object" || options === null) {
+            return { capture: !!options, once: false, passive: false };
+        }
+        return { capture: !!options.capture, once: !!options.once, passive: !!options.passive };
+    }
+
+    function addListener(key, type, listener, options) {
+        if (typeof listener !== "function" && !(listener && typeof listener.handleEvent === "function")) {
+            return;
+        }
+        var entry = listenerOptions(options);
+        entry.type = String(type);
+        entry.listener = listener;
+        var list = listeners[key] || (listeners[key] = []);
+        var exists = list.some(function (other) {
+            return other.type === entry.type && other.listener === listener && other.capture === entry.capture;
+        });
+        if (!exists) {
+            list.push(entry);
+        }
+    }
+
+    function removeListener(key, type, listener, options) {
+        var capture = listenerOptions(options).capture;
+        listeners[key] = (listeners[key] || []).filter(function (entry) {
+            return !(entry.type === String(type) && entry.listener === listener && entry.capture === capture);
+        });
+    }
+
+    function dispatch(key, target, event) {
+        event.target = event.target || target;
+        event.currentTarget = target;
+        (listeners[key] || []).slice().forEach(function (entry) {
+            if (entry.type !== event.type) {
+                return;
+            }
+            if (entry.once) {
+                removeListener(key, entry.type, entry.listener, entry);
+            }
+            if (typeof entry.listener === "function") {
+                entry.listener.call(target, event);
+            } else {
+                entry.listener.handleEvent(event);
+            }
+        });
+        return !event.defaultPrevented;
+    }
+
+    function makeEventTarget(object, key) {
+        object.addEventListener = function (type, listener, options) {
+            addListener(key, type, listener, options);
+        };
+        object.removeEventListener = function (type, listener, options) {
+            removeListener(key, type, listener, options);
+        };
+        object.dispatchEvent = function (event) {
+            return dispatch(key, object, event);
+        };
+    }
+
+    if (typeof global.Event !== "function") {
+        global.Event = function (type, init) {
+            this.type = String(type);
+            this.bubbles = !!(init && init.bubbles);
+            this.cancelable = !!(init && init.cancelable);
+            this.defaultPrevented = false;
+        };
+        global.Event.prototype.preventDefault = function () {
+            if (this.cancelable) {
+                this.defaultPrevented = true;
+            }
+        };
+    }
+
+    global.document = global.document || {};
+    var getElementById = global.document.getElementById;
+    global.document.getElementById = function (id) {
+        var element = getElementById ? getElementById.call(this, id) : null;
+        if (!element) {
+            return null;
+        }
+        id = String(id);
+        var node = element.__node;
+        makeEventTarget(element, "#" + id);
+        element.setAttribute = function (name, value) {
+            name = String(name).toLowerCase();
+            value = String(value);
+            var call = "setAttribute(" + JSON.stringify(name) + ", " + JSON.stringify(value) + ")";
+            checkWatches(node, id, "attributes", call);
+            mutations.push({ kind: "setAttribute", node: node, name: name, value: value });
+        };
+        element.removeAttribute = function (name) {
+            name = String(name).toLowerCase();
+            checkWatches(node, id, "attributes", "removeAttribute(" + JSON.stringify(name) + ")");
+            mutations.push({ kind: "removeAttribute", node: node, name: name });
+        };
+        element.remove = function () {
+            checkWatches(node, id, "childList", "remove()");
+            mutations.push({ kind: "remove", node: node });
+        };
+        return element;
+    };
+    makeEventTarget(global.document, "document");
+
+    // Window listeners are recorded here and still delivered by the messaging shim
+    var addWindowListener = global.addEventListener;
+    var removeWindowListener = global.removeEventListener;
+    global.addEventListener = function (type, listener, options) {
+        addListener("window", type, listener, options);
+        if (addWindowListener) {
+            addWindowListener.call(global, type, listener, options);
+        }
+    };
+    global.removeEventListener = function (type, listener, options) {
+        removeListener("window", type, listener, options);
+        if (removeWindowListener) {
+            removeWindowListener.call(global, type, listener, options);
+        }
+    };
+
+    global.__elementDispatch = function (id, init) {
+        var event = new Event(init.type, { bubbles: true });
+        Object.keys(init).forEach(function (key) {
+            if (key !== "type") {
+                event[key] = init[key];
+            }
+        });
+        var element = id === null ? null : global.document.getElementById(id);
+        if (element) {
+            dispatch("#" + id, element, event);
+        }
+        dispatch("document", global.document, event);
+        dispatch("window", global, event);
+    };
+    global.__elementTakeMutations = function () {
+        var taken = mutations;
+        mutations = [];
+        return JSON.stringify(taken);
+    };
+    global.__elementSetWatches = function (list) {
+        watches = list;
+    };
+    global.__elementTakePause = function () {
+        var taken = pause;
+        pause = null;
+        return JSON.stringify(taken);
+    };
+    global.__elementListeners = function (keys) {
+        var found = [];
+        keys.forEach(function (key) {
+            (listeners[key] || []).forEach(function (entry) {
+                var handler = typeof entry.listener === "function" ? entry.listener : entry.listener.handleEvent;
+                found.push({
+                    target: key,
+                    type: entry.type,
+                    capture: entry.capture,
+                    once: entry.once,
+                    passive: entry.passive,
+                    handler: handler.name || ""
+                });
+            });
+        });
+        return JSON.stringify(found);
+    };
+})(globalThis);
+"##;
+
+/// A change to the document made by a script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DomMutation {
+    /// `element.setAttribute(name, value)`
+    SetAttribute { node: NodeId, name: String, value: String },
+    /// `element.removeAttribute(name)`
+    RemoveAttribute { node: NodeId, name: String },
+    /// `element.remove()`
+    Remove { node: NodeId },
+}
+
+impl DomMutation {
+    /// The element the script changed
+    pub fn node(&self) -> NodeId {
+        match self {
+            DomMutation::SetAttribute { node, .. }
+            | DomMutation::RemoveAttribute { node, .. }
+            | DomMutation::Remove { node } => *node,
+        }
+    }
+
+    /// Make the change to a document
+    ///
+    /// Removing an element that is already out of its parent fails with
+    /// `NotFound`, so nothing changed.
+    pub fn apply(&self, document: &mut Document) -> Result<(), DomError> {
+        let node = self.node();
+        match self {
+            DomMutation::SetAttribute { name, value, .. } => document.set_attribute(node, name, value),
+            DomMutation::RemoveAttribute { name, .. } => document.remove_attribute(node, name).map(|_| ()),
+            DomMutation::Remove { .. } => {
+                let parent = document.parent(node).ok_or(DomError::NotFound)?;
+                document.remove_child(parent, node)
+            }
+        }
+    }
+}
+
+/// Changes to an element that stop a script, set by a DOM breakpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MutationWatch {
+    pub node: NodeId,
+    /// `Attributes` for attribute changes, `ChildList` for the element's removal
+    pub mutation: MutationType,
+    /// Index of the breakpoint that set the watch
+    pub breakpoint: usize,
+}
+
+/// Where a script was stopped by a DOM breakpoint
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DomBreakpointPause {
+    /// Index of the breakpoint, as given in its watch
+    pub breakpoint: usize,
+    /// Element the script was changing
+    #[serde(rename = "id")]
+    pub element_id: String,
+    /// The call that was stopped, e.g. `setAttribute("class", "open")`
+    pub call: String,
+}
+
+/// An event listener added by a script
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct EventListenerInfo {
+    /// `window`, `document` or `#id` for an element
+    pub target: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub capture: bool,
+    pub once: bool,
+    pub passive: bool,
+    /// Name of the listener function, empty if it is anonymous
+    pub handler: String,
+    /// Line of the page script defining or adding the listener, from 1
+    #[serde(default)]
+    pub line: Option<usize>,
+    /// Text of that line
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawMutation {
+    kind: String,
+    node: NodeId,
+    name: Option<String>,
+    value: Option<String>,
+}
+
+/// Install the element shim into a runtime
+pub(super) fn install(runtime: &mut JsRuntime) -> Result<(), JsError> {
+    runtime.execute(ELEMENT_SHIM).map(|_| ())
+}
+
+/// Drain element changes queued by scripts
+pub(super) fn take_mutations(runtime: &mut JsRuntime) -> Result<Vec<DomMutation>, JsError> {
+    let raw: Vec<RawMutation> = parse(runtime.execute("__elementTakeMutations()")?)?;
+    Ok(raw
+        .into_iter()
+        .filter_map(|r| match r.kind.as_str() {
+            "setAttribute" => Some(DomMutation::SetAttribute { node: r.node, name: r.name?, value: r.value? }),
+            "removeAttribute" => Some(DomMutation::RemoveAttribute { node: r.node, name: r.name? }),
+            "remove" => Some(DomMutation::Remove { node: r.node }),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Fire an event at the element with `id`, or at the document if there is
+/// none, bubbling up to the window
+///
+/// `init` holds the event's `type` and the properties it has besides.
+pub(super) fn dispatch(runtime: &mut JsRuntime, id: Option<&str>, init: serde_json::Value) -> Result<(), JsError> {
+    runtime.execute(&format!("__elementDispatch({}, {})", json!(id), init)).map(|_| ())
+}
+
+/// Replace the changes that stop scripts
+pub(super) fn set_watches(runtime: &mut JsRuntime, watches: &[MutationWatch]) -> Result<(), JsError> {
+    let list: Vec<_> = watches
+        .iter()
+        .filter_map(|watch| {
+            let mutation = match watch.mutation {
+                MutationType::Attributes => "attributes",
+                MutationType::ChildList => "childList",
+                MutationType::CharacterData => return None,
+            };
+            Some(json!({"node": watch.node, "mutation": mutation, "breakpoint": watch.breakpoint}))
+        })
+        .collect();
+    runtime.execute(&format!("__elementSetWatches({})", json!(list))).map(|_| ())
+}
+
+/// Where the last script was stopped by a watch, if it was
+pub(super) fn take_pause(runtime: &mut JsRuntime) -> Result<Option<DomBreakpointPause>, JsError> {
+    parse(runtime.execute("__elementTakePause()")?)
+}
+
+/// Listeners on the given targets, located in the page's scripts
+pub(super) fn listeners(
+    runtime: &mut JsRuntime,
+    targets: &[String],
+    scripts: &[String],
+) -> Result<Vec<EventListenerInfo>, JsError> {
+    let list = runtime.execute(&format!("__elementListeners({})", json!(targets)))?;
+    let mut found: Vec<EventListenerInfo> = parse(list)?;
+    for listener in &mut found {
+        if let Some((line, source)) = locate(listener, scripts) {
+            listener.line = Some(line);
+            listener.source = Some(source.trim().to_string());
+        }
+    }
+    Ok(found)
+}
+
+/// The line defining a listener by name, else the line adding one for its event
+fn locate<'a>(listener: &EventListenerInfo, scripts: &'a [String]) -> Option<(usize, &'a str)> {
+    let name = &listener.handler;
+    let defines = |line: &str| {
+        !name.is_empty()
+            && [format!("function {}(", name), format!("function {} (", name)].iter().any(|f| line.contains(f))
+    };
+    let event = &listener.event_type;
+    let adds = |line: &str| {
+        line.contains("addEventListener(")
+            && [format!("'{}'", event), format!("\"{}\"", event)].iter().any(|quoted| line.contains(quoted))
+    };
+    let find = |matches: &dyn Fn(&str) -> bool| {
+        scripts.iter().find_map(|script| {
+            script.lines().enumerate().find(|(_, line)| matches(line)).map(|(i, line)| (i + 1, line))
+        })
+    };
+    find(&defines).or_else(|| find(&adds))
+}
+
+fn parse<T: for<'de> Deserialize<'de>>(result: JsValue) -> Result<T, JsError> {
+    match result {
+        JsValue::String(json) => serde_json::from_str(&json).map_err(|e| JsError::RuntimeError(e.to_string())),
+        other => Err(JsError::TypeError(format!("unexpected element result: {:?}", other))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html::HtmlParser;
+    use crate::js::scroll_api;
+
+    fn document(html: &str) -> Document {
+        Document::new(HtmlParser::parse(html))
+    }
+
+    fn runtime(document: &Document) -> JsRuntime {
+        let mut runtime = JsRuntime::new();
+        scroll_api::install(&mut runtime).unwrap();
+        install(&mut runtime).unwrap();
+        scroll_api::set_element_ids(&mut runtime, document).unwrap();
+        runtime
+    }
+
+    #[test]
+    fn test_mutations_apply_to_document() {
+        let mut document = document("<html><body><p id=\"a\" class=\"x\">A</p><p id=\"b\">B</p></body></html>");
+        let (a, b) = (document.get_element_by_id("a").unwrap(), document.get_element_by_id("b").unwrap());
+        let mut runtime = runtime(&document);
+        runtime
+            .execute(
+                "var a = document.getElementById('a'); a.setAttribute('Title', 'hi'); a.removeAttribute('class');\
+                 document.getElementById('b').remove();",
+            )
+            .unwrap();
+        let mutations = take_mutations(&mut runtime).unwrap();
+        assert_eq!(mutations, vec![
+            DomMutation::SetAttribute { node: a, name: "title".to_string(), value: "hi".to_string() },
+            DomMutation::RemoveAttribute { node: a, name: "class".to_string() },
+            DomMutation::Remove { node: b },
+        ]);
+        assert!(take_mutations(&mut runtime).unwrap().is_empty());
+
+        for mutation in &mutations {
+            mutation.apply(&mut document).unwrap();
+        }
+        let element = document.element_data(a).unwrap();
+        assert_eq!(element.get_attribute("title"), Some("hi"));
+        assert_eq!(element.get_attribute("class"), None);
+        assert!(!document.is_connected(b));
+        assert_eq!(mutations[2].apply(&mut document), Err(DomError::NotFound));
+    }
+
+    #[test]
+    fn test_watch_stops_script_before_change() {
+        let document = document("<html><body><p id=\"a\">A</p></body></html>");
+        let mut runtime = runtime(&document);
+        set_watches(&mut runtime, &[MutationWatch {
+            node: document.get_element_by_id("a").unwrap(),
+            mutation: MutationType::Attributes,
+            breakpoint: 3,
+        }])
+        .unwrap();
+
+        let result = runtime.execute("var el = document.getElementById('a'); el.setAttribute('hidden', ''); 'done'");
+        assert!(result.is_err());
+        assert_eq!(take_pause(&mut runtime).unwrap(), Some(DomBreakpointPause {
+            breakpoint: 3,
+            element_id: "a".to_string(),
+            call: "setAttribute(\"hidden\", \"\")".to_string(),
+        }));
+        assert_eq!(take_pause(&mut runtime).unwrap(), None);
+        assert!(take_mutations(&mut runtime).unwrap().is_empty());
+
+        // Removal is not watched
+        runtime.execute("el.remove()").unwrap();
+        assert_eq!(take_mutations(&mut runtime).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_event_listeners() {
+        let document = document("<html><body><button id=\"go\">Go</button></body></html>");
+        let mut runtime = runtime(&document);
+        let script = "var clicks = 0;\n\
+                      function onClick(e) { clicks += 1; }\n\
+                      var button = document.getElementById('go');\n\
+                      button.addEventListener('click', onClick, { once: true });\n\
+                      document.addEventListener('keydown', function (e) {}, true);\n";
+        runtime.execute(script).unwrap();
+        runtime.execute("button.dispatchEvent(new Event('click')); button.dispatchEvent(new Event('click'))").unwrap();
+        assert_eq!(runtime.execute("clicks").unwrap(), JsValue::Number(1.0));
+
+        runtime.execute("document.getElementById('go').addEventListener('focus', onClick)").unwrap();
+        let targets = ["#go".to_string(), "document".to_string()];
+        let found = listeners(&mut runtime, &targets, &[script.to_string()]).unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!((found[0].target.as_str(), found[0].event_type.as_str()), ("#go", "focus"));
+        assert_eq!(found[0].handler, "onClick");
+        assert_eq!(found[0].line, Some(2));
+        assert_eq!(found[0].source.as_deref(), Some("function onClick(e) { clicks += 1; }"));
+        assert_eq!((found[1].event_type.as_str(), found[1].capture), ("keydown", true));
+        assert_eq!((found[1].handler.as_str(), found[1].line), ("", Some(5)));
+    }
+
+    #[test]
+    fn test_host_events_bubble() {
+        let document = document("<html><body><div id=\"box\">Box</div></body></html>");
+        let mut runtime = runtime(&document);
+        runtime
+            .execute(
+                "var seen = [];\n\
+                 document.getElementById('box').addEventListener('animationend', function (e) {\n\
+                     seen.push('box ' + e.animationName + ' ' + e.elapsedTime);\n\
+                 });\n\
+                 document.addEventListener('animationend', function (e) { seen.push('document ' + e.target.id); });",
+            )
+            .unwrap();
+        let init = json!({"type": "animationend", "animationName": "fade", "elapsedTime": 2});
+        dispatch(&mut runtime, Some("box"), init.clone()).unwrap();
+        dispatch(&mut runtime, None, init).unwrap();
+        let seen = runtime.execute("seen.join(', ')").unwrap();
+        assert_eq!(seen, JsValue::String("box fade 2, document box, document undefined".to_string()));
+    }
+}
@@ -9,6 +9,8 @@ pub enum EventType {
     MouseDown,
     MouseUp,
     MouseMove,
+    MouseOver,
+    MouseOut,
     KeyDown,
     KeyUp,
     KeyPress,
@@ -16,6 +18,8 @@ pub enum EventType {
     DOMContentLoaded,
     Resize,
     Scroll,
+    Submit,
+    Invalid,
 }
 
 impl EventType {
@@ -26,6 +30,8 @@ impl EventType {
             "mousedown" => Some(EventType::MouseDown),
             "mouseup" => Some(EventType::MouseUp),
             "mousemove" => Some(EventType::MouseMove),
+            "mouseover" => Some(EventType::MouseOver),
+            "mouseout" => Some(EventType::MouseOut),
             "keydown" => Some(EventType::KeyDown),
             "keyup" => Some(EventType::KeyUp),
             "keypress" => Some(EventType::KeyPress),
@@ -33,6 +39,8 @@ impl EventType {
             "domcontentloaded" => Some(EventType::DOMContentLoaded),
             "resize" => Some(EventType::Resize),
             "scroll" => Some(EventType::Scroll),
+            "submit" => Some(EventType::Submit),
+            "invalid" => Some(EventType::Invalid),
             _ => None,
         }
     }
@@ -44,6 +52,8 @@ impl EventType {
             EventType::MouseDown => "mousedown",
             EventType::MouseUp => "mouseup",
             EventType::MouseMove => "mousemove",
+            EventType::MouseOver => "mouseover",
+            EventType::MouseOut => "mouseout",
             EventType::KeyDown => "keydown",
             EventType::KeyUp => "keyup",
             EventType::KeyPress => "keypress",
@@ -51,6 +61,8 @@ impl EventType {
             EventType::DOMContentLoaded => "DOMContentLoaded",
             EventType::Resize => "resize",
             EventType::Scroll => "scroll",
+            EventType::Submit => "submit",
+            EventType::Invalid => "invalid",
         }
     }
 }
@@ -134,7 +146,8 @@ mod tests {
     fn test_event_type_from_str() {
         assert_eq!(EventType::from_str("click"), Some(EventType::Click));
         assert_eq!(EventType::from_str("keydown"), Some(EventType::KeyDown));
-        assert_eq!(EventType::from_str("invalid"), None);
+        assert_eq!(EventType::from_str("notanevent"), None);
+        assert_eq!(EventType::from_str("invalid"), Some(EventType::Invalid));
     }
     
     #[test]
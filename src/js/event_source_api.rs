@@ -0,0 +1,234 @@
+// EventSource binding
+//
+// Scripts create sources whose connections are made by the host; opens and
+// closes are queued for it to pick up. Events from the network are handed
+// back through `dispatch`, which updates `readyState` and calls `on*`
+// handlers and listeners registered for the event type.
+
+use super::runtime::{JsError, JsRuntime, JsValue};
+use crate::net::{EventSourceEvent, EventSourceState};
+use serde::Deserialize;
+
+/// Script installed into every context to provide `EventSource`
+const EVENT_SOURCE_SHIM: &str = r#"
+(function (global) {
+    var queue = [];
+    var sources = {};
+    var nextId = 1;
+
+    function EventSource(url, init) {
+        if (!(this instanceof EventSource)) {
+            throw new TypeError("EventSource requires 'new'");
+        }
+        this.url = String(url);
+        this.withCredentials = !!(init && init.withCredentials);
+        this.readyState = 0;
+        this.onopen = null;
+        this.onmessage = null;
+        this.onerror = null;
+        this.__id = nextId++;
+        this.__listeners = {};
+        sources[this.__id] = this;
+        queue.push({ kind: "open", id: this.__id, url: this.url, withCredentials: this.withCredentials });
+    }
+
+    EventSource.CONNECTING = EventSource.prototype.CONNECTING = 0;
+    EventSource.OPEN = EventSource.prototype.OPEN = 1;
+    EventSource.CLOSED = EventSource.prototype.CLOSED = 2;
+
+    EventSource.prototype.addEventListener = function (type, listener) {
+        var listeners = this.__listeners[type] || (this.__listeners[type] = []);
+        if (typeof listener === "function" && listeners.indexOf(listener) < 0) {
+            listeners.push(listener);
+        }
+    };
+
+    EventSource.prototype.removeEventListener = function (type, listener) {
+        var listeners = this.__listeners[type] || [];
+        var index = listeners.indexOf(listener);
+        if (index >= 0) {
+            listeners.splice(index, 1);
+        }
+    };
+
+    EventSource.prototype.close = function () {
+        if (this.readyState === 2) {
+            return;
+        }
+        this.readyState = 2;
+        delete sources[this.__id];
+        queue.push({ kind: "close", id: this.__id, url: this.url, withCredentials: this.withCredentials });
+    };
+
+    global.EventSource = EventSource;
+
+    global.__eventSourceDispatch = function (id, readyState, type, data, lastEventId, origin) {
+        var source = sources[id];
+        if (!source) {
+            return false;
+        }
+        source.readyState = readyState;
+        if (readyState === 2) {
+            delete sources[id];
+        }
+        var event = { type: type, target: source };
+        if (data !== null) {
+            event.data = data;
+            event.lastEventId = lastEventId;
+            event.origin = origin;
+        }
+        if ((type === "open" || type === "message" || type === "error") && typeof source["on" + type] === "function") {
+            source["on" + type].call(source, event);
+        }
+        (source.__listeners[type] || []).slice().forEach(function (listener) {
+            listener.call(source, event);
+        });
+        return true;
+    };
+
+    global.__eventSourceTake = function () {
+        var taken = queue;
+        queue = [];
+        return JSON.stringify(taken);
+    };
+})(globalThis);
+"#;
+
+/// An `EventSource` call the host has to act on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventSourceRequest {
+    /// `new EventSource(url)`; the URL is as passed by the script, unresolved
+    Open { id: u32, url: String, with_credentials: bool },
+    /// `source.close()`
+    Close { id: u32 },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawRequest {
+    kind: String,
+    id: u32,
+    url: String,
+    with_credentials: bool,
+}
+
+/// Install the EventSource shim into a runtime
+pub(super) fn install(runtime: &mut JsRuntime) -> Result<(), JsError> {
+    runtime.execute(EVENT_SOURCE_SHIM).map(|_| ())
+}
+
+/// Drain source opens and closes queued by scripts
+pub(super) fn take_requests(runtime: &mut JsRuntime) -> Result<Vec<EventSourceRequest>, JsError> {
+    let json = match runtime.execute("__eventSourceTake()")? {
+        JsValue::String(json) => json,
+        other => return Err(JsError::TypeError(format!("unexpected EventSource queue: {:?}", other))),
+    };
+
+    let raw: Vec<RawRequest> =
+        serde_json::from_str(&json).map_err(|e| JsError::RuntimeError(e.to_string()))?;
+
+    Ok(raw
+        .into_iter()
+        .map(|r| match r.kind.as_str() {
+            "close" => EventSourceRequest::Close { id: r.id },
+            _ => EventSourceRequest::Open {
+                id: r.id,
+                url: r.url,
+                with_credentials: r.with_credentials,
+            },
+        })
+        .collect())
+}
+
+/// Fire an event from the network at source `id`
+///
+/// `origin` is the origin of the source's URL. Returns false if the page
+/// already closed the source.
+pub(super) fn dispatch(runtime: &mut JsRuntime, id: u32, event: &EventSourceEvent, origin: &str) -> Result<bool, JsError> {
+    let quote = |s: &str| serde_json::to_string(s).map_err(|e| JsError::RuntimeError(e.to_string()));
+    let (state, event_type, data, last_event_id) = match event {
+        EventSourceEvent::Open => (EventSourceState::Open, "open".to_string(), "null".to_string(), "null".to_string()),
+        EventSourceEvent::Error(state) => (*state, "error".to_string(), "null".to_string(), "null".to_string()),
+        EventSourceEvent::Message(message) => (
+            EventSourceState::Open,
+            message.event_type.clone(),
+            quote(&message.data)?,
+            quote(&message.last_event_id)?,
+        ),
+    };
+    let script = format!(
+        "__eventSourceDispatch({}, {}, {}, {}, {}, {})",
+        id,
+        state as u8,
+        quote(&event_type)?,
+        data,
+        last_event_id,
+        quote(origin)?
+    );
+    Ok(runtime.execute(&script)? == JsValue::Boolean(true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::ServerSentEvent;
+
+    #[test]
+    fn test_sources_are_queued_and_closed() {
+        let mut runtime = JsRuntime::new();
+        install(&mut runtime).unwrap();
+
+        runtime.execute("var es = new EventSource('/events', { withCredentials: true })").unwrap();
+        assert_eq!(runtime.execute("es.readyState === EventSource.CONNECTING").unwrap(), JsValue::Boolean(true));
+        runtime.execute("es.close(); es.close()").unwrap();
+
+        let requests = take_requests(&mut runtime).unwrap();
+        assert_eq!(
+            requests,
+            vec![
+                EventSourceRequest::Open { id: 1, url: "/events".to_string(), with_credentials: true },
+                EventSourceRequest::Close { id: 1 },
+            ]
+        );
+        assert!(take_requests(&mut runtime).unwrap().is_empty());
+        assert_eq!(runtime.execute("es.readyState").unwrap(), JsValue::Number(2.0));
+        // Events for a closed source are dropped
+        assert!(!dispatch(&mut runtime, 1, &EventSourceEvent::Open, "null").unwrap());
+    }
+
+    #[test]
+    fn test_dispatch_message_events() {
+        let mut runtime = JsRuntime::new();
+        install(&mut runtime).unwrap();
+        runtime
+            .execute(
+                "var log = []; var es = new EventSource('https://example.com/feed');
+                 es.onopen = function () { log.push('open:' + this.readyState); };
+                 es.onmessage = function (e) { log.push(e.data + '@' + e.lastEventId + '@' + e.origin); };
+                 es.addEventListener('update', function (e) { log.push('update:' + e.data); });
+                 es.onerror = function () { log.push('error:' + es.readyState); };",
+            )
+            .unwrap();
+        take_requests(&mut runtime).unwrap();
+
+        let origin = "https://example.com";
+        let message = |event_type: &str, data: &str| {
+            EventSourceEvent::Message(ServerSentEvent {
+                event_type: event_type.to_string(),
+                data: data.to_string(),
+                last_event_id: "5".to_string(),
+            })
+        };
+        assert!(dispatch(&mut runtime, 1, &EventSourceEvent::Open, origin).unwrap());
+        dispatch(&mut runtime, 1, &message("message", "say \"hi\"\nbye"), origin).unwrap();
+        dispatch(&mut runtime, 1, &message("update", "42"), origin).unwrap();
+        let error = EventSourceEvent::Error(EventSourceState::Connecting);
+        dispatch(&mut runtime, 1, &error, origin).unwrap();
+
+        let log = runtime.execute("log.join('|')").unwrap();
+        assert_eq!(
+            log.to_string(),
+            "open:1|say \"hi\"\nbye@5@https://example.com|update:42|error:0"
+        );
+    }
+}
@@ -0,0 +1,389 @@
+// Extension API binding: the `chrome` (and `browser`) namespace
+//
+// Installed into an extension's background context, and into the isolated
+// world its content scripts run in, which gets only `runtime` and
+// `storage`. API calls are queued for the host with an id and return
+// promises (and call a callback, if given) that the host settles with a
+// JSON result or an error message. Messages are JSON values: the host hands
+// one to `runtime.onMessage` listeners with a reply id, and their answer
+// comes back out of the queue as a `runtime.reply` call. A
+// `webRequest.onBeforeRequest` listener is registered with the host along
+// with its filter, and run when the host sees a request matching it.
+
+use super::runtime::{JsError, JsRuntime, JsValue};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// Which part of an extension a context runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtensionWorld {
+    /// The background scripts, with the whole API
+    Background,
+    /// Content scripts in a page, with `runtime` and `storage`
+    Content,
+}
+
+/// Script installed into extension contexts to provide the `chrome` namespace
+const EXTENSION_SHIM: &str = r#"
+(function (global, extensionId, manifest, background) {
+    var queue = [];
+    var pending = {};
+    var nextId = 1;
+    var messageListeners = [];
+    var requestListeners = {};
+    var nextListener = 1;
+
+    var runtime = {
+        id: extensionId,
+        lastError: undefined,
+        getManifest: function () { return JSON.parse(JSON.stringify(manifest)); },
+        getURL: function (path) {
+            return "extension://" + extensionId + "/" + String(path).replace(/^\/+/, "");
+        }
+    };
+
+    // Queue a call for the host; the promise settles when the host answers
+    function call(method, args, callback) {
+        var id = nextId++;
+        var promise = new Promise(function (resolve, reject) {
+            pending[id] = { resolve: resolve, reject: reject };
+        });
+        queue.push({ id: id, method: method, args: args === undefined ? null : args });
+        if (typeof callback !== "function") {
+            return promise;
+        }
+        promise.then(function (result) {
+            callback(result);
+        }, function (error) {
+            runtime.lastError = { message: error.message };
+            try {
+                callback();
+            } finally {
+                runtime.lastError = undefined;
+            }
+        });
+        return undefined;
+    }
+
+    function makeEvent(listeners, onAdd, onRemove) {
+        return {
+            addListener: function (listener) {
+                if (typeof listener === "function" && listeners.indexOf(listener) < 0) {
+                    listeners.push(listener);
+                    if (onAdd) {
+                        onAdd.apply(null, arguments);
+                    }
+                }
+            },
+            removeListener: function (listener) {
+                var index = listeners.indexOf(listener);
+                if (index >= 0) {
+                    listeners.splice(index, 1);
+                    if (onRemove) {
+                        onRemove(listener);
+                    }
+                }
+            },
+            hasListener: function (listener) { return listeners.indexOf(listener) >= 0; },
+            hasListeners: function () { return listeners.length > 0; }
+        };
+    }
+
+    // Answer a message once; `undefined` is sent as no value
+    function replier(reply) {
+        var done = false;
+        return function (value, error) {
+            if (done) {
+                return;
+            }
+            done = true;
+            var args = { reply: reply, value: value === undefined ? null : value };
+            if (error !== undefined) {
+                args.error = String(error);
+            }
+            queue.push({ id: 0, method: "runtime.reply", args: args });
+        };
+    }
+
+    runtime.sendMessage = function (message, callback) {
+        return call("runtime.sendMessage", { message: message }, callback);
+    };
+    runtime.onMessage = makeEvent(messageListeners);
+
+    function storageArea(area) {
+        return {
+            get: function (keys, callback) {
+                if (typeof keys === "function") {
+                    callback = keys;
+                    keys = null;
+                }
+                return call("storage.get", { area: area, keys: keys === undefined ? null : keys }, callback);
+            },
+            set: function (items, callback) {
+                return call("storage.set", { area: area, items: items }, callback);
+            },
+            remove: function (keys, callback) {
+                return call("storage.remove", { area: area, keys: keys }, callback);
+            },
+            clear: function (callback) {
+                return call("storage.clear", { area: area }, callback);
+            }
+        };
+    }
+
+    var chrome = { runtime: runtime, storage: { local: storageArea("local") } };
+
+    if (background) {
+        chrome.tabs = {
+            query: function (info, callback) { return call("tabs.query", info || {}, callback); },
+            get: function (tabId, callback) { return call("tabs.get", { tabId: tabId }, callback); },
+            create: function (properties, callback) { return call("tabs.create", properties || {}, callback); },
+            update: function (tabId, properties, callback) {
+                if (typeof tabId === "object" && tabId !== null) {
+                    callback = properties;
+                    properties = tabId;
+                    tabId = null;
+                }
+                return call("tabs.update", { tabId: tabId, properties: properties || {} }, callback);
+            },
+            remove: function (tabId, callback) { return call("tabs.remove", { tabId: tabId }, callback); },
+            sendMessage: function (tabId, message, callback) {
+                return call("tabs.sendMessage", { tabId: tabId, message: message }, callback);
+            }
+        };
+
+        var beforeRequest = [];
+        var listenerIds = [];
+        var onBeforeRequest = makeEvent(beforeRequest, function (listener, filter, extraInfoSpec) {
+            var id = nextListener++;
+            requestListeners[id] = listener;
+            listenerIds.push({ id: id, listener: listener });
+            filter = filter || {};
+            queue.push({
+                id: 0,
+                method: "webRequest.addListener",
+                args: {
+                    listener: id,
+                    urls: Array.isArray(filter.urls) ? filter.urls.map(String) : ["<all_urls>"],
+                    types: Array.isArray(filter.types) ? filter.types.map(String) : [],
+                    blocking: Array.isArray(extraInfoSpec) && extraInfoSpec.indexOf("blocking") >= 0
+                }
+            });
+        }, function (listener) {
+            listenerIds = listenerIds.filter(function (entry) {
+                if (entry.listener !== listener) {
+                    return true;
+                }
+                delete requestListeners[entry.id];
+                queue.push({ id: 0, method: "webRequest.removeListener", args: { listener: entry.id } });
+                return false;
+            });
+        });
+        chrome.webRequest = { onBeforeRequest: onBeforeRequest };
+    }
+
+    global.chrome = chrome;
+    global.browser = chrome;
+
+    global.__extensionTake = function () {
+        var taken = queue;
+        queue = [];
+        return JSON.stringify(taken);
+    };
+
+    global.__extensionSettle = function (id, error, value) {
+        var entry = pending[id];
+        if (!entry) {
+            return false;
+        }
+        delete pending[id];
+        if (error !== null) {
+            entry.reject(new Error(error));
+        } else {
+            entry.resolve(value === null ? undefined : value);
+        }
+        return true;
+    };
+
+    // Run listeners for a message; a listener answers by calling
+    // sendResponse, by returning a promise, or by returning true and
+    // calling sendResponse later
+    global.__extensionMessage = function (message, sender, reply) {
+        var respond = replier(reply);
+        if (messageListeners.length === 0) {
+            respond(undefined, "Could not establish connection. Receiving end does not exist.");
+            return;
+        }
+        var waiting = false;
+        messageListeners.slice().forEach(function (listener) {
+            var sendResponse = function (value) { respond(value); };
+            var result;
+            try {
+                result = listener(message, sender, sendResponse);
+            } catch (e) {
+                if (global.console && console.error) {
+                    console.error(e);
+                }
+                return;
+            }
+            if (result === true) {
+                waiting = true;
+            } else if (result && typeof result.then === "function") {
+                waiting = true;
+                result.then(function (value) { respond(value); }, function (e) { respond(undefined, e && e.message); });
+            }
+        });
+        if (!waiting) {
+            respond(undefined);
+        }
+    };
+
+    // Ask the listener whether to cancel a request
+    global.__extensionWebRequest = function (id, details) {
+        var listener = requestListeners[id];
+        if (!listener) {
+            return false;
+        }
+        var result = listener(details);
+        return !!(result && result.cancel === true);
+    };
+})(globalThis, __EXTENSION_ID__, __MANIFEST__, __BACKGROUND__);
+"#;
+
+/// An API call made by an extension's script
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ExtensionCall {
+    /// Identifies the call to settle; 0 for calls that need no answer
+    pub id: u32,
+    /// Namespaced method name, e.g. `tabs.query`
+    pub method: String,
+    pub args: Value,
+}
+
+/// Install the `chrome` namespace into a runtime
+pub(super) fn install(
+    runtime: &mut JsRuntime,
+    extension_id: &str,
+    manifest: &Value,
+    world: ExtensionWorld,
+) -> Result<(), JsError> {
+    let shim = EXTENSION_SHIM
+        .replace("__EXTENSION_ID__", &json!(extension_id).to_string())
+        .replace("__MANIFEST__", &manifest.to_string())
+        .replace("__BACKGROUND__", if world == ExtensionWorld::Background { "true" } else { "false" });
+    runtime.execute(&shim).map(|_| ())
+}
+
+/// Drain the API calls queued by scripts
+pub(super) fn take_calls(runtime: &mut JsRuntime) -> Result<Vec<ExtensionCall>, JsError> {
+    let json = match runtime.execute("__extensionTake()")? {
+        JsValue::String(json) => json,
+        other => return Err(JsError::TypeError(format!("unexpected extension call queue: {:?}", other))),
+    };
+    serde_json::from_str(&json).map_err(|e| JsError::RuntimeError(e.to_string()))
+}
+
+/// Settle a call with its result or an error message
+pub(super) fn settle(runtime: &mut JsRuntime, id: u32, result: Result<&Value, &str>) -> Result<(), JsError> {
+    let (error, value) = match result {
+        Ok(value) => (Value::Null, value.clone()),
+        Err(message) => (json!(message), Value::Null),
+    };
+    runtime.execute(&format!("__extensionSettle({}, {}, {})", id, error, value)).map(|_| ())
+}
+
+/// Hand a message to `runtime.onMessage` listeners; the answer is queued
+/// as a `runtime.reply` call carrying `reply`
+pub(super) fn dispatch_message(
+    runtime: &mut JsRuntime,
+    message: &Value,
+    sender: &Value,
+    reply: u32,
+) -> Result<(), JsError> {
+    runtime.execute(&format!("__extensionMessage({}, {}, {})", message, sender, reply)).map(|_| ())
+}
+
+/// Run a `webRequest.onBeforeRequest` listener, returning whether it cancels the request
+pub(super) fn dispatch_web_request(runtime: &mut JsRuntime, listener: u32, details: &Value) -> Result<bool, JsError> {
+    Ok(runtime.execute(&format!("__extensionWebRequest({}, {})", listener, details))?.to_bool())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runtime(world: ExtensionWorld) -> JsRuntime {
+        let mut runtime = JsRuntime::new();
+        install(&mut runtime, "privacy", &json!({ "name": "Privacy" }), world).unwrap();
+        runtime
+    }
+
+    #[test]
+    fn test_calls_settle_promises_and_callbacks() {
+        let mut runtime = runtime(ExtensionWorld::Background);
+        runtime
+            .execute(
+                "var log = [];
+                 chrome.storage.local.get('a').then(function (items) { log.push('get:' + items.a); });
+                 chrome.tabs.query({ active: true }, function (tabs) {
+                     log.push('query:' + (tabs ? tabs.length : chrome.runtime.lastError.message));
+                 });
+                 log.push(chrome.runtime.getManifest().name, browser.runtime.id);",
+            )
+            .unwrap();
+        let calls = take_calls(&mut runtime).unwrap();
+        assert_eq!(calls[0], ExtensionCall {
+            id: 1,
+            method: "storage.get".to_string(),
+            args: json!({ "area": "local", "keys": "a" }),
+        });
+        assert_eq!((calls[1].id, calls[1].method.as_str()), (2, "tabs.query"));
+
+        settle(&mut runtime, 1, Ok(&json!({ "a": 5 }))).unwrap();
+        settle(&mut runtime, 2, Err("No window")).unwrap();
+        assert_eq!(
+            runtime.execute("log.join(',')").unwrap(),
+            JsValue::String("Privacy,privacy,get:5,query:No window".to_string())
+        );
+        assert!(take_calls(&mut runtime).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_messages_and_web_requests() {
+        let mut runtime = runtime(ExtensionWorld::Background);
+        runtime
+            .execute(
+                "chrome.runtime.onMessage.addListener(function (message, sender, sendResponse) {
+                     sendResponse(message.n * 2);
+                 });
+                 chrome.webRequest.onBeforeRequest.addListener(function (details) {
+                     return { cancel: details.url.indexOf('tracker') >= 0 };
+                 }, { urls: ['*://*/*'], types: ['script'] }, ['blocking']);",
+            )
+            .unwrap();
+        let calls = take_calls(&mut runtime).unwrap();
+        assert_eq!(calls[0].method, "webRequest.addListener");
+        assert_eq!(calls[0].args, json!({ "listener": 1, "urls": ["*://*/*"], "types": ["script"], "blocking": true }));
+
+        assert!(dispatch_web_request(&mut runtime, 1, &json!({ "url": "https://t.example/tracker.js" })).unwrap());
+        assert!(!dispatch_web_request(&mut runtime, 1, &json!({ "url": "https://t.example/app.js" })).unwrap());
+
+        dispatch_message(&mut runtime, &json!({ "n": 21 }), &json!({ "tab": { "id": 1 } }), 7).unwrap();
+        let calls = take_calls(&mut runtime).unwrap();
+        assert_eq!(calls[0].method, "runtime.reply");
+        assert_eq!(calls[0].args, json!({ "reply": 7, "value": 42 }));
+    }
+
+    #[test]
+    fn test_content_world_has_no_privileged_apis() {
+        let mut runtime = runtime(ExtensionWorld::Content);
+        assert_eq!(
+            runtime.execute("[typeof chrome.tabs, typeof chrome.webRequest, typeof chrome.storage.local].join()").unwrap(),
+            JsValue::String("undefined,undefined,object".to_string())
+        );
+        // Nobody listens in a fresh world
+        dispatch_message(&mut runtime, &json!("hi"), &json!({}), 3).unwrap();
+        let calls = take_calls(&mut runtime).unwrap();
+        assert_eq!(calls[0].args["error"], json!("Could not establish connection. Receiving end does not exist."));
+    }
+}
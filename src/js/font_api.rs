@@ -0,0 +1,244 @@
+// document.fonts binding
+//
+// The page's `@font-face` faces are loaded by the host, which sends their
+// state to the page after each change. The page keeps a FontFace for each,
+// settles `loaded`, `ready` and `load()` promises as faces finish, and fires
+// `loading` when faces start loading and `loadingdone` and `loadingerror`
+// once none are left loading.
+
+use super::runtime::{JsError, JsRuntime};
+use crate::web_fonts::FontFaceSet;
+
+/// Script installed into every context to provide `document.fonts`
+const FONT_SHIM: &str = r#"
+(function (global) {
+    var faces = [];
+    var waiting = [];
+    var batch = { loaded: [], failed: [] };
+    var listeners = {};
+
+    function FontFace(family, display) {
+        var face = this;
+        this.family = family;
+        this.display = display;
+        this.status = "unloaded";
+        this.loaded = new Promise(function (resolve, reject) {
+            face._resolve = resolve;
+            face._reject = reject;
+        });
+        // Failures are reported through the set's events too; an unread rejection is not an error
+        this.loaded.catch(function () {});
+    }
+    FontFace.prototype.load = function () {
+        return this.loaded;
+    };
+    global.FontFace = FontFace;
+
+    // Families named by a CSS font shorthand, e.g. `bold 16px "Open Sans", serif`
+    function families(font) {
+        var match = /[\d.]+(px|pt|pc|em|rem|ex|ch|%|in|cm|mm)(\/\S+)?\s+(.+)$/i.exec(String(font).trim());
+        if (!match) {
+            throw new SyntaxError("Could not parse font: " + font);
+        }
+        return match[3].split(",").map(function (family) {
+            return family.trim().replace(/^["']|["']$/g, "").toLowerCase();
+        });
+    }
+    function matching(font) {
+        var names = families(font);
+        return faces.filter(function (face) { return names.indexOf(face.family.toLowerCase()) >= 0; });
+    }
+    function settled(list) {
+        return list.every(function (face) { return face.status !== "loading" && face.status !== "unloaded"; });
+    }
+
+    var fonts = {
+        status: "loaded",
+        onloading: null,
+        onloadingdone: null,
+        onloadingerror: null,
+        get size() {
+            return faces.length;
+        },
+        forEach: function (callback, thisArg) {
+            faces.slice().forEach(function (face) { callback.call(thisArg, face, face, fonts); });
+        },
+        has: function (face) {
+            return faces.indexOf(face) >= 0;
+        },
+        check: function (font) {
+            return matching(font).every(function (face) { return face.status === "loaded"; });
+        },
+        load: function (font) {
+            return new Promise(function (resolve, reject) {
+                var found = matching(font);
+                waiting.push({ faces: found, resolve: resolve, reject: reject });
+                settleWaiting();
+            });
+        },
+        addEventListener: function (type, listener) {
+            var list = listeners[type] || (listeners[type] = []);
+            if (typeof listener === "function" && list.indexOf(listener) < 0) {
+                list.push(listener);
+            }
+        },
+        removeEventListener: function (type, listener) {
+            listeners[type] = (listeners[type] || []).filter(function (l) { return l !== listener; });
+        },
+    };
+    fonts[Symbol.iterator] = function () {
+        return faces.slice()[Symbol.iterator]();
+    };
+    var readyResolve = null;
+    fonts.ready = Promise.resolve(fonts);
+
+    function fire(type, fontfaces) {
+        var event = { type: type, fontfaces: fontfaces, target: fonts };
+        var handlers = (listeners[type] || []).slice();
+        if (typeof fonts["on" + type] === "function") {
+            handlers.unshift(fonts["on" + type]);
+        }
+        handlers.forEach(function (handler) {
+            // One listener throwing does not stop the others
+            try {
+                handler.call(fonts, event);
+            } catch (e) {
+                if (global.console && console.error) {
+                    console.error(e);
+                }
+            }
+        });
+    }
+    function settleWaiting() {
+        waiting = waiting.filter(function (entry) {
+            if (!settled(entry.faces)) {
+                return true;
+            }
+            var failed = entry.faces.some(function (face) { return face.status === "error"; });
+            if (failed) {
+                entry.reject(new Error("NetworkError: A font failed to load"));
+            } else {
+                entry.resolve(entry.faces);
+            }
+            return false;
+        });
+    }
+
+    global.document = global.document || {};
+    global.document.fonts = fonts;
+
+    global.__fontsUpdate = function (states) {
+        var started = [];
+        states.forEach(function (state, index) {
+            var face = faces[index];
+            if (!face) {
+                face = faces[index] = new FontFace(state.family, state.display);
+            }
+            if (face.status === state.status) {
+                return;
+            }
+            face.status = state.status;
+            if (state.status === "loading") {
+                started.push(face);
+            } else if (state.status === "loaded") {
+                batch.loaded.push(face);
+                face._resolve(face);
+            } else if (state.status === "error") {
+                batch.failed.push(face);
+                face._reject(new Error("NetworkError: " + face.family + " failed to load"));
+            }
+        });
+        if (started.length > 0 && fonts.status !== "loading") {
+            fonts.status = "loading";
+            fonts.ready = new Promise(function (resolve) { readyResolve = resolve; });
+            fire("loading", started);
+        }
+        var loading = faces.some(function (face) { return face.status === "loading"; });
+        if (!loading && (batch.loaded.length > 0 || batch.failed.length > 0 || fonts.status === "loading")) {
+            var done = batch;
+            batch = { loaded: [], failed: [] };
+            fonts.status = "loaded";
+            fire("loadingdone", done.loaded);
+            if (done.failed.length > 0) {
+                fire("loadingerror", done.failed);
+            }
+            if (readyResolve) {
+                readyResolve(fonts);
+                readyResolve = null;
+            }
+        }
+        settleWaiting();
+    };
+})(globalThis);
+"#;
+
+/// Install the `document.fonts` shim into a runtime
+pub(super) fn install(runtime: &mut JsRuntime) -> Result<(), JsError> {
+    runtime.execute(FONT_SHIM).map(|_| ())
+}
+
+/// Send the state of the page's faces, firing events and settling promises for what changed
+pub(super) fn set_faces(runtime: &mut JsRuntime, fonts: &FontFaceSet) -> Result<(), JsError> {
+    let states: Vec<serde_json::Value> = fonts
+        .faces()
+        .iter()
+        .map(|face| {
+            serde_json::json!({
+                "family": face.family,
+                "status": face.status().as_str(),
+                "display": face.display.as_str(),
+            })
+        })
+        .collect();
+    runtime.execute(&format!("__fontsUpdate({})", serde_json::Value::Array(states))).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::CssParser;
+    use crate::js::runtime::JsValue;
+    use std::time::Instant;
+    use url::Url;
+
+    #[test]
+    fn test_font_loading_events_and_promises() {
+        let mut runtime = JsRuntime::new();
+        install(&mut runtime).unwrap();
+        let css = "@font-face { font-family: Brand; src: url(brand.ttf); } \
+                   @font-face { font-family: Icons; src: url(icons.ttf); font-display: block; }";
+        let mut fonts = FontFaceSet::from_stylesheet(&CssParser::parse(css), &Url::parse("https://a.test/").unwrap());
+        let now = Instant::now();
+        fonts.start_loading(now);
+        set_faces(&mut runtime, &fonts).unwrap();
+        runtime
+            .execute(
+                "var log = [];
+                 document.fonts.addEventListener('loadingdone', function (e) {
+                     log.push('done:' + e.fontfaces.length);
+                 });
+                 document.fonts.onloadingerror = function (e) { log.push('error:' + e.fontfaces[0].family); };
+                 document.fonts.ready.then(function () { log.push('ready'); });
+                 document.fonts.load('16px Brand').then(function (f) { log.push('brand:' + f[0].status); });
+                 document.fonts.load('bold 12px \"Icons\", serif').catch(function () { log.push('icons failed'); });",
+            )
+            .unwrap();
+        assert_eq!(runtime.execute("document.fonts.status").unwrap(), JsValue::String("loading".to_string()));
+        assert_eq!(runtime.execute("document.fonts.size").unwrap(), JsValue::Number(2.0));
+        assert_eq!(runtime.execute("document.fonts.check('16px Brand')").unwrap(), JsValue::Boolean(false));
+
+        let file = crate::renderer::font_manager::FontManager::new().unwrap().get_font_data("serif").bytes.to_vec();
+        fonts.finish_load(0, Some(file), now);
+        set_faces(&mut runtime, &fonts).unwrap();
+        assert_eq!(runtime.execute("log.join(',')").unwrap(), JsValue::String("brand:loaded".to_string()));
+
+        fonts.finish_load(1, None, now);
+        set_faces(&mut runtime, &fonts).unwrap();
+        assert_eq!(
+            runtime.execute("log.join(',')").unwrap(),
+            JsValue::String("brand:loaded,done:1,error:Icons,ready,icons failed".to_string())
+        );
+        assert_eq!(runtime.execute("document.fonts.status").unwrap(), JsValue::String("loaded".to_string()));
+        assert_eq!(runtime.execute("document.fonts.check('16px Brand')").unwrap(), JsValue::Boolean(true));
+    }
+}
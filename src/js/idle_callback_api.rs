@@ -0,0 +1,147 @@
+// requestIdleCallback binding
+//
+// Callbacks wait in the page until the host has an idle period, then run
+// one after another while the period has time left. A callback given a
+// timeout runs once the timeout passes, in the next period or after the
+// next frame, even if no time is left. Callbacks requested while others run
+// wait for the next period.
+
+use super::runtime::{JsError, JsRuntime, JsValue};
+
+/// Script installed into every context to provide `requestIdleCallback`
+const IDLE_CALLBACK_SHIM: &str = r#"
+(function (global) {
+    var callbacks = [];
+    var running = [];
+    var nextHandle = 1;
+
+    function now() {
+        return global.performance && global.performance.now ? global.performance.now() : Date.now();
+    }
+
+    function IdleDeadline(deadline, didTimeout) {
+        this._deadline = deadline;
+        this.didTimeout = didTimeout;
+    }
+    IdleDeadline.prototype.timeRemaining = function () {
+        return this.didTimeout ? 0 : Math.max(0, this._deadline - now());
+    };
+    global.IdleDeadline = IdleDeadline;
+
+    global.requestIdleCallback = function (callback, options) {
+        if (typeof callback !== "function") {
+            throw new TypeError("requestIdleCallback: callback is not a function");
+        }
+        var timeout = options && Number(options.timeout) > 0 ? Number(options.timeout) : 0;
+        var handle = nextHandle++;
+        callbacks.push({ handle: handle, callback: callback, timeoutAt: timeout > 0 ? now() + timeout : null });
+        return handle;
+    };
+    global.cancelIdleCallback = function (handle) {
+        var keep = function (entry) { return entry.handle !== handle; };
+        callbacks = callbacks.filter(keep);
+        running = running.filter(keep);
+    };
+
+    function invoke(entry, deadline) {
+        // One callback throwing does not stop the others
+        try {
+            entry.callback.call(global, deadline);
+        } catch (e) {
+            if (global.console && console.error) {
+                console.error(e);
+            }
+        }
+    }
+
+    global.__idleCallbacksPending = function () {
+        return callbacks.length > 0;
+    };
+    global.__idleCallbacksRun = function (deadline) {
+        running = callbacks;
+        callbacks = [];
+        var ran = 0;
+        var start = now();
+        running.filter(function (entry) {
+            return entry.timeoutAt !== null && entry.timeoutAt <= start;
+        }).forEach(function (entry) {
+            if (running.indexOf(entry) >= 0) {
+                running.splice(running.indexOf(entry), 1);
+                ran++;
+                invoke(entry, new IdleDeadline(start, true));
+            }
+        });
+        while (running.length > 0 && now() < deadline) {
+            ran++;
+            invoke(running.shift(), new IdleDeadline(deadline, false));
+        }
+        // Those the period had no time for keep their place
+        callbacks = running.concat(callbacks);
+        running = [];
+        return ran;
+    };
+})(globalThis);
+"#;
+
+/// Install the idle callback shim into a runtime
+pub(super) fn install(runtime: &mut JsRuntime) -> Result<(), JsError> {
+    runtime.execute(IDLE_CALLBACK_SHIM).map(|_| ())
+}
+
+/// Are callbacks waiting for idle time
+pub(super) fn is_pending(runtime: &mut JsRuntime) -> Result<bool, JsError> {
+    Ok(runtime.execute("__idleCallbacksPending()")?.to_bool())
+}
+
+/// Run the waiting callbacks in an idle period ending at `deadline`, returning how many ran
+pub(super) fn run(runtime: &mut JsRuntime, deadline: f64) -> Result<usize, JsError> {
+    match runtime.execute(&format!("__idleCallbacksRun({})", deadline))? {
+        JsValue::Number(ran) => Ok(ran as usize),
+        other => Err(JsError::TypeError(format!("unexpected idle callback count: {:?}", other))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_callbacks_run_while_time_remains() {
+        let mut runtime = JsRuntime::new();
+        install(&mut runtime).unwrap();
+        assert!(!is_pending(&mut runtime).unwrap());
+        runtime
+            .execute(
+                "var clock = 0;
+                 performance = { now: function () { return clock; } };
+                 var log = [];
+                 requestIdleCallback(function (d) { log.push('a' + d.timeRemaining()); clock += 10; });
+                 requestIdleCallback(function (d) {
+                     log.push('b' + d.didTimeout);
+                     requestIdleCallback(function () { log.push('later'); });
+                 });
+                 requestIdleCallback(function (d) {
+                     log.push('late:' + d.didTimeout + d.timeRemaining());
+                 }, { timeout: 5 });
+                 var cancelled = requestIdleCallback(function () { log.push('cancelled'); });
+                 cancelIdleCallback(cancelled);",
+            )
+            .unwrap();
+        assert!(is_pending(&mut runtime).unwrap());
+
+        // The timed out callback runs first; the period ends after the first other callback
+        runtime.execute("clock = 6").unwrap();
+        assert_eq!(run(&mut runtime, 16.0).unwrap(), 2);
+        assert_eq!(runtime.execute("log.join(',')").unwrap(), JsValue::String("late:true0,a10".to_string()));
+
+        // No time left: nothing runs
+        assert_eq!(run(&mut runtime, 16.0).unwrap(), 0);
+        assert_eq!(run(&mut runtime, 50.0).unwrap(), 1);
+        assert_eq!(run(&mut runtime, 50.0).unwrap(), 1);
+        assert_eq!(
+            runtime.execute("log.join(',')").unwrap(),
+            JsValue::String("late:true0,a10,bfalse,later".to_string())
+        );
+        assert!(!is_pending(&mut runtime).unwrap());
+    }
+}
@@ -0,0 +1,205 @@
+// DOM access for the DevTools console: `$0`, `$()` and `$$()`
+//
+// The runtime has no live DOM objects, so the console works on a snapshot
+// of the document taken when it loads: tags, attributes, text and child
+// elements. `$0` is the element last picked in the inspector. Elements with
+// an id are built on what `document.getElementById` returns, so calls such
+// as `$0.scrollIntoView()` work too.
+
+use super::runtime::{JsError, JsRuntime};
+use crate::dom::{Node, NodeType};
+use serde_json::{json, Value};
+
+/// Script installed into every context to provide the console's DOM helpers
+const INSPECTOR_SHIM: &str = r##"
+(function (global) {
+    var root = null;
+
+    function makeElement(snapshot) {
+        var byId = snapshot.id && global.document && global.document.getElementById
+            ? global.document.getElementById(snapshot.id)
+            : null;
+        var element = Object.assign(byId || {}, snapshot);
+        element.children = snapshot.children.map(makeElement);
+        element.getAttribute = function (name) {
+            var value = this.attributes[name];
+            return value === undefined ? null : value;
+        };
+        return element;
+    }
+
+    // Compound selectors (`div#id.class`, `*`) joined by spaces or commas
+    function parseSelectors(text) {
+        return String(text).split(",").map(function (part) {
+            return part.trim().split(/\s+/).map(function (compound) {
+                var match = /^(\*|[A-Za-z][\w-]*)?((?:[#.][\w-]+)*)$/.exec(compound);
+                if (!match) {
+                    throw new SyntaxError("'" + text + "' is not a valid selector");
+                }
+                var tag = match[1] && match[1] !== "*" ? match[1].toLowerCase() : null;
+                var parsed = { tag: tag, id: null, classes: [] };
+                (match[2].match(/[#.][\w-]+/g) || []).forEach(function (token) {
+                    if (token[0] === "#") {
+                        parsed.id = token.slice(1);
+                    } else {
+                        parsed.classes.push(token.slice(1));
+                    }
+                });
+                return parsed;
+            });
+        });
+    }
+
+    function matchesCompound(element, compound) {
+        var classes = element.className.split(/\s+/);
+        return (!compound.tag || element.localName === compound.tag)
+            && (!compound.id || element.id === compound.id)
+            && compound.classes.every(function (name) { return classes.indexOf(name) >= 0; });
+    }
+
+    // The last compound matches the element, the others its ancestors in order
+    function matchesSelector(element, ancestors, compounds) {
+        if (!matchesCompound(element, compounds[compounds.length - 1])) {
+            return false;
+        }
+        var i = compounds.length - 2;
+        for (var a = ancestors.length - 1; a >= 0 && i >= 0; a--) {
+            if (matchesCompound(ancestors[a], compounds[i])) {
+                i--;
+            }
+        }
+        return i < 0;
+    }
+
+    function query(selectors, first) {
+        var list = parseSelectors(selectors);
+        var found = [];
+        function visit(element, ancestors) {
+            if (list.some(function (compounds) { return matchesSelector(element, ancestors, compounds); })) {
+                found.push(element);
+                if (first) {
+                    return true;
+                }
+            }
+            ancestors.push(element);
+            var done = element.children.some(function (child) { return visit(child, ancestors); });
+            ancestors.pop();
+            return done;
+        }
+        if (root) {
+            visit(root, []);
+        }
+        return found;
+    }
+
+    global.$ = function (selectors) {
+        return query(selectors, true)[0] || null;
+    };
+    global.$$ = function (selectors) {
+        return query(selectors, false);
+    };
+
+    global.__inspectorSetDocument = function (snapshot) {
+        root = snapshot ? makeElement(snapshot) : null;
+    };
+    global.__inspectorSetInspected = function (snapshot) {
+        global.$0 = snapshot ? makeElement(snapshot) : undefined;
+    };
+})(globalThis);
+"##;
+
+/// Install the console's DOM helpers into a runtime
+pub(super) fn install(runtime: &mut JsRuntime) -> Result<(), JsError> {
+    runtime.execute(INSPECTOR_SHIM).map(|_| ())
+}
+
+/// Let `$()` and `$$()` search a document
+pub(super) fn set_document(runtime: &mut JsRuntime, document: &Node) -> Result<(), JsError> {
+    let snapshot = snapshot(document).unwrap_or(Value::Null);
+    runtime.execute(&format!("__inspectorSetDocument({})", snapshot)).map(|_| ())
+}
+
+/// Point `$0` at an element, or clear it with `None`
+pub(super) fn set_inspected(runtime: &mut JsRuntime, element: Option<&Node>) -> Result<(), JsError> {
+    let snapshot = element.and_then(snapshot).unwrap_or(Value::Null);
+    runtime.execute(&format!("__inspectorSetInspected({})", snapshot)).map(|_| ())
+}
+
+/// An element and its child elements as the console sees them
+fn snapshot(node: &Node) -> Option<Value> {
+    let data = node.element_data()?;
+    let mut text = String::new();
+    collect_text(node, &mut text);
+    let children: Vec<Value> = node.children.iter().filter_map(snapshot).collect();
+    Some(json!({
+        "nodeType": 1,
+        "tagName": data.tag_name.to_ascii_uppercase(),
+        "localName": data.tag_name,
+        "id": data.id().unwrap_or(""),
+        "className": data.get_attribute("class").unwrap_or(""),
+        "attributes": data.attributes,
+        "textContent": text,
+        "childElementCount": children.len(),
+        "children": children,
+    }))
+}
+
+fn collect_text(node: &Node, text: &mut String) {
+    match &node.node_type {
+        NodeType::Text(content) => text.push_str(content),
+        NodeType::Element(_) => node.children.iter().for_each(|child| collect_text(child, text)),
+        NodeType::Comment(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html::HtmlParser;
+    use crate::js::JsValue;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_inspected_element() {
+        let mut attrs = HashMap::new();
+        attrs.insert("class".to_string(), "card wide".to_string());
+        let element = Node::element(
+            "section".to_string(),
+            attrs,
+            vec![Node::text("Hello ".to_string()), Node::element("b".to_string(), HashMap::new(), vec![
+                Node::text("world".to_string()),
+            ])],
+        );
+
+        let mut runtime = JsRuntime::new();
+        install(&mut runtime).unwrap();
+        set_inspected(&mut runtime, Some(&element)).unwrap();
+        assert_eq!(runtime.execute("$0.tagName").unwrap(), JsValue::String("SECTION".to_string()));
+        assert_eq!(runtime.execute("$0.textContent").unwrap(), JsValue::String("Hello world".to_string()));
+        assert_eq!(runtime.execute("$0.getAttribute('class')").unwrap(), JsValue::String("card wide".to_string()));
+        assert_eq!(runtime.execute("$0.children[0].localName").unwrap(), JsValue::String("b".to_string()));
+
+        set_inspected(&mut runtime, None).unwrap();
+        assert_eq!(runtime.execute("typeof $0").unwrap(), JsValue::String("undefined".to_string()));
+    }
+
+    #[test]
+    fn test_query_selectors() {
+        let document = HtmlParser::parse(
+            "<html><body><ul id=\"menu\"><li class=\"item\">A</li><li class=\"item on\">B</li></ul>\
+             <p class=\"item\">C</p></body></html>",
+        );
+        let mut runtime = JsRuntime::new();
+        install(&mut runtime).unwrap();
+        assert_eq!(runtime.execute("$('li')").unwrap(), JsValue::Null);
+        set_document(&mut runtime, &document).unwrap();
+
+        assert_eq!(runtime.execute("$('li').textContent").unwrap(), JsValue::String("A".to_string()));
+        assert_eq!(runtime.execute("$$('.item').length").unwrap(), JsValue::Number(3.0));
+        assert_eq!(runtime.execute("$$('#menu .item').length").unwrap(), JsValue::Number(2.0));
+        assert_eq!(runtime.execute("$('li.item.on').textContent").unwrap(), JsValue::String("B".to_string()));
+        assert_eq!(runtime.execute("$$('p, #menu').length").unwrap(), JsValue::Number(2.0));
+        assert_eq!(runtime.execute("$('table')").unwrap(), JsValue::Null);
+        assert!(runtime.execute("$('a > b')").is_err());
+    }
+}
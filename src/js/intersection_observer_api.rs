@@ -0,0 +1,366 @@
+// IntersectionObserver binding
+//
+// Observers are kept by the host's `ObserverManager`. After each frame the
+// host works out where the observed elements are against the viewport, or
+// an element with an id given as the root, and hands back the entries for
+// elements that crossed a threshold. Entries reach each observer in one
+// batch from a microtask, so callbacks run after the frame, not during it.
+
+use super::runtime::{JsError, JsRuntime, JsValue};
+use crate::observers::{IntersectionObserverEntry, MarginLength, Rect};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// Script installed into every context to provide `IntersectionObserver`
+const INTERSECTION_OBSERVER_SHIM: &str = r#"
+(function (global) {
+    var queue = [];
+    var observers = {};
+    var nextObserver = 1;
+
+    function elementId(target) {
+        var id = target && typeof target.id === "string" ? target.id : "";
+        if (id === "" || !global.document.getElementById(id)) {
+            throw new TypeError("IntersectionObserver: only elements with an id can be observed");
+        }
+        return id;
+    }
+
+    function syntaxError(message) {
+        var error = new Error(message);
+        error.name = "SyntaxError";
+        return error;
+    }
+
+    // "10px 20%" to [[10, "px"], [20, "%"], [10, "px"], [20, "%"]]: top, right, bottom, left
+    function parseRootMargin(margin) {
+        var parts = String(margin).trim().split(/\s+/);
+        if (parts.length > 4) {
+            throw syntaxError("IntersectionObserver: rootMargin has more than four values");
+        }
+        var lengths = parts.map(function (part) {
+            var match = /^(-?(?:\d+\.?\d*|\.\d+))(px|%)?$/.exec(part);
+            if (!match || (match[2] === undefined && Number(match[1]) !== 0)) {
+                throw syntaxError("IntersectionObserver: rootMargin must be in pixels or percent");
+            }
+            return [Number(match[1]), match[2] || "px"];
+        });
+        var top = lengths[0];
+        var right = lengths[1] || top;
+        var bottom = lengths[2] || top;
+        return [top, right, bottom, lengths[3] || right];
+    }
+
+    function parseThresholds(threshold) {
+        var list = threshold === undefined ? [0] : Array.isArray(threshold) ? threshold : [threshold];
+        list = list.map(Number);
+        list.forEach(function (value) {
+            if (!(value >= 0 && value <= 1)) {
+                throw new RangeError("IntersectionObserver: thresholds must be between 0 and 1");
+            }
+        });
+        return list.length > 0 ? list.sort(function (a, b) { return a - b; }) : [0];
+    }
+
+    function domRect(raw) {
+        if (raw === null) {
+            return null;
+        }
+        return {
+            x: raw.x, y: raw.y, width: raw.width, height: raw.height,
+            top: raw.y, left: raw.x, right: raw.x + raw.width, bottom: raw.y + raw.height
+        };
+    }
+
+    function IntersectionObserverEntry(raw) {
+        this.time = raw.time;
+        this.target = global.document.getElementById(raw.target) || { id: raw.target };
+        this.rootBounds = domRect(raw.rootBounds);
+        this.boundingClientRect = domRect(raw.boundingClientRect);
+        this.intersectionRect = domRect(raw.intersectionRect);
+        this.isIntersecting = raw.isIntersecting;
+        this.intersectionRatio = raw.intersectionRatio;
+    }
+
+    function IntersectionObserver(callback, options) {
+        if (typeof callback !== "function") {
+            throw new TypeError("IntersectionObserver requires a callback function");
+        }
+        options = options || {};
+        this._id = nextObserver++;
+        this._callback = callback;
+        this._records = [];
+        this._scheduled = false;
+        this.root = options.root === undefined ? null : options.root;
+        this._root = this.root === null || this.root === global.document ? null : elementId(this.root);
+        this._margin = parseRootMargin(options.rootMargin === undefined ? "0px" : options.rootMargin);
+        this.rootMargin = this._margin.map(function (length) { return length[0] + length[1]; }).join(" ");
+        this.thresholds = parseThresholds(options.threshold);
+    }
+    IntersectionObserver.prototype.observe = function (target) {
+        var id = elementId(target);
+        observers[this._id] = this;
+        queue.push({
+            kind: "observe",
+            id: this._id,
+            target: id,
+            root: this._root,
+            rootMargin: this._margin,
+            thresholds: this.thresholds
+        });
+    };
+    IntersectionObserver.prototype.unobserve = function (target) {
+        var id = target && typeof target.id === "string" ? target.id : "";
+        queue.push({ kind: "unobserve", id: this._id, target: id });
+    };
+    IntersectionObserver.prototype.disconnect = function () {
+        delete observers[this._id];
+        this._records = [];
+        queue.push({ kind: "disconnect", id: this._id });
+    };
+    IntersectionObserver.prototype.takeRecords = function () {
+        var taken = this._records;
+        this._records = [];
+        return taken;
+    };
+    global.IntersectionObserver = IntersectionObserver;
+    global.IntersectionObserverEntry = IntersectionObserverEntry;
+
+    global.__intersectionObserverDeliver = function (id, entries) {
+        var observer = observers[id];
+        if (!observer) {
+            return;
+        }
+        observer._records = observer._records.concat(entries.map(function (raw) {
+            return new IntersectionObserverEntry(raw);
+        }));
+        if (observer._scheduled) {
+            return;
+        }
+        observer._scheduled = true;
+        Promise.resolve().then(function () {
+            observer._scheduled = false;
+            var taken = observer.takeRecords();
+            if (taken.length > 0 && observers[id] === observer) {
+                observer._callback.call(observer, taken, observer);
+            }
+        });
+    };
+
+    global.__intersectionObserverPending = function () {
+        return queue.length > 0;
+    };
+
+    global.__intersectionObserverTake = function () {
+        var taken = queue;
+        queue = [];
+        return JSON.stringify(taken);
+    };
+})(globalThis);
+"#;
+
+/// A subscription change made by a script; targets and roots are element ids
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum IntersectionObserverRequest {
+    /// `observer.observe(target)`, with the observer's options
+    Observe {
+        id: u64,
+        target: String,
+        /// `None` for the viewport
+        root: Option<String>,
+        root_margin: [MarginLength; 4],
+        thresholds: Vec<f32>,
+    },
+    /// `observer.unobserve(target)`
+    Unobserve { id: u64, target: String },
+    /// `observer.disconnect()`
+    Disconnect { id: u64 },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawRequest {
+    kind: String,
+    id: u64,
+    #[serde(default)]
+    target: Option<String>,
+    #[serde(default)]
+    root: Option<String>,
+    #[serde(default)]
+    root_margin: Vec<(f32, String)>,
+    #[serde(default)]
+    thresholds: Vec<f32>,
+}
+
+/// Numbers the elements observers watch, by id
+#[derive(Debug, Default)]
+pub(super) struct IntersectionTargets {
+    ids: Vec<String>,
+}
+
+impl IntersectionTargets {
+    pub(super) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(super) fn number(&mut self, id: &str) -> u64 {
+        let index = match self.ids.iter().position(|known| known == id) {
+            Some(index) => index,
+            None => {
+                self.ids.push(id.to_string());
+                self.ids.len() - 1
+            }
+        };
+        index as u64
+    }
+
+    pub(super) fn id(&self, number: u64) -> Option<&str> {
+        self.ids.get(number as usize).map(String::as_str)
+    }
+}
+
+/// Install the IntersectionObserver shim into a runtime
+pub(super) fn install(runtime: &mut JsRuntime) -> Result<(), JsError> {
+    runtime.execute(INTERSECTION_OBSERVER_SHIM).map(|_| ())
+}
+
+/// Are subscription changes waiting for the host
+pub(super) fn is_pending(runtime: &mut JsRuntime) -> Result<bool, JsError> {
+    Ok(runtime.execute("__intersectionObserverPending()")?.to_bool())
+}
+
+/// Drain subscription changes queued by scripts
+pub(super) fn take_requests(runtime: &mut JsRuntime) -> Result<Vec<IntersectionObserverRequest>, JsError> {
+    let json = match runtime.execute("__intersectionObserverTake()")? {
+        JsValue::String(json) => json,
+        other => return Err(JsError::TypeError(format!("unexpected IntersectionObserver queue: {:?}", other))),
+    };
+
+    let raw: Vec<RawRequest> = serde_json::from_str(&json).map_err(|e| JsError::RuntimeError(e.to_string()))?;
+
+    Ok(raw
+        .into_iter()
+        .filter_map(|r| match r.kind.as_str() {
+            "observe" => {
+                let lengths: Vec<_> = r
+                    .root_margin
+                    .iter()
+                    .map(|(value, unit)| match unit.as_str() {
+                        "%" => MarginLength::Percent(*value),
+                        _ => MarginLength::Px(*value),
+                    })
+                    .collect();
+                Some(IntersectionObserverRequest::Observe {
+                    id: r.id,
+                    target: r.target?,
+                    root: r.root,
+                    root_margin: lengths.try_into().ok()?,
+                    thresholds: r.thresholds,
+                })
+            }
+            "unobserve" => Some(IntersectionObserverRequest::Unobserve { id: r.id, target: r.target? }),
+            "disconnect" => Some(IntersectionObserverRequest::Disconnect { id: r.id }),
+            _ => None,
+        })
+        .collect())
+}
+
+/// An entry as scripts see it, for the element with `target` as its id
+pub(super) fn entry_json(entry: &IntersectionObserverEntry, target: &str) -> Value {
+    let rect = |rect: &Rect| json!({"x": rect.x, "y": rect.y, "width": rect.width, "height": rect.height});
+    json!({
+        "time": entry.time,
+        "target": target,
+        "rootBounds": entry.root_bounds.as_ref().map(rect),
+        "boundingClientRect": rect(&entry.bounding_client_rect),
+        "intersectionRect": rect(&entry.intersection_rect),
+        "isIntersecting": entry.is_intersecting,
+        "intersectionRatio": entry.intersection_ratio,
+    })
+}
+
+/// Queue a batch of entries for an observer's next callback
+///
+/// The callback runs from the microtask queue once this script finishes.
+pub(super) fn deliver(runtime: &mut JsRuntime, id: u64, entries: &[Value]) -> Result<(), JsError> {
+    runtime.execute(&format!("__intersectionObserverDeliver({}, {})", id, json!(entries))).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_observer_options() {
+        let mut runtime = JsRuntime::new();
+        install(&mut runtime).unwrap();
+        runtime.execute("document = { getElementById: function (id) { return id ? { id: id } : null; } }").unwrap();
+        runtime
+            .execute(
+                "var observer = new IntersectionObserver(function () {}, {
+                     root: document.getElementById('list'), rootMargin: '10px 5%', threshold: [1, 0.5]
+                 });
+                 observer.observe(document.getElementById('item'));
+                 observer.unobserve(document.getElementById('item'));",
+            )
+            .unwrap();
+        assert!(is_pending(&mut runtime).unwrap());
+        assert_eq!(runtime.execute("observer.rootMargin").unwrap(), JsValue::String("10px 5% 10px 5%".to_string()));
+        assert_eq!(take_requests(&mut runtime).unwrap(), [
+            IntersectionObserverRequest::Observe {
+                id: 1,
+                target: "item".to_string(),
+                root: Some("list".to_string()),
+                root_margin: [
+                    MarginLength::Px(10.0),
+                    MarginLength::Percent(5.0),
+                    MarginLength::Px(10.0),
+                    MarginLength::Percent(5.0),
+                ],
+                thresholds: vec![0.5, 1.0],
+            },
+            IntersectionObserverRequest::Unobserve { id: 1, target: "item".to_string() },
+        ]);
+        assert!(!is_pending(&mut runtime).unwrap());
+
+        let mut error =
+            |code: &str| runtime.execute(&format!("try {{ {}; 'ok' }} catch (e) {{ e.name }}", code)).unwrap();
+        let name = |name: &str| JsValue::String(name.to_string());
+        assert_eq!(error("new IntersectionObserver(function () {}, { rootMargin: '10em' })"), name("SyntaxError"));
+        assert_eq!(error("new IntersectionObserver(function () {}, { threshold: 2 })"), name("RangeError"));
+        assert_eq!(error("observer.observe({})"), name("TypeError"));
+    }
+
+    #[test]
+    fn test_entries_called_back_from_microtasks() {
+        let mut runtime = JsRuntime::new();
+        install(&mut runtime).unwrap();
+        runtime
+            .execute(
+                "document = { getElementById: function (id) { return { id: id }; } };
+                 var seen = [];
+                 var observer = new IntersectionObserver(function (entries) {
+                     entries.forEach(function (e) {
+                         seen.push(e.target.id + ':' + e.isIntersecting + ':' + e.boundingClientRect.bottom);
+                     });
+                 });
+                 observer.observe(document.getElementById('a'));",
+            )
+            .unwrap();
+        let entry = IntersectionObserverEntry {
+            target: 0,
+            bounding_client_rect: Rect::new(0.0, 10.0, 50.0, 20.0),
+            root_bounds: Some(Rect::new(0.0, 0.0, 100.0, 100.0)),
+            intersection_rect: Rect::new(0.0, 10.0, 50.0, 20.0),
+            intersection_ratio: 1.0,
+            is_intersecting: true,
+            time: 12.0,
+        };
+        deliver(&mut runtime, 1, &[entry_json(&entry, "a")]).unwrap();
+        assert_eq!(runtime.execute("seen.join('|')").unwrap(), JsValue::String("a:true:30".to_string()));
+
+        runtime.execute("observer.disconnect()").unwrap();
+        deliver(&mut runtime, 1, &[entry_json(&entry, "a")]).unwrap();
+        assert_eq!(runtime.execute("seen.length").unwrap(), JsValue::Number(1.0));
+    }
+}
@@ -0,0 +1,353 @@
+// HTMLMediaElement binding: play(), pause(), currentTime, volume and friends
+//
+// <audio> and <video> elements from `document.getElementById` get the media
+// element API. Their state lives in the host's players and is sent to the
+// page after each change, together with the events the players fired, which
+// are dispatched to the element's listeners. Calls that change playback are
+// queued for the host; the state they set shows at once, so a script reads
+// back what it wrote.
+
+use super::runtime::{JsError, JsRuntime, JsValue};
+use crate::media::{MediaBackend, MediaElements, MediaEvent, MediaState};
+use serde::Deserialize;
+
+/// MIME types whose `canPlayType` answers are worked out by the host
+const PROBED_TYPES: &[&str] = &[
+    "audio/mpeg",
+    "audio/mp4",
+    "audio/mp4; codecs=\"mp4a.40.2\"",
+    "audio/aac",
+    "audio/ogg",
+    "audio/ogg; codecs=\"vorbis\"",
+    "audio/ogg; codecs=\"opus\"",
+    "audio/webm",
+    "audio/webm; codecs=\"vorbis\"",
+    "audio/webm; codecs=\"opus\"",
+    "audio/flac",
+    "audio/wav",
+    "audio/wav; codecs=\"1\"",
+    "video/mp4",
+    "video/mp4; codecs=\"avc1.42e01e, mp4a.40.2\"",
+    "video/webm",
+    "video/webm; codecs=\"vp8, vorbis\"",
+    "video/webm; codecs=\"vp9, opus\"",
+    "video/ogg",
+];
+
+/// Script installed into every context to provide the media element API
+const MEDIA_SHIM: &str = r#"
+(function (global) {
+    var queue = [];
+    var states = {};
+    var pendingPlays = {};
+    var canPlay = {};
+
+    function normalize(type) {
+        return String(type).toLowerCase().replace(/\s+/g, "").replace(/'/g, "\"");
+    }
+
+    function settlePlays(id, type) {
+        var pending = pendingPlays[id] || [];
+        if (type === "playing") {
+            delete pendingPlays[id];
+            pending.forEach(function (p) { p.resolve(); });
+        } else if (type === "pause" || type === "error") {
+            delete pendingPlays[id];
+            var name = type === "pause" ? "AbortError" : "NotSupportedError";
+            pending.forEach(function (p) {
+                var error = new Error(name === "AbortError"
+                    ? "The play() request was interrupted by a call to pause()"
+                    : "The element has no supported sources");
+                error.name = name;
+                p.reject(error);
+            });
+        }
+    }
+
+    function property(element, name, get, set) {
+        Object.defineProperty(element, name, { get: get, set: set, enumerable: true, configurable: true });
+    }
+
+    function makeMedia(element, id) {
+        var state = states[id];
+        element.play = function () {
+            return new Promise(function (resolve, reject) {
+                if (state.error === 4) {
+                    var error = new Error("The element has no supported sources");
+                    error.name = "NotSupportedError";
+                    reject(error);
+                    return;
+                }
+                var playing = !state.paused && state.readyState >= 3;
+                state.paused = false;
+                state.ended = false;
+                queue.push({ kind: "play", id: id });
+                if (playing) {
+                    resolve();
+                } else {
+                    (pendingPlays[id] || (pendingPlays[id] = [])).push({ resolve: resolve, reject: reject });
+                }
+            });
+        };
+        element.pause = function () {
+            state.paused = true;
+            queue.push({ kind: "pause", id: id });
+        };
+        element.load = function () {
+            queue.push({ kind: "load", id: id });
+        };
+        element.canPlayType = global.__mediaCanPlayType;
+        property(element, "currentTime", function () { return state.currentTime; }, function (value) {
+            value = Number(value);
+            if (!isFinite(value)) {
+                throw new TypeError("Failed to set currentTime: the value is not finite");
+            }
+            state.currentTime = value;
+            state.seeking = true;
+            queue.push({ kind: "seek", id: id, time: value });
+        });
+        property(element, "volume", function () { return state.volume; }, function (value) {
+            value = Number(value);
+            if (!(value >= 0 && value <= 1)) {
+                throw new RangeError("Failed to set volume: " + value + " is outside the range [0, 1]");
+            }
+            state.volume = value;
+            queue.push({ kind: "volume", id: id, volume: value, muted: state.muted });
+        });
+        property(element, "muted", function () { return state.muted; }, function (value) {
+            state.muted = !!value;
+            queue.push({ kind: "volume", id: id, volume: state.volume, muted: state.muted });
+        });
+        property(element, "loop", function () { return state.loop; }, function (value) {
+            state.loop = !!value;
+            queue.push({ kind: "loop", id: id, loop: state.loop });
+        });
+        property(element, "duration", function () { return state.duration === null ? NaN : state.duration; });
+        property(element, "error", function () {
+            return state.error === null ? null : { code: state.error };
+        });
+        ["paused", "ended", "seeking", "readyState", "networkState", "videoWidth", "videoHeight"]
+            .forEach(function (name) {
+                property(element, name, function () { return state[name]; });
+            });
+        return element;
+    }
+
+    global.__mediaCanPlayType = function (type) {
+        type = normalize(type);
+        if (Object.prototype.hasOwnProperty.call(canPlay, type)) {
+            return canPlay[type];
+        }
+        // Codecs not probed by the host may or may not play
+        var essence = type.split(";")[0];
+        return canPlay[essence] ? "maybe" : "";
+    };
+
+    global.document = global.document || {};
+    var getElementById = global.document.getElementById;
+    global.document.getElementById = function (id) {
+        var element = getElementById ? getElementById.call(this, id) : null;
+        id = String(id);
+        if (!element || !Object.prototype.hasOwnProperty.call(states, id)) {
+            return element;
+        }
+        return makeMedia(element, id);
+    };
+
+    global.__mediaSetElements = function (elements, answers) {
+        states = {};
+        pendingPlays = {};
+        canPlay = {};
+        Object.keys(answers).forEach(function (type) { canPlay[normalize(type)] = answers[type]; });
+        elements.forEach(function (element) { states[element.id] = element.state; });
+    };
+
+    global.__mediaUpdate = function (id, state, events) {
+        if (!Object.prototype.hasOwnProperty.call(states, id)) {
+            return;
+        }
+        var current = states[id];
+        Object.keys(state).forEach(function (key) { current[key] = state[key]; });
+        var element = events.length > 0 ? global.document.getElementById(id) : null;
+        events.forEach(function (type) {
+            settlePlays(id, type);
+            if (element && element.dispatchEvent) {
+                try {
+                    element.dispatchEvent({ type: type });
+                } catch (e) {
+                    if (global.console && console.error) {
+                        console.error(e);
+                    }
+                }
+            }
+        });
+    };
+
+    global.__mediaTake = function () {
+        var taken = queue;
+        queue = [];
+        return JSON.stringify(taken);
+    };
+})(globalThis);
+"#;
+
+/// A media element call the host has to act on
+#[derive(Debug, Clone, PartialEq)]
+pub enum MediaRequest {
+    /// `element.play()`
+    Play { id: String },
+    /// `element.pause()`
+    Pause { id: String },
+    /// `element.load()`
+    Load { id: String },
+    /// Setting `currentTime`
+    Seek { id: String, time: f64 },
+    /// Setting `volume` or `muted`
+    SetVolume { id: String, volume: f64, muted: bool },
+    /// Setting `loop`
+    SetLoop { id: String, looping: bool },
+}
+
+#[derive(Deserialize)]
+struct RawRequest {
+    kind: String,
+    id: String,
+    time: Option<f64>,
+    volume: Option<f64>,
+    muted: Option<bool>,
+    #[serde(rename = "loop")]
+    looping: Option<bool>,
+}
+
+/// Install the media element shim into a runtime
+pub(super) fn install(runtime: &mut JsRuntime) -> Result<(), JsError> {
+    runtime.execute(MEDIA_SHIM).map(|_| ())
+}
+
+/// Tell the page which media elements it has, their state and what `canPlayType` answers
+pub(super) fn set_elements(
+    runtime: &mut JsRuntime,
+    media: &MediaElements,
+    backend: &dyn MediaBackend,
+    now: std::time::Instant,
+) -> Result<(), JsError> {
+    let elements: Vec<serde_json::Value> = media
+        .iter()
+        .filter_map(|element| {
+            let id = element.id.as_ref()?;
+            Some(serde_json::json!({ "id": id, "state": element.player.state(now) }))
+        })
+        .collect();
+    let answers: serde_json::Map<String, serde_json::Value> = PROBED_TYPES
+        .iter()
+        .map(|&mime_type| (mime_type.to_string(), backend.can_play_type(mime_type).as_str().into()))
+        .collect();
+    runtime
+        .execute(&format!(
+            "__mediaSetElements({}, {})",
+            serde_json::Value::Array(elements),
+            serde_json::Value::Object(answers)
+        ))
+        .map(|_| ())
+}
+
+/// Send an element's state and fire the events its player fired
+pub(super) fn update(
+    runtime: &mut JsRuntime,
+    id: &str,
+    state: &MediaState,
+    events: &[MediaEvent],
+) -> Result<(), JsError> {
+    let state = serde_json::to_string(state).map_err(|e| JsError::RuntimeError(e.to_string()))?;
+    let events: Vec<&str> = events.iter().map(MediaEvent::as_str).collect();
+    let id = serde_json::to_string(id).map_err(|e| JsError::RuntimeError(e.to_string()))?;
+    let events = serde_json::to_string(&events).map_err(|e| JsError::RuntimeError(e.to_string()))?;
+    runtime.execute(&format!("__mediaUpdate({}, {}, {})", id, state, events)).map(|_| ())
+}
+
+/// Drain media element calls queued by scripts
+pub(super) fn take_requests(runtime: &mut JsRuntime) -> Result<Vec<MediaRequest>, JsError> {
+    let json = match runtime.execute("__mediaTake()")? {
+        JsValue::String(json) => json,
+        other => return Err(JsError::TypeError(format!("unexpected media queue: {:?}", other))),
+    };
+
+    let raw: Vec<RawRequest> = serde_json::from_str(&json).map_err(|e| JsError::RuntimeError(e.to_string()))?;
+
+    Ok(raw
+        .into_iter()
+        .filter_map(|r| {
+            let id = r.id;
+            match r.kind.as_str() {
+                "play" => Some(MediaRequest::Play { id }),
+                "pause" => Some(MediaRequest::Pause { id }),
+                "load" => Some(MediaRequest::Load { id }),
+                "seek" => Some(MediaRequest::Seek { id, time: r.time? }),
+                "volume" => Some(MediaRequest::SetVolume { id, volume: r.volume?, muted: r.muted? }),
+                "loop" => Some(MediaRequest::SetLoop { id, looping: r.looping? }),
+                _ => None,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dom::Document;
+    use crate::html::HtmlParser;
+    use crate::media::{Backends, ReadyState};
+    use std::sync::Arc;
+    use std::time::Instant;
+    use url::Url;
+
+    #[test]
+    fn test_media_element_calls_and_events() {
+        let document = HtmlParser::parse("<body><audio id=\"song\" src=\"song.mp3\" loop></audio></body>");
+        let backend = Arc::new(Backends::new());
+        let media = MediaElements::from_document(&document, &Url::parse("https://a.test/").unwrap(), backend.clone());
+        let mut runtime = JsRuntime::new();
+        super::super::scroll_api::install(&mut runtime).unwrap();
+        super::super::scroll_api::set_element_ids(&mut runtime, &Document::new(document)).unwrap();
+        super::super::element_api::install(&mut runtime).unwrap();
+        install(&mut runtime).unwrap();
+        let now = Instant::now();
+        set_elements(&mut runtime, &media, backend.as_ref(), now).unwrap();
+
+        runtime
+            .execute(
+                "var log = [];
+                 var song = document.getElementById('song');
+                 song.addEventListener('playing', function () { log.push('playing'); });
+                 song.play().then(function () { log.push('resolved'); });
+                 song.currentTime = 12;
+                 song.volume = 0.5;
+                 try { song.volume = 2; } catch (e) { log.push(e.name); }",
+            )
+            .unwrap();
+        assert_eq!(runtime.execute("song.paused").unwrap(), JsValue::Boolean(false));
+        assert_eq!(runtime.execute("song.loop && isNaN(song.duration)").unwrap(), JsValue::Boolean(true));
+        assert_eq!(
+            runtime.execute("song.canPlayType('audio/mpeg') + '|' + song.canPlayType('video/x-flv')").unwrap(),
+            JsValue::String("maybe|".to_string())
+        );
+        let song = "song".to_string();
+        assert_eq!(
+            take_requests(&mut runtime).unwrap(),
+            vec![
+                MediaRequest::Play { id: song.clone() },
+                MediaRequest::Seek { id: song.clone(), time: 12.0 },
+                MediaRequest::SetVolume { id: song, volume: 0.5, muted: false },
+            ]
+        );
+
+        let mut state = media.iter().next().unwrap().player.state(now);
+        state.paused = false;
+        state.current_time = 12.0;
+        state.ready_state = ReadyState::HaveFutureData as u8;
+        update(&mut runtime, "song", &state, &[MediaEvent::Playing]).unwrap();
+        let log = runtime.execute("log.join(',')").unwrap();
+        assert_eq!(log, JsValue::String("RangeError,playing,resolved".to_string()));
+        assert_eq!(runtime.execute("song.currentTime").unwrap(), JsValue::Number(12.0));
+    }
+}
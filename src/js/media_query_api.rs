@@ -0,0 +1,178 @@
+// window.matchMedia binding
+//
+// Queries are evaluated in the page against the user preference media
+// features the host last set, using the same rules as stylesheets: the
+// page is a screen, and features the engine does not know make a query
+// false. When the host changes the features, lists whose answer changed
+// fire `change` at their listeners.
+
+use super::runtime::{JsError, JsRuntime};
+use crate::css::MediaFeatures;
+
+/// Script installed into every context to provide `matchMedia`
+const MEDIA_QUERY_SHIM: &str = r#"
+(function (global) {
+    var features = { "prefers-reduced-motion": "no-preference" };
+    var watched = [];
+
+    function featureMatches(text) {
+        var colon = text.indexOf(":");
+        var name = (colon < 0 ? text : text.slice(0, colon)).trim();
+        var value = colon < 0 ? null : text.slice(colon + 1).trim();
+        if (!Object.prototype.hasOwnProperty.call(features, name)) {
+            return null;
+        }
+        if (value === null) {
+            return features[name] !== "no-preference";
+        }
+        return features[name] === value;
+    }
+
+    function queryMatches(query) {
+        var words = query.replace(/\(([^)]*)\)/g, function (_, feature) {
+            return " (" + feature.replace(/\s+/g, "") + ") ";
+        }).trim().split(/\s+/).filter(function (word) { return word.length > 0; });
+        var negated = false;
+        if (words[0] === "not" || words[0] === "only") {
+            negated = words.shift() === "not";
+        }
+        var matches = true;
+        for (var i = 0; i < words.length; i++) {
+            var word = words[i];
+            if (word === "and") {
+                continue;
+            }
+            if (word.charAt(0) === "(") {
+                var result = featureMatches(word.slice(1, -1));
+                if (result === null) {
+                    return false;
+                }
+                matches = matches && result;
+            } else if (word !== "all" && word !== "screen") {
+                matches = false;
+            }
+        }
+        return negated ? !matches : matches;
+    }
+
+    function evaluate(media) {
+        var queries = media.toLowerCase().split(",");
+        return queries.some(function (query) { return queryMatches(query); });
+    }
+
+    function MediaQueryList(media) {
+        this.media = media;
+        this.onchange = null;
+        this._listeners = [];
+        this._matches = evaluate(media);
+    }
+    Object.defineProperty(MediaQueryList.prototype, "matches", {
+        get: function () { return evaluate(this.media); }
+    });
+    MediaQueryList.prototype.addEventListener = function (type, listener) {
+        if (type === "change" && typeof listener === "function" && this._listeners.indexOf(listener) < 0) {
+            this._listeners.push(listener);
+            if (watched.indexOf(this) < 0) {
+                this._matches = evaluate(this.media);
+                watched.push(this);
+            }
+        }
+    };
+    MediaQueryList.prototype.removeEventListener = function (type, listener) {
+        if (type === "change") {
+            this._listeners = this._listeners.filter(function (l) { return l !== listener; });
+        }
+    };
+    MediaQueryList.prototype.addListener = function (listener) {
+        this.addEventListener("change", listener);
+    };
+    MediaQueryList.prototype.removeListener = function (listener) {
+        this.removeEventListener("change", listener);
+    };
+    MediaQueryList.prototype._notify = function () {
+        var event = { type: "change", media: this.media, matches: this._matches, target: this };
+        var listeners = this._listeners.slice();
+        if (typeof this.onchange === "function") {
+            listeners.unshift(this.onchange);
+        }
+        for (var i = 0; i < listeners.length; i++) {
+            // One listener throwing does not stop the others
+            try {
+                listeners[i].call(this, event);
+            } catch (e) {
+                if (global.console && console.error) {
+                    console.error(e);
+                }
+            }
+        }
+    };
+    global.MediaQueryList = MediaQueryList;
+
+    global.matchMedia = function (media) {
+        return new MediaQueryList(String(media));
+    };
+
+    global.__mediaSetFeatures = function (next) {
+        features = next;
+        var changed = 0;
+        watched.forEach(function (list) {
+            var matches = evaluate(list.media);
+            if (matches !== list._matches) {
+                list._matches = matches;
+                changed++;
+                list._notify();
+            }
+        });
+        return changed;
+    };
+})(globalThis);
+"#;
+
+/// Install the matchMedia shim into a runtime
+pub(super) fn install(runtime: &mut JsRuntime) -> Result<(), JsError> {
+    runtime.execute(MEDIA_QUERY_SHIM).map(|_| ())
+}
+
+/// Set the media features queries are evaluated against, firing `change` where answers changed
+pub(super) fn set_features(runtime: &mut JsRuntime, features: &MediaFeatures) -> Result<(), JsError> {
+    let reduced_motion = if features.reduced_motion { "reduce" } else { "no-preference" };
+    let features = serde_json::json!({ "prefers-reduced-motion": reduced_motion });
+    runtime.execute(&format!("__mediaSetFeatures({})", features)).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::js::runtime::JsValue;
+
+    #[test]
+    fn test_match_media_and_change_events() {
+        let mut runtime = JsRuntime::new();
+        install(&mut runtime).unwrap();
+        runtime
+            .execute(
+                "var reduce = matchMedia('(prefers-reduced-motion: reduce)');
+                 var log = [];
+                 reduce.addEventListener('change', function (e) { log.push(e.media + '=' + e.matches); });",
+            )
+            .unwrap();
+        assert_eq!(runtime.execute("reduce.matches").unwrap(), JsValue::Boolean(false));
+        assert_eq!(
+            runtime.execute("matchMedia('not all and (prefers-reduced-motion)').matches").unwrap(),
+            JsValue::Boolean(true)
+        );
+        assert_eq!(runtime.execute("matchMedia('print').matches").unwrap(), JsValue::Boolean(false));
+        assert_eq!(runtime.execute("matchMedia('(min-width: 10px)').matches").unwrap(), JsValue::Boolean(false));
+
+        set_features(&mut runtime, &MediaFeatures { reduced_motion: true }).unwrap();
+        assert_eq!(runtime.execute("reduce.matches").unwrap(), JsValue::Boolean(true));
+        assert_eq!(
+            runtime.execute("log.join(',')").unwrap(),
+            JsValue::String("(prefers-reduced-motion: reduce)=true".to_string())
+        );
+
+        // Setting the same features again fires nothing
+        set_features(&mut runtime, &MediaFeatures { reduced_motion: true }).unwrap();
+        assert_eq!(runtime.execute("log.length").unwrap(), JsValue::Number(1.0));
+    }
+}
@@ -0,0 +1,427 @@
+// BroadcastChannel and window.postMessage bindings
+//
+// Messages are structured-cloned in the page: values are serialized to a
+// tagged JSON form (keeping object identity, cycles, Maps, Sets, Dates,
+// RegExps and buffers) and rebuilt on the receiving side. Posting queues
+// the serialization for the host, which routes it over the message bus and
+// hands incoming messages back through `deliver`.
+
+use super::runtime::{JsError, JsRuntime, JsValue};
+use crate::multiprocess::{BusMessage, DocumentId, SerializedValue};
+use serde::Deserialize;
+
+/// Script installed into every context to provide cross-document messaging
+const MESSAGING_SHIM: &str = r#"
+(function (global) {
+    var queue = [];
+    var channels = {};
+    var nextChannel = 1;
+    var windowListeners = {};
+
+    function dataCloneError(what) {
+        var error = new Error(what + " could not be cloned");
+        error.name = "DataCloneError";
+        return error;
+    }
+
+    function serialize(value) {
+        var seen = [];
+        function walk(v) {
+            if (v === undefined) {
+                return { t: "undefined" };
+            }
+            if (v === null || typeof v === "boolean" || typeof v === "string") {
+                return { t: "value", v: v };
+            }
+            if (typeof v === "number") {
+                return isFinite(v) ? { t: "value", v: v } : { t: "number", v: String(v) };
+            }
+            if (typeof v === "bigint") {
+                return { t: "bigint", v: v.toString() };
+            }
+            if (typeof v === "function" || typeof v === "symbol") {
+                throw dataCloneError(typeof v === "function" ? "A function" : "A symbol");
+            }
+            var index = seen.indexOf(v);
+            if (index >= 0) {
+                return { t: "ref", id: index };
+            }
+            var id = seen.length;
+            seen.push(v);
+            if (v instanceof Date) {
+                return { t: "date", id: id, v: v.getTime() };
+            }
+            if (v instanceof RegExp) {
+                return { t: "regexp", id: id, source: v.source, flags: v.flags };
+            }
+            if (v instanceof Map) {
+                var entries = [];
+                v.forEach(function (val, key) { entries.push([walk(key), walk(val)]); });
+                return { t: "map", id: id, v: entries };
+            }
+            if (v instanceof Set) {
+                var items = [];
+                v.forEach(function (item) { items.push(walk(item)); });
+                return { t: "set", id: id, v: items };
+            }
+            if (v instanceof ArrayBuffer) {
+                return { t: "buffer", id: id, v: Array.from(new Uint8Array(v)) };
+            }
+            if (ArrayBuffer.isView(v)) {
+                var bytes = new Uint8Array(v.buffer, v.byteOffset, v.byteLength);
+                return { t: "view", id: id, kind: v.constructor.name, v: Array.from(bytes) };
+            }
+            if (v instanceof Error) {
+                return { t: "error", id: id, name: String(v.name), message: String(v.message) };
+            }
+            if (Array.isArray(v)) {
+                // Holes come across as undefined
+                var elements = [];
+                for (var i = 0; i < v.length; i++) {
+                    elements.push(walk(v[i]));
+                }
+                return { t: "array", id: id, v: elements };
+            }
+            return {
+                t: "object",
+                id: id,
+                v: Object.keys(v).map(function (key) { return [key, walk(v[key])]; })
+            };
+        }
+        return JSON.stringify(walk(value));
+    }
+
+    function deserialize(json) {
+        var made = [];
+        function build(n) {
+            switch (n.t) {
+            case "undefined": return undefined;
+            case "value": return n.v;
+            case "number": return Number(n.v);
+            case "bigint": return BigInt(n.v);
+            case "ref": return made[n.id];
+            case "date": return (made[n.id] = new Date(n.v));
+            case "regexp": return (made[n.id] = new RegExp(n.source, n.flags));
+            case "buffer": return (made[n.id] = new Uint8Array(n.v).buffer);
+            case "view":
+                var View = typeof global[n.kind] === "function" ? global[n.kind] : Uint8Array;
+                return (made[n.id] = new View(new Uint8Array(n.v).buffer));
+            case "error":
+                var ErrorType = /Error$/.test(n.name) && typeof global[n.name] === "function" ? global[n.name] : Error;
+                return (made[n.id] = new ErrorType(n.message));
+            case "array":
+                var array = made[n.id] = [];
+                n.v.forEach(function (item, i) { array[i] = build(item); });
+                return array;
+            case "map":
+                var map = made[n.id] = new Map();
+                n.v.forEach(function (entry) { map.set(build(entry[0]), build(entry[1])); });
+                return map;
+            case "set":
+                var set = made[n.id] = new Set();
+                n.v.forEach(function (item) { set.add(build(item)); });
+                return set;
+            case "object":
+                var object = made[n.id] = {};
+                n.v.forEach(function (entry) { object[entry[0]] = build(entry[1]); });
+                return object;
+            }
+            throw dataCloneError("A message");
+        }
+        return build(JSON.parse(json));
+    }
+
+    function fire(target, listeners, event) {
+        var handler = target["on" + event.type];
+        if (typeof handler === "function") {
+            handler.call(target, event);
+        }
+        (listeners[event.type] || []).slice().forEach(function (listener) {
+            listener.call(target, event);
+        });
+    }
+
+    function addListener(listeners, type, listener) {
+        var list = listeners[type] || (listeners[type] = []);
+        if (typeof listener === "function" && list.indexOf(listener) < 0) {
+            list.push(listener);
+        }
+    }
+
+    function removeListener(listeners, type, listener) {
+        var list = listeners[type] || [];
+        var index = list.indexOf(listener);
+        if (index >= 0) {
+            list.splice(index, 1);
+        }
+    }
+
+    function BroadcastChannel(name) {
+        if (!(this instanceof BroadcastChannel)) {
+            throw new TypeError("BroadcastChannel requires 'new'");
+        }
+        this.name = String(name);
+        this.onmessage = null;
+        this.onmessageerror = null;
+        this.__id = nextChannel++;
+        this.__listeners = {};
+        channels[this.__id] = this;
+        queue.push({ kind: "subscribe", channel: this.__id, name: this.name });
+    }
+
+    BroadcastChannel.prototype.postMessage = function (message) {
+        if (!channels[this.__id]) {
+            var error = new Error("BroadcastChannel is closed");
+            error.name = "InvalidStateError";
+            throw error;
+        }
+        queue.push({ kind: "broadcast", channel: this.__id, name: this.name, data: serialize(message) });
+    };
+
+    BroadcastChannel.prototype.close = function () {
+        if (channels[this.__id]) {
+            delete channels[this.__id];
+            queue.push({ kind: "unsubscribe", channel: this.__id });
+        }
+    };
+
+    BroadcastChannel.prototype.addEventListener = function (type, listener) {
+        addListener(this.__listeners, type, listener);
+    };
+
+    BroadcastChannel.prototype.removeEventListener = function (type, listener) {
+        removeListener(this.__listeners, type, listener);
+    };
+
+    global.BroadcastChannel = BroadcastChannel;
+    global.window = global.window || global;
+
+    global.postMessage = function (message, options) {
+        var targetOrigin = typeof options === "object" && options !== null ? options.targetOrigin : options;
+        targetOrigin = targetOrigin === undefined ? "/" : String(targetOrigin);
+        if (targetOrigin !== "*" && targetOrigin !== "/" && !/^[a-z][a-z0-9+.-]*:/i.test(targetOrigin)) {
+            var error = new Error("Invalid target origin '" + targetOrigin + "'");
+            error.name = "SyntaxError";
+            throw error;
+        }
+        queue.push({ kind: "post", targetOrigin: targetOrigin, data: serialize(message) });
+    };
+
+    if (typeof global.addEventListener !== "function") {
+        global.addEventListener = function (type, listener) {
+            addListener(windowListeners, type, listener);
+        };
+        global.removeEventListener = function (type, listener) {
+            removeListener(windowListeners, type, listener);
+        };
+    }
+
+    global.structuredClone = function (value) {
+        return deserialize(serialize(value));
+    };
+
+    global.__messagingDeliver = function (channel, json, origin, fromSelf) {
+        var target = channel === null ? global : channels[channel];
+        if (!target) {
+            return false;
+        }
+        var listeners = channel === null ? windowListeners : target.__listeners;
+        var event = { type: "message", origin: origin, lastEventId: "", source: null, ports: [], target: target };
+        if (channel === null && fromSelf) {
+            event.source = global;
+        }
+        try {
+            event.data = deserialize(json);
+        } catch (e) {
+            event.type = "messageerror";
+            event.data = null;
+        }
+        fire(target, listeners, event);
+        return true;
+    };
+
+    global.__messagingTake = function () {
+        var taken = queue;
+        queue = [];
+        return JSON.stringify(taken);
+    };
+})(globalThis);
+"#;
+
+/// A messaging call the host routes over the message bus
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessagingRequest {
+    /// `new BroadcastChannel(name)`
+    Subscribe { channel: u32, name: String },
+    /// `channel.close()`
+    Unsubscribe { channel: u32 },
+    /// `channel.postMessage(data)`
+    Broadcast { channel: u32, name: String, data: SerializedValue },
+    /// `window.postMessage(data, targetOrigin)`
+    PostMessage { target_origin: String, data: SerializedValue },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawRequest {
+    kind: String,
+    #[serde(default)]
+    channel: u32,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    target_origin: String,
+    #[serde(default)]
+    data: String,
+}
+
+/// Install the messaging shim into a runtime
+pub(super) fn install(runtime: &mut JsRuntime) -> Result<(), JsError> {
+    runtime.execute(MESSAGING_SHIM).map(|_| ())
+}
+
+/// Drain channel and `postMessage` calls queued by scripts
+pub(super) fn take_requests(runtime: &mut JsRuntime) -> Result<Vec<MessagingRequest>, JsError> {
+    let json = match runtime.execute("__messagingTake()")? {
+        JsValue::String(json) => json,
+        other => return Err(JsError::TypeError(format!("unexpected messaging queue: {:?}", other))),
+    };
+
+    let raw: Vec<RawRequest> =
+        serde_json::from_str(&json).map_err(|e| JsError::RuntimeError(e.to_string()))?;
+
+    raw.into_iter()
+        .map(|r| {
+            let data = || SerializedValue::from_json(r.data.clone()).map_err(|e| JsError::RuntimeError(e.to_string()));
+            Ok(match r.kind.as_str() {
+                "subscribe" => MessagingRequest::Subscribe { channel: r.channel, name: r.name.clone() },
+                "unsubscribe" => MessagingRequest::Unsubscribe { channel: r.channel },
+                "broadcast" => MessagingRequest::Broadcast { channel: r.channel, name: r.name.clone(), data: data()? },
+                _ => MessagingRequest::PostMessage { target_origin: r.target_origin.clone(), data: data()? },
+            })
+        })
+        .collect()
+}
+
+/// Fire a `message` event for a message from the bus
+///
+/// `document` is the receiving document, so a window message it sent to
+/// itself gets `event.source` set. Returns false if the channel it was
+/// sent to has been closed.
+pub(super) fn deliver(runtime: &mut JsRuntime, message: &BusMessage, document: DocumentId) -> Result<bool, JsError> {
+    let quote = |s: &str| serde_json::to_string(s).map_err(|e| JsError::RuntimeError(e.to_string()));
+    let script = match message {
+        BusMessage::Broadcast { channel_id, data, origin } => format!(
+            "__messagingDeliver({}, {}, {}, false)",
+            channel_id,
+            quote(data.as_json())?,
+            quote(origin)?
+        ),
+        BusMessage::Window { data, origin, source } => format!(
+            "__messagingDeliver(null, {}, {}, {})",
+            quote(data.as_json())?,
+            quote(origin)?,
+            *source == document
+        ),
+    };
+    Ok(runtime.execute(&script)? == JsValue::Boolean(true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runtime() -> JsRuntime {
+        let mut runtime = JsRuntime::new();
+        install(&mut runtime).unwrap();
+        runtime
+    }
+
+    #[test]
+    fn test_structured_clone() {
+        let mut runtime = runtime();
+        let result = runtime
+            .execute(
+                "var o = { n: 1, s: 'x', list: [1, , 3], when: new Date(5), re: /a+/g, big: 10n,
+                          map: new Map([[1, 'one']]), set: new Set(['a']), bytes: new Uint8Array([1, 2]),
+                          nan: NaN, nested: { deep: true } };
+                 o.self = o;
+                 var c = structuredClone(o);
+                 [c !== o, c.self === c, c.n, c.list.length, c.when.getTime(), c.re.flags, c.big === 10n,
+                  c.map.get(1), c.set.has('a'), c.bytes[1], c.bytes instanceof Uint8Array, isNaN(c.nan),
+                  c.nested.deep, c.nested !== o.nested].join(',')",
+            )
+            .unwrap();
+        assert_eq!(result.to_string(), "true,true,1,3,5,g,true,one,true,2,true,true,true,true");
+
+        let error = runtime
+            .execute("var name; try { structuredClone({ f: function () {} }); } catch (e) { name = e.name; } name")
+            .unwrap();
+        assert_eq!(error.to_string(), "DataCloneError");
+    }
+
+    #[test]
+    fn test_broadcast_channel_requests_and_delivery() {
+        let mut runtime = runtime();
+        runtime
+            .execute(
+                "var log = []; var chat = new BroadcastChannel('chat');
+                 chat.onmessage = function (e) { log.push(e.data.text + '@' + e.origin); };
+                 chat.postMessage({ text: 'hi' });
+                 var other = new BroadcastChannel('chat'); other.close(); other.close();",
+            )
+            .unwrap();
+
+        let requests = take_requests(&mut runtime).unwrap();
+        assert_eq!(requests.len(), 4);
+        assert_eq!(requests[0], MessagingRequest::Subscribe { channel: 1, name: "chat".to_string() });
+        let MessagingRequest::Broadcast { channel: 1, ref data, .. } = requests[1] else {
+            panic!("expected a broadcast, got {:?}", requests[1]);
+        };
+        assert_eq!(requests[3], MessagingRequest::Unsubscribe { channel: 2 });
+
+        let message = BusMessage::Broadcast { channel_id: 1, data: data.clone(), origin: "https://example.com".to_string() };
+        assert!(deliver(&mut runtime, &message, 1).unwrap());
+        let closed = BusMessage::Broadcast { channel_id: 2, data: data.clone(), origin: String::new() };
+        assert!(!deliver(&mut runtime, &closed, 1).unwrap());
+        assert_eq!(runtime.execute("log.join('|')").unwrap().to_string(), "hi@https://example.com");
+
+        let closed_post = runtime
+            .execute("var name; try { other.postMessage(1); } catch (e) { name = e.name; } name")
+            .unwrap();
+        assert_eq!(closed_post.to_string(), "InvalidStateError");
+    }
+
+    #[test]
+    fn test_window_post_message() {
+        let mut runtime = runtime();
+        runtime
+            .execute(
+                "var log = [];
+                 window.addEventListener('message', function (e) { log.push(e.data[0] + ':' + (e.source === window)); });
+                 window.postMessage([1], '*');
+                 postMessage([2], { targetOrigin: 'https://example.com' });
+                 var error; try { postMessage(3, 'example.com'); } catch (e) { error = e.name; }",
+            )
+            .unwrap();
+        assert_eq!(runtime.get_global("error"), Some(JsValue::String("SyntaxError".to_string())));
+
+        let requests = take_requests(&mut runtime).unwrap();
+        let origins: Vec<&str> = requests
+            .iter()
+            .map(|r| match r {
+                MessagingRequest::PostMessage { target_origin, .. } => target_origin.as_str(),
+                _ => "",
+            })
+            .collect();
+        assert_eq!(origins, vec!["*", "https://example.com"]);
+
+        let MessagingRequest::PostMessage { ref data, .. } = requests[0] else {
+            unreachable!();
+        };
+        let from_self = BusMessage::Window { data: data.clone(), origin: "https://example.com".to_string(), source: 7 };
+        deliver(&mut runtime, &from_self, 7).unwrap();
+        deliver(&mut runtime, &from_self, 8).unwrap();
+        assert_eq!(runtime.execute("log.join('|')").unwrap().to_string(), "1:true|1:false");
+    }
+}
@@ -3,13 +3,58 @@
 mod runtime;
 mod dom_bindings;
 mod event_handler;
+mod clipboard_api;
+mod scroll_api;
+mod window_api;
+mod event_source_api;
+mod messaging_api;
+mod element_api;
+mod inspector_api;
+mod console_api;
+mod performance_api;
+mod animation_frame_api;
+mod mutation_observer_api;
+mod intersection_observer_api;
+mod idle_callback_api;
+mod media_query_api;
+mod font_api;
+mod media_api;
+mod permissions_api;
+mod extension_api;
+mod editing_api;
 
 pub use runtime::{JsRuntime, JsValue, JsError};
 pub use dom_bindings::DomBindings;
 pub use event_handler::{EventType, EventHandler};
+pub use clipboard_api::{ClipboardRequest, ClipboardRequestKind};
+pub use scroll_api::ScrollRequest;
+pub use window_api::{OpenDisposition, WindowOpenRequest};
+pub use event_source_api::EventSourceRequest;
+pub use messaging_api::MessagingRequest;
+pub use element_api::{DomBreakpointPause, DomMutation, EventListenerInfo, MutationWatch};
+pub use console_api::{ConsoleProperty, ConsoleValue};
+pub use media_api::MediaRequest;
+pub use permissions_api::PermissionRequest;
+pub use extension_api::{ExtensionCall, ExtensionWorld};
+pub use editing_api::EditCommand;
 
-use crate::dom::Node;
+use crate::animation::{AnimationEvent, AnimationEventType};
+use crate::css::MediaFeatures;
+use crate::editing::InputEvent;
+use crate::dom::{Document, Node};
+use crate::media::{MediaBackend, MediaElements, MediaEvent, MediaState};
+use crate::multiprocess::{DocumentId, MessageBus};
+use crate::net::EventSourceEvent;
+use crate::permissions::{PermissionName, PermissionState};
+use crate::observers::{ObserverId, ObserverManager, Rect};
+use crate::performance::{Performance, PerformanceMeasure, TaskAttribution};
+use crate::web_fonts::FontFaceSet;
+use intersection_observer_api::{IntersectionObserverRequest, IntersectionTargets};
+use mutation_observer_api::{MutationNodes, MutationObserverRequest};
+use performance_api::PerformanceRequest;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 /// JavaScript execution context for a page
 pub struct JsContext {
@@ -21,16 +66,57 @@ pub struct JsContext {
     event_handler: EventHandler,
     /// Execution enabled
     enabled: bool,
+    /// Source of the page's scripts, for locating event listeners
+    scripts: Vec<String>,
+    /// The page's performance timeline and observers
+    performance: Performance,
+    /// The page's mutation and intersection observers
+    observers: ObserverManager,
+    /// Mutation observers by the id the page gave them
+    mutation_observer_ids: HashMap<u64, ObserverId>,
+    /// Nodes named by mutation records
+    mutation_nodes: MutationNodes,
+    /// Intersection observers by the id the page gave them
+    intersection_observer_ids: HashMap<u64, ObserverId>,
+    /// Elements intersection observers watch
+    intersection_targets: IntersectionTargets,
 }
 
 impl JsContext {
     /// Create a new JavaScript context
     pub fn new() -> Self {
+        let mut runtime = JsRuntime::new();
+        clipboard_api::install(&mut runtime).expect("clipboard shim must evaluate");
+        scroll_api::install(&mut runtime).expect("scroll shim must evaluate");
+        window_api::install(&mut runtime).expect("window shim must evaluate");
+        event_source_api::install(&mut runtime).expect("EventSource shim must evaluate");
+        messaging_api::install(&mut runtime).expect("messaging shim must evaluate");
+        element_api::install(&mut runtime).expect("element shim must evaluate");
+        editing_api::install(&mut runtime).expect("execCommand shim must evaluate");
+        inspector_api::install(&mut runtime).expect("inspector shim must evaluate");
+        console_api::install(&mut runtime).expect("console shim must evaluate");
+        performance_api::install(&mut runtime).expect("performance shim must evaluate");
+        animation_frame_api::install(&mut runtime).expect("animation frame shim must evaluate");
+        mutation_observer_api::install(&mut runtime).expect("MutationObserver shim must evaluate");
+        intersection_observer_api::install(&mut runtime).expect("IntersectionObserver shim must evaluate");
+        idle_callback_api::install(&mut runtime).expect("idle callback shim must evaluate");
+        media_query_api::install(&mut runtime).expect("matchMedia shim must evaluate");
+        font_api::install(&mut runtime).expect("document.fonts shim must evaluate");
+        media_api::install(&mut runtime).expect("media element shim must evaluate");
+        permissions_api::install(&mut runtime).expect("permissions shim must evaluate");
+        
         Self {
-            runtime: JsRuntime::new(),
+            runtime,
             dom_bindings: DomBindings::new(),
             event_handler: EventHandler::new(),
             enabled: true,
+            scripts: Vec::new(),
+            performance: Performance::new(),
+            observers: ObserverManager::new(),
+            mutation_observer_ids: HashMap::new(),
+            mutation_nodes: MutationNodes::new(),
+            intersection_observer_ids: HashMap::new(),
+            intersection_targets: IntersectionTargets::new(),
         }
     }
     
@@ -40,9 +126,27 @@ impl JsContext {
             return Err(JsError::ExecutionDisabled);
         }
         
-        self.runtime.execute(code)
+        // Scripts blocking the page for long are reported as long tasks
+        let start_time = self.performance.now();
+        let result = self.runtime.execute(code);
+        self.performance.record_task(start_time, self.performance.now() - start_time, TaskAttribution::Script);
+        result
+    }
+    
+    /// Run one of the page's scripts, keeping its source for DevTools
+    pub fn run_page_script(&mut self, code: &str) -> Result<JsValue, JsError> {
+        self.scripts.push(code.to_string());
+        self.execute(code)
     }
     
+    /// Estimated bytes of the context's script heap
+    ///
+    /// Boa does not report the size of its heap, so this counts the source
+    /// of the page's scripts, which their compiled functions are built from.
+    pub fn heap_size_estimate(&self) -> usize {
+        self.scripts.iter().map(String::capacity).sum()
+    }
+
     /// Bind a DOM tree to the JavaScript context
     pub fn bind_dom(&mut self, dom: Arc<Mutex<Node>>) {
         self.dom_bindings.bind_dom_tree(dom);
@@ -68,6 +172,495 @@ impl JsContext {
         Ok(())
     }
     
+    /// Fire an animation or transition event at the element with `id`, or
+    /// at the document for elements without one
+    pub fn dispatch_animation_event(&mut self, id: Option<&str>, event: &AnimationEvent) -> Result<(), JsError> {
+        let name = match event.event_type {
+            AnimationEventType::TransitionEnd => "propertyName",
+            _ => "animationName",
+        };
+        let init = serde_json::json!({
+            "type": event.event_type.name(),
+            name: event.name,
+            "elapsedTime": event.elapsed_time,
+            "pseudoElement": "",
+        });
+        element_api::dispatch(&mut self.runtime, id, init)
+    }
+    
+    /// Drain pending `navigator.clipboard` calls made by scripts
+    pub fn take_clipboard_requests(&mut self) -> Result<Vec<ClipboardRequest>, JsError> {
+        clipboard_api::take_requests(&mut self.runtime)
+    }
+    
+    /// Settle a `navigator.clipboard` promise
+    ///
+    /// `Ok` resolves it (with the text for reads); `Err` rejects it with a
+    /// `NotAllowedError` carrying the message.
+    pub fn settle_clipboard_request(
+        &mut self,
+        id: u32,
+        result: Result<Option<String>, String>,
+    ) -> Result<(), JsError> {
+        clipboard_api::settle(&mut self.runtime, id, result)
+    }
+    
+    /// Expose the document's element ids, and their nodes, to `document.getElementById`
+    pub fn set_element_ids(&mut self, document: &Document) -> Result<(), JsError> {
+        scroll_api::set_element_ids(&mut self.runtime, document)
+    }
+    
+    /// Let the DevTools console's `$()` and `$$()` search the document
+    pub fn set_console_document(&mut self, dom: &Node) -> Result<(), JsError> {
+        inspector_api::set_document(&mut self.runtime, dom)
+    }
+    
+    /// Expose the element picked in the DevTools inspector as `$0`
+    pub fn set_inspected_element(&mut self, element: Option<&Node>) -> Result<(), JsError> {
+        inspector_api::set_inspected(&mut self.runtime, element)
+    }
+    
+    /// Run a line typed into the DevTools console, previewing its result
+    pub fn evaluate_in_console(&mut self, code: &str) -> Result<ConsoleValue, JsError> {
+        if !self.enabled {
+            return Err(JsError::ExecutionDisabled);
+        }
+        
+        console_api::evaluate(&mut self.runtime, code)
+    }
+    
+    /// List the properties of an object the console showed
+    pub fn console_properties(&mut self, handle: u32) -> Result<Vec<ConsoleProperty>, JsError> {
+        console_api::properties(&mut self.runtime, handle)
+    }
+    
+    /// Names completing the identifier at the end of a console line
+    pub fn console_completions(&mut self, input: &str) -> Result<Vec<String>, JsError> {
+        console_api::completions(&mut self.runtime, input)
+    }
+    
+    /// Drain element changes made by scripts, to apply to the document
+    pub fn take_dom_mutations(&mut self) -> Result<Vec<DomMutation>, JsError> {
+        element_api::take_mutations(&mut self.runtime)
+    }
+    
+    /// Stop scripts before they make the watched changes
+    pub fn set_mutation_watches(&mut self, watches: &[MutationWatch]) -> Result<(), JsError> {
+        element_api::set_watches(&mut self.runtime, watches)
+    }
+    
+    /// Where the last script was stopped by a DOM breakpoint, if it was
+    pub fn take_dom_breakpoint_pause(&mut self) -> Result<Option<DomBreakpointPause>, JsError> {
+        element_api::take_pause(&mut self.runtime)
+    }
+    
+    /// Event listeners on `window`, `document` or `#id` elements
+    pub fn event_listeners(&mut self, targets: &[String]) -> Result<Vec<EventListenerInfo>, JsError> {
+        element_api::listeners(&mut self.runtime, targets, &self.scripts)
+    }
+    
+    /// Drain pending `scrollIntoView`/`scrollTo`/`scrollBy` calls made by scripts
+    pub fn take_scroll_requests(&mut self) -> Result<Vec<ScrollRequest>, JsError> {
+        scroll_api::take_requests(&mut self.runtime)
+    }
+    
+    /// Drain pending `window.open` calls made by scripts
+    pub fn take_window_open_requests(&mut self) -> Result<Vec<WindowOpenRequest>, JsError> {
+        window_api::take_requests(&mut self.runtime)
+    }
+    
+    /// Drain pending `new EventSource()` and `close()` calls made by scripts
+    pub fn take_event_source_requests(&mut self) -> Result<Vec<EventSourceRequest>, JsError> {
+        event_source_api::take_requests(&mut self.runtime)
+    }
+    
+    /// Fire an event received by EventSource `id` at the page
+    ///
+    /// Returns false if the page closed the source in the meantime.
+    pub fn dispatch_event_source_event(
+        &mut self,
+        id: u32,
+        event: &EventSourceEvent,
+        origin: &str,
+    ) -> Result<bool, JsError> {
+        event_source_api::dispatch(&mut self.runtime, id, event, origin)
+    }
+    
+    /// Route the page's BroadcastChannel and `postMessage` calls over the bus
+    ///
+    /// `document` is the page's registration on the bus. Returns how many
+    /// calls were routed.
+    pub fn send_messages(&mut self, bus: &MessageBus, document: DocumentId) -> Result<usize, JsError> {
+        let requests = messaging_api::take_requests(&mut self.runtime)?;
+        let count = requests.len();
+        for request in requests {
+            let result = match request {
+                MessagingRequest::Subscribe { channel, name } => bus.subscribe(document, channel, &name),
+                MessagingRequest::Unsubscribe { channel } => {
+                    bus.unsubscribe(document, channel);
+                    Ok(())
+                }
+                MessagingRequest::Broadcast { channel, name, data } => {
+                    bus.broadcast(document, channel, &name, &data).map(|_| ())
+                }
+                // Windows are only scriptable from themselves, so a page posts to itself
+                MessagingRequest::PostMessage { target_origin, data } => {
+                    bus.post_message(document, document, &target_origin, &data).map(|_| ())
+                }
+            };
+            result.map_err(|e| JsError::RuntimeError(e.to_string()))?;
+        }
+        Ok(count)
+    }
+    
+    /// Fire `message` events for messages waiting on the bus; returns how many
+    pub fn receive_messages(&mut self, bus: &MessageBus, document: DocumentId) -> Result<usize, JsError> {
+        let messages = bus.take_messages(document);
+        for message in &messages {
+            messaging_api::deliver(&mut self.runtime, message, document)?;
+        }
+        Ok(messages.len())
+    }
+    
+    /// The page's performance timeline
+    pub fn performance(&self) -> &Performance {
+        &self.performance
+    }
+    
+    /// Record paint and navigation timing on the page's timeline
+    pub fn performance_mut(&mut self) -> &mut Performance {
+        &mut self.performance
+    }
+    
+    /// Time the page from `origin`, when navigation to it started
+    pub fn set_time_origin(&mut self, origin: Instant) -> Result<(), JsError> {
+        self.performance.set_time_origin(origin);
+        performance_api::set_time_origin(&mut self.runtime, self.performance.time_origin())
+    }
+    
+    /// Record the page's marks and measures, then call back its PerformanceObservers
+    ///
+    /// Each observer gets the entries recorded since its last callback in
+    /// one batch. Returns how many observers were called back.
+    pub fn deliver_performance_entries(&mut self) -> Result<usize, JsError> {
+        for request in performance_api::take_requests(&mut self.runtime)? {
+            let performance = &mut self.performance;
+            match request {
+                PerformanceRequest::Mark { name, start_time } => performance.mark_at(name, start_time),
+                PerformanceRequest::Measure { name, start_time, duration } => {
+                    let entry_type = "measure".to_string();
+                    performance.add_measure(PerformanceMeasure { name, entry_type, start_time, duration });
+                }
+                PerformanceRequest::ClearMarks { name } => performance.clear_marks(name.as_deref()),
+                PerformanceRequest::ClearMeasures { name } => performance.clear_measures(name.as_deref()),
+                PerformanceRequest::Observe { id, entry_types, buffered } => {
+                    // Scripts only subscribe to supported types
+                    let _ = performance.observe(id, &entry_types, buffered);
+                }
+                PerformanceRequest::Disconnect { id } => performance.disconnect(id),
+            }
+        }
+        let batches = self.performance.take_observer_records();
+        for (id, entries) in &batches {
+            performance_api::deliver(&mut self.runtime, *id, entries)?;
+        }
+        Ok(batches.len())
+    }
+    
+    /// Record a change a script is about to make to `document` for the page's MutationObservers
+    pub fn record_dom_mutation(&mut self, document: &Document, mutation: &DomMutation) -> Result<(), JsError> {
+        // Observers the script set up see all the changes it made
+        self.update_mutation_observers()?;
+        if let Some((target, ancestors, record)) = self.mutation_nodes.dom_mutation(document, mutation) {
+            self.observers.record_subtree_mutation(target, &ancestors, record);
+        }
+        Ok(())
+    }
+    
+    /// Record the nodes parsed after the page's script as the parser's insertions
+    pub fn record_parser_insertions(&mut self, document: &Document) -> Result<(), JsError> {
+        self.update_mutation_observers()?;
+        let Some(script) = mutation_observer_api::script_node(document) else {
+            return Ok(());
+        };
+        for (target, ancestors, record) in self.mutation_nodes.parser_insertions(document, script) {
+            self.observers.record_subtree_mutation(target, &ancestors, record);
+        }
+        Ok(())
+    }
+    
+    /// Call back the page's MutationObservers with the records made since their last callback
+    ///
+    /// Returns how many observers were called back.
+    pub fn deliver_mutation_records(&mut self) -> Result<usize, JsError> {
+        self.update_mutation_observers()?;
+        let batches = self.observers.take_pending_mutations();
+        for (observer, records) in &batches {
+            let Some((&id, _)) = self.mutation_observer_ids.iter().find(|(_, o)| *o == observer) else {
+                continue;
+            };
+            let records: Vec<_> = records.iter().map(|record| self.mutation_nodes.record_json(record)).collect();
+            mutation_observer_api::deliver(&mut self.runtime, id, &records)?;
+        }
+        self.mutation_nodes.forget_descriptions();
+        Ok(batches.len())
+    }
+    
+    /// Make the page's `observe()` and `disconnect()` calls
+    fn update_mutation_observers(&mut self) -> Result<(), JsError> {
+        for request in mutation_observer_api::take_requests(&mut self.runtime)? {
+            match request {
+                MutationObserverRequest::Observe { id, target, options } => {
+                    let observers = &mut self.observers;
+                    let observer = *self
+                        .mutation_observer_ids
+                        .entry(id)
+                        .or_insert_with(|| observers.create_mutation_observer(|_| {}));
+                    let node = mutation_observer_api::number(target);
+                    if let Some(observer) = self.observers.get_mutation_observer(observer) {
+                        observer.observe(node, options);
+                    }
+                }
+                MutationObserverRequest::Disconnect { id } => {
+                    if let Some(observer) = self.mutation_observer_ids.remove(&id) {
+                        self.observers.remove_mutation_observer(observer);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    
+    /// Has the page asked to observe intersections since they were last worked out
+    pub fn has_pending_intersection_requests(&mut self) -> bool {
+        self.enabled && intersection_observer_api::is_pending(&mut self.runtime).unwrap_or(false)
+    }
+    
+    /// Does the page observe intersections, or has it just asked to
+    pub fn has_intersection_observers(&mut self) -> bool {
+        (self.enabled && !self.intersection_observer_ids.is_empty()) || self.has_pending_intersection_requests()
+    }
+    
+    /// Work out the page's intersections after layout and call back its
+    /// IntersectionObservers with the elements that crossed a threshold
+    ///
+    /// `rect_of` gives the border box of the element with an id, relative to
+    /// the viewport at `viewport`. Returns how many observers were called back.
+    pub fn update_intersections(
+        &mut self,
+        viewport: Rect,
+        rect_of: impl Fn(&str) -> Option<Rect>,
+    ) -> Result<usize, JsError> {
+        self.update_intersection_observers()?;
+        let targets = &self.intersection_targets;
+        let rect_of = |number: u64| targets.id(number).and_then(&rect_of);
+        let batches = self.observers.update_intersections(viewport, rect_of, self.performance.now());
+        for (observer, entries) in &batches {
+            let Some((&id, _)) = self.intersection_observer_ids.iter().find(|(_, o)| *o == observer) else {
+                continue;
+            };
+            let entries: Vec<_> = entries
+                .iter()
+                .filter_map(|entry| {
+                    let target = self.intersection_targets.id(entry.target)?;
+                    Some(intersection_observer_api::entry_json(entry, target))
+                })
+                .collect();
+            intersection_observer_api::deliver(&mut self.runtime, id, &entries)?;
+        }
+        Ok(batches.len())
+    }
+    
+    /// Make the page's `observe()`, `unobserve()` and `disconnect()` calls
+    fn update_intersection_observers(&mut self) -> Result<(), JsError> {
+        for request in intersection_observer_api::take_requests(&mut self.runtime)? {
+            match request {
+                IntersectionObserverRequest::Observe { id, target, root, root_margin, thresholds } => {
+                    let observers = &mut self.observers;
+                    let observer = *self
+                        .intersection_observer_ids
+                        .entry(id)
+                        .or_insert_with(|| observers.create_intersection_observer(|_| {}));
+                    let target = self.intersection_targets.number(&target);
+                    let root = root.map(|root| self.intersection_targets.number(&root));
+                    if let Some(observer) = self.observers.get_intersection_observer(observer) {
+                        observer.set_root(root);
+                        observer.set_root_margin_lengths(root_margin);
+                        observer.set_thresholds(thresholds);
+                        observer.observe(target);
+                    }
+                }
+                IntersectionObserverRequest::Unobserve { id, target } => {
+                    let target = self.intersection_targets.number(&target);
+                    let observer = self.intersection_observer_ids.get(&id).copied();
+                    if let Some(observer) = observer.and_then(|o| self.observers.get_intersection_observer(o)) {
+                        observer.unobserve(target);
+                    }
+                }
+                IntersectionObserverRequest::Disconnect { id } => {
+                    if let Some(observer) = self.intersection_observer_ids.remove(&id) {
+                        self.observers.remove_intersection_observer(observer);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    
+    /// Are `requestAnimationFrame` callbacks waiting for the next frame
+    pub fn has_animation_frames(&mut self) -> bool {
+        self.enabled && animation_frame_api::is_pending(&mut self.runtime).unwrap_or(false)
+    }
+    
+    /// Run the page's `requestAnimationFrame` callbacks for a frame begun at `frame_time`
+    ///
+    /// Returns how many callbacks ran.
+    pub fn run_animation_frames(&mut self, frame_time: Instant) -> Result<usize, JsError> {
+        if !self.enabled {
+            return Err(JsError::ExecutionDisabled);
+        }
+        let start_time = self.performance.now();
+        let timestamp = self.performance.timestamp_at(frame_time);
+        let ran = animation_frame_api::run(&mut self.runtime, timestamp);
+        self.performance.record_task(start_time, self.performance.now() - start_time, TaskAttribution::Script);
+        ran
+    }
+    
+    /// Are `requestIdleCallback` callbacks waiting for idle time
+    pub fn has_idle_callbacks(&mut self) -> bool {
+        self.enabled && idle_callback_api::is_pending(&mut self.runtime).unwrap_or(false)
+    }
+    
+    /// Run the page's `requestIdleCallback` callbacks in an idle period ending at `deadline`
+    ///
+    /// Returns how many callbacks ran.
+    pub fn run_idle_callbacks(&mut self, deadline: Instant) -> Result<usize, JsError> {
+        if !self.enabled {
+            return Err(JsError::ExecutionDisabled);
+        }
+        let start_time = self.performance.now();
+        let deadline = self.performance.timestamp_at(deadline);
+        let ran = idle_callback_api::run(&mut self.runtime, deadline);
+        self.performance.record_task(start_time, self.performance.now() - start_time, TaskAttribution::Script);
+        ran
+    }
+    
+    /// Set the user preference media features `matchMedia` answers from
+    ///
+    /// Media query lists whose answer changes fire `change`.
+    pub fn set_media_features(&mut self, features: &MediaFeatures) -> Result<(), JsError> {
+        media_query_api::set_features(&mut self.runtime, features)
+    }
+    
+    /// Send the state of the page's web fonts to `document.fonts`
+    ///
+    /// Load events fire and promises settle for faces whose state changed.
+    pub fn set_font_faces(&mut self, fonts: &FontFaceSet) -> Result<(), JsError> {
+        font_api::set_faces(&mut self.runtime, fonts)
+    }
+    
+    /// Tell the page about its <audio> and <video> elements
+    ///
+    /// `backend` answers `canPlayType`.
+    pub fn set_media_elements(
+        &mut self,
+        media: &MediaElements,
+        backend: &dyn MediaBackend,
+        now: Instant,
+    ) -> Result<(), JsError> {
+        media_api::set_elements(&mut self.runtime, media, backend, now)
+    }
+    
+    /// Send a media element's state, firing the events its player fired
+    pub fn update_media_element(
+        &mut self,
+        id: &str,
+        state: &MediaState,
+        events: &[MediaEvent],
+    ) -> Result<(), JsError> {
+        media_api::update(&mut self.runtime, id, state, events)
+    }
+    
+    /// Drain `document.execCommand` calls made by scripts
+    pub fn take_edit_commands(&mut self) -> Result<Vec<EditCommand>, JsError> {
+        editing_api::take_commands(&mut self.runtime)
+    }
+    
+    /// Fire `input` at the editing host with `id` after an edit, or at the
+    /// document for hosts without one
+    pub fn dispatch_input_event(&mut self, id: Option<&str>, event: &InputEvent) -> Result<(), JsError> {
+        let init = serde_json::json!({
+            "type": "input",
+            "inputType": event.input_type,
+            "data": event.data,
+        });
+        element_api::dispatch(&mut self.runtime, id, init)
+    }
+    
+    /// Drain `play()`, `pause()` and other media element calls made by scripts
+    pub fn take_media_requests(&mut self) -> Result<Vec<MediaRequest>, JsError> {
+        media_api::take_requests(&mut self.runtime)
+    }
+    
+    /// Drain permission requests scripts are waiting on
+    pub fn take_permission_requests(&mut self) -> Result<Vec<PermissionRequest>, JsError> {
+        permissions_api::take_requests(&mut self.runtime)
+    }
+    
+    /// Settle a permission request with the state the user chose
+    pub fn settle_permission_request(&mut self, id: u32, state: PermissionState) -> Result<(), JsError> {
+        permissions_api::settle(&mut self.runtime, id, state)
+    }
+    
+    /// Send the page's permission states for `navigator.permissions.query`
+    pub fn set_permission_states(
+        &mut self,
+        states: &HashMap<PermissionName, PermissionState>,
+    ) -> Result<(), JsError> {
+        permissions_api::set_states(&mut self.runtime, states)
+    }
+    
+    /// Give the context an extension's `chrome` namespace
+    ///
+    /// A fresh context is a world of its own: scripts run in it share the
+    /// page's document but none of the page's globals.
+    pub fn install_extension_api(
+        &mut self,
+        extension_id: &str,
+        manifest: &serde_json::Value,
+        world: ExtensionWorld,
+    ) -> Result<(), JsError> {
+        extension_api::install(&mut self.runtime, extension_id, manifest, world)
+    }
+    
+    /// Drain the extension API calls scripts made
+    pub fn take_extension_calls(&mut self) -> Result<Vec<ExtensionCall>, JsError> {
+        extension_api::take_calls(&mut self.runtime)
+    }
+    
+    /// Settle an extension API call with its result or an error message
+    pub fn settle_extension_call(&mut self, id: u32, result: Result<&serde_json::Value, &str>) -> Result<(), JsError> {
+        extension_api::settle(&mut self.runtime, id, result)
+    }
+    
+    /// Hand a message to the extension's `runtime.onMessage` listeners
+    ///
+    /// Their answer comes out of `take_extension_calls` as a
+    /// `runtime.reply` call carrying `reply`.
+    pub fn dispatch_extension_message(
+        &mut self,
+        message: &serde_json::Value,
+        sender: &serde_json::Value,
+        reply: u32,
+    ) -> Result<(), JsError> {
+        extension_api::dispatch_message(&mut self.runtime, message, sender, reply)
+    }
+    
+    /// Run a `webRequest.onBeforeRequest` listener, returning whether it cancels the request
+    pub fn dispatch_web_request(&mut self, listener: u32, details: &serde_json::Value) -> Result<bool, JsError> {
+        extension_api::dispatch_web_request(&mut self.runtime, listener, details)
+    }
+    
     /// Enable or disable JavaScript execution
     pub fn set_enabled(&mut self, enabled: bool) {
         self.enabled = enabled;
@@ -114,4 +707,108 @@ mod tests {
         let result = ctx.execute("1 + 1");
         assert!(result.is_err());
     }
+    
+    #[test]
+    fn test_broadcast_between_contexts() {
+        let bus = MessageBus::new();
+        let url = url::Url::parse("https://example.com/").unwrap();
+        let (mut first, mut second) = (JsContext::new(), JsContext::new());
+        let first_doc = bus.register_document(&url, 1, None);
+        let second_doc = bus.register_document(&url, 2, None);
+        
+        second.execute("var got = null; new BroadcastChannel('sync').onmessage = function (e) { got = e.data.count; };").unwrap();
+        second.send_messages(&bus, second_doc).unwrap();
+        first.execute("new BroadcastChannel('sync').postMessage({ count: 3 })").unwrap();
+        assert_eq!(first.send_messages(&bus, first_doc).unwrap(), 2);
+        
+        assert_eq!(first.receive_messages(&bus, first_doc).unwrap(), 0);
+        assert_eq!(second.receive_messages(&bus, second_doc).unwrap(), 1);
+        assert_eq!(second.execute("got").unwrap(), JsValue::Number(3.0));
+    }
+    
+    #[test]
+    fn test_performance_observer_batches() {
+        let mut ctx = JsContext::new();
+        ctx.performance_mut().add_paint("first-paint");
+        ctx.execute(
+            "var batches = [];
+             new PerformanceObserver(function (list) {
+                 batches.push(list.getEntries().map(function (e) { return e.name; }).join(','));
+             }).observe({ entryTypes: ['paint', 'mark', 'measure'], buffered: true });
+             performance.mark('start');
+             performance.mark('end');",
+        )
+        .unwrap();
+        assert_eq!(ctx.deliver_performance_entries().unwrap(), 1);
+        assert_eq!(ctx.execute("batches.join('|')").unwrap(), JsValue::String("first-paint,start,end".to_string()));
+        
+        ctx.execute("performance.measure('total', 'start', 'end')").unwrap();
+        assert_eq!(ctx.deliver_performance_entries().unwrap(), 1);
+        assert_eq!(ctx.performance().get_entries_by_name("total").len(), 1);
+        assert_eq!(ctx.execute("batches.length").unwrap(), JsValue::Number(2.0));
+        assert_eq!(ctx.deliver_performance_entries().unwrap(), 0);
+    }
+    
+    #[test]
+    fn test_mutation_observer_delivery() {
+        let mut document = crate::dom::Document::new(crate::html::HtmlParser::parse(
+            "<html><body><ul id=\"list\"><li id=\"one\" class=\"a\">1</li><li id=\"two\">2</li></ul></body></html>",
+        ));
+        let mut ctx = JsContext::new();
+        ctx.set_element_ids(&document).unwrap();
+        ctx.execute(
+            "var seen = [];
+             new MutationObserver(function (records) {
+                 seen.push(records.map(function (r) { return r.type + ':' + (r.oldValue || r.target.id); }).join(','));
+             }).observe(document.getElementById('list'), { subtree: true, childList: true, attributeOldValue: true });
+             document.getElementById('one').setAttribute('class', 'b');
+             document.getElementById('two').remove();",
+        )
+        .unwrap();
+        for mutation in ctx.take_dom_mutations().unwrap() {
+            ctx.record_dom_mutation(&document, &mutation).unwrap();
+            mutation.apply(&mut document).unwrap();
+        }
+        ctx.set_element_ids(&document).unwrap();
+        assert_eq!(ctx.execute("seen.length").unwrap(), JsValue::Number(0.0));
+        assert_eq!(ctx.deliver_mutation_records().unwrap(), 1);
+        assert_eq!(ctx.execute("seen.join('|')").unwrap(), JsValue::String("attributes:a,childList:list".to_string()));
+        assert_eq!(ctx.deliver_mutation_records().unwrap(), 0);
+    }
+    
+    #[test]
+    fn test_intersection_observer_lazy_load() {
+        let document =
+            crate::dom::Document::new(crate::html::HtmlParser::parse("<html><body><img id=\"late\"></body></html>"));
+        let mut ctx = JsContext::new();
+        ctx.set_element_ids(&document).unwrap();
+        assert!(!ctx.has_intersection_observers());
+        ctx.execute(
+            "var observer = new IntersectionObserver(function (entries) {
+                 entries.forEach(function (e) {
+                     if (e.isIntersecting) {
+                         e.target.setAttribute('src', 'photo.png');
+                         observer.unobserve(e.target);
+                     }
+                 });
+             }, { rootMargin: '50px' });
+             observer.observe(document.getElementById('late'));",
+        )
+        .unwrap();
+        assert!(ctx.has_pending_intersection_requests());
+        let viewport = Rect::new(0.0, 0.0, 800.0, 600.0);
+        let below = |y: f32| move |id: &str| (id == "late").then(|| Rect::new(0.0, y, 100.0, 100.0));
+        assert_eq!(ctx.update_intersections(viewport, below(900.0)).unwrap(), 1);
+        assert!(!ctx.has_pending_intersection_requests() && ctx.has_intersection_observers());
+        assert!(ctx.take_dom_mutations().unwrap().is_empty());
+        
+        // Scrolled to within the root margin
+        assert_eq!(ctx.update_intersections(viewport, below(640.0)).unwrap(), 1);
+        assert_eq!(ctx.take_dom_mutations().unwrap(), vec![DomMutation::SetAttribute {
+            node: document.get_element_by_id("late").unwrap(),
+            name: "src".to_string(),
+            value: "photo.png".to_string(),
+        }]);
+        assert_eq!(ctx.update_intersections(viewport, below(0.0)).unwrap(), 0);
+    }
 }
@@ -0,0 +1,470 @@
+// MutationObserver binding
+//
+// Observers are kept by the host's `ObserverManager`, which sees every
+// change made to the document: nodes the parser inserts after the page's
+// script, and the attribute changes and removals scripts queue. Scripts can
+// observe the document and the elements `getElementById` hands out, which
+// are known to the host by their `NodeId`. Subscriptions a script
+// makes apply to all the changes it queued; the records reach each observer
+// in one batch from a microtask once the host delivers them.
+
+use super::element_api::DomMutation;
+use super::runtime::{JsError, JsRuntime, JsValue};
+use crate::dom::{Document, NodeId, NodeType};
+use crate::observers::{MutationObserverInit, MutationRecord, MutationType};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// Script installed into every context to provide `MutationObserver`
+const MUTATION_OBSERVER_SHIM: &str = r##"
+(function (global) {
+    var queue = [];
+    var observers = {};
+    var nextObserver = 1;
+
+    function targetNode(target) {
+        if (target === global.document) {
+            return null;
+        }
+        if (!target || typeof target.__node !== "number") {
+            throw new TypeError("MutationObserver: only the document and elements with an id can be observed");
+        }
+        return target.__node;
+    }
+
+    function node(description) {
+        if (description === null) {
+            return null;
+        }
+        if (description.nodeType === 9) {
+            return global.document;
+        }
+        // Nodes no longer in the document are described, not looked up
+        var element = description.id ? global.document.getElementById(description.id) : null;
+        return element || description;
+    }
+
+    function MutationRecord(raw) {
+        this.type = raw.type;
+        this.target = node(raw.target);
+        this.addedNodes = raw.addedNodes.map(node);
+        this.removedNodes = raw.removedNodes.map(node);
+        this.previousSibling = node(raw.previousSibling);
+        this.nextSibling = node(raw.nextSibling);
+        this.attributeName = raw.attributeName;
+        this.attributeNamespace = null;
+        this.oldValue = raw.oldValue;
+    }
+
+    function MutationObserver(callback) {
+        if (typeof callback !== "function") {
+            throw new TypeError("MutationObserver requires a callback function");
+        }
+        this._id = nextObserver++;
+        this._callback = callback;
+        this._records = [];
+        this._scheduled = false;
+    }
+    MutationObserver.prototype.observe = function (target, options) {
+        options = options || {};
+        var attributes = options.attributes;
+        var characterData = options.characterData;
+        var filter = options.attributeFilter;
+        if (attributes === undefined && (options.attributeOldValue !== undefined || filter !== undefined)) {
+            attributes = true;
+        }
+        if (characterData === undefined && options.characterDataOldValue !== undefined) {
+            characterData = true;
+        }
+        if (!options.childList && !attributes && !characterData) {
+            throw new TypeError("MutationObserver: one of childList, attributes or characterData must be true");
+        }
+        if (!attributes && (options.attributeOldValue || filter !== undefined)) {
+            throw new TypeError("MutationObserver: attributeOldValue and attributeFilter need attributes");
+        }
+        if (!characterData && options.characterDataOldValue) {
+            throw new TypeError("MutationObserver: characterDataOldValue needs characterData");
+        }
+        var node = targetNode(target);
+        observers[this._id] = this;
+        queue.push({
+            kind: "observe",
+            id: this._id,
+            target: node,
+            options: {
+                childList: !!options.childList,
+                attributes: !!attributes,
+                characterData: !!characterData,
+                subtree: !!options.subtree,
+                attributeOldValue: !!options.attributeOldValue,
+                characterDataOldValue: !!options.characterDataOldValue,
+                attributeFilter: filter === undefined ? null : Array.prototype.map.call(filter, String)
+            }
+        });
+    };
+    MutationObserver.prototype.disconnect = function () {
+        delete observers[this._id];
+        this._records = [];
+        queue.push({ kind: "disconnect", id: this._id });
+    };
+    MutationObserver.prototype.takeRecords = function () {
+        var taken = this._records;
+        this._records = [];
+        return taken;
+    };
+    global.MutationObserver = MutationObserver;
+    global.MutationRecord = MutationRecord;
+
+    global.__mutationObserverDeliver = function (id, records) {
+        var observer = observers[id];
+        if (!observer) {
+            return;
+        }
+        observer._records = observer._records.concat(records.map(function (raw) {
+            return new MutationRecord(raw);
+        }));
+        if (observer._scheduled) {
+            return;
+        }
+        observer._scheduled = true;
+        Promise.resolve().then(function () {
+            observer._scheduled = false;
+            var taken = observer.takeRecords();
+            if (taken.length > 0 && observers[id] === observer) {
+                observer._callback.call(observer, taken, observer);
+            }
+        });
+    };
+
+    global.__mutationObserverTake = function () {
+        var taken = queue;
+        queue = [];
+        return JSON.stringify(taken);
+    };
+})(globalThis);
+"##;
+
+/// Number of the document among observed nodes
+const DOCUMENT: u64 = 0;
+
+/// A subscription change made by a script
+#[derive(Debug, Clone)]
+pub(super) enum MutationObserverRequest {
+    /// `observer.observe(target, options)`; the target is an element's node, or `None` for `document`
+    Observe { id: u64, target: Option<NodeId>, options: MutationObserverInit },
+    /// `observer.disconnect()`
+    Disconnect { id: u64 },
+}
+
+#[derive(Deserialize)]
+struct RawRequest {
+    kind: String,
+    id: u64,
+    #[serde(default)]
+    target: Option<NodeId>,
+    #[serde(default)]
+    options: Option<RawOptions>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawOptions {
+    child_list: bool,
+    attributes: bool,
+    character_data: bool,
+    subtree: bool,
+    attribute_old_value: bool,
+    character_data_old_value: bool,
+    attribute_filter: Option<Vec<String>>,
+}
+
+impl From<RawOptions> for MutationObserverInit {
+    fn from(raw: RawOptions) -> Self {
+        Self {
+            child_list: raw.child_list,
+            attributes: raw.attributes,
+            character_data: raw.character_data,
+            subtree: raw.subtree,
+            attribute_old_value: raw.attribute_old_value,
+            character_data_old_value: raw.character_data_old_value,
+            attribute_filter: raw.attribute_filter,
+        }
+    }
+}
+
+/// A mutation ready to record: its target, the target's observable
+/// ancestors (nearest first) and the record
+pub(super) type NodeMutation = (u64, Vec<u64>, MutationRecord);
+
+/// Number of a node as the observers know it: the document, or an element's `NodeId`
+pub(super) fn number(node: Option<NodeId>) -> u64 {
+    node.map_or(DOCUMENT, |node| node.to_u64() + 1)
+}
+
+/// Describes the nodes mutation records refer to
+///
+/// Nodes are numbered by their `NodeId`, which stays the same as the
+/// document changes. How a node looked is kept from when a record named it
+/// until the record is delivered, since a removed node may change again.
+#[derive(Debug, Default)]
+pub(super) struct MutationNodes {
+    /// How scripts see each node
+    descriptions: HashMap<u64, Value>,
+}
+
+impl MutationNodes {
+    pub(super) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number and describe a node
+    fn node(&mut self, document: &Document, node: NodeId) -> Option<u64> {
+        let description = match document.node_type(node)? {
+            NodeType::Element(data) => {
+                let name = data.tag_name.to_uppercase();
+                json!({"nodeType": 1, "nodeName": name, "id": data.id().unwrap_or_default()})
+            }
+            NodeType::Text(text) => json!({"nodeType": 3, "nodeName": "#text", "data": text}),
+            NodeType::Comment(text) => json!({"nodeType": 8, "nodeName": "#comment", "data": text}),
+        };
+        let number = number(Some(node));
+        self.descriptions.insert(number, description);
+        Some(number)
+    }
+
+    /// The parent of a node: the document for the root element
+    fn parent(&mut self, document: &Document, node: NodeId) -> Option<u64> {
+        match document.parent(node) {
+            Some(parent) => self.node(document, parent),
+            None => Some(DOCUMENT),
+        }
+    }
+
+    /// Ancestors of a node that scripts can observe, nearest first
+    fn ancestors(&mut self, document: &Document, node: NodeId) -> Vec<u64> {
+        let has_id = |ancestor: &NodeId| document.element_data(*ancestor).and_then(|e| e.id()).is_some();
+        let observable: Vec<NodeId> = document.ancestors(node).filter(has_id).collect();
+        let mut ancestors: Vec<u64> =
+            observable.into_iter().filter_map(|ancestor| self.node(document, ancestor)).collect();
+        ancestors.push(DOCUMENT);
+        ancestors
+    }
+
+    /// The record for a change a script is about to make to `document`
+    pub(super) fn dom_mutation(&mut self, document: &Document, mutation: &DomMutation) -> Option<NodeMutation> {
+        let node = mutation.node();
+        if !document.is_connected(node) {
+            return None;
+        }
+        let element = document.element_data(node)?;
+        match mutation {
+            DomMutation::SetAttribute { name, .. } | DomMutation::RemoveAttribute { name, .. } => {
+                let old_value = element.get_attribute(name).map(str::to_string);
+                let target = self.node(document, node)?;
+                let record = record(MutationType::Attributes, target, Some(name.clone()), old_value);
+                Some((target, self.ancestors(document, node), record))
+            }
+            DomMutation::Remove { .. } => {
+                // The root element cannot be removed
+                let parent = document.parent(node)?;
+                let target = self.node(document, parent)?;
+                let mut record = record(MutationType::ChildList, target, None, None);
+                record.removed_nodes = vec![self.node(document, node)?];
+                record.previous_sibling = document.previous_sibling(node).and_then(|n| self.node(document, n));
+                record.next_sibling = document.next_sibling(node).and_then(|n| self.node(document, n));
+                Some((target, self.ancestors(document, parent), record))
+            }
+        }
+    }
+
+    /// Records for the nodes the parser inserts after `script`
+    ///
+    /// The page's script runs once the whole document is parsed, so the
+    /// nodes after it are reported as the parser would have appended them
+    /// had it stopped for the script: one at a time, in document order.
+    pub(super) fn parser_insertions(&mut self, document: &Document, script: NodeId) -> Vec<NodeMutation> {
+        let nodes = document.descendants(document.root());
+        let Some(position) = nodes.iter().position(|&node| node == script) else {
+            return Vec::new();
+        };
+        // The script's own text was parsed before it ran
+        let after = position + document.descendants(script).len();
+        nodes[after..]
+            .iter()
+            .filter_map(|&node| {
+                let parent = document.parent(node)?;
+                let target = self.parent(document, node)?;
+                let mut record = record(MutationType::ChildList, target, None, None);
+                record.added_nodes = vec![self.node(document, node)?];
+                record.previous_sibling = document.previous_sibling(node).and_then(|n| self.node(document, n));
+                Some((target, self.ancestors(document, parent), record))
+            })
+            .collect()
+    }
+
+    /// A record as scripts see it
+    pub(super) fn record_json(&self, record: &MutationRecord) -> Value {
+        let describe = |node: &u64| match *node {
+            DOCUMENT => json!({"nodeType": 9, "nodeName": "#document"}),
+            node => self.descriptions.get(&node).cloned().unwrap_or(Value::Null),
+        };
+        let describe_all = |nodes: &[u64]| nodes.iter().map(describe).collect::<Vec<_>>();
+        let kind = match record.mutation_type {
+            MutationType::ChildList => "childList",
+            MutationType::Attributes => "attributes",
+            MutationType::CharacterData => "characterData",
+        };
+        json!({
+            "type": kind,
+            "target": describe(&record.target),
+            "addedNodes": describe_all(&record.added_nodes),
+            "removedNodes": describe_all(&record.removed_nodes),
+            "previousSibling": record.previous_sibling.as_ref().map(describe),
+            "nextSibling": record.next_sibling.as_ref().map(describe),
+            "attributeName": record.attribute_name,
+            "oldValue": record.old_value,
+        })
+    }
+
+    /// Forget how nodes looked, once the records naming them are delivered
+    pub(super) fn forget_descriptions(&mut self) {
+        self.descriptions.clear();
+    }
+}
+
+fn record(mutation_type: MutationType, target: u64, attribute_name: Option<String>, old_value: Option<String>)
+    -> MutationRecord {
+    MutationRecord {
+        mutation_type,
+        target,
+        added_nodes: Vec::new(),
+        removed_nodes: Vec::new(),
+        previous_sibling: None,
+        next_sibling: None,
+        attribute_name,
+        old_value,
+    }
+}
+
+/// The first `script` element, the one the page runs
+pub(super) fn script_node(document: &Document) -> Option<NodeId> {
+    document
+        .descendants(document.root())
+        .into_iter()
+        .find(|&node| document.element_data(node).is_some_and(|e| e.tag_name == "script"))
+}
+
+/// Install the MutationObserver shim into a runtime
+pub(super) fn install(runtime: &mut JsRuntime) -> Result<(), JsError> {
+    runtime.execute(MUTATION_OBSERVER_SHIM).map(|_| ())
+}
+
+/// Drain subscription changes queued by scripts
+pub(super) fn take_requests(runtime: &mut JsRuntime) -> Result<Vec<MutationObserverRequest>, JsError> {
+    let json = match runtime.execute("__mutationObserverTake()")? {
+        JsValue::String(json) => json,
+        other => return Err(JsError::TypeError(format!("unexpected MutationObserver queue: {:?}", other))),
+    };
+
+    let raw: Vec<RawRequest> = serde_json::from_str(&json).map_err(|e| JsError::RuntimeError(e.to_string()))?;
+
+    Ok(raw
+        .into_iter()
+        .filter_map(|r| match r.kind.as_str() {
+            "observe" => {
+                Some(MutationObserverRequest::Observe { id: r.id, target: r.target, options: r.options?.into() })
+            }
+            "disconnect" => Some(MutationObserverRequest::Disconnect { id: r.id }),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Queue a batch of records for an observer's next callback
+///
+/// The callback runs from the microtask queue once this script finishes.
+pub(super) fn deliver(runtime: &mut JsRuntime, id: u64, records: &[Value]) -> Result<(), JsError> {
+    runtime.execute(&format!("__mutationObserverDeliver({}, {})", id, json!(records))).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html::HtmlParser;
+
+    #[test]
+    fn test_observe_options() {
+        let mut runtime = JsRuntime::new();
+        install(&mut runtime).unwrap();
+        let document =
+            "document = { getElementById: function (id) { return id === 'a' ? { id: 'a', __node: 4 } : null; } }";
+        runtime.execute(document).unwrap();
+        runtime
+            .execute(
+                "var observer = new MutationObserver(function () {});
+                 var options = { attributeFilter: ['class'], attributeOldValue: true };
+                 observer.observe(document.getElementById('a'), options);
+                 observer.observe(document, { childList: true, subtree: true });
+                 observer.disconnect();",
+            )
+            .unwrap();
+        let requests = take_requests(&mut runtime).unwrap();
+        assert_eq!(requests.len(), 3);
+        let MutationObserverRequest::Observe { id, target, options } = &requests[0] else {
+            panic!("expected observe, got {:?}", requests[0]);
+        };
+        assert_eq!((*id, target.map(NodeId::index)), (1, Some(4)));
+        assert!(options.attributes && options.attribute_old_value && !options.child_list);
+        assert_eq!(options.attribute_filter, Some(vec!["class".to_string()]));
+        assert!(matches!(&requests[1], MutationObserverRequest::Observe { target: None, .. }));
+        assert!(matches!(requests[2], MutationObserverRequest::Disconnect { id: 1 }));
+
+        let mut error =
+            |code: &str| runtime.execute(&format!("try {{ {}; 'ok' }} catch (e) {{ e.name }}", code)).unwrap();
+        let type_error = JsValue::String("TypeError".to_string());
+        assert_eq!(error("observer.observe(document, {})"), type_error);
+        assert_eq!(error("observer.observe(document, { attributes: false, attributeOldValue: true })"), type_error);
+        assert_eq!(error("observer.observe({ id: 'gone' }, { childList: true })"), type_error);
+        assert!(take_requests(&mut runtime).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_records_for_changes() {
+        let document = Document::new(HtmlParser::parse(
+            "<html><body id=\"page\"><p id=\"a\" class=\"x\">A</p><script>s()</script><p id=\"b\">B</p></body></html>",
+        ));
+        let element = |id| document.get_element_by_id(id).unwrap();
+        let mut nodes = MutationNodes::new();
+        let page = number(Some(element("page")));
+
+        let set = DomMutation::SetAttribute { node: element("a"), name: "class".to_string(), value: "y".to_string() };
+        let (target, ancestors, record) = nodes.dom_mutation(&document, &set).unwrap();
+        assert_eq!(target, number(Some(element("a"))));
+        assert_eq!(ancestors, [page, DOCUMENT]);
+        assert_eq!((record.attribute_name.as_deref(), record.old_value.as_deref()), (Some("class"), Some("x")));
+
+        let remove = DomMutation::Remove { node: element("b") };
+        let (target, ancestors, record) = nodes.dom_mutation(&document, &remove).unwrap();
+        assert_eq!((target, ancestors), (page, vec![DOCUMENT]));
+        assert_eq!(record.removed_nodes, [number(Some(element("b")))]);
+        assert!(record.previous_sibling.is_some() && record.next_sibling.is_none());
+        let json = nodes.record_json(&record);
+        assert_eq!(json["type"], "childList");
+        assert_eq!(json["removedNodes"][0]["nodeName"], "P");
+        assert_eq!(json["previousSibling"]["nodeName"], "SCRIPT");
+
+        // The second paragraph and its text arrive after the script
+        let script = script_node(&document).unwrap();
+        let inserted = nodes.parser_insertions(&document, script);
+        let added: Vec<_> =
+            inserted.iter().map(|(_, _, record)| nodes.record_json(record)["addedNodes"][0].clone()).collect();
+        assert_eq!(added.len(), 2);
+        assert_eq!((added[0]["id"].as_str(), added[1]["data"].as_str()), (Some("b"), Some("B")));
+        assert_eq!(inserted[1].0, number(Some(element("b"))));
+        assert_eq!(inserted[1].1, [page, DOCUMENT]);
+        assert_eq!(nodes.record_json(&inserted[0].2)["target"]["id"], "page");
+        nodes.forget_descriptions();
+        assert!(nodes.descriptions.is_empty());
+    }
+}
@@ -0,0 +1,374 @@
+// performance.mark()/measure() and PerformanceObserver bindings
+//
+// The page's timeline lives in the host's `Performance`: marks and
+// measures made by scripts and observer subscriptions are queued for the
+// host, which records them and hands back each observer's batch of new
+// entries. Batches wait in the page for a microtask, so entries recorded
+// in the same task reach the observer in a single callback.
+
+use super::runtime::{JsError, JsRuntime, JsValue};
+use crate::observers::ObserverId;
+use crate::performance::{DOMHighResTimeStamp, PerformanceEntry, SUPPORTED_ENTRY_TYPES};
+use serde::Deserialize;
+use serde_json::json;
+
+/// Script installed into every context to provide user timing and observers
+const PERFORMANCE_SHIM: &str = r#"
+(function (global) {
+    var queue = [];
+    var origin = Date.now();
+    var marks = {};
+    var observers = {};
+    var nextObserver = 1;
+    var supported = __SUPPORTED__;
+
+    function now() {
+        return Date.now() - origin;
+    }
+
+    function entry(name, entryType, startTime, duration) {
+        return { name: name, entryType: entryType, startTime: startTime, duration: duration };
+    }
+
+    function markTime(mark) {
+        if (typeof mark === "number") {
+            return mark;
+        }
+        if (!Object.prototype.hasOwnProperty.call(marks, mark)) {
+            var error = new Error("The mark '" + mark + "' does not exist.");
+            error.name = "SyntaxError";
+            throw error;
+        }
+        return marks[mark];
+    }
+
+    var performance = global.performance || {};
+    global.performance = performance;
+    performance.timeOrigin = origin;
+    performance.now = now;
+    performance.mark = function (name, options) {
+        name = String(name);
+        var startTime = options && options.startTime !== undefined ? Number(options.startTime) : now();
+        marks[name] = startTime;
+        queue.push({ kind: "mark", name: name, startTime: startTime });
+        return entry(name, "mark", startTime, 0);
+    };
+    performance.measure = function (name, start, end) {
+        name = String(name);
+        var startTime = 0;
+        var endTime;
+        if (start && typeof start === "object") {
+            if (start.start !== undefined) {
+                startTime = markTime(start.start);
+            }
+            if (start.end !== undefined) {
+                endTime = markTime(start.end);
+            } else if (start.duration !== undefined) {
+                endTime = startTime + Number(start.duration);
+            }
+        } else {
+            if (start !== undefined) {
+                startTime = markTime(start);
+            }
+            if (end !== undefined) {
+                endTime = markTime(end);
+            }
+        }
+        if (endTime === undefined) {
+            endTime = now();
+        }
+        queue.push({ kind: "measure", name: name, startTime: startTime, duration: endTime - startTime });
+        return entry(name, "measure", startTime, endTime - startTime);
+    };
+    performance.clearMarks = function (name) {
+        if (name === undefined) {
+            marks = {};
+        } else {
+            delete marks[String(name)];
+        }
+        queue.push({ kind: "clearMarks", name: name === undefined ? null : String(name) });
+    };
+    performance.clearMeasures = function (name) {
+        queue.push({ kind: "clearMeasures", name: name === undefined ? null : String(name) });
+    };
+
+    function PerformanceObserverEntryList(entries) {
+        this._entries = entries;
+    }
+    PerformanceObserverEntryList.prototype.getEntries = function () {
+        return this._entries.slice();
+    };
+    PerformanceObserverEntryList.prototype.getEntriesByType = function (type) {
+        return this._entries.filter(function (e) { return e.entryType === type; });
+    };
+    PerformanceObserverEntryList.prototype.getEntriesByName = function (name, type) {
+        return this._entries.filter(function (e) {
+            return e.name === name && (type === undefined || e.entryType === type);
+        });
+    };
+
+    function PerformanceObserver(callback) {
+        if (typeof callback !== "function") {
+            throw new TypeError("PerformanceObserver requires a callback function");
+        }
+        this._id = nextObserver++;
+        this._callback = callback;
+        this._records = [];
+        this._scheduled = false;
+    }
+    PerformanceObserver.supportedEntryTypes = supported.slice();
+    PerformanceObserver.prototype.observe = function (options) {
+        options = options || {};
+        var types = options.entryTypes !== undefined ? Array.prototype.map.call(options.entryTypes, String)
+            : options.type !== undefined ? [String(options.type)] : [];
+        types = types.filter(function (t) { return supported.indexOf(t) >= 0; });
+        if (types.length === 0) {
+            return;
+        }
+        observers[this._id] = this;
+        queue.push({ kind: "observe", id: this._id, types: types, buffered: Boolean(options.buffered) });
+    };
+    PerformanceObserver.prototype.disconnect = function () {
+        delete observers[this._id];
+        this._records = [];
+        queue.push({ kind: "disconnect", id: this._id });
+    };
+    PerformanceObserver.prototype.takeRecords = function () {
+        var taken = this._records;
+        this._records = [];
+        return taken;
+    };
+    global.PerformanceObserver = PerformanceObserver;
+    global.PerformanceObserverEntryList = PerformanceObserverEntryList;
+
+    global.__performanceSetOrigin = function (timeOrigin) {
+        origin = timeOrigin;
+        performance.timeOrigin = timeOrigin;
+    };
+
+    global.__performanceDeliver = function (id, entries) {
+        var observer = observers[id];
+        if (!observer) {
+            return;
+        }
+        observer._records = observer._records.concat(entries);
+        if (observer._scheduled) {
+            return;
+        }
+        observer._scheduled = true;
+        Promise.resolve().then(function () {
+            observer._scheduled = false;
+            var records = observer.takeRecords();
+            if (records.length > 0 && observers[id] === observer) {
+                observer._callback.call(observer, new PerformanceObserverEntryList(records), observer);
+            }
+        });
+    };
+
+    global.__performanceTake = function () {
+        var taken = queue;
+        queue = [];
+        return JSON.stringify(taken);
+    };
+})(globalThis);
+"#;
+
+/// A timing call or subscription made by a script
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum PerformanceRequest {
+    /// `performance.mark()`
+    Mark { name: String, start_time: DOMHighResTimeStamp },
+    /// `performance.measure()`
+    Measure { name: String, start_time: DOMHighResTimeStamp, duration: DOMHighResTimeStamp },
+    /// `performance.clearMarks()`, of every mark without a name
+    ClearMarks { name: Option<String> },
+    /// `performance.clearMeasures()`
+    ClearMeasures { name: Option<String> },
+    /// `observer.observe()`
+    Observe { id: ObserverId, entry_types: Vec<String>, buffered: bool },
+    /// `observer.disconnect()`
+    Disconnect { id: ObserverId },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawRequest {
+    kind: String,
+    #[serde(default)]
+    id: ObserverId,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    start_time: DOMHighResTimeStamp,
+    #[serde(default)]
+    duration: DOMHighResTimeStamp,
+    #[serde(default)]
+    types: Vec<String>,
+    #[serde(default)]
+    buffered: bool,
+}
+
+/// Install the performance shim into a runtime
+pub(super) fn install(runtime: &mut JsRuntime) -> Result<(), JsError> {
+    runtime.execute(&PERFORMANCE_SHIM.replace("__SUPPORTED__", &json!(SUPPORTED_ENTRY_TYPES).to_string())).map(|_| ())
+}
+
+/// Make `performance.now()` count from `time_origin`, in milliseconds since the epoch
+pub(super) fn set_time_origin(runtime: &mut JsRuntime, time_origin: f64) -> Result<(), JsError> {
+    runtime.execute(&format!("__performanceSetOrigin({})", time_origin)).map(|_| ())
+}
+
+/// Drain timing calls and subscriptions queued by scripts
+pub(super) fn take_requests(runtime: &mut JsRuntime) -> Result<Vec<PerformanceRequest>, JsError> {
+    let json = match runtime.execute("__performanceTake()")? {
+        JsValue::String(json) => json,
+        other => return Err(JsError::TypeError(format!("unexpected performance queue: {:?}", other))),
+    };
+
+    let raw: Vec<RawRequest> =
+        serde_json::from_str(&json).map_err(|e| JsError::RuntimeError(e.to_string()))?;
+
+    Ok(raw
+        .into_iter()
+        .filter_map(|r| match r.kind.as_str() {
+            "mark" => Some(PerformanceRequest::Mark { name: r.name?, start_time: r.start_time }),
+            "measure" => Some(PerformanceRequest::Measure {
+                name: r.name?,
+                start_time: r.start_time,
+                duration: r.duration,
+            }),
+            "clearMarks" => Some(PerformanceRequest::ClearMarks { name: r.name }),
+            "clearMeasures" => Some(PerformanceRequest::ClearMeasures { name: r.name }),
+            "observe" => Some(PerformanceRequest::Observe { id: r.id, entry_types: r.types, buffered: r.buffered }),
+            "disconnect" => Some(PerformanceRequest::Disconnect { id: r.id }),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Queue a batch of entries for an observer's next callback
+///
+/// The callback runs from the microtask queue once this script finishes.
+pub(super) fn deliver(runtime: &mut JsRuntime, id: ObserverId, entries: &[PerformanceEntry]) -> Result<(), JsError> {
+    let list: Vec<_> = entries.iter().map(entry_json).collect();
+    runtime.execute(&format!("__performanceDeliver({}, {})", id, json!(list))).map(|_| ())
+}
+
+/// An entry as scripts see it
+fn entry_json(entry: &PerformanceEntry) -> serde_json::Value {
+    let mut value = json!({
+        "name": entry.name(),
+        "entryType": entry.entry_type(),
+        "startTime": entry.start_time(),
+        "duration": entry.duration(),
+    });
+    let extra = match entry {
+        PerformanceEntry::Resource(resource) => json!({
+            "initiatorType": resource.initiator_type,
+            "nextHopProtocol": resource.next_hop_protocol,
+            "fetchStart": resource.fetch_start,
+            "responseStart": resource.response_start,
+            "responseEnd": resource.response_end,
+            "transferSize": resource.transfer_size,
+            "encodedBodySize": resource.encoded_body_size,
+            "decodedBodySize": resource.decoded_body_size,
+        }),
+        PerformanceEntry::Navigation(timing) => json!({
+            "type": "navigate",
+            "fetchStart": timing.fetch_start,
+            "responseStart": timing.response_start,
+            "responseEnd": timing.response_end,
+            "domInteractive": timing.dom_interactive,
+            "domContentLoadedEventEnd": timing.dom_content_loaded_event_end,
+            "domComplete": timing.dom_complete,
+            "loadEventEnd": timing.load_event_end,
+        }),
+        PerformanceEntry::LargestContentfulPaint { size, element, url, .. } => json!({
+            "size": size,
+            "element": element,
+            "url": url,
+            "renderTime": entry.start_time(),
+        }),
+        // What the task was doing goes where the container would otherwise be
+        PerformanceEntry::LongTask { attribution, .. } => json!({
+            "attribution": [{
+                "name": "unknown",
+                "entryType": "taskattribution",
+                "startTime": 0,
+                "duration": 0,
+                "containerType": "window",
+                "containerSrc": "",
+                "containerId": "",
+                "containerName": attribution.label(),
+            }],
+        }),
+        PerformanceEntry::LayoutShift { value: score, had_recent_input, .. } => json!({
+            "value": score,
+            "hadRecentInput": had_recent_input,
+        }),
+        _ => return value,
+    };
+    if let (Some(value), serde_json::Value::Object(extra)) = (value.as_object_mut(), extra) {
+        value.extend(extra);
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mark(name: &str, start_time: DOMHighResTimeStamp) -> PerformanceEntry {
+        PerformanceEntry::Mark { name: name.to_string(), start_time }
+    }
+
+    #[test]
+    fn test_timing_requests() {
+        let mut runtime = JsRuntime::new();
+        install(&mut runtime).unwrap();
+        runtime
+            .execute(
+                "new PerformanceObserver(function () {}).observe({ entryTypes: ['mark', 'frame'], buffered: true });
+                 performance.mark('a', { startTime: 10 });
+                 performance.mark('b', { startTime: 25 });
+                 performance.measure('a-b', 'a', 'b');",
+            )
+            .unwrap();
+        assert_eq!(take_requests(&mut runtime).unwrap(), [
+            PerformanceRequest::Observe { id: 1, entry_types: vec!["mark".to_string()], buffered: true },
+            PerformanceRequest::Mark { name: "a".to_string(), start_time: 10.0 },
+            PerformanceRequest::Mark { name: "b".to_string(), start_time: 25.0 },
+            PerformanceRequest::Measure { name: "a-b".to_string(), start_time: 10.0, duration: 15.0 },
+        ]);
+        assert!(take_requests(&mut runtime).unwrap().is_empty());
+
+        let missing = runtime.execute("try { performance.measure('x', 'nope'); 'ok' } catch (e) { e.name }");
+        assert_eq!(missing.unwrap(), JsValue::String("SyntaxError".to_string()));
+    }
+
+    #[test]
+    fn test_batches_called_back_from_microtasks() {
+        let mut runtime = JsRuntime::new();
+        install(&mut runtime).unwrap();
+        runtime
+            .execute(
+                "var calls = [];
+                 var observer = new PerformanceObserver(function (list, self) {
+                     calls.push(list.getEntriesByType('mark').map(function (e) { return e.name; }).join(','));
+                 });
+                 observer.observe({ type: 'mark' });",
+            )
+            .unwrap();
+        deliver(&mut runtime, 1, &[mark("a", 1.0), mark("b", 2.0)]).unwrap();
+        assert_eq!(runtime.execute("calls.join('|')").unwrap(), JsValue::String("a,b".to_string()));
+
+        // Batches delivered during a task wait for its microtasks
+        let during = runtime.execute("__performanceDeliver(1, [{ name: 'c', entryType: 'mark' }]); calls.length");
+        assert_eq!(during.unwrap(), JsValue::Number(1.0));
+        assert_eq!(runtime.execute("calls.length").unwrap(), JsValue::Number(2.0));
+
+        runtime.execute("observer.disconnect()").unwrap();
+        deliver(&mut runtime, 1, &[mark("d", 3.0)]).unwrap();
+        assert_eq!(runtime.execute("calls.length").unwrap(), JsValue::Number(2.0));
+    }
+}
@@ -0,0 +1,309 @@
+// Permissions binding: navigator.permissions.query() and the features it gates
+//
+// The host sends the page the state of each permission for its origin;
+// `query()` answers from that table and its PermissionStatus objects fire
+// `change` when the host sends a new one. Asking for a permission that is
+// not decided (`Notification.requestPermission()`, geolocation, the camera,
+// clipboard reads) is queued for the host, which prompts the user and
+// settles the request with the answer.
+
+use super::runtime::{JsError, JsRuntime, JsValue};
+use crate::permissions::{PermissionName, PermissionState};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Script installed into every context to provide the permissions API
+const PERMISSIONS_SHIM: &str = r#"
+(function (global) {
+    var states = {
+        "notifications": "prompt",
+        "geolocation": "prompt",
+        "clipboard-read": "prompt",
+        "clipboard-write": "prompt",
+        "camera": "prompt"
+    };
+    // Whether the host has sent the page's states
+    var statesSent = false;
+    var statuses = [];
+    var pending = {};
+    var queue = [];
+    var nextId = 1;
+
+    // Resolves with the state the host settles the request with
+    function request(name) {
+        if (states[name] !== "prompt") {
+            return Promise.resolve(states[name]);
+        }
+        return new Promise(function (resolve) {
+            var id = nextId++;
+            pending[id] = resolve;
+            queue.push({ id: id, name: name });
+        });
+    }
+
+    function domError(name, message) {
+        var error = new Error(message);
+        error.name = name;
+        return error;
+    }
+
+    function makeStatus(name) {
+        var listeners = [];
+        var status = {
+            name: name,
+            state: states[name],
+            onchange: null,
+            addEventListener: function (type, listener) {
+                if (type === "change" && listeners.indexOf(listener) < 0) {
+                    listeners.push(listener);
+                }
+            },
+            removeEventListener: function (type, listener) {
+                var index = listeners.indexOf(listener);
+                if (type === "change" && index >= 0) {
+                    listeners.splice(index, 1);
+                }
+            },
+            __update: function (state) {
+                if (status.state === state) {
+                    return;
+                }
+                status.state = state;
+                var event = { type: "change", target: status };
+                var handlers = listeners.slice();
+                if (typeof status.onchange === "function") {
+                    handlers.unshift(status.onchange);
+                }
+                handlers.forEach(function (handler) {
+                    try {
+                        handler.call(status, event);
+                    } catch (e) {
+                        if (global.console && console.error) {
+                            console.error(e);
+                        }
+                    }
+                });
+            }
+        };
+        statuses.push(status);
+        return status;
+    }
+
+    global.navigator = global.navigator || {};
+    global.navigator.permissions = {
+        query: function (descriptor) {
+            var name = descriptor && descriptor.name;
+            if (!Object.prototype.hasOwnProperty.call(states, name)) {
+                return Promise.reject(new TypeError(
+                    "Failed to execute 'query' on 'Permissions': '" + name + "' is not a valid permission name"));
+            }
+            return Promise.resolve(makeStatus(name));
+        }
+    };
+
+    // Notification.permission reports an undecided permission as "default"
+    function notificationPermission(state) {
+        return state === "prompt" ? "default" : state;
+    }
+
+    var Notification = function (title) {
+        if (states.notifications !== "granted") {
+            throw new TypeError("Failed to construct 'Notification': permission has not been granted");
+        }
+        this.title = String(title);
+        this.close = function () {};
+    };
+    Object.defineProperty(Notification, "permission", {
+        get: function () { return notificationPermission(states.notifications); },
+        enumerable: true,
+        configurable: true
+    });
+    Notification.requestPermission = function (callback) {
+        return request("notifications").then(function (state) {
+            var permission = notificationPermission(state);
+            if (typeof callback === "function") {
+                callback(permission);
+            }
+            return permission;
+        });
+    };
+    global.Notification = Notification;
+
+    // No location source yet: granting reports the position as unavailable
+    function locate(success, error) {
+        request("geolocation").then(function (state) {
+            if (typeof error !== "function") {
+                return;
+            }
+            var denied = state !== "granted";
+            error({
+                code: denied ? 1 : 2,
+                message: denied ? "User denied Geolocation" : "Position unavailable",
+                PERMISSION_DENIED: 1,
+                POSITION_UNAVAILABLE: 2,
+                TIMEOUT: 3
+            });
+        });
+    }
+    var nextWatch = 1;
+    global.navigator.geolocation = {
+        getCurrentPosition: function (success, error) { locate(success, error); },
+        watchPosition: function (success, error) {
+            locate(success, error);
+            return nextWatch++;
+        },
+        clearWatch: function () {}
+    };
+
+    // No capture devices yet: granting finds no camera
+    global.navigator.mediaDevices = {
+        getUserMedia: function () {
+            return request("camera").then(function (state) {
+                if (state !== "granted") {
+                    throw domError("NotAllowedError", "Permission denied");
+                }
+                throw domError("NotFoundError", "Requested device not found");
+            });
+        },
+        enumerateDevices: function () { return Promise.resolve([]); }
+    };
+
+    // Clipboard reads ask for permission before reaching the clipboard,
+    // which checks it again; until the host sends states it checks alone
+    var clipboard = global.navigator.clipboard;
+    if (clipboard && clipboard.readText) {
+        var readText = clipboard.readText;
+        clipboard.readText = function () {
+            if (!statesSent) {
+                return readText.call(clipboard);
+            }
+            return request("clipboard-read").then(function () { return readText.call(clipboard); });
+        };
+    }
+
+    global.__permissionsTake = function () {
+        var taken = queue;
+        queue = [];
+        return JSON.stringify(taken);
+    };
+
+    global.__permissionsSettle = function (id, state) {
+        var resolve = pending[id];
+        if (!resolve) {
+            return false;
+        }
+        delete pending[id];
+        resolve(state);
+        return true;
+    };
+
+    global.__permissionsSet = function (table) {
+        statesSent = true;
+        Object.keys(table).forEach(function (name) {
+            if (Object.prototype.hasOwnProperty.call(states, name)) {
+                states[name] = table[name];
+            }
+        });
+        statuses.forEach(function (status) { status.__update(states[status.name]); });
+    };
+})(globalThis);
+"#;
+
+/// A page asking for a permission that is not decided
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermissionRequest {
+    /// Identifies the request to settle
+    pub id: u32,
+    pub name: PermissionName,
+}
+
+#[derive(Deserialize)]
+struct RawRequest {
+    id: u32,
+    name: String,
+}
+
+/// Install the permissions shim into a runtime
+pub(super) fn install(runtime: &mut JsRuntime) -> Result<(), JsError> {
+    runtime.execute(PERMISSIONS_SHIM).map(|_| ())
+}
+
+/// Drain permission requests queued by scripts
+pub(super) fn take_requests(runtime: &mut JsRuntime) -> Result<Vec<PermissionRequest>, JsError> {
+    let json = match runtime.execute("__permissionsTake()")? {
+        JsValue::String(json) => json,
+        other => return Err(JsError::TypeError(format!("unexpected permission queue: {:?}", other))),
+    };
+
+    let raw: Vec<RawRequest> = serde_json::from_str(&json).map_err(|e| JsError::RuntimeError(e.to_string()))?;
+
+    Ok(raw
+        .into_iter()
+        .filter_map(|r| Some(PermissionRequest { id: r.id, name: PermissionName::from_str(&r.name)? }))
+        .collect())
+}
+
+/// Settle a request with the state the user chose
+pub(super) fn settle(runtime: &mut JsRuntime, id: u32, state: PermissionState) -> Result<(), JsError> {
+    runtime
+        .execute(&format!("__permissionsSettle({}, \"{}\")", id, state.as_str()))
+        .map(|_| ())
+}
+
+/// Send the page's permission states, firing `change` at statuses whose state changed
+pub(super) fn set_states(
+    runtime: &mut JsRuntime,
+    states: &HashMap<PermissionName, PermissionState>,
+) -> Result<(), JsError> {
+    let table: serde_json::Map<String, serde_json::Value> = states
+        .iter()
+        .map(|(name, state)| (name.as_str().to_string(), state.as_str().into()))
+        .collect();
+    runtime
+        .execute(&format!("__permissionsSet({})", serde_json::Value::Object(table)))
+        .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_request_and_change() {
+        let mut runtime = JsRuntime::new();
+        install(&mut runtime).unwrap();
+
+        runtime
+            .execute(
+                "var log = [];
+                 navigator.permissions.query({ name: 'notifications' }).then(function (status) {
+                     log.push(status.state);
+                     status.onchange = function () { log.push('change:' + this.state); };
+                 });
+                 navigator.permissions.query({ name: 'midi' }).catch(function (e) { log.push(e.name); });
+                 Notification.requestPermission().then(function (p) { log.push(p); });
+                 navigator.geolocation.getCurrentPosition(null, function (e) { log.push('geo:' + e.code); });",
+            )
+            .unwrap();
+        assert_eq!(runtime.execute("Notification.permission").unwrap(), JsValue::String("default".to_string()));
+        let requests = take_requests(&mut runtime).unwrap();
+        assert_eq!(
+            requests,
+            vec![
+                PermissionRequest { id: 1, name: PermissionName::Notifications },
+                PermissionRequest { id: 2, name: PermissionName::Geolocation },
+            ]
+        );
+
+        settle(&mut runtime, 1, PermissionState::Granted).unwrap();
+        settle(&mut runtime, 2, PermissionState::Denied).unwrap();
+        let states = HashMap::from([(PermissionName::Notifications, PermissionState::Granted)]);
+        set_states(&mut runtime, &states).unwrap();
+        assert_eq!(
+            runtime.execute("log.join(',')").unwrap(),
+            JsValue::String("prompt,TypeError,granted,geo:1,change:granted".to_string())
+        );
+        assert_eq!(runtime.execute("Notification.permission").unwrap(), JsValue::String("granted".to_string()));
+        assert!(take_requests(&mut runtime).unwrap().is_empty());
+    }
+}
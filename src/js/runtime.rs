@@ -124,7 +124,11 @@ impl JsRuntime {
         let source = Source::from_bytes(code);
         
         match self.context.eval(source) {
-            Ok(value) => Ok(JsValue::from_boa(&value, &mut self.context)),
+            Ok(value) => {
+                // Settle promise reactions queued by the script
+                self.context.run_jobs();
+                Ok(JsValue::from_boa(&value, &mut self.context))
+            }
             Err(e) => {
                 let error_string = e.to_string();
                 
@@ -0,0 +1,204 @@
+// Scrolling bindings: element.scrollIntoView(), window.scrollTo()/scrollBy()
+//
+// Elements are exposed through a minimal document.getElementById() that
+// knows which ids exist in the page and the node each names. Element
+// handles carry their node's `NodeId`, which the other bindings use to
+// refer to the element. Scroll calls are queued and applied
+// by the host after the script runs.
+
+use super::runtime::{JsError, JsRuntime, JsValue};
+use crate::dom::Document;
+use crate::window::{ScrollAlign, ScrollBehavior};
+use serde::Deserialize;
+
+/// Script installed into every context to provide the scroll APIs
+const SCROLL_SHIM: &str = r#"
+(function (global) {
+    var queue = [];
+    var ids = {};
+
+    function behaviorOf(options) {
+        return options && typeof options === "object" && options.behavior
+            ? String(options.behavior)
+            : "auto";
+    }
+
+    function makeElement(id, node) {
+        return {
+            id: id,
+            __node: node,
+            scrollIntoView: function (options) {
+                var block = options === false ? "end" : "start";
+                if (options && typeof options === "object" && options.block) {
+                    block = String(options.block);
+                }
+                queue.push({ kind: "intoView", id: id, behavior: behaviorOf(options), block: block });
+            }
+        };
+    }
+
+    global.document = global.document || {};
+    global.document.getElementById = function (id) {
+        id = String(id);
+        return Object.prototype.hasOwnProperty.call(ids, id) ? makeElement(id, ids[id]) : null;
+    };
+
+    function scrollCall(kind) {
+        return function (x, y) {
+            if (x && typeof x === "object") {
+                queue.push({ kind: kind, x: Number(x.left || 0), y: Number(x.top || 0), behavior: behaviorOf(x) });
+            } else {
+                queue.push({ kind: kind, x: Number(x || 0), y: Number(y || 0), behavior: "auto" });
+            }
+        };
+    }
+    global.scrollTo = scrollCall("to");
+    global.scroll = global.scrollTo;
+    global.scrollBy = scrollCall("by");
+
+    global.__scrollSetIds = function (list) {
+        ids = {};
+        for (var i = 0; i < list.length; i++) {
+            // The first element with an id is the one found
+            if (!Object.prototype.hasOwnProperty.call(ids, list[i][0])) {
+                ids[list[i][0]] = list[i][1];
+            }
+        }
+    };
+
+    global.__scrollTake = function () {
+        var taken = queue;
+        queue = [];
+        return JSON.stringify(taken);
+    };
+})(globalThis);
+"#;
+
+/// A scroll call made by a script
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScrollRequest {
+    /// `element.scrollIntoView()`
+    IntoView {
+        id: String,
+        behavior: ScrollBehavior,
+        block: ScrollAlign,
+    },
+    /// `window.scrollTo()`
+    To { x: f32, y: f32, behavior: ScrollBehavior },
+    /// `window.scrollBy()`
+    By { x: f32, y: f32, behavior: ScrollBehavior },
+}
+
+#[derive(Deserialize)]
+struct RawRequest {
+    kind: String,
+    id: Option<String>,
+    x: Option<f32>,
+    y: Option<f32>,
+    behavior: String,
+    block: Option<String>,
+}
+
+/// Install the scroll shim into a runtime
+pub(super) fn install(runtime: &mut JsRuntime) -> Result<(), JsError> {
+    runtime.execute(SCROLL_SHIM).map(|_| ())
+}
+
+/// Tell `document.getElementById` which ids exist in the document, and the node of each
+pub(super) fn set_element_ids(runtime: &mut JsRuntime, document: &Document) -> Result<(), JsError> {
+    let ids: Vec<_> = document
+        .descendants(document.root())
+        .into_iter()
+        .filter_map(|node| Some((document.element_data(node)?.id()?, node)))
+        .collect();
+    let list = serde_json::to_string(&ids).map_err(|e| JsError::RuntimeError(e.to_string()))?;
+    runtime.execute(&format!("__scrollSetIds({})", list)).map(|_| ())
+}
+
+/// Drain scroll calls queued by scripts
+pub(super) fn take_requests(runtime: &mut JsRuntime) -> Result<Vec<ScrollRequest>, JsError> {
+    let json = match runtime.execute("__scrollTake()")? {
+        JsValue::String(json) => json,
+        other => return Err(JsError::TypeError(format!("unexpected scroll queue: {:?}", other))),
+    };
+
+    let raw: Vec<RawRequest> =
+        serde_json::from_str(&json).map_err(|e| JsError::RuntimeError(e.to_string()))?;
+
+    Ok(raw
+        .into_iter()
+        .filter_map(|r| {
+            let (x, y) = (r.x.unwrap_or(0.0), r.y.unwrap_or(0.0));
+            let behavior = ScrollBehavior::from_str(&r.behavior);
+            match r.kind.as_str() {
+                "intoView" => Some(ScrollRequest::IntoView {
+                    id: r.id?,
+                    behavior,
+                    block: ScrollAlign::from_str(r.block.as_deref().unwrap_or("start")),
+                }),
+                "to" => Some(ScrollRequest::To { x, y, behavior }),
+                "by" => Some(ScrollRequest::By { x, y, behavior }),
+                _ => None,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dom::Node;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_scroll_into_view_known_ids_only() {
+        let mut attrs = HashMap::new();
+        attrs.insert("id".to_string(), "section-2".to_string());
+        let dom = Node::element(
+            "body".to_string(),
+            HashMap::new(),
+            vec![Node::element("h2".to_string(), attrs, vec![])],
+        );
+
+        let mut runtime = JsRuntime::new();
+        install(&mut runtime).unwrap();
+        let document = Document::new(dom);
+        set_element_ids(&mut runtime, &document).unwrap();
+
+        let missing = runtime.execute("document.getElementById('nope') === null").unwrap();
+        assert_eq!(missing, JsValue::Boolean(true));
+        let node = runtime.execute("document.getElementById('section-2').__node").unwrap();
+        let heading = document.get_element_by_id("section-2").unwrap();
+        assert_eq!(node, JsValue::Number(heading.to_u64() as f64));
+
+        runtime
+            .execute("document.getElementById('section-2').scrollIntoView({ behavior: 'smooth', block: 'center' })")
+            .unwrap();
+        assert_eq!(
+            take_requests(&mut runtime).unwrap(),
+            vec![ScrollRequest::IntoView {
+                id: "section-2".to_string(),
+                behavior: ScrollBehavior::Smooth,
+                block: ScrollAlign::Center,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_window_scroll_calls() {
+        let mut runtime = JsRuntime::new();
+        install(&mut runtime).unwrap();
+        runtime
+            .execute("scrollTo(0, 250); scrollBy({ top: -50, behavior: 'instant' });")
+            .unwrap();
+
+        assert_eq!(
+            take_requests(&mut runtime).unwrap(),
+            vec![
+                ScrollRequest::To { x: 0.0, y: 250.0, behavior: ScrollBehavior::Auto },
+                ScrollRequest::By { x: 0.0, y: -50.0, behavior: ScrollBehavior::Instant },
+            ]
+        );
+        assert!(take_requests(&mut runtime).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,149 @@
+// window.open() binding
+//
+// Calls are queued in the page and serviced by the host, which decides
+// whether a popup is allowed and where the page opens. The call returns
+// null since other windows are not scriptable from here.
+
+use super::runtime::{JsError, JsRuntime, JsValue};
+use serde::Deserialize;
+
+/// Script installed into every context to provide `window.open`
+const WINDOW_SHIM: &str = r#"
+(function (global) {
+    var queue = [];
+
+    global.window = global.window || global;
+    global.open = function (url, target, features) {
+        queue.push({
+            url: url === undefined ? "" : String(url),
+            target: target === undefined ? "_blank" : String(target),
+            features: features === undefined ? "" : String(features)
+        });
+        return null;
+    };
+
+    global.__windowOpenTake = function () {
+        var taken = queue;
+        queue = [];
+        return JSON.stringify(taken);
+    };
+})(globalThis);
+"#;
+
+/// Where a page opened by `window.open` should go
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpenDisposition {
+    /// Replace the page in the opener's tab (`_self`, `_top`, `_parent`)
+    CurrentTab,
+    /// Open a new tab in the opener's window
+    NewTab,
+    /// Open a popup window, optionally with a requested inner size
+    NewWindow { width: Option<u32>, height: Option<u32> },
+}
+
+/// A `window.open` call made by a script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowOpenRequest {
+    /// URL as passed by the script, unresolved; empty means about:blank
+    pub url: String,
+    pub disposition: OpenDisposition,
+}
+
+#[derive(Deserialize)]
+struct RawRequest {
+    url: String,
+    target: String,
+    features: String,
+}
+
+/// Install the window shim into a runtime
+pub(super) fn install(runtime: &mut JsRuntime) -> Result<(), JsError> {
+    runtime.execute(WINDOW_SHIM).map(|_| ())
+}
+
+/// Drain `window.open` calls queued by scripts
+pub(super) fn take_requests(runtime: &mut JsRuntime) -> Result<Vec<WindowOpenRequest>, JsError> {
+    let json = match runtime.execute("__windowOpenTake()")? {
+        JsValue::String(json) => json,
+        other => return Err(JsError::TypeError(format!("unexpected window.open queue: {:?}", other))),
+    };
+
+    let raw: Vec<RawRequest> =
+        serde_json::from_str(&json).map_err(|e| JsError::RuntimeError(e.to_string()))?;
+
+    Ok(raw
+        .into_iter()
+        .map(|r| WindowOpenRequest {
+            disposition: disposition(&r.target, &r.features),
+            url: r.url,
+        })
+        .collect())
+}
+
+/// Decide where to open from the target name and feature string
+fn disposition(target: &str, features: &str) -> OpenDisposition {
+    if matches!(target.to_ascii_lowercase().as_str(), "_self" | "_top" | "_parent") {
+        return OpenDisposition::CurrentTab;
+    }
+
+    let mut popup = false;
+    let mut width = None;
+    let mut height = None;
+    for feature in features.split(',') {
+        let (name, value) = match feature.split_once('=') {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => (feature.trim(), "yes"),
+        };
+        match name.to_ascii_lowercase().as_str() {
+            "popup" => popup = !matches!(value, "no" | "0" | "false"),
+            "width" | "innerwidth" => width = value.parse().ok(),
+            "height" | "innerheight" => height = value.parse().ok(),
+            _ => {}
+        }
+    }
+
+    // Asking for a size implies a popup, as in other browsers
+    if popup || width.is_some() || height.is_some() {
+        OpenDisposition::NewWindow { width, height }
+    } else {
+        OpenDisposition::NewTab
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_open_is_queued() {
+        let mut runtime = JsRuntime::new();
+        install(&mut runtime).unwrap();
+
+        let result = runtime.execute("open('/help.html') === null").unwrap();
+        assert_eq!(result, JsValue::Boolean(true));
+        runtime.execute("window.open('https://example.com/', '_self')").unwrap();
+        runtime.execute("open('popup.html', 'help', 'width=400,height=300')").unwrap();
+
+        let requests = take_requests(&mut runtime).unwrap();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].url, "/help.html");
+        assert_eq!(requests[0].disposition, OpenDisposition::NewTab);
+        assert_eq!(requests[1].disposition, OpenDisposition::CurrentTab);
+        assert_eq!(
+            requests[2].disposition,
+            OpenDisposition::NewWindow { width: Some(400), height: Some(300) }
+        );
+        assert!(take_requests(&mut runtime).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_disposition_from_target_and_features() {
+        assert_eq!(disposition("_self", "width=200"), OpenDisposition::CurrentTab);
+        assert_eq!(disposition("_blank", ""), OpenDisposition::NewTab);
+        assert_eq!(
+            disposition("_blank", "popup"),
+            OpenDisposition::NewWindow { width: None, height: None }
+        );
+        assert_eq!(disposition("_blank", "popup=no"), OpenDisposition::NewTab);
+    }
+}
@@ -0,0 +1,781 @@
+// Inline layout - flowing text and inline boxes into line boxes
+//
+// The inline-level children of a block between its block-level ones form an
+// inline formatting context. Their text is broken where UAX #14 allows and
+// packed onto line boxes as wide as the block; each piece of a text node on
+// a line becomes a fragment with its own rect and baseline, which the display
+// list draws. Lines are as tall as their tallest content and the block's
+// strut, with baselines lined up. Inline elements cover the fragments inside
+// them, <br> ends a line, and a block inside an inline element sits between
+// lines of its own.
+
+use super::line_break::{self, BreakKind, Hyphens, Line};
+use super::positioning::is_out_of_flow;
+use super::replaced;
+use super::{BoxType, Dimensions, EdgeSizes, LayoutBox, LayoutContext, Rect, ToPx};
+use crate::css::{Unit, Value};
+use crate::style::StyledNode;
+use crate::text_style::{TextStyle, TextTransform};
+
+/// Font family text is set in when none is given
+const DEFAULT_FONT_FAMILY: &str = "sans-serif";
+/// Font size text is set in when none is given
+const DEFAULT_FONT_SIZE: f32 = 16.0;
+
+/// Elements laid out as a single box on a line, never broken
+const REPLACED_ELEMENTS: &[&str] = &["img", "video", "canvas", "iframe", "input", "textarea", "select"];
+
+/// Font a run of text is set in
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunFont {
+    pub family: String,
+    /// Size in pixels
+    pub size: f32,
+    /// Extra space after every letter, in pixels
+    pub letter_spacing: f32,
+    /// Extra space after every space between words, in pixels
+    pub word_spacing: f32,
+}
+
+impl RunFont {
+    /// The font a node's text is set in
+    pub fn of(styled: &StyledNode) -> Self {
+        let family = styled
+            .value("font-family")
+            .and_then(Value::first_font_family)
+            .unwrap_or_else(|| DEFAULT_FONT_FAMILY.to_string());
+        let size = match styled.value("font-size") {
+            Some(Value::Length(size, _)) => *size,
+            _ => DEFAULT_FONT_SIZE,
+        };
+        let style = TextStyle::of(styled, &[]);
+        Self { family, size, letter_spacing: style.letter_spacing, word_spacing: style.word_spacing }
+    }
+}
+
+/// Vertical metrics of a font at a size, in pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FontMetrics {
+    /// Height above the baseline
+    pub ascent: f32,
+    /// Depth below the baseline
+    pub descent: f32,
+    /// Space the font asks for between lines
+    pub line_gap: f32,
+}
+
+impl FontMetrics {
+    /// Typical proportions, for measuring without a font
+    pub fn estimated(size: f32) -> Self {
+        Self { ascent: size * 0.8, descent: size * 0.2, line_gap: size * 0.2 }
+    }
+
+    /// Height of a line with `line-height: normal`
+    pub fn line_height(&self) -> f32 {
+        self.ascent + self.descent + self.line_gap
+    }
+}
+
+/// Measures text for layout
+pub trait TextMeasure {
+    /// Width of text as drawn in a font
+    fn advance(&self, text: &str, font: &RunFont) -> f32;
+
+    /// Ascent, descent and line gap of a font
+    fn metrics(&self, font: &RunFont) -> FontMetrics {
+        FontMetrics::estimated(font.size)
+    }
+}
+
+/// Measures text without fonts, every character half the font size wide
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EstimatedMetrics;
+
+impl TextMeasure for EstimatedMetrics {
+    fn advance(&self, text: &str, font: &RunFont) -> f32 {
+        let characters = text.chars().count() as f32;
+        let spaces = text.chars().filter(|&c| c == ' ' || c == '\u{a0}').count() as f32;
+        characters * (font.size * 0.5 + font.letter_spacing) + spaces * font.word_spacing
+    }
+}
+
+/// A piece of a text node's text on one line
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextFragment {
+    /// Text drawn, white space collapsed and ending in any hyphen the line breaks at
+    pub text: String,
+    /// From the font's ascent above the baseline to its descent below
+    pub rect: Rect,
+    /// Page y of the baseline
+    pub baseline: f32,
+}
+
+/// The CSS `white-space` property
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WhiteSpace {
+    /// Spaces and newlines collapse; lines wrap
+    #[default]
+    Normal,
+    /// Spaces and newlines collapse; lines do not wrap
+    NoWrap,
+    /// Spaces and newlines are kept; lines do not wrap
+    Pre,
+    /// Spaces and newlines are kept; lines wrap
+    PreWrap,
+    /// Spaces collapse and newlines are kept; lines wrap
+    PreLine,
+}
+
+impl WhiteSpace {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "normal" => Some(WhiteSpace::Normal),
+            "nowrap" => Some(WhiteSpace::NoWrap),
+            "pre" => Some(WhiteSpace::Pre),
+            "pre-wrap" => Some(WhiteSpace::PreWrap),
+            "pre-line" => Some(WhiteSpace::PreLine),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WhiteSpace::Normal => "normal",
+            WhiteSpace::NoWrap => "nowrap",
+            WhiteSpace::Pre => "pre",
+            WhiteSpace::PreWrap => "pre-wrap",
+            WhiteSpace::PreLine => "pre-line",
+        }
+    }
+
+    /// How a node's text treats white space
+    pub fn of(styled: &StyledNode) -> Self {
+        match styled.value("white-space") {
+            Some(Value::Keyword(keyword)) => Self::from_str(keyword).unwrap_or_default(),
+            _ => WhiteSpace::Normal,
+        }
+    }
+
+    fn collapses_spaces(&self) -> bool {
+        matches!(self, WhiteSpace::Normal | WhiteSpace::NoWrap | WhiteSpace::PreLine)
+    }
+
+    fn keeps_newlines(&self) -> bool {
+        matches!(self, WhiteSpace::Pre | WhiteSpace::PreWrap | WhiteSpace::PreLine)
+    }
+
+    fn wraps(&self) -> bool {
+        matches!(self, WhiteSpace::Normal | WhiteSpace::PreWrap | WhiteSpace::PreLine)
+    }
+}
+
+/// Lay out a run of inline-level boxes on lines across a block's content box
+///
+/// Lines start `offset` below the top of the content box. Returns their
+/// total height.
+pub(super) fn layout_inline_run(
+    boxes: &mut [LayoutBox],
+    block: &Dimensions,
+    offset: f32,
+    style: Option<&StyledNode>,
+    cx: &mut LayoutContext,
+) -> f32 {
+    let top = block.content.y + offset;
+    let strut = style.map_or_else(
+        || {
+            let metrics = FontMetrics::estimated(DEFAULT_FONT_SIZE);
+            (metrics.ascent, metrics.descent)
+        },
+        |styled| line_extent(styled, &RunFont::of(styled), cx),
+    );
+    let mut formatter = InlineFormatter {
+        cx,
+        left: block.content.x,
+        width: block.content.width,
+        top,
+        x: 0.0,
+        align: style.map_or(0.0, align_factor),
+        strut,
+        line: Vec::new(),
+        resolved: Vec::new(),
+        skip_space: true,
+        next_id: 0,
+    };
+    for layout_box in boxes.iter_mut() {
+        formatter.flow(layout_box);
+    }
+    formatter.break_line();
+
+    let mut by_box: Vec<Vec<Resolved>> = vec![Vec::new(); formatter.next_id];
+    for resolved in formatter.resolved {
+        by_box[resolved.id].push(resolved);
+    }
+    let mut next_id = 0;
+    for layout_box in boxes.iter_mut() {
+        assign(layout_box, &mut next_id, &by_box);
+    }
+    formatter.top - top
+}
+
+/// Something on the current line, positioned once the line is complete
+struct Placed {
+    /// Box it belongs to, numbered in tree order within the run
+    id: usize,
+    /// From the start of the line
+    x: f32,
+    width: f32,
+    /// Extent above and below the baseline the line makes room for
+    above: f32,
+    below: f32,
+    /// Ascent and descent of the fragment's rect
+    ascent: f32,
+    descent: f32,
+    /// Text of a fragment
+    text: Option<String>,
+    /// Only gives its box a position, taking no room on the line
+    marker: bool,
+}
+
+/// Something placed on a finished line
+#[derive(Clone)]
+struct Resolved {
+    id: usize,
+    rect: Rect,
+    baseline: f32,
+    text: Option<String>,
+    marker: bool,
+}
+
+/// Packs inline content onto lines
+struct InlineFormatter<'c, 'm> {
+    cx: &'c mut LayoutContext<'m>,
+    /// Left edge and width of the lines
+    left: f32,
+    width: f32,
+    /// Top of the current line
+    top: f32,
+    /// Where the next content goes, from the start of the line
+    x: f32,
+    /// Share of a line's free space put before it: 0 for left, 0.5 for center, 1 for right
+    align: f32,
+    /// Room every line makes above and below its baseline for the block's font
+    strut: (f32, f32),
+    line: Vec<Placed>,
+    resolved: Vec<Resolved>,
+    /// A collapsible space here is dropped: at the start of a line or after a space
+    skip_space: bool,
+    next_id: usize,
+}
+
+impl InlineFormatter<'_, '_> {
+    fn flow(&mut self, layout_box: &mut LayoutBox) {
+        if is_out_of_flow(layout_box) {
+            // Laid out once its containing block is; for now only its static position
+            let id = self.next_id;
+            self.next_id += 1;
+            self.mark(id);
+            return;
+        }
+        if matches!(layout_box.box_type, BoxType::BlockNode(_) | BoxType::FlexNode(_)) {
+            // A block inside an inline element sits between lines of its own
+            self.break_line();
+            let containing = Dimensions {
+                content: Rect { x: self.left, y: self.top, width: self.width, height: 0.0 },
+                ..Dimensions::default()
+            };
+            layout_box.layout_in(containing, self.cx);
+            self.top += layout_box.dimensions.margin_box().height;
+            return;
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.mark(id);
+        let Some(styled) = layout_box.get_styled_node() else {
+            return;
+        };
+        if let Some(text) = styled.node.text_content() {
+            self.flow_text(id, styled, text);
+            return;
+        }
+        let Some(element) = styled.node.element_data() else {
+            return;
+        };
+        if element.tag_name == "br" {
+            let (above, below) = line_extent(styled, &RunFont::of(styled), self.cx);
+            self.line.push(Placed {
+                id,
+                x: self.x,
+                width: 0.0,
+                above,
+                below,
+                ascent: 0.0,
+                descent: 0.0,
+                text: None,
+                marker: false,
+            });
+            self.break_line();
+            return;
+        }
+
+        let (margin, border, padding) = inline_edges(styled);
+        if is_atomic(layout_box, styled) {
+            let (content_width, content_height) = replaced::replaced_size(styled, self.cx).unwrap_or_else(|| {
+                (styled.value("width").map_or(0.0, ToPx::to_px), styled.value("height").map_or(0.0, ToPx::to_px))
+            });
+            let width = content_width
+                + margin.left + margin.right + border.left + border.right + padding.left + padding.right;
+            let height = content_height
+                + margin.top + margin.bottom + border.top + border.bottom + padding.top + padding.bottom;
+            if self.x > 0.0 && self.x + width > self.width && WhiteSpace::of(styled).wraps() {
+                self.break_line();
+            }
+            self.line.push(Placed {
+                id,
+                x: self.x,
+                width,
+                above: height,
+                below: 0.0,
+                ascent: height,
+                descent: 0.0,
+                text: None,
+                marker: false,
+            });
+            self.x += width;
+            self.skip_space = false;
+            return;
+        }
+
+        let lang = element.get_attribute("lang");
+        if let Some(lang) = lang {
+            self.cx.lang.push(lang.to_string());
+        }
+        self.x += margin.left + border.left + padding.left;
+        for child in &mut layout_box.children {
+            self.flow(child);
+        }
+        self.x += padding.right + border.right + margin.right;
+        if lang.is_some() {
+            self.cx.lang.pop();
+        }
+    }
+
+    /// Break a text node's text onto lines, filling the current one first
+    fn flow_text(&mut self, id: usize, styled: &StyledNode, raw: &str) {
+        let white_space = WhiteSpace::of(styled);
+        let mut text = TextTransform::of(styled).apply(raw);
+        if white_space.collapses_spaces() {
+            text = collapse_spaces(&text, white_space.keeps_newlines(), self.skip_space);
+        }
+        if text.is_empty() {
+            return;
+        }
+        let font = RunFont::of(styled);
+        let (above, below) = line_extent(styled, &font, self.cx);
+        let metrics = self.cx.measure.metrics(&font);
+        let run = Run { id, text: &text, font: &font, above, below, ascent: metrics.ascent, descent: metrics.descent };
+
+        let mut breaks = line_break::break_opportunities(&text, Hyphens::of(styled), self.cx.lang());
+        if !white_space.wraps() {
+            breaks.retain(|opportunity| opportunity.kind == BreakKind::Mandatory);
+        }
+        let mut start = 0;
+        // The last break the current line fits up to
+        let mut fitting: Option<Line> = None;
+        let mut index = 0;
+        while index < breaks.len() {
+            let opportunity = breaks[index];
+            if opportunity.offset <= start {
+                index += 1;
+                continue;
+            }
+            let mut line = Line {
+                range: start..opportunity.offset,
+                hyphenated: opportunity.kind == BreakKind::Hyphen,
+                width: 0.0,
+            };
+            line.width = self.cx.measure.advance(&line.text(&text), &font);
+            let overflows = line.width > self.width - self.x;
+            if overflows && white_space.wraps() {
+                if let Some(fit) = fitting.take() {
+                    // End the line at the last break that fit and try this one again on the next
+                    start = fit.range.end;
+                    self.place_text(&run, &fit, false);
+                    self.break_line();
+                    continue;
+                }
+                if self.x > 0.0 {
+                    // Other content is on the line; this text starts the next one
+                    self.break_line();
+                    continue;
+                }
+            }
+            let hard = opportunity.kind == BreakKind::Mandatory && text[..opportunity.offset].ends_with(is_newline);
+            if hard || (overflows && white_space.wraps()) {
+                start = line.range.end;
+                self.place_text(&run, &line, hard);
+                self.break_line();
+                fitting = None;
+            } else {
+                fitting = Some(line);
+            }
+            index += 1;
+        }
+        if let Some(fit) = fitting {
+            self.place_text(&run, &fit, false);
+        }
+    }
+
+    /// Put a line's worth of a text run on the current line
+    ///
+    /// Spaces the piece ends with hang past its fragment but still move
+    /// the next content along. A piece ending at a newline keeps its line
+    /// open even if it draws nothing.
+    fn place_text(&mut self, run: &Run, line: &Line, hard: bool) {
+        let source = &run.text[line.range.clone()];
+        let drawn = line.text(run.text);
+        let advance = if !line.hyphenated && source.ends_with(char::is_whitespace) {
+            let spaced: String = source.chars().filter(|&c| c != '\u{ad}' && !is_newline(c)).collect();
+            self.cx.measure.advance(&spaced, run.font)
+        } else {
+            line.width
+        };
+        if !drawn.is_empty() || hard {
+            self.line.push(Placed {
+                id: run.id,
+                x: self.x,
+                width: if drawn.is_empty() { 0.0 } else { line.width },
+                above: run.above,
+                below: run.below,
+                ascent: run.ascent,
+                descent: run.descent,
+                text: (!drawn.is_empty()).then_some(drawn),
+                marker: false,
+            });
+        }
+        self.x += advance;
+        self.skip_space = source.ends_with(char::is_whitespace);
+    }
+
+    /// Give a box a position where the next content goes
+    fn mark(&mut self, id: usize) {
+        self.line.push(Placed {
+            id,
+            x: self.x,
+            width: 0.0,
+            above: 0.0,
+            below: 0.0,
+            ascent: 0.0,
+            descent: 0.0,
+            text: None,
+            marker: true,
+        });
+    }
+
+    /// Finish the current line, lining up what is on it, and start the next
+    ///
+    /// A line with nothing but markers on it takes no room.
+    fn break_line(&mut self) {
+        let content = self.line.iter().filter(|placed| !placed.marker);
+        let (mut baseline, mut depth) = (0.0_f32, 0.0_f32);
+        let mut used = 0.0_f32;
+        let mut empty = true;
+        for placed in content {
+            baseline = baseline.max(placed.above);
+            depth = depth.max(placed.below);
+            used = used.max(placed.x + placed.width);
+            empty = false;
+        }
+        if !empty {
+            baseline = baseline.max(self.strut.0);
+            depth = depth.max(self.strut.1);
+        }
+        let shift = ((self.width - used) * self.align).max(0.0);
+        for placed in self.line.drain(..) {
+            self.resolved.push(Resolved {
+                id: placed.id,
+                rect: Rect {
+                    x: self.left + placed.x + shift,
+                    y: self.top + baseline - placed.ascent,
+                    width: placed.width,
+                    height: placed.ascent + placed.descent,
+                },
+                baseline: self.top + baseline,
+                text: placed.text,
+                marker: placed.marker,
+            });
+        }
+        self.top += baseline + depth;
+        self.x = 0.0;
+        self.skip_space = true;
+    }
+}
+
+/// A text node's text being broken onto lines
+struct Run<'t> {
+    id: usize,
+    text: &'t str,
+    font: &'t RunFont,
+    above: f32,
+    below: f32,
+    ascent: f32,
+    descent: f32,
+}
+
+/// Set the fragments and dimensions of laid out boxes, returning the area each covers
+fn assign(layout_box: &mut LayoutBox, next_id: &mut usize, by_box: &[Vec<Resolved>]) -> Option<Rect> {
+    let out_of_flow = is_out_of_flow(layout_box);
+    if !out_of_flow && matches!(layout_box.box_type, BoxType::BlockNode(_) | BoxType::FlexNode(_)) {
+        return Some(layout_box.dimensions.border_box());
+    }
+    let id = *next_id;
+    *next_id += 1;
+    let placed = &by_box[id];
+    let position = placed.first().map(|first| Rect { width: 0.0, height: 0.0, ..first.rect }).unwrap_or_default();
+    if out_of_flow {
+        layout_box.dimensions = Dimensions { content: position, ..Dimensions::default() };
+        return None;
+    }
+    let styled = layout_box.get_styled_node();
+
+    let mut covered: Option<Rect> = None;
+    if styled.is_some_and(|styled| styled.node.text_content().is_some()) {
+        layout_box.fragments = placed
+            .iter()
+            .filter_map(|placed| {
+                let text = placed.text.clone()?;
+                Some(TextFragment { text, rect: placed.rect, baseline: placed.baseline })
+            })
+            .collect();
+        for fragment in &layout_box.fragments {
+            covered = Some(union(covered, fragment.rect));
+        }
+        layout_box.dimensions = Dimensions { content: covered.unwrap_or(position), ..Dimensions::default() };
+        return covered;
+    }
+
+    let (margin, border, padding) = styled.map(inline_edges).unwrap_or_default();
+    let atomic = styled.is_some_and(|styled| is_atomic(layout_box, styled));
+    if atomic {
+        let outer = placed.iter().find(|placed| !placed.marker).map_or(position, |placed| placed.rect);
+        covered = Some(outer);
+        let d = &mut layout_box.dimensions;
+        (d.margin, d.border, d.padding) = (margin, border, padding);
+        d.content = Rect {
+            x: outer.x + margin.left + border.left + padding.left,
+            y: outer.y + margin.top + border.top + padding.top,
+            width: (outer.width - margin.left - margin.right - border.left - border.right - padding.left
+                - padding.right)
+                .max(0.0),
+            height: (outer.height - margin.top - margin.bottom - border.top - border.bottom - padding.top
+                - padding.bottom)
+                .max(0.0),
+        };
+        return covered;
+    }
+
+    for child in &mut layout_box.children {
+        if let Some(rect) = assign(child, next_id, by_box) {
+            covered = Some(union(covered, rect));
+        }
+    }
+    let d = &mut layout_box.dimensions;
+    (d.margin, d.border, d.padding) = (margin, border, padding);
+    d.content = covered.unwrap_or(position);
+    covered.map(|_| d.border_box())
+}
+
+/// Smallest rect holding `rect` and what `covered` holds
+fn union(covered: Option<Rect>, rect: Rect) -> Rect {
+    let Some(covered) = covered else {
+        return rect;
+    };
+    let left = covered.x.min(rect.x);
+    let top = covered.y.min(rect.y);
+    let right = (covered.x + covered.width).max(rect.x + rect.width);
+    let bottom = (covered.y + covered.height).max(rect.y + rect.height);
+    Rect { x: left, y: top, width: right - left, height: bottom - top }
+}
+
+/// Is a box laid out as one unbreakable box on a line
+fn is_atomic(layout_box: &LayoutBox, styled: &StyledNode) -> bool {
+    let Some(element) = styled.node.element_data() else {
+        return false;
+    };
+    REPLACED_ELEMENTS.contains(&element.tag_name.as_str())
+        || (layout_box.children.is_empty() && (styled.value("width").is_some() || styled.value("height").is_some()))
+}
+
+/// Margins, borders and padding of an inline element
+fn inline_edges(styled: &StyledNode) -> (EdgeSizes, EdgeSizes, EdgeSizes) {
+    let zero = Value::Length(0.0, Unit::Px);
+    let edges = |prefix: &str, suffix: &str| {
+        let side = |side: &str| {
+            styled.lookup(&format!("{}-{}{}", prefix, side, suffix), &format!("{}{}", prefix, suffix), &zero).to_px()
+        };
+        EdgeSizes { left: side("left"), right: side("right"), top: side("top"), bottom: side("bottom") }
+    };
+    (edges("margin", ""), edges("border", "-width"), edges("padding", ""))
+}
+
+/// Room a line makes above and below its baseline for text in a font
+///
+/// The font's ascent and descent, with the rest of `line-height` split
+/// evenly above and below.
+fn line_extent(styled: &StyledNode, font: &RunFont, cx: &LayoutContext) -> (f32, f32) {
+    let metrics = cx.measure.metrics(font);
+    let line_height = match styled.value("line-height") {
+        Some(Value::Number(factor)) => factor * font.size,
+        Some(Value::Length(length, Unit::Px)) => *length,
+        Some(Value::Length(length, Unit::Em)) => length * font.size,
+        Some(Value::Length(length, Unit::Rem)) => length * DEFAULT_FONT_SIZE,
+        Some(Value::Length(percent, Unit::Percent)) | Some(Value::Percentage(percent)) => percent / 100.0 * font.size,
+        _ => metrics.line_height(),
+    };
+    let half_leading = (line_height - metrics.ascent - metrics.descent) / 2.0;
+    (metrics.ascent + half_leading, metrics.descent + half_leading)
+}
+
+/// Share of a line's free space `text-align` puts before it
+fn align_factor(styled: &StyledNode) -> f32 {
+    match styled.value("text-align") {
+        Some(Value::Keyword(keyword)) => match keyword.as_str() {
+            "center" => 0.5,
+            "right" | "end" => 1.0,
+            _ => 0.0,
+        },
+        _ => 0.0,
+    }
+}
+
+/// Collapse runs of white space to single spaces
+///
+/// A space at the start is dropped if `skip_first` is set, as at the start
+/// of a line or after another space. With `keep_newlines` newlines stay,
+/// taking the spaces around them.
+fn collapse_spaces(text: &str, keep_newlines: bool, skip_first: bool) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut skip = skip_first;
+    for c in text.chars() {
+        if keep_newlines && is_newline(c) {
+            if collapsed.ends_with(' ') {
+                collapsed.pop();
+            }
+            collapsed.push('\n');
+            skip = true;
+        } else if c.is_whitespace() && c != '\u{a0}' {
+            if !skip {
+                collapsed.push(' ');
+            }
+            skip = true;
+        } else {
+            collapsed.push(c);
+            skip = false;
+        }
+    }
+    collapsed
+}
+
+fn is_newline(c: char) -> bool {
+    matches!(c, '\n' | '\r' | '\u{b}' | '\u{c}' | '\u{85}' | '\u{2028}' | '\u{2029}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::CssParser;
+    use crate::dom::Node;
+    use crate::layout::layout_tree;
+    use crate::style::style_tree;
+    use std::collections::HashMap;
+
+    fn viewport(width: f32) -> Dimensions {
+        let mut viewport = Dimensions::default();
+        viewport.content.width = width;
+        viewport
+    }
+
+    fn element(tag: &str, children: Vec<Node>) -> Node {
+        Node::element(tag.to_string(), HashMap::new(), children)
+    }
+
+    fn fragments<'a>(layout_box: &'a LayoutBox, out: &mut Vec<&'a TextFragment>) {
+        out.extend(layout_box.fragments.iter());
+        for child in &layout_box.children {
+            fragments(child, out);
+        }
+    }
+
+    #[test]
+    fn test_text_wraps_onto_lines() {
+        // Every character is 8px wide at 16px, and lines are 20px tall
+        let css = CssParser::parse("div { display: block; font-size: 16px; line-height: 20px; }");
+        let html = element("div", vec![Node::text("the quick  brown\n fox jumps".to_string())]);
+        let styled = style_tree(&html, &css);
+        let layout = layout_tree(&styled, viewport(100.0));
+
+        let mut found = Vec::new();
+        fragments(&layout, &mut found);
+        let lines: Vec<(&str, f32, f32)> = found.iter().map(|f| (f.text.as_str(), f.rect.y, f.rect.width)).collect();
+        assert_eq!(lines, vec![("the quick", 2.0, 72.0), ("brown fox", 22.0, 72.0), ("jumps", 42.0, 40.0)]);
+        assert_eq!(found[0].baseline, 2.0 + 12.8);
+        assert_eq!(layout.dimensions.content.height, 60.0);
+        // The text box covers its fragments
+        let text_box = &layout.children[0].dimensions.content;
+        assert_eq!((text_box.y, text_box.width, text_box.height), (2.0, 72.0, 56.0));
+    }
+
+    #[test]
+    fn test_inline_elements_share_lines() {
+        let css = CssParser::parse(
+            "div { display: block; text-align: center; line-height: 20px; } \
+             b { font-size: 32px; padding-left: 4px; } p { display: block; height: 10px; }",
+        );
+        let html = element(
+            "div",
+            vec![
+                Node::text("one ".to_string()),
+                element("b", vec![Node::text("two".to_string())]),
+                element("br", vec![]),
+                Node::text("three".to_string()),
+                element("p", vec![]),
+                Node::text("four".to_string()),
+            ],
+        );
+        let styled = style_tree(&html, &css);
+        let layout = layout_tree(&styled, viewport(200.0));
+
+        let mut found = Vec::new();
+        fragments(&layout, &mut found);
+        let texts: Vec<&str> = found.iter().map(|f| f.text.as_str()).collect();
+        assert_eq!(texts, vec!["one", "two", "three", "four"]);
+        // "one " and the padded bold "two" are centered together on a baseline
+        let line_width = 32.0 + 4.0 + 48.0;
+        assert_eq!(found[0].rect.x, (200.0 - line_width) / 2.0);
+        assert_eq!(found[1].rect.x, found[0].rect.x + 36.0);
+        assert_eq!(found[0].baseline, found[1].baseline);
+        let bold = &layout.children[1].dimensions;
+        assert_eq!((bold.padding.left, bold.content.x), (4.0, found[1].rect.x));
+        // The break starts a new line, and the block sits between lines
+        assert!(found[2].rect.y > found[1].rect.y + 20.0);
+        let block = &layout.children[4].dimensions.content;
+        assert_eq!(block.y, found[2].rect.y - 2.0 + 20.0);
+        assert_eq!(found[3].rect.y, block.y + 10.0 + 2.0);
+    }
+
+    #[test]
+    fn test_white_space() {
+        assert_eq!(collapse_spaces("  a \t b\n c ", false, true), "a b c ");
+        assert_eq!(collapse_spaces("a  \n  b", true, false), "a\nb");
+
+        let css = CssParser::parse("div { display: block; white-space: pre; line-height: 20px; }");
+        let html = element("div", vec![Node::text("a  b\n\nlonger than the line".to_string())]);
+        let styled = style_tree(&html, &css);
+        let layout = layout_tree(&styled, viewport(40.0));
+        let mut found = Vec::new();
+        fragments(&layout, &mut found);
+        let texts: Vec<&str> = found.iter().map(|f| f.text.as_str()).collect();
+        assert_eq!(texts, vec!["a  b", "longer than the line"]);
+        // The empty line between them keeps its height
+        assert_eq!(found[1].rect.y - found[0].rect.y, 40.0);
+    }
+}
@@ -0,0 +1,279 @@
+// Line breaking - where text may wrap, and wrapping it to a width
+//
+// Break opportunities follow the Unicode line breaking algorithm (UAX #14):
+// after spaces, after hyphens and dashes, between ideographs, and never
+// inside a word or before closing punctuation. `hyphens` adds breaks inside
+// words: soft hyphens (U+00AD) with `manual`, and with `auto` the
+// syllables a hyphenation dictionary for the text's language finds too.
+// A line broken inside a word ends with a visible hyphen.
+
+use std::ops::Range;
+
+use crate::css::Value;
+use crate::style::StyledNode;
+
+/// Soft hyphen, invisible unless a line breaks at it
+const SOFT_HYPHEN: char = '\u{ad}';
+
+/// The CSS `hyphens` property
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Hyphens {
+    /// No breaks inside words, even at soft hyphens
+    None,
+    /// Breaks at soft hyphens only
+    #[default]
+    Manual,
+    /// Breaks where the language's hyphenation dictionary allows too
+    Auto,
+}
+
+impl Hyphens {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "none" => Some(Hyphens::None),
+            "manual" => Some(Hyphens::Manual),
+            "auto" => Some(Hyphens::Auto),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Hyphens::None => "none",
+            Hyphens::Manual => "manual",
+            Hyphens::Auto => "auto",
+        }
+    }
+
+    /// How a node's text is hyphenated
+    pub fn of(styled: &StyledNode) -> Self {
+        match styled.value("hyphens") {
+            Some(Value::Keyword(keyword)) => Self::from_str(keyword).unwrap_or_default(),
+            _ => Hyphens::Manual,
+        }
+    }
+}
+
+/// Kind of place a line may end
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakKind {
+    /// The line must end here, as after a newline
+    Mandatory,
+    /// The line may end here
+    Allowed,
+    /// The line may end here inside a word, with a hyphen shown
+    Hyphen,
+}
+
+/// A place a line may end: before the character at `offset` bytes into the text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakOpportunity {
+    pub offset: usize,
+    pub kind: BreakKind,
+}
+
+/// Where lines of `text` may end
+///
+/// `lang` is a BCP 47 language tag such as `en-US`, picking the dictionary
+/// `hyphens: auto` uses; languages without one get no dictionary breaks.
+/// The end of the text is always a mandatory break.
+pub fn break_opportunities(text: &str, hyphens: Hyphens, lang: &str) -> Vec<BreakOpportunity> {
+    let mut breaks: Vec<BreakOpportunity> = unicode_linebreak::linebreaks(text)
+        .filter_map(|(offset, opportunity)| {
+            let kind = match opportunity {
+                unicode_linebreak::BreakOpportunity::Mandatory => BreakKind::Mandatory,
+                _ if text[..offset].ends_with(SOFT_HYPHEN) => match hyphens {
+                    Hyphens::None => return None,
+                    _ => BreakKind::Hyphen,
+                },
+                _ => BreakKind::Allowed,
+            };
+            Some(BreakOpportunity { offset, kind })
+        })
+        .collect();
+
+    if hyphens == Hyphens::Auto {
+        if let Some(language) = hyphenation_language(lang) {
+            for (start, word) in words(text) {
+                // Soft hyphens in a word say where it may break, instead of the dictionary
+                if word.contains(SOFT_HYPHEN) {
+                    continue;
+                }
+                let mut offset = start;
+                let syllables: Vec<&str> = hypher::hyphenate(word, language).collect();
+                for syllable in &syllables[..syllables.len().saturating_sub(1)] {
+                    offset += syllable.len();
+                    breaks.push(BreakOpportunity { offset, kind: BreakKind::Hyphen });
+                }
+            }
+            breaks.sort_by_key(|opportunity| opportunity.offset);
+        }
+    }
+    breaks
+}
+
+/// Dictionary for a language tag, by its primary language
+fn hyphenation_language(lang: &str) -> Option<hypher::Lang> {
+    let primary = lang.split(['-', '_']).next()?.to_ascii_lowercase();
+    let code: [u8; 2] = primary.as_bytes().try_into().ok()?;
+    hypher::Lang::from_iso(code)
+}
+
+/// Runs of letters in text, with their byte offsets
+fn words(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut rest = text.char_indices().peekable();
+    std::iter::from_fn(move || {
+        while rest.next_if(|(_, c)| !c.is_alphabetic() && *c != SOFT_HYPHEN).is_some() {}
+        let (start, _) = *rest.peek()?;
+        let mut end = start;
+        while let Some((i, c)) = rest.next_if(|(_, c)| c.is_alphabetic() || *c == SOFT_HYPHEN) {
+            end = i + c.len_utf8();
+        }
+        Some((start, &text[start..end]))
+    })
+}
+
+/// A line of wrapped text
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    /// Bytes of the text on the line, spaces it ends with included
+    pub range: Range<usize>,
+    /// The line ends inside a word and shows a hyphen
+    pub hyphenated: bool,
+    /// Width of the line as drawn
+    pub width: f32,
+}
+
+impl Line {
+    /// Text drawn for the line: trailing spaces hang past its end, soft
+    /// hyphens are hidden, and a hyphenated line ends in a hyphen
+    pub fn text(&self, source: &str) -> String {
+        let text = source[self.range.clone()].trim_end();
+        let mut drawn: String = text.chars().filter(|&c| c != SOFT_HYPHEN).collect();
+        if self.hyphenated {
+            drawn.push('-');
+        }
+        drawn
+    }
+
+    /// Space to add at each word separator so the line fills `available` width
+    ///
+    /// None for lines with no separators to stretch.
+    pub fn justification(&self, source: &str, available: f32) -> Option<f32> {
+        let separators = self.text(source).matches([' ', '\u{a0}']).count();
+        if separators == 0 || self.width >= available {
+            return None;
+        }
+        Some((available - self.width) / separators as f32)
+    }
+}
+
+/// Wrap text into lines no wider than `max_width`, ending each as late as it can
+///
+/// `measure` gives the width of text as drawn. A piece with no break
+/// opportunity narrower than the width overflows on a line of its own.
+pub fn break_lines(
+    text: &str,
+    opportunities: &[BreakOpportunity],
+    max_width: f32,
+    mut measure: impl FnMut(&str) -> f32,
+) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    // The last break the current line fits up to
+    let mut fitting: Option<Line> = None;
+    let mut index = 0;
+    while index < opportunities.len() {
+        let opportunity = opportunities[index];
+        if opportunity.offset <= start {
+            index += 1;
+            continue;
+        }
+        let mut line = Line {
+            range: start..opportunity.offset,
+            hyphenated: opportunity.kind == BreakKind::Hyphen,
+            width: 0.0,
+        };
+        line.width = measure(&line.text(text));
+
+        if line.width > max_width {
+            if let Some(fit) = fitting.take() {
+                // End the line at the last break that fit and try this one again on the next
+                start = fit.range.end;
+                lines.push(fit);
+                continue;
+            }
+        }
+        if line.width > max_width || opportunity.kind == BreakKind::Mandatory {
+            start = line.range.end;
+            lines.push(line);
+            fitting = None;
+        } else {
+            fitting = Some(line);
+        }
+        index += 1;
+    }
+    if let Some(fit) = fitting {
+        lines.push(fit);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every character ten pixels wide
+    fn measure(text: &str) -> f32 {
+        text.chars().count() as f32 * 10.0
+    }
+
+    fn wrap(text: &str, hyphens: Hyphens, max_width: f32) -> Vec<String> {
+        let breaks = break_opportunities(text, hyphens, "en-US");
+        break_lines(text, &breaks, max_width, measure).iter().map(|line| line.text(text)).collect()
+    }
+
+    #[test]
+    fn test_break_opportunities() {
+        let breaks = break_opportunities("Hello world-wide (web).\nBye", Hyphens::Manual, "en");
+        let offsets: Vec<(usize, BreakKind)> = breaks.iter().map(|b| (b.offset, b.kind)).collect();
+        assert_eq!(
+            offsets,
+            vec![
+                (6, BreakKind::Allowed),
+                (12, BreakKind::Allowed),
+                (17, BreakKind::Allowed),
+                (24, BreakKind::Mandatory),
+                (27, BreakKind::Mandatory),
+            ]
+        );
+        // Ideographs may break between any two
+        assert_eq!(break_opportunities("\u{65e5}\u{672c}\u{8a9e}", Hyphens::Manual, "ja").len(), 3);
+    }
+
+    #[test]
+    fn test_wrapping_and_soft_hyphens() {
+        assert_eq!(wrap("the quick brown fox", Hyphens::Manual, 100.0), vec!["the quick", "brown fox"]);
+        // A word wider than the line overflows on its own
+        assert_eq!(wrap("a extraordinary b", Hyphens::Manual, 50.0), vec!["a", "extraordinary", "b"]);
+        assert_eq!(wrap("one\ntwo three", Hyphens::Manual, 1000.0), vec!["one", "two three"]);
+
+        let shy = "extra\u{ad}ordinary";
+        assert_eq!(wrap(shy, Hyphens::Manual, 80.0), vec!["extra-", "ordinary"]);
+        assert_eq!(wrap(shy, Hyphens::Manual, 1000.0), vec!["extraordinary"]);
+        assert_eq!(wrap(shy, Hyphens::None, 80.0), vec!["extraordinary"]);
+    }
+
+    #[test]
+    fn test_auto_hyphenation_and_justification() {
+        assert_eq!(wrap("an extensive hyphenation", Hyphens::Auto, 90.0), vec!["an exten-", "sive hy-", "phenation"]);
+        // Without a dictionary for the language only soft hyphens break words
+        let breaks = break_opportunities("extensive", Hyphens::Auto, "xx");
+        assert_eq!(breaks, vec![BreakOpportunity { offset: 9, kind: BreakKind::Mandatory }]);
+
+        let text = "a b c";
+        let line = Line { range: 0..5, hyphenated: false, width: 50.0 };
+        assert_eq!(line.justification(text, 70.0), Some(10.0));
+        assert_eq!(Line { range: 0..1, hyphenated: false, width: 10.0 }.justification(text, 70.0), None);
+    }
+}
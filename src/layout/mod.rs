@@ -1,12 +1,21 @@
 pub mod flexbox;
 pub mod positioning;
 pub mod grid;
+pub mod line_break;
+pub mod inline;
+pub mod replaced;
+pub mod overflow;
 
 #[cfg(test)]
 mod flexbox_tests;
 
 use crate::css::{Value, Unit};
 use crate::style::{StyledNode, Display};
+use crate::style::properties::INITIAL_FONT_SIZE;
+use serde::{Deserialize, Serialize};
+use inline::{EstimatedMetrics, TextFragment, TextMeasure};
+use replaced::ImageSizes;
+use url::Url;
 
 /// A box in the layout tree
 #[derive(Debug, Clone)]
@@ -14,6 +23,8 @@ pub struct LayoutBox<'a> {
     pub dimensions: Dimensions,
     pub box_type: BoxType<'a>,
     pub children: Vec<LayoutBox<'a>>,
+    /// Pieces of a text node's text on each line it spans
+    pub fragments: Vec<TextFragment>,
 }
 
 /// Type of box (block, inline, flex, or anonymous)
@@ -35,7 +46,7 @@ pub struct Dimensions {
 }
 
 /// Rectangle dimensions
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
 pub struct Rect {
     pub x: f32,
     pub y: f32,
@@ -76,6 +87,41 @@ impl Rect {
             height: self.height + edge.top + edge.bottom,
         }
     }
+
+    /// Check if a point lies inside the rectangle
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && x <= self.x + self.width && y >= self.y && y <= self.y + self.height
+    }
+
+    /// The area both rectangles cover, or an empty rectangle if they do not overlap
+    pub fn intersection(&self, other: &Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = (self.x + self.width).min(other.x + other.width);
+        let bottom = (self.y + self.height).min(other.y + other.height);
+        Rect { x, y, width: (right - x).max(0.0), height: (bottom - y).max(0.0) }
+    }
+}
+
+/// State carried down the tree while laying it out
+pub(crate) struct LayoutContext<'m> {
+    measure: &'m dyn TextMeasure,
+    /// `lang` attributes of the elements being laid out, innermost last
+    lang: Vec<String>,
+    /// What fixed boxes, and absolutely positioned ones with no positioned
+    /// ancestor, are placed in
+    viewport: Rect,
+    /// Natural sizes of the document's images, if any are known
+    images: Option<&'m ImageSizes>,
+    /// What image sources are resolved against
+    base_url: Option<&'m Url>,
+}
+
+impl LayoutContext<'_> {
+    /// Language of the content being laid out, empty if none is given
+    fn lang(&self) -> &str {
+        self.lang.last().map_or("", String::as_str)
+    }
 }
 
 impl<'a> LayoutBox<'a> {
@@ -85,6 +131,7 @@ impl<'a> LayoutBox<'a> {
             box_type,
             dimensions: Default::default(),
             children: Vec::new(),
+            fragments: Vec::new(),
         }
     }
 
@@ -96,31 +143,103 @@ impl<'a> LayoutBox<'a> {
         }
     }
 
-    /// Lay out a box and its descendants
+    /// Find the boxes under a point
+    ///
+    /// Returns the path from this box down to the innermost box whose border
+    /// box contains the point, or an empty path if nothing was hit. Later
+    /// siblings paint on top of earlier ones, so they are tested first.
+    pub fn hit_test(&self, x: f32, y: f32) -> Vec<&LayoutBox<'a>> {
+        let mut path = Vec::new();
+        self.hit_test_into(x, y, &mut path);
+        path
+    }
+
+    fn hit_test_into<'b>(&'b self, x: f32, y: f32, path: &mut Vec<&'b LayoutBox<'a>>) -> bool {
+        // Descendants may overflow their parent, so test them even when the
+        // parent itself misses
+        path.push(self);
+        for child in self.children.iter().rev() {
+            if child.hit_test_into(x, y, path) {
+                return true;
+            }
+        }
+
+        if self.dimensions.border_box().contains(x, y) {
+            true
+        } else {
+            path.pop();
+            false
+        }
+    }
+
+    /// Lay out a box and its descendants, measuring text without fonts
     pub fn layout(&mut self, containing_block: Dimensions) {
+        self.layout_with_metrics(containing_block, &EstimatedMetrics);
+    }
+
+    /// Lay out a box and its descendants, measuring text with `measure`
+    pub fn layout_with_metrics(&mut self, containing_block: Dimensions, measure: &dyn TextMeasure) {
+        let mut cx = LayoutContext {
+            measure,
+            lang: Vec::new(),
+            viewport: containing_block.content,
+            images: None,
+            base_url: None,
+        };
+        self.layout_root(containing_block, &mut cx);
+    }
+
+    /// Lay out the normal flow, then the positioned boxes
+    fn layout_root(&mut self, containing_block: Dimensions, cx: &mut LayoutContext) {
+        self.layout_in(containing_block, cx);
+        let viewport = cx.viewport;
+        positioning::layout_positioned(self, viewport, cx);
+    }
+
+    fn layout_in(&mut self, containing_block: Dimensions, cx: &mut LayoutContext) {
+        let lang = self
+            .get_styled_node()
+            .and_then(|styled| styled.node.element_data())
+            .and_then(|element| element.get_attribute("lang"));
+        if let Some(lang) = lang {
+            cx.lang.push(lang.to_string());
+        }
         match self.box_type {
-            BoxType::BlockNode(_) => self.layout_block(containing_block),
-            BoxType::FlexNode(_) => self.layout_flex(containing_block),
+            BoxType::BlockNode(_) => self.layout_block(containing_block, cx),
+            BoxType::FlexNode(_) => self.layout_flex(containing_block, cx),
             BoxType::InlineNode(_) | BoxType::AnonymousBlock => {
-                // Simplified: treat inline as block for now
-                self.layout_block(containing_block)
+                // An inline box laid out on its own, like the root, holds its
+                // content as a block would
+                self.layout_block(containing_block, cx)
             }
         }
+        if lang.is_some() {
+            cx.lang.pop();
+        }
     }
 
     /// Lay out a block-level element
-    fn layout_block(&mut self, containing_block: Dimensions) {
+    fn layout_block(&mut self, containing_block: Dimensions, cx: &mut LayoutContext) {
         // Calculate width
         self.calculate_block_width(containing_block);
 
         // Determine position
         self.calculate_block_position(containing_block);
 
+        // Replaced elements take their natural size where CSS leaves it open
+        let replaced = self.get_styled_node().and_then(|styled| replaced::replaced_size(styled, cx));
+        if let Some((width, _)) = replaced {
+            self.dimensions.content.width = width;
+        }
+
         // Lay out children
-        self.layout_block_children();
+        self.layout_block_children(cx);
 
         // Calculate height based on children
         self.calculate_block_height();
+        if let Some((_, height)) = replaced {
+            self.dimensions.content.height = height;
+        }
     }
 
     /// Calculate width of a block box
@@ -133,14 +252,16 @@ impl<'a> LayoutBox<'a> {
         let auto = Value::Keyword("auto".to_string());
         let zero = Value::Length(0.0, Unit::Px);
 
-        let mut width = style.value("width").unwrap_or(&auto).clone();
+        let base = containing_block.content.width;
+        let width = of_font(style.value("width").unwrap_or(&auto).clone(), font_size(style));
+        let mut width = of_width(width, base);
 
-        let margin_left = style.lookup("margin-left", "margin", &zero);
-        let margin_right = style.lookup("margin-right", "margin", &zero);
+        let margin_left = of_width(style.lookup("margin-left", "margin", &zero), base);
+        let margin_right = of_width(style.lookup("margin-right", "margin", &zero), base);
         let border_left = style.lookup("border-left-width", "border-width", &zero);
         let border_right = style.lookup("border-right-width", "border-width", &zero);
-        let padding_left = style.lookup("padding-left", "padding", &zero);
-        let padding_right = style.lookup("padding-right", "padding", &zero);
+        let padding_left = of_width(style.lookup("padding-left", "padding", &zero), base);
+        let padding_right = of_width(style.lookup("padding-right", "padding", &zero), base);
 
         let total = [
             &margin_left,
@@ -213,11 +334,12 @@ impl<'a> LayoutBox<'a> {
         };
 
         let zero = Value::Length(0.0, Unit::Px);
+        let base = containing_block.content.width;
 
         let d = &mut self.dimensions;
 
-        d.margin.top = style.lookup("margin-top", "margin", &zero).to_px();
-        d.margin.bottom = style.lookup("margin-bottom", "margin", &zero).to_px();
+        d.margin.top = of_width(style.lookup("margin-top", "margin", &zero), base).to_px();
+        d.margin.bottom = of_width(style.lookup("margin-bottom", "margin", &zero), base).to_px();
 
         d.border.top = style
             .lookup("border-top-width", "border-width", &zero)
@@ -226,8 +348,8 @@ impl<'a> LayoutBox<'a> {
             .lookup("border-bottom-width", "border-width", &zero)
             .to_px();
 
-        d.padding.top = style.lookup("padding-top", "padding", &zero).to_px();
-        d.padding.bottom = style.lookup("padding-bottom", "padding", &zero).to_px();
+        d.padding.top = of_width(style.lookup("padding-top", "padding", &zero), base).to_px();
+        d.padding.bottom = of_width(style.lookup("padding-bottom", "padding", &zero), base).to_px();
 
         d.content.x = containing_block.content.x + d.margin.left + d.border.left + d.padding.left;
 
@@ -239,12 +361,33 @@ impl<'a> LayoutBox<'a> {
     }
 
     /// Lay out children of a block box
-    fn layout_block_children(&mut self) {
+    ///
+    /// Block-level children stack; runs of inline-level children between
+    /// them are laid out on lines. Out of flow children go with the inline
+    /// ones, which only note where they would have gone.
+    fn layout_block_children(&mut self, cx: &mut LayoutContext) {
+        let style = self.get_styled_node();
         let d = &mut self.dimensions;
-        for child in &mut self.children {
-            child.layout(*d);
+        let in_line = |child: &LayoutBox| {
+            matches!(child.box_type, BoxType::InlineNode(_)) || positioning::is_out_of_flow(child)
+        };
+        let mut index = 0;
+        while index < self.children.len() {
+            if in_line(&self.children[index]) {
+                let end = self.children[index..]
+                    .iter()
+                    .position(|child| !in_line(child))
+                    .map_or(self.children.len(), |run| index + run);
+                let offset = d.content.height;
+                d.content.height += inline::layout_inline_run(&mut self.children[index..end], d, offset, style, cx);
+                index = end;
+                continue;
+            }
+            let child = &mut self.children[index];
+            child.layout_in(*d, cx);
             // Track cumulative height
             d.content.height += child.dimensions.margin_box().height;
+            index += 1;
         }
     }
 
@@ -252,14 +395,15 @@ impl<'a> LayoutBox<'a> {
     fn calculate_block_height(&mut self) {
         // If height is explicitly set, use that
         if let Some(style) = self.get_styled_node() {
-            if let Some(Value::Length(h, Unit::Px)) = style.value("height") {
-                self.dimensions.content.height = *h;
+            let height = style.value("height").map(|height| of_font(height.clone(), font_size(style)));
+            if let Some(Value::Length(h, Unit::Px)) = height {
+                self.dimensions.content.height = h;
             }
         }
     }
 
     /// Lay out a flex container
-    fn layout_flex(&mut self, containing_block: Dimensions) {
+    fn layout_flex(&mut self, containing_block: Dimensions, cx: &mut LayoutContext) {
         // Calculate width and position (same as block)
         self.calculate_block_width(containing_block);
         self.calculate_block_position(containing_block);
@@ -274,7 +418,7 @@ impl<'a> LayoutBox<'a> {
             Some(container) => container,
             None => {
                 // Fallback to block layout if flex parsing fails
-                self.layout_block_children();
+                self.layout_block_children(cx);
                 self.calculate_block_height();
                 return;
             }
@@ -297,7 +441,7 @@ impl<'a> LayoutBox<'a> {
             if i < flex_dimensions.len() {
                 child.dimensions = flex_dimensions[i];
                 // Recursively layout child's children
-                child.layout_block_children();
+                child.layout_block_children(cx);
             }
         }
 
@@ -317,14 +461,56 @@ impl<'a> LayoutBox<'a> {
 
 /// Build the layout tree from a styled tree
 pub fn layout_tree<'a>(
+    node: &'a StyledNode<'a>,
+    containing_block: Dimensions,
+) -> LayoutBox<'a> {
+    layout_tree_with_metrics(node, containing_block, &EstimatedMetrics)
+}
+
+/// Build the layout tree from a styled tree, measuring text with `measure`
+pub fn layout_tree_with_metrics<'a>(
+    node: &'a StyledNode<'a>,
+    containing_block: Dimensions,
+    measure: &dyn TextMeasure,
+) -> LayoutBox<'a> {
+    layout_document(node, containing_block, LayoutContext {
+        measure,
+        lang: Vec::new(),
+        viewport: containing_block.content,
+        images: None,
+        base_url: None,
+    })
+}
+
+/// Build the layout tree from a styled tree, sizing images from `images`
+///
+/// Image sources are resolved against `base_url`.
+pub fn layout_tree_with_images<'a>(
+    node: &'a StyledNode<'a>,
+    containing_block: Dimensions,
+    measure: &dyn TextMeasure,
+    images: &ImageSizes,
+    base_url: Option<&Url>,
+) -> LayoutBox<'a> {
+    layout_document(node, containing_block, LayoutContext {
+        measure,
+        lang: Vec::new(),
+        viewport: containing_block.content,
+        images: Some(images),
+        base_url,
+    })
+}
+
+fn layout_document<'a>(
     node: &'a StyledNode<'a>,
     mut containing_block: Dimensions,
+    mut cx: LayoutContext,
 ) -> LayoutBox<'a> {
     // Initialize containing block
     containing_block.content.height = 0.0;
 
     let mut root_box = build_layout_tree(node);
-    root_box.layout(containing_block);
+    root_box.layout_root(containing_block, &mut cx);
     root_box
 }
 
@@ -341,10 +527,7 @@ fn build_layout_tree<'a>(style_node: &'a StyledNode<'a>) -> LayoutBox<'a> {
         match child.display() {
             Display::Block => root.children.push(build_layout_tree(child)),
             Display::Flex => root.children.push(build_layout_tree(child)),
-            Display::Inline => {
-                // For simplicity, treat inline as block for now
-                root.children.push(build_layout_tree(child))
-            }
+            Display::Inline => root.children.push(build_layout_tree(child)),
             Display::None => {} // Skip nodes with display: none
         }
     }
@@ -352,6 +535,38 @@ fn build_layout_tree<'a>(style_node: &'a StyledNode<'a>) -> LayoutBox<'a> {
     root
 }
 
+/// A value with percentages and `calc()` resolved against the containing block's width
+fn of_width(value: Value, containing_width: f32) -> Value {
+    match value {
+        Value::Percentage(_) | Value::Length(_, Unit::Percent) | Value::Function(..) => value
+            .resolve_length(Some(containing_width))
+            .map_or(value, |px| Value::Length(px, Unit::Px)),
+        _ => value,
+    }
+}
+
+/// A value with `em` and `rem` lengths, also those inside `calc()`, in pixels
+///
+/// `rem` is relative to the initial font size, as when styles are computed.
+fn of_font(value: Value, font_size: f32) -> Value {
+    match value {
+        Value::Length(length, Unit::Em) => Value::Length(length * font_size, Unit::Px),
+        Value::Length(length, Unit::Rem) => Value::Length(length * INITIAL_FONT_SIZE, Unit::Px),
+        Value::Function(name, arguments) => {
+            Value::Function(name, arguments.into_iter().map(|argument| of_font(argument, font_size)).collect())
+        }
+        value => value,
+    }
+}
+
+/// Computed font size of a box in pixels
+fn font_size(style: &StyledNode) -> f32 {
+    match style.value("font-size") {
+        Some(Value::Length(size, Unit::Px)) => *size,
+        _ => INITIAL_FONT_SIZE,
+    }
+}
+
 /// Extension trait to convert CSS values to pixels
 trait ToPx {
     fn to_px(&self) -> f32;
@@ -390,4 +605,91 @@ mod tests {
         assert_eq!(layout.dimensions.content.width, 100.0);
         assert_eq!(layout.dimensions.content.height, 50.0);
     }
+
+    #[test]
+    fn test_percentages_calc_and_box_shorthands() {
+        let html = Node::element("div".to_string(), HashMap::new(), vec![]);
+        let css = CssParser::parse("div { width: calc(50% - 20px); margin: 5px 10% 0; padding: 1px 2px 3px 4px; }");
+        let styled = style_tree(&html, &css);
+
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+
+        let d = layout_tree(&styled, viewport).dimensions;
+        assert_eq!(d.content.width, 380.0);
+        assert_eq!((d.margin.top, d.margin.right, d.margin.bottom, d.margin.left), (5.0, 80.0, 0.0, 80.0));
+        assert_eq!((d.padding.top, d.padding.right, d.padding.bottom, d.padding.left), (1.0, 2.0, 3.0, 4.0));
+    }
+
+    #[test]
+    fn test_hit_test() {
+        let mut attrs = HashMap::new();
+        attrs.insert("id".to_string(), "inner".to_string());
+        let html = Node::element(
+            "div".to_string(),
+            HashMap::new(),
+            vec![Node::element("p".to_string(), attrs, vec![])],
+        );
+        let css = CssParser::parse("div { display: block; } p { display: block; height: 20px; }");
+        let styled = style_tree(&html, &css);
+
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+        let layout = layout_tree(&styled, viewport);
+
+        let path = layout.hit_test(10.0, 10.0);
+        assert_eq!(path.len(), 2);
+        let hit = path.last().unwrap().get_styled_node().unwrap();
+        assert_eq!(hit.node.element_data().unwrap().id(), Some("inner"));
+
+        assert!(layout.hit_test(900.0, 10.0).is_empty());
+    }
+
+    #[test]
+    fn test_positioned_boxes() {
+        let element = |tag: &str, class: &str, children: Vec<Node>| {
+            let attrs = HashMap::from([("class".to_string(), class.to_string())]);
+            Node::element(tag.to_string(), attrs, children)
+        };
+        let html = element(
+            "div",
+            "frame",
+            vec![
+                element("p", "", vec![]),
+                element("span", "badge", vec![]),
+                element("p", "nudged", vec![]),
+                element("em", "label", vec![Node::text("hello".to_string())]),
+                element("div", "bar", vec![]),
+            ],
+        );
+        let css = CssParser::parse(
+            "div { display: block; } p { display: block; height: 20px; } \
+             .frame { position: relative; width: 400px; padding: 10px; } \
+             .badge { position: absolute; top: 5px; right: 5px; width: 50px; height: 30px; } \
+             .nudged { position: relative; top: 3px; left: 4px; } \
+             .label { position: absolute; left: 0px; bottom: 0px; } \
+             .bar { position: fixed; bottom: 0px; left: 0px; width: 100px; height: 10px; }",
+        );
+        let styled = style_tree(&html, &css);
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+        viewport.content.height = 600.0;
+        let layout = layout_tree(&styled, viewport);
+        let content = |index: usize| layout.children[index].dimensions.content;
+
+        // Out of flow boxes take no room: the frame holds the two paragraphs
+        assert_eq!(layout.dimensions.padding_box(), Rect { x: 0.0, y: 0.0, width: 420.0, height: 60.0 });
+        assert_eq!((content(0).x, content(0).y), (10.0, 10.0));
+        // Against the frame's padding box, from its top right corner
+        assert_eq!(content(1), Rect { x: 365.0, y: 5.0, width: 50.0, height: 30.0 });
+        // Shifted from where it would have been
+        assert_eq!((content(2).x, content(2).y), (14.0, 33.0));
+        // As wide as its text, which moves with it
+        let label = &layout.children[3];
+        assert_eq!((label.dimensions.content.x, label.dimensions.content.width), (0.0, 40.0));
+        assert_eq!(label.dimensions.margin_box().y + label.dimensions.margin_box().height, 60.0);
+        assert_eq!(label.children[0].fragments[0].rect.x, 0.0);
+        // Against the viewport
+        assert_eq!(content(4), Rect { x: 0.0, y: 590.0, width: 100.0, height: 10.0 });
+    }
 }
@@ -0,0 +1,376 @@
+// Overflow - boxes that clip their descendants and scroll them
+//
+// A block or flex box whose `overflow` is `hidden`, `scroll` or `auto`
+// clips what its descendants paint to its padding box. `scroll` and `auto`
+// boxes can also be scrolled, which moves their descendants up and left
+// under the clip. Offsets live in `BoxScrolls`, keyed by the element's path
+// in the document, so they survive the page being laid out again. Overflow
+// on <html> and <body> belongs to the viewport, which the browser scrolls
+// itself.
+
+use std::collections::HashMap;
+
+use super::{LayoutBox, Rect};
+use crate::animation::{element_path, ElementPath};
+use crate::css::Value;
+use crate::dom::Node;
+
+/// Width of a scrollbar thumb, in CSS pixels
+const THUMB_WIDTH: f32 = 6.0;
+/// Gap between a thumb and the edges of the box it scrolls
+const THUMB_MARGIN: f32 = 2.0;
+/// Thumbs never get shorter than this, however long the content
+const MIN_THUMB_LENGTH: f32 = 20.0;
+
+/// CSS overflow property values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Descendants paint outside the box
+    #[default]
+    Visible,
+    /// Descendants are clipped and cannot be scrolled into view by the user
+    Hidden,
+    /// Descendants are clipped and can be scrolled
+    Scroll,
+    /// Like `scroll`; thumbs only show when there is something to scroll
+    Auto,
+}
+
+impl Overflow {
+    /// The overflow a box clips its descendants with
+    pub fn of(layout_box: &LayoutBox) -> Self {
+        let styled = match layout_box.box_type {
+            super::BoxType::BlockNode(styled) | super::BoxType::FlexNode(styled) => styled,
+            _ => return Overflow::Visible,
+        };
+        let viewport_element = styled
+            .node
+            .element_data()
+            .is_some_and(|element| matches!(element.tag_name.to_lowercase().as_str(), "html" | "body"));
+        if viewport_element {
+            return Overflow::Visible;
+        }
+        match styled.value("overflow") {
+            Some(Value::Keyword(keyword)) => match keyword.as_str() {
+                "hidden" | "clip" => Overflow::Hidden,
+                "scroll" => Overflow::Scroll,
+                "auto" | "overlay" => Overflow::Auto,
+                _ => Overflow::Visible,
+            },
+            _ => Overflow::Visible,
+        }
+    }
+
+    /// Whether descendants are clipped to the padding box
+    pub fn clips(self) -> bool {
+        self != Overflow::Visible
+    }
+
+    /// Whether the user can scroll the box
+    pub fn scrolls(self) -> bool {
+        matches!(self, Overflow::Scroll | Overflow::Auto)
+    }
+}
+
+/// A box that clips its descendants
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScrollContainer {
+    /// Path to the box's element from the document
+    pub element: ElementPath,
+    pub overflow: Overflow,
+    /// The box's padding box, where its descendants are visible
+    pub padding_box: Rect,
+    /// The part of the padding box the user sees, once the containers
+    /// around it are scrolled and have clipped it
+    pub clip: Rect,
+    /// Width and height of the padding box and everything overflowing it
+    pub scroll_width: f32,
+    pub scroll_height: f32,
+}
+
+impl ScrollContainer {
+    /// The container a box forms, if it clips its descendants
+    pub fn of(layout_box: &LayoutBox, element: ElementPath) -> Option<Self> {
+        let overflow = Overflow::of(layout_box);
+        if !overflow.clips() {
+            return None;
+        }
+        let padding_box = layout_box.dimensions.padding_box();
+        let mut extent = padding_box;
+        content_extent(layout_box, &mut extent);
+        Some(Self {
+            element,
+            overflow,
+            padding_box,
+            clip: padding_box,
+            scroll_width: extent.x + extent.width - padding_box.x,
+            scroll_height: extent.y + extent.height - padding_box.y,
+        })
+    }
+
+    /// Furthest the box can be scrolled right and down
+    pub fn max_scroll(&self) -> (f32, f32) {
+        (
+            (self.scroll_width - self.padding_box.width).max(0.0),
+            (self.scroll_height - self.padding_box.height).max(0.0),
+        )
+    }
+
+    /// Whether the user can scroll the box any distance
+    pub fn is_scrollable(&self) -> bool {
+        let (max_x, max_y) = self.max_scroll();
+        self.overflow.scrolls() && (max_x > 0.0 || max_y > 0.0)
+    }
+
+    /// Scrollbar thumbs for the box scrolled to `offset`: one along the
+    /// right edge if it scrolls vertically, one along the bottom if it
+    /// scrolls horizontally
+    pub fn scrollbar_thumbs(&self, offset: (f32, f32)) -> Vec<Rect> {
+        if !self.overflow.scrolls() {
+            return Vec::new();
+        }
+        let (max_x, max_y) = self.max_scroll();
+        let b = &self.padding_box;
+        let mut thumbs = Vec::new();
+        if max_y > 0.0 {
+            let track = b.height - 2.0 * THUMB_MARGIN;
+            let (start, length) = thumb(track, b.height / self.scroll_height, offset.1 / max_y);
+            thumbs.push(Rect {
+                x: b.x + b.width - THUMB_WIDTH - THUMB_MARGIN,
+                y: b.y + THUMB_MARGIN + start,
+                width: THUMB_WIDTH,
+                height: length,
+            });
+        }
+        if max_x > 0.0 {
+            let track = b.width - 2.0 * THUMB_MARGIN;
+            let (start, length) = thumb(track, b.width / self.scroll_width, offset.0 / max_x);
+            thumbs.push(Rect {
+                x: b.x + THUMB_MARGIN + start,
+                y: b.y + b.height - THUMB_WIDTH - THUMB_MARGIN,
+                width: length,
+                height: THUMB_WIDTH,
+            });
+        }
+        thumbs
+    }
+}
+
+/// Where a thumb starts along its track and how long it is, for the
+/// fraction of the content visible and how far it is scrolled from 0 to 1
+fn thumb(track: f32, visible: f32, scrolled: f32) -> (f32, f32) {
+    let track = track.max(0.0);
+    let length = (track * visible).max(MIN_THUMB_LENGTH).min(track);
+    ((track - length) * scrolled.clamp(0.0, 1.0), length)
+}
+
+/// Grow `extent` to hold the boxes and text of a box's descendants
+///
+/// Descendants of nested containers stay inside them, so only the nested
+/// container itself counts.
+fn content_extent(layout_box: &LayoutBox, extent: &mut Rect) {
+    for child in &layout_box.children {
+        let areas = std::iter::once(child.dimensions.margin_box()).chain(child.fragments.iter().map(|f| f.rect));
+        for area in areas.filter(|area| area.width > 0.0 || area.height > 0.0) {
+            *extent = union(extent, &area);
+        }
+        if !Overflow::of(child).clips() {
+            content_extent(child, extent);
+        }
+    }
+}
+
+fn union(a: &Rect, b: &Rect) -> Rect {
+    let x = a.x.min(b.x);
+    let y = a.y.min(b.y);
+    let right = (a.x + a.width).max(b.x + b.width);
+    let bottom = (a.y + a.height).max(b.y + b.height);
+    Rect { x, y, width: right - x, height: bottom - y }
+}
+
+/// The boxes of a laid out page that clip their descendants, outermost
+/// first, as they are painted when scrolled by `scrolls`
+///
+/// A nested container's clip is its padding box moved by the offsets of
+/// the containers around it and cut down to the part their clips leave
+/// visible, so it is where the user sees it.
+pub fn scroll_containers(layout_root: &LayoutBox, scrolls: &BoxScrolls) -> Vec<ScrollContainer> {
+    let mut containers = Vec::new();
+    if let Some(styled) = layout_root.get_styled_node() {
+        collect_containers(layout_root, styled.node, scrolls, (0.0, 0.0), None, &mut containers);
+    }
+    containers
+}
+
+fn collect_containers(
+    layout_box: &LayoutBox,
+    document: &Node,
+    scrolls: &BoxScrolls,
+    mut shift: (f32, f32),
+    mut clip: Option<Rect>,
+    containers: &mut Vec<ScrollContainer>,
+) {
+    if Overflow::of(layout_box).clips() {
+        let element = layout_box.get_styled_node().and_then(|styled| element_path(document, styled.node));
+        if let Some(mut container) = element.and_then(|element| ScrollContainer::of(layout_box, element)) {
+            container.clip.x -= shift.0;
+            container.clip.y -= shift.1;
+            if let Some(outer) = clip {
+                container.clip = container.clip.intersection(&outer);
+            }
+            let offset = scrolls.offset(&container.element);
+            shift = (shift.0 + offset.0, shift.1 + offset.1);
+            clip = Some(container.clip);
+            containers.push(container);
+        }
+    }
+    for child in &layout_box.children {
+        collect_containers(child, document, scrolls, shift, clip, containers);
+    }
+}
+
+/// How far each scrollable box of a page is scrolled
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BoxScrolls {
+    offsets: HashMap<ElementPath, (f32, f32)>,
+}
+
+impl BoxScrolls {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How far an element is scrolled right and down
+    pub fn offset(&self, element: &[usize]) -> (f32, f32) {
+        self.offsets.get(element).copied().unwrap_or((0.0, 0.0))
+    }
+
+    /// Scroll the innermost scrollable container under a point by `dx`, `dy`
+    ///
+    /// A container already scrolled as far as it goes passes the scroll on
+    /// to the container around it. Returns whether any container moved;
+    /// when none did the viewport should scroll instead.
+    pub fn scroll_at(&mut self, containers: &[ScrollContainer], x: f32, y: f32, dx: f32, dy: f32) -> bool {
+        // Containers are listed outermost first, so the last one under the point is innermost
+        for container in containers.iter().rev() {
+            if !container.is_scrollable() || !container.clip.contains(x, y) {
+                continue;
+            }
+            let (max_x, max_y) = container.max_scroll();
+            let (old_x, old_y) = self.offset(&container.element);
+            let offset = ((old_x + dx).clamp(0.0, max_x), (old_y + dy).clamp(0.0, max_y));
+            if offset != (old_x, old_y) {
+                self.offsets.insert(container.element.clone(), offset);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Keep offsets within what each container can scroll after the page
+    /// is laid out again, forgetting containers that are gone
+    pub fn clamp(&mut self, containers: &[ScrollContainer]) {
+        self.offsets.retain(|element, offset| {
+            let Some(container) = containers.iter().find(|c| c.element == *element && c.overflow.scrolls()) else {
+                return false;
+            };
+            let (max_x, max_y) = container.max_scroll();
+            *offset = (offset.0.clamp(0.0, max_x), offset.1.clamp(0.0, max_y));
+            true
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::CssParser;
+    use crate::dom::Node;
+    use crate::layout::{layout_tree, Dimensions};
+    use crate::style::style_tree;
+    use std::collections::HashMap;
+
+    fn element(tag: &str, id: &str, children: Vec<Node>) -> Node {
+        let mut attributes = HashMap::new();
+        attributes.insert("id".to_string(), id.to_string());
+        Node::element(tag.to_string(), attributes, children)
+    }
+
+    fn with_layout(css: &str, f: impl FnOnce(&LayoutBox)) {
+        // A list 400px tall inside a 100px box that scrolls, itself inside one that hides
+        let items = (0..4).map(|i| element("div", &format!("item{}", i), vec![])).collect();
+        let list = element("div", "list", vec![element("div", "inner", items)]);
+        let root = element("div", "outer", vec![list]);
+        let stylesheet = CssParser::parse(css);
+        let styled = style_tree(&root, &stylesheet);
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+        viewport.content.height = 600.0;
+        let layout = layout_tree(&styled, viewport);
+        f(&layout);
+    }
+
+    const CSS: &str = "div { display: block; } \
+        #outer { overflow: hidden; height: 300px; } \
+        #list { overflow: auto; height: 100px; } \
+        #inner div { height: 100px; }";
+
+    #[test]
+    fn test_containers_and_extent() {
+        with_layout(CSS, |layout| {
+            let containers = scroll_containers(layout, &BoxScrolls::new());
+            assert_eq!(containers.len(), 2);
+            assert_eq!(containers[0].overflow, Overflow::Hidden);
+            assert!(!containers[0].is_scrollable());
+
+            let list = &containers[1];
+            assert_eq!(list.element, vec![0]);
+            assert_eq!(list.overflow, Overflow::Auto);
+            assert_eq!(list.scroll_height, 400.0);
+            assert_eq!(list.max_scroll(), (0.0, 300.0));
+
+            let thumbs = list.scrollbar_thumbs((0.0, 300.0));
+            assert_eq!(thumbs.len(), 1);
+            // A quarter of the content is visible, and the thumb sits at the bottom of its track
+            assert_eq!(thumbs[0].height, 24.0);
+            assert_eq!(thumbs[0].y + thumbs[0].height, list.padding_box.y + 100.0 - THUMB_MARGIN);
+        });
+    }
+
+    #[test]
+    fn test_wheel_scrolls_innermost_and_clamps() {
+        with_layout(CSS, |layout| {
+            let containers = scroll_containers(layout, &BoxScrolls::new());
+            let mut scrolls = BoxScrolls::new();
+
+            // Outside the list nothing scrolls, and the hidden box is not user-scrollable
+            assert!(!scrolls.scroll_at(&containers, 10.0, 250.0, 0.0, 50.0));
+            assert!(scrolls.scroll_at(&containers, 10.0, 50.0, 0.0, 50.0));
+            assert_eq!(scrolls.offset(&[0]), (0.0, 50.0));
+            assert!(scrolls.scroll_at(&containers, 10.0, 50.0, 0.0, 1000.0));
+            assert_eq!(scrolls.offset(&[0]), (0.0, 300.0));
+            // Scrolled to the end, the wheel goes on to the viewport
+            assert!(!scrolls.scroll_at(&containers, 10.0, 50.0, 0.0, 10.0));
+
+            scrolls.clamp(&containers[..1]);
+            assert_eq!(scrolls.offset(&[0]), (0.0, 0.0));
+        });
+    }
+
+    #[test]
+    fn test_nested_container_moves_with_its_scroller() {
+        let css = "div { display: block; } \
+            #outer { overflow: scroll; height: 100px; } \
+            #list { overflow: auto; height: 100px; margin-top: 50px; } \
+            #inner div { height: 100px; }";
+        with_layout(css, |layout| {
+            let mut scrolls = BoxScrolls::new();
+            scrolls.offsets.insert(Vec::new(), (0.0, 30.0));
+            let containers = scroll_containers(layout, &scrolls);
+            // Scrolled up 30px and cut to the 100px the outer box shows
+            assert_eq!(containers[1].clip.y, 20.0);
+            assert_eq!(containers[1].clip.height, 80.0);
+            assert_eq!(containers[1].padding_box.y, 50.0);
+        });
+    }
+}
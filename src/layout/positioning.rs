@@ -1,7 +1,15 @@
 // CSS Positioning implementation
+//
+// Absolutely positioned and fixed boxes are out of flow: the boxes around
+// them are laid out as if they were not there, and they only note where
+// they would have gone, their static position. Once the whole tree is laid
+// out, relatively positioned boxes are shifted by their offsets, and out of
+// flow boxes are laid out against their containing block: the padding box
+// of the nearest positioned ancestor, or the viewport for fixed boxes and
+// boxes with no positioned ancestor.
 
 use crate::css::{Value, Unit};
-use crate::layout::{Dimensions, Rect};
+use crate::layout::{font_size, of_font, Dimensions, LayoutBox, LayoutContext, Rect};
 use crate::style::StyledNode;
 
 /// CSS position property values
@@ -56,7 +64,7 @@ impl PositionedElement {
     /// Parse positioning properties from a styled node
     pub fn from_styled_node(node: &StyledNode) -> Self {
         let position = Self::parse_position(node);
-        let offsets = Self::parse_offsets(node);
+        let offsets = Self::parse_offsets(node, None);
         let z_index = Self::parse_z_index(node);
 
         Self {
@@ -82,21 +90,28 @@ impl PositionedElement {
     }
 
     /// Parse offset properties (top, right, bottom, left)
-    fn parse_offsets(node: &StyledNode) -> Offsets {
+    ///
+    /// Percentages and `calc()` resolve against the containing block: the
+    /// left and right insets against its width, top and bottom against its
+    /// height. With no containing block they count as `auto`. `em` and
+    /// `rem` resolve against the font size.
+    fn parse_offsets(node: &StyledNode, containing: Option<Rect>) -> Offsets {
+        let width = containing.map(|rect| rect.width);
+        let height = containing.map(|rect| rect.height);
         Offsets {
-            top: Self::parse_offset(node, "top"),
-            right: Self::parse_offset(node, "right"),
-            bottom: Self::parse_offset(node, "bottom"),
-            left: Self::parse_offset(node, "left"),
+            top: Self::parse_offset(node, "top", height),
+            right: Self::parse_offset(node, "right", width),
+            bottom: Self::parse_offset(node, "bottom", height),
+            left: Self::parse_offset(node, "left", width),
         }
     }
 
     /// Parse a single offset property
-    fn parse_offset(node: &StyledNode, property: &str) -> Option<f32> {
+    fn parse_offset(node: &StyledNode, property: &str, percent_base: Option<f32>) -> Option<f32> {
         match node.value(property) {
-            Some(Value::Length(val, Unit::Px)) => Some(*val),
             Some(Value::Keyword(s)) if s == "auto" => None,
-            _ => None,
+            Some(value) => of_font(value.clone(), font_size(node)).resolve_length(percent_base),
+            None => None,
         }
     }
 
@@ -114,92 +129,180 @@ impl PositionedElement {
     pub fn is_positioned(&self) -> bool {
         self.position != Position::Static
     }
+}
 
-    /// Apply positioning to dimensions
-    pub fn apply_positioning(
-        &self,
-        dimensions: &mut Dimensions,
-        containing_block: &Dimensions,
-        viewport: &Rect,
-    ) {
-        match self.position {
-            Position::Static => {
-                // No adjustment needed
-            }
-            Position::Relative => {
-                self.apply_relative_positioning(dimensions);
-            }
-            Position::Absolute => {
-                self.apply_absolute_positioning(dimensions, containing_block);
-            }
-            Position::Fixed => {
-                self.apply_fixed_positioning(dimensions, viewport);
-            }
-            Position::Sticky => {
-                // Sticky is complex - for now treat as relative
-                // TODO: Implement proper sticky behavior with scroll tracking
-                self.apply_relative_positioning(dimensions);
-            }
-        }
-    }
+/// How a box is positioned; anonymous boxes are static
+fn positioning(layout_box: &LayoutBox) -> PositionedElement {
+    layout_box.get_styled_node().map(PositionedElement::from_styled_node).unwrap_or_default()
+}
 
-    /// Apply relative positioning (offset from normal position)
-    fn apply_relative_positioning(&self, dimensions: &mut Dimensions) {
-        // Apply offsets to the positioned element
-        if let Some(top) = self.offsets.top {
-            dimensions.content.y += top;
-        } else if let Some(bottom) = self.offsets.bottom {
-            dimensions.content.y -= bottom;
-        }
+/// A box's insets, resolved against its containing block
+fn offsets_in(layout_box: &LayoutBox, containing: Rect) -> Offsets {
+    layout_box
+        .get_styled_node()
+        .map(|styled| PositionedElement::parse_offsets(styled, Some(containing)))
+        .unwrap_or_default()
+}
 
-        if let Some(left) = self.offsets.left {
-            dimensions.content.x += left;
-        } else if let Some(right) = self.offsets.right {
-            dimensions.content.x -= right;
-        }
-    }
+/// Whether a width or height is `auto`, as it is when not given
+fn is_auto(value: Option<&Value>) -> bool {
+    value.is_none_or(|value| matches!(value, Value::Keyword(keyword) if keyword == "auto"))
+}
 
-    /// Apply absolute positioning (relative to containing block)
-    fn apply_absolute_positioning(&self, dimensions: &mut Dimensions, containing_block: &Dimensions) {
-        let cb = &containing_block.content;
+/// Whether a box is taken out of normal flow: absolutely positioned or fixed
+pub(super) fn is_out_of_flow(layout_box: &LayoutBox) -> bool {
+    let position = match layout_box.get_styled_node() {
+        Some(styled) => PositionedElement::parse_position(styled),
+        None => Position::Static,
+    };
+    matches!(position, Position::Absolute | Position::Fixed)
+}
 
-        // Start with containing block position
-        dimensions.content.x = cb.x;
-        dimensions.content.y = cb.y;
+/// Whether a box is fixed to the viewport, staying put as the page scrolls
+pub fn is_fixed(layout_box: &LayoutBox) -> bool {
+    layout_box
+        .get_styled_node()
+        .is_some_and(|styled| PositionedElement::parse_position(styled) == Position::Fixed)
+}
 
-        // Apply offsets
-        if let Some(left) = self.offsets.left {
-            dimensions.content.x = cb.x + left;
-        } else if let Some(right) = self.offsets.right {
-            dimensions.content.x = cb.x + cb.width - dimensions.content.width - right;
+/// Move fixed boxes by how far the page is scrolled, to where they are seen
+///
+/// Layout places fixed boxes against the unscrolled viewport. Hit testing
+/// a scrolled page adds the scroll offset to the point, so it only finds
+/// them once they have been moved along with it.
+pub fn pin_fixed_boxes(layout_box: &mut LayoutBox, offset_x: f32, offset_y: f32) {
+    for child in &mut layout_box.children {
+        if is_fixed(child) {
+            translate(child, offset_x, offset_y);
+        } else {
+            pin_fixed_boxes(child, offset_x, offset_y);
         }
+    }
+}
 
-        if let Some(top) = self.offsets.top {
-            dimensions.content.y = cb.y + top;
-        } else if let Some(bottom) = self.offsets.bottom {
-            dimensions.content.y = cb.y + cb.height - dimensions.content.height - bottom;
-        }
+/// Move a box and everything inside it
+pub(super) fn translate(layout_box: &mut LayoutBox, dx: f32, dy: f32) {
+    layout_box.dimensions.content.x += dx;
+    layout_box.dimensions.content.y += dy;
+    for fragment in &mut layout_box.fragments {
+        fragment.rect.x += dx;
+        fragment.rect.y += dy;
+        fragment.baseline += dy;
+    }
+    for child in &mut layout_box.children {
+        translate(child, dx, dy);
     }
+}
 
-    /// Apply fixed positioning (relative to viewport)
-    fn apply_fixed_positioning(&self, dimensions: &mut Dimensions, viewport: &Rect) {
-        // Start with viewport position
-        dimensions.content.x = viewport.x;
-        dimensions.content.y = viewport.y;
-
-        // Apply offsets
-        if let Some(left) = self.offsets.left {
-            dimensions.content.x = viewport.x + left;
-        } else if let Some(right) = self.offsets.right {
-            dimensions.content.x = viewport.x + viewport.width - dimensions.content.width - right;
+/// Offset the relatively positioned descendants of a laid out box and lay
+/// out its out of flow descendants
+///
+/// `containing` is the padding box of the box's nearest positioned
+/// ancestor, or the viewport.
+pub(super) fn layout_positioned(layout_box: &mut LayoutBox, containing: Rect, cx: &mut LayoutContext) {
+    let containing = if positioning(layout_box).is_positioned() {
+        layout_box.dimensions.padding_box()
+    } else {
+        containing
+    };
+    // In flow children's containing block is this box's content box
+    let content = layout_box.dimensions.content;
+    for child in &mut layout_box.children {
+        match positioning(child).position {
+            Position::Static => {}
+            Position::Relative | Position::Sticky => {
+                let offsets = offsets_in(child, content);
+                let dx = offsets.left.or(offsets.right.map(|right| -right)).unwrap_or(0.0);
+                let dy = offsets.top.or(offsets.bottom.map(|bottom| -bottom)).unwrap_or(0.0);
+                translate(child, dx, dy);
+            }
+            Position::Absolute => layout_absolute(child, &offsets_in(child, containing), containing, cx),
+            Position::Fixed => {
+                let viewport = cx.viewport;
+                layout_absolute(child, &offsets_in(child, viewport), viewport, cx)
+            }
         }
+        layout_positioned(child, containing, cx);
+    }
+}
 
-        if let Some(top) = self.offsets.top {
-            dimensions.content.y = viewport.y + top;
-        } else if let Some(bottom) = self.offsets.bottom {
-            dimensions.content.y = viewport.y + viewport.height - dimensions.content.height - bottom;
+/// Lay out an out of flow box against its containing block
+///
+/// Insets that are `auto` leave the box at its static position on that
+/// axis. A box with `width: auto` is as wide as its content, unless both
+/// `left` and `right` are given and stretch it; `top` and `bottom` stretch
+/// one with `height: auto` the same way. Percentage sizes are of the
+/// containing block.
+fn layout_absolute(layout_box: &mut LayoutBox, offsets: &Offsets, containing: Rect, cx: &mut LayoutContext) {
+    // Flow layout left the box where it would have gone
+    let static_position = (layout_box.dimensions.content.x, layout_box.dimensions.content.y);
+    let styled = layout_box.get_styled_node();
+    let auto_width = is_auto(styled.and_then(|s| s.value("width")));
+    let auto_height = is_auto(styled.and_then(|s| s.value("height")));
+    let height = styled
+        .filter(|_| !auto_height)
+        .and_then(|s| of_font(s.value("height")?.clone(), font_size(s)).resolve_length(Some(containing.height)));
+    let stretch_x = offsets.left.is_some() && offsets.right.is_some();
+
+    // A given width is resolved against the containing block, an auto one
+    // fills what the insets leave of it
+    let available = if auto_width {
+        containing.width - offsets.left.unwrap_or(0.0) - offsets.right.unwrap_or(0.0)
+    } else {
+        containing.width
+    };
+    let mut block = Dimensions {
+        content: Rect { x: containing.x, y: containing.y, width: available.max(0.0), height: 0.0 },
+        ..Dimensions::default()
+    };
+    layout_box.dimensions = Dimensions::default();
+    layout_box.layout_in(block, cx);
+    if auto_width && !stretch_x {
+        let d = layout_box.dimensions;
+        let edges = d.margin_box().width - d.content.width;
+        let shrunk = content_extent(layout_box) + edges;
+        if shrunk < block.content.width {
+            block.content.width = shrunk;
+            layout_box.dimensions = Dimensions::default();
+            layout_box.layout_in(block, cx);
         }
     }
+    if let Some(height) = height {
+        layout_box.dimensions.content.height = height.max(0.0);
+    } else if let (true, Some(top), Some(bottom)) = (auto_height, offsets.top, offsets.bottom) {
+        let d = &mut layout_box.dimensions;
+        let edges = d.margin_box().height - d.content.height;
+        d.content.height = (containing.height - top - bottom - edges).max(0.0);
+    }
+
+    let outer = layout_box.dimensions.margin_box();
+    let x = match (offsets.left, offsets.right) {
+        (Some(left), _) => containing.x + left,
+        (None, Some(right)) => containing.x + containing.width - right - outer.width,
+        (None, None) => static_position.0,
+    };
+    let y = match (offsets.top, offsets.bottom) {
+        (Some(top), _) => containing.y + top,
+        (None, Some(bottom)) => containing.y + containing.height - bottom - outer.height,
+        (None, None) => static_position.1,
+    };
+    translate(layout_box, x - outer.x, y - outer.y);
+}
+
+/// How far a laid out box's content reaches from the left of its content box
+fn content_extent(layout_box: &LayoutBox) -> f32 {
+    let left = layout_box.dimensions.content.x;
+    layout_box.children.iter().map(used_right).fold(left, f32::max) - left
+}
+
+/// Right edge of what a box draws; boxes with no set width are only as
+/// wide as their content, not as the block they were laid out in
+fn used_right(layout_box: &LayoutBox) -> f32 {
+    let d = layout_box.dimensions;
+    let sized = layout_box.get_styled_node().is_some_and(|styled| !is_auto(styled.value("width")));
+    let inner = if sized { d.content.x + d.content.width } else { d.content.x + content_extent(layout_box) };
+    let inner = layout_box.fragments.iter().map(|fragment| fragment.rect.x + fragment.rect.width).fold(inner, f32::max);
+    inner + d.padding.right + d.border.right + d.margin.right
 }
 
 #[cfg(test)]
@@ -242,123 +345,119 @@ mod tests {
     }
 
     #[test]
-    fn test_relative_positioning() {
-        let mut dims = Dimensions::default();
-        dims.content.x = 100.0;
-        dims.content.y = 100.0;
-        dims.content.width = 50.0;
-        dims.content.height = 50.0;
+    fn test_offsets_resolve_against_containing_block() {
+        use crate::css::CssParser;
+        use crate::dom::Node;
+        use crate::style::style_tree;
+        use std::collections::HashMap;
+
+        let attrs = HashMap::from([("class".to_string(), "inset".to_string())]);
+        let html = Node::element("div".to_string(), attrs, vec![]);
+        let css = CssParser::parse(".inset { top: 50%; left: calc(10% + 5px); right: auto; bottom: 0; }");
+        let styled = style_tree(&html, &css);
+
+        let offsets = PositionedElement::parse_offsets(&styled, Some(Rect { x: 0.0, y: 0.0, width: 400.0, height: 200.0 }));
+        assert_eq!((offsets.top, offsets.left), (Some(100.0), Some(45.0)));
+        assert_eq!((offsets.right, offsets.bottom), (None, Some(0.0)));
+        // Percentages wait for a containing block
+        assert_eq!(PositionedElement::from_styled_node(&styled).offsets.top, None);
+    }
 
-        let positioned = PositionedElement {
-            position: Position::Relative,
-            offsets: Offsets {
-                top: Some(20.0),
-                left: Some(30.0),
-                bottom: None,
-                right: None,
-            },
-            z_index: 0,
+    /// Lay out `.child`, holding `.grandchild` and some text, in a 400x400
+    /// positioned `.frame` at (200, 200) of an 800x600 viewport
+    fn layout_child<T>(css: &str, inspect: impl FnOnce(&LayoutBox) -> T) -> T {
+        use crate::css::CssParser;
+        use crate::dom::Node;
+        use crate::layout::layout_tree;
+        use crate::style::style_tree;
+        use std::collections::HashMap;
+
+        let element = |class: &str, children: Vec<Node>| {
+            let attrs = HashMap::from([("class".to_string(), class.to_string())]);
+            Node::element("div".to_string(), attrs, children)
         };
+        let child = element("child", vec![element("grandchild", vec![]), Node::text("hello".to_string())]);
+        let html = element("page", vec![element("frame", vec![child])]);
+        let css = CssParser::parse(&format!(
+            "div {{ display: block; }} \
+             .frame {{ position: relative; left: 200px; top: 200px; width: 400px; height: 400px; }} {}",
+            css
+        ));
+        let styled = style_tree(&html, &css);
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+        viewport.content.height = 600.0;
+        let layout = layout_tree(&styled, viewport);
+        inspect(&layout.children[0].children[0])
+    }
 
-        let viewport = Rect::default();
-        let containing_block = Dimensions::default();
-        positioned.apply_positioning(&mut dims, &containing_block, &viewport);
+    fn child_box(css: &str) -> Rect {
+        layout_child(css, |child| child.dimensions.content)
+    }
 
-        assert_eq!(dims.content.x, 130.0); // 100 + 30
-        assert_eq!(dims.content.y, 120.0); // 100 + 20
+    #[test]
+    fn test_relative_positioning() {
+        let rect = child_box(".child { position: relative; top: 20px; left: 30px; width: 50px; height: 50px; }");
+        // Shifted from its place at the top left of the frame
+        assert_eq!((rect.x, rect.y), (230.0, 220.0));
     }
 
     #[test]
     fn test_absolute_positioning() {
-        let mut dims = Dimensions::default();
-        dims.content.width = 50.0;
-        dims.content.height = 50.0;
-
-        let mut containing_block = Dimensions::default();
-        containing_block.content.x = 200.0;
-        containing_block.content.y = 200.0;
-        containing_block.content.width = 400.0;
-        containing_block.content.height = 400.0;
-
-        let positioned = PositionedElement {
-            position: Position::Absolute,
-            offsets: Offsets {
-                top: Some(10.0),
-                left: Some(20.0),
-                bottom: None,
-                right: None,
-            },
-            z_index: 0,
-        };
-
-        let viewport = Rect::default();
-        positioned.apply_positioning(&mut dims, &containing_block, &viewport);
-
-        assert_eq!(dims.content.x, 220.0); // 200 + 20
-        assert_eq!(dims.content.y, 210.0); // 200 + 10
+        let rect = child_box(".child { position: absolute; top: 10px; left: 20px; width: 50px; height: 50px; }");
+        assert_eq!(rect, Rect { x: 220.0, y: 210.0, width: 50.0, height: 50.0 });
     }
 
     #[test]
     fn test_absolute_positioning_right_bottom() {
-        let mut dims = Dimensions::default();
-        dims.content.width = 50.0;
-        dims.content.height = 50.0;
-
-        let mut containing_block = Dimensions::default();
-        containing_block.content.x = 0.0;
-        containing_block.content.y = 0.0;
-        containing_block.content.width = 400.0;
-        containing_block.content.height = 400.0;
-
-        let positioned = PositionedElement {
-            position: Position::Absolute,
-            offsets: Offsets {
-                top: None,
-                left: None,
-                bottom: Some(10.0),
-                right: Some(20.0),
-            },
-            z_index: 0,
-        };
-
-        let viewport = Rect::default();
-        positioned.apply_positioning(&mut dims, &containing_block, &viewport);
-
-        // Right: 400 - 50 (width) - 20 = 330
-        // Bottom: 400 - 50 (height) - 10 = 340
-        assert_eq!(dims.content.x, 330.0);
-        assert_eq!(dims.content.y, 340.0);
+        let rect = child_box(".child { position: absolute; bottom: 10px; right: 20px; width: 50px; height: 50px; }");
+        // Right: 200 + 400 - 50 (width) - 20 = 530
+        // Bottom: 200 + 400 - 50 (height) - 10 = 540
+        assert_eq!((rect.x, rect.y), (530.0, 540.0));
     }
 
     #[test]
     fn test_fixed_positioning() {
-        let mut dims = Dimensions::default();
-        dims.content.width = 50.0;
-        dims.content.height = 50.0;
-
-        let viewport = Rect {
-            x: 0.0,
-            y: 0.0,
-            width: 800.0,
-            height: 600.0,
-        };
+        let rect = child_box(".child { position: fixed; top: 10px; right: 10px; width: 50px; height: 50px; }");
+        // Right: 800 - 50 (width) - 10 = 740
+        assert_eq!((rect.x, rect.y), (740.0, 10.0));
+    }
 
-        let positioned = PositionedElement {
-            position: Position::Fixed,
-            offsets: Offsets {
-                top: Some(10.0),
-                right: Some(10.0),
-                bottom: None,
-                left: None,
-            },
-            z_index: 0,
-        };
+    #[test]
+    fn test_percentage_and_calc_resolve_against_containing_block() {
+        let rect = child_box(
+            ".child { position: absolute; left: 10%; top: calc(25% + 5px); width: 50%; height: 25%; }",
+        );
+        assert_eq!(rect, Rect { x: 240.0, y: 305.0, width: 200.0, height: 100.0 });
+
+        let rect = child_box(
+            ".child { position: absolute; right: 0px; bottom: 0px; width: calc(50% - 40px); height: calc(10% + 2px); }",
+        );
+        assert_eq!(rect, Rect { x: 440.0, y: 558.0, width: 160.0, height: 42.0 });
+    }
 
-        let containing_block = Dimensions::default();
-        positioned.apply_positioning(&mut dims, &containing_block, &viewport);
+    #[test]
+    fn test_font_relative_insets_and_sizes() {
+        let rect = child_box(
+            ".child { position: absolute; font-size: 20px; left: 2em; top: 1rem; width: 3em; height: 2rem; }",
+        );
+        assert_eq!(rect, Rect { x: 240.0, y: 216.0, width: 60.0, height: 32.0 });
+
+        let rect = child_box(
+            ".child { position: absolute; font-size: 10px; right: 1em; bottom: 2em; width: 5em; height: 5em; }",
+        );
+        assert_eq!((rect.x, rect.y), (540.0, 530.0));
+    }
 
-        // Right: 800 - 50 (width) - 10 = 740
-        assert_eq!(dims.content.x, 740.0);
-        assert_eq!(dims.content.y, 10.0);
+    #[test]
+    fn test_only_auto_sizes_shrink_to_content() {
+        // `auto`, given or not, is as wide as the text
+        assert_eq!(child_box(".child { position: absolute; right: 0px; width: auto; }").width, 40.0);
+        assert_eq!(child_box(".child { position: absolute; right: 0px; }").width, 40.0);
+        // A box inside with a given width counts at that width, whatever its unit
+        let css = ".child { position: absolute; left: 0px; } .grandchild { width: 10em; height: 5px; }";
+        let (child, grandchild) =
+            layout_child(css, |child| (child.dimensions.content, child.children[0].dimensions.content));
+        assert_eq!((child.width, grandchild.width), (160.0, 160.0));
     }
 }
@@ -0,0 +1,175 @@
+// Replaced elements - boxes sized by the content they show
+//
+// An <img> without a CSS width and height takes the natural size of its
+// decoded image. With only one of them given, the other follows the image's
+// aspect ratio. Natural sizes live in `ImageSizes`, a store shared between
+// the image cache, which records each image as it is decoded, and layout,
+// which looks them up by the element's `src` resolved against the
+// document's base URL. Until an image is known its box is sized from CSS
+// alone.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use url::Url;
+
+use super::{LayoutContext, ToPx};
+use crate::css::Value;
+use crate::style::StyledNode;
+
+/// Width and height of an image as decoded, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntrinsicSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Natural sizes of decoded images by URL
+///
+/// Clones share the same store, so sizes recorded through one are seen by
+/// all of them.
+#[derive(Debug, Clone, Default)]
+pub struct ImageSizes {
+    sizes: Arc<RwLock<HashMap<Url, IntrinsicSize>>>,
+}
+
+impl ImageSizes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the natural size of an image
+    pub fn record(&self, url: &Url, size: IntrinsicSize) {
+        if let Ok(mut sizes) = self.sizes.write() {
+            sizes.insert(url.clone(), size);
+        }
+    }
+
+    /// Natural size of an image, if it has been decoded
+    pub fn get(&self, url: &Url) -> Option<IntrinsicSize> {
+        self.sizes.read().ok()?.get(url).copied()
+    }
+
+    /// Natural size of the image an element's `src` names
+    pub fn lookup(&self, base_url: Option<&Url>, src: &str) -> Option<IntrinsicSize> {
+        let url = match base_url {
+            Some(base) => base.join(src).ok()?,
+            None => Url::parse(src).ok()?,
+        };
+        self.get(&url)
+    }
+
+    /// Forget all sizes
+    pub fn clear(&self) {
+        if let Ok(mut sizes) = self.sizes.write() {
+            sizes.clear();
+        }
+    }
+}
+
+/// Content width and height of a replaced element
+///
+/// None for elements that are not images, or whose image is not known yet.
+pub(super) fn replaced_size(styled: &StyledNode, cx: &LayoutContext) -> Option<(f32, f32)> {
+    let element = styled.node.element_data()?;
+    if element.tag_name != "img" {
+        return None;
+    }
+    let images = cx.images?;
+    let intrinsic = images.lookup(cx.base_url, element.get_attribute("src")?)?;
+    Some(used_size(specified(styled, "width"), specified(styled, "height"), intrinsic))
+}
+
+/// A dimension set in CSS, None for `auto`
+fn specified(styled: &StyledNode, name: &str) -> Option<f32> {
+    match styled.value(name) {
+        Some(value @ (Value::Length(..) | Value::Number(_))) => Some(value.to_px()),
+        _ => None,
+    }
+}
+
+/// Size of an image given the dimensions CSS sets, keeping its aspect ratio
+/// when only one is set
+fn used_size(width: Option<f32>, height: Option<f32>, intrinsic: IntrinsicSize) -> (f32, f32) {
+    let (natural_width, natural_height) = (intrinsic.width as f32, intrinsic.height as f32);
+    match (width, height) {
+        (Some(width), Some(height)) => (width, height),
+        (Some(width), None) if natural_width > 0.0 => (width, width * natural_height / natural_width),
+        (None, Some(height)) if natural_height > 0.0 => (height * natural_width / natural_height, height),
+        (Some(width), None) => (width, natural_height),
+        (None, Some(height)) => (natural_width, height),
+        (None, None) => (natural_width, natural_height),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::CssParser;
+    use crate::dom::Node;
+    use crate::layout::inline::EstimatedMetrics;
+    use crate::layout::{layout_tree_with_images, Dimensions};
+    use crate::style::style_tree;
+
+    #[test]
+    fn test_used_size_keeps_aspect_ratio() {
+        let photo = IntrinsicSize { width: 400, height: 300 };
+        assert_eq!(used_size(None, None, photo), (400.0, 300.0));
+        assert_eq!(used_size(Some(200.0), None, photo), (200.0, 150.0));
+        assert_eq!(used_size(None, Some(600.0), photo), (800.0, 600.0));
+        assert_eq!(used_size(Some(10.0), Some(10.0), photo), (10.0, 10.0));
+    }
+
+    #[test]
+    fn test_image_sizes_are_shared() {
+        let sizes = ImageSizes::new();
+        let shared = sizes.clone();
+        let base = Url::parse("https://example.com/gallery/").unwrap();
+        shared.record(&base.join("cat.png").unwrap(), IntrinsicSize { width: 64, height: 32 });
+
+        assert_eq!(sizes.lookup(Some(&base), "cat.png"), Some(IntrinsicSize { width: 64, height: 32 }));
+        assert_eq!(sizes.lookup(None, "cat.png"), None);
+        sizes.clear();
+        assert_eq!(shared.lookup(Some(&base), "cat.png"), None);
+    }
+
+    #[test]
+    fn test_images_take_natural_size() {
+        let image = |class: &str| {
+            let attrs = std::collections::HashMap::from([
+                ("src".to_string(), "cat.png".to_string()),
+                ("class".to_string(), class.to_string()),
+            ]);
+            Node::element("img".to_string(), attrs, vec![])
+        };
+        let html = Node::element(
+            "div".to_string(),
+            Default::default(),
+            vec![image("natural"), image("wide"), image("block")],
+        );
+        let css = CssParser::parse(
+            "div { display: block; } img { display: inline; } .wide { width: 200px; } \
+             .block { display: block; height: 30px; }",
+        );
+        let styled = style_tree(&html, &css);
+        let base = Url::parse("https://example.com/").unwrap();
+        let sizes = ImageSizes::new();
+        sizes.record(&base.join("cat.png").unwrap(), IntrinsicSize { width: 40, height: 20 });
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+
+        let layout = layout_tree_with_images(&styled, viewport, &EstimatedMetrics, &sizes, Some(&base));
+        let size = |index: usize| {
+            let content = layout.children[index].dimensions.content;
+            (content.width, content.height)
+        };
+        assert_eq!(size(0), (40.0, 20.0));
+        assert_eq!(size(1), (200.0, 100.0));
+        assert_eq!(size(2), (60.0, 30.0));
+
+        // Unknown images are sized from CSS alone
+        let layout = layout_tree_with_images(&styled, viewport, &EstimatedMetrics, &ImageSizes::new(), Some(&base));
+        assert_eq!(layout.children[0].dimensions.content.width, 0.0);
+        assert_eq!(layout.children[1].dimensions.content.width, 200.0);
+    }
+}
@@ -12,16 +12,40 @@ pub mod net;
 pub mod ui;
 pub mod js;
 pub mod navigation;
+pub mod bookmarks;
+pub mod history;
 pub mod forms;
+pub mod clipboard;
+pub mod accessibility;
+pub mod reader;
+pub mod preferences;
+pub mod print;
 pub mod devtools;
 pub mod compositor;
 pub mod animation;
+pub mod transform;
+pub mod web_fonts;
+pub mod text_style;
 pub mod canvas;
 pub mod storage;
 pub mod websocket;
 pub mod multiprocess;
 pub mod observers;
 pub mod performance;
+pub mod web_vitals;
 pub mod fetch;
 pub mod benchmarks;
 pub mod indexeddb;
+pub mod media;
+pub mod permissions;
+pub mod content_filter;
+pub mod user_content;
+pub mod extensions;
+pub mod editing;
+pub mod crash;
+pub mod memory;
+pub mod engine;
+pub mod headless;
+
+pub use engine::{InputEvent, Screenshot, WebView, WebViewEvent};
+pub use headless::{HeadlessBrowser, HeadlessError, HeadlessOptions};
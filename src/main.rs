@@ -1,18 +1,7 @@
-mod dom;
-mod html;
-mod css;
-mod style;
-mod layout;
-mod window;
-mod renderer;
-
-use html::HtmlParser;
-use css::CssParser;
-use style::style_tree;
-use layout::{layout_tree, Dimensions};
-use window::{Window, WindowConfig};
-use renderer::Renderer;
-use winit::event::WindowEvent;
+use browser_engine::html::HtmlParser;
+use browser_engine::css::CssParser;
+use browser_engine::style::style_tree;
+use browser_engine::layout::{layout_tree, Dimensions};
 
 fn main() {
     println!("=== Browser Engine Phase 1 Demo ===");
@@ -0,0 +1,152 @@
+// Media backends - demuxing and decoding a resource into audio and frames
+//
+// A backend opens the bytes of a resource as they arrive and decodes it a
+// packet at a time. Any container and codec can be supported by plugging in
+// a backend; the built-in one decodes audio with symphonia.
+
+use std::sync::Arc;
+
+use super::buffer::BufferReader;
+use super::MediaError;
+
+/// What a backend found in a resource it opened
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaInfo {
+    /// Length in seconds, if the container says
+    pub duration: Option<f64>,
+    pub audio: Option<AudioInfo>,
+    /// Size of the video's frames in pixels, if it has video
+    pub video: Option<(u32, u32)>,
+}
+
+/// Format of an audio track
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioInfo {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+/// Samples decoded from one packet of audio
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioChunk {
+    /// Media time of the first sample, in seconds
+    pub timestamp: f64,
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Samples of each channel in turn for each frame, in -1 to 1
+    pub samples: Vec<f32>,
+}
+
+impl AudioChunk {
+    /// Seconds the samples play for
+    pub fn duration(&self) -> f64 {
+        let frames = self.samples.len() / self.channels.max(1) as usize;
+        frames as f64 / self.sample_rate.max(1) as f64
+    }
+}
+
+/// A decoded picture of a video
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoFrame {
+    /// Media time the frame is shown from, in seconds
+    pub timestamp: f64,
+    /// Seconds the frame is shown for
+    pub duration: f64,
+    pub width: u32,
+    pub height: u32,
+    /// RGBA pixels, rows from the top
+    pub pixels: Arc<[u8]>,
+}
+
+/// Something a decoder produced
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decoded {
+    Audio(AudioChunk),
+    Video(VideoFrame),
+    /// More bytes have to arrive before decoding can go on
+    Waiting,
+    /// The end of the media was reached
+    Ended,
+}
+
+/// How sure a backend is that it can play a MIME type, as `canPlayType` answers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CanPlay {
+    No,
+    Maybe,
+    Probably,
+}
+
+impl CanPlay {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CanPlay::No => "",
+            CanPlay::Maybe => "maybe",
+            CanPlay::Probably => "probably",
+        }
+    }
+}
+
+/// Decodes an opened resource
+pub trait MediaDecoder: Send {
+    fn info(&self) -> &MediaInfo;
+
+    /// Decode the next packet
+    fn decode(&mut self) -> Result<Decoded, MediaError>;
+
+    /// Move to a media time, returning the time decoding resumes from
+    ///
+    /// `Ok(None)` if the bytes needed to find it have not arrived; decoding
+    /// then waits for them and goes on from the time sought.
+    fn seek(&mut self, time: f64) -> Result<Option<f64>, MediaError>;
+}
+
+/// Opens resources of the formats it supports
+pub trait MediaBackend: Send + Sync {
+    /// How sure the backend is that it plays a MIME type such as `audio/ogg; codecs=vorbis`
+    fn can_play_type(&self, mime_type: &str) -> CanPlay;
+
+    /// Open a resource from its bytes
+    ///
+    /// `mime_type` is a hint and may be empty. `Ok(None)` if more bytes
+    /// have to arrive before the resource can be opened.
+    fn open(&self, reader: BufferReader, mime_type: &str) -> Result<Option<Box<dyn MediaDecoder>>, MediaError>;
+}
+
+/// Backends tried in turn, the first to open a resource decoding it
+#[derive(Default)]
+pub struct Backends {
+    backends: Vec<Box<dyn MediaBackend>>,
+}
+
+impl Backends {
+    /// The built-in backends
+    pub fn new() -> Self {
+        Self { backends: vec![Box::new(super::symphonia_backend::SymphoniaBackend)] }
+    }
+
+    /// Try a backend before the others
+    pub fn register(&mut self, backend: Box<dyn MediaBackend>) {
+        self.backends.insert(0, backend);
+    }
+}
+
+impl MediaBackend for Backends {
+    fn can_play_type(&self, mime_type: &str) -> CanPlay {
+        self.backends.iter().map(|backend| backend.can_play_type(mime_type)).max().unwrap_or(CanPlay::No)
+    }
+
+    fn open(&self, reader: BufferReader, mime_type: &str) -> Result<Option<Box<dyn MediaDecoder>>, MediaError> {
+        let mut error = MediaError::NotSupported("no backend plays this media".to_string());
+        for backend in &self.backends {
+            if !mime_type.is_empty() && backend.can_play_type(mime_type) == CanPlay::No {
+                continue;
+            }
+            match backend.open(reader.clone(), mime_type) {
+                Ok(decoder) => return Ok(decoder),
+                Err(e) => error = e,
+            }
+        }
+        Err(error)
+    }
+}
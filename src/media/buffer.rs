@@ -0,0 +1,248 @@
+// Media buffer - the bytes of a media resource fetched so far
+//
+// Media is fetched a range at a time rather than whole: a little ahead of
+// where it is being read, and from wherever a seek or the container's index
+// sends the reader. Ranges that arrive are merged into spans of contiguous
+// bytes. Reading where nothing has arrived fails with `WouldBlock`, and the
+// place is remembered so it is fetched next.
+
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use crate::net::Response;
+
+/// Bytes asked for by each range request
+pub const CHUNK_SIZE: u64 = 256 * 1024;
+/// How far ahead of the reader bytes are fetched
+pub const READ_AHEAD: u64 = 2 * 1024 * 1024;
+
+/// Bytes of a resource from one range request
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeChunk {
+    /// Offset of the first byte in the resource
+    pub start: u64,
+    pub bytes: Vec<u8>,
+    /// Length of the whole resource, if the server said
+    pub total_length: Option<u64>,
+}
+
+impl RangeChunk {
+    /// The part of a resource a response holds
+    ///
+    /// A server that ignores the `Range` header sends the whole resource.
+    pub fn from_response(response: Response) -> Self {
+        match response.content_range() {
+            Some((start, total_length)) => Self { start, bytes: response.body, total_length },
+            None => Self { start: 0, total_length: Some(response.body.len() as u64), bytes: response.body },
+        }
+    }
+}
+
+/// The bytes of a resource fetched so far
+#[derive(Debug, Default)]
+pub struct MediaBuffer {
+    /// Runs of contiguous bytes by their offset, in order, none touching
+    spans: Vec<(u64, Vec<u8>)>,
+    total_length: Option<u64>,
+    /// Where the reader is
+    read_position: u64,
+    /// Where the reader last wanted bytes that had not arrived
+    wanted: Option<u64>,
+}
+
+impl MediaBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Length of the resource, once a response has said
+    pub fn total_length(&self) -> Option<u64> {
+        self.total_length
+    }
+
+    /// Add the bytes of a range that arrived
+    pub fn insert(&mut self, chunk: RangeChunk) {
+        if chunk.total_length.is_some() {
+            self.total_length = chunk.total_length;
+        }
+        if chunk.bytes.is_empty() {
+            return;
+        }
+        let (mut start, mut bytes) = (chunk.start, chunk.bytes);
+        // Merge with every span the new bytes overlap or touch
+        let mut kept = Vec::with_capacity(self.spans.len() + 1);
+        for (span_start, span) in std::mem::take(&mut self.spans) {
+            let span_end = span_start + span.len() as u64;
+            let end = start + bytes.len() as u64;
+            if span_end < start || span_start > end {
+                kept.push((span_start, span));
+                continue;
+            }
+            let merged_start = start.min(span_start);
+            let mut merged = vec![0; (end.max(span_end) - merged_start) as usize];
+            merged[(span_start - merged_start) as usize..][..span.len()].copy_from_slice(&span);
+            merged[(start - merged_start) as usize..][..bytes.len()].copy_from_slice(&bytes);
+            (start, bytes) = (merged_start, merged);
+        }
+        let index = kept.partition_point(|(span_start, _)| *span_start < start);
+        kept.insert(index, (start, bytes));
+        self.spans = kept;
+    }
+
+    /// Copy bytes from `position` into `buf`, as many as have arrived in a row
+    pub fn read_at(&self, position: u64, buf: &mut [u8]) -> usize {
+        let Some((start, span)) = self.span_at(position) else {
+            return 0;
+        };
+        let offset = (position - start) as usize;
+        let count = buf.len().min(span.len() - offset);
+        buf[..count].copy_from_slice(&span[offset..offset + count]);
+        count
+    }
+
+    /// End of the bytes that have arrived in a row from `position`
+    pub fn buffered_end(&self, position: u64) -> u64 {
+        self.span_at(position).map_or(position, |(start, span)| start + span.len() as u64)
+    }
+
+    /// Byte ranges that have arrived, in order
+    pub fn buffered(&self) -> Vec<Range<u64>> {
+        self.spans.iter().map(|(start, span)| *start..start + span.len() as u64).collect()
+    }
+
+    /// Has the whole resource arrived
+    pub fn is_complete(&self) -> bool {
+        self.total_length.is_some_and(|length| self.buffered_end(0) >= length)
+    }
+
+    /// The range to fetch next, if any
+    ///
+    /// Bytes the reader is waiting for come first, then the gap after where
+    /// it is reading, until the buffer is `READ_AHEAD` ahead of it.
+    pub fn next_request(&self) -> Option<Range<u64>> {
+        let from = self.wanted.unwrap_or(self.read_position);
+        let start = self.buffered_end(from);
+        if self.wanted.is_none() && start >= self.read_position + READ_AHEAD {
+            return None;
+        }
+        let mut end = start + CHUNK_SIZE;
+        if let Some(length) = self.total_length {
+            end = end.min(length);
+        }
+        if let Some((next, _)) = self.spans.iter().find(|(span_start, _)| *span_start > start) {
+            end = end.min(*next);
+        }
+        (start < end).then_some(start..end)
+    }
+
+    /// The span holding the byte at `position`
+    fn span_at(&self, position: u64) -> Option<(u64, &[u8])> {
+        let index = self.spans.partition_point(|(start, _)| *start <= position).checked_sub(1)?;
+        let (start, span) = &self.spans[index];
+        (position < start + span.len() as u64).then_some((*start, span.as_slice()))
+    }
+}
+
+/// Reads a shared buffer as a file, for a decoder
+///
+/// Reads and seeks move the buffer's read position, which decides what is
+/// fetched next.
+#[derive(Debug, Clone)]
+pub struct BufferReader {
+    buffer: Arc<Mutex<MediaBuffer>>,
+    position: u64,
+}
+
+impl BufferReader {
+    pub fn new(buffer: Arc<Mutex<MediaBuffer>>) -> Self {
+        Self { buffer, position: 0 }
+    }
+
+    /// Length of the resource, once known
+    pub fn total_length(&self) -> Option<u64> {
+        self.buffer.lock().ok()?.total_length()
+    }
+}
+
+impl Read for BufferReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut buffer = self.buffer.lock().map_err(|_| io::Error::other("media buffer poisoned"))?;
+        let count = buffer.read_at(self.position, buf);
+        if count == 0 && !buf.is_empty() {
+            if buffer.total_length().is_some_and(|length| self.position >= length) {
+                return Ok(0);
+            }
+            buffer.wanted = Some(self.position);
+            return Err(io::Error::new(io::ErrorKind::WouldBlock, "media data has not arrived"));
+        }
+        self.position += count as u64;
+        buffer.read_position = self.position;
+        buffer.wanted = None;
+        Ok(count)
+    }
+}
+
+impl Seek for BufferReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+            SeekFrom::End(delta) => self.total_length().and_then(|length| length.checked_add_signed(delta)),
+        };
+        let position = position.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid media seek"))?;
+        self.position = position;
+        if let Ok(mut buffer) = self.buffer.lock() {
+            buffer.read_position = position;
+        }
+        Ok(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(start: u64, bytes: &[u8], total_length: u64) -> RangeChunk {
+        RangeChunk { start, bytes: bytes.to_vec(), total_length: Some(total_length) }
+    }
+
+    #[test]
+    fn test_ranges_merge() {
+        let mut buffer = MediaBuffer::new();
+        assert_eq!(buffer.next_request(), Some(0..CHUNK_SIZE));
+        buffer.insert(chunk(0, b"abcd", 12));
+        buffer.insert(chunk(8, b"ijkl", 12));
+        assert_eq!(buffer.buffered(), vec![0..4, 8..12]);
+        assert_eq!(buffer.next_request(), Some(4..8));
+        assert!(!buffer.is_complete());
+
+        buffer.insert(chunk(3, b"defgh", 12));
+        assert_eq!(buffer.buffered(), vec![0..12]);
+        assert!(buffer.is_complete());
+        assert_eq!(buffer.next_request(), None);
+        let mut read = [0; 5];
+        assert_eq!(buffer.read_at(6, &mut read), 5);
+        assert_eq!(&read, b"ghijk");
+    }
+
+    #[test]
+    fn test_reader_waits_for_data_it_seeks_to() {
+        let buffer = Arc::new(Mutex::new(MediaBuffer::new()));
+        buffer.lock().unwrap().insert(chunk(0, &[1; 100], 10_000_000));
+        let mut reader = BufferReader::new(buffer.clone());
+        let mut read = [0; 64];
+        assert_eq!(reader.read(&mut read).unwrap(), 64);
+        assert_eq!(reader.read(&mut read).unwrap(), 36);
+        assert_eq!(reader.read(&mut read).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(buffer.lock().unwrap().next_request(), Some(100..100 + CHUNK_SIZE));
+
+        // An index at the end of the file is fetched before reading on
+        reader.seek(SeekFrom::End(-16)).unwrap();
+        assert!(reader.read(&mut read).is_err());
+        assert_eq!(buffer.lock().unwrap().next_request(), Some(9_999_984..10_000_000));
+        buffer.lock().unwrap().insert(chunk(9_999_984, &[2; 16], 10_000_000));
+        assert_eq!(reader.read(&mut read).unwrap(), 16);
+        assert_eq!(reader.read(&mut read).unwrap(), 0);
+    }
+}
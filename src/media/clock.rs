@@ -0,0 +1,103 @@
+// Playback clock - the media time a player is at
+//
+// While running, media time advances with the wall clock times the
+// playback rate from the last point it was anchored at. Pausing, seeking
+// and rate changes re-anchor it, so no error builds up across them.
+
+use std::time::Instant;
+
+/// Media time of a playing or paused element, in seconds
+#[derive(Debug, Clone)]
+pub struct PlaybackClock {
+    /// Media time at the anchor
+    position: f64,
+    /// When the clock was last anchored, if it is running
+    anchor: Option<Instant>,
+    /// Media seconds per wall clock second
+    rate: f64,
+}
+
+impl PlaybackClock {
+    /// A stopped clock at the start of the media
+    pub fn new() -> Self {
+        Self { position: 0.0, anchor: None, rate: 1.0 }
+    }
+
+    /// Media time at `now`
+    pub fn position(&self, now: Instant) -> f64 {
+        match self.anchor {
+            Some(anchor) => self.position + now.saturating_duration_since(anchor).as_secs_f64() * self.rate,
+            None => self.position,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.anchor.is_some()
+    }
+
+    /// Start advancing from where the clock stands
+    pub fn start(&mut self, now: Instant) {
+        if self.anchor.is_none() {
+            self.anchor = Some(now);
+        }
+    }
+
+    /// Stop advancing, holding the time reached at `now`
+    pub fn stop(&mut self, now: Instant) {
+        self.position = self.position(now);
+        self.anchor = None;
+    }
+
+    /// Jump to a media time, running on from there if the clock was running
+    pub fn seek(&mut self, position: f64, now: Instant) {
+        self.position = position.max(0.0);
+        if self.anchor.is_some() {
+            self.anchor = Some(now);
+        }
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Change how fast media time passes from `now` on
+    pub fn set_rate(&mut self, rate: f64, now: Instant) {
+        if self.anchor.is_some() {
+            self.position = self.position(now);
+            self.anchor = Some(now);
+        }
+        self.rate = rate.max(0.0);
+    }
+}
+
+impl Default for PlaybackClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_clock_runs_stops_and_seeks() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut clock = PlaybackClock::new();
+        assert_eq!(clock.position(at(500)), 0.0);
+
+        clock.start(start);
+        assert_eq!(clock.position(at(1500)), 1.5);
+        clock.stop(at(2000));
+        assert_eq!(clock.position(at(9000)), 2.0);
+
+        clock.seek(10.0, at(9000));
+        clock.start(at(9000));
+        clock.set_rate(2.0, at(10000));
+        assert_eq!(clock.position(at(11000)), 13.0);
+        clock.seek(1.0, at(11000));
+        assert_eq!(clock.position(at(11500)), 2.0);
+    }
+}
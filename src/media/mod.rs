@@ -0,0 +1,258 @@
+// Media - playback of <audio> and <video> elements
+//
+// Each media element of a page gets a player, which fetches its resource
+// with range requests, decodes it through a pluggable backend and keeps
+// time with a playback clock. The host fetches what players ask for,
+// updates them each frame, hands their events to scripts and their video
+// frames to the compositor.
+
+mod backend;
+mod buffer;
+mod clock;
+mod player;
+mod symphonia_backend;
+
+use std::sync::Arc;
+
+use url::Url;
+
+use crate::animation::ElementPath;
+use crate::dom::Node;
+
+pub use backend::{
+    AudioChunk, AudioInfo, Backends, CanPlay, Decoded, MediaBackend, MediaDecoder, MediaInfo, VideoFrame,
+};
+pub use buffer::{BufferReader, MediaBuffer, RangeChunk, CHUNK_SIZE, READ_AHEAD};
+pub use clock::PlaybackClock;
+pub use player::{MediaPlayer, MediaState};
+pub use symphonia_backend::SymphoniaBackend;
+
+/// Kind of media element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaKind {
+    Audio,
+    Video,
+}
+
+impl MediaKind {
+    pub fn from_str(tag_name: &str) -> Option<Self> {
+        match tag_name {
+            "audio" => Some(MediaKind::Audio),
+            "video" => Some(MediaKind::Video),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MediaKind::Audio => "audio",
+            MediaKind::Video => "video",
+        }
+    }
+}
+
+/// `HTMLMediaElement.networkState`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkState {
+    Empty = 0,
+    Idle = 1,
+    Loading = 2,
+    NoSource = 3,
+}
+
+/// `HTMLMediaElement.readyState`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ReadyState {
+    HaveNothing = 0,
+    HaveMetadata = 1,
+    HaveCurrentData = 2,
+    HaveFutureData = 3,
+    HaveEnoughData = 4,
+}
+
+/// Events fired at media elements
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaEvent {
+    LoadStart,
+    Progress,
+    Suspend,
+    Emptied,
+    LoadedMetadata,
+    LoadedData,
+    CanPlay,
+    CanPlayThrough,
+    Play,
+    Playing,
+    Pause,
+    Waiting,
+    Seeking,
+    Seeked,
+    TimeUpdate,
+    Ended,
+    DurationChange,
+    VolumeChange,
+    Error,
+}
+
+impl MediaEvent {
+    /// The event's type, as listeners are added for
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MediaEvent::LoadStart => "loadstart",
+            MediaEvent::Progress => "progress",
+            MediaEvent::Suspend => "suspend",
+            MediaEvent::Emptied => "emptied",
+            MediaEvent::LoadedMetadata => "loadedmetadata",
+            MediaEvent::LoadedData => "loadeddata",
+            MediaEvent::CanPlay => "canplay",
+            MediaEvent::CanPlayThrough => "canplaythrough",
+            MediaEvent::Play => "play",
+            MediaEvent::Playing => "playing",
+            MediaEvent::Pause => "pause",
+            MediaEvent::Waiting => "waiting",
+            MediaEvent::Seeking => "seeking",
+            MediaEvent::Seeked => "seeked",
+            MediaEvent::TimeUpdate => "timeupdate",
+            MediaEvent::Ended => "ended",
+            MediaEvent::DurationChange => "durationchange",
+            MediaEvent::VolumeChange => "volumechange",
+            MediaEvent::Error => "error",
+        }
+    }
+}
+
+/// Why a media element failed, as `MediaError` reports it
+#[derive(Debug, Clone, PartialEq)]
+pub enum MediaError {
+    Aborted,
+    Network(String),
+    Decode(String),
+    NotSupported(String),
+}
+
+impl MediaError {
+    /// `MediaError.code`
+    pub fn code(&self) -> u16 {
+        match self {
+            MediaError::Aborted => 1,
+            MediaError::Network(_) => 2,
+            MediaError::Decode(_) => 3,
+            MediaError::NotSupported(_) => 4,
+        }
+    }
+}
+
+impl std::fmt::Display for MediaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MediaError::Aborted => write!(f, "Media loading aborted"),
+            MediaError::Network(msg) => write!(f, "Media network error: {}", msg),
+            MediaError::Decode(msg) => write!(f, "Media decode error: {}", msg),
+            MediaError::NotSupported(msg) => write!(f, "Media not supported: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for MediaError {}
+
+/// An <audio> or <video> element and its player
+pub struct MediaElement {
+    /// Child indices from the document to the element
+    pub path: ElementPath,
+    pub id: Option<String>,
+    pub kind: MediaKind,
+    pub player: MediaPlayer,
+}
+
+/// The media elements of a page
+#[derive(Default)]
+pub struct MediaElements {
+    elements: Vec<MediaElement>,
+}
+
+impl MediaElements {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Players for the media elements of a document, loading their sources
+    ///
+    /// The source is the `src` attribute, or else the first <source> child
+    /// whose `type` the backend may play.
+    pub fn from_document(document: &Node, base_url: &Url, backend: Arc<dyn MediaBackend>) -> Self {
+        fn collect(
+            node: &Node,
+            path: &mut ElementPath,
+            base_url: &Url,
+            backend: &Arc<dyn MediaBackend>,
+            elements: &mut Vec<MediaElement>,
+        ) {
+            let media = node.element_data().and_then(|data| Some((data, MediaKind::from_str(&data.tag_name)?)));
+            if let Some((data, kind)) = media {
+                let mut player = MediaPlayer::new(backend.clone());
+                player.set_loop(data.get_attribute("loop").is_some());
+                player.set_muted(data.get_attribute("muted").is_some());
+                player.set_autoplay(data.get_attribute("autoplay").is_some());
+                player.take_events();
+                if let Some((src, mime_type)) = media_source(node, backend.as_ref()) {
+                    // A source that is not a URL is left unloaded, as having no source
+                    if let Ok(url) = base_url.join(src) {
+                        player.load(url, mime_type);
+                    }
+                }
+                elements.push(MediaElement { path: path.clone(), id: data.id().map(str::to_string), kind, player });
+            }
+            for (index, child) in node.children.iter().enumerate() {
+                path.push(index);
+                collect(child, path, base_url, backend, elements);
+                path.pop();
+            }
+        }
+
+        let mut elements = Vec::new();
+        collect(document, &mut Vec::new(), base_url, &backend, &mut elements);
+        Self { elements }
+    }
+
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &MediaElement> {
+        self.elements.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut MediaElement> {
+        self.elements.iter_mut()
+    }
+
+    /// The element with an `id`
+    pub fn get_mut(&mut self, id: &str) -> Option<&mut MediaElement> {
+        self.elements.iter_mut().find(|element| element.id.as_deref() == Some(id))
+    }
+
+    /// Whether any player has something to do as time passes
+    pub fn is_active(&self) -> bool {
+        self.elements.iter().any(|element| element.player.is_active())
+    }
+}
+
+/// The URL and type of the resource a media element plays
+fn media_source<'a>(node: &'a Node, backend: &dyn MediaBackend) -> Option<(&'a str, &'a str)> {
+    if let Some(src) = node.element_data()?.get_attribute("src") {
+        return Some((src, ""));
+    }
+    node.children.iter().find_map(|child| {
+        let data = child.element_data().filter(|data| data.tag_name == "source")?;
+        let src = data.attributes.get("src")?;
+        let mime_type = data.attributes.get("type").map_or("", String::as_str);
+        if !mime_type.is_empty() && backend.can_play_type(mime_type) == CanPlay::No {
+            return None;
+        }
+        Some((src.as_str(), mime_type))
+    })
+}
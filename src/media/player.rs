@@ -0,0 +1,679 @@
+// Media player - playback of one media element's resource
+//
+// The player asks for the resource a range at a time, opens it once enough
+// has arrived and decodes half a second ahead of the playback clock. The
+// ready state follows what is decoded: the clock runs only while the media
+// plays and the next half second is there, and waits, firing `waiting`,
+// when bytes run out. Events are queued in the order the HTML media
+// element algorithms fire them, for the host to hand to the page.
+
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use serde::Serialize;
+use url::Url;
+
+use super::backend::{AudioChunk, CanPlay, Decoded, MediaBackend, MediaDecoder, VideoFrame};
+use super::buffer::{BufferReader, MediaBuffer, RangeChunk};
+use super::clock::PlaybackClock;
+use super::{MediaError, MediaEvent, NetworkState, ReadyState};
+
+/// Seconds of media decoded ahead of the clock
+const DECODE_AHEAD: f64 = 0.5;
+/// Seconds of playback between `timeupdate` events
+const TIME_UPDATE_INTERVAL: f64 = 0.25;
+/// Packets decoded at most per update, so a backend making no progress cannot hang the page
+const MAX_PACKETS_PER_UPDATE: usize = 512;
+
+/// What a media element reports to scripts
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaState {
+    pub current_time: f64,
+    /// `None` until the duration is known
+    pub duration: Option<f64>,
+    pub paused: bool,
+    pub ended: bool,
+    pub seeking: bool,
+    pub volume: f64,
+    pub muted: bool,
+    #[serde(rename = "loop")]
+    pub looping: bool,
+    pub ready_state: u8,
+    pub network_state: u8,
+    /// `MediaError.code`, if loading or decoding failed
+    pub error: Option<u16>,
+    pub video_width: u32,
+    pub video_height: u32,
+}
+
+/// Plays one media resource
+pub struct MediaPlayer {
+    backend: Arc<dyn MediaBackend>,
+    src: Option<Url>,
+    mime_type: String,
+    buffer: Arc<Mutex<MediaBuffer>>,
+    /// Whether a range request is out
+    fetching: bool,
+    decoder: Option<Box<dyn MediaDecoder>>,
+    /// Whether the decoder reached the end of the media
+    decoded_all: bool,
+    /// Media time decoded up to
+    decoded_to: f64,
+    audio: VecDeque<AudioChunk>,
+    frames: VecDeque<VideoFrame>,
+    /// The frame showing, and whether it changed since it was last taken
+    frame: Option<VideoFrame>,
+    frame_changed: bool,
+    clock: PlaybackClock,
+    network_state: NetworkState,
+    ready_state: ReadyState,
+    /// Whether `loadeddata` fired for the current resource
+    loaded_data: bool,
+    paused: bool,
+    ended: bool,
+    /// Time being sought, and whether the decoder has been sent there
+    seeking: Option<f64>,
+    seek_sent: bool,
+    duration: Option<f64>,
+    volume: f64,
+    muted: bool,
+    looping: bool,
+    autoplay: bool,
+    error: Option<MediaError>,
+    events: Vec<MediaEvent>,
+    /// Media time of the last `timeupdate`
+    last_time_update: f64,
+}
+
+impl MediaPlayer {
+    /// A player with no resource, decoding with `backend`
+    pub fn new(backend: Arc<dyn MediaBackend>) -> Self {
+        Self {
+            backend,
+            src: None,
+            mime_type: String::new(),
+            buffer: Arc::new(Mutex::new(MediaBuffer::new())),
+            fetching: false,
+            decoder: None,
+            decoded_all: false,
+            decoded_to: 0.0,
+            audio: VecDeque::new(),
+            frames: VecDeque::new(),
+            frame: None,
+            frame_changed: false,
+            clock: PlaybackClock::new(),
+            network_state: NetworkState::Empty,
+            ready_state: ReadyState::HaveNothing,
+            loaded_data: false,
+            paused: true,
+            ended: false,
+            seeking: None,
+            seek_sent: false,
+            duration: None,
+            volume: 1.0,
+            muted: false,
+            looping: false,
+            autoplay: false,
+            error: None,
+            events: Vec::new(),
+            last_time_update: 0.0,
+        }
+    }
+
+    /// Start loading a resource, dropping the one playing
+    ///
+    /// `mime_type` is the `type` the page gave, or empty.
+    pub fn load(&mut self, src: Url, mime_type: &str) {
+        let emptied = self.network_state != NetworkState::Empty;
+        *self = Self {
+            volume: self.volume,
+            muted: self.muted,
+            looping: self.looping,
+            autoplay: self.autoplay,
+            events: std::mem::take(&mut self.events),
+            ..Self::new(self.backend.clone())
+        };
+        if emptied {
+            self.events.push(MediaEvent::Emptied);
+        }
+        self.src = Some(src);
+        self.mime_type = mime_type.to_string();
+        self.network_state = NetworkState::Loading;
+        self.events.push(MediaEvent::LoadStart);
+        if !mime_type.is_empty() && self.backend.can_play_type(mime_type) == CanPlay::No {
+            self.fail(MediaError::NotSupported(format!("cannot play {}", mime_type)));
+        }
+    }
+
+    /// Load the resource again from the start (`load()`)
+    pub fn reload(&mut self) {
+        if let Some(src) = self.src.clone() {
+            let mime_type = std::mem::take(&mut self.mime_type);
+            self.load(src, &mime_type);
+        }
+    }
+
+    pub fn src(&self) -> Option<&Url> {
+        self.src.as_ref()
+    }
+
+    /// Whether the player has a range to fetch
+    pub fn wants_fetch(&self) -> bool {
+        !self.fetching
+            && self.network_state == NetworkState::Loading
+            && self.buffer.lock().is_ok_and(|buffer| buffer.next_request().is_some())
+    }
+
+    /// The range of the resource to fetch next, if the player wants one
+    ///
+    /// Only one request is out at a time; the response goes to `receive`.
+    pub fn next_fetch(&mut self) -> Option<(Url, Range<u64>)> {
+        if !self.wants_fetch() {
+            return None;
+        }
+        let range = self.buffer.lock().ok()?.next_request()?;
+        self.fetching = true;
+        Some((self.src.clone()?, range))
+    }
+
+    /// Take in the response to a range request for `src`
+    ///
+    /// Responses for a resource the player no longer plays are dropped.
+    pub fn receive(&mut self, src: &Url, result: Result<RangeChunk, MediaError>, now: Instant) {
+        if self.src.as_ref() != Some(src) {
+            return;
+        }
+        self.fetching = false;
+        let chunk = match result {
+            Ok(chunk) => chunk,
+            Err(e) => return self.fail(e),
+        };
+        let complete = match self.buffer.lock() {
+            Ok(mut buffer) => {
+                buffer.insert(chunk);
+                buffer.is_complete()
+            }
+            Err(_) => false,
+        };
+        self.events.push(MediaEvent::Progress);
+        if complete {
+            self.network_state = NetworkState::Idle;
+            self.events.push(MediaEvent::Suspend);
+        }
+        self.update(now);
+    }
+
+    /// Start or resume playing (`play()`)
+    pub fn play(&mut self, now: Instant) {
+        if self.ended {
+            self.seek(0.0, now);
+        }
+        if !self.paused {
+            return;
+        }
+        self.paused = false;
+        self.events.push(MediaEvent::Play);
+        if self.ready_state >= ReadyState::HaveFutureData {
+            self.events.push(MediaEvent::Playing);
+            self.clock.start(now);
+        } else {
+            self.events.push(MediaEvent::Waiting);
+        }
+    }
+
+    /// Pause playing (`pause()`)
+    pub fn pause(&mut self, now: Instant) {
+        if self.paused {
+            return;
+        }
+        self.paused = true;
+        self.clock.stop(now);
+        self.events.push(MediaEvent::TimeUpdate);
+        self.events.push(MediaEvent::Pause);
+    }
+
+    /// Jump to a media time (setting `currentTime`)
+    ///
+    /// A seek before the resource is open is made once it is.
+    pub fn seek(&mut self, time: f64, now: Instant) {
+        let time = match self.duration {
+            Some(duration) => time.clamp(0.0, duration),
+            None => time.max(0.0),
+        };
+        self.ended = false;
+        self.seeking = Some(time);
+        self.seek_sent = false;
+        self.audio.clear();
+        self.frames.clear();
+        self.decoded_all = false;
+        self.decoded_to = time;
+        self.clock.seek(time, now);
+        self.last_time_update = time;
+        self.events.push(MediaEvent::Seeking);
+        if self.ready_state > ReadyState::HaveMetadata {
+            self.set_ready_state(ReadyState::HaveMetadata, now);
+        }
+    }
+
+    /// Set the volume, from 0 to 1
+    pub fn set_volume(&mut self, volume: f64) {
+        let volume = volume.clamp(0.0, 1.0);
+        if volume != self.volume {
+            self.volume = volume;
+            self.events.push(MediaEvent::VolumeChange);
+        }
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        if muted != self.muted {
+            self.muted = muted;
+            self.events.push(MediaEvent::VolumeChange);
+        }
+    }
+
+    /// Whether playing past the end starts again from the beginning
+    pub fn set_loop(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    /// Whether to start playing once enough has arrived to play through
+    pub fn set_autoplay(&mut self, autoplay: bool) {
+        self.autoplay = autoplay;
+    }
+
+    /// Media time at `now`, in seconds
+    pub fn current_time(&self, now: Instant) -> f64 {
+        if let Some(time) = self.seeking {
+            return time;
+        }
+        let position = self.clock.position(now);
+        self.duration.map_or(position, |duration| position.min(duration))
+    }
+
+    pub fn duration(&self) -> Option<f64> {
+        self.duration
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn ended(&self) -> bool {
+        self.ended
+    }
+
+    pub fn volume(&self) -> f64 {
+        self.volume
+    }
+
+    pub fn muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn ready_state(&self) -> ReadyState {
+        self.ready_state
+    }
+
+    pub fn network_state(&self) -> NetworkState {
+        self.network_state
+    }
+
+    pub fn error(&self) -> Option<&MediaError> {
+        self.error.as_ref()
+    }
+
+    /// Size of the video's frames, once known
+    pub fn video_size(&self) -> Option<(u32, u32)> {
+        self.decoder.as_ref()?.info().video
+    }
+
+    /// Whether the clock is running
+    pub fn is_playing(&self) -> bool {
+        self.clock.is_running()
+    }
+
+    /// Whether the player has anything to do when time passes
+    pub fn is_active(&self) -> bool {
+        self.error.is_none() && (!self.paused || self.network_state == NetworkState::Loading)
+    }
+
+    /// Decode, show the frames that came due and fire events for `now`
+    pub fn update(&mut self, now: Instant) {
+        if self.error.is_some() || self.src.is_none() {
+            return;
+        }
+        if self.decoder.is_none() && !self.open(now) {
+            return;
+        }
+        if let (Some(time), false) = (self.seeking, self.seek_sent) {
+            match self.decoder.as_mut().map(|decoder| decoder.seek(time)) {
+                Some(Err(e)) => return self.fail(e),
+                _ => self.seek_sent = true,
+            }
+        }
+        self.decode_ahead(now);
+        self.present(now);
+        self.update_ready_state(now);
+        self.check_ended(now);
+
+        let position = self.current_time(now);
+        if self.clock.is_running() && (position - self.last_time_update).abs() >= TIME_UPDATE_INTERVAL {
+            self.last_time_update = position;
+            self.events.push(MediaEvent::TimeUpdate);
+        }
+    }
+
+    /// Events fired since they were last taken, oldest first
+    pub fn take_events(&mut self) -> Vec<MediaEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// The frame to show, if it changed since it was last taken
+    pub fn take_frame(&mut self) -> Option<VideoFrame> {
+        if !self.frame_changed {
+            return None;
+        }
+        self.frame_changed = false;
+        self.frame.clone()
+    }
+
+    /// The frame showing, if any has been decoded
+    pub fn current_frame(&self) -> Option<&VideoFrame> {
+        self.frame.as_ref()
+    }
+
+    /// Decoded audio not yet taken, at the element's volume
+    ///
+    /// Each chunk plays from its timestamp by the player's clock.
+    pub fn take_audio(&mut self) -> Vec<AudioChunk> {
+        let gain = if self.muted { 0.0 } else { self.volume as f32 };
+        self.audio
+            .drain(..)
+            .map(|mut chunk| {
+                chunk.samples.iter_mut().for_each(|sample| *sample *= gain);
+                chunk
+            })
+            .collect()
+    }
+
+    /// What scripts see of the element at `now`
+    pub fn state(&self, now: Instant) -> MediaState {
+        let (video_width, video_height) = self.video_size().unwrap_or((0, 0));
+        MediaState {
+            current_time: self.current_time(now),
+            duration: self.duration,
+            paused: self.paused,
+            ended: self.ended,
+            seeking: self.seeking.is_some(),
+            volume: self.volume,
+            muted: self.muted,
+            looping: self.looping,
+            ready_state: self.ready_state as u8,
+            network_state: self.network_state as u8,
+            error: self.error.as_ref().map(MediaError::code),
+            video_width,
+            video_height,
+        }
+    }
+
+    /// Open the resource from the bytes so far, returning whether it is open
+    fn open(&mut self, now: Instant) -> bool {
+        let reader = BufferReader::new(self.buffer.clone());
+        match self.backend.open(reader, &self.mime_type) {
+            Ok(Some(decoder)) => {
+                self.duration = decoder.info().duration;
+                self.decoder = Some(decoder);
+                if self.duration.is_some() {
+                    self.events.push(MediaEvent::DurationChange);
+                }
+                self.set_ready_state(ReadyState::HaveMetadata, now);
+                true
+            }
+            Ok(None) => false,
+            Err(e) => {
+                self.fail(e);
+                false
+            }
+        }
+    }
+
+    /// Decode until the media is there for `DECODE_AHEAD` past the clock
+    fn decode_ahead(&mut self, now: Instant) {
+        let target = self.clock.position(now) + DECODE_AHEAD;
+        for _ in 0..MAX_PACKETS_PER_UPDATE {
+            if self.decoded_all || self.decoded_to >= target {
+                return;
+            }
+            let Some(decoder) = self.decoder.as_mut() else {
+                return;
+            };
+            match decoder.decode() {
+                Ok(Decoded::Audio(chunk)) => {
+                    self.decoded_to = self.decoded_to.max(chunk.timestamp + chunk.duration());
+                    self.audio.push_back(chunk);
+                }
+                Ok(Decoded::Video(frame)) => {
+                    self.decoded_to = self.decoded_to.max(frame.timestamp + frame.duration);
+                    self.frames.push_back(frame);
+                }
+                Ok(Decoded::Waiting) => return,
+                Ok(Decoded::Ended) => {
+                    self.decoded_all = true;
+                    // The container's duration was missing or wrong
+                    if self.duration != Some(self.decoded_to) && self.seeking.is_none() {
+                        self.duration = Some(self.decoded_to);
+                        self.events.push(MediaEvent::DurationChange);
+                    }
+                }
+                Err(e) => return self.fail(e),
+            }
+        }
+    }
+
+    /// Show the frame due at the clock and drop media it has passed
+    fn present(&mut self, now: Instant) {
+        let position = self.clock.position(now);
+        while self.frames.front().is_some_and(|frame| frame.timestamp <= position) {
+            self.frame = self.frames.pop_front();
+            self.frame_changed = true;
+        }
+        while self.audio.front().is_some_and(|chunk| chunk.timestamp + chunk.duration() <= position) {
+            self.audio.pop_front();
+        }
+    }
+
+    /// Work out the ready state from what is decoded past the clock
+    fn update_ready_state(&mut self, now: Instant) {
+        let position = self.clock.position(now);
+        let end = self.duration.unwrap_or(f64::INFINITY);
+        let state = if self.decoded_all || self.decoded_to >= (position + DECODE_AHEAD).min(end) {
+            let fetched = self.buffer.lock().map_or(true, |buffer| buffer.next_request().is_none());
+            if fetched {
+                ReadyState::HaveEnoughData
+            } else {
+                ReadyState::HaveFutureData
+            }
+        } else if self.decoded_to > position {
+            ReadyState::HaveCurrentData
+        } else {
+            ReadyState::HaveMetadata
+        };
+        if self.seek_sent && state >= ReadyState::HaveCurrentData {
+            if let Some(time) = self.seeking.take() {
+                self.last_time_update = time;
+                self.events.push(MediaEvent::TimeUpdate);
+                self.events.push(MediaEvent::Seeked);
+            }
+        }
+        self.set_ready_state(state, now);
+    }
+
+    /// Move to a ready state, firing the events crossing into it fires
+    fn set_ready_state(&mut self, state: ReadyState, now: Instant) {
+        let old = self.ready_state;
+        if state == old {
+            return;
+        }
+        self.ready_state = state;
+        if state == ReadyState::HaveMetadata && old == ReadyState::HaveNothing {
+            self.events.push(MediaEvent::LoadedMetadata);
+        }
+        if state >= ReadyState::HaveCurrentData && !self.loaded_data {
+            self.loaded_data = true;
+            self.events.push(MediaEvent::LoadedData);
+        }
+        if old < ReadyState::HaveFutureData && state >= ReadyState::HaveFutureData {
+            self.events.push(MediaEvent::CanPlay);
+            if !self.paused {
+                self.events.push(MediaEvent::Playing);
+                self.clock.start(now);
+            }
+        }
+        if old < ReadyState::HaveEnoughData && state == ReadyState::HaveEnoughData {
+            self.events.push(MediaEvent::CanPlayThrough);
+            if self.autoplay && self.paused {
+                self.autoplay = false;
+                self.paused = false;
+                self.events.push(MediaEvent::Play);
+                self.events.push(MediaEvent::Playing);
+                self.clock.start(now);
+            }
+        }
+        if old >= ReadyState::HaveFutureData && state < ReadyState::HaveFutureData && !self.paused {
+            self.clock.stop(now);
+            self.events.push(MediaEvent::Waiting);
+        }
+    }
+
+    /// Stop at the end of the media, or start over if looping
+    fn check_ended(&mut self, now: Instant) {
+        let position = self.clock.position(now);
+        if self.ended || !self.decoded_all || !self.frames.is_empty() || position < self.decoded_to {
+            return;
+        }
+        if self.looping && !self.paused {
+            self.seek(0.0, now);
+            return;
+        }
+        self.clock.stop(now);
+        self.clock.seek(self.decoded_to, now);
+        self.ended = true;
+        self.events.push(MediaEvent::TimeUpdate);
+        if !self.paused {
+            self.paused = true;
+            self.events.push(MediaEvent::Pause);
+        }
+        self.events.push(MediaEvent::Ended);
+    }
+
+    /// Stop loading and playing after an error
+    fn fail(&mut self, error: MediaError) {
+        self.network_state = match error {
+            MediaError::NotSupported(_) if self.decoder.is_none() => NetworkState::NoSource,
+            _ => NetworkState::Idle,
+        };
+        self.fetching = false;
+        self.clock.stop(Instant::now());
+        self.error = Some(error);
+        self.events.push(MediaEvent::Error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::Backends;
+    use std::time::Duration;
+
+    /// A second of mono 16-bit WAV at 8 kHz
+    fn wav() -> Vec<u8> {
+        let samples: Vec<i16> = (0..8000).map(|i| ((i as f32 / 10.0).sin() * 8000.0) as i16).collect();
+        let data_len = samples.len() as u32 * 2;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&8000u32.to_le_bytes());
+        bytes.extend_from_slice(&16000u32.to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        samples.iter().for_each(|sample| bytes.extend_from_slice(&sample.to_le_bytes()));
+        bytes
+    }
+
+    /// Answer the player's range requests from `resource`, at most `limit` bytes at a time
+    fn serve(player: &mut MediaPlayer, resource: &[u8], limit: u64, now: Instant) {
+        while let Some((url, range)) = player.next_fetch() {
+            let end = range.end.min(range.start + limit) as usize;
+            let bytes = resource[range.start as usize..end].to_vec();
+            let chunk = RangeChunk { start: range.start, bytes, total_length: Some(resource.len() as u64) };
+            player.receive(&url, Ok(chunk), now);
+        }
+    }
+
+    fn events(player: &mut MediaPlayer) -> Vec<&'static str> {
+        player.take_events().iter().map(MediaEvent::as_str).filter(|event| *event != "progress").collect()
+    }
+
+    #[test]
+    fn test_wav_loads_plays_and_ends() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let resource = wav();
+        let mut player = MediaPlayer::new(Arc::new(Backends::new()));
+        player.load(Url::parse("https://a.test/tone.wav").unwrap(), "audio/wav");
+        player.play(start);
+        serve(&mut player, &resource, 4096, start);
+
+        assert_eq!(player.duration(), Some(1.0));
+        assert_eq!(player.ready_state(), ReadyState::HaveEnoughData);
+        assert_eq!(player.network_state(), NetworkState::Idle);
+        assert_eq!(
+            events(&mut player),
+            vec![
+                "loadstart", "play", "waiting", "durationchange", "loadedmetadata", "loadeddata", "canplay", "playing",
+                "suspend", "canplaythrough"
+            ]
+        );
+        assert!(player.is_playing());
+        let audio = player.take_audio();
+        assert!(audio.iter().all(|chunk| chunk.sample_rate == 8000 && chunk.channels == 1));
+        assert!(audio.iter().map(AudioChunk::duration).sum::<f64>() >= DECODE_AHEAD);
+
+        player.update(at(300));
+        assert!((player.current_time(at(300)) - 0.3).abs() < 1e-9);
+        assert_eq!(events(&mut player), vec!["timeupdate"]);
+        player.pause(at(400));
+        player.seek(0.8, at(400));
+        player.update(at(400));
+        assert_eq!(
+            events(&mut player),
+            vec!["timeupdate", "pause", "seeking", "timeupdate", "seeked", "canplay", "canplaythrough"]
+        );
+        assert_eq!(player.current_time(at(400)), 0.8);
+
+        player.play(at(400));
+        player.update(at(1500));
+        assert!(player.ended() && player.paused());
+        assert_eq!(player.current_time(at(1600)), 1.0);
+        assert_eq!(events(&mut player), vec!["play", "playing", "timeupdate", "pause", "ended"]);
+    }
+
+    #[test]
+    fn test_unplayable_type_fails() {
+        let mut player = MediaPlayer::new(Arc::new(Backends::new()));
+        player.load(Url::parse("https://a.test/clip.mkv").unwrap(), "video/x-matroska");
+        assert_eq!(player.next_fetch(), None);
+        assert_eq!(player.network_state(), NetworkState::NoSource);
+        assert_eq!(player.state(Instant::now()).error, Some(4));
+        assert_eq!(events(&mut player), vec!["loadstart", "error"]);
+    }
+}
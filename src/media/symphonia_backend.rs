@@ -0,0 +1,202 @@
+// Symphonia backend - audio in the common web containers and codecs
+//
+// Plays MP3, AAC in MP4, Vorbis in Ogg and WebM, FLAC and WAV. A read that
+// runs out of buffered bytes part way through a packet leaves the demuxer
+// mid-packet, so decoding picks up again by seeking back to where the last
+// whole packet ended once more bytes have arrived.
+
+use std::io;
+
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::{MediaSource, MediaSourceStream};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use symphonia::core::units::{Time, TimeBase};
+
+use super::backend::{AudioChunk, AudioInfo, CanPlay, Decoded, MediaBackend, MediaDecoder, MediaInfo};
+use super::buffer::BufferReader;
+use super::MediaError;
+
+impl MediaSource for BufferReader {
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        self.total_length()
+    }
+}
+
+/// Decodes audio with symphonia
+pub struct SymphoniaBackend;
+
+impl MediaBackend for SymphoniaBackend {
+    fn can_play_type(&self, mime_type: &str) -> CanPlay {
+        let mut parts = mime_type.split(';');
+        let essence = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+        let container = matches!(
+            essence.as_str(),
+            "audio/mpeg" | "audio/mp3" | "audio/mp4" | "audio/aac" | "audio/x-m4a" | "audio/ogg" | "audio/webm"
+                | "audio/flac" | "audio/x-flac" | "audio/wav" | "audio/wave" | "audio/x-wav"
+        );
+        if !container {
+            return CanPlay::No;
+        }
+        let codecs = parts.find_map(|part| part.trim().strip_prefix("codecs="));
+        let Some(codecs) = codecs else {
+            return CanPlay::Maybe;
+        };
+        let supported = codecs.trim_matches('"').split(',').all(|codec| {
+            let codec = codec.trim().to_ascii_lowercase();
+            matches!(codec.as_str(), "mp3" | "vorbis" | "flac" | "1") || codec.starts_with("mp4a.40")
+        });
+        if supported {
+            CanPlay::Probably
+        } else {
+            CanPlay::No
+        }
+    }
+
+    fn open(&self, reader: BufferReader, mime_type: &str) -> Result<Option<Box<dyn MediaDecoder>>, MediaError> {
+        let mut hint = Hint::new();
+        let essence = mime_type.split(';').next().unwrap_or_default().trim();
+        if !essence.is_empty() {
+            hint.mime_type(essence);
+        }
+        let stream = MediaSourceStream::new(Box::new(reader), Default::default());
+        let options = FormatOptions { enable_gapless: true, ..Default::default() };
+        let metadata = MetadataOptions::default();
+        let probed = match symphonia::default::get_probe().format(&hint, stream, &options, &metadata) {
+            Ok(probed) => probed,
+            Err(e) if is_starved(&e) => return Ok(None),
+            Err(e) => return Err(media_error(e)),
+        };
+        let format = probed.format;
+        let track = format
+            .tracks()
+            .iter()
+            .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or_else(|| MediaError::NotSupported("no audio track".to_string()))?;
+        let params = &track.codec_params;
+        let decoder = symphonia::default::get_codecs()
+            .make(params, &DecoderOptions::default())
+            .map_err(|e| MediaError::NotSupported(e.to_string()))?;
+
+        let sample_rate = params.sample_rate.unwrap_or(0);
+        let time_base = params.time_base.unwrap_or_else(|| TimeBase::new(1, sample_rate.max(1)));
+        let info = MediaInfo {
+            duration: params.n_frames.map(|frames| seconds(time_base.calc_time(frames))),
+            audio: Some(AudioInfo {
+                sample_rate,
+                channels: params.channels.map_or(0, |channels| channels.count() as u16),
+            }),
+            video: None,
+        };
+        let track_id = track.id;
+        let decoder = SymphoniaDecoder { format, decoder, track_id, time_base, info, last_end: 0.0, resume_at: None };
+        Ok(Some(Box::new(decoder)))
+    }
+}
+
+/// An audio track being decoded
+struct SymphoniaDecoder {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    time_base: TimeBase,
+    info: MediaInfo,
+    /// Media time the last packet decoded ends at
+    last_end: f64,
+    /// Where decoding picks up after running out of bytes, if it did
+    resume_at: Option<f64>,
+}
+
+impl SymphoniaDecoder {
+    /// Seek the demuxer, `Ok(None)` if it ran out of bytes
+    fn seek_to(&mut self, time: f64) -> Result<Option<f64>, MediaError> {
+        let to = SeekTo::Time { time: Time::from(time.max(0.0)), track_id: Some(self.track_id) };
+        match self.format.seek(SeekMode::Accurate, to) {
+            Ok(seeked) => {
+                self.decoder.reset();
+                self.last_end = seconds(self.time_base.calc_time(seeked.actual_ts));
+                Ok(Some(self.last_end))
+            }
+            Err(e) if is_starved(&e) => Ok(None),
+            Err(e) => Err(media_error(e)),
+        }
+    }
+}
+
+impl MediaDecoder for SymphoniaDecoder {
+    fn info(&self) -> &MediaInfo {
+        &self.info
+    }
+
+    fn decode(&mut self) -> Result<Decoded, MediaError> {
+        if let Some(time) = self.resume_at {
+            if self.seek_to(time)?.is_none() {
+                return Ok(Decoded::Waiting);
+            }
+            self.resume_at = None;
+        }
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(Error::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(Decoded::Ended),
+                Err(e) if is_starved(&e) => {
+                    self.resume_at = Some(self.last_end);
+                    return Ok(Decoded::Waiting);
+                }
+                Err(e) => return Err(media_error(e)),
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+            let timestamp = seconds(self.time_base.calc_time(packet.ts()));
+            let end = seconds(self.time_base.calc_time(packet.ts() + packet.dur()));
+            match self.decoder.decode(&packet) {
+                Ok(decoded) => {
+                    let spec = *decoded.spec();
+                    let mut samples = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+                    samples.copy_interleaved_ref(decoded);
+                    self.last_end = end;
+                    return Ok(Decoded::Audio(AudioChunk {
+                        timestamp,
+                        sample_rate: spec.rate,
+                        channels: spec.channels.count() as u16,
+                        samples: samples.samples().to_vec(),
+                    }));
+                }
+                // A damaged packet is skipped, as a player would
+                Err(Error::DecodeError(_)) => continue,
+                Err(e) => return Err(media_error(e)),
+            }
+        }
+    }
+
+    fn seek(&mut self, time: f64) -> Result<Option<f64>, MediaError> {
+        let seeked = self.seek_to(time)?;
+        // A seek that waits for bytes is tried again by the next decode
+        self.resume_at = if seeked.is_some() { None } else { Some(time) };
+        Ok(seeked)
+    }
+}
+
+/// Did a read run out of buffered bytes
+fn is_starved(error: &Error) -> bool {
+    matches!(error, Error::IoError(e) if e.kind() == io::ErrorKind::WouldBlock)
+}
+
+fn media_error(error: Error) -> MediaError {
+    match error {
+        Error::Unsupported(what) => MediaError::NotSupported(what.to_string()),
+        error => MediaError::Decode(error.to_string()),
+    }
+}
+
+fn seconds(time: Time) -> f64 {
+    time.seconds as f64 + time.frac
+}
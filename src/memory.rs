@@ -0,0 +1,305 @@
+// Memory accounting - what the browser's memory goes to, and shedding it
+//
+// A `MemoryReport` adds up the bytes held by each kind of store: the tabs'
+// DOM arenas, decoded images, the glyph atlas, the HTTP cache's memory
+// tier, compositor tile backing stores and script heaps. Sizes are
+// estimates from the data each store holds, not allocator figures. The
+// `MemoryMonitor` compares the total against a budget from time to time and
+// raises a process-wide pressure level, which the browser answers by
+// evicting caches that refill on demand and, when that is not enough, by
+// discarding background tabs. `about:memory` shows the latest report.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::ui::TabId;
+
+/// Budget used when none is configured
+pub const DEFAULT_MEMORY_BUDGET: usize = 1024 * 1024 * 1024;
+
+/// Share of the budget past which memory is under moderate pressure
+const MODERATE_FRACTION: f64 = 0.8;
+
+/// How often the monitor takes a report
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Kind of store memory is held in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MemorySource {
+    /// Nodes of a tab's document
+    Dom,
+    /// Decoded images
+    Images,
+    /// Rasterized glyphs
+    GlyphAtlas,
+    /// Responses held in memory by the HTTP cache
+    HttpCache,
+    /// Backing stores of compositor tiles
+    CompositorTiles,
+    /// Script engine heaps
+    JsHeap,
+}
+
+impl MemorySource {
+    pub const ALL: [MemorySource; 6] = [
+        MemorySource::Dom,
+        MemorySource::Images,
+        MemorySource::GlyphAtlas,
+        MemorySource::HttpCache,
+        MemorySource::CompositorTiles,
+        MemorySource::JsHeap,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MemorySource::Dom => "DOM",
+            MemorySource::Images => "Images",
+            MemorySource::GlyphAtlas => "Glyph atlas",
+            MemorySource::HttpCache => "HTTP cache",
+            MemorySource::CompositorTiles => "Compositor tiles",
+            MemorySource::JsHeap => "JavaScript heap",
+        }
+    }
+}
+
+/// Bytes one store holds, for a tab or the whole browser
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryEntry {
+    pub source: MemorySource,
+    /// Tab the memory is held for; None for stores the tabs share
+    pub tab: Option<TabId>,
+    pub bytes: usize,
+}
+
+/// Memory use across the browser at one moment
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryReport {
+    pub entries: Vec<MemoryEntry>,
+    /// Titles of the tabs entries are held for
+    tab_names: BTreeMap<TabId, String>,
+}
+
+impl MemoryReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count memory held for the whole browser
+    pub fn add(&mut self, source: MemorySource, bytes: usize) {
+        self.entries.push(MemoryEntry { source, tab: None, bytes });
+    }
+
+    /// Count memory held for a tab
+    pub fn add_for_tab(&mut self, tab: TabId, name: &str, source: MemorySource, bytes: usize) {
+        self.tab_names.entry(tab).or_insert_with(|| name.to_string());
+        self.entries.push(MemoryEntry { source, tab: Some(tab), bytes });
+    }
+
+    /// Bytes across every store
+    pub fn total(&self) -> usize {
+        self.entries.iter().map(|entry| entry.bytes).sum()
+    }
+
+    /// Bytes held in one kind of store
+    pub fn total_for(&self, source: MemorySource) -> usize {
+        self.entries.iter().filter(|entry| entry.source == source).map(|entry| entry.bytes).sum()
+    }
+
+    /// Bytes held for a tab
+    pub fn tab_total(&self, tab: TabId) -> usize {
+        self.entries.iter().filter(|entry| entry.tab == Some(tab)).map(|entry| entry.bytes).sum()
+    }
+
+    /// Tabs with memory counted, largest first
+    pub fn tabs_by_size(&self) -> Vec<(TabId, usize)> {
+        let mut tabs: Vec<(TabId, usize)> = self.tab_names.keys().map(|&tab| (tab, self.tab_total(tab))).collect();
+        tabs.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        tabs
+    }
+
+    /// The report as the `about:memory` page
+    pub fn to_html(&self, budget: usize) -> String {
+        let mut html = String::from("<html><head><title>about:memory</title></head><body><h1>Memory</h1>");
+        html.push_str(&format!(
+            "<p>{} in use of a {} budget; pressure: {}</p><h2>By kind</h2><ul>",
+            format_bytes(self.total()),
+            format_bytes(budget),
+            MemoryPressure::for_usage(self.total(), budget).as_str()
+        ));
+        for source in MemorySource::ALL {
+            html.push_str(&format!("<li>{}: {}</li>", source.as_str(), format_bytes(self.total_for(source))));
+        }
+        html.push_str("</ul><h2>By tab</h2><ul>");
+        for (tab, bytes) in self.tabs_by_size() {
+            html.push_str(&format!("<li>{}: {}<ul>", escape(&self.tab_names[&tab]), format_bytes(bytes)));
+            for entry in self.entries.iter().filter(|entry| entry.tab == Some(tab)) {
+                html.push_str(&format!("<li>{}: {}</li>", entry.source.as_str(), format_bytes(entry.bytes)));
+            }
+            html.push_str("</ul></li>");
+        }
+        html.push_str("</ul></body></html>");
+        html
+    }
+}
+
+/// Bytes as the largest unit that keeps them above one
+pub fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "kB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// How close memory use is to the budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum MemoryPressure {
+    #[default]
+    None,
+    /// Caches should shed what they can refill
+    Moderate,
+    /// Over budget: background tabs should be discarded too
+    Critical,
+}
+
+impl MemoryPressure {
+    /// Pressure of using `used` bytes of a `budget`
+    pub fn for_usage(used: usize, budget: usize) -> Self {
+        if used >= budget {
+            MemoryPressure::Critical
+        } else if used as f64 >= budget as f64 * MODERATE_FRACTION {
+            MemoryPressure::Moderate
+        } else {
+            MemoryPressure::None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MemoryPressure::None => "none",
+            MemoryPressure::Moderate => "moderate",
+            MemoryPressure::Critical => "critical",
+        }
+    }
+
+    fn from_u8(level: u8) -> Self {
+        match level {
+            0 => MemoryPressure::None,
+            1 => MemoryPressure::Moderate,
+            _ => MemoryPressure::Critical,
+        }
+    }
+}
+
+/// The process-wide pressure level
+static PRESSURE: AtomicU8 = AtomicU8::new(0);
+
+/// Set the process-wide pressure level, returning the one it replaces
+pub fn signal_pressure(pressure: MemoryPressure) -> MemoryPressure {
+    MemoryPressure::from_u8(PRESSURE.swap(pressure as u8, Ordering::Relaxed))
+}
+
+/// Pressure as last signalled
+pub fn current_pressure() -> MemoryPressure {
+    MemoryPressure::from_u8(PRESSURE.load(Ordering::Relaxed))
+}
+
+/// Takes reports now and then and signals the pressure they show
+#[derive(Debug, Clone)]
+pub struct MemoryMonitor {
+    /// Bytes the browser aims to stay within
+    budget: usize,
+    last_check: Option<Instant>,
+}
+
+impl MemoryMonitor {
+    pub fn new(budget: usize) -> Self {
+        Self { budget, last_check: None }
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Is a report due, noting that one is taken if so
+    pub fn due(&mut self, now: Instant) -> bool {
+        if self.last_check.is_some_and(|last| now.duration_since(last) < CHECK_INTERVAL) {
+            return false;
+        }
+        self.last_check = Some(now);
+        true
+    }
+
+    /// Signal the pressure a report shows
+    pub fn assess(&self, report: &MemoryReport) -> MemoryPressure {
+        let pressure = MemoryPressure::for_usage(report.total(), self.budget);
+        signal_pressure(pressure);
+        pressure
+    }
+
+    /// Bytes a report's use would have to shrink by to be out of pressure
+    pub fn relief_needed(&self, report: &MemoryReport) -> usize {
+        let moderate = (self.budget as f64 * MODERATE_FRACTION) as usize;
+        (report.total() + 1).saturating_sub(moderate)
+    }
+}
+
+impl Default for MemoryMonitor {
+    fn default() -> Self {
+        Self::new(DEFAULT_MEMORY_BUDGET)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_totals_and_pressure() {
+        let mut report = MemoryReport::new();
+        report.add(MemorySource::Images, 300);
+        report.add_for_tab(1, "News", MemorySource::Dom, 100);
+        report.add_for_tab(1, "News", MemorySource::JsHeap, 50);
+        report.add_for_tab(2, "Mail <inbox>", MemorySource::Dom, 400);
+        assert_eq!(report.total(), 850);
+        assert_eq!(report.total_for(MemorySource::Dom), 500);
+        assert_eq!(report.tabs_by_size(), vec![(2, 400), (1, 150)]);
+
+        assert_eq!(MemoryPressure::for_usage(report.total(), 1000), MemoryPressure::Moderate);
+        assert_eq!(MemoryPressure::for_usage(report.total(), 2000), MemoryPressure::None);
+        assert_eq!(MemoryPressure::for_usage(report.total(), 850), MemoryPressure::Critical);
+
+        let html = report.to_html(1000);
+        assert!(html.contains("850 B in use of a 1000 B budget; pressure: moderate"));
+        assert!(html.contains("Mail &lt;inbox&gt;: 400 B"));
+        assert_eq!(format_bytes(3 * 1024 * 1024 / 2), "1.5 MB");
+    }
+
+    #[test]
+    fn test_monitor_signals_pressure() {
+        let mut monitor = MemoryMonitor::new(100);
+        let start = Instant::now();
+        assert!(monitor.due(start));
+        assert!(!monitor.due(start + Duration::from_secs(1)));
+        assert!(monitor.due(start + CHECK_INTERVAL));
+
+        let mut report = MemoryReport::new();
+        report.add(MemorySource::HttpCache, 120);
+        assert_eq!(monitor.assess(&report), MemoryPressure::Critical);
+        assert_eq!(monitor.relief_needed(&report), 41);
+        assert_eq!(current_pressure(), MemoryPressure::Critical);
+        assert_eq!(signal_pressure(MemoryPressure::None), MemoryPressure::Critical);
+    }
+}
@@ -0,0 +1,833 @@
+// Site-isolated content processes
+//
+// The browser process supervises one content process per site, and keeps
+// the tree of frames in each tab: a cross-site iframe lives in its own
+// site's process, and other processes reach it through a proxy. As a
+// first step a content process is a thread that shares nothing with the
+// browser: it owns its handler, runs sandboxed, and reaches the outside
+// world only through its IPC channel and its broker. The channel is a
+// socket carrying the versioned protocol of `ipc`, opened with a handshake. A thread that panics or
+// exits on its own is a crash; the frames it hosted are marked crashed until
+// they are navigated or reloaded, which starts a fresh process for them.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use url::Url;
+
+use super::message_bus::target_origin_matches;
+use super::sandbox::{enter_sandbox, spawn_broker, BrokerClient, BrokerPolicy};
+use super::shm::shm_dir;
+use super::ipc::{channel_pair, BrowserChannel, BrowserMessage, ContentChannel, ContentMessage, IpcStream};
+use super::{MultiprocessError, ProcessId, ProcessManager, ProcessState, ProcessType, SerializedValue, PROTOCOL_VERSION};
+use crate::js::JsRuntime;
+
+/// How long a new process has to answer the browser's `Hello`
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Runs inside a content process, answering the browser's messages
+pub trait ContentHandler {
+    /// Handle one message, returning the reply if there is one
+    fn handle(&mut self, message: BrowserMessage) -> Option<ContentMessage>;
+}
+
+/// Builds the handler for a new process, given the site it hosts and
+/// the broker for its files and connections
+pub type HandlerFactory = Arc<dyn Fn(&str, BrokerClient) -> Box<dyn ContentHandler> + Send + Sync>;
+
+/// Default handler: answers pings and evaluates scripts
+///
+/// The script runtime is shared by the site's tabs, like a worker.
+#[derive(Default)]
+pub struct ScriptHost {
+    runtime: Option<JsRuntime>,
+}
+
+impl ScriptHost {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ContentHandler for ScriptHost {
+    fn handle(&mut self, message: BrowserMessage) -> Option<ContentMessage> {
+        match message {
+            BrowserMessage::Ping => Some(ContentMessage::Pong),
+            BrowserMessage::EvalScript { script } => {
+                let runtime = self.runtime.get_or_insert_with(JsRuntime::new);
+                let result = match runtime.execute(&script) {
+                    Ok(value) => value.to_string(),
+                    Err(e) => e.to_string(),
+                };
+                Some(ContentMessage::ScriptResult { result })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Browsing context ID: a tab's main frame or an iframe
+pub type FrameId = u64;
+
+/// Something that happened in a content process
+#[derive(Debug, Clone)]
+pub enum ContentEvent {
+    /// A message from the process
+    Message { process_id: ProcessId, message: ContentMessage },
+    /// The process died. `frames` are all the frames it hosted; `tabs`
+    /// lost their page and now show the crash page.
+    Crashed { process_id: ProcessId, site: String, frames: Vec<FrameId>, tabs: Vec<u64> },
+}
+
+/// Handle a process holds for a frame another process hosts
+///
+/// It stands in for the frame's window: messages posted to it are
+/// forwarded by the browser, but its document cannot be reached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameProxy {
+    pub frame_id: FrameId,
+    /// Process hosting the frame
+    pub process_id: ProcessId,
+    /// Origin of the frame's document
+    pub origin: String,
+}
+
+/// A running content process, seen from the browser
+struct ContentProcess {
+    site: String,
+    channel: BrowserChannel<IpcStream>,
+    thread: JoinHandle<()>,
+}
+
+/// A frame in a tab's frame tree and where its document lives
+struct FrameEntry {
+    tab_id: u64,
+    parent: Option<FrameId>,
+    process: ProcessId,
+    url: Url,
+    crashed: bool,
+}
+
+/// Browser-side owner of the content processes and the frame trees
+pub struct ContentSupervisor {
+    manager: ProcessManager,
+    browser_process: ProcessId,
+    factory: HandlerFactory,
+    /// What new processes may ask their broker for
+    policy: BrokerPolicy,
+    /// Are new processes sandboxed
+    sandboxed: bool,
+    processes: HashMap<ProcessId, ContentProcess>,
+    /// Live process for each site
+    sites: HashMap<String, ProcessId>,
+    frames: HashMap<FrameId, FrameEntry>,
+    /// Main frame of each tab
+    main_frames: HashMap<u64, FrameId>,
+    next_frame: FrameId,
+}
+
+impl ContentSupervisor {
+    /// Create a supervisor whose processes run `ScriptHost`
+    pub fn new() -> Self {
+        Self::with_handler(Arc::new(|_: &str, _: BrokerClient| Box::new(ScriptHost::new()) as Box<dyn ContentHandler>))
+    }
+
+    /// Create a supervisor whose processes run handlers from `factory`
+    pub fn with_handler(factory: HandlerFactory) -> Self {
+        let mut manager = ProcessManager::new();
+        let browser_process = manager
+            .spawn_process(ProcessType::Browser, None)
+            .expect("a new process manager has room for the browser process");
+        // Processes can map the shared memory they exchange with the browser
+        let mut policy = BrokerPolicy::new();
+        policy.allow_write(shm_dir());
+        Self {
+            manager,
+            browser_process,
+            factory,
+            policy,
+            sandboxed: true,
+            processes: HashMap::new(),
+            sites: HashMap::new(),
+            frames: HashMap::new(),
+            main_frames: HashMap::new(),
+            next_frame: 0,
+        }
+    }
+
+    /// ID of the browser process itself
+    pub fn browser_process(&self) -> ProcessId {
+        self.browser_process
+    }
+
+    /// Run new processes without the OS sandbox, for debugging
+    pub fn set_sandboxed(&mut self, sandboxed: bool) {
+        self.sandboxed = sandboxed;
+    }
+
+    /// Set what new processes may ask their broker for
+    pub fn set_broker_policy(&mut self, policy: BrokerPolicy) {
+        self.policy = policy;
+    }
+
+    /// Navigate a tab's main frame to `url`, in its site's process
+    ///
+    /// Starts the process if the site has none. A crashed tab is recovered
+    /// this way, and a process left without frames is shut down.
+    pub fn assign_tab(&mut self, tab_id: u64, url: &Url) -> Result<ProcessId, MultiprocessError> {
+        match self.main_frames.get(&tab_id) {
+            Some(&frame_id) => self.navigate_frame(frame_id, url),
+            None => {
+                let frame_id = self.create_frame(tab_id, None, url)?;
+                self.main_frames.insert(tab_id, frame_id);
+                Ok(self.frames[&frame_id].process)
+            }
+        }
+    }
+
+    /// Add an iframe loading `url` to the document in `parent`
+    ///
+    /// A cross-site iframe gets its own site's process.
+    pub fn attach_frame(&mut self, parent: FrameId, url: &Url) -> Result<FrameId, MultiprocessError> {
+        let tab_id = match self.frames.get(&parent) {
+            Some(entry) if !entry.crashed => entry.tab_id,
+            Some(_) => return Err(MultiprocessError::ProcessNotFound),
+            None => return Err(MultiprocessError::FrameNotFound),
+        };
+        self.create_frame(tab_id, Some(parent), url)
+    }
+
+    /// Load `url` in a frame, moving it to another process if the site
+    /// changes; the frame's iframes go away with its old document
+    pub fn navigate_frame(&mut self, frame_id: FrameId, url: &Url) -> Result<ProcessId, MultiprocessError> {
+        if !self.frames.contains_key(&frame_id) {
+            return Err(MultiprocessError::FrameNotFound);
+        }
+        for child in self.child_frames(frame_id) {
+            self.detach_frame(child);
+        }
+        let process_id = self.process_for_site(url)?;
+        let entry = self.frames.get_mut(&frame_id).ok_or(MultiprocessError::FrameNotFound)?;
+        let previous = std::mem::replace(&mut entry.process, process_id);
+        let was_crashed = std::mem::replace(&mut entry.crashed, false);
+        entry.url = url.clone();
+        let tab_id = entry.tab_id;
+        if previous != process_id && !was_crashed {
+            self.release_if_unused(previous);
+        }
+        self.send(process_id, &BrowserMessage::Navigate { tab_id, frame_id, url: url.to_string() })?;
+        Ok(process_id)
+    }
+
+    /// Remove a frame and its descendants
+    pub fn detach_frame(&mut self, frame_id: FrameId) {
+        for child in self.child_frames(frame_id) {
+            self.detach_frame(child);
+        }
+        if let Some(entry) = self.frames.remove(&frame_id) {
+            if entry.parent.is_none() {
+                self.main_frames.remove(&entry.tab_id);
+            }
+            self.release_if_unused(entry.process);
+        }
+    }
+
+    /// Send a message to the process hosting a tab's page
+    pub fn send_to_tab(&mut self, tab_id: u64, message: &BrowserMessage) -> Result<(), MultiprocessError> {
+        match self.main_frames.get(&tab_id).and_then(|id| self.frames.get(id)) {
+            Some(entry) if !entry.crashed => self.send(entry.process, message),
+            _ => Err(MultiprocessError::ProcessNotFound),
+        }
+    }
+
+    /// `postMessage` from the document in `source` to the window of `target`
+    ///
+    /// `target_origin` is `*`, `/` or a URL, as for `MessageBus::post_message`.
+    /// The message goes to the process hosting `target`, whichever process
+    /// the sender is in. Returns false if the target's origin did not match.
+    pub fn post_message(
+        &mut self,
+        source: FrameId,
+        target: FrameId,
+        target_origin: &str,
+        data: &SerializedValue,
+    ) -> Result<bool, MultiprocessError> {
+        let source_entry = self.frames.get(&source).ok_or(MultiprocessError::FrameNotFound)?;
+        let target_entry = self.frames.get(&target).ok_or(MultiprocessError::FrameNotFound)?;
+        if target_entry.crashed {
+            return Err(MultiprocessError::ProcessNotFound);
+        }
+        let origin = source_entry.url.origin().ascii_serialization();
+        if !target_origin_matches(target_origin, &origin, &target_entry.url.origin().ascii_serialization())? {
+            return Ok(false);
+        }
+        let message = BrowserMessage::PostMessage {
+            target_frame: target,
+            source_frame: source,
+            origin,
+            data: data.as_json().to_string(),
+        };
+        self.send(target_entry.process, &message)?;
+        Ok(true)
+    }
+
+    /// How the document in `viewer` reaches `frame_id`: `None` if they
+    /// share a process, otherwise a proxy for the remote frame
+    pub fn proxy(&self, frame_id: FrameId, viewer: FrameId) -> Option<FrameProxy> {
+        let frame = self.frames.get(&frame_id)?;
+        let viewer = self.frames.get(&viewer)?;
+        (frame.process != viewer.process).then(|| FrameProxy {
+            frame_id,
+            process_id: frame.process,
+            origin: frame.url.origin().ascii_serialization(),
+        })
+    }
+
+    /// Collect messages and detect processes that died
+    pub fn poll(&mut self) -> Vec<ContentEvent> {
+        let mut events = Vec::new();
+        let mut exited = Vec::new();
+        for (&process_id, process) in self.processes.iter_mut() {
+            // Only what has arrived is read; sending stays blocking
+            let _ = process.channel.stream().set_nonblocking(true);
+            while let Ok(Some(message)) = process.channel.recv() {
+                events.push(ContentEvent::Message { process_id, message });
+            }
+            let _ = process.channel.stream().set_nonblocking(false);
+            if process.thread.is_finished() {
+                exited.push(process_id);
+            }
+        }
+
+        for process_id in exited {
+            let Some(process) = self.processes.remove(&process_id) else {
+                continue;
+            };
+            // Processes only stop on their own by crashing; panics end up here
+            let _ = process.thread.join();
+            self.sites.remove(&process.site);
+            let _ = self.manager.mark_process_crashed(process_id);
+            self.manager.cleanup_crashed_processes();
+            let (frames, tabs) = self.mark_frames_crashed(process_id);
+            events.push(ContentEvent::Crashed { process_id, site: process.site, frames, tabs });
+        }
+        events
+    }
+
+    /// Stop a process, leaving its frames crashed; returns the tabs whose
+    /// page it hosted
+    pub fn terminate_process(&mut self, process_id: ProcessId) -> Result<Vec<u64>, MultiprocessError> {
+        if !self.processes.contains_key(&process_id) {
+            return Err(MultiprocessError::ProcessNotFound);
+        }
+        self.stop(process_id);
+        Ok(self.mark_frames_crashed(process_id).1)
+    }
+
+    /// Make the process hosting a tab crash, to exercise recovery
+    pub fn crash_tab(&mut self, tab_id: u64) -> Result<(), MultiprocessError> {
+        self.send_to_tab(tab_id, &BrowserMessage::Crash)
+    }
+
+    /// Forget a closed tab, shutting down processes no frame uses anymore
+    pub fn close_tab(&mut self, tab_id: u64) {
+        if let Some(&frame_id) = self.main_frames.get(&tab_id) {
+            self.detach_frame(frame_id);
+        }
+    }
+
+    /// Has the process showing this tab's page crashed
+    pub fn is_crashed(&self, tab_id: u64) -> bool {
+        self.main_frame(tab_id).is_some_and(|id| self.frames[&id].crashed)
+    }
+
+    /// URL the tab was showing, for its crash page
+    pub fn tab_url(&self, tab_id: u64) -> Option<&Url> {
+        self.frame_url(self.main_frame(tab_id)?)
+    }
+
+    /// Live process hosting a tab's page
+    pub fn process_for_tab(&self, tab_id: u64) -> Option<ProcessId> {
+        self.frame_process(self.main_frame(tab_id)?)
+    }
+
+    /// Tabs whose page a process hosts
+    pub fn tabs_in_process(&self, process_id: ProcessId) -> Vec<u64> {
+        let mut tabs: Vec<u64> = self
+            .main_frames
+            .iter()
+            .filter(|(_, frame_id)| self.frame_process(**frame_id) == Some(process_id))
+            .map(|(tab_id, _)| *tab_id)
+            .collect();
+        tabs.sort_unstable();
+        tabs
+    }
+
+    /// Main frame of a tab
+    pub fn main_frame(&self, tab_id: u64) -> Option<FrameId> {
+        self.main_frames.get(&tab_id).copied()
+    }
+
+    /// Frame embedding `frame_id`, if it is an iframe
+    pub fn frame_parent(&self, frame_id: FrameId) -> Option<FrameId> {
+        self.frames.get(&frame_id)?.parent
+    }
+
+    /// Iframes of the document in a frame
+    pub fn child_frames(&self, frame_id: FrameId) -> Vec<FrameId> {
+        let mut children: Vec<FrameId> = self
+            .frames
+            .iter()
+            .filter(|(_, entry)| entry.parent == Some(frame_id))
+            .map(|(id, _)| *id)
+            .collect();
+        children.sort_unstable();
+        children
+    }
+
+    /// URL of a frame's document
+    pub fn frame_url(&self, frame_id: FrameId) -> Option<&Url> {
+        self.frames.get(&frame_id).map(|entry| &entry.url)
+    }
+
+    /// Live process hosting a frame
+    pub fn frame_process(&self, frame_id: FrameId) -> Option<ProcessId> {
+        self.frames.get(&frame_id).filter(|entry| !entry.crashed).map(|entry| entry.process)
+    }
+
+    /// Is a frame hosted by another process than its parent
+    pub fn is_out_of_process(&self, frame_id: FrameId) -> bool {
+        self.frame_parent(frame_id)
+            .is_some_and(|parent| self.frames[&parent].process != self.frames[&frame_id].process)
+    }
+
+    /// Site a process hosts
+    pub fn site(&self, process_id: ProcessId) -> Option<&str> {
+        self.processes.get(&process_id).map(|process| process.site.as_str())
+    }
+
+    /// State of a process; `None` once it is gone
+    pub fn process_state(&self, process_id: ProcessId) -> Option<ProcessState> {
+        self.manager.get_process_info(process_id).map(|info| info.state)
+    }
+
+    /// Number of running content processes
+    pub fn process_count(&self) -> usize {
+        self.processes.len()
+    }
+
+    /// Shut down every content process
+    pub fn shutdown(&mut self) {
+        let ids: Vec<ProcessId> = self.processes.keys().copied().collect();
+        for process_id in ids {
+            self.stop(process_id);
+        }
+    }
+
+    fn create_frame(&mut self, tab_id: u64, parent: Option<FrameId>, url: &Url) -> Result<FrameId, MultiprocessError> {
+        let process = self.process_for_site(url)?;
+        self.next_frame += 1;
+        let frame_id = self.next_frame;
+        self.frames.insert(frame_id, FrameEntry { tab_id, parent, process, url: url.clone(), crashed: false });
+        self.send(process, &BrowserMessage::Navigate { tab_id, frame_id, url: url.to_string() })?;
+        Ok(frame_id)
+    }
+
+    /// Live process for the site of `url`, started if needed
+    fn process_for_site(&mut self, url: &Url) -> Result<ProcessId, MultiprocessError> {
+        let site = site_key(url);
+        match self.sites.get(&site) {
+            Some(&process_id) => Ok(process_id),
+            None => self.spawn(&site),
+        }
+    }
+
+    fn spawn(&mut self, site: &str) -> Result<ProcessId, MultiprocessError> {
+        let process_id = self.manager.spawn_process(ProcessType::Renderer, Some(self.browser_process))?;
+        let factory = Arc::clone(&self.factory);
+        let thread_site = site.to_string();
+        let sandboxed = self.sandboxed;
+        let started = channel_pair().map_err(|_| MultiprocessError::SpawnFailed).and_then(|(channel, content)| {
+            let broker = spawn_broker(self.policy.clone())?;
+            let thread = std::thread::Builder::new()
+                .name(format!("content-{}", process_id))
+                .spawn(move || {
+                    // Page content never runs unconfined; exiting here fails the handshake
+                    if sandboxed && enter_sandbox().is_err() {
+                        return;
+                    }
+                    run_content_process(factory(&thread_site, broker), content)
+                })
+                .map_err(|_| MultiprocessError::SpawnFailed)?;
+            Ok((channel, thread))
+        });
+        let process = started.and_then(|(mut channel, thread)| match handshake(&mut channel) {
+            Ok(()) => Ok(ContentProcess { site: site.to_string(), channel, thread }),
+            Err(e) => {
+                // Dropping the channel ends a process still waiting for its first message
+                drop(channel);
+                let _ = thread.join();
+                Err(e)
+            }
+        });
+        let process = match process {
+            Ok(process) => process,
+            Err(e) => {
+                let _ = self.manager.terminate_process(process_id);
+                return Err(e);
+            }
+        };
+
+        self.processes.insert(process_id, process);
+        self.sites.insert(site.to_string(), process_id);
+        Ok(process_id)
+    }
+
+    fn send(&mut self, process_id: ProcessId, message: &BrowserMessage) -> Result<(), MultiprocessError> {
+        let process = self.processes.get_mut(&process_id).ok_or(MultiprocessError::ProcessNotFound)?;
+        process.channel.send(message)
+    }
+
+    fn release_if_unused(&mut self, process_id: ProcessId) {
+        if self.frames.values().all(|entry| entry.process != process_id || entry.crashed) {
+            self.stop(process_id);
+        }
+    }
+
+    /// Ask a process to exit without waiting for it, so a hung page
+    /// cannot hang the browser
+    fn stop(&mut self, process_id: ProcessId) {
+        if let Some(mut process) = self.processes.remove(&process_id) {
+            let _ = process.channel.send(&BrowserMessage::Shutdown);
+            self.sites.remove(&process.site);
+            let _ = self.manager.terminate_process(process_id);
+        }
+    }
+
+    /// Mark the frames of a dead process crashed; returns them and the
+    /// tabs among them whose main frame it was
+    fn mark_frames_crashed(&mut self, process_id: ProcessId) -> (Vec<FrameId>, Vec<u64>) {
+        let mut frames = Vec::new();
+        let mut tabs = Vec::new();
+        for (&frame_id, entry) in self.frames.iter_mut() {
+            if entry.process == process_id && !entry.crashed {
+                entry.crashed = true;
+                frames.push(frame_id);
+                if entry.parent.is_none() {
+                    tabs.push(entry.tab_id);
+                }
+            }
+        }
+        frames.sort_unstable();
+        tabs.sort_unstable();
+        (frames, tabs)
+    }
+}
+
+impl Default for ContentSupervisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for ContentSupervisor {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Greet a new process and check it speaks our protocol version
+fn handshake(channel: &mut BrowserChannel<IpcStream>) -> Result<(), MultiprocessError> {
+    channel.send(&BrowserMessage::Hello { version: PROTOCOL_VERSION }).map_err(|_| MultiprocessError::SpawnFailed)?;
+    channel.stream().set_read_timeout(Some(HANDSHAKE_TIMEOUT)).map_err(|_| MultiprocessError::IpcError)?;
+    let reply = channel.recv();
+    channel.stream().set_read_timeout(None).map_err(|_| MultiprocessError::IpcError)?;
+    match reply {
+        Ok(Some(ContentMessage::Hello { version })) if version == PROTOCOL_VERSION => Ok(()),
+        Ok(Some(ContentMessage::Hello { version })) => {
+            Err(MultiprocessError::VersionMismatch { expected: PROTOCOL_VERSION, found: version })
+        }
+        Err(e @ MultiprocessError::VersionMismatch { .. }) => Err(e),
+        // No answer, or not a greeting: the process did not start
+        _ => Err(MultiprocessError::SpawnFailed),
+    }
+}
+
+/// Message loop of a content process
+///
+/// The browser speaks first; a process that does not get a `Hello` in
+/// its version exits.
+fn run_content_process(mut handler: Box<dyn ContentHandler>, mut channel: ContentChannel<IpcStream>) {
+    match channel.recv() {
+        Ok(Some(BrowserMessage::Hello { version })) if version == PROTOCOL_VERSION => {}
+        _ => return,
+    }
+    if channel.send(&ContentMessage::Hello { version: PROTOCOL_VERSION }).is_err() {
+        return;
+    }
+    while let Ok(Some(message)) = channel.recv() {
+        match message {
+            BrowserMessage::Shutdown => break,
+            BrowserMessage::Crash => panic!("content process crashed on request"),
+            message => {
+                if let Some(reply) = handler.handle(message) {
+                    if channel.send(&reply).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Site a URL's content is isolated by: scheme plus registrable domain
+///
+/// URLs without a host (`about:`, `data:`) share one process per scheme.
+pub fn site_key(url: &Url) -> String {
+    match crate::net::site(url) {
+        Some(site) => format!("{}://{}", url.scheme(), site),
+        None => format!("{}:", url.scheme()),
+    }
+}
+
+/// Page shown in place of a tab whose process crashed
+pub fn crash_page_html(url: &Url) -> String {
+    let url = url
+        .as_str()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    format!(
+        "<html><head><title>Aw, Snap!</title></head><body>\
+         <h1>Aw, Snap!</h1>\
+         <p>Something went wrong while displaying this webpage.</p>\
+         <p>{}</p>\
+         <p>Reload the page to try again.</p>\
+         </body></html>",
+        url
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::Color;
+    use crate::display::DisplayCommand;
+    use crate::layout::Rect;
+    use crate::multiprocess::{InputEvent, MouseButton};
+    use std::time::Instant;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    /// Poll until an event arrives or a second passes
+    fn wait_for_event(supervisor: &mut ContentSupervisor) -> Option<ContentEvent> {
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while Instant::now() < deadline {
+            if let Some(event) = supervisor.poll().into_iter().next() {
+                return Some(event);
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        None
+    }
+
+    #[test]
+    fn test_tabs_share_site_process() {
+        let mut supervisor = ContentSupervisor::new();
+        let a = supervisor.assign_tab(1, &url("https://www.example.com/a")).unwrap();
+        let b = supervisor.assign_tab(2, &url("https://example.com/b")).unwrap();
+        let other = supervisor.assign_tab(3, &url("https://other.org/")).unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a, other);
+        assert_eq!(supervisor.site(a), Some("https://example.com"));
+        assert_eq!(supervisor.tabs_in_process(a), vec![1, 2]);
+        assert_eq!(supervisor.process_state(a), Some(ProcessState::Running));
+
+        supervisor.send_to_tab(1, &BrowserMessage::EvalScript { script: "6 * 7".to_string() }).unwrap();
+        match wait_for_event(&mut supervisor) {
+            Some(ContentEvent::Message { process_id, message: ContentMessage::ScriptResult { result } }) => {
+                assert_eq!(process_id, a);
+                assert_eq!(result, "42");
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        // A process is shut down once its last tab leaves
+        supervisor.assign_tab(3, &url("https://example.com/c")).unwrap();
+        assert_eq!(supervisor.process_state(other), None);
+        supervisor.close_tab(1);
+        supervisor.close_tab(2);
+        supervisor.close_tab(3);
+        assert_eq!(supervisor.process_count(), 0);
+    }
+
+    #[test]
+    fn test_crash_and_recovery() {
+        let mut supervisor = ContentSupervisor::new();
+        let crashing = supervisor.assign_tab(1, &url("https://example.com/")).unwrap();
+        supervisor.assign_tab(2, &url("https://example.com/other")).unwrap();
+        let survivor = supervisor.assign_tab(3, &url("https://other.org/")).unwrap();
+
+        supervisor.crash_tab(1).unwrap();
+        match wait_for_event(&mut supervisor) {
+            Some(ContentEvent::Crashed { process_id, site, tabs, .. }) => {
+                assert_eq!(process_id, crashing);
+                assert_eq!(site, "https://example.com");
+                assert_eq!(tabs, vec![1, 2]);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+        assert!(supervisor.is_crashed(2));
+        assert!(!supervisor.is_crashed(3));
+        assert_eq!(supervisor.process_for_tab(1), None);
+        assert_eq!(supervisor.send_to_tab(1, &BrowserMessage::Ping), Err(MultiprocessError::ProcessNotFound));
+        assert_eq!(supervisor.process_for_tab(3), Some(survivor));
+        assert!(crash_page_html(supervisor.tab_url(1).unwrap()).contains("Aw, Snap!"));
+
+        // Reloading starts a fresh process for the site
+        let recovered = supervisor.assign_tab(1, &url("https://example.com/")).unwrap();
+        assert_ne!(recovered, crashing);
+        assert!(!supervisor.is_crashed(1));
+        assert!(supervisor.is_crashed(2));
+
+        assert_eq!(supervisor.terminate_process(survivor), Ok(vec![3]));
+        assert!(supervisor.is_crashed(3));
+    }
+
+    /// Echoes `postMessage` back to the browser
+    struct MessageEcho;
+
+    impl ContentHandler for MessageEcho {
+        fn handle(&mut self, message: BrowserMessage) -> Option<ContentMessage> {
+            match message {
+                BrowserMessage::PostMessage { target_frame, source_frame, origin, data } => {
+                    Some(ContentMessage::PostMessage { target_frame, source_frame, origin, data })
+                }
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn test_out_of_process_iframes() {
+        let mut supervisor = ContentSupervisor::with_handler(Arc::new(|_: &str, _: BrokerClient| {
+            Box::new(MessageEcho) as Box<dyn ContentHandler>
+        }));
+        let top_process = supervisor.assign_tab(1, &url("https://example.com/")).unwrap();
+        let top = supervisor.main_frame(1).unwrap();
+        let same_site = supervisor.attach_frame(top, &url("https://static.example.com/widget")).unwrap();
+        let ad = supervisor.attach_frame(top, &url("https://ads.example.co.uk/")).unwrap();
+        let nested = supervisor.attach_frame(ad, &url("https://example.com/tracker")).unwrap();
+
+        assert_eq!(supervisor.child_frames(top), vec![same_site, ad]);
+        assert_eq!(supervisor.frame_parent(nested), Some(ad));
+        assert!(!supervisor.is_out_of_process(same_site));
+        assert!(supervisor.is_out_of_process(ad));
+        // A frame nested in a cross-site frame goes back to its own site's process
+        assert_eq!(supervisor.frame_process(nested), Some(top_process));
+        let ad_process = supervisor.frame_process(ad).unwrap();
+        assert_eq!(supervisor.site(ad_process), Some("https://example.co.uk"));
+
+        // Same-process frames see each other directly, others through a proxy
+        assert_eq!(supervisor.proxy(same_site, top), None);
+        let proxy = supervisor.proxy(ad, top).unwrap();
+        assert_eq!(proxy.process_id, ad_process);
+        assert_eq!(proxy.origin, "https://ads.example.co.uk");
+
+        let data = SerializedValue::from_json("{\"hello\":1}".to_string()).unwrap();
+        assert_eq!(supervisor.post_message(top, ad, "https://other.org", &data), Ok(false));
+        assert_eq!(supervisor.post_message(top, ad, "https://ads.example.co.uk/", &data), Ok(true));
+        match wait_for_event(&mut supervisor) {
+            Some(ContentEvent::Message {
+                process_id,
+                message: ContentMessage::PostMessage { target_frame, source_frame, origin, data },
+            }) => {
+                assert_eq!(process_id, ad_process);
+                assert_eq!((target_frame, source_frame), (ad, top));
+                assert_eq!(origin, "https://example.com");
+                assert_eq!(data, "{\"hello\":1}");
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        // Navigating the iframe away drops its subtree and its process
+        supervisor.navigate_frame(ad, &url("https://example.com/ok")).unwrap();
+        assert_eq!(supervisor.frame_url(nested), None);
+        assert!(!supervisor.is_out_of_process(ad));
+        assert_eq!(supervisor.process_state(ad_process), None);
+
+        supervisor.detach_frame(ad);
+        assert_eq!(supervisor.child_frames(top), vec![same_site]);
+        assert_eq!(supervisor.attach_frame(ad, &url("https://example.com/")), Err(MultiprocessError::FrameNotFound));
+        supervisor.close_tab(1);
+        assert_eq!(supervisor.process_count(), 0);
+    }
+
+    /// Paints a frame for each navigation and click
+    struct Painter;
+
+    impl ContentHandler for Painter {
+        fn handle(&mut self, message: BrowserMessage) -> Option<ContentMessage> {
+            let (tab_id, frame_id, x, y) = match message {
+                BrowserMessage::Navigate { tab_id, frame_id, .. } => (tab_id, frame_id, 0.0, 0.0),
+                BrowserMessage::Input { tab_id, event: InputEvent::MouseDown { x, y, .. } } => (tab_id, 0, x, y),
+                _ => return None,
+            };
+            let rect = Rect { x, y, width: 10.0, height: 10.0 };
+            Some(ContentMessage::Frame {
+                tab_id,
+                frame_id,
+                display_list: vec![DisplayCommand::SolidRect { color: Color::new(0, 0, 255, 255), rect }],
+                damage: vec![rect],
+            })
+        }
+    }
+
+    #[test]
+    fn test_navigation_and_input_over_the_channel() {
+        let mut supervisor = ContentSupervisor::with_handler(Arc::new(|_: &str, _: BrokerClient| {
+            Box::new(Painter) as Box<dyn ContentHandler>
+        }));
+        supervisor.assign_tab(1, &url("https://example.com/")).unwrap();
+        let main_frame = supervisor.main_frame(1).unwrap();
+        match wait_for_event(&mut supervisor) {
+            Some(ContentEvent::Message { message: ContentMessage::Frame { tab_id, frame_id, damage, .. }, .. }) => {
+                assert_eq!((tab_id, frame_id), (1, main_frame));
+                assert_eq!(damage[0].x, 0.0);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        let click = InputEvent::MouseDown { x: 20.0, y: 30.0, button: MouseButton::Left };
+        supervisor.send_to_tab(1, &BrowserMessage::Input { tab_id: 1, event: click }).unwrap();
+        match wait_for_event(&mut supervisor) {
+            Some(ContentEvent::Message { message: ContentMessage::Frame { display_list, .. }, .. }) => {
+                assert_eq!(display_list[0].rect(), Rect { x: 20.0, y: 30.0, width: 10.0, height: 10.0 });
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_handshake_refuses_other_versions() {
+        let (mut browser, mut content) = channel_pair().unwrap();
+        let old = std::thread::spawn(move || {
+            content.recv().unwrap();
+            content.send(&ContentMessage::Hello { version: PROTOCOL_VERSION - 1 }).unwrap();
+        });
+        assert_eq!(
+            handshake(&mut browser),
+            Err(MultiprocessError::VersionMismatch { expected: PROTOCOL_VERSION, found: PROTOCOL_VERSION - 1 })
+        );
+        old.join().unwrap();
+
+        // A process that exits without answering fails to start
+        let (mut browser, content) = channel_pair().unwrap();
+        drop(content);
+        assert_eq!(handshake(&mut browser), Err(MultiprocessError::SpawnFailed));
+    }
+}
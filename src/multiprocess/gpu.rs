@@ -0,0 +1,609 @@
+// GPU process
+//
+// The GPU device is owned by a process of its own, which receives frames,
+// display lists with the decoded images and video pictures they show, and
+// paints them. A driver crash or a lost device only takes that
+// process down: the browser notices, starts a new one with a fresh device
+// and repaints the last frame. A GPU that keeps failing is given up on in
+// favour of the software rasterizer, whose frames come back through shared
+// memory. Like content processes this is a thread for now; it is not
+// sandboxed since the driver needs access to the device.
+
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use winit::window::Window;
+
+use super::shm::{ShmHandle, ShmWriter};
+use super::MultiprocessError;
+use crate::display::DisplayList;
+use crate::layout::Rect;
+use crate::media::VideoFrame;
+use crate::renderer::image_cache::{DecodedImage, ImageCache};
+use crate::renderer::software::SoftwareRasterizer;
+use crate::renderer::{GpuCanvas, Renderer};
+
+/// Consecutive GPU failures after which the software rasterizer takes over
+const MAX_CONTEXT_RESTARTS: u32 = 3;
+
+/// Paints frames; lives inside the GPU process
+pub trait PaintBackend {
+    /// Paint a frame. Returns the pixels if they are not presented
+    /// directly; an error means the device is gone.
+    fn paint(&mut self, frame: &GpuFrame) -> Result<Option<ShmHandle>, MultiprocessError>;
+
+    /// Resize the output, in device pixels
+    fn resize(&mut self, width: u32, height: u32, scale_factor: f32);
+
+    /// Bytes held by the glyph atlas
+    fn glyph_atlas_bytes(&self) -> usize {
+        0
+    }
+
+    /// Drop caches that refill on demand, under memory pressure
+    fn trim(&mut self) {}
+}
+
+/// Creates a backend inside a newly started GPU process
+pub type BackendFactory = Arc<dyn Fn() -> Result<Box<dyn PaintBackend>, MultiprocessError> + Send + Sync>;
+
+/// Which backend the GPU process paints with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpuBackendKind {
+    Hardware,
+    Software,
+}
+
+/// Something that happened in the GPU process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GpuEvent {
+    /// A frame was painted; software frames come with their RGBA pixels
+    Presented { frame: u64, pixels: Option<ShmHandle> },
+    /// The process died and was restarted with `backend`
+    ContextLost { backend: GpuBackendKind },
+}
+
+/// Everything one frame of a window shows
+#[derive(Debug, Clone, Default)]
+pub struct GpuFrame {
+    /// Page content and browser chrome
+    pub list: DisplayList,
+    /// Videos composited over the list
+    pub videos: Vec<VideoLayer>,
+    /// Painted over the videos
+    pub overlay: DisplayList,
+    /// Decoded images the lists draw
+    pub images: Vec<Arc<DecodedImage>>,
+}
+
+impl From<DisplayList> for GpuFrame {
+    fn from(list: DisplayList) -> Self {
+        Self { list, ..Self::default() }
+    }
+}
+
+impl GpuFrame {
+    /// Keep the video pictures of an older frame that never got painted,
+    /// for the videos that have no newer picture
+    fn inherit_pictures(&mut self, older: GpuFrame) {
+        for layer in older.videos {
+            let Some(picture) = layer.picture else {
+                continue;
+            };
+            if let Some(newer) = self.videos.iter_mut().find(|newer| newer.id == layer.id && newer.picture.is_none()) {
+                newer.picture = Some(picture);
+            }
+        }
+    }
+}
+
+/// A video in a frame
+#[derive(Debug, Clone)]
+pub struct VideoLayer {
+    /// Names the video's backing store from frame to frame
+    pub id: u64,
+    /// Where it is shown, in CSS pixels from the window's top left
+    pub rect: Rect,
+    /// A picture to show from now on; without one the last is shown again
+    pub picture: Option<VideoFrame>,
+}
+
+/// Browser to GPU process
+enum GpuCommand {
+    Paint { frame: u64, contents: GpuFrame },
+    Resize { width: u32, height: u32, scale_factor: f32 },
+    TrimCaches,
+    Shutdown,
+    Crash,
+}
+
+/// A running GPU process, seen from the browser
+struct GpuThread {
+    to_gpu: Sender<GpuCommand>,
+    from_gpu: Receiver<GpuEvent>,
+    thread: JoinHandle<()>,
+}
+
+/// Browser-side owner of the GPU process
+pub struct GpuProcess {
+    hardware: BackendFactory,
+    software: BackendFactory,
+    backend: GpuBackendKind,
+    /// Failures since the last presented frame
+    failures: u32,
+    size: (u32, u32, f32),
+    next_frame: u64,
+    /// Repainted after a restart, with the latest picture of every video
+    last_frame: Option<GpuFrame>,
+    /// Glyph atlas size, as the GPU process last reported it
+    glyph_atlas_bytes: Arc<AtomicUsize>,
+    process: Option<GpuThread>,
+}
+
+impl GpuProcess {
+    /// Start a GPU process painting with `hardware`, falling back to the
+    /// software rasterizer
+    pub fn new(hardware: BackendFactory, width: u32, height: u32) -> Self {
+        Self::with_backends(hardware, Arc::new(software_backend), width, height)
+    }
+
+    /// Start a GPU process painting into a window with wgpu, or with the
+    /// software rasterizer once the GPU has failed
+    pub fn for_window(window: Arc<Window>) -> Self {
+        let size = window.inner_size();
+        let scale_factor = window.scale_factor() as f32;
+        let mut gpu = Self::with_backends(
+            wgpu_backend(Arc::clone(&window)),
+            software_window_backend(window),
+            size.width,
+            size.height,
+        );
+        gpu.resize(size.width, size.height, scale_factor);
+        gpu
+    }
+
+    /// Start a GPU process with both backends given
+    pub fn with_backends(hardware: BackendFactory, software: BackendFactory, width: u32, height: u32) -> Self {
+        let mut gpu = Self {
+            hardware,
+            software,
+            backend: GpuBackendKind::Hardware,
+            failures: 0,
+            size: (width, height, 1.0),
+            next_frame: 0,
+            last_frame: None,
+            glyph_atlas_bytes: Arc::new(AtomicUsize::new(0)),
+            process: None,
+        };
+        gpu.spawn();
+        gpu
+    }
+
+    /// Backend frames are currently painted with
+    pub fn backend(&self) -> GpuBackendKind {
+        self.backend
+    }
+
+    /// Output size in device pixels
+    pub fn size(&self) -> (u32, u32) {
+        (self.size.0, self.size.1)
+    }
+
+    /// Bytes of the GPU process's glyph atlas
+    pub fn glyph_atlas_bytes(&self) -> usize {
+        self.glyph_atlas_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Send a frame to be painted; returns its frame number
+    ///
+    /// If the process is down the frame is painted once it is restarted.
+    pub fn paint(&mut self, contents: impl Into<GpuFrame>) -> u64 {
+        let contents = contents.into();
+        self.next_frame += 1;
+        let mut last = contents.clone();
+        if let Some(previous) = self.last_frame.take() {
+            last.inherit_pictures(previous);
+        }
+        self.last_frame = Some(last);
+        self.send(GpuCommand::Paint { frame: self.next_frame, contents });
+        self.next_frame
+    }
+
+    /// Resize the output, in device pixels
+    pub fn resize(&mut self, width: u32, height: u32, scale_factor: f32) {
+        self.size = (width, height, scale_factor);
+        self.send(GpuCommand::Resize { width, height, scale_factor });
+    }
+
+    /// Drop the GPU process's caches that refill on demand
+    pub fn trim_caches(&self) {
+        self.send(GpuCommand::TrimCaches);
+    }
+
+    /// Make the GPU process crash, to exercise recovery
+    pub fn crash(&self) {
+        self.send(GpuCommand::Crash);
+    }
+
+    /// Collect painted frames and restart the process if it died
+    pub fn poll(&mut self) -> Vec<GpuEvent> {
+        let Some(process) = &self.process else {
+            return Vec::new();
+        };
+        let mut events: Vec<GpuEvent> = process.from_gpu.try_iter().collect();
+        if events.iter().any(|event| matches!(event, GpuEvent::Presented { .. })) {
+            self.failures = 0;
+        }
+        if process.thread.is_finished() {
+            if let Some(process) = self.process.take() {
+                let _ = process.thread.join();
+            }
+            self.failures += 1;
+            if self.backend == GpuBackendKind::Hardware && self.failures >= MAX_CONTEXT_RESTARTS {
+                self.backend = GpuBackendKind::Software;
+                self.failures = 0;
+            }
+            self.spawn();
+            if let Some(contents) = self.last_frame.clone() {
+                self.send(GpuCommand::Paint { frame: self.next_frame, contents });
+            }
+            events.push(GpuEvent::ContextLost { backend: self.backend });
+        }
+        events
+    }
+
+    /// Stop the GPU process
+    pub fn shutdown(&mut self) {
+        if let Some(process) = self.process.take() {
+            let _ = process.to_gpu.send(GpuCommand::Shutdown);
+            let _ = process.thread.join();
+        }
+    }
+
+    fn spawn(&mut self) {
+        let factory = match self.backend {
+            GpuBackendKind::Hardware => Arc::clone(&self.hardware),
+            GpuBackendKind::Software => Arc::clone(&self.software),
+        };
+        let (to_gpu, inbox) = mpsc::channel();
+        let (outbox, from_gpu) = mpsc::channel();
+        let size = self.size;
+        let atlas = Arc::clone(&self.glyph_atlas_bytes);
+        let thread = std::thread::Builder::new()
+            .name("gpu".to_string())
+            .spawn(move || run_gpu_process(factory, size, atlas, inbox, outbox));
+        // A process that could not start is restarted by the next poll
+        self.process = match thread {
+            Ok(thread) => Some(GpuThread { to_gpu, from_gpu, thread }),
+            Err(_) => None,
+        };
+    }
+
+    fn send(&self, command: GpuCommand) {
+        if let Some(process) = &self.process {
+            let _ = process.to_gpu.send(command);
+        }
+    }
+}
+
+impl Drop for GpuProcess {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Message loop of the GPU process
+///
+/// Returning without a `Shutdown` tells the browser the context was lost.
+fn run_gpu_process(
+    factory: BackendFactory,
+    (width, height, scale_factor): (u32, u32, f32),
+    atlas: Arc<AtomicUsize>,
+    inbox: Receiver<GpuCommand>,
+    outbox: Sender<GpuEvent>,
+) {
+    let Ok(mut backend) = factory() else {
+        return;
+    };
+    backend.resize(width, height, scale_factor);
+    // A command read while looking for newer frames, handled next
+    let mut held = None;
+    while let Some(command) = held.take().or_else(|| inbox.recv().ok()) {
+        match command {
+            GpuCommand::Paint { mut frame, mut contents } => {
+                // Only the newest of the frames waiting is worth painting
+                loop {
+                    match inbox.try_recv() {
+                        Ok(GpuCommand::Paint { frame: newer, contents: mut newer_contents }) => {
+                            newer_contents.inherit_pictures(contents);
+                            (frame, contents) = (newer, newer_contents);
+                        }
+                        Ok(other) => {
+                            held = Some(other);
+                            break;
+                        }
+                        Err(_) => break,
+                    }
+                }
+                let Ok(pixels) = backend.paint(&contents) else {
+                    return;
+                };
+                atlas.store(backend.glyph_atlas_bytes(), Ordering::Relaxed);
+                if outbox.send(GpuEvent::Presented { frame, pixels }).is_err() {
+                    break;
+                }
+            }
+            GpuCommand::Resize { width, height, scale_factor } => backend.resize(width, height, scale_factor),
+            GpuCommand::TrimCaches => {
+                backend.trim();
+                atlas.store(backend.glyph_atlas_bytes(), Ordering::Relaxed);
+            }
+            GpuCommand::Shutdown => break,
+            GpuCommand::Crash => panic!("GPU process crashed on request"),
+        }
+    }
+}
+
+/// wgpu renderer, with the images and video backing stores frames use
+struct HardwareBackend {
+    renderer: Renderer<'static>,
+    images: ImageCache,
+    videos: HashMap<u64, GpuCanvas>,
+}
+
+impl PaintBackend for HardwareBackend {
+    fn paint(&mut self, frame: &GpuFrame) -> Result<Option<ShmHandle>, MultiprocessError> {
+        for image in &frame.images {
+            if !self.images.shared(&image.url).is_some_and(|known| Arc::ptr_eq(&known, image)) {
+                self.images.insert_shared(Arc::clone(image));
+            }
+        }
+        // Backing stores of videos no longer shown are released
+        self.videos.retain(|id, _| frame.videos.iter().any(|layer| layer.id == *id));
+        for layer in &frame.videos {
+            let Some(picture) = &layer.picture else {
+                continue;
+            };
+            let size = self.videos.get(&layer.id).map(|canvas| (canvas.width(), canvas.height()));
+            if size != Some((picture.width, picture.height)) {
+                self.videos.insert(layer.id, self.renderer.create_canvas(picture.width, picture.height));
+            }
+            if let Some(canvas) = self.videos.get_mut(&layer.id) {
+                self.renderer.paint_video_frame(canvas, picture);
+            }
+        }
+        let canvases: Vec<(&GpuCanvas, Rect)> = frame
+            .videos
+            .iter()
+            .filter_map(|layer| Some((self.videos.get(&layer.id)?, layer.rect)))
+            .collect();
+        self.renderer
+            .render_display_list_with_canvases(&frame.list, &canvases, &frame.overlay, &self.images)
+            .map_err(|_| MultiprocessError::ContextLost)?;
+        Ok(None)
+    }
+
+    fn resize(&mut self, width: u32, height: u32, scale_factor: f32) {
+        self.renderer.set_scale_factor(scale_factor as f64);
+        self.renderer.resize(width, height);
+    }
+
+    fn glyph_atlas_bytes(&self) -> usize {
+        self.renderer.glyph_atlas_bytes()
+    }
+
+    fn trim(&mut self) {
+        self.renderer.trim_glyph_atlas();
+        self.images.clear();
+    }
+}
+
+/// Software rasterizer publishing its frames through shared memory
+struct SoftwareBackend {
+    raster: SoftwareRasterizer,
+    shm: ShmWriter,
+    /// Window the frames are also shown in
+    surface: Option<softbuffer::Surface<Arc<Window>, Arc<Window>>>,
+}
+
+impl PaintBackend for SoftwareBackend {
+    fn paint(&mut self, frame: &GpuFrame) -> Result<Option<ShmHandle>, MultiprocessError> {
+        // Text, images and videos are left out, as the rasterizer has no painters for them
+        let mut list = frame.list.clone();
+        list.extend(frame.overlay.iter().cloned());
+        self.raster.paint(&list);
+        if let Some(surface) = &mut self.surface {
+            present(surface, &self.raster)?;
+        }
+        self.shm.write(self.raster.pixels()).map(Some)
+    }
+
+    fn resize(&mut self, width: u32, height: u32, scale_factor: f32) {
+        self.raster.resize(width, height);
+        self.raster.set_scale_factor(scale_factor);
+    }
+}
+
+/// Copy the rasterizer's frame into a window
+fn present(
+    surface: &mut softbuffer::Surface<Arc<Window>, Arc<Window>>,
+    raster: &SoftwareRasterizer,
+) -> Result<(), MultiprocessError> {
+    let (width, height) = raster.size();
+    let (Some(nonzero_width), Some(nonzero_height)) = (NonZeroU32::new(width), NonZeroU32::new(height)) else {
+        return Ok(());
+    };
+    surface.resize(nonzero_width, nonzero_height).map_err(|_| MultiprocessError::ContextLost)?;
+    let mut buffer = surface.buffer_mut().map_err(|_| MultiprocessError::ContextLost)?;
+    for (out, pixel) in buffer.iter_mut().zip(raster.pixels().chunks_exact(4)) {
+        *out = (pixel[0] as u32) << 16 | (pixel[1] as u32) << 8 | pixel[2] as u32;
+    }
+    buffer.present().map_err(|_| MultiprocessError::ContextLost)
+}
+
+/// Backend factory for the software rasterizer
+pub fn software_backend() -> Result<Box<dyn PaintBackend>, MultiprocessError> {
+    Ok(Box::new(SoftwareBackend { raster: SoftwareRasterizer::new(1, 1), shm: ShmWriter::new(), surface: None }))
+}
+
+/// Backend factory for the software rasterizer showing its frames in `window`
+pub fn software_window_backend(window: Arc<Window>) -> BackendFactory {
+    Arc::new(move || {
+        let context = softbuffer::Context::new(Arc::clone(&window)).map_err(|_| MultiprocessError::ContextLost)?;
+        let surface =
+            softbuffer::Surface::new(&context, Arc::clone(&window)).map_err(|_| MultiprocessError::ContextLost)?;
+        Ok(Box::new(SoftwareBackend {
+            raster: SoftwareRasterizer::new(1, 1),
+            shm: ShmWriter::new(),
+            surface: Some(surface),
+        }) as Box<dyn PaintBackend>)
+    })
+}
+
+/// Backend factory creating a wgpu device and surface for `window`
+pub fn wgpu_backend(window: Arc<Window>) -> BackendFactory {
+    Arc::new(move || {
+        let renderer = pollster::block_on(Renderer::for_window(Arc::clone(&window)))
+            .map_err(|_| MultiprocessError::ContextLost)?;
+        Ok(Box::new(HardwareBackend { renderer, images: ImageCache::default(), videos: HashMap::new() })
+            as Box<dyn PaintBackend>)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::Color;
+    use crate::display::DisplayCommand;
+    use crate::multiprocess::ShmReader;
+    use std::time::{Duration, Instant};
+
+    /// Poll until an event arrives or a second passes
+    fn wait_for_event(gpu: &mut GpuProcess) -> Option<GpuEvent> {
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while Instant::now() < deadline {
+            if let Some(event) = gpu.poll().into_iter().next() {
+                return Some(event);
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        None
+    }
+
+    /// A GPU whose device is lost on every paint
+    struct LostDevice;
+
+    impl PaintBackend for LostDevice {
+        fn paint(&mut self, _: &GpuFrame) -> Result<Option<ShmHandle>, MultiprocessError> {
+            Err(MultiprocessError::ContextLost)
+        }
+
+        fn resize(&mut self, _: u32, _: u32, _: f32) {}
+    }
+
+    /// A GPU whose driver panics while painting
+    struct PanickingDriver;
+
+    impl PaintBackend for PanickingDriver {
+        fn paint(&mut self, _: &GpuFrame) -> Result<Option<ShmHandle>, MultiprocessError> {
+            panic!("driver bug");
+        }
+
+        fn resize(&mut self, _: u32, _: u32, _: f32) {}
+    }
+
+    #[test]
+    fn test_software_frames_survive_crash() {
+        let mut gpu = GpuProcess::with_backends(Arc::new(software_backend), Arc::new(software_backend), 4, 4);
+        let blue = Color::new(0, 0, 255, 255);
+        let frame = gpu.paint(vec![DisplayCommand::SolidRect {
+            color: blue,
+            rect: Rect { x: 0.0, y: 0.0, width: 2.0, height: 4.0 },
+        }]);
+        let mut reader = ShmReader::new();
+        match wait_for_event(&mut gpu) {
+            Some(GpuEvent::Presented { frame: presented, pixels: Some(handle) }) => {
+                assert_eq!(presented, frame);
+                let pixels = reader.read(&handle).unwrap();
+                assert_eq!(pixels.len(), 4 * 4 * 4);
+                assert_eq!(&pixels[..4], &[0, 0, 255, 255]);
+                assert_eq!(&pixels[8..12], &[255, 255, 255, 255]);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        // A crash restarts the process, which repaints the last frame
+        gpu.crash();
+        assert_eq!(wait_for_event(&mut gpu), Some(GpuEvent::ContextLost { backend: GpuBackendKind::Hardware }));
+        assert!(matches!(wait_for_event(&mut gpu), Some(GpuEvent::Presented { frame: f, .. }) if f == frame));
+    }
+
+    #[test]
+    fn test_falls_back_to_software() {
+        let lost: BackendFactory = Arc::new(|| Ok(Box::new(LostDevice) as Box<dyn PaintBackend>));
+        let mut gpu = GpuProcess::new(lost, 8, 8);
+        gpu.paint(Vec::new());
+        let mut lost_contexts = 0;
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while gpu.backend() == GpuBackendKind::Hardware && Instant::now() < deadline {
+            lost_contexts += gpu.poll().len();
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(lost_contexts as u32, MAX_CONTEXT_RESTARTS);
+        assert!(matches!(wait_for_event(&mut gpu), Some(GpuEvent::Presented { pixels: Some(_), .. })));
+
+        // A backend that cannot even start counts as lost too
+        let failing: BackendFactory = Arc::new(|| Err(MultiprocessError::ContextLost));
+        let mut gpu = GpuProcess::new(failing, 8, 8);
+        gpu.paint(Vec::new());
+        assert_eq!(wait_for_event(&mut gpu), Some(GpuEvent::ContextLost { backend: GpuBackendKind::Hardware }));
+    }
+
+    #[test]
+    fn test_gpu_panic_leaves_browser_running() {
+        let panicking: BackendFactory = Arc::new(|| Ok(Box::new(PanickingDriver) as Box<dyn PaintBackend>));
+        let mut gpu = GpuProcess::new(panicking, 4, 4);
+        let red = Color::new(255, 0, 0, 255);
+        gpu.paint(vec![DisplayCommand::SolidRect { color: red, rect: Rect { x: 0.0, y: 0.0, width: 4.0, height: 4.0 } }]);
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let mut lost_contexts = 0;
+        while gpu.backend() == GpuBackendKind::Hardware && Instant::now() < deadline {
+            lost_contexts += gpu.poll().len();
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(lost_contexts as u32, MAX_CONTEXT_RESTARTS);
+
+        // The panics stayed in the GPU process: this thread goes on painting,
+        // and the frame sent before them is shown by the software rasterizer
+        let mut reader = ShmReader::new();
+        match wait_for_event(&mut gpu) {
+            Some(GpuEvent::Presented { frame: 1, pixels: Some(handle) }) => {
+                assert_eq!(&reader.read(&handle).unwrap()[..4], &[255, 0, 0, 255]);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+        let frame = gpu.paint(Vec::new());
+        assert!(matches!(wait_for_event(&mut gpu), Some(GpuEvent::Presented { frame: f, .. }) if f == frame));
+    }
+
+    #[test]
+    fn test_coalesced_frames_keep_video_pictures() {
+        let picture = VideoFrame {
+            timestamp: 0.0,
+            duration: 0.04,
+            width: 2,
+            height: 2,
+            pixels: Arc::from(vec![0; 16]),
+        };
+        let rect = Rect { x: 0.0, y: 0.0, width: 2.0, height: 2.0 };
+        let layer = |picture| VideoLayer { id: 7, rect, picture };
+        let older = GpuFrame { videos: vec![layer(Some(picture.clone()))], ..GpuFrame::default() };
+        let mut newer = GpuFrame { videos: vec![layer(None)], ..GpuFrame::default() };
+        newer.inherit_pictures(older);
+        assert_eq!(newer.videos[0].picture, Some(picture));
+    }
+}
@@ -0,0 +1,447 @@
+// Typed IPC protocol between the browser and content processes
+//
+// The browser sends navigations and input events; content processes send
+// back display lists with the damage since their previous frame. Messages
+// travel as length-prefixed JSON envelopes over any byte stream, usually a
+// Unix socket pair. A connection opens with both ends saying `Hello` with
+// their protocol version. Every envelope carries the protocol version, its
+// sequence number and the highest sequence number received from the peer,
+// so a sender can tell how far behind the peer is and back off.
+
+use std::io::{ErrorKind, Read, Write};
+use std::marker::PhantomData;
+use std::time::SystemTime;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::{MultiprocessError, ShmHandle};
+use crate::display::DisplayList;
+use crate::layout::Rect;
+
+/// Version of the protocol; both ends must speak the same one
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Largest envelope either end accepts
+pub const MAX_ENVELOPE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Unacknowledged messages a sender may have outstanding by default
+pub const DEFAULT_SEND_WINDOW: u64 = 64;
+
+/// Mouse button in an input event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+}
+
+/// Modifier keys held during an input event
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub meta: bool,
+}
+
+/// User input routed to a page; coordinates are in CSS pixels
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum InputEvent {
+    MouseMove { x: f32, y: f32 },
+    MouseDown { x: f32, y: f32, button: MouseButton },
+    MouseUp { x: f32, y: f32, button: MouseButton },
+    Wheel { x: f32, y: f32, delta_x: f32, delta_y: f32 },
+    KeyDown { key: String, modifiers: Modifiers },
+    KeyUp { key: String, modifiers: Modifiers },
+    /// Text typed or committed by an input method
+    Text { text: String },
+}
+
+/// Browser process to content process
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BrowserMessage {
+    /// First message on a connection
+    Hello { version: u32 },
+    /// Load a URL in one of a tab's frames
+    Navigate { tab_id: u64, frame_id: u64, url: String },
+    /// Deliver input to a tab's page
+    Input { tab_id: u64, event: InputEvent },
+    /// The tab's viewport changed size
+    Resize { tab_id: u64, width: f32, height: f32 },
+    /// Run a script, answered with `ScriptResult`
+    EvalScript { script: String },
+    /// `postMessage` to a frame the process hosts, from a frame that may
+    /// live in another one
+    PostMessage { target_frame: u64, source_frame: u64, origin: String, data: String },
+    /// Answered with `Pong`
+    Ping,
+    /// Exit cleanly
+    Shutdown,
+    /// Crash on purpose, to test recovery
+    Crash,
+}
+
+/// Content process to browser process
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ContentMessage {
+    /// Answer to the browser's `Hello`
+    Hello { version: u32 },
+    /// A navigation committed; the tab now shows `url`
+    NavigationCommitted { tab_id: u64, url: String, title: String },
+    /// A new frame for a tab
+    Frame {
+        tab_id: u64,
+        frame_id: u64,
+        display_list: DisplayList,
+        /// Areas that changed since the previous frame
+        damage: Vec<Rect>,
+    },
+    /// A frame whose display list is in shared memory
+    SharedFrame { tab_id: u64, frame_id: u64, display_list: ShmHandle, damage: Vec<Rect> },
+    /// An image was decoded into shared memory as RGBA pixels
+    ImageDecoded { url: String, width: u32, height: u32, pixels: ShmHandle },
+    /// What a script evaluated to, or the error it threw
+    ScriptResult { result: String },
+    /// `postMessage` from a frame the process hosts
+    PostMessage { target_frame: u64, source_frame: u64, origin: String, data: String },
+    Pong,
+}
+
+/// A message type carried by an `IpcChannel`
+pub trait IpcPayload: Serialize + DeserializeOwned {
+    /// Short name for traces
+    fn kind(&self) -> &'static str;
+}
+
+impl IpcPayload for BrowserMessage {
+    fn kind(&self) -> &'static str {
+        match self {
+            BrowserMessage::Hello { .. } => "hello",
+            BrowserMessage::Navigate { .. } => "navigate",
+            BrowserMessage::Input { .. } => "input",
+            BrowserMessage::Resize { .. } => "resize",
+            BrowserMessage::EvalScript { .. } => "eval-script",
+            BrowserMessage::PostMessage { .. } => "post-message",
+            BrowserMessage::Ping => "ping",
+            BrowserMessage::Shutdown => "shutdown",
+            BrowserMessage::Crash => "crash",
+        }
+    }
+}
+
+impl IpcPayload for ContentMessage {
+    fn kind(&self) -> &'static str {
+        match self {
+            ContentMessage::Hello { .. } => "hello",
+            ContentMessage::NavigationCommitted { .. } => "navigation-committed",
+            ContentMessage::Frame { .. } => "frame",
+            ContentMessage::SharedFrame { .. } => "shared-frame",
+            ContentMessage::ImageDecoded { .. } => "image-decoded",
+            ContentMessage::ScriptResult { .. } => "script-result",
+            ContentMessage::PostMessage { .. } => "post-message",
+            ContentMessage::Pong => "pong",
+        }
+    }
+}
+
+/// Which way a traced message went
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpcDirection {
+    Sent,
+    Received,
+}
+
+/// One message in a channel's trace
+#[derive(Debug, Clone)]
+pub struct IpcTraceEntry {
+    pub direction: IpcDirection,
+    /// Sequence number; 0 for acknowledgements
+    pub seq: u64,
+    pub kind: &'static str,
+    /// Encoded size in bytes
+    pub size: usize,
+    pub timestamp: SystemTime,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Envelope<T> {
+    version: u32,
+    /// 0 for a bare acknowledgement without payload
+    seq: u64,
+    /// Highest sequence number received from the peer
+    ack: u64,
+    payload: Option<T>,
+}
+
+/// Just the version, checked before the rest of an envelope is decoded
+#[derive(Deserialize)]
+struct EnvelopeHeader {
+    version: u32,
+}
+
+/// One end of an IPC connection, sending `Out` and receiving `In`
+pub struct IpcChannel<S, Out, In> {
+    stream: S,
+    /// Bytes read but not yet decoded
+    buffer: Vec<u8>,
+    /// Sequence number of the last message sent
+    sent: u64,
+    /// Highest of our sequence numbers the peer has received
+    peer_acked: u64,
+    /// Sequence number of the last message received
+    received: u64,
+    /// Value of `received` last reported to the peer
+    ack_sent: u64,
+    window: u64,
+    trace: Option<Vec<IpcTraceEntry>>,
+    _messages: PhantomData<fn(Out) -> In>,
+}
+
+/// Browser end of a connection to a content process
+pub type BrowserChannel<S> = IpcChannel<S, BrowserMessage, ContentMessage>;
+
+/// Content process end of a connection to the browser
+pub type ContentChannel<S> = IpcChannel<S, ContentMessage, BrowserMessage>;
+
+/// Stream connecting the browser to a content process
+#[cfg(unix)]
+pub type IpcStream = std::os::unix::net::UnixStream;
+#[cfg(not(unix))]
+pub type IpcStream = std::net::TcpStream;
+
+/// Connected browser and content ends: a Unix socket pair, or a loopback
+/// TCP connection where there are no Unix sockets
+pub fn channel_pair() -> std::io::Result<(BrowserChannel<IpcStream>, ContentChannel<IpcStream>)> {
+    #[cfg(unix)]
+    let (browser, content) = IpcStream::pair()?;
+    #[cfg(not(unix))]
+    let (browser, content) = {
+        let listener = std::net::TcpListener::bind(("127.0.0.1", 0))?;
+        let browser = IpcStream::connect(listener.local_addr()?)?;
+        (browser, listener.accept()?.0)
+    };
+    Ok((IpcChannel::new(browser), IpcChannel::new(content)))
+}
+
+impl<S: Read + Write, Out: IpcPayload, In: IpcPayload> IpcChannel<S, Out, In> {
+    /// Wrap a connected stream
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            buffer: Vec::new(),
+            sent: 0,
+            peer_acked: 0,
+            received: 0,
+            ack_sent: 0,
+            window: DEFAULT_SEND_WINDOW,
+            trace: None,
+            _messages: PhantomData,
+        }
+    }
+
+    /// Set how many unacknowledged messages may be outstanding
+    pub fn set_send_window(&mut self, window: u64) {
+        self.window = window.max(1);
+    }
+
+    /// Messages sent that the peer has not acknowledged yet
+    pub fn in_flight(&self) -> u64 {
+        self.sent - self.peer_acked
+    }
+
+    /// Would `send` refuse a message right now
+    pub fn is_backpressured(&self) -> bool {
+        self.in_flight() >= self.window
+    }
+
+    /// Start or stop recording the messages that pass through
+    pub fn set_tracing(&mut self, enabled: bool) {
+        self.trace = if enabled { Some(self.trace.take().unwrap_or_default()) } else { None };
+    }
+
+    /// Take the messages recorded since the last call
+    pub fn take_trace(&mut self) -> Vec<IpcTraceEntry> {
+        self.trace.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// The underlying stream, e.g. to set a read timeout
+    pub fn stream(&self) -> &S {
+        &self.stream
+    }
+
+    /// Send a message
+    ///
+    /// Fails with `Backpressure` while the send window is full; receiving
+    /// from the peer picks up its acknowledgements.
+    pub fn send(&mut self, message: &Out) -> Result<(), MultiprocessError> {
+        if self.is_backpressured() {
+            return Err(MultiprocessError::Backpressure);
+        }
+        self.write_envelope(Some(message))?;
+        self.sent += 1;
+        Ok(())
+    }
+
+    /// Tell the peer about every message received so far
+    pub fn acknowledge(&mut self) -> Result<(), MultiprocessError> {
+        if self.ack_sent == self.received {
+            return Ok(());
+        }
+        self.write_envelope(None)
+    }
+
+    /// Receive the next message
+    ///
+    /// Blocks until one arrives. Returns `None` if the stream's read
+    /// timed out first; a partly read message is kept for the next call.
+    pub fn recv(&mut self) -> Result<Option<In>, MultiprocessError> {
+        loop {
+            if let Some(message) = self.decode_buffered()? {
+                return Ok(Some(message));
+            }
+            let mut chunk = [0u8; 16 * 1024];
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err(MultiprocessError::Disconnected),
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => return Ok(None),
+                Err(_) => return Err(MultiprocessError::IpcError),
+            }
+        }
+    }
+
+    fn write_envelope(&mut self, payload: Option<&Out>) -> Result<(), MultiprocessError> {
+        let seq = if payload.is_some() { self.sent + 1 } else { 0 };
+        let envelope = Envelope { version: PROTOCOL_VERSION, seq, ack: self.received, payload };
+        let body = serde_json::to_vec(&envelope).map_err(|_| MultiprocessError::IpcError)?;
+        if body.len() > MAX_ENVELOPE_SIZE {
+            return Err(MultiprocessError::MessageTooLarge);
+        }
+
+        let mut frame = Vec::with_capacity(4 + body.len());
+        frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&body);
+        self.stream.write_all(&frame).map_err(|_| MultiprocessError::IpcError)?;
+        self.stream.flush().map_err(|_| MultiprocessError::IpcError)?;
+        self.ack_sent = self.received;
+
+        let kind = payload.map_or("ack", |message| message.kind());
+        self.record(IpcDirection::Sent, seq, kind, frame.len());
+        Ok(())
+    }
+
+    /// Decode envelopes already read, up to the first one with a payload
+    fn decode_buffered(&mut self) -> Result<Option<In>, MultiprocessError> {
+        while self.buffer.len() >= 4 {
+            let len = u32::from_be_bytes([self.buffer[0], self.buffer[1], self.buffer[2], self.buffer[3]]) as usize;
+            if len > MAX_ENVELOPE_SIZE {
+                return Err(MultiprocessError::MessageTooLarge);
+            }
+            if self.buffer.len() < 4 + len {
+                return Ok(None);
+            }
+            let body: Vec<u8> = self.buffer.drain(..4 + len).skip(4).collect();
+
+            let header: EnvelopeHeader = serde_json::from_slice(&body).map_err(|_| MultiprocessError::IpcError)?;
+            if header.version != PROTOCOL_VERSION {
+                return Err(MultiprocessError::VersionMismatch { expected: PROTOCOL_VERSION, found: header.version });
+            }
+            let envelope: Envelope<In> = serde_json::from_slice(&body).map_err(|_| MultiprocessError::IpcError)?;
+            self.peer_acked = self.peer_acked.max(envelope.ack.min(self.sent));
+
+            let Some(message) = envelope.payload else {
+                self.record(IpcDirection::Received, 0, "ack", 4 + len);
+                continue;
+            };
+            self.received = envelope.seq;
+            self.record(IpcDirection::Received, envelope.seq, message.kind(), 4 + len);
+            // Acknowledge in batches so the peer's window keeps moving
+            if self.received - self.ack_sent >= (self.window / 2).max(1) {
+                self.acknowledge()?;
+            }
+            return Ok(Some(message));
+        }
+        Ok(None)
+    }
+
+    fn record(&mut self, direction: IpcDirection, seq: u64, kind: &'static str, size: usize) {
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(IpcTraceEntry { direction, seq, kind, size, timestamp: SystemTime::now() });
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::css::Color;
+    use crate::display::DisplayCommand;
+    use std::time::Duration;
+
+    #[test]
+    fn test_round_trip_with_tracing() {
+        let (mut browser, mut content) = channel_pair().unwrap();
+        browser.set_tracing(true);
+
+        let input = BrowserMessage::Input {
+            tab_id: 1,
+            event: InputEvent::KeyDown { key: "a".to_string(), modifiers: Modifiers { shift: true, ..Default::default() } },
+        };
+        let navigate = BrowserMessage::Navigate { tab_id: 1, frame_id: 1, url: "https://example.com/".to_string() };
+        browser.send(&navigate).unwrap();
+        browser.send(&input).unwrap();
+        assert!(matches!(content.recv().unwrap(), Some(BrowserMessage::Navigate { tab_id: 1, .. })));
+        assert_eq!(content.recv().unwrap(), Some(input));
+
+        let rect = Rect { x: 0.0, y: 0.0, width: 10.0, height: 10.0 };
+        let frame = ContentMessage::Frame {
+            tab_id: 1,
+            frame_id: 1,
+            display_list: vec![DisplayCommand::SolidRect { color: Color::new(255, 0, 0, 255), rect }],
+            damage: vec![rect],
+        };
+        content.send(&frame).unwrap();
+        assert_eq!(browser.recv().unwrap(), Some(frame));
+
+        // The frame carried the acknowledgement of both browser messages
+        assert_eq!(browser.in_flight(), 0);
+        let trace: Vec<(IpcDirection, u64, &str)> = browser.take_trace().iter().map(|e| (e.direction, e.seq, e.kind)).collect();
+        assert_eq!(
+            trace,
+            vec![
+                (IpcDirection::Sent, 1, "navigate"),
+                (IpcDirection::Sent, 2, "input"),
+                (IpcDirection::Received, 1, "frame"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_backpressure_and_version_check() {
+        let (mut browser, mut content) = channel_pair().unwrap();
+        content.set_send_window(2);
+        let committed = ContentMessage::NavigationCommitted { tab_id: 1, url: "about:blank".to_string(), title: String::new() };
+        content.send(&committed).unwrap();
+        content.send(&committed).unwrap();
+        assert!(content.is_backpressured());
+        assert_eq!(content.send(&committed), Err(MultiprocessError::Backpressure));
+
+        // Reading lets the browser acknowledge, which reopens the window
+        browser.recv().unwrap();
+        browser.recv().unwrap();
+        browser.acknowledge().unwrap();
+        content.stream().set_read_timeout(Some(Duration::from_millis(50))).unwrap();
+        assert_eq!(content.recv().unwrap(), None);
+        assert_eq!(content.in_flight(), 0);
+        content.send(&committed).unwrap();
+
+        // A peer speaking another version is refused
+        let body = br#"{"version":99,"seq":1,"ack":0,"payload":"Shutdown"}"#;
+        let (mut raw, stream) = std::os::unix::net::UnixStream::pair().unwrap();
+        raw.write_all(&(body.len() as u32).to_be_bytes()).unwrap();
+        raw.write_all(body).unwrap();
+        let mut content: ContentChannel<_> = IpcChannel::new(stream);
+        assert_eq!(content.recv(), Err(MultiprocessError::VersionMismatch { expected: PROTOCOL_VERSION, found: 99 }));
+    }
+}
@@ -0,0 +1,340 @@
+// In-process message bus for cross-document messaging
+//
+// Documents (tabs and iframes) register with the bus under their origin.
+// BroadcastChannel messages go to every other channel with the same name
+// in a document of the same origin; `postMessage` goes to one document,
+// and only if it has the origin the sender asked for. Payloads travel as
+// structured-clone serializations produced by the page's script shim.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use url::Url;
+
+use super::MultiprocessError;
+
+/// Document ID type
+pub type DocumentId = u64;
+
+/// Messages a document may have waiting before new ones are refused
+const INBOX_LIMIT: usize = 1000;
+
+/// A structured-clone serialization of a script value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerializedValue(String);
+
+impl SerializedValue {
+    /// Wrap a serialization, checking that it is well-formed JSON
+    pub fn from_json(json: String) -> Result<Self, MultiprocessError> {
+        serde_json::from_str::<serde_json::Value>(&json).map_err(|_| MultiprocessError::IpcError)?;
+        Ok(Self(json))
+    }
+
+    pub fn as_json(&self) -> &str {
+        &self.0
+    }
+
+    /// Size of the serialization in bytes
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// A message waiting for a document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BusMessage {
+    /// Posted to a BroadcastChannel the document has open
+    Broadcast {
+        /// The receiving channel object in the document
+        channel_id: u32,
+        data: SerializedValue,
+        /// Origin of the sending document
+        origin: String,
+    },
+    /// Sent to the document's window with `postMessage`
+    Window {
+        data: SerializedValue,
+        /// Origin of the sending document
+        origin: String,
+        source: DocumentId,
+    },
+}
+
+/// A registered document
+struct Document {
+    /// ASCII serialization of the origin; `null` for opaque origins
+    origin: String,
+    tab_id: u64,
+    parent: Option<DocumentId>,
+    inbox: VecDeque<BusMessage>,
+}
+
+/// A BroadcastChannel object open in a document
+struct Subscription {
+    document: DocumentId,
+    channel_id: u32,
+    name: String,
+}
+
+#[derive(Default)]
+struct BusState {
+    next_id: DocumentId,
+    documents: HashMap<DocumentId, Document>,
+    subscriptions: Vec<Subscription>,
+}
+
+/// Routes messages between the documents of all tabs
+///
+/// Clones share the same bus.
+#[derive(Clone, Default)]
+pub struct MessageBus {
+    state: Arc<Mutex<BusState>>,
+}
+
+impl MessageBus {
+    /// Create an empty bus
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, BusState>, MultiprocessError> {
+        self.state.lock().map_err(|_| MultiprocessError::LockError)
+    }
+
+    /// Register a document loaded from `url`; `parent` is set for iframes
+    pub fn register_document(&self, url: &Url, tab_id: u64, parent: Option<DocumentId>) -> DocumentId {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.next_id += 1;
+        let id = state.next_id;
+        state.documents.insert(
+            id,
+            Document {
+                origin: url.origin().ascii_serialization(),
+                tab_id,
+                parent,
+                inbox: VecDeque::new(),
+            },
+        );
+        id
+    }
+
+    /// Forget a document that was unloaded, closing its channels
+    pub fn unregister_document(&self, id: DocumentId) {
+        if let Ok(mut state) = self.lock() {
+            state.documents.remove(&id);
+            state.subscriptions.retain(|s| s.document != id);
+        }
+    }
+
+    /// Every registered document
+    pub fn documents(&self) -> Vec<DocumentId> {
+        self.lock().map(|state| state.documents.keys().copied().collect()).unwrap_or_default()
+    }
+
+    /// Origin of a document
+    pub fn origin(&self, id: DocumentId) -> Option<String> {
+        self.lock().ok()?.documents.get(&id).map(|d| d.origin.clone())
+    }
+
+    /// Document embedding `id`, if it is an iframe
+    pub fn parent(&self, id: DocumentId) -> Option<DocumentId> {
+        self.lock().ok()?.documents.get(&id)?.parent
+    }
+
+    /// Documents shown in a tab: its page and the page's iframes
+    pub fn documents_in_tab(&self, tab_id: u64) -> Vec<DocumentId> {
+        let Ok(state) = self.lock() else {
+            return Vec::new();
+        };
+        let mut ids: Vec<DocumentId> =
+            state.documents.iter().filter(|(_, d)| d.tab_id == tab_id).map(|(id, _)| *id).collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// `new BroadcastChannel(name)` in `document`
+    pub fn subscribe(&self, document: DocumentId, channel_id: u32, name: &str) -> Result<(), MultiprocessError> {
+        let mut state = self.lock()?;
+        if !state.documents.contains_key(&document) {
+            return Err(MultiprocessError::DocumentNotFound);
+        }
+        state.subscriptions.retain(|s| !(s.document == document && s.channel_id == channel_id));
+        state.subscriptions.push(Subscription { document, channel_id, name: name.to_string() });
+        Ok(())
+    }
+
+    /// `channel.close()`
+    pub fn unsubscribe(&self, document: DocumentId, channel_id: u32) {
+        if let Ok(mut state) = self.lock() {
+            state.subscriptions.retain(|s| !(s.document == document && s.channel_id == channel_id));
+        }
+    }
+
+    /// `channel.postMessage(data)` from channel `channel_id` of `document`
+    ///
+    /// Every other open channel with the same name in a same-origin
+    /// document receives it, including other channels of the sender.
+    /// Returns how many channels it was queued for.
+    pub fn broadcast(
+        &self,
+        document: DocumentId,
+        channel_id: u32,
+        name: &str,
+        data: &SerializedValue,
+    ) -> Result<usize, MultiprocessError> {
+        let mut state = self.lock()?;
+        let origin = state.documents.get(&document).ok_or(MultiprocessError::DocumentNotFound)?.origin.clone();
+        let same_origin = |other: DocumentId, other_origin: &str| {
+            // Each opaque origin is unique, so only its own document shares it
+            other_origin == origin && (origin != "null" || other == document)
+        };
+
+        let targets: Vec<(DocumentId, u32)> = state
+            .subscriptions
+            .iter()
+            .filter(|s| s.name == name && !(s.document == document && s.channel_id == channel_id))
+            .map(|s| (s.document, s.channel_id))
+            .collect();
+        let mut delivered = 0;
+        for (target, target_channel) in targets {
+            let Some(doc) = state.documents.get_mut(&target) else {
+                continue;
+            };
+            if !same_origin(target, &doc.origin) || doc.inbox.len() >= INBOX_LIMIT {
+                continue;
+            }
+            doc.inbox.push_back(BusMessage::Broadcast {
+                channel_id: target_channel,
+                data: data.clone(),
+                origin: origin.clone(),
+            });
+            delivered += 1;
+        }
+        Ok(delivered)
+    }
+
+    /// `targetWindow.postMessage(data, targetOrigin)` from `source` to `target`
+    ///
+    /// `target_origin` is `*` for any origin, `/` for the sender's origin,
+    /// or a URL whose origin the target must have. Returns false when the
+    /// target's origin does not match and the message was dropped.
+    pub fn post_message(
+        &self,
+        source: DocumentId,
+        target: DocumentId,
+        target_origin: &str,
+        data: &SerializedValue,
+    ) -> Result<bool, MultiprocessError> {
+        let mut state = self.lock()?;
+        let origin = state.documents.get(&source).ok_or(MultiprocessError::DocumentNotFound)?.origin.clone();
+        let doc = state.documents.get_mut(&target).ok_or(MultiprocessError::DocumentNotFound)?;
+        if !target_origin_matches(target_origin, &origin, &doc.origin)? {
+            return Ok(false);
+        }
+        if doc.inbox.len() >= INBOX_LIMIT {
+            return Err(MultiprocessError::MessageQueueFull);
+        }
+        doc.inbox.push_back(BusMessage::Window { data: data.clone(), origin, source });
+        Ok(true)
+    }
+
+    /// Take the messages waiting for a document
+    pub fn take_messages(&self, document: DocumentId) -> Vec<BusMessage> {
+        self.lock()
+            .ok()
+            .and_then(|mut state| state.documents.get_mut(&document).map(|d| d.inbox.drain(..).collect()))
+            .unwrap_or_default()
+    }
+}
+
+/// Does a `postMessage` target origin allow delivery to `target`
+///
+/// `*` matches anything and `/` the sender's origin; an opaque origin is
+/// never matched by name.
+pub(super) fn target_origin_matches(
+    target_origin: &str,
+    sender: &str,
+    target: &str,
+) -> Result<bool, MultiprocessError> {
+    let required = match target_origin {
+        "*" => return Ok(true),
+        "/" => sender.to_string(),
+        url => Url::parse(url)
+            .map_err(|_| MultiprocessError::InvalidTargetOrigin)?
+            .origin()
+            .ascii_serialization(),
+    };
+    Ok(required == target && required != "null")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    fn value(json: &str) -> SerializedValue {
+        SerializedValue::from_json(json.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_broadcast_same_origin_only() {
+        let bus = MessageBus::new();
+        let a = bus.register_document(&url("https://example.com/a"), 1, None);
+        let b = bus.register_document(&url("https://example.com/b"), 2, None);
+        let other = bus.register_document(&url("https://other.com/"), 3, None);
+        let frame = bus.register_document(&url("https://example.com/frame"), 1, Some(a));
+        assert_eq!(bus.documents_in_tab(1), vec![a, frame]);
+        assert_eq!(bus.parent(frame), Some(a));
+
+        bus.subscribe(a, 1, "chat").unwrap();
+        bus.subscribe(a, 2, "chat").unwrap();
+        bus.subscribe(b, 1, "chat").unwrap();
+        bus.subscribe(b, 2, "news").unwrap();
+        bus.subscribe(other, 1, "chat").unwrap();
+        bus.subscribe(frame, 1, "chat").unwrap();
+
+        let data = value(r#"{"t":"value","v":"hi"}"#);
+        assert_eq!(bus.broadcast(a, 1, "chat", &data).unwrap(), 3);
+        // The sender's other channel hears it, the sending channel does not
+        let received = bus.take_messages(a);
+        assert_eq!(received.len(), 1);
+        assert!(matches!(&received[0], BusMessage::Broadcast { channel_id: 2, origin, .. } if origin == "https://example.com"));
+        assert_eq!(bus.take_messages(b).len(), 1);
+        assert_eq!(bus.take_messages(frame).len(), 1);
+        assert!(bus.take_messages(other).is_empty());
+
+        // Closed channels and unloaded documents stop receiving
+        bus.unsubscribe(a, 2);
+        bus.unregister_document(frame);
+        assert_eq!(bus.broadcast(b, 1, "chat", &data).unwrap(), 1);
+        assert_eq!(bus.subscribe(frame, 1, "chat"), Err(MultiprocessError::DocumentNotFound));
+    }
+
+    #[test]
+    fn test_post_message_target_origin() {
+        let bus = MessageBus::new();
+        let page = bus.register_document(&url("https://example.com/"), 1, None);
+        let frame = bus.register_document(&url("https://widgets.example.net/embed"), 1, Some(page));
+        let data = value("{}");
+
+        assert_eq!(bus.post_message(page, frame, "https://widgets.example.net", &data), Ok(true));
+        assert_eq!(bus.post_message(page, frame, "https://example.com", &data), Ok(false));
+        assert_eq!(bus.post_message(page, frame, "/", &data), Ok(false));
+        assert_eq!(bus.post_message(frame, page, "*", &data), Ok(true));
+        assert_eq!(bus.post_message(page, frame, "not a url", &data), Err(MultiprocessError::InvalidTargetOrigin));
+
+        assert_eq!(
+            bus.take_messages(page),
+            vec![BusMessage::Window { data: data.clone(), origin: "https://widgets.example.net".to_string(), source: frame }]
+        );
+        assert_eq!(bus.take_messages(frame).len(), 1);
+        assert!(SerializedValue::from_json("{".to_string()).is_err());
+    }
+}
@@ -1,8 +1,31 @@
 // Multi-Process Architecture - Phase 7 Task 6
 
+mod content;
+mod gpu;
+mod ipc;
+mod message_bus;
+mod sandbox;
+mod shm;
+
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
+pub use content::{
+    crash_page_html, site_key, ContentEvent, ContentHandler, ContentSupervisor, FrameId, FrameProxy, HandlerFactory,
+    ScriptHost,
+};
+pub use gpu::{
+    software_backend, software_window_backend, wgpu_backend, BackendFactory, GpuBackendKind, GpuEvent, GpuFrame,
+    GpuProcess, PaintBackend, VideoLayer,
+};
+pub use ipc::{
+    channel_pair, BrowserChannel, BrowserMessage, ContentChannel, ContentMessage, InputEvent, IpcChannel,
+    IpcDirection, IpcPayload, IpcStream, IpcTraceEntry, Modifiers, MouseButton, PROTOCOL_VERSION,
+};
+pub use message_bus::{BusMessage, DocumentId, MessageBus, SerializedValue};
+pub use sandbox::{enter_sandbox, spawn_broker, BrokerClient, BrokerPolicy, BrokerRequest, SandboxLevel};
+pub use shm::{SharedRegion, ShmHandle, ShmReader, ShmWriter};
+
 /// Process ID type
 pub type ProcessId = u64;
 
@@ -437,6 +460,34 @@ pub enum MultiprocessError {
     OutOfBounds,
     /// IPC error
     IpcError,
+    /// No document is registered with that ID
+    DocumentNotFound,
+    /// `postMessage` target origin is not `*`, `/` or a URL
+    InvalidTargetOrigin,
+    /// The operating system could not start the process
+    SpawnFailed,
+    /// Too many messages are waiting for the peer to acknowledge them
+    Backpressure,
+    /// The peer closed the connection
+    Disconnected,
+    /// An IPC message exceeds the size limit
+    MessageTooLarge,
+    /// The peer speaks another version of the IPC protocol
+    VersionMismatch { expected: u32, found: u32 },
+    /// Shared memory was written to while it was being read
+    TornRead,
+    /// Shared memory no longer holds the payload a handle refers to
+    StaleHandle,
+    /// The OS sandbox could not be applied
+    SandboxFailed,
+    /// The sandbox policy does not allow the request
+    AccessDenied,
+    /// The broker was allowed to act but the operation failed
+    BrokerFailed(std::io::ErrorKind),
+    /// No frame is registered with that ID
+    FrameNotFound,
+    /// The GPU device was lost or could not be created
+    ContextLost,
 }
 
 impl std::fmt::Display for MultiprocessError {
@@ -449,6 +500,22 @@ impl std::fmt::Display for MultiprocessError {
             MultiprocessError::MemoryLimitExceeded => write!(f, "Memory limit exceeded"),
             MultiprocessError::OutOfBounds => write!(f, "Out of bounds access"),
             MultiprocessError::IpcError => write!(f, "IPC error"),
+            MultiprocessError::DocumentNotFound => write!(f, "Document not found"),
+            MultiprocessError::InvalidTargetOrigin => write!(f, "Invalid target origin"),
+            MultiprocessError::SpawnFailed => write!(f, "Failed to start process"),
+            MultiprocessError::Backpressure => write!(f, "Peer is not keeping up"),
+            MultiprocessError::Disconnected => write!(f, "Peer disconnected"),
+            MultiprocessError::MessageTooLarge => write!(f, "IPC message too large"),
+            MultiprocessError::VersionMismatch { expected, found } => {
+                write!(f, "IPC protocol version {} expected, peer speaks {}", expected, found)
+            }
+            MultiprocessError::TornRead => write!(f, "Shared memory changed while being read"),
+            MultiprocessError::StaleHandle => write!(f, "Shared memory handle is stale"),
+            MultiprocessError::SandboxFailed => write!(f, "Failed to apply sandbox"),
+            MultiprocessError::AccessDenied => write!(f, "Access denied by sandbox policy"),
+            MultiprocessError::BrokerFailed(kind) => write!(f, "Broker request failed: {}", kind),
+            MultiprocessError::FrameNotFound => write!(f, "Frame not found"),
+            MultiprocessError::ContextLost => write!(f, "GPU context lost"),
         }
     }
 }
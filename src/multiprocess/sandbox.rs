@@ -0,0 +1,560 @@
+// Sandboxing for content processes
+//
+// Content runs behind a system call filter that refuses to open files,
+// create or connect sockets, or start programs. What it needs from the
+// outside world it asks the broker for: the broker runs in the browser,
+// checks each request against the process's `BrokerPolicy` and hands back
+// an open file or connected socket, which the sandboxed side can still
+// read and write.
+//
+// Content processes are still threads, so on Linux the filter is the
+// per-thread seccomp-bpf filter. Windows and macOS have no OS sandbox for
+// content yet: AppContainer and Seatbelt confine a whole process, and
+// applying either from a content thread would confine the browser with it.
+// They need content to move out of process first; until then
+// `enter_sandbox` reports `SandboxLevel::None` there and page content runs
+// with only the broker's checks on what it asks for.
+
+use std::fs::{File, OpenOptions};
+use std::net::{TcpStream, ToSocketAddrs};
+#[cfg(target_os = "linux")]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::time::Duration;
+
+use super::MultiprocessError;
+
+/// How long the broker waits for a TCP connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How the calling content process is confined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SandboxLevel {
+    /// No OS sandbox: Windows and macOS, where AppContainer and Seatbelt
+    /// are not applied yet
+    None,
+    /// Linux seccomp-bpf system call filter
+    Seccomp,
+}
+
+/// Confine the calling thread, and threads it starts, for good
+pub fn enter_sandbox() -> Result<SandboxLevel, MultiprocessError> {
+    #[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        seccomp::install()?;
+        Ok(SandboxLevel::Seccomp)
+    }
+    #[cfg(not(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64"))))]
+    {
+        Ok(SandboxLevel::None)
+    }
+}
+
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod seccomp {
+    use libc::{c_long, sock_filter, sock_fprog};
+
+    use super::MultiprocessError;
+
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: u32 = 0xC000_003E;
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: u32 = 0xC000_00B7;
+
+    /// x32 system calls on x86_64 have this bit set; they are refused
+    const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+    /// Offsets into `struct seccomp_data`
+    const NR_OFFSET: u32 = 0;
+    const ARCH_OFFSET: u32 = 4;
+    /// Low half of the first argument; both architectures are little-endian
+    const ARG0_OFFSET: u32 = 16;
+
+    /// `clone` flags that create namespaces
+    const CLONE_NAMESPACES: u32 = (libc::CLONE_NEWNS
+        | libc::CLONE_NEWCGROUP
+        | libc::CLONE_NEWUTS
+        | libc::CLONE_NEWIPC
+        | libc::CLONE_NEWUSER
+        | libc::CLONE_NEWPID
+        | libc::CLONE_NEWNET) as u32;
+
+    /// Filesystem, network and process creation calls content may not make
+    const DENIED: &[c_long] = &[
+        libc::SYS_openat,
+        libc::SYS_openat2,
+        libc::SYS_name_to_handle_at,
+        libc::SYS_open_by_handle_at,
+        libc::SYS_unlinkat,
+        libc::SYS_renameat,
+        libc::SYS_renameat2,
+        libc::SYS_mkdirat,
+        libc::SYS_mknodat,
+        libc::SYS_symlinkat,
+        libc::SYS_linkat,
+        libc::SYS_fchmodat,
+        libc::SYS_fchownat,
+        libc::SYS_truncate,
+        libc::SYS_open_tree,
+        libc::SYS_move_mount,
+        libc::SYS_fsopen,
+        libc::SYS_fsconfig,
+        libc::SYS_fsmount,
+        libc::SYS_fspick,
+        libc::SYS_mount_setattr,
+        libc::SYS_socket,
+        libc::SYS_socketpair,
+        libc::SYS_connect,
+        libc::SYS_bind,
+        libc::SYS_listen,
+        libc::SYS_accept,
+        libc::SYS_accept4,
+        // Passing descriptors over a socket
+        libc::SYS_sendmsg,
+        libc::SYS_recvmsg,
+        libc::SYS_sendmmsg,
+        libc::SYS_recvmmsg,
+        // io_uring does file and network I/O without the calls above
+        libc::SYS_io_uring_setup,
+        libc::SYS_io_uring_enter,
+        libc::SYS_io_uring_register,
+        libc::SYS_execve,
+        libc::SYS_execveat,
+        libc::SYS_ptrace,
+        libc::SYS_mount,
+        libc::SYS_chroot,
+        // Namespaces, which `clone` may not create either
+        libc::SYS_unshare,
+        libc::SYS_setns,
+        // Kernel attack surface content has no use for
+        libc::SYS_bpf,
+        libc::SYS_userfaultfd,
+        libc::SYS_perf_event_open,
+        // Reaching into other processes
+        libc::SYS_process_vm_readv,
+        libc::SYS_process_vm_writev,
+        libc::SYS_pidfd_getfd,
+    ];
+
+    /// Older forms of the above that only x86_64 has
+    #[cfg(target_arch = "x86_64")]
+    const DENIED_LEGACY: &[c_long] = &[
+        libc::SYS_open,
+        libc::SYS_creat,
+        libc::SYS_unlink,
+        libc::SYS_rename,
+        libc::SYS_mkdir,
+        libc::SYS_rmdir,
+        libc::SYS_mknod,
+        libc::SYS_symlink,
+        libc::SYS_link,
+        libc::SYS_chmod,
+        libc::SYS_chown,
+        libc::SYS_lchown,
+        libc::SYS_fork,
+        libc::SYS_vfork,
+    ];
+    #[cfg(target_arch = "aarch64")]
+    const DENIED_LEGACY: &[c_long] = &[];
+
+    /// Calls that fail as if the kernel lacked them
+    ///
+    /// `clone3` cannot be filtered on its flags, which it passes in memory;
+    /// refused with `ENOSYS`, the C library falls back to `clone`, whose
+    /// flags are checked, to start threads.
+    const UNSUPPORTED: &[c_long] = &[libc::SYS_clone3];
+
+    fn statement(code: u32, k: u32) -> sock_filter {
+        sock_filter { code: code as u16, jt: 0, jf: 0, k }
+    }
+
+    fn jump(code: u32, k: u32, jt: u8, jf: u8) -> sock_filter {
+        sock_filter { code: code as u16, jt, jf, k }
+    }
+
+    pub(super) fn install() -> Result<(), MultiprocessError> {
+        let load = libc::BPF_LD | libc::BPF_W | libc::BPF_ABS;
+        let jump_eq = libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K;
+        let ret = libc::BPF_RET | libc::BPF_K;
+        let deny = libc::SECCOMP_RET_ERRNO | (libc::EPERM as u32 & libc::SECCOMP_RET_DATA);
+        let unsupported = libc::SECCOMP_RET_ERRNO | (libc::ENOSYS as u32 & libc::SECCOMP_RET_DATA);
+
+        let mut filter = vec![
+            statement(load, ARCH_OFFSET),
+            jump(jump_eq, AUDIT_ARCH, 1, 0),
+            statement(ret, libc::SECCOMP_RET_KILL_PROCESS),
+            statement(load, NR_OFFSET),
+            jump(libc::BPF_JMP | libc::BPF_JGE | libc::BPF_K, X32_SYSCALL_BIT, 0, 1),
+            statement(ret, deny),
+        ];
+        for &nr in DENIED.iter().chain(DENIED_LEGACY) {
+            filter.push(jump(jump_eq, nr as u32, 0, 1));
+            filter.push(statement(ret, deny));
+        }
+        for &nr in UNSUPPORTED {
+            filter.push(jump(jump_eq, nr as u32, 0, 1));
+            filter.push(statement(ret, unsupported));
+        }
+        // `clone` starts threads, but not in new namespaces
+        filter.extend([
+            jump(jump_eq, libc::SYS_clone as u32, 0, 3),
+            statement(load, ARG0_OFFSET),
+            jump(libc::BPF_JMP | libc::BPF_JSET | libc::BPF_K, CLONE_NAMESPACES, 0, 1),
+            statement(ret, deny),
+        ]);
+        filter.push(statement(ret, libc::SECCOMP_RET_ALLOW));
+
+        let program = sock_fprog { len: filter.len() as u16, filter: filter.as_mut_ptr() };
+        // SAFETY: both calls only read their arguments; `program` points at
+        // `filter`, which outlives the call that copies it into the kernel
+        let installed = unsafe {
+            libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) == 0
+                && libc::prctl(libc::PR_SET_SECCOMP, libc::SECCOMP_MODE_FILTER, &program as *const sock_fprog) == 0
+        };
+        if installed {
+            Ok(())
+        } else {
+            Err(MultiprocessError::SandboxFailed)
+        }
+    }
+}
+
+/// What a content process may ask the broker for
+///
+/// A new policy allows nothing.
+#[derive(Debug, Clone, Default)]
+pub struct BrokerPolicy {
+    read: Vec<PathBuf>,
+    write: Vec<PathBuf>,
+    network: bool,
+}
+
+impl BrokerPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow reading files under `dir`
+    pub fn allow_read(&mut self, dir: impl AsRef<Path>) {
+        self.read.push(canonical(dir.as_ref()));
+    }
+
+    /// Allow reading, creating and writing files under `dir`
+    pub fn allow_write(&mut self, dir: impl AsRef<Path>) {
+        self.write.push(canonical(dir.as_ref()));
+    }
+
+    /// Allow outgoing TCP connections
+    pub fn set_network_allowed(&mut self, allowed: bool) {
+        self.network = allowed;
+    }
+
+    /// May `path` be opened, for writing if `write` is set
+    ///
+    /// Symbolic links are resolved first, so they cannot lead out of the
+    /// allowed directories.
+    pub fn permits_open(&self, path: &Path, write: bool) -> bool {
+        self.resolve_open(path, write).is_some()
+    }
+
+    /// The allowed directory `path` resolves into, and the resolved path
+    /// within it, if the policy allows opening it
+    ///
+    /// The broker opens the resolved path from the directory without
+    /// following links, so one swapped in after the check is refused.
+    fn resolve_open(&self, path: &Path, write: bool) -> Option<(&Path, PathBuf)> {
+        if !path.is_absolute() || path.components().any(|c| c == Component::ParentDir) {
+            return None;
+        }
+        let resolved = match (path.canonicalize(), path.parent(), path.file_name()) {
+            (Ok(resolved), _, _) => resolved,
+            (Err(_), Some(parent), Some(name)) => canonical(parent).join(name),
+            _ => return None,
+        };
+        let dirs = if write { &self.write[..] } else { &self.read[..] };
+        dirs.iter().chain(&self.write).find_map(|dir| {
+            let relative = resolved.strip_prefix(dir).ok()?;
+            Some((dir.as_path(), relative.to_path_buf()))
+        })
+    }
+
+    pub fn permits_network(&self) -> bool {
+        self.network
+    }
+}
+
+/// Resolve what can be resolved of a path
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Open `relative`, a path with no links in it, under the directory `dir`
+///
+/// Each directory on the way is opened from the one before with
+/// `O_NOFOLLOW`, so a directory swapped for a link after the policy was
+/// checked fails the open instead of leading out of `dir`.
+#[cfg(target_os = "linux")]
+fn open_beneath(dir: &Path, relative: &Path, write: bool) -> std::io::Result<File> {
+    use std::ffi::CString;
+    use std::io::{Error, ErrorKind};
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut current: OwnedFd = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_PATH | libc::O_DIRECTORY | libc::O_CLOEXEC)
+        .open(dir)?
+        .into();
+    let mut components = relative.components().peekable();
+    while let Some(component) = components.next() {
+        let Component::Normal(name) = component else {
+            return Err(Error::from(ErrorKind::InvalidInput));
+        };
+        let name = CString::new(name.as_bytes()).map_err(|_| Error::from(ErrorKind::InvalidInput))?;
+        let last = components.peek().is_none();
+        let flags = libc::O_NOFOLLOW
+            | libc::O_CLOEXEC
+            | match (last, write) {
+                (false, _) => libc::O_PATH | libc::O_DIRECTORY,
+                (true, false) => libc::O_RDONLY,
+                (true, true) => libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC,
+            };
+        // SAFETY: `current` is an open directory and `name` a NUL-terminated
+        // string, both alive for the call
+        let fd = unsafe { libc::openat(current.as_raw_fd(), name.as_ptr(), flags, 0o666 as libc::c_uint) };
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+        // SAFETY: `openat` returned a new descriptor that nothing else owns
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        if last {
+            return Ok(File::from(fd));
+        }
+        current = fd;
+    }
+    // The allowed directory itself is not a file to hand out
+    Err(Error::from(ErrorKind::InvalidInput))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_beneath(dir: &Path, relative: &Path, write: bool) -> std::io::Result<File> {
+    let mut options = OpenOptions::new();
+    if write {
+        options.write(true).create(true).truncate(true);
+    } else {
+        options.read(true);
+    }
+    options.open(dir.join(relative))
+}
+
+/// Something a content process asked the broker for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BrokerRequest {
+    /// Open an existing file, or create or truncate it if `write` is set
+    Open { path: PathBuf, write: bool },
+    /// Connect to a host over TCP
+    Connect { host: String, port: u16 },
+}
+
+/// What the broker handed over
+enum Capability {
+    File(File),
+    Stream(TcpStream),
+}
+
+type BrokerReply = Result<Capability, MultiprocessError>;
+
+/// Content side of a broker; clones share the broker
+#[derive(Clone)]
+pub struct BrokerClient {
+    requests: Sender<(BrokerRequest, Sender<BrokerReply>)>,
+}
+
+impl BrokerClient {
+    /// Open a file for reading
+    pub fn open_file(&self, path: &Path) -> Result<File, MultiprocessError> {
+        match self.request(BrokerRequest::Open { path: path.to_path_buf(), write: false })? {
+            Capability::File(file) => Ok(file),
+            Capability::Stream(_) => Err(MultiprocessError::IpcError),
+        }
+    }
+
+    /// Create or truncate a file and open it for writing
+    pub fn create_file(&self, path: &Path) -> Result<File, MultiprocessError> {
+        match self.request(BrokerRequest::Open { path: path.to_path_buf(), write: true })? {
+            Capability::File(file) => Ok(file),
+            Capability::Stream(_) => Err(MultiprocessError::IpcError),
+        }
+    }
+
+    /// Open a TCP connection
+    pub fn connect(&self, host: &str, port: u16) -> Result<TcpStream, MultiprocessError> {
+        match self.request(BrokerRequest::Connect { host: host.to_string(), port })? {
+            Capability::Stream(stream) => Ok(stream),
+            Capability::File(_) => Err(MultiprocessError::IpcError),
+        }
+    }
+
+    fn request(&self, request: BrokerRequest) -> BrokerReply {
+        let (reply, response) = mpsc::channel();
+        self.requests.send((request, reply)).map_err(|_| MultiprocessError::Disconnected)?;
+        response.recv().map_err(|_| MultiprocessError::Disconnected)?
+    }
+}
+
+/// Start a broker enforcing `policy`
+///
+/// It runs until every client is dropped.
+pub fn spawn_broker(policy: BrokerPolicy) -> Result<BrokerClient, MultiprocessError> {
+    let (requests, incoming) = mpsc::channel::<(BrokerRequest, Sender<BrokerReply>)>();
+    std::thread::Builder::new()
+        .name("broker".to_string())
+        .spawn(move || {
+            for (request, reply) in incoming {
+                let _ = reply.send(serve(&policy, request));
+            }
+        })
+        .map_err(|_| MultiprocessError::SpawnFailed)?;
+    Ok(BrokerClient { requests })
+}
+
+/// Carry out a request the policy allows
+fn serve(policy: &BrokerPolicy, request: BrokerRequest) -> BrokerReply {
+    match request {
+        BrokerRequest::Open { path, write } => {
+            let (dir, relative) = policy.resolve_open(&path, write).ok_or(MultiprocessError::AccessDenied)?;
+            open_beneath(dir, &relative, write)
+                .map(Capability::File)
+                .map_err(|e| MultiprocessError::BrokerFailed(e.kind()))
+        }
+        BrokerRequest::Connect { host, port } => {
+            if !policy.permits_network() {
+                return Err(MultiprocessError::AccessDenied);
+            }
+            let addr = (host.as_str(), port)
+                .to_socket_addrs()
+                .map_err(|e| MultiprocessError::BrokerFailed(e.kind()))?
+                .next()
+                .ok_or(MultiprocessError::BrokerFailed(std::io::ErrorKind::NotFound))?;
+            TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)
+                .map(Capability::Stream)
+                .map_err(|e| MultiprocessError::BrokerFailed(e.kind()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("browser-sandbox-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_broker_policy() {
+        let dir = scratch_dir("policy");
+        let mut policy = BrokerPolicy::new();
+        policy.allow_read(&dir);
+        let inside = dir.join("page.txt");
+
+        assert!(policy.permits_open(&inside, false));
+        assert!(!policy.permits_open(&inside, true));
+        assert!(!policy.permits_open(&dir.join("../escape.txt"), false));
+        assert!(!policy.permits_open(Path::new("relative.txt"), false));
+        assert!(!policy.permits_open(Path::new("/etc/passwd"), false));
+        #[cfg(unix)]
+        {
+            let link = dir.join("link.txt");
+            let _ = std::fs::remove_file(&link);
+            std::os::unix::fs::symlink("/etc/passwd", &link).unwrap();
+            assert!(!policy.permits_open(&link, false));
+        }
+        policy.allow_write(&dir);
+        assert!(policy.permits_open(&inside, true));
+
+        let client = spawn_broker(policy).unwrap();
+        client.create_file(&inside).unwrap().write_all(b"hello").unwrap();
+        let mut text = String::new();
+        client.open_file(&inside).unwrap().read_to_string(&mut text).unwrap();
+        assert_eq!(text, "hello");
+        assert_eq!(client.connect("localhost", 80).err(), Some(MultiprocessError::AccessDenied));
+        assert_eq!(
+            client.open_file(&dir.join("missing.txt")).err(),
+            Some(MultiprocessError::BrokerFailed(std::io::ErrorKind::NotFound))
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_open_does_not_follow_swapped_directory() {
+        let dir = scratch_dir("swap");
+        let sub = dir.join("sub");
+        std::fs::create_dir_all(&sub).unwrap();
+        std::fs::write(sub.join("passwd"), "page data").unwrap();
+        let mut policy = BrokerPolicy::new();
+        policy.allow_read(&dir);
+        let (root, relative) = policy.resolve_open(&sub.join("passwd"), false).unwrap();
+        let root = root.to_path_buf();
+        assert_eq!(relative, Path::new("sub/passwd"));
+        assert!(open_beneath(&root, &relative, false).is_ok());
+
+        // A directory on the checked path becomes a link out of the allowed one
+        std::fs::remove_dir_all(&sub).unwrap();
+        std::os::unix::fs::symlink("/etc", &sub).unwrap();
+        assert!(open_beneath(&root, &relative, false).is_err());
+        assert!(open_beneath(&root, &relative, true).is_err());
+        assert!(open_beneath(&root, Path::new(""), false).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+    #[test]
+    fn test_sandboxed_thread_goes_through_broker() {
+        let dir = scratch_dir("seccomp");
+        let allowed = dir.join("allowed.txt");
+        std::fs::write(&allowed, "brokered").unwrap();
+        let mut policy = BrokerPolicy::new();
+        policy.allow_read(&dir);
+        let client = spawn_broker(policy).unwrap();
+
+        let path = allowed.clone();
+        let result = std::thread::spawn(move || {
+            assert_eq!(enter_sandbox(), Ok(SandboxLevel::Seccomp));
+            let direct = File::open(&path).map_err(|e| e.kind());
+            let socket = std::net::TcpListener::bind("127.0.0.1:0").map(|_| ()).map_err(|e| e.kind());
+            let pair = std::os::unix::net::UnixStream::pair().map(|_| ()).map_err(|e| e.kind());
+            // SAFETY: neither call touches memory. Without the filter the
+            // kernel refuses this `clone` (a thread in a new user namespace)
+            // with EINVAL rather than starting anything.
+            let namespaces = unsafe {
+                let thread = libc::CLONE_VM | libc::CLONE_SIGHAND | libc::CLONE_THREAD;
+                let cloned = libc::syscall(libc::SYS_clone, (thread | libc::CLONE_NEWUSER) as libc::c_ulong, 0, 0, 0, 0);
+                let cloned = (cloned, std::io::Error::last_os_error().raw_os_error());
+                let unshared = (libc::unshare(libc::CLONE_NEWUSER), std::io::Error::last_os_error().raw_os_error());
+                (cloned, unshared)
+            };
+            assert_eq!(namespaces, ((-1, Some(libc::EPERM)), (-1, Some(libc::EPERM))));
+            // Threads still start, through `clone` once `clone3` is refused
+            assert_eq!(std::thread::spawn(|| 7).join().unwrap(), 7);
+            let mut text = String::new();
+            client.open_file(&path).unwrap().read_to_string(&mut text).unwrap();
+            (direct.err(), socket.err(), text, pair.err())
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(result.0, Some(std::io::ErrorKind::PermissionDenied));
+        assert_eq!(result.1, Some(std::io::ErrorKind::PermissionDenied));
+        assert_eq!(result.2, "brokered");
+        assert_eq!(result.3, Some(std::io::ErrorKind::PermissionDenied));
+        // The rest of the process is not affected
+        assert!(File::open(&allowed).is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,323 @@
+// Shared-memory transport for large payloads
+//
+// Decoded images, big display lists and tile bitmaps are written into
+// memory-mapped files both processes map, and only a small `ShmHandle`
+// travels over IPC. Each region starts with a generation counter used as a
+// sequence lock: it is odd while the writer is busy and bumped again when
+// the write completes, so a reader can tell that what it read was torn or
+// has been overwritten since the handle was sent.
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+
+use memmap2::MmapMut;
+use serde::{Deserialize, Serialize};
+
+use super::MultiprocessError;
+use crate::display::DisplayList;
+
+/// Generation counter and payload length
+const HEADER_SIZE: usize = 16;
+
+/// Regions a writer rotates through, so the reader can still be reading
+/// the previous payload while the next one is written
+const WRITER_REGIONS: usize = 2;
+
+/// Smallest region a writer creates
+const MIN_REGION_SIZE: usize = 64 * 1024;
+
+/// Names regions created by this process
+static NEXT_REGION: AtomicU64 = AtomicU64::new(1);
+
+/// Where a payload is, as sent over IPC
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShmHandle {
+    /// File backing the region
+    pub path: String,
+    /// Payload length in bytes
+    pub len: usize,
+    /// Generation the payload was written with
+    pub generation: u64,
+}
+
+/// A memory-mapped file shared between processes
+pub struct SharedRegion {
+    map: MmapMut,
+    path: PathBuf,
+    /// The creator removes the file when it is done with it
+    owner: bool,
+}
+
+impl SharedRegion {
+    /// Create a region with room for `capacity` payload bytes
+    pub fn create(capacity: usize) -> Result<Self, MultiprocessError> {
+        let name = format!("browser-shm-{}-{}", std::process::id(), NEXT_REGION.fetch_add(1, Ordering::Relaxed));
+        let path = shm_dir().join(name);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(|_| MultiprocessError::IpcError)?;
+        let region = Self::map(&file, path.clone(), true, HEADER_SIZE + capacity);
+        if region.is_err() {
+            let _ = std::fs::remove_file(&path);
+        }
+        region
+    }
+
+    /// Map a region another process created
+    pub fn open(path: &Path) -> Result<Self, MultiprocessError> {
+        let file = OpenOptions::new().read(true).write(true).open(path).map_err(|_| MultiprocessError::IpcError)?;
+        let len = file.metadata().map_err(|_| MultiprocessError::IpcError)?.len() as usize;
+        if len < HEADER_SIZE {
+            return Err(MultiprocessError::OutOfBounds);
+        }
+        Self::map(&file, path.to_path_buf(), false, len)
+    }
+
+    fn map(file: &File, path: PathBuf, owner: bool, len: usize) -> Result<Self, MultiprocessError> {
+        if owner {
+            file.set_len(len as u64).map_err(|_| MultiprocessError::IpcError)?;
+        }
+        // SAFETY: the file is only ever modified through `SharedRegion`,
+        // whose readers validate what they read against the generation
+        let map = unsafe { MmapMut::map_mut(file) }.map_err(|_| MultiprocessError::IpcError)?;
+        Ok(Self { map, path, owner })
+    }
+
+    /// File backing the region
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Payload bytes the region can hold
+    pub fn capacity(&self) -> usize {
+        self.map.len() - HEADER_SIZE
+    }
+
+    /// Generation of the last completed write; odd during a write
+    pub fn generation(&self) -> u64 {
+        self.counter(0).load(Ordering::Acquire)
+    }
+
+    /// Handle for the payload currently in the region
+    pub fn handle(&self) -> ShmHandle {
+        ShmHandle {
+            path: self.path.to_string_lossy().into_owned(),
+            len: self.counter(8).load(Ordering::Acquire) as usize,
+            generation: self.generation(),
+        }
+    }
+
+    /// Copy `data` into the region
+    pub fn write(&mut self, data: &[u8]) -> Result<ShmHandle, MultiprocessError> {
+        self.write_with(data.len(), |buf| buf.copy_from_slice(data))
+    }
+
+    /// Fill `len` bytes of the region in place, e.g. straight from an
+    /// image decoder
+    pub fn write_with(&mut self, len: usize, fill: impl FnOnce(&mut [u8])) -> Result<ShmHandle, MultiprocessError> {
+        if len > self.capacity() {
+            return Err(MultiprocessError::OutOfBounds);
+        }
+        let generation = self.generation() | 1;
+        self.counter(0).store(generation, Ordering::Relaxed);
+        fence(Ordering::Release);
+        fill(&mut self.map[HEADER_SIZE..HEADER_SIZE + len]);
+        self.counter(8).store(len as u64, Ordering::Relaxed);
+        self.counter(0).store(generation + 1, Ordering::Release);
+        Ok(self.handle())
+    }
+
+    /// Look at the payload `handle` refers to without copying it
+    ///
+    /// The result of `read` is only returned if the payload was not
+    /// being written and was not replaced while it ran.
+    pub fn read_with<R>(&self, handle: &ShmHandle, read: impl FnOnce(&[u8]) -> R) -> Result<R, MultiprocessError> {
+        let before = self.generation();
+        if before & 1 == 1 {
+            return Err(MultiprocessError::TornRead);
+        }
+        if before != handle.generation {
+            return Err(MultiprocessError::StaleHandle);
+        }
+        if handle.len > self.capacity() {
+            return Err(MultiprocessError::OutOfBounds);
+        }
+        let result = read(&self.map[HEADER_SIZE..HEADER_SIZE + handle.len]);
+        fence(Ordering::Acquire);
+        if self.counter(0).load(Ordering::Relaxed) != before {
+            return Err(MultiprocessError::TornRead);
+        }
+        Ok(result)
+    }
+
+    /// Header word at `offset`
+    fn counter(&self, offset: usize) -> &AtomicU64 {
+        // SAFETY: the mapping is page-aligned and at least HEADER_SIZE
+        // long, so both header words are aligned and in bounds
+        unsafe { &*(self.map.as_ptr().add(offset) as *const AtomicU64) }
+    }
+}
+
+impl Drop for SharedRegion {
+    fn drop(&mut self) {
+        if self.owner {
+            // Mappings other processes hold stay valid after the unlink
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Producer side: writes payloads and hands out handles to them
+pub struct ShmWriter {
+    regions: Vec<SharedRegion>,
+    next: usize,
+}
+
+impl ShmWriter {
+    pub fn new() -> Self {
+        Self { regions: Vec::new(), next: 0 }
+    }
+
+    /// Write a payload into the next region, growing it if needed
+    pub fn write(&mut self, data: &[u8]) -> Result<ShmHandle, MultiprocessError> {
+        self.write_with(data.len(), |buf| buf.copy_from_slice(data))
+    }
+
+    /// Fill `len` bytes of the next region in place
+    pub fn write_with(&mut self, len: usize, fill: impl FnOnce(&mut [u8])) -> Result<ShmHandle, MultiprocessError> {
+        let slot = self.next;
+        self.next = (self.next + 1) % WRITER_REGIONS;
+        if self.regions.get(slot).is_none_or(|region| region.capacity() < len) {
+            let region = SharedRegion::create(len.next_power_of_two().max(MIN_REGION_SIZE))?;
+            if slot < self.regions.len() {
+                self.regions[slot] = region;
+            } else {
+                self.regions.push(region);
+            }
+        }
+        self.regions[slot].write_with(len, fill)
+    }
+
+    /// Serialize a display list into shared memory
+    pub fn write_display_list(&mut self, list: &DisplayList) -> Result<ShmHandle, MultiprocessError> {
+        let json = serde_json::to_vec(list).map_err(|_| MultiprocessError::IpcError)?;
+        self.write(&json)
+    }
+}
+
+impl Default for ShmWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Consumer side: maps the regions handles point into
+pub struct ShmReader {
+    regions: HashMap<String, SharedRegion>,
+}
+
+impl ShmReader {
+    pub fn new() -> Self {
+        Self { regions: HashMap::new() }
+    }
+
+    /// Look at a payload in place; see `SharedRegion::read_with`
+    pub fn read_with<R>(&mut self, handle: &ShmHandle, read: impl FnOnce(&[u8]) -> R) -> Result<R, MultiprocessError> {
+        if !self.regions.contains_key(&handle.path) {
+            let region = SharedRegion::open(Path::new(&handle.path))?;
+            self.regions.insert(handle.path.clone(), region);
+        }
+        self.regions[&handle.path].read_with(handle, read)
+    }
+
+    /// Copy a payload out
+    pub fn read(&mut self, handle: &ShmHandle) -> Result<Vec<u8>, MultiprocessError> {
+        self.read_with(handle, |bytes| bytes.to_vec())
+    }
+
+    /// Decode a display list written with `ShmWriter::write_display_list`
+    pub fn read_display_list(&mut self, handle: &ShmHandle) -> Result<DisplayList, MultiprocessError> {
+        self.read_with(handle, |bytes| serde_json::from_slice(bytes))?.map_err(|_| MultiprocessError::IpcError)
+    }
+
+    /// Unmap regions that are not in `paths`
+    pub fn retain(&mut self, paths: &[&str]) {
+        self.regions.retain(|path, _| paths.contains(&path.as_str()));
+    }
+}
+
+impl Default for ShmReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Directory for region files: memory-backed where the OS has one
+pub(super) fn shm_dir() -> PathBuf {
+    let dev_shm = Path::new("/dev/shm");
+    if dev_shm.is_dir() {
+        dev_shm.to_path_buf()
+    } else {
+        std::env::temp_dir()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::Color;
+    use crate::display::DisplayCommand;
+    use crate::layout::Rect;
+
+    #[test]
+    fn test_write_and_read_in_place() {
+        let mut region = SharedRegion::create(1024).unwrap();
+        let first = region.write(b"hello").unwrap();
+        assert_eq!(first.generation, 2);
+
+        let mut reader = ShmReader::new();
+        assert_eq!(reader.read(&first).unwrap(), b"hello");
+        let second = region.write_with(3, |buf| buf.copy_from_slice(b"abc")).unwrap();
+        assert_eq!(reader.read_with(&second, |bytes| bytes.len()), Ok(3));
+        // The first payload was overwritten
+        assert_eq!(reader.read(&first), Err(MultiprocessError::StaleHandle));
+        assert_eq!(region.write(&[0; 2048]), Err(MultiprocessError::OutOfBounds));
+
+        // A read overlapping a write is detected
+        let path = region.path().to_path_buf();
+        let other = SharedRegion::open(&path).unwrap();
+        let torn = other.read_with(&second, |_| region.write(b"xyz").unwrap());
+        assert_eq!(torn.err(), Some(MultiprocessError::TornRead));
+
+        drop(region);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_writer_rotates_and_grows() {
+        let mut writer = ShmWriter::new();
+        let mut reader = ShmReader::new();
+        let list = vec![DisplayCommand::SolidRect {
+            color: Color::new(0, 0, 255, 255),
+            rect: Rect { x: 1.0, y: 2.0, width: 3.0, height: 4.0 },
+        }];
+        let a = writer.write_display_list(&list).unwrap();
+        let b = writer.write(&vec![7u8; MIN_REGION_SIZE * 2]).unwrap();
+        assert_ne!(a.path, b.path);
+
+        // The previous payload is still readable while the next is written
+        assert_eq!(reader.read_display_list(&a).unwrap(), list);
+        assert_eq!(reader.read(&b).unwrap().len(), MIN_REGION_SIZE * 2);
+        let c = writer.write(b"third").unwrap();
+        assert_eq!(c.path, a.path);
+        assert_eq!(reader.read(&a), Err(MultiprocessError::StaleHandle));
+
+        reader.retain(&[c.path.as_str()]);
+        assert_eq!(reader.read(&c).unwrap(), b"third");
+    }
+}
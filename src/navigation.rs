@@ -1,8 +1,12 @@
 // Browser navigation and history management
 
+use crate::dom::Node;
+use crate::layout::{LayoutBox, Rect};
 use url::Url;
 use std::collections::VecDeque;
 
+pub use crate::bookmarks::{Bookmark, BookmarkManager};
+
 /// Browser navigation history
 #[derive(Debug, Clone)]
 pub struct NavigationHistory {
@@ -23,6 +27,8 @@ pub struct HistoryEntry {
     pub title: Option<String>,
     /// Timestamp when visited
     pub timestamp: std::time::SystemTime,
+    /// Scroll offset (x, y) when the user left this entry
+    pub scroll_position: (f32, f32),
 }
 
 impl NavigationHistory {
@@ -52,6 +58,7 @@ impl NavigationHistory {
             url,
             title: None,
             timestamp: std::time::SystemTime::now(),
+            scroll_position: (0.0, 0.0),
         };
 
         self.entries.push_back(entry);
@@ -113,6 +120,13 @@ impl NavigationHistory {
         }
     }
 
+    /// Remember the scroll offset of the current page (before leaving it)
+    pub fn save_scroll_position(&mut self, x: f32, y: f32) {
+        if let Some(entry) = self.entries.get_mut(self.current_index) {
+            entry.scroll_position = (x, y);
+        }
+    }
+
     /// Get all history entries
     pub fn entries(&self) -> &VecDeque<HistoryEntry> {
         &self.entries
@@ -145,70 +159,105 @@ impl Default for NavigationHistory {
     }
 }
 
-/// Simple bookmark manager
-#[derive(Debug, Clone)]
-pub struct BookmarkManager {
-    bookmarks: Vec<Bookmark>,
+/// Check if two URLs refer to the same document (differ at most in fragment)
+pub fn is_same_document(a: &Url, b: &Url) -> bool {
+    let mut a = a.clone();
+    let mut b = b.clone();
+    a.set_fragment(None);
+    b.set_fragment(None);
+    a == b
 }
 
-/// A single bookmark
-#[derive(Debug, Clone)]
-pub struct Bookmark {
-    pub url: Url,
-    pub title: String,
-    pub created_at: std::time::SystemTime,
+/// Find where a `#fragment` points in a laid-out page
+///
+/// Matches an element `id`, then `<a name>`. An empty fragment or `top`
+/// with no matching element scrolls to the top of the page.
+pub fn fragment_target(root: &LayoutBox, fragment: &str) -> Option<Rect> {
+    let decoded = percent_decode(fragment);
+    let found = [fragment, decoded.as_str()]
+        .iter()
+        .find_map(|name| find_anchor(root, name));
+
+    match found {
+        Some(rect) => Some(rect),
+        None if fragment.is_empty() || decoded.eq_ignore_ascii_case("top") => Some(Rect::default()),
+        None => None,
+    }
 }
 
-impl BookmarkManager {
-    /// Create a new bookmark manager
-    pub fn new() -> Self {
-        Self {
-            bookmarks: Vec::new(),
-        }
+/// Border box of the element with the given id
+pub fn element_rect_by_id(root: &LayoutBox, id: &str) -> Option<Rect> {
+    let elem = root.get_styled_node().and_then(|s| s.node.element_data());
+    if elem.and_then(|e| e.id()) == Some(id) {
+        return Some(root.dimensions.border_box());
     }
+    root.children.iter().find_map(|child| element_rect_by_id(child, id))
+}
 
-    /// Add a bookmark
-    pub fn add(&mut self, url: Url, title: String) -> bool {
-        // Check if already bookmarked
-        if self.bookmarks.iter().any(|b| b.url == url) {
-            return false;
-        }
-
-        self.bookmarks.push(Bookmark {
-            url,
-            title,
-            created_at: std::time::SystemTime::now(),
-        });
-        true
-    }
+fn find_anchor(root: &LayoutBox, name: &str) -> Option<Rect> {
+    element_rect_by_id(root, name).or_else(|| find_named_anchor(root, name))
+}
 
-    /// Remove a bookmark by URL
-    pub fn remove(&mut self, url: &Url) -> bool {
-        let len_before = self.bookmarks.len();
-        self.bookmarks.retain(|b| &b.url != url);
-        self.bookmarks.len() < len_before
+fn find_named_anchor(root: &LayoutBox, name: &str) -> Option<Rect> {
+    let elem = root.get_styled_node().and_then(|s| s.node.element_data());
+    if elem.is_some_and(|e| e.tag_name == "a" && e.get_attribute("name") == Some(name)) {
+        return Some(root.dimensions.border_box());
     }
+    root.children.iter().find_map(|child| find_named_anchor(child, name))
+}
 
-    /// Check if URL is bookmarked
-    pub fn is_bookmarked(&self, url: &Url) -> bool {
-        self.bookmarks.iter().any(|b| &b.url == url)
+/// Decode `%XX` escapes in a URL fragment
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
     }
+    String::from_utf8_lossy(&out).into_owned()
+}
 
-    /// Get all bookmarks
-    pub fn all(&self) -> &[Bookmark] {
-        &self.bookmarks
-    }
+/// Determine the base URL of a document
+///
+/// Uses the first `<base href>` element if present, resolved against the
+/// document URL; otherwise the document URL itself.
+pub fn document_base_url(dom: &Node, document_url: &Url) -> Url {
+    find_base_href(dom)
+        .and_then(|href| document_url.join(href).ok())
+        .unwrap_or_else(|| document_url.clone())
+}
 
-    /// Clear all bookmarks
-    pub fn clear(&mut self) {
-        self.bookmarks.clear();
+fn find_base_href(node: &Node) -> Option<&str> {
+    if let Some(elem) = node.element_data() {
+        if elem.tag_name == "base" {
+            if let Some(href) = elem.get_attribute("href") {
+                return Some(href);
+            }
+        }
     }
+    node.children.iter().find_map(find_base_href)
 }
 
-impl Default for BookmarkManager {
-    fn default() -> Self {
-        Self::new()
+/// Resolve a link's href against the document base URL
+///
+/// Returns `None` for hrefs that cannot be navigated to (invalid URLs and
+/// `javascript:` links).
+pub fn resolve_href(base: &Url, href: &str) -> Option<Url> {
+    let url = base.join(href.trim()).ok()?;
+    if url.scheme() == "javascript" {
+        return None;
     }
+    Some(url)
 }
 
 #[cfg(test)]
@@ -312,22 +361,85 @@ mod tests {
     }
 
     #[test]
-    fn test_bookmark_manager() {
-        let mut bookmarks = BookmarkManager::new();
-        
-        let url = test_url("/page1");
-        assert!(bookmarks.add(url.clone(), "Page 1".to_string()));
-        assert!(bookmarks.is_bookmarked(&url));
+    fn test_document_base_url() {
+        let mut attrs = std::collections::HashMap::new();
+        attrs.insert("href".to_string(), "/docs/".to_string());
+        let base = Node::element("base".to_string(), attrs, vec![]);
+        let head = Node::element("head".to_string(), Default::default(), vec![base]);
+        let dom = Node::element("html".to_string(), Default::default(), vec![head]);
+
+        let page = test_url("/index.html");
+        assert_eq!(document_base_url(&dom, &page).as_str(), "http://example.com/docs/");
+
+        let empty = Node::element("html".to_string(), Default::default(), vec![]);
+        assert_eq!(document_base_url(&empty, &page), page);
+    }
 
-        // Can't add duplicate
-        assert!(!bookmarks.add(url.clone(), "Page 1".to_string()));
+    #[test]
+    fn test_resolve_href() {
+        let base = test_url("/docs/");
+        assert_eq!(resolve_href(&base, "guide.html").unwrap().path(), "/docs/guide.html");
+        assert_eq!(resolve_href(&base, "../about").unwrap().path(), "/about");
+        assert_eq!(
+            resolve_href(&base, "https://example.org/").unwrap().as_str(),
+            "https://example.org/"
+        );
+        assert!(resolve_href(&base, "javascript:void(0)").is_none());
+    }
 
-        assert_eq!(bookmarks.all().len(), 1);
+    #[test]
+    fn test_scroll_position_per_entry() {
+        let mut history = NavigationHistory::new();
+        history.navigate_to(test_url("/page1"));
+        history.save_scroll_position(0.0, 420.0);
+        history.navigate_to(test_url("/page2"));
+        assert_eq!(history.current_entry().unwrap().scroll_position, (0.0, 0.0));
+
+        let entry = history.go_back().unwrap();
+        assert_eq!(entry.scroll_position, (0.0, 420.0));
+    }
 
-        // Remove bookmark
-        assert!(bookmarks.remove(&url));
-        assert!(!bookmarks.is_bookmarked(&url));
-        assert_eq!(bookmarks.all().len(), 0);
+    #[test]
+    fn test_same_document() {
+        assert!(is_same_document(&test_url("/a#one"), &test_url("/a#two")));
+        assert!(is_same_document(&test_url("/a"), &test_url("/a#two")));
+        assert!(!is_same_document(&test_url("/a#one"), &test_url("/b#one")));
+    }
+
+    #[test]
+    fn test_fragment_target() {
+        use crate::css::CssParser;
+        use crate::layout::{layout_tree, Dimensions};
+        use crate::style::style_tree;
+        use std::collections::HashMap;
+
+        let section = |attr: &str, value: &str| {
+            let mut attrs = HashMap::new();
+            attrs.insert(attr.to_string(), value.to_string());
+            Node::element("div".to_string(), attrs, vec![])
+        };
+        let mut anchor_attrs = HashMap::new();
+        anchor_attrs.insert("name".to_string(), "legacy".to_string());
+        let dom = Node::element(
+            "body".to_string(),
+            HashMap::new(),
+            vec![
+                section("id", "intro"),
+                section("id", "caf\u{e9}"),
+                Node::element("a".to_string(), anchor_attrs, vec![]),
+            ],
+        );
+        let css = CssParser::parse("body { display: block; } div { display: block; height: 100px; } a { display: block; height: 100px; }");
+        let styled = style_tree(&dom, &css);
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+        let layout = layout_tree(&styled, viewport);
+
+        assert_eq!(fragment_target(&layout, "intro").unwrap().y, 0.0);
+        assert_eq!(fragment_target(&layout, "caf%C3%A9").unwrap().y, 100.0);
+        assert_eq!(fragment_target(&layout, "legacy").unwrap().y, 200.0);
+        assert_eq!(fragment_target(&layout, "top").unwrap().y, 0.0);
+        assert!(fragment_target(&layout, "missing").is_none());
     }
 
     #[test]
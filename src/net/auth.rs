@@ -0,0 +1,491 @@
+// HTTP authentication: Basic and Digest challenges, and the credentials
+// a user gave for them.
+//
+// A `401 Unauthorized` response names the schemes it accepts in its
+// `WWW-Authenticate` headers. The client asks its `CredentialPrompt` for
+// a username and password, retries with an `Authorization` header, and
+// keeps what worked per origin and realm for the rest of the session, so
+// later requests below the same directory send it up front. Nothing is
+// written to disk.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use md5::{Digest, Md5};
+use url::{Position, Url};
+
+use super::Transfer;
+
+/// How a server wants credentials sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthScheme {
+    /// Username and password in the clear, base64-encoded
+    Basic,
+    /// An MD5 hash of the credentials with a server nonce (RFC 7616)
+    Digest,
+}
+
+impl AuthScheme {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthScheme::Basic => "Basic",
+            AuthScheme::Digest => "Digest",
+        }
+    }
+}
+
+/// One challenge of a `WWW-Authenticate` header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthChallenge {
+    pub scheme: AuthScheme,
+    /// Protection space the server names, shown in the prompt
+    pub realm: String,
+    /// Digest: the server's nonce, hashed into each response
+    pub nonce: String,
+    /// Digest: returned to the server unchanged
+    pub opaque: Option<String>,
+    /// Digest: the server offers `qop=auth`, which adds a client nonce
+    pub qop_auth: bool,
+    /// Digest: the credentials were right but the nonce had expired
+    pub stale: bool,
+}
+
+impl AuthChallenge {
+    /// The supported challenges of a header value, in order
+    ///
+    /// Digest challenges with an algorithm other than MD5, or a quality of
+    /// protection other than `auth`, and unknown schemes are left out.
+    pub fn parse(header: &str) -> Vec<AuthChallenge> {
+        let mut challenges = Vec::new();
+        let mut current: Option<(String, Vec<(String, String)>)> = None;
+        let mut rest = header;
+        loop {
+            rest = rest.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+            let end = rest.find(|c: char| c == '=' || c == ',' || c.is_whitespace()).unwrap_or(rest.len());
+            let (token, after) = rest.split_at(end);
+            if token.is_empty() {
+                break;
+            }
+            match after.trim_start().strip_prefix('=') {
+                // `name=value` belongs to the challenge being read
+                Some(value) => {
+                    let (value, after) = parse_param_value(value.trim_start());
+                    if let Some((_, params)) = current.as_mut() {
+                        params.push((token.to_ascii_lowercase(), value));
+                    }
+                    rest = after;
+                }
+                // A lone token starts the next challenge
+                None => {
+                    challenges.extend(current.take().and_then(|(scheme, params)| Self::from_params(&scheme, &params)));
+                    current = Some((token.to_string(), Vec::new()));
+                    rest = after;
+                }
+            }
+        }
+        challenges.extend(current.and_then(|(scheme, params)| Self::from_params(&scheme, &params)));
+        challenges
+    }
+
+    /// The challenge to answer from a response's `WWW-Authenticate` headers:
+    /// Digest if offered, as it does not send the password
+    pub fn preferred<'a>(headers: impl IntoIterator<Item = &'a str>) -> Option<AuthChallenge> {
+        let challenges: Vec<AuthChallenge> = headers.into_iter().flat_map(Self::parse).collect();
+        challenges
+            .iter()
+            .find(|challenge| challenge.scheme == AuthScheme::Digest)
+            .or_else(|| challenges.first())
+            .cloned()
+    }
+
+    fn from_params(scheme: &str, params: &[(String, String)]) -> Option<AuthChallenge> {
+        let param = |name: &str| params.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_str());
+        let realm = param("realm").unwrap_or_default().to_string();
+        if scheme.eq_ignore_ascii_case("basic") {
+            return Some(AuthChallenge {
+                scheme: AuthScheme::Basic,
+                realm,
+                nonce: String::new(),
+                opaque: None,
+                qop_auth: false,
+                stale: false,
+            });
+        }
+        if !scheme.eq_ignore_ascii_case("digest") {
+            return None;
+        }
+        if param("algorithm").is_some_and(|algorithm| !algorithm.eq_ignore_ascii_case("md5")) {
+            return None;
+        }
+        let qop_auth = match param("qop") {
+            Some(qop) if qop.split(',').any(|qop| qop.trim().eq_ignore_ascii_case("auth")) => true,
+            Some(_) => return None,
+            None => false,
+        };
+        Some(AuthChallenge {
+            scheme: AuthScheme::Digest,
+            realm,
+            nonce: param("nonce")?.to_string(),
+            opaque: param("opaque").map(str::to_string),
+            qop_auth,
+            stale: param("stale").is_some_and(|stale| stale.eq_ignore_ascii_case("true")),
+        })
+    }
+
+    /// The `Authorization` header answering the challenge for a `method`
+    /// request to `url`, e.g. `GET`
+    ///
+    /// `nonce_count` counts the requests made with this nonce, from 1.
+    pub fn authorization(&self, credentials: &Credentials, method: &str, url: &Url, nonce_count: u32) -> String {
+        match self.scheme {
+            AuthScheme::Basic => {
+                let token = BASE64.encode(format!("{}:{}", credentials.username, credentials.password));
+                format!("Basic {}", token)
+            }
+            AuthScheme::Digest => {
+                let cnonce = format!("{:016x}", rand::random::<u64>());
+                self.digest_authorization(credentials, method, url, nonce_count, &cnonce)
+            }
+        }
+    }
+
+    fn digest_authorization(
+        &self,
+        credentials: &Credentials,
+        method: &str,
+        url: &Url,
+        nonce_count: u32,
+        cnonce: &str,
+    ) -> String {
+        let uri = &url[Position::BeforePath..Position::AfterQuery];
+        let ha1 = md5_hex(format!("{}:{}:{}", credentials.username, self.realm, credentials.password).as_bytes());
+        let ha2 = md5_hex(format!("{}:{}", method, uri).as_bytes());
+        let nc = format!("{:08x}", nonce_count);
+        let mut header = format!(
+            "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\"",
+            quote(&credentials.username),
+            quote(&self.realm),
+            quote(&self.nonce),
+            quote(uri)
+        );
+        if self.qop_auth {
+            let response = md5_hex(format!("{}:{}:{}:{}:auth:{}", ha1, self.nonce, nc, cnonce, ha2).as_bytes());
+            header.push_str(&format!(", qop=auth, nc={}, cnonce=\"{}\", response=\"{}\"", nc, cnonce, response));
+        } else {
+            let response = md5_hex(format!("{}:{}:{}", ha1, self.nonce, ha2).as_bytes());
+            header.push_str(&format!(", response=\"{}\"", response));
+        }
+        if let Some(opaque) = &self.opaque {
+            header.push_str(&format!(", opaque=\"{}\"", quote(opaque)));
+        }
+        header
+    }
+}
+
+/// A parameter's value, quoted or not, and the text after it
+fn parse_param_value(text: &str) -> (String, &str) {
+    let Some(quoted) = text.strip_prefix('"') else {
+        let end = text.find(|c: char| c == ',' || c.is_whitespace()).unwrap_or(text.len());
+        return (text[..end].to_string(), &text[end..]);
+    };
+    let mut value = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return (value, &quoted[index + 1..]),
+            '\\' => value.extend(chars.next().map(|(_, escaped)| escaped)),
+            _ => value.push(c),
+        }
+    }
+    (value, "")
+}
+
+/// Escape a value for a quoted string
+fn quote(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// A username and password; the password is left out of debug output
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl Credentials {
+    pub fn new(username: &str, password: &str) -> Self {
+        Self { username: username.to_string(), password: password.to_string() }
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials").field("username", &self.username).finish_non_exhaustive()
+    }
+}
+
+/// A server asking for credentials, as a prompt shows it
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthRequest {
+    /// The URL that answered `401 Unauthorized`
+    pub url: Url,
+    pub challenge: AuthChallenge,
+    /// Credentials were sent and refused, so the last ones were wrong
+    pub retry: bool,
+}
+
+impl AuthRequest {
+    /// The request a `401 Unauthorized` response to `url` makes, if it
+    /// offers a supported scheme
+    ///
+    /// For loads that do not prompt themselves, such as the fetcher's. The
+    /// request is for the URL that answered, if redirects led elsewhere.
+    pub fn for_transfer(url: &Url, transfer: &Transfer) -> Option<Self> {
+        if transfer.status != 401 {
+            return None;
+        }
+        let challenges = transfer
+            .response_headers
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("www-authenticate"))
+            .map(|(_, value)| value.as_str());
+        let retry = transfer.request_headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("authorization"));
+        let url = transfer.url.clone().unwrap_or_else(|| url.clone());
+        Some(Self { url, challenge: AuthChallenge::preferred(challenges)?, retry })
+    }
+
+    /// Origin asking, e.g. `https://intranet.example`
+    pub fn origin(&self) -> String {
+        self.url.origin().ascii_serialization()
+    }
+
+    /// e.g. "https://intranet.example is asking for a username and password"
+    pub fn message(&self) -> String {
+        format!("{} is asking for a username and password", self.origin())
+    }
+
+    /// Are the credentials readable by anyone on the network: Basic over plain HTTP
+    pub fn is_insecure(&self) -> bool {
+        self.challenge.scheme == AuthScheme::Basic && self.url.scheme() != "https"
+    }
+}
+
+/// Hook asked for credentials when a server answers `401 Unauthorized`
+///
+/// The browser shows a prompt; an embedder may answer from its own store.
+/// `None` gives up, leaving the server's 401 response as the result.
+pub trait CredentialPrompt: Send + Sync {
+    fn credentials(&self, request: &AuthRequest) -> Option<Credentials>;
+}
+
+impl<F> CredentialPrompt for F
+where
+    F: Fn(&AuthRequest) -> Option<Credentials> + Send + Sync,
+{
+    fn credentials(&self, request: &AuthRequest) -> Option<Credentials> {
+        self(request)
+    }
+}
+
+/// Credentials that worked, per origin and realm, for the session
+#[derive(Debug, Default)]
+pub struct AuthCache {
+    /// Keyed by origin and realm
+    entries: HashMap<(String, String), AuthEntry>,
+}
+
+#[derive(Debug)]
+struct AuthEntry {
+    challenge: AuthChallenge,
+    credentials: Credentials,
+    /// Requests made with the challenge's nonce so far
+    nonce_count: u32,
+    /// Directory of the URL that asked, e.g. `/private/`; requests below
+    /// it send the credentials without waiting to be asked
+    directory: String,
+}
+
+impl AuthEntry {
+    fn authorization(&mut self, method: &str, url: &Url) -> String {
+        self.nonce_count += 1;
+        self.challenge.authorization(&self.credentials, method, url, self.nonce_count)
+    }
+}
+
+impl AuthCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep credentials given for a challenge from `url`, the URL that
+    /// answered `401 Unauthorized`
+    pub fn store(&mut self, url: &Url, challenge: AuthChallenge, credentials: Credentials) {
+        let path = url.path();
+        let directory = path[..path.rfind('/').map_or(0, |slash| slash + 1)].to_string();
+        let key = (origin_key(url), challenge.realm.clone());
+        self.entries.insert(key, AuthEntry { challenge, credentials, nonce_count: 0, directory });
+    }
+
+    /// The `Authorization` header to send up front with a `method` request
+    /// to `url`, if credentials were given for a directory it is in
+    ///
+    /// With credentials for several realms, the deepest directory wins.
+    pub fn authorization(&mut self, method: &str, url: &Url) -> Option<String> {
+        let origin = origin_key(url);
+        let entry = self
+            .entries
+            .iter_mut()
+            .filter(|((entry_origin, _), entry)| *entry_origin == origin && url.path().starts_with(&entry.directory))
+            .map(|(_, entry)| entry)
+            .max_by_key(|entry| entry.directory.len())?;
+        Some(entry.authorization(method, url))
+    }
+
+    /// The `Authorization` header answering a challenge for `realm` from
+    /// `url`, if the origin has credentials for the realm
+    pub fn authorization_for(&mut self, method: &str, url: &Url, realm: &str) -> Option<String> {
+        let entry = self.entries.get_mut(&(origin_key(url), realm.to_string()))?;
+        Some(entry.authorization(method, url))
+    }
+
+    /// Take the new nonce of a `stale` challenge from `url`; false if its
+    /// origin has no credentials for the realm to retry with
+    pub fn renew(&mut self, url: &Url, challenge: &AuthChallenge) -> bool {
+        match self.entries.get_mut(&(origin_key(url), challenge.realm.clone())) {
+            Some(entry) => {
+                entry.challenge = challenge.clone();
+                entry.nonce_count = 0;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Does the origin of `url` have credentials for `realm`
+    pub fn contains(&self, url: &Url, realm: &str) -> bool {
+        self.entries.contains_key(&(origin_key(url), realm.to_string()))
+    }
+
+    /// Forget the credentials of `url`'s origin for `realm`, e.g. after
+    /// they were refused
+    pub fn forget(&mut self, url: &Url, realm: &str) {
+        self.entries.remove(&(origin_key(url), realm.to_string()));
+    }
+
+    /// Forget every credential
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+fn origin_key(url: &Url) -> String {
+    url.origin().ascii_serialization()
+}
+
+/// MD5 of `data` in lowercase hex, which Digest authentication hashes with
+fn md5_hex(data: &[u8]) -> String {
+    Md5::digest(data).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_challenges() {
+        let challenges = AuthChallenge::parse(
+            "Basic realm=\"Admin, \\\"staff\\\"\", Digest realm=\"api\", qop=\"auth,auth-int\", \
+             nonce=\"abc\", opaque=xyz, stale=TRUE, Bearer realm=\"x\"",
+        );
+        assert_eq!(challenges.len(), 2);
+        assert_eq!(challenges[0].scheme, AuthScheme::Basic);
+        assert_eq!(challenges[0].realm, "Admin, \"staff\"");
+        assert_eq!(challenges[1].scheme, AuthScheme::Digest);
+        assert_eq!((challenges[1].nonce.as_str(), challenges[1].opaque.as_deref()), ("abc", Some("xyz")));
+        assert!(challenges[1].qop_auth && challenges[1].stale);
+
+        assert!(AuthChallenge::parse("Digest realm=\"a\", nonce=\"n\", algorithm=SHA-256").is_empty());
+        assert!(AuthChallenge::parse("Digest realm=\"a\"").is_empty());
+        let preferred = AuthChallenge::preferred(["Basic realm=\"a\"", "Digest realm=\"b\", nonce=\"n\""]).unwrap();
+        assert_eq!(preferred.scheme, AuthScheme::Digest);
+    }
+
+    #[test]
+    fn test_authorization_headers() {
+        assert_eq!(md5_hex(b""), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(md5_hex(b"The quick brown fox jumps over the lazy dog"), "9e107d9d372bb6826bd81d3542a419d6");
+
+        let basic = AuthChallenge::preferred(["Basic realm=\"a\""]).unwrap();
+        let url = Url::parse("http://example.com/dir/index.html?x=1").unwrap();
+        let credentials = Credentials::new("Aladdin", "open sesame");
+        assert_eq!(basic.authorization(&credentials, "GET", &url, 1), "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==");
+
+        // The example of RFC 2617, section 3.5
+        let digest = AuthChallenge::preferred([
+            "Digest realm=\"testrealm@host.com\", qop=\"auth,auth-int\", \
+             nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", opaque=\"5ccc069c403ebaf9f0171e9517f40e41\"",
+        ])
+        .unwrap();
+        let url = Url::parse("http://www.nowhere.org/dir/index.html").unwrap();
+        let credentials = Credentials::new("Mufasa", "Circle Of Life");
+        let header = digest.digest_authorization(&credentials, "GET", &url, 1, "0a4f113b");
+        assert!(header.starts_with("Digest username=\"Mufasa\", realm=\"testrealm@host.com\""));
+        assert!(header.contains("uri=\"/dir/index.html\", qop=auth, nc=00000001, cnonce=\"0a4f113b\""));
+        assert!(header.contains("response=\"6629fae49393a05397450978507c4ef1\""));
+        assert!(header.ends_with("opaque=\"5ccc069c403ebaf9f0171e9517f40e41\""));
+        // The method is hashed in
+        let header = digest.digest_authorization(&credentials, "POST", &url, 1, "0a4f113b");
+        assert!(header.contains("response=\"440c5a7b9ed304fecd2ddd39c9c7b726\""));
+    }
+
+    #[test]
+    fn test_cache_per_origin_and_realm() {
+        let mut cache = AuthCache::new();
+        let get = |cache: &mut AuthCache, url: &str| cache.authorization("GET", &Url::parse(url).unwrap());
+        let url = Url::parse("https://a.test/private/page").unwrap();
+        let challenge = AuthChallenge::preferred(["Basic realm=\"a\""]).unwrap();
+        cache.store(&url, challenge.clone(), Credentials::new("u", "p"));
+
+        assert!(get(&mut cache, "https://a.test/private/other").is_some());
+        assert_eq!(get(&mut cache, "https://a.test/other"), None);
+        assert_eq!(get(&mut cache, "http://a.test/private/page"), None);
+
+        // Another realm of the origin keeps its own credentials
+        let admin = Url::parse("https://a.test/private/admin/").unwrap();
+        let admin_realm = AuthChallenge { realm: "b".to_string(), ..challenge.clone() };
+        cache.store(&admin, admin_realm, Credentials::new("root", "q"));
+        assert_eq!(get(&mut cache, "https://a.test/private/admin/users"), Some("Basic cm9vdDpx".to_string()));
+        assert_eq!(get(&mut cache, "https://a.test/private/page"), Some("Basic dTpw".to_string()));
+        assert_eq!(cache.authorization_for("GET", &url, "b"), Some("Basic cm9vdDpx".to_string()));
+        assert_eq!(cache.authorization_for("GET", &url, "c"), None);
+
+        assert!(cache.renew(&url, &challenge));
+        assert!(!cache.renew(&url, &AuthChallenge { realm: "c".to_string(), ..challenge }));
+        cache.forget(&url, "a");
+        assert!(!cache.contains(&url, "a") && cache.contains(&url, "b"));
+        assert!(format!("{:?}", Credentials::new("u", "secret")).find("secret").is_none());
+    }
+
+    #[test]
+    fn test_request_for_transfer() {
+        let url = Url::parse("https://a.test/").unwrap();
+        let mut transfer = Transfer {
+            status: 401,
+            response_headers: vec![("www-authenticate".to_string(), "Basic realm=\"a\"".to_string())],
+            ..Transfer::default()
+        };
+        let request = AuthRequest::for_transfer(&url, &transfer).unwrap();
+        assert_eq!((request.challenge.realm.as_str(), request.retry), ("a", false));
+        assert_eq!(request.message(), "https://a.test is asking for a username and password");
+
+        transfer.request_headers.push(("authorization".to_string(), "Basic eDp5".to_string()));
+        assert!(AuthRequest::for_transfer(&url, &transfer).unwrap().retry);
+        // Asked for by where a redirect led
+        transfer.url = Some(Url::parse("https://login.a.test/").unwrap());
+        assert_eq!(AuthRequest::for_transfer(&url, &transfer).unwrap().origin(), "https://login.a.test");
+        transfer.status = 403;
+        assert_eq!(AuthRequest::for_transfer(&url, &transfer), None);
+    }
+}
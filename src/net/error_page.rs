@@ -0,0 +1,170 @@
+// Error pages: what a tab shows when its document cannot be loaded.
+//
+// A failed navigation leaves the tab at the URL it was going to, showing
+// an internal page that says what went wrong, in words and as the error
+// itself, with a link to try again. Servers answering 4xx or 5xx with no
+// body get one too, rather than a blank page.
+
+use url::Url;
+
+use super::NetError;
+
+/// Why a document could not be shown
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadFailure {
+    /// The host name did not resolve
+    NameNotResolved,
+    /// The server took too long to answer
+    TimedOut,
+    /// The connection could not be made or was dropped
+    ConnectionFailed,
+    /// The server's certificate could not be verified
+    Certificate,
+    /// The server answered with an error status and nothing to show
+    Http(u16),
+    /// Anything else, such as a document that is not text
+    Other,
+}
+
+/// An internal page explaining a failed load
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorPage {
+    /// The URL that failed, which the tab stays at
+    pub url: Url,
+    pub failure: LoadFailure,
+    /// The error as reported, for the details section
+    pub details: String,
+}
+
+impl ErrorPage {
+    /// The page for a network error; none for a load the user stopped
+    pub fn for_error(url: &Url, error: &NetError) -> Option<Self> {
+        let failure = match error {
+            NetError::Cancelled => return None,
+            NetError::NameNotResolved(_) => LoadFailure::NameNotResolved,
+            NetError::Timeout => LoadFailure::TimedOut,
+            NetError::NetworkError(_) => LoadFailure::ConnectionFailed,
+            NetError::Certificate(_) => LoadFailure::Certificate,
+            _ => LoadFailure::Other,
+        };
+        Some(Self { url: url.clone(), failure, details: error.to_string() })
+    }
+
+    /// The page for a response with an error status and an empty body
+    ///
+    /// A server that sends its own error page gets it shown instead.
+    pub fn for_response(url: &Url, status: u16, body: &[u8]) -> Option<Self> {
+        if status < 400 || !body.iter().all(u8::is_ascii_whitespace) {
+            return None;
+        }
+        let details = format!("HTTP {} {}", status, reason_phrase(status));
+        Some(Self { url: url.clone(), failure: LoadFailure::Http(status), details: details.trim_end().to_string() })
+    }
+
+    /// Heading and tab title
+    pub fn title(&self) -> &'static str {
+        match self.failure {
+            LoadFailure::NameNotResolved | LoadFailure::TimedOut | LoadFailure::ConnectionFailed => {
+                "This site can't be reached"
+            }
+            LoadFailure::Certificate => "Your connection is not private",
+            LoadFailure::Http(_) | LoadFailure::Other => "This page isn't working",
+        }
+    }
+
+    /// What went wrong, in a sentence naming the host
+    pub fn description(&self) -> String {
+        let host = self.url.host_str().unwrap_or(self.url.as_str());
+        match self.failure {
+            LoadFailure::NameNotResolved => format!("{}'s server address could not be found.", host),
+            LoadFailure::TimedOut => format!("{} took too long to respond.", host),
+            LoadFailure::ConnectionFailed => format!("The connection to {} failed.", host),
+            LoadFailure::Certificate => {
+                format!("The certificate {} presented could not be verified, so the connection may be intercepted.", host)
+            }
+            LoadFailure::Http(status) if status >= 500 => format!("{} is currently unable to handle this request.", host),
+            LoadFailure::Http(404) => format!("No page was found at this address on {}.", host),
+            LoadFailure::Http(_) => format!("{} refused to show this page.", host),
+            LoadFailure::Other => format!("The page from {} could not be shown.", host),
+        }
+    }
+
+    /// The page's document, with a link that loads the URL again
+    pub fn to_html(&self) -> String {
+        format!(
+            "<html><head><title>{title}</title></head><body>\
+             <div class=\"content\" id=\"error-page\">\
+             <h1>{title}</h1>\
+             <p>{description}</p>\
+             <p>{url}</p>\
+             <div class=\"feature-box\"><p><a id=\"retry\" href=\"{href}\">Try again</a></p></div>\
+             <h2>Details</h2>\
+             <p id=\"error-details\">{details}</p>\
+             </div></body></html>",
+            title = self.title(),
+            description = escape(&self.description()),
+            url = escape(self.url.as_str()),
+            href = escape(self.url.as_str()).replace('"', "&quot;"),
+            details = escape(&self.details),
+        )
+    }
+}
+
+/// The standard reason phrase of a status code, or "" for unusual ones
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        410 => "Gone",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => "",
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_pages() {
+        let url = Url::parse("https://example.com/a?b=<c>").unwrap();
+        assert_eq!(ErrorPage::for_error(&url, &NetError::Cancelled), None);
+
+        let page = ErrorPage::for_error(&url, &NetError::NameNotResolved("example.com".to_string())).unwrap();
+        assert_eq!(page.failure, LoadFailure::NameNotResolved);
+        assert_eq!(page.title(), "This site can't be reached");
+        let html = page.to_html();
+        assert!(html.contains("example.com's server address could not be found."));
+        assert!(html.contains("href=\"https://example.com/a?b=%3Cc%3E\""));
+
+        let timeout = ErrorPage::for_error(&url, &NetError::Timeout).unwrap();
+        assert_eq!(timeout.failure, LoadFailure::TimedOut);
+        assert_eq!(timeout.details, "Request timed out");
+    }
+
+    #[test]
+    fn test_error_status_without_body() {
+        let url = Url::parse("https://example.com/missing").unwrap();
+        assert_eq!(ErrorPage::for_response(&url, 200, b""), None);
+        assert_eq!(ErrorPage::for_response(&url, 404, b"<h1>Not here</h1>"), None);
+
+        let page = ErrorPage::for_response(&url, 404, b" \n").unwrap();
+        assert_eq!(page.failure, LoadFailure::Http(404));
+        assert_eq!(page.details, "HTTP 404 Not Found");
+        assert_eq!(page.title(), "This page isn't working");
+        assert_eq!(ErrorPage::for_response(&url, 599, b"").unwrap().details, "HTTP 599");
+    }
+}
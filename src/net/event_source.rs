@@ -0,0 +1,446 @@
+// Server-Sent Events (EventSource)
+//
+// A `text/event-stream` response is parsed as it arrives into events made
+// of `event`, `data`, `id` and `retry` fields. When the stream ends or the
+// connection drops, the source reconnects after the reconnection time,
+// sending the last event ID so the server can resume where it left off.
+
+use std::io::Read;
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+use url::Url;
+
+use super::{CancellationToken, HttpClient, NetError, RequestOptions};
+
+/// Reconnection delay until the server sends a `retry` field
+pub const DEFAULT_RECONNECTION_TIME: Duration = Duration::from_secs(3);
+
+/// UTF-8 byte order mark, skipped at the start of a stream
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// How long one connection may stay open before it is re-established
+const STREAM_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// One event dispatched from an event stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerSentEvent {
+    /// Event type, `message` unless the stream named one
+    pub event_type: String,
+    pub data: String,
+    /// Last event ID at the time of dispatch (`MessageEvent.lastEventId`)
+    pub last_event_id: String,
+}
+
+/// Incremental parser for `text/event-stream` bodies
+#[derive(Debug, Clone, Default)]
+pub struct EventStreamParser {
+    /// Bytes of the line being received
+    line: Vec<u8>,
+    /// The previous chunk ended in CR, so a leading LF is part of that line break
+    after_cr: bool,
+    /// Whether the start of the stream (and a possible BOM) was seen
+    started: bool,
+    /// First bytes of the stream, while they may still be a BOM
+    start: Vec<u8>,
+    data: String,
+    event_type: String,
+    last_event_id: String,
+    /// Reconnection time from a `retry` field, not yet taken
+    retry: Option<Duration>,
+}
+
+impl EventStreamParser {
+    /// Create a parser at the start of a stream
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse a chunk of the stream, returning the events it completes
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<ServerSentEvent> {
+        let start;
+        let bytes = if self.started {
+            bytes
+        } else {
+            // A UTF-8 BOM is only skipped at the very start, and may be split
+            self.start.extend_from_slice(bytes);
+            if self.start.len() < 3 && BOM.starts_with(&self.start) {
+                return Vec::new();
+            }
+            self.started = true;
+            start = std::mem::take(&mut self.start);
+            start.strip_prefix(BOM).unwrap_or(&start)
+        };
+
+        let mut events = Vec::new();
+        for &byte in bytes {
+            let after_cr = std::mem::replace(&mut self.after_cr, false);
+            match byte {
+                b'\n' if after_cr => {}
+                b'\n' | b'\r' => {
+                    self.after_cr = byte == b'\r';
+                    let line = std::mem::take(&mut self.line);
+                    if let Some(event) = self.process_line(&String::from_utf8_lossy(&line)) {
+                        events.push(event);
+                    }
+                }
+                _ => self.line.push(byte),
+            }
+        }
+        events
+    }
+
+    /// Apply one line, dispatching an event on a blank line
+    fn process_line(&mut self, line: &str) -> Option<ServerSentEvent> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            // Comment, often sent as a keepalive
+            return None;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event_type = value.to_string(),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" if !value.contains('\0') => self.last_event_id = value.to_string(),
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                if let Ok(ms) = value.parse() {
+                    self.retry = Some(Duration::from_millis(ms));
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// End of an event: build it from the buffered fields
+    fn dispatch(&mut self) -> Option<ServerSentEvent> {
+        let event_type = std::mem::take(&mut self.event_type);
+        if self.data.is_empty() {
+            return None;
+        }
+        let mut data = std::mem::take(&mut self.data);
+        data.pop();
+
+        Some(ServerSentEvent {
+            event_type: if event_type.is_empty() { "message".to_string() } else { event_type },
+            data,
+            last_event_id: self.last_event_id.clone(),
+        })
+    }
+
+    /// ID sent with a reconnection as `Last-Event-ID`
+    pub fn last_event_id(&self) -> &str {
+        &self.last_event_id
+    }
+
+    /// Take the reconnection time set by a `retry` field since the last call
+    pub fn take_retry(&mut self) -> Option<Duration> {
+        self.retry.take()
+    }
+
+    /// Start over for a new connection; the last event ID survives
+    ///
+    /// An event that was only partly received is dropped.
+    pub fn restart(&mut self) {
+        *self = Self {
+            last_event_id: std::mem::take(&mut self.last_event_id),
+            ..Self::default()
+        };
+    }
+}
+
+/// `EventSource.readyState`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventSourceState {
+    Connecting = 0,
+    Open = 1,
+    Closed = 2,
+}
+
+/// What an event source reports to the page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventSourceEvent {
+    /// The stream was (re)established
+    Open,
+    /// An event arrived
+    Message(ServerSentEvent),
+    /// The connection was lost (the source is reconnecting) or failed for
+    /// good (the source is `Closed`)
+    Error(EventSourceState),
+}
+
+/// An `EventSource` connection to one URL
+pub struct EventSource {
+    url: Url,
+    state: EventSourceState,
+    parser: EventStreamParser,
+    reconnection_time: Duration,
+    /// Page the source was created by, for third-party cookie checks
+    first_party: Option<Url>,
+}
+
+impl EventSource {
+    /// Create a source for `url`; nothing is sent until it runs
+    pub fn new(url: Url) -> Self {
+        Self {
+            url,
+            state: EventSourceState::Connecting,
+            parser: EventStreamParser::new(),
+            reconnection_time: DEFAULT_RECONNECTION_TIME,
+            first_party: None,
+        }
+    }
+
+    /// Set the page the source belongs to
+    pub fn set_first_party(&mut self, page: Url) {
+        self.first_party = Some(page);
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn state(&self) -> EventSourceState {
+        self.state
+    }
+
+    /// ID of the last event received with one
+    pub fn last_event_id(&self) -> &str {
+        self.parser.last_event_id()
+    }
+
+    /// Delay before reconnecting after the connection drops
+    pub fn reconnection_time(&self) -> Duration {
+        self.reconnection_time
+    }
+
+    /// Headers for the next connection attempt
+    pub fn request_headers(&self) -> Vec<(&'static str, String)> {
+        let mut headers = vec![
+            ("Accept", "text/event-stream".to_string()),
+            ("Cache-Control", "no-cache".to_string()),
+        ];
+        if !self.last_event_id().is_empty() {
+            headers.push(("Last-Event-ID", self.last_event_id().to_string()));
+        }
+        headers
+    }
+
+    /// A response arrived; only a 200 `text/event-stream` opens the source
+    ///
+    /// Anything else fails the source for good, with no reconnection.
+    pub fn connected(&mut self, status: u16, content_type: &str) -> EventSourceEvent {
+        let mime = content_type.split(';').next().unwrap_or("").trim();
+        if status == 200 && mime.eq_ignore_ascii_case("text/event-stream") {
+            self.parser.restart();
+            self.state = EventSourceState::Open;
+            EventSourceEvent::Open
+        } else {
+            self.state = EventSourceState::Closed;
+            EventSourceEvent::Error(EventSourceState::Closed)
+        }
+    }
+
+    /// Parse body bytes of the open stream
+    pub fn receive(&mut self, bytes: &[u8]) -> Vec<EventSourceEvent> {
+        let events = self.parser.feed(bytes);
+        if let Some(retry) = self.parser.take_retry() {
+            self.reconnection_time = retry;
+        }
+        events.into_iter().map(EventSourceEvent::Message).collect()
+    }
+
+    /// The stream ended or the connection failed
+    ///
+    /// Returns how long to wait before reconnecting, or `None` if the
+    /// source was closed.
+    pub fn disconnected(&mut self) -> Option<Duration> {
+        if self.state == EventSourceState::Closed {
+            return None;
+        }
+        self.state = EventSourceState::Connecting;
+        Some(self.reconnection_time)
+    }
+
+    /// Stop the source; it will not reconnect
+    pub fn close(&mut self) {
+        self.state = EventSourceState::Closed;
+    }
+
+    /// Connect, stream events to `emit` and reconnect until closed or cancelled
+    ///
+    /// Blocks the calling thread; cancelling the token stops the source at
+    /// the next chunk or reconnection.
+    pub fn run(&mut self, client: &HttpClient, cancel: &CancellationToken, emit: &mut dyn FnMut(EventSourceEvent)) {
+        let options = RequestOptions {
+            cancel: Some(cancel.clone()),
+            first_party: self.first_party.clone(),
+            ..RequestOptions::default()
+        };
+
+        while !cancel.is_cancelled() && self.state != EventSourceState::Closed {
+            match client.fetch_stream(&self.url, &self.request_headers(), &options, STREAM_TIMEOUT) {
+                Ok(mut response) => {
+                    let event = self.connected(response.status, &response.content_type);
+                    emit(event);
+                    let mut chunk = [0u8; 8 * 1024];
+                    while self.state == EventSourceState::Open && !cancel.is_cancelled() {
+                        match response.read(&mut chunk) {
+                            Ok(0) | Err(_) => break,
+                            Ok(n) => self.receive(&chunk[..n]).into_iter().for_each(&mut *emit),
+                        }
+                    }
+                }
+                Err(NetError::Cancelled) => break,
+                Err(_) => {}
+            }
+            if cancel.is_cancelled() {
+                break;
+            }
+
+            let Some(delay) = self.disconnected() else {
+                break;
+            };
+            emit(EventSourceEvent::Error(EventSourceState::Connecting));
+            wait_unless_cancelled(delay, cancel);
+        }
+        self.close();
+    }
+
+    /// Run the source on its own thread
+    pub fn spawn(mut self, client: HttpClient) -> EventSourceHandle {
+        let (sender, events) = mpsc::channel();
+        let cancel = CancellationToken::new();
+        let token = cancel.clone();
+        std::thread::spawn(move || {
+            self.run(&client, &token, &mut |event| {
+                let _ = sender.send(event);
+            });
+        });
+        EventSourceHandle { events, cancel }
+    }
+}
+
+/// Sleep for `delay`, waking early if `cancel` fires
+fn wait_unless_cancelled(delay: Duration, cancel: &CancellationToken) {
+    let step = Duration::from_millis(50);
+    let mut waited = Duration::ZERO;
+    while waited < delay && !cancel.is_cancelled() {
+        std::thread::sleep(step.min(delay - waited));
+        waited += step;
+    }
+}
+
+/// An event source running on a background thread
+pub struct EventSourceHandle {
+    events: Receiver<EventSourceEvent>,
+    cancel: CancellationToken,
+}
+
+impl EventSourceHandle {
+    /// Events received since the last call
+    pub fn take_events(&self) -> Vec<EventSourceEvent> {
+        self.events.try_iter().collect()
+    }
+
+    /// Stop the source (`EventSource.close()`)
+    pub fn close(&self) {
+        self.cancel.cancel();
+    }
+}
+
+impl Drop for EventSourceHandle {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(data: &str, last_event_id: &str) -> ServerSentEvent {
+        ServerSentEvent {
+            event_type: "message".to_string(),
+            data: data.to_string(),
+            last_event_id: last_event_id.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_fields() {
+        let mut parser = EventStreamParser::new();
+        let events = parser.feed(
+            b": keepalive\n\ndata: first\ndata:second\n\nevent: update\ndata\nid: 7\nretry: 1500\nbogus: x\n\n",
+        );
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0], message("first\nsecond", ""));
+        assert_eq!(events[1].event_type, "update");
+        assert_eq!(events[1].data, "");
+        assert_eq!(events[1].last_event_id, "7");
+        assert_eq!(parser.take_retry(), Some(Duration::from_millis(1500)));
+        assert_eq!(parser.take_retry(), None);
+
+        // The event type resets, the last event ID does not
+        assert_eq!(parser.feed(b"data: x\n\n"), vec![message("x", "7")]);
+        // IDs containing NUL and non-numeric retries are ignored
+        parser.feed(b"id: a\0b\nretry: 10s\ndata: y\n\n");
+        assert_eq!(parser.last_event_id(), "7");
+        assert_eq!(parser.take_retry(), None);
+        // An event without data is not dispatched but its ID sticks
+        assert!(parser.feed(b"id\n\n").is_empty());
+        assert_eq!(parser.last_event_id(), "");
+    }
+
+    #[test]
+    fn test_parse_chunked_line_endings() {
+        let mut parser = EventStreamParser::new();
+        // BOM split across chunks, then CRLF split between chunks
+        assert!(parser.feed(b"\xEF\xBB").is_empty());
+        assert!(parser.feed(b"\xBFdata: a\r").is_empty());
+        assert!(parser.feed(b"\n\r").len() == 1);
+        assert!(parser.feed(b"\ndata: b\r\rdata: \xC3").len() == 1);
+        assert_eq!(parser.feed(b"\xA9\n\n"), vec![message("\u{e9}", "")]);
+
+        // A partial event is dropped on reconnection
+        parser.feed(b"id: 3\n\ndata: half");
+        parser.restart();
+        assert_eq!(parser.feed(b"\n\ndata: whole\n\n"), vec![message("whole", "3")]);
+    }
+
+    #[test]
+    fn test_event_source_reconnection() {
+        let mut source = EventSource::new(Url::parse("https://example.com/events").unwrap());
+        assert_eq!(source.state(), EventSourceState::Connecting);
+        assert!(source.request_headers().iter().all(|(name, _)| *name != "Last-Event-ID"));
+
+        assert_eq!(source.connected(200, "text/event-stream; charset=utf-8"), EventSourceEvent::Open);
+        let events = source.receive(b"retry: 250\nid: 42\ndata: hello\n\ndata: cut");
+        assert_eq!(events, vec![EventSourceEvent::Message(message("hello", "42"))]);
+        assert_eq!(source.reconnection_time(), Duration::from_millis(250));
+
+        // The stream ends; the source reconnects resuming from event 42
+        assert_eq!(source.disconnected(), Some(Duration::from_millis(250)));
+        assert_eq!(source.state(), EventSourceState::Connecting);
+        assert!(source.request_headers().contains(&("Last-Event-ID", "42".to_string())));
+        assert_eq!(source.connected(200, "text/event-stream"), EventSourceEvent::Open);
+        assert!(source.receive(b"\n\n").is_empty());
+
+        // A wrong content type fails the source for good
+        source.disconnected();
+        assert_eq!(source.connected(200, "text/html"), EventSourceEvent::Error(EventSourceState::Closed));
+        assert_eq!(source.disconnected(), None);
+
+        let mut source = EventSource::new(Url::parse("https://example.com/events").unwrap());
+        assert_eq!(source.connected(204, "text/event-stream"), EventSourceEvent::Error(EventSourceState::Closed));
+    }
+}
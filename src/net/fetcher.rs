@@ -0,0 +1,513 @@
+// Asynchronous resource fetching
+//
+// `ResourceFetcher` sends requests from a small tokio runtime, so the
+// window never waits on the network. Requests queue by priority - the
+// document first, then what blocks rendering (stylesheets and fonts), then
+// scripts, and images last - and at most `MAX_CONCURRENT` run at once.
+// Each reports its progress over a channel the window's event loop drains
+// with `poll`: the response head, the body chunk by chunk as it arrives,
+// then the whole resource, cached as the blocking `ResourceLoader` caches.
+
+use super::resource_loader::{stale_if_unreachable, store_response, Lookup, ResourceCache};
+use super::{
+    cookie_header, header_list, millis, record_certificate, request_error, request_headers, store_cookies, AuthCache, AuthChallenge, CacheMode,
+    CachedResource, CancellationToken, CertificateStore, CookiePolicy, InterceptedRequest, NetError, PartitionKey, RequestInterceptor, RequestOptions,
+    RequestTiming, ResourceType, Response, Transfer, USER_AGENT,
+};
+use crate::storage::CookieJar;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// Requests in flight at once; the rest wait their turn
+const MAX_CONCURRENT: usize = 6;
+
+/// Identifies a fetch in the events it reports
+pub type FetchId = u64;
+
+/// How soon a request is sent while others wait
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FetchPriority {
+    Low,
+    Medium,
+    High,
+    Highest,
+}
+
+impl FetchPriority {
+    /// Priority for a type of resource: what holds up the first paint goes first
+    pub fn for_type(resource_type: ResourceType) -> Self {
+        match resource_type {
+            ResourceType::Html => FetchPriority::Highest,
+            ResourceType::Css | ResourceType::Font => FetchPriority::High,
+            ResourceType::Other => FetchPriority::Medium,
+            ResourceType::Image => FetchPriority::Low,
+        }
+    }
+}
+
+/// A resource to fetch
+#[derive(Debug, Clone)]
+pub struct FetchRequest {
+    pub url: Url,
+    pub resource_type: ResourceType,
+    pub priority: FetchPriority,
+    pub cache_mode: CacheMode,
+    /// Page the request is made for; `None` for the page itself
+    pub first_party: Option<Url>,
+    pub cancel: Option<CancellationToken>,
+}
+
+impl FetchRequest {
+    /// Request a resource of a type, at the type's priority
+    pub fn new(url: Url, resource_type: ResourceType) -> Self {
+        Self {
+            url,
+            resource_type,
+            priority: FetchPriority::for_type(resource_type),
+            cache_mode: CacheMode::Default,
+            first_party: None,
+            cancel: None,
+        }
+    }
+
+    pub fn with_priority(mut self, priority: FetchPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn with_cache_mode(mut self, cache_mode: CacheMode) -> Self {
+        self.cache_mode = cache_mode;
+        self
+    }
+
+    pub fn with_first_party(mut self, page: Url) -> Self {
+        self.first_party = Some(page);
+        self
+    }
+
+    pub fn with_cancel(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+}
+
+/// Progress of a fetch: `Response`, any `Data`, then `Finished` or `Failed`
+#[derive(Debug)]
+pub enum FetchEvent {
+    /// The response head arrived; resources served from the cache skip it
+    Response { id: FetchId, url: Url, status: u16, content_type: String },
+    /// A chunk of the body, as it arrived
+    Data { id: FetchId, chunk: Vec<u8> },
+    /// The whole resource; there is no transfer when it came from the
+    /// cache without a request
+    Finished { id: FetchId, resource: Box<CachedResource>, transfer: Option<Transfer> },
+    Failed { id: FetchId, error: NetError },
+}
+
+impl FetchEvent {
+    /// The fetch the event is about
+    pub fn id(&self) -> FetchId {
+        match self {
+            FetchEvent::Response { id, .. }
+            | FetchEvent::Data { id, .. }
+            | FetchEvent::Finished { id, .. }
+            | FetchEvent::Failed { id, .. } => *id,
+        }
+    }
+}
+
+/// A request waiting to be sent
+struct Queued {
+    id: FetchId,
+    request: FetchRequest,
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    /// Higher priority first, then the earlier request
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.request.priority.cmp(&other.request.priority).then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+/// Requests waiting and how many are in flight
+#[derive(Default)]
+struct Scheduler {
+    waiting: BinaryHeap<Queued>,
+    running: usize,
+    max_running: usize,
+}
+
+/// What the fetcher's tasks share with it
+struct Shared {
+    client: reqwest::Client,
+    /// Client accepting invalid certificates, for hosts the user trusted anyway
+    insecure_client: reqwest::Client,
+    cache: Arc<Mutex<ResourceCache>>,
+    cookies: Arc<Mutex<CookieJar>>,
+    certificates: Arc<Mutex<CertificateStore>>,
+    /// Credentials given for page loads, sent up front; the fetcher never prompts
+    auth: Arc<Mutex<AuthCache>>,
+    cookie_policy: Mutex<CookiePolicy>,
+    interceptor: Mutex<Option<Arc<dyn RequestInterceptor>>>,
+    scheduler: Mutex<Scheduler>,
+    runtime: tokio::runtime::Handle,
+    events: Sender<FetchEvent>,
+}
+
+impl Shared {
+    /// Send waiting requests, most urgent first, while there is room
+    fn start_waiting(self: &Arc<Self>) {
+        let mut scheduler = self.scheduler.lock().unwrap();
+        while scheduler.running < scheduler.max_running {
+            let Some(Queued { id, request }) = scheduler.waiting.pop() else {
+                break;
+            };
+            scheduler.running += 1;
+            let shared = self.clone();
+            self.runtime.spawn(async move {
+                let event = match shared.fetch(id, &request).await {
+                    Ok((resource, transfer)) => FetchEvent::Finished { id, resource: Box::new(resource), transfer },
+                    Err(error) => FetchEvent::Failed { id, error },
+                };
+                // Freed before reporting, so a finished fetcher is idle when polled
+                shared.scheduler.lock().unwrap().running -= 1;
+                let _ = shared.events.send(event);
+                shared.start_waiting();
+            });
+        }
+    }
+
+    /// Fetch a resource through the cache, as `ResourceLoader::load_traced` does
+    async fn fetch(&self, id: FetchId, request: &FetchRequest) -> Result<(CachedResource, Option<Transfer>), NetError> {
+        let cancel = request.cancel.clone().unwrap_or_default();
+        cancel.check()?;
+        let url = &request.url;
+
+        // Interceptors may wait on other threads, so they run off the runtime's workers
+        let interceptor = self.interceptor.lock().unwrap().clone();
+        if let Some(interceptor) = interceptor {
+            let (url, resource_type) = (url.clone(), request.resource_type);
+            let first_party = request.first_party.clone().unwrap_or_else(|| url.clone());
+            let blocked = tokio::task::spawn_blocking(move || {
+                interceptor.should_block(&InterceptedRequest { url: &url, first_party: &first_party, resource_type })
+            });
+            if blocked.await.unwrap_or(false) {
+                return Err(NetError::Blocked(request.url.to_string()));
+            }
+        }
+
+        let partition = PartitionKey::for_request(url, request.first_party.as_ref());
+        let cached = match self.cache.lock().unwrap().lookup(url, partition.as_ref(), request.cache_mode) {
+            Lookup::Fresh(resource) => return Ok((resource, None)),
+            Lookup::Validate(cached) => cached,
+        };
+
+        let options = RequestOptions {
+            cache_mode: request.cache_mode,
+            if_none_match: cached.as_ref().and_then(|c| c.etag.clone()),
+            if_modified_since: cached.as_ref().and_then(|c| c.last_modified.clone()),
+            ..RequestOptions::default()
+        };
+        let use_cookies = self.cookie_policy.lock().unwrap().allows(url, request.first_party.as_ref());
+        let overridden = self.certificates.lock().unwrap().is_overridden(url.host_str().unwrap_or(""));
+        let client = if overridden { &self.insecure_client } else { &self.client };
+        let mut builder = client.get(url.clone()).header("User-Agent", USER_AGENT);
+        if let Some(cookies) = cookie_header(&self.cookies, url, partition.as_ref()).filter(|_| use_cookies) {
+            builder = builder.header("Cookie", cookies);
+        }
+        for (name, value) in request_headers(&options) {
+            builder = builder.header(name, value);
+        }
+        let authorization = self.auth.lock().unwrap().authorization("GET", url);
+        if let Some(ref authorization) = authorization {
+            builder = builder.header("Authorization", authorization.as_str());
+        }
+        let http_request = builder.build().map_err(|e| NetError::RequestFailed(e.to_string()))?;
+        let sent_headers = header_list(http_request.headers());
+        let sent = Instant::now();
+        let mut response = match client.execute(http_request).await {
+            Ok(response) => response,
+            Err(error) => {
+                let resource = stale_if_unreachable(cached, request.cache_mode, request_error(error))?;
+                return Ok((resource, None));
+            }
+        };
+        let headers_received = Instant::now();
+        cancel.check()?;
+        record_certificate(&self.certificates, url, response.extensions().get::<reqwest::tls::TlsInfo>());
+
+        let status = response.status().as_u16();
+        let final_url = response.url().clone();
+        // Refused credentials are not sent again; the page asks for new ones
+        if status == 401 && authorization.is_some() {
+            let challenges = response.headers().get_all("www-authenticate").iter().filter_map(|v| v.to_str().ok());
+            if let Some(challenge) = AuthChallenge::preferred(challenges) {
+                self.auth.lock().unwrap().forget(&final_url, &challenge.realm);
+            }
+        }
+        let header = |name: &str| response.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+        let content_type = header("content-type").unwrap_or_default();
+        let (etag, last_modified) = (header("etag"), header("last-modified"));
+        let headers = header_list(response.headers());
+        if use_cookies {
+            store_cookies(&self.cookies, response.headers(), url, partition.as_ref());
+        }
+        let head = FetchEvent::Response { id, url: url.clone(), status, content_type: content_type.clone() };
+        let _ = self.events.send(head);
+
+        // Stream the body, stopping between chunks if the fetch is cancelled
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(|e| NetError::RequestFailed(e.to_string()))? {
+            cancel.check()?;
+            body.extend_from_slice(&chunk);
+            let _ = self.events.send(FetchEvent::Data { id, chunk: chunk.to_vec() });
+        }
+
+        let response = Response {
+            url: url.clone(),
+            final_url,
+            status,
+            content_type,
+            body,
+            etag,
+            last_modified,
+            request_headers: sent_headers,
+            headers,
+            timing: RequestTiming {
+                wait_ms: millis(headers_received - sent),
+                receive_ms: millis(headers_received.elapsed()),
+            },
+        };
+        Ok(store_response(&self.cache, response, cached, partition))
+    }
+}
+
+/// Fetches resources in the background, reporting to the thread that polls it
+pub struct ResourceFetcher {
+    shared: Arc<Shared>,
+    events: Receiver<FetchEvent>,
+    next_id: FetchId,
+    /// Kept last, so it shuts down once nothing else needs it
+    runtime: tokio::runtime::Runtime,
+}
+
+impl ResourceFetcher {
+    /// Create a fetcher that caches into `cache`, keeps cookies in
+    /// `cookies` and certificates in `certificates`, and sends the
+    /// credentials in `auth`
+    pub(super) fn new(
+        cache: Arc<Mutex<ResourceCache>>,
+        cookies: Arc<Mutex<CookieJar>>,
+        certificates: Arc<Mutex<CertificateStore>>,
+        auth: Arc<Mutex<AuthCache>>,
+    ) -> Self {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .thread_name("resource-fetcher")
+            .enable_all()
+            .build()
+            .expect("Failed to create fetcher runtime");
+        let builder = || {
+            reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .user_agent(USER_AGENT)
+                .tls_info(true)
+        };
+        let client = builder().build().expect("Failed to create HTTP client");
+        let insecure_client = builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .expect("Failed to create HTTP client");
+        let (sender, events) = mpsc::channel();
+        let shared = Arc::new(Shared {
+            client,
+            insecure_client,
+            cache,
+            cookies,
+            certificates,
+            auth,
+            cookie_policy: Mutex::new(CookiePolicy::default()),
+            interceptor: Mutex::new(None),
+            scheduler: Mutex::new(Scheduler { max_running: MAX_CONCURRENT, ..Scheduler::default() }),
+            runtime: runtime.handle().clone(),
+            events: sender,
+        });
+        Self { shared, events, next_id: 1, runtime }
+    }
+
+    /// Change which requests may send and store cookies
+    pub fn set_cookie_policy(&self, policy: CookiePolicy) {
+        *self.shared.cookie_policy.lock().unwrap() = policy;
+    }
+
+    /// Set or remove the hook that may block requests
+    pub fn set_interceptor(&self, interceptor: Option<Arc<dyn RequestInterceptor>>) {
+        *self.shared.interceptor.lock().unwrap() = interceptor;
+    }
+
+    /// Change how many requests may be in flight at once
+    pub fn set_max_concurrent(&self, max: usize) {
+        self.shared.scheduler.lock().unwrap().max_running = max.max(1);
+        self.shared.start_waiting();
+    }
+
+    /// Queue a request, returning the id its events carry
+    pub fn fetch(&mut self, request: FetchRequest) -> FetchId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.shared.scheduler.lock().unwrap().waiting.push(Queued { id, request });
+        self.shared.start_waiting();
+        id
+    }
+
+    /// Has every request finished
+    pub fn is_idle(&self) -> bool {
+        let scheduler = self.shared.scheduler.lock().unwrap();
+        scheduler.waiting.is_empty() && scheduler.running == 0
+    }
+
+    /// Drain the events reported since the last poll, without waiting
+    pub fn poll(&self) -> Vec<FetchEvent> {
+        self.events.try_iter().collect()
+    }
+
+    /// Wait up to `timeout` for the next event
+    pub fn wait(&self, timeout: Duration) -> Option<FetchEvent> {
+        self.events.recv_timeout(timeout).ok()
+    }
+
+    /// Handle on the runtime requests are sent from, for other async network work
+    pub fn runtime(&self) -> &tokio::runtime::Handle {
+        self.runtime.handle()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::ResourceLoader;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    /// Serve `count` requests one at a time, answering each with its path,
+    /// once `go` says so
+    fn serve(count: usize, go: Receiver<()>) -> (Url, std::thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        let server = std::thread::spawn(move || {
+            let mut paths = Vec::new();
+            for stream in listener.incoming().take(count) {
+                let mut stream = stream.unwrap();
+                let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+                let request_line = lines.next().unwrap().unwrap();
+                while lines.next().is_some_and(|line| !line.unwrap().is_empty()) {}
+                let path = request_line.split(' ').nth(1).unwrap().to_string();
+                if paths.is_empty() {
+                    go.recv().unwrap();
+                }
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/css\r\nCache-Control: max-age=60\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    path.len(),
+                    path
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+                paths.push(path);
+            }
+            paths
+        });
+        (base, server)
+    }
+
+    fn finish(fetcher: &ResourceFetcher, count: usize) -> Vec<(FetchId, Result<CachedResource, NetError>)> {
+        let mut finished = Vec::new();
+        while finished.len() < count {
+            match fetcher.wait(Duration::from_secs(10)).expect("fetch timed out") {
+                FetchEvent::Finished { id, resource, .. } => finished.push((id, Ok(*resource))),
+                FetchEvent::Failed { id, error } => finished.push((id, Err(error))),
+                _ => {}
+            }
+        }
+        finished
+    }
+
+    #[test]
+    fn test_priority_order_and_streaming() {
+        let loader = ResourceLoader::new(1024);
+        let mut fetcher = loader.fetcher();
+        fetcher.set_max_concurrent(1);
+        let (go, wait) = mpsc::channel();
+        let (base, server) = serve(4, wait);
+
+        // The first request holds the only slot while the others queue
+        let first = fetcher.fetch(FetchRequest::new(base.join("first").unwrap(), ResourceType::Other));
+        std::thread::sleep(Duration::from_millis(100));
+        fetcher.fetch(FetchRequest::new(base.join("image.png").unwrap(), ResourceType::Image));
+        fetcher.fetch(FetchRequest::new(base.join("script.js").unwrap(), ResourceType::Other));
+        fetcher.fetch(FetchRequest::new(base.join("style.css").unwrap(), ResourceType::Css));
+        go.send(()).unwrap();
+
+        let finished = finish(&fetcher, 4);
+        assert_eq!(server.join().unwrap(), ["/first", "/style.css", "/script.js", "/image.png"]);
+        assert_eq!(finished[0].0, first);
+        let style = finished[1].1.as_ref().unwrap();
+        assert_eq!((style.data.as_slice(), style.resource_type), (b"/style.css".as_slice(), ResourceType::Css));
+        assert!(fetcher.is_idle());
+
+        // Fetched resources are in the loader's cache
+        assert_eq!(loader.cache_count(), 4);
+        assert_eq!(loader.load(&base.join("image.png").unwrap()).unwrap().data, b"/image.png");
+    }
+
+    #[test]
+    fn test_cache_interceptor_and_cancel() {
+        struct BlockImages;
+        impl RequestInterceptor for BlockImages {
+            fn should_block(&self, request: &InterceptedRequest<'_>) -> bool {
+                request.resource_type == ResourceType::Image
+            }
+        }
+
+        let loader = ResourceLoader::new(1024);
+        let mut fetcher = loader.fetcher();
+        let (go, wait) = mpsc::channel();
+        let (base, server) = serve(1, wait);
+        go.send(()).unwrap();
+        let page = base.join("page").unwrap();
+        let fetched = fetcher.fetch(FetchRequest::new(page.clone(), ResourceType::Html));
+        let finished = finish(&fetcher, 1);
+        assert_eq!(finished[0].0, fetched);
+        server.join().unwrap();
+
+        // Served from the cache now that the server is gone
+        fetcher.fetch(FetchRequest::new(page.clone(), ResourceType::Html));
+        let events = finish(&fetcher, 1);
+        assert_eq!(events[0].1.as_ref().unwrap().data, b"/page");
+
+        fetcher.set_interceptor(Some(Arc::new(BlockImages)));
+        fetcher.fetch(FetchRequest::new(base.join("ad.png").unwrap(), ResourceType::Image));
+        let stopped = CancellationToken::new();
+        stopped.cancel();
+        fetcher.fetch(FetchRequest::new(page, ResourceType::Html).with_cancel(stopped));
+        let mut errors: Vec<_> = finish(&fetcher, 2).into_iter().map(|(_, result)| result.unwrap_err()).collect();
+        errors.sort_by_key(|error| matches!(error, NetError::Cancelled));
+        assert!(matches!(errors.as_slice(), [NetError::Blocked(_), NetError::Cancelled]));
+    }
+}
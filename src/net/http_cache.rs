@@ -0,0 +1,524 @@
+// HTTP caching rules and the on-disk tier of the resource cache.
+//
+// Responses are fresh for the lifetime their `Cache-Control` or `Expires`
+// headers give, or a tenth of their age since `Last-Modified` when they give
+// none. Fresh entries are used without a request; stale ones are revalidated
+// with `If-None-Match` / `If-Modified-Since`, and used as they are when the
+// server cannot be reached unless they are `must-revalidate`. Responses that
+// vary on request headers which differ between requests are not kept.
+// Entries loaded for a page of another site are kept in that top-level
+// site's partition.
+
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+use super::{CachedResource, PartitionKey, ResourceType};
+
+/// Longest heuristic lifetime for responses without explicit freshness
+const MAX_HEURISTIC_LIFETIME: u64 = 7 * 24 * 60 * 60;
+
+/// Request headers the client sends the same way with every request, or
+/// never, so a response that varies on them suits any later request
+const UNVARYING_REQUEST_HEADERS: [&str; 4] = ["accept", "accept-encoding", "accept-language", "user-agent"];
+
+/// The `Cache-Control` directives a private browser cache acts on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheControl {
+    /// `max-age`: seconds the response stays fresh
+    pub max_age: Option<u64>,
+    /// `no-cache`: revalidate before every use
+    pub no_cache: bool,
+    /// `no-store`: never cache the response
+    pub no_store: bool,
+    /// `must-revalidate`: never use the response once stale
+    pub must_revalidate: bool,
+    /// `immutable`: the response never changes while fresh, even on reload
+    pub immutable: bool,
+}
+
+impl CacheControl {
+    /// Parse the value of a `Cache-Control` header, ignoring unknown directives
+    pub fn parse(value: &str) -> Self {
+        let mut control = CacheControl::default();
+        for directive in value.split(',') {
+            let (name, argument) = match directive.split_once('=') {
+                Some((name, argument)) => (name.trim(), Some(argument.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+            match name.to_ascii_lowercase().as_str() {
+                // An invalid max-age makes the response stale
+                "max-age" => control.max_age = Some(argument.and_then(|a| a.parse().ok()).unwrap_or(0)),
+                "no-cache" => control.no_cache = true,
+                "no-store" => control.no_store = true,
+                "must-revalidate" => control.must_revalidate = true,
+                "immutable" => control.immutable = true,
+                _ => {}
+            }
+        }
+        control
+    }
+
+    /// The directives in a response's headers; `Pragma: no-cache` counts
+    /// when there is no `Cache-Control`
+    pub fn from_headers(headers: &[(String, String)]) -> Self {
+        let values: Vec<&str> = header_values(headers, "cache-control").collect();
+        if values.is_empty() {
+            let pragma = header_values(headers, "pragma").any(|v| v.eq_ignore_ascii_case("no-cache"));
+            return CacheControl { no_cache: pragma, ..CacheControl::default() };
+        }
+        CacheControl::parse(&values.join(","))
+    }
+}
+
+/// How long a cached response may be used without asking the server
+///
+/// The default is stale, so entries without it are always revalidated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Freshness {
+    /// When the response was generated, in seconds since the epoch
+    pub stored_at: u64,
+    /// Seconds the response stays fresh from `stored_at`
+    pub lifetime: u64,
+    /// Revalidate before every use
+    pub no_cache: bool,
+    /// Use without revalidating while fresh, even on reload
+    pub immutable: bool,
+    /// Never use once stale, even when the server cannot be reached
+    #[serde(default)]
+    pub must_revalidate: bool,
+}
+
+impl Freshness {
+    /// Fresh for `lifetime` seconds from `now`
+    pub fn for_lifetime(lifetime: u64, now: u64) -> Self {
+        Freshness { stored_at: now, lifetime, ..Freshness::default() }
+    }
+
+    /// Freshness of a response received at `now`, from its headers
+    pub fn from_headers(headers: &[(String, String)], now: u64) -> Self {
+        let control = CacheControl::from_headers(headers);
+        let date = header(headers, "date").and_then(parse_http_date).unwrap_or(now).min(now);
+        let lifetime = if let Some(max_age) = control.max_age {
+            max_age
+        } else if let Some(expires) = header(headers, "expires") {
+            // Invalid dates such as `0` mean already expired
+            parse_http_date(expires).map_or(0, |expires| expires.saturating_sub(date))
+        } else if let Some(modified) = header(headers, "last-modified").and_then(parse_http_date) {
+            (date.saturating_sub(modified) / 10).min(MAX_HEURISTIC_LIFETIME)
+        } else {
+            0
+        };
+        let age = header(headers, "age").and_then(|age| age.trim().parse().ok()).unwrap_or(0);
+        Freshness {
+            stored_at: now.saturating_sub(age),
+            lifetime,
+            no_cache: control.no_cache,
+            immutable: control.immutable,
+            must_revalidate: control.must_revalidate,
+        }
+    }
+
+    /// Freshness after a `304 Not Modified` at `now`
+    ///
+    /// The 304's headers replace the stored ones; without freshness headers
+    /// the previous lifetime starts over.
+    pub fn revalidated(&self, headers: &[(String, String)], now: u64) -> Self {
+        let explicit = ["cache-control", "expires", "pragma"].iter().any(|name| header(headers, name).is_some());
+        if explicit {
+            Freshness::from_headers(headers, now)
+        } else {
+            Freshness { stored_at: now, ..*self }
+        }
+    }
+
+    /// Can the response be used at `now` without revalidating
+    pub fn is_fresh(&self, now: u64) -> bool {
+        !self.no_cache && now < self.stored_at.saturating_add(self.lifetime)
+    }
+
+    /// Can the response be used once stale, when revalidating it failed
+    /// because the server could not be reached
+    pub fn may_serve_stale(&self) -> bool {
+        !self.no_cache && !self.must_revalidate
+    }
+}
+
+/// May a response with these status and headers be stored
+///
+/// A response that varies on `*`, or on a request header that can differ
+/// between requests such as `Cookie`, is not, as a later request could get
+/// what the server would not have sent it.
+pub fn is_storable(status: u16, headers: &[(String, String)]) -> bool {
+    let varies = header_values(headers, "vary")
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .any(|name| !UNVARYING_REQUEST_HEADERS.iter().any(|unvarying| name.eq_ignore_ascii_case(unvarying)));
+    matches!(status, 200 | 203) && !CacheControl::from_headers(headers).no_store && !varies
+}
+
+/// How requests were served from the HTTP cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Served from the cache without a request
+    pub hits: u64,
+    /// The server confirmed the cached copy with `304 Not Modified`
+    pub revalidated: u64,
+    /// Fetched in full from the network
+    pub misses: u64,
+}
+
+/// What a response is cached under: its URL in a partition
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(super) struct CacheKey {
+    pub(super) partition: Option<PartitionKey>,
+    pub(super) url: Url,
+}
+
+impl CacheKey {
+    pub(super) fn new(url: &Url, partition: Option<&PartitionKey>) -> Self {
+        CacheKey { partition: partition.cloned(), url: url.clone() }
+    }
+
+    fn of(resource: &CachedResource) -> Self {
+        CacheKey::new(&resource.url, resource.partition.as_ref())
+    }
+}
+
+/// What is kept beside a cached body on disk
+#[derive(Debug, Serialize, Deserialize)]
+struct DiskMeta {
+    url: Url,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    partition: Option<PartitionKey>,
+    content_type: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    freshness: Freshness,
+    last_accessed: u64,
+}
+
+/// An on-disk entry, as the index remembers it
+#[derive(Debug, Clone, Copy)]
+struct DiskEntry {
+    size: usize,
+    last_accessed: u64,
+}
+
+/// Cached responses kept in a directory across runs
+///
+/// Each entry is a `.meta` JSON file and a `.body` file named by a hash of
+/// its URL and partition. Disk errors lose entries rather than failing loads.
+pub(super) struct DiskCache {
+    dir: PathBuf,
+    /// Maximum size of the bodies in bytes
+    max_size: usize,
+    current_size: usize,
+    entries: HashMap<CacheKey, DiskEntry>,
+}
+
+impl DiskCache {
+    /// Open the cache in `dir`, creating it if needed
+    pub(super) fn open(dir: &Path, max_size: usize) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let mut cache = DiskCache { dir: dir.to_path_buf(), max_size, current_size: 0, entries: HashMap::new() };
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "meta") {
+                continue;
+            }
+            let meta = read_meta(&path);
+            let size = fs::metadata(path.with_extension("body")).map(|m| m.len() as usize);
+            match (meta, size) {
+                (Some(meta), Ok(size)) => {
+                    cache.current_size += size;
+                    let key = CacheKey { partition: meta.partition, url: meta.url };
+                    cache.entries.insert(key, DiskEntry { size, last_accessed: meta.last_accessed });
+                }
+                // Half-written entries are dropped
+                _ => {
+                    let _ = fs::remove_file(&path);
+                    let _ = fs::remove_file(path.with_extension("body"));
+                }
+            }
+        }
+        cache.evict_to(cache.max_size);
+        Ok(cache)
+    }
+
+    fn path(&self, key: &CacheKey, extension: &str) -> PathBuf {
+        let name = match &key.partition {
+            Some(partition) => format!("{} {}", partition, key.url),
+            None => key.url.to_string(),
+        };
+        let hash: String = Sha1::digest(name.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect();
+        self.dir.join(hash).with_extension(extension)
+    }
+
+    pub(super) fn get(&mut self, key: &CacheKey, now: u64) -> Option<CachedResource> {
+        self.entries.get(key)?;
+        let meta = read_meta(&self.path(key, "meta"));
+        let data = fs::read(self.path(key, "body")).ok();
+        let (Some(meta), Some(data)) = (meta, data) else {
+            self.remove(key);
+            return None;
+        };
+        if let Some(entry) = self.entries.get_mut(key) {
+            entry.last_accessed = now;
+        }
+        Some(CachedResource {
+            resource_type: ResourceType::detect(&meta.content_type, &key.url),
+            url: meta.url,
+            partition: meta.partition,
+            content_type: meta.content_type,
+            data,
+            last_accessed: now,
+            etag: meta.etag,
+            last_modified: meta.last_modified,
+            freshness: meta.freshness,
+        })
+    }
+
+    pub(super) fn put(&mut self, resource: &CachedResource) {
+        let size = resource.data.len();
+        let key = CacheKey::of(resource);
+        self.remove(&key);
+        if size > self.max_size {
+            return;
+        }
+        self.evict_to(self.max_size - size);
+
+        let meta = DiskMeta {
+            url: resource.url.clone(),
+            partition: resource.partition.clone(),
+            content_type: resource.content_type.clone(),
+            etag: resource.etag.clone(),
+            last_modified: resource.last_modified.clone(),
+            freshness: resource.freshness,
+            last_accessed: resource.last_accessed,
+        };
+        // The body goes first, so an entry with metadata is complete
+        let written = fs::write(self.path(&key, "body"), &resource.data).is_ok()
+            && serde_json::to_vec(&meta).is_ok_and(|json| fs::write(self.path(&key, "meta"), json).is_ok());
+        if written {
+            self.current_size += size;
+            self.entries.insert(key, DiskEntry { size, last_accessed: resource.last_accessed });
+        } else {
+            let _ = fs::remove_file(self.path(&key, "body"));
+        }
+    }
+
+    pub(super) fn remove(&mut self, key: &CacheKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.current_size -= entry.size;
+            let _ = fs::remove_file(self.path(key, "meta"));
+            let _ = fs::remove_file(self.path(key, "body"));
+        }
+    }
+
+    /// Drop least recently used entries until the bodies fit in `size`
+    fn evict_to(&mut self, size: usize) {
+        while self.current_size > size {
+            let Some(key) = self.entries.iter().min_by_key(|(_, entry)| entry.last_accessed).map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            self.remove(&key);
+        }
+    }
+
+    /// Remove entries whose URL matches, in every partition, returning how
+    /// many were removed
+    pub(super) fn remove_where(&mut self, mut matches: impl FnMut(&Url) -> bool) -> usize {
+        let keys: Vec<CacheKey> = self.entries.keys().filter(|key| matches(&key.url)).cloned().collect();
+        for key in &keys {
+            self.remove(key);
+        }
+        keys.len()
+    }
+
+    /// URLs and body sizes of the entries
+    pub(super) fn sizes(&self) -> impl Iterator<Item = (&Url, usize)> {
+        self.entries.iter().map(|(key, entry)| (&key.url, entry.size))
+    }
+
+    pub(super) fn contains(&self, key: &CacheKey) -> bool {
+        self.entries.contains_key(key)
+    }
+}
+
+fn read_meta(path: &Path) -> Option<DiskMeta> {
+    serde_json::from_slice(&fs::read(path).ok()?).ok()
+}
+
+/// The first value of a header, by case-insensitive name
+fn header<'a>(headers: &'a [(String, String)], name: &'static str) -> Option<&'a str> {
+    header_values(headers, name).next()
+}
+
+fn header_values<'a>(headers: &'a [(String, String)], name: &'static str) -> impl Iterator<Item = &'a str> {
+    headers.iter().filter(move |(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+}
+
+/// Parse an HTTP date such as `Sun, 06 Nov 1994 08:49:37 GMT` into seconds
+/// since the epoch
+pub fn parse_http_date(value: &str) -> Option<u64> {
+    let (_, date) = value.trim().split_once(',')?;
+    let parts: Vec<&str> = date.split_whitespace().collect();
+    let [day, month, year, time, "GMT"] = parts.as_slice() else {
+        return None;
+    };
+    let day: u64 = day.parse().ok()?;
+    let month = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"]
+        .iter()
+        .position(|m| m.eq_ignore_ascii_case(month))? as u64
+        + 1;
+    let year: u64 = year.parse().ok()?;
+    let mut clock = time.split(':').map(|part| part.parse::<u64>().ok());
+    let (Some(Some(hours)), Some(Some(minutes)), Some(Some(seconds)), None) =
+        (clock.next(), clock.next(), clock.next(), clock.next())
+    else {
+        return None;
+    };
+    if year < 1970 || !(1..=31).contains(&day) || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+
+    // Days since the epoch of a proleptic Gregorian date
+    let (y, m) = if month <= 2 { (year - 1, month + 9) } else { (year, month - 3) };
+    let era = y / 400;
+    let year_of_era = y % 400;
+    let day_of_year = (153 * m + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = (era * 146_097 + day_of_era).checked_sub(719_468)?;
+    Some(days * 86_400 + hours * 3600 + minutes * 60 + seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn test_cache_control_parse() {
+        let control = CacheControl::parse("public, Max-Age=\"300\", must-revalidate");
+        assert_eq!(control.max_age, Some(300));
+        assert!(control.must_revalidate && !control.no_store);
+        assert!(CacheControl::parse("no-store").no_store);
+        assert_eq!(CacheControl::parse("max-age=soon").max_age, Some(0));
+
+        let pragma = CacheControl::from_headers(&headers(&[("Pragma", "no-cache")]));
+        assert!(pragma.no_cache);
+        assert!(!is_storable(200, &headers(&[("cache-control", "private"), ("Cache-Control", "no-store")])));
+        assert!(!is_storable(404, &[]));
+    }
+
+    #[test]
+    fn test_vary() {
+        // Every request sends these the same way
+        assert!(is_storable(200, &headers(&[("Vary", "Accept-Encoding"), ("vary", "user-agent, Accept")])));
+        assert!(!is_storable(200, &headers(&[("Vary", "Accept-Encoding, Cookie")])));
+        assert!(!is_storable(200, &headers(&[("Vary", "Accept-Encoding"), ("Vary", "Authorization")])));
+        assert!(!is_storable(200, &headers(&[("Vary", "*")])));
+    }
+
+    #[test]
+    fn test_http_date() {
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(784_111_777));
+        assert_eq!(parse_http_date("Thu, 01 Jan 1970 00:00:00 GMT"), Some(0));
+        assert_eq!(parse_http_date("Tue, 29 Feb 2000 12:00:00 GMT"), Some(951_825_600));
+        assert_eq!(parse_http_date("0"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49 GMT"), None);
+    }
+
+    #[test]
+    fn test_freshness() {
+        let now = 1_000_000;
+        let max_age = Freshness::from_headers(&headers(&[("Cache-Control", "max-age=60"), ("Age", "20")]), now);
+        assert!(max_age.is_fresh(now + 39));
+        assert!(!max_age.is_fresh(now + 40));
+
+        // Expires counts from the server's Date
+        let expires = Freshness::from_headers(
+            &headers(&[("Date", "Thu, 01 Jan 1970 00:00:00 GMT"), ("Expires", "Thu, 01 Jan 1970 00:01:40 GMT")]),
+            now,
+        );
+        assert_eq!(expires.lifetime, 100);
+        assert!(!Freshness::from_headers(&headers(&[("Expires", "0")]), now).is_fresh(now));
+
+        // A tenth of the time since the last modification
+        let heuristic = Freshness::from_headers(
+            &headers(&[("Date", "Thu, 01 Jan 1970 01:00:00 GMT"), ("Last-Modified", "Thu, 01 Jan 1970 00:00:00 GMT")]),
+            now,
+        );
+        assert_eq!(heuristic.lifetime, 360);
+
+        let no_cache = Freshness::from_headers(&headers(&[("Cache-Control", "no-cache, max-age=600")]), now);
+        assert!(!no_cache.is_fresh(now));
+        assert!(!Freshness::default().is_fresh(now));
+
+        // A 304 without freshness headers restarts the stored lifetime
+        let revalidated = max_age.revalidated(&headers(&[("ETag", "\"v1\"")]), now + 100);
+        assert!(revalidated.is_fresh(now + 150));
+        let now_no_cache = max_age.revalidated(&headers(&[("Cache-Control", "no-cache")]), now + 100);
+        assert!(!now_no_cache.is_fresh(now + 100));
+
+        // Only what may be used stale stands in for an unreachable server
+        assert!(max_age.may_serve_stale());
+        assert!(!now_no_cache.may_serve_stale());
+        let must_revalidate = Freshness::from_headers(&headers(&[("Cache-Control", "max-age=60, must-revalidate")]), now);
+        assert!(must_revalidate.is_fresh(now) && !must_revalidate.may_serve_stale());
+    }
+
+    #[test]
+    fn test_disk_cache_persists_and_evicts() {
+        let dir = std::env::temp_dir().join(format!("http-cache-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let resource = |path: &str, size: usize, last_accessed: u64| CachedResource {
+            url: Url::parse("https://example.com/").unwrap().join(path).unwrap(),
+            partition: None,
+            resource_type: ResourceType::Css,
+            content_type: "text/css".to_string(),
+            data: vec![b'a'; size],
+            last_accessed,
+            etag: Some("\"v1\"".to_string()),
+            last_modified: None,
+            freshness: Freshness::for_lifetime(60, 1000),
+        };
+
+        let mut cache = DiskCache::open(&dir, 10).unwrap();
+        cache.put(&resource("old.css", 4, 1));
+        cache.put(&resource("new.css", 4, 2));
+        drop(cache);
+
+        let mut cache = DiskCache::open(&dir, 10).unwrap();
+        assert_eq!(cache.current_size, 8);
+        let key = |path: &str| CacheKey::of(&resource(path, 0, 0));
+        let loaded = cache.get(&key("old.css"), 3).unwrap();
+        assert_eq!((loaded.data.len(), loaded.etag.as_deref()), (4, Some("\"v1\"")));
+        assert_eq!((loaded.resource_type, loaded.freshness), (ResourceType::Css, Freshness::for_lifetime(60, 1000)));
+
+        // new.css is now the least recently used
+        cache.put(&resource("third.css", 4, 4));
+        assert!(!cache.contains(&key("new.css")));
+        assert!(cache.contains(&key("old.css")));
+
+        // The same URL loaded for another site's page is a separate entry
+        let partition = PartitionKey::of(&Url::parse("https://news.example.net/").unwrap());
+        cache.put(&CachedResource { partition: Some(partition.clone()), ..resource("old.css", 2, 5) });
+        let partitioned = CacheKey::new(&key("old.css").url, Some(&partition));
+        assert_eq!(cache.get(&partitioned, 6).unwrap().data.len(), 2);
+        assert_eq!(cache.get(&key("old.css"), 6).unwrap().data.len(), 4);
+        assert_eq!(cache.remove_where(|_| true), 3);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,25 +1,322 @@
 mod resource_loader;
 mod page_loader;
+mod event_source;
+mod fetcher;
+mod http_cache;
+mod tls;
+mod error_page;
+mod auth;
 
-use reqwest::blocking::Client;
-use std::time::Duration;
+use crate::storage::{Cookie, CookieJar};
+use publicsuffix::Psl;
+use reqwest::blocking::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 use url::Url;
 
-pub use resource_loader::{ResourceLoader, ResourceType, CachedResource};
-pub use page_loader::{PageLoader, LoadedPage};
+pub use resource_loader::{
+    CachedResource, InterceptedRequest, RequestInterceptor, ResourceLoader, ResourceType, SiteData, Transfer,
+};
+pub use page_loader::{PageLoader, LoadedPage, LoadEvent};
+pub use http_cache::{parse_http_date, CacheControl, CacheStats, Freshness};
+pub use tls::{is_mixed_content, Certificate, CertificateStore, SecurityState};
+pub use error_page::{ErrorPage, LoadFailure};
+pub use auth::{AuthCache, AuthChallenge, AuthRequest, AuthScheme, CredentialPrompt, Credentials};
+pub use fetcher::{FetchEvent, FetchId, FetchPriority, FetchRequest, ResourceFetcher};
+pub use event_source::{
+    EventSource, EventSourceEvent, EventSourceHandle, EventSourceState, EventStreamParser, ServerSentEvent,
+};
+
+/// `User-Agent` sent with every request
+const USER_AGENT: &str = "BrowserEngine/0.1.0";
+
+/// Times a request asks for credentials before the 401 response is given up on
+const MAX_AUTH_PROMPTS: usize = 3;
+
+/// Times a request is sent again with credentials, asked for or not,
+/// before the 401 response is given up on
+const MAX_AUTH_RETRIES: usize = 6;
 
 /// HTTP client for fetching web resources
 pub struct HttpClient {
     client: Client,
+    /// Client accepting invalid certificates, for hosts the user trusted anyway
+    insecure_client: Client,
+    /// Cookies received from servers, shared with `SiteData` handles
+    cookies: Arc<Mutex<CookieJar>>,
+    /// Which requests may send and store cookies
+    cookie_policy: CookiePolicy,
+    /// Certificates servers presented and the hosts whose invalid ones are
+    /// accepted, shared with the fetcher
+    certificates: Arc<Mutex<CertificateStore>>,
+    /// Credentials that worked, per origin, shared with the fetcher
+    auth: Arc<Mutex<AuthCache>>,
+    /// Asked for credentials when a server answers `401 Unauthorized`
+    credential_prompt: Option<Arc<dyn CredentialPrompt>>,
 }
 
 /// Response from an HTTP request
 #[derive(Debug)]
 pub struct Response {
     pub url: Url,
+    /// URL that answered, after redirects
+    pub final_url: Url,
     pub status: u16,
     pub content_type: String,
     pub body: Vec<u8>,
+    /// `ETag` validator, if the server sent one
+    pub etag: Option<String>,
+    /// `Last-Modified` validator, if the server sent one
+    pub last_modified: Option<String>,
+    /// Headers sent with the request
+    pub request_headers: Vec<(String, String)>,
+    /// Headers of the response, in the order received
+    pub headers: Vec<(String, String)>,
+    pub timing: RequestTiming,
+}
+
+/// How long the phases of a request took, in milliseconds
+///
+/// The blocking client connects while sending, so DNS lookup, connecting
+/// and the TLS handshake count as waiting.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RequestTiming {
+    /// From sending the request to the response headers arriving
+    pub wait_ms: f64,
+    /// Reading the response body
+    pub receive_ms: f64,
+}
+
+/// Response whose body is read as it arrives (e.g. an event stream)
+pub struct StreamingResponse {
+    pub url: Url,
+    pub status: u16,
+    pub content_type: String,
+    body: reqwest::blocking::Response,
+}
+
+impl Read for StreamingResponse {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.body.read(buf)
+    }
+}
+
+impl Response {
+    /// Did a conditional request find the cached copy still fresh
+    pub fn is_not_modified(&self) -> bool {
+        self.status == 304
+    }
+
+    /// Where the body of a `206 Partial Content` response starts in the
+    /// whole resource, and the resource's length if the server knows it
+    pub fn content_range(&self) -> Option<(u64, Option<u64>)> {
+        if self.status != 206 {
+            return None;
+        }
+        let (_, value) = self.headers.iter().find(|(name, _)| name.eq_ignore_ascii_case("content-range"))?;
+        // `bytes 100-199/1000`, or `bytes 100-199/*` when the length is unknown
+        let (range, length) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+        let (start, _) = range.split_once('-')?;
+        Some((start.trim().parse().ok()?, length.trim().parse().ok()))
+    }
+}
+
+/// How a request uses the HTTP cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CacheMode {
+    /// Use a fresh cached copy, revalidating a stale one
+    #[default]
+    Default,
+    /// Revalidate cached copies with the server (reload)
+    Revalidate,
+    /// Bypass the cache entirely (hard reload)
+    Reload,
+}
+
+impl CacheMode {
+    /// Parse a Fetch API `cache` value
+    pub fn from_str(s: &str) -> Self {
+        match s {
+            "no-cache" => CacheMode::Revalidate,
+            "reload" | "no-store" => CacheMode::Reload,
+            _ => CacheMode::Default,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheMode::Default => "default",
+            CacheMode::Revalidate => "no-cache",
+            CacheMode::Reload => "reload",
+        }
+    }
+}
+
+/// Which requests may send and store cookies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CookiePolicy {
+    #[default]
+    AllowAll,
+    /// Only requests to the site of the page being shown
+    BlockThirdParty,
+    BlockAll,
+}
+
+impl CookiePolicy {
+    /// Parse a policy name
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "allow-all" => Some(CookiePolicy::AllowAll),
+            "block-third-party" => Some(CookiePolicy::BlockThirdParty),
+            "block-all" => Some(CookiePolicy::BlockAll),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CookiePolicy::AllowAll => "allow-all",
+            CookiePolicy::BlockThirdParty => "block-third-party",
+            CookiePolicy::BlockAll => "block-all",
+        }
+    }
+
+    /// May a request to `url` use cookies
+    ///
+    /// `first_party` is the page the request is made for; `None` means
+    /// the request is for the page itself.
+    pub fn allows(&self, url: &Url, first_party: Option<&Url>) -> bool {
+        match self {
+            CookiePolicy::AllowAll => true,
+            CookiePolicy::BlockAll => false,
+            CookiePolicy::BlockThirdParty => match first_party {
+                None => true,
+                Some(page) => site(url) == site(page),
+            },
+        }
+    }
+}
+
+/// The Public Suffix List, both its ICANN and private sections
+///
+/// Shared hosting suffixes such as `github.io` or `s3.amazonaws.com` are in
+/// the private section, so each name under them is a site of its own.
+fn public_suffix_list() -> &'static publicsuffix::List {
+    static LIST: OnceLock<publicsuffix::List> = OnceLock::new();
+    LIST.get_or_init(|| {
+        include_str!("public_suffix_list.dat").parse().expect("the bundled public suffix list is valid")
+    })
+}
+
+/// Registrable domain (eTLD+1) of a host, e.g. `example.co.uk` for
+/// `www.example.co.uk`; IP addresses, and hosts that are themselves a
+/// public suffix (such as `localhost`), are their own site
+pub fn registrable_domain(url: &Url) -> Option<String> {
+    let host = match url.host()? {
+        url::Host::Domain(domain) => domain.trim_end_matches('.').to_ascii_lowercase(),
+        ip => return Some(ip.to_string()),
+    };
+    let domain = public_suffix_list().domain(host.as_bytes());
+    match domain.and_then(|domain| std::str::from_utf8(domain.as_bytes()).ok()) {
+        Some(domain) => Some(domain.to_string()),
+        None => Some(host),
+    }
+}
+
+/// Site of a URL: its registrable domain
+pub(crate) fn site(url: &Url) -> Option<String> {
+    registrable_domain(url)
+}
+
+/// Top-level site that state kept for a third party is partitioned by
+///
+/// Cookies, cache entries and storage a site gets while embedded in, or
+/// loaded by, a page of another site are kept apart for each top-level
+/// site, so a third party cannot recognise a user across the sites that
+/// embed it. What a site keeps as the top-level page has no partition.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PartitionKey(String);
+
+impl PartitionKey {
+    /// Partition of the top-level page `top_level`: its scheme and site
+    pub fn of(top_level: &Url) -> Self {
+        PartitionKey(crate::multiprocess::site_key(top_level))
+    }
+
+    /// Partition for state of `url` used under the top-level page
+    /// `top_level`; `None` when they are the same site
+    pub fn for_request(url: &Url, top_level: Option<&Url>) -> Option<Self> {
+        let top_level = top_level?;
+        let key = PartitionKey::of(top_level);
+        (crate::multiprocess::site_key(url) != key.0).then_some(key)
+    }
+
+    /// The top-level site, e.g. `https://example.com`
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for PartitionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Shared flag used to abort in-flight requests
+///
+/// Clones share the same flag, so the token handed to a load can be
+/// cancelled from elsewhere (e.g., the stop button).
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Create a token that is not cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Abort every request using this token
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Has the token been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Fail with `NetError::Cancelled` if the token was cancelled
+    pub fn check(&self) -> Result<(), NetError> {
+        if self.is_cancelled() {
+            Err(NetError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Per-request options for `HttpClient::fetch_with`
+#[derive(Debug, Clone, Default)]
+pub struct RequestOptions {
+    pub cache_mode: CacheMode,
+    /// `If-None-Match` validator for revalidation
+    pub if_none_match: Option<String>,
+    /// `If-Modified-Since` validator for revalidation
+    pub if_modified_since: Option<String>,
+    pub cancel: Option<CancellationToken>,
+    /// Page the request is made for, for third-party cookie checks and
+    /// partitioning; `None` for the page itself
+    pub first_party: Option<Url>,
+    /// Bytes of the resource to ask for with a `Range` header; a server
+    /// may still send the whole resource
+    pub range: Option<Range<u64>>,
 }
 
 /// Network errors
@@ -30,6 +327,14 @@ pub enum NetError {
     Timeout,
     NetworkError(String),
     ParseError(String),
+    /// The request was aborted through its cancellation token
+    Cancelled,
+    /// A request interceptor (e.g. the content filter) blocked the URL
+    Blocked(String),
+    /// The server's certificate could not be verified
+    Certificate(String),
+    /// The host name did not resolve to an address
+    NameNotResolved(String),
 }
 
 impl std::fmt::Display for NetError {
@@ -40,6 +345,10 @@ impl std::fmt::Display for NetError {
             NetError::Timeout => write!(f, "Request timed out"),
             NetError::NetworkError(msg) => write!(f, "Network error: {}", msg),
             NetError::ParseError(msg) => write!(f, "Parse error: {}", msg),
+            NetError::Cancelled => write!(f, "Request cancelled"),
+            NetError::Blocked(url) => write!(f, "Request blocked: {}", url),
+            NetError::Certificate(msg) => write!(f, "Certificate error: {}", msg),
+            NetError::NameNotResolved(host) => write!(f, "Could not resolve host: {}", host),
         }
     }
 }
@@ -49,46 +358,278 @@ impl std::error::Error for NetError {}
 impl HttpClient {
     /// Create a new HTTP client
     pub fn new() -> Self {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(30))
-            .user_agent("BrowserEngine/0.1.0")
+        let builder = || {
+            Client::builder()
+                .timeout(Duration::from_secs(30))
+                .user_agent(USER_AGENT)
+                .tls_info(true)
+        };
+        let client = builder().build().expect("Failed to create HTTP client");
+        let insecure_client = builder()
+            .danger_accept_invalid_certs(true)
             .build()
             .expect("Failed to create HTTP client");
 
-        Self { client }
+        Self {
+            client,
+            insecure_client,
+            cookies: Arc::new(Mutex::new(CookieJar::new())),
+            cookie_policy: CookiePolicy::default(),
+            certificates: Arc::new(Mutex::new(CertificateStore::new())),
+            auth: Arc::new(Mutex::new(AuthCache::new())),
+            credential_prompt: None,
+        }
+    }
+
+    /// Credentials that worked this session, per origin
+    pub fn auth_cache(&self) -> Arc<Mutex<AuthCache>> {
+        self.auth.clone()
+    }
+
+    /// Set or remove the hook asked for credentials on `401 Unauthorized`
+    ///
+    /// Without one, 401 responses are returned as they are.
+    pub fn set_credential_prompt(&mut self, prompt: Option<Arc<dyn CredentialPrompt>>) {
+        self.credential_prompt = prompt;
+    }
+
+    /// Certificates servers presented, and the hosts whose invalid
+    /// certificates the user accepted
+    pub fn certificates(&self) -> Arc<Mutex<CertificateStore>> {
+        self.certificates.clone()
+    }
+
+    /// The client to send a request to `url` with
+    fn client_for(&self, url: &Url) -> &Client {
+        let host = url.host_str().unwrap_or("");
+        match self.certificates.lock() {
+            Ok(store) if store.is_overridden(host) => &self.insecure_client,
+            _ => &self.client,
+        }
+    }
+
+    /// Change which requests may send and store cookies
+    pub fn set_cookie_policy(&mut self, policy: CookiePolicy) {
+        self.cookie_policy = policy;
+    }
+
+    /// Current cookie policy
+    pub fn cookie_policy(&self) -> CookiePolicy {
+        self.cookie_policy
+    }
+
+    /// Forget every stored cookie
+    pub fn clear_cookies(&self) {
+        if let Ok(mut jar) = self.cookies.lock() {
+            jar.clear();
+        }
     }
 
     /// Fetch a resource from a URL
     pub fn fetch(&self, url: &Url) -> Result<Response, NetError> {
-        // Make request
-        let response = self.client
-            .get(url.clone())
-            .send()
-            .map_err(|e| NetError::RequestFailed(e.to_string()))?;
+        self.fetch_with(url, &RequestOptions::default())
+    }
+
+    /// Fetch a resource with cache headers and cancellation
+    ///
+    /// The cancellation token is checked before sending and between body
+    /// chunks, so a cancelled load stops reading and returns `Cancelled`.
+    pub fn fetch_with(&self, url: &Url, options: &RequestOptions) -> Result<Response, NetError> {
+        let check = || options.cancel.as_ref().map_or(Ok(()), |c| c.check());
+        check()?;
+
+        let use_cookies = self.cookie_policy.allows(url, options.first_party.as_ref());
+        let mut authorization = self.auth.lock().ok().and_then(|mut auth| auth.authorization("GET", url));
+        // A retry with credentials goes straight to the URL that asked for
+        // them, which redirects may have led to
+        let mut target = url.clone();
+        let (mut prompts, mut retries) = (0, 0);
+        let (mut response, request_headers, sent, headers_received) = loop {
+            let client = self.client_for(&target);
+            let mut request = client.get(target.clone()).header("User-Agent", USER_AGENT);
+            if use_cookies && self.cookie_policy.allows(&target, options.first_party.as_ref()) {
+                request = self.attach_cookies(request, &target, options.first_party.as_ref());
+            }
+            for (name, value) in request_headers(options) {
+                request = request.header(name, value);
+            }
+            if let Some(ref authorization) = authorization {
+                request = request.header("Authorization", authorization.as_str());
+            }
 
-        // Get status and content type
+            // Make request, keeping its headers for DevTools
+            let request = request.build().map_err(|e| NetError::RequestFailed(e.to_string()))?;
+            let request_headers = header_list(request.headers());
+            let sent = Instant::now();
+            let response = client.execute(request).map_err(request_error)?;
+            let headers_received = Instant::now();
+            check()?;
+            if response.status() == reqwest::StatusCode::UNAUTHORIZED && retries < MAX_AUTH_RETRIES {
+                let answered = response.url().clone();
+                // Credentials are not sent on to another origin
+                let sent_credentials = authorization.is_some() && answered.origin() == target.origin();
+                if let Some(retry) = self.authenticate(&answered, response.headers(), sent_credentials, &mut prompts) {
+                    authorization = Some(retry);
+                    target = answered;
+                    retries += 1;
+                    continue;
+                }
+            }
+            break (response, request_headers, sent, headers_received);
+        };
+        record_certificate(&self.certificates, url, response.extensions().get::<reqwest::tls::TlsInfo>());
+
+        // Get status, content type and validators
         let status = response.status().as_u16();
-        let content_type = response
-            .headers()
-            .get("content-type")
-            .and_then(|v| v.to_str().ok())
-            .map(|s| s.to_string())
-            .unwrap_or_default();
-
-        // Read body
-        let body = response
-            .bytes()
-            .map_err(|e| NetError::RequestFailed(e.to_string()))?
-            .to_vec();
+        let final_url = response.url().clone();
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|s| s.to_string())
+        };
+        let content_type = header("content-type").unwrap_or_default();
+        let etag = header("etag");
+        let last_modified = header("last-modified");
+        let headers = header_list(response.headers());
+        if use_cookies {
+            self.store_cookies(&response, url, options.first_party.as_ref());
+        }
+
+        // Read body in chunks so a stop can interrupt large downloads
+        let mut body = Vec::new();
+        let mut chunk = [0u8; 16 * 1024];
+        loop {
+            let n = response
+                .read(&mut chunk)
+                .map_err(|e| NetError::RequestFailed(e.to_string()))?;
+            if n == 0 {
+                break;
+            }
+            body.extend_from_slice(&chunk[..n]);
+            check()?;
+        }
 
         Ok(Response {
             url: url.clone(),
+            final_url,
             status,
             content_type,
             body,
+            etag,
+            last_modified,
+            request_headers,
+            headers,
+            timing: RequestTiming {
+                wait_ms: millis(headers_received - sent),
+                receive_ms: millis(headers_received.elapsed()),
+            },
         })
     }
 
+    /// The `Authorization` header to retry a `401 Unauthorized` response
+    /// from `url` with
+    ///
+    /// A stale Digest nonce is renewed without asking, and credentials kept
+    /// for the realm are tried if they were not sent; otherwise refused
+    /// credentials are forgotten and the prompt is asked, up to
+    /// `MAX_AUTH_PROMPTS` times. `None` keeps the 401 response.
+    fn authenticate(
+        &self,
+        url: &Url,
+        headers: &reqwest::header::HeaderMap,
+        sent_credentials: bool,
+        prompts: &mut usize,
+    ) -> Option<String> {
+        let challenges = headers.get_all("www-authenticate").iter().filter_map(|value| value.to_str().ok());
+        let challenge = AuthChallenge::preferred(challenges)?;
+        {
+            let mut auth = self.auth.lock().ok()?;
+            if sent_credentials && challenge.stale && auth.renew(url, &challenge) {
+                return auth.authorization_for("GET", url, &challenge.realm);
+            }
+            if !sent_credentials {
+                if let Some(authorization) = auth.authorization_for("GET", url, &challenge.realm) {
+                    return Some(authorization);
+                }
+            }
+            auth.forget(url, &challenge.realm);
+        }
+
+        let prompt = self.credential_prompt.as_ref()?;
+        if *prompts >= MAX_AUTH_PROMPTS {
+            return None;
+        }
+        *prompts += 1;
+        let request = AuthRequest { url: url.clone(), challenge: challenge.clone(), retry: sent_credentials };
+        let credentials = prompt.credentials(&request)?;
+        let realm = challenge.realm.clone();
+        let mut auth = self.auth.lock().ok()?;
+        auth.store(url, challenge, credentials);
+        auth.authorization_for("GET", url, &realm)
+    }
+
+    /// Send a GET and return the response as soon as its headers arrive
+    ///
+    /// The body is left unread for the caller to consume incrementally.
+    /// `timeout` replaces the client's overall timeout, which would
+    /// otherwise cut long-lived streams short.
+    pub fn fetch_stream(
+        &self,
+        url: &Url,
+        headers: &[(&str, String)],
+        options: &RequestOptions,
+        timeout: Duration,
+    ) -> Result<StreamingResponse, NetError> {
+        if let Some(ref cancel) = options.cancel {
+            cancel.check()?;
+        }
+
+        let use_cookies = self.cookie_policy.allows(url, options.first_party.as_ref());
+        let mut request = self.client_for(url).get(url.clone()).timeout(timeout);
+        if use_cookies {
+            request = self.attach_cookies(request, url, options.first_party.as_ref());
+        }
+        for (name, value) in headers {
+            request = request.header(*name, value.as_str());
+        }
+        if let Some(authorization) = self.auth.lock().ok().and_then(|mut auth| auth.authorization("GET", url)) {
+            request = request.header("Authorization", authorization);
+        }
+
+        let response = request.send().map_err(request_error)?;
+        if use_cookies {
+            self.store_cookies(&response, url, options.first_party.as_ref());
+        }
+
+        Ok(StreamingResponse {
+            url: url.clone(),
+            status: response.status().as_u16(),
+            content_type: response
+                .headers()
+                .get("content-type")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_string(),
+            body: response,
+        })
+    }
+
+    /// Add the `Cookie` header for `url` to a request made for `first_party`
+    fn attach_cookies(&self, request: RequestBuilder, url: &Url, first_party: Option<&Url>) -> RequestBuilder {
+        match cookie_header(&self.cookies, url, PartitionKey::for_request(url, first_party).as_ref()) {
+            Some(header) => request.header("Cookie", header),
+            None => request,
+        }
+    }
+
+    /// Store the cookies a response to `url` made for `first_party` sets
+    fn store_cookies(&self, response: &reqwest::blocking::Response, url: &Url, first_party: Option<&Url>) {
+        let partition = PartitionKey::for_request(url, first_party);
+        store_cookies(&self.cookies, response.headers(), url, partition.as_ref());
+    }
+
     /// Fetch and return as UTF-8 string (for HTML/CSS)
     pub fn fetch_text(&self, url: &Url) -> Result<String, NetError> {
         let response = self.fetch(url)?;
@@ -104,10 +645,118 @@ impl Default for HttpClient {
     }
 }
 
+/// Cache, validator and range headers a request sends for its options
+fn request_headers(options: &RequestOptions) -> Vec<(&'static str, String)> {
+    let mut headers = Vec::new();
+    match options.cache_mode {
+        CacheMode::Default => {}
+        CacheMode::Revalidate => headers.push(("Cache-Control", "max-age=0".to_string())),
+        CacheMode::Reload => {
+            headers.push(("Cache-Control", "no-cache".to_string()));
+            headers.push(("Pragma", "no-cache".to_string()));
+        }
+    }
+    // Stale entries are revalidated in any mode but a reload
+    if options.cache_mode != CacheMode::Reload {
+        if let Some(ref etag) = options.if_none_match {
+            headers.push(("If-None-Match", etag.clone()));
+        }
+        if let Some(ref date) = options.if_modified_since {
+            headers.push(("If-Modified-Since", date.clone()));
+        }
+    }
+    if let Some(range) = options.range.as_ref().filter(|range| !range.is_empty()) {
+        headers.push(("Range", format!("bytes={}-{}", range.start, range.end - 1)));
+    }
+    headers
+}
+
+/// A failed request's error, telling timeouts, unresolved hosts,
+/// connection failures and certificate failures apart
+pub(super) fn request_error(error: reqwest::Error) -> NetError {
+    if error.is_timeout() {
+        return NetError::Timeout;
+    }
+    // The resolver's or TLS backend's error is somewhere down the source chain
+    let mut source = std::error::Error::source(&error);
+    let mut innermost = None;
+    while let Some(cause) = source {
+        let message = cause.to_string().to_ascii_lowercase();
+        if message.contains("certificate") {
+            return NetError::Certificate(cause.to_string());
+        }
+        if message.contains("dns error") || message.contains("failed to lookup address") {
+            let host = error.url().and_then(Url::host_str).unwrap_or_default();
+            return NetError::NameNotResolved(host.to_string());
+        }
+        innermost = Some(cause);
+        source = cause.source();
+    }
+    match innermost {
+        Some(cause) if error.is_connect() => NetError::NetworkError(cause.to_string()),
+        _ => NetError::RequestFailed(error.to_string()),
+    }
+}
+
+/// Remember the certificate a response to `url` came with
+pub(super) fn record_certificate(certificates: &Mutex<CertificateStore>, url: &Url, info: Option<&reqwest::tls::TlsInfo>) {
+    let certificate = info.and_then(|info| info.peer_certificate()).and_then(Certificate::from_der);
+    if let (Some(certificate), Some(host), Ok(mut store)) = (certificate, url.host_str(), certificates.lock()) {
+        store.record(host, certificate);
+    }
+}
+
+/// `Cookie` header for a request to `url` in a partition, if any cookies apply
+fn cookie_header(cookies: &Mutex<CookieJar>, url: &Url, partition: Option<&PartitionKey>) -> Option<String> {
+    let host = url.host_str().unwrap_or("").to_ascii_lowercase();
+    let secure = url.scheme() == "https";
+    cookies.lock().ok().and_then(|jar| jar.cookie_header(&host, url.path(), secure, partition))
+}
+
+/// Store the cookies a response to `url` sets, in a partition
+fn store_cookies(
+    cookies: &Mutex<CookieJar>,
+    headers: &reqwest::header::HeaderMap,
+    url: &Url,
+    partition: Option<&PartitionKey>,
+) {
+    let host = url.host_str().unwrap_or("").to_ascii_lowercase();
+    if let Ok(mut jar) = cookies.lock() {
+        let set_cookies = headers.get_all("set-cookie");
+        for mut cookie in set_cookies.iter().filter_map(|v| Cookie::parse(v.to_str().ok()?, &host)) {
+            cookie.partition = partition.cloned();
+            jar.set_cookie(cookie);
+        }
+    }
+}
+
+/// Header names and values, for display; values that are not text are left out
+fn header_list(headers: &reqwest::header::HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.as_str().to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// A page load started by the navigator
+#[derive(Debug, Clone)]
+pub struct LoadRequest {
+    pub url: Url,
+    pub cache_mode: CacheMode,
+    /// Cancelled when the load is stopped or replaced by another load
+    pub cancel: CancellationToken,
+}
+
 /// Navigation history manager
 pub struct Navigator {
     history: Vec<Url>,
     current_index: usize,
+    /// Cancellation token of the in-flight load
+    in_flight: Option<CancellationToken>,
 }
 
 impl Navigator {
@@ -116,6 +765,7 @@ impl Navigator {
         Self {
             history: Vec::new(),
             current_index: 0,
+            in_flight: None,
         }
     }
 
@@ -163,6 +813,47 @@ impl Navigator {
     pub fn can_go_forward(&self) -> bool {
         self.current_index + 1 < self.history.len()
     }
+
+    /// Start loading a URL, aborting any load already in flight
+    pub fn begin_load(&mut self, url: Url, cache_mode: CacheMode) -> LoadRequest {
+        self.stop();
+        let cancel = CancellationToken::new();
+        self.in_flight = Some(cancel.clone());
+        LoadRequest { url, cache_mode, cancel }
+    }
+
+    /// Reload the current page, revalidating cached resources
+    pub fn reload(&mut self) -> Option<LoadRequest> {
+        let url = self.current()?.clone();
+        Some(self.begin_load(url, CacheMode::Revalidate))
+    }
+
+    /// Reload the current page, bypassing the cache
+    pub fn hard_reload(&mut self) -> Option<LoadRequest> {
+        let url = self.current()?.clone();
+        Some(self.begin_load(url, CacheMode::Reload))
+    }
+
+    /// Abort the in-flight load; returns false if nothing was loading
+    pub fn stop(&mut self) -> bool {
+        match self.in_flight.take() {
+            Some(cancel) => {
+                cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Mark the in-flight load as complete
+    pub fn finish_load(&mut self) {
+        self.in_flight = None;
+    }
+
+    /// Is a load in flight
+    pub fn is_loading(&self) -> bool {
+        self.in_flight.is_some()
+    }
 }
 
 impl Default for Navigator {
@@ -223,4 +914,126 @@ mod tests {
         assert_eq!(nav.current().unwrap(), &url3);
         assert!(!nav.can_go_forward());
     }
+
+    #[test]
+    fn test_navigator_reload_and_stop() {
+        let mut nav = Navigator::new();
+        assert!(nav.reload().is_none());
+        assert!(!nav.stop());
+
+        nav.navigate_to(Url::parse("http://example.com/").unwrap());
+        let reload = nav.reload().unwrap();
+        assert_eq!(reload.cache_mode, CacheMode::Revalidate);
+        assert!(nav.is_loading());
+
+        // Starting another load aborts the first
+        let hard = nav.hard_reload().unwrap();
+        assert_eq!(hard.cache_mode, CacheMode::Reload);
+        assert!(reload.cancel.is_cancelled());
+
+        assert!(nav.stop());
+        assert!(hard.cancel.is_cancelled());
+        assert!(!nav.is_loading());
+    }
+
+    #[test]
+    fn test_cancelled_fetch_is_not_sent() {
+        let client = HttpClient::new();
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let options = RequestOptions {
+            cancel: Some(cancel),
+            ..Default::default()
+        };
+        let result = client.fetch_with(&Url::parse("http://example.invalid/").unwrap(), &options);
+        assert!(matches!(result, Err(NetError::Cancelled)));
+        assert_eq!(CacheMode::from_str("no-cache"), CacheMode::Revalidate);
+    }
+
+    #[test]
+    fn test_content_range() {
+        let url = Url::parse("https://example.com/video.mp4").unwrap();
+        let response = |status: u16, content_range: &str| Response {
+            url: url.clone(),
+            final_url: url.clone(),
+            status,
+            content_type: "video/mp4".to_string(),
+            body: Vec::new(),
+            etag: None,
+            last_modified: None,
+            request_headers: Vec::new(),
+            headers: vec![("content-range".to_string(), content_range.to_string())],
+            timing: RequestTiming::default(),
+        };
+        assert_eq!(response(206, "bytes 100-199/1000").content_range(), Some((100, Some(1000))));
+        assert_eq!(response(206, "bytes 0-99/*").content_range(), Some((0, None)));
+        // A server ignoring the range sends the whole resource
+        assert_eq!(response(200, "bytes 100-199/1000").content_range(), None);
+    }
+
+    #[test]
+    fn test_cookie_policy() {
+        let page = Url::parse("https://www.example.com/article").unwrap();
+        let same_site = Url::parse("https://static.example.com/app.css").unwrap();
+        let tracker = Url::parse("https://tracker.example.net/pixel").unwrap();
+
+        assert!(CookiePolicy::AllowAll.allows(&tracker, Some(&page)));
+        assert!(!CookiePolicy::BlockAll.allows(&page, None));
+        let policy = CookiePolicy::BlockThirdParty;
+        assert!(policy.allows(&page, None));
+        assert!(policy.allows(&same_site, Some(&page)));
+        assert!(!policy.allows(&tracker, Some(&page)));
+
+        assert_eq!(CookiePolicy::from_str(policy.as_str()), Some(policy));
+        let uk_page = Url::parse("https://shop.example.co.uk/").unwrap();
+        let uk_other = Url::parse("https://other.co.uk/").unwrap();
+        assert!(!policy.allows(&uk_other, Some(&uk_page)));
+        let mut client = HttpClient::new();
+        client.set_cookie_policy(CookiePolicy::BlockAll);
+        assert_eq!(client.cookie_policy(), CookiePolicy::BlockAll);
+    }
+
+    #[test]
+    fn test_registrable_domain() {
+        let domain = |url: &str| registrable_domain(&Url::parse(url).unwrap());
+        assert_eq!(domain("https://www.example.com/").as_deref(), Some("example.com"));
+        assert_eq!(domain("https://a.b.example.co.uk/").as_deref(), Some("example.co.uk"));
+        assert_eq!(domain("http://localhost:8080/").as_deref(), Some("localhost"));
+        assert_eq!(domain("http://127.0.0.1/").as_deref(), Some("127.0.0.1"));
+
+        // Suffixes from the list's private section are each tenant's own site
+        assert_eq!(domain("https://alice.github.io/").as_deref(), Some("alice.github.io"));
+        assert_eq!(domain("https://my-bucket.s3.amazonaws.com/x").as_deref(), Some("my-bucket.s3.amazonaws.com"));
+        assert_eq!(domain("https://app.azurewebsites.net/").as_deref(), Some("app.azurewebsites.net"));
+        assert_eq!(domain("https://d111.cloudfront.net/").as_deref(), Some("d111.cloudfront.net"));
+        let page = Url::parse("https://alice.github.io/").unwrap();
+        let other = Url::parse("https://mallory.github.io/").unwrap();
+        assert_ne!(site(&page), site(&other));
+    }
+
+    #[test]
+    fn test_partition_key() {
+        let page = Url::parse("https://www.example.com/article").unwrap();
+        let key = PartitionKey::of(&page);
+        assert_eq!(key.as_str(), "https://example.com");
+        let same_site = Url::parse("https://static.example.com/app.css").unwrap();
+        assert_eq!(PartitionKey::for_request(&same_site, Some(&page)), None);
+        assert_eq!(PartitionKey::for_request(&same_site, None), None);
+        let tracker = Url::parse("https://tracker.example.net/pixel").unwrap();
+        assert_eq!(PartitionKey::for_request(&tracker, Some(&page)), Some(key));
+        // The scheme is part of the site
+        let insecure = Url::parse("http://www.example.com/").unwrap();
+        assert!(PartitionKey::for_request(&insecure, Some(&page)).is_some());
+
+        // Tenants of a shared hosting suffix are separate top-level sites
+        let alice = Url::parse("https://alice.github.io/blog/").unwrap();
+        let mallory = Url::parse("https://mallory.github.io/").unwrap();
+        assert_ne!(PartitionKey::of(&alice), PartitionKey::of(&mallory));
+        assert_eq!(PartitionKey::of(&alice).as_str(), "https://alice.github.io");
+        let embed = Url::parse("https://widgets.example.net/frame").unwrap();
+        assert_ne!(PartitionKey::for_request(&embed, Some(&alice)), PartitionKey::for_request(&embed, Some(&mallory)));
+        let bucket = Url::parse("https://one.s3.amazonaws.com/a.js").unwrap();
+        let other_bucket = Url::parse("https://two.s3.amazonaws.com/").unwrap();
+        assert!(PartitionKey::for_request(&bucket, Some(&other_bucket)).is_some());
+    }
 }
@@ -1,10 +1,26 @@
+use std::sync::Arc;
 use url::Url;
 
-use super::{NetError, ResourceLoader};
+use super::{CacheMode, CancellationToken, CredentialPrompt, NetError, ResourceLoader};
 use crate::dom::Node;
 use crate::html::HtmlParser;
 use crate::css::{Stylesheet, CssParser};
 
+/// Lifecycle of a page load, reported while it progresses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadEvent {
+    /// The document request was sent
+    Started(Url),
+    /// The document arrived and was parsed
+    DocumentLoaded,
+    /// The document needs this many subresources (external stylesheets)
+    SubresourcesDiscovered(usize),
+    /// A subresource finished loading, successfully or not
+    SubresourceFinished(Url),
+    /// The page and its subresources have loaded
+    Finished,
+}
+
 /// Page loader that fetches and processes web pages
 pub struct PageLoader {
     resource_loader: ResourceLoader,
@@ -27,17 +43,61 @@ impl PageLoader {
 
     /// Load a complete page: fetch HTML, parse DOM, fetch CSS, extract images
     pub fn load_page(&self, url: &Url) -> Result<LoadedPage, NetError> {
+        self.load_page_with(url, CacheMode::Default, None)
+    }
+
+    /// Load a page with a cache mode (reload / hard reload) and cancellation
+    ///
+    /// The cache mode applies to the document and its stylesheets.
+    pub fn load_page_with(
+        &self,
+        url: &Url,
+        cache_mode: CacheMode,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<LoadedPage, NetError> {
+        self.load_page_observed(url, cache_mode, cancel, &mut |_| {})
+    }
+
+    /// Load a page, reporting lifecycle events (e.g. for a progress indicator)
+    pub fn load_page_observed(
+        &self,
+        url: &Url,
+        cache_mode: CacheMode,
+        cancel: Option<&CancellationToken>,
+        on_event: &mut dyn FnMut(LoadEvent),
+    ) -> Result<LoadedPage, NetError> {
+        on_event(LoadEvent::Started(url.clone()));
+
         // Fetch HTML
-        let html_text = self.resource_loader.load_text(url)?;
+        let html_text = self.resource_loader.load_with(url, cache_mode, cancel)?.as_text()?;
+        self.process_html(&html_text, url, cache_mode, cancel, on_event)
+    }
 
+    /// Load a page from HTML already at hand, fetching its subresources relative to `url`
+    pub fn load_html(&self, html: &str, url: &Url) -> Result<LoadedPage, NetError> {
+        self.process_html(html, url, CacheMode::Default, None, &mut |_| {})
+    }
+
+    /// Parse a document and load what it needs
+    fn process_html(
+        &self,
+        html_text: &str,
+        url: &Url,
+        cache_mode: CacheMode,
+        cancel: Option<&CancellationToken>,
+        on_event: &mut dyn FnMut(LoadEvent),
+    ) -> Result<LoadedPage, NetError> {
         // Parse HTML to DOM
-        let dom = HtmlParser::parse(&html_text);
+        let dom = HtmlParser::parse(html_text);
+        on_event(LoadEvent::DocumentLoaded);
+        on_event(LoadEvent::SubresourcesDiscovered(count_stylesheet_links(&dom)));
 
         // Extract and fetch CSS resources
-        let stylesheets = self.extract_and_load_css(&dom, url)?;
+        let stylesheets = self.extract_and_load_css(&dom, url, cache_mode, cancel, on_event)?;
         
         // Extract image URLs
         let image_urls = self.extract_image_urls(&dom, url);
+        on_event(LoadEvent::Finished);
 
         Ok(LoadedPage {
             url: url.clone(),
@@ -48,11 +108,18 @@ impl PageLoader {
     }
 
     /// Extract CSS from <style> tags and <link> tags, then fetch external stylesheets
-    fn extract_and_load_css(&self, dom: &Node, base_url: &Url) -> Result<Vec<Stylesheet>, NetError> {
+    fn extract_and_load_css(
+        &self,
+        dom: &Node,
+        base_url: &Url,
+        cache_mode: CacheMode,
+        cancel: Option<&CancellationToken>,
+        on_event: &mut dyn FnMut(LoadEvent),
+    ) -> Result<Vec<Stylesheet>, NetError> {
         let mut stylesheets = Vec::new();
 
         // Recursively find style and link elements
-        self.collect_css_from_node(dom, base_url, &mut stylesheets)?;
+        self.collect_css_from_node(dom, base_url, cache_mode, cancel, on_event, &mut stylesheets)?;
 
         Ok(stylesheets)
     }
@@ -62,6 +129,9 @@ impl PageLoader {
         &self,
         node: &Node,
         base_url: &Url,
+        cache_mode: CacheMode,
+        cancel: Option<&CancellationToken>,
+        on_event: &mut dyn FnMut(LoadEvent),
         stylesheets: &mut Vec<Stylesheet>,
     ) -> Result<(), NetError> {
         if let Some(elem) = node.element_data() {
@@ -76,22 +146,25 @@ impl PageLoader {
             }
 
             // Handle <link rel="stylesheet"> tags
-            if elem.tag_name.to_lowercase() == "link" {
-                if let Some(rel) = elem.attributes.get("rel") {
-                    if rel.to_lowercase() == "stylesheet" {
-                        if let Some(href) = elem.attributes.get("href") {
-                            // Resolve relative URL
-                            if let Ok(css_url) = base_url.join(href) {
-                                // Fetch CSS
-                                match self.resource_loader.load_text(&css_url) {
-                                    Ok(css_text) => {
-                                        let stylesheet = CssParser::parse(&css_text);
-                                        stylesheets.push(stylesheet);
-                                    }
-                                    Err(_) => {
-                                        // Silently ignore CSS loading errors
-                                    }
-                                }
+            if is_stylesheet_link(node) {
+                if let Some(href) = elem.attributes.get("href") {
+                    // Resolve relative URL
+                    if let Ok(css_url) = base_url.join(href) {
+                        // Fetch CSS
+                        let css = self
+                            .resource_loader
+                            .load_with(&css_url, cache_mode, cancel)
+                            .and_then(|r| r.as_text());
+                        on_event(LoadEvent::SubresourceFinished(css_url));
+                        match css {
+                            Ok(css_text) => {
+                                let stylesheet = CssParser::parse(&css_text);
+                                stylesheets.push(stylesheet);
+                            }
+                            // A stop aborts the whole page load
+                            Err(NetError::Cancelled) => return Err(NetError::Cancelled),
+                            Err(_) => {
+                                // Silently ignore CSS loading errors
                             }
                         }
                     }
@@ -101,7 +174,7 @@ impl PageLoader {
 
         // Recursively process children
         for child in &node.children {
-            self.collect_css_from_node(child, base_url, stylesheets)?;
+            self.collect_css_from_node(child, base_url, cache_mode, cancel, on_event, stylesheets)?;
         }
 
         Ok(())
@@ -125,6 +198,11 @@ impl PageLoader {
         &self.resource_loader
     }
 
+    /// Set or remove the hook asked for credentials on `401 Unauthorized`
+    pub fn set_credential_prompt(&mut self, prompt: Option<Arc<dyn CredentialPrompt>>) {
+        self.resource_loader.set_credential_prompt(prompt);
+    }
+
     /// Extract image URLs from <img> tags
     fn extract_image_urls(&self, dom: &Node, base_url: &Url) -> Vec<Url> {
         let mut urls = Vec::new();
@@ -164,6 +242,20 @@ impl Default for PageLoader {
     }
 }
 
+/// Is this a `<link rel="stylesheet">` element
+fn is_stylesheet_link(node: &Node) -> bool {
+    node.element_data().is_some_and(|elem| {
+        elem.tag_name.eq_ignore_ascii_case("link")
+            && elem.attributes.get("rel").is_some_and(|rel| rel.eq_ignore_ascii_case("stylesheet"))
+    })
+}
+
+/// Number of external stylesheets a document links to
+fn count_stylesheet_links(node: &Node) -> usize {
+    let own = usize::from(is_stylesheet_link(node) && node.element_data().is_some_and(|e| e.attributes.contains_key("href")));
+    own + node.children.iter().map(count_stylesheet_links).sum::<usize>()
+}
+
 /// A loaded page with DOM, stylesheets, and image URLs
 pub struct LoadedPage {
     pub url: Url,
@@ -175,15 +267,11 @@ pub struct LoadedPage {
 impl LoadedPage {
     /// Get a merged stylesheet from all loaded stylesheets
     pub fn merged_stylesheet(&self) -> Stylesheet {
-        if !self.stylesheets.is_empty() {
-            let mut all_rules = Vec::new();
-            for stylesheet in &self.stylesheets {
-                all_rules.extend(stylesheet.rules.clone());
-            }
-            Stylesheet::new(all_rules)
-        } else {
-            Stylesheet::new(Vec::new())
+        let mut merged = Stylesheet::new(Vec::new());
+        for stylesheet in &self.stylesheets {
+            merged.append(stylesheet.clone());
         }
+        merged
     }
 }
 
@@ -251,6 +339,18 @@ mod tests {
         assert_eq!(stylesheet.rules.len(), 2);
     }
     
+    #[test]
+    fn test_count_stylesheet_links() {
+        let html = r#"<html><head>
+            <link rel="stylesheet" href="a.css">
+            <link rel="icon" href="favicon.ico">
+            <link rel="Stylesheet" href="b.css">
+            <style>p { color: red; }</style>
+        </head><body></body></html>"#;
+        let dom = HtmlParser::parse(html);
+        assert_eq!(count_stylesheet_links(&dom), 2);
+    }
+
     #[test]
     fn test_extract_image_urls() {
         use std::collections::HashMap;
@@ -1,8 +1,17 @@
 use std::collections::HashMap;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use url::Url;
 
-use super::{HttpClient, NetError};
+use super::http_cache::{self, CacheKey, CacheStats, DiskCache, Freshness};
+use super::{
+    AuthCache, CacheMode, CancellationToken, CertificateStore, CookiePolicy, CredentialPrompt, HttpClient, NetError, PartitionKey, RequestOptions,
+    RequestTiming, ResourceFetcher, Response,
+};
+use crate::storage::{CookieJar, NetworkSiteData};
 
 /// Represents a resource type
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -45,17 +54,34 @@ impl ResourceType {
             ResourceType::Other
         }
     }
+
+    /// Type from a response's content type, or its URL when it has none
+    pub fn detect(content_type: &str, url: &Url) -> Self {
+        if content_type.is_empty() {
+            ResourceType::from_extension(url)
+        } else {
+            ResourceType::from_content_type(content_type)
+        }
+    }
 }
 
 /// Cached resource data
 #[derive(Debug, Clone)]
 pub struct CachedResource {
     pub url: Url,
+    /// Top-level site the resource was loaded for, if another site
+    pub partition: Option<PartitionKey>,
     pub resource_type: ResourceType,
     pub content_type: String,
     pub data: Vec<u8>,
     /// Timestamp for LRU eviction (system time in seconds)
     pub last_accessed: u64,
+    /// `ETag` used to revalidate the entry
+    pub etag: Option<String>,
+    /// `Last-Modified` used to revalidate the entry
+    pub last_modified: Option<String>,
+    /// How long the entry may be used without revalidating
+    pub freshness: Freshness,
 }
 
 impl CachedResource {
@@ -66,10 +92,55 @@ impl CachedResource {
     }
 }
 
+/// What went over the network to load a resource, for DevTools
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Transfer {
+    /// URL that answered, after redirects
+    pub url: Option<Url>,
+    pub status: u16,
+    pub request_headers: Vec<(String, String)>,
+    pub response_headers: Vec<(String, String)>,
+    pub timing: RequestTiming,
+}
+
+/// A request the loader is about to make, as seen by a `RequestInterceptor`
+#[derive(Debug, Clone, Copy)]
+pub struct InterceptedRequest<'a> {
+    pub url: &'a Url,
+    /// Page the request is made for; the URL itself for a page load
+    pub first_party: &'a Url,
+    /// Type guessed from the URL, as the response has not arrived
+    pub resource_type: ResourceType,
+}
+
+impl InterceptedRequest<'_> {
+    /// Is the request to another site than the page it is made for
+    pub fn is_third_party(&self) -> bool {
+        super::registrable_domain(self.url) != super::registrable_domain(self.first_party)
+    }
+}
+
+/// Hook that sees every request before the cache or network, and may block it
+pub trait RequestInterceptor: Send + Sync {
+    /// Should the request be blocked
+    fn should_block(&self, request: &InterceptedRequest<'_>) -> bool;
+}
+
+/// Several interceptors: a request is blocked if any of them blocks it
+impl RequestInterceptor for Vec<Arc<dyn RequestInterceptor>> {
+    fn should_block(&self, request: &InterceptedRequest<'_>) -> bool {
+        self.iter().any(|interceptor| interceptor.should_block(request))
+    }
+}
+
 /// Resource loader with LRU caching
 pub struct ResourceLoader {
     client: HttpClient,
     cache: Arc<Mutex<ResourceCache>>,
+    /// Checks requests before they are made
+    interceptor: Option<Arc<dyn RequestInterceptor>>,
+    /// Page requests are made for, as interceptors see it
+    first_party: Option<Url>,
 }
 
 impl ResourceLoader {
@@ -78,6 +149,8 @@ impl ResourceLoader {
         Self {
             client: HttpClient::new(),
             cache: Arc::new(Mutex::new(ResourceCache::new(cache_size))),
+            interceptor: None,
+            first_party: None,
         }
     }
 
@@ -86,42 +159,106 @@ impl ResourceLoader {
         Self::new(50 * 1024 * 1024) // 50 MB
     }
 
+    /// Change which requests may send and store cookies
+    pub fn set_cookie_policy(&mut self, policy: CookiePolicy) {
+        self.client.set_cookie_policy(policy);
+    }
+
+    /// Set or remove the hook that may block requests
+    pub fn set_interceptor(&mut self, interceptor: Option<Arc<dyn RequestInterceptor>>) {
+        self.interceptor = interceptor;
+    }
+
+    /// Set or remove the hook asked for credentials on `401 Unauthorized`
+    pub fn set_credential_prompt(&mut self, prompt: Option<Arc<dyn CredentialPrompt>>) {
+        self.client.set_credential_prompt(prompt);
+    }
+
+    /// Set the page the following requests are made for
+    ///
+    /// `None` treats each request as its own first party.
+    pub fn set_first_party(&mut self, page: Option<Url>) {
+        self.first_party = page;
+    }
+
+    /// Fail with `NetError::Blocked` if the interceptor blocks a request
+    fn intercept(&self, url: &Url) -> Result<(), NetError> {
+        let Some(interceptor) = &self.interceptor else {
+            return Ok(());
+        };
+        let request = InterceptedRequest {
+            url,
+            first_party: self.first_party.as_ref().unwrap_or(url),
+            resource_type: ResourceType::from_extension(url),
+        };
+        if interceptor.should_block(&request) {
+            return Err(NetError::Blocked(url.to_string()));
+        }
+        Ok(())
+    }
+
     /// Load a resource, using cache if available
     pub fn load(&self, url: &Url) -> Result<CachedResource, NetError> {
-        // Check cache first
-        {
-            let mut cache = self.cache.lock().unwrap();
-            if let Some(resource) = cache.get(url) {
-                return Ok(resource);
-            }
+        self.load_with(url, CacheMode::Default, None)
+    }
+
+    /// Load a resource with a cache mode and optional cancellation
+    ///
+    /// `Default` uses a fresh cached entry and revalidates a stale one;
+    /// `Revalidate` sends the cached entry's validators even when it is
+    /// fresh, unless it is `immutable`, and keeps the entry on `304 Not
+    /// Modified`; `Reload` ignores the cache but stores the new response.
+    pub fn load_with(
+        &self,
+        url: &Url,
+        cache_mode: CacheMode,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<CachedResource, NetError> {
+        self.load_traced(url, cache_mode, cancel).map(|(resource, _)| resource)
+    }
+
+    /// Like `load_with`, also returning the network transfer; there is
+    /// none when the resource came from the cache without a request
+    pub fn load_traced(
+        &self,
+        url: &Url,
+        cache_mode: CacheMode,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<(CachedResource, Option<Transfer>), NetError> {
+        if let Some(cancel) = cancel {
+            cancel.check()?;
         }
+        self.intercept(url)?;
 
-        // Fetch from network
-        let response = self.client.fetch(url)?;
-        
-        // Determine resource type
-        let resource_type = if !response.content_type.is_empty() {
-            ResourceType::from_content_type(&response.content_type)
-        } else {
-            ResourceType::from_extension(&response.url)
+        let partition = PartitionKey::for_request(url, self.first_party.as_ref());
+        let cached = match self.cache.lock().unwrap().lookup(url, partition.as_ref(), cache_mode) {
+            Lookup::Fresh(resource) => return Ok((resource, None)),
+            Lookup::Validate(cached) => cached,
         };
 
-        // Create cached resource
-        let resource = CachedResource {
-            url: response.url.clone(),
-            resource_type,
-            content_type: response.content_type,
-            data: response.body,
-            last_accessed: current_timestamp(),
+        // Fetch from network
+        let options = RequestOptions {
+            cache_mode,
+            if_none_match: cached.as_ref().and_then(|c| c.etag.clone()),
+            if_modified_since: cached.as_ref().and_then(|c| c.last_modified.clone()),
+            cancel: cancel.cloned(),
+            first_party: self.first_party.clone(),
+            range: None,
         };
+        let response = match self.client.fetch_with(url, &options) {
+            Ok(response) => response,
+            Err(error) => return stale_if_unreachable(cached, cache_mode, error).map(|resource| (resource, None)),
+        };
+        Ok(store_response(&self.cache, response, cached, partition))
+    }
 
-        // Store in cache
-        {
-            let mut cache = self.cache.lock().unwrap();
-            cache.put(response.url, resource.clone());
-        }
-
-        Ok(resource)
+    /// Fetch part of a resource from the network, bypassing the cache
+    ///
+    /// For media, which is read a range at a time as it plays.
+    pub fn load_range(&self, url: &Url, range: Range<u64>) -> Result<Response, NetError> {
+        self.intercept(url)?;
+        let options = RequestOptions { range: Some(range), first_party: self.first_party.clone(), ..RequestOptions::default() };
+        self.client.fetch_with(url, &options)
     }
 
     /// Load a resource and return as UTF-8 text
@@ -136,6 +273,13 @@ impl ResourceLoader {
         cache.clear();
     }
 
+    /// Drop cached resources not used for `unused_for` from memory, returning
+    /// how many were dropped; those on disk stay
+    pub fn evict_unused(&self, unused_for: Duration) -> usize {
+        let mut cache = self.cache.lock().unwrap();
+        cache.evict_accessed_before(current_timestamp().saturating_sub(unused_for.as_secs()))
+    }
+
     /// Get current cache size in bytes
     pub fn cache_size(&self) -> usize {
         let cache = self.cache.lock().unwrap();
@@ -147,16 +291,168 @@ impl ResourceLoader {
         let cache = self.cache.lock().unwrap();
         cache.entries.len()
     }
+
+    /// Keep cached responses in `dir` too, so they survive restarts
+    ///
+    /// Entries in memory stay bounded by the loader's cache size; those on
+    /// disk by `max_size` bytes.
+    pub fn open_disk_cache(&self, dir: &Path, max_size: usize) -> io::Result<()> {
+        let disk = DiskCache::open(dir, max_size)?;
+        self.cache.lock().unwrap().disk = Some(disk);
+        Ok(())
+    }
+
+    /// How requests have been served from the cache so far
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.lock().unwrap().stats
+    }
+
+    /// An asynchronous fetcher sharing this loader's cache, cookies, certificates and credentials
+    pub fn fetcher(&self) -> ResourceFetcher {
+        ResourceFetcher::new(
+            self.cache.clone(),
+            self.client.cookies.clone(),
+            self.client.certificates(),
+            self.client.auth_cache(),
+        )
+    }
+
+    /// Certificates servers presented, and the hosts whose invalid
+    /// certificates the user accepted
+    pub fn certificates(&self) -> Arc<Mutex<CertificateStore>> {
+        self.client.certificates()
+    }
+
+    /// Credentials that worked this session, per origin
+    pub fn auth_cache(&self) -> Arc<Mutex<AuthCache>> {
+        self.client.auth_cache()
+    }
+
+    /// Handle on the HTTP cache and cookies for the storage manager
+    pub fn site_data(&self) -> SiteData {
+        SiteData {
+            cache: self.cache.clone(),
+            cookies: self.client.cookies.clone(),
+        }
+    }
 }
 
-/// Internal LRU cache implementation
-struct ResourceCache {
+/// The loader's HTTP cache and cookies, as seen by the storage manager
+pub struct SiteData {
+    cache: Arc<Mutex<ResourceCache>>,
+    cookies: Arc<Mutex<CookieJar>>,
+}
+
+impl NetworkSiteData for SiteData {
+    fn http_cache_usage(&self, origin: &Url) -> usize {
+        self.cache.lock().map_or(0, |cache| cache.usage_for(origin))
+    }
+
+    fn clear_http_cache(&self, origin: &Url) {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.clear_origin(origin);
+        }
+    }
+
+    fn cookie_usage(&self, host: &str) -> usize {
+        self.cookies.lock().map_or(0, |jar| jar.size_for_host(host))
+    }
+
+    fn clear_cookies(&self, host: &str) {
+        if let Ok(mut jar) = self.cookies.lock() {
+            jar.remove_for_host(host);
+        }
+    }
+}
+
+/// The stale `cached` copy a normal load uses when the server could not be
+/// reached, unless it must be revalidated; otherwise the load's error
+pub(super) fn stale_if_unreachable(
+    cached: Option<CachedResource>,
+    cache_mode: CacheMode,
+    error: NetError,
+) -> Result<CachedResource, NetError> {
+    let unreachable = matches!(error, NetError::Timeout | NetError::NetworkError(_) | NetError::NameNotResolved(_));
+    match cached {
+        Some(resource) if unreachable && cache_mode == CacheMode::Default && resource.freshness.may_serve_stale() => {
+            Ok(resource)
+        }
+        _ => Err(error),
+    }
+}
+
+/// The resource a response brings, cached if it may be, with its transfer
+///
+/// A `304 Not Modified` response brings the `cached` copy it revalidated,
+/// fresh again. New entries go in `partition`.
+pub(super) fn store_response(
+    cache: &Mutex<ResourceCache>,
+    mut response: Response,
+    cached: Option<CachedResource>,
+    partition: Option<PartitionKey>,
+) -> (CachedResource, Option<Transfer>) {
+    let now = current_timestamp();
+    let mut cache = cache.lock().unwrap();
+    if response.is_not_modified() {
+        if let Some(mut resource) = cached {
+            resource.freshness = resource.freshness.revalidated(&response.headers, now);
+            resource.last_accessed = now;
+            cache.stats.revalidated += 1;
+            cache.put(resource.url.clone(), resource.clone());
+            return (resource, Some(Transfer::from(response)));
+        }
+    }
+    cache.stats.misses += 1;
+
+    let storable = http_cache::is_storable(response.status, &response.headers);
+    let resource = CachedResource {
+        url: response.url.clone(),
+        partition,
+        resource_type: ResourceType::detect(&response.content_type, &response.url),
+        content_type: response.content_type.clone(),
+        data: std::mem::take(&mut response.body),
+        last_accessed: now,
+        etag: response.etag.clone(),
+        last_modified: response.last_modified.clone(),
+        freshness: Freshness::from_headers(&response.headers, now),
+    };
+    if storable {
+        cache.put(response.url.clone(), resource.clone());
+    }
+    (resource, Some(Transfer::from(response)))
+}
+
+impl From<Response> for Transfer {
+    fn from(response: Response) -> Self {
+        Transfer {
+            url: Some(response.final_url),
+            status: response.status,
+            request_headers: response.request_headers,
+            response_headers: response.headers,
+            timing: response.timing,
+        }
+    }
+}
+
+/// What the cache can do for a request
+pub(super) enum Lookup {
+    /// Use this copy without a request
+    Fresh(CachedResource),
+    /// Make a request, conditional on this copy if there is one
+    Validate(Option<CachedResource>),
+}
+
+/// Internal LRU cache implementation, in memory and optionally on disk
+pub(super) struct ResourceCache {
     /// Maximum cache size in bytes
     max_size: usize,
     /// Current cache size in bytes
     current_size: usize,
     /// Cached resources
-    entries: HashMap<Url, CachedResource>,
+    entries: HashMap<CacheKey, CachedResource>,
+    /// Every entry is written through to disk, and read back on a memory miss
+    disk: Option<DiskCache>,
+    stats: CacheStats,
 }
 
 impl ResourceCache {
@@ -165,20 +461,49 @@ impl ResourceCache {
             max_size,
             current_size: 0,
             entries: HashMap::new(),
+            disk: None,
+            stats: CacheStats::default(),
         }
     }
 
-    fn get(&mut self, url: &Url) -> Option<CachedResource> {
-        if let Some(resource) = self.entries.get_mut(url) {
+    pub(super) fn get(&mut self, url: &Url, partition: Option<&PartitionKey>) -> Option<CachedResource> {
+        let now = current_timestamp();
+        let key = CacheKey::new(url, partition);
+        if let Some(resource) = self.entries.get_mut(&key) {
             // Update last accessed time
-            resource.last_accessed = current_timestamp();
-            Some(resource.clone())
-        } else {
-            None
+            resource.last_accessed = now;
+            return Some(resource.clone());
+        }
+        let resource = self.disk.as_mut()?.get(&key, now)?;
+        self.put_memory(url.clone(), resource.clone());
+        Some(resource)
+    }
+
+    /// Find what a request in `cache_mode` can use, counting hits
+    pub(super) fn lookup(&mut self, url: &Url, partition: Option<&PartitionKey>, cache_mode: CacheMode) -> Lookup {
+        if cache_mode == CacheMode::Reload {
+            return Lookup::Validate(None);
+        }
+        match self.get(url, partition) {
+            Some(resource)
+                if resource.freshness.is_fresh(current_timestamp())
+                    && (cache_mode == CacheMode::Default || resource.freshness.immutable) =>
+            {
+                self.stats.hits += 1;
+                Lookup::Fresh(resource)
+            }
+            cached => Lookup::Validate(cached),
         }
     }
 
     fn put(&mut self, url: Url, resource: CachedResource) {
+        if let Some(disk) = &mut self.disk {
+            disk.put(&resource);
+        }
+        self.put_memory(url, resource);
+    }
+
+    fn put_memory(&mut self, url: Url, resource: CachedResource) {
         let resource_size = resource.data.len();
 
         // Evict old entries if needed
@@ -192,32 +517,81 @@ impl ResourceCache {
         }
 
         // Remove old entry if exists
-        if let Some(old) = self.entries.remove(&url) {
+        let key = CacheKey::new(&url, resource.partition.as_ref());
+        if let Some(old) = self.entries.remove(&key) {
             self.current_size -= old.data.len();
         }
 
         // Add new entry
         self.current_size += resource_size;
-        self.entries.insert(url, resource);
+        self.entries.insert(key, resource);
     }
 
     fn evict_lru(&mut self) {
         // Find the least recently used entry
-        if let Some((url, _)) = self
+        if let Some((key, _)) = self
             .entries
             .iter()
             .min_by_key(|(_, resource)| resource.last_accessed)
         {
-            let url = url.clone();
-            if let Some(removed) = self.entries.remove(&url) {
+            let key = key.clone();
+            if let Some(removed) = self.entries.remove(&key) {
                 self.current_size -= removed.data.len();
             }
         }
     }
 
+    fn evict_accessed_before(&mut self, timestamp: u64) -> usize {
+        let before = self.entries.len();
+        let mut freed = 0;
+        self.entries.retain(|_, resource| {
+            let keep = resource.last_accessed >= timestamp;
+            if !keep {
+                freed += resource.data.len();
+            }
+            keep
+        });
+        self.current_size -= freed;
+        before - self.entries.len()
+    }
+
     fn clear(&mut self) {
         self.entries.clear();
         self.current_size = 0;
+        if let Some(disk) = &mut self.disk {
+            disk.remove_where(|_| true);
+        }
+    }
+
+    /// Bytes cached for URLs of `origin` in any partition, on disk or only
+    /// in memory
+    fn usage_for(&self, origin: &Url) -> usize {
+        let origin = origin.origin();
+        let on_disk = |key: &CacheKey| self.disk.as_ref().is_some_and(|disk| disk.contains(key));
+        let in_memory: usize = self
+            .entries
+            .iter()
+            .filter(|(key, r)| r.url.origin() == origin && !on_disk(key))
+            .map(|(_, r)| r.data.len())
+            .sum();
+        let disk = self.disk.iter().flat_map(|disk| disk.sizes());
+        in_memory + disk.filter(|(url, _)| url.origin() == origin).map(|(_, size)| size).sum::<usize>()
+    }
+
+    fn clear_origin(&mut self, origin: &Url) {
+        let origin = origin.origin();
+        if let Some(disk) = &mut self.disk {
+            disk.remove_where(|url| url.origin() == origin);
+        }
+        let mut freed = 0;
+        self.entries.retain(|key, resource| {
+            let keep = key.url.origin() != origin;
+            if !keep {
+                freed += resource.data.len();
+            }
+            keep
+        });
+        self.current_size -= freed;
     }
 }
 
@@ -232,6 +606,7 @@ fn current_timestamp() -> u64 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage::Cookie;
 
     #[test]
     fn test_resource_type_from_content_type() {
@@ -278,18 +653,27 @@ mod tests {
         let url = Url::parse("https://example.com/test").unwrap();
         let resource = CachedResource {
             url: url.clone(),
+            partition: None,
             resource_type: ResourceType::Html,
             content_type: "text/html".to_string(),
             data: vec![1, 2, 3, 4],
             last_accessed: current_timestamp(),
+            etag: None,
+            last_modified: None,
+            freshness: Freshness::default(),
         };
 
         cache.put(url.clone(), resource.clone());
         assert_eq!(cache.current_size, 4);
         assert_eq!(cache.entries.len(), 1);
 
-        let retrieved = cache.get(&url).unwrap();
+        let retrieved = cache.get(&url, None).unwrap();
         assert_eq!(retrieved.data, vec![1, 2, 3, 4]);
+
+        let stale = Url::parse("https://example.com/stale").unwrap();
+        cache.put(stale.clone(), CachedResource { url: stale, last_accessed: 100, ..resource });
+        assert_eq!(cache.evict_accessed_before(200), 1);
+        assert_eq!((cache.entries.len(), cache.current_size), (1, 4));
     }
 
     #[test]
@@ -305,10 +689,14 @@ mod tests {
             url1.clone(),
             CachedResource {
                 url: url1.clone(),
+                partition: None,
                 resource_type: ResourceType::Html,
                 content_type: "text/html".to_string(),
                 data: vec![1, 2, 3, 4], // 4 bytes
                 last_accessed: 100,
+                etag: None,
+                last_modified: None,
+                freshness: Freshness::default(),
             },
         );
 
@@ -317,10 +705,14 @@ mod tests {
             url2.clone(),
             CachedResource {
                 url: url2.clone(),
+                partition: None,
                 resource_type: ResourceType::Html,
                 content_type: "text/html".to_string(),
                 data: vec![5, 6], // 2 bytes
                 last_accessed: 200,
+                etag: None,
+                last_modified: None,
+                freshness: Freshness::default(),
             },
         );
 
@@ -332,17 +724,21 @@ mod tests {
             url3.clone(),
             CachedResource {
                 url: url3.clone(),
+                partition: None,
                 resource_type: ResourceType::Html,
                 content_type: "text/html".to_string(),
                 data: vec![7, 8, 9, 10, 11], // 5 bytes
                 last_accessed: 300,
+                etag: None,
+                last_modified: None,
+                freshness: Freshness::default(),
             },
         );
 
         // Should have evicted url1 (oldest) to make room
-        assert!(cache.get(&url1).is_none());
-        assert!(cache.get(&url2).is_some());
-        assert!(cache.get(&url3).is_some());
+        assert!(cache.get(&url1, None).is_none());
+        assert!(cache.get(&url2, None).is_some());
+        assert!(cache.get(&url3, None).is_some());
     }
 
     #[test]
@@ -354,10 +750,14 @@ mod tests {
             url.clone(),
             CachedResource {
                 url: url.clone(),
+                partition: None,
                 resource_type: ResourceType::Html,
                 content_type: "text/html".to_string(),
                 data: vec![1, 2, 3],
                 last_accessed: current_timestamp(),
+                etag: None,
+                last_modified: None,
+                freshness: Freshness::default(),
             },
         );
 
@@ -368,4 +768,239 @@ mod tests {
         assert_eq!(cache.entries.len(), 0);
         assert_eq!(cache.current_size, 0);
     }
+
+    #[test]
+    fn test_cache_modes() {
+        let loader = ResourceLoader::new(1024);
+        let url = Url::parse("http://example.invalid/page").unwrap();
+        loader.cache.lock().unwrap().put(
+            url.clone(),
+            CachedResource {
+                url: url.clone(),
+                partition: None,
+                resource_type: ResourceType::Html,
+                content_type: "text/html".to_string(),
+                data: b"cached".to_vec(),
+                last_accessed: current_timestamp(),
+                etag: Some("\"v1\"".to_string()),
+                last_modified: None,
+                freshness: Freshness::for_lifetime(60, current_timestamp()),
+            },
+        );
+
+        // A normal load is served from the cache without touching the network
+        assert_eq!(loader.load(&url).unwrap().data, b"cached");
+
+        // Reloads go to the network, so a stopped load fails instead
+        let stopped = CancellationToken::new();
+        stopped.cancel();
+        for mode in [CacheMode::Revalidate, CacheMode::Reload] {
+            assert!(matches!(loader.load_with(&url, mode, Some(&stopped)), Err(NetError::Cancelled)));
+        }
+        assert_eq!(loader.cache_count(), 1);
+
+        // So does a normal load once the entry is stale
+        loader.cache.lock().unwrap().entries.get_mut(&CacheKey::new(&url, None)).unwrap().freshness = Freshness::default();
+        assert!(matches!(loader.load_with(&url, CacheMode::Default, Some(&stopped)), Err(NetError::Cancelled)));
+        assert_eq!(loader.cache_stats(), CacheStats { hits: 1, revalidated: 0, misses: 0 });
+    }
+
+    #[test]
+    fn test_stale_copy_when_server_unreachable() {
+        // Nothing listens on the port once the listener is gone
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/style.css", listener.local_addr().unwrap())).unwrap();
+        drop(listener);
+        let loader = ResourceLoader::new(1024);
+        let stale = |must_revalidate: bool| CachedResource {
+            url: url.clone(),
+            partition: None,
+            resource_type: ResourceType::Css,
+            content_type: "text/css".to_string(),
+            data: b"stale".to_vec(),
+            last_accessed: current_timestamp(),
+            etag: None,
+            last_modified: None,
+            freshness: Freshness { must_revalidate, ..Freshness::default() },
+        };
+
+        loader.cache.lock().unwrap().put(url.clone(), stale(false));
+        assert_eq!(loader.load(&url).unwrap().data, b"stale");
+        // A reload shows that the server is down
+        assert!(loader.load_with(&url, CacheMode::Revalidate, None).is_err());
+
+        loader.cache.lock().unwrap().put(url.clone(), stale(true));
+        assert!(matches!(loader.load(&url), Err(NetError::NetworkError(_))));
+    }
+
+    #[test]
+    fn test_revalidation_and_disk_cache() {
+        use std::io::{BufRead, BufReader, Write};
+
+        // Answers with a stale copy, then 304 to requests that carry its ETag
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/style.css", listener.local_addr().unwrap())).unwrap();
+        let server = std::thread::spawn(move || {
+            let mut conditional = Vec::new();
+            for stream in listener.incoming().take(2) {
+                let mut stream = stream.unwrap();
+                let lines: Vec<String> = BufReader::new(stream.try_clone().unwrap())
+                    .lines()
+                    .map(Result::unwrap)
+                    .take_while(|line| !line.is_empty())
+                    .collect();
+                let validated = lines.iter().any(|line| line.eq_ignore_ascii_case("if-none-match: \"v1\""));
+                let response = if validated {
+                    "HTTP/1.1 304 Not Modified\r\nCache-Control: max-age=60\r\nConnection: close\r\n\r\n"
+                } else {
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/css\r\nETag: \"v1\"\r\nCache-Control: max-age=0\r\nContent-Length: 4\r\nConnection: close\r\n\r\nbody"
+                };
+                stream.write_all(response.as_bytes()).unwrap();
+                conditional.push(validated);
+            }
+            conditional
+        });
+
+        let dir = std::env::temp_dir().join(format!("resource-loader-cache-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let loader = ResourceLoader::new(1024);
+        loader.open_disk_cache(&dir, 1024).unwrap();
+        loader.load(&url).unwrap();
+        let (revalidated, transfer) = loader.load_traced(&url, CacheMode::Default, None).unwrap();
+        assert_eq!((revalidated.data.as_slice(), transfer.unwrap().status), (b"body".as_slice(), 304));
+        assert_eq!(server.join().unwrap(), [false, true]);
+
+        // Fresh again after the 304, and kept on disk for the next run
+        let restarted = ResourceLoader::new(1024);
+        restarted.open_disk_cache(&dir, 1024).unwrap();
+        let (cached, transfer) = restarted.load_traced(&url, CacheMode::Default, None).unwrap();
+        assert_eq!((cached.data.as_slice(), cached.resource_type), (b"body".as_slice(), ResourceType::Css));
+        assert!(transfer.is_none());
+        assert_eq!(loader.cache_stats(), CacheStats { hits: 0, revalidated: 1, misses: 1 });
+        assert_eq!(restarted.cache_stats().hits, 1);
+
+        restarted.clear_cache();
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_credentials_are_prompted_for_and_reused() {
+        use crate::net::{AuthRequest, Credentials};
+        use std::io::{BufRead, BufReader, Write};
+
+        // Asks for Basic credentials until `user:right` comes, then serves the path
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        let server = std::thread::spawn(move || {
+            let mut authorizations = Vec::new();
+            for stream in listener.incoming().take(4) {
+                let mut stream = stream.unwrap();
+                let lines: Vec<String> = BufReader::new(stream.try_clone().unwrap())
+                    .lines()
+                    .map(Result::unwrap)
+                    .take_while(|line| !line.is_empty())
+                    .collect();
+                let authorization = lines.iter().find_map(|line| line.strip_prefix("authorization: ")).map(str::to_string);
+                let response = if authorization.as_deref() == Some("Basic dXNlcjpyaWdodA==") {
+                    "HTTP/1.1 200 OK\r\nCache-Control: no-store\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok"
+                } else {
+                    "HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Basic realm=\"Staff\"\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                };
+                stream.write_all(response.as_bytes()).unwrap();
+                authorizations.push(authorization);
+            }
+            authorizations
+        });
+
+        let asked = Arc::new(Mutex::new(Vec::new()));
+        let prompt = {
+            let asked = asked.clone();
+            move |request: &AuthRequest| {
+                asked.lock().unwrap().push((request.challenge.realm.clone(), request.retry));
+                let password = if request.retry { "right" } else { "wrong" };
+                Some(Credentials::new("user", password))
+            }
+        };
+        let mut loader = ResourceLoader::new(1024);
+        loader.set_credential_prompt(Some(Arc::new(prompt)));
+        let page = loader.load(&base.join("a").unwrap()).unwrap();
+        assert_eq!(page.data, b"ok");
+        assert_eq!(*asked.lock().unwrap(), [("Staff".to_string(), false), ("Staff".to_string(), true)]);
+
+        // The origin's credentials go with the next request without asking
+        assert_eq!(loader.load(&base.join("b").unwrap()).unwrap().data, b"ok");
+        assert_eq!(asked.lock().unwrap().len(), 2);
+        let sent: Vec<bool> = server.join().unwrap().iter().map(Option::is_some).collect();
+        assert_eq!(sent, [false, true, true, true]);
+    }
+
+    #[test]
+    fn test_site_data_per_origin() {
+        let loader = ResourceLoader::new(1024);
+        for (address, size) in [("https://a.com/x", 3), ("https://a.com/y", 4), ("https://b.com/x", 5)] {
+            let url = Url::parse(address).unwrap();
+            let resource = CachedResource {
+                url: url.clone(),
+                partition: None,
+                resource_type: ResourceType::Other,
+                content_type: String::new(),
+                data: vec![0; size],
+                last_accessed: current_timestamp(),
+                etag: None,
+                last_modified: None,
+                freshness: Freshness::default(),
+            };
+            loader.cache.lock().unwrap().put(url, resource);
+        }
+        loader.client.cookies.lock().unwrap().set_cookie(Cookie::parse("id=1", "a.com").unwrap());
+
+        let site_data = loader.site_data();
+        let a = Url::parse("https://a.com/").unwrap();
+        assert_eq!(site_data.http_cache_usage(&a), 7);
+        assert_eq!(site_data.cookie_usage("a.com"), 3);
+
+        site_data.clear_http_cache(&a);
+        site_data.clear_cookies("a.com");
+        assert_eq!(loader.cache_count(), 1);
+        assert_eq!(loader.cache_size(), 5);
+        assert_eq!(site_data.cookie_usage("a.com"), 0);
+    }
+
+    #[test]
+    fn test_interceptor_blocks_before_cache() {
+        struct BlockThirdPartyImages;
+        impl RequestInterceptor for BlockThirdPartyImages {
+            fn should_block(&self, request: &InterceptedRequest<'_>) -> bool {
+                request.resource_type == ResourceType::Image && request.is_third_party()
+            }
+        }
+
+        let mut loader = ResourceLoader::new(1024);
+        let image = Url::parse("https://ads.example.net/banner.png").unwrap();
+        let resource = CachedResource {
+            url: image.clone(),
+            partition: None,
+            resource_type: ResourceType::Image,
+            content_type: "image/png".to_string(),
+            data: vec![1, 2, 3],
+            last_accessed: current_timestamp(),
+            etag: None,
+            last_modified: None,
+            freshness: Freshness::for_lifetime(60, current_timestamp()),
+        };
+        loader.cache.lock().unwrap().put(image.clone(), resource);
+        loader.set_interceptor(Some(Arc::new(BlockThirdPartyImages)));
+
+        // Without a page, the image is its own first party
+        assert!(loader.load(&image).is_ok());
+
+        // Blocked even though it is cached
+        loader.set_first_party(Some(Url::parse("https://news.example.com/").unwrap()));
+        assert!(matches!(loader.load(&image), Err(NetError::Blocked(_))));
+        assert!(matches!(loader.load_range(&image, 0..10), Err(NetError::Blocked(_))));
+
+        loader.set_first_party(Some(Url::parse("https://cdn.example.net/").unwrap()));
+        assert!(loader.load(&image).is_ok());
+    }
 }
@@ -0,0 +1,404 @@
+// Connection security: the certificates servers present and the state
+// the address bar shows for a page.
+//
+// The HTTP clients ask for the peer certificate of every TLS connection
+// and keep what the certificate viewer shows of it, by host. Only the
+// fields users see are read from the DER; verification is the TLS
+// backend's job.
+
+use sha1::{Digest, Sha1};
+use std::collections::{HashMap, HashSet};
+use url::Url;
+
+/// `commonName` (2.5.4.3)
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+/// `organizationName` (2.5.4.10)
+const OID_ORGANIZATION: &[u8] = &[0x55, 0x04, 0x0a];
+/// `subjectAltName` extension (2.5.29.17)
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_UTC_TIME: u8 = 0x17;
+const TAG_GENERALIZED_TIME: u8 = 0x18;
+const TAG_SEQUENCE: u8 = 0x30;
+/// `[3]` wrapping the extensions of a v3 certificate
+const TAG_EXTENSIONS: u8 = 0xa3;
+/// `dNSName` in a `GeneralName`
+const TAG_DNS_NAME: u8 = 0x82;
+
+/// A server's certificate, as the certificate viewer shows it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Certificate {
+    /// Who the certificate was issued to, e.g. `CN=example.com, O=Example`
+    pub subject: String,
+    /// Who signed it
+    pub issuer: String,
+    /// Serial number in hex
+    pub serial: String,
+    /// Start of the validity period, e.g. `2024-01-31 12:00:00 UTC`
+    pub not_before: String,
+    /// End of the validity period
+    pub not_after: String,
+    /// Host names the certificate is valid for
+    pub dns_names: Vec<String>,
+    /// SHA-1 of the DER encoding, as colon-separated hex
+    pub sha1_fingerprint: String,
+}
+
+impl Certificate {
+    /// Read the fields shown to users from a DER encoded X.509 certificate
+    pub fn from_der(der: &[u8]) -> Option<Self> {
+        let mut certificate = Der::new(der).expect(TAG_SEQUENCE)?;
+        let mut tbs = certificate.expect(TAG_SEQUENCE)?;
+        // v1 certificates leave out the version
+        if tbs.peek() == Some(0xa0) {
+            tbs.next()?;
+        }
+        let serial = tbs.expect(TAG_INTEGER)?.bytes;
+        tbs.expect(TAG_SEQUENCE)?;
+        let issuer = tbs.expect(TAG_SEQUENCE)?;
+        let mut validity = tbs.expect(TAG_SEQUENCE)?;
+        let not_before = validity.next()?;
+        let not_after = validity.next()?;
+        let subject = tbs.expect(TAG_SEQUENCE)?;
+        tbs.expect(TAG_SEQUENCE)?;
+
+        let mut dns_names = Vec::new();
+        while let Some((tag, value)) = tbs.next() {
+            if tag == TAG_EXTENSIONS {
+                dns_names = subject_alt_names(value).unwrap_or_default();
+            }
+        }
+        let fingerprint = Sha1::digest(der);
+        Some(Self {
+            subject: distinguished_name(subject)?,
+            issuer: distinguished_name(issuer)?,
+            serial: hex(trim_leading_zeros(serial), ""),
+            not_before: format_time(not_before)?,
+            not_after: format_time(not_after)?,
+            dns_names,
+            sha1_fingerprint: hex(&fingerprint, ":"),
+        })
+    }
+}
+
+/// Certificates servers presented and sites whose invalid ones are accepted
+///
+/// Kept by host and shared between the HTTP clients, so a page loaded
+/// from the cache still shows the certificate its server last presented.
+#[derive(Debug, Default)]
+pub struct CertificateStore {
+    certificates: HashMap<String, Certificate>,
+    overrides: HashSet<String>,
+}
+
+impl CertificateStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember the certificate `host` presented
+    pub fn record(&mut self, host: &str, certificate: Certificate) {
+        self.certificates.insert(host.to_ascii_lowercase(), certificate);
+    }
+
+    /// The certificate `host` last presented
+    pub fn get(&self, host: &str) -> Option<&Certificate> {
+        self.certificates.get(&host.to_ascii_lowercase())
+    }
+
+    /// Accept invalid certificates from `host`, as the user chose to proceed
+    pub fn add_override(&mut self, host: &str) {
+        self.overrides.insert(host.to_ascii_lowercase());
+    }
+
+    /// Are invalid certificates from `host` accepted
+    pub fn is_overridden(&self, host: &str) -> bool {
+        self.overrides.contains(&host.to_ascii_lowercase())
+    }
+
+    /// Forget every override
+    pub fn clear_overrides(&mut self) {
+        self.overrides.clear();
+    }
+}
+
+/// How safe the connection to a page is, as the address bar shows it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecurityState {
+    /// Browser pages and local files
+    Internal,
+    /// Loaded over HTTPS with a valid certificate
+    Secure,
+    /// An HTTPS page that loaded resources over plain HTTP
+    MixedContent,
+    /// Loaded over HTTPS although the user accepted an invalid certificate
+    CertificateOverride,
+    /// Loaded over plain HTTP
+    Insecure,
+}
+
+impl SecurityState {
+    /// State of a page at `url`
+    ///
+    /// `mixed_content` is whether the page loaded any resource insecurely;
+    /// `overridden` whether its host's invalid certificate was accepted.
+    pub fn for_page(url: &Url, mixed_content: bool, overridden: bool) -> Self {
+        match url.scheme() {
+            "https" | "wss" if overridden => SecurityState::CertificateOverride,
+            "https" | "wss" if mixed_content => SecurityState::MixedContent,
+            "https" | "wss" => SecurityState::Secure,
+            "http" | "ws" => SecurityState::Insecure,
+            _ => SecurityState::Internal,
+        }
+    }
+
+    /// Does the page have a certificate to show
+    pub fn has_certificate(&self) -> bool {
+        matches!(self, SecurityState::Secure | SecurityState::MixedContent | SecurityState::CertificateOverride)
+    }
+
+    /// Short label, e.g. "Not secure"
+    pub fn label(&self) -> &'static str {
+        match self {
+            SecurityState::Internal => "Browser page",
+            SecurityState::Secure => "Connection is secure",
+            SecurityState::MixedContent => "Parts of this page are not secure",
+            SecurityState::CertificateOverride => "Certificate is not valid",
+            SecurityState::Insecure => "Not secure",
+        }
+    }
+
+    /// What the state means for the user
+    pub fn description(&self) -> &'static str {
+        match self {
+            SecurityState::Internal => "This page is part of the browser or stored on this computer.",
+            SecurityState::Secure => "Information you send to this site is private.",
+            SecurityState::MixedContent => "This page loaded resources without encryption, which others could change.",
+            SecurityState::CertificateOverride => "You chose to trust a certificate the browser could not verify.",
+            SecurityState::Insecure => "Information you send to this site could be read by others.",
+        }
+    }
+}
+
+/// Is loading `resource` into `page` mixed content
+pub fn is_mixed_content(page: &Url, resource: &Url) -> bool {
+    matches!(page.scheme(), "https" | "wss") && matches!(resource.scheme(), "http" | "ws")
+}
+
+/// A DER reader over a run of TLV elements
+#[derive(Clone, Copy)]
+struct Der<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Der<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.first().copied()
+    }
+
+    /// Read the next element's tag and contents
+    fn next(&mut self) -> Option<(u8, Der<'a>)> {
+        let (&tag, rest) = self.bytes.split_first()?;
+        let (&first, rest) = rest.split_first()?;
+        let (len, rest) = if first < 0x80 {
+            (first as usize, rest)
+        } else {
+            let count = (first & 0x7f) as usize;
+            if count == 0 || count > 4 || rest.len() < count {
+                return None;
+            }
+            let len = rest[..count].iter().fold(0usize, |len, &b| (len << 8) | b as usize);
+            (len, &rest[count..])
+        };
+        if rest.len() < len {
+            return None;
+        }
+        self.bytes = &rest[len..];
+        Some((tag, Der::new(&rest[..len])))
+    }
+
+    /// Read the next element, which must have `tag`
+    fn expect(&mut self, tag: u8) -> Option<Der<'a>> {
+        match self.next()? {
+            (found, contents) if found == tag => Some(contents),
+            _ => None,
+        }
+    }
+}
+
+/// `CN=..., O=...` from a `Name`, or the whole name's attributes if it has neither
+fn distinguished_name(mut name: Der<'_>) -> Option<String> {
+    let mut parts = Vec::new();
+    while let Some((_, mut rdn)) = name.next() {
+        while let Some((_, mut attribute)) = rdn.next() {
+            let oid = attribute.expect(TAG_OID)?.bytes;
+            let (_, value) = attribute.next()?;
+            let label = match oid {
+                OID_COMMON_NAME => "CN",
+                OID_ORGANIZATION => "O",
+                _ => continue,
+            };
+            parts.push(format!("{}={}", label, String::from_utf8_lossy(value.bytes)));
+        }
+    }
+    Some(parts.join(", "))
+}
+
+/// DNS names of the `subjectAltName` extension, from the `[3]` extensions field
+fn subject_alt_names(mut extensions: Der<'_>) -> Option<Vec<String>> {
+    let mut extensions = extensions.expect(TAG_SEQUENCE)?;
+    while let Some((_, mut extension)) = extensions.next() {
+        let oid = extension.expect(TAG_OID)?.bytes;
+        if oid != OID_SUBJECT_ALT_NAME {
+            continue;
+        }
+        // A `critical` flag may come before the value
+        let mut value = loop {
+            match extension.next()? {
+                (TAG_OCTET_STRING, value) => break value,
+                _ => continue,
+            }
+        };
+        let mut names = value.expect(TAG_SEQUENCE)?;
+        let mut dns_names = Vec::new();
+        while let Some((tag, name)) = names.next() {
+            if tag == TAG_DNS_NAME {
+                dns_names.push(String::from_utf8_lossy(name.bytes).into_owned());
+            }
+        }
+        return Some(dns_names);
+    }
+    Some(Vec::new())
+}
+
+/// `2024-01-31 12:00:00 UTC` from a `UTCTime` or `GeneralizedTime`
+fn format_time((tag, time): (u8, Der<'_>)) -> Option<String> {
+    let text = std::str::from_utf8(time.bytes).ok()?.trim_end_matches('Z');
+    let (year, rest) = match tag {
+        // Two-digit years are 1950 to 2049
+        TAG_UTC_TIME => {
+            let yy: u32 = text.get(..2)?.parse().ok()?;
+            (if yy < 50 { 2000 + yy } else { 1900 + yy }, text.get(2..)?)
+        }
+        TAG_GENERALIZED_TIME => (text.get(..4)?.parse().ok()?, text.get(4..)?),
+        _ => return None,
+    };
+    let field = |i: usize| rest.get(i * 2..i * 2 + 2);
+    Some(format!(
+        "{}-{}-{} {}:{}:{} UTC",
+        year,
+        field(0)?,
+        field(1)?,
+        field(2)?,
+        field(3)?,
+        field(4).unwrap_or("00")
+    ))
+}
+
+fn trim_leading_zeros(bytes: &[u8]) -> &[u8] {
+    match bytes.iter().position(|&b| b != 0) {
+        Some(start) => &bytes[start..],
+        None => &bytes[bytes.len().saturating_sub(1)..],
+    }
+}
+
+fn hex(bytes: &[u8], separator: &str) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(separator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TAG_SET: u8 = 0x31;
+
+    fn tlv(tag: u8, contents: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        if contents.len() < 0x80 {
+            out.push(contents.len() as u8);
+        } else {
+            out.extend([0x82, (contents.len() >> 8) as u8, contents.len() as u8]);
+        }
+        out.extend_from_slice(contents);
+        out
+    }
+
+    fn name(common_name: &str, organization: &str) -> Vec<u8> {
+        let attribute = |oid: &[u8], value: &str| {
+            let pair = [tlv(TAG_OID, oid), tlv(0x0c, value.as_bytes())].concat();
+            tlv(TAG_SET, &tlv(TAG_SEQUENCE, &pair))
+        };
+        tlv(TAG_SEQUENCE, &[attribute(OID_ORGANIZATION, organization), attribute(OID_COMMON_NAME, common_name)].concat())
+    }
+
+    fn certificate() -> Vec<u8> {
+        let alt_names = tlv(TAG_SEQUENCE, &[tlv(TAG_DNS_NAME, b"example.com"), tlv(TAG_DNS_NAME, b"*.example.com")].concat());
+        let extension = [tlv(TAG_OID, OID_SUBJECT_ALT_NAME), tlv(TAG_OCTET_STRING, &alt_names)].concat();
+        let tbs = [
+            tlv(0xa0, &tlv(TAG_INTEGER, &[2])),
+            tlv(TAG_INTEGER, &[0x00, 0x9f, 0x01]),
+            tlv(TAG_SEQUENCE, &tlv(TAG_OID, &[0x2a, 0x86, 0x48])),
+            name("Example CA", "Example Trust"),
+            tlv(TAG_SEQUENCE, &[tlv(TAG_UTC_TIME, b"240131120000Z"), tlv(TAG_GENERALIZED_TIME, b"20250301000000Z")].concat()),
+            name("example.com", "Example"),
+            tlv(TAG_SEQUENCE, &[0x05, 0x00]),
+            tlv(TAG_EXTENSIONS, &tlv(TAG_SEQUENCE, &tlv(TAG_SEQUENCE, &extension))),
+        ]
+        .concat();
+        let signature = [tlv(TAG_SEQUENCE, &tlv(TAG_OID, &[0x2a])), tlv(0x03, &[0x00, 0x01])].concat();
+        tlv(TAG_SEQUENCE, &[tlv(TAG_SEQUENCE, &tbs), signature].concat())
+    }
+
+    #[test]
+    fn test_certificate_from_der() {
+        let der = certificate();
+        let certificate = Certificate::from_der(&der).unwrap();
+        assert_eq!(certificate.subject, "O=Example, CN=example.com");
+        assert_eq!(certificate.issuer, "O=Example Trust, CN=Example CA");
+        assert_eq!(certificate.serial, "9F01");
+        assert_eq!(certificate.not_before, "2024-01-31 12:00:00 UTC");
+        assert_eq!(certificate.not_after, "2025-03-01 00:00:00 UTC");
+        assert_eq!(certificate.dns_names, vec!["example.com", "*.example.com"]);
+        assert_eq!(certificate.sha1_fingerprint.split(':').count(), 20);
+
+        // Truncated or malformed input is rejected, not misread
+        assert!(Certificate::from_der(&der[..der.len() / 2]).is_none());
+        assert!(Certificate::from_der(b"not der").is_none());
+    }
+
+    #[test]
+    fn test_security_state_for_page() {
+        let https = Url::parse("https://example.com/").unwrap();
+        let http = Url::parse("http://example.com/").unwrap();
+        assert_eq!(SecurityState::for_page(&https, false, false), SecurityState::Secure);
+        assert_eq!(SecurityState::for_page(&https, true, false), SecurityState::MixedContent);
+        assert_eq!(SecurityState::for_page(&https, true, true), SecurityState::CertificateOverride);
+        assert_eq!(SecurityState::for_page(&http, false, false), SecurityState::Insecure);
+        let internal = Url::parse("about:memory").unwrap();
+        assert_eq!(SecurityState::for_page(&internal, false, false), SecurityState::Internal);
+
+        assert!(is_mixed_content(&https, &Url::parse("http://cdn.example.com/a.png").unwrap()));
+        assert!(!is_mixed_content(&http, &Url::parse("http://cdn.example.com/a.png").unwrap()));
+        assert!(!is_mixed_content(&https, &Url::parse("data:image/png;base64,AA==").unwrap()));
+    }
+
+    #[test]
+    fn test_store_is_keyed_by_host() {
+        let mut store = CertificateStore::new();
+        store.record("Example.com", Certificate::from_der(&certificate()).unwrap());
+        assert!(store.get("example.com").is_some());
+        assert!(store.get("other.com").is_none());
+
+        store.add_override("self-signed.test");
+        assert!(store.is_overridden("SELF-SIGNED.test") && !store.is_overridden("example.com"));
+        store.clear_overrides();
+        assert!(!store.is_overridden("self-signed.test"));
+    }
+}
@@ -1,6 +1,13 @@
 // DOM Observers - Phase 8 Advanced JavaScript
+//
+// Mutation records are queued for every observer interested in the
+// changed node, by the options it observes that node or an ancestor with,
+// and handed over in one batch per observer at the next checkpoint.
+//
+// Intersections are worked out after layout from the elements' rects, and
+// an observer hears about an element when it crosses one of its thresholds.
 
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
 /// Observer ID type
@@ -12,10 +19,8 @@ pub struct MutationObserver {
     id: ObserverId,
     /// Callback function (simplified)
     callback: Arc<Mutex<Box<dyn Fn(&[MutationRecord]) + Send>>>,
-    /// Observed nodes
-    observed_nodes: HashSet<u64>, // node IDs
-    /// Configuration
-    config: MutationObserverInit,
+    /// Observed nodes (node IDs) and the options each is observed with
+    observed_nodes: HashMap<u64, MutationObserverInit>,
 }
 
 /// Mutation observer configuration
@@ -51,8 +56,23 @@ impl Default for MutationObserverInit {
     }
 }
 
+impl MutationObserverInit {
+    /// Whether these options take a mutation; if so, whether its old value is kept
+    pub fn wants(&self, record: &MutationRecord) -> Option<bool> {
+        match record.mutation_type {
+            MutationType::ChildList => self.child_list.then_some(false),
+            MutationType::Attributes => {
+                let name = record.attribute_name.as_deref().unwrap_or_default();
+                let filtered = self.attribute_filter.as_ref().is_some_and(|filter| !filter.iter().any(|n| n == name));
+                (self.attributes && !filtered).then_some(self.attribute_old_value)
+            }
+            MutationType::CharacterData => self.character_data.then_some(self.character_data_old_value),
+        }
+    }
+}
+
 /// Mutation record describing a DOM change
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MutationRecord {
     /// Type of mutation
     pub mutation_type: MutationType,
@@ -89,8 +109,7 @@ impl MutationObserver {
         Self {
             id,
             callback: Arc::new(Mutex::new(Box::new(callback))),
-            observed_nodes: HashSet::new(),
-            config: MutationObserverInit::default(),
+            observed_nodes: HashMap::new(),
         }
     }
     
@@ -99,10 +118,9 @@ impl MutationObserver {
         self.id
     }
     
-    /// Observe a node
+    /// Observe a node, replacing the options it was observed with
     pub fn observe(&mut self, node_id: u64, config: MutationObserverInit) {
-        self.observed_nodes.insert(node_id);
-        self.config = config;
+        self.observed_nodes.insert(node_id, config);
     }
     
     /// Disconnect observer
@@ -112,7 +130,18 @@ impl MutationObserver {
     
     /// Check if observing a node
     pub fn is_observing(&self, node_id: u64) -> bool {
-        self.observed_nodes.contains(&node_id)
+        self.observed_nodes.contains_key(&node_id)
+    }
+    
+    /// Whether a mutation of `target`, whose ancestors are `ancestors`
+    /// (nearest first), is of interest; if so, whether to keep its old value
+    pub fn interest(&self, target: u64, ancestors: &[u64], record: &MutationRecord) -> Option<bool> {
+        let nodes = std::iter::once(&target).chain(ancestors);
+        let wanted = nodes.filter_map(|node| {
+            let options = self.observed_nodes.get(node)?;
+            (*node == target || options.subtree).then_some(options)?.wants(record)
+        });
+        wanted.reduce(|keep, more| keep || more)
     }
     
     /// Notify observer of mutations
@@ -122,9 +151,9 @@ impl MutationObserver {
         }
     }
     
-    /// Get configuration
-    pub fn config(&self) -> &MutationObserverInit {
-        &self.config
+    /// Options a node is observed with
+    pub fn options(&self, node_id: u64) -> Option<&MutationObserverInit> {
+        self.observed_nodes.get(&node_id)
     }
 }
 
@@ -134,16 +163,34 @@ pub struct IntersectionObserver {
     id: ObserverId,
     /// Callback
     callback: Arc<Mutex<Box<dyn Fn(&[IntersectionObserverEntry]) + Send>>>,
-    /// Observed elements
-    observed_elements: HashSet<u64>,
+    /// Observed elements, with the threshold index and intersecting state
+    /// they were last reported with
+    observed_elements: HashMap<u64, Option<(usize, bool)>>,
     /// Root element (None = viewport)
     root: Option<u64>,
-    /// Root margin (in pixels)
-    root_margin: (f32, f32, f32, f32), // top, right, bottom, left
-    /// Thresholds
+    /// Root margin: top, right, bottom, left
+    root_margin: [MarginLength; 4],
+    /// Thresholds, ascending
     thresholds: Vec<f32>,
 }
 
+/// A side of an intersection observer's root margin
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MarginLength {
+    Px(f32),
+    /// Percentage of the root's width, for left and right, or height
+    Percent(f32),
+}
+
+impl MarginLength {
+    fn resolve(&self, size: f32) -> f32 {
+        match self {
+            MarginLength::Px(px) => *px,
+            MarginLength::Percent(percent) => size * percent / 100.0,
+        }
+    }
+}
+
 /// Intersection observer entry
 #[derive(Debug, Clone)]
 pub struct IntersectionObserverEntry {
@@ -192,6 +239,15 @@ impl Rect {
         }
     }
     
+    /// Intersection that counts rects touching at an edge, as an empty rect
+    pub fn edge_intersection(&self, other: &Rect) -> Option<Rect> {
+        let x1 = self.x.max(other.x);
+        let y1 = self.y.max(other.y);
+        let x2 = (self.x + self.width).min(other.x + other.width);
+        let y2 = (self.y + self.height).min(other.y + other.height);
+        (x2 >= x1 && y2 >= y1).then(|| Rect::new(x1, y1, x2 - x1, y2 - y1))
+    }
+    
     /// Calculate area
     pub fn area(&self) -> f32 {
         self.width * self.height
@@ -207,9 +263,9 @@ impl IntersectionObserver {
         Self {
             id,
             callback: Arc::new(Mutex::new(Box::new(callback))),
-            observed_elements: HashSet::new(),
+            observed_elements: HashMap::new(),
             root: None,
-            root_margin: (0.0, 0.0, 0.0, 0.0),
+            root_margin: [MarginLength::Px(0.0); 4],
             thresholds: vec![0.0],
         }
     }
@@ -219,19 +275,30 @@ impl IntersectionObserver {
         self.root = root;
     }
     
+    /// Root element, if not the viewport
+    pub fn root(&self) -> Option<u64> {
+        self.root
+    }
+    
     /// Set root margin
     pub fn set_root_margin(&mut self, top: f32, right: f32, bottom: f32, left: f32) {
-        self.root_margin = (top, right, bottom, left);
+        self.root_margin = [top, right, bottom, left].map(MarginLength::Px);
+    }
+    
+    /// Set root margin with percentages of the root's size
+    pub fn set_root_margin_lengths(&mut self, margin: [MarginLength; 4]) {
+        self.root_margin = margin;
     }
     
     /// Set thresholds
-    pub fn set_thresholds(&mut self, thresholds: Vec<f32>) {
+    pub fn set_thresholds(&mut self, mut thresholds: Vec<f32>) {
+        thresholds.sort_by(f32::total_cmp);
         self.thresholds = thresholds;
     }
     
-    /// Observe an element
+    /// Observe an element; its first intersection is always reported
     pub fn observe(&mut self, element_id: u64) {
-        self.observed_elements.insert(element_id);
+        self.observed_elements.entry(element_id).or_insert(None);
     }
     
     /// Unobserve an element
@@ -246,7 +313,55 @@ impl IntersectionObserver {
     
     /// Check if observing an element
     pub fn is_observing(&self, element_id: u64) -> bool {
-        self.observed_elements.contains(&element_id)
+        self.observed_elements.contains_key(&element_id)
+    }
+    
+    /// The root's rect grown by the root margin
+    pub fn root_bounds(&self, root_rect: Rect) -> Rect {
+        let [top, right, bottom, left] = self.root_margin;
+        let (top, bottom) = (top.resolve(root_rect.height), bottom.resolve(root_rect.height));
+        let (right, left) = (right.resolve(root_rect.width), left.resolve(root_rect.width));
+        let (width, height) = (root_rect.width + left + right, root_rect.height + top + bottom);
+        Rect::new(root_rect.x - left, root_rect.y - top, width, height)
+    }
+    
+    /// Work out the observed elements' intersections with the root, given
+    /// their rects (`None` for elements not laid out)
+    ///
+    /// Returns entries for the elements that crossed a threshold, or started
+    /// or stopped intersecting, since they were last reported.
+    pub fn update(
+        &mut self,
+        root_rect: Rect,
+        rect_of: impl Fn(u64) -> Option<Rect>,
+        time: f64,
+    ) -> Vec<IntersectionObserverEntry> {
+        let mut elements: Vec<u64> = self.observed_elements.keys().copied().collect();
+        elements.sort_unstable();
+        let mut entries = Vec::new();
+        for element in elements {
+            let mut entry = match rect_of(element) {
+                Some(rect) => self.calculate_intersection(rect, root_rect),
+                None => IntersectionObserverEntry {
+                    target: element,
+                    bounding_client_rect: Rect::new(0.0, 0.0, 0.0, 0.0),
+                    root_bounds: Some(self.root_bounds(root_rect)),
+                    intersection_rect: Rect::new(0.0, 0.0, 0.0, 0.0),
+                    intersection_ratio: 0.0,
+                    is_intersecting: false,
+                    time,
+                },
+            };
+            entry.target = element;
+            entry.time = time;
+            let crossed = self.thresholds.iter().filter(|threshold| **threshold <= entry.intersection_ratio).count();
+            let state = Some((if entry.is_intersecting { crossed } else { 0 }, entry.is_intersecting));
+            let last = self.observed_elements.insert(element, state);
+            if last != Some(state) {
+                entries.push(entry);
+            }
+        }
+        entries
     }
     
     /// Notify observer of intersection changes
@@ -257,12 +372,16 @@ impl IntersectionObserver {
     }
     
     /// Calculate intersection for an element
+    ///
+    /// An element touching the root at an edge intersects it; an empty
+    /// element intersecting the root has a ratio of 1.
     pub fn calculate_intersection(&self, element_rect: Rect, root_rect: Rect) -> IntersectionObserverEntry {
-        let intersection = element_rect.intersection(&root_rect);
+        let root_bounds = self.root_bounds(root_rect);
+        let intersection = element_rect.edge_intersection(&root_bounds);
         
         let (intersection_rect, intersection_ratio, is_intersecting) = if let Some(rect) = intersection {
-            let ratio = rect.area() / element_rect.area();
-            (rect, ratio, ratio > 0.0)
+            let ratio = if element_rect.area() > 0.0 { rect.area() / element_rect.area() } else { 1.0 };
+            (rect, ratio, true)
         } else {
             (Rect::new(0.0, 0.0, 0.0, 0.0), 0.0, false)
         };
@@ -270,7 +389,7 @@ impl IntersectionObserver {
         IntersectionObserverEntry {
             target: 0, // Would be set by caller
             bounding_client_rect: element_rect,
-            root_bounds: Some(root_rect),
+            root_bounds: Some(root_bounds),
             intersection_rect,
             intersection_ratio,
             is_intersecting,
@@ -448,23 +567,82 @@ impl ObserverManager {
     
     /// Record a mutation
     pub fn record_mutation(&mut self, node_id: u64, record: MutationRecord) {
-        // Find all observers watching this node
-        for (observer_id, observer) in &self.mutation_observers {
-            if observer.is_observing(node_id) {
-                self.pending_mutations.push((*observer_id, record.clone()));
+        self.record_subtree_mutation(node_id, &[], record);
+    }
+    
+    /// Record a mutation of `node_id` for observers of the node and, observing
+    /// their subtree, of its `ancestors` (nearest first)
+    ///
+    /// Each interested observer gets the record once, without the old value
+    /// unless it asked for it.
+    pub fn record_subtree_mutation(&mut self, node_id: u64, ancestors: &[u64], record: MutationRecord) {
+        let mut observers: Vec<_> = self.mutation_observers.iter().collect();
+        observers.sort_by_key(|(id, _)| **id);
+        for (observer_id, observer) in observers {
+            if let Some(keep_old_value) = observer.interest(node_id, ancestors, &record) {
+                let mut record = record.clone();
+                if !keep_old_value {
+                    record.old_value = None;
+                }
+                self.pending_mutations.push((*observer_id, record));
             }
         }
     }
     
-    /// Flush pending mutations
-    pub fn flush_mutations(&mut self) {
-        let mut by_observer: HashMap<ObserverId, Vec<MutationRecord>> = HashMap::new();
-        
+    /// Take every queued record, batched by observer in the order observers were created
+    pub fn take_pending_mutations(&mut self) -> Vec<(ObserverId, Vec<MutationRecord>)> {
+        let mut by_observer: Vec<(ObserverId, Vec<MutationRecord>)> = Vec::new();
         for (observer_id, record) in self.pending_mutations.drain(..) {
-            by_observer.entry(observer_id).or_insert_with(Vec::new).push(record);
+            match by_observer.iter_mut().find(|(id, _)| *id == observer_id) {
+                Some((_, records)) => records.push(record),
+                None => by_observer.push((observer_id, vec![record])),
+            }
         }
-        
-        for (observer_id, records) in by_observer {
+        by_observer.sort_by_key(|(id, _)| *id);
+        by_observer
+    }
+    
+    /// Work out every intersection observer's intersections after layout
+    ///
+    /// `rect_of` gives the rects of elements, in the same coordinates as
+    /// `viewport`, the root of observers without one. Observers whose root
+    /// is not laid out are skipped. Returns the entries for each observer,
+    /// in the order observers were created.
+    pub fn update_intersections(
+        &mut self,
+        viewport: Rect,
+        rect_of: impl Fn(u64) -> Option<Rect>,
+        time: f64,
+    ) -> Vec<(ObserverId, Vec<IntersectionObserverEntry>)> {
+        let mut observers: Vec<_> = self.intersection_observers.iter_mut().collect();
+        observers.sort_by_key(|(id, _)| **id);
+        observers
+            .into_iter()
+            .filter_map(|(id, observer)| {
+                let root_rect = match observer.root() {
+                    Some(root) => rect_of(root)?,
+                    None => viewport,
+                };
+                let entries = observer.update(root_rect, &rect_of, time);
+                (!entries.is_empty()).then_some((*id, entries))
+            })
+            .collect()
+    }
+    
+    /// Stop and forget an intersection observer
+    pub fn remove_intersection_observer(&mut self, id: ObserverId) {
+        self.intersection_observers.remove(&id);
+    }
+    
+    /// Stop and forget a mutation observer
+    pub fn remove_mutation_observer(&mut self, id: ObserverId) {
+        self.mutation_observers.remove(&id);
+        self.pending_mutations.retain(|(observer_id, _)| *observer_id != id);
+    }
+    
+    /// Flush pending mutations
+    pub fn flush_mutations(&mut self) {
+        for (observer_id, records) in self.take_pending_mutations() {
             if let Some(observer) = self.mutation_observers.get(&observer_id) {
                 observer.notify(&records);
             }
@@ -624,4 +802,90 @@ mod tests {
         observer.disconnect();
         assert!(!observer.is_observing(100));
     }
+    
+    #[test]
+    fn test_mutation_filters() {
+        let mut manager = ObserverManager::new();
+        let subtree = manager.create_mutation_observer(|_| {});
+        let direct = manager.create_mutation_observer(|_| {});
+        manager.get_mutation_observer(subtree).unwrap().observe(1, MutationObserverInit {
+            attributes: true,
+            subtree: true,
+            attribute_old_value: true,
+            attribute_filter: Some(vec!["class".to_string()]),
+            ..Default::default()
+        });
+        manager.get_mutation_observer(direct).unwrap().observe(2, MutationObserverInit {
+            attributes: true,
+            child_list: true,
+            ..Default::default()
+        });
+        let attribute = |name: &str| MutationRecord {
+            mutation_type: MutationType::Attributes,
+            target: 2,
+            added_nodes: vec![],
+            removed_nodes: vec![],
+            previous_sibling: None,
+            next_sibling: None,
+            attribute_name: Some(name.to_string()),
+            old_value: Some("old".to_string()),
+        };
+        
+        // Node 2 is inside node 1
+        manager.record_subtree_mutation(2, &[1], attribute("class"));
+        manager.record_subtree_mutation(2, &[1], attribute("title"));
+        // A change below node 2 is not seen by its direct observer
+        manager.record_subtree_mutation(3, &[2, 1], MutationRecord { target: 3, ..attribute("class") });
+        
+        let batches = manager.take_pending_mutations();
+        assert_eq!(batches.len(), 2);
+        let (id, records) = &batches[0];
+        assert_eq!(*id, subtree);
+        let names: Vec<_> = records.iter().map(|r| (r.target, r.attribute_name.as_deref())).collect();
+        assert_eq!(names, [(2, Some("class")), (3, Some("class"))]);
+        assert_eq!(records[0].old_value.as_deref(), Some("old"));
+        let (id, records) = &batches[1];
+        assert_eq!(*id, direct);
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().all(|r| r.old_value.is_none()));
+        assert!(manager.take_pending_mutations().is_empty());
+        
+        manager.record_subtree_mutation(2, &[1], attribute("class"));
+        manager.remove_mutation_observer(subtree);
+        let batches = manager.take_pending_mutations();
+        assert_eq!(batches.iter().map(|(id, _)| *id).collect::<Vec<_>>(), [direct]);
+    }
+    
+    #[test]
+    fn test_intersection_thresholds() {
+        let mut manager = ObserverManager::new();
+        let id = manager.create_intersection_observer(|_| {});
+        let observer = manager.get_intersection_observer(id).unwrap();
+        observer.set_thresholds(vec![1.0, 0.5]);
+        let none = MarginLength::Px(0.0);
+        observer.set_root_margin_lengths([none, none, MarginLength::Percent(10.0), none]);
+        observer.observe(7);
+        let viewport = Rect::new(0.0, 0.0, 100.0, 100.0);
+        let at = |y: f32| move |_: u64| Some(Rect::new(0.0, y, 100.0, 20.0));
+        
+        // Below the viewport, the first check still reports
+        let batches = manager.update_intersections(viewport, at(200.0), 1.0);
+        let entry = &batches[0].1[0];
+        assert_eq!((entry.target, entry.is_intersecting, entry.time), (7, false, 1.0));
+        assert_eq!(entry.root_bounds, Some(Rect::new(0.0, 0.0, 100.0, 110.0)));
+        assert!(manager.update_intersections(viewport, at(150.0), 2.0).is_empty());
+        
+        // Touching the margin's edge intersects, without crossing 0.5
+        let batches = manager.update_intersections(viewport, at(110.0), 3.0);
+        assert!(batches[0].1[0].is_intersecting);
+        assert_eq!(batches[0].1[0].intersection_ratio, 0.0);
+        assert!(manager.update_intersections(viewport, at(105.0), 4.0).is_empty());
+        let batches = manager.update_intersections(viewport, at(95.0), 5.0);
+        assert_eq!(batches[0].1[0].intersection_ratio, 0.75);
+        assert!(manager.update_intersections(viewport, at(10.0), 6.0)[0].1[0].intersection_ratio == 1.0);
+        
+        // Elements no longer laid out stop intersecting
+        let batches = manager.update_intersections(viewport, |_| None, 7.0);
+        assert!(!batches[0].1[0].is_intersecting);
+    }
 }
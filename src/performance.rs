@@ -1,5 +1,10 @@
 // Performance APIs - Phase 8 Advanced JavaScript
+//
+// Entries are recorded on the page's timeline and queued for every
+// PerformanceObserver subscribed to their type. The host takes the queued
+// entries in batches and hands each observer its batch in one callback.
 
+use crate::observers::ObserverId;
 use std::collections::HashMap;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
@@ -17,8 +22,35 @@ pub struct Performance {
     measures: HashMap<String, PerformanceMeasure>,
     /// Memory info (if available)
     memory: Option<MemoryInfo>,
+    /// Has the navigation been timed
+    navigated: bool,
+    /// Paint timing entries, e.g. `first-paint`
+    paints: Vec<(String, DOMHighResTimeStamp)>,
+    /// Long task entries
+    long_tasks: Vec<PerformanceEntry>,
+    /// Largest contentful paint candidates, each larger than the last
+    largest_contentful_paints: Vec<PerformanceEntry>,
+    /// Layout shift entries
+    layout_shifts: Vec<PerformanceEntry>,
+    /// Observers and the entries waiting for their next callback
+    observers: Vec<PerformanceObserver>,
 }
 
+/// Entry types a PerformanceObserver can subscribe to
+pub const SUPPORTED_ENTRY_TYPES: [&str; 8] = [
+    "mark",
+    "measure",
+    "resource",
+    "navigation",
+    "paint",
+    "longtask",
+    "largest-contentful-paint",
+    "layout-shift",
+];
+
+/// Tasks running at least this long are long tasks (milliseconds)
+pub const LONG_TASK_THRESHOLD: DOMHighResTimeStamp = 50.0;
+
 /// High-resolution timestamp (milliseconds since time origin)
 pub type DOMHighResTimeStamp = f64;
 
@@ -157,6 +189,30 @@ pub struct PerformanceMeasure {
     pub duration: DOMHighResTimeStamp,
 }
 
+/// A PerformanceObserver's subscription
+#[derive(Debug, Clone)]
+pub struct PerformanceObserver {
+    id: ObserverId,
+    /// Entry types observed
+    entry_types: Vec<String>,
+    /// Entries queued for the next callback
+    records: Vec<PerformanceEntry>,
+}
+
+impl PerformanceObserver {
+    pub fn id(&self) -> ObserverId {
+        self.id
+    }
+
+    pub fn entry_types(&self) -> &[String] {
+        &self.entry_types
+    }
+
+    pub fn observes(&self, entry_type: &str) -> bool {
+        self.entry_types.iter().any(|t| t == entry_type)
+    }
+}
+
 /// Memory information
 #[derive(Debug, Clone, Copy)]
 pub struct MemoryInfo {
@@ -182,15 +238,31 @@ impl Performance {
                 total_js_heap_size: 0,
                 js_heap_size_limit: 2 * 1024 * 1024 * 1024, // 2GB default
             }),
+            navigated: false,
+            paints: Vec::new(),
+            long_tasks: Vec::new(),
+            largest_contentful_paints: Vec::new(),
+            layout_shifts: Vec::new(),
+            observers: Vec::new(),
         }
     }
     
+    /// Measure time from `origin`, e.g. when navigation started
+    pub fn set_time_origin(&mut self, origin: Instant) {
+        self.time_origin = origin;
+    }
+    
     /// Get current high-resolution time
     pub fn now(&self) -> DOMHighResTimeStamp {
         let elapsed = self.time_origin.elapsed();
         elapsed.as_secs_f64() * 1000.0
     }
     
+    /// The time `at` on this timeline
+    pub fn timestamp_at(&self, at: Instant) -> DOMHighResTimeStamp {
+        at.saturating_duration_since(self.time_origin).as_secs_f64() * 1000.0
+    }
+    
     /// Get time origin as Unix timestamp
     pub fn time_origin(&self) -> f64 {
         SystemTime::now()
@@ -202,11 +274,16 @@ impl Performance {
     
     /// Create a performance mark
     pub fn mark(&mut self, name: String) -> Result<(), PerformanceError> {
-        let timestamp = self.now();
-        self.marks.insert(name, timestamp);
+        self.mark_at(name, self.now());
         Ok(())
     }
     
+    /// Create a performance mark at a given time
+    pub fn mark_at(&mut self, name: String, start_time: DOMHighResTimeStamp) {
+        self.queue_entry(PerformanceEntry::Mark { name: name.clone(), start_time });
+        self.marks.insert(name, start_time);
+    }
+    
     /// Clear marks
     pub fn clear_marks(&mut self, name: Option<&str>) {
         if let Some(mark_name) = name {
@@ -242,10 +319,16 @@ impl Performance {
             duration: end_time - start_time,
         };
         
-        self.measures.insert(name, measure.clone());
+        self.add_measure(measure.clone());
         Ok(measure)
     }
     
+    /// Record a measure taken elsewhere, e.g. by a script
+    pub fn add_measure(&mut self, measure: PerformanceMeasure) {
+        self.queue_entry(PerformanceEntry::Measure(measure.clone()));
+        self.measures.insert(measure.name.clone(), measure);
+    }
+    
     /// Clear measures
     pub fn clear_measures(&mut self, name: Option<&str>) {
         if let Some(measure_name) = name {
@@ -278,9 +361,17 @@ impl Performance {
                     entries.push(PerformanceEntry::Resource(resource.clone()));
                 }
             }
-            "navigation" => {
+            "navigation" if self.navigated => {
                 entries.push(PerformanceEntry::Navigation(self.navigation_timing.clone()));
             }
+            "paint" => {
+                for (name, start_time) in &self.paints {
+                    entries.push(PerformanceEntry::Paint { name: name.clone(), start_time: *start_time });
+                }
+            }
+            "longtask" => entries.extend(self.long_tasks.iter().cloned()),
+            "largest-contentful-paint" => entries.extend(self.largest_contentful_paints.iter().cloned()),
+            "layout-shift" => entries.extend(self.layout_shifts.iter().cloned()),
             _ => {}
         }
         
@@ -308,6 +399,12 @@ impl Performance {
             }
         }
         
+        for (paint, start_time) in &self.paints {
+            if paint == name {
+                entries.push(PerformanceEntry::Paint { name: name.to_string(), start_time: *start_time });
+            }
+        }
+        
         entries
     }
     
@@ -330,13 +427,16 @@ impl Performance {
             entries.push(PerformanceEntry::Resource(resource.clone()));
         }
         
-        entries.push(PerformanceEntry::Navigation(self.navigation_timing.clone()));
+        for entry_type in ["navigation", "paint", "longtask", "largest-contentful-paint", "layout-shift"] {
+            entries.extend(self.get_entries_by_type(entry_type));
+        }
         
         entries
     }
     
     /// Add resource timing entry
     pub fn add_resource_entry(&mut self, entry: PerformanceResourceTiming) {
+        self.queue_entry(PerformanceEntry::Resource(entry.clone()));
         // Limit to 150 resource entries (browser default)
         if self.resource_entries.len() >= 150 {
             self.resource_entries.remove(0);
@@ -344,6 +444,129 @@ impl Performance {
         self.resource_entries.push(entry);
     }
     
+    /// Record a paint timing entry, e.g. `first-contentful-paint`, now
+    pub fn add_paint(&mut self, name: &str) {
+        let start_time = self.now();
+        self.queue_entry(PerformanceEntry::Paint { name: name.to_string(), start_time });
+        self.paints.push((name.to_string(), start_time));
+    }
+    
+    /// Record a new largest contentful paint, now
+    ///
+    /// `size` is its visible area, `element` describes what it painted and
+    /// `url` is the image's, if it is one. Returns false, recording
+    /// nothing, unless it is larger than the previous one.
+    pub fn add_largest_contentful_paint(&mut self, size: f64, element: String, url: String) -> bool {
+        let previous = match self.largest_contentful_paints.last() {
+            Some(PerformanceEntry::LargestContentfulPaint { size, .. }) => *size,
+            _ => 0.0,
+        };
+        if previous >= size {
+            return false;
+        }
+        let entry = PerformanceEntry::LargestContentfulPaint { start_time: self.now(), size, element, url };
+        self.queue_entry(entry.clone());
+        self.largest_contentful_paints.push(entry);
+        true
+    }
+    
+    /// Record a layout shift scoring `value`, now
+    pub fn add_layout_shift(&mut self, value: f64, had_recent_input: bool) {
+        let entry = PerformanceEntry::LayoutShift { start_time: self.now(), value, had_recent_input };
+        self.queue_entry(entry.clone());
+        self.layout_shifts.push(entry);
+    }
+    
+    /// Record a task that ran for `duration`; returns whether it was a long task
+    pub fn record_task(
+        &mut self,
+        start_time: DOMHighResTimeStamp,
+        duration: DOMHighResTimeStamp,
+        attribution: TaskAttribution,
+    ) -> bool {
+        if duration < LONG_TASK_THRESHOLD {
+            return false;
+        }
+        let entry = PerformanceEntry::LongTask { start_time, duration, attribution };
+        self.queue_entry(entry.clone());
+        self.long_tasks.push(entry);
+        true
+    }
+    
+    /// Record a task that ran from `start` until now; returns whether it was a long task
+    pub fn record_task_since(&mut self, start: Instant, attribution: TaskAttribution) -> bool {
+        let duration = start.elapsed().as_secs_f64() * 1000.0;
+        self.record_task(self.now() - duration, duration, attribution)
+    }
+    
+    /// Long tasks so far, oldest first
+    pub fn long_tasks(&self) -> &[PerformanceEntry] {
+        &self.long_tasks
+    }
+    
+    /// Subscribe observer `id` to entry types, adding to those it observes
+    ///
+    /// Unsupported types are ignored. With `buffered`, entries already on
+    /// the timeline of the newly observed types are queued for it at once.
+    pub fn observe(
+        &mut self,
+        id: ObserverId,
+        entry_types: &[String],
+        buffered: bool,
+    ) -> Result<(), PerformanceError> {
+        let supported: Vec<&String> =
+            entry_types.iter().filter(|t| SUPPORTED_ENTRY_TYPES.contains(&t.as_str())).collect();
+        if supported.is_empty() {
+            return Err(PerformanceError::UnsupportedEntryType);
+        }
+        let index = match self.observers.iter().position(|o| o.id == id) {
+            Some(index) => index,
+            None => {
+                self.observers.push(PerformanceObserver { id, entry_types: Vec::new(), records: Vec::new() });
+                self.observers.len() - 1
+            }
+        };
+        let mut added = Vec::new();
+        for entry_type in supported {
+            if !self.observers[index].observes(entry_type) && !added.contains(entry_type) {
+                added.push(entry_type.clone());
+            }
+        }
+        if buffered {
+            let mut entries: Vec<_> = added.iter().flat_map(|t| self.get_entries_by_type(t)).collect();
+            entries.sort_by(|a, b| a.start_time().total_cmp(&b.start_time()));
+            self.observers[index].records.extend(entries);
+        }
+        self.observers[index].entry_types.extend(added);
+        Ok(())
+    }
+    
+    /// Stop observer `id`, dropping entries queued for it
+    pub fn disconnect(&mut self, id: ObserverId) {
+        self.observers.retain(|o| o.id != id);
+    }
+    
+    pub fn observers(&self) -> &[PerformanceObserver] {
+        &self.observers
+    }
+    
+    /// Take the entries queued for observers, one batch per observer with any
+    pub fn take_observer_records(&mut self) -> Vec<(ObserverId, Vec<PerformanceEntry>)> {
+        self.observers
+            .iter_mut()
+            .filter(|o| !o.records.is_empty())
+            .map(|o| (o.id, std::mem::take(&mut o.records)))
+            .collect()
+    }
+    
+    fn queue_entry(&mut self, entry: PerformanceEntry) {
+        for observer in &mut self.observers {
+            if observer.observes(entry.entry_type()) {
+                observer.records.push(entry.clone());
+            }
+        }
+    }
+    
     /// Clear resource timings
     pub fn clear_resource_timings(&mut self) {
         self.resource_entries.clear();
@@ -351,7 +574,9 @@ impl Performance {
     
     /// Set navigation timing value
     pub fn set_navigation_timing(&mut self, timing: NavigationTiming) {
+        self.queue_entry(PerformanceEntry::Navigation(timing.clone()));
         self.navigation_timing = timing;
+        self.navigated = true;
     }
     
     /// Get navigation timing
@@ -397,6 +622,82 @@ pub enum PerformanceEntry {
     Measure(PerformanceMeasure),
     Resource(PerformanceResourceTiming),
     Navigation(NavigationTiming),
+    Paint { name: String, start_time: DOMHighResTimeStamp },
+    LongTask { start_time: DOMHighResTimeStamp, duration: DOMHighResTimeStamp, attribution: TaskAttribution },
+    LargestContentfulPaint { start_time: DOMHighResTimeStamp, size: f64, element: String, url: String },
+    LayoutShift { start_time: DOMHighResTimeStamp, value: f64, had_recent_input: bool },
+}
+
+impl PerformanceEntry {
+    /// The entry's `entryType`
+    pub fn entry_type(&self) -> &'static str {
+        match self {
+            PerformanceEntry::Mark { .. } => "mark",
+            PerformanceEntry::Measure(_) => "measure",
+            PerformanceEntry::Resource(_) => "resource",
+            PerformanceEntry::Navigation(_) => "navigation",
+            PerformanceEntry::Paint { .. } => "paint",
+            PerformanceEntry::LongTask { .. } => "longtask",
+            PerformanceEntry::LargestContentfulPaint { .. } => "largest-contentful-paint",
+            PerformanceEntry::LayoutShift { .. } => "layout-shift",
+        }
+    }
+    
+    pub fn name(&self) -> &str {
+        match self {
+            PerformanceEntry::Mark { name, .. } | PerformanceEntry::Paint { name, .. } => name,
+            PerformanceEntry::Measure(measure) => &measure.name,
+            PerformanceEntry::Resource(resource) => &resource.name,
+            PerformanceEntry::Navigation(_) => "document",
+            // Long tasks are attributed to the page's own frame
+            PerformanceEntry::LongTask { .. } => "self",
+            PerformanceEntry::LargestContentfulPaint { .. } | PerformanceEntry::LayoutShift { .. } => "",
+        }
+    }
+    
+    pub fn start_time(&self) -> DOMHighResTimeStamp {
+        match self {
+            PerformanceEntry::Mark { start_time, .. }
+            | PerformanceEntry::Paint { start_time, .. }
+            | PerformanceEntry::LongTask { start_time, .. }
+            | PerformanceEntry::LargestContentfulPaint { start_time, .. }
+            | PerformanceEntry::LayoutShift { start_time, .. } => *start_time,
+            PerformanceEntry::Measure(measure) => measure.start_time,
+            PerformanceEntry::Resource(resource) => resource.start_time,
+            PerformanceEntry::Navigation(timing) => timing.navigation_start,
+        }
+    }
+    
+    pub fn duration(&self) -> DOMHighResTimeStamp {
+        match self {
+            PerformanceEntry::Mark { .. }
+            | PerformanceEntry::Paint { .. }
+            | PerformanceEntry::LargestContentfulPaint { .. }
+            | PerformanceEntry::LayoutShift { .. } => 0.0,
+            PerformanceEntry::LongTask { duration, .. } => *duration,
+            PerformanceEntry::Measure(measure) => measure.duration,
+            PerformanceEntry::Resource(resource) => resource.duration,
+            PerformanceEntry::Navigation(timing) => timing.load_event_end - timing.navigation_start,
+        }
+    }
+}
+
+/// The kind of work that kept the main thread busy in a long task
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskAttribution {
+    Script,
+    Layout,
+    Parse,
+}
+
+impl TaskAttribution {
+    pub fn label(&self) -> &'static str {
+        match self {
+            TaskAttribution::Script => "script",
+            TaskAttribution::Layout => "layout",
+            TaskAttribution::Parse => "parse",
+        }
+    }
 }
 
 /// Performance errors
@@ -405,6 +706,7 @@ pub enum PerformanceError {
     MarkNotFound,
     InvalidMeasure,
     InvalidEntry,
+    UnsupportedEntryType,
 }
 
 impl std::fmt::Display for PerformanceError {
@@ -413,6 +715,7 @@ impl std::fmt::Display for PerformanceError {
             PerformanceError::MarkNotFound => write!(f, "Performance mark not found"),
             PerformanceError::InvalidMeasure => write!(f, "Invalid performance measure"),
             PerformanceError::InvalidEntry => write!(f, "Invalid performance entry"),
+            PerformanceError::UnsupportedEntryType => write!(f, "No supported performance entry types"),
         }
     }
 }
@@ -593,6 +896,56 @@ mod tests {
         assert_eq!(perf.marks.len(), 0);
     }
     
+    #[test]
+    fn test_observers_get_buffered_and_new_entries() {
+        let mut perf = Performance::new();
+        perf.mark_at("boot".to_string(), 5.0);
+        perf.add_paint("first-paint");
+        perf.record_task(1.0, 20.0, TaskAttribution::Script);
+        perf.record_task(2.0, 80.0, TaskAttribution::Layout);
+        
+        let types = |list: &[&str]| list.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        assert_eq!(perf.observe(1, &types(&["event"]), false), Err(PerformanceError::UnsupportedEntryType));
+        perf.observe(1, &types(&["mark", "longtask", "event"]), true).unwrap();
+        perf.observe(2, &types(&["measure"]), false).unwrap();
+        assert_eq!(perf.observers()[0].entry_types(), ["mark", "longtask"]);
+        
+        // Buffered entries come in timeline order, then new ones join the batch
+        perf.mark_at("ready".to_string(), 40.0);
+        perf.add_paint("first-contentful-paint");
+        let batches = perf.take_observer_records();
+        assert_eq!(batches.len(), 1);
+        let (id, entries) = &batches[0];
+        let described: Vec<_> = entries.iter().map(|e| (e.entry_type(), e.name(), e.start_time())).collect();
+        assert_eq!(*id, 1);
+        assert_eq!(described, [("longtask", "self", 2.0), ("mark", "boot", 5.0), ("mark", "ready", 40.0)]);
+        assert!(perf.take_observer_records().is_empty());
+        
+        // Observing a type again buffers only what was not observed before
+        perf.observe(1, &types(&["mark", "paint"]), true).unwrap();
+        perf.measure("boot-to-ready".to_string(), Some("boot"), Some("ready")).unwrap();
+        let batches = perf.take_observer_records();
+        let names: Vec<_> = batches.iter().map(|(id, entries)| (*id, entries.len(), entries[0].name())).collect();
+        assert_eq!(names, [(1, 2, "first-paint"), (2, 1, "boot-to-ready")]);
+        
+        perf.disconnect(1);
+        perf.add_paint("first-paint");
+        assert!(perf.take_observer_records().is_empty());
+    }
+    
+    #[test]
+    fn test_navigation_entry_once_timed() {
+        let mut perf = Performance::new();
+        assert!(perf.get_entries_by_type("navigation").is_empty());
+        perf.observe(1, &["navigation".to_string()], true).unwrap();
+        assert!(perf.take_observer_records().is_empty());
+        
+        perf.set_navigation_timing(NavigationTiming { load_event_end: 120.0, ..Default::default() });
+        let batches = perf.take_observer_records();
+        assert_eq!(batches[0].1[0].duration(), 120.0);
+        assert_eq!(perf.get_entries().len(), 1);
+    }
+    
     #[test]
     fn test_time_origin() {
         let perf = Performance::new();
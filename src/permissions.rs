@@ -0,0 +1,428 @@
+// Permissions - per-origin decisions on powerful features
+//
+// Pages ask for notifications, location, the clipboard or the camera; the
+// answer depends on what the user decided for the page's origin. Undecided
+// requests are put to a prompter, which answers at once (embedders, tests)
+// or leaves the prompt pending for the browser UI to ask the user. Allow
+// and Block are remembered in a JSON file next to the preferences;
+// dismissing a prompt decides nothing.
+
+use std::collections::HashMap;
+use std::path::Path;
+use url::Url;
+
+/// A feature pages need permission to use, as `navigator.permissions` names it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PermissionName {
+    Notifications,
+    Geolocation,
+    ClipboardRead,
+    ClipboardWrite,
+    Camera,
+}
+
+impl PermissionName {
+    pub const ALL: [PermissionName; 5] = [
+        PermissionName::Notifications,
+        PermissionName::Geolocation,
+        PermissionName::ClipboardRead,
+        PermissionName::ClipboardWrite,
+        PermissionName::Camera,
+    ];
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "notifications" => Some(PermissionName::Notifications),
+            "geolocation" => Some(PermissionName::Geolocation),
+            "clipboard-read" => Some(PermissionName::ClipboardRead),
+            "clipboard-write" => Some(PermissionName::ClipboardWrite),
+            "camera" => Some(PermissionName::Camera),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PermissionName::Notifications => "notifications",
+            PermissionName::Geolocation => "geolocation",
+            PermissionName::ClipboardRead => "clipboard-read",
+            PermissionName::ClipboardWrite => "clipboard-write",
+            PermissionName::Camera => "camera",
+        }
+    }
+
+    /// What the prompt says the page wants, e.g. "know your location"
+    pub fn prompt_text(&self) -> &'static str {
+        match self {
+            PermissionName::Notifications => "show notifications",
+            PermissionName::Geolocation => "know your location",
+            PermissionName::ClipboardRead => "see text and images copied to the clipboard",
+            PermissionName::ClipboardWrite => "change the clipboard",
+            PermissionName::Camera => "use your camera",
+        }
+    }
+}
+
+/// Whether a page may use a feature, as `PermissionStatus.state` reports it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionState {
+    Granted,
+    Denied,
+    /// Not decided; asking prompts the user, though clipboard writes are allowed during user activation
+    Prompt,
+}
+
+impl PermissionState {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "granted" => Some(PermissionState::Granted),
+            "denied" => Some(PermissionState::Denied),
+            "prompt" => Some(PermissionState::Prompt),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PermissionState::Granted => "granted",
+            PermissionState::Denied => "denied",
+            PermissionState::Prompt => "prompt",
+        }
+    }
+}
+
+/// Identifies a pending prompt
+pub type PromptId = u64;
+
+/// A request waiting for the user's answer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermissionPrompt {
+    pub id: PromptId,
+    /// Origin asking, e.g. `https://maps.example`
+    pub origin: String,
+    pub name: PermissionName,
+}
+
+impl PermissionPrompt {
+    /// e.g. "https://maps.example wants to know your location"
+    pub fn message(&self) -> String {
+        format!("{} wants to {}", self.origin, self.name.prompt_text())
+    }
+}
+
+/// How the user answered a prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptAnswer {
+    /// Grant, and remember it
+    Allow,
+    /// Deny, and remember it
+    Block,
+    /// Close the prompt without deciding; the request is denied this time
+    Dismiss,
+}
+
+impl PromptAnswer {
+    /// State the request is settled with
+    pub fn state(&self) -> PermissionState {
+        match self {
+            PromptAnswer::Allow => PermissionState::Granted,
+            PromptAnswer::Block | PromptAnswer::Dismiss => PermissionState::Denied,
+        }
+    }
+}
+
+/// Asks the user about permission requests for the embedder
+pub trait PermissionPrompter: Send {
+    /// Answer a prompt, or `None` to leave it pending until `PermissionManager::answer`
+    fn prompt(&mut self, prompt: &PermissionPrompt) -> Option<PromptAnswer>;
+}
+
+/// What became of a permission request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestOutcome {
+    /// Settled from a decision or the prompter's answer
+    Decided(PermissionState),
+    /// Waiting for the user to answer a prompt
+    Pending(PromptId),
+}
+
+/// Errors loading or saving permission decisions
+#[derive(Debug)]
+pub enum PermissionsError {
+    /// The decisions file could not be read or written
+    Io(String),
+    /// The decisions file is not valid
+    Parse(String),
+}
+
+impl std::fmt::Display for PermissionsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PermissionsError::Io(msg) => write!(f, "Permissions I/O error: {}", msg),
+            PermissionsError::Parse(msg) => write!(f, "Permissions parse error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for PermissionsError {}
+
+/// Decisions by origin, as saved: origin -> permission name -> "granted" or "denied"
+type StoredDecisions = HashMap<String, HashMap<String, String>>;
+
+/// The user's permission decisions and the prompts waiting for one
+#[derive(Default)]
+pub struct PermissionManager {
+    decisions: HashMap<String, HashMap<PermissionName, PermissionState>>,
+    pending: Vec<PermissionPrompt>,
+    prompter: Option<Box<dyn PermissionPrompter>>,
+    next_id: PromptId,
+}
+
+impl PermissionManager {
+    /// No decisions, with prompts left for the browser UI
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask a prompter about requests before leaving them pending
+    pub fn set_prompter(&mut self, prompter: Box<dyn PermissionPrompter>) {
+        self.prompter = Some(prompter);
+    }
+
+    /// State of a permission for a page
+    ///
+    /// Pages in insecure contexts and opaque origins are always denied.
+    pub fn state(&self, url: &Url, name: PermissionName) -> PermissionState {
+        let Some(origin) = secure_origin(url) else {
+            return PermissionState::Denied;
+        };
+        self.decisions
+            .get(&origin)
+            .and_then(|decisions| decisions.get(&name))
+            .copied()
+            .unwrap_or(PermissionState::Prompt)
+    }
+
+    /// State of every permission for a page
+    pub fn states(&self, url: &Url) -> HashMap<PermissionName, PermissionState> {
+        PermissionName::ALL.iter().map(|&name| (name, self.state(url, name))).collect()
+    }
+
+    /// Decide a permission for a page's origin; `Prompt` forgets the decision
+    ///
+    /// Returns false for pages whose origin cannot be granted anything.
+    pub fn set_state(&mut self, url: &Url, name: PermissionName, state: PermissionState) -> bool {
+        let Some(origin) = secure_origin(url) else {
+            return false;
+        };
+        self.decide(origin, name, state);
+        true
+    }
+
+    /// Forget every decision for a page's origin
+    pub fn reset(&mut self, url: &Url) {
+        if let Some(origin) = secure_origin(url) {
+            self.decisions.remove(&origin);
+        }
+    }
+
+    /// Origins with decisions, sorted
+    pub fn origins(&self) -> Vec<&str> {
+        let mut origins: Vec<&str> = self.decisions.keys().map(String::as_str).collect();
+        origins.sort_unstable();
+        origins
+    }
+
+    /// A page asks to use a feature
+    ///
+    /// Decided permissions settle at once. Otherwise the prompter is asked;
+    /// without an answer the request waits on a prompt, shared with any
+    /// other request from the origin for the same permission.
+    pub fn request(&mut self, url: &Url, name: PermissionName) -> RequestOutcome {
+        let state = self.state(url, name);
+        let origin = match secure_origin(url) {
+            Some(origin) if state == PermissionState::Prompt => origin,
+            _ => return RequestOutcome::Decided(state),
+        };
+        let existing = self.pending.iter().find(|prompt| prompt.origin == origin && prompt.name == name);
+        if let Some(prompt) = existing {
+            return RequestOutcome::Pending(prompt.id);
+        }
+        self.next_id += 1;
+        let prompt = PermissionPrompt { id: self.next_id, origin, name };
+        match self.prompter.as_mut().and_then(|prompter| prompter.prompt(&prompt)) {
+            Some(answer) => {
+                self.remember(&prompt, answer);
+                RequestOutcome::Decided(answer.state())
+            }
+            None => {
+                self.pending.push(prompt);
+                RequestOutcome::Pending(self.next_id)
+            }
+        }
+    }
+
+    /// Prompts waiting for an answer, oldest first
+    pub fn pending(&self) -> &[PermissionPrompt] {
+        &self.pending
+    }
+
+    /// The oldest prompt for a page's origin
+    pub fn pending_for(&self, url: &Url) -> Option<&PermissionPrompt> {
+        let origin = secure_origin(url)?;
+        self.pending.iter().find(|prompt| prompt.origin == origin)
+    }
+
+    /// Answer a pending prompt, returning it and the state its requests settle with
+    pub fn answer(&mut self, id: PromptId, answer: PromptAnswer) -> Option<(PermissionPrompt, PermissionState)> {
+        let index = self.pending.iter().position(|prompt| prompt.id == id)?;
+        let prompt = self.pending.remove(index);
+        self.remember(&prompt, answer);
+        Some((prompt, answer.state()))
+    }
+
+    /// Load decisions from a file
+    ///
+    /// Returns no decisions if the file does not exist yet. Unknown
+    /// permissions and states are skipped.
+    pub fn load(path: &Path) -> Result<Self, PermissionsError> {
+        let json = match std::fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::new()),
+            Err(e) => return Err(PermissionsError::Io(e.to_string())),
+        };
+        let stored: StoredDecisions =
+            serde_json::from_str(&json).map_err(|e| PermissionsError::Parse(e.to_string()))?;
+        let mut manager = Self::new();
+        for (origin, decisions) in stored {
+            for (name, state) in decisions {
+                let decision = (PermissionName::from_str(&name), PermissionState::from_str(&state));
+                if let (Some(name), Some(state)) = decision {
+                    manager.decide(origin.clone(), name, state);
+                }
+            }
+        }
+        Ok(manager)
+    }
+
+    /// Save decisions to a file, creating its directory if needed
+    pub fn save(&self, path: &Path) -> Result<(), PermissionsError> {
+        let stored: StoredDecisions = self
+            .decisions
+            .iter()
+            .map(|(origin, decisions)| {
+                let decisions =
+                    decisions.iter().map(|(name, state)| (name.as_str().to_string(), state.as_str().to_string()));
+                (origin.clone(), decisions.collect())
+            })
+            .collect();
+        let json = serde_json::to_string_pretty(&stored).map_err(|e| PermissionsError::Parse(e.to_string()))?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| PermissionsError::Io(e.to_string()))?;
+        }
+        std::fs::write(path, json).map_err(|e| PermissionsError::Io(e.to_string()))
+    }
+
+    fn remember(&mut self, prompt: &PermissionPrompt, answer: PromptAnswer) {
+        if answer != PromptAnswer::Dismiss {
+            self.decide(prompt.origin.clone(), prompt.name, answer.state());
+        }
+    }
+
+    fn decide(&mut self, origin: String, name: PermissionName, state: PermissionState) {
+        if state == PermissionState::Prompt {
+            if let Some(decisions) = self.decisions.get_mut(&origin) {
+                decisions.remove(&name);
+                if decisions.is_empty() {
+                    self.decisions.remove(&origin);
+                }
+            }
+        } else {
+            self.decisions.entry(origin).or_default().insert(name, state);
+        }
+    }
+}
+
+/// A page's origin, if it is a secure context that can be granted permissions
+///
+/// HTTPS pages are secure, as are pages from the local machine.
+fn secure_origin(url: &Url) -> Option<String> {
+    let origin = url.origin();
+    if !origin.is_tuple() {
+        return None;
+    }
+    let local = matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]"));
+    (url.scheme() == "https" || local).then(|| origin.ascii_serialization())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    struct AlwaysBlock;
+
+    impl PermissionPrompter for AlwaysBlock {
+        fn prompt(&mut self, _prompt: &PermissionPrompt) -> Option<PromptAnswer> {
+            Some(PromptAnswer::Block)
+        }
+    }
+
+    #[test]
+    fn test_prompts_are_shared_and_answers_remembered() {
+        let mut permissions = PermissionManager::new();
+        let page = url("https://maps.example/route?to=home");
+        assert_eq!(permissions.state(&page, PermissionName::Geolocation), PermissionState::Prompt);
+        let insecure = url("http://maps.example/");
+        assert_eq!(permissions.state(&insecure, PermissionName::Geolocation), PermissionState::Denied);
+
+        let RequestOutcome::Pending(id) = permissions.request(&page, PermissionName::Geolocation) else {
+            panic!("expected a prompt");
+        };
+        let other_page = url("https://maps.example/other");
+        assert_eq!(permissions.request(&other_page, PermissionName::Geolocation), RequestOutcome::Pending(id));
+        let prompt = permissions.pending_for(&page).unwrap();
+        assert_eq!(prompt.message(), "https://maps.example wants to know your location");
+
+        let (_, state) = permissions.answer(id, PromptAnswer::Allow).unwrap();
+        assert_eq!(state, PermissionState::Granted);
+        assert!(permissions.pending().is_empty());
+        let outcome = permissions.request(&page, PermissionName::Geolocation);
+        assert_eq!(outcome, RequestOutcome::Decided(PermissionState::Granted));
+
+        // Dismissing denies once and asks again next time
+        let RequestOutcome::Pending(id) = permissions.request(&page, PermissionName::Camera) else {
+            panic!("expected a prompt");
+        };
+        assert_eq!(permissions.answer(id, PromptAnswer::Dismiss).unwrap().1, PermissionState::Denied);
+        assert_eq!(permissions.state(&page, PermissionName::Camera), PermissionState::Prompt);
+
+        permissions.set_prompter(Box::new(AlwaysBlock));
+        let outcome = permissions.request(&page, PermissionName::Notifications);
+        assert_eq!(outcome, RequestOutcome::Decided(PermissionState::Denied));
+        assert_eq!(permissions.state(&page, PermissionName::Notifications), PermissionState::Denied);
+    }
+
+    #[test]
+    fn test_decisions_persist() {
+        let path = std::env::temp_dir().join(format!("permissions-test-{}.json", std::process::id()));
+        let mut permissions = PermissionManager::new();
+        let page = url("https://chat.example/");
+        permissions.set_state(&page, PermissionName::Notifications, PermissionState::Granted);
+        permissions.set_state(&page, PermissionName::Camera, PermissionState::Denied);
+        let local = url("http://localhost:8080/");
+        permissions.set_state(&local, PermissionName::ClipboardRead, PermissionState::Granted);
+        permissions.save(&path).unwrap();
+
+        let loaded = PermissionManager::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.origins(), vec!["http://localhost:8080", "https://chat.example"]);
+        let states = loaded.states(&page);
+        assert_eq!(states[&PermissionName::Notifications], PermissionState::Granted);
+        assert_eq!(states[&PermissionName::Camera], PermissionState::Denied);
+        assert_eq!(states[&PermissionName::Geolocation], PermissionState::Prompt);
+    }
+}
@@ -0,0 +1,521 @@
+// Browser preferences with per-site overrides
+//
+// Global settings live in one JSON file in the user's config directory.
+// Sites can override the settings that make sense per origin (scripts,
+// cookies, fonts); `for_site` resolves the values a page load should use.
+
+use crate::animation::MotionPolicy;
+use crate::css::{MediaFeatures, Unit, Value};
+use crate::net::CookiePolicy;
+use crate::style::PropertyMap;
+use crate::ui::SearchEngine;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// Environment variable naming the preferences file, overriding the default
+pub const PREFERENCES_PATH_VAR: &str = "BROWSER_PREFERENCES";
+
+/// Smallest and largest default font sizes accepted
+const FONT_SIZE_RANGE: (f32, f32) = (9.0, 72.0);
+
+/// Colour scheme for the browser chrome and pages that support one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    /// Follow the operating system
+    System,
+    Light,
+    Dark,
+}
+
+impl Theme {
+    /// Parse a theme name
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "system" => Some(Theme::System),
+            "light" => Some(Theme::Light),
+            "dark" => Some(Theme::Dark),
+            _ => None,
+        }
+    }
+
+    /// Theme name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Theme::System => "system",
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+        }
+    }
+}
+
+/// Whether pages should minimise non-essential motion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReducedMotion {
+    /// Follow the operating system's accessibility setting
+    System,
+    Reduce,
+    NoPreference,
+}
+
+impl ReducedMotion {
+    /// Parse a reduced-motion setting name
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "system" => Some(ReducedMotion::System),
+            "reduce" => Some(ReducedMotion::Reduce),
+            "no-preference" => Some(ReducedMotion::NoPreference),
+            _ => None,
+        }
+    }
+
+    /// Reduced-motion setting name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReducedMotion::System => "system",
+            ReducedMotion::Reduce => "reduce",
+            ReducedMotion::NoPreference => "no-preference",
+        }
+    }
+}
+
+/// Does the operating system ask applications to reduce motion
+///
+/// Reads the GNOME animations switch on Linux, the accessibility
+/// "Reduce motion" setting on macOS and the "Animate windows" setting
+/// on Windows. Returns false when the setting cannot be read.
+pub fn system_prefers_reduced_motion() -> bool {
+    use std::process::Command;
+
+    let (command, args, reduced): (&str, &[&str], fn(&str) -> bool) = if cfg!(target_os = "macos") {
+        ("defaults", &["read", "com.apple.universalaccess", "reduceMotion"], |out| out.trim() == "1")
+    } else if cfg!(windows) {
+        let args: &[&str] = &["query", r"HKCU\Control Panel\Desktop\WindowMetrics", "/v", "MinAnimate"];
+        ("reg", args, |out| out.split_whitespace().last() == Some("0"))
+    } else {
+        let args: &[&str] = &["get", "org.gnome.desktop.interface", "enable-animations"];
+        ("gsettings", args, |out| out.trim() == "false")
+    };
+    Command::new(command)
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .is_some_and(|output| reduced(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Errors loading or saving preferences
+#[derive(Debug)]
+pub enum PreferencesError {
+    /// The preferences file could not be read or written
+    Io(String),
+    /// The preferences file is not valid
+    Parse(String),
+}
+
+impl std::fmt::Display for PreferencesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PreferencesError::Io(msg) => write!(f, "Preferences I/O error: {}", msg),
+            PreferencesError::Parse(msg) => write!(f, "Preferences parse error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for PreferencesError {}
+
+/// Settings one origin overrides; `None` uses the global value
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SiteOverrides {
+    pub javascript_enabled: Option<bool>,
+    pub cookie_policy: Option<CookiePolicy>,
+    pub content_filtering: Option<bool>,
+    pub default_font: Option<String>,
+    pub font_size: Option<f32>,
+}
+
+impl SiteOverrides {
+    /// Does this override nothing
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Settings in effect for one page, after applying its site's overrides
+#[derive(Debug, Clone, PartialEq)]
+pub struct SitePreferences {
+    pub javascript_enabled: bool,
+    pub cookie_policy: CookiePolicy,
+    pub content_filtering: bool,
+    pub default_font: String,
+    pub font_size: f32,
+}
+
+impl SitePreferences {
+    /// Inherited font values the page's style starts from
+    pub fn style_defaults(&self) -> PropertyMap {
+        let mut defaults = PropertyMap::new();
+        defaults.insert("font-family".to_string(), Value::Keyword(self.default_font.clone()));
+        defaults.insert("font-size".to_string(), Value::Length(self.font_size, Unit::Px));
+        defaults
+    }
+}
+
+/// Engine-wide preferences
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preferences {
+    /// Font family for text the page gives no font
+    pub default_font: String,
+    /// Font size in CSS pixels for text the page gives no size
+    pub font_size: f32,
+    pub javascript_enabled: bool,
+    pub cookie_policy: CookiePolicy,
+    /// Block requests and hide elements matching the content filter lists
+    pub content_filtering: bool,
+    pub theme: Theme,
+    /// Answer pages get for the `prefers-reduced-motion` media feature
+    pub reduced_motion: ReducedMotion,
+    /// What happens to CSS animations and transitions
+    pub animations: MotionPolicy,
+    /// Page opened at startup
+    pub homepage: String,
+    /// Engine for searches typed in the address bar
+    pub search_engine: SearchEngine,
+    /// Overrides by origin (e.g. `https://example.com`)
+    sites: HashMap<String, SiteOverrides>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct StoredSite {
+    javascript_enabled: Option<bool>,
+    cookie_policy: Option<String>,
+    content_filtering: Option<bool>,
+    default_font: Option<String>,
+    font_size: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct StoredPreferences {
+    default_font: String,
+    font_size: f32,
+    javascript_enabled: bool,
+    cookie_policy: String,
+    content_filtering: bool,
+    theme: String,
+    reduced_motion: String,
+    animations: String,
+    homepage: String,
+    search_engine_name: String,
+    search_engine_template: String,
+    sites: HashMap<String, StoredSite>,
+}
+
+impl Default for StoredPreferences {
+    fn default() -> Self {
+        Preferences::new().to_stored()
+    }
+}
+
+impl Preferences {
+    /// Create the default preferences
+    pub fn new() -> Self {
+        Self {
+            default_font: "sans-serif".to_string(),
+            font_size: 16.0,
+            javascript_enabled: true,
+            cookie_policy: CookiePolicy::BlockThirdParty,
+            content_filtering: true,
+            theme: Theme::System,
+            reduced_motion: ReducedMotion::System,
+            animations: MotionPolicy::Normal,
+            homepage: "about:blank".to_string(),
+            search_engine: SearchEngine::default(),
+            sites: HashMap::new(),
+        }
+    }
+
+    /// Default location of the preferences file
+    ///
+    /// `$BROWSER_PREFERENCES` if set, otherwise `preferences.json` in the
+    /// browser's directory under `$XDG_CONFIG_HOME` or `~/.config`.
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os(PREFERENCES_PATH_VAR) {
+            return Some(PathBuf::from(path));
+        }
+        let config = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config.join("rust-browser").join("preferences.json"))
+    }
+
+    /// Settings for a page, with its origin's overrides applied
+    pub fn for_site(&self, url: &Url) -> SitePreferences {
+        let site = self.site_overrides(url).cloned().unwrap_or_default();
+        SitePreferences {
+            javascript_enabled: site.javascript_enabled.unwrap_or(self.javascript_enabled),
+            cookie_policy: site.cookie_policy.unwrap_or(self.cookie_policy),
+            content_filtering: site.content_filtering.unwrap_or(self.content_filtering),
+            default_font: site.default_font.unwrap_or_else(|| self.default_font.clone()),
+            font_size: site.font_size.unwrap_or(self.font_size),
+        }
+    }
+
+    /// Does the user want reduced motion, asking the OS if set to follow it
+    pub fn prefers_reduced_motion(&self) -> bool {
+        match self.reduced_motion {
+            ReducedMotion::System => system_prefers_reduced_motion(),
+            ReducedMotion::Reduce => true,
+            ReducedMotion::NoPreference => false,
+        }
+    }
+
+    /// User preference media features pages' media queries are evaluated against
+    pub fn media_features(&self) -> MediaFeatures {
+        MediaFeatures { reduced_motion: self.prefers_reduced_motion() }
+    }
+
+    /// Overrides for a URL's origin
+    pub fn site_overrides(&self, url: &Url) -> Option<&SiteOverrides> {
+        self.sites.get(&origin_key(url)?)
+    }
+
+    /// Replace the overrides for a URL's origin
+    ///
+    /// Empty overrides remove the entry. Returns false for URLs without
+    /// an origin (e.g. `about:` and `data:` pages).
+    pub fn set_site_overrides(&mut self, url: &Url, overrides: SiteOverrides) -> bool {
+        let Some(key) = origin_key(url) else {
+            return false;
+        };
+        if overrides.is_empty() {
+            self.sites.remove(&key);
+        } else {
+            self.sites.insert(key, overrides);
+        }
+        true
+    }
+
+    /// Change one origin's overrides in place
+    pub fn update_site_overrides(&mut self, url: &Url, change: impl FnOnce(&mut SiteOverrides)) -> bool {
+        let mut overrides = self.site_overrides(url).cloned().unwrap_or_default();
+        change(&mut overrides);
+        self.set_site_overrides(url, overrides)
+    }
+
+    /// Origins with overrides, sorted
+    pub fn sites(&self) -> Vec<&str> {
+        let mut sites: Vec<&str> = self.sites.keys().map(String::as_str).collect();
+        sites.sort_unstable();
+        sites
+    }
+
+    /// Remove every per-site override
+    pub fn clear_site_overrides(&mut self) {
+        self.sites.clear();
+    }
+
+    /// Load preferences from a file
+    ///
+    /// Returns the defaults if the file does not exist yet. Unknown or
+    /// invalid values fall back to their defaults.
+    pub fn load(path: &Path) -> Result<Self, PreferencesError> {
+        let json = match std::fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::new()),
+            Err(e) => return Err(PreferencesError::Io(e.to_string())),
+        };
+        let stored: StoredPreferences =
+            serde_json::from_str(&json).map_err(|e| PreferencesError::Parse(e.to_string()))?;
+        Ok(Self::from_stored(stored))
+    }
+
+    /// Save preferences to a file, creating its directory if needed
+    pub fn save(&self, path: &Path) -> Result<(), PreferencesError> {
+        let json = serde_json::to_string_pretty(&self.to_stored())
+            .map_err(|e| PreferencesError::Parse(e.to_string()))?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| PreferencesError::Io(e.to_string()))?;
+        }
+        std::fs::write(path, json).map_err(|e| PreferencesError::Io(e.to_string()))
+    }
+
+    fn to_stored(&self) -> StoredPreferences {
+        StoredPreferences {
+            default_font: self.default_font.clone(),
+            font_size: self.font_size,
+            javascript_enabled: self.javascript_enabled,
+            cookie_policy: self.cookie_policy.as_str().to_string(),
+            content_filtering: self.content_filtering,
+            theme: self.theme.as_str().to_string(),
+            reduced_motion: self.reduced_motion.as_str().to_string(),
+            animations: self.animations.as_str().to_string(),
+            homepage: self.homepage.clone(),
+            search_engine_name: self.search_engine.name.clone(),
+            search_engine_template: self.search_engine.template.clone(),
+            sites: self
+                .sites
+                .iter()
+                .map(|(origin, site)| {
+                    let stored = StoredSite {
+                        javascript_enabled: site.javascript_enabled,
+                        cookie_policy: site.cookie_policy.map(|p| p.as_str().to_string()),
+                        content_filtering: site.content_filtering,
+                        default_font: site.default_font.clone(),
+                        font_size: site.font_size,
+                    };
+                    (origin.clone(), stored)
+                })
+                .collect(),
+        }
+    }
+
+    fn from_stored(stored: StoredPreferences) -> Self {
+        let defaults = Self::new();
+        let (min, max) = FONT_SIZE_RANGE;
+        let search_engine = if stored.search_engine_template.contains("{searchTerms}") {
+            SearchEngine::new(&stored.search_engine_name, &stored.search_engine_template)
+        } else {
+            defaults.search_engine.clone()
+        };
+        Self {
+            default_font: stored.default_font,
+            font_size: stored.font_size.clamp(min, max),
+            javascript_enabled: stored.javascript_enabled,
+            cookie_policy: CookiePolicy::from_str(&stored.cookie_policy).unwrap_or(defaults.cookie_policy),
+            content_filtering: stored.content_filtering,
+            theme: Theme::from_str(&stored.theme).unwrap_or(defaults.theme),
+            reduced_motion: ReducedMotion::from_str(&stored.reduced_motion).unwrap_or(defaults.reduced_motion),
+            animations: MotionPolicy::from_str(&stored.animations).unwrap_or(defaults.animations),
+            homepage: stored.homepage,
+            search_engine,
+            sites: stored
+                .sites
+                .into_iter()
+                .map(|(origin, site)| {
+                    let overrides = SiteOverrides {
+                        javascript_enabled: site.javascript_enabled,
+                        cookie_policy: site.cookie_policy.as_deref().and_then(CookiePolicy::from_str),
+                        content_filtering: site.content_filtering,
+                        default_font: site.default_font,
+                        font_size: site.font_size.map(|size| size.clamp(min, max)),
+                    };
+                    (origin, overrides)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Key for a URL's origin, or `None` for opaque origins
+fn origin_key(url: &Url) -> Option<String> {
+    let origin = url.origin();
+    origin.is_tuple().then(|| origin.ascii_serialization())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_site_overrides_apply_per_origin() {
+        let mut prefs = Preferences::new();
+        let news = url("https://news.example.com/story");
+        assert!(prefs.set_site_overrides(
+            &news,
+            SiteOverrides { javascript_enabled: Some(false), font_size: Some(20.0), ..Default::default() },
+        ));
+
+        let site = prefs.for_site(&url("https://news.example.com/other"));
+        assert!(!site.javascript_enabled);
+        assert_eq!(site.font_size, 20.0);
+        assert_eq!(site.cookie_policy, prefs.cookie_policy);
+
+        // Other origins, including another scheme, use the global values
+        assert!(prefs.for_site(&url("http://news.example.com/")).javascript_enabled);
+        assert!(prefs.for_site(&url("https://example.com/")).javascript_enabled);
+        assert_eq!(prefs.sites(), vec!["https://news.example.com"]);
+
+        // Pages without an origin cannot have overrides
+        assert!(!prefs.set_site_overrides(&url("about:blank"), SiteOverrides::default()));
+    }
+
+    #[test]
+    fn test_update_and_remove_overrides() {
+        let mut prefs = Preferences::new();
+        let page = url("https://example.com/");
+        prefs.update_site_overrides(&page, |site| site.cookie_policy = Some(CookiePolicy::BlockAll));
+        assert_eq!(prefs.for_site(&page).cookie_policy, CookiePolicy::BlockAll);
+
+        // Clearing the last override drops the entry
+        prefs.update_site_overrides(&page, |site| site.cookie_policy = None);
+        assert!(prefs.site_overrides(&page).is_none());
+        assert!(prefs.sites().is_empty());
+    }
+
+    #[test]
+    fn test_style_defaults() {
+        let mut prefs = Preferences::new();
+        prefs.default_font = "serif".to_string();
+        prefs.font_size = 18.0;
+        let defaults = prefs.for_site(&url("https://example.com/")).style_defaults();
+        assert_eq!(defaults.get("font-family"), Some(&Value::Keyword("serif".to_string())));
+        assert_eq!(defaults.get("font-size"), Some(&Value::Length(18.0, Unit::Px)));
+    }
+
+    #[test]
+    fn test_reduced_motion_media_features() {
+        let mut prefs = Preferences::new();
+        prefs.reduced_motion = ReducedMotion::Reduce;
+        assert!(prefs.media_features().reduced_motion);
+        prefs.reduced_motion = ReducedMotion::NoPreference;
+        assert!(!prefs.media_features().reduced_motion);
+        assert_eq!(ReducedMotion::from_str("no-preference"), Some(ReducedMotion::NoPreference));
+    }
+
+    #[test]
+    fn test_save_and_load_file() {
+        let dir = std::env::temp_dir().join(format!("browser-prefs-test-{}", std::process::id()));
+        let path = dir.join("nested").join("preferences.json");
+        assert_eq!(Preferences::load(&path).unwrap(), Preferences::new());
+
+        let mut prefs = Preferences::new();
+        prefs.theme = Theme::Dark;
+        prefs.reduced_motion = ReducedMotion::Reduce;
+        prefs.animations = MotionPolicy::Finish;
+        prefs.homepage = "https://example.com/".to_string();
+        prefs.search_engine = SearchEngine::new("Example", "https://search.example/?q={searchTerms}");
+        prefs.update_site_overrides(&url("https://example.org/"), |site| {
+            site.javascript_enabled = Some(false);
+            site.cookie_policy = Some(CookiePolicy::AllowAll);
+            site.content_filtering = Some(false);
+        });
+        prefs.save(&path).unwrap();
+        assert_eq!(Preferences::load(&path).unwrap(), prefs);
+
+        // Missing and invalid values fall back to defaults
+        std::fs::write(&path, r#"{"theme": "neon", "font_size": 500}"#).unwrap();
+        let loaded = Preferences::load(&path).unwrap();
+        assert_eq!(loaded.theme, Theme::System);
+        assert_eq!(loaded.reduced_motion, ReducedMotion::System);
+        assert_eq!(loaded.font_size, FONT_SIZE_RANGE.1);
+        assert!(loaded.javascript_enabled);
+        assert!(loaded.content_filtering);
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(matches!(Preferences::load(&path), Err(PreferencesError::Parse(_))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,744 @@
+// Printing - paged layout and PDF export
+
+use crate::css::{Color, MediaType, Stylesheet};
+use crate::display::{build_display_list, DisplayCommand, DisplayList};
+use crate::dom::Node;
+use crate::layout::{layout_tree, Dimensions, Rect};
+use crate::net::LoadedPage;
+use crate::style::{style_tree_with_defaults, PropertyMap};
+use crate::text_style::TextStyle;
+use crate::ui::document_title;
+use std::fmt;
+use url::Url;
+
+/// CSS pixels per inch; paper sizes are given in CSS pixels
+pub const CSS_PX_PER_INCH: f32 = 96.0;
+
+/// PDF points per CSS pixel (72pt per inch)
+const PT_PER_PX: f32 = 72.0 / CSS_PX_PER_INCH;
+
+/// Font size of the header and footer lines
+const HEADER_FONT_SIZE: f32 = 9.0;
+
+const HEADER_COLOR: Color = Color { r: 80, g: 80, b: 80, a: 255 };
+
+/// Paper sizes offered when printing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PaperSize {
+    Letter,
+    Legal,
+    A4,
+    A5,
+    /// Width and height in CSS pixels
+    Custom { width: f32, height: f32 },
+}
+
+impl PaperSize {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "letter" => Some(PaperSize::Letter),
+            "legal" => Some(PaperSize::Legal),
+            "a4" => Some(PaperSize::A4),
+            "a5" => Some(PaperSize::A5),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PaperSize::Letter => "letter",
+            PaperSize::Legal => "legal",
+            PaperSize::A4 => "a4",
+            PaperSize::A5 => "a5",
+            PaperSize::Custom { .. } => "custom",
+        }
+    }
+
+    /// Portrait width and height in CSS pixels
+    pub fn size(&self) -> (f32, f32) {
+        let mm = CSS_PX_PER_INCH / 25.4;
+        match *self {
+            PaperSize::Letter => (8.5 * CSS_PX_PER_INCH, 11.0 * CSS_PX_PER_INCH),
+            PaperSize::Legal => (8.5 * CSS_PX_PER_INCH, 14.0 * CSS_PX_PER_INCH),
+            PaperSize::A4 => (210.0 * mm, 297.0 * mm),
+            PaperSize::A5 => (148.0 * mm, 210.0 * mm),
+            PaperSize::Custom { width, height } => (width, height),
+        }
+    }
+}
+
+/// Page margins in CSS pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Margins {
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+}
+
+impl Margins {
+    /// The same margin on every side
+    pub fn uniform(margin: f32) -> Self {
+        Self { top: margin, right: margin, bottom: margin, left: margin }
+    }
+}
+
+impl Default for Margins {
+    fn default() -> Self {
+        // Half an inch, leaving room for the header and footer
+        Self::uniform(CSS_PX_PER_INCH / 2.0)
+    }
+}
+
+/// Options for laying a document out on paper
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrintOptions {
+    pub paper: PaperSize,
+    pub landscape: bool,
+    pub margins: Margins,
+    /// Zoom applied to the content, between `MIN_SCALE` and `MAX_SCALE`
+    pub scale: f32,
+    /// Print the title, URL and page numbers in the margins
+    pub header_footer: bool,
+    /// Print background colors; off by default to save ink
+    pub background_graphics: bool,
+}
+
+impl PrintOptions {
+    pub const MIN_SCALE: f32 = 0.1;
+    pub const MAX_SCALE: f32 = 2.0;
+
+    pub fn new() -> Self {
+        Self {
+            paper: PaperSize::Letter,
+            landscape: false,
+            margins: Margins::default(),
+            scale: 1.0,
+            header_footer: true,
+            background_graphics: false,
+        }
+    }
+
+    /// Width and height of a sheet in CSS pixels, orientation applied
+    pub fn page_size(&self) -> (f32, f32) {
+        let (width, height) = self.paper.size();
+        if self.landscape {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    /// Area of a sheet the content is printed in
+    pub fn content_area(&self) -> Rect {
+        let (width, height) = self.page_size();
+        Rect {
+            x: self.margins.left,
+            y: self.margins.top,
+            width: width - self.margins.left - self.margins.right,
+            height: height - self.margins.top - self.margins.bottom,
+        }
+    }
+
+    /// Check the options leave room for content
+    pub fn validate(&self) -> Result<(), PrintError> {
+        if !(Self::MIN_SCALE..=Self::MAX_SCALE).contains(&self.scale) {
+            return Err(PrintError::InvalidScale(self.scale));
+        }
+        let area = self.content_area();
+        let margins = [self.margins.top, self.margins.right, self.margins.bottom, self.margins.left];
+        if margins.iter().any(|m| *m < 0.0) || area.width <= 0.0 || area.height <= 0.0 {
+            return Err(PrintError::InvalidMargins);
+        }
+        Ok(())
+    }
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Errors from printing
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrintError {
+    /// Scale outside the supported range
+    InvalidScale(f32),
+    /// Margins leave no room for content
+    InvalidMargins,
+}
+
+impl fmt::Display for PrintError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PrintError::InvalidScale(scale) => write!(
+                f,
+                "Scale {} is outside {}..={}",
+                scale,
+                PrintOptions::MIN_SCALE,
+                PrintOptions::MAX_SCALE
+            ),
+            PrintError::InvalidMargins => write!(f, "Margins leave no room for content"),
+        }
+    }
+}
+
+impl std::error::Error for PrintError {}
+
+/// One printed sheet
+#[derive(Debug, Clone)]
+pub struct PrintedPage {
+    /// Page number, starting at 1
+    pub number: usize,
+    /// Drawing commands in sheet coordinates (CSS pixels)
+    pub display_list: DisplayList,
+}
+
+/// A document ready to print
+pub struct Page {
+    pub document: Node,
+    /// Stylesheet as parsed; `@media print` blocks apply when printing
+    pub stylesheet: Stylesheet,
+    pub url: Option<Url>,
+    pub title: String,
+    /// Inherited font defaults from the site's preferences
+    pub style_defaults: PropertyMap,
+}
+
+impl Page {
+    /// Create a page from a document; the title comes from its `<title>`
+    pub fn new(document: Node, stylesheet: Stylesheet) -> Self {
+        let title = document_title(&document).unwrap_or_default();
+        Self {
+            document,
+            stylesheet,
+            url: None,
+            title,
+            style_defaults: PropertyMap::new(),
+        }
+    }
+
+    /// Set the URL printed in the footer
+    pub fn with_url(mut self, url: Url) -> Self {
+        self.url = Some(url);
+        self
+    }
+
+    /// Set the inherited font defaults
+    pub fn with_style_defaults(mut self, defaults: PropertyMap) -> Self {
+        self.style_defaults = defaults;
+        self
+    }
+
+    /// Lay the document out on sheets of paper
+    pub fn paginate(&self, options: &PrintOptions) -> Result<Vec<PrintedPage>, PrintError> {
+        options.validate()?;
+
+        // Lay out in one long column as wide as the printable area
+        let area = options.content_area();
+        let stylesheet = self.stylesheet.for_media(MediaType::Print);
+        let styled = style_tree_with_defaults(&self.document, &stylesheet, &self.style_defaults);
+        let mut viewport = Dimensions::default();
+        viewport.content.width = area.width / options.scale;
+        viewport.content.height = area.height / options.scale;
+        let layout_root = layout_tree(&styled, viewport);
+        let mut display_list = build_display_list(&layout_root);
+        if !options.background_graphics {
+            display_list.retain(|command| !matches!(command, DisplayCommand::SolidRect { .. }));
+        }
+
+        let slices = fragment(&display_list, area.height / options.scale);
+        let total = slices.len();
+        Ok(slices
+            .into_iter()
+            .enumerate()
+            .map(|(index, slice)| {
+                let mut display_list: DisplayList = slice
+                    .into_iter()
+                    .map(|command| place(command, area.x, area.y, options.scale))
+                    .collect();
+                if options.header_footer {
+                    display_list.extend(self.header_footer(options, index + 1, total));
+                }
+                PrintedPage { number: index + 1, display_list }
+            })
+            .collect())
+    }
+
+    /// Lay the document out on paper and encode it as a PDF file
+    pub fn print_to_pdf(&self, options: &PrintOptions) -> Result<Vec<u8>, PrintError> {
+        let pages = self.paginate(options)?;
+        Ok(write_pdf(&pages, options.page_size()))
+    }
+
+    /// Title in the top margin; URL and page number in the bottom margin
+    fn header_footer(&self, options: &PrintOptions, number: usize, total: usize) -> DisplayList {
+        let (width, height) = options.page_size();
+        let margins = options.margins;
+        let header_y = (margins.top - HEADER_FONT_SIZE) / 2.0;
+        let footer_y = height - (margins.bottom + HEADER_FONT_SIZE) / 2.0;
+        let page_number = format!("{}/{}", number, total);
+        let number_width = text_width(&page_number, HEADER_FONT_SIZE);
+
+        let mut lines = vec![(self.title.clone(), margins.left, header_y)];
+        if let Some(url) = &self.url {
+            lines.push((url.to_string(), margins.left, footer_y));
+        }
+        lines.push((page_number, width - margins.right - number_width, footer_y));
+
+        lines
+            .into_iter()
+            .filter(|(text, _, _)| !text.is_empty())
+            .map(|(text, x, y)| DisplayCommand::Text {
+                rect: Rect { x, y, width: text_width(&text, HEADER_FONT_SIZE), height: HEADER_FONT_SIZE },
+                text,
+                color: HEADER_COLOR,
+                font_family: "sans-serif".to_string(),
+                font_size: HEADER_FONT_SIZE,
+                style: TextStyle::default(),
+            })
+            .collect()
+    }
+}
+
+impl From<&LoadedPage> for Page {
+    fn from(loaded: &LoadedPage) -> Self {
+        Page::new(loaded.dom.clone(), loaded.merged_stylesheet()).with_url(loaded.url.clone())
+    }
+}
+
+/// Split a display list laid out in one column into page-high slices
+///
+/// Pages break between lines and images rather than through them; boxes
+/// spanning a break are sliced, and lose the border on the cut edge.
+/// Each slice is in coordinates relative to its own top.
+pub fn fragment(display_list: &DisplayList, page_height: f32) -> Vec<DisplayList> {
+    let breaks = page_breaks(display_list, page_height);
+    let mut pages: Vec<DisplayList> = vec![Vec::new(); breaks.len()];
+
+    for command in display_list {
+        match command {
+            DisplayCommand::Text { rect, .. } | DisplayCommand::Image { rect, .. } => {
+                // Unbreakable: goes on the page its top is on
+                let index = breaks.iter().rposition(|top| *top <= rect.y).unwrap_or(0);
+                pages[index].push(translate(command.clone(), -breaks[index]));
+            }
+            command if command.is_marker() => {
+                // Every page gets each clip and fixed box marker whole, so none is left unended
+                for (page, top) in pages.iter_mut().zip(&breaks) {
+                    page.push(translate(command.clone(), -top));
+                }
+            }
+            _ => {
+                for (index, top) in breaks.iter().enumerate() {
+                    let bottom = breaks.get(index + 1).copied().unwrap_or(f32::INFINITY);
+                    if let Some(slice) = slice_command(command, *top, bottom) {
+                        pages[index].push(translate(slice, -top));
+                    }
+                }
+            }
+        }
+    }
+
+    pages
+}
+
+/// Top of each page, in layout coordinates
+fn page_breaks(display_list: &DisplayList, page_height: f32) -> Vec<f32> {
+    let unbreakable: Vec<Rect> = display_list
+        .iter()
+        .filter_map(|command| match command {
+            DisplayCommand::Text { rect, .. } | DisplayCommand::Image { rect, .. } => Some(*rect),
+            _ => None,
+        })
+        .collect();
+    let content_bottom = display_list
+        .iter()
+        .map(|command| command_rect(command).y + command_rect(command).height)
+        .fold(0.0f32, f32::max);
+
+    let mut breaks = vec![0.0];
+    let mut top = 0.0;
+    while top + page_height < content_bottom {
+        let mut bottom = top + page_height;
+        // Moving the break up may cut through an earlier line; repeat until clear
+        while let Some(cut) = unbreakable
+            .iter()
+            .find(|rect| rect.y > top && rect.y < bottom && rect.y + rect.height > bottom)
+        {
+            bottom = cut.y;
+        }
+        breaks.push(bottom);
+        top = bottom;
+    }
+    breaks
+}
+
+/// Part of a box drawn between `top` and `bottom`, if any
+fn slice_command(command: &DisplayCommand, top: f32, bottom: f32) -> Option<DisplayCommand> {
+    let rect = command_rect(command);
+    let start = rect.y.max(top);
+    let end = (rect.y + rect.height).min(bottom);
+    if end <= start {
+        return None;
+    }
+    let sliced = Rect { y: start, height: end - start, ..*rect };
+
+    Some(match command.clone() {
+        DisplayCommand::Border { color, widths: (left, right, top_width, bottom_width), .. } => {
+            DisplayCommand::Border {
+                color,
+                rect: sliced,
+                widths: (
+                    left,
+                    right,
+                    if start > rect.y { 0.0 } else { top_width },
+                    if end < rect.y + rect.height { 0.0 } else { bottom_width },
+                ),
+            }
+        }
+        DisplayCommand::SolidRect { color, .. } => DisplayCommand::SolidRect { color, rect: sliced },
+        DisplayCommand::Highlight { color, .. } => DisplayCommand::Highlight { color, rect: sliced },
+        other => other,
+    })
+}
+
+fn command_rect(command: &DisplayCommand) -> &Rect {
+    match command {
+        DisplayCommand::SolidRect { rect, .. }
+        | DisplayCommand::Border { rect, .. }
+        | DisplayCommand::Text { rect, .. }
+        | DisplayCommand::Image { rect, .. }
+        | DisplayCommand::Highlight { rect, .. }
+        | DisplayCommand::PushClip { rect }
+        | DisplayCommand::PopClip { rect }
+        | DisplayCommand::PushFixed { rect }
+        | DisplayCommand::PopFixed { rect } => rect,
+    }
+}
+
+fn command_rect_mut(command: &mut DisplayCommand) -> &mut Rect {
+    match command {
+        DisplayCommand::SolidRect { rect, .. }
+        | DisplayCommand::Border { rect, .. }
+        | DisplayCommand::Text { rect, .. }
+        | DisplayCommand::Image { rect, .. }
+        | DisplayCommand::Highlight { rect, .. }
+        | DisplayCommand::PushClip { rect }
+        | DisplayCommand::PopClip { rect }
+        | DisplayCommand::PushFixed { rect }
+        | DisplayCommand::PopFixed { rect } => rect,
+    }
+}
+
+fn translate(mut command: DisplayCommand, dy: f32) -> DisplayCommand {
+    command_rect_mut(&mut command).y += dy;
+    command
+}
+
+/// Scale a page slice and move it into the sheet's printable area
+fn place(mut command: DisplayCommand, x: f32, y: f32, scale: f32) -> DisplayCommand {
+    let rect = command_rect_mut(&mut command);
+    *rect = Rect {
+        x: x + rect.x * scale,
+        y: y + rect.y * scale,
+        width: rect.width * scale,
+        height: rect.height * scale,
+    };
+    match &mut command {
+        DisplayCommand::Text { font_size, .. } => *font_size *= scale,
+        DisplayCommand::Border { widths, .. } => {
+            *widths = (widths.0 * scale, widths.1 * scale, widths.2 * scale, widths.3 * scale);
+        }
+        _ => {}
+    }
+    command
+}
+
+/// Rough advance width of a line of text
+fn text_width(text: &str, font_size: f32) -> f32 {
+    text.chars().count() as f32 * font_size * 0.5
+}
+
+/// Encode printed pages as a PDF 1.4 file
+///
+/// Text uses the standard Type 1 fonts, so nothing is embedded; images
+/// are not encoded and print as an outlined placeholder.
+fn write_pdf(pages: &[PrintedPage], page_size: (f32, f32)) -> Vec<u8> {
+    let (width, height) = (page_size.0 * PT_PER_PX, page_size.1 * PT_PER_PX);
+
+    // Objects 1-5 are fixed; each page then adds a page and a content object
+    let page_ids: Vec<usize> = (0..pages.len()).map(|i| 6 + i * 2).collect();
+    let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), pages.len()),
+    ];
+    for font in ["Helvetica", "Times-Roman", "Courier"] {
+        objects.push(format!(
+            "<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>",
+            font
+        ));
+    }
+    for (page, id) in pages.iter().zip(&page_ids) {
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] \
+             /Resources << /Font << /F1 3 0 R /F2 4 0 R /F3 5 0 R >> >> /Contents {} 0 R >>",
+            width,
+            height,
+            id + 1
+        ));
+        let content = content_stream(&page.display_list, height);
+        objects.push(format!("<< /Length {} >>\nstream\n{}\nendstream", content.len(), content));
+    }
+
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.push_str(&format!("{} 0 obj\n{}\nendobj\n", index + 1, object));
+    }
+    let xref = pdf.len();
+    pdf.push_str(&format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1));
+    for offset in offsets {
+        pdf.push_str(&format!("{:010} 00000 n \n", offset));
+    }
+    pdf.push_str(&format!(
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref
+    ));
+    pdf.into_bytes()
+}
+
+/// PDF drawing operators for a page; PDF's y axis points up
+fn content_stream(display_list: &DisplayList, page_height: f32) -> String {
+    let mut ops = Vec::new();
+    let fill = |ops: &mut Vec<String>, color: &Color, rect: &Rect| {
+        if rect.width > 0.0 && rect.height > 0.0 {
+            ops.push(format!(
+                "{} rg {:.2} {:.2} {:.2} {:.2} re f",
+                pdf_color(color),
+                rect.x * PT_PER_PX,
+                page_height - (rect.y + rect.height) * PT_PER_PX,
+                rect.width * PT_PER_PX,
+                rect.height * PT_PER_PX
+            ));
+        }
+    };
+
+    for command in display_list {
+        match command {
+            DisplayCommand::SolidRect { color, rect } if color.a > 0 => fill(&mut ops, color, rect),
+            DisplayCommand::Border { color, rect, widths: (left, right, top, bottom) } if color.a > 0 => {
+                let r = rect;
+                fill(&mut ops, color, &Rect { height: *top, ..*r });
+                fill(&mut ops, color, &Rect { y: r.y + r.height - bottom, height: *bottom, ..*r });
+                fill(&mut ops, color, &Rect { width: *left, ..*r });
+                fill(&mut ops, color, &Rect { x: r.x + r.width - right, width: *right, ..*r });
+            }
+            DisplayCommand::Text { text, rect, color, font_family, font_size, .. } => {
+                let text = pdf_string(text);
+                if text.is_empty() {
+                    continue;
+                }
+                let font = match font_family.to_ascii_lowercase().as_str() {
+                    "serif" | "times" | "times new roman" | "georgia" => "F2",
+                    "monospace" | "courier" | "courier new" => "F3",
+                    _ => "F1",
+                };
+                // Place the baseline about 80% down the line
+                ops.push(format!(
+                    "BT /{} {:.2} Tf {} rg {:.2} {:.2} Td ({}) Tj ET",
+                    font,
+                    font_size * PT_PER_PX,
+                    pdf_color(color),
+                    rect.x * PT_PER_PX,
+                    page_height - (rect.y + font_size * 0.8) * PT_PER_PX,
+                    text
+                ));
+            }
+            DisplayCommand::Image { rect, .. } => {
+                ops.push(format!(
+                    "0.6 G {:.2} {:.2} {:.2} {:.2} re S",
+                    rect.x * PT_PER_PX,
+                    page_height - (rect.y + rect.height) * PT_PER_PX,
+                    rect.width * PT_PER_PX,
+                    rect.height * PT_PER_PX
+                ));
+            }
+            // Find-in-page highlights and transparent boxes don't print
+            _ => {}
+        }
+    }
+
+    ops.join("\n")
+}
+
+fn pdf_color(color: &Color) -> String {
+    format!(
+        "{:.3} {:.3} {:.3}",
+        color.r as f32 / 255.0,
+        color.g as f32 / 255.0,
+        color.b as f32 / 255.0
+    )
+}
+
+/// Escape text for a PDF string literal in WinAnsi encoding
+///
+/// Whitespace runs collapse to one space; characters outside Latin-1
+/// print as `?`.
+fn pdf_string(text: &str) -> String {
+    let mut out = String::new();
+    for word in text.split_whitespace() {
+        if !out.is_empty() {
+            out.push(' ');
+        }
+        for c in word.chars() {
+            match c {
+                '(' | ')' | '\\' => {
+                    out.push('\\');
+                    out.push(c);
+                }
+                ' '..='~' => out.push(c),
+                '\u{a0}'..='\u{ff}' => out.push_str(&format!("\\{:03o}", c as u32)),
+                _ => out.push('?'),
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::CssParser;
+    use crate::html::HtmlParser;
+
+    fn text_at(text: &str, y: f32, height: f32) -> DisplayCommand {
+        DisplayCommand::Text {
+            text: text.to_string(),
+            rect: Rect { x: 0.0, y, width: 100.0, height },
+            color: Color::black(),
+            font_family: "sans-serif".to_string(),
+            font_size: height,
+            style: TextStyle::default(),
+        }
+    }
+
+    #[test]
+    fn test_fragment_breaks_between_lines() {
+        let list = vec![
+            DisplayCommand::Border {
+                color: Color::black(),
+                rect: Rect { x: 0.0, y: 0.0, width: 200.0, height: 250.0 },
+                widths: (1.0, 1.0, 1.0, 1.0),
+            },
+            text_at("first", 10.0, 20.0),
+            // Straddles the 100px page boundary, so it moves to page two
+            text_at("second", 90.0, 20.0),
+            text_at("third", 230.0, 20.0),
+        ];
+        let pages = fragment(&list, 100.0);
+
+        assert_eq!(pages.len(), 3);
+        assert!(matches!(&pages[1][1], DisplayCommand::Text { text, rect, .. } if text == "second" && rect.y == 0.0));
+
+        // The box is sliced and only keeps borders on its real edges
+        let widths: Vec<(f32, f32, f32, f32)> = pages
+            .iter()
+            .map(|page| match &page[0] {
+                DisplayCommand::Border { widths, .. } => *widths,
+                other => panic!("expected border, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(widths[0], (1.0, 1.0, 1.0, 0.0));
+        assert_eq!(widths[1], (1.0, 1.0, 0.0, 0.0));
+        assert_eq!(widths[2], (1.0, 1.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_tall_item_does_not_stall_pagination() {
+        let list = vec![text_at("huge", 0.0, 350.0), text_at("after", 350.0, 20.0)];
+        let pages = fragment(&list, 100.0);
+
+        assert!(pages.len() >= 2);
+        assert_eq!(pages.iter().map(Vec::len).sum::<usize>(), 2);
+    }
+
+    #[test]
+    fn test_options_validation() {
+        let mut options = PrintOptions::new();
+        assert!(options.validate().is_ok());
+
+        options.landscape = true;
+        let (width, height) = options.page_size();
+        assert!(width > height);
+
+        options.scale = 3.0;
+        assert_eq!(options.validate(), Err(PrintError::InvalidScale(3.0)));
+        options.scale = 1.0;
+        options.margins = Margins::uniform(CSS_PX_PER_INCH * 6.0);
+        assert_eq!(options.validate(), Err(PrintError::InvalidMargins));
+
+        assert_eq!(PaperSize::from_str("A4"), Some(PaperSize::A4));
+        assert_eq!(PaperSize::A4.as_str(), "a4");
+    }
+
+    #[test]
+    fn test_print_styles_and_header_footer() {
+        let dom = HtmlParser::parse(
+            "<html><head><title>Report</title></head><body><div class=\"nav\">Menu</div><p>Body text</p></body></html>",
+        );
+        let stylesheet = CssParser::parse(
+            "head { display: none; } html, body, p, div { display: block; } @media print { .nav { display: none; } }",
+        );
+        let page = Page::new(dom, stylesheet).with_url(Url::parse("https://example.com/report").unwrap());
+        let pages = page.paginate(&PrintOptions::new()).unwrap();
+
+        let texts: Vec<&str> = pages[0]
+            .display_list
+            .iter()
+            .filter_map(|command| match command {
+                DisplayCommand::Text { text, .. } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert!(texts.contains(&"Body text"));
+        assert!(!texts.contains(&"Menu"));
+        assert!(texts.contains(&"Report"));
+        assert!(texts.contains(&"https://example.com/report"));
+        assert!(texts.contains(&"1/1"));
+    }
+
+    #[test]
+    fn test_print_to_pdf() {
+        let dom = HtmlParser::parse("<html><body><p>Hello (world)</p></body></html>");
+        let stylesheet = CssParser::parse("html, body, p { display: block; } p { background-color: #eeeeee; }");
+        let page = Page::new(dom, stylesheet);
+        let pdf = page.print_to_pdf(&PrintOptions::new()).unwrap();
+        let text = String::from_utf8(pdf).unwrap();
+
+        assert!(text.starts_with("%PDF-1.4\n"));
+        assert!(text.ends_with("%%EOF\n"));
+        assert!(text.contains("/Count 1"));
+        assert!(text.contains("(Hello \\(world\\)) Tj"));
+        // Backgrounds are left out unless asked for
+        assert!(!text.contains("re f"));
+
+        // The cross-reference table points at each object
+        let xref: usize = text.rsplit("startxref\n").next().unwrap().lines().next().unwrap().parse().unwrap();
+        let entries: Vec<&str> = text[xref..].lines().skip(3).take_while(|l| l.ends_with(" n ")).collect();
+        for (index, entry) in entries.iter().enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(text[offset..].starts_with(&format!("{} 0 obj", index + 1)));
+        }
+    }
+
+    #[test]
+    fn test_pdf_string_escaping() {
+        assert_eq!(pdf_string("a  (b)\n c\\"), "a \\(b\\) c\\\\");
+        assert_eq!(pdf_string("caf\u{e9} \u{4e2d}"), "caf\\351 ?");
+    }
+}
@@ -0,0 +1,713 @@
+// Reader mode: main article extraction and the reading stylesheet
+//
+// Extraction follows the Readability approach: paragraphs award points to
+// their parent and grandparent, class and id names hint at content or
+// boilerplate, and link-heavy blocks are penalised. The best-scoring
+// element is cleaned into a plain document shown with a stylesheet the
+// user can adjust.
+
+use crate::dom::{AttrMap, Node, NodeType};
+use crate::storage::{LocalStorage, StorageError};
+use crate::ui::document_title;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Local storage key for the reader settings
+pub const READER_SETTINGS_KEY: &str = "browser.reader";
+
+/// Least text, in characters, for a page to count as an article
+const MIN_ARTICLE_LENGTH: usize = 250;
+/// Shortest paragraph that scores
+const MIN_PARAGRAPH_LENGTH: usize = 25;
+
+/// Elements removed before scoring; they never hold the article text
+const BOILERPLATE_ELEMENTS: &[&str] = &[
+    "script", "style", "noscript", "template", "nav", "aside", "footer", "form", "iframe", "button",
+    "input", "select", "textarea", "svg", "object", "embed", "head", "link", "meta",
+];
+
+/// Class and id words that suggest content
+const POSITIVE_HINTS: &[&str] = &[
+    "article", "body", "content", "entry", "main", "page", "post", "text", "blog", "story",
+];
+
+/// Class and id words that suggest boilerplate
+const NEGATIVE_HINTS: &[&str] = &[
+    "ad", "ads", "banner", "breadcrumb", "comment", "comments", "footer", "masthead", "menu",
+    "meta", "nav", "popup", "promo", "related", "share", "sidebar", "social", "sponsor", "widget",
+];
+
+/// Attributes kept on article elements; the rest (class, id, style)
+/// belong to the site's stylesheet and are dropped
+const KEPT_ATTRIBUTES: &[&str] = &["href", "src", "alt", "title"];
+
+/// The main content of a page
+#[derive(Debug, Clone)]
+pub struct Article {
+    pub title: String,
+    /// Author line, if the page has one
+    pub byline: Option<String>,
+    /// Cleaned article element
+    pub content: Node,
+    /// Characters of text in the article
+    pub length: usize,
+}
+
+impl Article {
+    /// Build a standalone document showing the article
+    pub fn to_document(&self) -> Node {
+        let mut header = vec![element("h1", vec![Node::text(self.title.clone())])];
+        if let Some(byline) = &self.byline {
+            header.push(element_with_class("p", "byline", vec![Node::text(byline.clone())]));
+        }
+        let mut children = header;
+        children.push(self.content.clone());
+
+        let head = element("head", vec![element("title", vec![Node::text(self.title.clone())])]);
+        let body = element("body", vec![element("article", children)]);
+        element("html", vec![head, body])
+    }
+}
+
+/// Find and clean the main article of a page
+///
+/// Returns `None` for pages without enough running text, such as search
+/// results or app-like pages, where reader mode would lose content.
+pub fn extract_article(dom: &Node) -> Option<Article> {
+    let cleaned = strip_boilerplate(dom)?;
+
+    let mut scores: HashMap<Vec<usize>, f32> = HashMap::new();
+    let mut path = Vec::new();
+    score_paragraphs(&cleaned, &mut path, &mut scores);
+
+    let (best_path, _) = scores
+        .iter()
+        .map(|(path, score)| {
+            let node = node_at(&cleaned, path).expect("scored path exists");
+            (path, (initial_score(node) + score) * (1.0 - link_density(node)))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1).then_with(|| b.0.len().cmp(&a.0.len())))?;
+    let best = node_at(&cleaned, best_path)?;
+
+    let length = text_of(best).chars().count();
+    if length < MIN_ARTICLE_LENGTH {
+        return None;
+    }
+
+    let mut content = clean_content(best).unwrap_or_else(|| best.clone());
+    if let NodeType::Element(data) = &mut content.node_type {
+        // The article's root becomes a plain container
+        data.tag_name = "div".to_string();
+    }
+
+    let title = article_title(dom, best);
+    remove_leading_title(&mut content, &title);
+
+    Some(Article {
+        byline: find_byline(dom),
+        title,
+        content,
+        length,
+    })
+}
+
+/// Does the page have an article worth showing in reader mode
+pub fn is_readerable(dom: &Node) -> bool {
+    extract_article(dom).is_some()
+}
+
+/// Copy the tree without boilerplate elements and hidden content
+fn strip_boilerplate(node: &Node) -> Option<Node> {
+    if let Some(elem) = node.element_data() {
+        let tag = elem.tag_name.to_ascii_lowercase();
+        if BOILERPLATE_ELEMENTS.contains(&tag.as_str())
+            || elem.attributes.contains_key("hidden")
+            || elem.get_attribute("aria-hidden") == Some("true")
+            || (class_weight(node) < 0.0 && !matches!(tag.as_str(), "html" | "body" | "article" | "main"))
+            // Shown above the article instead
+            || byline_text(node).is_some()
+        {
+            return None;
+        }
+    } else if node.text_content().is_none() {
+        // Comments
+        return None;
+    }
+
+    let mut copy = node.clone();
+    copy.children = node.children.iter().filter_map(strip_boilerplate).collect();
+    Some(copy)
+}
+
+/// Award each paragraph's score to its parent and half to its grandparent
+///
+/// Candidates are keyed by their path of child indices from the root.
+fn score_paragraphs(node: &Node, path: &mut Vec<usize>, scores: &mut HashMap<Vec<usize>, f32>) {
+    for (index, child) in node.children.iter().enumerate() {
+        path.push(index);
+        if is_paragraph(child) {
+            let text = text_of(child);
+            let length = text.chars().count();
+            if length >= MIN_PARAGRAPH_LENGTH {
+                let score = 1.0 + text.matches(',').count() as f32 + (length / 100).min(3) as f32;
+                let parent = &path[..path.len() - 1];
+                *scores.entry(parent.to_vec()).or_default() += score;
+                if let Some((_, grandparent)) = parent.split_last() {
+                    *scores.entry(grandparent.to_vec()).or_default() += score / 2.0;
+                }
+            }
+        }
+        score_paragraphs(child, path, scores);
+        path.pop();
+    }
+}
+
+/// Starting score of a candidate from its tag and class names
+fn initial_score(node: &Node) -> f32 {
+    let tag = node.element_data().map(|e| e.tag_name.to_ascii_lowercase()).unwrap_or_default();
+    let tag_score = match tag.as_str() {
+        "article" | "main" => 10.0,
+        "div" | "section" => 5.0,
+        "pre" | "td" | "blockquote" => 3.0,
+        "address" | "ol" | "ul" | "dl" | "dd" | "dt" | "li" => -3.0,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+        _ => 0.0,
+    };
+    tag_score + class_weight(node)
+}
+
+/// +25 for content-like class or id words, -25 for boilerplate-like ones
+fn class_weight(node: &Node) -> f32 {
+    let Some(elem) = node.element_data() else {
+        return 0.0;
+    };
+    let names = format!("{} {}", elem.get_attribute("class").unwrap_or(""), elem.id().unwrap_or(""))
+        .to_ascii_lowercase();
+    let words: Vec<&str> = names.split(|c: char| !c.is_ascii_alphanumeric()).filter(|w| !w.is_empty()).collect();
+    let mut weight = 0.0;
+    if words.iter().any(|w| POSITIVE_HINTS.contains(w)) {
+        weight += 25.0;
+    }
+    if words.iter().any(|w| NEGATIVE_HINTS.contains(w)) {
+        weight -= 25.0;
+    }
+    weight
+}
+
+fn is_paragraph(node: &Node) -> bool {
+    node.element_data()
+        .is_some_and(|e| matches!(e.tag_name.to_ascii_lowercase().as_str(), "p" | "pre" | "td" | "blockquote"))
+}
+
+/// Share of a node's text that is inside links
+fn link_density(node: &Node) -> f32 {
+    fn link_text(node: &Node) -> usize {
+        if node.element_data().is_some_and(|e| e.tag_name.eq_ignore_ascii_case("a")) {
+            return text_of(node).chars().count();
+        }
+        node.children.iter().map(link_text).sum()
+    }
+    let total = text_of(node).chars().count();
+    if total == 0 {
+        return 0.0;
+    }
+    link_text(node) as f32 / total as f32
+}
+
+/// Remove leftover boilerplate from the article and strip site styling hooks
+fn clean_content(node: &Node) -> Option<Node> {
+    let Some(elem) = node.element_data() else {
+        return Some(node.clone());
+    };
+    let tag = elem.tag_name.to_ascii_lowercase();
+
+    // Link lists and share bars inside the article
+    let text_length = text_of(node).chars().count();
+    if matches!(tag.as_str(), "div" | "section" | "ul" | "ol" | "header" | "table")
+        && link_density(node) > 0.5
+        && text_length < MIN_ARTICLE_LENGTH
+    {
+        return None;
+    }
+    if tag == "header" && text_length < MIN_PARAGRAPH_LENGTH {
+        return None;
+    }
+
+    let attributes: AttrMap = elem
+        .attributes
+        .iter()
+        .filter(|(name, _)| KEPT_ATTRIBUTES.contains(&name.as_str()))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    let children = node.children.iter().filter_map(clean_content).collect();
+    Some(Node::element(tag, attributes, children))
+}
+
+/// Title of the article: the document title unless it only names the site
+fn article_title(dom: &Node, article: &Node) -> String {
+    let heading = first_element(article, &["h1"]).or_else(|| first_element(dom, &["h1"])).map(text_of);
+    let Some(title) = document_title(dom) else {
+        return heading.unwrap_or_default();
+    };
+
+    // "Article name - Site name" and similar
+    for separator in [" | ", " - ", " \u{2013} ", " \u{2014} ", " :: "] {
+        if let Some((first, _)) = title.split_once(separator) {
+            if first.split_whitespace().count() >= 3 || heading.as_deref() == Some(first.trim()) {
+                return first.trim().to_string();
+            }
+        }
+    }
+    title
+}
+
+/// Drop a heading at the start of the content that repeats the title
+fn remove_leading_title(content: &mut Node, title: &str) {
+    let first = content
+        .children
+        .iter()
+        .position(|c| c.element_data().is_some() || c.text_content().is_some_and(|t| !t.trim().is_empty()));
+    if let Some(index) = first {
+        let child = &content.children[index];
+        let is_heading = child
+            .element_data()
+            .is_some_and(|e| matches!(e.tag_name.as_str(), "h1" | "h2"));
+        if is_heading && text_of(child) == title {
+            content.children.remove(index);
+        }
+    }
+}
+
+/// First author line in the document
+fn find_byline(node: &Node) -> Option<String> {
+    byline_text(node).or_else(|| node.children.iter().find_map(find_byline))
+}
+
+/// Text of an author line marked with `rel="author"` or a byline/author class
+fn byline_text(node: &Node) -> Option<String> {
+    let elem = node.element_data()?;
+    let names = format!("{} {}", elem.get_attribute("class").unwrap_or(""), elem.id().unwrap_or(""))
+        .to_ascii_lowercase();
+    if elem.get_attribute("rel") != Some("author") && !names.contains("byline") && !names.contains("author") {
+        return None;
+    }
+    let text = text_of(node);
+    (!text.is_empty() && text.chars().count() < 100).then_some(text)
+}
+
+fn first_element<'n>(node: &'n Node, tags: &[&str]) -> Option<&'n Node> {
+    if node.element_data().is_some_and(|e| tags.contains(&e.tag_name.to_ascii_lowercase().as_str())) {
+        return Some(node);
+    }
+    node.children.iter().find_map(|c| first_element(c, tags))
+}
+
+fn node_at<'n>(root: &'n Node, path: &[usize]) -> Option<&'n Node> {
+    path.iter().try_fold(root, |node, index| node.children.get(*index))
+}
+
+/// Text content with whitespace collapsed
+fn text_of(node: &Node) -> String {
+    fn collect(node: &Node, out: &mut String) {
+        if let Some(text) = node.text_content() {
+            out.push_str(text);
+            out.push(' ');
+        }
+        for child in &node.children {
+            collect(child, out);
+        }
+    }
+    let mut out = String::new();
+    collect(node, &mut out);
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn element(tag: &str, children: Vec<Node>) -> Node {
+    Node::element(tag.to_string(), AttrMap::new(), children)
+}
+
+fn element_with_class(tag: &str, class: &str, children: Vec<Node>) -> Node {
+    let mut attributes = AttrMap::new();
+    attributes.insert("class".to_string(), class.to_string());
+    Node::element(tag.to_string(), attributes, children)
+}
+
+/// Typeface for reader mode text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReaderFont {
+    Serif,
+    SansSerif,
+}
+
+impl ReaderFont {
+    /// Parse a font name
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "serif" => Some(ReaderFont::Serif),
+            "sans-serif" => Some(ReaderFont::SansSerif),
+            _ => None,
+        }
+    }
+
+    /// CSS generic family name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReaderFont::Serif => "serif",
+            ReaderFont::SansSerif => "sans-serif",
+        }
+    }
+}
+
+/// Colour scheme for reader mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReaderTheme {
+    Light,
+    Sepia,
+    Dark,
+}
+
+impl ReaderTheme {
+    /// Parse a theme name
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "light" => Some(ReaderTheme::Light),
+            "sepia" => Some(ReaderTheme::Sepia),
+            "dark" => Some(ReaderTheme::Dark),
+            _ => None,
+        }
+    }
+
+    /// Theme name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReaderTheme::Light => "light",
+            ReaderTheme::Sepia => "sepia",
+            ReaderTheme::Dark => "dark",
+        }
+    }
+
+    /// The next theme, for cycling with a shortcut
+    pub fn next(&self) -> Self {
+        match self {
+            ReaderTheme::Light => ReaderTheme::Sepia,
+            ReaderTheme::Sepia => ReaderTheme::Dark,
+            ReaderTheme::Dark => ReaderTheme::Light,
+        }
+    }
+
+    /// Background, text and link colours
+    fn colors(&self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            ReaderTheme::Light => ("#ffffff", "#1b1b1b", "#0b57d0"),
+            ReaderTheme::Sepia => ("#f4ecd8", "#5b4636", "#7a4b1f"),
+            ReaderTheme::Dark => ("#1c1b22", "#e6e6e6", "#8ab4f8"),
+        }
+    }
+}
+
+/// Errors loading or saving reader settings
+#[derive(Debug)]
+pub enum ReaderError {
+    /// Stored settings could not be read
+    Parse(String),
+    /// Saving to storage failed
+    Storage(StorageError),
+}
+
+impl std::fmt::Display for ReaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReaderError::Parse(msg) => write!(f, "Reader settings parse error: {}", msg),
+            ReaderError::Storage(e) => write!(f, "Reader settings storage error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ReaderError {}
+
+/// User-configurable reading style
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReaderSettings {
+    pub font: ReaderFont,
+    /// Body text size in CSS pixels
+    pub font_size: f32,
+    /// Width of the text column in CSS pixels
+    pub content_width: f32,
+    pub theme: ReaderTheme,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredSettings {
+    font: String,
+    font_size: f32,
+    content_width: f32,
+    theme: String,
+}
+
+impl ReaderSettings {
+    /// Smallest and largest text sizes offered
+    pub const FONT_SIZE_RANGE: (f32, f32) = (12.0, 32.0);
+    const FONT_SIZE_STEP: f32 = 2.0;
+
+    /// Create the default settings
+    pub fn new() -> Self {
+        Self {
+            font: ReaderFont::Serif,
+            font_size: 18.0,
+            content_width: 680.0,
+            theme: ReaderTheme::Light,
+        }
+    }
+
+    /// Make the text one step larger; returns false at the largest size
+    pub fn increase_font_size(&mut self) -> bool {
+        self.set_font_size(self.font_size + Self::FONT_SIZE_STEP)
+    }
+
+    /// Make the text one step smaller; returns false at the smallest size
+    pub fn decrease_font_size(&mut self) -> bool {
+        self.set_font_size(self.font_size - Self::FONT_SIZE_STEP)
+    }
+
+    fn set_font_size(&mut self, size: f32) -> bool {
+        let (min, max) = Self::FONT_SIZE_RANGE;
+        let size = size.clamp(min, max);
+        let changed = size != self.font_size;
+        self.font_size = size;
+        changed
+    }
+
+    /// Switch between serif and sans-serif text
+    pub fn toggle_font(&mut self) {
+        self.font = match self.font {
+            ReaderFont::Serif => ReaderFont::SansSerif,
+            ReaderFont::SansSerif => ReaderFont::Serif,
+        };
+    }
+
+    /// CSS for documents built by `Article::to_document`
+    pub fn stylesheet(&self) -> String {
+        let (background, text, link) = self.theme.colors();
+        let size = self.font_size;
+        format!(
+            r#"
+html, body {{
+    display: block;
+    margin: 0;
+    background-color: {background};
+}}
+
+body {{
+    padding-top: 40px;
+    padding-bottom: 40px;
+}}
+
+article {{
+    display: block;
+    width: {width}px;
+    margin-left: auto;
+    margin-right: auto;
+    padding-left: 20px;
+    padding-right: 20px;
+    color: {text};
+    font-family: {font};
+    font-size: {size}px;
+}}
+
+div, section, p, ul, ol, li, blockquote, pre, figure, figcaption, table, h1, h2, h3, h4, h5, h6 {{
+    display: block;
+}}
+
+h1 {{
+    font-size: {h1}px;
+    margin-bottom: 8px;
+}}
+
+h2 {{
+    font-size: {h2}px;
+    margin-top: 24px;
+    margin-bottom: 8px;
+}}
+
+h3, h4, h5, h6 {{
+    font-size: {h3}px;
+    margin-top: 20px;
+    margin-bottom: 8px;
+}}
+
+p, ul, ol, pre, figure {{
+    margin-top: 0;
+    margin-bottom: {gap}px;
+}}
+
+.byline {{
+    font-size: {small}px;
+    margin-bottom: 24px;
+}}
+
+li {{
+    margin-left: 24px;
+}}
+
+blockquote {{
+    margin-left: 0;
+    padding-left: 16px;
+    border-left-width: 3px;
+    border-color: {text};
+}}
+
+a {{
+    color: {link};
+}}
+"#,
+            background = background,
+            text = text,
+            link = link,
+            font = self.font.as_str(),
+            width = self.content_width,
+            size = size,
+            h1 = (size * 1.8).round(),
+            h2 = (size * 1.4).round(),
+            h3 = (size * 1.2).round(),
+            gap = (size * 0.9).round(),
+            small = (size * 0.85).round(),
+        )
+    }
+
+    /// Save the settings to local storage
+    pub fn save(&self, storage: &mut LocalStorage) -> Result<(), ReaderError> {
+        let stored = StoredSettings {
+            font: self.font.as_str().to_string(),
+            font_size: self.font_size,
+            content_width: self.content_width,
+            theme: self.theme.as_str().to_string(),
+        };
+        let json = serde_json::to_string(&stored).map_err(|e| ReaderError::Parse(e.to_string()))?;
+        storage
+            .set_item(READER_SETTINGS_KEY.to_string(), json)
+            .map_err(ReaderError::Storage)
+    }
+
+    /// Load settings saved to local storage
+    ///
+    /// Returns the defaults if nothing was saved.
+    pub fn load(storage: &LocalStorage) -> Result<Self, ReaderError> {
+        let Some(json) = storage.get_item(READER_SETTINGS_KEY) else {
+            return Ok(Self::new());
+        };
+        let stored: StoredSettings =
+            serde_json::from_str(&json).map_err(|e| ReaderError::Parse(e.to_string()))?;
+
+        let defaults = Self::new();
+        let (min, max) = Self::FONT_SIZE_RANGE;
+        Ok(Self {
+            font: ReaderFont::from_str(&stored.font).unwrap_or(defaults.font),
+            font_size: stored.font_size.clamp(min, max),
+            content_width: stored.content_width.max(200.0),
+            theme: ReaderTheme::from_str(&stored.theme).unwrap_or(defaults.theme),
+        })
+    }
+}
+
+impl Default for ReaderSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::CssParser;
+    use crate::html::HtmlParser;
+
+    const PARAGRAPH: &str = "Rust's ownership model lets the compiler check memory safety, \
+        which means whole classes of bugs, such as use-after-free and data races, are caught \
+        before the program ever runs.";
+
+    fn news_page() -> String {
+        format!(
+            r#"<html><head><title>Ownership explained - The Daily Crab</title></head><body>
+                <div id="masthead"><a href="/">Home</a> <a href="/news">News</a></div>
+                <nav><a href="/a">A</a><a href="/b">B</a></nav>
+                <div class="sidebar"><p>{p}</p></div>
+                <div class="post-body">
+                    <h1>Ownership explained</h1>
+                    <p class="byline">By Ferris Crab</p>
+                    <p>{p}</p><p>{p}</p><p>{p}</p>
+                    <ul class="share"><li><a href="/s1">Share</a></li><li><a href="/s2">Tweet</a></li></ul>
+                    <script>track();</script>
+                </div>
+                <footer><p>{p}</p></footer>
+            </body></html>"#,
+            p = PARAGRAPH
+        )
+    }
+
+    #[test]
+    fn test_extracts_main_article_without_boilerplate() {
+        let dom = HtmlParser::parse(&news_page());
+        let article = extract_article(&dom).unwrap();
+
+        assert_eq!(article.title, "Ownership explained");
+        assert_eq!(article.byline.as_deref(), Some("By Ferris Crab"));
+        let text = text_of(&article.content);
+        assert_eq!(text.matches("ownership model").count(), 3);
+        assert!(!text.contains("Home"));
+        assert!(!text.contains("Share"));
+        assert!(!text.contains("track()"));
+        // The heading repeating the title is shown once, above the content
+        assert!(!text.contains("Ownership explained"));
+        assert!(!text.contains("Ferris"));
+        assert!(article.length >= 3 * PARAGRAPH.len());
+    }
+
+    #[test]
+    fn test_article_document_drops_site_styling() {
+        let dom = HtmlParser::parse(&news_page());
+        let document = extract_article(&dom).unwrap().to_document();
+
+        assert_eq!(document_title(&document).as_deref(), Some("Ownership explained"));
+        fn has_site_class(node: &Node) -> bool {
+            node.element_data().is_some_and(|e| e.get_attribute("class") == Some("post-body"))
+                || node.children.iter().any(has_site_class)
+        }
+        assert!(!has_site_class(&document));
+        assert!(first_element(&document, &["article"]).is_some());
+    }
+
+    #[test]
+    fn test_short_pages_are_not_readerable() {
+        let dom = HtmlParser::parse(r#"<body><form><input name="q"></form><p>Search the web</p></body>"#);
+        assert!(!is_readerable(&dom));
+        assert!(is_readerable(&HtmlParser::parse(&news_page())));
+    }
+
+    #[test]
+    fn test_settings_adjust_stylesheet() {
+        let mut settings = ReaderSettings::new();
+        settings.theme = settings.theme.next();
+        settings.toggle_font();
+        assert!(settings.increase_font_size());
+        let css = settings.stylesheet();
+        assert!(css.contains("font-size: 20px"));
+        assert!(css.contains("font-family: sans-serif"));
+        assert!(css.contains("#f4ecd8"));
+        assert!(!CssParser::parse(&css).rules.is_empty());
+
+        settings.font_size = ReaderSettings::FONT_SIZE_RANGE.0;
+        assert!(!settings.decrease_font_size());
+    }
+
+    #[test]
+    fn test_settings_persist() {
+        let mut storage = LocalStorage::new();
+        assert_eq!(ReaderSettings::load(&storage).unwrap(), ReaderSettings::new());
+
+        let mut settings = ReaderSettings::new();
+        settings.theme = ReaderTheme::Dark;
+        settings.font_size = 24.0;
+        settings.save(&mut storage).unwrap();
+        assert_eq!(ReaderSettings::load(&storage).unwrap(), settings);
+    }
+}
@@ -132,9 +132,7 @@ impl BorderPainter {
         let mut vertices = Vec::new();
         let mut indices = Vec::new();
 
-        for (_border_idx, (rect, color, widths)) in borders.iter().take(count).enumerate() {
-            let (left_w, right_w, top_w, bottom_w) = widths;
-
+        for (rect, color, widths) in borders.iter().take(count) {
             // Convert color to normalized float
             let color_f = [
                 color.r as f32 / 255.0,
@@ -143,51 +141,8 @@ impl BorderPainter {
                 color.a as f32 / 255.0,
             ];
 
-            // Create rectangles for each border edge
-            // Each border generates up to 4 rectangles (one per edge)
-            
-            // Top border
-            if *top_w > 0.0 {
-                let top_rect = Rect {
-                    x: rect.x,
-                    y: rect.y,
-                    width: rect.width,
-                    height: *top_w,
-                };
-                add_rect_vertices(&mut vertices, &mut indices, &top_rect, color_f, viewport_size);
-            }
-
-            // Bottom border
-            if *bottom_w > 0.0 {
-                let bottom_rect = Rect {
-                    x: rect.x,
-                    y: rect.y + rect.height - bottom_w,
-                    width: rect.width,
-                    height: *bottom_w,
-                };
-                add_rect_vertices(&mut vertices, &mut indices, &bottom_rect, color_f, viewport_size);
-            }
-
-            // Left border (between top and bottom)
-            if *left_w > 0.0 {
-                let left_rect = Rect {
-                    x: rect.x,
-                    y: rect.y + top_w,
-                    width: *left_w,
-                    height: rect.height - top_w - bottom_w,
-                };
-                add_rect_vertices(&mut vertices, &mut indices, &left_rect, color_f, viewport_size);
-            }
-
-            // Right border (between top and bottom)
-            if *right_w > 0.0 {
-                let right_rect = Rect {
-                    x: rect.x + rect.width - right_w,
-                    y: rect.y + top_w,
-                    width: *right_w,
-                    height: rect.height - top_w - bottom_w,
-                };
-                add_rect_vertices(&mut vertices, &mut indices, &right_rect, color_f, viewport_size);
+            for edge in border_edges(rect, *widths) {
+                add_rect_vertices(&mut vertices, &mut indices, &edge, color_f, viewport_size);
             }
         }
 
@@ -214,6 +169,22 @@ impl BorderPainter {
     }
 }
 
+/// The rectangles of a border's edges, for widths (left, right, top, bottom)
+///
+/// The top and bottom edges span the whole width; the left and right edges
+/// fill the height between them. Edges of zero width are left out.
+pub(super) fn border_edges(rect: &Rect, widths: (f32, f32, f32, f32)) -> Vec<Rect> {
+    let (left_w, right_w, top_w, bottom_w) = widths;
+    let side_height = rect.height - top_w - bottom_w;
+    let edges = [
+        (top_w, Rect { x: rect.x, y: rect.y, width: rect.width, height: top_w }),
+        (bottom_w, Rect { x: rect.x, y: rect.y + rect.height - bottom_w, width: rect.width, height: bottom_w }),
+        (left_w, Rect { x: rect.x, y: rect.y + top_w, width: left_w, height: side_height }),
+        (right_w, Rect { x: rect.x + rect.width - right_w, y: rect.y + top_w, width: right_w, height: side_height }),
+    ];
+    edges.into_iter().filter(|(width, _)| *width > 0.0).map(|(_, edge)| edge).collect()
+}
+
 /// Helper to add rectangle vertices for a border edge
 fn add_rect_vertices(
     vertices: &mut Vec<Vertex>,
@@ -257,4 +228,14 @@ mod tests {
     fn test_vertex_size() {
         assert_eq!(std::mem::size_of::<Vertex>(), 24);
     }
+
+    #[test]
+    fn test_border_edges() {
+        let rect = Rect { x: 0.0, y: 0.0, width: 100.0, height: 50.0 };
+        let edges = border_edges(&rect, (2.0, 0.0, 4.0, 4.0));
+        assert_eq!(edges.len(), 3);
+        assert_eq!(edges[0], Rect { x: 0.0, y: 0.0, width: 100.0, height: 4.0 });
+        assert_eq!(edges[1], Rect { x: 0.0, y: 46.0, width: 100.0, height: 4.0 });
+        assert_eq!(edges[2], Rect { x: 0.0, y: 4.0, width: 2.0, height: 42.0 });
+    }
 }
@@ -0,0 +1,669 @@
+// Canvas painter - GPU backing stores for accelerated 2D canvases
+//
+// Each canvas draws into its own multisampled texture. Fills are drawn
+// stencil-then-cover: a triangle fan of the path marks its winding
+// (nonzero) or parity (evenodd) in the stencil, then a quad over the path's
+// bounds paints wherever the stencil is set and resets it. Colors are kept
+// premultiplied so the finished texture composites straight onto the page
+// as the canvas's layer.
+
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroup, Buffer, Device, Queue, RenderPass, RenderPipeline, Sampler, Texture, TextureView};
+use bytemuck::{Pod, Zeroable};
+use crate::canvas::{CanvasCommand, CanvasRenderingContext2D, Color, FillRule, Subpath};
+use crate::layout::Rect;
+use crate::media::VideoFrame;
+
+/// Samples per canvas pixel, for anti-aliased edges
+const SAMPLE_COUNT: u32 = 4;
+const CANVAS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+const STENCIL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+
+/// Vertex of a solid color triangle
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct SolidVertex {
+    position: [f32; 2],
+    color: [f32; 4],
+}
+
+impl SolidVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![
+        0 => Float32x2,  // position
+        1 => Float32x4,  // color
+    ];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<SolidVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+/// Vertex of a textured triangle
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct TexturedVertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+}
+
+impl TexturedVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![
+        0 => Float32x2,  // position
+        1 => Float32x2,  // tex_coords
+    ];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<TexturedVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+/// A canvas's backing store on the GPU
+pub struct GpuCanvas {
+    width: u32,
+    height: u32,
+    /// Resolved pixels, sampled when compositing
+    _texture: Texture,
+    view: TextureView,
+    /// Samples drawn into, kept between paints
+    msaa_view: TextureView,
+    stencil_view: TextureView,
+    /// Binds the resolved pixels for compositing
+    bind_group: BindGroup,
+    /// Whether anything has been painted yet
+    initialized: bool,
+}
+
+impl GpuCanvas {
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The resolved pixels, premultiplied
+    pub fn view(&self) -> &TextureView {
+        &self.view
+    }
+}
+
+/// One draw of a paint, in the order commands were recorded
+enum CanvasDraw {
+    /// Mark the stencil with a fan, then cover it
+    Fill { rule: FillRule, fan: std::ops::Range<u32>, cover: std::ops::Range<u32> },
+    Clear(std::ops::Range<u32>),
+    Image(std::ops::Range<u32>, BindGroup),
+}
+
+/// Pipelines that paint canvas commands into backing stores and composite them
+pub struct CanvasPainter {
+    mark_nonzero: RenderPipeline,
+    mark_evenodd: RenderPipeline,
+    cover_nonzero: RenderPipeline,
+    cover_evenodd: RenderPipeline,
+    clear: RenderPipeline,
+    image: RenderPipeline,
+    composite: RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: Sampler,
+    /// Quads of the canvases prepared for compositing
+    composite_vertices: Option<Buffer>,
+}
+
+impl CanvasPainter {
+    /// Create the canvas pipelines, compositing onto a surface of the given format
+    pub fn new(device: &Device, surface_format: wgpu::TextureFormat) -> Self {
+        let solid_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Canvas Solid Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/rect.wgsl").into()),
+        });
+        let image_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Canvas Image Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/image.wgsl").into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Canvas Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let solid_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Canvas Solid Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+        let image_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Canvas Image Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        // Canvas images are sampled pixel for pixel, as the CPU backing store does
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Canvas Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let face = |compare, pass_op| wgpu::StencilFaceState {
+            compare,
+            fail_op: wgpu::StencilOperation::Keep,
+            depth_fail_op: wgpu::StencilOperation::Keep,
+            pass_op,
+        };
+        let untouched = wgpu::StencilState {
+            front: face(wgpu::CompareFunction::Always, wgpu::StencilOperation::Keep),
+            back: face(wgpu::CompareFunction::Always, wgpu::StencilOperation::Keep),
+            read_mask: 0xff,
+            write_mask: 0,
+        };
+        // Triangles wound one way count up and the other way down
+        let winding = wgpu::StencilState {
+            front: face(wgpu::CompareFunction::Always, wgpu::StencilOperation::IncrementWrap),
+            back: face(wgpu::CompareFunction::Always, wgpu::StencilOperation::DecrementWrap),
+            read_mask: 0xff,
+            write_mask: 0xff,
+        };
+        let parity = wgpu::StencilState {
+            front: face(wgpu::CompareFunction::Always, wgpu::StencilOperation::Invert),
+            back: face(wgpu::CompareFunction::Always, wgpu::StencilOperation::Invert),
+            read_mask: 0xff,
+            write_mask: 0xff,
+        };
+        // Paint where the stencil is set against a reference of zero, leaving it clear
+        let cover = |read_mask| wgpu::StencilState {
+            front: face(wgpu::CompareFunction::NotEqual, wgpu::StencilOperation::Zero),
+            back: face(wgpu::CompareFunction::NotEqual, wgpu::StencilOperation::Zero),
+            read_mask,
+            write_mask: 0xff,
+        };
+
+        let blend = Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING);
+        let canvas_pipeline = |label, shader, layout, buffer, blend, write_mask, stencil| {
+            create_pipeline(device, &PipelineSetup {
+                label,
+                shader,
+                layout,
+                buffer,
+                format: CANVAS_FORMAT,
+                blend,
+                write_mask,
+                stencil: Some(stencil),
+                sample_count: SAMPLE_COUNT,
+            })
+        };
+        let solid = SolidVertex::desc;
+        let (all, none) = (wgpu::ColorWrites::ALL, wgpu::ColorWrites::empty());
+        Self {
+            mark_nonzero: canvas_pipeline("Canvas Mark Nonzero", &solid_shader, &solid_layout, solid(), None, none,
+                winding),
+            mark_evenodd: canvas_pipeline("Canvas Mark Evenodd", &solid_shader, &solid_layout, solid(), None, none,
+                parity),
+            cover_nonzero: canvas_pipeline("Canvas Cover Nonzero", &solid_shader, &solid_layout, solid(), blend, all,
+                cover(0xff)),
+            cover_evenodd: canvas_pipeline("Canvas Cover Evenodd", &solid_shader, &solid_layout, solid(), blend, all,
+                cover(0x01)),
+            clear: canvas_pipeline("Canvas Clear", &solid_shader, &solid_layout, solid(), None, all, untouched.clone()),
+            image: canvas_pipeline("Canvas Image", &image_shader, &image_layout, TexturedVertex::desc(), blend, all,
+                untouched),
+            composite: create_pipeline(device, &PipelineSetup {
+                label: "Canvas Composite",
+                shader: &image_shader,
+                layout: &image_layout,
+                buffer: TexturedVertex::desc(),
+                format: surface_format,
+                blend,
+                write_mask: all,
+                stencil: None,
+                sample_count: 1,
+            }),
+            bind_group_layout,
+            sampler,
+            composite_vertices: None,
+        }
+    }
+
+    /// Create a transparent backing store for a canvas
+    pub fn create_canvas(&self, device: &Device, width: u32, height: u32) -> GpuCanvas {
+        let size = wgpu::Extent3d { width: width.max(1), height: height.max(1), depth_or_array_layers: 1 };
+        let create = |label, format, sample_count, usage| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage,
+                view_formats: &[],
+            })
+        };
+        let attachment = wgpu::TextureUsages::RENDER_ATTACHMENT;
+        let texture = create("Canvas Texture", CANVAS_FORMAT, 1, attachment | wgpu::TextureUsages::TEXTURE_BINDING);
+        let msaa = create("Canvas Samples", CANVAS_FORMAT, SAMPLE_COUNT, attachment);
+        let stencil = create("Canvas Stencil", STENCIL_FORMAT, SAMPLE_COUNT, attachment);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = self.bind_texture(device, &view);
+        GpuCanvas {
+            width,
+            height,
+            _texture: texture,
+            view,
+            msaa_view: msaa.create_view(&wgpu::TextureViewDescriptor::default()),
+            stencil_view: stencil.create_view(&wgpu::TextureViewDescriptor::default()),
+            bind_group,
+            initialized: false,
+        }
+    }
+
+    fn bind_texture(&self, device: &Device, view: &TextureView) -> BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Canvas Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(view) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&self.sampler) },
+            ],
+        })
+    }
+
+    /// Bring a backing store up to date with a context
+    ///
+    /// An accelerated context hands over the drawing recorded since the last
+    /// paint. Any other context is uploaded whole, so it should only be
+    /// painted after it changed.
+    pub fn paint_context(&self, device: &Device, queue: &Queue, canvas: &mut GpuCanvas,
+                         context: &mut CanvasRenderingContext2D) {
+        let commands = if context.is_accelerated() {
+            context.take_commands()
+        } else {
+            context.sync_pixels();
+            let corners = canvas_corners(canvas.width, canvas.height);
+            vec![
+                CanvasCommand::Clear { corners },
+                CanvasCommand::Image {
+                    pixels: context.pixels().into(),
+                    width: canvas.width,
+                    height: canvas.height,
+                    corners,
+                },
+            ]
+        };
+        self.paint(device, queue, canvas, &commands);
+    }
+
+    /// Show a video frame in a backing store, scaled to fill it
+    pub fn paint_frame(&self, device: &Device, queue: &Queue, canvas: &mut GpuCanvas, frame: &VideoFrame) {
+        let corners = canvas_corners(canvas.width, canvas.height);
+        let commands = [
+            CanvasCommand::Clear { corners },
+            CanvasCommand::Image { pixels: frame.pixels.clone(), width: frame.width, height: frame.height, corners },
+        ];
+        self.paint(device, queue, canvas, &commands);
+    }
+
+    /// Draw commands into a backing store, in order
+    pub fn paint(&self, device: &Device, queue: &Queue, canvas: &mut GpuCanvas, commands: &[CanvasCommand]) {
+        if commands.is_empty() && canvas.initialized {
+            return;
+        }
+        let (width, height) = (canvas.width, canvas.height);
+        let mut solid = Vec::new();
+        let mut textured = Vec::new();
+        let mut draws = Vec::new();
+        let mut push_solid = |points: &[(f32, f32)], color: [f32; 4]| {
+            let start = solid.len() as u32;
+            solid.extend(points.iter().map(|&point| SolidVertex { position: to_ndc(point, width, height), color }));
+            start..solid.len() as u32
+        };
+        for command in commands {
+            match command {
+                CanvasCommand::Fill { subpaths, rule, color } => {
+                    let Some(bounds) = bounds(subpaths) else {
+                        continue;
+                    };
+                    let fan = push_solid(&fan_triangles(subpaths), [0.0; 4]);
+                    let cover = push_solid(&quad_triangles(bounds), premultiply(*color));
+                    draws.push(CanvasDraw::Fill { rule: *rule, fan, cover });
+                }
+                CanvasCommand::Clear { corners } => {
+                    draws.push(CanvasDraw::Clear(push_solid(&quad_triangles(*corners), [0.0; 4])));
+                }
+                CanvasCommand::Image { pixels, width: image_width, height: image_height, corners } => {
+                    if *image_width == 0 || *image_height == 0 {
+                        continue;
+                    }
+                    let view = self.upload_image(device, queue, pixels, *image_width, *image_height);
+                    let start = textured.len() as u32;
+                    let tex_coords = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+                    let vertices = quad_triangles(*corners).into_iter().zip(quad_triangles(tex_coords));
+                    textured.extend(vertices.map(|(point, (u, v))| TexturedVertex {
+                        position: to_ndc(point, width, height),
+                        tex_coords: [u, v],
+                    }));
+                    draws.push(CanvasDraw::Image(start..textured.len() as u32, self.bind_texture(device, &view)));
+                }
+            }
+        }
+
+        let buffer = |label, contents: &[u8]| {
+            (!contents.is_empty()).then(|| {
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(label),
+                    contents,
+                    usage: wgpu::BufferUsages::VERTEX,
+                })
+            })
+        };
+        let solid_buffer = buffer("Canvas Solid Vertex Buffer", bytemuck::cast_slice(&solid));
+        let textured_buffer = buffer("Canvas Image Vertex Buffer", bytemuck::cast_slice(&textured));
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Canvas Encoder"),
+        });
+        {
+            let load = match canvas.initialized {
+                true => wgpu::LoadOp::Load,
+                false => wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+            };
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Canvas Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &canvas.msaa_view,
+                    resolve_target: Some(&canvas.view),
+                    ops: wgpu::Operations { load, store: wgpu::StoreOp::Store },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &canvas.stencil_view,
+                    depth_ops: Some(wgpu::Operations { load: wgpu::LoadOp::Clear(1.0), store: wgpu::StoreOp::Discard }),
+                    stencil_ops: Some(wgpu::Operations { load: wgpu::LoadOp::Clear(0), store: wgpu::StoreOp::Discard }),
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_stencil_reference(0);
+            if let Some(buffer) = &solid_buffer {
+                render_pass.set_vertex_buffer(0, buffer.slice(..));
+            }
+            for draw in &draws {
+                match draw {
+                    CanvasDraw::Fill { rule, fan, cover } => {
+                        let (mark, fill) = match rule {
+                            FillRule::NonZero => (&self.mark_nonzero, &self.cover_nonzero),
+                            FillRule::EvenOdd => (&self.mark_evenodd, &self.cover_evenodd),
+                        };
+                        render_pass.set_pipeline(mark);
+                        render_pass.draw(fan.clone(), 0..1);
+                        render_pass.set_pipeline(fill);
+                        render_pass.draw(cover.clone(), 0..1);
+                    }
+                    CanvasDraw::Clear(range) => {
+                        render_pass.set_pipeline(&self.clear);
+                        render_pass.draw(range.clone(), 0..1);
+                    }
+                    CanvasDraw::Image(range, bind_group) => {
+                        if let Some(buffer) = &textured_buffer {
+                            render_pass.set_pipeline(&self.image);
+                            render_pass.set_bind_group(0, bind_group, &[]);
+                            render_pass.set_vertex_buffer(0, buffer.slice(..));
+                            render_pass.draw(range.clone(), 0..1);
+                        }
+                        // Later solid draws need their buffer back
+                        if let Some(buffer) = &solid_buffer {
+                            render_pass.set_vertex_buffer(0, buffer.slice(..));
+                        }
+                    }
+                }
+            }
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+        canvas.initialized = true;
+    }
+
+    /// Upload an image's pixels, premultiplied, to a texture
+    fn upload_image(&self, device: &Device, queue: &Queue, pixels: &[u8], width: u32, height: u32) -> TextureView {
+        let size = wgpu::Extent3d { width, height, depth_or_array_layers: 1 };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Canvas Image Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: CANVAS_FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &premultiply_pixels(pixels),
+            wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(4 * width), rows_per_image: Some(height) },
+            size,
+        );
+        texture.create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    /// Prepare canvases to composite at rectangles of a viewport, in device pixels
+    pub fn prepare_composite(&mut self, device: &Device, canvases: &[(&GpuCanvas, Rect)], viewport_size: (u32, u32)) {
+        let tex_coords = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let vertices: Vec<TexturedVertex> = canvases
+            .iter()
+            .flat_map(|(_, rect)| {
+                let corners = [
+                    (rect.x, rect.y),
+                    (rect.x + rect.width, rect.y),
+                    (rect.x + rect.width, rect.y + rect.height),
+                    (rect.x, rect.y + rect.height),
+                ];
+                quad_triangles(corners).into_iter().zip(quad_triangles(tex_coords)).map(|(point, (u, v))| {
+                    TexturedVertex { position: to_ndc(point, viewport_size.0, viewport_size.1), tex_coords: [u, v] }
+                })
+            })
+            .collect();
+        self.composite_vertices = (!vertices.is_empty()).then(|| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Canvas Composite Vertex Buffer"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            })
+        });
+    }
+
+    /// Composite the prepared canvases, given again in the same order
+    pub fn render_composite<'rpass>(&'rpass self, render_pass: &mut RenderPass<'rpass>,
+                                    canvases: &[&'rpass GpuCanvas]) {
+        let Some(buffer) = &self.composite_vertices else {
+            return;
+        };
+        render_pass.set_pipeline(&self.composite);
+        render_pass.set_vertex_buffer(0, buffer.slice(..));
+        for (i, canvas) in canvases.iter().enumerate() {
+            render_pass.set_bind_group(0, &canvas.bind_group, &[]);
+            let start = (i * 6) as u32;
+            render_pass.draw(start..start + 6, 0..1);
+        }
+    }
+}
+
+/// What differs between the canvas pipelines
+struct PipelineSetup<'a> {
+    label: &'a str,
+    shader: &'a wgpu::ShaderModule,
+    layout: &'a wgpu::PipelineLayout,
+    buffer: wgpu::VertexBufferLayout<'static>,
+    format: wgpu::TextureFormat,
+    blend: Option<wgpu::BlendState>,
+    write_mask: wgpu::ColorWrites,
+    /// Stencil use, for pipelines drawing into a backing store
+    stencil: Option<wgpu::StencilState>,
+    sample_count: u32,
+}
+
+fn create_pipeline(device: &Device, setup: &PipelineSetup) -> RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(setup.label),
+        layout: Some(setup.layout),
+        vertex: wgpu::VertexState {
+            module: setup.shader,
+            entry_point: "vs_main",
+            buffers: std::slice::from_ref(&setup.buffer),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: setup.shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format: setup.format,
+                blend: setup.blend,
+                write_mask: setup.write_mask,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: setup.stencil.clone().map(|stencil| wgpu::DepthStencilState {
+            format: STENCIL_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil,
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: setup.sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+/// Corners of a whole canvas, clockwise from its top left
+fn canvas_corners(width: u32, height: u32) -> [(f32, f32); 4] {
+    let (width, height) = (width as f32, height as f32);
+    [(0.0, 0.0), (width, 0.0), (width, height), (0.0, height)]
+}
+
+/// Convert a point in pixels to normalized device coordinates
+fn to_ndc((x, y): (f32, f32), width: u32, height: u32) -> [f32; 2] {
+    [x / width.max(1) as f32 * 2.0 - 1.0, 1.0 - y / height.max(1) as f32 * 2.0]
+}
+
+/// Triangles fanning out from the first point of each subpath
+///
+/// Drawn into the stencil, each point inside the polygons is covered by
+/// as many front-facing less back-facing triangles as the polygons wind
+/// around it, and by an odd number of triangles when evenodd puts it inside.
+fn fan_triangles(subpaths: &[Subpath]) -> Vec<(f32, f32)> {
+    let mut triangles = Vec::new();
+    for subpath in subpaths {
+        let Some((&first, rest)) = subpath.points.split_first() else {
+            continue;
+        };
+        for pair in rest.windows(2) {
+            triangles.extend([first, pair[0], pair[1]]);
+        }
+    }
+    triangles
+}
+
+/// Bounding quad of polygons, if they have any points
+fn bounds(subpaths: &[Subpath]) -> Option<[(f32, f32); 4]> {
+    let mut points = subpaths.iter().flat_map(|subpath| subpath.points.iter());
+    let &(x, y) = points.next()?;
+    let (left, top, right, bottom) = points.fold((x, y, x, y), |(l, t, r, b), &(x, y)| {
+        (l.min(x), t.min(y), r.max(x), b.max(y))
+    });
+    Some([(left, top), (right, top), (right, bottom), (left, bottom)])
+}
+
+/// Two triangles making up a quad given by its corners in order
+fn quad_triangles([a, b, c, d]: [(f32, f32); 4]) -> [(f32, f32); 6] {
+    [a, b, c, a, c, d]
+}
+
+/// A color as premultiplied floats
+fn premultiply(color: Color) -> [f32; 4] {
+    let alpha = color.a as f32 / 255.0;
+    [color.r as f32 / 255.0 * alpha, color.g as f32 / 255.0 * alpha, color.b as f32 / 255.0 * alpha, alpha]
+}
+
+/// RGBA pixels with their colors multiplied by their alpha
+fn premultiply_pixels(pixels: &[u8]) -> Vec<u8> {
+    pixels
+        .chunks_exact(4)
+        .flat_map(|pixel| {
+            let multiply = |channel: u8| ((channel as u32 * pixel[3] as u32 + 127) / 255) as u8;
+            [multiply(pixel[0]), multiply(pixel[1]), multiply(pixel[2]), pixel[3]]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill_geometry() {
+        // A square and a triangle: two fan triangles and one
+        let square = Subpath { points: vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)], closed: true };
+        let triangle = Subpath { points: vec![(20.0, 5.0), (30.0, 5.0), (25.0, 15.0)], closed: true };
+        let subpaths = [square, triangle];
+        let fan = fan_triangles(&subpaths);
+        assert_eq!(fan.len(), 9);
+        assert_eq!(&fan[..3], &[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]);
+        assert_eq!(bounds(&subpaths), Some([(0.0, 0.0), (30.0, 0.0), (30.0, 15.0), (0.0, 15.0)]));
+        assert_eq!(bounds(&[]), None);
+
+        // Canvas pixels map onto the whole of clip space, y upwards
+        assert_eq!(to_ndc((0.0, 0.0), 200, 100), [-1.0, 1.0]);
+        assert_eq!(to_ndc((200.0, 100.0), 200, 100), [1.0, -1.0]);
+        assert_eq!(to_ndc((50.0, 75.0), 200, 100), [-0.5, -0.5]);
+    }
+
+    #[test]
+    fn test_colors_are_premultiplied() {
+        let color = premultiply(Color::rgba(255, 0, 51, 51));
+        let expected = [0.2, 0.0, 0.04, 0.2];
+        assert!(color.iter().zip(expected).all(|(channel, expected)| (channel - expected).abs() < 1e-6));
+        assert_eq!(premultiply_pixels(&[255, 128, 0, 128, 10, 20, 30, 0]), vec![128, 64, 0, 128, 0, 0, 0, 0]);
+    }
+}
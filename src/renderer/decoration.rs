@@ -0,0 +1,184 @@
+// Text decoration geometry - lines under, over and through text
+//
+// Lines are placed and sized from the font's own metrics: the underline
+// position and thickness from its `post` table, the strikeout metrics from
+// its OS/2 table and its ascender for overlines. With skip-ink, underlines
+// and overlines break where they would cross a glyph's ink, leaving a gap
+// of the line's thickness either side.
+
+use crate::layout::Rect;
+use crate::text_style::{DecorationLine, DecorationStyle, TextDecoration};
+
+use super::font_manager::FontData;
+
+/// Where a font puts decoration lines at a size, in pixels above the baseline
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecorationMetrics {
+    pub ascent: f32,
+    /// Top of the underline, negative below the baseline
+    pub underline_position: f32,
+    pub underline_thickness: f32,
+    /// Top of the line through the text
+    pub strikeout_position: f32,
+    pub strikeout_thickness: f32,
+}
+
+impl DecorationMetrics {
+    /// Metrics of a font at `font_size` pixels, or typical proportions if the font lacks them
+    pub fn from_font(font: &FontData, font_size: f32) -> Self {
+        let mut metrics = Self {
+            ascent: font_size * 0.8,
+            underline_position: font_size * -0.1,
+            underline_thickness: font_size * 0.05,
+            strikeout_position: font_size * 0.3,
+            strikeout_thickness: font_size * 0.05,
+        };
+        let Some(face) = font.face() else {
+            return metrics;
+        };
+        let scale = font_size / face.units_per_em() as f32;
+        metrics.ascent = face.ascender() as f32 * scale;
+        if let Some(underline) = face.underline_metrics() {
+            metrics.underline_position = underline.position as f32 * scale;
+            metrics.underline_thickness = underline.thickness as f32 * scale;
+        }
+        if let Some(strikeout) = face.strikeout_metrics() {
+            metrics.strikeout_position = strikeout.position as f32 * scale;
+            metrics.strikeout_thickness = strikeout.thickness as f32 * scale;
+        }
+        metrics
+    }
+}
+
+/// Rectangles that draw a decoration along `width` pixels of text
+///
+/// Positions are relative to the start of the text's baseline, y pointing
+/// down. `ink` holds the boxes of the glyphs drawn, in the same space, for
+/// skip-ink to break underlines and overlines around. Wavy lines are drawn
+/// as steps a thickness high.
+pub fn decoration_rects(
+    decoration: &TextDecoration,
+    metrics: &DecorationMetrics,
+    width: f32,
+    ink: &[Rect],
+    skip_ink: bool,
+) -> Vec<Rect> {
+    let (top, font_thickness) = match decoration.line {
+        DecorationLine::Underline => (-metrics.underline_position, metrics.underline_thickness),
+        DecorationLine::Overline => (-metrics.ascent, metrics.underline_thickness),
+        DecorationLine::LineThrough => (-metrics.strikeout_position, metrics.strikeout_thickness),
+    };
+    // Hairlines would vanish
+    let thickness = decoration.thickness.unwrap_or(font_thickness).max(1.0);
+
+    // Double lines grow away from the text, or either side of a line through it
+    let bands = match (decoration.style, decoration.line) {
+        (DecorationStyle::Double, DecorationLine::Underline) => vec![top, top + thickness * 2.0],
+        (DecorationStyle::Double, DecorationLine::Overline) => vec![top, top - thickness * 2.0],
+        (DecorationStyle::Double, DecorationLine::LineThrough) => vec![top - thickness, top + thickness],
+        _ => vec![top],
+    };
+    let (on, off) = match decoration.style {
+        DecorationStyle::Dotted => (thickness, thickness),
+        DecorationStyle::Dashed => (thickness * 3.0, thickness * 2.0),
+        DecorationStyle::Wavy => (thickness * 2.0, 0.0),
+        DecorationStyle::Solid | DecorationStyle::Double => (width, 0.0),
+    };
+
+    let mut rects = Vec::new();
+    for y in bands {
+        let mut x = 0.0;
+        let mut step = 0;
+        while x < width && on > 0.0 {
+            let y = if decoration.style == DecorationStyle::Wavy && step % 2 == 1 { y + thickness } else { y };
+            rects.push(Rect { x, y, width: on.min(width - x), height: thickness });
+            x += on + off;
+            step += 1;
+        }
+    }
+
+    if skip_ink && decoration.line != DecorationLine::LineThrough {
+        for glyph in ink {
+            rects = rects.into_iter().flat_map(|rect| skip(rect, glyph, thickness)).collect();
+        }
+    }
+    rects
+}
+
+/// What is left of a line segment once a gap is cut around a glyph's ink crossing it
+fn skip(rect: Rect, glyph: &Rect, gap: f32) -> Vec<Rect> {
+    let crosses = glyph.y < rect.y + rect.height + gap && glyph.y + glyph.height > rect.y - gap;
+    let (start, end) = (glyph.x - gap, glyph.x + glyph.width + gap);
+    if !crosses || end <= rect.x || start >= rect.x + rect.width {
+        return vec![rect];
+    }
+    let mut pieces = Vec::new();
+    if start > rect.x {
+        pieces.push(Rect { width: start - rect.x, ..rect });
+    }
+    if end < rect.x + rect.width {
+        pieces.push(Rect { x: end, width: rect.x + rect.width - end, ..rect });
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::Color;
+    use crate::renderer::font_manager::FontManager;
+
+    fn decoration(line: DecorationLine, style: DecorationStyle) -> TextDecoration {
+        TextDecoration { line, style, color: Color::black(), thickness: Some(2.0) }
+    }
+
+    #[test]
+    fn test_lines_follow_font_metrics() {
+        let font = FontManager::new().unwrap().get_font_data("sans-serif");
+        let metrics = DecorationMetrics::from_font(&font, 20.0);
+        assert!(metrics.underline_position < 0.0 && metrics.strikeout_position > 0.0);
+        assert!(metrics.ascent > metrics.strikeout_position);
+
+        let from_font = TextDecoration {
+            thickness: None,
+            ..decoration(DecorationLine::Underline, DecorationStyle::Solid)
+        };
+        let rects = decoration_rects(&from_font, &metrics, 50.0, &[], true);
+        assert_eq!(rects.len(), 1);
+        assert_eq!(rects[0].y, -metrics.underline_position);
+        assert_eq!(rects[0].height, metrics.underline_thickness.max(1.0));
+    }
+
+    #[test]
+    fn test_styles_and_skip_ink() {
+        let metrics = DecorationMetrics {
+            ascent: 16.0,
+            underline_position: -2.0,
+            underline_thickness: 1.0,
+            strikeout_position: 6.0,
+            strikeout_thickness: 1.0,
+        };
+        let underline = decoration(DecorationLine::Underline, DecorationStyle::Solid);
+        // A descender crossing the underline between x 10 and 14
+        let descender = Rect { x: 10.0, y: -8.0, width: 4.0, height: 12.0 };
+        let rects = decoration_rects(&underline, &metrics, 40.0, &[descender], true);
+        assert_eq!(
+            rects,
+            vec![
+                Rect { x: 0.0, y: 2.0, width: 8.0, height: 2.0 },
+                Rect { x: 16.0, y: 2.0, width: 24.0, height: 2.0 },
+            ]
+        );
+        assert_eq!(decoration_rects(&underline, &metrics, 40.0, &[descender], false).len(), 1);
+
+        // Lines through text are never broken
+        let through = decoration(DecorationLine::LineThrough, DecorationStyle::Double);
+        let rects = decoration_rects(&through, &metrics, 40.0, &[descender], true);
+        assert_eq!(rects.iter().map(|rect| rect.y).collect::<Vec<_>>(), vec![-8.0, -4.0]);
+
+        let overline = decoration(DecorationLine::Overline, DecorationStyle::Dotted);
+        let dotted = decoration_rects(&overline, &metrics, 20.0, &[], true);
+        assert_eq!(dotted.len(), 5);
+        assert!(dotted.iter().all(|rect| rect.width == 2.0 && rect.y == -16.0));
+    }
+}
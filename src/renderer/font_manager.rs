@@ -1,45 +1,70 @@
 use font_kit::family_name::FamilyName;
+use font_kit::handle::Handle;
 use font_kit::properties::Properties;
 use font_kit::source::SystemSource;
 use fontdue::{Font, FontSettings};
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use super::shaping::{self, Spacing};
+use crate::layout::inline::{FontMetrics, RunFont, TextMeasure};
+
+thread_local! {
+    /// System font source, one per thread as it cannot be sent between them
+    static SYSTEM_SOURCE: SystemSource = SystemSource::new();
+}
+
+/// Raw font file, kept for shaping
+#[derive(Debug)]
+pub struct FontData {
+    /// Contents of the font file
+    pub bytes: Arc<Vec<u8>>,
+    /// Face within a font collection
+    pub index: u32,
+}
+
+impl FontData {
+    /// Parse the face for shaping
+    pub fn face(&self) -> Option<rustybuzz::Face<'_>> {
+        rustybuzz::Face::from_slice(&self.bytes, self.index)
+    }
+}
+
+/// A font parsed for rasterizing along with its file
+type LoadedFont = (Arc<Font>, Arc<FontData>);
+
 /// Font manager for loading and caching fonts
 pub struct FontManager {
     /// Loaded fonts by family name
-    fonts: HashMap<String, Arc<Font>>,
+    fonts: HashMap<String, LoadedFont>,
     /// Default fallback font
-    default_font: Arc<Font>,
-    /// System font source
-    system_source: SystemSource,
+    default_font: LoadedFont,
 }
 
 impl FontManager {
     /// Create a new font manager
     pub fn new() -> Result<Self, FontLoadError> {
-        let system_source = SystemSource::new();
-        
         // Load a default fallback font (sans-serif)
-        let default_font = Self::load_system_font(&system_source, &FamilyName::SansSerif)?;
+        let default_font = Self::load_system_font(&FamilyName::SansSerif)?;
         
         Ok(Self {
             fonts: HashMap::new(),
-            default_font: Arc::new(default_font),
-            system_source,
+            default_font,
         })
     }
 
     /// Load a font from the system
-    fn load_system_font(
-        source: &SystemSource,
-        family: &FamilyName,
-    ) -> Result<Font, FontLoadError> {
+    fn load_system_font(family: &FamilyName) -> Result<LoadedFont, FontLoadError> {
         // Try to find the font
-        let handle = source
-            .select_best_match(&[family.clone()], &Properties::new())
+        let handle = SYSTEM_SOURCE
+            .with(|source| source.select_best_match(std::slice::from_ref(family), &Properties::new()))
             .map_err(|e| FontLoadError::NotFound(format!("Font not found: {:?}", e)))?;
 
+        let index = match handle {
+            Handle::Path { font_index, .. } | Handle::Memory { font_index, .. } => font_index,
+        };
+
         // Load the font data
         let font_data = handle
             .load()
@@ -50,15 +75,28 @@ impl FontManager {
             .ok_or_else(|| FontLoadError::LoadFailed("Failed to copy font data".to_string()))?;
 
         // Parse with fontdue (need to pass as slice reference)
-        Font::from_bytes(bytes.as_slice(), FontSettings::default())
-            .map_err(|e| FontLoadError::ParseFailed(format!("Failed to parse font: {}", e)))
+        let settings = FontSettings { collection_index: index, ..FontSettings::default() };
+        let font = Font::from_bytes(bytes.as_slice(), settings)
+            .map_err(|e| FontLoadError::ParseFailed(format!("Failed to parse font: {}", e)))?;
+        Ok((Arc::new(font), Arc::new(FontData { bytes, index })))
     }
 
     /// Get or load a font by CSS font-family name
     pub fn get_font(&mut self, family: &str) -> Arc<Font> {
+        Arc::clone(&self.load(family).0)
+    }
+
+    /// Get or load the file of a font by CSS font-family name, for shaping
+    ///
+    /// Matches the font `get_font` returns for the same family.
+    pub fn get_font_data(&mut self, family: &str) -> Arc<FontData> {
+        Arc::clone(&self.load(family).1)
+    }
+
+    fn load(&mut self, family: &str) -> &LoadedFont {
         // Check if already loaded
-        if let Some(font) = self.fonts.get(family) {
-            return Arc::clone(font);
+        if self.fonts.contains_key(family) {
+            return &self.fonts[family];
         }
 
         // Try to load the font
@@ -71,22 +109,18 @@ impl FontManager {
             _ => FamilyName::Title(family.to_string()),
         };
 
-        match Self::load_system_font(&self.system_source, &family_name) {
-            Ok(font) => {
-                let arc_font = Arc::new(font);
-                self.fonts.insert(family.to_string(), Arc::clone(&arc_font));
-                arc_font
-            }
+        match Self::load_system_font(&family_name) {
+            Ok(font) => self.fonts.entry(family.to_string()).or_insert(font),
             Err(_) => {
                 // Fallback to default if load fails
-                Arc::clone(&self.default_font)
+                &self.default_font
             }
         }
     }
 
     /// Get the default fallback font
     pub fn default_font(&self) -> Arc<Font> {
-        Arc::clone(&self.default_font)
+        Arc::clone(&self.default_font.0)
     }
 
     /// Preload common fonts
@@ -115,6 +149,41 @@ impl Default for FontManager {
     }
 }
 
+/// Measures text for layout by shaping it in the fonts it is drawn with
+pub struct FontMeasure {
+    fonts: RefCell<FontManager>,
+}
+
+impl FontMeasure {
+    pub fn new(fonts: FontManager) -> Self {
+        Self { fonts: RefCell::new(fonts) }
+    }
+
+    fn font_data(&self, family: &str) -> Arc<FontData> {
+        self.fonts.borrow_mut().get_font_data(family)
+    }
+}
+
+impl TextMeasure for FontMeasure {
+    fn advance(&self, text: &str, font: &RunFont) -> f32 {
+        let spacing = Spacing { letter: font.letter_spacing, word: font.word_spacing };
+        shaping::shape_text_spaced(text, &self.font_data(&font.family), font.size, None, spacing).width()
+    }
+
+    fn metrics(&self, font: &RunFont) -> FontMetrics {
+        let data = self.font_data(&font.family);
+        let Some(face) = data.face() else {
+            return FontMetrics::estimated(font.size);
+        };
+        let scale = font.size / face.units_per_em() as f32;
+        FontMetrics {
+            ascent: face.ascender() as f32 * scale,
+            descent: -(face.descender() as f32) * scale,
+            line_gap: face.line_gap() as f32 * scale,
+        }
+    }
+}
+
 /// Font loading errors
 #[derive(Debug)]
 pub enum FontLoadError {
@@ -1,7 +1,19 @@
+// Glyph cache - rasterized glyphs packed into paged texture atlases
+//
+// Each render mode keeps its own atlases, since their pixels differ in
+// size. Glyphs are packed into shelves on a page; when every page a mode
+// may have is full, the least recently used page is emptied and its glyphs
+// rasterized again the next time they are asked for. Pages used since the
+// current frame began are never emptied, so glyphs already placed in the
+// frame stay valid.
+
 use fontdue::Font;
 use std::collections::HashMap;
 use std::sync::Arc;
 
+/// Pages each render mode may have before glyphs are evicted
+const DEFAULT_MAX_PAGES: usize = 4;
+
 /// Key for identifying a cached glyph
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct GlyphKey {
@@ -13,9 +25,55 @@ pub struct GlyphKey {
     pub font_id: usize,
 }
 
+/// Key for identifying a cached glyph chosen by shaping
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GlyphIdKey {
+    /// Index of the glyph in the font
+    pub glyph_id: u16,
+    /// Font size in pixels
+    pub size: u32,
+    /// Font index (for multiple fonts)
+    pub font_id: usize,
+}
+
+/// How glyphs are rasterized; each mode has atlases of its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RenderMode {
+    /// One coverage byte per pixel
+    Grayscale,
+    /// Red, green and blue coverage per pixel, for LCD antialiasing
+    Subpixel,
+    /// RGBA per pixel; fontdue draws no color glyphs, so outlines are white with coverage as alpha
+    Color,
+}
+
+impl RenderMode {
+    pub const ALL: [RenderMode; 3] = [RenderMode::Grayscale, RenderMode::Subpixel, RenderMode::Color];
+
+    /// Bytes each atlas pixel takes
+    pub fn bytes_per_pixel(&self) -> u32 {
+        match self {
+            RenderMode::Grayscale => 1,
+            RenderMode::Subpixel => 3,
+            RenderMode::Color => 4,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RenderMode::Grayscale => "grayscale",
+            RenderMode::Subpixel => "subpixel",
+            RenderMode::Color => "color",
+        }
+    }
+}
+
 /// Information about a rasterized glyph in the atlas
 #[derive(Debug, Clone, Copy)]
 pub struct GlyphInfo {
+    /// Atlas the glyph was rasterized for and the page of it holding the glyph
+    pub mode: RenderMode,
+    pub page: usize,
     /// Position in atlas texture (pixels)
     pub atlas_x: u32,
     pub atlas_y: u32,
@@ -29,68 +87,93 @@ pub struct GlyphInfo {
     pub advance: f32,
 }
 
-/// Simple texture atlas allocator for glyphs
+/// A row of the atlas glyphs no taller than it are packed into
+#[derive(Debug, Clone, Copy)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    /// Left edge of the free space at the end of the shelf
+    x: u32,
+}
+
+/// Texture atlas page, packing glyphs into shelves
 #[derive(Debug)]
 pub struct TextureAtlas {
     /// Atlas dimensions
     width: u32,
     height: u32,
-    /// Current packing position
-    current_x: u32,
-    current_y: u32,
-    /// Current row height
-    row_height: u32,
-    /// Actual texture data (grayscale, R8)
+    bytes_per_pixel: u32,
+    shelves: Vec<Shelf>,
+    /// Top of the space below the last shelf
+    next_y: u32,
+    /// Pixels taken by allocated glyphs
+    used_area: u64,
+    /// Actual texture data, rows of `width * bytes_per_pixel` bytes
     data: Vec<u8>,
 }
 
 impl TextureAtlas {
-    /// Create a new texture atlas
+    /// Create a new single channel texture atlas
     pub fn new(width: u32, height: u32) -> Self {
-        let size = (width * height) as usize;
+        Self::with_bytes_per_pixel(width, height, 1)
+    }
+
+    /// Create a new texture atlas with `bytes_per_pixel` bytes to each pixel
+    pub fn with_bytes_per_pixel(width: u32, height: u32, bytes_per_pixel: u32) -> Self {
+        let size = (width * height * bytes_per_pixel) as usize;
         Self {
             width,
             height,
-            current_x: 0,
-            current_y: 0,
-            row_height: 0,
+            bytes_per_pixel,
+            shelves: Vec::new(),
+            next_y: 0,
+            used_area: 0,
             data: vec![0; size],
         }
     }
 
     /// Try to allocate space for a glyph in the atlas
     /// Returns (x, y) position if successful
+    ///
+    /// The glyph goes on the shelf that fits it with the least height to
+    /// spare, or on a new shelf if that would waste more than half its height.
     pub fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
-        // Check if glyph fits in current row
-        if self.current_x + width > self.width {
-            // Move to next row
-            self.current_x = 0;
-            self.current_y += self.row_height;
-            self.row_height = 0;
-        }
-
-        // Check if we have vertical space
-        if self.current_y + height > self.height {
-            return None; // Atlas is full
+        if width > self.width {
+            return None;
         }
+        let best = self
+            .shelves
+            .iter()
+            .enumerate()
+            .filter(|(_, shelf)| shelf.height >= height && shelf.x + width <= self.width)
+            .min_by_key(|(_, shelf)| shelf.height - height)
+            .map(|(index, _)| index);
+        let can_open = self.next_y + height <= self.height;
+        let index = match best {
+            Some(index) if !can_open || self.shelves[index].height - height <= height / 2 => index,
+            _ if can_open => {
+                self.shelves.push(Shelf { y: self.next_y, height, x: 0 });
+                self.next_y += height;
+                self.shelves.len() - 1
+            }
+            _ => return None, // Atlas is full
+        };
 
-        let x = self.current_x;
-        let y = self.current_y;
-
-        // Update position
-        self.current_x += width;
-        self.row_height = self.row_height.max(height);
-
-        Some((x, y))
+        let shelf = &mut self.shelves[index];
+        let position = (shelf.x, shelf.y);
+        shelf.x += width;
+        self.used_area += width as u64 * height as u64;
+        Some(position)
     }
 
     /// Upload glyph bitmap data to the atlas
     pub fn upload_glyph(&mut self, x: u32, y: u32, width: u32, height: u32, data: &[u8]) {
+        let row_bytes = (width * self.bytes_per_pixel) as usize;
         for row in 0..height {
-            let src_start = (row * width) as usize;
-            let src_end = src_start + width as usize;
-            let dst_start = ((y + row) * self.width + x) as usize;
-            let dst_end = dst_start + width as usize;
+            let src_start = row as usize * row_bytes;
+            let src_end = src_start + row_bytes;
+            let dst_start = (((y + row) * self.width + x) * self.bytes_per_pixel) as usize;
+            let dst_end = dst_start + row_bytes;
 
             if src_end <= data.len() && dst_end <= self.data.len() {
                 self.data[dst_start..dst_end].copy_from_slice(&data[src_start..src_end]);
@@ -107,28 +190,148 @@ impl TextureAtlas {
     pub fn dimensions(&self) -> (u32, u32) {
         (self.width, self.height)
     }
+
+    pub fn bytes_per_pixel(&self) -> u32 {
+        self.bytes_per_pixel
+    }
+
+    /// Fraction of the atlas allocated to glyphs
+    pub fn occupancy(&self) -> f32 {
+        self.used_area as f32 / (self.width as u64 * self.height as u64).max(1) as f32
+    }
+}
+
+/// A glyph as the cache knows it: by character or by glyph index
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum CachedGlyph {
+    Char(GlyphKey),
+    Index(GlyphIdKey),
+}
+
+impl CachedGlyph {
+    fn font_id(&self) -> usize {
+        match self {
+            CachedGlyph::Char(key) => key.font_id,
+            CachedGlyph::Index(key) => key.font_id,
+        }
+    }
+}
+
+/// An atlas page and when it was last used
+#[derive(Debug)]
+struct AtlasPage {
+    atlas: TextureAtlas,
+    last_used: u64,
+    glyphs: usize,
+    dirty: bool,
+}
+
+/// Glyph atlas usage, for DevTools
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GlyphAtlasStats {
+    pub pages: Vec<AtlasPageStats>,
+    pub glyphs: usize,
+    /// Bytes of texture data across every page
+    pub bytes: usize,
+    /// Lookups answered from the cache and lookups that rasterized
+    pub hits: u64,
+    pub misses: u64,
+    /// Pages emptied to make room
+    pub evictions: u64,
+}
+
+impl GlyphAtlasStats {
+    /// Fraction of lookups answered from the cache
+    pub fn hit_rate(&self) -> f32 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f32 / lookups as f32
+        }
+    }
+}
+
+/// Usage of one atlas page
+#[derive(Debug, Clone, PartialEq)]
+pub struct AtlasPageStats {
+    pub mode: RenderMode,
+    pub page: usize,
+    pub glyphs: usize,
+    /// Fraction of the page allocated to glyphs
+    pub occupancy: f32,
 }
 
 /// Glyph cache for rasterizing and caching glyphs
 pub struct GlyphCache {
-    /// Cached glyphs
-    glyphs: HashMap<GlyphKey, GlyphInfo>,
-    /// Texture atlas
-    atlas: TextureAtlas,
+    /// Cached glyphs by the mode they were rasterized for
+    glyphs: HashMap<(CachedGlyph, RenderMode), GlyphInfo>,
+    /// Atlas pages of each render mode
+    pages: HashMap<RenderMode, Vec<AtlasPage>>,
+    /// Size of every page
+    page_size: (u32, u32),
+    max_pages: usize,
     /// Font registry (font_id -> Font)
     fonts: Vec<Arc<Font>>,
-    /// Dirty flag for atlas updates
-    dirty: bool,
+    /// Counts lookups, to order pages by when they were last used
+    clock: u64,
+    /// Clock reading when the frame began; pages used since are kept
+    frame_start: u64,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
 }
 
 impl GlyphCache {
-    /// Create a new glyph cache with specified atlas size
+    /// Create a new glyph cache with pages of the specified atlas size
     pub fn new(atlas_width: u32, atlas_height: u32) -> Self {
         Self {
             glyphs: HashMap::new(),
-            atlas: TextureAtlas::new(atlas_width, atlas_height),
+            pages: HashMap::new(),
+            page_size: (atlas_width, atlas_height),
+            max_pages: DEFAULT_MAX_PAGES,
             fonts: Vec::new(),
-            dirty: false,
+            clock: 0,
+            frame_start: 0,
+            hits: 0,
+            misses: 0,
+            evictions: 0,
+        }
+    }
+
+    /// Set how many pages each render mode may have, dropping the glyphs on pages past it
+    pub fn set_max_pages(&mut self, max_pages: usize) {
+        self.max_pages = max_pages.max(1);
+        let max = self.max_pages;
+        self.glyphs.retain(|_, info| info.width == 0 || info.page < max);
+        for pages in self.pages.values_mut() {
+            pages.truncate(max);
+        }
+    }
+
+    /// Drop every cached glyph and empty the atlas
+    ///
+    /// Registered fonts are kept, and so are the pages, emptied.
+    pub fn clear(&mut self) {
+        self.glyphs.clear();
+        let (width, height) = self.page_size;
+        for (mode, pages) in &mut self.pages {
+            for page in pages {
+                page.atlas = TextureAtlas::with_bytes_per_pixel(width, height, mode.bytes_per_pixel());
+                page.glyphs = 0;
+                page.dirty = true;
+            }
+        }
+    }
+
+    /// Drop every page but the first of each render mode, with the glyphs on them
+    ///
+    /// Frees atlas memory under memory pressure; glyphs are rasterized again
+    /// as they are drawn.
+    pub fn trim(&mut self) {
+        self.glyphs.retain(|_, info| info.width == 0 || info.page == 0);
+        for pages in self.pages.values_mut() {
+            pages.truncate(1);
         }
     }
 
@@ -139,92 +342,225 @@ impl GlyphCache {
         id
     }
 
+    /// Start a frame: glyphs looked up from now on stay in the atlas until the next frame
+    pub fn begin_frame(&mut self) {
+        self.frame_start = self.clock + 1;
+    }
+
     /// Get or rasterize a glyph
     pub fn get_or_rasterize(&mut self, key: GlyphKey) -> Option<GlyphInfo> {
-        // Check if already cached
-        if let Some(info) = self.glyphs.get(&key) {
-            return Some(*info);
-        }
+        self.lookup(CachedGlyph::Char(key), RenderMode::Grayscale)
+    }
 
-        // Get the font
-        let font = self.fonts.get(key.font_id)?;
+    /// Get or rasterize a glyph for a render mode
+    pub fn get_or_rasterize_as(&mut self, key: GlyphKey, mode: RenderMode) -> Option<GlyphInfo> {
+        self.lookup(CachedGlyph::Char(key), mode)
+    }
 
-        // Rasterize the glyph
-        let (metrics, bitmap) = font.rasterize(key.character, key.size as f32);
+    /// Get or rasterize a glyph by its index in the font, as shaping picks them
+    pub fn get_or_rasterize_glyph(&mut self, key: GlyphIdKey) -> Option<GlyphInfo> {
+        self.lookup(CachedGlyph::Index(key), RenderMode::Grayscale)
+    }
 
-        // Handle empty glyphs (e.g., space)
-        if bitmap.is_empty() {
-            let info = GlyphInfo {
-                atlas_x: 0,
-                atlas_y: 0,
-                width: 0,
-                height: 0,
-                bearing_x: metrics.xmin as f32,
-                bearing_y: metrics.ymin as f32,
-                advance: metrics.advance_width,
-            };
-            self.glyphs.insert(key, info);
+    /// Get or rasterize a glyph by its index in the font for a render mode
+    pub fn get_or_rasterize_glyph_as(&mut self, key: GlyphIdKey, mode: RenderMode) -> Option<GlyphInfo> {
+        self.lookup(CachedGlyph::Index(key), mode)
+    }
+
+    fn lookup(&mut self, glyph: CachedGlyph, mode: RenderMode) -> Option<GlyphInfo> {
+        self.clock += 1;
+        if let Some(info) = self.glyphs.get(&(glyph, mode)).copied() {
+            self.hits += 1;
+            if info.width > 0 {
+                if let Some(page) = self.pages.get_mut(&mode).and_then(|pages| pages.get_mut(info.page)) {
+                    page.last_used = self.clock;
+                }
+            }
             return Some(info);
         }
+        self.misses += 1;
 
-        // Allocate space in atlas
-        let (atlas_x, atlas_y) = self.atlas.allocate(metrics.width as u32, metrics.height as u32)?;
-
-        // Upload glyph to atlas
-        self.atlas.upload_glyph(
-            atlas_x,
-            atlas_y,
-            metrics.width as u32,
-            metrics.height as u32,
-            &bitmap,
-        );
-
-        // Create glyph info
-        let info = GlyphInfo {
-            atlas_x,
-            atlas_y,
-            width: metrics.width as u32,
-            height: metrics.height as u32,
+        // Rasterize the glyph
+        let font = self.fonts.get(glyph.font_id())?.clone();
+        let (metrics, bitmap) = match (glyph, mode) {
+            (CachedGlyph::Char(key), RenderMode::Subpixel) => font.rasterize_subpixel(key.character, key.size as f32),
+            (CachedGlyph::Char(key), _) => font.rasterize(key.character, key.size as f32),
+            (CachedGlyph::Index(key), RenderMode::Subpixel) => {
+                font.rasterize_indexed_subpixel(key.glyph_id, key.size as f32)
+            }
+            (CachedGlyph::Index(key), _) => font.rasterize_indexed(key.glyph_id, key.size as f32),
+        };
+        let bitmap = match mode {
+            RenderMode::Color => bitmap.iter().flat_map(|&coverage| [255, 255, 255, coverage]).collect(),
+            _ => bitmap,
+        };
+        let info = self.store(mode, metrics, &bitmap)?;
+        self.glyphs.insert((glyph, mode), info);
+        Some(info)
+    }
+
+    /// Put a rasterized glyph in one of the mode's pages
+    fn store(&mut self, mode: RenderMode, metrics: fontdue::Metrics, bitmap: &[u8]) -> Option<GlyphInfo> {
+        let mut info = GlyphInfo {
+            mode,
+            page: 0,
+            atlas_x: 0,
+            atlas_y: 0,
+            width: 0,
+            height: 0,
             bearing_x: metrics.xmin as f32,
             bearing_y: metrics.ymin as f32,
             advance: metrics.advance_width,
         };
+        // Handle empty glyphs (e.g., space)
+        if bitmap.is_empty() {
+            return Some(info);
+        }
 
-        self.glyphs.insert(key, info);
-        self.dirty = true;
-
+        let (width, height) = (metrics.width as u32, metrics.height as u32);
+        let (page, (atlas_x, atlas_y)) = self.allocate(mode, width, height)?;
+        let page_entry = &mut self.pages.get_mut(&mode)?[page];
+        page_entry.atlas.upload_glyph(atlas_x, atlas_y, width, height, bitmap);
+        page_entry.last_used = self.clock;
+        page_entry.glyphs += 1;
+        page_entry.dirty = true;
+
+        info.page = page;
+        info.atlas_x = atlas_x;
+        info.atlas_y = atlas_y;
+        info.width = width;
+        info.height = height;
         Some(info)
     }
 
-    /// Get the atlas texture data
-    pub fn atlas_data(&self) -> &[u8] {
-        self.atlas.data()
+    /// Find room for a glyph: on an existing page, a new page, or the least recently used page emptied
+    fn allocate(&mut self, mode: RenderMode, width: u32, height: u32) -> Option<(usize, (u32, u32))> {
+        let (page_width, page_height) = self.page_size;
+        let pages = self.pages.entry(mode).or_default();
+        for (index, page) in pages.iter_mut().enumerate() {
+            if let Some(position) = page.atlas.allocate(width, height) {
+                return Some((index, position));
+            }
+        }
+        if pages.len() < self.max_pages {
+            let mut atlas = TextureAtlas::with_bytes_per_pixel(page_width, page_height, mode.bytes_per_pixel());
+            let position = atlas.allocate(width, height)?;
+            pages.push(AtlasPage { atlas, last_used: 0, glyphs: 0, dirty: true });
+            return Some((pages.len() - 1, position));
+        }
+
+        // Every page is full; empty the one used longest ago, unless the frame still needs it
+        let frame_start = self.frame_start;
+        let (victim, _) = pages
+            .iter()
+            .enumerate()
+            .filter(|(_, page)| page.last_used < frame_start)
+            .min_by_key(|(_, page)| page.last_used)?;
+        let mut atlas = TextureAtlas::with_bytes_per_pixel(page_width, page_height, mode.bytes_per_pixel());
+        let position = atlas.allocate(width, height)?;
+        pages[victim] = AtlasPage { atlas, last_used: 0, glyphs: 0, dirty: true };
+        self.glyphs.retain(|(_, glyph_mode), info| *glyph_mode != mode || info.width == 0 || info.page != victim);
+        self.evictions += 1;
+        Some((victim, position))
     }
 
-    /// Get atlas dimensions
+    /// Get a cached glyph's pixels, copied out of its atlas page row by row
+    ///
+    /// Rows are `width * bytes_per_pixel` bytes of the glyph's render mode.
+    pub fn glyph_bitmap(&self, info: &GlyphInfo) -> Vec<u8> {
+        let Some(page) = self.pages.get(&info.mode).and_then(|pages| pages.get(info.page)) else {
+            return Vec::new();
+        };
+        let bytes_per_pixel = info.mode.bytes_per_pixel();
+        let data = page.atlas.data();
+        let row_bytes = (info.width * bytes_per_pixel) as usize;
+        let mut bitmap = Vec::with_capacity(row_bytes * info.height as usize);
+        for row in 0..info.height {
+            let start = (((info.atlas_y + row) * self.page_size.0 + info.atlas_x) * bytes_per_pixel) as usize;
+            bitmap.extend_from_slice(&data[start..start + row_bytes]);
+        }
+        bitmap
+    }
+
+    /// Number of atlas pages a render mode has
+    pub fn page_count(&self, mode: RenderMode) -> usize {
+        self.pages.get(&mode).map_or(0, Vec::len)
+    }
+
+    /// Get an atlas page's texture data
+    pub fn page_data(&self, mode: RenderMode, page: usize) -> Option<&[u8]> {
+        self.pages.get(&mode)?.get(page).map(|page| page.atlas.data())
+    }
+
+    /// Get atlas page dimensions
     pub fn atlas_dimensions(&self) -> (u32, u32) {
-        self.atlas.dimensions()
+        self.page_size
     }
 
-    /// Check if atlas has been updated
+    /// Check if any atlas page has been updated
     pub fn is_dirty(&self) -> bool {
-        self.dirty
+        self.pages.values().flatten().any(|page| page.dirty)
     }
 
-    /// Mark atlas as clean (after GPU upload)
+    /// Pages of a render mode updated since they were last marked clean
+    pub fn dirty_pages(&self, mode: RenderMode) -> Vec<usize> {
+        let pages = self.pages.get(&mode).map_or(&[][..], Vec::as_slice);
+        pages.iter().enumerate().filter(|(_, page)| page.dirty).map(|(index, _)| index).collect()
+    }
+
+    /// Mark every page as clean (after GPU upload)
     pub fn mark_clean(&mut self) {
-        self.dirty = false;
+        for page in self.pages.values_mut().flatten() {
+            page.dirty = false;
+        }
     }
 
     /// Get number of cached glyphs
     pub fn glyph_count(&self) -> usize {
         self.glyphs.len()
     }
+
+    /// Page usage, glyph counts and hit rates
+    pub fn stats(&self) -> GlyphAtlasStats {
+        let mut stats = GlyphAtlasStats {
+            glyphs: self.glyphs.len(),
+            hits: self.hits,
+            misses: self.misses,
+            evictions: self.evictions,
+            ..GlyphAtlasStats::default()
+        };
+        for mode in RenderMode::ALL {
+            let Some(pages) = self.pages.get(&mode) else {
+                continue;
+            };
+            for (index, page) in pages.iter().enumerate() {
+                stats.bytes += page.atlas.data().len();
+                stats.pages.push(AtlasPageStats {
+                    mode,
+                    page: index,
+                    glyphs: page.glyphs,
+                    occupancy: page.atlas.occupancy(),
+                });
+            }
+        }
+        stats
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::renderer::font_manager::FontManager;
+
+    fn cache_with_font(width: u32, height: u32) -> GlyphCache {
+        let mut cache = GlyphCache::new(width, height);
+        cache.register_font(FontManager::new().unwrap().get_font("sans-serif"));
+        cache
+    }
+
+    fn key(character: char, size: u32) -> GlyphKey {
+        GlyphKey { character, size, font_id: 0 }
+    }
 
     #[test]
     fn test_texture_atlas_creation() {
@@ -267,14 +603,80 @@ mod tests {
         assert!(allocations.len() >= 20); // At least 5x5 grid
     }
 
+    #[test]
+    fn test_shelves_are_reused_by_best_fit() {
+        let mut atlas = TextureAtlas::new(100, 100);
+        assert_eq!(atlas.allocate(10, 30), Some((0, 0)));
+        // Far shorter than the first shelf, so it opens a shelf of its own
+        assert_eq!(atlas.allocate(10, 10), Some((0, 30)));
+        // Glyphs go back to the shelf that fits them best
+        assert_eq!(atlas.allocate(10, 28), Some((10, 0)));
+        assert_eq!(atlas.allocate(10, 9), Some((10, 30)));
+        assert_eq!(atlas.allocate(101, 5), None);
+        assert!((atlas.occupancy() - 0.0770).abs() < 0.001);
+    }
+
     #[test]
     fn test_glyph_cache_creation() {
         let cache = GlyphCache::new(512, 512);
         assert_eq!(cache.glyph_count(), 0);
         assert_eq!(cache.atlas_dimensions(), (512, 512));
         assert!(!cache.is_dirty());
+        assert_eq!(cache.page_count(RenderMode::Grayscale), 0);
     }
 
-    // Test for glyph rasterization would require actual font file
-    // This is tested in integration tests with real fonts
+    #[test]
+    fn test_render_modes_have_separate_atlases() {
+        let mut cache = cache_with_font(256, 256);
+        let gray = cache.get_or_rasterize(key('A', 20)).unwrap();
+        let subpixel = cache.get_or_rasterize_as(key('A', 20), RenderMode::Subpixel).unwrap();
+        let color = cache.get_or_rasterize_as(key('A', 20), RenderMode::Color).unwrap();
+        assert_eq!(cache.glyph_count(), 3);
+        for mode in RenderMode::ALL {
+            assert_eq!(cache.page_count(mode), 1);
+            assert_eq!(cache.page_data(mode, 0).unwrap().len(), 256 * 256 * mode.bytes_per_pixel() as usize);
+        }
+        assert_eq!(cache.glyph_bitmap(&gray).len(), (gray.width * gray.height) as usize);
+        assert_eq!(cache.glyph_bitmap(&subpixel).len(), (subpixel.width * subpixel.height * 3) as usize);
+        let bitmap = cache.glyph_bitmap(&color);
+        assert!(bitmap.chunks(4).all(|pixel| pixel[..3] == [255, 255, 255]));
+        assert!(bitmap.chunks(4).any(|pixel| pixel[3] > 0));
+
+        assert_eq!(cache.dirty_pages(RenderMode::Subpixel), vec![0]);
+        cache.mark_clean();
+        assert!(!cache.is_dirty());
+        cache.get_or_rasterize(key('A', 20)).unwrap();
+        assert!(!cache.is_dirty());
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.pages.len()), (1, 3, 3));
+    }
+
+    #[test]
+    fn test_full_pages_evict_least_recently_used() {
+        // Pages only big enough for one glyph each
+        let mut cache = cache_with_font(40, 40);
+        cache.set_max_pages(2);
+        cache.begin_frame();
+        assert_eq!(cache.get_or_rasterize(key('M', 36)).unwrap().page, 0);
+        assert_eq!(cache.get_or_rasterize(key('M', 37)).unwrap().page, 1);
+
+        // Using the first page again leaves the second used longest ago
+        cache.begin_frame();
+        cache.get_or_rasterize(key('M', 36)).unwrap();
+        assert_eq!(cache.get_or_rasterize(key('M', 38)).unwrap().page, 1);
+        assert_eq!(cache.page_count(RenderMode::Grayscale), 2);
+        assert_eq!(cache.glyph_count(), 2);
+        let stats = cache.stats();
+        assert_eq!((stats.evictions, stats.misses), (1, 3));
+
+        // Both pages hold glyphs this frame needs, so there is no room
+        assert!(cache.get_or_rasterize(key('M', 37)).is_none());
+        assert_eq!(cache.stats().evictions, 1);
+        cache.begin_frame();
+        assert_eq!(cache.get_or_rasterize(key('M', 37)).unwrap().page, 0);
+
+        cache.trim();
+        assert_eq!(cache.page_count(RenderMode::Grayscale), 1);
+        assert_eq!(cache.glyph_count(), 1);
+    }
 }
@@ -1,6 +1,9 @@
 use image::{ImageError, ImageFormat};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use url::Url;
+use crate::layout::replaced::{ImageSizes, IntrinsicSize};
+use crate::net::CachedResource;
 
 /// Decoded image data ready for GPU upload
 #[derive(Debug, Clone)]
@@ -42,13 +45,22 @@ impl DecodedImage {
 }
 
 /// Cache for decoded images
+///
+/// It also tracks images that are being fetched and those that could not be
+/// fetched or decoded, which are drawn as placeholders.
 pub struct ImageCache {
-    /// Cached images by URL
-    images: HashMap<Url, DecodedImage>,
+    /// Cached images by URL, shared with the GPU process
+    images: HashMap<Url, Arc<DecodedImage>>,
+    /// Images requested and not yet loaded
+    loading: HashSet<Url>,
+    /// Images that failed to load or decode, not requested again
+    failed: HashSet<Url>,
     /// Maximum cache size in bytes
     max_size: usize,
     /// Current cache size in bytes
     current_size: usize,
+    /// Natural sizes of every image decoded, kept after eviction for layout
+    sizes: ImageSizes,
 }
 
 impl ImageCache {
@@ -56,8 +68,11 @@ impl ImageCache {
     pub fn new(max_size: usize) -> Self {
         Self {
             images: HashMap::new(),
+            loading: HashSet::new(),
+            failed: HashSet::new(),
             max_size,
             current_size: 0,
+            sizes: ImageSizes::new(),
         }
     }
     
@@ -66,16 +81,69 @@ impl ImageCache {
         Self::new(100 * 1024 * 1024) // 100 MB
     }
     
+    /// Note that an image is needed; true if it should be fetched
+    ///
+    /// Images that are decoded, being fetched or failed are not fetched again.
+    pub fn request(&mut self, url: &Url) -> bool {
+        if self.images.contains_key(url) || self.loading.contains(url) || self.failed.contains(url) {
+            return false;
+        }
+        self.loading.insert(url.clone());
+        true
+    }
+    
+    /// Is the image requested and not yet loaded
+    pub fn is_loading(&self, url: &Url) -> bool {
+        self.loading.contains(url)
+    }
+    
+    /// Did the image fail to load or decode
+    pub fn has_failed(&self, url: &Url) -> bool {
+        self.failed.contains(url)
+    }
+    
+    /// Note that an image could not be fetched
+    pub fn fail(&mut self, url: &Url) {
+        self.loading.remove(url);
+        self.failed.insert(url.clone());
+    }
+    
+    /// Decode a PNG, JPEG, GIF or WebP image fetched by the resource loader
+    pub fn load_resource(&mut self, resource: &CachedResource) -> Result<&DecodedImage, ImageError> {
+        self.loading.remove(&resource.url);
+        if let Err(e) = DecodedImage::from_bytes(resource.url.clone(), &resource.data).map(|image| self.insert(image)) {
+            self.failed.insert(resource.url.clone());
+            return Err(e);
+        }
+        Ok(&self.images[&resource.url])
+    }
+    
     /// Load and decode an image from bytes
     pub fn load_from_bytes(&mut self, url: Url, bytes: &[u8]) -> Result<&DecodedImage, ImageError> {
         // Check if already cached
         if self.images.contains_key(&url) {
-            return Ok(self.images.get(&url).unwrap());
+            return Ok(&self.images[&url]);
         }
         
         // Decode the image
         let decoded = DecodedImage::from_bytes(url.clone(), bytes)?;
+        self.insert(decoded);
+        Ok(&self.images[&url])
+    }
+    
+    /// Add a decoded image, evicting others to make room for it
+    pub fn insert(&mut self, decoded: DecodedImage) {
+        self.insert_shared(Arc::new(decoded));
+    }
+    
+    /// Add a decoded image another cache holds too
+    pub fn insert_shared(&mut self, decoded: Arc<DecodedImage>) {
+        let url = decoded.url.clone();
         let size = decoded.byte_size();
+        self.sizes.record(&url, IntrinsicSize { width: decoded.width, height: decoded.height });
+        if let Some(replaced) = self.images.remove(&url) {
+            self.current_size = self.current_size.saturating_sub(replaced.byte_size());
+        }
         
         // Evict old images if needed
         while self.current_size + size > self.max_size && !self.images.is_empty() {
@@ -84,21 +152,34 @@ impl ImageCache {
         
         // Don't cache if image is larger than max cache size
         if size > self.max_size {
-            // Still decode and store temporarily
-            self.images.insert(url.clone(), decoded);
-            return Ok(self.images.get(&url).unwrap());
+            // Still store it temporarily
+            self.images.insert(url, decoded);
+            return;
         }
         
         // Add to cache
         self.current_size += size;
-        self.images.insert(url.clone(), decoded);
-        
-        Ok(self.images.get(&url).unwrap())
+        self.images.insert(url, decoded);
     }
     
     /// Get a cached image
     pub fn get(&self, url: &Url) -> Option<&DecodedImage> {
-        self.images.get(url)
+        self.images.get(url).map(|image| &**image)
+    }
+    
+    /// Get a cached image to hand to another thread
+    pub fn shared(&self, url: &Url) -> Option<Arc<DecodedImage>> {
+        self.images.get(url).cloned()
+    }
+    
+    /// Evict images until the cache holds at most `max_bytes`
+    ///
+    /// Evicted images are fetched again, from the HTTP cache if it kept
+    /// them, when a page next needs them.
+    pub fn evict_to(&mut self, max_bytes: usize) {
+        while self.current_size > max_bytes && !self.images.is_empty() {
+            self.evict_oldest();
+        }
     }
     
     /// Evict the oldest image (simple FIFO for now)
@@ -106,7 +187,7 @@ impl ImageCache {
         if let Some((url, _)) = self.images.iter().next() {
             let url = url.clone();
             if let Some(removed) = self.images.remove(&url) {
-                self.current_size -= removed.byte_size();
+                self.current_size = self.current_size.saturating_sub(removed.byte_size());
             }
         }
     }
@@ -114,7 +195,15 @@ impl ImageCache {
     /// Clear the cache
     pub fn clear(&mut self) {
         self.images.clear();
+        self.loading.clear();
+        self.failed.clear();
         self.current_size = 0;
+        self.sizes.clear();
+    }
+
+    /// Natural sizes of the images decoded, shared with layout
+    pub fn sizes(&self) -> &ImageSizes {
+        &self.sizes
     }
     
     /// Get current cache size in bytes
@@ -128,6 +217,12 @@ impl ImageCache {
     }
 }
 
+impl Default for ImageCache {
+    fn default() -> Self {
+        Self::with_default_size()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -212,6 +307,43 @@ mod tests {
         assert_eq!(cache.size(), 0);
     }
     
+    #[test]
+    fn test_image_cache_load_resource() {
+        let mut cache = ImageCache::new(1024 * 1024);
+        let resource = |path: &str, data: Vec<u8>| CachedResource {
+            url: Url::parse("http://example.com/").unwrap().join(path).unwrap(),
+            partition: None,
+            resource_type: crate::net::ResourceType::Image,
+            content_type: "image/png".to_string(),
+            data,
+            last_accessed: 0,
+            etag: None,
+            last_modified: None,
+            freshness: Default::default(),
+        };
+        let image = resource("a.png", create_test_png());
+        let broken = resource("b.png", b"not an image".to_vec());
+        
+        assert!(cache.request(&image.url));
+        assert!(!cache.request(&image.url));
+        assert!(cache.is_loading(&image.url));
+        assert_eq!(cache.load_resource(&image).unwrap().width, 1);
+        assert!(!cache.is_loading(&image.url));
+        assert_eq!(cache.sizes().get(&image.url), Some(IntrinsicSize { width: 1, height: 1 }));
+        assert!(!cache.request(&image.url));
+        
+        assert!(cache.request(&broken.url));
+        assert!(cache.load_resource(&broken).is_err());
+        assert!(cache.has_failed(&broken.url));
+        assert!(!cache.request(&broken.url));
+        
+        // Evicted images are fetched again, and their sizes stay known
+        cache.evict_to(0);
+        assert_eq!((cache.count(), cache.size()), (0, 0));
+        assert!(cache.request(&image.url));
+        assert!(cache.sizes().get(&image.url).is_some());
+    }
+    
     #[test]
     fn test_image_cache_eviction() {
         // Very small cache - only 100 bytes
@@ -1,6 +1,7 @@
 use wgpu::{Device, Queue, RenderPass, RenderPipeline, Buffer, Texture, Sampler, BindGroup};
 use bytemuck::{Pod, Zeroable};
 use std::collections::HashMap;
+use std::ops::Range;
 use url::Url;
 use crate::layout::Rect;
 use super::image_cache::{ImageCache, DecodedImage};
@@ -49,7 +50,7 @@ pub struct ImagePainter {
     sampler: Sampler,
     gpu_images: HashMap<Url, GpuImage>,
     max_images: usize,
-    current_commands: Vec<(Url, usize)>, // (URL, vertex_offset)
+    current_commands: Vec<(Url, usize)>, // (URL, command index)
 }
 
 impl ImagePainter {
@@ -249,11 +250,14 @@ impl ImagePainter {
             return 0;
         }
 
+        // Textures of images evicted from the cache are released
+        self.gpu_images.retain(|url, _| image_cache.get(url).is_some());
+
         let mut vertices = Vec::new();
         let mut indices = Vec::new();
         self.current_commands.clear();
 
-        for cmd in commands.iter().take(self.max_images) {
+        for (index, cmd) in commands.iter().enumerate().take(self.max_images) {
             // Get decoded image from cache
             let decoded = match image_cache.get(&cmd.url) {
                 Some(img) => img,
@@ -293,7 +297,7 @@ impl ImagePainter {
                 base_index + 3,
             ]);
 
-            self.current_commands.push((cmd.url.clone(), (vertices.len() - 4) / 4));
+            self.current_commands.push((cmd.url.clone(), index));
         }
 
         if !vertices.is_empty() {
@@ -306,7 +310,14 @@ impl ImagePainter {
 
     /// Render the prepared images
     pub fn render<'rpass>(&'rpass self, render_pass: &mut RenderPass<'rpass>) {
-        if self.current_commands.is_empty() {
+        self.render_range(render_pass, 0..usize::MAX);
+    }
+
+    /// Render the prepared images of a range of the commands given to `prepare`
+    ///
+    /// Images that were not decoded yet are skipped.
+    pub fn render_range<'rpass>(&'rpass self, render_pass: &mut RenderPass<'rpass>, commands: Range<usize>) {
+        if !self.current_commands.iter().any(|(_, index)| commands.contains(index)) {
             return;
         }
 
@@ -315,7 +326,10 @@ impl ImagePainter {
         render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
 
         // Render each image with its own bind group
-        for (i, (url, _vertex_offset)) in self.current_commands.iter().enumerate() {
+        for (i, (url, index)) in self.current_commands.iter().enumerate() {
+            if !commands.contains(index) {
+                continue;
+            }
             if let Some(gpu_image) = self.gpu_images.get(url) {
                 render_pass.set_bind_group(0, &gpu_image.bind_group, &[]);
                 let index_start = (i * 6) as u32;
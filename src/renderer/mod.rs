@@ -2,10 +2,14 @@ mod painter;
 mod border_painter;
 mod text_painter;
 mod image_painter;
+mod canvas_painter;
 pub mod font_manager;
 pub mod glyph_cache;
+pub mod shaping;
+pub mod decoration;
 pub mod text_renderer;
 pub mod image_cache;
+pub mod software;
 
 use wgpu::{
     Adapter, Device, Instance, Queue, Surface, SurfaceConfiguration, TextureFormat,
@@ -16,10 +20,18 @@ use std::sync::Arc;
 
 pub use painter::RectPainter;
 pub use border_painter::BorderPainter;
-pub use text_painter::TextPainter;
-pub use image_painter::ImagePainter;
+pub use text_painter::{TextCommand, TextPainter};
+pub use image_painter::{ImageCommand, ImagePainter};
+pub use canvas_painter::{CanvasPainter, GpuCanvas};
+use crate::canvas::CanvasRenderingContext2D;
 use crate::css::Color;
+use crate::display::DisplayCommand;
 use crate::layout::Rect;
+use crate::media::VideoFrame;
+use border_painter::border_edges;
+use image_cache::ImageCache;
+use std::ops::Range;
+use text_renderer::TextRenderer;
 
 /// GPU-accelerated renderer using wgpu
 pub struct Renderer<'window> {
@@ -28,8 +40,35 @@ pub struct Renderer<'window> {
     queue: Queue,
     config: SurfaceConfiguration,
     size: (u32, u32),
+    /// Device pixels per CSS pixel
+    scale_factor: f32,
     rect_painter: RectPainter,
     border_painter: BorderPainter,
+    /// Shapes text and keeps its glyphs in the atlas the text painter samples
+    text_renderer: TextRenderer,
+    text_painter: TextPainter,
+    image_painter: ImagePainter,
+    /// Decoded images the display list's image commands draw
+    image_cache: ImageCache,
+    /// Created for the first canvas
+    canvas_painter: Option<CanvasPainter>,
+}
+
+impl Renderer<'static> {
+    /// Initialize a renderer that shares ownership of its window
+    ///
+    /// Unlike `new`, the renderer does not borrow the window, so it can be
+    /// stored alongside it (one renderer per window in a `WindowManager`).
+    pub async fn for_window(window: Arc<Window>) -> Result<Self, RendererError> {
+        let size = window.inner_size();
+        let scale_factor = window.scale_factor();
+        let instance = Self::create_instance();
+        let surface = instance
+            .create_surface(window)
+            .map_err(|e| RendererError::Initialization(format!("Failed to create surface: {}", e)))?;
+
+        Self::with_surface(&instance, surface, size, scale_factor).await
+    }
 }
 
 impl<'window> Renderer<'window> {
@@ -40,10 +79,7 @@ impl<'window> Renderer<'window> {
         let size = window.inner_size();
         
         // Create wgpu instance with default backends
-        let instance = Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
-            ..Default::default()
-        });
+        let instance = Self::create_instance();
 
         // Create surface for the window
         // SAFETY: The window must live as long as the surface
@@ -51,8 +87,25 @@ impl<'window> Renderer<'window> {
             .create_surface(window)
             .map_err(|e| RendererError::Initialization(format!("Failed to create surface: {}", e)))?;
 
+        Self::with_surface(&instance, surface, size, window.scale_factor()).await
+    }
+
+    fn create_instance() -> Instance {
+        Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        })
+    }
+
+    /// Set up the adapter, device and painters for a created surface
+    async fn with_surface(
+        instance: &Instance,
+        surface: Surface<'window>,
+        size: winit::dpi::PhysicalSize<u32>,
+        scale_factor: f64,
+    ) -> Result<Self, RendererError> {
         // Request an adapter (represents a physical GPU)
-        let adapter = Self::request_adapter(&instance, &surface).await?;
+        let adapter = Self::request_adapter(instance, &surface).await?;
 
         // Request a device and queue (logical GPU interface)
         let (device, queue) = Self::request_device(&adapter).await?;
@@ -82,6 +135,10 @@ impl<'window> Renderer<'window> {
         // Create painters
         let rect_painter = RectPainter::new(&device, surface_format);
         let border_painter = BorderPainter::new(&device, surface_format);
+        let text_painter = TextPainter::new(&device, surface_format);
+        let image_painter = ImagePainter::new(&device, surface_format);
+        let mut text_renderer = TextRenderer::new().map_err(RendererError::Initialization)?;
+        text_renderer.set_scale_factor(scale_factor as f32);
 
         Ok(Self {
             surface,
@@ -89,8 +146,14 @@ impl<'window> Renderer<'window> {
             queue,
             config,
             size: (size.width, size.height),
+            scale_factor: scale_factor as f32,
             rect_painter,
             border_painter,
+            text_renderer,
+            text_painter,
+            image_painter,
+            image_cache: ImageCache::with_default_size(),
+            canvas_painter: None,
         })
     }
 
@@ -143,6 +206,19 @@ impl<'window> Renderer<'window> {
         self.size
     }
 
+    /// Set the device pixel ratio, e.g. after the window moved to another monitor
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        if scale_factor > 0.0 {
+            self.scale_factor = scale_factor as f32;
+            self.text_renderer.set_scale_factor(self.scale_factor);
+        }
+    }
+
+    /// Device pixels per CSS pixel
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+
     /// Get the surface format
     pub fn format(&self) -> TextureFormat {
         self.config.format
@@ -158,6 +234,22 @@ impl<'window> Renderer<'window> {
         &self.queue
     }
 
+    /// Bytes of glyph atlas pages
+    pub fn glyph_atlas_bytes(&self) -> usize {
+        self.text_renderer.atlas_stats().bytes
+    }
+
+    /// Free glyph atlas pages past the first, under memory pressure
+    pub fn trim_glyph_atlas(&mut self) {
+        self.text_renderer.trim_atlas();
+        self.text_painter.unbind_atlas_pages(self.text_renderer.atlas_textures().len());
+    }
+
+    /// Images display lists draw, to decode fetched images into
+    pub fn image_cache_mut(&mut self) -> &mut ImageCache {
+        &mut self.image_cache
+    }
+
     /// Begin a new frame
     /// 
     /// Returns the current surface texture to render to
@@ -227,9 +319,12 @@ impl<'window> Renderer<'window> {
     }
 
     /// Render rectangles from a display list
+    ///
+    /// Rectangles are in CSS pixels and scaled to the surface's device pixels.
     pub fn render_rects(&mut self, rects: &[(Rect, Color)]) -> Result<(), RendererError> {
         // Prepare rectangle data
-        self.rect_painter.prepare(&self.device, &self.queue, rects, self.size);
+        let rects = scale_rects(rects, self.scale_factor);
+        self.rect_painter.prepare(&self.device, &self.queue, &rects, self.size);
 
         // Render
         self.render(|_device, _queue, view, encoder| {
@@ -258,14 +353,64 @@ impl<'window> Renderer<'window> {
     }
 
     /// Render rectangles and borders together
+    ///
+    /// Geometry is in CSS pixels and scaled to the surface's device pixels.
     pub fn render_rects_and_borders(
         &mut self,
         rects: &[(Rect, Color)],
         borders: &[(Rect, Color, (f32, f32, f32, f32))],
+    ) -> Result<(), RendererError> {
+        self.render_with_canvases(rects, borders, &[])
+    }
+
+    /// Create a GPU backing store for a canvas of the given size in pixels
+    pub fn create_canvas(&mut self, width: u32, height: u32) -> GpuCanvas {
+        let format = self.config.format;
+        let device = &self.device;
+        self.canvas_painter
+            .get_or_insert_with(|| CanvasPainter::new(device, format))
+            .create_canvas(device, width, height)
+    }
+
+    /// Bring a canvas's backing store up to date with its context
+    ///
+    /// Only what an accelerated context drew since the last paint is drawn
+    /// again; other contexts are uploaded whole.
+    pub fn paint_canvas(&mut self, canvas: &mut GpuCanvas, context: &mut CanvasRenderingContext2D) {
+        if let Some(painter) = &self.canvas_painter {
+            painter.paint_context(&self.device, &self.queue, canvas, context);
+        }
+    }
+
+    /// Show a video's current frame in its backing store
+    pub fn paint_video_frame(&mut self, canvas: &mut GpuCanvas, frame: &VideoFrame) {
+        if let Some(painter) = &self.canvas_painter {
+            painter.paint_frame(&self.device, &self.queue, canvas, frame);
+        }
+    }
+
+    /// Render rectangles and borders, compositing canvases at their boxes between the two
+    ///
+    /// Geometry is in CSS pixels and scaled to the surface's device pixels.
+    pub fn render_with_canvases(
+        &mut self,
+        rects: &[(Rect, Color)],
+        borders: &[Border],
+        canvases: &[(&GpuCanvas, Rect)],
     ) -> Result<(), RendererError> {
         // Prepare data
-        self.rect_painter.prepare(&self.device, &self.queue, rects, self.size);
-        self.border_painter.prepare(&self.device, &self.queue, borders, self.size);
+        let rects = scale_rects(rects, self.scale_factor);
+        let borders = scale_borders(borders, self.scale_factor);
+        self.rect_painter.prepare(&self.device, &self.queue, &rects, self.size);
+        self.border_painter.prepare(&self.device, &self.queue, &borders, self.size);
+        let layers: Vec<_> = canvases
+            .iter()
+            .map(|(canvas, rect)| (*canvas, to_device_rect(rect, self.scale_factor)))
+            .collect();
+        if let Some(painter) = &mut self.canvas_painter {
+            painter.prepare_composite(&self.device, &layers, self.size);
+        }
+        let canvases: Vec<&GpuCanvas> = canvases.iter().map(|(canvas, _)| *canvas).collect();
 
         // Render both in same pass
         self.render(|_device, _queue, view, encoder| {
@@ -289,11 +434,278 @@ impl<'window> Renderer<'window> {
                 occlusion_query_set: None,
             });
 
-            // Render backgrounds first, then borders on top
+            // Render backgrounds first, then canvases, then borders on top
             self.rect_painter.render(&mut render_pass);
+            if let Some(painter) = &self.canvas_painter {
+                painter.render_composite(&mut render_pass, &canvases);
+            }
             self.border_painter.render(&mut render_pass);
         })
     }
+
+    /// Render a display list, such as `display::build_display_list` makes
+    ///
+    /// Every kind of command is drawn in one pass, in the list's order, so
+    /// later commands paint over earlier ones whichever painter draws them.
+    /// Images are drawn once they are decoded into `image_cache_mut`, and
+    /// as placeholders until then.
+    pub fn render_display_list(&mut self, list: &[DisplayCommand]) -> Result<(), RendererError> {
+        let images = std::mem::take(&mut self.image_cache);
+        let result = self.render_display_list_with_canvases(list, &[], &[], &images);
+        self.image_cache = images;
+        result
+    }
+
+    /// Render a display list, then canvases at their boxes, then an overlay display list
+    ///
+    /// Images are drawn from `images`, uploaded to the GPU the first time
+    /// they are drawn.
+    pub fn render_display_list_with_canvases(
+        &mut self,
+        list: &[DisplayCommand],
+        canvases: &[(&GpuCanvas, Rect)],
+        overlay: &[DisplayCommand],
+        images: &ImageCache,
+    ) -> Result<(), RendererError> {
+        let mut plan = PaintPlan::default();
+        list.iter().for_each(|command| plan.push(command, images));
+        plan.end_clips();
+        if !canvases.is_empty() {
+            plan.steps.push(PaintStep::Canvases);
+        }
+        overlay.iter().for_each(|command| plan.push(command, images));
+
+        // Prepare each painter's share of the list
+        let rects = scale_rects(&plan.rects, self.scale_factor);
+        self.rect_painter.prepare(&self.device, &self.queue, &rects, self.size);
+        // Glyphs missing from the atlas are rasterized and uploaded here
+        let text_runs = plan.text_runs();
+        self.text_painter
+            .prepare_runs(&self.device, &self.queue, &mut self.text_renderer, &plan.texts, &text_runs, self.size);
+        let plan_images: Vec<ImageCommand> = plan
+            .images
+            .iter()
+            .map(|image| ImageCommand { url: image.url.clone(), rect: to_device_rect(&image.rect, self.scale_factor) })
+            .collect();
+        self.image_painter.prepare(&self.device, &self.queue, images, &plan_images, self.size);
+        let layers: Vec<_> = canvases
+            .iter()
+            .map(|(canvas, rect)| (*canvas, to_device_rect(rect, self.scale_factor)))
+            .collect();
+        if let Some(painter) = &mut self.canvas_painter {
+            painter.prepare_composite(&self.device, &layers, self.size);
+        }
+        let canvases: Vec<&GpuCanvas> = canvases.iter().map(|(canvas, _)| *canvas).collect();
+        let (scale_factor, size) = (self.scale_factor, self.size);
+
+        self.render(|_device, _queue, view, encoder| {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Display List Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color {
+                            r: 1.0,
+                            g: 1.0,
+                            b: 1.0,
+                            a: 1.0,
+                        }),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            // Each run of commands with its painter, in painting order
+            let mut text_run = 0;
+            for step in &plan.steps {
+                match step {
+                    PaintStep::Rects(rects) => self.rect_painter.render_range(&mut render_pass, rects.clone()),
+                    PaintStep::Text(_) => {
+                        self.text_painter.render_run(&mut render_pass, text_run);
+                        text_run += 1;
+                    }
+                    PaintStep::Images(images) => self.image_painter.render_range(&mut render_pass, images.clone()),
+                    PaintStep::Canvases => {
+                        if let Some(painter) = &self.canvas_painter {
+                            painter.render_composite(&mut render_pass, &canvases);
+                        }
+                    }
+                    PaintStep::Clip(clip) => {
+                        let (x, y, width, height) = scissor_rect(clip.as_ref(), scale_factor, size);
+                        render_pass.set_scissor_rect(x, y, width, height);
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// Colours of the box drawn for an image that is loading or failed to load
+const PLACEHOLDER_FILL: Color = Color { r: 240, g: 240, b: 240, a: 255 };
+const PLACEHOLDER_BORDER: Color = Color { r: 200, g: 200, b: 200, a: 255 };
+
+/// A display list split among the painters, with the order to draw it in
+#[derive(Default)]
+struct PaintPlan {
+    /// Backgrounds, highlights and border edges, in CSS pixels
+    rects: Vec<(Rect, Color)>,
+    texts: Vec<TextCommand>,
+    images: Vec<ImageCommand>,
+    /// Runs of consecutive commands drawn by the same painter
+    steps: Vec<PaintStep>,
+    /// Clips in force at the end of the plan, innermost last
+    clips: Vec<Rect>,
+}
+
+/// A run of commands drawn by one painter, by their indices in its share of the plan
+#[derive(Debug, Clone, PartialEq)]
+enum PaintStep {
+    Rects(Range<usize>),
+    Text(Range<usize>),
+    Images(Range<usize>),
+    /// Canvases composited at their boxes
+    Canvases,
+    /// Clip what follows to a rectangle in CSS pixels, or stop clipping
+    Clip(Option<Rect>),
+}
+
+impl PaintPlan {
+    /// Add a command after those already planned
+    ///
+    /// Images not decoded in `images` get a placeholder instead.
+    fn push(&mut self, command: &DisplayCommand, images: &ImageCache) {
+        match command {
+            DisplayCommand::SolidRect { color, rect } | DisplayCommand::Highlight { color, rect } => {
+                let start = self.rects.len();
+                self.rects.push((*rect, *color));
+                self.extend(PaintStep::Rects, start..self.rects.len());
+            }
+            DisplayCommand::Border { color, rect, widths } => {
+                let start = self.rects.len();
+                self.rects.extend(border_edges(rect, *widths).into_iter().map(|edge| (edge, *color)));
+                self.extend(PaintStep::Rects, start..self.rects.len());
+            }
+            DisplayCommand::Text { text, rect, color, font_family, font_size, style } => {
+                let start = self.texts.len();
+                self.texts.push(TextCommand {
+                    text: text.clone(),
+                    rect: *rect,
+                    color: *color,
+                    font_family: font_family.clone(),
+                    font_size: *font_size,
+                    style: style.clone(),
+                });
+                self.extend(PaintStep::Text, start..self.texts.len());
+            }
+            DisplayCommand::Image { rect, url } if images.get(url).is_none() => {
+                self.push(&DisplayCommand::SolidRect { color: PLACEHOLDER_FILL, rect: *rect }, images);
+                let widths = (1.0, 1.0, 1.0, 1.0);
+                self.push(&DisplayCommand::Border { color: PLACEHOLDER_BORDER, rect: *rect, widths }, images);
+            }
+            DisplayCommand::Image { url, rect } => {
+                let start = self.images.len();
+                self.images.push(ImageCommand { url: url.clone(), rect: *rect });
+                self.extend(PaintStep::Images, start..self.images.len());
+            }
+            DisplayCommand::PushClip { rect } => {
+                let clip = self.clips.last().map_or(*rect, |outer| outer.intersection(rect));
+                self.clips.push(clip);
+                self.steps.push(PaintStep::Clip(Some(clip)));
+            }
+            DisplayCommand::PopClip { .. } => {
+                self.clips.pop();
+                self.steps.push(PaintStep::Clip(self.clips.last().copied()));
+            }
+            // Scrolling has already placed fixed boxes
+            DisplayCommand::PushFixed { .. } | DisplayCommand::PopFixed { .. } => {}
+        }
+    }
+
+    /// Stop clipping, for commands that follow a list whose clips were left open
+    fn end_clips(&mut self) {
+        if !self.clips.is_empty() {
+            self.clips.clear();
+            self.steps.push(PaintStep::Clip(None));
+        }
+    }
+
+    /// Add items to the last step if the same painter draws it, or start a new step
+    fn extend(&mut self, step: fn(Range<usize>) -> PaintStep, items: Range<usize>) {
+        if items.is_empty() {
+            return;
+        }
+        let next = step(items.clone());
+        let same_painter = |last: &&mut PaintStep| std::mem::discriminant(&**last) == std::mem::discriminant(&next);
+        if let Some(PaintStep::Rects(run) | PaintStep::Text(run) | PaintStep::Images(run)) =
+            self.steps.last_mut().filter(same_painter)
+        {
+            run.end = items.end;
+            return;
+        }
+        self.steps.push(next);
+    }
+
+    /// The text commands of each text step, in painting order
+    fn text_runs(&self) -> Vec<Range<usize>> {
+        self.steps
+            .iter()
+            .filter_map(|step| match step {
+                PaintStep::Text(run) => Some(run.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Convert a rectangle from CSS pixels to device pixels
+pub fn to_device_rect(rect: &Rect, scale_factor: f32) -> Rect {
+    Rect {
+        x: rect.x * scale_factor,
+        y: rect.y * scale_factor,
+        width: rect.width * scale_factor,
+        height: rect.height * scale_factor,
+    }
+}
+
+/// A clip in CSS pixels as a scissor rectangle on a surface of `size`
+/// device pixels: x, y, width and height
+fn scissor_rect(clip: Option<&Rect>, scale_factor: f32, size: (u32, u32)) -> (u32, u32, u32, u32) {
+    let Some(clip) = clip else {
+        return (0, 0, size.0, size.1);
+    };
+    let rect = to_device_rect(clip, scale_factor);
+    let x0 = rect.x.round().clamp(0.0, size.0 as f32) as u32;
+    let y0 = rect.y.round().clamp(0.0, size.1 as f32) as u32;
+    let x1 = (rect.x + rect.width).round().clamp(0.0, size.0 as f32) as u32;
+    let y1 = (rect.y + rect.height).round().clamp(0.0, size.1 as f32) as u32;
+    (x0, y0, x1.saturating_sub(x0), y1.saturating_sub(y0))
+}
+
+fn scale_rects(rects: &[(Rect, Color)], scale_factor: f32) -> Vec<(Rect, Color)> {
+    rects
+        .iter()
+        .map(|(rect, color)| (to_device_rect(rect, scale_factor), *color))
+        .collect()
+}
+
+/// A border: its box, colour and (top, right, bottom, left) widths
+type Border = (Rect, Color, (f32, f32, f32, f32));
+
+fn scale_borders(borders: &[Border], scale_factor: f32) -> Vec<Border> {
+    borders
+        .iter()
+        .map(|(rect, color, (top, right, bottom, left))| {
+            (
+                to_device_rect(rect, scale_factor),
+                *color,
+                (top * scale_factor, right * scale_factor, bottom * scale_factor, left * scale_factor),
+            )
+        })
+        .collect()
 }
 
 /// Renderer errors
@@ -315,3 +727,113 @@ impl std::fmt::Display for RendererError {
 }
 
 impl std::error::Error for RendererError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_css_pixels_scale_to_device_pixels() {
+        let rect = Rect { x: 10.0, y: 20.0, width: 100.0, height: 50.5 };
+        let device = to_device_rect(&rect, 2.0);
+        assert_eq!(device.x, 20.0);
+        assert_eq!(device.y, 40.0);
+        assert_eq!(device.width, 200.0);
+        assert_eq!(device.height, 101.0);
+
+        let black = Color { r: 0, g: 0, b: 0, a: 255 };
+        let borders = scale_borders(&[(rect, black, (1.0, 2.0, 1.0, 2.0))], 1.5);
+        assert_eq!(borders[0].2, (1.5, 3.0, 1.5, 3.0));
+    }
+
+    #[test]
+    fn test_paint_plan_keeps_painting_order() {
+        let rect = Rect { x: 0.0, y: 0.0, width: 40.0, height: 16.0 };
+        let red = Color { r: 255, g: 0, b: 0, a: 255 };
+        let text = |text: &str| DisplayCommand::Text {
+            text: text.to_string(),
+            rect,
+            color: red,
+            font_family: "serif".to_string(),
+            font_size: 16.0,
+            style: Default::default(),
+        };
+        let url = url::Url::parse("https://example.com/a.png").unwrap();
+        let mut images = ImageCache::default();
+        let pixel = vec![255; 4];
+        let format = image::ImageFormat::Png;
+        images.insert(image_cache::DecodedImage { url: url.clone(), width: 1, height: 1, data: pixel, format });
+        let image = DisplayCommand::Image { url, rect };
+        let list = [
+            DisplayCommand::SolidRect { color: red, rect },
+            DisplayCommand::Border { color: red, rect, widths: (1.0, 1.0, 1.0, 1.0) },
+            text("one"),
+            text("two"),
+            image,
+            DisplayCommand::Highlight { color: red, rect },
+            text("three"),
+        ];
+
+        let mut plan = PaintPlan::default();
+        list.iter().for_each(|command| plan.push(command, &images));
+        assert_eq!(
+            plan.steps,
+            vec![
+                PaintStep::Rects(0..5),
+                PaintStep::Text(0..2),
+                PaintStep::Images(0..1),
+                PaintStep::Rects(5..6),
+                PaintStep::Text(2..3),
+            ]
+        );
+        assert_eq!(plan.text_runs(), vec![0..2, 2..3]);
+        assert_eq!(plan.texts[2].text, "three");
+        assert_eq!(plan.texts[2].color, red);
+    }
+
+    #[test]
+    fn test_image_placeholder_until_decoded() {
+        let rect = Rect { x: 10.0, y: 10.0, width: 40.0, height: 30.0 };
+        let url = url::Url::parse("https://example.com/loading.png").unwrap();
+
+        let mut plan = PaintPlan::default();
+        plan.push(&DisplayCommand::Image { url, rect }, &ImageCache::default());
+        assert!(plan.images.is_empty());
+        assert_eq!(plan.steps, vec![PaintStep::Rects(0..5)]);
+        assert_eq!(plan.rects[0], (rect, PLACEHOLDER_FILL));
+        assert!(plan.rects[1..].iter().all(|(_, color)| *color == PLACEHOLDER_BORDER));
+    }
+
+    #[test]
+    fn test_clips_split_runs_and_nest() {
+        let red = Color { r: 255, g: 0, b: 0, a: 255 };
+        let outer = Rect { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+        let inner = Rect { x: 50.0, y: 50.0, width: 100.0, height: 100.0 };
+        let list = [
+            DisplayCommand::SolidRect { color: red, rect: outer },
+            DisplayCommand::PushClip { rect: outer },
+            DisplayCommand::SolidRect { color: red, rect: outer },
+            DisplayCommand::PushClip { rect: inner },
+            DisplayCommand::PopClip { rect: inner },
+            DisplayCommand::PopClip { rect: outer },
+        ];
+
+        let mut plan = PaintPlan::default();
+        list.iter().for_each(|command| plan.push(command, &ImageCache::default()));
+        let both = Rect { x: 50.0, y: 50.0, width: 50.0, height: 50.0 };
+        assert_eq!(
+            plan.steps,
+            vec![
+                PaintStep::Rects(0..1),
+                PaintStep::Clip(Some(outer)),
+                PaintStep::Rects(1..2),
+                PaintStep::Clip(Some(both)),
+                PaintStep::Clip(Some(outer)),
+                PaintStep::Clip(None),
+            ]
+        );
+
+        assert_eq!(scissor_rect(Some(&both), 2.0, (150, 150)), (100, 100, 50, 50));
+        assert_eq!(scissor_rect(None, 2.0, (150, 150)), (0, 0, 150, 150));
+    }
+}
@@ -1,3 +1,5 @@
+use std::ops::Range;
+
 use wgpu::{Device, Queue, RenderPass, RenderPipeline, Buffer};
 use bytemuck::{Pod, Zeroable};
 use crate::css::Color;
@@ -87,8 +89,8 @@ impl RectPainter {
             multiview: None,
         });
 
-        // Create buffers (preallocate for 1000 rectangles)
-        let max_rects = 1000;
+        // Create buffers (preallocate for 10000 rectangles, border edges included)
+        let max_rects = 10000;
         let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Rectangle Vertex Buffer"),
             size: (max_rects * 4 * std::mem::size_of::<Vertex>()) as u64,
@@ -180,6 +182,19 @@ impl RectPainter {
         render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
         render_pass.draw_indexed(0..(self.rect_count * 6) as u32, 0, 0..1);
     }
+
+    /// Render a range of the prepared rectangles, by their order in `prepare`
+    pub fn render_range<'rpass>(&'rpass self, render_pass: &mut RenderPass<'rpass>, rects: Range<usize>) {
+        let end = rects.end.min(self.rect_count);
+        if rects.start >= end {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed((rects.start * 6) as u32..(end * 6) as u32, 0, 0..1);
+    }
 }
 
 #[cfg(test)]
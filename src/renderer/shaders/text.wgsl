@@ -32,8 +32,9 @@ fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
     let dist = textureSample(glyph_atlas, glyph_sampler, input.tex_coords).r;
     
     // For standard alpha rendering (not SDF yet, will upgrade later)
-    // Just use the sampled value as alpha
-    let alpha = dist;
+    // Just use the sampled value as alpha; quads with negative texture
+    // coordinates are solid, for decoration lines
+    let alpha = select(dist, 1.0, input.tex_coords.x < 0.0);
     
     // Apply text color with alpha
     return vec4<f32>(input.color.rgb, input.color.a * alpha);
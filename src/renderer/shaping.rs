@@ -0,0 +1,227 @@
+// Text shaping - bidi reordering and glyph selection for runs of text
+//
+// Text is split into runs of one direction with the Unicode bidi algorithm
+// and the runs are put in visual order. Each run is shaped with rustybuzz,
+// so ligatures, kerning, combining marks and joining scripts get the glyphs
+// and positions the font asks for rather than one glyph per character.
+
+use std::ops::Range;
+
+use rustybuzz::ttf_parser::Tag;
+use rustybuzz::{Feature, UnicodeBuffer};
+use unicode_bidi::{BidiInfo, Level};
+
+use super::font_manager::FontData;
+
+/// Direction text is written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Ltr,
+    Rtl,
+}
+
+impl Direction {
+    fn level(self) -> Level {
+        match self {
+            Direction::Ltr => Level::ltr(),
+            Direction::Rtl => Level::rtl(),
+        }
+    }
+}
+
+/// A glyph placed by shaping, in pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapedGlyph {
+    /// Index of the glyph in the font
+    pub glyph_id: u16,
+    /// Byte offset in the text of the cluster the glyph was shaped from
+    pub cluster: usize,
+    /// How far the pen moves after the glyph
+    pub x_advance: f32,
+    pub y_advance: f32,
+    /// Offset of the glyph from the pen, y pointing up
+    pub x_offset: f32,
+    pub y_offset: f32,
+}
+
+/// Glyphs for a run of text in one direction
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlyphRun {
+    /// Bytes of the text the run was shaped from
+    pub range: Range<usize>,
+    pub direction: Direction,
+    /// Glyphs in visual order, left to right
+    pub glyphs: Vec<ShapedGlyph>,
+}
+
+impl GlyphRun {
+    /// Total advance of the run
+    pub fn width(&self) -> f32 {
+        self.glyphs.iter().map(|glyph| glyph.x_advance).sum()
+    }
+}
+
+/// Shaped text, as runs in visual order from left to right
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShapedText {
+    pub runs: Vec<GlyphRun>,
+}
+
+impl ShapedText {
+    /// Total advance of the text
+    pub fn width(&self) -> f32 {
+        self.runs.iter().map(GlyphRun::width).sum()
+    }
+
+    /// Every glyph with its origin relative to the start of the baseline, y pointing up
+    pub fn positioned_glyphs(&self) -> Vec<(f32, f32, ShapedGlyph)> {
+        let (mut x, mut y) = (0.0, 0.0);
+        let mut glyphs = Vec::new();
+        for glyph in self.runs.iter().flat_map(|run| &run.glyphs) {
+            glyphs.push((x + glyph.x_offset, y + glyph.y_offset, *glyph));
+            x += glyph.x_advance;
+            y += glyph.y_advance;
+        }
+        glyphs
+    }
+}
+
+/// Extra space CSS adds to text, in pixels
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Spacing {
+    /// After every cluster
+    pub letter: f32,
+    /// After every word separator, on top of the letter spacing
+    pub word: f32,
+}
+
+/// Shape text in a font at `font_size` pixels
+///
+/// `base` is the paragraph direction; `None` takes it from the first
+/// strongly directional character. Returns no runs if the font cannot be
+/// parsed.
+pub fn shape_text(text: &str, font: &FontData, font_size: f32, base: Option<Direction>) -> ShapedText {
+    shape_text_spaced(text, font, font_size, base, Spacing::default())
+}
+
+/// Shape text with letter and word spacing added
+///
+/// Optional ligatures are turned off while letters are spaced, since the
+/// spacing belongs between the letters a ligature would join.
+pub fn shape_text_spaced(
+    text: &str,
+    font: &FontData,
+    font_size: f32,
+    base: Option<Direction>,
+    spacing: Spacing,
+) -> ShapedText {
+    let Some(face) = font.face() else {
+        return ShapedText::default();
+    };
+    let scale = font_size / face.units_per_em() as f32;
+    let features: Vec<Feature> = if spacing.letter != 0.0 {
+        [b"liga", b"clig", b"dlig"].iter().map(|tag| Feature::new(Tag::from_bytes(tag), 0, ..)).collect()
+    } else {
+        Vec::new()
+    };
+    let bidi = BidiInfo::new(text, base.map(Direction::level));
+    let mut runs = Vec::new();
+    for paragraph in &bidi.paragraphs {
+        let (levels, level_runs) = bidi.visual_runs(paragraph, paragraph.range.clone());
+        for range in level_runs {
+            let direction = if levels[range.start].is_rtl() { Direction::Rtl } else { Direction::Ltr };
+            let mut buffer = UnicodeBuffer::new();
+            buffer.push_str(&text[range.clone()]);
+            buffer.set_direction(match direction {
+                Direction::Ltr => rustybuzz::Direction::LeftToRight,
+                Direction::Rtl => rustybuzz::Direction::RightToLeft,
+            });
+            buffer.guess_segment_properties();
+            let shaped = rustybuzz::shape(&face, &features, buffer);
+            let mut glyphs: Vec<ShapedGlyph> = shaped
+                .glyph_infos()
+                .iter()
+                .zip(shaped.glyph_positions())
+                .map(|(info, position)| ShapedGlyph {
+                    glyph_id: info.glyph_id as u16,
+                    cluster: range.start + info.cluster as usize,
+                    x_advance: position.x_advance as f32 * scale,
+                    y_advance: position.y_advance as f32 * scale,
+                    x_offset: position.x_offset as f32 * scale,
+                    y_offset: position.y_offset as f32 * scale,
+                })
+                .collect();
+            add_spacing(&mut glyphs, text, spacing);
+            runs.push(GlyphRun { range, direction, glyphs });
+        }
+    }
+    ShapedText { runs }
+}
+
+/// Add spacing to the last glyph of each cluster, so marks stay on their letters
+fn add_spacing(glyphs: &mut [ShapedGlyph], text: &str, spacing: Spacing) {
+    if spacing == Spacing::default() {
+        return;
+    }
+    for i in 0..glyphs.len() {
+        let cluster = glyphs[i].cluster;
+        if glyphs.get(i + 1).is_some_and(|next| next.cluster == cluster) {
+            continue;
+        }
+        glyphs[i].x_advance += spacing.letter;
+        if text[cluster..].starts_with([' ', '\u{a0}']) {
+            glyphs[i].x_advance += spacing.word;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::font_manager::FontManager;
+
+    fn shape(text: &str, base: Option<Direction>) -> ShapedText {
+        let font = FontManager::new().unwrap().get_font_data("sans-serif");
+        shape_text(text, &font, 16.0, base)
+    }
+
+    #[test]
+    fn test_mixed_direction_runs_in_visual_order() {
+        // "abc " then three Hebrew letters: the Hebrew run reads right to left
+        let shaped = shape("abc \u{5d0}\u{5d1}\u{5d2}", None);
+        let directions: Vec<Direction> = shaped.runs.iter().map(|run| run.direction).collect();
+        assert_eq!(directions, vec![Direction::Ltr, Direction::Rtl]);
+        let clusters: Vec<usize> = shaped.runs[1].glyphs.iter().map(|glyph| glyph.cluster).collect();
+        assert_eq!(clusters, vec![8, 6, 4]);
+
+        // In a right-to-left paragraph the Latin word ends up on the left
+        let shaped = shape("\u{5d0}\u{5d1} abc", Some(Direction::Rtl));
+        assert_eq!(shaped.runs[0].direction, Direction::Ltr);
+        assert_eq!(&"\u{5d0}\u{5d1} abc"[shaped.runs[0].range.clone()], "abc");
+        assert!(shape("", None).runs.is_empty());
+    }
+
+    #[test]
+    fn test_combining_marks_take_no_space() {
+        let decomposed = shape("e\u{301}", None);
+        let precomposed = shape("\u{e9}", None);
+        assert!((decomposed.width() - precomposed.width()).abs() < 0.5);
+        assert!(decomposed.width() > 0.0);
+        // The mark stays in the cluster of its base letter
+        assert!(decomposed.positioned_glyphs().iter().all(|(_, _, glyph)| glyph.cluster == 0));
+    }
+
+    #[test]
+    fn test_letter_and_word_spacing() {
+        let font = FontManager::new().unwrap().get_font_data("sans-serif");
+        let plain = shape_text("ab cd", &font, 16.0, None);
+        let spacing = Spacing { letter: 2.0, word: 5.0 };
+        let spaced = shape_text_spaced("ab cd", &font, 16.0, None, spacing);
+        // Five clusters get letter spacing and the space gets word spacing too
+        assert!((spaced.width() - plain.width() - 15.0).abs() < 0.01);
+        // A mark shares its letter's spacing
+        let marked = shape_text_spaced("e\u{301}", &font, 16.0, None, spacing);
+        let unmarked = shape_text_spaced("e", &font, 16.0, None, spacing);
+        assert!((marked.width() - unmarked.width()).abs() < 0.5);
+    }
+}
@@ -0,0 +1,189 @@
+// Software rasterizer
+//
+// Paints display lists into an RGBA pixel buffer on the CPU. It is the
+// fallback when no GPU is available or the GPU keeps failing, so it covers
+// what the wgpu painters cover: filled rectangles, highlights and borders,
+// clipped as the display list says.
+
+use crate::css::Color;
+use crate::display::DisplayCommand;
+use crate::layout::Rect;
+
+use super::to_device_rect;
+
+/// CPU renderer writing into an RGBA8 buffer
+pub struct SoftwareRasterizer {
+    width: u32,
+    height: u32,
+    /// Device pixels per CSS pixel
+    scale_factor: f32,
+    pixels: Vec<u8>,
+    /// Clips in force while painting, in device pixels, innermost last
+    clips: Vec<Rect>,
+}
+
+impl SoftwareRasterizer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            scale_factor: 1.0,
+            pixels: vec![0; width as usize * height as usize * 4],
+            clips: Vec::new(),
+        }
+    }
+
+    /// Resize the buffer; its contents are lost
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        self.pixels = vec![0; width as usize * height as usize * 4];
+    }
+
+    /// Set the device pixel ratio
+    pub fn set_scale_factor(&mut self, scale_factor: f32) {
+        if scale_factor > 0.0 {
+            self.scale_factor = scale_factor;
+        }
+    }
+
+    /// Buffer size in device pixels
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// The painted frame, row by row, four bytes per pixel
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Colour of one device pixel
+    pub fn pixel(&self, x: u32, y: u32) -> Option<Color> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        let p = &self.pixels[i..i + 4];
+        Some(Color::new(p[0], p[1], p[2], p[3]))
+    }
+
+    /// Paint a display list over a white background
+    ///
+    /// Geometry is in CSS pixels. Text and images are left to the GPU
+    /// painters and skipped here.
+    pub fn paint(&mut self, list: &[DisplayCommand]) {
+        for pixel in self.pixels.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&[255, 255, 255, 255]);
+        }
+        self.clips.clear();
+        for command in list {
+            match command {
+                DisplayCommand::SolidRect { color, rect } | DisplayCommand::Highlight { color, rect } => {
+                    self.fill_rect(&to_device_rect(rect, self.scale_factor), *color);
+                }
+                DisplayCommand::Border { color, rect, widths } => {
+                    let rect = to_device_rect(rect, self.scale_factor);
+                    let s = self.scale_factor;
+                    let (left, right, top, bottom) = (widths.0 * s, widths.1 * s, widths.2 * s, widths.3 * s);
+                    let inner_height = rect.height - top - bottom;
+                    self.fill_rect(&Rect { height: top, ..rect }, *color);
+                    self.fill_rect(&Rect { y: rect.y + rect.height - bottom, height: bottom, ..rect }, *color);
+                    self.fill_rect(&Rect { y: rect.y + top, width: left, height: inner_height, ..rect }, *color);
+                    self.fill_rect(
+                        &Rect { x: rect.x + rect.width - right, y: rect.y + top, width: right, height: inner_height },
+                        *color,
+                    );
+                }
+                DisplayCommand::PushClip { rect } => {
+                    let rect = to_device_rect(rect, self.scale_factor);
+                    let clip = self.clips.last().map_or(rect, |outer| outer.intersection(&rect));
+                    self.clips.push(clip);
+                }
+                DisplayCommand::PopClip { .. } => {
+                    self.clips.pop();
+                }
+                DisplayCommand::Text { .. }
+                | DisplayCommand::Image { .. }
+                | DisplayCommand::PushFixed { .. }
+                | DisplayCommand::PopFixed { .. } => {}
+            }
+        }
+    }
+
+    /// Blend a colour over the pixels whose centres lie in `rect` and the current clip
+    fn fill_rect(&mut self, rect: &Rect, color: Color) {
+        let rect = &self.clips.last().map_or(*rect, |clip| clip.intersection(rect));
+        if rect.width <= 0.0 || rect.height <= 0.0 || color.a == 0 {
+            return;
+        }
+        let x0 = rect.x.round().clamp(0.0, self.width as f32) as usize;
+        let x1 = (rect.x + rect.width).round().clamp(0.0, self.width as f32) as usize;
+        let y0 = rect.y.round().clamp(0.0, self.height as f32) as usize;
+        let y1 = (rect.y + rect.height).round().clamp(0.0, self.height as f32) as usize;
+        let alpha = color.a as u32;
+        let source = [color.r, color.g, color.b];
+        for y in y0..y1 {
+            let row = y * self.width as usize * 4;
+            for pixel in self.pixels[row + x0 * 4..row + x1 * 4].chunks_exact_mut(4) {
+                for (channel, &value) in pixel.iter_mut().zip(&source) {
+                    *channel = ((value as u32 * alpha + *channel as u32 * (255 - alpha) + 127) / 255) as u8;
+                }
+                pixel[3] = (alpha + (pixel[3] as u32 * (255 - alpha) + 127) / 255) as u8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paint_rects_and_borders() {
+        let mut raster = SoftwareRasterizer::new(20, 20);
+        raster.set_scale_factor(2.0);
+        let red = Color::new(255, 0, 0, 255);
+        raster.paint(&[
+            DisplayCommand::SolidRect { color: red, rect: Rect { x: 0.0, y: 0.0, width: 5.0, height: 5.0 } },
+            DisplayCommand::Border {
+                color: Color::new(0, 0, 0, 255),
+                rect: Rect { x: 5.0, y: 5.0, width: 5.0, height: 5.0 },
+                widths: (1.0, 0.0, 0.0, 0.0),
+            },
+            DisplayCommand::Highlight {
+                color: Color::new(0, 0, 255, 128),
+                rect: Rect { x: 0.0, y: 0.0, width: 1.0, height: 1.0 },
+            },
+        ]);
+        assert_eq!(raster.pixel(9, 9), Some(red));
+        assert_eq!(raster.pixel(14, 14), Some(Color::new(255, 255, 255, 255)));
+        // Only the left edge of the border is painted, two device pixels wide
+        assert_eq!(raster.pixel(11, 15), Some(Color::new(0, 0, 0, 255)));
+        assert_eq!(raster.pixel(12, 15), Some(Color::new(255, 255, 255, 255)));
+        // A translucent highlight blends with what is below
+        assert_eq!(raster.pixel(0, 0), Some(Color::new(127, 0, 128, 255)));
+        assert_eq!(raster.pixel(20, 0), None);
+    }
+
+    #[test]
+    fn test_clips_nest() {
+        let mut raster = SoftwareRasterizer::new(20, 20);
+        let red = Color::new(255, 0, 0, 255);
+        let white = Color::new(255, 255, 255, 255);
+        let clip = |x, y| Rect { x, y, width: 10.0, height: 10.0 };
+        let everything = Rect { x: 0.0, y: 0.0, width: 20.0, height: 20.0 };
+        raster.paint(&[
+            DisplayCommand::PushClip { rect: clip(0.0, 0.0) },
+            DisplayCommand::PushClip { rect: clip(5.0, 5.0) },
+            DisplayCommand::SolidRect { color: red, rect: everything },
+            DisplayCommand::PopClip { rect: clip(5.0, 5.0) },
+            DisplayCommand::PopClip { rect: clip(0.0, 0.0) },
+            DisplayCommand::Highlight { color: red, rect: Rect { x: 15.0, y: 15.0, width: 5.0, height: 5.0 } },
+        ]);
+        assert_eq!(raster.pixel(7, 7), Some(red));
+        assert_eq!(raster.pixel(2, 2), Some(white));
+        assert_eq!(raster.pixel(12, 12), Some(white));
+        // Painted after both clips ended
+        assert_eq!(raster.pixel(17, 17), Some(red));
+    }
+}
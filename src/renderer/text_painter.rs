@@ -1,9 +1,17 @@
+use std::ops::Range;
+
 use wgpu::{Device, Queue, RenderPass, RenderPipeline, Buffer, Texture, Sampler, BindGroup};
 use bytemuck::{Pod, Zeroable};
 use crate::css::Color;
 use crate::layout::Rect;
+use crate::text_style::{DecorationLine, TextStyle};
 use super::text_renderer::TextRenderer;
-use super::glyph_cache::GlyphKey;
+use super::glyph_cache::GlyphIdKey;
+use super::decoration::decoration_rects;
+use super::shaping::Spacing;
+
+/// Texture coordinates marking a quad as solid, for decoration lines
+const SOLID_TEX_COORDS: [f32; 4] = [-1.0, -1.0, -1.0, -1.0];
 
 /// Vertex data for text rendering (position + tex coords + color)
 #[repr(C)]
@@ -37,6 +45,23 @@ pub struct TextCommand {
     pub color: Color,
     pub font_family: String,
     pub font_size: f32,
+    /// Spacing and decoration lines
+    pub style: TextStyle,
+}
+
+/// A quad from device pixel corners and texture coordinates, both left, top, right, bottom
+fn quad(corners: [f32; 4], tex: [f32; 4], color: [f32; 4], viewport_size: (u32, u32)) -> [TextVertex; 4] {
+    // Convert to NDC
+    let ndc_x = |x: f32| (x / viewport_size.0 as f32) * 2.0 - 1.0;
+    let ndc_y = |y: f32| 1.0 - (y / viewport_size.1 as f32) * 2.0;
+    let [x1, y1, x2, y2] = corners;
+    let [u1, v1, u2, v2] = tex;
+    [
+        TextVertex { position: [ndc_x(x1), ndc_y(y1)], tex_coords: [u1, v1], color },
+        TextVertex { position: [ndc_x(x2), ndc_y(y1)], tex_coords: [u2, v1], color },
+        TextVertex { position: [ndc_x(x2), ndc_y(y2)], tex_coords: [u2, v2], color },
+        TextVertex { position: [ndc_x(x1), ndc_y(y2)], tex_coords: [u1, v2], color },
+    ]
 }
 
 /// Painter for rendering text with GPU acceleration
@@ -44,11 +69,15 @@ pub struct TextPainter {
     pipeline: RenderPipeline,
     vertex_buffer: Buffer,
     index_buffer: Buffer,
-    bind_group: Option<BindGroup>,
+    /// Bind groups for the glyph atlas pages, indexed by page
+    bind_groups: Vec<BindGroup>,
     bind_group_layout: wgpu::BindGroupLayout,
     sampler: Sampler,
     max_glyphs: usize,
     glyph_count: usize,
+    /// Atlas page and index range of each draw, per run of commands
+    /// and one per page the run's glyphs were placed from
+    draws: Vec<Vec<(usize, Range<u32>)>>,
 }
 
 impl TextPainter {
@@ -161,15 +190,16 @@ impl TextPainter {
             pipeline,
             vertex_buffer,
             index_buffer,
-            bind_group: None,
+            bind_groups: Vec::new(),
             bind_group_layout,
             sampler,
             max_glyphs,
             glyph_count: 0,
+            draws: Vec::new(),
         }
     }
 
-    /// Update the atlas texture binding
+    /// Bind the next atlas page's texture
     pub fn update_atlas(&mut self, device: &Device, atlas_texture: &Texture) {
         let texture_view = atlas_texture.create_view(&wgpu::TextureViewDescriptor::default());
 
@@ -188,7 +218,12 @@ impl TextPainter {
             ],
         });
 
-        self.bind_group = Some(bind_group);
+        self.bind_groups.push(bind_group);
+    }
+
+    /// Forget the bindings of atlas pages past the first `pages`, whose textures were freed
+    pub fn unbind_atlas_pages(&mut self, pages: usize) {
+        self.bind_groups.truncate(pages);
     }
 
     /// Prepare text for rendering
@@ -200,105 +235,166 @@ impl TextPainter {
         commands: &[TextCommand],
         viewport_size: (u32, u32),
     ) -> usize {
+        self.prepare_runs(device, queue, text_renderer, commands, &[0..commands.len()], viewport_size)
+    }
+
+    /// Prepare text for rendering as runs of commands, each drawn on its own by `render_run`
+    ///
+    /// Within a run, quads are batched by the atlas page they sample, so a
+    /// run takes one draw per page whatever its fonts and sizes.
+    pub fn prepare_runs(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        text_renderer: &mut TextRenderer,
+        commands: &[TextCommand],
+        runs: &[Range<usize>],
+        viewport_size: (u32, u32),
+    ) -> usize {
+        self.draws.clear();
         if commands.is_empty() {
             self.glyph_count = 0;
             return 0;
         }
 
-        // Upload atlas if needed
-        text_renderer.upload_atlas(device, queue);
+        // Glyphs placed from here on stay in the atlas until the frame is drawn
+        text_renderer.glyph_cache_mut().begin_frame();
 
-        // Update bind group if we have an atlas texture
-        if let Some(atlas_texture) = text_renderer.atlas_texture() {
-            if self.bind_group.is_none() {
-                self.update_atlas(device, atlas_texture);
-            }
-        }
-
-        let mut vertices = Vec::new();
-        let mut indices = Vec::new();
+        // Quads grouped by run, then by the atlas page they sample
+        let mut run_quads: Vec<Vec<Vec<[TextVertex; 4]>>> = Vec::new();
         let mut total_glyphs = 0;
 
         let atlas_dims = text_renderer.glyph_cache().atlas_dimensions();
 
-        for cmd in commands {
-            if total_glyphs >= self.max_glyphs {
-                break;
-            }
+        for run in runs {
+            let mut page_quads: Vec<Vec<[TextVertex; 4]>> = Vec::new();
+            for cmd in &commands[run.clone()] {
+                if total_glyphs >= self.max_glyphs {
+                    break;
+                }
 
-            // Get font
-            let font = text_renderer.font_manager_mut().get_font(&cmd.font_family);
-            let font_id = text_renderer.glyph_cache_mut().register_font(font);
-
-            let mut x_offset = cmd.rect.x;
-            let y_offset = cmd.rect.y;
-
-            // Convert color
-            let color_f = [
-                cmd.color.r as f32 / 255.0,
-                cmd.color.g as f32 / 255.0,
-                cmd.color.b as f32 / 255.0,
-                cmd.color.a as f32 / 255.0,
-            ];
-
-            // Render each character
-            for ch in cmd.text.chars() {
-                let key = GlyphKey {
-                    character: ch,
-                    size: cmd.font_size as u32,
-                    font_id,
-                };
-
-                if let Some(glyph) = text_renderer.glyph_cache_mut().get_or_rasterize(key) {
-                    // Skip empty glyphs (spaces)
-                    if glyph.width > 0 && glyph.height > 0 {
-                        // Screen coordinates
-                        let x1 = x_offset + glyph.bearing_x;
-                        let y1 = y_offset + glyph.bearing_y;
-                        let x2 = x1 + glyph.width as f32;
-                        let y2 = y1 + glyph.height as f32;
-
-                        // Convert to NDC
-                        let ndc_x1 = (x1 / viewport_size.0 as f32) * 2.0 - 1.0;
-                        let ndc_y1 = 1.0 - (y1 / viewport_size.1 as f32) * 2.0;
-                        let ndc_x2 = (x2 / viewport_size.0 as f32) * 2.0 - 1.0;
-                        let ndc_y2 = 1.0 - (y2 / viewport_size.1 as f32) * 2.0;
-
-                        // Texture coordinates
-                        let u1 = glyph.atlas_x as f32 / atlas_dims.0 as f32;
-                        let v1 = glyph.atlas_y as f32 / atlas_dims.1 as f32;
-                        let u2 = (glyph.atlas_x + glyph.width) as f32 / atlas_dims.0 as f32;
-                        let v2 = (glyph.atlas_y + glyph.height) as f32 / atlas_dims.1 as f32;
-
-                        // Create vertices
-                        // Protect against u16 overflow
-                        if vertices.len() >= (u16::MAX as usize - 4) {
-                            break; // Stop adding vertices
+                // Get font
+                let font_id = text_renderer.font_id(&cmd.font_family);
+                let spacing = Spacing { letter: cmd.style.letter_spacing, word: cmd.style.word_spacing };
+                let shaped = text_renderer.shape_text_spaced(&cmd.text, &cmd.font_family, cmd.font_size, spacing);
+                let metrics = text_renderer.decoration_metrics(&cmd.font_family, cmd.font_size);
+                // The command's rect is the text's box; glyphs sit on the font's baseline in it
+                let baseline = cmd.rect.y + metrics.ascent;
+
+                // Commands are in CSS pixels; glyphs and the viewport in device pixels
+                let scale = text_renderer.scale_factor();
+                let size = text_renderer.glyph_size(cmd.font_size);
+
+                // Convert color
+                let color_f = [
+                    cmd.color.r as f32 / 255.0,
+                    cmd.color.g as f32 / 255.0,
+                    cmd.color.b as f32 / 255.0,
+                    cmd.color.a as f32 / 255.0,
+                ];
+
+                // Place each shaped glyph where shaping put it, noting its ink for skip-ink
+                let mut glyph_quads = Vec::new();
+                let mut ink = Vec::new();
+                for (glyph_x, glyph_y, shaped_glyph) in shaped.positioned_glyphs() {
+                    let key = GlyphIdKey {
+                        glyph_id: shaped_glyph.glyph_id,
+                        size,
+                        font_id,
+                    };
+
+                    if let Some(glyph) = text_renderer.glyph_cache_mut().get_or_rasterize_glyph(key) {
+                        // Skip empty glyphs (spaces)
+                        if glyph.width > 0 && glyph.height > 0 {
+                            // Screen coordinates; bearings are from the baseline up to the bitmap's bottom
+                            let x1 = (cmd.rect.x + glyph_x) * scale + glyph.bearing_x;
+                            let y1 = (baseline - glyph_y) * scale - glyph.bearing_y - glyph.height as f32;
+                            let x2 = x1 + glyph.width as f32;
+                            let y2 = y1 + glyph.height as f32;
+                            ink.push(Rect {
+                                x: x1 / scale - cmd.rect.x,
+                                y: y1 / scale - baseline,
+                                width: glyph.width as f32 / scale,
+                                height: glyph.height as f32 / scale,
+                            });
+
+                            // Texture coordinates
+                            let u1 = glyph.atlas_x as f32 / atlas_dims.0 as f32;
+                            let v1 = glyph.atlas_y as f32 / atlas_dims.1 as f32;
+                            let u2 = (glyph.atlas_x + glyph.width) as f32 / atlas_dims.0 as f32;
+                            let v2 = (glyph.atlas_y + glyph.height) as f32 / atlas_dims.1 as f32;
+
+                            let vertices = quad([x1, y1, x2, y2], [u1, v1, u2, v2], color_f, viewport_size);
+                            glyph_quads.push((glyph.page, vertices));
                         }
-                        let base_index = vertices.len() as u16;
-                        vertices.extend_from_slice(&[
-                            TextVertex { position: [ndc_x1, ndc_y1], tex_coords: [u1, v1], color: color_f },
-                            TextVertex { position: [ndc_x2, ndc_y1], tex_coords: [u2, v1], color: color_f },
-                            TextVertex { position: [ndc_x2, ndc_y2], tex_coords: [u2, v2], color: color_f },
-                            TextVertex { position: [ndc_x1, ndc_y2], tex_coords: [u1, v2], color: color_f },
-                        ]);
-
-                        // Create indices
-                        indices.extend_from_slice(&[
-                            base_index,
-                            base_index + 1,
-                            base_index + 2,
-                            base_index,
-                            base_index + 2,
-                            base_index + 3,
-                        ]);
-
-                        total_glyphs += 1;
                     }
+                }
+
+                // Underlines and overlines go beneath the glyphs and lines through them on top
+                let mut under = Vec::new();
+                let mut over = Vec::new();
+                for decoration in &cmd.style.decorations {
+                    let color = decoration.color;
+                    let color_f =
+                        [color.r as f32 / 255.0, color.g as f32 / 255.0, color.b as f32 / 255.0, color.a as f32 / 255.0];
+                    let lines = decoration_rects(decoration, &metrics, shaped.width(), &ink, cmd.style.skip_ink);
+                    for line in lines {
+                        let x1 = (cmd.rect.x + line.x) * scale;
+                        let y1 = (baseline + line.y) * scale;
+                        let corners = [x1, y1, x1 + line.width * scale, y1 + line.height * scale];
+                        let vertices = quad(corners, SOLID_TEX_COORDS, color_f, viewport_size);
+                        match decoration.line {
+                            DecorationLine::LineThrough => over.push((0, vertices)),
+                            _ => under.push((0, vertices)),
+                        }
+                    }
+                }
+
+                for (page, vertices) in under.into_iter().chain(glyph_quads).chain(over) {
+                    // Protect against overflowing the buffers
+                    if total_glyphs >= self.max_glyphs {
+                        break;
+                    }
+                    if page_quads.len() <= page {
+                        page_quads.resize_with(page + 1, Vec::new);
+                    }
+                    page_quads[page].push(vertices);
+                    total_glyphs += 1;
+                }
+            }
+            run_quads.push(page_quads);
+        }
 
-                    x_offset += glyph.advance;
+        // Upload glyphs rasterized for this frame and bind any new atlas pages
+        text_renderer.upload_atlas(device, queue);
+        for texture in text_renderer.atlas_textures().iter().skip(self.bind_groups.len()) {
+            self.update_atlas(device, texture);
+        }
+
+        // One draw per page of each run, each over its own range of indices
+        let mut vertices = Vec::new();
+        let mut indices: Vec<u16> = Vec::new();
+        for page_quads in &run_quads {
+            let mut draws = Vec::new();
+            for (page, quads) in page_quads.iter().enumerate() {
+                let start = indices.len() as u32;
+                for quad in quads {
+                    let base_index = vertices.len() as u16;
+                    vertices.extend_from_slice(quad);
+                    indices.extend_from_slice(&[
+                        base_index,
+                        base_index + 1,
+                        base_index + 2,
+                        base_index,
+                        base_index + 2,
+                        base_index + 3,
+                    ]);
+                }
+                if !quads.is_empty() {
+                    draws.push((page, start..indices.len() as u32));
                 }
             }
+            self.draws.push(draws);
         }
 
         if !vertices.is_empty() {
@@ -312,14 +408,25 @@ impl TextPainter {
 
     /// Render the prepared text
     pub fn render<'rpass>(&'rpass self, render_pass: &mut RenderPass<'rpass>) {
-        if self.glyph_count == 0 || self.bind_group.is_none() {
-            return;
+        for run in 0..self.draws.len() {
+            self.render_run(render_pass, run);
         }
+    }
+
+    /// Render one run of the prepared text, by its order in `prepare_runs`
+    pub fn render_run<'rpass>(&'rpass self, render_pass: &mut RenderPass<'rpass>, run: usize) {
+        let Some(draws) = self.draws.get(run).filter(|draws| self.glyph_count > 0 && !draws.is_empty()) else {
+            return;
+        };
 
         render_pass.set_pipeline(&self.pipeline);
-        render_pass.set_bind_group(0, self.bind_group.as_ref().unwrap(), &[]);
         render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
         render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-        render_pass.draw_indexed(0..(self.glyph_count * 6) as u32, 0, 0..1);
+        for (page, indices) in draws {
+            if let Some(bind_group) = self.bind_groups.get(*page) {
+                render_pass.set_bind_group(0, bind_group, &[]);
+                render_pass.draw_indexed(indices.clone(), 0, 0..1);
+            }
+        }
     }
 }
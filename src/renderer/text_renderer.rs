@@ -1,9 +1,18 @@
 // Text rendering module with GPU-accelerated text drawing
+//
+// Text is shaped before it is measured or drawn, so both use the glyphs
+// and positions the font picks for it rather than one glyph per character.
+
+use std::collections::HashMap;
 
 use wgpu::{Device, Queue, Texture};
 
+use crate::layout::line_break::{self, Hyphens, Line};
+
 use super::font_manager::FontManager;
-use super::glyph_cache::{GlyphCache, GlyphKey};
+use super::glyph_cache::{GlyphAtlasStats, GlyphCache, GlyphIdKey, RenderMode};
+use super::decoration::DecorationMetrics;
+use super::shaping::{self, ShapedText, Spacing};
 
 /// Text renderer with GPU-accelerated text drawing
 pub struct TextRenderer {
@@ -11,8 +20,12 @@ pub struct TextRenderer {
     font_manager: FontManager,
     /// Glyph cache with texture atlas
     glyph_cache: GlyphCache,
-    /// GPU texture for glyph atlas
-    atlas_texture: Option<Texture>,
+    /// Glyph cache font IDs by font family
+    font_ids: HashMap<String, usize>,
+    /// GPU textures for the grayscale glyph atlas, one per page
+    atlas_textures: Vec<Texture>,
+    /// Device pixels per CSS pixel; glyphs are rasterized at this density
+    scale_factor: f32,
 }
 
 impl TextRenderer {
@@ -27,71 +40,144 @@ impl TextRenderer {
         Ok(Self {
             font_manager,
             glyph_cache,
-            atlas_texture: None,
+            font_ids: HashMap::new(),
+            atlas_textures: Vec::new(),
+            scale_factor: 1.0,
         })
     }
 
-    /// Initialize the GPU texture atlas
+    /// Set the device pixel ratio glyphs are rasterized for
+    ///
+    /// Cached glyphs were rasterized for the old ratio, so the cache is
+    /// cleared when it changes.
+    pub fn set_scale_factor(&mut self, scale_factor: f32) {
+        if scale_factor > 0.0 && scale_factor != self.scale_factor {
+            self.scale_factor = scale_factor;
+            self.glyph_cache.clear();
+        }
+    }
+
+    /// Device pixels per CSS pixel
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+
+    /// Rasterization size in device pixels for a font size in CSS pixels
+    pub fn glyph_size(&self, font_size: f32) -> u32 {
+        (font_size * self.scale_factor).round().max(1.0) as u32
+    }
+
+    /// Create GPU textures for atlas pages that have none yet
     pub fn init_atlas(&mut self, device: &Device) {
         let (width, height) = self.glyph_cache.atlas_dimensions();
         
-        let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Glyph Atlas"),
-            size: wgpu::Extent3d {
-                width,
-                height,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::R8Unorm, // Single channel for alpha
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            view_formats: &[],
-        });
-
-        self.atlas_texture = Some(texture);
+        while self.atlas_textures.len() < self.glyph_cache.page_count(RenderMode::Grayscale) {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Glyph Atlas"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R8Unorm, // Single channel for alpha
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+
+            self.atlas_textures.push(texture);
+        }
+    }
+
+    /// Glyph cache ID of a font family's font, registering it the first time
+    pub fn font_id(&mut self, font_family: &str) -> usize {
+        if let Some(&id) = self.font_ids.get(font_family) {
+            return id;
+        }
+        let font = self.font_manager.get_font(font_family);
+        let id = self.glyph_cache.register_font(font);
+        self.font_ids.insert(font_family.to_string(), id);
+        id
+    }
+
+    /// Shape text into glyph runs in visual order, positioned in CSS pixels
+    ///
+    /// The paragraph direction comes from the text's first strongly
+    /// directional character.
+    pub fn shape_text(&mut self, text: &str, font_family: &str, font_size: f32) -> ShapedText {
+        let font = self.font_manager.get_font_data(font_family);
+        shaping::shape_text(text, &font, font_size, None)
+    }
+
+    /// Shape text with CSS letter and word spacing, in CSS pixels
+    pub fn shape_text_spaced(&mut self, text: &str, font_family: &str, font_size: f32, spacing: Spacing) -> ShapedText {
+        let font = self.font_manager.get_font_data(font_family);
+        shaping::shape_text_spaced(text, &font, font_size, None, spacing)
+    }
+
+    /// Where a font family puts its ascent and decoration lines at a size, in CSS pixels
+    pub fn decoration_metrics(&mut self, font_family: &str, font_size: f32) -> DecorationMetrics {
+        DecorationMetrics::from_font(&self.font_manager.get_font_data(font_family), font_size)
     }
 
     /// Measure text dimensions using actual font metrics
+    ///
+    /// Sizes are in CSS pixels. The width is the shaped advance; glyphs
+    /// are measured at device resolution so layout matches what is drawn.
     pub fn measure_text(&mut self, text: &str, font_family: &str, font_size: f32) -> (f32, f32) {
-        let font = self.font_manager.get_font(font_family);
-        let font_id = self.glyph_cache.register_font(font);
+        let shaped = self.shape_text(text, font_family, font_size);
+        let font_id = self.font_id(font_family);
+        let size = self.glyph_size(font_size);
         
-        let mut width = 0.0_f32;
         let mut max_height = 0.0_f32;
-        
-        for ch in text.chars() {
-            let key = GlyphKey {
-                character: ch,
-                size: font_size as u32,
-                font_id,
-            };
-            
-            if let Some(glyph) = self.glyph_cache.get_or_rasterize(key) {
-                width += glyph.advance;
-                max_height = max_height.max(glyph.height as f32);
+        for (_, _, glyph) in shaped.positioned_glyphs() {
+            let key = GlyphIdKey { glyph_id: glyph.glyph_id, size, font_id };
+            if let Some(glyph) = self.glyph_cache.get_or_rasterize_glyph(key) {
+                max_height = max_height.max(glyph.height as f32 / self.scale_factor);
             }
         }
         
-        (width, max_height.max(font_size))
+        (shaped.width(), max_height.max(font_size))
+    }
+
+    /// Wrap text to `max_width` CSS pixels, measuring lines by their shaped advance
+    ///
+    /// `lang` picks the dictionary `hyphens: auto` breaks words with.
+    pub fn wrap_text(
+        &mut self,
+        text: &str,
+        font_family: &str,
+        font_size: f32,
+        max_width: f32,
+        hyphens: Hyphens,
+        lang: &str,
+    ) -> Vec<Line> {
+        let font = self.font_manager.get_font_data(font_family);
+        let breaks = line_break::break_opportunities(text, hyphens, lang);
+        line_break::break_lines(text, &breaks, max_width, |line| {
+            shaping::shape_text(line, &font, font_size, None).width()
+        })
     }
 
-    /// Upload atlas texture to GPU if dirty
+    /// Upload atlas pages changed since the last upload to the GPU
     pub fn upload_atlas(&mut self, device: &Device, queue: &Queue) {
         if !self.glyph_cache.is_dirty() {
             return;
         }
         
-        // Ensure texture exists
-        if self.atlas_texture.is_none() {
-            self.init_atlas(device);
-        }
+        // Ensure every page has a texture
+        self.init_atlas(device);
         
-        if let Some(texture) = &self.atlas_texture {
-            let (width, height) = self.glyph_cache.atlas_dimensions();
-            let data = self.glyph_cache.atlas_data();
-            
+        let (width, height) = self.glyph_cache.atlas_dimensions();
+        for page in self.glyph_cache.dirty_pages(RenderMode::Grayscale) {
+            let (Some(texture), Some(data)) = (
+                self.atlas_textures.get(page),
+                self.glyph_cache.page_data(RenderMode::Grayscale, page),
+            ) else {
+                continue;
+            };
             queue.write_texture(
                 wgpu::ImageCopyTexture {
                     texture,
@@ -111,9 +197,8 @@ impl TextRenderer {
                     depth_or_array_layers: 1,
                 },
             );
-            
-            self.glyph_cache.mark_clean();
         }
+        self.glyph_cache.mark_clean();
     }
 
     /// Prepare text for rendering (rasterize glyphs and upload to GPU)
@@ -124,19 +209,14 @@ impl TextRenderer {
         font_family: &str,
         texts: &[(String, f32)], // (text, font_size)
     ) {
-        let font = self.font_manager.get_font(font_family);
-        let font_id = self.glyph_cache.register_font(font);
+        let font_id = self.font_id(font_family);
         
         // Rasterize all glyphs
         for (text, font_size) in texts {
-            let size = *font_size as u32;
-            for ch in text.chars() {
-                let key = GlyphKey {
-                    character: ch,
-                    size,
-                    font_id,
-                };
-                let _ = self.glyph_cache.get_or_rasterize(key);
+            let size = self.glyph_size(*font_size);
+            for (_, _, glyph) in self.shape_text(text, font_family, *font_size).positioned_glyphs() {
+                let key = GlyphIdKey { glyph_id: glyph.glyph_id, size, font_id };
+                let _ = self.glyph_cache.get_or_rasterize_glyph(key);
             }
         }
         
@@ -144,9 +224,20 @@ impl TextRenderer {
         self.upload_atlas(device, queue);
     }
 
-    /// Get the atlas texture for binding
-    pub fn atlas_texture(&self) -> Option<&Texture> {
-        self.atlas_texture.as_ref()
+    /// Get the atlas page textures for binding, indexed by page
+    pub fn atlas_textures(&self) -> &[Texture] {
+        &self.atlas_textures
+    }
+    
+    /// Glyph atlas usage, for DevTools
+    pub fn atlas_stats(&self) -> GlyphAtlasStats {
+        self.glyph_cache.stats()
+    }
+    
+    /// Free glyph atlas pages past the first, and their textures
+    pub fn trim_atlas(&mut self) {
+        self.glyph_cache.trim();
+        self.atlas_textures.truncate(self.glyph_cache.page_count(RenderMode::Grayscale));
     }
     
     /// Get the glyph cache
@@ -197,6 +288,22 @@ mod tests {
         assert!(height > 0.0);
     }
     
+    #[test]
+    fn test_scale_factor_rasterizes_at_device_resolution() {
+        let mut renderer = TextRenderer::new().unwrap();
+        let (width_1x, _) = renderer.measure_text("Hello", "sans-serif", 16.0);
+        let cached = renderer.glyph_cache().glyph_count();
+        
+        renderer.set_scale_factor(2.0);
+        assert_eq!(renderer.glyph_size(16.0), 32);
+        assert_eq!(renderer.glyph_cache().glyph_count(), 0);
+        
+        // Layout sizes stay in CSS pixels
+        let (width_2x, _) = renderer.measure_text("Hello", "sans-serif", 16.0);
+        assert!((width_2x - width_1x).abs() < 2.0);
+        assert_eq!(renderer.glyph_cache().glyph_count(), cached);
+    }
+    
     #[test]
     fn test_atlas_dimensions() {
         let renderer = TextRenderer::new().unwrap();
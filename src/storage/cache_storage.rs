@@ -0,0 +1,609 @@
+// Cache API - named caches of request/response pairs per origin
+//
+// `caches.open(name)` and friends for documents and, later, service
+// workers. Every origin has its own ordered list of caches; each cache
+// keeps its entries in insertion order, as `cache.keys()` reports them.
+// Matching follows the spec: fragments never count, `ignoreSearch` drops
+// the query, and a cached response's `Vary` header makes the listed
+// request headers part of the match. Each origin's caches are written to
+// their own file in the cache directory after every change.
+
+use super::origin_key;
+use crate::fetch::{Headers, Method, Request, Response};
+use crate::net::{HttpClient, RequestOptions};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// Options for `match`, `matchAll`, `delete` and `keys`
+#[derive(Debug, Clone, Default)]
+pub struct CacheQueryOptions {
+    /// Ignore the query string of both URLs
+    pub ignore_search: bool,
+    /// Match requests whatever their method
+    pub ignore_method: bool,
+    /// Ignore the cached response's `Vary` header
+    pub ignore_vary: bool,
+    /// Only look in this cache (`caches.match` only)
+    pub cache_name: Option<String>,
+}
+
+/// Cache API errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheError {
+    /// Request or response cannot be cached
+    TypeError(String),
+    /// Caches are not available to the document (opaque origin)
+    SecurityError,
+    /// No cache with that name
+    NotFound(String),
+    /// Reading or writing the cache directory failed
+    Io(String),
+}
+
+impl std::fmt::Display for CacheError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CacheError::TypeError(msg) => write!(f, "TypeError: {}", msg),
+            CacheError::SecurityError => write!(f, "Caches are not available to this document"),
+            CacheError::NotFound(name) => write!(f, "No cache named '{}'", name),
+            CacheError::Io(msg) => write!(f, "Failed to write cache: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for CacheError {}
+
+impl From<io::Error> for CacheError {
+    fn from(e: io::Error) -> Self {
+        CacheError::Io(e.to_string())
+    }
+}
+
+/// A stored request/response pair
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    /// Request URL without its fragment
+    url: String,
+    method: String,
+    request_headers: Vec<(String, String)>,
+    status: u16,
+    status_text: String,
+    response_headers: Vec<(String, String)>,
+    response_url: String,
+    #[serde(with = "hex_body")]
+    body: Vec<u8>,
+}
+
+impl CacheEntry {
+    fn new(url: &Url, request: &Request, response: &Response) -> Self {
+        Self {
+            url: url.to_string(),
+            method: request.method.as_str().to_string(),
+            request_headers: request.headers.entries(),
+            status: response.status,
+            status_text: response.status_text.clone(),
+            response_headers: response.headers.entries(),
+            response_url: response.url.clone(),
+            body: response.body().to_vec(),
+        }
+    }
+
+    fn request_header(&self, name: &str) -> Option<&str> {
+        self.request_headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+
+    fn vary(&self) -> Option<&str> {
+        self.response_headers.iter().find(|(n, _)| n.eq_ignore_ascii_case("vary")).map(|(_, v)| v.as_str())
+    }
+
+    /// Does `request` (already normalised to `url`) select this entry
+    fn matches(&self, url: &Url, request: &Request, options: &CacheQueryOptions) -> bool {
+        let Ok(cached) = Url::parse(&self.url) else {
+            return false;
+        };
+        if normalize(cached, options.ignore_search) != normalize(url.clone(), options.ignore_search) {
+            return false;
+        }
+        if options.ignore_vary {
+            return true;
+        }
+        let Some(vary) = self.vary() else {
+            return true;
+        };
+        vary.split(',').map(str::trim).filter(|name| !name.is_empty()).all(|name| {
+            name != "*" && request.headers.get(name) == self.request_header(name)
+        })
+    }
+
+    fn request(&self) -> Request {
+        let mut request = Request::new(self.url.clone(), Method::from_str(&self.method).unwrap_or(Method::Get));
+        request.headers = headers(&self.request_headers);
+        request
+    }
+
+    fn response(&self) -> Response {
+        Response::new(
+            self.status,
+            self.status_text.clone(),
+            headers(&self.response_headers),
+            self.body.clone(),
+            self.response_url.clone(),
+        )
+    }
+
+    fn size(&self) -> usize {
+        self.url.len() + self.response_url.len() + self.body.len()
+    }
+}
+
+/// One named cache
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Cache {
+    name: String,
+    entries: Vec<CacheEntry>,
+}
+
+impl Cache {
+    fn query(&self, request: Option<&Request>, options: &CacheQueryOptions) -> Result<Vec<&CacheEntry>, CacheError> {
+        let Some(request) = request else {
+            return Ok(self.entries.iter().collect());
+        };
+        if !options.ignore_method && request.method != Method::Get {
+            return Ok(Vec::new());
+        }
+        let url = parse_url(&request.url)?;
+        Ok(self.entries.iter().filter(|entry| entry.matches(&url, request, options)).collect())
+    }
+}
+
+/// An origin's caches, as stored in its file
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct OriginCaches {
+    origin: String,
+    /// In creation order
+    caches: Vec<Cache>,
+}
+
+impl OriginCaches {
+    fn cache(&self, name: &str) -> Result<&Cache, CacheError> {
+        self.caches.iter().find(|c| c.name == name).ok_or_else(|| CacheError::NotFound(name.to_string()))
+    }
+}
+
+/// Every origin's caches (`self.caches` in a window or worker)
+pub struct CacheStorage {
+    origins: HashMap<String, OriginCaches>,
+    /// Directory holding one file per origin; `None` keeps caches in memory
+    dir: Option<PathBuf>,
+}
+
+impl CacheStorage {
+    /// Create a store that keeps everything in memory
+    pub fn new() -> Self {
+        Self {
+            origins: HashMap::new(),
+            dir: None,
+        }
+    }
+
+    /// Open a store persisted to `dir`, loading the caches saved there
+    ///
+    /// Unreadable origin files are skipped rather than failing the whole store.
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let mut storage = Self::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let Ok(data) = fs::read(&path) else { continue };
+            if let Ok(caches) = serde_json::from_slice::<OriginCaches>(&data) {
+                storage.origins.insert(caches.origin.clone(), caches);
+            }
+        }
+        storage.dir = Some(dir.to_path_buf());
+        Ok(storage)
+    }
+
+    /// `caches.open(name)`: create the cache if it does not exist yet
+    pub fn open_cache(&mut self, origin: &Url, name: &str) -> Result<(), CacheError> {
+        let key = origin_key(origin).ok_or(CacheError::SecurityError)?;
+        let caches = self.origins.entry(key.clone()).or_insert_with(|| OriginCaches { origin: key.clone(), ..Default::default() });
+        if caches.caches.iter().any(|c| c.name == name) {
+            return Ok(());
+        }
+        caches.caches.push(Cache { name: name.to_string(), entries: Vec::new() });
+        self.save(&key)
+    }
+
+    /// `caches.has(name)`
+    pub fn has(&self, origin: &Url, name: &str) -> bool {
+        self.caches(origin).is_ok_and(|caches| caches.cache(name).is_ok())
+    }
+
+    /// `caches.delete(name)`: was there such a cache
+    pub fn delete_cache(&mut self, origin: &Url, name: &str) -> Result<bool, CacheError> {
+        let key = origin_key(origin).ok_or(CacheError::SecurityError)?;
+        let Some(caches) = self.origins.get_mut(&key) else {
+            return Ok(false);
+        };
+        let before = caches.caches.len();
+        caches.caches.retain(|c| c.name != name);
+        if caches.caches.len() == before {
+            return Ok(false);
+        }
+        self.save(&key)?;
+        Ok(true)
+    }
+
+    /// `caches.keys()`: cache names in creation order
+    pub fn cache_names(&self, origin: &Url) -> Vec<String> {
+        self.caches(origin).map_or_else(|_| Vec::new(), |caches| caches.caches.iter().map(|c| c.name.clone()).collect())
+    }
+
+    /// `caches.match(request)`: first match in any cache, oldest cache first
+    pub fn match_request(&self, origin: &Url, request: &Request, options: &CacheQueryOptions) -> Result<Option<Response>, CacheError> {
+        let caches = self.caches(origin)?;
+        if let Some(name) = &options.cache_name {
+            return match caches.cache(name) {
+                Ok(cache) => first_response(cache, request, options),
+                Err(_) => Ok(None),
+            };
+        }
+        for cache in &caches.caches {
+            if let Some(response) = first_response(cache, request, options)? {
+                return Ok(Some(response));
+            }
+        }
+        Ok(None)
+    }
+
+    /// `cache.match(request)`
+    pub fn cache_match(&self, origin: &Url, name: &str, request: &Request, options: &CacheQueryOptions) -> Result<Option<Response>, CacheError> {
+        first_response(self.caches(origin)?.cache(name)?, request, options)
+    }
+
+    /// `cache.matchAll(request)`; every response when `request` is `None`
+    pub fn match_all(&self, origin: &Url, name: &str, request: Option<&Request>, options: &CacheQueryOptions) -> Result<Vec<Response>, CacheError> {
+        let cache = self.caches(origin)?.cache(name)?;
+        Ok(cache.query(request, options)?.into_iter().map(CacheEntry::response).collect())
+    }
+
+    /// `cache.keys(request)`; every request when `request` is `None`
+    pub fn keys(&self, origin: &Url, name: &str, request: Option<&Request>, options: &CacheQueryOptions) -> Result<Vec<Request>, CacheError> {
+        let cache = self.caches(origin)?.cache(name)?;
+        Ok(cache.query(request, options)?.into_iter().map(CacheEntry::request).collect())
+    }
+
+    /// `cache.put(request, response)`, replacing the entries the request matches
+    pub fn put(&mut self, origin: &Url, name: &str, request: &Request, response: &Response) -> Result<(), CacheError> {
+        let url = parse_url(&request.url)?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(CacheError::TypeError(format!("Cannot cache a {} request", url.scheme())));
+        }
+        if request.method != Method::Get {
+            return Err(CacheError::TypeError("Only GET requests can be cached".to_string()));
+        }
+        if response.status == 206 {
+            return Err(CacheError::TypeError("Partial responses cannot be cached".to_string()));
+        }
+        if response.headers.get("vary").is_some_and(|vary| vary.split(',').any(|v| v.trim() == "*")) {
+            return Err(CacheError::TypeError("Responses with 'Vary: *' cannot be cached".to_string()));
+        }
+        if response.body_used() {
+            return Err(CacheError::TypeError("Response body already used".to_string()));
+        }
+
+        let key = origin_key(origin).ok_or(CacheError::SecurityError)?;
+        let cache = self.cache_mut(&key, name)?;
+        let entry = CacheEntry::new(&normalize(url.clone(), false), request, response);
+        cache.entries.retain(|cached| !cached.matches(&url, request, &CacheQueryOptions::default()));
+        cache.entries.push(entry);
+        self.save(&key)
+    }
+
+    /// `cache.add(request)`: fetch the request and store a successful response
+    pub fn add(&mut self, origin: &Url, name: &str, request: &Request, client: &HttpClient) -> Result<(), CacheError> {
+        let url = parse_url(&request.url)?;
+        let fetched = client
+            .fetch_with(&url, &RequestOptions { first_party: Some(origin.clone()), ..Default::default() })
+            .map_err(|e| CacheError::TypeError(e.to_string()))?;
+        if !(200..300).contains(&fetched.status) {
+            return Err(CacheError::TypeError(format!("Fetching {} failed with status {}", url, fetched.status)));
+        }
+
+        let mut headers = Headers::new();
+        headers.set("Content-Type".to_string(), fetched.content_type);
+        if let Some(etag) = fetched.etag {
+            headers.set("ETag".to_string(), etag);
+        }
+        if let Some(date) = fetched.last_modified {
+            headers.set("Last-Modified".to_string(), date);
+        }
+        let response = Response::new(fetched.status, String::new(), headers, fetched.body, fetched.url.to_string());
+        self.put(origin, name, request, &response)
+    }
+
+    /// `cache.delete(request)`: were any entries removed
+    pub fn delete(&mut self, origin: &Url, name: &str, request: &Request, options: &CacheQueryOptions) -> Result<bool, CacheError> {
+        if !options.ignore_method && request.method != Method::Get {
+            return Ok(false);
+        }
+        let url = parse_url(&request.url)?;
+        let key = origin_key(origin).ok_or(CacheError::SecurityError)?;
+        let cache = self.cache_mut(&key, name)?;
+        let before = cache.entries.len();
+        cache.entries.retain(|entry| !entry.matches(&url, request, options));
+        if cache.entries.len() == before {
+            return Ok(false);
+        }
+        self.save(&key)?;
+        Ok(true)
+    }
+
+    /// Bytes stored by an origin's caches
+    pub fn usage(&self, origin: &Url) -> usize {
+        self.caches(origin)
+            .map_or(0, |caches| caches.caches.iter().flat_map(|c| &c.entries).map(CacheEntry::size).sum())
+    }
+
+    /// Origins with caches
+    pub fn origins(&self) -> Vec<String> {
+        self.origins.keys().cloned().collect()
+    }
+
+    /// Delete every cache of an origin, on disk too
+    pub fn clear_origin(&mut self, origin: &Url) -> Result<(), CacheError> {
+        let key = origin_key(origin).ok_or(CacheError::SecurityError)?;
+        self.origins.remove(&key);
+        self.save(&key)
+    }
+
+    fn caches(&self, origin: &Url) -> Result<&OriginCaches, CacheError> {
+        static EMPTY: OriginCaches = OriginCaches { origin: String::new(), caches: Vec::new() };
+        let key = origin_key(origin).ok_or(CacheError::SecurityError)?;
+        Ok(self.origins.get(&key).unwrap_or(&EMPTY))
+    }
+
+    fn cache_mut(&mut self, origin: &str, name: &str) -> Result<&mut Cache, CacheError> {
+        self.origins
+            .get_mut(origin)
+            .and_then(|caches| caches.caches.iter_mut().find(|c| c.name == name))
+            .ok_or_else(|| CacheError::NotFound(name.to_string()))
+    }
+
+    /// Rewrite an origin's file, or remove it once the origin has no caches
+    fn save(&self, origin: &str) -> Result<(), CacheError> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        let path = dir.join(file_name(origin));
+        match self.origins.get(origin).filter(|caches| !caches.caches.is_empty()) {
+            Some(caches) => {
+                // Write then rename, so a crash never leaves half a file
+                let temp = path.with_extension("tmp");
+                fs::write(&temp, serde_json::to_vec(caches).map_err(|e| CacheError::Io(e.to_string()))?)?;
+                fs::rename(&temp, &path)?;
+            }
+            None => match fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            },
+        }
+        Ok(())
+    }
+}
+
+impl Default for CacheStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn first_response(cache: &Cache, request: &Request, options: &CacheQueryOptions) -> Result<Option<Response>, CacheError> {
+    Ok(cache.query(Some(request), options)?.first().map(|entry| entry.response()))
+}
+
+fn parse_url(url: &str) -> Result<Url, CacheError> {
+    Url::parse(url).map_err(|e| CacheError::TypeError(format!("Invalid URL '{}': {}", url, e)))
+}
+
+/// URL as compared by the cache: no fragment, and no query with `ignore_search`
+fn normalize(mut url: Url, ignore_search: bool) -> Url {
+    url.set_fragment(None);
+    if ignore_search {
+        url.set_query(None);
+    }
+    url
+}
+
+fn headers(entries: &[(String, String)]) -> Headers {
+    let mut headers = Headers::new();
+    for (name, value) in entries {
+        headers.set(name.clone(), value.clone());
+    }
+    headers
+}
+
+/// File holding an origin's caches, e.g. "https_3a_2f_2fexample.com.json"
+fn file_name(origin: &str) -> String {
+    let mut name = String::new();
+    for byte in origin.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'.' || byte == b'-' {
+            name.push(byte as char);
+        } else {
+            name.push_str(&format!("_{:02x}", byte));
+        }
+    }
+    name + ".json"
+}
+
+/// Bodies are stored as hex strings rather than JSON arrays of numbers
+mod hex_body {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(body: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let hex: String = body.iter().map(|b| format!("{:02x}", b)).collect();
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        if hex.len() % 2 != 0 {
+            return Err(serde::de::Error::custom("odd hex length"));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(serde::de::Error::custom))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    fn get(url: &str) -> Request {
+        Request::new(url.to_string(), Method::Get)
+    }
+
+    fn response(body: &str) -> Response {
+        Response::new(200, "OK".to_string(), Headers::new(), body.as_bytes().to_vec(), String::new())
+    }
+
+    fn body(response: Option<Response>) -> Option<String> {
+        response.map(|r| String::from_utf8(r.body().to_vec()).unwrap())
+    }
+
+    #[test]
+    fn test_open_put_and_match() {
+        let origin = url("https://example.com/app/");
+        let mut storage = CacheStorage::new();
+        let options = CacheQueryOptions::default();
+        assert_eq!(storage.put(&origin, "v1", &get("https://example.com/a"), &response("a")), Err(CacheError::NotFound("v1".to_string())));
+
+        storage.open_cache(&origin, "v1").unwrap();
+        storage.open_cache(&origin, "v2").unwrap();
+        storage.open_cache(&origin, "v1").unwrap();
+        assert_eq!(storage.cache_names(&origin), vec!["v1".to_string(), "v2".to_string()]);
+
+        storage.put(&origin, "v2", &get("https://example.com/a"), &response("from v2")).unwrap();
+        storage.put(&origin, "v1", &get("https://example.com/a#top"), &response("old")).unwrap();
+        storage.put(&origin, "v1", &get("https://example.com/a"), &response("from v1")).unwrap();
+
+        // Fragments do not count, so the second put replaced the first
+        assert_eq!(storage.keys(&origin, "v1", None, &options).unwrap().len(), 1);
+        assert_eq!(body(storage.match_request(&origin, &get("https://example.com/a"), &options).unwrap()), Some("from v1".to_string()));
+        let only_v2 = CacheQueryOptions { cache_name: Some("v2".to_string()), ..Default::default() };
+        assert_eq!(body(storage.match_request(&origin, &get("https://example.com/a"), &only_v2).unwrap()), Some("from v2".to_string()));
+
+        // Other origins see nothing
+        assert!(storage.cache_names(&url("https://other.com/")).is_empty());
+        assert!(!storage.has(&url("https://other.com/"), "v1"));
+
+        assert_eq!(storage.delete_cache(&origin, "v1"), Ok(true));
+        assert_eq!(storage.delete_cache(&origin, "v1"), Ok(false));
+        assert_eq!(body(storage.match_request(&origin, &get("https://example.com/a"), &options).unwrap()), Some("from v2".to_string()));
+    }
+
+    #[test]
+    fn test_ignore_search_and_method() {
+        let origin = url("https://example.com/");
+        let mut storage = CacheStorage::new();
+        storage.open_cache(&origin, "c").unwrap();
+        storage.put(&origin, "c", &get("https://example.com/list?page=1"), &response("1")).unwrap();
+        storage.put(&origin, "c", &get("https://example.com/list?page=2"), &response("2")).unwrap();
+
+        let exact = CacheQueryOptions::default();
+        assert!(storage.cache_match(&origin, "c", &get("https://example.com/list"), &exact).unwrap().is_none());
+        let ignore_search = CacheQueryOptions { ignore_search: true, ..Default::default() };
+        assert_eq!(storage.match_all(&origin, "c", Some(&get("https://example.com/list")), &ignore_search).unwrap().len(), 2);
+
+        let post = Request::new("https://example.com/list?page=1".to_string(), Method::Post);
+        assert!(storage.cache_match(&origin, "c", &post, &exact).unwrap().is_none());
+        let ignore_method = CacheQueryOptions { ignore_method: true, ..Default::default() };
+        assert_eq!(body(storage.cache_match(&origin, "c", &post, &ignore_method).unwrap()), Some("1".to_string()));
+
+        assert_eq!(storage.delete(&origin, "c", &get("https://example.com/list"), &ignore_search), Ok(true));
+        assert!(storage.keys(&origin, "c", None, &exact).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_vary_header() {
+        let origin = url("https://example.com/");
+        let mut storage = CacheStorage::new();
+        storage.open_cache(&origin, "c").unwrap();
+
+        let mut english = get("https://example.com/doc");
+        english.headers.set("Accept-Language".to_string(), "en".to_string());
+        let mut french = get("https://example.com/doc");
+        french.headers.set("Accept-Language".to_string(), "fr".to_string());
+        let mut varies = response("hello");
+        varies.headers.set("Vary".to_string(), "Accept-Language".to_string());
+        storage.put(&origin, "c", &english, &varies).unwrap();
+
+        let options = CacheQueryOptions::default();
+        assert!(storage.cache_match(&origin, "c", &english, &options).unwrap().is_some());
+        assert!(storage.cache_match(&origin, "c", &french, &options).unwrap().is_none());
+        let ignore_vary = CacheQueryOptions { ignore_vary: true, ..Default::default() };
+        assert!(storage.cache_match(&origin, "c", &french, &ignore_vary).unwrap().is_some());
+
+        // A response for another language is stored alongside
+        storage.put(&origin, "c", &french, &varies).unwrap();
+        assert_eq!(storage.keys(&origin, "c", None, &options).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_put_rejects_uncacheable() {
+        let origin = url("https://example.com/");
+        let mut storage = CacheStorage::new();
+        storage.open_cache(&origin, "c").unwrap();
+
+        let post = Request::new("https://example.com/".to_string(), Method::Post);
+        assert!(matches!(storage.put(&origin, "c", &post, &response("")), Err(CacheError::TypeError(_))));
+        assert!(matches!(storage.put(&origin, "c", &get("data:text/plain,hi"), &response("")), Err(CacheError::TypeError(_))));
+        let mut partial = response("");
+        partial.status = 206;
+        assert!(matches!(storage.put(&origin, "c", &get("https://example.com/"), &partial), Err(CacheError::TypeError(_))));
+        let mut vary_all = response("");
+        vary_all.headers.set("Vary".to_string(), "*".to_string());
+        assert!(matches!(storage.put(&origin, "c", &get("https://example.com/"), &vary_all), Err(CacheError::TypeError(_))));
+
+        assert_eq!(storage.open_cache(&url("data:text/plain,hi"), "c"), Err(CacheError::SecurityError));
+    }
+
+    #[test]
+    fn test_caches_persist_per_origin() {
+        let dir = std::env::temp_dir().join(format!("cache-storage-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let origin = url("https://example.com:8443/");
+        let other = url("http://other.org/");
+        {
+            let mut storage = CacheStorage::open(&dir).unwrap();
+            storage.open_cache(&origin, "static").unwrap();
+            let mut varies = Response::new(200, "OK".to_string(), Headers::new(), vec![0, 255, 10], String::new());
+            varies.headers.set("Content-Type".to_string(), "application/octet-stream".to_string());
+            storage.put(&origin, "static", &get("https://example.com:8443/bin"), &varies).unwrap();
+            storage.open_cache(&other, "x").unwrap();
+            storage.clear_origin(&other).unwrap();
+        }
+
+        let storage = CacheStorage::open(&dir).unwrap();
+        assert_eq!(storage.origins(), vec!["https://example.com:8443".to_string()]);
+        let cached = storage.cache_match(&origin, "static", &get("https://example.com:8443/bin"), &CacheQueryOptions::default()).unwrap().unwrap();
+        assert_eq!(cached.body(), &[0, 255, 10]);
+        assert_eq!(cached.headers.get("content-type"), Some("application/octet-stream"));
+        assert_eq!(storage.usage(&origin), "https://example.com:8443/bin".len() + 3);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
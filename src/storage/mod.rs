@@ -0,0 +1,1069 @@
+// Storage APIs - Phase 7 Task 4
+
+mod cache_storage;
+mod site_data;
+mod web_storage;
+
+use crate::indexeddb::IDBFactory;
+use crate::net::PartitionKey;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, SystemTime};
+use url::Url;
+
+pub use cache_storage::{CacheError, CacheQueryOptions, CacheStorage};
+pub use site_data::{NetworkSiteData, SiteDataTypes, StorageEstimate, UsageDetails, GLOBAL_QUOTA, ORIGIN_QUOTA};
+pub use web_storage::{DocumentId, SessionId, WebStorage};
+
+/// Storage quota limit (5MB for localStorage, 5MB for sessionStorage)
+pub const STORAGE_QUOTA: usize = 5 * 1024 * 1024;
+
+/// LocalStorage - persistent key-value storage
+pub struct LocalStorage {
+    /// Data store
+    data: HashMap<String, String>,
+    /// Current size in bytes
+    size: usize,
+    /// Storage quota
+    quota: usize,
+}
+
+impl LocalStorage {
+    /// Create a new LocalStorage
+    pub fn new() -> Self {
+        Self {
+            data: HashMap::new(),
+            size: 0,
+            quota: STORAGE_QUOTA,
+        }
+    }
+    
+    /// Set an item
+    pub fn set_item(&mut self, key: String, value: String) -> Result<(), StorageError> {
+        let key_size = key.as_bytes().len();
+        let value_size = value.as_bytes().len();
+        let new_size = key_size + value_size;
+        
+        // Check if key exists to calculate size difference
+        let existing_size = self.data.get(&key)
+            .map(|v| key.as_bytes().len() + v.as_bytes().len())
+            .unwrap_or(0);
+        
+        let size_delta = new_size as i64 - existing_size as i64;
+        let new_total_size = (self.size as i64 + size_delta) as usize;
+        
+        if new_total_size > self.quota {
+            return Err(StorageError::QuotaExceeded);
+        }
+        
+        self.data.insert(key, value);
+        self.size = new_total_size;
+        
+        Ok(())
+    }
+    
+    /// Get an item
+    pub fn get_item(&self, key: &str) -> Option<String> {
+        self.data.get(key).cloned()
+    }
+    
+    /// Remove an item
+    pub fn remove_item(&mut self, key: &str) -> Option<String> {
+        if let Some(value) = self.data.remove(key) {
+            let size = key.as_bytes().len() + value.as_bytes().len();
+            self.size = self.size.saturating_sub(size);
+            Some(value)
+        } else {
+            None
+        }
+    }
+    
+    /// Clear all items
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.size = 0;
+    }
+    
+    /// Get number of items
+    pub fn length(&self) -> usize {
+        self.data.len()
+    }
+    
+    /// Get key at index
+    pub fn key(&self, index: usize) -> Option<String> {
+        self.data.keys().nth(index).cloned()
+    }
+    
+    /// Get current size in bytes
+    pub fn size(&self) -> usize {
+        self.size
+    }
+    
+    /// Get remaining quota
+    pub fn remaining_quota(&self) -> usize {
+        self.quota.saturating_sub(self.size)
+    }
+    
+    /// Change the quota; items already stored are kept
+    pub fn set_quota(&mut self, quota: usize) {
+        self.quota = quota;
+    }
+    
+    /// Iterate over the stored items
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.data.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+impl Default for LocalStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// SessionStorage - per-tab key-value storage (cleared on tab close)
+pub struct SessionStorage {
+    /// Data store
+    data: HashMap<String, String>,
+    /// Current size in bytes
+    size: usize,
+    /// Storage quota
+    quota: usize,
+}
+
+impl SessionStorage {
+    /// Create a new SessionStorage
+    pub fn new() -> Self {
+        Self {
+            data: HashMap::new(),
+            size: 0,
+            quota: STORAGE_QUOTA,
+        }
+    }
+    
+    /// Set an item
+    pub fn set_item(&mut self, key: String, value: String) -> Result<(), StorageError> {
+        let key_size = key.as_bytes().len();
+        let value_size = value.as_bytes().len();
+        let new_size = key_size + value_size;
+        
+        let existing_size = self.data.get(&key)
+            .map(|v| key.as_bytes().len() + v.as_bytes().len())
+            .unwrap_or(0);
+        
+        let size_delta = new_size as i64 - existing_size as i64;
+        let new_total_size = (self.size as i64 + size_delta) as usize;
+        
+        if new_total_size > self.quota {
+            return Err(StorageError::QuotaExceeded);
+        }
+        
+        self.data.insert(key, value);
+        self.size = new_total_size;
+        
+        Ok(())
+    }
+    
+    /// Get an item
+    pub fn get_item(&self, key: &str) -> Option<String> {
+        self.data.get(key).cloned()
+    }
+    
+    /// Remove an item
+    pub fn remove_item(&mut self, key: &str) -> Option<String> {
+        if let Some(value) = self.data.remove(key) {
+            let size = key.as_bytes().len() + value.as_bytes().len();
+            self.size = self.size.saturating_sub(size);
+            Some(value)
+        } else {
+            None
+        }
+    }
+    
+    /// Clear all items
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.size = 0;
+    }
+    
+    /// Get number of items
+    pub fn length(&self) -> usize {
+        self.data.len()
+    }
+    
+    /// Get key at index
+    pub fn key(&self, index: usize) -> Option<String> {
+        self.data.keys().nth(index).cloned()
+    }
+    
+    /// Get current size in bytes
+    pub fn size(&self) -> usize {
+        self.size
+    }
+    
+    /// Change the quota; items already stored are kept
+    pub fn set_quota(&mut self, quota: usize) {
+        self.quota = quota;
+    }
+}
+
+impl Default for SessionStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Cookie with attributes
+#[derive(Debug, Clone)]
+pub struct Cookie {
+    /// Cookie name
+    pub name: String,
+    /// Cookie value
+    pub value: String,
+    /// Expiration time
+    pub expires: Option<SystemTime>,
+    /// Max age in seconds
+    pub max_age: Option<Duration>,
+    /// Domain
+    pub domain: Option<String>,
+    /// Path
+    pub path: String,
+    /// Secure flag
+    pub secure: bool,
+    /// HttpOnly flag
+    pub http_only: bool,
+    /// SameSite attribute
+    pub same_site: SameSite,
+    /// Top-level site the cookie was set under by a third party; it is
+    /// only sent to that site's subresources
+    pub partition: Option<PartitionKey>,
+}
+
+/// SameSite attribute values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl Cookie {
+    /// Create a new cookie
+    pub fn new(name: String, value: String) -> Self {
+        Self {
+            name,
+            value,
+            expires: None,
+            max_age: None,
+            domain: None,
+            path: "/".to_string(),
+            secure: false,
+            http_only: false,
+            same_site: SameSite::Lax,
+            partition: None,
+        }
+    }
+    
+    /// Check if cookie is expired
+    pub fn is_expired(&self) -> bool {
+        if let Some(expires) = self.expires {
+            SystemTime::now() > expires
+        } else if let Some(max_age) = self.max_age {
+            // Would need creation time to check max_age properly
+            // For simplicity, we'll just use expires
+            false
+        } else {
+            false
+        }
+    }
+    
+    /// Check if cookie matches domain
+    pub fn matches_domain(&self, domain: &str) -> bool {
+        if let Some(cookie_domain) = &self.domain {
+            domain.ends_with(cookie_domain)
+        } else {
+            true
+        }
+    }
+    
+    /// Is the cookie sent to `host`, or set for one of its subdomains
+    pub fn belongs_to_host(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        match &self.domain {
+            Some(domain) => {
+                self.matches_domain(&host) || domain.strip_suffix(host.as_str()).is_some_and(|sub| sub.ends_with('.'))
+            }
+            None => true,
+        }
+    }
+    
+    /// Check if cookie matches path
+    pub fn matches_path(&self, path: &str) -> bool {
+        path.starts_with(&self.path)
+    }
+
+    /// Parse a `Set-Cookie` header received from `host`
+    ///
+    /// Cookies without a `Domain` attribute are scoped to the host that
+    /// set them. Returns `None` for malformed headers.
+    pub fn parse(header: &str, host: &str) -> Option<Self> {
+        let mut parts = header.split(';');
+        let (name, value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut cookie = Cookie::new(name.to_string(), value.trim().trim_matches('"').to_string());
+        cookie.domain = Some(host.to_ascii_lowercase());
+        for attribute in parts {
+            let (key, value) = match attribute.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => (attribute.trim(), ""),
+            };
+            match key.to_ascii_lowercase().as_str() {
+                "domain" if !value.is_empty() => {
+                    cookie.domain = Some(value.trim_start_matches('.').to_ascii_lowercase());
+                }
+                "path" if value.starts_with('/') => cookie.path = value.to_string(),
+                "max-age" => {
+                    if let Ok(seconds) = value.parse::<i64>() {
+                        let seconds = Duration::from_secs(seconds.max(0) as u64);
+                        cookie.max_age = Some(seconds);
+                        cookie.expires = Some(SystemTime::now() + seconds);
+                    }
+                }
+                "secure" => cookie.secure = true,
+                "httponly" => cookie.http_only = true,
+                "samesite" => {
+                    cookie.same_site = match value.to_ascii_lowercase().as_str() {
+                        "strict" => SameSite::Strict,
+                        "none" => SameSite::None,
+                        _ => SameSite::Lax,
+                    };
+                }
+                _ => {}
+            }
+        }
+        Some(cookie)
+    }
+}
+
+/// Cookie jar for managing cookies
+pub struct CookieJar {
+    /// Cookies by partition and name
+    cookies: HashMap<(Option<PartitionKey>, String), Cookie>,
+}
+
+impl CookieJar {
+    /// Create a new cookie jar
+    pub fn new() -> Self {
+        Self {
+            cookies: HashMap::new(),
+        }
+    }
+    
+    /// Set a cookie, in its partition
+    pub fn set_cookie(&mut self, cookie: Cookie) {
+        self.cookies.insert((cookie.partition.clone(), cookie.name.clone()), cookie);
+    }
+    
+    /// Get an unpartitioned cookie by name
+    pub fn get_cookie(&self, name: &str) -> Option<&Cookie> {
+        let cookie = self.cookies.get(&(None, name.to_string()))?;
+        if cookie.is_expired() {
+            return None;
+        }
+        Some(cookie)
+    }
+    
+    /// Get all cookies for a domain and path in a partition
+    ///
+    /// `partition` is `None` for requests made by the site's own pages.
+    pub fn get_cookies_for_request(
+        &self,
+        domain: &str,
+        path: &str,
+        secure: bool,
+        partition: Option<&PartitionKey>,
+    ) -> Vec<&Cookie> {
+        self.cookies
+            .values()
+            .filter(|c| {
+                !c.is_expired() &&
+                c.partition.as_ref() == partition &&
+                c.matches_domain(domain) &&
+                c.matches_path(path) &&
+                (!c.secure || secure)
+            })
+            .collect()
+    }
+    
+    /// Value for a request's `Cookie` header, if any cookies apply
+    pub fn cookie_header(
+        &self,
+        domain: &str,
+        path: &str,
+        secure: bool,
+        partition: Option<&PartitionKey>,
+    ) -> Option<String> {
+        let mut cookies = self.get_cookies_for_request(domain, path, secure, partition);
+        if cookies.is_empty() {
+            return None;
+        }
+        cookies.sort_by(|a, b| a.name.cmp(&b.name));
+        Some(
+            cookies
+                .iter()
+                .map(|c| format!("{}={}", c.name, c.value))
+                .collect::<Vec<_>>()
+                .join("; "),
+        )
+    }
+
+    /// Remove an unpartitioned cookie
+    pub fn remove_cookie(&mut self, name: &str) -> Option<Cookie> {
+        self.cookies.remove(&(None, name.to_string()))
+    }
+    
+    /// Clear all cookies
+    pub fn clear(&mut self) {
+        self.cookies.clear();
+    }
+    
+    /// Remove expired cookies
+    pub fn purge_expired(&mut self) {
+        self.cookies.retain(|_, cookie| !cookie.is_expired());
+    }
+    
+    /// Get all cookie names, of every partition
+    pub fn names(&self) -> Vec<String> {
+        self.cookies.values().map(|c| c.name.clone()).collect()
+    }
+
+    /// Bytes of cookies for a host and its subdomains, in every partition
+    pub fn size_for_host(&self, host: &str) -> usize {
+        self.cookies
+            .values()
+            .filter(|c| c.belongs_to_host(host))
+            .map(|c| c.name.len() + c.value.len())
+            .sum()
+    }
+
+    /// Remove the cookies for a host and its subdomains from every
+    /// partition; returns how many
+    pub fn remove_for_host(&mut self, host: &str) -> usize {
+        let before = self.cookies.len();
+        self.cookies.retain(|_, c| !c.belongs_to_host(host));
+        before - self.cookies.len()
+    }
+}
+
+impl Default for CookieJar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Storage key for a URL's origin; opaque origins have none
+fn origin_key(url: &Url) -> Option<String> {
+    let origin = url.origin();
+    origin.is_tuple().then(|| origin.ascii_serialization())
+}
+
+/// Storage error types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageError {
+    /// Quota exceeded
+    QuotaExceeded,
+    /// Invalid key
+    InvalidKey,
+    /// Invalid value
+    InvalidValue,
+    /// Storage is not available to the document (opaque or unknown origin)
+    SecurityError,
+    /// Writing the change to disk failed
+    Io,
+}
+
+impl std::fmt::Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            StorageError::QuotaExceeded => write!(f, "Storage quota exceeded"),
+            StorageError::InvalidKey => write!(f, "Invalid storage key"),
+            StorageError::InvalidValue => write!(f, "Invalid storage value"),
+            StorageError::SecurityError => write!(f, "Storage is not available to this document"),
+            StorageError::Io => write!(f, "Failed to write storage to disk"),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+/// Storage event (fired when storage changes)
+#[derive(Debug, Clone)]
+pub struct StorageEvent {
+    /// Storage key
+    pub key: Option<String>,
+    /// Old value
+    pub old_value: Option<String>,
+    /// New value
+    pub new_value: Option<String>,
+    /// Storage area type
+    pub storage_area: StorageArea,
+    /// Address of the document that made the change
+    pub url: Option<Url>,
+}
+
+/// Storage area type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageArea {
+    Local,
+    Session,
+}
+
+/// Storage manager coordinating all storage types
+pub struct StorageManager {
+    /// LocalStorage instance
+    local_storage: LocalStorage,
+    /// SessionStorage instance
+    session_storage: SessionStorage,
+    /// Cookie jar
+    cookie_jar: CookieJar,
+    /// Per-origin localStorage and sessionStorage for web pages
+    web_storage: WebStorage,
+    /// Per-origin Cache API caches
+    cache_storage: CacheStorage,
+    /// IndexedDB databases by origin
+    indexed_db: HashMap<String, IDBFactory>,
+    /// HTTP cache and cookies of the network stack
+    network: Option<Box<dyn NetworkSiteData>>,
+    /// Bytes all origins together may store
+    global_quota: usize,
+    /// Bytes one origin may store
+    origin_quota: usize,
+    /// When each origin last used its storage, for LRU eviction
+    last_used: HashMap<String, u64>,
+    /// Ticks on every use, ordering `last_used`
+    clock: u64,
+    /// Origins never evicted (`navigator.storage.persist()`)
+    persisted: HashSet<String>,
+    /// Storage event listeners
+    event_listeners: Vec<Box<dyn Fn(&StorageEvent)>>,
+}
+
+impl StorageManager {
+    /// Create a new storage manager
+    pub fn new() -> Self {
+        Self {
+            local_storage: LocalStorage::new(),
+            session_storage: SessionStorage::new(),
+            cookie_jar: CookieJar::new(),
+            web_storage: WebStorage::new(),
+            cache_storage: CacheStorage::new(),
+            indexed_db: HashMap::new(),
+            network: None,
+            global_quota: GLOBAL_QUOTA,
+            origin_quota: ORIGIN_QUOTA,
+            last_used: HashMap::new(),
+            clock: 0,
+            persisted: HashSet::new(),
+            event_listeners: Vec::new(),
+        }
+    }
+    
+    /// Get LocalStorage
+    pub fn local_storage(&mut self) -> &mut LocalStorage {
+        &mut self.local_storage
+    }
+    
+    /// Get SessionStorage
+    pub fn session_storage(&mut self) -> &mut SessionStorage {
+        &mut self.session_storage
+    }
+    
+    /// Get CookieJar
+    pub fn cookie_jar(&mut self) -> &mut CookieJar {
+        &mut self.cookie_jar
+    }
+    
+    /// Get the web pages' localStorage and sessionStorage
+    pub fn web_storage(&mut self) -> &mut WebStorage {
+        &mut self.web_storage
+    }
+    
+    /// Replace the web storage, e.g. with one persisted to disk
+    pub fn set_web_storage(&mut self, web_storage: WebStorage) {
+        self.web_storage = web_storage;
+    }
+    
+    /// Get the web pages' Cache API caches
+    pub fn cache_storage(&mut self) -> &mut CacheStorage {
+        &mut self.cache_storage
+    }
+    
+    /// Replace the Cache API storage, e.g. with one persisted to disk
+    pub fn set_cache_storage(&mut self, cache_storage: CacheStorage) {
+        self.cache_storage = cache_storage;
+    }
+    
+    /// Get an origin's IndexedDB databases
+    pub fn indexed_db(&mut self, url: &Url) -> Result<&IDBFactory, StorageError> {
+        let origin = origin_key(url).ok_or(StorageError::SecurityError)?;
+        self.touch(url);
+        Ok(self.indexed_db.entry(origin).or_default())
+    }
+    
+    /// Let the manager report and clear the network stack's cookies and HTTP cache
+    pub fn set_network_data(&mut self, network: Box<dyn NetworkSiteData>) {
+        self.network = Some(network);
+    }
+    
+    /// Change the global and per-origin quotas; nothing is evicted until the next `reserve`
+    pub fn set_quotas(&mut self, global: usize, per_origin: usize) {
+        self.global_quota = global;
+        self.origin_quota = per_origin;
+    }
+    
+    /// Record that an origin used its storage, keeping it from eviction a while longer
+    pub fn touch(&mut self, url: &Url) {
+        if let Some(origin) = origin_key(url) {
+            self.clock += 1;
+            self.last_used.insert(origin, self.clock);
+        }
+    }
+    
+    /// Origins with stored data, sorted
+    pub fn origins(&self) -> Vec<String> {
+        let mut origins: Vec<String> = self.web_storage.origins()
+            .into_iter()
+            .chain(self.cache_storage.origins())
+            .chain(self.indexed_db.iter().filter(|(_, f)| !f.databases().is_empty()).map(|(o, _)| o.clone()))
+            .collect();
+        origins.sort();
+        origins.dedup();
+        origins
+    }
+    
+    /// Bytes an origin stores, by kind
+    pub fn usage_details(&self, url: &Url) -> UsageDetails {
+        let Some(origin) = origin_key(url) else {
+            return UsageDetails::default();
+        };
+        let host = url.host_str().unwrap_or("");
+        UsageDetails {
+            local_storage: self.web_storage.usage(url),
+            indexed_db: self.indexed_db.get(&origin).map_or(0, IDBFactory::size),
+            caches: self.cache_storage.usage(url),
+            cookies: self.cookie_jar.size_for_host(host) + self.network.as_ref().map_or(0, |n| n.cookie_usage(host)),
+            http_cache: self.network.as_ref().map_or(0, |n| n.http_cache_usage(url)),
+        }
+    }
+    
+    /// Bytes all origins store against the global quota
+    pub fn total_usage(&self) -> usize {
+        self.origins().iter().map(|origin| self.origin_usage(origin)).sum()
+    }
+    
+    /// `navigator.storage.estimate()` for a document's origin
+    pub fn estimate(&self, url: &Url) -> Result<StorageEstimate, StorageError> {
+        origin_key(url).ok_or(StorageError::SecurityError)?;
+        let details = self.usage_details(url);
+        let usage = details.quota_usage();
+        // What the other origins leave of the global quota
+        let available = self.global_quota.saturating_sub(self.total_usage().saturating_sub(usage));
+        Ok(StorageEstimate {
+            usage,
+            quota: self.origin_quota.min(available),
+            details,
+        })
+    }
+    
+    /// Make room for an origin to store `bytes` more
+    ///
+    /// Fails with `QuotaExceeded` past the origin's quota. Past the global
+    /// quota, other origins are evicted, least recently used first, until
+    /// the write fits; persisted origins are never evicted. Returns the
+    /// evicted origins.
+    pub fn reserve(&mut self, url: &Url, bytes: usize) -> Result<Vec<String>, StorageError> {
+        let origin = origin_key(url).ok_or(StorageError::SecurityError)?;
+        self.touch(url);
+        if self.origin_usage(&origin) + bytes > self.origin_quota {
+            return Err(StorageError::QuotaExceeded);
+        }
+    
+        let mut evicted = Vec::new();
+        let mut total = self.total_usage();
+        while total + bytes > self.global_quota {
+            let victim = self.origins()
+                .into_iter()
+                .filter(|o| *o != origin && !self.persisted.contains(o))
+                .min_by_key(|o| self.last_used.get(o).copied().unwrap_or(0));
+            let Some(victim) = victim else {
+                return Err(StorageError::QuotaExceeded);
+            };
+            total -= self.origin_usage(&victim);
+            if let Ok(victim_url) = Url::parse(&victim) {
+                self.clear_site_data(&victim_url, SiteDataTypes::storage())?;
+            }
+            evicted.push(victim);
+        }
+        Ok(evicted)
+    }
+    
+    /// `navigator.storage.persist()`: keep the origin's data from eviction
+    pub fn persist(&mut self, url: &Url) -> bool {
+        match origin_key(url) {
+            Some(origin) => {
+                self.persisted.insert(origin);
+                true
+            }
+            None => false,
+        }
+    }
+    
+    /// `navigator.storage.persisted()`
+    pub fn is_persisted(&self, url: &Url) -> bool {
+        origin_key(url).is_some_and(|origin| self.persisted.contains(&origin))
+    }
+    
+    /// Clear data for a site, as from the UI or devtools
+    pub fn clear_site_data(&mut self, url: &Url, types: SiteDataTypes) -> Result<(), StorageError> {
+        let origin = origin_key(url).ok_or(StorageError::SecurityError)?;
+        if types.storage {
+            self.web_storage.clear_origin(url)?;
+            self.cache_storage.clear_origin(url).map_err(|_| StorageError::Io)?;
+            self.indexed_db.remove(&origin);
+            self.last_used.remove(&origin);
+            self.persisted.remove(&origin);
+        }
+        if types.cookies {
+            let host = url.host_str().unwrap_or("");
+            self.cookie_jar.remove_for_host(host);
+            if let Some(network) = &self.network {
+                network.clear_cookies(host);
+            }
+        }
+        if types.cache {
+            if let Some(network) = &self.network {
+                network.clear_http_cache(url);
+            }
+        }
+        Ok(())
+    }
+    
+    /// Bytes an origin (by key) stores against the quotas
+    fn origin_usage(&self, origin: &str) -> usize {
+        Url::parse(origin).map_or(0, |url| self.usage_details(&url).quota_usage())
+    }
+    
+    /// Add storage event listener
+    pub fn add_storage_listener<F>(&mut self, listener: F)
+    where
+        F: Fn(&StorageEvent) + 'static,
+    {
+        self.event_listeners.push(Box::new(listener));
+    }
+    
+    /// Fire storage event
+    pub fn fire_storage_event(&self, event: StorageEvent) {
+        for listener in &self.event_listeners {
+            listener(&event);
+        }
+    }
+    
+    /// Set item in LocalStorage and fire event
+    pub fn set_local_item(&mut self, key: String, value: String) -> Result<(), StorageError> {
+        let old_value = self.local_storage.get_item(&key);
+        self.local_storage.set_item(key.clone(), value.clone())?;
+        
+        self.fire_storage_event(StorageEvent {
+            key: Some(key),
+            old_value,
+            new_value: Some(value),
+            storage_area: StorageArea::Local,
+            url: None,
+        });
+        
+        Ok(())
+    }
+    
+    /// Set item in SessionStorage and fire event
+    pub fn set_session_item(&mut self, key: String, value: String) -> Result<(), StorageError> {
+        let old_value = self.session_storage.get_item(&key);
+        self.session_storage.set_item(key.clone(), value.clone())?;
+        
+        self.fire_storage_event(StorageEvent {
+            key: Some(key),
+            old_value,
+            new_value: Some(value),
+            storage_area: StorageArea::Session,
+            url: None,
+        });
+        
+        Ok(())
+    }
+}
+
+impl Default for StorageManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_local_storage_basic() {
+        let mut storage = LocalStorage::new();
+        
+        storage.set_item("key1".to_string(), "value1".to_string()).unwrap();
+        assert_eq!(storage.get_item("key1"), Some("value1".to_string()));
+        assert_eq!(storage.length(), 1);
+    }
+    
+    #[test]
+    fn test_local_storage_update() {
+        let mut storage = LocalStorage::new();
+        
+        storage.set_item("key1".to_string(), "value1".to_string()).unwrap();
+        storage.set_item("key1".to_string(), "value2".to_string()).unwrap();
+        
+        assert_eq!(storage.get_item("key1"), Some("value2".to_string()));
+        assert_eq!(storage.length(), 1);
+    }
+    
+    #[test]
+    fn test_local_storage_remove() {
+        let mut storage = LocalStorage::new();
+        
+        storage.set_item("key1".to_string(), "value1".to_string()).unwrap();
+        let removed = storage.remove_item("key1");
+        
+        assert_eq!(removed, Some("value1".to_string()));
+        assert_eq!(storage.get_item("key1"), None);
+        assert_eq!(storage.length(), 0);
+    }
+    
+    #[test]
+    fn test_local_storage_clear() {
+        let mut storage = LocalStorage::new();
+        
+        storage.set_item("key1".to_string(), "value1".to_string()).unwrap();
+        storage.set_item("key2".to_string(), "value2".to_string()).unwrap();
+        storage.clear();
+        
+        assert_eq!(storage.length(), 0);
+        assert_eq!(storage.size(), 0);
+    }
+    
+    #[test]
+    fn test_local_storage_quota() {
+        let mut storage = LocalStorage::new();
+        
+        // Try to exceed quota
+        let large_value = "x".repeat(6 * 1024 * 1024); // 6MB
+        let result = storage.set_item("key".to_string(), large_value);
+        
+        assert_eq!(result, Err(StorageError::QuotaExceeded));
+    }
+    
+    #[test]
+    fn test_session_storage() {
+        let mut storage = SessionStorage::new();
+        
+        storage.set_item("key1".to_string(), "value1".to_string()).unwrap();
+        assert_eq!(storage.get_item("key1"), Some("value1".to_string()));
+        
+        storage.clear();
+        assert_eq!(storage.length(), 0);
+    }
+    
+    #[test]
+    fn test_cookie_basic() {
+        let cookie = Cookie::new("name".to_string(), "value".to_string());
+        
+        assert_eq!(cookie.name, "name");
+        assert_eq!(cookie.value, "value");
+        assert!(!cookie.is_expired());
+    }
+    
+    #[test]
+    fn test_cookie_jar() {
+        let mut jar = CookieJar::new();
+        
+        let cookie = Cookie::new("session_id".to_string(), "abc123".to_string());
+        jar.set_cookie(cookie);
+        
+        assert!(jar.get_cookie("session_id").is_some());
+        assert_eq!(jar.get_cookie("session_id").unwrap().value, "abc123");
+    }
+    
+    #[test]
+    fn test_cookie_domain_matching() {
+        let mut cookie = Cookie::new("test".to_string(), "value".to_string());
+        cookie.domain = Some("example.com".to_string());
+        
+        assert!(cookie.matches_domain("example.com"));
+        assert!(cookie.matches_domain("sub.example.com"));
+        assert!(!cookie.matches_domain("other.com"));
+    }
+    
+    #[test]
+    fn test_cookie_path_matching() {
+        let mut cookie = Cookie::new("test".to_string(), "value".to_string());
+        cookie.path = "/api".to_string();
+        
+        assert!(cookie.matches_path("/api"));
+        assert!(cookie.matches_path("/api/users"));
+        assert!(!cookie.matches_path("/other"));
+    }
+    
+    #[test]
+    fn test_storage_manager() {
+        let mut manager = StorageManager::new();
+        
+        manager.set_local_item("key1".to_string(), "value1".to_string()).unwrap();
+        manager.set_session_item("key2".to_string(), "value2".to_string()).unwrap();
+        
+        assert_eq!(manager.local_storage().get_item("key1"), Some("value1".to_string()));
+        assert_eq!(manager.session_storage().get_item("key2"), Some("value2".to_string()));
+    }
+    
+    #[test]
+    fn test_cookie_jar_request_filtering() {
+        let mut jar = CookieJar::new();
+        
+        let mut cookie1 = Cookie::new("test1".to_string(), "value1".to_string());
+        cookie1.domain = Some("example.com".to_string());
+        cookie1.path = "/api".to_string();
+        
+        let mut cookie2 = Cookie::new("test2".to_string(), "value2".to_string());
+        cookie2.domain = Some("other.com".to_string());
+        
+        jar.set_cookie(cookie1);
+        jar.set_cookie(cookie2);
+        
+        let cookies = jar.get_cookies_for_request("example.com", "/api/users", false, None);
+        assert_eq!(cookies.len(), 1);
+        assert_eq!(cookies[0].name, "test1");
+    }
+    
+    #[test]
+    fn test_set_cookie_header_parsing() {
+        let cookie = Cookie::parse("sid=abc123; Path=/app; Secure; HttpOnly; SameSite=Strict", "Example.com").unwrap();
+        assert_eq!((cookie.name.as_str(), cookie.value.as_str()), ("sid", "abc123"));
+        assert_eq!(cookie.domain.as_deref(), Some("example.com"));
+        assert_eq!(cookie.path, "/app");
+        assert!(cookie.secure && cookie.http_only);
+        assert_eq!(cookie.same_site, SameSite::Strict);
+        
+        let expired = Cookie::parse("old=1; Max-Age=0; Domain=.example.com", "www.example.com").unwrap();
+        assert_eq!(expired.domain.as_deref(), Some("example.com"));
+        assert!(Cookie::parse("no-equals-sign", "example.com").is_none());
+        
+        let mut jar = CookieJar::new();
+        jar.set_cookie(cookie);
+        jar.set_cookie(Cookie::parse("theme=dark", "example.com").unwrap());
+        assert_eq!(jar.cookie_header("example.com", "/app/page", true, None).as_deref(), Some("sid=abc123; theme=dark"));
+        // Secure cookies are not sent over plain HTTP
+        assert_eq!(jar.cookie_header("example.com", "/app/page", false, None).as_deref(), Some("theme=dark"));
+        assert!(jar.cookie_header("other.com", "/", true, None).is_none());
+    }
+
+    #[test]
+    fn test_partitioned_cookies() {
+        let mut jar = CookieJar::new();
+        jar.set_cookie(Cookie::parse("id=first", "tracker.example").unwrap());
+        let news = PartitionKey::of(&url("https://news.example/"));
+        let mut embedded = Cookie::parse("id=news", "tracker.example").unwrap();
+        embedded.partition = Some(news.clone());
+        jar.set_cookie(embedded);
+
+        // Same name, kept apart by partition
+        assert_eq!(jar.cookie_header("tracker.example", "/", true, None).as_deref(), Some("id=first"));
+        assert_eq!(jar.cookie_header("tracker.example", "/", true, Some(&news)).as_deref(), Some("id=news"));
+        let shop = PartitionKey::of(&url("https://shop.example/"));
+        assert!(jar.cookie_header("tracker.example", "/", true, Some(&shop)).is_none());
+        assert_eq!(jar.get_cookie("id").unwrap().value, "first");
+        assert_eq!(jar.size_for_host("tracker.example"), 13);
+        assert_eq!(jar.remove_for_host("tracker.example"), 2);
+    }
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+    
+    /// Store `bytes` of localStorage for an origin through the web storage
+    fn store(manager: &mut StorageManager, origin: &Url, bytes: usize) {
+        let web_storage = manager.web_storage();
+        let document = web_storage.register_document(origin, 1).unwrap();
+        web_storage.set_item(document, StorageArea::Local, "k".to_string(), "v".repeat(bytes - 1)).unwrap();
+    }
+    
+    #[test]
+    fn test_estimate_and_origin_quota() {
+        let mut manager = StorageManager::new();
+        manager.set_quotas(100, 40);
+        let site = url("https://example.com/page");
+        store(&mut manager, &site, 30);
+        manager.cache_storage().open_cache(&site, "c").unwrap();
+        
+        let estimate = manager.estimate(&site).unwrap();
+        assert_eq!(estimate.usage, 30);
+        assert_eq!(estimate.quota, 40);
+        assert_eq!(estimate.details.local_storage, 30);
+        assert_eq!(manager.origins(), vec!["https://example.com".to_string()]);
+        
+        assert_eq!(manager.reserve(&site, 10), Ok(Vec::new()));
+        assert_eq!(manager.reserve(&site, 11), Err(StorageError::QuotaExceeded));
+        assert_eq!(manager.estimate(&url("data:text/plain,x")), Err(StorageError::SecurityError));
+    }
+    
+    #[test]
+    fn test_global_quota_evicts_least_recently_used() {
+        let mut manager = StorageManager::new();
+        manager.set_quotas(100, 60);
+        let (a, b, c, d) = (url("https://a.com/"), url("https://b.com/"), url("https://c.com/"), url("https://d.com/"));
+        for origin in [&a, &b, &c] {
+            store(&mut manager, origin, 30);
+            manager.touch(origin);
+        }
+        manager.persist(&a);
+        manager.touch(&b);
+        
+        // a is oldest but persisted, so c goes first, then b
+        assert_eq!(manager.reserve(&d, 20), Ok(vec!["https://c.com".to_string()]));
+        assert_eq!(manager.reserve(&d, 50), Ok(vec!["https://b.com".to_string()]));
+        assert_eq!(manager.origins(), vec!["https://a.com".to_string()]);
+        assert!(manager.is_persisted(&a));
+        
+        // Only the persisted origin is left to evict
+        manager.set_quotas(40, 60);
+        assert_eq!(manager.reserve(&d, 20), Err(StorageError::QuotaExceeded));
+    }
+    
+    #[test]
+    fn test_clear_site_data() {
+        let mut manager = StorageManager::new();
+        let site = url("https://www.example.com/");
+        let other = url("https://other.com/");
+        store(&mut manager, &site, 10);
+        store(&mut manager, &other, 10);
+        manager.indexed_db(&site).unwrap().open("db", 1).unwrap();
+        for (cookie, host) in [("a=1", "www.example.com"), ("b=2", "api.www.example.com"), ("c=3", "other.com")] {
+            manager.cookie_jar().set_cookie(Cookie::parse(cookie, host).unwrap());
+        }
+        assert!(manager.usage_details(&site).indexed_db > 0);
+        assert_eq!(manager.usage_details(&site).cookies, 4);
+        
+        manager.clear_site_data(&site, SiteDataTypes { cookies: true, storage: false, cache: false }).unwrap();
+        assert_eq!(manager.cookie_jar().names(), vec!["c".to_string()]);
+        assert_eq!(manager.usage_details(&site).local_storage, 10);
+        
+        manager.clear_site_data(&site, SiteDataTypes::all()).unwrap();
+        assert_eq!(manager.usage_details(&site), UsageDetails::default());
+        assert_eq!(manager.origins(), vec!["https://other.com".to_string()]);
+    }
+}
@@ -0,0 +1,91 @@
+// Site data - what each origin stores and how much it may store
+//
+// localStorage, IndexedDB and Cache Storage count against the quotas the
+// storage manager enforces; cookies and the HTTP cache are reported and
+// cleared with the rest of a site's data but managed by the network stack.
+
+use url::Url;
+
+/// Bytes all origins together may keep in localStorage, IndexedDB and caches
+pub const GLOBAL_QUOTA: usize = 1024 * 1024 * 1024;
+
+/// Bytes a single origin may keep
+pub const ORIGIN_QUOTA: usize = 256 * 1024 * 1024;
+
+/// Bytes an origin stores, by kind of data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct UsageDetails {
+    pub local_storage: usize,
+    pub indexed_db: usize,
+    pub caches: usize,
+    pub cookies: usize,
+    pub http_cache: usize,
+}
+
+impl UsageDetails {
+    /// Bytes counted against the quotas
+    pub fn quota_usage(&self) -> usize {
+        self.local_storage + self.indexed_db + self.caches
+    }
+
+    /// Every byte the site stores
+    pub fn total(&self) -> usize {
+        self.quota_usage() + self.cookies + self.http_cache
+    }
+}
+
+/// `navigator.storage.estimate()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageEstimate {
+    /// Bytes counted against the quota
+    pub usage: usize,
+    /// Bytes the origin may use, given its own and the global quota
+    pub quota: usize,
+    pub details: UsageDetails,
+}
+
+/// Kinds of data removed by "clear data for this site"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SiteDataTypes {
+    /// Cookies for the site's host and its subdomains
+    pub cookies: bool,
+    /// localStorage, sessionStorage, IndexedDB and Cache Storage
+    pub storage: bool,
+    /// HTTP cache entries
+    pub cache: bool,
+}
+
+impl SiteDataTypes {
+    /// Everything
+    pub fn all() -> Self {
+        Self {
+            cookies: true,
+            storage: true,
+            cache: true,
+        }
+    }
+
+    /// Only what counts against the quota, as removed by eviction
+    pub fn storage() -> Self {
+        Self {
+            cookies: false,
+            storage: true,
+            cache: false,
+        }
+    }
+}
+
+/// Per-site data kept by the network stack: the HTTP cache and its cookies
+pub trait NetworkSiteData {
+    /// Bytes of HTTP cache entries for URLs of `origin`
+    fn http_cache_usage(&self, origin: &Url) -> usize;
+
+    /// Drop the HTTP cache entries for URLs of `origin`
+    fn clear_http_cache(&self, origin: &Url);
+
+    /// Bytes of cookies for `host` and its subdomains
+    fn cookie_usage(&self, host: &str) -> usize;
+
+    /// Drop the cookies for `host` and its subdomains
+    fn clear_cookies(&self, host: &str);
+}
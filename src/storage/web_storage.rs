@@ -0,0 +1,646 @@
+// Web Storage - per-origin localStorage and sessionStorage
+//
+// Every origin gets its own areas and quota. localStorage outlives the
+// browser through an append-only log of changes, replayed on open and
+// rewritten once most of it is stale. Documents register with the store
+// so a change made by one is queued as a `storage` event for the others
+// of the same origin; the JS bindings drain that queue. A document embedded
+// by another site gets its origin's areas in that top-level site's
+// partition, apart from those it has as a top-level page.
+
+use super::{origin_key, PartitionKey, LocalStorage, SessionStorage, StorageArea, StorageError, StorageEvent, STORAGE_QUOTA};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// Log records kept before compaction is considered
+const COMPACT_MIN_RECORDS: usize = 1000;
+
+/// Identifies a document registered with the store
+pub type DocumentId = u64;
+
+/// Identifies a top-level browsing context (a tab); sessionStorage is per session
+pub type SessionId = u64;
+
+/// One change to localStorage, as written to the log
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum LogRecord {
+    Set { origin: String, key: String, value: String },
+    Remove { origin: String, key: String },
+    Clear { origin: String },
+}
+
+/// Append-only log file backing localStorage
+struct StorageLog {
+    path: PathBuf,
+    file: File,
+    /// Records in the file, live or stale
+    records: usize,
+}
+
+impl StorageLog {
+    fn append(&mut self, record: &LogRecord) -> io::Result<()> {
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        self.file.write_all(line.as_bytes())?;
+        self.file.flush()?;
+        self.records += 1;
+        Ok(())
+    }
+}
+
+/// A document using the store
+struct Document {
+    /// Origin, and partition if any, of the areas the document uses
+    origin: String,
+    url: Url,
+    session: SessionId,
+}
+
+/// Per-origin localStorage and sessionStorage for every document
+pub struct WebStorage {
+    /// localStorage areas by origin
+    local: HashMap<String, LocalStorage>,
+    /// sessionStorage areas by session and origin
+    session: HashMap<(SessionId, String), SessionStorage>,
+    /// Bytes each origin may store per area
+    quota: usize,
+    /// Disk log; `None` keeps localStorage in memory only
+    log: Option<StorageLog>,
+    documents: HashMap<DocumentId, Document>,
+    next_document: DocumentId,
+    /// `storage` events waiting to be dispatched, by target document
+    events: HashMap<DocumentId, Vec<StorageEvent>>,
+}
+
+impl WebStorage {
+    /// Create a store that keeps everything in memory
+    pub fn new() -> Self {
+        Self {
+            local: HashMap::new(),
+            session: HashMap::new(),
+            quota: STORAGE_QUOTA,
+            log: None,
+            documents: HashMap::new(),
+            next_document: 1,
+            events: HashMap::new(),
+        }
+    }
+
+    /// Open a store persisting localStorage to a log file
+    ///
+    /// Existing records are replayed; a truncated last record (from a
+    /// crash mid-write) is ignored.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut storage = Self::new();
+        let mut records = 0;
+        let mut damaged = false;
+        match File::open(path) {
+            Ok(file) => {
+                for line in BufReader::new(file).lines() {
+                    let Ok(record) = serde_json::from_str::<LogRecord>(&line?) else {
+                        damaged = true;
+                        continue;
+                    };
+                    storage.replay(record);
+                    records += 1;
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        storage.log = Some(StorageLog { path: path.to_path_buf(), file, records });
+        if damaged {
+            // Appending after a partial line would damage the next record too
+            storage.compact()?;
+        } else {
+            storage.compact_if_stale();
+        }
+        Ok(storage)
+    }
+
+    fn replay(&mut self, record: LogRecord) {
+        match record {
+            LogRecord::Set { origin, key, value } => {
+                let _ = self.local_area(&origin).set_item(key, value);
+            }
+            LogRecord::Remove { origin, key } => {
+                if let Some(area) = self.local.get_mut(&origin) {
+                    area.remove_item(&key);
+                }
+            }
+            LogRecord::Clear { origin } => {
+                self.local.remove(&origin);
+            }
+        }
+    }
+
+    /// Bytes each origin may store in each area
+    pub fn quota(&self) -> usize {
+        self.quota
+    }
+
+    /// Change the per-origin quota; stored items over it are kept
+    pub fn set_quota(&mut self, quota: usize) {
+        self.quota = quota;
+        for area in self.local.values_mut() {
+            area.set_quota(quota);
+        }
+        for area in self.session.values_mut() {
+            area.set_quota(quota);
+        }
+    }
+
+    /// Register a document loaded from `url` in a tab
+    ///
+    /// Documents with opaque origins (`data:`, `file:`...) get no storage.
+    pub fn register_document(&mut self, url: &Url, session: SessionId) -> Result<DocumentId, StorageError> {
+        self.register(url, None, session)
+    }
+
+    /// Register a document loaded from `url` in a frame of the top-level
+    /// page `top_level`
+    ///
+    /// A document of another site than the page gets its areas in the
+    /// page's partition.
+    pub fn register_frame(&mut self, url: &Url, top_level: &Url, session: SessionId) -> Result<DocumentId, StorageError> {
+        self.register(url, PartitionKey::for_request(url, Some(top_level)).as_ref(), session)
+    }
+
+    fn register(&mut self, url: &Url, partition: Option<&PartitionKey>, session: SessionId) -> Result<DocumentId, StorageError> {
+        let origin = origin_key(url).ok_or(StorageError::SecurityError)?;
+        let origin = match partition {
+            Some(partition) => format!("{}^{}", origin, partition),
+            None => origin,
+        };
+        let id = self.next_document;
+        self.next_document += 1;
+        self.documents.insert(id, Document { origin, url: url.clone(), session });
+        Ok(id)
+    }
+
+    /// Forget a document that was unloaded, with its pending events
+    pub fn unregister_document(&mut self, document: DocumentId) {
+        self.documents.remove(&document);
+        self.events.remove(&document);
+    }
+
+    /// Drop a closed tab's sessionStorage
+    pub fn close_session(&mut self, session: SessionId) {
+        self.session.retain(|(s, _), _| *s != session);
+    }
+
+    /// `storage.length`
+    pub fn length(&self, document: DocumentId, area: StorageArea) -> Result<usize, StorageError> {
+        let doc = self.document(document)?;
+        Ok(match area {
+            StorageArea::Local => self.local.get(&doc.origin).map_or(0, LocalStorage::length),
+            StorageArea::Session => self.session.get(&(doc.session, doc.origin.clone())).map_or(0, SessionStorage::length),
+        })
+    }
+
+    /// `storage.key(index)`
+    pub fn key(&self, document: DocumentId, area: StorageArea, index: usize) -> Result<Option<String>, StorageError> {
+        let doc = self.document(document)?;
+        Ok(match area {
+            StorageArea::Local => self.local.get(&doc.origin).and_then(|a| a.key(index)),
+            StorageArea::Session => self.session.get(&(doc.session, doc.origin.clone())).and_then(|a| a.key(index)),
+        })
+    }
+
+    /// `storage.getItem(key)`
+    pub fn get_item(&self, document: DocumentId, area: StorageArea, key: &str) -> Result<Option<String>, StorageError> {
+        let doc = self.document(document)?;
+        Ok(match area {
+            StorageArea::Local => self.local.get(&doc.origin).and_then(|a| a.get_item(key)),
+            StorageArea::Session => self.session.get(&(doc.session, doc.origin.clone())).and_then(|a| a.get_item(key)),
+        })
+    }
+
+    /// `storage.setItem(key, value)`; fails with `QuotaExceeded` over the origin's quota
+    pub fn set_item(&mut self, document: DocumentId, area: StorageArea, key: String, value: String) -> Result<(), StorageError> {
+        let doc = self.document(document)?;
+        let origin = doc.origin.clone();
+        let session = doc.session;
+        let old_value = self.get_item(document, area, &key)?;
+        if old_value.as_deref() == Some(value.as_str()) {
+            return Ok(());
+        }
+
+        match area {
+            StorageArea::Local => {
+                self.local_area(&origin).set_item(key.clone(), value.clone())?;
+                let record = LogRecord::Set { origin: origin.clone(), key: key.clone(), value: value.clone() };
+                if let Err(e) = self.write_log(&record) {
+                    // Keep memory in step with the disk
+                    let area = self.local_area(&origin);
+                    match &old_value {
+                        Some(old) => area.set_item(key, old.clone())?,
+                        None => {
+                            area.remove_item(&key);
+                        }
+                    }
+                    return Err(e);
+                }
+                self.compact_if_stale();
+            }
+            StorageArea::Session => {
+                self.session_area(session, &origin).set_item(key.clone(), value.clone())?;
+            }
+        }
+
+        self.broadcast(document, area, Some(key), old_value, Some(value));
+        Ok(())
+    }
+
+    /// `storage.removeItem(key)`
+    pub fn remove_item(&mut self, document: DocumentId, area: StorageArea, key: &str) -> Result<(), StorageError> {
+        let doc = self.document(document)?;
+        let origin = doc.origin.clone();
+        let session = doc.session;
+        if self.get_item(document, area, key)?.is_none() {
+            return Ok(());
+        }
+
+        let old_value = match area {
+            StorageArea::Local => {
+                self.write_log(&LogRecord::Remove { origin: origin.clone(), key: key.to_string() })?;
+                let old_value = self.local_area(&origin).remove_item(key);
+                self.compact_if_stale();
+                old_value
+            }
+            StorageArea::Session => self.session_area(session, &origin).remove_item(key),
+        };
+
+        self.broadcast(document, area, Some(key.to_string()), old_value, None);
+        Ok(())
+    }
+
+    /// `storage.clear()`
+    pub fn clear(&mut self, document: DocumentId, area: StorageArea) -> Result<(), StorageError> {
+        if self.length(document, area)? == 0 {
+            return Ok(());
+        }
+        let doc = self.document(document)?;
+        let origin = doc.origin.clone();
+        let session = doc.session;
+
+        match area {
+            StorageArea::Local => {
+                self.write_log(&LogRecord::Clear { origin: origin.clone() })?;
+                self.local.remove(&origin);
+                self.compact_if_stale();
+            }
+            StorageArea::Session => {
+                self.session.remove(&(session, origin));
+            }
+        }
+
+        // A cleared area is reported with every field null
+        self.broadcast(document, area, None, None, None);
+        Ok(())
+    }
+
+    /// Take the `storage` events queued for a document
+    pub fn take_events(&mut self, document: DocumentId) -> Vec<StorageEvent> {
+        self.events.remove(&document).unwrap_or_default()
+    }
+
+    /// Bytes of localStorage used by a URL's origin, in every partition
+    pub fn usage(&self, url: &Url) -> usize {
+        let Some(origin) = origin_key(url) else {
+            return 0;
+        };
+        self.local.iter().filter(|(key, _)| area_origin(key) == origin).map(|(_, area)| area.size()).sum()
+    }
+
+    /// Origins with localStorage data
+    pub fn origins(&self) -> Vec<String> {
+        let mut origins: Vec<String> = self.local.keys().map(|key| area_origin(key).to_string()).collect();
+        origins.sort();
+        origins.dedup();
+        origins
+    }
+
+    /// Delete everything a URL's origin stored, in every partition, without
+    /// firing events
+    pub fn clear_origin(&mut self, url: &Url) -> Result<(), StorageError> {
+        let Some(origin) = origin_key(url) else {
+            return Ok(());
+        };
+        let keys: Vec<String> = self.local.keys().filter(|key| area_origin(key) == origin).cloned().collect();
+        for key in keys {
+            self.write_log(&LogRecord::Clear { origin: key.clone() })?;
+            self.local.remove(&key);
+        }
+        self.compact_if_stale();
+        self.session.retain(|(_, key), _| area_origin(key) != origin);
+        Ok(())
+    }
+
+    /// Rewrite the log with only the live items
+    ///
+    /// The new log replaces the old in one rename, so on failure the old
+    /// one is still in place.
+    pub fn compact(&mut self) -> io::Result<()> {
+        let Some(log) = self.log.as_mut() else {
+            return Ok(());
+        };
+
+        let temp_path = log.path.with_extension("compact");
+        let mut records = 0;
+        {
+            let mut temp = File::create(&temp_path)?;
+            let mut origins: Vec<&String> = self.local.keys().collect();
+            origins.sort();
+            for origin in origins {
+                for (key, value) in self.local[origin].iter() {
+                    let record = LogRecord::Set {
+                        origin: origin.clone(),
+                        key: key.to_string(),
+                        value: value.to_string(),
+                    };
+                    writeln!(temp, "{}", serde_json::to_string(&record)?)?;
+                    records += 1;
+                }
+            }
+            temp.sync_all()?;
+        }
+        // Opened before the rename, so appends never go to the replaced file
+        let file = OpenOptions::new().append(true).open(&temp_path)?;
+        fs::rename(&temp_path, &log.path)?;
+
+        log.file = file;
+        log.records = records;
+        Ok(())
+    }
+
+    /// Compact once the log is mostly stale records
+    ///
+    /// Called once a change is in both the log and memory. A compaction
+    /// that fails leaves the longer log, which replays to the same items,
+    /// so the change still stands.
+    fn compact_if_stale(&mut self) {
+        let Some(log) = &self.log else {
+            return;
+        };
+        let live: usize = self.local.values().map(LocalStorage::length).sum();
+        if log.records > COMPACT_MIN_RECORDS && log.records > live * 2 && self.compact().is_err() {
+            if let Some(log) = &self.log {
+                let _ = fs::remove_file(log.path.with_extension("compact"));
+            }
+        }
+    }
+
+    /// Append a change to the log
+    fn write_log(&mut self, record: &LogRecord) -> Result<(), StorageError> {
+        let Some(log) = self.log.as_mut() else {
+            return Ok(());
+        };
+        log.append(record).map_err(|_| StorageError::Io)
+    }
+
+    fn document(&self, document: DocumentId) -> Result<&Document, StorageError> {
+        self.documents.get(&document).ok_or(StorageError::SecurityError)
+    }
+
+    fn local_area(&mut self, origin: &str) -> &mut LocalStorage {
+        let quota = self.quota;
+        self.local.entry(origin.to_string()).or_insert_with(|| {
+            let mut area = LocalStorage::new();
+            area.set_quota(quota);
+            area
+        })
+    }
+
+    fn session_area(&mut self, session: SessionId, origin: &str) -> &mut SessionStorage {
+        let quota = self.quota;
+        self.session.entry((session, origin.to_string())).or_insert_with(|| {
+            let mut area = SessionStorage::new();
+            area.set_quota(quota);
+            area
+        })
+    }
+
+    /// Queue a `storage` event for every other document sharing the area
+    fn broadcast(
+        &mut self,
+        source: DocumentId,
+        area: StorageArea,
+        key: Option<String>,
+        old_value: Option<String>,
+        new_value: Option<String>,
+    ) {
+        let Some(doc) = self.documents.get(&source) else {
+            return;
+        };
+        let event = StorageEvent { key, old_value, new_value, storage_area: area, url: Some(doc.url.clone()) };
+        let targets: Vec<DocumentId> = self
+            .documents
+            .iter()
+            .filter(|(id, other)| {
+                **id != source
+                    && other.origin == doc.origin
+                    && (area == StorageArea::Local || other.session == doc.session)
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        for target in targets {
+            self.events.entry(target).or_default().push(event.clone());
+        }
+    }
+}
+
+/// Origin of the areas under a key, without their partition
+fn area_origin(key: &str) -> &str {
+    key.split_once('^').map_or(key, |(origin, _)| origin)
+}
+
+impl Default for WebStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    fn temp_log(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("web-storage-{}-{}.log", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_areas_are_per_origin() {
+        let mut storage = WebStorage::new();
+        let a = storage.register_document(&url("https://a.example/page"), 1).unwrap();
+        let b = storage.register_document(&url("https://b.example/"), 1).unwrap();
+
+        storage.set_item(a, StorageArea::Local, "k".into(), "a".into()).unwrap();
+        assert_eq!(storage.get_item(a, StorageArea::Local, "k").unwrap(), Some("a".into()));
+        assert_eq!(storage.get_item(b, StorageArea::Local, "k").unwrap(), None);
+        assert_eq!(storage.usage(&url("https://a.example/other")), 2);
+
+        // Opaque origins get no storage
+        assert_eq!(
+            storage.register_document(&url("data:text/html,hi"), 1),
+            Err(StorageError::SecurityError)
+        );
+    }
+
+    #[test]
+    fn test_embedded_documents_are_partitioned() {
+        let mut storage = WebStorage::new();
+        let widget = url("https://widgets.example/embed");
+        let top = storage.register_document(&widget, 1).unwrap();
+        let in_a = storage.register_frame(&widget, &url("https://a.example/"), 1).unwrap();
+        let in_b = storage.register_frame(&widget, &url("https://b.example/"), 1).unwrap();
+        let also_in_a = storage.register_frame(&widget, &url("https://www.a.example/page"), 2).unwrap();
+
+        storage.set_item(in_a, StorageArea::Local, "id".into(), "a".into()).unwrap();
+        assert_eq!(storage.get_item(also_in_a, StorageArea::Local, "id").unwrap(), Some("a".into()));
+        assert_eq!(storage.get_item(in_b, StorageArea::Local, "id").unwrap(), None);
+        assert_eq!(storage.get_item(top, StorageArea::Local, "id").unwrap(), None);
+        assert_eq!(storage.take_events(also_in_a).len(), 1);
+        assert!(storage.take_events(in_b).is_empty() && storage.take_events(top).is_empty());
+
+        // A frame of its own site shares the top-level areas
+        let own = storage.register_frame(&widget, &url("https://shop.widgets.example/"), 1).unwrap();
+        storage.set_item(own, StorageArea::Local, "id".into(), "own".into()).unwrap();
+        assert_eq!(storage.get_item(top, StorageArea::Local, "id").unwrap(), Some("own".into()));
+
+        // Partitions count towards the origin and are cleared with it
+        assert_eq!(storage.origins(), vec!["https://widgets.example".to_string()]);
+        assert_eq!(storage.usage(&widget), 8);
+        storage.clear_origin(&widget).unwrap();
+        assert_eq!(storage.get_item(in_a, StorageArea::Local, "id").unwrap(), None);
+        assert!(storage.origins().is_empty());
+    }
+
+    #[test]
+    fn test_session_storage_is_per_tab() {
+        let mut storage = WebStorage::new();
+        let tab1 = storage.register_document(&url("https://a.example/"), 1).unwrap();
+        let tab2 = storage.register_document(&url("https://a.example/"), 2).unwrap();
+
+        storage.set_item(tab1, StorageArea::Session, "k".into(), "v".into()).unwrap();
+        assert_eq!(storage.get_item(tab2, StorageArea::Session, "k").unwrap(), None);
+        assert_eq!(storage.length(tab1, StorageArea::Session).unwrap(), 1);
+
+        storage.close_session(1);
+        assert_eq!(storage.length(tab1, StorageArea::Session).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_quota_is_per_origin() {
+        let mut storage = WebStorage::new();
+        storage.set_quota(10);
+        let a = storage.register_document(&url("https://a.example/"), 1).unwrap();
+        let b = storage.register_document(&url("https://b.example/"), 1).unwrap();
+
+        storage.set_item(a, StorageArea::Local, "key".into(), "1234567".into()).unwrap();
+        assert_eq!(
+            storage.set_item(a, StorageArea::Local, "more".into(), "x".into()),
+            Err(StorageError::QuotaExceeded)
+        );
+        // Another origin has its own quota
+        assert!(storage.set_item(b, StorageArea::Local, "key".into(), "1234567".into()).is_ok());
+    }
+
+    #[test]
+    fn test_storage_events_reach_other_documents_of_origin() {
+        let mut storage = WebStorage::new();
+        let writer = storage.register_document(&url("https://a.example/one"), 1).unwrap();
+        let same_origin = storage.register_document(&url("https://a.example/two"), 2).unwrap();
+        let other_origin = storage.register_document(&url("https://b.example/"), 1).unwrap();
+
+        storage.set_item(writer, StorageArea::Local, "k".into(), "v".into()).unwrap();
+        // Setting the same value again is not a change
+        storage.set_item(writer, StorageArea::Local, "k".into(), "v".into()).unwrap();
+        storage.clear(writer, StorageArea::Local).unwrap();
+
+        let events = storage.take_events(same_origin);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].key.as_deref(), Some("k"));
+        assert_eq!(events[0].new_value.as_deref(), Some("v"));
+        assert_eq!(events[0].url, Some(url("https://a.example/one")));
+        assert_eq!(events[1].key, None);
+        assert!(storage.take_events(same_origin).is_empty());
+        assert!(storage.take_events(writer).is_empty());
+        assert!(storage.take_events(other_origin).is_empty());
+
+        // sessionStorage changes stay within the tab
+        storage.set_item(writer, StorageArea::Session, "k".into(), "v".into()).unwrap();
+        assert!(storage.take_events(same_origin).is_empty());
+    }
+
+    #[test]
+    fn test_local_storage_persists_across_open() {
+        let path = temp_log("persist");
+        {
+            let mut storage = WebStorage::open(&path).unwrap();
+            let doc = storage.register_document(&url("https://a.example/"), 1).unwrap();
+            storage.set_item(doc, StorageArea::Local, "keep".into(), "1".into()).unwrap();
+            storage.set_item(doc, StorageArea::Local, "drop".into(), "2".into()).unwrap();
+            storage.remove_item(doc, StorageArea::Local, "drop").unwrap();
+            storage.set_item(doc, StorageArea::Session, "session".into(), "3".into()).unwrap();
+        }
+        // A record cut short by a crash is skipped
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(b"{\"op\":\"set\",\"ori").unwrap();
+
+        let mut storage = WebStorage::open(&path).unwrap();
+        let doc = storage.register_document(&url("https://a.example/"), 1).unwrap();
+        assert_eq!(storage.get_item(doc, StorageArea::Local, "keep").unwrap(), Some("1".into()));
+        assert_eq!(storage.get_item(doc, StorageArea::Local, "drop").unwrap(), None);
+        assert_eq!(storage.get_item(doc, StorageArea::Session, "session").unwrap(), None);
+
+        // Reopening rewrote the damaged log; compacting leaves one record
+        storage.compact().unwrap();
+        let lines = fs::read_to_string(&path).unwrap().lines().count();
+        assert_eq!(lines, 1);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_failed_compaction_keeps_the_change() {
+        let path = temp_log("compact-fails");
+        // Compaction cannot create its new log where a directory is in the way
+        let blocked = path.with_extension("compact");
+        fs::create_dir_all(&blocked).unwrap();
+        {
+            let mut storage = WebStorage::open(&path).unwrap();
+            let doc = storage.register_document(&url("https://a.example/"), 1).unwrap();
+            for n in 0..=COMPACT_MIN_RECORDS {
+                storage.set_item(doc, StorageArea::Local, "count".into(), n.to_string()).unwrap();
+            }
+            // The log is past compacting, which fails after each append
+            storage.set_item(doc, StorageArea::Local, "last".into(), "set".into()).unwrap();
+            storage.remove_item(doc, StorageArea::Local, "count").unwrap();
+            assert_eq!(storage.get_item(doc, StorageArea::Local, "last").unwrap(), Some("set".into()));
+        }
+        let _ = fs::remove_dir(&blocked);
+
+        let mut storage = WebStorage::open(&path).unwrap();
+        let doc = storage.register_document(&url("https://a.example/"), 1).unwrap();
+        assert_eq!(storage.get_item(doc, StorageArea::Local, "last").unwrap(), Some("set".into()));
+        assert_eq!(storage.get_item(doc, StorageArea::Local, "count").unwrap(), None);
+        // Opening compacted the log now that it could
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+        let _ = fs::remove_file(&path);
+    }
+}
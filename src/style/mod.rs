@@ -1,5 +1,9 @@
-use crate::css::{Stylesheet, Selector, SimpleSelector, Value, specificity, Specificity};
+pub mod properties;
+
+use crate::css::{Combinator, Stylesheet, Selector, SimpleSelector, Unit, Value, specificity, Specificity};
 use crate::dom::{Node, NodeType, ElementData};
+use crate::forms;
+use properties::{inherited_properties, PropertyInfo, INITIAL_FONT_SIZE};
 use std::collections::HashMap;
 
 /// A node with computed styles
@@ -28,11 +32,30 @@ impl<'a> StyledNode<'a> {
     }
 
     /// Get the lookup value or a default
+    ///
+    /// A shorthand of one to four values, like `margin: 0 auto`, gives the
+    /// longhand the value for the side in its name.
     pub fn lookup(&self, name: &str, fallback_name: &str, default: &Value) -> Value {
-        self.value(name)
-            .or_else(|| self.value(fallback_name))
-            .unwrap_or(default)
-            .clone()
+        if let Some(value) = self.value(name) {
+            return value.clone();
+        }
+        match self.value(fallback_name) {
+            Some(Value::List(values)) if (2..=4).contains(&values.len()) => {
+                // Top, right, bottom, left; missing sides copy their opposite
+                let side = ["top", "right", "bottom", "left"]
+                    .iter()
+                    .position(|side| name.contains(side))
+                    .unwrap_or(0);
+                let index = match values.len() {
+                    2 => side % 2,
+                    3 if side == 3 => 1,
+                    _ => side,
+                };
+                values[index].clone()
+            }
+            Some(value) => value.clone(),
+            None => default.clone(),
+        }
     }
 
     /// Get the display property
@@ -51,31 +74,62 @@ impl<'a> StyledNode<'a> {
 
 /// Apply a stylesheet to a DOM tree to create a styled tree
 pub fn style_tree<'a>(root: &'a Node, stylesheet: &'a Stylesheet) -> StyledNode<'a> {
+    style_tree_with_defaults(root, stylesheet, &HashMap::new())
+}
+
+/// Apply a stylesheet, starting inherited properties from `defaults`
+///
+/// `defaults` holds the user's preferred font family and size; elements
+/// the stylesheet gives no font inherit them from the root down.
+pub fn style_tree_with_defaults<'a>(
+    root: &'a Node,
+    stylesheet: &'a Stylesheet,
+    defaults: &PropertyMap,
+) -> StyledNode<'a> {
+    style_node(root, &mut Vec::new(), stylesheet, defaults)
+}
+
+/// Style a node whose ancestors, from the root down, are `ancestors`
+///
+/// `parent` holds the computed values of the node's parent, or the
+/// defaults for the root.
+fn style_node<'a>(
+    root: &'a Node,
+    ancestors: &mut Vec<&'a Node>,
+    stylesheet: &'a Stylesheet,
+    parent: &PropertyMap,
+) -> StyledNode<'a> {
+    let values = match &root.node_type {
+        NodeType::Element(_) => computed_values(specified_values(root, ancestors, stylesheet), parent),
+        NodeType::Comment(_) => HashMap::new(),
+        _ => computed_values(HashMap::new(), parent),
+    };
+
+    ancestors.push(root);
+    let children = root
+        .children
+        .iter()
+        .map(|child| style_node(child, ancestors, stylesheet, &values))
+        .collect();
+    ancestors.pop();
     StyledNode {
         node: root,
-        specified_values: match &root.node_type {
-            NodeType::Element(elem) => specified_values(elem, stylesheet),
-            _ => HashMap::new(),
-        },
-        children: root
-            .children
-            .iter()
-            .map(|child| style_tree(child, stylesheet))
-            .collect(),
+        children,
+        specified_values: values,
     }
 }
 
 /// Get the specified values for an element
-fn specified_values(elem: &ElementData, stylesheet: &Stylesheet) -> PropertyMap {
+fn specified_values(node: &Node, ancestors: &[&Node], stylesheet: &Stylesheet) -> PropertyMap {
     let mut values = HashMap::new();
-    let mut rules = matching_rules(elem, stylesheet);
+    let mut rules = matching_rules(node, ancestors, stylesheet);
 
-    // Sort by specificity (lowest to highest)
-    rules.sort_by_key(|&(spec, _)| spec);
+    // Sort by origin, then specificity (lowest to highest)
+    rules.sort_by_key(|&(spec, rule)| (rule.origin, spec));
 
     // Apply rules in order (later rules override earlier ones)
     for (_, rule) in rules {
-        for declaration in &rule.declarations {
+        for declaration in rule.declarations.iter().filter(|declaration| declaration.enabled) {
             values.insert(declaration.name.clone(), declaration.value.clone());
         }
     }
@@ -83,9 +137,63 @@ fn specified_values(elem: &ElementData, stylesheet: &Stylesheet) -> PropertyMap
     values
 }
 
+/// Resolve a node's specified values against its parent's computed values
+///
+/// The `inherit`, `initial` and `unset` keywords are replaced by the
+/// values they stand for, relative font sizes become pixels, and inherited
+/// properties no rule set take the parent's value.
+fn computed_values(mut values: PropertyMap, parent: &PropertyMap) -> PropertyMap {
+    let keywords: Vec<(String, String)> = values
+        .iter()
+        .filter_map(|(name, value)| match value {
+            Value::Keyword(keyword) => {
+                let keyword = keyword.to_ascii_lowercase();
+                matches!(keyword.as_str(), "inherit" | "initial" | "unset").then(|| (name.clone(), keyword))
+            }
+            _ => None,
+        })
+        .collect();
+    for (name, keyword) in keywords {
+        let info = properties::property(&name);
+        let inherit = keyword == "inherit" || (keyword == "unset" && info.is_some_and(|info| info.inherited));
+        let initial = || info.map(PropertyInfo::initial_value);
+        let value = if inherit { parent.get(&name).cloned().or_else(initial) } else { initial() };
+        match value {
+            Some(value) => values.insert(name, value),
+            None => values.remove(&name),
+        };
+    }
+
+    let parent_font_size = match parent.get("font-size") {
+        Some(Value::Length(size, Unit::Px)) => *size,
+        _ => INITIAL_FONT_SIZE,
+    };
+    if let Some(font_size) = values.get_mut("font-size") {
+        let px = match *font_size {
+            Value::Length(size, Unit::Em) => Some(size * parent_font_size),
+            Value::Length(size, Unit::Rem) => Some(size * INITIAL_FONT_SIZE),
+            Value::Length(percent, Unit::Percent) | Value::Percentage(percent) => {
+                Some(percent / 100.0 * parent_font_size)
+            }
+            _ => None,
+        };
+        if let Some(px) = px {
+            *font_size = Value::Length(px, Unit::Px);
+        }
+    }
+
+    for name in inherited_properties() {
+        if let (false, Some(value)) = (values.contains_key(name), parent.get(name)) {
+            values.insert(name.to_string(), value.clone());
+        }
+    }
+    values
+}
+
 /// Find all CSS rules that match an element
 fn matching_rules<'a>(
-    elem: &ElementData,
+    node: &Node,
+    ancestors: &[&Node],
     stylesheet: &'a Stylesheet,
 ) -> Vec<(Specificity, &'a crate::css::Rule)> {
     stylesheet
@@ -94,19 +202,67 @@ fn matching_rules<'a>(
         .filter_map(|rule| {
             rule.selectors
                 .iter()
-                .find(|selector| matches(elem, selector))
+                .find(|selector| matches_in(node, ancestors, selector))
                 .map(|selector| (specificity(selector), rule))
         })
         .collect()
 }
 
-/// Check if a selector matches an element
-fn matches(elem: &ElementData, selector: &Selector) -> bool {
+/// Check if a selector matches an element, not knowing where it is
+///
+/// Selectors with combinators need the element's ancestors and siblings,
+/// so they never match; use `matches_in` for them.
+pub fn matches(elem: &ElementData, selector: &Selector) -> bool {
     match selector {
         Selector::Simple(simple) => matches_simple_selector(elem, simple),
+        Selector::Complex { .. } => false,
     }
 }
 
+/// Check if a selector matches an element node in its document
+///
+/// `ancestors` are the node's ancestors from the root down to its parent.
+pub fn matches_in(node: &Node, ancestors: &[&Node], selector: &Selector) -> bool {
+    let Some(elem) = node.element_data() else {
+        return false;
+    };
+    match selector {
+        Selector::Simple(simple) => matches_simple_selector(elem, simple),
+        Selector::Complex { parts, subject } => {
+            matches_simple_selector(elem, subject) && matches_parts(node, ancestors, parts)
+        }
+    }
+}
+
+/// Check the parts of a complex selector left of an element that matched the part after them
+///
+/// Descendant and subsequent sibling combinators try every candidate, so a
+/// later part that fails for the nearest one can still match through another.
+fn matches_parts(node: &Node, ancestors: &[&Node], parts: &[(SimpleSelector, Combinator)]) -> bool {
+    let Some(((simple, combinator), rest)) = parts.split_last() else {
+        return true;
+    };
+    let matched = |candidate: &Node, ancestors: &[&Node]| {
+        candidate.element_data().is_some_and(|elem| matches_simple_selector(elem, simple))
+            && matches_parts(candidate, ancestors, rest)
+    };
+    match combinator {
+        Combinator::Child => ancestors.split_last().is_some_and(|(parent, above)| matched(parent, above)),
+        Combinator::Descendant => (0..ancestors.len()).rev().any(|i| matched(ancestors[i], &ancestors[..i])),
+        Combinator::NextSibling => {
+            previous_siblings(node, ancestors).next().is_some_and(|sibling| matched(sibling, ancestors))
+        }
+        Combinator::SubsequentSibling => previous_siblings(node, ancestors).any(|sibling| matched(sibling, ancestors)),
+    }
+}
+
+/// Element siblings before a node, nearest first
+fn previous_siblings<'n>(node: &Node, ancestors: &[&'n Node]) -> impl Iterator<Item = &'n Node> {
+    let siblings: &[Node] = ancestors.last().map_or(&[], |parent| &parent.children);
+    let index = siblings.iter().position(|sibling| std::ptr::eq(sibling, node)).unwrap_or(0);
+    siblings[..index].iter().rev().filter(|sibling| sibling.element_data().is_some())
+}
+
 /// Check if a simple selector matches an element
 fn matches_simple_selector(elem: &ElementData, selector: &SimpleSelector) -> bool {
     // Check tag name
@@ -131,13 +287,28 @@ fn matches_simple_selector(elem: &ElementData, selector: &SimpleSelector) -> boo
         }
     }
 
-    true
+    // Check pseudo-classes
+    selector
+        .pseudo_classes
+        .iter()
+        .all(|pseudo| matches_pseudo_class(elem, pseudo))
+}
+
+/// Check if a pseudo-class applies to an element
+///
+/// Unsupported pseudo-classes never match.
+fn matches_pseudo_class(elem: &ElementData, pseudo: &str) -> bool {
+    match pseudo {
+        "valid" => forms::element_validity(elem) == Some(true),
+        "invalid" => forms::element_validity(elem) == Some(false),
+        _ => false,
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::css::{CssParser, Color, Unit};
+    use crate::css::{CssParser, Color, Origin, Unit};
     use crate::dom::Node;
     use std::collections::HashMap;
 
@@ -153,6 +324,7 @@ mod tests {
             tag_name: Some("div".to_string()),
             id: None,
             classes: Vec::new(),
+            pseudo_classes: Vec::new(),
         };
 
         assert!(matches_simple_selector(&elem, &selector));
@@ -172,6 +344,7 @@ mod tests {
             tag_name: None,
             id: Some("main".to_string()),
             classes: Vec::new(),
+            pseudo_classes: Vec::new(),
         };
 
         assert!(matches_simple_selector(&elem, &selector));
@@ -191,11 +364,36 @@ mod tests {
             tag_name: None,
             id: None,
             classes: vec!["container".to_string()],
+            pseudo_classes: Vec::new(),
         };
 
         assert!(matches_simple_selector(&elem, &selector));
     }
 
+    #[test]
+    fn test_matches_validity_pseudo_classes() {
+        let mut attrs = HashMap::new();
+        attrs.insert("required".to_string(), String::new());
+        let elem = ElementData {
+            tag_name: "input".to_string(),
+            attributes: attrs,
+        };
+
+        let invalid = SimpleSelector {
+            tag_name: Some("input".to_string()),
+            id: None,
+            classes: Vec::new(),
+            pseudo_classes: vec!["invalid".to_string()],
+        };
+        let valid = SimpleSelector {
+            pseudo_classes: vec!["valid".to_string()],
+            ..invalid.clone()
+        };
+
+        assert!(matches_simple_selector(&elem, &invalid));
+        assert!(!matches_simple_selector(&elem, &valid));
+    }
+
     #[test]
     fn test_style_tree() {
         let css = "div { color: red; font-size: 16px; }";
@@ -209,4 +407,122 @@ mod tests {
         assert!(styled.value("color").is_some());
         assert!(styled.value("font-size").is_some());
     }
+
+    #[test]
+    fn test_fonts_inherit_from_defaults() {
+        let stylesheet = CssParser::parse("h1 { font-size: 32px; }");
+        let heading = Node::element("h1".to_string(), HashMap::new(), vec![Node::text("Title".to_string())]);
+        let para = Node::element("p".to_string(), HashMap::new(), vec![Node::text("Body".to_string())]);
+        let body = Node::element("body".to_string(), HashMap::new(), vec![heading, para]);
+
+        let mut defaults = HashMap::new();
+        defaults.insert("font-family".to_string(), Value::Keyword("serif".to_string()));
+        defaults.insert("font-size".to_string(), Value::Length(20.0, Unit::Px));
+        let styled = style_tree_with_defaults(&body, &stylesheet, &defaults);
+
+        let heading_text = &styled.children[0].children[0];
+        assert_eq!(heading_text.value("font-size"), Some(&Value::Length(32.0, Unit::Px)));
+        assert_eq!(heading_text.value("font-family"), Some(&Value::Keyword("serif".to_string())));
+        let para_text = &styled.children[1].children[0];
+        assert_eq!(para_text.value("font-size"), Some(&Value::Length(20.0, Unit::Px)));
+
+        // Without defaults only what the stylesheet sets is inherited
+        let styled = style_tree(&body, &stylesheet);
+        assert!(styled.children[1].children[0].value("font-size").is_none());
+        assert!(styled.children[0].children[0].value("font-size").is_some());
+    }
+
+    #[test]
+    fn test_combinators() {
+        let stylesheet = CssParser::parse(
+            ".box p { color: red; } ul > li { padding: 1px; } h1 + p { margin: 1px; } h1 ~ ul { margin: 2px; } \
+             .box > .inner em { color: red; }",
+        );
+        let element = |tag: &str, class: &str, children: Vec<Node>| {
+            let attrs = HashMap::from([("class".to_string(), class.to_string())]);
+            Node::element(tag.to_string(), attrs, children)
+        };
+        let document = element(
+            "div",
+            "box",
+            vec![
+                element("h1", "", vec![]),
+                Node::text(" ".to_string()),
+                element("p", "", vec![]),
+                element("ul", "", vec![element("li", "", vec![element("ol", "", vec![element("li", "", vec![])])])]),
+                element("span", "inner", vec![element("b", "", vec![element("em", "", vec![])])]),
+            ],
+        );
+        let styled = style_tree(&document, &stylesheet);
+        let value = |path: &[usize], name: &str| {
+            path.iter().fold(&styled, |node, &i| &node.children[i]).value(name).map(|v| v.to_string())
+        };
+
+        // The paragraph is inside .box and right after the heading, text between them aside
+        assert_eq!(value(&[2], "color").as_deref(), Some("red"));
+        assert_eq!(value(&[2], "margin").as_deref(), Some("1px"));
+        // The list follows the heading, though not right after it
+        assert_eq!(value(&[3], "margin").as_deref(), Some("2px"));
+        assert_eq!(value(&[3, 0], "padding").as_deref(), Some("1px"));
+        // A list item whose parent is not a ul
+        assert_eq!(value(&[3, 0, 0, 0], "padding"), None);
+        // The em is inside a child of .box, two levels down
+        assert_eq!(value(&[4, 0, 0], "color").as_deref(), Some("red"));
+        assert_eq!(value(&[0], "color"), None);
+    }
+
+    #[test]
+    fn test_inheritance_and_css_wide_keywords() {
+        let stylesheet = CssParser::parse(
+            "div { color: #ff0000; font-family: serif; font-size: 20px; margin-top: 5px; } \
+             em { font-size: 1.5em; } .reset { color: initial; font-size: 50%; } \
+             .copy { margin-top: inherit; font-family: unset; } .plain { margin-top: unset; }",
+        );
+        let element = |tag: &str, class: &str, children: Vec<Node>| {
+            let attrs = HashMap::from([("class".to_string(), class.to_string())]);
+            Node::element(tag.to_string(), attrs, children)
+        };
+        let document = element(
+            "div",
+            "",
+            vec![
+                element("p", "", vec![element("em", "", vec![Node::text("nested".to_string())])]),
+                element("span", "reset", vec![]),
+                element("span", "copy", vec![]),
+                element("span", "plain", vec![]),
+            ],
+        );
+        let styled = style_tree(&document, &stylesheet);
+        let value = |path: &[usize], name: &str| path.iter().fold(&styled, |node, &i| &node.children[i]).value(name);
+
+        // Text two levels down gets the div's color and font, its size relative to the parent's
+        let red = Value::Color(Color::new(255, 0, 0, 255));
+        assert_eq!(value(&[0, 0, 0], "color"), Some(&red));
+        assert_eq!(value(&[0, 0, 0], "font-family"), Some(&Value::Keyword("serif".to_string())));
+        assert_eq!(value(&[0, 0, 0], "font-size"), Some(&Value::Length(30.0, Unit::Px)));
+        // Box properties stay on the element
+        assert_eq!(value(&[0], "margin-top"), None);
+
+        assert_eq!(value(&[1], "color"), Some(&Value::Color(Color::new(0, 0, 0, 255))));
+        assert_eq!(value(&[1], "font-size"), Some(&Value::Length(10.0, Unit::Px)));
+        // `inherit` copies even properties that do not inherit; `unset` inherits only those that do
+        assert_eq!(value(&[2], "margin-top"), Some(&Value::Length(5.0, Unit::Px)));
+        assert_eq!(value(&[2], "font-family"), Some(&Value::Keyword("serif".to_string())));
+        assert_eq!(value(&[3], "margin-top"), Some(&Value::Length(0.0, Unit::Px)));
+    }
+
+    #[test]
+    fn test_origins_cascade_before_specificity() {
+        let mut stylesheet = CssParser::parse("p { color: #0000ff; }");
+        stylesheet.append(CssParser::parse("#a { color: #ff0000; margin: 1px; }").with_origin(Origin::User));
+        stylesheet.append(CssParser::parse("p#a { margin: 2px; padding: 3px; }").with_origin(Origin::UserAgent));
+        let attrs = HashMap::from([("id".to_string(), "a".to_string())]);
+        let node = Node::element("p".to_string(), attrs, vec![]);
+        let styled = style_tree(&node, &stylesheet);
+
+        // The page's rule wins over a more specific user rule, and the user's over the browser's
+        assert_eq!(styled.value("color"), Some(&Value::Color(Color::new(0, 0, 255, 255))));
+        assert_eq!(styled.value("margin"), Some(&Value::Length(1.0, Unit::Px)));
+        assert_eq!(styled.value("padding"), Some(&Value::Length(3.0, Unit::Px)));
+    }
 }
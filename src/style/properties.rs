@@ -0,0 +1,107 @@
+// Property registry - which CSS properties inherit, and their initial values
+//
+// The cascade consults the registry to pass inherited properties from an
+// element to its children and text, and to resolve the `inherit`,
+// `initial` and `unset` keywords. Properties missing from the registry do
+// not inherit and have no initial value: `initial` just removes them, which
+// leaves consumers to their own defaults.
+
+use crate::css::{Color, Unit, Value};
+
+/// Font size before any rule sets one, in pixels
+pub const INITIAL_FONT_SIZE: f32 = 16.0;
+
+/// How the cascade treats a property
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PropertyInfo {
+    pub name: &'static str,
+    /// Whether elements take their parent's value when no rule sets one
+    pub inherited: bool,
+    initial: Initial,
+}
+
+/// An initial value, in a form the registry can hold as a constant
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Initial {
+    Keyword(&'static str),
+    Px(f32),
+    Number(f32),
+    Color(Color),
+}
+
+impl PropertyInfo {
+    /// Value of the property when no rule sets it and it does not inherit
+    pub fn initial_value(&self) -> Value {
+        match self.initial {
+            Initial::Keyword(keyword) => Value::Keyword(keyword.to_string()),
+            Initial::Px(px) => Value::Length(px, Unit::Px),
+            Initial::Number(number) => Value::Number(number),
+            Initial::Color(color) => Value::Color(color),
+        }
+    }
+}
+
+const fn inherited(name: &'static str, initial: Initial) -> PropertyInfo {
+    PropertyInfo { name, inherited: true, initial }
+}
+
+const fn reset(name: &'static str, initial: Initial) -> PropertyInfo {
+    PropertyInfo { name, inherited: false, initial }
+}
+
+const BLACK: Color = Color { r: 0, g: 0, b: 0, a: 255 };
+const TRANSPARENT: Color = Color { r: 0, g: 0, b: 0, a: 0 };
+
+/// Every property the cascade knows about
+pub const PROPERTIES: &[PropertyInfo] = &[
+    // Text and fonts pass down to the text inside an element
+    inherited("color", Initial::Color(BLACK)),
+    inherited("font-family", Initial::Keyword("sans-serif")),
+    inherited("font-size", Initial::Px(INITIAL_FONT_SIZE)),
+    inherited("font-style", Initial::Keyword("normal")),
+    inherited("font-weight", Initial::Keyword("normal")),
+    inherited("letter-spacing", Initial::Keyword("normal")),
+    inherited("word-spacing", Initial::Keyword("normal")),
+    inherited("text-transform", Initial::Keyword("none")),
+    inherited("text-decoration-skip-ink", Initial::Keyword("auto")),
+    inherited("hyphens", Initial::Keyword("manual")),
+    inherited("line-height", Initial::Keyword("normal")),
+    inherited("white-space", Initial::Keyword("normal")),
+    inherited("text-align", Initial::Keyword("start")),
+    inherited("direction", Initial::Keyword("ltr")),
+    inherited("visibility", Initial::Keyword("visible")),
+    inherited("cursor", Initial::Keyword("auto")),
+    inherited("list-style-type", Initial::Keyword("disc")),
+    // Box properties belong to the element alone
+    reset("display", Initial::Keyword("inline")),
+    reset("position", Initial::Keyword("static")),
+    reset("width", Initial::Keyword("auto")),
+    reset("height", Initial::Keyword("auto")),
+    reset("margin-top", Initial::Px(0.0)),
+    reset("margin-right", Initial::Px(0.0)),
+    reset("margin-bottom", Initial::Px(0.0)),
+    reset("margin-left", Initial::Px(0.0)),
+    reset("padding-top", Initial::Px(0.0)),
+    reset("padding-right", Initial::Px(0.0)),
+    reset("padding-bottom", Initial::Px(0.0)),
+    reset("padding-left", Initial::Px(0.0)),
+    reset("border-width", Initial::Px(0.0)),
+    reset("background-color", Initial::Color(TRANSPARENT)),
+    reset("opacity", Initial::Number(1.0)),
+    reset("text-decoration", Initial::Keyword("none")),
+];
+
+/// Look a property up in the registry
+pub fn property(name: &str) -> Option<&'static PropertyInfo> {
+    PROPERTIES.iter().find(|info| info.name == name)
+}
+
+/// Whether a property passes from an element to its children
+pub fn is_inherited(name: &str) -> bool {
+    property(name).is_some_and(|info| info.inherited)
+}
+
+/// Names of the properties that inherit
+pub fn inherited_properties() -> impl Iterator<Item = &'static str> {
+    PROPERTIES.iter().filter(|info| info.inherited).map(|info| info.name)
+}
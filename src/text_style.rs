@@ -0,0 +1,319 @@
+// Text styles - letter and word spacing, case transforms and decoration lines
+//
+// Spacing, `text-transform` and `text-decoration-skip-ink` inherit like
+// fonts do. Decorations do not inherit but propagate: the lines an element
+// draws run through all the text inside it, and descendants can add lines
+// of their own but not take them away.
+
+use crate::css::{Color, CssParser, Unit, Value};
+use crate::style::StyledNode;
+use serde::{Deserialize, Serialize};
+
+/// Font size text is styled at when none is given
+const DEFAULT_FONT_SIZE: f32 = 16.0;
+
+/// Case change `text-transform` makes to text before it is shaped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextTransform {
+    #[default]
+    None,
+    Uppercase,
+    Lowercase,
+    /// First letter of every word upper case
+    Capitalize,
+}
+
+impl TextTransform {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "none" => Some(TextTransform::None),
+            "uppercase" => Some(TextTransform::Uppercase),
+            "lowercase" => Some(TextTransform::Lowercase),
+            "capitalize" => Some(TextTransform::Capitalize),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TextTransform::None => "none",
+            TextTransform::Uppercase => "uppercase",
+            TextTransform::Lowercase => "lowercase",
+            TextTransform::Capitalize => "capitalize",
+        }
+    }
+
+    /// The transform a node's text is drawn with
+    pub fn of(styled: &StyledNode) -> Self {
+        match styled.value("text-transform") {
+            Some(Value::Keyword(keyword)) => Self::from_str(keyword).unwrap_or_default(),
+            _ => TextTransform::None,
+        }
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        match self {
+            TextTransform::None => text.to_string(),
+            TextTransform::Uppercase => text.to_uppercase(),
+            TextTransform::Lowercase => text.to_lowercase(),
+            TextTransform::Capitalize => {
+                let mut capitalized = String::with_capacity(text.len());
+                // Punctuation opening a word, as in "(word", leaves its first letter to capitalize
+                let mut word_start = true;
+                for character in text.chars() {
+                    if word_start && character.is_alphanumeric() {
+                        capitalized.extend(character.to_uppercase());
+                    } else {
+                        capitalized.push(character);
+                    }
+                    word_start = character.is_whitespace() || (word_start && !character.is_alphanumeric());
+                }
+                capitalized
+            }
+        }
+    }
+}
+
+/// Where a decoration line runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DecorationLine {
+    Underline,
+    Overline,
+    LineThrough,
+}
+
+impl DecorationLine {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "underline" => Some(DecorationLine::Underline),
+            "overline" => Some(DecorationLine::Overline),
+            "line-through" => Some(DecorationLine::LineThrough),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DecorationLine::Underline => "underline",
+            DecorationLine::Overline => "overline",
+            DecorationLine::LineThrough => "line-through",
+        }
+    }
+}
+
+/// How a decoration line is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DecorationStyle {
+    #[default]
+    Solid,
+    Double,
+    Dotted,
+    Dashed,
+    Wavy,
+}
+
+impl DecorationStyle {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "solid" => Some(DecorationStyle::Solid),
+            "double" => Some(DecorationStyle::Double),
+            "dotted" => Some(DecorationStyle::Dotted),
+            "dashed" => Some(DecorationStyle::Dashed),
+            "wavy" => Some(DecorationStyle::Wavy),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DecorationStyle::Solid => "solid",
+            DecorationStyle::Double => "double",
+            DecorationStyle::Dotted => "dotted",
+            DecorationStyle::Dashed => "dashed",
+            DecorationStyle::Wavy => "wavy",
+        }
+    }
+}
+
+/// A line drawn under, over or through text
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TextDecoration {
+    pub line: DecorationLine,
+    pub style: DecorationStyle,
+    pub color: Color,
+    /// Thickness in pixels; `None` takes it from the font
+    pub thickness: Option<f32>,
+}
+
+impl TextDecoration {
+    /// The lines an element draws, from `text-decoration` and its longhands
+    ///
+    /// Longhands override the parts of the shorthand they name. Lines are
+    /// drawn in the element's `color` unless given one of their own.
+    pub fn of(styled: &StyledNode) -> Vec<TextDecoration> {
+        let font_size = font_size(styled);
+        let mut lines = Vec::new();
+        let mut style = DecorationStyle::Solid;
+        let mut color = match styled.value("color") {
+            Some(Value::Color(color)) => *color,
+            _ => Color::black(),
+        };
+        let mut thickness = None;
+
+        if let Some(Value::Keyword(shorthand)) = styled.value("text-decoration") {
+            for word in shorthand.split_whitespace() {
+                let word = word.to_ascii_lowercase();
+                if let Some(line) = DecorationLine::from_str(&word) {
+                    lines.push(line);
+                } else if let Some(parsed) = DecorationStyle::from_str(&word) {
+                    style = parsed;
+                } else if word.starts_with('#') {
+                    if let Ok(Value::Color(parsed)) = CssParser::parse_hex_color(&word) {
+                        color = parsed;
+                    }
+                } else if let Some(length) = parse_length(&word, font_size) {
+                    thickness = Some(length);
+                }
+            }
+        }
+        if let Some(Value::Keyword(line)) = styled.value("text-decoration-line") {
+            lines = line.split_whitespace().filter_map(DecorationLine::from_str).collect();
+        }
+        if let Some(Value::Keyword(keyword)) = styled.value("text-decoration-style") {
+            style = DecorationStyle::from_str(keyword).unwrap_or(style);
+        }
+        if let Some(Value::Color(parsed)) = styled.value("text-decoration-color") {
+            color = *parsed;
+        }
+        match styled.value("text-decoration-thickness") {
+            Some(Value::Keyword(_)) => thickness = None,
+            Some(value) => thickness = length_px(value, font_size),
+            None => {}
+        }
+
+        lines.dedup();
+        lines.into_iter().map(|line| TextDecoration { line, style, color, thickness }).collect()
+    }
+}
+
+/// How a run of text is spaced and decorated
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextStyle {
+    /// Extra space after every letter, in pixels
+    pub letter_spacing: f32,
+    /// Extra space after every space between words, in pixels
+    pub word_spacing: f32,
+    /// Lines from the text's element and its ancestors, outermost first
+    pub decorations: Vec<TextDecoration>,
+    /// Break underlines and overlines where they would cross glyphs
+    pub skip_ink: bool,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self { letter_spacing: 0.0, word_spacing: 0.0, decorations: Vec::new(), skip_ink: true }
+    }
+}
+
+impl TextStyle {
+    /// The style a node's text is drawn with, under the decorations propagated to it
+    pub fn of(styled: &StyledNode, decorations: &[TextDecoration]) -> Self {
+        let font_size = font_size(styled);
+        let spacing = |name: &str| styled.value(name).and_then(|value| length_px(value, font_size)).unwrap_or(0.0);
+        let skip_ink = !matches!(styled.value("text-decoration-skip-ink"), Some(Value::Keyword(k)) if k == "none");
+        Self {
+            letter_spacing: spacing("letter-spacing"),
+            word_spacing: spacing("word-spacing"),
+            decorations: decorations.to_vec(),
+            skip_ink,
+        }
+    }
+
+    /// Scale lengths along with the text, as when it is zoomed or transformed
+    pub fn scale(&mut self, factor: f32) {
+        self.letter_spacing *= factor;
+        self.word_spacing *= factor;
+        for decoration in &mut self.decorations {
+            decoration.thickness = decoration.thickness.map(|thickness| thickness * factor);
+        }
+    }
+}
+
+/// Font size of a node in pixels
+fn font_size(styled: &StyledNode) -> f32 {
+    match styled.value("font-size") {
+        Some(Value::Length(size, _)) => *size,
+        _ => DEFAULT_FONT_SIZE,
+    }
+}
+
+/// A length value in pixels; `normal` and other keywords have none
+fn length_px(value: &Value, font_size: f32) -> Option<f32> {
+    match value {
+        Value::Length(length, Unit::Px) => Some(*length),
+        Value::Length(length, Unit::Em) => Some(length * font_size),
+        Value::Length(length, Unit::Rem) => Some(length * DEFAULT_FONT_SIZE),
+        Value::Number(number) if *number == 0.0 => Some(0.0),
+        _ => None,
+    }
+}
+
+/// A length written out, such as `2px` or `0.1em`
+fn parse_length(text: &str, font_size: f32) -> Option<f32> {
+    if let Some(px) = text.strip_suffix("px") {
+        px.parse().ok()
+    } else if let Some(em) = text.strip_suffix("em") {
+        em.parse::<f32>().ok().map(|em| em * font_size)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::CssParser;
+    use crate::dom::Node;
+    use crate::style::style_tree;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_text_transform() {
+        assert_eq!(TextTransform::Uppercase.apply("straße"), "STRASSE");
+        assert_eq!(TextTransform::Lowercase.apply("ÀB"), "àb");
+        assert_eq!(TextTransform::Capitalize.apply("hello (big) world's end"), "Hello (Big) World's End");
+        assert_eq!(TextTransform::from_str("capitalize"), Some(TextTransform::Capitalize));
+    }
+
+    #[test]
+    fn test_spacing_and_decorations_from_styles() {
+        let css = "p { font-size: 20px; letter-spacing: 0.1em; word-spacing: 4px; color: #112233; \
+                   text-decoration: underline dotted 3px; text-decoration-skip-ink: none; } \
+                   em { text-decoration-line: overline line-through; text-decoration-color: #ff0000; }";
+        let stylesheet = CssParser::parse(css);
+        let node = Node::element("p".to_string(), HashMap::new(), vec![Node::text("Hi".to_string())]);
+        let styled = style_tree(&node, &stylesheet);
+        let decorations = TextDecoration::of(&styled);
+        assert_eq!(
+            decorations,
+            vec![TextDecoration {
+                line: DecorationLine::Underline,
+                style: DecorationStyle::Dotted,
+                color: Color::new(0x11, 0x22, 0x33, 255),
+                thickness: Some(3.0),
+            }]
+        );
+
+        // Text inherits the spacing and is decorated by its element
+        let style = TextStyle::of(&styled.children[0], &decorations);
+        assert_eq!((style.letter_spacing, style.word_spacing, style.skip_ink), (2.0, 4.0, false));
+        assert_eq!(style.decorations, decorations);
+
+        let node = Node::element("em".to_string(), HashMap::new(), vec![]);
+        let lines: Vec<(DecorationLine, Color)> =
+            TextDecoration::of(&style_tree(&node, &stylesheet)).iter().map(|d| (d.line, d.color)).collect();
+        let red = Color::new(255, 0, 0, 255);
+        assert_eq!(lines, vec![(DecorationLine::Overline, red), (DecorationLine::LineThrough, red)]);
+        assert!(TextStyle::default().skip_ink);
+    }
+}
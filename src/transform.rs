@@ -0,0 +1,845 @@
+// CSS Transforms - transform lists, their matrices and interpolation
+//
+// A `transform` value is a list of functions composed left to right into a
+// 4x4 matrix that maps points of an element's box about its
+// `transform-origin`. Lists whose functions pair up interpolate function by
+// function; others interpolate their matrices, decomposed into translation,
+// scale, skew, perspective and a rotation quaternion.
+
+use crate::css::Value;
+use crate::layout::Rect;
+use crate::style::StyledNode;
+use std::fmt;
+
+/// 4x4 matrix mapping column vectors, `m[row][column]`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix4 {
+    pub m: [[f32; 4]; 4],
+}
+
+impl Matrix4 {
+    /// Create identity transform
+    pub fn identity() -> Self {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            row[i] = 1.0;
+        }
+        Self { m }
+    }
+
+    /// A 2D affine matrix, as `matrix(a, b, c, d, e, f)` gives it
+    pub fn affine(a: f32, b: f32, c: f32, d: f32, e: f32, f: f32) -> Self {
+        let mut matrix = Self::identity();
+        matrix.m[0] = [a, c, 0.0, e];
+        matrix.m[1] = [b, d, 0.0, f];
+        matrix
+    }
+
+    /// A matrix from 16 values in column order, as `matrix3d()` gives them
+    pub fn from_columns(values: [f32; 16]) -> Self {
+        let mut m = [[0.0; 4]; 4];
+        for (i, value) in values.into_iter().enumerate() {
+            m[i % 4][i / 4] = value;
+        }
+        Self { m }
+    }
+
+    pub fn translate(x: f32, y: f32, z: f32) -> Self {
+        let mut matrix = Self::identity();
+        matrix.m[0][3] = x;
+        matrix.m[1][3] = y;
+        matrix.m[2][3] = z;
+        matrix
+    }
+
+    pub fn scale(x: f32, y: f32, z: f32) -> Self {
+        let mut matrix = Self::identity();
+        matrix.m[0][0] = x;
+        matrix.m[1][1] = y;
+        matrix.m[2][2] = z;
+        matrix
+    }
+
+    /// Rotation by `degrees` about the axis (`x`, `y`, `z`); clockwise on
+    /// screen about the z axis, as y points down
+    pub fn rotate(x: f32, y: f32, z: f32, degrees: f32) -> Self {
+        let length = (x * x + y * y + z * z).sqrt();
+        if length == 0.0 {
+            return Self::identity();
+        }
+        let (x, y, z) = (x / length, y / length, z / length);
+        let half = degrees.to_radians() / 2.0;
+        let (sc, sq) = (half.sin() * half.cos(), half.sin() * half.sin());
+        let mut matrix = Self::identity();
+        matrix.m[0][..3].copy_from_slice(&[
+            1.0 - 2.0 * (y * y + z * z) * sq,
+            2.0 * (x * y * sq - z * sc),
+            2.0 * (x * z * sq + y * sc),
+        ]);
+        matrix.m[1][..3].copy_from_slice(&[
+            2.0 * (x * y * sq + z * sc),
+            1.0 - 2.0 * (x * x + z * z) * sq,
+            2.0 * (y * z * sq - x * sc),
+        ]);
+        matrix.m[2][..3].copy_from_slice(&[
+            2.0 * (x * z * sq - y * sc),
+            2.0 * (y * z * sq + x * sc),
+            1.0 - 2.0 * (x * x + y * y) * sq,
+        ]);
+        matrix
+    }
+
+    /// Skew by angles in degrees along the x and y axes
+    pub fn skew(x_degrees: f32, y_degrees: f32) -> Self {
+        Self::affine(1.0, y_degrees.to_radians().tan(), x_degrees.to_radians().tan(), 1.0, 0.0, 0.0)
+    }
+
+    /// Perspective projection with the viewer `distance` from the z = 0 plane
+    pub fn perspective(distance: f32) -> Self {
+        let mut matrix = Self::identity();
+        // Distances under a pixel are a pixel, so the projection stays finite
+        matrix.m[3][2] = -1.0 / distance.max(1.0);
+        matrix
+    }
+
+    /// The matrix applying `other` first, then this one
+    pub fn multiply(&self, other: &Matrix4) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        Matrix4 { m }
+    }
+
+    pub fn transpose(&self) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Matrix4 { m }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::identity()
+    }
+
+    /// Does the matrix keep z = 0 points there, with no perspective
+    pub fn is_2d(&self) -> bool {
+        let m = &self.m;
+        m[0][2] == 0.0 && m[1][2] == 0.0 && m[2] == [0.0, 0.0, 1.0, 0.0] && m[3] == [0.0, 0.0, 0.0, 1.0]
+    }
+
+    /// Apply transform to a point on the z = 0 plane
+    pub fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        let m = &self.m;
+        let w = m[3][0] * x + m[3][1] * y + m[3][3];
+        let w = if w.abs() < f32::EPSILON { f32::EPSILON } else { w };
+        ((m[0][0] * x + m[0][1] * y + m[0][3]) / w, (m[1][0] * x + m[1][1] * y + m[1][3]) / w)
+    }
+
+    /// Smallest rectangle holding a rectangle once transformed
+    pub fn map_rect(&self, rect: Rect) -> Rect {
+        let corners = [
+            self.apply(rect.x, rect.y),
+            self.apply(rect.x + rect.width, rect.y),
+            self.apply(rect.x + rect.width, rect.y + rect.height),
+            self.apply(rect.x, rect.y + rect.height),
+        ];
+        let (left, top) = corners.iter().fold((f32::INFINITY, f32::INFINITY), |(l, t), p| (l.min(p.0), t.min(p.1)));
+        let (right, bottom) = corners.iter().fold((f32::NEG_INFINITY, f32::NEG_INFINITY), |(r, b), p| {
+            (r.max(p.0), b.max(p.1))
+        });
+        Rect { x: left, y: top, width: right - left, height: bottom - top }
+    }
+
+    pub fn determinant(&self) -> f32 {
+        (0..4).map(|j| self.m[0][j] * self.cofactor(0, j)).sum()
+    }
+
+    fn cofactor(&self, row: usize, column: usize) -> f32 {
+        let mut minor = [[0.0; 3]; 3];
+        for (mi, i) in (0..4).filter(|i| *i != row).enumerate() {
+            for (mj, j) in (0..4).filter(|j| *j != column).enumerate() {
+                minor[mi][mj] = self.m[i][j];
+            }
+        }
+        let determinant = minor[0][0] * (minor[1][1] * minor[2][2] - minor[1][2] * minor[2][1])
+            - minor[0][1] * (minor[1][0] * minor[2][2] - minor[1][2] * minor[2][0])
+            + minor[0][2] * (minor[1][0] * minor[2][1] - minor[1][1] * minor[2][0]);
+        if (row + column).is_multiple_of(2) {
+            determinant
+        } else {
+            -determinant
+        }
+    }
+
+    /// The inverse, if the matrix has one
+    pub fn inverse(&self) -> Option<Matrix4> {
+        let determinant = self.determinant();
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.cofactor(j, i) / determinant;
+            }
+        }
+        Some(Matrix4 { m })
+    }
+
+    /// Split into the parts matrices interpolate by, if the matrix can be
+    ///
+    /// Follows CSS Transforms 2, which works on the transposed matrix.
+    pub fn decompose(&self) -> Option<DecomposedMatrix> {
+        let mut matrix = self.transpose().m;
+        if matrix[3][3] == 0.0 {
+            return None;
+        }
+        let w = matrix[3][3];
+        for value in matrix.iter_mut().flatten() {
+            *value /= w;
+        }
+        let mut perspective_matrix = Matrix4 { m: matrix };
+        for row in perspective_matrix.m.iter_mut().take(3) {
+            row[3] = 0.0;
+        }
+        perspective_matrix.m[3][3] = 1.0;
+        if perspective_matrix.determinant().abs() < 1e-12 {
+            return None;
+        }
+
+        let perspective = if matrix[0][3] != 0.0 || matrix[1][3] != 0.0 || matrix[2][3] != 0.0 {
+            let rhs = [matrix[0][3], matrix[1][3], matrix[2][3], matrix[3][3]];
+            // rhs times the transposed inverse, as a row vector
+            let inverse = perspective_matrix.inverse()?;
+            let mut perspective = [0.0; 4];
+            for (i, value) in perspective.iter_mut().enumerate() {
+                *value = (0..4).map(|j| rhs[j] * inverse.m[i][j]).sum();
+            }
+            perspective
+        } else {
+            [0.0, 0.0, 0.0, 1.0]
+        };
+        let translate = [matrix[3][0], matrix[3][1], matrix[3][2]];
+
+        let mut rows = [[0.0f32; 3]; 3];
+        for (i, row) in rows.iter_mut().enumerate() {
+            row.copy_from_slice(&matrix[i][..3]);
+        }
+        let (mut scale, mut skew) = ([0.0f32; 3], [0.0f32; 3]);
+        scale[0] = length(rows[0]);
+        rows[0] = normalize(rows[0]);
+        skew[0] = dot(rows[0], rows[1]);
+        rows[1] = combine(rows[1], rows[0], 1.0, -skew[0]);
+        scale[1] = length(rows[1]);
+        rows[1] = normalize(rows[1]);
+        skew[0] /= scale[1];
+        skew[1] = dot(rows[0], rows[2]);
+        rows[2] = combine(rows[2], rows[0], 1.0, -skew[1]);
+        skew[2] = dot(rows[1], rows[2]);
+        rows[2] = combine(rows[2], rows[1], 1.0, -skew[2]);
+        scale[2] = length(rows[2]);
+        rows[2] = normalize(rows[2]);
+        skew[1] /= scale[2];
+        skew[2] /= scale[2];
+        // A flipped coordinate system is a negative scale
+        if dot(rows[0], cross(rows[1], rows[2])) < 0.0 {
+            for i in 0..3 {
+                scale[i] = -scale[i];
+                rows[i] = rows[i].map(|v| -v);
+            }
+        }
+
+        let mut quaternion = [
+            0.5 * (1.0 + rows[0][0] - rows[1][1] - rows[2][2]).max(0.0).sqrt(),
+            0.5 * (1.0 - rows[0][0] + rows[1][1] - rows[2][2]).max(0.0).sqrt(),
+            0.5 * (1.0 - rows[0][0] - rows[1][1] + rows[2][2]).max(0.0).sqrt(),
+            0.5 * (1.0 + rows[0][0] + rows[1][1] + rows[2][2]).max(0.0).sqrt(),
+        ];
+        if rows[2][1] > rows[1][2] {
+            quaternion[0] = -quaternion[0];
+        }
+        if rows[0][2] > rows[2][0] {
+            quaternion[1] = -quaternion[1];
+        }
+        if rows[1][0] > rows[0][1] {
+            quaternion[2] = -quaternion[2];
+        }
+        Some(DecomposedMatrix { translate, scale, skew, perspective, quaternion })
+    }
+}
+
+impl Default for Matrix4 {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+fn length(v: [f32; 3]) -> f32 {
+    dot(v, v).sqrt()
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let length = length(v);
+    if length == 0.0 {
+        v
+    } else {
+        v.map(|c| c / length)
+    }
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn combine(a: [f32; 3], b: [f32; 3], a_scale: f32, b_scale: f32) -> [f32; 3] {
+    [0, 1, 2].map(|i| a[i] * a_scale + b[i] * b_scale)
+}
+
+fn lerp(a: f32, b: f32, progress: f32) -> f32 {
+    a + (b - a) * progress
+}
+
+/// A matrix split into the parts it interpolates by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecomposedMatrix {
+    pub translate: [f32; 3],
+    pub scale: [f32; 3],
+    /// XY, XZ and YZ shears
+    pub skew: [f32; 3],
+    pub perspective: [f32; 4],
+    /// Rotation as a unit quaternion (x, y, z, w)
+    pub quaternion: [f32; 4],
+}
+
+impl DecomposedMatrix {
+    /// Interpolate part by part, the rotation along the great circle between them
+    pub fn interpolate(&self, other: &DecomposedMatrix, progress: f32) -> DecomposedMatrix {
+        let mix3 = |a: [f32; 3], b: [f32; 3]| [0, 1, 2].map(|i| lerp(a[i], b[i], progress));
+        let perspective = [0, 1, 2, 3].map(|i| lerp(self.perspective[i], other.perspective[i], progress));
+
+        let (a, b) = (self.quaternion, other.quaternion);
+        let product = (0..4).map(|i| a[i] * b[i]).sum::<f32>().clamp(-1.0, 1.0);
+        let quaternion = if product.abs() >= 1.0 {
+            a
+        } else {
+            let theta = product.acos();
+            let w = (progress * theta).sin() / (1.0 - product * product).sqrt();
+            let a_scale = (progress * theta).cos() - product * w;
+            [0, 1, 2, 3].map(|i| a[i] * a_scale + b[i] * w)
+        };
+        DecomposedMatrix {
+            translate: mix3(self.translate, other.translate),
+            scale: mix3(self.scale, other.scale),
+            skew: mix3(self.skew, other.skew),
+            perspective,
+            quaternion,
+        }
+    }
+
+    /// Put the parts back together into a matrix
+    pub fn recompose(&self) -> Matrix4 {
+        // Built transposed, as decomposed
+        let mut matrix = Matrix4::identity();
+        for i in 0..4 {
+            matrix.m[i][3] = self.perspective[i];
+        }
+        for i in 0..4 {
+            for j in 0..3 {
+                matrix.m[3][i] += self.translate[j] * matrix.m[j][i];
+            }
+        }
+
+        let [x, y, z, w] = self.quaternion;
+        let mut rotation = Matrix4::identity();
+        rotation.m[0][..3].copy_from_slice(&[
+            1.0 - 2.0 * (y * y + z * z),
+            2.0 * (x * y - z * w),
+            2.0 * (x * z + y * w),
+        ]);
+        rotation.m[1][..3].copy_from_slice(&[
+            2.0 * (x * y + z * w),
+            1.0 - 2.0 * (x * x + z * z),
+            2.0 * (y * z - x * w),
+        ]);
+        rotation.m[2][..3].copy_from_slice(&[
+            2.0 * (x * z - y * w),
+            2.0 * (y * z + x * w),
+            1.0 - 2.0 * (x * x + y * y),
+        ]);
+        // The rotation is built for column vectors; transposed it fits the rest
+        matrix = rotation.transpose().multiply(&matrix);
+
+        for (row, column, shear) in [(2, 1, self.skew[2]), (2, 0, self.skew[1]), (1, 0, self.skew[0])] {
+            if shear != 0.0 {
+                let mut skew = Matrix4::identity();
+                skew.m[row][column] = shear;
+                matrix = skew.multiply(&matrix);
+            }
+        }
+        for (i, scale) in self.scale.iter().enumerate() {
+            for value in &mut matrix.m[i] {
+                *value *= scale;
+            }
+        }
+        matrix.transpose()
+    }
+}
+
+/// A transform function, lengths in pixels and angles in degrees
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransformFunction {
+    /// `translate()`, `translate3d()` and the single axis forms
+    Translate(f32, f32, f32),
+    /// `scale()`, `scale3d()` and the single axis forms
+    Scale(f32, f32, f32),
+    /// `rotate3d()`: an axis and an angle; `rotate()` turns about z
+    Rotate(f32, f32, f32, f32),
+    /// `skew()`, `skewX()` and `skewY()`
+    Skew(f32, f32),
+    Perspective(f32),
+    /// `matrix()` and `matrix3d()`
+    Matrix(Matrix4),
+}
+
+impl TransformFunction {
+    pub fn to_matrix(&self) -> Matrix4 {
+        match *self {
+            TransformFunction::Translate(x, y, z) => Matrix4::translate(x, y, z),
+            TransformFunction::Scale(x, y, z) => Matrix4::scale(x, y, z),
+            TransformFunction::Rotate(x, y, z, angle) => Matrix4::rotate(x, y, z, angle),
+            TransformFunction::Skew(x, y) => Matrix4::skew(x, y),
+            TransformFunction::Perspective(distance) => Matrix4::perspective(distance),
+            TransformFunction::Matrix(matrix) => matrix,
+        }
+    }
+
+    /// The function of the same kind that does nothing
+    fn identity_like(&self) -> TransformFunction {
+        match *self {
+            TransformFunction::Translate(..) => TransformFunction::Translate(0.0, 0.0, 0.0),
+            TransformFunction::Scale(..) => TransformFunction::Scale(1.0, 1.0, 1.0),
+            TransformFunction::Rotate(x, y, z, _) => TransformFunction::Rotate(x, y, z, 0.0),
+            TransformFunction::Skew(..) => TransformFunction::Skew(0.0, 0.0),
+            TransformFunction::Perspective(_) => TransformFunction::Perspective(f32::INFINITY),
+            TransformFunction::Matrix(_) => TransformFunction::Matrix(Matrix4::identity()),
+        }
+    }
+
+    /// Interpolate with a function of the same kind, if it is one
+    ///
+    /// Rotations interpolate their angles when they share an axis; other
+    /// pairs go through their matrices.
+    fn interpolate(&self, other: &TransformFunction, progress: f32) -> Option<TransformFunction> {
+        use TransformFunction::*;
+
+        let mix = |a: f32, b: f32| lerp(a, b, progress);
+        Some(match (*self, *other) {
+            (Translate(x1, y1, z1), Translate(x2, y2, z2)) => Translate(mix(x1, x2), mix(y1, y2), mix(z1, z2)),
+            (Scale(x1, y1, z1), Scale(x2, y2, z2)) => Scale(mix(x1, x2), mix(y1, y2), mix(z1, z2)),
+            (Skew(x1, y1), Skew(x2, y2)) => Skew(mix(x1, x2), mix(y1, y2)),
+            (Rotate(x1, y1, z1, a1), Rotate(x2, y2, z2, a2)) => {
+                let (axis1, axis2) = (normalize([x1, y1, z1]), normalize([x2, y2, z2]));
+                if axis1 == axis2 || a1 == 0.0 || a2 == 0.0 {
+                    let [x, y, z] = if a1 == 0.0 { axis2 } else { axis1 };
+                    Rotate(x, y, z, mix(a1, a2))
+                } else {
+                    Matrix(interpolate_matrices(&self.to_matrix(), &other.to_matrix(), progress))
+                }
+            }
+            (Perspective(_), Perspective(_)) | (Matrix(_), Matrix(_)) => {
+                Matrix(interpolate_matrices(&self.to_matrix(), &other.to_matrix(), progress))
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// Interpolate two matrices by their decompositions, or jump halfway if
+/// either cannot be decomposed
+fn interpolate_matrices(from: &Matrix4, to: &Matrix4, progress: f32) -> Matrix4 {
+    match (from.decompose(), to.decompose()) {
+        (Some(from), Some(to)) => from.interpolate(&to, progress).recompose(),
+        _ if progress < 0.5 => *from,
+        _ => *to,
+    }
+}
+
+impl fmt::Display for TransformFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransformFunction::Translate(x, y, z) => write!(f, "translate3d({}px, {}px, {}px)", x, y, z),
+            TransformFunction::Scale(x, y, z) => write!(f, "scale3d({}, {}, {})", x, y, z),
+            TransformFunction::Rotate(x, y, z, angle) => write!(f, "rotate3d({}, {}, {}, {}deg)", x, y, z, angle),
+            TransformFunction::Skew(x, y) => write!(f, "skew({}deg, {}deg)", x, y),
+            TransformFunction::Perspective(distance) => write!(f, "perspective({}px)", distance),
+            TransformFunction::Matrix(matrix) => {
+                let values: Vec<String> = (0..16).map(|i| matrix.m[i % 4][i / 4].to_string()).collect();
+                write!(f, "matrix3d({})", values.join(", "))
+            }
+        }
+    }
+}
+
+/// A `transform` value: functions applied right to left
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Transform {
+    pub functions: Vec<TransformFunction>,
+}
+
+impl Transform {
+    /// The identity transform, `none`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn translate(x: f32, y: f32) -> Self {
+        Self { functions: vec![TransformFunction::Translate(x, y, 0.0)] }
+    }
+
+    pub fn scale(x: f32, y: f32) -> Self {
+        Self { functions: vec![TransformFunction::Scale(x, y, 1.0)] }
+    }
+
+    /// Rotation clockwise on screen, in degrees
+    pub fn rotate(degrees: f32) -> Self {
+        Self { functions: vec![TransformFunction::Rotate(0.0, 0.0, 1.0, degrees)] }
+    }
+
+    /// Parse a `transform` value, e.g. `translate(10px, 0) rotate(45deg)` or `none`
+    ///
+    /// Lengths are pixels; percentages of the box are not supported.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("none") {
+            return Some(Self::new());
+        }
+        let mut functions = Vec::new();
+        let mut rest = value;
+        while !rest.is_empty() {
+            let open = rest.find('(')?;
+            let close = open + rest[open..].find(')')?;
+            let name = rest[..open].trim().to_ascii_lowercase();
+            let args: Vec<&str> = rest[open + 1..close].split(',').map(str::trim).collect();
+            functions.push(parse_function(&name, &args)?);
+            rest = rest[close + 1..].trim_start();
+        }
+        (!functions.is_empty()).then_some(Self { functions })
+    }
+
+    /// An element's `transform`, unless it is `none` or not understood
+    pub fn of(styled: &StyledNode) -> Option<Self> {
+        match styled.value("transform") {
+            Some(Value::Keyword(value)) => Self::parse(value).filter(|transform| !transform.functions.is_empty()),
+            _ => None,
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.to_matrix().is_identity()
+    }
+
+    /// The functions composed into one matrix
+    pub fn to_matrix(&self) -> Matrix4 {
+        self.functions.iter().fold(Matrix4::identity(), |matrix, function| matrix.multiply(&function.to_matrix()))
+    }
+
+    /// The matrix transforming about `origin`, a point in the same space as the box
+    pub fn to_matrix_about(&self, (x, y, z): (f32, f32, f32)) -> Matrix4 {
+        Matrix4::translate(x, y, z).multiply(&self.to_matrix()).multiply(&Matrix4::translate(-x, -y, -z))
+    }
+
+    /// Interpolate towards `other`
+    ///
+    /// `none` stands for the other list with each function doing nothing.
+    /// Lists whose functions pair up by kind interpolate pair by pair;
+    /// others interpolate their whole matrices.
+    pub fn interpolate(&self, other: &Transform, progress: f32) -> Transform {
+        let identity = |list: &Transform| list.functions.iter().map(TransformFunction::identity_like).collect();
+        let from: Vec<TransformFunction> =
+            if self.functions.is_empty() { identity(other) } else { self.functions.clone() };
+        let to: Vec<TransformFunction> =
+            if other.functions.is_empty() { identity(self) } else { other.functions.clone() };
+        if from.len() == to.len() {
+            let paired: Option<Vec<TransformFunction>> =
+                from.iter().zip(&to).map(|(a, b)| a.interpolate(b, progress)).collect();
+            if let Some(functions) = paired {
+                return Transform { functions };
+            }
+        }
+        let matrix = interpolate_matrices(&self.to_matrix(), &other.to_matrix(), progress);
+        Transform { functions: vec![TransformFunction::Matrix(matrix)] }
+    }
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.functions.is_empty() {
+            return write!(f, "none");
+        }
+        let functions: Vec<String> = self.functions.iter().map(|function| function.to_string()).collect();
+        write!(f, "{}", functions.join(" "))
+    }
+}
+
+/// A length in pixels; only `0` may leave out its unit
+fn parse_length(value: &str) -> Option<f32> {
+    match value.strip_suffix("px") {
+        Some(px) => px.trim().parse().ok(),
+        None => value.parse().ok().filter(|n: &f32| *n == 0.0),
+    }
+}
+
+/// An angle in degrees; only `0` may leave out its unit
+fn parse_angle(value: &str) -> Option<f32> {
+    let value = value.to_ascii_lowercase();
+    let (number, per_degree) = if let Some(n) = value.strip_suffix("deg") {
+        (n, 1.0)
+    } else if let Some(n) = value.strip_suffix("grad") {
+        (n, 0.9)
+    } else if let Some(n) = value.strip_suffix("rad") {
+        (n, 180.0 / std::f32::consts::PI)
+    } else if let Some(n) = value.strip_suffix("turn") {
+        (n, 360.0)
+    } else {
+        return value.parse().ok().filter(|n: &f32| *n == 0.0);
+    };
+    number.trim().parse::<f32>().ok().map(|n| n * per_degree)
+}
+
+fn parse_number(value: &str) -> Option<f32> {
+    value.parse().ok()
+}
+
+fn parse_function(name: &str, args: &[&str]) -> Option<TransformFunction> {
+    use TransformFunction::*;
+
+    let lengths = || args.iter().map(|a| parse_length(a)).collect::<Option<Vec<f32>>>();
+    let numbers = || args.iter().map(|a| parse_number(a)).collect::<Option<Vec<f32>>>();
+    let angle = || match args {
+        [angle] => parse_angle(angle),
+        _ => None,
+    };
+    Some(match name {
+        "translate" => match lengths()?.as_slice() {
+            [x] => Translate(*x, 0.0, 0.0),
+            [x, y] => Translate(*x, *y, 0.0),
+            _ => return None,
+        },
+        "translatex" => Translate(parse_length(args.first().filter(|_| args.len() == 1)?)?, 0.0, 0.0),
+        "translatey" => Translate(0.0, parse_length(args.first().filter(|_| args.len() == 1)?)?, 0.0),
+        "translatez" => Translate(0.0, 0.0, parse_length(args.first().filter(|_| args.len() == 1)?)?),
+        "translate3d" => match lengths()?.as_slice() {
+            [x, y, z] => Translate(*x, *y, *z),
+            _ => return None,
+        },
+        "scale" => match numbers()?.as_slice() {
+            [s] => Scale(*s, *s, 1.0),
+            [x, y] => Scale(*x, *y, 1.0),
+            _ => return None,
+        },
+        "scalex" | "scaley" | "scalez" => {
+            let s = match numbers()?.as_slice() {
+                [s] => *s,
+                _ => return None,
+            };
+            match name {
+                "scalex" => Scale(s, 1.0, 1.0),
+                "scaley" => Scale(1.0, s, 1.0),
+                _ => Scale(1.0, 1.0, s),
+            }
+        }
+        "scale3d" => match numbers()?.as_slice() {
+            [x, y, z] => Scale(*x, *y, *z),
+            _ => return None,
+        },
+        "rotate" | "rotatez" => Rotate(0.0, 0.0, 1.0, angle()?),
+        "rotatex" => Rotate(1.0, 0.0, 0.0, angle()?),
+        "rotatey" => Rotate(0.0, 1.0, 0.0, angle()?),
+        "rotate3d" => match args {
+            [x, y, z, a] => Rotate(parse_number(x)?, parse_number(y)?, parse_number(z)?, parse_angle(a)?),
+            _ => return None,
+        },
+        "skew" => match args {
+            [x] => Skew(parse_angle(x)?, 0.0),
+            [x, y] => Skew(parse_angle(x)?, parse_angle(y)?),
+            _ => return None,
+        },
+        "skewx" => Skew(angle()?, 0.0),
+        "skewy" => Skew(0.0, angle()?),
+        "perspective" => match args {
+            [distance] => Perspective(parse_length(distance).filter(|d| *d >= 0.0)?),
+            _ => return None,
+        },
+        "matrix" => match numbers()?.as_slice() {
+            [a, b, c, d, e, f] => Matrix(Matrix4::affine(*a, *b, *c, *d, *e, *f)),
+            _ => return None,
+        },
+        "matrix3d" => Matrix(Matrix4::from_columns(numbers()?.try_into().ok()?)),
+        _ => return None,
+    })
+}
+
+/// The point an element transforms about, given its border box
+pub fn element_origin(styled: &StyledNode, bounds: Rect) -> (f32, f32, f32) {
+    let origin = match styled.value("transform-origin") {
+        Some(Value::Keyword(origin)) => Some(origin.as_str()),
+        _ => None,
+    };
+    transform_origin(origin, bounds)
+}
+
+/// The point a box transforms about, from its `transform-origin`
+///
+/// Keywords, pixels and percentages of the box are understood; anything
+/// else, or no value, is the box's center.
+pub fn transform_origin(value: Option<&str>, bounds: Rect) -> (f32, f32, f32) {
+    let center = (bounds.x + bounds.width / 2.0, bounds.y + bounds.height / 2.0, 0.0);
+    let Some(value) = value else {
+        return center;
+    };
+    let parts: Vec<String> = value.split_whitespace().map(str::to_ascii_lowercase).collect();
+    if parts.len() > 3 {
+        return center;
+    }
+    // A lone vertical keyword, or keywords given vertical first, swap places
+    let vertical = |part: &str| matches!(part, "top" | "bottom");
+    let horizontal = |part: &str| matches!(part, "left" | "right");
+    let (x, y) = match parts.as_slice() {
+        [only] if vertical(only) => ("center", only.as_str()),
+        [only] => (only.as_str(), "center"),
+        [first, second, ..] if vertical(first) || horizontal(second) => (second.as_str(), first.as_str()),
+        [first, second, ..] => (first.as_str(), second.as_str()),
+        [] => return center,
+    };
+    let resolve = |part: &str, start: f32, size: f32| -> Option<f32> {
+        let fraction = match part {
+            "left" | "top" => Some(0.0),
+            "center" => Some(0.5),
+            "right" | "bottom" => Some(1.0),
+            _ => part.strip_suffix('%').and_then(|p| p.parse::<f32>().ok()).map(|p| p / 100.0),
+        };
+        match fraction {
+            Some(fraction) => Some(start + size * fraction),
+            None => parse_length(part).map(|px| start + px),
+        }
+    };
+    let z = parts.get(2).and_then(|z| parse_length(z)).unwrap_or(0.0);
+    match (resolve(x, bounds.x, bounds.width), resolve(y, bounds.y, bounds.height)) {
+        (Some(x), Some(y)) => (x, y, z),
+        _ => center,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_matrix_eq(a: &Matrix4, b: &Matrix4) {
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((a.m[i][j] - b.m[i][j]).abs() < 1e-3, "{:?} != {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_and_compose() {
+        let transform = Transform::parse("translate(10px, 20px) scale(2)").unwrap();
+        assert_eq!(transform.functions, [
+            TransformFunction::Translate(10.0, 20.0, 0.0),
+            TransformFunction::Scale(2.0, 2.0, 1.0),
+        ]);
+        // The rightmost function applies first
+        assert_eq!(transform.to_matrix().apply(5.0, 10.0), (20.0, 40.0));
+
+        let (x, y) = Transform::parse("rotate(90deg)").unwrap().to_matrix().apply(10.0, 0.0);
+        assert!(x.abs() < 1e-5 && (y - 10.0).abs() < 1e-5);
+        let rotations = ["rotate(0.25turn)", "rotateZ(100grad)", "rotate3d(0, 0, 2, 1.5707964rad)"];
+        for rotation in rotations {
+            assert_matrix_eq(&Transform::parse(rotation).unwrap().to_matrix(), &Matrix4::rotate(0.0, 0.0, 1.0, 90.0));
+        }
+        assert_matrix_eq(
+            &Transform::parse("matrix(1, 2, 3, 4, 5, 6)").unwrap().to_matrix(),
+            &Transform::parse("matrix3d(1, 2, 0, 0, 3, 4, 0, 0, 0, 0, 1, 0, 5, 6, 0, 1)").unwrap().to_matrix(),
+        );
+        let (x, y) = Transform::parse("skewX(45deg)").unwrap().to_matrix().apply(0.0, 10.0);
+        assert!((x - 10.0).abs() < 1e-5 && y == 10.0);
+        assert!(!Transform::parse("perspective(100px) rotateY(30deg)").unwrap().to_matrix().is_2d());
+        assert!(Transform::parse("translate(1px) scaleX(3)").unwrap().to_matrix().is_2d());
+
+        assert_eq!(Transform::parse("none"), Some(Transform::new()));
+        for invalid in ["", "translate(10%)", "rotate(45)", "scale(1, 2, 3)", "spin(1deg)", "matrix(1, 2)"] {
+            assert_eq!(Transform::parse(invalid), None, "{}", invalid);
+        }
+        // Printed values parse back to themselves
+        let transform = Transform::parse("translateZ(4px) skew(10deg, 5deg) perspective(50px) matrix(1,0,0,1,2,3)");
+        let transform = transform.unwrap();
+        assert_eq!(Transform::parse(&transform.to_string()), Some(transform));
+    }
+
+    #[test]
+    fn test_origin_and_rects() {
+        let bounds = Rect { x: 100.0, y: 100.0, width: 100.0, height: 50.0 };
+        assert_eq!(transform_origin(None, bounds), (150.0, 125.0, 0.0));
+        assert_eq!(transform_origin(Some("left top"), bounds), (100.0, 100.0, 0.0));
+        assert_eq!(transform_origin(Some("bottom right"), bounds), (200.0, 150.0, 0.0));
+        assert_eq!(transform_origin(Some("top"), bounds), (150.0, 100.0, 0.0));
+        assert_eq!(transform_origin(Some("25% 10px 5px"), bounds), (125.0, 110.0, 5.0));
+
+        // Rotating a quarter turn about the center keeps the center in place
+        let matrix = Transform::rotate(90.0).to_matrix_about(transform_origin(None, bounds));
+        let rect = matrix.map_rect(bounds);
+        assert!((rect.x - 125.0).abs() < 1e-3 && (rect.y - 75.0).abs() < 1e-3);
+        assert!((rect.width - 50.0).abs() < 1e-3 && (rect.height - 100.0).abs() < 1e-3);
+        let inverse = matrix.inverse().unwrap();
+        assert_matrix_eq(&inverse.multiply(&matrix), &Matrix4::identity());
+        assert_eq!(Matrix4::scale(0.0, 1.0, 1.0).inverse(), None);
+    }
+
+    #[test]
+    fn test_decompose_round_trip() {
+        let matrices = [
+            Transform::parse("translate(10px, -5px) rotate(30deg) scale(2, 3)").unwrap().to_matrix(),
+            Transform::parse("rotate3d(1, 1, 0, 45deg) translate3d(1px, 2px, 3px) skew(10deg, 0)").unwrap().to_matrix(),
+            Transform::parse("perspective(200px) rotateX(20deg)").unwrap().to_matrix(),
+            Transform::parse("scale(-1, 1)").unwrap().to_matrix(),
+        ];
+        for matrix in matrices {
+            assert_matrix_eq(&matrix.decompose().unwrap().recompose(), &matrix);
+        }
+        assert_eq!(Matrix4::scale(0.0, 0.0, 1.0).decompose(), None);
+    }
+
+    #[test]
+    fn test_interpolate() {
+        // Lists that pair up interpolate function by function
+        let from = Transform::parse("translate(0px, 0px) rotate(0deg)").unwrap();
+        let to = Transform::parse("translate(100px, 50px) rotate(360deg)").unwrap();
+        assert_eq!(from.interpolate(&to, 0.5).functions, [
+            TransformFunction::Translate(50.0, 25.0, 0.0),
+            TransformFunction::Rotate(0.0, 0.0, 1.0, 180.0),
+        ]);
+        // `none` is the other list doing nothing
+        assert_eq!(Transform::new().interpolate(&Transform::scale(3.0, 3.0), 0.5), Transform::scale(2.0, 2.0));
+
+        // Mismatched lists interpolate their matrices
+        let from = Transform::rotate(0.0);
+        let to = Transform::parse("translate(100px) rotate(90deg)").unwrap();
+        let halfway = from.interpolate(&to, 0.5);
+        assert!(matches!(halfway.functions.as_slice(), [TransformFunction::Matrix(_)]));
+        let expected = Matrix4::translate(50.0, 0.0, 0.0).multiply(&Matrix4::rotate(0.0, 0.0, 1.0, 45.0));
+        assert_matrix_eq(&halfway.to_matrix(), &expected);
+        assert_matrix_eq(&from.interpolate(&to, 0.0).to_matrix(), &from.to_matrix());
+        assert_matrix_eq(&from.interpolate(&to, 1.0).to_matrix(), &to.to_matrix());
+    }
+}
@@ -1,11 +1,135 @@
 // Address bar for URL input
 
+use crate::css::Color;
+use crate::display::{DisplayCommand, DisplayList};
 use crate::layout::Rect;
+use crate::bookmarks::BookmarkManager;
+use crate::history::HistoryDatabase;
+use crate::text_style::TextStyle;
+use url::Url;
+use winit::keyboard::{Key, NamedKey};
+
+/// Maximum number of suggestions shown in the dropdown
+const MAX_SUGGESTIONS: usize = 6;
+/// Height of each suggestion row
+const SUGGESTION_HEIGHT: f32 = 28.0;
+
+const DROPDOWN_BACKGROUND: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+const DROPDOWN_BORDER: Color = Color { r: 190, g: 193, b: 198, a: 255 };
+const HIGHLIGHT: Color = Color { r: 210, g: 227, b: 252, a: 255 };
+const TEXT: Color = Color { r: 32, g: 33, b: 36, a: 255 };
+const URL_TEXT: Color = Color { r: 26, g: 115, b: 232, a: 255 };
+
+/// Search engine used for non-URL input
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchEngine {
+    /// Display name
+    pub name: String,
+    /// URL template; `{searchTerms}` is replaced with the encoded query
+    pub template: String,
+}
+
+impl SearchEngine {
+    /// Create a search engine from a URL template
+    pub fn new(name: &str, template: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            template: template.to_string(),
+        }
+    }
+
+    /// Build the results URL for a query
+    pub fn search_url(&self, query: &str) -> Option<Url> {
+        let encoded: String = url::form_urlencoded::byte_serialize(query.as_bytes()).collect();
+        Url::parse(&self.template.replace("{searchTerms}", &encoded)).ok()
+    }
+}
+
+impl Default for SearchEngine {
+    fn default() -> Self {
+        Self::new("DuckDuckGo", "https://duckduckgo.com/?q={searchTerms}")
+    }
+}
+
+/// Where a suggestion came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuggestionSource {
+    History,
+    Bookmark,
+}
+
+/// An entry in the address bar dropdown
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub url: Url,
+    pub title: Option<String>,
+    pub source: SuggestionSource,
+}
+
+/// Result of a key press in the address bar
+#[derive(Debug, Clone, PartialEq)]
+pub enum AddressBarAction {
+    /// Key was handled, nothing else to do
+    None,
+    /// Navigate to the URL
+    Navigate(Url),
+    /// Editing was cancelled (Escape)
+    Cancel,
+    /// Key was not handled by the address bar
+    Ignored,
+}
+
+/// Turn typed address bar text into a URL
+///
+/// Input with a scheme is used as-is, host-like input gets `https://`,
+/// and anything else becomes a search.
+pub fn resolve_input(input: &str, engine: &SearchEngine) -> Option<Url> {
+    let input = input.trim();
+    if input.is_empty() {
+        return None;
+    }
+
+    if !input.contains(char::is_whitespace) {
+        if let Ok(url) = Url::parse(input) {
+            // "localhost:8080" parses with "localhost" as the scheme
+            if matches!(url.scheme(), "http" | "https" | "file" | "about" | "data") {
+                return Some(url);
+            }
+        }
+
+        if looks_like_host(input) {
+            if let Ok(url) = Url::parse(&format!("https://{}", input)) {
+                return Some(url);
+            }
+        }
+    }
+
+    engine.search_url(input)
+}
+
+/// Does the input look like a host name, optionally with port and path
+fn looks_like_host(input: &str) -> bool {
+    let authority = input.split(['/', '?', '#']).next().unwrap_or("");
+    let host = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.is_empty() && port.chars().all(|c| c.is_ascii_digit()) => host,
+        _ => authority,
+    };
+
+    host == "localhost"
+        || (host.contains('.')
+            && !host.starts_with('.')
+            && !host.ends_with('.')
+            && host.chars().all(|c| c.is_alphanumeric() || c == '.' || c == '-'))
+}
 
 /// Address bar state and rendering
 pub struct AddressBar {
     /// Current URL text
     url: String,
+    /// Caret position (byte offset into `url`)
+    caret: usize,
+    /// Other end of the selection, if any text is selected
+    selection_anchor: Option<usize>,
     /// Is the address bar focused
     focused: bool,
     /// Loading state
@@ -14,6 +138,12 @@ pub struct AddressBar {
     progress: f32,
     /// Visual bounds
     bounds: Rect,
+    /// Engine used for non-URL input
+    search_engine: SearchEngine,
+    /// Dropdown suggestions for the current text
+    suggestions: Vec<Suggestion>,
+    /// Highlighted suggestion
+    highlighted: Option<usize>,
 }
 
 impl AddressBar {
@@ -21,6 +151,8 @@ impl AddressBar {
     pub fn new() -> Self {
         Self {
             url: String::from("about:blank"),
+            caret: "about:blank".len(),
+            selection_anchor: None,
             focused: false,
             loading: false,
             progress: 0.0,
@@ -30,6 +162,9 @@ impl AddressBar {
                 width: 560.0,
                 height: 40.0,
             },
+            search_engine: SearchEngine::default(),
+            suggestions: Vec::new(),
+            highlighted: None,
         }
     }
     
@@ -40,17 +175,30 @@ impl AddressBar {
     
     /// Set the URL (e.g., after navigation)
     pub fn set_url(&mut self, url: String) {
-        self.url = url;
+        self.update_url(url);
+        self.suggestions.clear();
+        self.highlighted = None;
     }
     
     /// Update the URL (e.g., during typing)
     pub fn update_url(&mut self, url: String) {
         self.url = url;
+        self.caret = self.url.len();
+        self.selection_anchor = None;
     }
     
     /// Set focus state
+    ///
+    /// Focusing selects the whole URL so typing replaces it.
     pub fn set_focused(&mut self, focused: bool) {
         self.focused = focused;
+        if focused {
+            self.select_all();
+        } else {
+            self.selection_anchor = None;
+            self.suggestions.clear();
+            self.highlighted = None;
+        }
     }
     
     /// Check if focused
@@ -90,18 +238,77 @@ impl AddressBar {
     pub fn set_width(&mut self, width: f32) {
         self.bounds.width = width;
     }
+
+    /// Get the search engine used for non-URL input
+    pub fn search_engine(&self) -> &SearchEngine {
+        &self.search_engine
+    }
+
+    /// Set the search engine used for non-URL input
+    pub fn set_search_engine(&mut self, engine: SearchEngine) {
+        self.search_engine = engine;
+    }
+
+    /// Get the caret position (byte offset)
+    pub fn caret(&self) -> usize {
+        self.caret
+    }
+
+    /// Get the selected byte range, if any
+    pub fn selection(&self) -> Option<(usize, usize)> {
+        let anchor = self.selection_anchor?;
+        if anchor == self.caret {
+            None
+        } else {
+            Some((anchor.min(self.caret), anchor.max(self.caret)))
+        }
+    }
+
+    /// Get the selected text
+    pub fn selected_text(&self) -> Option<&str> {
+        self.selection().map(|(start, end)| &self.url[start..end])
+    }
+
+    /// Select the whole URL
+    pub fn select_all(&mut self) {
+        self.selection_anchor = Some(0);
+        self.caret = self.url.len();
+    }
     
     /// Handle character input
     pub fn insert_char(&mut self, ch: char) {
         if self.focused {
-            self.url.push(ch);
+            self.delete_selection();
+            self.url.insert(self.caret, ch);
+            self.caret += ch.len_utf8();
+        }
+    }
+
+    /// Insert a string at the caret, replacing any selection (e.g., paste)
+    pub fn insert_str(&mut self, text: &str) {
+        if self.focused {
+            self.delete_selection();
+            self.url.insert_str(self.caret, text);
+            self.caret += text.len();
         }
     }
     
     /// Handle backspace
     pub fn backspace(&mut self) {
-        if self.focused {
-            self.url.pop();
+        if self.focused && !self.delete_selection() {
+            if let Some(prev) = self.prev_boundary(self.caret) {
+                self.url.replace_range(prev..self.caret, "");
+                self.caret = prev;
+            }
+        }
+    }
+
+    /// Handle forward delete
+    pub fn delete(&mut self) {
+        if self.focused && !self.delete_selection() {
+            if let Some(next) = self.next_boundary(self.caret) {
+                self.url.replace_range(self.caret..next, "");
+            }
         }
     }
     
@@ -109,8 +316,259 @@ impl AddressBar {
     pub fn clear(&mut self) {
         if self.focused {
             self.url.clear();
+            self.caret = 0;
+            self.selection_anchor = None;
+        }
+    }
+
+    /// Move the caret one character left, extending the selection if requested
+    pub fn move_left(&mut self, extend: bool) {
+        let target = match self.selection() {
+            Some((start, _)) if !extend => start,
+            _ => self.prev_boundary(self.caret).unwrap_or(0),
+        };
+        self.move_caret(target, extend);
+    }
+
+    /// Move the caret one character right, extending the selection if requested
+    pub fn move_right(&mut self, extend: bool) {
+        let target = match self.selection() {
+            Some((_, end)) if !extend => end,
+            _ => self.next_boundary(self.caret).unwrap_or(self.url.len()),
+        };
+        self.move_caret(target, extend);
+    }
+
+    /// Move the caret to the start of the text
+    pub fn move_home(&mut self, extend: bool) {
+        self.move_caret(0, extend);
+    }
+
+    /// Move the caret to the end of the text
+    pub fn move_end(&mut self, extend: bool) {
+        self.move_caret(self.url.len(), extend);
+    }
+
+    /// Resolve the typed text (or highlighted suggestion) to a URL to load
+    pub fn submit(&mut self) -> Option<Url> {
+        let target = match self.highlighted_suggestion() {
+            Some(suggestion) => Some(suggestion.url.clone()),
+            None => resolve_input(&self.url, &self.search_engine),
+        };
+
+        if let Some(ref url) = target {
+            self.set_url(url.to_string());
+            self.set_focused(false);
+        }
+        target
+    }
+
+    /// Handle a key press while focused
+    pub fn handle_key(&mut self, key: &Key, ctrl: bool, shift: bool) -> AddressBarAction {
+        if !self.focused {
+            return AddressBarAction::Ignored;
+        }
+
+        match key {
+            Key::Named(NamedKey::Enter) => match self.submit() {
+                Some(url) => AddressBarAction::Navigate(url),
+                None => AddressBarAction::None,
+            },
+            Key::Named(NamedKey::Escape) => {
+                if self.suggestions.is_empty() {
+                    self.set_focused(false);
+                    AddressBarAction::Cancel
+                } else {
+                    self.suggestions.clear();
+                    self.highlighted = None;
+                    AddressBarAction::None
+                }
+            }
+            Key::Named(NamedKey::Backspace) => {
+                self.backspace();
+                AddressBarAction::None
+            }
+            Key::Named(NamedKey::Delete) => {
+                self.delete();
+                AddressBarAction::None
+            }
+            Key::Named(NamedKey::ArrowLeft) => {
+                self.move_left(shift);
+                AddressBarAction::None
+            }
+            Key::Named(NamedKey::ArrowRight) => {
+                self.move_right(shift);
+                AddressBarAction::None
+            }
+            Key::Named(NamedKey::Home) => {
+                self.move_home(shift);
+                AddressBarAction::None
+            }
+            Key::Named(NamedKey::End) => {
+                self.move_end(shift);
+                AddressBarAction::None
+            }
+            Key::Named(NamedKey::ArrowDown) => {
+                self.move_highlight(1);
+                AddressBarAction::None
+            }
+            Key::Named(NamedKey::ArrowUp) => {
+                self.move_highlight(-1);
+                AddressBarAction::None
+            }
+            Key::Named(NamedKey::Space) => {
+                self.insert_char(' ');
+                AddressBarAction::None
+            }
+            Key::Character(c) if ctrl => {
+                if c.eq_ignore_ascii_case("a") {
+                    self.select_all();
+                    AddressBarAction::None
+                } else {
+                    AddressBarAction::Ignored
+                }
+            }
+            Key::Character(c) => {
+                self.insert_str(c);
+                AddressBarAction::None
+            }
+            _ => AddressBarAction::Ignored,
+        }
+    }
+
+    /// Rebuild the dropdown from history and bookmarks matching the typed text
+    ///
+    /// Bookmarks are listed before history, which is ranked by frecency;
+    /// duplicates are dropped.
+    pub fn update_suggestions(&mut self, history: &HistoryDatabase, bookmarks: &BookmarkManager) {
+        self.highlighted = None;
+        self.suggestions.clear();
+
+        let query = self.url.trim().to_lowercase();
+        if !self.focused || query.is_empty() {
+            return;
+        }
+
+        let matches = |url: &Url, title: Option<&str>| {
+            url.as_str().to_lowercase().contains(&query)
+                || title.is_some_and(|t| t.to_lowercase().contains(&query))
+        };
+
+        let bookmarked = bookmarks
+            .all()
+            .iter()
+            .filter(|b| matches(&b.url, Some(&b.title)))
+            .map(|b| Suggestion {
+                url: b.url.clone(),
+                title: Some(b.title.clone()),
+                source: SuggestionSource::Bookmark,
+            });
+        let visited = history
+            .search(&query, MAX_SUGGESTIONS)
+            .into_iter()
+            .map(|r| Suggestion {
+                url: r.url.clone(),
+                title: r.title.clone(),
+                source: SuggestionSource::History,
+            });
+
+        for suggestion in bookmarked.chain(visited) {
+            if self.suggestions.len() == MAX_SUGGESTIONS {
+                break;
+            }
+            if !self.suggestions.iter().any(|s| s.url == suggestion.url) {
+                self.suggestions.push(suggestion);
+            }
         }
     }
+
+    /// Get the dropdown suggestions
+    pub fn suggestions(&self) -> &[Suggestion] {
+        &self.suggestions
+    }
+
+    /// Get the highlighted suggestion
+    pub fn highlighted_suggestion(&self) -> Option<&Suggestion> {
+        self.highlighted.and_then(|i| self.suggestions.get(i))
+    }
+
+    /// Move the dropdown highlight; moving above the first entry clears it
+    pub fn move_highlight(&mut self, delta: i32) {
+        if self.suggestions.is_empty() {
+            return;
+        }
+        let last = self.suggestions.len() as i32 - 1;
+        let next = match self.highlighted {
+            Some(i) => i as i32 + delta,
+            None if delta > 0 => 0,
+            None => return,
+        };
+        self.highlighted = if next < 0 { None } else { Some(next.min(last) as usize) };
+    }
+
+    /// Bounds of each dropdown row, below the address bar
+    pub fn suggestion_rects(&self) -> Vec<Rect> {
+        (0..self.suggestions.len())
+            .map(|i| Rect {
+                x: self.bounds.x,
+                y: self.bounds.y + self.bounds.height + i as f32 * SUGGESTION_HEIGHT,
+                width: self.bounds.width,
+                height: SUGGESTION_HEIGHT,
+            })
+            .collect()
+    }
+
+    /// Find the suggestion under a point
+    pub fn suggestion_at(&self, x: f32, y: f32) -> Option<&Suggestion> {
+        self.suggestion_rects()
+            .iter()
+            .position(|r| r.contains(x, y))
+            .map(|i| &self.suggestions[i])
+    }
+
+    /// Build display commands for the suggestion dropdown
+    pub fn paint_suggestions(&self) -> DisplayList {
+        let rects = self.suggestion_rects();
+        let (first, last) = match (rects.first(), rects.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Vec::new(),
+        };
+
+        let popup = Rect {
+            x: first.x,
+            y: first.y,
+            width: first.width,
+            height: last.y + last.height - first.y,
+        };
+        let mut list = vec![
+            DisplayCommand::SolidRect { color: DROPDOWN_BACKGROUND, rect: popup },
+            DisplayCommand::Border { color: DROPDOWN_BORDER, rect: popup, widths: (1.0, 1.0, 1.0, 1.0) },
+        ];
+
+        for (i, (suggestion, rect)) in self.suggestions.iter().zip(rects).enumerate() {
+            if self.highlighted == Some(i) {
+                list.push(DisplayCommand::SolidRect { color: HIGHLIGHT, rect });
+            }
+            let marker = match suggestion.source {
+                SuggestionSource::Bookmark => "\u{2605} ",
+                SuggestionSource::History => "",
+            };
+            let (text, color) = match suggestion.title {
+                Some(ref title) => (format!("{}{} \u{2014} {}", marker, title, suggestion.url), TEXT),
+                None => (format!("{}{}", marker, suggestion.url), URL_TEXT),
+            };
+            list.push(DisplayCommand::Text {
+                text,
+                rect: Rect { x: rect.x + 10.0, y: rect.y + 7.0, width: rect.width - 20.0, height: 14.0 },
+                color,
+                font_family: "sans-serif".to_string(),
+                font_size: 13.0,
+                style: TextStyle::default(),
+            });
+        }
+
+        list
+    }
     
     /// Check if the address bar contains a point
     pub fn contains_point(&self, x: f32, y: f32) -> bool {
@@ -119,6 +577,39 @@ impl AddressBar {
             && y >= self.bounds.y
             && y <= self.bounds.y + self.bounds.height
     }
+
+    fn move_caret(&mut self, target: usize, extend: bool) {
+        if extend {
+            self.selection_anchor.get_or_insert(self.caret);
+        } else {
+            self.selection_anchor = None;
+        }
+        self.caret = target;
+    }
+
+    /// Remove the selected text; returns true if there was a selection
+    fn delete_selection(&mut self) -> bool {
+        match self.selection() {
+            Some((start, end)) => {
+                self.url.replace_range(start..end, "");
+                self.caret = start;
+                self.selection_anchor = None;
+                true
+            }
+            None => {
+                self.selection_anchor = None;
+                false
+            }
+        }
+    }
+
+    fn prev_boundary(&self, pos: usize) -> Option<usize> {
+        self.url[..pos].char_indices().next_back().map(|(i, _)| i)
+    }
+
+    fn next_boundary(&self, pos: usize) -> Option<usize> {
+        self.url[pos..].chars().next().map(|c| pos + c.len_utf8())
+    }
 }
 
 impl Default for AddressBar {
@@ -130,6 +621,7 @@ impl Default for AddressBar {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::history::VisitTransition;
     
     #[test]
     fn test_address_bar_creation() {
@@ -190,4 +682,101 @@ mod tests {
         // Outside bounds
         assert!(!bar.contains_point(50.0, 30.0));
     }
+
+    #[test]
+    fn test_caret_and_selection_editing() {
+        let mut bar = AddressBar::new();
+        bar.set_url("example.com".to_string());
+        bar.set_focused(true);
+        assert_eq!(bar.selected_text(), Some("example.com"));
+
+        // Typing replaces the selection
+        bar.insert_char('x');
+        assert_eq!(bar.url(), "x");
+
+        bar.insert_str("abcd");
+        bar.move_left(false);
+        bar.move_left(true);
+        assert_eq!(bar.selected_text(), Some("c"));
+        bar.backspace();
+        assert_eq!(bar.url(), "xabd");
+        assert_eq!(bar.caret(), 3);
+
+        bar.move_home(false);
+        bar.delete();
+        assert_eq!(bar.url(), "abd");
+        bar.move_end(true);
+        assert_eq!(bar.selected_text(), Some("abd"));
+        bar.move_right(false);
+        assert!(bar.selection().is_none());
+        assert_eq!(bar.caret(), 3);
+    }
+
+    #[test]
+    fn test_resolve_input() {
+        let engine = SearchEngine::default();
+        assert_eq!(resolve_input("example.com", &engine).unwrap().as_str(), "https://example.com/");
+        assert_eq!(resolve_input("http://a.org/x", &engine).unwrap().as_str(), "http://a.org/x");
+        assert_eq!(
+            resolve_input("localhost:8080/app", &engine).unwrap().as_str(),
+            "https://localhost:8080/app"
+        );
+        assert_eq!(
+            resolve_input("rust borrow checker", &engine).unwrap().as_str(),
+            "https://duckduckgo.com/?q=rust+borrow+checker"
+        );
+        assert_eq!(
+            resolve_input("rust", &engine).unwrap().as_str(),
+            "https://duckduckgo.com/?q=rust"
+        );
+        assert!(resolve_input("   ", &engine).is_none());
+
+        let custom = SearchEngine::new("Example", "https://search.example/find?term={searchTerms}");
+        assert_eq!(
+            resolve_input("a&b", &custom).unwrap().as_str(),
+            "https://search.example/find?term=a%26b"
+        );
+    }
+
+    #[test]
+    fn test_enter_navigates() {
+        let mut bar = AddressBar::new();
+        bar.set_focused(true);
+        for key in ["r", "u", "s", "t", "-", "l", "a", "n", "g", ".", "o", "r", "g"] {
+            bar.handle_key(&Key::Character(key.into()), false, false);
+        }
+        let action = bar.handle_key(&Key::Named(NamedKey::Enter), false, false);
+        assert_eq!(action, AddressBarAction::Navigate(Url::parse("https://rust-lang.org/").unwrap()));
+        assert!(!bar.is_focused());
+        assert_eq!(bar.url(), "https://rust-lang.org/");
+    }
+
+    #[test]
+    fn test_suggestions_from_history_and_bookmarks() {
+        let mut history = HistoryDatabase::new();
+        history.record_visit(&Url::parse("https://docs.rs/url").unwrap(), VisitTransition::Typed);
+        history.record_visit(&Url::parse("https://example.com/").unwrap(), VisitTransition::Typed);
+        let mut bookmarks = BookmarkManager::new();
+        bookmarks.add(Url::parse("https://docs.rs/").unwrap(), "Docs.rs".to_string());
+
+        let mut bar = AddressBar::new();
+        bar.set_focused(true);
+        bar.insert_str("docs");
+        bar.update_suggestions(&history, &bookmarks);
+
+        let suggestions = bar.suggestions();
+        assert_eq!(suggestions.len(), 2);
+        assert_eq!(suggestions[0].source, SuggestionSource::Bookmark);
+        assert_eq!(suggestions[1].url.as_str(), "https://docs.rs/url");
+
+        let rects = bar.suggestion_rects();
+        assert_eq!(bar.suggestion_at(rects[1].x + 5.0, rects[1].y + 5.0), Some(&suggestions[1]));
+        assert!(!bar.paint_suggestions().is_empty());
+
+        bar.handle_key(&Key::Named(NamedKey::ArrowDown), false, false);
+        bar.handle_key(&Key::Named(NamedKey::ArrowDown), false, false);
+        let action = bar.handle_key(&Key::Named(NamedKey::Enter), false, false);
+        assert_eq!(action, AddressBarAction::Navigate(Url::parse("https://docs.rs/url").unwrap()));
+        assert!(bar.suggestions().is_empty());
+    }
 }
@@ -0,0 +1,337 @@
+// Sign-in prompt shown over the top of the page when a server asks for credentials
+
+use crate::css::Color;
+use crate::display::{DisplayCommand, DisplayList};
+use crate::layout::Rect;
+use crate::net::{AuthRequest, Credentials};
+use crate::text_style::TextStyle;
+use winit::keyboard::{Key, NamedKey};
+
+const BAR_WIDTH: f32 = 420.0;
+const BAR_HEIGHT: f32 = 164.0;
+const FIELD_HEIGHT: f32 = 26.0;
+const BUTTON_WIDTH: f32 = 72.0;
+const BUTTON_HEIGHT: f32 = 26.0;
+
+const BAR_BACKGROUND: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+const BAR_BORDER: Color = Color { r: 190, g: 193, b: 198, a: 255 };
+const FOCUS_BORDER: Color = Color { r: 26, g: 115, b: 232, a: 255 };
+const BAR_TEXT: Color = Color { r: 32, g: 33, b: 36, a: 255 };
+const HINT_TEXT: Color = Color { r: 95, g: 99, b: 104, a: 255 };
+const WARNING_TEXT: Color = Color { r: 197, g: 34, b: 31, a: 255 };
+const SIGN_IN_BACKGROUND: Color = Color { r: 26, g: 115, b: 232, a: 255 };
+const SIGN_IN_TEXT: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+const CANCEL_TEXT: Color = Color { r: 26, g: 115, b: 232, a: 255 };
+
+/// The field typing goes to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthField {
+    Username,
+    Password,
+}
+
+/// Result of a key press or click on the sign-in bar
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthAction {
+    /// Sign in: retry the request with these credentials
+    SignIn(Box<AuthRequest>, Credentials),
+    /// The prompt was dismissed; the server's response stays
+    Cancelled,
+    /// The bar took the input but nothing else follows
+    Handled,
+    /// The input was not for the bar
+    Ignored,
+}
+
+/// Asks for a username and password, with Sign in and Cancel buttons
+///
+/// Unlike the permission bar it keeps its request, as the fields hold
+/// what was typed; the password is only ever drawn masked.
+pub struct AuthBar {
+    request: Option<AuthRequest>,
+    username: String,
+    password: String,
+    focus: AuthField,
+    bounds: Rect,
+}
+
+impl AuthBar {
+    pub fn new() -> Self {
+        Self {
+            request: None,
+            username: String::new(),
+            password: String::new(),
+            focus: AuthField::Username,
+            bounds: Rect { x: 0.0, y: 0.0, width: BAR_WIDTH, height: BAR_HEIGHT },
+        }
+    }
+
+    /// Ask for credentials for a request, with empty fields
+    pub fn open(&mut self, request: AuthRequest) {
+        self.request = Some(request);
+        self.username.clear();
+        self.password.clear();
+        self.focus = AuthField::Username;
+    }
+
+    /// Hide the bar, forgetting what was typed
+    pub fn close(&mut self) {
+        self.request = None;
+        self.username.clear();
+        self.password.clear();
+    }
+
+    /// Is the bar shown
+    pub fn is_open(&self) -> bool {
+        self.request.is_some()
+    }
+
+    /// The request being answered
+    pub fn request(&self) -> Option<&AuthRequest> {
+        self.request.as_ref()
+    }
+
+    /// Place the bar at the top left of the content area
+    pub fn set_position(&mut self, content_left: f32, content_top: f32) {
+        self.bounds.x = content_left + 8.0;
+        self.bounds.y = content_top + 4.0;
+    }
+
+    /// Get the visual bounds
+    pub fn bounds(&self) -> &Rect {
+        &self.bounds
+    }
+
+    /// Check if a point is on the bar, when it is shown
+    pub fn contains_point(&self, x: f32, y: f32) -> bool {
+        self.is_open() && self.bounds.contains(x, y)
+    }
+
+    /// Typing, Tab between fields, Enter to sign in and Escape to cancel
+    pub fn handle_key(&mut self, key: &Key) -> AuthAction {
+        if !self.is_open() {
+            return AuthAction::Ignored;
+        }
+        match key {
+            Key::Named(NamedKey::Escape) => self.cancel(),
+            Key::Named(NamedKey::Enter) => self.sign_in(),
+            Key::Named(NamedKey::Tab) => {
+                self.focus = match self.focus {
+                    AuthField::Username => AuthField::Password,
+                    AuthField::Password => AuthField::Username,
+                };
+                AuthAction::Handled
+            }
+            Key::Named(NamedKey::Backspace) => {
+                self.focused_mut().pop();
+                AuthAction::Handled
+            }
+            Key::Named(NamedKey::Space) => {
+                self.focused_mut().push(' ');
+                AuthAction::Handled
+            }
+            Key::Character(c) => {
+                self.focused_mut().push_str(c);
+                AuthAction::Handled
+            }
+            // The page gets no keys while the bar is up
+            _ => AuthAction::Handled,
+        }
+    }
+
+    /// Focus a field or press a button
+    pub fn handle_click(&mut self, x: f32, y: f32) -> AuthAction {
+        if !self.contains_point(x, y) {
+            return AuthAction::Ignored;
+        }
+        if self.sign_in_button().contains(x, y) {
+            self.sign_in()
+        } else if self.cancel_button().contains(x, y) {
+            self.cancel()
+        } else {
+            if self.field(AuthField::Username).contains(x, y) {
+                self.focus = AuthField::Username;
+            } else if self.field(AuthField::Password).contains(x, y) {
+                self.focus = AuthField::Password;
+            }
+            AuthAction::Handled
+        }
+    }
+
+    fn sign_in(&mut self) -> AuthAction {
+        let Some(request) = self.request.clone() else {
+            return AuthAction::Ignored;
+        };
+        let credentials = Credentials::new(&self.username, &self.password);
+        self.close();
+        AuthAction::SignIn(Box::new(request), credentials)
+    }
+
+    fn cancel(&mut self) -> AuthAction {
+        self.close();
+        AuthAction::Cancelled
+    }
+
+    fn focused_mut(&mut self) -> &mut String {
+        match self.focus {
+            AuthField::Username => &mut self.username,
+            AuthField::Password => &mut self.password,
+        }
+    }
+
+    /// Draw the bar; nothing when it is closed
+    pub fn paint(&self) -> DisplayList {
+        let Some(request) = &self.request else {
+            return Vec::new();
+        };
+        let b = self.bounds;
+        let line = |row: f32| Rect { x: b.x + 12.0, y: b.y + 10.0 + row * 18.0, width: b.width - 24.0, height: 16.0 };
+        let note = if request.retry {
+            Some(("The username or password was not accepted".to_string(), WARNING_TEXT))
+        } else if request.is_insecure() {
+            Some(("Your connection to this site is not private".to_string(), WARNING_TEXT))
+        } else if !request.challenge.realm.is_empty() {
+            Some((format!("The site says: \"{}\"", request.challenge.realm), HINT_TEXT))
+        } else {
+            None
+        };
+
+        let mut list = vec![
+            DisplayCommand::SolidRect { color: BAR_BACKGROUND, rect: b },
+            DisplayCommand::Border { color: BAR_BORDER, rect: b, widths: (1.0, 1.0, 1.0, 1.0) },
+            label(request.message(), line(0.0), BAR_TEXT),
+        ];
+        if let Some((text, color)) = note {
+            list.push(label(text, line(1.0), color));
+        }
+        let masked = "\u{2022}".repeat(self.password.chars().count());
+        for (field, text, placeholder) in [
+            (AuthField::Username, self.username.clone(), "Username"),
+            (AuthField::Password, masked, "Password"),
+        ] {
+            let rect = self.field(field);
+            let border = if field == self.focus { FOCUS_BORDER } else { BAR_BORDER };
+            list.push(DisplayCommand::Border { color: border, rect, widths: (1.0, 1.0, 1.0, 1.0) });
+            let (text, color) = if text.is_empty() { (placeholder.to_string(), HINT_TEXT) } else { (text, BAR_TEXT) };
+            list.push(label(text, inset(rect, 8.0), color));
+        }
+        let sign_in = self.sign_in_button();
+        let cancel = self.cancel_button();
+        list.extend([
+            DisplayCommand::SolidRect { color: SIGN_IN_BACKGROUND, rect: sign_in },
+            label("Sign in".to_string(), inset(sign_in, 12.0), SIGN_IN_TEXT),
+            DisplayCommand::Border { color: BAR_BORDER, rect: cancel, widths: (1.0, 1.0, 1.0, 1.0) },
+            label("Cancel".to_string(), inset(cancel, 12.0), CANCEL_TEXT),
+        ]);
+        list
+    }
+
+    fn field(&self, field: AuthField) -> Rect {
+        let b = self.bounds;
+        let row = match field {
+            AuthField::Username => 0.0,
+            AuthField::Password => 1.0,
+        };
+        Rect { x: b.x + 12.0, y: b.y + 50.0 + row * (FIELD_HEIGHT + 6.0), width: b.width - 24.0, height: FIELD_HEIGHT }
+    }
+
+    fn sign_in_button(&self) -> Rect {
+        let b = self.bounds;
+        Rect {
+            x: b.x + b.width - 12.0 - BUTTON_WIDTH,
+            y: b.y + b.height - 10.0 - BUTTON_HEIGHT,
+            width: BUTTON_WIDTH,
+            height: BUTTON_HEIGHT,
+        }
+    }
+
+    fn cancel_button(&self) -> Rect {
+        let sign_in = self.sign_in_button();
+        Rect { x: sign_in.x - 8.0 - BUTTON_WIDTH, ..sign_in }
+    }
+}
+
+impl Default for AuthBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn label(text: String, rect: Rect, color: Color) -> DisplayCommand {
+    DisplayCommand::Text {
+        text,
+        rect,
+        color,
+        font_family: "sans-serif".to_string(),
+        font_size: 13.0,
+        style: TextStyle::default(),
+    }
+}
+
+/// Where the text of a field or button goes
+fn inset(rect: Rect, padding: f32) -> Rect {
+    Rect { x: rect.x + padding, y: rect.y + 6.0, width: rect.width - 2.0 * padding, height: 16.0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::AuthChallenge;
+    use url::Url;
+
+    fn request(url: &str) -> AuthRequest {
+        AuthRequest {
+            url: Url::parse(url).unwrap(),
+            challenge: AuthChallenge::preferred(["Basic realm=\"Staff\""]).unwrap(),
+            retry: false,
+        }
+    }
+
+    #[test]
+    fn test_typing_and_signing_in() {
+        let mut bar = AuthBar::new();
+        assert_eq!(bar.handle_key(&Key::Character("a".into())), AuthAction::Ignored);
+        assert!(bar.paint().is_empty());
+
+        bar.open(request("https://intranet.test/"));
+        for key in [Key::Character("ann".into()), Key::Named(NamedKey::Tab), Key::Character("pw!".into())] {
+            assert_eq!(bar.handle_key(&key), AuthAction::Handled);
+        }
+        bar.handle_key(&Key::Named(NamedKey::Backspace));
+        let texts: Vec<String> = bar
+            .paint()
+            .into_iter()
+            .filter_map(|command| match command {
+                DisplayCommand::Text { text, .. } => Some(text),
+                _ => None,
+            })
+            .collect();
+        assert!(texts.contains(&"The site says: \"Staff\"".to_string()));
+        assert!(texts.contains(&"\u{2022}\u{2022}".to_string()) && !texts.iter().any(|text| text.contains("pw")));
+
+        assert_eq!(
+            bar.handle_key(&Key::Named(NamedKey::Enter)),
+            AuthAction::SignIn(Box::new(request("https://intranet.test/")), Credentials::new("ann", "pw"))
+        );
+        assert!(!bar.is_open());
+    }
+
+    #[test]
+    fn test_buttons() {
+        let mut bar = AuthBar::new();
+        bar.set_position(0.0, 60.0);
+        bar.open(request("http://intranet.test/"));
+        assert!(bar.request().unwrap().is_insecure());
+        let b = *bar.bounds();
+        let button_y = b.y + b.height - 20.0;
+
+        assert_eq!(bar.handle_click(b.x + 30.0, b.y + 90.0), AuthAction::Handled);
+        assert_eq!(bar.focus, AuthField::Password);
+        assert_eq!(bar.handle_click(b.x + b.width - 40.0 - BUTTON_WIDTH - 8.0, button_y), AuthAction::Cancelled);
+        assert!(!bar.is_open());
+        assert_eq!(bar.handle_click(b.x + 30.0, b.y + 90.0), AuthAction::Ignored);
+
+        bar.open(request("http://intranet.test/"));
+        assert!(matches!(bar.handle_click(b.x + b.width - 40.0, button_y), AuthAction::SignIn(..)));
+    }
+}
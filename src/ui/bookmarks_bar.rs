@@ -0,0 +1,377 @@
+// Bookmarks bar under the address bar and the star (bookmark this page) button
+
+use crate::bookmarks::{BookmarkId, BookmarkItem, BookmarkManager};
+use crate::css::Color;
+use crate::display::{DisplayCommand, DisplayList};
+use crate::layout::Rect;
+use crate::text_style::TextStyle;
+
+/// Height of the bookmarks bar
+pub const BOOKMARKS_BAR_HEIGHT: f32 = 28.0;
+
+/// Height of each row in an open folder's menu
+const MENU_ITEM_HEIGHT: f32 = 26.0;
+const MENU_WIDTH: f32 = 220.0;
+const ITEM_MAX_WIDTH: f32 = 160.0;
+const ITEM_PADDING: f32 = 10.0;
+/// Rough advance per character, used to size bar items
+const CHAR_WIDTH: f32 = 7.0;
+const FONT_SIZE: f32 = 12.0;
+
+const BAR_BACKGROUND: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+const BAR_BORDER: Color = Color { r: 218, g: 220, b: 224, a: 255 };
+const MENU_BACKGROUND: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+const OPEN_FOLDER_BACKGROUND: Color = Color { r: 232, g: 234, b: 237, a: 255 };
+const TEXT: Color = Color { r: 32, g: 33, b: 36, a: 255 };
+const STAR_ON: Color = Color { r: 26, g: 115, b: 232, a: 255 };
+const STAR_OFF: Color = Color { r: 95, g: 99, b: 104, a: 255 };
+
+/// What was clicked in the bookmarks bar or an open folder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookmarksBarHit {
+    /// A bookmark to navigate to
+    Bookmark(BookmarkId),
+    /// A folder, which was opened or closed
+    Folder(BookmarkId),
+}
+
+/// Row of bookmarks shown under the address bar
+pub struct BookmarksBar {
+    bounds: Rect,
+    /// Folder whose contents are shown in a dropdown
+    open_folder: Option<BookmarkId>,
+}
+
+impl BookmarksBar {
+    /// Create a bookmarks bar spanning the window width
+    pub fn new(width: f32) -> Self {
+        Self {
+            bounds: Rect {
+                x: 0.0,
+                y: 0.0,
+                width,
+                height: BOOKMARKS_BAR_HEIGHT,
+            },
+            open_folder: None,
+        }
+    }
+
+    /// Get the visual bounds
+    pub fn bounds(&self) -> &Rect {
+        &self.bounds
+    }
+
+    /// Move the bar's top edge
+    pub fn set_y(&mut self, y: f32) {
+        self.bounds.y = y;
+    }
+
+    /// Update the bar width
+    pub fn set_width(&mut self, width: f32) {
+        self.bounds.width = width;
+    }
+
+    /// Folder whose dropdown is open
+    pub fn open_folder(&self) -> Option<BookmarkId> {
+        self.open_folder
+    }
+
+    /// Close any open folder dropdown
+    pub fn close_folder(&mut self) {
+        self.open_folder = None;
+    }
+
+    /// Top-level items laid out left to right; items that don't fit are dropped
+    pub fn item_rects(&self, bookmarks: &BookmarkManager) -> Vec<(BookmarkItem, Rect)> {
+        let mut x = self.bounds.x + 8.0;
+        let mut items = Vec::new();
+        for item in bookmarks.children(None) {
+            let label = Self::label(bookmarks, item);
+            let width = (label.chars().count() as f32 * CHAR_WIDTH + 2.0 * ITEM_PADDING).min(ITEM_MAX_WIDTH);
+            if x + width > self.bounds.x + self.bounds.width {
+                break;
+            }
+            items.push((
+                item,
+                Rect {
+                    x,
+                    y: self.bounds.y + 2.0,
+                    width,
+                    height: self.bounds.height - 4.0,
+                },
+            ));
+            x += width + 4.0;
+        }
+        items
+    }
+
+    /// Rows of the open folder's dropdown
+    pub fn menu_rects(&self, bookmarks: &BookmarkManager) -> Vec<(BookmarkItem, Rect)> {
+        let Some(folder) = self.open_folder else {
+            return Vec::new();
+        };
+        let anchor = self
+            .item_rects(bookmarks)
+            .into_iter()
+            .find(|(item, _)| *item == BookmarkItem::Folder(folder))
+            .map(|(_, rect)| rect.x)
+            .unwrap_or(self.bounds.x);
+        let top = self.bounds.y + self.bounds.height;
+
+        bookmarks
+            .children(Some(folder))
+            .into_iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let rect = Rect {
+                    x: anchor,
+                    y: top + i as f32 * MENU_ITEM_HEIGHT,
+                    width: MENU_WIDTH,
+                    height: MENU_ITEM_HEIGHT,
+                };
+                (item, rect)
+            })
+            .collect()
+    }
+
+    /// Find the item under a point, in the bar or the open dropdown
+    pub fn hit_test(&self, x: f32, y: f32, bookmarks: &BookmarkManager) -> Option<BookmarkItem> {
+        self.menu_rects(bookmarks)
+            .into_iter()
+            .chain(self.item_rects(bookmarks))
+            .find(|(_, rect)| rect.contains(x, y))
+            .map(|(item, _)| item)
+    }
+
+    /// Does the bar or its open dropdown cover a point
+    pub fn contains_point(&self, x: f32, y: f32, bookmarks: &BookmarkManager) -> bool {
+        self.bounds.contains(x, y) || self.menu_rects(bookmarks).iter().any(|(_, r)| r.contains(x, y))
+    }
+
+    /// Handle a click; folders open (or close) their dropdown
+    ///
+    /// Any click that doesn't land on a folder closes the open dropdown.
+    pub fn handle_click(&mut self, x: f32, y: f32, bookmarks: &BookmarkManager) -> Option<BookmarksBarHit> {
+        let hit = self.hit_test(x, y, bookmarks);
+        match hit {
+            Some(BookmarkItem::Folder(id)) => {
+                self.open_folder = if self.open_folder == Some(id) { None } else { Some(id) };
+                Some(BookmarksBarHit::Folder(id))
+            }
+            Some(BookmarkItem::Bookmark(id)) => {
+                self.open_folder = None;
+                Some(BookmarksBarHit::Bookmark(id))
+            }
+            None => {
+                self.open_folder = None;
+                None
+            }
+        }
+    }
+
+    /// Build display commands for the bar
+    pub fn paint(&self, bookmarks: &BookmarkManager) -> DisplayList {
+        let mut list = vec![
+            DisplayCommand::SolidRect {
+                color: BAR_BACKGROUND,
+                rect: self.bounds,
+            },
+            DisplayCommand::Border {
+                color: BAR_BORDER,
+                rect: self.bounds,
+                widths: (0.0, 0.0, 1.0, 0.0),
+            },
+        ];
+
+        for (item, rect) in self.item_rects(bookmarks) {
+            if self.open_folder.map(BookmarkItem::Folder) == Some(item) {
+                list.push(DisplayCommand::SolidRect {
+                    color: OPEN_FOLDER_BACKGROUND,
+                    rect,
+                });
+            }
+            list.push(Self::label_command(bookmarks, item, rect));
+        }
+
+        list
+    }
+
+    /// Build display commands for the open folder's dropdown, drawn over the page
+    pub fn paint_menu(&self, bookmarks: &BookmarkManager) -> DisplayList {
+        let mut list = Vec::new();
+        let menu = self.menu_rects(bookmarks);
+        if let (Some((_, first)), Some((_, last))) = (menu.first(), menu.last()) {
+            let outline = Rect {
+                height: last.y + last.height - first.y,
+                ..*first
+            };
+            list.push(DisplayCommand::SolidRect {
+                color: MENU_BACKGROUND,
+                rect: outline,
+            });
+            list.push(DisplayCommand::Border {
+                color: BAR_BORDER,
+                rect: outline,
+                widths: (1.0, 1.0, 1.0, 1.0),
+            });
+            for (item, rect) in menu {
+                list.push(Self::label_command(bookmarks, item, rect));
+            }
+        }
+
+        list
+    }
+
+    fn label(bookmarks: &BookmarkManager, item: BookmarkItem) -> String {
+        let title = bookmarks.title(item).unwrap_or("");
+        let title = match (item, title.is_empty()) {
+            (BookmarkItem::Bookmark(id), true) => bookmarks
+                .get(id)
+                .and_then(|b| b.url.host_str().map(str::to_string))
+                .unwrap_or_default(),
+            _ => title.to_string(),
+        };
+        match item {
+            BookmarkItem::Folder(_) => format!("\u{25b8} {}", title),
+            BookmarkItem::Bookmark(_) => title,
+        }
+    }
+
+    fn label_command(bookmarks: &BookmarkManager, item: BookmarkItem, rect: Rect) -> DisplayCommand {
+        DisplayCommand::Text {
+            text: Self::label(bookmarks, item),
+            rect: Rect {
+                x: rect.x + ITEM_PADDING,
+                y: rect.y + (rect.height - FONT_SIZE) / 2.0,
+                width: rect.width - 2.0 * ITEM_PADDING,
+                height: FONT_SIZE,
+            },
+            color: TEXT,
+            font_family: "sans-serif".to_string(),
+            font_size: FONT_SIZE,
+            style: TextStyle::default(),
+        }
+    }
+}
+
+/// Star button at the end of the address bar that bookmarks the page
+pub struct StarButton {
+    bounds: Rect,
+}
+
+impl StarButton {
+    /// Create a star button for an address bar
+    pub fn new(address_bar: &Rect) -> Self {
+        let mut button = Self { bounds: Rect::default() };
+        button.set_position(address_bar);
+        button
+    }
+
+    /// Place the button inside the right end of the address bar
+    pub fn set_position(&mut self, address_bar: &Rect) {
+        let size = address_bar.height - 12.0;
+        self.bounds = Rect {
+            x: address_bar.x + address_bar.width - size - 6.0,
+            y: address_bar.y + 6.0,
+            width: size,
+            height: size,
+        };
+    }
+
+    /// Get the visual bounds
+    pub fn bounds(&self) -> &Rect {
+        &self.bounds
+    }
+
+    /// Check if a point is on the button
+    pub fn contains_point(&self, x: f32, y: f32) -> bool {
+        self.bounds.contains(x, y)
+    }
+
+    /// Filled star for bookmarked pages, outline otherwise
+    pub fn paint(&self, bookmarked: bool) -> DisplayList {
+        vec![DisplayCommand::Text {
+            text: if bookmarked { "\u{2605}" } else { "\u{2606}" }.to_string(),
+            rect: self.bounds,
+            color: if bookmarked { STAR_ON } else { STAR_OFF },
+            font_family: "sans-serif".to_string(),
+            font_size: self.bounds.height,
+            style: TextStyle::default(),
+        }]
+    }
+}
+
+impl Default for StarButton {
+    fn default() -> Self {
+        Self::new(&Rect::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use url::Url;
+
+    fn bookmarks() -> (BookmarkManager, BookmarkId) {
+        let mut bookmarks = BookmarkManager::new();
+        let folder = bookmarks.create_folder("Work".to_string(), None).unwrap();
+        bookmarks
+            .add_to_folder(Url::parse("https://example.com/wiki").unwrap(), "Wiki".to_string(), Some(folder))
+            .unwrap();
+        bookmarks.add(Url::parse("https://example.com/").unwrap(), "Example".to_string());
+        (bookmarks, folder)
+    }
+
+    #[test]
+    fn test_item_layout() {
+        let (bookmarks, folder) = bookmarks();
+        let mut bar = BookmarksBar::new(800.0);
+        bar.set_y(60.0);
+
+        let items = bar.item_rects(&bookmarks);
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].0, BookmarkItem::Folder(folder));
+        assert_eq!(items[0].1.y, 62.0);
+        assert!(items[1].1.x > items[0].1.x + items[0].1.width);
+
+        // Items that don't fit are dropped
+        bar.set_width(100.0);
+        assert_eq!(bar.item_rects(&bookmarks).len(), 1);
+    }
+
+    #[test]
+    fn test_folder_dropdown() {
+        let (bookmarks, folder) = bookmarks();
+        let mut bar = BookmarksBar::new(800.0);
+        let folder_rect = bar.item_rects(&bookmarks)[0].1;
+
+        let hit = bar.handle_click(folder_rect.x + 2.0, folder_rect.y + 2.0, &bookmarks);
+        assert_eq!(hit, Some(BookmarksBarHit::Folder(folder)));
+        assert_eq!(bar.open_folder(), Some(folder));
+
+        let (item, row) = bar.menu_rects(&bookmarks)[0];
+        assert!(bar.contains_point(row.x + 2.0, row.y + 2.0, &bookmarks));
+        let wiki = bookmarks.find(&Url::parse("https://example.com/wiki").unwrap()).unwrap().id;
+        assert_eq!(item, BookmarkItem::Bookmark(wiki));
+        assert_eq!(
+            bar.handle_click(row.x + 2.0, row.y + 2.0, &bookmarks),
+            Some(BookmarksBarHit::Bookmark(wiki))
+        );
+        assert_eq!(bar.open_folder(), None);
+    }
+
+    #[test]
+    fn test_star_button() {
+        let address_bar = Rect { x: 120.0, y: 10.0, width: 560.0, height: 40.0 };
+        let star = StarButton::new(&address_bar);
+        let bounds = star.bounds();
+        assert!(bounds.x + bounds.width <= address_bar.x + address_bar.width);
+        assert!(star.contains_point(bounds.x + 1.0, bounds.y + 1.0));
+        assert!(!star.contains_point(address_bar.x + 1.0, address_bar.y + 1.0));
+
+        match &star.paint(true)[0] {
+            DisplayCommand::Text { text, .. } => assert_eq!(text, "\u{2605}"),
+            other => panic!("unexpected command: {:?}", other),
+        }
+    }
+}
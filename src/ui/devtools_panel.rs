@@ -0,0 +1,1895 @@
+// DevTools panel (F12): console, elements, network, layers, performance and Lighthouse views
+//
+// Docked below or to the right of the page, which is laid out in the space
+// left over. The state shown lives in `DevTools`, shared by every window;
+// the panel only keeps what is per window: where it is docked, the console
+// input line and how far each view is scrolled. The elements view shows the
+// selected node's styles, event listeners and DOM breakpoints beside the
+// tree; edits to styles and breakpoints are handed back to the embedder,
+// which owns the stylesheet and runs the scripts. The layers view lists
+// the page's compositor layers and switches the rendering overlays, the
+// performance view graphs recent frame times above the page's long tasks,
+// and the Lighthouse view grades the page's Core Web Vitals.
+
+use crate::compositor::{Compositor, Layer};
+use crate::css::{Color, Stylesheet};
+use crate::devtools::{
+    computed_style, matched_rules, styled_node_at_path, Console, ConsoleMessageType, DevTools, DevToolsTab,
+    DomBreakpointKind, DomInspector, FrameTimeline, InspectedElement, LayersView, MatchedDeclaration,
+    CacheUse, NetworkFilter, NetworkRequest, NetworkRequestType, StatusFilter, FRAME_BUDGET,
+};
+use crate::display::{box_model_highlight, DisplayCommand, DisplayList};
+use crate::dom::{Node, NodeType};
+use crate::js::EventListenerInfo;
+use crate::layout::Rect;
+use crate::performance::{Performance, PerformanceEntry};
+use crate::style::StyledNode;
+use crate::text_style::TextStyle;
+use crate::web_vitals::{Rating, WebVitals};
+use winit::keyboard::{Key, NamedKey};
+
+const DEFAULT_HEIGHT: f32 = 260.0;
+const DEFAULT_WIDTH: f32 = 590.0;
+/// Space always left for the page
+const MIN_PAGE_SIZE: f32 = 120.0;
+const TAB_BAR_HEIGHT: f32 = 26.0;
+const TAB_WIDTH: f32 = 84.0;
+const BUTTON_WIDTH: f32 = 26.0;
+const ROW_HEIGHT: f32 = 18.0;
+const INDENT: f32 = 14.0;
+/// Approximate glyph width used to cut off long lines
+const CHAR_WIDTH: f32 = 7.0;
+/// Widths of the network table's Method, Status, Type, Size and Time columns
+const NETWORK_COLUMNS: [f32; 5] = [56.0, 64.0, 80.0, 64.0, 64.0];
+/// Type buttons of the network filter bar and the types each lists
+const TYPE_FILTERS: [(&str, &[NetworkRequestType]); 10] = [
+    ("All", &[]),
+    ("Doc", &[NetworkRequestType::Document]),
+    ("CSS", &[NetworkRequestType::Stylesheet]),
+    ("JS", &[NetworkRequestType::Script]),
+    ("Fetch/XHR", &[NetworkRequestType::Fetch, NetworkRequestType::XHR]),
+    ("Img", &[NetworkRequestType::Image]),
+    ("Font", &[NetworkRequestType::Font]),
+    ("Media", &[NetworkRequestType::Media]),
+    ("WS", &[NetworkRequestType::WebSocket]),
+    ("Other", &[NetworkRequestType::Other]),
+];
+
+const PANEL_BACKGROUND: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+const TAB_BAR_BACKGROUND: Color = Color { r: 241, g: 243, b: 244, a: 255 };
+const ACTIVE_TAB: Color = Color { r: 26, g: 115, b: 232, a: 255 };
+const PANEL_BORDER: Color = Color { r: 202, g: 205, b: 209, a: 255 };
+const TEXT: Color = Color { r: 32, g: 33, b: 36, a: 255 };
+const DIM_TEXT: Color = Color { r: 128, g: 134, b: 139, a: 255 };
+const TAG_TEXT: Color = Color { r: 136, g: 18, b: 128, a: 255 };
+const ERROR_TEXT: Color = Color { r: 197, g: 34, b: 31, a: 255 };
+const ERROR_BACKGROUND: Color = Color { r: 252, g: 235, b: 235, a: 255 };
+const GOOD_TEXT: Color = Color { r: 24, g: 128, b: 56, a: 255 };
+const WARNING_TEXT: Color = Color { r: 94, g: 64, b: 0, a: 255 };
+const WARNING_BACKGROUND: Color = Color { r: 254, g: 247, b: 224, a: 255 };
+const SELECTED_ROW: Color = Color { r: 207, g: 232, b: 252, a: 255 };
+const TOOLTIP_BACKGROUND: Color = Color { r: 51, g: 51, b: 51, a: 230 };
+const TOOLTIP_TEXT: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+const TILE: Color = Color { r: 232, g: 234, b: 237, a: 255 };
+/// Largest side of a tile in the layers view's tile grid
+const MAX_TILE_CELL: f32 = 24.0;
+const SLOW_FRAME: Color = Color { r: 242, g: 153, b: 0, a: 255 };
+/// Rows the frame graph takes up in the performance view
+const GRAPH_ROWS: usize = 4;
+/// Frame time drawn at the top of the graph (milliseconds)
+const GRAPH_MAX_MS: f32 = 100.0;
+const FRAME_BAR_WIDTH: f32 = 4.0;
+
+/// Side of the window the panel is docked to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DevToolsDock {
+    Bottom,
+    Right,
+}
+
+/// Result of input to the panel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DevToolsAction {
+    /// Run a line typed into the console
+    Evaluate(String),
+    /// Complete the identifier at the end of the console line (Tab)
+    Complete(String),
+    /// List the properties of a logged object
+    ExpandObject(u32),
+    /// The panel moved or closed; lay the page out again
+    LayoutChanged,
+    /// A node was selected in the elements view
+    NodeSelected(Vec<usize>),
+    /// Switch a declaration of the stylesheet on or off
+    ToggleDeclaration { rule: usize, declaration: usize },
+    /// Give a declaration of the stylesheet a new value, as typed
+    SetDeclarationValue { rule: usize, declaration: usize, value: String },
+    /// Save the requests listed in the network view as a HAR file
+    ExportHar,
+    /// A DOM breakpoint was set or cleared; watch the document again
+    DomBreakpointsChanged,
+    /// Handled within the panel; repaint
+    Handled,
+    /// Not for the panel
+    Ignored,
+}
+
+/// A line of the console view: a message, or a property of an expanded object
+#[derive(Debug, Clone, PartialEq)]
+struct ConsoleRow {
+    msg_type: ConsoleMessageType,
+    text: String,
+    depth: usize,
+    /// Handle of an object that can be expanded
+    object: Option<u32>,
+}
+
+/// Console messages, oldest first, with the properties of expanded objects
+fn console_rows(console: &Console) -> Vec<ConsoleRow> {
+    let mut rows = Vec::new();
+    for message in console.messages() {
+        let text = message.content.lines().next().unwrap_or("").to_string();
+        rows.push(ConsoleRow { msg_type: message.msg_type.clone(), text, depth: 0, object: message.object });
+        if let Some(handle) = message.object {
+            push_property_rows(console, &message.msg_type, handle, 1, &mut rows);
+        }
+    }
+    rows
+}
+
+fn push_property_rows(
+    console: &Console,
+    msg_type: &ConsoleMessageType,
+    handle: u32,
+    depth: usize,
+    rows: &mut Vec<ConsoleRow>,
+) {
+    for property in console.expanded_properties(handle).unwrap_or_default() {
+        rows.push(ConsoleRow {
+            msg_type: msg_type.clone(),
+            text: format!("{}: {}", property.name, property.value.preview),
+            depth,
+            object: property.value.handle,
+        });
+        if let Some(handle) = property.value.handle {
+            push_property_rows(console, msg_type, handle, depth + 1, rows);
+        }
+    }
+}
+
+/// A button of the network filter bar
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FilterButton {
+    /// List these types only, or every type when empty
+    Types(&'static [NetworkRequestType]),
+    /// Go on to the next status filter
+    Status,
+    /// List only the selected request's domain, or every domain again
+    Domain,
+    Export,
+}
+
+/// The active page, as the elements view inspects it
+#[derive(Clone, Copy)]
+pub struct InspectedPage<'a> {
+    /// The document, styled
+    pub styled: &'a StyledNode<'a>,
+    /// Stylesheet applied to the document
+    pub stylesheet: &'a Stylesheet,
+    /// The page's compositor layers
+    pub layers: &'a Compositor,
+    /// The page's Core Web Vitals so far
+    pub vitals: &'a WebVitals,
+    /// The page's performance timeline
+    pub performance: &'a Performance,
+}
+
+/// A line of the elements view
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DomRow {
+    /// Path from the root element, as used by `DomInspector`
+    pub path: Vec<usize>,
+    pub depth: usize,
+    pub label: String,
+    pub has_children: bool,
+    pub expanded: bool,
+}
+
+/// The DOM tree as shown: expanded elements list their children
+///
+/// Whitespace-only text is left out, as are text nodes and comments when
+/// the inspector hides them.
+pub fn dom_rows(inspector: &DomInspector, root: &Node) -> Vec<DomRow> {
+    let mut rows = Vec::new();
+    push_dom_rows(inspector, root, &mut Vec::new(), &mut rows);
+    rows
+}
+
+fn push_dom_rows(inspector: &DomInspector, node: &Node, path: &mut Vec<usize>, rows: &mut Vec<DomRow>) {
+    let label = match &node.node_type {
+        NodeType::Element(data) => {
+            let mut attributes: Vec<_> = data.attributes.iter().collect();
+            attributes.sort();
+            let attributes: String =
+                attributes.iter().map(|(name, value)| format!(" {}=\"{}\"", name, value)).collect();
+            format!("<{}{}>", data.tag_name, attributes)
+        }
+        NodeType::Text(text) if inspector.shows_text_nodes() && !text.trim().is_empty() => {
+            format!("\"{}\"", text.trim())
+        }
+        NodeType::Comment(text) if inspector.shows_comments() => format!("<!--{}-->", text),
+        NodeType::Text(_) | NodeType::Comment(_) => return,
+    };
+    let expanded = inspector.is_expanded(path);
+    rows.push(DomRow {
+        path: path.clone(),
+        depth: path.len(),
+        label,
+        has_children: !node.children.is_empty(),
+        expanded,
+    });
+    if expanded {
+        for (i, child) in node.children.iter().enumerate() {
+            path.push(i);
+            push_dom_rows(inspector, child, path, rows);
+            path.pop();
+        }
+    }
+}
+
+/// A line of the styles pane
+#[derive(Debug, Clone, PartialEq)]
+enum StyleRow {
+    /// `selector {` and the rule's specificity
+    RuleStart { selector: String, specificity: String },
+    /// A declaration of the stylesheet's `rule`
+    Declaration { rule: usize, index: usize, declaration: MatchedDeclaration },
+    RuleEnd,
+    /// Heading of the listeners on the node and its ancestors
+    Listeners,
+    Listener(EventListenerInfo),
+    /// Heading of the breakpoints that can be set on the node
+    Breakpoints,
+    Breakpoint { kind: DomBreakpointKind, set: bool },
+    /// Dim text standing in for an empty section
+    Note(&'static str),
+    /// Heading of the computed values
+    Computed,
+    ComputedValue(String, String),
+}
+
+/// Matched rules, event listeners, DOM breakpoints and computed values of
+/// the node selected in the inspector
+fn style_rows(devtools: &DevTools, page: InspectedPage) -> Vec<StyleRow> {
+    let inspector = &devtools.dom_inspector;
+    let Some(styled) = styled_node_at_path(page.styled, inspector.selected_path()) else {
+        return Vec::new();
+    };
+    if styled.node.element_data().is_none() {
+        return Vec::new();
+    }
+    let ancestors = page.styled.node.ancestors(inspector.selected_path());
+    let mut rows = Vec::new();
+    for rule in matched_rules(styled.node, &ancestors, page.stylesheet) {
+        rows.push(StyleRow::RuleStart { selector: rule.selector, specificity: rule.specificity.to_string() });
+        rows.extend(rule.declarations.into_iter().enumerate().map(|(index, declaration)| {
+            StyleRow::Declaration { rule: rule.index, index, declaration }
+        }));
+        rows.push(StyleRow::RuleEnd);
+    }
+    rows.push(StyleRow::Listeners);
+    if inspector.event_listeners().is_empty() {
+        rows.push(StyleRow::Note("No listeners"));
+    }
+    rows.extend(inspector.event_listeners().iter().cloned().map(StyleRow::Listener));
+    rows.push(StyleRow::Breakpoints);
+    rows.extend(DomBreakpointKind::ALL.into_iter().map(|kind| StyleRow::Breakpoint {
+        kind,
+        set: devtools.dom_breakpoints.is_set(inspector.selected_path(), kind),
+    }));
+    rows.push(StyleRow::Computed);
+    rows.extend(computed_style(styled).into_iter().map(|(name, value)| StyleRow::ComputedValue(name, value)));
+    rows
+}
+
+/// Layers with their depth in the tree, parents first and siblings by z-index
+fn layer_rows(layers: &Compositor) -> Vec<(usize, &Layer)> {
+    fn visit<'a>(layers: &'a Compositor, layer: &'a Layer, depth: usize, rows: &mut Vec<(usize, &'a Layer)>) {
+        rows.push((depth, layer));
+        let mut children: Vec<&Layer> = layer.children.iter().filter_map(|&id| layers.get_layer(id)).collect();
+        children.sort_by_key(|child| child.z_index);
+        for child in children {
+            visit(layers, child, depth + 1, rows);
+        }
+    }
+    let mut rows = Vec::new();
+    if let Some(root) = layers.root_layer_id().and_then(|id| layers.get_layer(id)) {
+        visit(layers, root, 0, &mut rows);
+    }
+    rows
+}
+
+/// What the layers view shows about a layer
+fn layer_details(layer: &Layer, layers: &Compositor) -> Vec<String> {
+    let b = layer.bounds;
+    let damaged = layer.tiles().iter().filter(|tile| layer.is_damaged(tile.coord)).count();
+    vec![
+        layer.name.clone(),
+        layer.reason.map_or("Compositing reason unknown", |reason| reason.description()).to_string(),
+        format!("Size: {} \u{d7} {} at ({}, {})", b.width.round(), b.height.round(), b.x.round(), b.y.round()),
+        format!("z-index: {}  Opacity: {}", layer.z_index, layer.opacity),
+        format!("Tiles: {}, {} damaged", layer.tiles().len(), damaged),
+        format!("Memory: {}", memory_label(layer.memory_bytes(layers.tile_resolution()))),
+    ]
+}
+
+fn memory_label(bytes: usize) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.1} MB", bytes as f32 / (1024.0 * 1024.0))
+    } else {
+        format!("{:.1} kB", bytes as f32 / 1024.0)
+    }
+}
+
+/// Developer tools docked beside the page
+pub struct DevToolsPanel {
+    open: bool,
+    dock: DevToolsDock,
+    /// Height when docked at the bottom
+    height: f32,
+    /// Width when docked at the right
+    width: f32,
+    /// Area below the chrome shared by the page and the panel
+    area: Rect,
+    /// Does the console input line have keyboard focus
+    focused: bool,
+    input: String,
+    /// Lines run from the console, oldest first
+    history: Vec<String>,
+    /// Entry of `history` recalled with the arrow keys
+    history_pos: Option<usize>,
+    /// Names the identifier being typed could complete to
+    suggestions: Vec<String>,
+    /// Rows scrolled: back from the newest message in the console, down
+    /// from the top in the other views
+    scroll: usize,
+    /// Network request whose details are shown
+    selected_request: Option<usize>,
+    /// Lines the request details are scrolled down
+    details_scroll: usize,
+    /// Rows the styles pane is scrolled down
+    styles_scroll: usize,
+    /// Declaration whose value is being typed: stylesheet rule and index
+    editing: Option<(usize, usize)>,
+    /// Value typed for the declaration being edited
+    edit_value: String,
+    /// Is the element picker on: hovering the page highlights elements
+    picking: bool,
+    /// Element highlighted over the page
+    highlight: Option<InspectedElement>,
+}
+
+impl DevToolsPanel {
+    /// Create a closed panel docked at the bottom
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            dock: DevToolsDock::Bottom,
+            height: DEFAULT_HEIGHT,
+            width: DEFAULT_WIDTH,
+            area: Rect::default(),
+            focused: false,
+            input: String::new(),
+            history: Vec::new(),
+            history_pos: None,
+            suggestions: Vec::new(),
+            scroll: 0,
+            selected_request: None,
+            details_scroll: 0,
+            styles_scroll: 0,
+            editing: None,
+            edit_value: String::new(),
+            picking: false,
+            highlight: None,
+        }
+    }
+
+    /// Show or hide the panel
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.focused = self.open;
+        if !self.open {
+            self.set_picking(false);
+        }
+    }
+
+    /// Is the panel shown
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Side the panel is docked to
+    pub fn dock(&self) -> DevToolsDock {
+        self.dock
+    }
+
+    /// Dock the panel to a side of the window
+    pub fn set_dock(&mut self, dock: DevToolsDock) {
+        self.dock = dock;
+    }
+
+    /// Does the console input line have keyboard focus
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    /// Give keyboard focus to the console input line, or take it away
+    pub fn set_focused(&mut self, focused: bool) {
+        self.focused = focused && self.open;
+    }
+
+    /// Text typed into the console input line
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    /// Is the element picker on
+    pub fn is_picking(&self) -> bool {
+        self.picking
+    }
+
+    /// Turn the element picker on or off; turning it off clears the highlight
+    pub fn set_picking(&mut self, picking: bool) {
+        self.picking = picking && self.open;
+        if !self.picking {
+            self.highlight = None;
+        }
+    }
+
+    /// Highlight an element over the page, or clear the highlight
+    pub fn set_highlight(&mut self, element: Option<InspectedElement>) {
+        self.highlight = element;
+    }
+
+    /// Element highlighted over the page
+    pub fn highlighted(&self) -> Option<&InspectedElement> {
+        self.highlight.as_ref()
+    }
+
+    /// Complete the identifier at the end of the console line
+    ///
+    /// It is extended as far as all `candidates` agree; when they differ
+    /// they are listed above the line.
+    pub fn complete(&mut self, candidates: &[String]) {
+        let Some((first, rest)) = candidates.split_first() else {
+            return;
+        };
+        let mut common = first.clone();
+        for candidate in rest {
+            let same = common.chars().zip(candidate.chars()).take_while(|(a, b)| a == b);
+            let len = same.map(|(c, _)| c.len_utf8()).sum();
+            common.truncate(len);
+        }
+        let start = self
+            .input
+            .char_indices()
+            .rev()
+            .find(|&(_, c)| !(c.is_alphanumeric() || c == '_' || c == '$'))
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        if common.len() >= self.input.len() - start {
+            self.input.replace_range(start.., &common);
+        }
+        self.suggestions = if rest.is_empty() { Vec::new() } else { candidates.to_vec() };
+    }
+
+    /// Place the panel in the area below the chrome
+    pub fn set_area(&mut self, area: Rect) {
+        self.area = area;
+    }
+
+    /// Bounds of the panel; empty when closed
+    pub fn bounds(&self) -> Rect {
+        if !self.open {
+            return Rect::default();
+        }
+        let a = self.area;
+        match self.dock {
+            DevToolsDock::Bottom => {
+                let height = self.height.min(a.height - MIN_PAGE_SIZE).max(TAB_BAR_HEIGHT);
+                Rect { y: a.y + a.height - height, height, ..a }
+            }
+            DevToolsDock::Right => {
+                let width = self.width.min(a.width - MIN_PAGE_SIZE).max(TAB_WIDTH);
+                Rect { x: a.x + a.width - width, width, ..a }
+            }
+        }
+    }
+
+    /// What is left of `area` for the page
+    pub fn page_area(&self) -> Rect {
+        let a = self.area;
+        if !self.open {
+            return a;
+        }
+        let panel = self.bounds();
+        match self.dock {
+            DevToolsDock::Bottom => Rect { height: (panel.y - a.y).max(0.0), ..a },
+            DevToolsDock::Right => Rect { width: (panel.x - a.x).max(0.0), ..a },
+        }
+    }
+
+    /// Is a point over the panel
+    pub fn contains_point(&self, x: f32, y: f32) -> bool {
+        let b = self.bounds();
+        self.open && x >= b.x && x < b.x + b.width && y >= b.y && y < b.y + b.height
+    }
+
+    /// Handle a key press while the console input has focus
+    ///
+    /// Enter runs the line, Up/Down recall earlier lines, Tab completes
+    /// names and Esc gives focus back to the page. Esc also leaves the
+    /// element picker.
+    pub fn handle_key(&mut self, key: &Key) -> DevToolsAction {
+        if self.picking && *key == Key::Named(NamedKey::Escape) {
+            self.set_picking(false);
+            return DevToolsAction::Handled;
+        }
+        if !self.open || !self.focused {
+            return DevToolsAction::Ignored;
+        }
+        if let Some((rule, declaration)) = self.editing {
+            return self.handle_edit_key(key, rule, declaration);
+        }
+        self.suggestions.clear();
+
+        match key {
+            Key::Named(NamedKey::Tab) => return DevToolsAction::Complete(self.input.clone()),
+            Key::Named(NamedKey::Escape) => self.focused = false,
+            Key::Named(NamedKey::Enter) => {
+                let line = std::mem::take(&mut self.input);
+                self.history_pos = None;
+                self.scroll = 0;
+                if line.trim().is_empty() {
+                    return DevToolsAction::Handled;
+                }
+                if self.history.last() != Some(&line) {
+                    self.history.push(line.clone());
+                }
+                return DevToolsAction::Evaluate(line);
+            }
+            Key::Named(NamedKey::Backspace) => {
+                self.input.pop();
+            }
+            Key::Named(NamedKey::Space) => self.input.push(' '),
+            Key::Named(NamedKey::ArrowUp) if !self.history.is_empty() => {
+                let pos = self.history_pos.map_or(self.history.len() - 1, |pos| pos.saturating_sub(1));
+                self.history_pos = Some(pos);
+                self.input = self.history[pos].clone();
+            }
+            Key::Named(NamedKey::ArrowDown) => {
+                if let Some(pos) = self.history_pos {
+                    self.history_pos = (pos + 1 < self.history.len()).then_some(pos + 1);
+                    self.input = self.history_pos.map(|pos| self.history[pos].clone()).unwrap_or_default();
+                }
+            }
+            Key::Character(c) => self.input.push_str(c),
+            _ => return DevToolsAction::Ignored,
+        }
+        DevToolsAction::Handled
+    }
+
+    /// Keys while a declaration's value is typed: Enter applies it, Esc
+    /// leaves it as it was
+    fn handle_edit_key(&mut self, key: &Key, rule: usize, declaration: usize) -> DevToolsAction {
+        match key {
+            Key::Named(NamedKey::Escape) => self.editing = None,
+            Key::Named(NamedKey::Enter) => {
+                self.editing = None;
+                let value = std::mem::take(&mut self.edit_value);
+                return DevToolsAction::SetDeclarationValue { rule, declaration, value: value.trim().to_string() };
+            }
+            Key::Named(NamedKey::Backspace) => {
+                self.edit_value.pop();
+            }
+            Key::Named(NamedKey::Space) => self.edit_value.push(' '),
+            Key::Character(c) => self.edit_value.push_str(c),
+            _ => return DevToolsAction::Ignored,
+        }
+        DevToolsAction::Handled
+    }
+
+    /// Handle a click, focusing the console input if it is over the panel
+    ///
+    /// Clicks on the tab bar switch views, start the element picker, dock
+    /// or close the panel; in the elements view they select and expand
+    /// nodes and switch or edit their style declarations, in the network
+    /// view they filter the requests and show a request's details.
+    pub fn handle_click(
+        &mut self,
+        x: f32,
+        y: f32,
+        devtools: &mut DevTools,
+        page: Option<InspectedPage>,
+    ) -> DevToolsAction {
+        if !self.contains_point(x, y) {
+            self.focused = false;
+            self.editing = None;
+            return DevToolsAction::Ignored;
+        }
+        self.focused = true;
+        self.editing = None;
+        let b = self.bounds();
+
+        if y < b.y + TAB_BAR_HEIGHT {
+            if x >= b.x + b.width - BUTTON_WIDTH {
+                self.toggle();
+                return DevToolsAction::LayoutChanged;
+            }
+            if x >= b.x + b.width - BUTTON_WIDTH * 2.0 {
+                self.dock = match self.dock {
+                    DevToolsDock::Bottom => DevToolsDock::Right,
+                    DevToolsDock::Right => DevToolsDock::Bottom,
+                };
+                return DevToolsAction::LayoutChanged;
+            }
+            if x >= b.x + b.width - BUTTON_WIDTH * 3.0 {
+                self.set_picking(!self.picking);
+                return DevToolsAction::Handled;
+            }
+            let tab = match ((x - b.x) / TAB_WIDTH) as usize {
+                0 => DevToolsTab::Console,
+                1 => DevToolsTab::DomInspector,
+                2 => DevToolsTab::Network,
+                3 => DevToolsTab::Layers,
+                4 => DevToolsTab::Performance,
+                5 => DevToolsTab::Lighthouse,
+                _ => return DevToolsAction::Handled,
+            };
+            if tab != devtools.active_tab {
+                devtools.set_active_tab(tab);
+                self.scroll = 0;
+            }
+            return DevToolsAction::Handled;
+        }
+
+        let row = ((y - b.y - TAB_BAR_HEIGHT) / ROW_HEIGHT) as usize;
+        match devtools.active_tab {
+            DevToolsTab::Console => {
+                // Clicking an object's line shows or hides its properties
+                let rows = console_rows(&devtools.console);
+                let shown = &rows[self.console_window(rows.len())];
+                if let Some(handle) = shown.get(row).and_then(|row| row.object) {
+                    if devtools.console.expanded_properties(handle).is_none() {
+                        return DevToolsAction::ExpandObject(handle);
+                    }
+                    devtools.console.collapse(handle);
+                }
+            }
+            DevToolsTab::DomInspector if x >= self.styles_x() => {
+                let Some(page) = page else {
+                    return DevToolsAction::Handled;
+                };
+                let rows = style_rows(devtools, page);
+                match rows.get(self.styles_scroll + row) {
+                    Some(StyleRow::Declaration { rule, index, declaration }) => {
+                        // The checkbox switches the declaration, the rest of the line edits its value
+                        if x < self.styles_x() + 8.0 + CHAR_WIDTH * 3.0 {
+                            return DevToolsAction::ToggleDeclaration { rule: *rule, declaration: *index };
+                        }
+                        self.editing = Some((*rule, *index));
+                        self.edit_value = declaration.value.clone();
+                    }
+                    Some(StyleRow::Listener(listener)) => {
+                        // Show the script line the listener comes from in the console
+                        let location = listener.line.map(|line| format!("(inline):{}", line));
+                        let source = listener.source.clone().unwrap_or_else(|| handler_name(listener));
+                        devtools.console.add_message(ConsoleMessageType::Log, source, location);
+                        devtools.set_active_tab(DevToolsTab::Console);
+                        self.scroll = 0;
+                    }
+                    Some(StyleRow::Breakpoint { kind, .. }) => {
+                        let path = devtools.dom_inspector.selected_path().to_vec();
+                        devtools.dom_breakpoints.toggle(&path, *kind);
+                        return DevToolsAction::DomBreakpointsChanged;
+                    }
+                    _ => {}
+                }
+            }
+            DevToolsTab::DomInspector => {
+                let Some(page) = page else {
+                    return DevToolsAction::Handled;
+                };
+                let rows = dom_rows(&devtools.dom_inspector, page.styled.node);
+                if let Some(clicked) = rows.get(self.scroll + row) {
+                    // A second click on the selected node expands or collapses it
+                    let on_arrow = x < b.x + 8.0 + (clicked.depth as f32 + 1.0) * INDENT;
+                    let reselected = devtools.dom_inspector.selected_path() == clicked.path.as_slice();
+                    if clicked.has_children && (on_arrow || reselected) {
+                        devtools.dom_inspector.toggle_node(clicked.path.clone());
+                    }
+                    devtools.dom_inspector.select_node(clicked.path.clone());
+                    self.styles_scroll = 0;
+                    return DevToolsAction::NodeSelected(clicked.path.clone());
+                }
+            }
+            DevToolsTab::Network if row == 0 => {
+                let buttons = self.filter_buttons(devtools.network.filter());
+                let clicked = buttons.iter().find(|(rect, ..)| x >= rect.x && x < rect.x + rect.width);
+                if let Some(&(_, button, ..)) = clicked {
+                    return self.click_filter_button(button, devtools);
+                }
+            }
+            DevToolsTab::Network => {
+                // Below the filter bar, the first row is the table header
+                let listed = devtools.network.filtered_requests();
+                let clicked = row.checked_sub(2).and_then(|row| listed.get(self.scroll + row));
+                self.selected_request = clicked.map(|&(index, _)| index).filter(|_| x < self.network_table_right());
+                self.details_scroll = 0;
+            }
+            DevToolsTab::Layers if row == 0 => {
+                let view = &mut devtools.layers;
+                match self.layer_toggles(view).iter().position(|(rect, ..)| x >= rect.x && x < rect.x + rect.width) {
+                    Some(0) => view.show_paint_rects = !view.show_paint_rects,
+                    Some(1) => view.show_layer_borders = !view.show_layer_borders,
+                    Some(_) => view.slow_animations = !view.slow_animations,
+                    None => {}
+                }
+            }
+            DevToolsTab::Layers if x < self.styles_x() => {
+                let rows = page.map(|page| layer_rows(page.layers)).unwrap_or_default();
+                let clicked = rows.get(self.scroll + row - 1).map(|(_, layer)| layer.id);
+                devtools.layers.select(clicked);
+            }
+            DevToolsTab::Layers | DevToolsTab::Performance | DevToolsTab::Lighthouse => {}
+        }
+        DevToolsAction::Handled
+    }
+
+    /// Paint flashing, layer borders and slow animations switches of the layers view
+    fn layer_toggles(&self, view: &LayersView) -> [(Rect, &'static str, bool); 3] {
+        let b = self.bounds();
+        let y = b.y + TAB_BAR_HEIGHT;
+        let mut x = b.x + 4.0;
+        let toggles = [
+            ("Paint flashing", view.show_paint_rects),
+            ("Layer borders", view.show_layer_borders),
+            ("Slow animations", view.slow_animations),
+        ];
+        toggles.map(|(label, on)| {
+            let width = (label.chars().count() + 2) as f32 * CHAR_WIDTH + 12.0;
+            let rect = Rect { x, y, width, height: ROW_HEIGHT };
+            x += width + 2.0;
+            (rect, label, on)
+        })
+    }
+
+    /// Change the network filter, or ask for the listed requests to be exported
+    fn click_filter_button(&mut self, button: FilterButton, devtools: &mut DevTools) -> DevToolsAction {
+        let mut filter = devtools.network.filter().clone();
+        match button {
+            FilterButton::Types(types) => filter.types = types.to_vec(),
+            FilterButton::Status => {
+                filter.status = match filter.status {
+                    StatusFilter::Any => StatusFilter::Failed,
+                    StatusFilter::Failed => StatusFilter::Successful,
+                    StatusFilter::Successful => StatusFilter::Pending,
+                    StatusFilter::Pending => StatusFilter::Any,
+                }
+            }
+            FilterButton::Domain if filter.domain.is_some() => filter.domain = None,
+            FilterButton::Domain => {
+                let selected = self.selected_request.and_then(|index| devtools.network.requests().get(index));
+                filter.domain = selected.and_then(|request| request.url.host_str()).map(str::to_string);
+            }
+            FilterButton::Export => return DevToolsAction::ExportHar,
+        }
+        let selected = self.selected_request.and_then(|index| devtools.network.requests().get(index));
+        if !selected.is_some_and(|request| filter.matches(request)) {
+            self.selected_request = None;
+        }
+        devtools.network.set_filter(filter);
+        self.scroll = 0;
+        DevToolsAction::Handled
+    }
+
+    /// Buttons of the network filter bar, left to right, with whether each is on
+    fn filter_buttons(&self, filter: &NetworkFilter) -> Vec<(Rect, FilterButton, String, bool)> {
+        let b = self.bounds();
+        let status = match filter.status {
+            StatusFilter::Any => "any",
+            StatusFilter::Successful => "ok",
+            StatusFilter::Failed => "failed",
+            StatusFilter::Pending => "pending",
+        };
+        let mut buttons: Vec<(FilterButton, String, bool)> = TYPE_FILTERS
+            .iter()
+            .map(|&(label, types)| (FilterButton::Types(types), label.to_string(), filter.types == types))
+            .collect();
+        buttons.push((FilterButton::Status, format!("Status: {}", status), filter.status != StatusFilter::Any));
+        let domain = filter.domain.as_deref().unwrap_or("all");
+        buttons.push((FilterButton::Domain, format!("Domain: {}", domain), filter.domain.is_some()));
+        buttons.push((FilterButton::Export, "Export HAR".to_string(), false));
+
+        let mut x = b.x + 4.0;
+        let y = b.y + TAB_BAR_HEIGHT;
+        buttons
+            .into_iter()
+            .map(|(button, label, on)| {
+                let width = label.chars().count() as f32 * CHAR_WIDTH + 12.0;
+                let rect = Rect { x, y, width, height: ROW_HEIGHT };
+                x += width + 2.0;
+                (rect, button, label, on)
+            })
+            .collect()
+    }
+
+    /// Scroll the view under `x` by `dy` CSS pixels (positive is down)
+    pub fn scroll_by(&mut self, x: f32, dy: f32, devtools: &DevTools, page: Option<InspectedPage>) {
+        let rows = (dy / ROW_HEIGHT).round() as isize;
+        let selected = self.selected_request.and_then(|index| devtools.network.requests().get(index));
+        if let Some(request) = selected.filter(|_| devtools.active_tab == DevToolsTab::Network) {
+            if x >= self.network_table_right() {
+                let total = request.detail_lines().len();
+                let max = total.saturating_sub(self.visible_rows(DevToolsTab::Network).saturating_sub(1));
+                self.details_scroll = self.details_scroll.saturating_add_signed(rows).min(max);
+                return;
+            }
+        }
+        if devtools.active_tab == DevToolsTab::DomInspector && x >= self.styles_x() {
+            let total = page.map_or(0, |page| style_rows(devtools, page).len());
+            let max = total.saturating_sub(self.visible_rows(DevToolsTab::DomInspector));
+            self.styles_scroll = self.styles_scroll.saturating_add_signed(rows).min(max);
+            return;
+        }
+        let total = match devtools.active_tab {
+            DevToolsTab::Console => console_rows(&devtools.console).len(),
+            DevToolsTab::DomInspector => {
+                page.map_or(0, |page| dom_rows(&devtools.dom_inspector, page.styled.node).len())
+            }
+            // Counting the filter bar and table header, which stay in place
+            DevToolsTab::Network => devtools.network.filtered_requests().len() + 2,
+            // Counting the toolbar
+            DevToolsTab::Layers => page.map_or(0, |page| layer_rows(page.layers).len()) + 1,
+            // Counting the summary, graph and long tasks header
+            DevToolsTab::Performance => page.map_or(0, |page| page.performance.long_tasks().len()) + GRAPH_ROWS + 2,
+            DevToolsTab::Lighthouse => 0,
+        };
+        let max = total.saturating_sub(self.visible_rows(devtools.active_tab));
+        self.scroll = match devtools.active_tab {
+            // The console is scrolled back from its newest message
+            DevToolsTab::Console => self.scroll.saturating_add_signed(-rows),
+            _ => self.scroll.saturating_add_signed(rows),
+        }
+        .min(max);
+    }
+
+    /// Rows that fit in the body of a view, the console's input line aside
+    fn visible_rows(&self, tab: DevToolsTab) -> usize {
+        let mut height = self.bounds().height - TAB_BAR_HEIGHT;
+        if tab == DevToolsTab::Console {
+            height -= ROW_HEIGHT + 6.0;
+        }
+        (height / ROW_HEIGHT).max(0.0) as usize
+    }
+
+    /// Console rows shown, out of `total`: the newest ones, less those
+    /// scrolled back past
+    fn console_window(&self, total: usize) -> std::ops::Range<usize> {
+        let end = total.saturating_sub(self.scroll.min(total));
+        end.saturating_sub(self.visible_rows(DevToolsTab::Console))..end
+    }
+
+    /// Left edge of the styles pane, beside the elements tree
+    fn styles_x(&self) -> f32 {
+        let b = self.bounds();
+        b.x + b.width / 2.0
+    }
+
+    /// Right edge of the network table, which leaves room for details
+    fn network_table_right(&self) -> f32 {
+        let b = self.bounds();
+        if self.selected_request.is_some() {
+            b.x + b.width / 2.0
+        } else {
+            b.x + b.width
+        }
+    }
+
+    /// Build display commands for the panel
+    pub fn paint(&self, devtools: &DevTools, page: Option<InspectedPage>) -> DisplayList {
+        if !self.open {
+            return Vec::new();
+        }
+
+        let b = self.bounds();
+        let mut list = vec![
+            DisplayCommand::SolidRect { color: PANEL_BACKGROUND, rect: b },
+            DisplayCommand::SolidRect { color: TAB_BAR_BACKGROUND, rect: Rect { height: TAB_BAR_HEIGHT, ..b } },
+        ];
+        let edge = match self.dock {
+            DevToolsDock::Bottom => (0.0, 0.0, 1.0, 0.0),
+            DevToolsDock::Right => (1.0, 0.0, 0.0, 0.0),
+        };
+        list.push(DisplayCommand::Border { color: PANEL_BORDER, rect: b, widths: edge });
+        list.push(DisplayCommand::Border {
+            color: PANEL_BORDER,
+            rect: Rect { height: TAB_BAR_HEIGHT, ..b },
+            widths: (0.0, 0.0, 0.0, 1.0),
+        });
+
+        let tabs = [
+            (DevToolsTab::Console, tab_label("Console", devtools.console.error_count())),
+            (DevToolsTab::DomInspector, "Elements".to_string()),
+            (DevToolsTab::Network, tab_label("Network", devtools.network.failed_count())),
+            (DevToolsTab::Layers, "Layers".to_string()),
+            (DevToolsTab::Performance, "Performance".to_string()),
+            (DevToolsTab::Lighthouse, "Lighthouse".to_string()),
+        ];
+        for (i, (tab, label)) in tabs.into_iter().enumerate() {
+            let x = b.x + i as f32 * TAB_WIDTH;
+            let active = tab == devtools.active_tab;
+            if active {
+                list.push(DisplayCommand::SolidRect {
+                    color: ACTIVE_TAB,
+                    rect: Rect { x, y: b.y + TAB_BAR_HEIGHT - 2.0, width: TAB_WIDTH, height: 2.0 },
+                });
+            }
+            list.push(text(label, x + 10.0, b.y + 6.0, TAB_WIDTH - 12.0, if active { ACTIVE_TAB } else { TEXT }));
+        }
+        let dock_icon = match self.dock {
+            DevToolsDock::Bottom => "\u{21e5}",
+            DevToolsDock::Right => "\u{21e9}",
+        };
+        let buttons_x = b.x + b.width - BUTTON_WIDTH * 3.0;
+        let picker_color = if self.picking { ACTIVE_TAB } else { TEXT };
+        list.push(text("\u{2b09}".to_string(), buttons_x + 8.0, b.y + 6.0, BUTTON_WIDTH, picker_color));
+        list.push(text(dock_icon.to_string(), buttons_x + BUTTON_WIDTH + 8.0, b.y + 6.0, BUTTON_WIDTH, TEXT));
+        list.push(text("\u{d7}".to_string(), buttons_x + BUTTON_WIDTH * 2.0 + 8.0, b.y + 6.0, BUTTON_WIDTH, TEXT));
+
+        let body = Rect { y: b.y + TAB_BAR_HEIGHT, height: b.height - TAB_BAR_HEIGHT, ..b };
+        match devtools.active_tab {
+            DevToolsTab::Console => self.paint_console(devtools, body, &mut list),
+            DevToolsTab::DomInspector => {
+                let tree = Rect { width: self.styles_x() - body.x, ..body };
+                self.paint_elements(devtools, page.map(|page| page.styled.node), tree, &mut list);
+                let styles = Rect { x: tree.x + tree.width, width: body.width - tree.width, ..body };
+                self.paint_styles(devtools, page, styles, &mut list);
+            }
+            DevToolsTab::Network => self.paint_network(devtools, body, &mut list),
+            DevToolsTab::Layers => self.paint_layers(devtools, page.map(|page| page.layers), body, &mut list),
+            DevToolsTab::Performance => {
+                self.paint_performance(&devtools.frames, page.map(|page| page.performance), body, &mut list)
+            }
+            DevToolsTab::Lighthouse => self.paint_lighthouse(page.map(|page| page.vitals), body, &mut list),
+        }
+        list
+    }
+
+    /// Box-model overlay and size label for the highlighted element, drawn
+    /// over a page scrolled by `offset_y`
+    pub fn paint_highlight(&self, offset_y: f32) -> DisplayList {
+        let Some(element) = &self.highlight else {
+            return Vec::new();
+        };
+        let mut list = box_model_highlight(&element.dimensions, offset_y);
+        let border_box = element.dimensions.border_box();
+        let label = format!("{}  {} \u{d7} {}", element.label, border_box.width.round(), border_box.height.round());
+        let width = label.chars().count() as f32 * CHAR_WIDTH + 12.0;
+        // Above the element, or below it when there is no room
+        let mut y = border_box.y - offset_y - ROW_HEIGHT - 6.0;
+        if y < self.area.y {
+            y = border_box.y + border_box.height - offset_y + 4.0;
+        }
+        let rect = Rect { x: border_box.x.max(self.area.x), y, width, height: ROW_HEIGHT + 2.0 };
+        list.push(DisplayCommand::SolidRect { color: TOOLTIP_BACKGROUND, rect });
+        list.push(text(label, rect.x + 6.0, rect.y + 3.0, width, TOOLTIP_TEXT));
+        list
+    }
+
+    /// Messages, newest at the bottom, above the input line
+    fn paint_console(&self, devtools: &DevTools, body: Rect, list: &mut DisplayList) {
+        let input_y = body.y + body.height - ROW_HEIGHT - 6.0;
+        let rows = console_rows(&devtools.console);
+        for (row, console_row) in rows[self.console_window(rows.len())].iter().enumerate() {
+            let y = body.y + row as f32 * ROW_HEIGHT;
+            let (color, background, prefix) = match console_row.msg_type {
+                ConsoleMessageType::Error => (ERROR_TEXT, Some(ERROR_BACKGROUND), "\u{2716} "),
+                ConsoleMessageType::Warn => (WARNING_TEXT, Some(WARNING_BACKGROUND), "\u{26a0} "),
+                ConsoleMessageType::Info | ConsoleMessageType::Log => (TEXT, None, ""),
+                ConsoleMessageType::Debug => (DIM_TEXT, None, ""),
+            };
+            if let Some(background) = background {
+                list.push(DisplayCommand::SolidRect {
+                    color: background,
+                    rect: Rect { y, height: ROW_HEIGHT, ..body },
+                });
+            }
+            let arrow = match console_row.object {
+                Some(handle) if devtools.console.expanded_properties(handle).is_some() => "\u{25be} ",
+                Some(_) => "\u{25b8} ",
+                None => "",
+            };
+            let prefix = if console_row.depth == 0 { prefix } else { "" };
+            let line = format!("{}{}{}", prefix, arrow, console_row.text);
+            let x = body.x + 8.0 + console_row.depth as f32 * INDENT;
+            list.push(text(line, x, y + 2.0, body.x + body.width - x - 8.0, color));
+        }
+
+        // Completions for the name being typed, over the newest line
+        if !self.suggestions.is_empty() {
+            let y = input_y - ROW_HEIGHT;
+            let rect = Rect { y, height: ROW_HEIGHT, ..body };
+            list.push(DisplayCommand::SolidRect { color: TAB_BAR_BACKGROUND, rect });
+            list.push(text(self.suggestions.join("  "), body.x + 8.0, y + 2.0, body.width - 16.0, DIM_TEXT));
+        }
+
+        let input = Rect { y: input_y, height: ROW_HEIGHT + 6.0, ..body };
+        list.push(DisplayCommand::Border { color: PANEL_BORDER, rect: input, widths: (0.0, 0.0, 1.0, 0.0) });
+        let caret = if self.focused { "|" } else { "" };
+        let line = format!("> {}{}", self.input, caret);
+        list.push(text(line, body.x + 8.0, input_y + 5.0, body.width - 16.0, ACTIVE_TAB));
+    }
+
+    /// The DOM tree with the selected node highlighted
+    fn paint_elements(&self, devtools: &DevTools, document: Option<&Node>, body: Rect, list: &mut DisplayList) {
+        let Some(root) = document else {
+            list.push(text("No document".to_string(), body.x + 8.0, body.y + 4.0, body.width - 16.0, DIM_TEXT));
+            return;
+        };
+        let rows = self.visible_rows(DevToolsTab::DomInspector);
+        let selected = devtools.dom_inspector.selected_path();
+        for (row, dom_row) in dom_rows(&devtools.dom_inspector, root).iter().skip(self.scroll).take(rows).enumerate() {
+            let y = body.y + row as f32 * ROW_HEIGHT;
+            if dom_row.path == selected {
+                let rect = Rect { y, height: ROW_HEIGHT, ..body };
+                list.push(DisplayCommand::SolidRect { color: SELECTED_ROW, rect });
+            }
+            let x = body.x + 8.0 + dom_row.depth as f32 * INDENT;
+            let arrow = match (dom_row.has_children, dom_row.expanded) {
+                (false, _) => "  ",
+                (true, true) => "\u{25be} ",
+                (true, false) => "\u{25b8} ",
+            };
+            let color = if dom_row.label.starts_with('<') { TAG_TEXT } else { TEXT };
+            list.push(text(format!("{}{}", arrow, dom_row.label), x, y + 2.0, body.x + body.width - x - 8.0, color));
+        }
+    }
+
+    /// Matched rules, overridden declarations struck through, then computed values
+    fn paint_styles(&self, devtools: &DevTools, page: Option<InspectedPage>, area: Rect, list: &mut DisplayList) {
+        list.push(DisplayCommand::Border { color: PANEL_BORDER, rect: area, widths: (1.0, 0.0, 0.0, 0.0) });
+        let rows = page.map(|page| style_rows(devtools, page)).unwrap_or_default();
+        if rows.is_empty() {
+            list.push(text("Select an element".to_string(), area.x + 8.0, area.y + 4.0, area.width - 16.0, DIM_TEXT));
+            return;
+        }
+        let visible = self.visible_rows(DevToolsTab::DomInspector);
+        for (row, style_row) in rows.iter().skip(self.styles_scroll).take(visible).enumerate() {
+            let y = area.y + row as f32 * ROW_HEIGHT;
+            let (line, color) = match style_row {
+                StyleRow::RuleStart { selector, specificity } => {
+                    let width = specificity.chars().count() as f32 * CHAR_WIDTH;
+                    let x = area.x + area.width - width - 8.0;
+                    list.push(text(specificity.clone(), x, y + 2.0, width + 2.0, DIM_TEXT));
+                    (format!("{} {{", selector), TEXT)
+                }
+                StyleRow::Declaration { rule, index, declaration } => {
+                    let checkbox = if declaration.enabled { "\u{2611}" } else { "\u{2610}" };
+                    let line = match self.editing {
+                        Some(editing) if editing == (*rule, *index) => {
+                            format!("{} {}: {}|", checkbox, declaration.name, self.edit_value)
+                        }
+                        _ => format!("{} {}: {};", checkbox, declaration.name, declaration.value),
+                    };
+                    if declaration.overridden {
+                        // Past the indent and the checkbox
+                        let x = area.x + 8.0 + CHAR_WIDTH * 4.0;
+                        let width = ((line.chars().count() as f32 - 2.0) * CHAR_WIDTH).min(area.x + area.width - x);
+                        let rect = Rect { x, y: y + ROW_HEIGHT / 2.0, width, height: 1.0 };
+                        list.push(DisplayCommand::SolidRect { color: DIM_TEXT, rect });
+                    }
+                    let dim = !declaration.enabled || declaration.overridden;
+                    (format!("  {}", line), if dim { DIM_TEXT } else { TAG_TEXT })
+                }
+                StyleRow::RuleEnd => ("}".to_string(), TEXT),
+                StyleRow::Listeners | StyleRow::Breakpoints | StyleRow::Computed => {
+                    let rect = Rect { y, height: ROW_HEIGHT, ..area };
+                    list.push(DisplayCommand::SolidRect { color: TAB_BAR_BACKGROUND, rect });
+                    let heading = match style_row {
+                        StyleRow::Listeners => "Event Listeners",
+                        StyleRow::Breakpoints => "DOM Breakpoints",
+                        _ => "Computed",
+                    };
+                    (heading.to_string(), DIM_TEXT)
+                }
+                StyleRow::Listener(listener) => {
+                    let location = listener.line.map_or("(anonymous)".to_string(), |line| format!("(inline):{}", line));
+                    let width = location.chars().count() as f32 * CHAR_WIDTH;
+                    let x = area.x + area.width - width - 8.0;
+                    list.push(text(location, x, y + 2.0, width + 2.0, ACTIVE_TAB));
+                    let flags: String = [(listener.capture, " capture"), (listener.once, " once")]
+                        .into_iter()
+                        .chain([(listener.passive, " passive")])
+                        .filter_map(|(on, flag)| on.then_some(flag))
+                        .collect();
+                    let handler = handler_name(listener);
+                    (format!("{}  {} {}{}", listener.event_type, listener.target, handler, flags), TAG_TEXT)
+                }
+                StyleRow::Breakpoint { kind, set } => {
+                    let checkbox = if *set { "\u{2611}" } else { "\u{2610}" };
+                    (format!("{} {}", checkbox, kind.label()), TEXT)
+                }
+                StyleRow::Note(note) => (note.to_string(), DIM_TEXT),
+                StyleRow::ComputedValue(name, value) => (format!("{}: {}", name, value), TEXT),
+            };
+            list.push(text(line, area.x + 8.0, y + 2.0, area.width - 16.0, color));
+        }
+    }
+
+    /// Overlay switches, then the layer tree with the selected layer's
+    /// details and tile grid beside it
+    fn paint_layers(&self, devtools: &DevTools, layers: Option<&Compositor>, body: Rect, list: &mut DisplayList) {
+        let bar = Rect { height: ROW_HEIGHT, ..body };
+        list.push(DisplayCommand::Border { color: PANEL_BORDER, rect: bar, widths: (0.0, 0.0, 0.0, 1.0) });
+        for (rect, label, on) in self.layer_toggles(&devtools.layers) {
+            let checkbox = if on { "\u{2611}" } else { "\u{2610}" };
+            list.push(text(format!("{} {}", checkbox, label), rect.x + 6.0, rect.y + 2.0, rect.width, TEXT));
+        }
+        let Some(layers) = layers else {
+            return;
+        };
+        let summary = format!("{} layers, {}", layers.layer_count(), memory_label(layers.memory_bytes()));
+        let width = summary.chars().count() as f32 * CHAR_WIDTH;
+        list.push(text(summary, bar.x + bar.width - width - 8.0, bar.y + 2.0, width + 2.0, DIM_TEXT));
+
+        let body = Rect { y: body.y + ROW_HEIGHT, height: body.height - ROW_HEIGHT, ..body };
+        let tree = Rect { width: self.styles_x() - body.x, ..body };
+        let rows = self.visible_rows(DevToolsTab::Layers).saturating_sub(1);
+        for (row, (depth, layer)) in layer_rows(layers).into_iter().skip(self.scroll).take(rows).enumerate() {
+            let y = tree.y + row as f32 * ROW_HEIGHT;
+            if devtools.layers.selected() == Some(layer.id) {
+                let rect = Rect { y, height: ROW_HEIGHT, ..tree };
+                list.push(DisplayCommand::SolidRect { color: SELECTED_ROW, rect });
+            }
+            let x = tree.x + 8.0 + depth as f32 * INDENT;
+            let size = format!("{} \u{d7} {}", layer.bounds.width.round(), layer.bounds.height.round());
+            let size_width = size.chars().count() as f32 * CHAR_WIDTH;
+            list.push(text(size, tree.x + tree.width - size_width - 8.0, y + 2.0, size_width + 2.0, DIM_TEXT));
+            list.push(text(layer.name.clone(), x, y + 2.0, tree.x + tree.width - size_width - x - 16.0, TAG_TEXT));
+        }
+
+        let details = Rect { x: tree.x + tree.width, width: body.width - tree.width, ..body };
+        list.push(DisplayCommand::Border { color: PANEL_BORDER, rect: details, widths: (1.0, 0.0, 0.0, 1.0) });
+        let Some(layer) = devtools.layers.selected().and_then(|id| layers.get_layer(id)) else {
+            let width = details.width - 16.0;
+            list.push(text("Select a layer".to_string(), details.x + 8.0, details.y + 4.0, width, DIM_TEXT));
+            return;
+        };
+        let lines = layer_details(layer, layers);
+        for (row, line) in lines.iter().enumerate() {
+            let color = if row == 0 { TAG_TEXT } else { TEXT };
+            let y = details.y + row as f32 * ROW_HEIGHT + 2.0;
+            list.push(text(line.clone(), details.x + 8.0, y, details.width - 16.0, color));
+        }
+
+        // Tiles drawn to scale below the details, damaged ones tinted
+        let grid = Rect {
+            x: details.x + 8.0,
+            y: details.y + lines.len() as f32 * ROW_HEIGHT + 6.0,
+            width: details.width - 16.0,
+            height: details.height - lines.len() as f32 * ROW_HEIGHT - 12.0,
+        };
+        let columns = layer.tiles().iter().map(|tile| tile.coord.x).collect::<std::collections::BTreeSet<_>>();
+        let rows = layer.tiles().iter().map(|tile| tile.coord.y).collect::<std::collections::BTreeSet<_>>();
+        let (Some(&first_column), Some(&first_row)) = (columns.first(), rows.first()) else {
+            return;
+        };
+        let cell = (grid.width / columns.len() as f32).min(grid.height / rows.len() as f32).min(MAX_TILE_CELL);
+        if cell < 2.0 {
+            return;
+        }
+        for tile in layer.tiles() {
+            let rect = Rect {
+                x: grid.x + (tile.coord.x - first_column) as f32 * cell,
+                y: grid.y + (tile.coord.y - first_row) as f32 * cell,
+                width: cell - 1.0,
+                height: cell - 1.0,
+            };
+            let color = if layer.is_damaged(tile.coord) { ERROR_BACKGROUND } else { TILE };
+            list.push(DisplayCommand::SolidRect { color, rect });
+            list.push(DisplayCommand::Border { color: PANEL_BORDER, rect, widths: (1.0, 1.0, 1.0, 1.0) });
+        }
+    }
+
+    /// Frame rate summary and a graph of recent frame times against the
+    /// 60fps budget, then the page's long tasks, newest first
+    fn paint_performance(
+        &self,
+        frames: &FrameTimeline,
+        performance: Option<&Performance>,
+        body: Rect,
+        list: &mut DisplayList,
+    ) {
+        let (x, width) = (body.x + 8.0, body.width - 16.0);
+        let summary = if frames.is_empty() {
+            "No frames recorded".to_string()
+        } else {
+            let rate = frames.frame_rate().map(|fps| format!(" \u{b7} {:.0} fps", fps)).unwrap_or_default();
+            let average = frames.average().as_secs_f64() * 1000.0;
+            let janky = frames.janky_count();
+            format!("{} frames{} \u{b7} {:.1} ms average \u{b7} {} janky", frames.len(), rate, average, janky)
+        };
+        list.push(text(summary, x, body.y + 2.0, width, DIM_TEXT));
+
+        // Bars for the newest frames that fit, the budget drawn across them
+        let graph = Rect { x, y: body.y + ROW_HEIGHT, width, height: GRAPH_ROWS as f32 * ROW_HEIGHT - 4.0 };
+        list.push(DisplayCommand::SolidRect { color: TAB_BAR_BACKGROUND, rect: graph });
+        let height = |ms: f32| (ms / GRAPH_MAX_MS).min(1.0) * graph.height;
+        let fit = (graph.width / FRAME_BAR_WIDTH) as usize;
+        for (i, frame) in frames.frames().skip(frames.len().saturating_sub(fit)).enumerate() {
+            let bar = height(frame.duration.as_secs_f32() * 1000.0).max(1.0);
+            let rect = Rect {
+                x: graph.x + i as f32 * FRAME_BAR_WIDTH,
+                y: graph.y + graph.height - bar,
+                width: FRAME_BAR_WIDTH - 1.0,
+                height: bar,
+            };
+            let color = if frame.is_janky() {
+                ERROR_TEXT
+            } else if frame.duration > FRAME_BUDGET {
+                SLOW_FRAME
+            } else {
+                ACTIVE_TAB
+            };
+            list.push(DisplayCommand::SolidRect { color, rect });
+        }
+        let budget_y = graph.y + graph.height - height(FRAME_BUDGET.as_secs_f32() * 1000.0);
+        list.push(DisplayCommand::SolidRect { color: PANEL_BORDER, rect: Rect { y: budget_y, height: 1.0, ..graph } });
+
+        let row_y = |row: usize| body.y + row as f32 * ROW_HEIGHT + 2.0;
+        list.push(text("Long tasks".to_string(), x, row_y(GRAPH_ROWS + 1), width, TEXT));
+        let tasks = performance.map(Performance::long_tasks).unwrap_or_default();
+        if tasks.is_empty() {
+            list.push(text("No long tasks".to_string(), x, row_y(GRAPH_ROWS + 2), width, DIM_TEXT));
+            return;
+        }
+        let rows = self.visible_rows(DevToolsTab::Performance).saturating_sub(GRAPH_ROWS + 2);
+        for (row, task) in tasks.iter().rev().skip(self.scroll).take(rows).enumerate() {
+            let PerformanceEntry::LongTask { start_time, duration, attribution } = task else {
+                continue;
+            };
+            let y = row_y(GRAPH_ROWS + 2 + row);
+            list.push(text(format!("{:.0} ms", start_time), x, y, 92.0, DIM_TEXT));
+            list.push(text(attribution.label().to_string(), x + 100.0, y, 92.0, TAG_TEXT));
+            let color = if *duration >= 100.0 { ERROR_TEXT } else { TEXT };
+            list.push(text(format!("{:.0} ms", duration), x + 200.0, y, width - 200.0, color));
+        }
+    }
+
+    /// The performance score, each Core Web Vital with its rating, then
+    /// what the largest contentful paint painted
+    fn paint_lighthouse(&self, vitals: Option<&WebVitals>, body: Rect, list: &mut DisplayList) {
+        let (x, width) = (body.x + 8.0, body.width - 16.0);
+        let row_y = |row: usize| body.y + row as f32 * ROW_HEIGHT + 4.0;
+        let Some((vitals, score)) = vitals.and_then(|vitals| Some((vitals, vitals.score()?))) else {
+            list.push(text("Waiting for the page to paint".to_string(), x, row_y(0), width, DIM_TEXT));
+            return;
+        };
+        let overall = match score {
+            90.. => Rating::Good,
+            50.. => Rating::NeedsImprovement,
+            _ => Rating::Poor,
+        };
+        list.push(text(format!("Performance {}", score), x, row_y(0), width, rating_color(overall)));
+
+        let value_x = x + 200.0;
+        let rating_x = value_x + 80.0;
+        for (row, (metric, value)) in vitals.metrics().into_iter().enumerate() {
+            let y = row_y(row + 2);
+            let rating = metric.rate(value);
+            list.push(text(metric.name().to_string(), x, y, value_x - x - 8.0, TEXT));
+            list.push(text(metric.format(value), value_x, y, rating_x - value_x - 8.0, TEXT));
+            let label = format!("\u{25cf} {}", rating.label());
+            list.push(text(label, rating_x, y, x + width - rating_x, rating_color(rating)));
+        }
+
+        let mut row = vitals.metrics().len() + 3;
+        if let Some(element) = &vitals.largest_contentful_element {
+            list.push(text(format!("Largest contentful paint: {}", element), x, row_y(row), width, DIM_TEXT));
+            row += 1;
+        }
+        let shifts = format!("Layout shifts: {}", vitals.layout_shifts);
+        list.push(text(shifts, x, row_y(row), width, DIM_TEXT));
+    }
+
+    /// Filter bar, then the request table with the selected request's
+    /// details beside it
+    fn paint_network(&self, devtools: &DevTools, body: Rect, list: &mut DisplayList) {
+        let bar = Rect { height: ROW_HEIGHT, ..body };
+        list.push(DisplayCommand::Border { color: PANEL_BORDER, rect: bar, widths: (0.0, 0.0, 0.0, 1.0) });
+        let buttons = self.filter_buttons(devtools.network.filter());
+        let buttons_right = buttons.last().map_or(bar.x, |(rect, ..)| rect.x + rect.width);
+        for (rect, _, label, on) in buttons {
+            if on {
+                list.push(DisplayCommand::SolidRect { color: SELECTED_ROW, rect });
+            }
+            list.push(text(label, rect.x + 6.0, rect.y + 2.0, rect.width, if on { ACTIVE_TAB } else { TEXT }));
+        }
+        let stats = devtools.network.cache_stats();
+        if stats.hits + stats.revalidated + stats.misses > 0 {
+            let (hits, revalidated, misses) = (stats.hits, stats.revalidated, stats.misses);
+            let summary = format!("Cache: {} hit, {} revalidated, {} miss", hits, revalidated, misses);
+            let width = bar.x + bar.width - buttons_right - 16.0;
+            list.push(text(summary, buttons_right + 12.0, bar.y + 2.0, width, DIM_TEXT));
+        }
+
+        let body = Rect { y: body.y + ROW_HEIGHT, height: body.height - ROW_HEIGHT, ..body };
+        let table = Rect { width: self.network_table_right() - body.x, ..body };
+        let name_width = (table.width - NETWORK_COLUMNS.iter().sum::<f32>() - 8.0).max(40.0);
+        let row_cells = |cells: [String; 6], y: f32, color: Color, list: &mut DisplayList| {
+            let mut x = table.x + 8.0;
+            for (i, cell) in cells.into_iter().enumerate() {
+                let width = if i == 0 { name_width } else { NETWORK_COLUMNS[i - 1] };
+                list.push(text(cell, x, y + 2.0, width - 6.0, color));
+                x += width;
+            }
+        };
+
+        list.push(DisplayCommand::SolidRect { color: TAB_BAR_BACKGROUND, rect: Rect { height: ROW_HEIGHT, ..table } });
+        let header = ["Name", "Method", "Status", "Type", "Size", "Time"].map(str::to_string);
+        row_cells(header, table.y, DIM_TEXT, list);
+
+        let rows = self.visible_rows(DevToolsTab::Network).saturating_sub(2);
+        let listed = devtools.network.filtered_requests();
+        for (row, &(index, request)) in listed.iter().skip(self.scroll).take(rows).enumerate() {
+            let y = table.y + (row + 1) as f32 * ROW_HEIGHT;
+            if self.selected_request == Some(index) {
+                let rect = Rect { y, height: ROW_HEIGHT, ..table };
+                list.push(DisplayCommand::SolidRect { color: SELECTED_ROW, rect });
+            }
+            row_cells(network_cells(request), y, if request.is_failed() { ERROR_TEXT } else { TEXT }, list);
+        }
+
+        if let Some(request) = self.selected_request.and_then(|index| devtools.network.requests().get(index)) {
+            let details = Rect { x: table.x + table.width, width: body.width - table.width, ..body };
+            list.push(DisplayCommand::Border { color: PANEL_BORDER, rect: details, widths: (1.0, 0.0, 0.0, 0.0) });
+            let lines = (details.height / ROW_HEIGHT) as usize;
+            for (row, line) in request.detail_lines().into_iter().skip(self.details_scroll).take(lines).enumerate() {
+                let y = details.y + row as f32 * ROW_HEIGHT + 2.0;
+                list.push(text(line, details.x + 8.0, y, details.width - 16.0, TEXT));
+            }
+        }
+    }
+}
+
+impl Default for DevToolsPanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Tab name, with a count of problems if there are any
+fn rating_color(rating: Rating) -> Color {
+    match rating {
+        Rating::Good => GOOD_TEXT,
+        Rating::NeedsImprovement => WARNING_TEXT,
+        Rating::Poor => ERROR_TEXT,
+    }
+}
+
+fn tab_label(name: &str, problems: usize) -> String {
+    match problems {
+        0 => name.to_string(),
+        n => format!("{} ({})", name, n),
+    }
+}
+
+/// Name, Method, Status, Type, Size and Time of a request
+fn network_cells(request: &NetworkRequest) -> [String; 6] {
+    let name = request
+        .url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| request.url.host_str().unwrap_or(request.url.as_str()).to_string());
+    let status = match (request.status, request.completed_at) {
+        (Some(0), _) | (None, Some(_)) => "(failed)".to_string(),
+        (Some(status), _) => status.to_string(),
+        (None, None) => "(pending)".to_string(),
+    };
+    let size = match request.size {
+        _ if request.cache == Some(CacheUse::Hit) => "(cache)".to_string(),
+        Some(size) if size >= 1024 => format!("{:.1} kB", size as f32 / 1024.0),
+        Some(size) => format!("{} B", size),
+        None => String::new(),
+    };
+    let time = request.duration_ms.map(|ms| format!("{} ms", ms)).unwrap_or_default();
+    [name, request.method.clone(), status, format!("{:?}", request.request_type), size, time]
+}
+
+/// `ƒ onClick()`, or `ƒ anonymous()`
+fn handler_name(listener: &EventListenerInfo) -> String {
+    let name = if listener.handler.is_empty() { "anonymous" } else { &listener.handler };
+    format!("\u{192} {}()", name)
+}
+
+/// A line of panel text, cut off to fit `width`
+fn text(text: String, x: f32, y: f32, width: f32, color: Color) -> DisplayCommand {
+    let max_chars = (width / CHAR_WIDTH).max(0.0) as usize;
+    let text = if text.chars().count() > max_chars {
+        let mut cut: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+        cut.push('\u{2026}');
+        cut
+    } else {
+        text
+    };
+    DisplayCommand::Text {
+        text,
+        rect: Rect { x, y, width, height: 14.0 },
+        color,
+        font_family: "monospace".to_string(),
+        font_size: 12.0,
+        style: TextStyle::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::CssParser;
+    use crate::html::HtmlParser;
+    use crate::performance::TaskAttribution;
+    use crate::style::style_tree;
+
+    fn area() -> Rect {
+        Rect { x: 0.0, y: 100.0, width: 1000.0, height: 700.0 }
+    }
+
+    #[test]
+    fn test_docking_shrinks_the_page() {
+        let mut panel = DevToolsPanel::new();
+        panel.set_area(area());
+        assert_eq!(panel.page_area(), area());
+        assert!(panel.paint(&DevTools::new(), None).is_empty());
+
+        panel.toggle();
+        assert_eq!(panel.page_area(), Rect { height: 700.0 - DEFAULT_HEIGHT, ..area() });
+        assert!(panel.contains_point(10.0, 790.0));
+        assert!(!panel.contains_point(10.0, 200.0));
+
+        // The dock button moves the panel to the right
+        let mut devtools = DevTools::new();
+        let action = panel.handle_click(1000.0 - BUTTON_WIDTH * 1.5, 800.0 - DEFAULT_HEIGHT + 5.0, &mut devtools, None);
+        assert_eq!(action, DevToolsAction::LayoutChanged);
+        assert_eq!(panel.dock(), DevToolsDock::Right);
+        assert_eq!(panel.page_area(), Rect { width: 1000.0 - DEFAULT_WIDTH, ..area() });
+        assert_eq!(panel.bounds().height, 700.0);
+    }
+
+    #[test]
+    fn test_console_input_and_history() {
+        let mut panel = DevToolsPanel::new();
+        panel.set_area(area());
+        panel.toggle();
+        assert!(panel.is_focused());
+        for key in ["1", "+", "1"] {
+            panel.handle_key(&Key::Character(key.into()));
+        }
+        assert_eq!(panel.input(), "1+1");
+        assert_eq!(panel.handle_key(&Key::Named(NamedKey::Enter)), DevToolsAction::Evaluate("1+1".to_string()));
+        assert_eq!(panel.input(), "");
+        panel.handle_key(&Key::Named(NamedKey::ArrowUp));
+        assert_eq!(panel.input(), "1+1");
+        panel.handle_key(&Key::Named(NamedKey::ArrowDown));
+        assert_eq!(panel.input(), "");
+
+        // Clicking the page gives its keys back
+        let mut devtools = DevTools::new();
+        assert_eq!(panel.handle_click(10.0, 200.0, &mut devtools, None), DevToolsAction::Ignored);
+        assert_eq!(panel.handle_key(&Key::Character("a".into())), DevToolsAction::Ignored);
+    }
+
+    #[test]
+    fn test_console_objects_and_completion() {
+        use crate::js::{ConsoleProperty, ConsoleValue};
+
+        let mut devtools = DevTools::new();
+        devtools.console.log_value(ConsoleValue { preview: "{a: 1}".to_string(), handle: Some(0) });
+        let mut panel = DevToolsPanel::new();
+        panel.set_area(area());
+        panel.toggle();
+        let first_row = panel.bounds().y + TAB_BAR_HEIGHT + 5.0;
+        assert_eq!(panel.handle_click(20.0, first_row, &mut devtools, None), DevToolsAction::ExpandObject(0));
+        let property = ConsoleProperty {
+            name: "a".to_string(),
+            value: ConsoleValue { preview: "1".to_string(), handle: None },
+        };
+        devtools.console.expand(0, vec![property]);
+        let lines: Vec<String> = panel
+            .paint(&devtools, None)
+            .into_iter()
+            .filter_map(|command| match command {
+                DisplayCommand::Text { text, .. } => Some(text),
+                _ => None,
+            })
+            .collect();
+        assert!(lines.contains(&"\u{25be} {a: 1}".to_string()) && lines.contains(&"a: 1".to_string()));
+        assert_eq!(panel.handle_click(20.0, first_row, &mut devtools, None), DevToolsAction::Handled);
+        assert!(devtools.console.expanded_properties(0).is_none());
+
+        // Tab asks for completions, which extend the name as far as they agree
+        panel.handle_key(&Key::Character("1 + doc".into()));
+        assert_eq!(panel.handle_key(&Key::Named(NamedKey::Tab)), DevToolsAction::Complete("1 + doc".to_string()));
+        panel.complete(&["document".to_string()]);
+        assert_eq!(panel.input(), "1 + document");
+        panel.handle_key(&Key::Character(".b".into()));
+        panel.complete(&["body".to_string(), "baseURI".to_string()]);
+        assert_eq!(panel.input(), "1 + document.b");
+        assert_eq!(panel.suggestions, ["body", "baseURI"]);
+        panel.complete(&["bgColor".to_string(), "bgSound".to_string()]);
+        assert_eq!(panel.input(), "1 + document.bg");
+    }
+
+    #[test]
+    fn test_network_filter_bar() {
+        let mut devtools = DevTools::new();
+        for (url, request_type) in [
+            ("https://example.com/", NetworkRequestType::Document),
+            ("https://cdn.example.com/site.css", NetworkRequestType::Stylesheet),
+            ("https://other.org/", NetworkRequestType::Document),
+        ] {
+            devtools.network.log_request(url::Url::parse(url).unwrap(), "GET".to_string(), request_type);
+        }
+        devtools.set_active_tab(DevToolsTab::Network);
+        let mut panel = DevToolsPanel::new();
+        panel.set_area(area());
+        panel.toggle();
+        let click = |panel: &mut DevToolsPanel, devtools: &mut DevTools, button: FilterButton| {
+            let buttons = panel.filter_buttons(devtools.network.filter());
+            let (rect, ..) = buttons.into_iter().find(|&(_, b, ..)| b == button).unwrap();
+            panel.handle_click(rect.x + 2.0, rect.y + 2.0, devtools, None)
+        };
+
+        click(&mut panel, &mut devtools, FilterButton::Types(&[NetworkRequestType::Document]));
+        assert_eq!(devtools.network.filtered_requests().len(), 2);
+        // The table lists the filtered requests below the filter bar and header
+        let third_row = panel.bounds().y + TAB_BAR_HEIGHT + ROW_HEIGHT * 3.0 + 2.0;
+        panel.handle_click(20.0, third_row, &mut devtools, None);
+        assert_eq!(panel.selected_request, Some(2));
+        click(&mut panel, &mut devtools, FilterButton::Domain);
+        assert_eq!(devtools.network.filter().domain.as_deref(), Some("other.org"));
+        assert_eq!(panel.selected_request, Some(2));
+        click(&mut panel, &mut devtools, FilterButton::Types(&[NetworkRequestType::Stylesheet]));
+        assert!(devtools.network.filtered_requests().is_empty() && panel.selected_request.is_none());
+
+        click(&mut panel, &mut devtools, FilterButton::Domain);
+        click(&mut panel, &mut devtools, FilterButton::Types(&[]));
+        click(&mut panel, &mut devtools, FilterButton::Status);
+        assert_eq!(devtools.network.filter().status, StatusFilter::Failed);
+        let labels: Vec<String> = panel
+            .paint(&devtools, None)
+            .into_iter()
+            .filter_map(|command| match command {
+                DisplayCommand::Text { text, .. } => Some(text),
+                _ => None,
+            })
+            .collect();
+        assert!(labels.contains(&"Status: failed".to_string()) && labels.contains(&"Domain: all".to_string()));
+        assert!(!labels.iter().any(|label| label.starts_with("Cache:")));
+
+        // Loads through the HTTP cache are summed up beside the filters
+        devtools.network.record_cache_use(0, None);
+        devtools.network.record_cache_use(1, None);
+        devtools.network.record_cache_use(2, Some(&crate::net::Transfer { status: 304, ..Default::default() }));
+        // On to the pending ones, which all of them are
+        click(&mut panel, &mut devtools, FilterButton::Status);
+        click(&mut panel, &mut devtools, FilterButton::Status);
+        let painted = panel.paint(&devtools, None);
+        let labels: Vec<&str> = painted
+            .iter()
+            .filter_map(|command| match command {
+                DisplayCommand::Text { text, .. } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert!(labels.contains(&"Cache: 2 hit, 1 revalidated, 0 miss"));
+        assert_eq!(labels.iter().filter(|&&label| label == "(cache)").count(), 2);
+        assert_eq!(click(&mut panel, &mut devtools, FilterButton::Export), DevToolsAction::ExportHar);
+    }
+
+    #[test]
+    fn test_elements_view() {
+        let document = HtmlParser::parse("<html><body><p id=\"a\">Hi</p>\n<div></div></body></html>");
+        let stylesheet = Stylesheet::new(Vec::new());
+        let styled = style_tree(&document, &stylesheet);
+        let (layers, vitals, performance) = (Compositor::default(), WebVitals::new(), Performance::new());
+        let page = Some(InspectedPage {
+            styled: &styled,
+            stylesheet: &stylesheet,
+            layers: &layers,
+            vitals: &vitals,
+            performance: &performance,
+        });
+        let mut devtools = DevTools::new();
+        let labels = |devtools: &DevTools| -> Vec<String> {
+            dom_rows(&devtools.dom_inspector, &document).into_iter().map(|row| row.label).collect()
+        };
+        assert_eq!(labels(&devtools), ["<html>", "<head>", "<body>"]);
+
+        let mut panel = DevToolsPanel::new();
+        panel.set_area(area());
+        panel.toggle();
+        let top = panel.bounds().y;
+        panel.handle_click(10.0 + TAB_WIDTH, top + 5.0, &mut devtools, page);
+        assert_eq!(devtools.active_tab, DevToolsTab::DomInspector);
+
+        // Clicking a node's arrow expands it
+        let body_row = |row: f32| top + TAB_BAR_HEIGHT + row * ROW_HEIGHT + 5.0;
+        devtools.dom_inspector.collapse_all();
+        devtools.dom_inspector.toggle_node(vec![]);
+        assert_eq!(labels(&devtools), ["<html>"]);
+        panel.handle_click(10.0, body_row(0.0), &mut devtools, page);
+        assert_eq!(labels(&devtools), ["<html>", "<head>", "<body>"]);
+        let action = panel.handle_click(10.0 + INDENT, body_row(2.0), &mut devtools, page);
+        assert_eq!(action, DevToolsAction::NodeSelected(vec![1]));
+        assert_eq!(labels(&devtools), ["<html>", "<head>", "<body>", "<p id=\"a\">", "<div>"]);
+        assert_eq!(devtools.dom_inspector.selected_path(), &[1]);
+        assert!(panel.paint(&devtools, page).iter().any(|command| matches!(
+            command,
+            DisplayCommand::Text { text, .. } if text.contains("<p id=\"a\">")
+        )));
+    }
+
+    #[test]
+    fn test_element_picker() {
+        let mut panel = DevToolsPanel::new();
+        panel.set_area(area());
+        panel.toggle();
+        let mut devtools = DevTools::new();
+        let action = panel.handle_click(1000.0 - BUTTON_WIDTH * 2.5, panel.bounds().y + 5.0, &mut devtools, None);
+        assert_eq!(action, DevToolsAction::Handled);
+        assert!(panel.is_picking());
+
+        let dimensions = crate::layout::Dimensions {
+            content: Rect { x: 10.0, y: 300.0, width: 100.0, height: 40.0 },
+            ..Default::default()
+        };
+        panel.set_highlight(Some(InspectedElement { path: vec![1, 0], dimensions, label: "div#a".to_string() }));
+        let overlay = panel.paint_highlight(50.0);
+        assert!(overlay.iter().any(|command| matches!(
+            command,
+            DisplayCommand::Highlight { rect, .. } if rect.y == 250.0 && rect.height == 40.0
+        )));
+        assert!(overlay.iter().any(|command| matches!(
+            command,
+            DisplayCommand::Text { text, .. } if text == "div#a  100 \u{d7} 40"
+        )));
+
+        // Esc leaves the picker and drops the highlight
+        assert_eq!(panel.handle_key(&Key::Named(NamedKey::Escape)), DevToolsAction::Handled);
+        assert!(!panel.is_picking());
+        assert!(panel.highlighted().is_none());
+    }
+
+    #[test]
+    fn test_styles_pane() {
+        let document = HtmlParser::parse("<html><body><p class=\"a\">Hi</p></body></html>");
+        let stylesheet = CssParser::parse("p { color: red; } .a { color: blue; }");
+        let styled = style_tree(&document, &stylesheet);
+        let (layers, vitals, performance) = (Compositor::default(), WebVitals::new(), Performance::new());
+        let page = Some(InspectedPage {
+            styled: &styled,
+            stylesheet: &stylesheet,
+            layers: &layers,
+            vitals: &vitals,
+            performance: &performance,
+        });
+        let mut devtools = DevTools::new();
+        devtools.set_active_tab(DevToolsTab::DomInspector);
+        devtools.dom_inspector.reveal(vec![1, 0]);
+
+        let mut panel = DevToolsPanel::new();
+        panel.set_area(area());
+        panel.toggle();
+        let body_top = panel.bounds().y + TAB_BAR_HEIGHT;
+        let styles_lines = |panel: &DevToolsPanel, devtools: &DevTools| -> Vec<String> {
+            panel
+                .paint(devtools, page)
+                .into_iter()
+                .filter_map(|command| match command {
+                    DisplayCommand::Text { text, rect, .. } if rect.x >= 500.0 && rect.y > body_top => Some(text),
+                    _ => None,
+                })
+                .collect()
+        };
+        let lines = styles_lines(&panel, &devtools);
+        let expected = ["(0,1,0)", ".a {", "  \u{2611} color: blue;", "}", "(0,0,1)", "p {", "  \u{2611} color: red;"];
+        assert_eq!(&lines[..7], expected);
+        assert_eq!(&lines[8..11], ["Event Listeners", "No listeners", "DOM Breakpoints"]);
+        panel.scroll_by(600.0, 200.0, &devtools, page);
+        let lines = styles_lines(&panel, &devtools);
+        assert!(lines.contains(&"Computed".to_string()) && lines.contains(&"color: blue".to_string()));
+        panel.scroll_by(600.0, -1000.0, &devtools, page);
+
+        // The checkbox switches a declaration off, the value can be retyped
+        let row = |row: f32| body_top + row * ROW_HEIGHT + 5.0;
+        let action = panel.handle_click(510.0, row(1.0), &mut devtools, page);
+        assert_eq!(action, DevToolsAction::ToggleDeclaration { rule: 1, declaration: 0 });
+        assert_eq!(panel.handle_click(600.0, row(1.0), &mut devtools, page), DevToolsAction::Handled);
+        for _ in 0..4 {
+            panel.handle_key(&Key::Named(NamedKey::Backspace));
+        }
+        panel.handle_key(&Key::Character("green".into()));
+        assert_eq!(
+            panel.handle_key(&Key::Named(NamedKey::Enter)),
+            DevToolsAction::SetDeclarationValue { rule: 1, declaration: 0, value: "green".to_string() }
+        );
+    }
+
+    #[test]
+    fn test_listeners_and_dom_breakpoints() {
+        let document = HtmlParser::parse("<html><body><p id=\"a\">Hi</p></body></html>");
+        let stylesheet = CssParser::parse("");
+        let styled = style_tree(&document, &stylesheet);
+        let (layers, vitals, performance) = (Compositor::default(), WebVitals::new(), Performance::new());
+        let page = Some(InspectedPage {
+            styled: &styled,
+            stylesheet: &stylesheet,
+            layers: &layers,
+            vitals: &vitals,
+            performance: &performance,
+        });
+        let mut devtools = DevTools::new();
+        devtools.set_active_tab(DevToolsTab::DomInspector);
+        devtools.dom_inspector.reveal(vec![1, 0]);
+        devtools.dom_inspector.set_event_listeners(vec![EventListenerInfo {
+            target: "#a".to_string(),
+            event_type: "click".to_string(),
+            capture: false,
+            once: true,
+            passive: false,
+            handler: "onClick".to_string(),
+            line: Some(3),
+            source: Some("function onClick(e) {".to_string()),
+        }]);
+
+        let mut panel = DevToolsPanel::new();
+        panel.set_area(area());
+        panel.toggle();
+        let body_top = panel.bounds().y + TAB_BAR_HEIGHT;
+        let row = |row: f32| body_top + row * ROW_HEIGHT + 5.0;
+        let texts: Vec<String> = panel
+            .paint(&devtools, page)
+            .into_iter()
+            .filter_map(|command| match command {
+                DisplayCommand::Text { text, rect, .. } if rect.x >= 500.0 && rect.y > body_top => Some(text),
+                _ => None,
+            })
+            .collect();
+        let listener = "click  #a \u{192} onClick() once";
+        assert_eq!(texts[..4], ["Event Listeners", "(inline):3", listener, "DOM Breakpoints"]);
+
+        // Rows below the listener switch breakpoints on the selected node
+        assert_eq!(panel.handle_click(600.0, row(3.0), &mut devtools, page), DevToolsAction::DomBreakpointsChanged);
+        assert!(devtools.dom_breakpoints.is_set(&[1, 0], DomBreakpointKind::SubtreeModified));
+        panel.handle_click(600.0, row(3.0), &mut devtools, page);
+        assert!(devtools.dom_breakpoints.breakpoints().is_empty());
+
+        // The listener links to its line, shown in the console
+        panel.handle_click(600.0, row(1.0), &mut devtools, page);
+        assert_eq!(devtools.active_tab, DevToolsTab::Console);
+        let message = devtools.console.messages().last().unwrap();
+        assert_eq!(message.content, "function onClick(e) {");
+        assert_eq!(message.source.as_deref(), Some("(inline):3"));
+    }
+
+    #[test]
+    fn test_lighthouse_summary() {
+        let document = HtmlParser::parse("<html><body><p>Hi</p></body></html>");
+        let stylesheet = Stylesheet::new(Vec::new());
+        let styled = style_tree(&document, &stylesheet);
+        let (layers, performance) = (Compositor::default(), Performance::new());
+        let mut vitals = WebVitals::new();
+        let mut devtools = DevTools::new();
+        let mut panel = DevToolsPanel::new();
+        panel.set_area(area());
+        panel.toggle();
+        panel.handle_click(10.0 + TAB_WIDTH * 5.0, panel.bounds().y + 5.0, &mut devtools, None);
+        assert_eq!(devtools.active_tab, DevToolsTab::Lighthouse);
+
+        let texts = |panel: &DevToolsPanel, devtools: &DevTools, vitals: &WebVitals| -> Vec<String> {
+            let performance = &performance;
+            let page = InspectedPage { styled: &styled, stylesheet: &stylesheet, layers: &layers, vitals, performance };
+            let body_top = panel.bounds().y + TAB_BAR_HEIGHT;
+            panel
+                .paint(devtools, Some(page))
+                .into_iter()
+                .filter_map(|command| match command {
+                    DisplayCommand::Text { text, rect, .. } if rect.y > body_top => Some(text),
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(texts(&panel, &devtools, &vitals), ["Waiting for the page to paint"]);
+
+        vitals.first_contentful_paint = Some(900.0);
+        vitals.largest_contentful_paint = Some(3100.0);
+        vitals.largest_contentful_element = Some("\"Hi\"".to_string());
+        vitals.cumulative_layout_shift = 0.3;
+        vitals.layout_shifts = 2;
+        assert_eq!(texts(&panel, &devtools, &vitals), [
+            "Performance 67",
+            "First Contentful Paint",
+            "0.90 s",
+            "\u{25cf} Good",
+            "Largest Contentful Paint",
+            "3.10 s",
+            "\u{25cf} Needs improvement",
+            "Cumulative Layout Shift",
+            "0.300",
+            "\u{25cf} Poor",
+            "Largest contentful paint: \"Hi\"",
+            "Layout shifts: 2",
+        ]);
+    }
+
+    #[test]
+    fn test_performance_view() {
+        let document = HtmlParser::parse("<html><body></body></html>");
+        let stylesheet = Stylesheet::new(Vec::new());
+        let styled = style_tree(&document, &stylesheet);
+        let (layers, vitals) = (Compositor::default(), WebVitals::new());
+        let mut performance = Performance::new();
+        let mut devtools = DevTools::new();
+        let mut panel = DevToolsPanel::new();
+        panel.set_area(area());
+        panel.toggle();
+        panel.handle_click(10.0 + TAB_WIDTH * 4.0, panel.bounds().y + 5.0, &mut devtools, None);
+        assert_eq!(devtools.active_tab, DevToolsTab::Performance);
+
+        let paint = |devtools: &DevTools, performance: &Performance| -> DisplayList {
+            let vitals = &vitals;
+            let page = InspectedPage { styled: &styled, stylesheet: &stylesheet, layers: &layers, vitals, performance };
+            let body_top = panel.bounds().y + TAB_BAR_HEIGHT;
+            panel.paint(devtools, Some(page)).into_iter().filter(|command| command.rect().y > body_top).collect()
+        };
+        let texts = |list: &DisplayList| -> Vec<String> {
+            list.iter()
+                .filter_map(|command| match command {
+                    DisplayCommand::Text { text, .. } => Some(text.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(texts(&paint(&devtools, &performance)), ["No frames recorded", "Long tasks", "No long tasks"]);
+
+        let start = std::time::Instant::now();
+        let ms = std::time::Duration::from_millis;
+        devtools.frames.record_frame(start, ms(8));
+        devtools.frames.record_frame(start + ms(20), ms(30));
+        devtools.frames.record_frame(start + ms(60), ms(80));
+        performance.record_task(12.0, 64.0, TaskAttribution::Parse);
+        performance.record_task(300.0, 140.0, TaskAttribution::Script);
+        let list = paint(&devtools, &performance);
+        assert_eq!(texts(&list), [
+            "3 frames \u{b7} 33 fps \u{b7} 39.3 ms average \u{b7} 1 janky",
+            "Long tasks",
+            "300 ms",
+            "script",
+            "140 ms",
+            "12 ms",
+            "parse",
+            "64 ms",
+        ]);
+        // A bar per frame, coloured by how far over budget it ran
+        let bars: Vec<Color> = list
+            .iter()
+            .filter_map(|command| match command {
+                DisplayCommand::SolidRect { color, rect } if rect.width == FRAME_BAR_WIDTH - 1.0 => Some(*color),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(bars, [ACTIVE_TAB, SLOW_FRAME, ERROR_TEXT]);
+    }
+}
@@ -0,0 +1,492 @@
+// Find-in-page bar (Ctrl+F)
+
+use crate::css::Color;
+use crate::display::{DisplayCommand, DisplayList};
+use crate::layout::{LayoutBox, Rect};
+use crate::text_style::TextStyle;
+use crate::window::ScrollState;
+use winit::keyboard::{Key, NamedKey};
+
+const BAR_WIDTH: f32 = 360.0;
+const BAR_HEIGHT: f32 = 36.0;
+/// Space kept above a match when scrolling it into view
+const SCROLL_MARGIN: f32 = 40.0;
+
+const BAR_BACKGROUND: Color = Color { r: 248, g: 249, b: 250, a: 255 };
+const BAR_BORDER: Color = Color { r: 190, g: 193, b: 198, a: 255 };
+const BAR_TEXT: Color = Color { r: 32, g: 33, b: 36, a: 255 };
+const NO_MATCH_TEXT: Color = Color { r: 197, g: 34, b: 31, a: 255 };
+const MATCH_HIGHLIGHT: Color = Color { r: 255, g: 235, b: 59, a: 140 };
+const ACTIVE_MATCH_HIGHLIGHT: Color = Color { r: 255, g: 150, b: 50, a: 180 };
+
+/// A single match of the search query in the page text
+#[derive(Debug, Clone)]
+pub struct FindMatch {
+    /// Text of the node containing the match
+    pub text: String,
+    /// Byte range of the match within `text`
+    pub start: usize,
+    pub end: usize,
+    /// Approximate on-page bounds of the matched text
+    pub rect: Rect,
+}
+
+/// Result of a key press in the find bar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindAction {
+    /// Query changed; search again
+    QueryChanged,
+    /// Active match changed; scroll it into view
+    ActiveMatchChanged,
+    /// Find bar was closed
+    Closed,
+    /// Key was not handled by the find bar
+    Ignored,
+}
+
+/// Find bar state: query, options and current matches
+pub struct FindBar {
+    query: String,
+    case_sensitive: bool,
+    open: bool,
+    matches: Vec<FindMatch>,
+    active: Option<usize>,
+    bounds: Rect,
+}
+
+impl FindBar {
+    /// Create a closed find bar
+    pub fn new() -> Self {
+        Self {
+            query: String::new(),
+            case_sensitive: false,
+            open: false,
+            matches: Vec::new(),
+            active: None,
+            bounds: Rect {
+                x: 0.0,
+                y: 0.0,
+                width: BAR_WIDTH,
+                height: BAR_HEIGHT,
+            },
+        }
+    }
+
+    /// Show the find bar, keeping the previous query
+    pub fn open(&mut self) {
+        self.open = true;
+    }
+
+    /// Hide the find bar and drop highlights
+    pub fn close(&mut self) {
+        self.open = false;
+        self.matches.clear();
+        self.active = None;
+    }
+
+    /// Is the find bar shown
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Get the visual bounds
+    pub fn bounds(&self) -> &Rect {
+        &self.bounds
+    }
+
+    /// Place the bar at the top right of the content area
+    pub fn set_position(&mut self, content_right: f32, content_top: f32) {
+        self.bounds.x = (content_right - BAR_WIDTH - 8.0).max(0.0);
+        self.bounds.y = content_top + 4.0;
+    }
+
+    /// Get the search query
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// Replace the search query
+    pub fn set_query(&mut self, query: &str) {
+        self.query = query.to_string();
+    }
+
+    /// Is matching case-sensitive
+    pub fn is_case_sensitive(&self) -> bool {
+        self.case_sensitive
+    }
+
+    /// Toggle case-sensitive matching
+    pub fn toggle_case_sensitive(&mut self) {
+        self.case_sensitive = !self.case_sensitive;
+    }
+
+    /// Search the text of a laid-out page, keeping the active match if possible
+    ///
+    /// Returns the number of matches.
+    pub fn search(&mut self, root: &LayoutBox) -> usize {
+        self.matches.clear();
+        if !self.query.is_empty() {
+            collect_matches(root, &self.query, self.case_sensitive, &mut self.matches);
+        }
+
+        self.active = match self.active {
+            _ if self.matches.is_empty() => None,
+            Some(i) => Some(i.min(self.matches.len() - 1)),
+            None => Some(0),
+        };
+        self.matches.len()
+    }
+
+    /// All current matches in document order
+    pub fn matches(&self) -> &[FindMatch] {
+        &self.matches
+    }
+
+    /// Index of the active match
+    pub fn active_index(&self) -> Option<usize> {
+        self.active
+    }
+
+    /// The active match
+    pub fn active_match(&self) -> Option<&FindMatch> {
+        self.active.and_then(|i| self.matches.get(i))
+    }
+
+    /// Move to the next match, wrapping around
+    pub fn find_next(&mut self) -> Option<&FindMatch> {
+        if !self.matches.is_empty() {
+            self.active = Some(self.active.map_or(0, |i| (i + 1) % self.matches.len()));
+        }
+        self.active_match()
+    }
+
+    /// Move to the previous match, wrapping around
+    pub fn find_previous(&mut self) -> Option<&FindMatch> {
+        if !self.matches.is_empty() {
+            let len = self.matches.len();
+            self.active = Some(self.active.map_or(len - 1, |i| (i + len - 1) % len));
+        }
+        self.active_match()
+    }
+
+    /// Scroll so the active match is visible; returns true if the offset changed
+    pub fn scroll_into_view(&self, scroll: &mut ScrollState) -> bool {
+        let rect = match self.active_match() {
+            Some(m) => m.rect,
+            None => return false,
+        };
+
+        let top = scroll.offset_y;
+        let bottom = top + scroll.viewport_height;
+        if rect.y >= top && rect.y + rect.height <= bottom {
+            return false;
+        }
+
+        scroll.scroll_to(scroll.offset_x, (rect.y - SCROLL_MARGIN).max(0.0));
+        scroll.offset_y != top
+    }
+
+    /// Status text, e.g. "2 of 5"
+    pub fn status_text(&self) -> String {
+        match self.active {
+            Some(i) => format!("{} of {}", i + 1, self.matches.len()),
+            None if self.query.is_empty() => String::new(),
+            None => "No results".to_string(),
+        }
+    }
+
+    /// Handle a key press while the bar is open
+    ///
+    /// Enter/Shift+Enter step through matches, Alt+C toggles case sensitivity.
+    pub fn handle_key(&mut self, key: &Key, alt: bool, shift: bool) -> FindAction {
+        if !self.open {
+            return FindAction::Ignored;
+        }
+
+        match key {
+            Key::Named(NamedKey::Escape) => {
+                self.close();
+                FindAction::Closed
+            }
+            Key::Named(NamedKey::Enter) => {
+                if shift {
+                    self.find_previous();
+                } else {
+                    self.find_next();
+                }
+                FindAction::ActiveMatchChanged
+            }
+            Key::Named(NamedKey::Backspace) => {
+                self.query.pop();
+                self.active = None;
+                FindAction::QueryChanged
+            }
+            Key::Named(NamedKey::Space) => {
+                self.query.push(' ');
+                self.active = None;
+                FindAction::QueryChanged
+            }
+            Key::Character(c) if alt => {
+                if c.eq_ignore_ascii_case("c") {
+                    self.toggle_case_sensitive();
+                    FindAction::QueryChanged
+                } else {
+                    FindAction::Ignored
+                }
+            }
+            Key::Character(c) => {
+                self.query.push_str(c);
+                self.active = None;
+                FindAction::QueryChanged
+            }
+            _ => FindAction::Ignored,
+        }
+    }
+
+    /// Highlight commands for every match, drawn over page content
+    ///
+    /// `offset_y` is subtracted from match positions (page scroll).
+    pub fn highlights(&self, offset_y: f32) -> DisplayList {
+        self.matches
+            .iter()
+            .enumerate()
+            .map(|(i, m)| DisplayCommand::Highlight {
+                color: if self.active == Some(i) { ACTIVE_MATCH_HIGHLIGHT } else { MATCH_HIGHLIGHT },
+                rect: Rect { y: m.rect.y - offset_y, ..m.rect },
+            })
+            .collect()
+    }
+
+    /// Build display commands for the bar itself
+    pub fn paint(&self) -> DisplayList {
+        if !self.open {
+            return Vec::new();
+        }
+
+        let b = self.bounds;
+        let status = self.status_text();
+        let no_results = self.active.is_none() && !self.query.is_empty();
+
+        vec![
+            DisplayCommand::SolidRect { color: BAR_BACKGROUND, rect: b },
+            DisplayCommand::Border { color: BAR_BORDER, rect: b, widths: (1.0, 1.0, 1.0, 1.0) },
+            DisplayCommand::Text {
+                text: self.query.clone(),
+                rect: Rect { x: b.x + 10.0, y: b.y + 10.0, width: b.width - 170.0, height: 16.0 },
+                color: BAR_TEXT,
+                font_family: "sans-serif".to_string(),
+                font_size: 13.0,
+                style: TextStyle::default(),
+            },
+            DisplayCommand::Text {
+                text: status,
+                rect: Rect { x: b.x + b.width - 155.0, y: b.y + 10.0, width: 80.0, height: 16.0 },
+                color: if no_results { NO_MATCH_TEXT } else { BAR_TEXT },
+                font_family: "sans-serif".to_string(),
+                font_size: 12.0,
+                style: TextStyle::default(),
+            },
+            DisplayCommand::Text {
+                text: if self.case_sensitive { "Aa \u{2713}".to_string() } else { "Aa".to_string() },
+                rect: Rect { x: b.x + b.width - 65.0, y: b.y + 10.0, width: 55.0, height: 16.0 },
+                color: BAR_TEXT,
+                font_family: "sans-serif".to_string(),
+                font_size: 12.0,
+                style: TextStyle::default(),
+            },
+        ]
+    }
+}
+
+impl Default for FindBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Walk the layout tree collecting matches in text boxes
+fn collect_matches(layout_box: &LayoutBox, query: &str, case_sensitive: bool, out: &mut Vec<FindMatch>) {
+    if let Some(text) = layout_box.get_styled_node().and_then(|s| s.node.text_content()) {
+        let rect = layout_box.dimensions.content;
+        for (start, end) in find_all(text, query, case_sensitive) {
+            out.push(FindMatch {
+                text: text.to_string(),
+                start,
+                end,
+                rect: match_rect(text, start, end, rect),
+            });
+        }
+    }
+
+    for child in &layout_box.children {
+        collect_matches(child, query, case_sensitive, out);
+    }
+}
+
+/// Byte ranges of non-overlapping occurrences of `query` in `text`
+fn find_all(text: &str, query: &str, case_sensitive: bool) -> Vec<(usize, usize)> {
+    if case_sensitive {
+        return text
+            .match_indices(query)
+            .map(|(i, m)| (i, i + m.len()))
+            .collect();
+    }
+
+    // Compare char by char so byte offsets stay valid in the original text
+    let needle: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let mut matched = 0;
+        let mut j = i;
+        while j < chars.len() && matched < needle.len() {
+            let lower: Vec<char> = chars[j].1.to_lowercase().collect();
+            if needle[matched..].starts_with(&lower) {
+                matched += lower.len();
+                j += 1;
+            } else {
+                break;
+            }
+        }
+        if matched == needle.len() && j > i {
+            let end = chars.get(j).map_or(text.len(), |&(b, _)| b);
+            ranges.push((chars[i].0, end));
+            i = j;
+        } else {
+            i += 1;
+        }
+    }
+    ranges
+}
+
+/// Approximate the bounds of a substring, assuming evenly spaced characters
+fn match_rect(text: &str, start: usize, end: usize, content: Rect) -> Rect {
+    let total = text.chars().count().max(1) as f32;
+    let char_width = content.width / total;
+    let before = text[..start].chars().count() as f32;
+    let len = text[start..end].chars().count() as f32;
+    Rect {
+        x: content.x + before * char_width,
+        y: content.y,
+        width: len * char_width,
+        height: content.height,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar_with_matches(count: usize) -> FindBar {
+        let mut bar = FindBar::new();
+        bar.open();
+        bar.set_query("x");
+        bar.matches = (0..count)
+            .map(|i| FindMatch {
+                text: "x".to_string(),
+                start: 0,
+                end: 1,
+                rect: Rect { x: 0.0, y: i as f32 * 500.0, width: 10.0, height: 20.0 },
+            })
+            .collect();
+        bar.active = Some(0);
+        bar
+    }
+
+    #[test]
+    fn test_find_all_case_sensitivity() {
+        assert_eq!(find_all("Rust rust RUST", "rust", false), vec![(0, 4), (5, 9), (10, 14)]);
+        assert_eq!(find_all("Rust rust RUST", "rust", true), vec![(5, 9)]);
+        assert_eq!(find_all("aaaa", "aa", false), vec![(0, 2), (2, 4)]);
+        // Multi-byte characters keep byte offsets valid
+        assert_eq!(find_all("Ünïcode ünï", "ÜNÏ", false), vec![(0, 5), (10, 15)]);
+    }
+
+    #[test]
+    fn test_match_rect_is_proportional() {
+        let content = Rect { x: 10.0, y: 5.0, width: 100.0, height: 20.0 };
+        let rect = match_rect("0123456789", 2, 5, content);
+        assert_eq!(rect.x, 30.0);
+        assert_eq!(rect.width, 30.0);
+        assert_eq!(rect.y, 5.0);
+    }
+
+    #[test]
+    fn test_next_previous_wrap() {
+        let mut bar = bar_with_matches(3);
+        assert_eq!(bar.status_text(), "1 of 3");
+        bar.find_next();
+        bar.find_next();
+        assert_eq!(bar.active_index(), Some(2));
+        bar.find_next();
+        assert_eq!(bar.active_index(), Some(0));
+        bar.find_previous();
+        assert_eq!(bar.active_index(), Some(2));
+
+        assert_eq!(bar.handle_key(&Key::Named(NamedKey::Enter), false, true), FindAction::ActiveMatchChanged);
+        assert_eq!(bar.active_index(), Some(1));
+    }
+
+    #[test]
+    fn test_scroll_active_match_into_view() {
+        let mut bar = bar_with_matches(3);
+        let mut scroll = ScrollState::new(800.0, 400.0);
+        scroll.set_content_size(800.0, 2000.0);
+
+        // First match is already visible
+        assert!(!bar.scroll_into_view(&mut scroll));
+
+        bar.find_next();
+        assert!(bar.scroll_into_view(&mut scroll));
+        assert_eq!(scroll.offset_y, 500.0 - SCROLL_MARGIN);
+
+        let highlights = bar.highlights(scroll.offset_y);
+        assert_eq!(highlights.len(), 3);
+        match &highlights[1] {
+            DisplayCommand::Highlight { color, rect } => {
+                assert_eq!(*color, ACTIVE_MATCH_HIGHLIGHT);
+                assert_eq!(rect.y, SCROLL_MARGIN);
+            }
+            other => panic!("unexpected command {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_search_layout_text() {
+        use crate::css::CssParser;
+        use crate::dom::Node;
+        use crate::layout::{layout_tree, Dimensions};
+        use crate::style::style_tree;
+        use std::collections::HashMap;
+
+        let p = |text: &str| Node::element("p".to_string(), HashMap::new(), vec![Node::text(text.to_string())]);
+        let dom = Node::element("body".to_string(), HashMap::new(), vec![p("Find me"), p("and FIND me too")]);
+        let css = CssParser::parse("body, p { display: block; } p { height: 20px; }");
+        let styled = style_tree(&dom, &css);
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+        let layout = layout_tree(&styled, viewport);
+
+        let mut bar = FindBar::new();
+        bar.open();
+        for key in ["F", "I", "N", "D"] {
+            assert_eq!(bar.handle_key(&Key::Character(key.into()), false, false), FindAction::QueryChanged);
+        }
+        assert_eq!(bar.search(&layout), 2);
+        assert_eq!(bar.active_match().unwrap().text, "Find me");
+
+        let toggle = bar.handle_key(&Key::Character("c".into()), true, false);
+        assert_eq!(toggle, FindAction::QueryChanged);
+        assert!(bar.is_case_sensitive());
+        assert_eq!(bar.search(&layout), 1);
+        assert_eq!(bar.active_match().unwrap().text, "and FIND me too");
+
+        bar.set_query("missing");
+        assert_eq!(bar.search(&layout), 0);
+        assert_eq!(bar.status_text(), "No results");
+
+        assert_eq!(bar.handle_key(&Key::Named(NamedKey::Escape), false, false), FindAction::Closed);
+        assert!(!bar.is_open());
+    }
+}
@@ -0,0 +1,485 @@
+// Interactive form control widgets (checkbox, radio, select dropdown)
+
+use crate::css::Color;
+use crate::display::{DisplayCommand, DisplayList};
+use crate::forms::{InputState, RadioGroup, SelectState};
+use crate::layout::Rect;
+use crate::text_style::TextStyle;
+
+const CONTROL_BACKGROUND: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+const CONTROL_BORDER: Color = Color { r: 118, g: 118, b: 118, a: 255 };
+const CONTROL_ACCENT: Color = Color { r: 0, g: 117, b: 255, a: 255 };
+const CONTROL_HOVER: Color = Color { r: 232, g: 240, b: 254, a: 255 };
+const CONTROL_TEXT: Color = Color { r: 0, g: 0, b: 0, a: 255 };
+const CONTROL_DISABLED_TEXT: Color = Color { r: 160, g: 160, b: 160, a: 255 };
+const CONTROL_FONT_SIZE: f32 = 14.0;
+
+/// Checkbox widget bound to an `InputState`
+pub struct CheckboxWidget {
+    bounds: Rect,
+    hovered: bool,
+}
+
+impl CheckboxWidget {
+    /// Create a checkbox at the given bounds
+    pub fn new(bounds: Rect) -> Self {
+        Self {
+            bounds,
+            hovered: false,
+        }
+    }
+
+    /// Get the visual bounds
+    pub fn bounds(&self) -> &Rect {
+        &self.bounds
+    }
+
+    /// Check if the checkbox contains a point
+    pub fn contains_point(&self, x: f32, y: f32) -> bool {
+        contains(&self.bounds, x, y)
+    }
+
+    /// Update hover state from the mouse position
+    pub fn update_hover(&mut self, x: f32, y: f32) {
+        self.hovered = self.contains_point(x, y);
+    }
+
+    /// Handle a click; returns true if the state changed
+    pub fn handle_click(&mut self, x: f32, y: f32, state: &mut InputState) -> bool {
+        self.contains_point(x, y) && state.toggle_checked()
+    }
+
+    /// Build display commands for the current state
+    pub fn paint(&self, state: &InputState) -> DisplayList {
+        let mut list = Vec::new();
+        let background = if self.hovered && !state.disabled {
+            CONTROL_HOVER
+        } else {
+            CONTROL_BACKGROUND
+        };
+
+        list.push(DisplayCommand::SolidRect {
+            color: if state.checked { CONTROL_ACCENT } else { background },
+            rect: self.bounds,
+        });
+        list.push(DisplayCommand::Border {
+            color: if state.checked { CONTROL_ACCENT } else { CONTROL_BORDER },
+            rect: self.bounds,
+            widths: (1.0, 1.0, 1.0, 1.0),
+        });
+
+        if state.checked {
+            list.push(DisplayCommand::SolidRect {
+                color: CONTROL_BACKGROUND,
+                rect: inset(&self.bounds, self.bounds.width * 0.3),
+            });
+        }
+
+        list
+    }
+}
+
+/// Radio button widget bound to one member of a `RadioGroup`
+pub struct RadioWidget {
+    bounds: Rect,
+    member_index: usize,
+    hovered: bool,
+}
+
+impl RadioWidget {
+    /// Create a radio button for the group member at `member_index`
+    pub fn new(bounds: Rect, member_index: usize) -> Self {
+        Self {
+            bounds,
+            member_index,
+            hovered: false,
+        }
+    }
+
+    /// Get the visual bounds
+    pub fn bounds(&self) -> &Rect {
+        &self.bounds
+    }
+
+    /// Index of the group member this widget controls
+    pub fn member_index(&self) -> usize {
+        self.member_index
+    }
+
+    /// Check if the radio button contains a point
+    pub fn contains_point(&self, x: f32, y: f32) -> bool {
+        contains(&self.bounds, x, y)
+    }
+
+    /// Update hover state from the mouse position
+    pub fn update_hover(&mut self, x: f32, y: f32) {
+        self.hovered = self.contains_point(x, y);
+    }
+
+    /// Handle a click; returns true if the group selection changed
+    pub fn handle_click(&mut self, x: f32, y: f32, group: &mut RadioGroup) -> bool {
+        if !self.contains_point(x, y) || group.checked_index() == Some(self.member_index) {
+            return false;
+        }
+        group.check(self.member_index)
+    }
+
+    /// Build display commands for the current group state
+    pub fn paint(&self, group: &RadioGroup) -> DisplayList {
+        let mut list = Vec::new();
+        let checked = group.checked_index() == Some(self.member_index);
+        let disabled = group
+            .members
+            .get(self.member_index)
+            .map(|m| m.disabled)
+            .unwrap_or(true);
+
+        list.push(DisplayCommand::SolidRect {
+            color: if self.hovered && !disabled { CONTROL_HOVER } else { CONTROL_BACKGROUND },
+            rect: self.bounds,
+        });
+        list.push(DisplayCommand::Border {
+            color: if checked { CONTROL_ACCENT } else { CONTROL_BORDER },
+            rect: self.bounds,
+            widths: (1.0, 1.0, 1.0, 1.0),
+        });
+
+        if checked {
+            list.push(DisplayCommand::SolidRect {
+                color: CONTROL_ACCENT,
+                rect: inset(&self.bounds, self.bounds.width * 0.25),
+            });
+        }
+
+        list
+    }
+}
+
+/// Select dropdown widget with a popup option list
+pub struct SelectWidget {
+    bounds: Rect,
+    /// Is the popup list open
+    open: bool,
+    /// Option highlighted in the popup (keyboard or hover)
+    highlighted: Option<usize>,
+    /// Height of each row in the popup
+    option_height: f32,
+}
+
+impl SelectWidget {
+    /// Create a select dropdown at the given bounds
+    pub fn new(bounds: Rect) -> Self {
+        Self {
+            bounds,
+            open: false,
+            highlighted: None,
+            option_height: bounds.height,
+        }
+    }
+
+    /// Get the visual bounds of the closed control
+    pub fn bounds(&self) -> &Rect {
+        &self.bounds
+    }
+
+    /// Check if the popup is open
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Get the highlighted option index
+    pub fn highlighted(&self) -> Option<usize> {
+        self.highlighted
+    }
+
+    /// Open the popup, highlighting the current selection
+    pub fn open(&mut self, state: &SelectState) {
+        if state.disabled {
+            return;
+        }
+        self.open = true;
+        self.highlighted = state.selected_index;
+    }
+
+    /// Close the popup without changing the selection
+    pub fn close(&mut self) {
+        self.open = false;
+        self.highlighted = None;
+    }
+
+    /// Bounds of the popup list, directly below the control
+    pub fn popup_bounds(&self, option_count: usize) -> Rect {
+        Rect {
+            x: self.bounds.x,
+            y: self.bounds.y + self.bounds.height,
+            width: self.bounds.width,
+            height: self.option_height * option_count as f32,
+        }
+    }
+
+    /// Find the popup option under a point
+    pub fn option_at(&self, x: f32, y: f32, option_count: usize) -> Option<usize> {
+        if !self.open {
+            return None;
+        }
+
+        let popup = self.popup_bounds(option_count);
+        if !contains(&popup, x, y) || option_count == 0 {
+            return None;
+        }
+
+        let index = ((y - popup.y) / self.option_height) as usize;
+        Some(index.min(option_count - 1))
+    }
+
+    /// Update the highlighted option from the mouse position
+    pub fn update_hover(&mut self, x: f32, y: f32, state: &SelectState) {
+        if let Some(index) = self.option_at(x, y, state.options.len()) {
+            self.highlighted = Some(index);
+        }
+    }
+
+    /// Handle a click; returns true if the selection changed
+    ///
+    /// Clicking the control toggles the popup, clicking an option selects it
+    /// and closes the popup, and clicking anywhere else dismisses it.
+    pub fn handle_click(&mut self, x: f32, y: f32, state: &mut SelectState) -> bool {
+        if let Some(index) = self.option_at(x, y, state.options.len()) {
+            if state.options[index].disabled {
+                return false;
+            }
+            let changed = state.selected_index != Some(index) && state.select(index);
+            self.close();
+            return changed;
+        }
+
+        if contains(&self.bounds, x, y) {
+            if self.open {
+                self.close();
+            } else {
+                self.open(state);
+            }
+        } else {
+            self.close();
+        }
+        false
+    }
+
+    /// Move the highlight by `delta` rows, skipping disabled options
+    pub fn move_highlight(&mut self, delta: isize, state: &SelectState) {
+        let count = state.options.len() as isize;
+        if count == 0 {
+            return;
+        }
+
+        let mut index = self.highlighted.map(|i| i as isize).unwrap_or(-1);
+        loop {
+            let next = index + delta.signum();
+            if next < 0 || next >= count {
+                return;
+            }
+            index = next;
+            if !state.options[index as usize].disabled {
+                break;
+            }
+        }
+        self.highlighted = Some(index as usize);
+    }
+
+    /// Select the highlighted option and close the popup
+    pub fn commit(&mut self, state: &mut SelectState) -> bool {
+        let changed = match self.highlighted {
+            Some(index) if state.selected_index != Some(index) => state.select(index),
+            _ => false,
+        };
+        self.close();
+        changed
+    }
+
+    /// Build display commands for the control and, if open, its popup
+    pub fn paint(&self, state: &SelectState) -> DisplayList {
+        let mut list = Vec::new();
+
+        list.push(DisplayCommand::SolidRect {
+            color: CONTROL_BACKGROUND,
+            rect: self.bounds,
+        });
+        list.push(DisplayCommand::Border {
+            color: if self.open { CONTROL_ACCENT } else { CONTROL_BORDER },
+            rect: self.bounds,
+            widths: (1.0, 1.0, 1.0, 1.0),
+        });
+
+        if let Some(option) = state.selected() {
+            list.push(label(&option.label, inset(&self.bounds, 4.0), !state.disabled));
+        }
+
+        if !self.open {
+            return list;
+        }
+
+        let popup = self.popup_bounds(state.options.len());
+        list.push(DisplayCommand::SolidRect {
+            color: CONTROL_BACKGROUND,
+            rect: popup,
+        });
+        list.push(DisplayCommand::Border {
+            color: CONTROL_BORDER,
+            rect: popup,
+            widths: (1.0, 1.0, 1.0, 1.0),
+        });
+
+        for (i, option) in state.options.iter().enumerate() {
+            let row = Rect {
+                x: popup.x,
+                y: popup.y + self.option_height * i as f32,
+                width: popup.width,
+                height: self.option_height,
+            };
+            if self.highlighted == Some(i) {
+                list.push(DisplayCommand::SolidRect {
+                    color: CONTROL_HOVER,
+                    rect: row,
+                });
+            }
+            list.push(label(&option.label, inset(&row, 4.0), !option.disabled));
+        }
+
+        list
+    }
+}
+
+/// Text command for a control label
+fn label(text: &str, rect: Rect, enabled: bool) -> DisplayCommand {
+    DisplayCommand::Text {
+        text: text.to_string(),
+        rect,
+        color: if enabled { CONTROL_TEXT } else { CONTROL_DISABLED_TEXT },
+        font_family: "sans-serif".to_string(),
+        font_size: CONTROL_FONT_SIZE,
+        style: TextStyle::default(),
+    }
+}
+
+/// Shrink a rectangle by the same amount on every side
+fn inset(rect: &Rect, amount: f32) -> Rect {
+    Rect {
+        x: rect.x + amount,
+        y: rect.y + amount,
+        width: (rect.width - amount * 2.0).max(0.0),
+        height: (rect.height - amount * 2.0).max(0.0),
+    }
+}
+
+fn contains(rect: &Rect, x: f32, y: f32) -> bool {
+    x >= rect.x && x <= rect.x + rect.width && y >= rect.y && y <= rect.y + rect.height
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forms::{InputType, SelectOption};
+
+    fn rect(x: f32, y: f32, width: f32, height: f32) -> Rect {
+        Rect { x, y, width, height }
+    }
+
+    fn select_state() -> SelectState {
+        SelectState {
+            options: vec![
+                SelectOption { value: "a".to_string(), label: "A".to_string(), disabled: false },
+                SelectOption { value: "b".to_string(), label: "B".to_string(), disabled: true },
+                SelectOption { value: "c".to_string(), label: "C".to_string(), disabled: false },
+            ],
+            selected_index: Some(0),
+            disabled: false,
+        }
+    }
+
+    #[test]
+    fn test_checkbox_click() {
+        let mut widget = CheckboxWidget::new(rect(10.0, 10.0, 16.0, 16.0));
+        let mut state = InputState {
+            input_type: InputType::Checkbox,
+            ..Default::default()
+        };
+
+        assert!(widget.handle_click(15.0, 15.0, &mut state));
+        assert!(state.checked);
+        assert!(!widget.handle_click(100.0, 100.0, &mut state));
+        assert!(state.checked);
+
+        // Checked box paints an inner mark
+        assert_eq!(widget.paint(&state).len(), 3);
+    }
+
+    #[test]
+    fn test_radio_click_switches_group() {
+        let mut group = RadioGroup::new("size".to_string());
+        for _ in 0..2 {
+            group.add(InputState {
+                input_type: InputType::Radio,
+                ..Default::default()
+            });
+        }
+
+        let mut first = RadioWidget::new(rect(0.0, 0.0, 16.0, 16.0), 0);
+        let mut second = RadioWidget::new(rect(0.0, 20.0, 16.0, 16.0), 1);
+
+        assert!(first.handle_click(5.0, 5.0, &mut group));
+        assert!(!first.handle_click(5.0, 5.0, &mut group)); // Already checked
+        assert!(second.handle_click(5.0, 25.0, &mut group));
+        assert_eq!(group.checked_index(), Some(1));
+    }
+
+    #[test]
+    fn test_select_popup_selection() {
+        let mut widget = SelectWidget::new(rect(0.0, 0.0, 100.0, 20.0));
+        let mut state = select_state();
+
+        // Click control to open
+        assert!(!widget.handle_click(50.0, 10.0, &mut state));
+        assert!(widget.is_open());
+
+        // Disabled option is ignored and popup stays open
+        assert!(!widget.handle_click(50.0, 45.0, &mut state));
+        assert!(widget.is_open());
+
+        // Third option sits in the third row below the control
+        assert!(widget.handle_click(50.0, 65.0, &mut state));
+        assert!(!widget.is_open());
+        assert_eq!(state.value(), Some("c"));
+    }
+
+    #[test]
+    fn test_select_keyboard_highlight() {
+        let mut widget = SelectWidget::new(rect(0.0, 0.0, 100.0, 20.0));
+        let mut state = select_state();
+
+        widget.open(&state);
+        assert_eq!(widget.highlighted(), Some(0));
+
+        // Skips the disabled option
+        widget.move_highlight(1, &state);
+        assert_eq!(widget.highlighted(), Some(2));
+
+        // Stops at the end of the list
+        widget.move_highlight(1, &state);
+        assert_eq!(widget.highlighted(), Some(2));
+
+        assert!(widget.commit(&mut state));
+        assert_eq!(state.selected_index, Some(2));
+        assert!(!widget.is_open());
+    }
+
+    #[test]
+    fn test_select_click_outside_closes() {
+        let mut widget = SelectWidget::new(rect(0.0, 0.0, 100.0, 20.0));
+        let mut state = select_state();
+
+        widget.open(&state);
+        assert!(!widget.handle_click(500.0, 500.0, &mut state));
+        assert!(!widget.is_open());
+        assert_eq!(state.selected_index, Some(0));
+    }
+}
@@ -0,0 +1,173 @@
+// Link hover and activation driven by layout hit testing
+
+use crate::layout::LayoutBox;
+use crate::navigation::resolve_href;
+use url::Url;
+use winit::window::CursorIcon;
+
+/// Change in the hovered link after the pointer moved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HoverChange {
+    /// Href of the link the pointer left (fires `mouseout`)
+    pub left: Option<String>,
+    /// Href of the link the pointer entered (fires `mouseover`)
+    pub entered: Option<String>,
+}
+
+/// Tracks the hovered link and resolves clicked links to URLs
+pub struct LinkHandler {
+    /// Base URL used to resolve relative hrefs
+    base_url: Option<Url>,
+    /// Href of the link under the pointer
+    hovered: Option<String>,
+}
+
+impl LinkHandler {
+    /// Create a new link handler
+    pub fn new() -> Self {
+        Self {
+            base_url: None,
+            hovered: None,
+        }
+    }
+
+    /// Set the document base URL (e.g., after a page load)
+    pub fn set_base_url(&mut self, url: Url) {
+        self.base_url = Some(url);
+        self.hovered = None;
+    }
+
+    /// Get the document base URL
+    pub fn base_url(&self) -> Option<&Url> {
+        self.base_url.as_ref()
+    }
+
+    /// Get the href of the hovered link
+    pub fn hovered_link(&self) -> Option<&str> {
+        self.hovered.as_deref()
+    }
+
+    /// Resolve the hovered link to an absolute URL, e.g. for the status bar
+    pub fn hovered_url(&self) -> Option<Url> {
+        resolve_href(self.base_url.as_ref()?, self.hovered.as_deref()?)
+    }
+
+    /// Cursor to show for the current hover state
+    pub fn cursor(&self) -> CursorIcon {
+        if self.hovered.is_some() {
+            CursorIcon::Pointer
+        } else {
+            CursorIcon::Default
+        }
+    }
+
+    /// Update the hovered link from the pointer position
+    ///
+    /// Returns the change if the pointer moved onto, off, or between links.
+    pub fn update_hover(&mut self, root: &LayoutBox, x: f32, y: f32) -> Option<HoverChange> {
+        let link = link_at(root, x, y);
+        if link == self.hovered {
+            return None;
+        }
+
+        let left = std::mem::replace(&mut self.hovered, link.clone());
+        Some(HoverChange {
+            left,
+            entered: link,
+        })
+    }
+
+    /// Resolve the link under a click to the URL to navigate to
+    pub fn activate(&self, root: &LayoutBox, x: f32, y: f32) -> Option<Url> {
+        let href = link_at(root, x, y)?;
+        let base = self.base_url.as_ref()?;
+        resolve_href(base, &href)
+    }
+}
+
+impl Default for LinkHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Find the href of the innermost `<a href>` under a point
+pub fn link_at(root: &LayoutBox, x: f32, y: f32) -> Option<String> {
+    root.hit_test(x, y).iter().rev().find_map(|layout_box| {
+        let elem = layout_box.get_styled_node()?.node.element_data()?;
+        if elem.tag_name == "a" {
+            elem.get_attribute("href").map(|href| href.to_string())
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::CssParser;
+    use crate::dom::Node;
+    use crate::layout::{layout_tree, Dimensions};
+    use crate::style::style_tree;
+    use std::collections::HashMap;
+
+    fn page() -> Node {
+        let mut attrs = HashMap::new();
+        attrs.insert("href".to_string(), "next.html".to_string());
+        let link = Node::element("a".to_string(), attrs, vec![Node::text("Next".to_string())]);
+        let spacer = Node::element("p".to_string(), HashMap::new(), vec![]);
+        Node::element("body".to_string(), HashMap::new(), vec![link, spacer])
+    }
+
+    fn viewport() -> Dimensions {
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+        viewport
+    }
+
+    #[test]
+    fn test_link_activation_resolves_href() {
+        let dom = page();
+        let css = CssParser::parse("body { display: block; } a { display: block; height: 20px; } p { display: block; height: 20px; }");
+        let styled = style_tree(&dom, &css);
+        let layout = layout_tree(&styled, viewport());
+
+        let mut handler = LinkHandler::new();
+        handler.set_base_url(Url::parse("http://example.com/docs/index.html").unwrap());
+
+        let url = handler.activate(&layout, 10.0, 10.0).unwrap();
+        assert_eq!(url.as_str(), "http://example.com/docs/next.html");
+
+        // The hovered link resolves the same way for the status bar
+        assert!(handler.hovered_url().is_none());
+        handler.update_hover(&layout, 10.0, 10.0);
+        assert_eq!(handler.hovered_url(), Some(url));
+
+        // Clicking the paragraph below the link does nothing
+        assert!(handler.activate(&layout, 10.0, 30.0).is_none());
+    }
+
+    #[test]
+    fn test_hover_changes_cursor() {
+        let dom = page();
+        let css = CssParser::parse("body { display: block; } a { display: block; height: 20px; } p { display: block; height: 20px; }");
+        let styled = style_tree(&dom, &css);
+        let layout = layout_tree(&styled, viewport());
+
+        let mut handler = LinkHandler::new();
+        assert_eq!(handler.cursor(), CursorIcon::Default);
+
+        let change = handler.update_hover(&layout, 10.0, 10.0).unwrap();
+        assert_eq!(change.entered.as_deref(), Some("next.html"));
+        assert_eq!(change.left, None);
+        assert_eq!(handler.cursor(), CursorIcon::Pointer);
+
+        // Moving within the same link is not a change
+        assert!(handler.update_hover(&layout, 20.0, 10.0).is_none());
+
+        let change = handler.update_hover(&layout, 10.0, 30.0).unwrap();
+        assert_eq!(change.left.as_deref(), Some("next.html"));
+        assert_eq!(handler.cursor(), CursorIcon::Default);
+    }
+}
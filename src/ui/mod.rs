@@ -3,31 +3,93 @@
 mod address_bar;
 mod navigation;
 mod input_handler;
+mod form_widgets;
+mod link_handler;
+mod find_bar;
+mod selection;
+mod tabs;
+mod bookmarks_bar;
+mod status_bar;
+mod reader_button;
+mod print_preview;
+mod devtools_panel;
+mod permission_bar;
+mod auth_bar;
+mod site_info;
 
-pub use address_bar::AddressBar;
-pub use navigation::{NavigationButtons, NavigationState};
+pub use address_bar::{
+    resolve_input, AddressBar, AddressBarAction, SearchEngine, Suggestion, SuggestionSource,
+};
+pub use navigation::{NavButton, NavCommand, NavigationButtons, NavigationState};
 pub use input_handler::InputHandler;
+pub use form_widgets::{CheckboxWidget, RadioWidget, SelectWidget};
+pub use link_handler::{link_at, HoverChange, LinkHandler};
+pub use find_bar::{FindAction, FindBar, FindMatch};
+pub use selection::PageSelection;
+pub use bookmarks_bar::{BookmarksBar, BookmarksBarHit, StarButton, BOOKMARKS_BAR_HEIGHT};
+pub use status_bar::{LoadProgress, StatusBar, STATUS_BAR_HEIGHT};
+pub use reader_button::ReaderButton;
+pub use print_preview::{PrintPreview, PrintPreviewAction};
+pub use devtools_panel::{dom_rows, DevToolsAction, DevToolsDock, DevToolsPanel, DomRow, InspectedPage};
+pub use permission_bar::PermissionBar;
+pub use auth_bar::{AuthAction, AuthBar, AuthField};
+pub use site_info::{SiteInfo, SiteInfoAction, SiteInfoPanel};
+pub use tabs::{
+    document_title, favicon_url, Tab, TabCommand, TabId, TabManager, TabStrip, TabStripHit,
+    TAB_STRIP_HEIGHT,
+};
 
 use crate::layout::Rect;
 
+/// Height of the toolbar holding the address bar and navigation buttons
+const TOOLBAR_HEIGHT: f32 = 60.0;
+
 /// Browser chrome UI containing address bar and navigation
 pub struct BrowserUI {
     pub address_bar: AddressBar,
     pub navigation: NavigationButtons,
     pub input_handler: InputHandler,
+    pub tab_strip: TabStrip,
+    pub find_bar: FindBar,
+    pub bookmarks_bar: BookmarksBar,
+    pub star_button: StarButton,
+    pub reader_button: ReaderButton,
+    pub print_preview: PrintPreview,
+    pub devtools: DevToolsPanel,
+    pub permission_bar: PermissionBar,
+    pub auth_bar: AuthBar,
+    pub site_info: SiteInfoPanel,
+    pub status_bar: StatusBar,
     pub bounds: Rect,
     pub chrome_height: f32,
+    tab_strip_visible: bool,
+    bookmarks_bar_visible: bool,
 }
 
 impl BrowserUI {
     /// Create a new browser UI
     pub fn new(width: f32) -> Self {
-        let chrome_height = 60.0;
+        let chrome_height = TOOLBAR_HEIGHT;
+        let address_bar = AddressBar::new();
+        let star_button = StarButton::new(address_bar.bounds());
+        let reader_button = ReaderButton::new(star_button.bounds());
+        let site_info = SiteInfoPanel::new(address_bar.bounds());
         
-        Self {
-            address_bar: AddressBar::new(),
+        let mut ui = Self {
+            address_bar,
             navigation: NavigationButtons::new(),
             input_handler: InputHandler::new(),
+            tab_strip: TabStrip::new(width),
+            find_bar: FindBar::new(),
+            bookmarks_bar: BookmarksBar::new(width),
+            star_button,
+            reader_button,
+            print_preview: PrintPreview::new(),
+            devtools: DevToolsPanel::new(),
+            permission_bar: PermissionBar::new(),
+            auth_bar: AuthBar::new(),
+            site_info,
+            status_bar: StatusBar::new(width, chrome_height),
             bounds: Rect {
                 x: 0.0,
                 y: 0.0,
@@ -35,17 +97,72 @@ impl BrowserUI {
                 height: chrome_height,
             },
             chrome_height,
-        }
+            tab_strip_visible: false,
+            bookmarks_bar_visible: false,
+        };
+        ui.layout_content();
+        ui
     }
     
-    /// Get the content viewport (below the chrome)
-    pub fn content_viewport(&self) -> Rect {
-        Rect {
+    /// Show or hide the tab strip below the toolbar
+    pub fn set_tab_strip_visible(&mut self, visible: bool) {
+        self.tab_strip_visible = visible;
+        self.layout_chrome();
+    }
+
+    /// Is the tab strip shown
+    pub fn is_tab_strip_visible(&self) -> bool {
+        self.tab_strip_visible
+    }
+
+    /// Show or hide the bookmarks bar under the address bar
+    pub fn set_bookmarks_bar_visible(&mut self, visible: bool) {
+        self.bookmarks_bar_visible = visible;
+        if !visible {
+            self.bookmarks_bar.close_folder();
+        }
+        self.layout_chrome();
+    }
+
+    /// Is the bookmarks bar shown
+    pub fn is_bookmarks_bar_visible(&self) -> bool {
+        self.bookmarks_bar_visible
+    }
+
+    /// Stack the toolbar, bookmarks bar and tab strip, top to bottom
+    fn layout_chrome(&mut self) {
+        let mut y = TOOLBAR_HEIGHT;
+        self.bookmarks_bar.set_y(y);
+        if self.bookmarks_bar_visible {
+            y += BOOKMARKS_BAR_HEIGHT;
+        }
+        self.tab_strip.set_y(y);
+        if self.tab_strip_visible {
+            y += TAB_STRIP_HEIGHT;
+        }
+        self.chrome_height = y;
+        self.layout_content();
+    }
+
+    /// Share the area below the chrome between the page and the DevTools
+    /// panel, and place what floats over the page
+    fn layout_content(&mut self) {
+        self.devtools.set_area(Rect {
             x: 0.0,
             y: self.chrome_height,
             width: self.bounds.width,
             height: self.bounds.height - self.chrome_height,
-        }
+        });
+        let page = self.devtools.page_area();
+        self.find_bar.set_position(page.x + page.width, self.chrome_height);
+        self.permission_bar.set_position(page.x, self.chrome_height);
+        self.auth_bar.set_position(page.x, self.chrome_height);
+        self.status_bar.set_layout(page.x + page.width, page.y + page.height, self.chrome_height);
+    }
+
+    /// Get the content viewport (below the chrome, beside the DevTools panel)
+    pub fn content_viewport(&self) -> Rect {
+        self.devtools.page_area()
     }
     
     /// Update UI layout when window resizes
@@ -55,6 +172,13 @@ impl BrowserUI {
         
         // Update address bar width
         self.address_bar.set_width(width - 200.0); // Leave room for nav buttons
+        self.star_button.set_position(self.address_bar.bounds());
+        self.reader_button.set_position(self.star_button.bounds());
+        self.site_info.set_position(self.address_bar.bounds());
+        self.tab_strip.set_width(width);
+        self.bookmarks_bar.set_width(width);
+        self.layout_content();
+        self.print_preview.set_bounds(self.bounds);
     }
     
     /// Check if a point is within the chrome area
@@ -88,4 +212,34 @@ mod tests {
         assert!(ui.contains_point(100.0, 30.0)); // In chrome
         assert!(!ui.contains_point(100.0, 100.0)); // Below chrome
     }
+
+    #[test]
+    fn test_tab_strip_extends_chrome() {
+        let mut ui = BrowserUI::new(800.0);
+        assert!(!ui.is_tab_strip_visible());
+
+        ui.set_tab_strip_visible(true);
+        assert_eq!(ui.chrome_height, 60.0 + TAB_STRIP_HEIGHT);
+        assert_eq!(ui.tab_strip.bounds().y, 60.0);
+        assert_eq!(ui.content_viewport().y, 60.0 + TAB_STRIP_HEIGHT);
+        assert!(ui.contains_point(100.0, 70.0));
+
+        ui.set_tab_strip_visible(false);
+        assert_eq!(ui.chrome_height, 60.0);
+    }
+
+    #[test]
+    fn test_bookmarks_bar_sits_under_address_bar() {
+        let mut ui = BrowserUI::new(800.0);
+        ui.set_tab_strip_visible(true);
+        ui.set_bookmarks_bar_visible(true);
+
+        assert_eq!(ui.bookmarks_bar.bounds().y, 60.0);
+        assert_eq!(ui.tab_strip.bounds().y, 60.0 + BOOKMARKS_BAR_HEIGHT);
+        assert_eq!(ui.chrome_height, 60.0 + BOOKMARKS_BAR_HEIGHT + TAB_STRIP_HEIGHT);
+
+        ui.set_bookmarks_bar_visible(false);
+        assert_eq!(ui.tab_strip.bounds().y, 60.0);
+        assert_eq!(ui.chrome_height, 60.0 + TAB_STRIP_HEIGHT);
+    }
 }
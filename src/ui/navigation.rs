@@ -1,6 +1,15 @@
-// Navigation buttons (back, forward, refresh)
+// Navigation buttons (back, forward, refresh/stop)
 
+use crate::css::Color;
+use crate::display::{DisplayCommand, DisplayList};
 use crate::layout::Rect;
+use crate::text_style::TextStyle;
+use winit::keyboard::{Key, NamedKey};
+
+const BUTTON_HOVER: Color = Color { r: 232, g: 234, b: 237, a: 255 };
+const ICON_ENABLED: Color = Color { r: 60, g: 64, b: 67, a: 255 };
+const ICON_DISABLED: Color = Color { r: 189, g: 193, b: 198, a: 255 };
+const ICON_SIZE: f32 = 18.0;
 
 /// Navigation state (history)
 #[derive(Clone)]
@@ -78,6 +87,58 @@ pub enum NavButton {
     Back,
     Forward,
     Refresh,
+    /// Takes the refresh button's place while a page is loading
+    Stop,
+}
+
+/// Navigation action from a button or keyboard shortcut
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NavCommand {
+    Back,
+    Forward,
+    /// Reload, revalidating cached resources
+    Reload,
+    /// Reload bypassing the cache
+    HardReload,
+    /// Abort the in-flight load
+    Stop,
+}
+
+impl NavCommand {
+    /// Map a key press to a navigation command
+    ///
+    /// F5 / Ctrl+R reload; Ctrl+F5, Shift+F5 and Ctrl+Shift+R hard reload;
+    /// Alt+Left / Alt+Right go back / forward. Escape stops only while
+    /// `loading`, so it stays free for other uses otherwise.
+    pub fn from_key(key: &Key, ctrl: bool, shift: bool, alt: bool, loading: bool) -> Option<Self> {
+        match key {
+            Key::Named(NamedKey::F5) if ctrl || shift => Some(NavCommand::HardReload),
+            Key::Named(NamedKey::F5) => Some(NavCommand::Reload),
+            Key::Character(c) if ctrl && c.eq_ignore_ascii_case("r") => {
+                Some(if shift { NavCommand::HardReload } else { NavCommand::Reload })
+            }
+            Key::Named(NamedKey::ArrowLeft) if alt => Some(NavCommand::Back),
+            Key::Named(NamedKey::ArrowRight) if alt => Some(NavCommand::Forward),
+            Key::Named(NamedKey::BrowserBack) => Some(NavCommand::Back),
+            Key::Named(NamedKey::BrowserForward) => Some(NavCommand::Forward),
+            Key::Named(NamedKey::BrowserRefresh) => Some(NavCommand::Reload),
+            Key::Named(NamedKey::BrowserStop) => Some(NavCommand::Stop),
+            Key::Named(NamedKey::Escape) if loading => Some(NavCommand::Stop),
+            _ => None,
+        }
+    }
+}
+
+impl NavButton {
+    /// Command run when the button is clicked
+    pub fn command(&self) -> NavCommand {
+        match self {
+            NavButton::Back => NavCommand::Back,
+            NavButton::Forward => NavCommand::Forward,
+            NavButton::Refresh => NavCommand::Reload,
+            NavButton::Stop => NavCommand::Stop,
+        }
+    }
 }
 
 /// Navigation buttons UI component
@@ -90,6 +151,10 @@ pub struct NavigationButtons {
     refresh_bounds: Rect,
     /// Currently hovered button
     hovered: Option<NavButton>,
+    /// Is a page loading (refresh shows as stop)
+    loading: bool,
+    can_go_back: bool,
+    can_go_forward: bool,
 }
 
 impl NavigationButtons {
@@ -115,15 +180,56 @@ impl NavigationButtons {
                 height: 30.0,
             },
             hovered: None,
+            loading: false,
+            can_go_back: false,
+            can_go_forward: false,
+        }
+    }
+    
+    /// Switch the refresh button to stop while loading
+    pub fn set_loading(&mut self, loading: bool) {
+        self.loading = loading;
+        if loading && self.hovered == Some(NavButton::Refresh) {
+            self.hovered = Some(NavButton::Stop);
+        } else if !loading && self.hovered == Some(NavButton::Stop) {
+            self.hovered = Some(NavButton::Refresh);
         }
     }
     
+    /// Is the stop button showing
+    pub fn is_loading(&self) -> bool {
+        self.loading
+    }
+    
+    /// Enable or disable back and forward from the history state
+    pub fn set_history_state(&mut self, can_go_back: bool, can_go_forward: bool) {
+        self.can_go_back = can_go_back;
+        self.can_go_forward = can_go_forward;
+    }
+    
+    /// Can the button be clicked
+    pub fn is_enabled(&self, button: NavButton) -> bool {
+        match button {
+            NavButton::Back => self.can_go_back,
+            NavButton::Forward => self.can_go_forward,
+            NavButton::Refresh => !self.loading,
+            NavButton::Stop => self.loading,
+        }
+    }
+    
+    /// Command for a click at a point, if it lands on an enabled button
+    pub fn click(&mut self, x: f32, y: f32) -> Option<NavCommand> {
+        self.hit_test(x, y)
+            .filter(|button| self.is_enabled(*button))
+            .map(|button| button.command())
+    }
+    
     /// Get bounds for a button
     pub fn button_bounds(&self, button: NavButton) -> &Rect {
         match button {
             NavButton::Back => &self.back_bounds,
             NavButton::Forward => &self.forward_bounds,
-            NavButton::Refresh => &self.refresh_bounds,
+            NavButton::Refresh | NavButton::Stop => &self.refresh_bounds,
         }
     }
     
@@ -136,8 +242,9 @@ impl NavigationButtons {
             self.hovered = Some(NavButton::Forward);
             Some(NavButton::Forward)
         } else if self.refresh_bounds.contains(x, y) {
-            self.hovered = Some(NavButton::Refresh);
-            Some(NavButton::Refresh)
+            let button = if self.loading { NavButton::Stop } else { NavButton::Refresh };
+            self.hovered = Some(button);
+            Some(button)
         } else {
             self.hovered = None;
             None
@@ -153,6 +260,40 @@ impl NavigationButtons {
     pub fn clear_hover(&mut self) {
         self.hovered = None;
     }
+    
+    /// Build display commands for the buttons; disabled ones are greyed out
+    pub fn paint(&self) -> DisplayList {
+        let reload = if self.loading { NavButton::Stop } else { NavButton::Refresh };
+        let mut list = Vec::new();
+        for (button, icon) in [
+            (NavButton::Back, "\u{2190}"),
+            (NavButton::Forward, "\u{2192}"),
+            (reload, if self.loading { "\u{2715}" } else { "\u{21bb}" }),
+        ] {
+            let bounds = *self.button_bounds(button);
+            let enabled = self.is_enabled(button);
+            if enabled && self.hovered == Some(button) {
+                list.push(DisplayCommand::SolidRect {
+                    color: BUTTON_HOVER,
+                    rect: bounds,
+                });
+            }
+            list.push(DisplayCommand::Text {
+                text: icon.to_string(),
+                rect: Rect {
+                    x: bounds.x + (bounds.width - ICON_SIZE) / 2.0,
+                    y: bounds.y + (bounds.height - ICON_SIZE) / 2.0,
+                    width: ICON_SIZE,
+                    height: ICON_SIZE,
+                },
+                color: if enabled { ICON_ENABLED } else { ICON_DISABLED },
+                font_family: "sans-serif".to_string(),
+                font_size: ICON_SIZE,
+                style: TextStyle::default(),
+            });
+        }
+        list
+    }
 }
 
 impl Default for NavigationButtons {
@@ -161,17 +302,6 @@ impl Default for NavigationButtons {
     }
 }
 
-// Helper trait for Rect to check containment
-trait RectContains {
-    fn contains(&self, x: f32, y: f32) -> bool;
-}
-
-impl RectContains for Rect {
-    fn contains(&self, x: f32, y: f32) -> bool {
-        x >= self.x && x <= self.x + self.width && y >= self.y && y <= self.y + self.height
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -232,4 +362,41 @@ mod tests {
         let hit = buttons.hit_test(500.0, 25.0);
         assert_eq!(hit, None);
     }
+    
+    #[test]
+    fn test_refresh_becomes_stop_while_loading() {
+        let mut buttons = NavigationButtons::new();
+        assert_eq!(buttons.click(100.0, 25.0), Some(NavCommand::Reload));
+        // Back is disabled without history
+        assert_eq!(buttons.click(20.0, 25.0), None);
+        
+        buttons.set_loading(true);
+        assert_eq!(buttons.hit_test(100.0, 25.0), Some(NavButton::Stop));
+        assert_eq!(buttons.click(100.0, 25.0), Some(NavCommand::Stop));
+        assert!(!buttons.is_enabled(NavButton::Refresh));
+        
+        buttons.set_history_state(true, false);
+        assert_eq!(buttons.click(20.0, 25.0), Some(NavCommand::Back));
+        assert_eq!(buttons.paint().len(), 4);
+    }
+    
+    #[test]
+    fn test_nav_command_shortcuts() {
+        let f5 = Key::Named(NamedKey::F5);
+        assert_eq!(NavCommand::from_key(&f5, false, false, false, false), Some(NavCommand::Reload));
+        assert_eq!(NavCommand::from_key(&f5, true, false, false, false), Some(NavCommand::HardReload));
+        
+        let r = Key::Character("r".into());
+        assert_eq!(NavCommand::from_key(&r, true, false, false, false), Some(NavCommand::Reload));
+        assert_eq!(NavCommand::from_key(&r, true, true, false, false), Some(NavCommand::HardReload));
+        assert_eq!(NavCommand::from_key(&r, false, false, false, false), None);
+        
+        let escape = Key::Named(NamedKey::Escape);
+        assert_eq!(NavCommand::from_key(&escape, false, false, false, false), None);
+        assert_eq!(NavCommand::from_key(&escape, false, false, false, true), Some(NavCommand::Stop));
+        
+        let left = Key::Named(NamedKey::ArrowLeft);
+        assert_eq!(NavCommand::from_key(&left, false, false, true, false), Some(NavCommand::Back));
+        assert_eq!(NavCommand::from_key(&left, false, false, false, false), None);
+    }
 }
@@ -0,0 +1,151 @@
+// Permission prompt shown over the top left of the page
+
+use crate::css::Color;
+use crate::display::{DisplayCommand, DisplayList};
+use crate::layout::Rect;
+use crate::permissions::{PermissionPrompt, PromptAnswer};
+use crate::text_style::TextStyle;
+
+const BAR_WIDTH: f32 = 420.0;
+const BAR_HEIGHT: f32 = 72.0;
+const BUTTON_WIDTH: f32 = 72.0;
+const BUTTON_HEIGHT: f32 = 26.0;
+
+const BAR_BACKGROUND: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+const BAR_BORDER: Color = Color { r: 190, g: 193, b: 198, a: 255 };
+const BAR_TEXT: Color = Color { r: 32, g: 33, b: 36, a: 255 };
+const ALLOW_BACKGROUND: Color = Color { r: 26, g: 115, b: 232, a: 255 };
+const ALLOW_TEXT: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+const BLOCK_TEXT: Color = Color { r: 26, g: 115, b: 232, a: 255 };
+
+/// Asks whether the page may use a feature, with Allow, Block and close buttons
+///
+/// The bar shows the oldest prompt for the active page's origin; the
+/// browser passes it in, so the bar keeps no prompt of its own.
+pub struct PermissionBar {
+    bounds: Rect,
+}
+
+impl PermissionBar {
+    pub fn new() -> Self {
+        Self { bounds: Rect { x: 0.0, y: 0.0, width: BAR_WIDTH, height: BAR_HEIGHT } }
+    }
+
+    /// Place the bar at the top left of the content area
+    pub fn set_position(&mut self, content_left: f32, content_top: f32) {
+        self.bounds.x = content_left + 8.0;
+        self.bounds.y = content_top + 4.0;
+    }
+
+    /// Get the visual bounds
+    pub fn bounds(&self) -> &Rect {
+        &self.bounds
+    }
+
+    /// Check if a point is on the bar, when a prompt is shown
+    pub fn contains_point(&self, x: f32, y: f32, prompt: Option<&PermissionPrompt>) -> bool {
+        prompt.is_some() && self.bounds.contains(x, y)
+    }
+
+    /// The answer a click gives the prompt, if it lands on a button
+    pub fn handle_click(&self, x: f32, y: f32, prompt: Option<&PermissionPrompt>) -> Option<PromptAnswer> {
+        prompt?;
+        if self.allow_button().contains(x, y) {
+            Some(PromptAnswer::Allow)
+        } else if self.block_button().contains(x, y) {
+            Some(PromptAnswer::Block)
+        } else if self.close_button().contains(x, y) {
+            Some(PromptAnswer::Dismiss)
+        } else {
+            None
+        }
+    }
+
+    /// Draw the prompt; nothing without one
+    pub fn paint(&self, prompt: Option<&PermissionPrompt>) -> DisplayList {
+        let Some(prompt) = prompt else {
+            return Vec::new();
+        };
+        let b = self.bounds;
+        let allow = self.allow_button();
+        let block = self.block_button();
+        let message = Rect { x: b.x + 12.0, y: b.y + 10.0, width: b.width - 48.0, height: 16.0 };
+        vec![
+            DisplayCommand::SolidRect { color: BAR_BACKGROUND, rect: b },
+            DisplayCommand::Border { color: BAR_BORDER, rect: b, widths: (1.0, 1.0, 1.0, 1.0) },
+            label(prompt.message(), message, BAR_TEXT),
+            label("\u{00d7}".to_string(), self.close_button(), BAR_TEXT),
+            DisplayCommand::SolidRect { color: ALLOW_BACKGROUND, rect: allow },
+            label("Allow".to_string(), inset(allow), ALLOW_TEXT),
+            DisplayCommand::Border { color: BAR_BORDER, rect: block, widths: (1.0, 1.0, 1.0, 1.0) },
+            label("Block".to_string(), inset(block), BLOCK_TEXT),
+        ]
+    }
+
+    fn allow_button(&self) -> Rect {
+        let b = self.bounds;
+        Rect {
+            x: b.x + b.width - 12.0 - BUTTON_WIDTH,
+            y: b.y + b.height - 10.0 - BUTTON_HEIGHT,
+            width: BUTTON_WIDTH,
+            height: BUTTON_HEIGHT,
+        }
+    }
+
+    fn block_button(&self) -> Rect {
+        let allow = self.allow_button();
+        Rect { x: allow.x - 8.0 - BUTTON_WIDTH, ..allow }
+    }
+
+    fn close_button(&self) -> Rect {
+        let b = self.bounds;
+        Rect { x: b.x + b.width - 28.0, y: b.y + 6.0, width: 20.0, height: 20.0 }
+    }
+}
+
+impl Default for PermissionBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn label(text: String, rect: Rect, color: Color) -> DisplayCommand {
+    DisplayCommand::Text {
+        text,
+        rect,
+        color,
+        font_family: "sans-serif".to_string(),
+        font_size: 13.0,
+        style: TextStyle::default(),
+    }
+}
+
+/// Where a button's label goes
+fn inset(button: Rect) -> Rect {
+    Rect { x: button.x + 16.0, y: button.y + 6.0, width: button.width - 32.0, height: 16.0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::permissions::PermissionName;
+
+    #[test]
+    fn test_buttons_answer_the_prompt() {
+        let mut bar = PermissionBar::new();
+        bar.set_position(0.0, 60.0);
+        let prompt = PermissionPrompt { id: 1, origin: "https://a.test".to_string(), name: PermissionName::Camera };
+        let b = *bar.bounds();
+        let (allow_x, button_y) = (b.x + b.width - 40.0, b.y + b.height - 20.0);
+
+        assert_eq!(bar.handle_click(allow_x, button_y, Some(&prompt)), Some(PromptAnswer::Allow));
+        assert_eq!(bar.handle_click(allow_x - BUTTON_WIDTH - 8.0, button_y, Some(&prompt)), Some(PromptAnswer::Block));
+        assert_eq!(bar.handle_click(b.x + b.width - 18.0, b.y + 12.0, Some(&prompt)), Some(PromptAnswer::Dismiss));
+        assert_eq!(bar.handle_click(b.x + 20.0, b.y + 12.0, Some(&prompt)), None);
+
+        // Nothing to answer without a prompt
+        assert!(bar.paint(None).is_empty());
+        assert!(!bar.contains_point(allow_x, button_y, None));
+        assert_eq!(bar.handle_click(allow_x, button_y, None), None);
+    }
+}
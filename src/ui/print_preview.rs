@@ -0,0 +1,315 @@
+// Print preview (Ctrl+P)
+
+use crate::css::Color;
+use crate::display::{DisplayCommand, DisplayList};
+use crate::layout::Rect;
+use crate::print::{PaperSize, PrintOptions, PrintedPage};
+use crate::text_style::TextStyle;
+use winit::keyboard::{Key, NamedKey};
+
+/// Space around the sheet and height of the status line under it
+const SHEET_MARGIN: f32 = 24.0;
+const STATUS_HEIGHT: f32 = 28.0;
+const SCALE_STEP: f32 = 0.1;
+
+const BACKDROP: Color = Color { r: 60, g: 64, b: 67, a: 230 };
+const SHEET: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+const SHEET_BORDER: Color = Color { r: 32, g: 33, b: 36, a: 255 };
+const STATUS_TEXT: Color = Color { r: 241, g: 243, b: 244, a: 255 };
+
+/// Result of a key press in the print preview
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrintPreviewAction {
+    /// Paper, orientation, scale, headers or backgrounds changed; paginate again
+    OptionsChanged,
+    /// Shown page changed
+    PageChanged,
+    /// Save the document as a PDF
+    Export,
+    /// Preview was closed
+    Closed,
+    /// Key was not handled by the preview
+    Ignored,
+}
+
+/// Print preview overlay: print options and the paginated document
+///
+/// The pages are laid out by the caller, which owns the document, and
+/// handed over again with `set_pages` whenever the options change.
+pub struct PrintPreview {
+    open: bool,
+    options: PrintOptions,
+    pages: Vec<PrintedPage>,
+    /// Index of the page shown
+    current: usize,
+    bounds: Rect,
+}
+
+impl PrintPreview {
+    /// Create a closed print preview
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            options: PrintOptions::new(),
+            pages: Vec::new(),
+            current: 0,
+            bounds: Rect::default(),
+        }
+    }
+
+    /// Show the preview from the first page
+    pub fn open(&mut self) {
+        self.open = true;
+        self.current = 0;
+    }
+
+    /// Hide the preview and drop its pages
+    pub fn close(&mut self) {
+        self.open = false;
+        self.pages.clear();
+    }
+
+    /// Is the preview shown
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Current print options
+    pub fn options(&self) -> &PrintOptions {
+        &self.options
+    }
+
+    /// Replace the paginated document, keeping the shown page if it still exists
+    pub fn set_pages(&mut self, pages: Vec<PrintedPage>) {
+        self.pages = pages;
+        self.current = self.current.min(self.pages.len().saturating_sub(1));
+    }
+
+    /// Number of pages in the preview
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Index of the page shown
+    pub fn current_page(&self) -> usize {
+        self.current
+    }
+
+    /// Area covered by the overlay (the whole window)
+    pub fn set_bounds(&mut self, bounds: Rect) {
+        self.bounds = bounds;
+    }
+
+    /// Handle a key press: Enter saves, Esc closes, arrows page through,
+    /// L/P/+/-/H/B change orientation, paper, scale, headers and backgrounds
+    pub fn handle_key(&mut self, key: &Key) -> PrintPreviewAction {
+        if !self.open {
+            return PrintPreviewAction::Ignored;
+        }
+
+        match key {
+            Key::Named(NamedKey::Escape) => {
+                self.close();
+                PrintPreviewAction::Closed
+            }
+            Key::Named(NamedKey::Enter) => PrintPreviewAction::Export,
+            Key::Named(NamedKey::ArrowRight | NamedKey::ArrowDown | NamedKey::PageDown) => {
+                self.show_page(self.current + 1)
+            }
+            Key::Named(NamedKey::ArrowLeft | NamedKey::ArrowUp | NamedKey::PageUp) => {
+                self.show_page(self.current.saturating_sub(1))
+            }
+            Key::Character(c) => {
+                let options = &mut self.options;
+                match c.to_ascii_lowercase().as_str() {
+                    "l" => options.landscape = !options.landscape,
+                    "p" => options.paper = next_paper(options.paper),
+                    "h" => options.header_footer = !options.header_footer,
+                    "b" => options.background_graphics = !options.background_graphics,
+                    "=" | "+" => {
+                        options.scale = (options.scale + SCALE_STEP).min(PrintOptions::MAX_SCALE);
+                    }
+                    "-" => {
+                        options.scale = (options.scale - SCALE_STEP).max(PrintOptions::MIN_SCALE);
+                    }
+                    _ => return PrintPreviewAction::Ignored,
+                }
+                PrintPreviewAction::OptionsChanged
+            }
+            _ => PrintPreviewAction::Ignored,
+        }
+    }
+
+    fn show_page(&mut self, index: usize) -> PrintPreviewAction {
+        let index = index.min(self.pages.len().saturating_sub(1));
+        if index == self.current {
+            return PrintPreviewAction::Ignored;
+        }
+        self.current = index;
+        PrintPreviewAction::PageChanged
+    }
+
+    /// Where the shown sheet is drawn, fitted to the overlay
+    pub fn sheet_rect(&self) -> Rect {
+        let (width, height) = self.options.page_size();
+        let available_width = (self.bounds.width - SHEET_MARGIN * 2.0).max(1.0);
+        let available_height = (self.bounds.height - SHEET_MARGIN * 2.0 - STATUS_HEIGHT).max(1.0);
+        let zoom = (available_width / width).min(available_height / height);
+        Rect {
+            x: self.bounds.x + (self.bounds.width - width * zoom) / 2.0,
+            y: self.bounds.y + SHEET_MARGIN,
+            width: width * zoom,
+            height: height * zoom,
+        }
+    }
+
+    /// Backdrop, the shown sheet and a status line with the options
+    pub fn paint(&self) -> DisplayList {
+        if !self.open {
+            return Vec::new();
+        }
+
+        let sheet = self.sheet_rect();
+        let zoom = sheet.width / self.options.page_size().0;
+        let mut list = vec![
+            DisplayCommand::SolidRect { color: BACKDROP, rect: self.bounds },
+            DisplayCommand::SolidRect { color: SHEET, rect: sheet },
+            DisplayCommand::Border { color: SHEET_BORDER, rect: sheet, widths: (1.0, 1.0, 1.0, 1.0) },
+        ];
+        if let Some(page) = self.pages.get(self.current) {
+            list.extend(page.display_list.iter().cloned().map(|command| fit(command, &sheet, zoom)));
+        }
+        list.push(DisplayCommand::Text {
+            text: self.status_text(),
+            rect: Rect {
+                x: self.bounds.x + SHEET_MARGIN,
+                y: sheet.y + sheet.height + SHEET_MARGIN / 2.0,
+                width: self.bounds.width - SHEET_MARGIN * 2.0,
+                height: STATUS_HEIGHT,
+            },
+            color: STATUS_TEXT,
+            font_family: "sans-serif".to_string(),
+            font_size: 13.0,
+            style: TextStyle::default(),
+        });
+        list
+    }
+
+    /// e.g. "Page 1 of 3 · letter · portrait · 100%"
+    pub fn status_text(&self) -> String {
+        let options = &self.options;
+        format!(
+            "Page {} of {} \u{b7} {} \u{b7} {} \u{b7} {:.0}% \u{2014} Enter: save as PDF, Esc: close",
+            if self.pages.is_empty() { 0 } else { self.current + 1 },
+            self.pages.len(),
+            options.paper.as_str(),
+            if options.landscape { "landscape" } else { "portrait" },
+            options.scale * 100.0
+        )
+    }
+}
+
+impl Default for PrintPreview {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Paper size offered after `paper` when cycling with P
+fn next_paper(paper: PaperSize) -> PaperSize {
+    match paper {
+        PaperSize::Letter => PaperSize::A4,
+        PaperSize::A4 => PaperSize::Legal,
+        PaperSize::Legal => PaperSize::A5,
+        PaperSize::A5 | PaperSize::Custom { .. } => PaperSize::Letter,
+    }
+}
+
+/// Shrink a command in sheet coordinates onto the on-screen sheet
+fn fit(mut command: DisplayCommand, sheet: &Rect, zoom: f32) -> DisplayCommand {
+    let rect = match &mut command {
+        DisplayCommand::SolidRect { rect, .. }
+        | DisplayCommand::Border { rect, .. }
+        | DisplayCommand::Text { rect, .. }
+        | DisplayCommand::Image { rect, .. }
+        | DisplayCommand::Highlight { rect, .. }
+        | DisplayCommand::PushClip { rect }
+        | DisplayCommand::PopClip { rect }
+        | DisplayCommand::PushFixed { rect }
+        | DisplayCommand::PopFixed { rect } => rect,
+    };
+    *rect = Rect {
+        x: sheet.x + rect.x * zoom,
+        y: sheet.y + rect.y * zoom,
+        width: rect.width * zoom,
+        height: rect.height * zoom,
+    };
+    match &mut command {
+        DisplayCommand::Text { font_size, style, .. } => {
+            *font_size *= zoom;
+            style.scale(zoom);
+        }
+        DisplayCommand::Border { widths, .. } => {
+            // Keep hairlines visible at small zoom levels
+            let scale = |w: f32| if w > 0.0 { (w * zoom).max(0.5) } else { 0.0 };
+            *widths = (scale(widths.0), scale(widths.1), scale(widths.2), scale(widths.3));
+        }
+        _ => {}
+    }
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preview_with_pages(count: usize) -> PrintPreview {
+        let mut preview = PrintPreview::new();
+        preview.set_bounds(Rect { x: 0.0, y: 0.0, width: 1000.0, height: 800.0 });
+        preview.open();
+        preview.set_pages(
+            (1..=count).map(|number| PrintedPage { number, display_list: Vec::new() }).collect(),
+        );
+        preview
+    }
+
+    #[test]
+    fn test_keys_change_options_and_pages() {
+        let mut preview = preview_with_pages(2);
+        assert_eq!(preview.handle_key(&Key::Character("l".into())), PrintPreviewAction::OptionsChanged);
+        assert!(preview.options().landscape);
+        assert_eq!(preview.handle_key(&Key::Character("p".into())), PrintPreviewAction::OptionsChanged);
+        assert_eq!(preview.options().paper, PaperSize::A4);
+        preview.handle_key(&Key::Character("-".into()));
+        assert!((preview.options().scale - 0.9).abs() < 1e-6);
+
+        assert_eq!(preview.handle_key(&Key::Named(NamedKey::ArrowRight)), PrintPreviewAction::PageChanged);
+        assert_eq!(preview.current_page(), 1);
+        // Already on the last page
+        assert_eq!(preview.handle_key(&Key::Named(NamedKey::ArrowRight)), PrintPreviewAction::Ignored);
+        assert!(preview.status_text().starts_with("Page 2 of 2"));
+
+        // Fewer pages after a change keeps the index in range
+        preview.set_pages(vec![PrintedPage { number: 1, display_list: Vec::new() }]);
+        assert_eq!(preview.current_page(), 0);
+
+        assert_eq!(preview.handle_key(&Key::Named(NamedKey::Enter)), PrintPreviewAction::Export);
+        assert_eq!(preview.handle_key(&Key::Named(NamedKey::Escape)), PrintPreviewAction::Closed);
+        assert!(!preview.is_open());
+        assert_eq!(preview.handle_key(&Key::Named(NamedKey::Enter)), PrintPreviewAction::Ignored);
+    }
+
+    #[test]
+    fn test_sheet_fits_overlay_with_paper_proportions() {
+        let preview = preview_with_pages(1);
+        let sheet = preview.sheet_rect();
+        let (width, height) = preview.options().page_size();
+
+        assert!(sheet.y + sheet.height <= 800.0 - STATUS_HEIGHT);
+        assert!((sheet.width / sheet.height - width / height).abs() < 1e-3);
+        // Centered horizontally
+        assert!((sheet.x * 2.0 + sheet.width - 1000.0).abs() < 1e-3);
+        assert!(!preview.paint().is_empty());
+        assert!(PrintPreview::new().paint().is_empty());
+    }
+}
@@ -0,0 +1,85 @@
+// Reader mode toggle in the address bar
+
+use crate::css::Color;
+use crate::display::{DisplayCommand, DisplayList};
+use crate::layout::Rect;
+use crate::text_style::TextStyle;
+
+const READER_ON: Color = Color { r: 26, g: 115, b: 232, a: 255 };
+const READER_OFF: Color = Color { r: 95, g: 99, b: 104, a: 255 };
+
+/// Button toggling reader mode, shown left of the bookmark star
+///
+/// It only appears on pages with an article to extract, or while reader
+/// mode is on so it can be turned off again.
+pub struct ReaderButton {
+    bounds: Rect,
+}
+
+impl ReaderButton {
+    /// Create a reader button next to a star button
+    pub fn new(star_button: &Rect) -> Self {
+        let mut button = Self { bounds: Rect::default() };
+        button.set_position(star_button);
+        button
+    }
+
+    /// Place the button just left of the star button
+    pub fn set_position(&mut self, star_button: &Rect) {
+        self.bounds = Rect {
+            x: star_button.x - star_button.width - 6.0,
+            ..*star_button
+        };
+    }
+
+    /// Get the visual bounds
+    pub fn bounds(&self) -> &Rect {
+        &self.bounds
+    }
+
+    /// Check if a point is on the button, when it is shown
+    pub fn contains_point(&self, x: f32, y: f32, available: bool, active: bool) -> bool {
+        (available || active) && self.bounds.contains(x, y)
+    }
+
+    /// Highlighted while reader mode is on; hidden without an article
+    pub fn paint(&self, available: bool, active: bool) -> DisplayList {
+        if !available && !active {
+            return Vec::new();
+        }
+        vec![DisplayCommand::Text {
+            text: "\u{2261}".to_string(),
+            rect: self.bounds,
+            color: if active { READER_ON } else { READER_OFF },
+            font_family: "sans-serif".to_string(),
+            font_size: self.bounds.height,
+            style: TextStyle::default(),
+        }]
+    }
+}
+
+impl Default for ReaderButton {
+    fn default() -> Self {
+        Self::new(&Rect::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reader_button_sits_left_of_star() {
+        let star = Rect { x: 500.0, y: 16.0, width: 28.0, height: 28.0 };
+        let button = ReaderButton::new(&star);
+        assert!(button.bounds().x + button.bounds().width < star.x);
+        assert_eq!(button.bounds().y, star.y);
+
+        // Hidden and not clickable on pages without an article
+        let (x, y) = (button.bounds().x + 2.0, button.bounds().y + 2.0);
+        assert!(button.paint(false, false).is_empty());
+        assert!(!button.contains_point(x, y, false, false));
+        assert!(button.contains_point(x, y, true, false));
+        assert!(button.contains_point(x, y, false, true));
+    }
+}
@@ -0,0 +1,156 @@
+// Page text selection by pointer drag
+
+use crate::css::Color;
+use crate::display::{DisplayCommand, DisplayList};
+use crate::layout::{LayoutBox, Rect};
+
+const SELECTION_HIGHLIGHT: Color = Color { r: 51, g: 144, b: 255, a: 90 };
+
+/// Text selected by dragging across the page
+///
+/// Selection is by text box: every text box touched by the dragged
+/// rectangle is selected whole.
+/// Points are in page coordinates, so the selection stays on the text it
+/// covers as the page scrolls.
+#[derive(Debug, Clone, Default)]
+pub struct PageSelection {
+    anchor: Option<(f32, f32)>,
+    focus: Option<(f32, f32)>,
+    dragging: bool,
+}
+
+impl PageSelection {
+    /// Create an empty selection
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a selection at a point (pointer pressed)
+    pub fn start(&mut self, x: f32, y: f32) {
+        self.anchor = Some((x, y));
+        self.focus = Some((x, y));
+        self.dragging = true;
+    }
+
+    /// Extend the selection to a point while dragging
+    pub fn extend(&mut self, x: f32, y: f32) {
+        if self.dragging {
+            self.focus = Some((x, y));
+        }
+    }
+
+    /// Finish dragging (pointer released)
+    pub fn end(&mut self) {
+        self.dragging = false;
+    }
+
+    /// Drop the selection
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Is a drag in progress
+    pub fn is_dragging(&self) -> bool {
+        self.dragging
+    }
+
+    /// Rectangle spanned by the selection, if it is not collapsed
+    pub fn rect(&self) -> Option<Rect> {
+        let ((ax, ay), (fx, fy)) = (self.anchor?, self.focus?);
+        if ax == fx && ay == fy {
+            return None;
+        }
+        Some(Rect {
+            x: ax.min(fx),
+            y: ay.min(fy),
+            width: (ax - fx).abs(),
+            height: (ay - fy).abs(),
+        })
+    }
+
+    /// Selected text in document order, one line per text box
+    pub fn selected_text(&self, root: &LayoutBox) -> String {
+        self.selected_boxes(root)
+            .into_iter()
+            .map(|(text, _)| text.trim())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Highlight commands for the selected text boxes
+    pub fn highlights(&self, root: &LayoutBox) -> DisplayList {
+        self.selected_boxes(root)
+            .into_iter()
+            .map(|(_, rect)| DisplayCommand::Highlight {
+                color: SELECTION_HIGHLIGHT,
+                rect,
+            })
+            .collect()
+    }
+
+    fn selected_boxes<'b>(&self, root: &'b LayoutBox) -> Vec<(&'b str, Rect)> {
+        let mut boxes = Vec::new();
+        if let Some(selection) = self.rect() {
+            collect_text_boxes(root, &selection, &mut boxes);
+        }
+        boxes
+    }
+}
+
+fn collect_text_boxes<'b>(layout_box: &'b LayoutBox, selection: &Rect, out: &mut Vec<(&'b str, Rect)>) {
+    collect_in(layout_box, layout_box.dimensions.content, selection, out);
+}
+
+/// `parent` is the content box of the enclosing box, used for text boxes
+/// that were laid out without a height of their own
+fn collect_in<'b>(layout_box: &'b LayoutBox, parent: Rect, selection: &Rect, out: &mut Vec<(&'b str, Rect)>) {
+    let content = layout_box.dimensions.content;
+    if let Some(text) = layout_box.get_styled_node().and_then(|s| s.node.text_content()) {
+        let rect = if content.height > 0.0 { content } else { parent };
+        if !text.trim().is_empty() && intersects(&rect, selection) {
+            out.push((text, rect));
+        }
+    }
+    for child in &layout_box.children {
+        collect_in(child, content, selection, out);
+    }
+}
+
+fn intersects(a: &Rect, b: &Rect) -> bool {
+    a.x <= b.x + b.width && a.x + a.width >= b.x && a.y <= b.y + b.height && a.y + a.height >= b.y
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::CssParser;
+    use crate::dom::Node;
+    use crate::layout::{layout_tree, Dimensions};
+    use crate::style::style_tree;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_drag_selects_text_boxes() {
+        let p = |text: &str| Node::element("p".to_string(), HashMap::new(), vec![Node::text(text.to_string())]);
+        let dom = Node::element("body".to_string(), HashMap::new(), vec![p("First"), p("Second"), p("Third")]);
+        let css = CssParser::parse("body, p { display: block; } p { height: 20px; }");
+        let styled = style_tree(&dom, &css);
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+        let layout = layout_tree(&styled, viewport);
+
+        let mut selection = PageSelection::new();
+        selection.start(5.0, 5.0);
+        assert!(selection.rect().is_none());
+        assert_eq!(selection.selected_text(&layout), "");
+
+        selection.extend(50.0, 25.0);
+        selection.end();
+        selection.extend(50.0, 200.0); // ignored after release
+        assert_eq!(selection.selected_text(&layout), "First\nSecond");
+        assert_eq!(selection.highlights(&layout).len(), 2);
+
+        selection.clear();
+        assert!(selection.rect().is_none());
+    }
+}
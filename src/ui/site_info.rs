@@ -0,0 +1,317 @@
+// Site information: the security indicator in the address bar and the
+// panel it opens, with the certificate and the site's permissions
+
+use crate::css::Color;
+use crate::display::{DisplayCommand, DisplayList};
+use crate::layout::Rect;
+use crate::net::{Certificate, SecurityState};
+use crate::permissions::{PermissionName, PermissionState};
+use crate::text_style::TextStyle;
+
+const PANEL_WIDTH: f32 = 380.0;
+const ROW_HEIGHT: f32 = 20.0;
+const PADDING: f32 = 12.0;
+const BUTTON_WIDTH: f32 = 140.0;
+const BUTTON_HEIGHT: f32 = 26.0;
+/// Insecure resources listed by name; the rest are counted
+const MAX_LISTED_RESOURCES: usize = 3;
+
+const PANEL_BACKGROUND: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+const PANEL_BORDER: Color = Color { r: 190, g: 193, b: 198, a: 255 };
+const TEXT: Color = Color { r: 32, g: 33, b: 36, a: 255 };
+const DETAIL_TEXT: Color = Color { r: 95, g: 99, b: 104, a: 255 };
+const SECURE: Color = Color { r: 24, g: 128, b: 56, a: 255 };
+const WARNING: Color = Color { r: 197, g: 34, b: 31, a: 255 };
+const BUTTON_TEXT: Color = Color { r: 26, g: 115, b: 232, a: 255 };
+
+/// What the panel shows about the active page
+#[derive(Debug, Clone, PartialEq)]
+pub struct SiteInfo {
+    /// The page's origin, e.g. `https://example.com`
+    pub origin: String,
+    pub state: SecurityState,
+    /// The certificate the page's server presented, if it is known
+    pub certificate: Option<Certificate>,
+    /// Resources the page loaded over plain HTTP
+    pub insecure_content: Vec<String>,
+    /// Permissions decided for the origin, in `PermissionName::ALL` order
+    pub permissions: Vec<(PermissionName, PermissionState)>,
+}
+
+/// What a click in the panel asks the browser to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SiteInfoAction {
+    /// Forget the origin's permission decisions
+    ResetPermissions,
+}
+
+/// The security indicator at the left of the address bar, which opens
+/// the site information panel under it
+///
+/// The browser passes the active page's `SiteInfo` in, so the panel
+/// keeps nothing but whether it is open.
+pub struct SiteInfoPanel {
+    icon: Rect,
+    open: bool,
+}
+
+impl SiteInfoPanel {
+    /// Create the indicator for an address bar
+    pub fn new(address_bar: &Rect) -> Self {
+        let mut panel = Self { icon: Rect::default(), open: false };
+        panel.set_position(address_bar);
+        panel
+    }
+
+    /// Place the indicator inside the left end of the address bar
+    pub fn set_position(&mut self, address_bar: &Rect) {
+        let size = address_bar.height - 12.0;
+        self.icon = Rect { x: address_bar.x + 6.0, y: address_bar.y + 6.0, width: size, height: size };
+    }
+
+    /// Get the bounds of the indicator
+    pub fn icon_bounds(&self) -> &Rect {
+        &self.icon
+    }
+
+    /// Is the panel shown
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Hide the panel
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    /// Bounds of the panel for a page, under the indicator
+    pub fn panel_bounds(&self, info: &SiteInfo) -> Rect {
+        let height = rows(info).len() as f32 * ROW_HEIGHT + BUTTON_HEIGHT + PADDING * 3.0;
+        Rect { x: self.icon.x, y: self.icon.y + self.icon.height + 8.0, width: PANEL_WIDTH, height }
+    }
+
+    /// Check if a point is on the indicator, or on the panel while it is open
+    pub fn contains_point(&self, x: f32, y: f32, info: Option<&SiteInfo>) -> bool {
+        let Some(info) = info else {
+            return false;
+        };
+        self.icon.contains(x, y) || (self.open && self.panel_bounds(info).contains(x, y))
+    }
+
+    /// Handle a click: the indicator toggles the panel, a click elsewhere
+    /// closes it, and the reset button asks for the permissions to be reset
+    pub fn handle_click(&mut self, x: f32, y: f32, info: Option<&SiteInfo>) -> Option<SiteInfoAction> {
+        let Some(info) = info else {
+            self.open = false;
+            return None;
+        };
+        if self.icon.contains(x, y) {
+            self.open = !self.open;
+            return None;
+        }
+        if !self.open || !self.panel_bounds(info).contains(x, y) {
+            self.open = false;
+            return None;
+        }
+        let resettable = info.permissions.iter().any(|(_, state)| *state != PermissionState::Prompt);
+        if resettable && self.reset_button(info).contains(x, y) {
+            self.open = false;
+            return Some(SiteInfoAction::ResetPermissions);
+        }
+        None
+    }
+
+    /// Draw the indicator for a page's security state
+    pub fn paint_icon(&self, state: SecurityState) -> DisplayList {
+        let (icon, color) = match state {
+            SecurityState::Internal => ("\u{24d8}", DETAIL_TEXT),
+            SecurityState::Secure => ("\u{1f512}", SECURE),
+            SecurityState::MixedContent | SecurityState::CertificateOverride | SecurityState::Insecure => {
+                ("\u{26a0}", WARNING)
+            }
+        };
+        vec![DisplayCommand::Text {
+            text: icon.to_string(),
+            rect: self.icon,
+            color,
+            font_family: "sans-serif".to_string(),
+            font_size: self.icon.height,
+            style: TextStyle::default(),
+        }]
+    }
+
+    /// Draw the panel; nothing while it is closed
+    pub fn paint(&self, info: &SiteInfo) -> DisplayList {
+        if !self.open {
+            return Vec::new();
+        }
+        let b = self.panel_bounds(info);
+        let mut list = vec![
+            DisplayCommand::SolidRect { color: PANEL_BACKGROUND, rect: b },
+            DisplayCommand::Border { color: PANEL_BORDER, rect: b, widths: (1.0, 1.0, 1.0, 1.0) },
+        ];
+        for (i, (text, color)) in rows(info).into_iter().enumerate() {
+            let y = b.y + PADDING + i as f32 * ROW_HEIGHT;
+            list.push(label(text, Rect { x: b.x + PADDING, y, width: b.width - PADDING * 2.0, height: 16.0 }, color));
+        }
+        if info.permissions.iter().any(|(_, state)| *state != PermissionState::Prompt) {
+            let button = self.reset_button(info);
+            list.push(DisplayCommand::Border { color: PANEL_BORDER, rect: button, widths: (1.0, 1.0, 1.0, 1.0) });
+            list.push(label(
+                "Reset permissions".to_string(),
+                Rect { x: button.x + 12.0, y: button.y + 6.0, width: button.width - 24.0, height: 16.0 },
+                BUTTON_TEXT,
+            ));
+        }
+        list
+    }
+
+    fn reset_button(&self, info: &SiteInfo) -> Rect {
+        let b = self.panel_bounds(info);
+        Rect {
+            x: b.x + PADDING,
+            y: b.y + b.height - PADDING - BUTTON_HEIGHT,
+            width: BUTTON_WIDTH,
+            height: BUTTON_HEIGHT,
+        }
+    }
+}
+
+impl Default for SiteInfoPanel {
+    fn default() -> Self {
+        Self::new(&Rect::default())
+    }
+}
+
+/// The panel's lines of text, top to bottom
+fn rows(info: &SiteInfo) -> Vec<(String, Color)> {
+    let state_color = match info.state {
+        SecurityState::Secure => SECURE,
+        SecurityState::Internal => TEXT,
+        _ => WARNING,
+    };
+    let mut rows = vec![
+        (info.origin.clone(), TEXT),
+        (info.state.label().to_string(), state_color),
+        (info.state.description().to_string(), DETAIL_TEXT),
+    ];
+
+    if !info.insecure_content.is_empty() {
+        rows.push((String::new(), TEXT));
+        rows.push(("Loaded without encryption".to_string(), TEXT));
+        for url in info.insecure_content.iter().take(MAX_LISTED_RESOURCES) {
+            rows.push((url.clone(), DETAIL_TEXT));
+        }
+        let more = info.insecure_content.len().saturating_sub(MAX_LISTED_RESOURCES);
+        if more > 0 {
+            rows.push((format!("and {} more", more), DETAIL_TEXT));
+        }
+    }
+
+    if info.state.has_certificate() {
+        rows.push((String::new(), TEXT));
+        rows.push(("Certificate".to_string(), TEXT));
+        match info.certificate {
+            Some(ref certificate) => {
+                let detail = |name: &str, value: &str| (format!("{}: {}", name, value), DETAIL_TEXT);
+                rows.push(detail("Issued to", &certificate.subject));
+                rows.push(detail("Issued by", &certificate.issuer));
+                rows.push(detail("Valid from", &certificate.not_before));
+                rows.push(detail("Valid until", &certificate.not_after));
+                if !certificate.dns_names.is_empty() {
+                    rows.push(detail("Names", &certificate.dns_names.join(", ")));
+                }
+                rows.push(detail("Serial", &certificate.serial));
+                rows.push(detail("SHA-1", &certificate.sha1_fingerprint));
+            }
+            None => rows.push(("Not available for this page".to_string(), DETAIL_TEXT)),
+        }
+    }
+
+    rows.push((String::new(), TEXT));
+    rows.push(("Permissions".to_string(), TEXT));
+    let decided: Vec<_> = info.permissions.iter().filter(|(_, state)| *state != PermissionState::Prompt).collect();
+    if decided.is_empty() {
+        rows.push(("No permissions decided for this site".to_string(), DETAIL_TEXT));
+    }
+    for (name, state) in decided {
+        let decision = if *state == PermissionState::Granted { "Allowed" } else { "Blocked" };
+        rows.push((format!("{}: {}", permission_label(*name), decision), DETAIL_TEXT));
+    }
+    rows
+}
+
+fn permission_label(name: PermissionName) -> &'static str {
+    match name {
+        PermissionName::Notifications => "Notifications",
+        PermissionName::Geolocation => "Location",
+        PermissionName::ClipboardRead => "Clipboard (read)",
+        PermissionName::ClipboardWrite => "Clipboard (write)",
+        PermissionName::Camera => "Camera",
+    }
+}
+
+fn label(text: String, rect: Rect, color: Color) -> DisplayCommand {
+    DisplayCommand::Text {
+        text,
+        rect,
+        color,
+        font_family: "sans-serif".to_string(),
+        font_size: 13.0,
+        style: TextStyle::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(permissions: Vec<(PermissionName, PermissionState)>) -> SiteInfo {
+        SiteInfo {
+            origin: "https://example.com".to_string(),
+            state: SecurityState::MixedContent,
+            certificate: None,
+            insecure_content: vec!["http://cdn.example.com/a.png".to_string()],
+            permissions,
+        }
+    }
+
+    #[test]
+    fn test_indicator_toggles_panel_and_resets_permissions() {
+        let address_bar = Rect { x: 120.0, y: 10.0, width: 560.0, height: 40.0 };
+        let mut panel = SiteInfoPanel::new(&address_bar);
+        let icon = *panel.icon_bounds();
+        assert!(icon.x > address_bar.x && icon.x + icon.width < address_bar.x + 60.0);
+
+        let decided = info(vec![(PermissionName::Camera, PermissionState::Granted)]);
+        assert!(panel.paint(&decided).is_empty());
+        assert_eq!(panel.handle_click(icon.x + 2.0, icon.y + 2.0, Some(&decided)), None);
+        assert!(panel.is_open());
+        let painted = panel.paint(&decided);
+        let text: Vec<&str> = painted
+            .iter()
+            .filter_map(|command| match command {
+                DisplayCommand::Text { text, .. } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert!(text.contains(&"Parts of this page are not secure"));
+        assert!(text.contains(&"Not available for this page"));
+        assert!(text.contains(&"Camera: Allowed"));
+
+        // The reset button asks for a reset and closes the panel
+        let bounds = panel.panel_bounds(&decided);
+        let (x, y) = (bounds.x + PADDING + 4.0, bounds.y + bounds.height - PADDING - 4.0);
+        assert!(panel.contains_point(x, y, Some(&decided)));
+        assert_eq!(panel.handle_click(x, y, Some(&decided)), Some(SiteInfoAction::ResetPermissions));
+        assert!(!panel.is_open());
+
+        // Without decisions there is nothing to reset; clicking outside closes
+        let undecided = info(vec![(PermissionName::Camera, PermissionState::Prompt)]);
+        panel.handle_click(icon.x + 2.0, icon.y + 2.0, Some(&undecided));
+        assert_eq!(panel.handle_click(x, y, Some(&undecided)), None);
+        assert!(panel.is_open());
+        panel.handle_click(bounds.x + bounds.width + 50.0, y, Some(&undecided));
+        assert!(!panel.is_open());
+    }
+}
@@ -0,0 +1,270 @@
+// Status bar: hovered link preview and page load progress
+
+use crate::css::Color;
+use crate::display::{DisplayCommand, DisplayList};
+use crate::layout::Rect;
+use crate::net::LoadEvent;
+use crate::text_style::TextStyle;
+
+/// Height of the status bubble at the bottom of the window
+pub const STATUS_BAR_HEIGHT: f32 = 22.0;
+/// Thickness of the progress line under the chrome
+const PROGRESS_HEIGHT: f32 = 3.0;
+/// Widest the status bubble grows before the text is cut off
+const MAX_BUBBLE_WIDTH: f32 = 480.0;
+/// Approximate glyph width used to size the bubble
+const CHAR_WIDTH: f32 = 6.5;
+
+const BUBBLE_BACKGROUND: Color = Color { r: 248, g: 249, b: 250, a: 255 };
+const BUBBLE_BORDER: Color = Color { r: 190, g: 193, b: 198, a: 255 };
+const BUBBLE_TEXT: Color = Color { r: 60, g: 64, b: 67, a: 255 };
+const PROGRESS_COLOR: Color = Color { r: 26, g: 115, b: 232, a: 255 };
+
+/// Page load progress, driven by `PageLoader` lifecycle events
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoadProgress {
+    loading: bool,
+    document_loaded: bool,
+    subresources: usize,
+    finished_subresources: usize,
+}
+
+impl LoadProgress {
+    /// Create progress for no load in flight
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Update from a load lifecycle event
+    pub fn handle(&mut self, event: &LoadEvent) {
+        match event {
+            LoadEvent::Started(_) => {
+                *self = Self { loading: true, ..Self::default() };
+            }
+            LoadEvent::DocumentLoaded => self.document_loaded = true,
+            LoadEvent::SubresourcesDiscovered(count) => self.subresources += count,
+            LoadEvent::SubresourceFinished(_) => {
+                self.finished_subresources = (self.finished_subresources + 1).min(self.subresources);
+            }
+            LoadEvent::Finished => self.finish(),
+        }
+    }
+
+    /// End the load, e.g. when it completes, fails or is stopped
+    pub fn finish(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Is a load in flight
+    pub fn is_loading(&self) -> bool {
+        self.loading
+    }
+
+    /// Subresources still being fetched
+    pub fn outstanding(&self) -> usize {
+        self.subresources - self.finished_subresources
+    }
+
+    /// Estimated completion from 0.0 to 1.0
+    ///
+    /// The document counts for half; subresources share the rest.
+    pub fn fraction(&self) -> f32 {
+        if !self.loading {
+            return 0.0;
+        }
+        if !self.document_loaded {
+            return 0.1;
+        }
+        if self.subresources == 0 {
+            return 0.9;
+        }
+        0.5 + 0.4 * self.finished_subresources as f32 / self.subresources as f32
+    }
+}
+
+/// Status bubble in the bottom corner and the load progress line
+pub struct StatusBar {
+    /// Window bounds
+    window: Rect,
+    /// Top of the page content, where the progress line is drawn
+    content_top: f32,
+    /// URL of the hovered link
+    link: Option<String>,
+    progress: LoadProgress,
+}
+
+impl StatusBar {
+    /// Create a status bar for a window
+    pub fn new(width: f32, height: f32) -> Self {
+        Self {
+            window: Rect { x: 0.0, y: 0.0, width, height },
+            content_top: 0.0,
+            link: None,
+            progress: LoadProgress::new(),
+        }
+    }
+
+    /// Place the status bar in the window, below chrome of the given height
+    pub fn set_layout(&mut self, width: f32, height: f32, content_top: f32) {
+        self.window.width = width;
+        self.window.height = height;
+        self.content_top = content_top;
+    }
+
+    /// Show a hovered link's URL, or clear it with `None`
+    pub fn set_link(&mut self, link: Option<String>) {
+        self.link = link;
+    }
+
+    /// URL of the hovered link
+    pub fn link(&self) -> Option<&str> {
+        self.link.as_deref()
+    }
+
+    /// Load progress
+    pub fn progress(&self) -> &LoadProgress {
+        &self.progress
+    }
+
+    /// Load progress, for feeding load events
+    pub fn progress_mut(&mut self) -> &mut LoadProgress {
+        &mut self.progress
+    }
+
+    /// Text for the status bubble; a hovered link wins over load status
+    pub fn text(&self) -> Option<String> {
+        if let Some(link) = &self.link {
+            return Some(link.clone());
+        }
+        if !self.progress.is_loading() {
+            return None;
+        }
+        Some(match self.progress.outstanding() {
+            0 => "Loading…".to_string(),
+            1 => "Loading 1 resource…".to_string(),
+            n => format!("Loading {} resources…", n),
+        })
+    }
+
+    /// Bounds of the status bubble, if it is shown
+    pub fn bubble_rect(&self) -> Option<Rect> {
+        let text = self.text()?;
+        let width = (text.chars().count() as f32 * CHAR_WIDTH + 16.0)
+            .min(MAX_BUBBLE_WIDTH)
+            .min(self.window.width);
+        Some(Rect {
+            x: 0.0,
+            y: self.window.height - STATUS_BAR_HEIGHT,
+            width,
+            height: STATUS_BAR_HEIGHT,
+        })
+    }
+
+    /// Build display commands for the progress line and status bubble
+    pub fn paint(&self) -> DisplayList {
+        let mut list = Vec::new();
+
+        if self.progress.is_loading() {
+            list.push(DisplayCommand::SolidRect {
+                color: PROGRESS_COLOR,
+                rect: Rect {
+                    x: 0.0,
+                    y: self.content_top,
+                    width: self.window.width * self.progress.fraction(),
+                    height: PROGRESS_HEIGHT,
+                },
+            });
+        }
+
+        if let (Some(text), Some(bubble)) = (self.text(), self.bubble_rect()) {
+            list.push(DisplayCommand::SolidRect { color: BUBBLE_BACKGROUND, rect: bubble });
+            list.push(DisplayCommand::Border {
+                color: BUBBLE_BORDER,
+                rect: bubble,
+                widths: (0.0, 1.0, 1.0, 0.0),
+            });
+            list.push(DisplayCommand::Text {
+                text,
+                rect: Rect {
+                    x: bubble.x + 8.0,
+                    y: bubble.y + 4.0,
+                    width: bubble.width - 16.0,
+                    height: 14.0,
+                },
+                color: BUBBLE_TEXT,
+                font_family: "sans-serif".to_string(),
+                font_size: 12.0,
+                style: TextStyle::default(),
+            });
+        }
+
+        list
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use url::Url;
+
+    fn css(name: &str) -> LoadEvent {
+        LoadEvent::SubresourceFinished(Url::parse(&format!("http://example.com/{}", name)).unwrap())
+    }
+
+    #[test]
+    fn test_progress_follows_load_events() {
+        let mut progress = LoadProgress::new();
+        assert_eq!(progress.fraction(), 0.0);
+
+        progress.handle(&LoadEvent::Started(Url::parse("http://example.com/").unwrap()));
+        assert!(progress.is_loading());
+        let started = progress.fraction();
+
+        progress.handle(&LoadEvent::DocumentLoaded);
+        progress.handle(&LoadEvent::SubresourcesDiscovered(2));
+        assert_eq!(progress.outstanding(), 2);
+        let document = progress.fraction();
+        assert!(document > started);
+
+        progress.handle(&css("a.css"));
+        assert_eq!(progress.outstanding(), 1);
+        assert!(progress.fraction() > document);
+
+        progress.handle(&LoadEvent::Finished);
+        assert!(!progress.is_loading());
+        assert_eq!(progress.outstanding(), 0);
+    }
+
+    #[test]
+    fn test_link_preview_wins_over_load_status() {
+        let mut status = StatusBar::new(800.0, 600.0);
+        assert!(status.text().is_none());
+        assert!(status.paint().is_empty());
+
+        status.progress_mut().handle(&LoadEvent::Started(Url::parse("http://example.com/").unwrap()));
+        status.progress_mut().handle(&LoadEvent::DocumentLoaded);
+        status.progress_mut().handle(&LoadEvent::SubresourcesDiscovered(3));
+        assert_eq!(status.text().as_deref(), Some("Loading 3 resources…"));
+
+        status.set_link(Some("http://example.com/next".to_string()));
+        assert_eq!(status.text().as_deref(), Some("http://example.com/next"));
+        let bubble = status.bubble_rect().unwrap();
+        assert_eq!(bubble.y, 600.0 - STATUS_BAR_HEIGHT);
+        assert!(bubble.width <= MAX_BUBBLE_WIDTH);
+    }
+
+    #[test]
+    fn test_progress_line_under_chrome() {
+        let mut status = StatusBar::new(800.0, 600.0);
+        status.set_layout(1000.0, 700.0, 88.0);
+        status.progress_mut().handle(&LoadEvent::Started(Url::parse("http://example.com/").unwrap()));
+
+        let line = status.paint().into_iter().find_map(|cmd| match cmd {
+            DisplayCommand::SolidRect { color, rect } if color == PROGRESS_COLOR => Some(rect),
+            _ => None,
+        });
+        let line = line.unwrap();
+        assert_eq!(line.y, 88.0);
+        assert_eq!(line.width, 100.0);
+    }
+}
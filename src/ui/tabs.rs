@@ -0,0 +1,710 @@
+// Tabbed browsing: per-tab page sessions and the tab strip
+
+use crate::css::Color;
+use crate::display::{DisplayCommand, DisplayList};
+use crate::dom::{Document, Node};
+use crate::editing::Editor;
+use crate::js::JsContext;
+use crate::layout::Rect;
+use crate::multiprocess::DocumentId;
+use crate::navigation::NavigationHistory;
+use crate::text_style::TextStyle;
+use crate::window::ScrollState;
+use url::Url;
+use winit::keyboard::{Key, NamedKey};
+
+/// Unique identifier for a tab
+pub type TabId = u64;
+
+/// Height of the tab strip
+pub const TAB_STRIP_HEIGHT: f32 = 32.0;
+
+const STRIP_BACKGROUND: Color = Color { r: 222, g: 225, b: 230, a: 255 };
+const TAB_BACKGROUND: Color = Color { r: 235, g: 237, b: 240, a: 255 };
+const ACTIVE_TAB_BACKGROUND: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+const TAB_BORDER: Color = Color { r: 190, g: 193, b: 198, a: 255 };
+const TAB_TEXT: Color = Color { r: 32, g: 33, b: 36, a: 255 };
+const TAB_FONT_SIZE: f32 = 12.0;
+
+/// An independent page session
+pub struct Tab {
+    id: TabId,
+    /// Page title shown in the tab strip
+    pub title: String,
+    /// Favicon URL, if the page has one
+    pub favicon: Option<Url>,
+    /// Retained document of the current page, changed in place by scripts
+    pub document: Option<Document>,
+    /// Caret and selection in the page's focused contenteditable region
+    pub editor: Option<Editor>,
+    /// Per-tab navigation history
+    pub history: NavigationHistory,
+    /// Per-tab scroll position
+    pub scroll: ScrollState,
+    /// Per-tab JavaScript context
+    pub js_context: JsContext,
+    /// Isolated worlds the page's extension content scripts run in, by extension
+    pub extension_worlds: Vec<(String, JsContext)>,
+    /// The page's registration on the cross-document message bus
+    pub document_id: Option<DocumentId>,
+    /// Is the tab loading a page
+    pub loading: bool,
+    /// Does the current page have an article for reader mode
+    pub reader_available: bool,
+    /// Is the page shown in reader mode
+    pub reader_mode: bool,
+    /// Was the page dropped to save memory, to be loaded again when shown
+    pub discarded: bool,
+    /// Resources the page loaded over plain HTTP, though it is secure
+    pub insecure_content: Vec<Url>,
+}
+
+impl Tab {
+    fn new(id: TabId) -> Self {
+        Self {
+            id,
+            title: "New Tab".to_string(),
+            favicon: None,
+            document: None,
+            editor: None,
+            history: NavigationHistory::new(),
+            scroll: ScrollState::default(),
+            js_context: JsContext::new(),
+            extension_worlds: Vec::new(),
+            document_id: None,
+            loading: false,
+            reader_available: false,
+            reader_mode: false,
+            discarded: false,
+            insecure_content: Vec::new(),
+        }
+    }
+
+    /// Get the tab ID
+    pub fn id(&self) -> TabId {
+        self.id
+    }
+
+    /// Get the URL of the current page
+    pub fn url(&self) -> Option<&Url> {
+        self.history.current_url()
+    }
+
+    /// The current page's document as an owned tree, for styling and layout
+    pub fn tree(&self) -> Option<&Node> {
+        self.document.as_ref().map(Document::tree)
+    }
+
+    /// Replace the document after a page load, updating title and favicon
+    pub fn set_document(&mut self, document: Node, base_url: &Url) {
+        self.title = document_title(&document).unwrap_or_else(|| base_url.to_string());
+        self.favicon = favicon_url(&document, base_url);
+        self.document = Some(Document::new(document));
+        self.editor = None;
+        self.scroll.scroll_to(0.0, 0.0);
+        // Each page load gets a fresh script environment
+        self.js_context = JsContext::new();
+        self.extension_worlds.clear();
+        self.discarded = false;
+        self.insecure_content.clear();
+    }
+
+    /// Drop the page's document and scripts to save memory
+    ///
+    /// History, scroll position, title and favicon are kept, so the page can
+    /// be loaded again where it was when the tab is shown.
+    pub fn discard(&mut self) {
+        self.document = None;
+        self.editor = None;
+        self.js_context = JsContext::new();
+        self.extension_worlds.clear();
+        self.discarded = true;
+    }
+}
+
+/// Commands bound to tab keyboard shortcuts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TabCommand {
+    /// Ctrl+T
+    NewTab,
+    /// Ctrl+W
+    CloseTab,
+    /// Ctrl+Tab
+    NextTab,
+    /// Ctrl+Shift+Tab
+    PreviousTab,
+    /// Ctrl+1 through Ctrl+8 (zero-based index)
+    SelectTab(usize),
+    /// Ctrl+9
+    SelectLastTab,
+}
+
+impl TabCommand {
+    /// Map a key press to a tab command
+    pub fn from_key(key: &Key, ctrl: bool, shift: bool) -> Option<Self> {
+        if !ctrl {
+            return None;
+        }
+
+        match key {
+            Key::Named(NamedKey::Tab) if shift => Some(TabCommand::PreviousTab),
+            Key::Named(NamedKey::Tab) => Some(TabCommand::NextTab),
+            Key::Character(c) => match c.to_lowercase().as_str() {
+                "t" => Some(TabCommand::NewTab),
+                "w" => Some(TabCommand::CloseTab),
+                "9" => Some(TabCommand::SelectLastTab),
+                digit => digit
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| (1..=8).contains(n))
+                    .map(|n| TabCommand::SelectTab(n - 1)),
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Manages the set of open tabs and which one is active
+///
+/// There is always at least one tab; closing the last tab replaces it
+/// with a fresh one.
+pub struct TabManager {
+    tabs: Vec<Tab>,
+    active: usize,
+    next_id: TabId,
+}
+
+impl TabManager {
+    /// Create a tab manager with a single blank tab
+    pub fn new() -> Self {
+        let mut manager = Self {
+            tabs: Vec::new(),
+            active: 0,
+            next_id: 1,
+        };
+        manager.open_tab();
+        manager
+    }
+
+    /// Open a new tab after the active one and activate it
+    pub fn open_tab(&mut self) -> TabId {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let index = if self.tabs.is_empty() { 0 } else { self.active + 1 };
+        self.tabs.insert(index, Tab::new(id));
+        self.active = index;
+        id
+    }
+
+    /// Close a tab; returns false if no tab has that ID
+    pub fn close_tab(&mut self, id: TabId) -> bool {
+        self.take_tab(id).is_some()
+    }
+
+    /// Remove a tab without dropping it, e.g. to move it to another window
+    pub fn take_tab(&mut self, id: TabId) -> Option<Tab> {
+        let index = self.index_of(id)?;
+
+        let tab = self.tabs.remove(index);
+        if self.tabs.is_empty() {
+            self.open_tab();
+        } else if index < self.active || self.active >= self.tabs.len() {
+            self.active = self.active.saturating_sub(1);
+        }
+        Some(tab)
+    }
+
+    /// Insert a tab taken from another manager after the active one and activate it
+    ///
+    /// The tab is given a new ID so it cannot clash with this manager's tabs.
+    pub fn adopt_tab(&mut self, mut tab: Tab) -> TabId {
+        let id = self.next_id;
+        self.next_id += 1;
+        tab.id = id;
+
+        let index = if self.tabs.is_empty() { 0 } else { self.active + 1 };
+        self.tabs.insert(index, tab);
+        self.active = index;
+        id
+    }
+
+    /// Activate a tab by ID
+    pub fn activate(&mut self, id: TabId) -> bool {
+        match self.index_of(id) {
+            Some(index) => {
+                self.active = index;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Activate the tab at a position in the strip
+    pub fn activate_index(&mut self, index: usize) -> bool {
+        if index < self.tabs.len() {
+            self.active = index;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Activate the next tab, wrapping around
+    pub fn next_tab(&mut self) {
+        self.active = (self.active + 1) % self.tabs.len();
+    }
+
+    /// Activate the previous tab, wrapping around
+    pub fn previous_tab(&mut self) {
+        self.active = (self.active + self.tabs.len() - 1) % self.tabs.len();
+    }
+
+    /// Run a keyboard shortcut command, returning the active tab afterwards
+    pub fn execute(&mut self, command: TabCommand) -> TabId {
+        match command {
+            TabCommand::NewTab => {
+                self.open_tab();
+            }
+            TabCommand::CloseTab => {
+                let id = self.active_id();
+                self.close_tab(id);
+            }
+            TabCommand::NextTab => self.next_tab(),
+            TabCommand::PreviousTab => self.previous_tab(),
+            TabCommand::SelectTab(index) => {
+                self.activate_index(index);
+            }
+            TabCommand::SelectLastTab => self.active = self.tabs.len() - 1,
+        }
+        self.active_id()
+    }
+
+    /// Get the active tab
+    pub fn active(&self) -> &Tab {
+        &self.tabs[self.active]
+    }
+
+    /// Get the active tab mutably
+    pub fn active_mut(&mut self) -> &mut Tab {
+        &mut self.tabs[self.active]
+    }
+
+    /// Get the active tab's ID
+    pub fn active_id(&self) -> TabId {
+        self.tabs[self.active].id
+    }
+
+    /// Get the active tab's position in the strip
+    pub fn active_index(&self) -> usize {
+        self.active
+    }
+
+    /// Get a tab by ID
+    pub fn tab(&self, id: TabId) -> Option<&Tab> {
+        self.tabs.iter().find(|t| t.id == id)
+    }
+
+    /// Get a tab by ID mutably
+    pub fn tab_mut(&mut self, id: TabId) -> Option<&mut Tab> {
+        self.tabs.iter_mut().find(|t| t.id == id)
+    }
+
+    /// All tabs in strip order
+    pub fn tabs(&self) -> &[Tab] {
+        &self.tabs
+    }
+
+    /// All tabs in strip order, mutably
+    pub fn tabs_mut(&mut self) -> &mut [Tab] {
+        &mut self.tabs
+    }
+
+    /// Number of open tabs
+    pub fn len(&self) -> usize {
+        self.tabs.len()
+    }
+
+    /// Always false; a tab manager keeps at least one tab open
+    pub fn is_empty(&self) -> bool {
+        self.tabs.is_empty()
+    }
+
+    fn index_of(&self, id: TabId) -> Option<usize> {
+        self.tabs.iter().position(|t| t.id == id)
+    }
+}
+
+impl Default for TabManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Part of the tab strip under a point
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TabStripHit {
+    Tab(usize),
+    Close(usize),
+    NewTab,
+}
+
+/// Tab strip UI component
+pub struct TabStrip {
+    bounds: Rect,
+    max_tab_width: f32,
+}
+
+impl TabStrip {
+    /// Create a tab strip spanning the given width
+    pub fn new(width: f32) -> Self {
+        Self {
+            bounds: Rect {
+                x: 0.0,
+                y: 0.0,
+                width,
+                height: TAB_STRIP_HEIGHT,
+            },
+            max_tab_width: 220.0,
+        }
+    }
+
+    /// Get the visual bounds
+    pub fn bounds(&self) -> &Rect {
+        &self.bounds
+    }
+
+    /// Set the vertical position of the strip
+    pub fn set_y(&mut self, y: f32) {
+        self.bounds.y = y;
+    }
+
+    /// Set the width of the strip
+    pub fn set_width(&mut self, width: f32) {
+        self.bounds.width = width;
+    }
+
+    /// Rectangles for each tab, shrinking tabs to fit the strip
+    pub fn tab_rects(&self, count: usize) -> Vec<Rect> {
+        if count == 0 {
+            return Vec::new();
+        }
+
+        let available = (self.bounds.width - TAB_STRIP_HEIGHT).max(0.0);
+        let width = (available / count as f32).min(self.max_tab_width);
+
+        (0..count)
+            .map(|i| Rect {
+                x: self.bounds.x + width * i as f32,
+                y: self.bounds.y,
+                width,
+                height: self.bounds.height,
+            })
+            .collect()
+    }
+
+    /// Close button inside a tab
+    pub fn close_button_rect(tab: &Rect) -> Rect {
+        let size = 16.0;
+        Rect {
+            x: tab.x + tab.width - size - 8.0,
+            y: tab.y + (tab.height - size) / 2.0,
+            width: size,
+            height: size,
+        }
+    }
+
+    /// New tab button following the last tab
+    pub fn new_tab_button_rect(&self, count: usize) -> Rect {
+        let x = self
+            .tab_rects(count)
+            .last()
+            .map(|r| r.x + r.width)
+            .unwrap_or(self.bounds.x);
+        Rect {
+            x,
+            y: self.bounds.y,
+            width: TAB_STRIP_HEIGHT,
+            height: self.bounds.height,
+        }
+    }
+
+    /// Find what part of the strip is under a point
+    pub fn hit_test(&self, x: f32, y: f32, count: usize) -> Option<TabStripHit> {
+        if !self.bounds.contains(x, y) {
+            return None;
+        }
+
+        for (i, rect) in self.tab_rects(count).iter().enumerate() {
+            if Self::close_button_rect(rect).contains(x, y) {
+                return Some(TabStripHit::Close(i));
+            }
+            if rect.contains(x, y) {
+                return Some(TabStripHit::Tab(i));
+            }
+        }
+
+        if self.new_tab_button_rect(count).contains(x, y) {
+            Some(TabStripHit::NewTab)
+        } else {
+            None
+        }
+    }
+
+    /// Handle a click on the strip, updating the tab manager
+    pub fn handle_click(&self, x: f32, y: f32, tabs: &mut TabManager) -> Option<TabStripHit> {
+        let hit = self.hit_test(x, y, tabs.len())?;
+        match hit {
+            TabStripHit::Tab(index) => {
+                tabs.activate_index(index);
+            }
+            TabStripHit::Close(index) => {
+                let id = tabs.tabs()[index].id();
+                tabs.close_tab(id);
+            }
+            TabStripHit::NewTab => {
+                tabs.open_tab();
+            }
+        }
+        Some(hit)
+    }
+
+    /// Build display commands for the strip
+    pub fn paint(&self, tabs: &TabManager) -> DisplayList {
+        let mut list = vec![DisplayCommand::SolidRect {
+            color: STRIP_BACKGROUND,
+            rect: self.bounds,
+        }];
+
+        for (i, (tab, rect)) in tabs.tabs().iter().zip(self.tab_rects(tabs.len())).enumerate() {
+            let active = i == tabs.active_index();
+            list.push(DisplayCommand::SolidRect {
+                color: if active { ACTIVE_TAB_BACKGROUND } else { TAB_BACKGROUND },
+                rect,
+            });
+            list.push(DisplayCommand::Border {
+                color: TAB_BORDER,
+                rect,
+                widths: (0.0, 1.0, 0.0, if active { 0.0 } else { 1.0 }),
+            });
+
+            let mut text_x = rect.x + 8.0;
+            if let Some(ref favicon) = tab.favicon {
+                list.push(DisplayCommand::Image {
+                    url: favicon.clone(),
+                    rect: Rect {
+                        x: text_x,
+                        y: rect.y + (rect.height - 16.0) / 2.0,
+                        width: 16.0,
+                        height: 16.0,
+                    },
+                });
+                text_x += 22.0;
+            }
+
+            let close = Self::close_button_rect(&rect);
+            list.push(DisplayCommand::Text {
+                text: if tab.loading { "Loading...".to_string() } else { tab.title.clone() },
+                rect: Rect {
+                    x: text_x,
+                    y: rect.y + 8.0,
+                    width: (close.x - text_x - 4.0).max(0.0),
+                    height: rect.height - 16.0,
+                },
+                color: TAB_TEXT,
+                font_family: "sans-serif".to_string(),
+                font_size: TAB_FONT_SIZE,
+                style: TextStyle::default(),
+            });
+            list.push(DisplayCommand::Text {
+                text: "\u{00d7}".to_string(),
+                rect: close,
+                color: TAB_TEXT,
+                font_family: "sans-serif".to_string(),
+                font_size: TAB_FONT_SIZE,
+                style: TextStyle::default(),
+            });
+        }
+
+        list.push(DisplayCommand::Text {
+            text: "+".to_string(),
+            rect: self.new_tab_button_rect(tabs.len()),
+            color: TAB_TEXT,
+            font_family: "sans-serif".to_string(),
+            font_size: TAB_FONT_SIZE + 4.0,
+            style: TextStyle::default(),
+        });
+
+        list
+    }
+}
+
+/// Extract the text of the document's `<title>` element
+pub fn document_title(node: &Node) -> Option<String> {
+    if let Some(elem) = node.element_data() {
+        if elem.tag_name == "title" {
+            let title: String = node
+                .children
+                .iter()
+                .filter_map(|c| c.text_content())
+                .collect();
+            let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+            return if title.is_empty() { None } else { Some(title) };
+        }
+    }
+    node.children.iter().find_map(document_title)
+}
+
+/// Find the page favicon from `<link rel="icon">`, falling back to `/favicon.ico`
+pub fn favicon_url(node: &Node, base_url: &Url) -> Option<Url> {
+    find_icon_href(node)
+        .and_then(|href| base_url.join(href).ok())
+        .or_else(|| match base_url.scheme() {
+            "http" | "https" => base_url.join("/favicon.ico").ok(),
+            _ => None,
+        })
+}
+
+fn find_icon_href(node: &Node) -> Option<&str> {
+    if let Some(elem) = node.element_data() {
+        let is_icon = elem.tag_name == "link"
+            && elem
+                .get_attribute("rel")
+                .map(|rel| rel.split_whitespace().any(|r| r.eq_ignore_ascii_case("icon")))
+                .unwrap_or(false);
+        if is_icon {
+            if let Some(href) = elem.get_attribute("href") {
+                return Some(href);
+            }
+        }
+    }
+    node.children.iter().find_map(find_icon_href)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html::HtmlParser;
+
+    #[test]
+    fn test_tab_manager_open_close() {
+        let mut tabs = TabManager::new();
+        assert_eq!(tabs.len(), 1);
+        let first = tabs.active_id();
+
+        let second = tabs.open_tab();
+        assert_eq!(tabs.len(), 2);
+        assert_eq!(tabs.active_id(), second);
+
+        assert!(tabs.close_tab(second));
+        assert_eq!(tabs.active_id(), first);
+
+        // Closing the last tab leaves a fresh one
+        assert!(tabs.close_tab(first));
+        assert_eq!(tabs.len(), 1);
+        assert_ne!(tabs.active_id(), first);
+
+        assert!(!tabs.close_tab(first));
+    }
+
+    #[test]
+    fn test_tab_sessions_are_independent() {
+        let mut tabs = TabManager::new();
+        let first = tabs.active_id();
+        tabs.active_mut()
+            .history
+            .navigate_to(Url::parse("http://example.com/").unwrap());
+        tabs.active_mut().scroll.set_content_size(800.0, 2000.0);
+        tabs.active_mut().scroll.scroll_by(0.0, 300.0);
+
+        tabs.open_tab();
+        assert!(tabs.active().url().is_none());
+        assert_eq!(tabs.active().scroll.offset_y, 0.0);
+
+        tabs.activate(first);
+        assert_eq!(tabs.active().url().unwrap().as_str(), "http://example.com/");
+        assert_eq!(tabs.active().scroll.offset_y, 300.0);
+    }
+
+    #[test]
+    fn test_move_tab_between_managers() {
+        let mut source = TabManager::new();
+        let moved = source.active_id();
+        source.active_mut()
+            .history
+            .navigate_to(Url::parse("http://example.com/").unwrap());
+
+        let mut target = TabManager::new();
+        let tab = source.take_tab(moved).unwrap();
+        // The source window keeps a blank tab
+        assert_eq!(source.len(), 1);
+        assert!(source.active().url().is_none());
+
+        let id = target.adopt_tab(tab);
+        assert_eq!(target.len(), 2);
+        assert_eq!(target.active_id(), id);
+        assert_ne!(id, target.tabs()[0].id());
+        assert_eq!(target.active().url().unwrap().as_str(), "http://example.com/");
+    }
+
+    #[test]
+    fn test_tab_shortcuts() {
+        let ctrl_t = Key::Character("t".into());
+        assert_eq!(TabCommand::from_key(&ctrl_t, true, false), Some(TabCommand::NewTab));
+        assert_eq!(TabCommand::from_key(&ctrl_t, false, false), None);
+        assert_eq!(
+            TabCommand::from_key(&Key::Named(NamedKey::Tab), true, true),
+            Some(TabCommand::PreviousTab)
+        );
+        assert_eq!(
+            TabCommand::from_key(&Key::Character("2".into()), true, false),
+            Some(TabCommand::SelectTab(1))
+        );
+
+        let mut tabs = TabManager::new();
+        let first = tabs.active_id();
+        tabs.execute(TabCommand::NewTab);
+        tabs.execute(TabCommand::NewTab);
+        assert_eq!(tabs.execute(TabCommand::NextTab), first);
+        assert_eq!(tabs.execute(TabCommand::PreviousTab), tabs.tabs()[2].id());
+        tabs.execute(TabCommand::CloseTab);
+        assert_eq!(tabs.len(), 2);
+    }
+
+    #[test]
+    fn test_tab_strip_hit_test() {
+        let strip = TabStrip::new(800.0);
+        let rects = strip.tab_rects(2);
+        assert_eq!(rects[0].width, 220.0);
+
+        assert_eq!(strip.hit_test(20.0, 16.0, 2), Some(TabStripHit::Tab(0)));
+        assert_eq!(strip.hit_test(230.0, 16.0, 2), Some(TabStripHit::Tab(1)));
+        let close = TabStrip::close_button_rect(&rects[0]);
+        assert_eq!(strip.hit_test(close.x + 1.0, close.y + 1.0, 2), Some(TabStripHit::Close(0)));
+        assert_eq!(strip.hit_test(450.0, 16.0, 2), Some(TabStripHit::NewTab));
+        assert_eq!(strip.hit_test(700.0, 16.0, 2), None);
+        assert_eq!(strip.hit_test(20.0, 100.0, 2), None);
+    }
+
+    #[test]
+    fn test_set_document_updates_title_and_favicon() {
+        let dom = HtmlParser::parse(
+            r#"<html><head><title> Example
+            Page </title><link rel="icon" href="/static/icon.png"></head><body></body></html>"#,
+        );
+        let base = Url::parse("https://example.com/a/b.html").unwrap();
+
+        let mut tabs = TabManager::new();
+        tabs.active_mut().set_document(dom, &base);
+        assert_eq!(tabs.active().title, "Example Page");
+        assert_eq!(
+            tabs.active().favicon.as_ref().unwrap().as_str(),
+            "https://example.com/static/icon.png"
+        );
+
+        let plain = HtmlParser::parse("<p>No head</p>");
+        tabs.active_mut().set_document(plain, &base);
+        assert_eq!(tabs.active().title, base.to_string());
+        assert_eq!(tabs.active().favicon.as_ref().unwrap().path(), "/favicon.ico");
+    }
+}
@@ -0,0 +1,441 @@
+// User content - stylesheets and scripts the user adds to pages
+//
+// User stylesheets join the cascade at the user origin, between the
+// browser's defaults and the page's own rules. User scripts run in the
+// page's script context, either before the page's scripts
+// (`document-start`) or after them, before `DOMContentLoaded`
+// (`document-end`). Both are limited to the pages their match patterns
+// cover, written as WebExtension match patterns (`https://*.example.com/*`,
+// `<all_urls>`). Files carry the patterns in a Greasemonkey-style header:
+//
+//     // ==UserScript==
+//     // @match   https://example.com/*
+//     // @run-at  document-start
+//     // ==/UserScript==
+
+use crate::css::{CssParser, Origin, Stylesheet};
+use regex::Regex;
+use std::fmt;
+use std::path::Path;
+use url::Url;
+
+/// Which pages a user stylesheet or script applies to
+#[derive(Debug, Clone)]
+pub struct MatchPattern {
+    source: String,
+    kind: PatternKind,
+}
+
+#[derive(Debug, Clone)]
+enum PatternKind {
+    /// `<all_urls>`: every web and file page
+    AllUrls,
+    Url {
+        /// Scheme, or `*` for http and https
+        scheme: String,
+        /// Host, lowercased; empty for `*` and for file URLs
+        host: String,
+        /// `*.host`: the host and its subdomains
+        subdomains: bool,
+        /// Path and query, with `*` matching anything
+        path: Regex,
+    },
+}
+
+impl MatchPattern {
+    /// Parse a match pattern, e.g. `*://*.example.com/news/*`
+    pub fn parse(pattern: &str) -> Option<Self> {
+        let source = pattern.trim();
+        if source == "<all_urls>" {
+            return Some(Self { source: source.to_string(), kind: PatternKind::AllUrls });
+        }
+        let (scheme, rest) = source.split_once("://")?;
+        if !matches!(scheme, "*" | "http" | "https" | "file") {
+            return None;
+        }
+        let (host, path) = rest.split_at(rest.find('/')?);
+        let (host, subdomains) = match host {
+            "*" => ("", true),
+            _ => match host.strip_prefix("*.") {
+                Some(domain) => (domain, true),
+                None => (host, false),
+            },
+        };
+        // Hosts are matched whole: no wildcards inside them and no ports
+        if host.contains(['*', ':']) || (scheme == "file") != (host.is_empty() && !subdomains) {
+            return None;
+        }
+        let glob: Vec<String> = path.split('*').map(regex::escape).collect();
+        let path = Regex::new(&format!("^{}$", glob.join(".*"))).ok()?;
+        let kind = PatternKind::Url { scheme: scheme.to_string(), host: host.to_ascii_lowercase(), subdomains, path };
+        Some(Self { source: source.to_string(), kind })
+    }
+
+    /// The pattern covering every page
+    pub fn all_urls() -> Self {
+        Self { source: "<all_urls>".to_string(), kind: PatternKind::AllUrls }
+    }
+
+    /// Does the pattern cover a page
+    pub fn matches(&self, url: &Url) -> bool {
+        match &self.kind {
+            PatternKind::AllUrls => matches!(url.scheme(), "http" | "https" | "file"),
+            PatternKind::Url { scheme, host, subdomains, path } => {
+                let scheme_matches = match scheme.as_str() {
+                    "*" => matches!(url.scheme(), "http" | "https"),
+                    scheme => url.scheme() == scheme,
+                };
+                let url_host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+                let host_matches = url_host == *host
+                    || (*subdomains && (host.is_empty() || url_host.ends_with(&format!(".{}", host))));
+                let path_and_query = match url.query() {
+                    Some(query) => format!("{}?{}", url.path(), query),
+                    None => url.path().to_string(),
+                };
+                scheme_matches && host_matches && path.is_match(&path_and_query)
+            }
+        }
+    }
+}
+
+impl fmt::Display for MatchPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// When a user script runs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RunAt {
+    /// Before the page's own scripts
+    DocumentStart,
+    /// After the page's scripts, before `DOMContentLoaded`
+    #[default]
+    DocumentEnd,
+}
+
+impl RunAt {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "document-start" => Some(RunAt::DocumentStart),
+            // There is no later point than the end of the document to run at
+            "document-end" | "document-idle" => Some(RunAt::DocumentEnd),
+            _ => None,
+        }
+    }
+}
+
+/// Pages a stylesheet or script applies to
+#[derive(Debug, Clone, Default)]
+struct Matches {
+    /// `None` for every page `<all_urls>` covers
+    include: Option<Vec<MatchPattern>>,
+    exclude: Vec<MatchPattern>,
+}
+
+impl Matches {
+    fn applies_to(&self, url: &Url) -> bool {
+        let included = match &self.include {
+            Some(include) => include.iter().any(|pattern| pattern.matches(url)),
+            None => MatchPattern::all_urls().matches(url),
+        };
+        included && !self.exclude.iter().any(|pattern| pattern.matches(url))
+    }
+}
+
+/// A stylesheet added to pages at the user origin
+#[derive(Debug, Clone)]
+pub struct UserStyleSheet {
+    pub name: String,
+    pub css: String,
+    matches: Matches,
+}
+
+impl UserStyleSheet {
+    /// A stylesheet for every page
+    pub fn new(name: &str, css: &str) -> Self {
+        Self { name: name.to_string(), css: css.to_string(), matches: Matches::default() }
+    }
+
+    /// A stylesheet whose `==UserStyle==` header may name its pages
+    pub fn parse(name: &str, source: &str) -> Self {
+        let mut sheet = Self::new(name, source);
+        if let Some(header) = Header::parse(source, "/* ==UserStyle==", "==/UserStyle== */") {
+            sheet.name = header.name.unwrap_or(sheet.name);
+            sheet.matches = header.matches;
+        }
+        sheet
+    }
+
+    /// Only apply the stylesheet to pages a pattern covers (the first call
+    /// replaces the default of every page)
+    pub fn with_match(mut self, pattern: MatchPattern) -> Self {
+        self.matches.include.get_or_insert_with(Vec::new).push(pattern);
+        self
+    }
+
+    /// Leave out pages a pattern covers
+    pub fn with_exclude_match(mut self, pattern: MatchPattern) -> Self {
+        self.matches.exclude.push(pattern);
+        self
+    }
+
+    /// Does the stylesheet apply to a page
+    pub fn applies_to(&self, url: &Url) -> bool {
+        self.matches.applies_to(url)
+    }
+}
+
+/// A script run in pages the user chose
+#[derive(Debug, Clone)]
+pub struct UserScript {
+    pub name: String,
+    pub source: String,
+    pub run_at: RunAt,
+    matches: Matches,
+}
+
+impl UserScript {
+    /// A script for every page
+    pub fn new(name: &str, source: &str, run_at: RunAt) -> Self {
+        Self { name: name.to_string(), source: source.to_string(), run_at, matches: Matches::default() }
+    }
+
+    /// A script whose `==UserScript==` header may name its pages and when it runs
+    pub fn parse(name: &str, source: &str) -> Self {
+        let mut script = Self::new(name, source, RunAt::default());
+        if let Some(header) = Header::parse(source, "// ==UserScript==", "// ==/UserScript==") {
+            script.name = header.name.unwrap_or(script.name);
+            script.run_at = header.run_at.unwrap_or_default();
+            script.matches = header.matches;
+        }
+        script
+    }
+
+    /// Only run the script in pages a pattern covers (the first call
+    /// replaces the default of every page)
+    pub fn with_match(mut self, pattern: MatchPattern) -> Self {
+        self.matches.include.get_or_insert_with(Vec::new).push(pattern);
+        self
+    }
+
+    /// Leave out pages a pattern covers
+    pub fn with_exclude_match(mut self, pattern: MatchPattern) -> Self {
+        self.matches.exclude.push(pattern);
+        self
+    }
+
+    /// Does the script run in a page
+    pub fn applies_to(&self, url: &Url) -> bool {
+        self.matches.applies_to(url)
+    }
+}
+
+/// Metadata from a user script or style header
+struct Header {
+    name: Option<String>,
+    run_at: Option<RunAt>,
+    matches: Matches,
+}
+
+impl Header {
+    /// Read the `@key value` lines between the opening and closing markers
+    ///
+    /// A header with no `@match` applies everywhere; one whose patterns
+    /// cannot be read applies nowhere, rather than everywhere.
+    fn parse(source: &str, open: &str, close: &str) -> Option<Self> {
+        let mut lines = source.lines().map(str::trim).skip_while(|line| *line != open);
+        lines.next()?;
+        let mut header = Header { name: None, run_at: None, matches: Matches::default() };
+        for line in lines.take_while(|line| *line != close) {
+            let line = line.trim_start_matches("//").trim();
+            let Some((key, value)) = line.strip_prefix('@').and_then(|line| line.split_once(char::is_whitespace)) else {
+                continue;
+            };
+            let value = value.trim();
+            match key {
+                "name" => header.name = Some(value.to_string()),
+                "run-at" => header.run_at = RunAt::parse(value),
+                "match" => header.matches.include.get_or_insert_with(Vec::new).extend(MatchPattern::parse(value)),
+                "exclude-match" => header.matches.exclude.extend(MatchPattern::parse(value)),
+                _ => {}
+            }
+        }
+        Some(header)
+    }
+}
+
+/// The user's stylesheets and scripts
+#[derive(Debug, Clone, Default)]
+pub struct UserContent {
+    stylesheets: Vec<UserStyleSheet>,
+    scripts: Vec<UserScript>,
+}
+
+impl UserContent {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the `*.user.css` and `*.user.js` files of a directory, in name order
+    pub fn load(dir: &Path) -> std::io::Result<Self> {
+        let mut paths: Vec<_> = std::fs::read_dir(dir)?.filter_map(|entry| Some(entry.ok()?.path())).collect();
+        paths.sort();
+        let mut content = Self::new();
+        for path in paths {
+            let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+            if let Some(name) = file_name.strip_suffix(".user.css") {
+                content.add_stylesheet(UserStyleSheet::parse(name, &std::fs::read_to_string(&path)?));
+            } else if let Some(name) = file_name.strip_suffix(".user.js") {
+                content.add_script(UserScript::parse(name, &std::fs::read_to_string(&path)?));
+            }
+        }
+        Ok(content)
+    }
+
+    pub fn add_stylesheet(&mut self, stylesheet: UserStyleSheet) {
+        self.stylesheets.push(stylesheet);
+    }
+
+    pub fn add_script(&mut self, script: UserScript) {
+        self.scripts.push(script);
+    }
+
+    /// Remove the stylesheets and scripts with a name, returning whether there were any
+    pub fn remove(&mut self, name: &str) -> bool {
+        let before = self.stylesheets.len() + self.scripts.len();
+        self.stylesheets.retain(|sheet| sheet.name != name);
+        self.scripts.retain(|script| script.name != name);
+        self.stylesheets.len() + self.scripts.len() != before
+    }
+
+    pub fn stylesheets(&self) -> &[UserStyleSheet] {
+        &self.stylesheets
+    }
+
+    pub fn scripts(&self) -> &[UserScript] {
+        &self.scripts
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stylesheets.is_empty() && self.scripts.is_empty()
+    }
+
+    /// The stylesheets applying to a page, parsed and at the user origin
+    pub fn stylesheet(&self, url: &Url) -> Stylesheet {
+        let mut stylesheet = Stylesheet::new(Vec::new());
+        for sheet in self.stylesheets.iter().filter(|sheet| sheet.applies_to(url)) {
+            stylesheet.append(CssParser::parse(&sheet.css));
+        }
+        stylesheet.with_origin(Origin::User)
+    }
+
+    /// Source of the scripts to run in a page at a point of its load, in the order added
+    pub fn scripts_at(&self, url: &Url, run_at: RunAt) -> Vec<&str> {
+        self.scripts
+            .iter()
+            .filter(|script| script.run_at == run_at && script.applies_to(url))
+            .map(|script| script.source.as_str())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    fn matches(pattern: &str, page: &str) -> bool {
+        MatchPattern::parse(pattern).unwrap().matches(&url(page))
+    }
+
+    #[test]
+    fn test_match_patterns() {
+        assert!(matches("<all_urls>", "https://example.com/"));
+        assert!(matches("<all_urls>", "file:///home/a.html"));
+        assert!(!matches("<all_urls>", "about:blank"));
+
+        assert!(matches("*://*.example.com/*", "http://example.com/a"));
+        assert!(matches("*://*.example.com/*", "https://www.example.com/a?b=c"));
+        assert!(!matches("*://*.example.com/*", "https://badexample.com/"));
+        assert!(!matches("*://example.com/*", "file:///example.com/"));
+        assert!(matches("https://*/news/*", "https://any.org/news/today"));
+        assert!(!matches("https://*/news/*", "http://any.org/news/today"));
+        assert!(matches("https://example.com/search?q=*", "https://example.com/search?q=rust"));
+        assert!(!matches("https://example.com/", "https://example.com/page"));
+        assert!(matches("file:///home/*", "file:///home/a.html"));
+
+        let bad = ["example.com/*", "ftp://a.com/*", "https://ex*.com/*", "https://a.com", "file://host/*", "https:///a"];
+        for bad in bad {
+            assert!(MatchPattern::parse(bad).is_none(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_headers() {
+        let script = UserScript::parse(
+            "file",
+            "// ==UserScript==\n\
+             // @name        Dark news\n\
+             // @match       https://news.example/*\n\
+             // @exclude-match https://news.example/admin/*\n\
+             // @run-at      document-start\n\
+             // ==/UserScript==\n\
+             document.title = 'x';",
+        );
+        assert_eq!(script.name, "Dark news");
+        assert_eq!(script.run_at, RunAt::DocumentStart);
+        assert!(script.applies_to(&url("https://news.example/today")));
+        assert!(!script.applies_to(&url("https://news.example/admin/users")));
+        assert!(!script.applies_to(&url("https://other.example/")));
+
+        // No header, or no @match, means every page; unreadable patterns mean none
+        assert!(UserScript::parse("plain", "1 + 1").applies_to(&url("https://a.example/")));
+        let sheet = UserStyleSheet::parse("s", "/* ==UserStyle==\n@name Big\n@match nonsense\n==/UserStyle== */\np {}");
+        assert_eq!(sheet.name, "Big");
+        assert!(!sheet.applies_to(&url("https://a.example/")));
+    }
+
+    #[test]
+    fn test_stylesheets_and_scripts_for_a_page() {
+        let mut content = UserContent::new();
+        content.add_stylesheet(UserStyleSheet::new("all", "body { margin: 0px; }"));
+        let only_b = MatchPattern::parse("https://b.example/*").unwrap();
+        content.add_stylesheet(UserStyleSheet::new("b", "p { color: red; }").with_match(only_b.clone()));
+        content.add_script(UserScript::new("start", "1", RunAt::DocumentStart));
+        content.add_script(UserScript::new("end", "2", RunAt::DocumentEnd).with_match(only_b));
+        content.add_script(UserScript::new("end-all", "3", RunAt::DocumentEnd));
+
+        let a = url("https://a.example/");
+        let stylesheet = content.stylesheet(&a);
+        assert_eq!(stylesheet.rules.len(), 1);
+        assert!(stylesheet.rules.iter().all(|rule| rule.origin == Origin::User));
+        assert_eq!(content.stylesheet(&url("https://b.example/x")).rules.len(), 2);
+        assert_eq!(content.scripts_at(&a, RunAt::DocumentStart), ["1"]);
+        assert_eq!(content.scripts_at(&a, RunAt::DocumentEnd), ["3"]);
+        assert_eq!(content.scripts_at(&url("https://b.example/"), RunAt::DocumentEnd), ["2", "3"]);
+
+        assert!(content.remove("b"));
+        assert!(!content.remove("b"));
+        assert_eq!(content.stylesheets().len(), 1);
+    }
+
+    #[test]
+    fn test_load_directory() {
+        let dir = std::env::temp_dir().join(format!("user-content-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("b.user.js"), "// ==UserScript==\n// @run-at document-start\n// ==/UserScript==\n1").unwrap();
+        std::fs::write(dir.join("a.user.css"), "p { color: red; }").unwrap();
+        std::fs::write(dir.join("notes.txt"), "ignored").unwrap();
+        let content = UserContent::load(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(content.stylesheets()[0].name, "a");
+        assert_eq!(content.scripts()[0].name, "b");
+        assert_eq!(content.scripts()[0].run_at, RunAt::DocumentStart);
+        assert_eq!(content.scripts().len() + content.stylesheets().len(), 2);
+    }
+}
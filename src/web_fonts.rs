@@ -0,0 +1,393 @@
+// Web fonts - loading `@font-face` rules and their `font-display` timing
+//
+// Each `@font-face` rule is a face loaded from the first of its `url()`
+// sources that can be fetched. While a face loads, text in its family is
+// hidden for the block period of its `font-display`, then drawn in a
+// fallback font. A face arriving within its swap period replaces the
+// fallback; one arriving later is left unused so the page does not jump.
+// `update` reports when what text is drawn with has changed, so the page
+// can be laid out again.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use cssparser::{Parser, ParserInput, Token};
+use url::Url;
+
+use crate::css::{FontFaceRule, Stylesheet};
+
+/// Block period of `font-display: auto` and `block`
+const LONG_BLOCK_PERIOD: Duration = Duration::from_secs(3);
+/// Block period of `swap`, `fallback` and `optional`
+const SHORT_BLOCK_PERIOD: Duration = Duration::from_millis(100);
+/// Swap period of `fallback`
+const FALLBACK_SWAP_PERIOD: Duration = Duration::from_secs(3);
+
+/// How text waits for a face that is loading
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FontDisplay {
+    #[default]
+    Auto,
+    /// Hide text for a while, then swap the face in whenever it arrives
+    Block,
+    /// Draw a fallback at once, then swap the face in whenever it arrives
+    Swap,
+    /// Hide text briefly and swap the face in only if it arrives soon
+    Fallback,
+    /// Hide text briefly and use the face only if it is there by then
+    Optional,
+}
+
+impl FontDisplay {
+    /// Parse a `font-display` value
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "auto" => Some(FontDisplay::Auto),
+            "block" => Some(FontDisplay::Block),
+            "swap" => Some(FontDisplay::Swap),
+            "fallback" => Some(FontDisplay::Fallback),
+            "optional" => Some(FontDisplay::Optional),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FontDisplay::Auto => "auto",
+            FontDisplay::Block => "block",
+            FontDisplay::Swap => "swap",
+            FontDisplay::Fallback => "fallback",
+            FontDisplay::Optional => "optional",
+        }
+    }
+
+    /// How long text stays hidden while the face loads
+    pub fn block_period(&self) -> Duration {
+        match self {
+            FontDisplay::Auto | FontDisplay::Block => LONG_BLOCK_PERIOD,
+            FontDisplay::Swap | FontDisplay::Fallback | FontDisplay::Optional => SHORT_BLOCK_PERIOD,
+        }
+    }
+
+    /// How long after the block period the face may still replace the fallback; `None` is forever
+    pub fn swap_period(&self) -> Option<Duration> {
+        match self {
+            FontDisplay::Auto | FontDisplay::Block | FontDisplay::Swap => None,
+            FontDisplay::Fallback => Some(FALLBACK_SWAP_PERIOD),
+            FontDisplay::Optional => Some(Duration::ZERO),
+        }
+    }
+}
+
+/// Load state of a face, as `FontFace.status` reports it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontFaceStatus {
+    Unloaded,
+    Loading,
+    Loaded,
+    Error,
+}
+
+impl FontFaceStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FontFaceStatus::Unloaded => "unloaded",
+            FontFaceStatus::Loading => "loading",
+            FontFaceStatus::Loaded => "loaded",
+            FontFaceStatus::Error => "error",
+        }
+    }
+}
+
+/// What text in a face's family is drawn with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontRendering {
+    /// The face is loading and text waits for it invisibly
+    Hidden,
+    /// Text is drawn in a fallback font
+    Fallback,
+    /// Text is drawn in the face
+    WebFont,
+}
+
+/// A face declared by an `@font-face` rule
+#[derive(Debug, Clone)]
+pub struct FontFace {
+    pub family: String,
+    /// `url()` sources, tried in order
+    pub sources: Vec<Url>,
+    pub display: FontDisplay,
+    status: FontFaceStatus,
+    /// When loading started and when it finished
+    started: Option<Instant>,
+    finished: Option<Instant>,
+    /// Contents of the font file, once loaded
+    data: Option<Arc<Vec<u8>>>,
+}
+
+impl FontFace {
+    /// Read a face from an `@font-face` rule, resolving its sources against `base`
+    ///
+    /// Returns `None` for rules without a family.
+    pub fn from_rule(rule: &FontFaceRule, base: &Url) -> Option<Self> {
+        let family = unquote(rule.descriptor("font-family")?);
+        if family.is_empty() {
+            return None;
+        }
+        Some(Self {
+            family,
+            sources: rule.descriptor("src").map(|src| parse_sources(src, base)).unwrap_or_default(),
+            display: rule.descriptor("font-display").and_then(FontDisplay::from_str).unwrap_or_default(),
+            status: FontFaceStatus::Unloaded,
+            started: None,
+            finished: None,
+            data: None,
+        })
+    }
+
+    pub fn status(&self) -> FontFaceStatus {
+        self.status
+    }
+
+    /// Contents of the font file, once loaded
+    pub fn data(&self) -> Option<&Arc<Vec<u8>>> {
+        self.data.as_ref()
+    }
+
+    /// What text in the face's family is drawn with at `now`
+    pub fn rendering(&self, now: Instant) -> FontRendering {
+        let Some(started) = self.started else {
+            return FontRendering::Fallback;
+        };
+        let block_end = started + self.display.block_period();
+        match self.status {
+            FontFaceStatus::Loading if now < block_end => FontRendering::Hidden,
+            FontFaceStatus::Loaded => {
+                let finished = self.finished.unwrap_or(started);
+                match self.display.swap_period() {
+                    Some(swap) if finished > block_end + swap => FontRendering::Fallback,
+                    _ => FontRendering::WebFont,
+                }
+            }
+            FontFaceStatus::Unloaded | FontFaceStatus::Loading | FontFaceStatus::Error => FontRendering::Fallback,
+        }
+    }
+
+    /// When the face's rendering changes next without it finishing loading
+    fn next_change(&self, now: Instant) -> Option<Instant> {
+        let started = self.started.filter(|_| self.status == FontFaceStatus::Loading)?;
+        Some(started + self.display.block_period()).filter(|&at| at > now)
+    }
+}
+
+/// The page's web fonts, as `document.fonts` sees them
+#[derive(Debug, Clone, Default)]
+pub struct FontFaceSet {
+    faces: Vec<FontFace>,
+    /// Rendering of each face when `update` last ran
+    rendering: Vec<FontRendering>,
+}
+
+impl FontFaceSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Faces of a stylesheet's `@font-face` rules
+    pub fn from_stylesheet(stylesheet: &Stylesheet, base: &Url) -> Self {
+        let faces: Vec<FontFace> =
+            stylesheet.font_faces.iter().filter_map(|rule| FontFace::from_rule(rule, base)).collect();
+        let rendering = vec![FontRendering::Fallback; faces.len()];
+        Self { faces, rendering }
+    }
+
+    pub fn faces(&self) -> &[FontFace] {
+        &self.faces
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.faces.is_empty()
+    }
+
+    /// Start loading every face not loaded yet, returning the indices of those now loading
+    ///
+    /// Faces without a source fail at once.
+    pub fn start_loading(&mut self, now: Instant) -> Vec<usize> {
+        let mut loading = Vec::new();
+        for (index, face) in self.faces.iter_mut().enumerate() {
+            if face.status != FontFaceStatus::Unloaded {
+                continue;
+            }
+            face.started = Some(now);
+            if face.sources.is_empty() {
+                face.status = FontFaceStatus::Error;
+                face.finished = Some(now);
+            } else {
+                face.status = FontFaceStatus::Loading;
+                loading.push(index);
+            }
+        }
+        self.update(now);
+        loading
+    }
+
+    /// Indices of the faces still loading
+    pub fn loading(&self) -> Vec<usize> {
+        (0..self.faces.len()).filter(|&index| self.faces[index].status == FontFaceStatus::Loading).collect()
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.faces.iter().any(|face| face.status == FontFaceStatus::Loading)
+    }
+
+    /// Finish loading a face with the fetched file, or `None` if no source could be fetched
+    ///
+    /// Files that are not fonts fail the face.
+    pub fn finish_load(&mut self, index: usize, data: Option<Vec<u8>>, now: Instant) {
+        let Some(face) = self.faces.get_mut(index).filter(|face| face.status == FontFaceStatus::Loading) else {
+            return;
+        };
+        let data = data.filter(|data| fontdue::Font::from_bytes(data.as_slice(), Default::default()).is_ok());
+        face.status = if data.is_some() { FontFaceStatus::Loaded } else { FontFaceStatus::Error };
+        face.finished = Some(now);
+        face.data = data.map(Arc::new);
+    }
+
+    /// Has what any family is drawn with changed since the last call
+    ///
+    /// Changes come from block periods ending and faces arriving; the page
+    /// should be laid out again when this returns true.
+    pub fn update(&mut self, now: Instant) -> bool {
+        let rendering: Vec<FontRendering> = self.faces.iter().map(|face| face.rendering(now)).collect();
+        let changed = rendering != self.rendering;
+        self.rendering = rendering;
+        changed
+    }
+
+    /// When a block period ends next, for the host to call `update` then
+    pub fn next_change(&self, now: Instant) -> Option<Instant> {
+        self.faces.iter().filter_map(|face| face.next_change(now)).min()
+    }
+
+    /// What text in a family is drawn with, or `None` if no face declares it
+    ///
+    /// A family with several faces uses any that is ready.
+    pub fn rendering(&self, family: &str, now: Instant) -> Option<FontRendering> {
+        let family = unquote(family);
+        self.faces
+            .iter()
+            .filter(|face| face.family.eq_ignore_ascii_case(&family))
+            .map(|face| face.rendering(now))
+            .max_by_key(|rendering| match rendering {
+                FontRendering::Hidden => 1,
+                FontRendering::Fallback => 0,
+                FontRendering::WebFont => 2,
+            })
+    }
+}
+
+/// A family name without its quotes
+fn unquote(family: &str) -> String {
+    family.trim().trim_matches(|c| c == '"' || c == '\'').trim().to_string()
+}
+
+/// The `url()` sources of a `src` descriptor; `local()` sources are skipped
+fn parse_sources(src: &str, base: &Url) -> Vec<Url> {
+    let mut input = ParserInput::new(src);
+    let mut parser = Parser::new(&mut input);
+    let mut sources = Vec::new();
+    while let Ok(token) = parser.next() {
+        let url = match token.clone() {
+            Token::UnquotedUrl(url) => Some(url.to_string()),
+            Token::Function(name) if name.eq_ignore_ascii_case("url") => parser
+                .parse_nested_block(|parser| {
+                    Ok::<String, cssparser::ParseError<()>>(parser.expect_string()?.to_string())
+                })
+                .ok(),
+            _ => None,
+        };
+        sources.extend(url.and_then(|url| base.join(&url).ok()));
+    }
+    sources
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::CssParser;
+    use crate::renderer::font_manager::FontManager;
+
+    fn font_set(display: &str) -> FontFaceSet {
+        let css = format!(
+            "@font-face {{ font-family: 'Web Sans'; src: local(Web), url(\"fonts/web.ttf\") format('truetype'), \
+             url(https://cdn.example/web.otf); font-display: {}; }} @font-face {{ src: url(x.ttf); }}",
+            display
+        );
+        FontFaceSet::from_stylesheet(&CssParser::parse(&css), &Url::parse("https://example.com/page/").unwrap())
+    }
+
+    fn font_file() -> Vec<u8> {
+        FontManager::new().unwrap().get_font_data("sans-serif").bytes.to_vec()
+    }
+
+    #[test]
+    fn test_faces_from_stylesheet() {
+        let fonts = font_set("fallback");
+        assert_eq!(fonts.faces().len(), 1);
+        let face = &fonts.faces()[0];
+        assert_eq!(face.family, "Web Sans");
+        assert_eq!(face.display, FontDisplay::Fallback);
+        let sources: Vec<&str> = face.sources.iter().map(Url::as_str).collect();
+        assert_eq!(sources, ["https://example.com/page/fonts/web.ttf", "https://cdn.example/web.otf"]);
+        assert_eq!(fonts.rendering("\"web sans\"", Instant::now()), Some(FontRendering::Fallback));
+        assert_eq!(fonts.rendering("Other", Instant::now()), None);
+    }
+
+    #[test]
+    fn test_block_period_then_swap() {
+        let mut fonts = font_set("block");
+        let start = Instant::now();
+        assert_eq!(fonts.start_loading(start), vec![0]);
+        assert_eq!(fonts.rendering("Web Sans", start), Some(FontRendering::Hidden));
+        assert_eq!(fonts.next_change(start), Some(start + LONG_BLOCK_PERIOD));
+
+        // Text shows in the fallback once the block period is over
+        let late = start + Duration::from_secs(4);
+        assert!(fonts.update(late));
+        assert!(!fonts.update(late));
+        assert_eq!(fonts.rendering("Web Sans", late), Some(FontRendering::Fallback));
+        assert_eq!(fonts.next_change(late), None);
+
+        // A block face swaps in however late it arrives
+        fonts.finish_load(0, Some(font_file()), start + Duration::from_secs(30));
+        assert!(fonts.update(start + Duration::from_secs(30)));
+        assert_eq!(fonts.faces()[0].status(), FontFaceStatus::Loaded);
+        assert_eq!(fonts.rendering("Web Sans", late), Some(FontRendering::WebFont));
+    }
+
+    #[test]
+    fn test_fallback_and_optional_give_up() {
+        let start = Instant::now();
+        for (display, arrival, expected) in [
+            ("swap", 10_000, FontRendering::WebFont),
+            ("fallback", 2_000, FontRendering::WebFont),
+            ("fallback", 4_000, FontRendering::Fallback),
+            ("optional", 50, FontRendering::WebFont),
+            ("optional", 500, FontRendering::Fallback),
+        ] {
+            let mut fonts = font_set(display);
+            fonts.start_loading(start);
+            let arrived = start + Duration::from_millis(arrival);
+            fonts.finish_load(0, Some(font_file()), arrived);
+            assert_eq!(fonts.rendering("Web Sans", arrived), Some(expected), "{} at {}ms", display, arrival);
+        }
+
+        // Files that are not fonts fail the face and text keeps the fallback
+        let mut fonts = font_set("swap");
+        fonts.start_loading(start);
+        fonts.finish_load(0, Some(b"<html>".to_vec()), start);
+        assert_eq!(fonts.faces()[0].status(), FontFaceStatus::Error);
+        assert!(!fonts.is_loading());
+        assert_eq!(fonts.rendering("Web Sans", start), Some(FontRendering::Fallback));
+    }
+}
@@ -0,0 +1,372 @@
+// Core Web Vitals - paint timing, largest contentful paint and layout shift
+//
+// Contentful paints are the text and images of a page's display list. The
+// largest one visible in the viewport is the largest contentful paint, and
+// contentful paints that move when the page is laid out again are layout
+// shifts, scored by how much of the viewport moved and how far. The page's
+// metrics are graded against the Core Web Vitals thresholds and weighted
+// into a score the way Lighthouse does.
+
+use crate::display::{DisplayCommand, DisplayList};
+use crate::layout::Rect;
+use crate::performance::{DOMHighResTimeStamp, Performance, PerformanceEntry};
+
+/// Layout shifts this soon after user input are expected and don't count (ms)
+pub const RECENT_INPUT: DOMHighResTimeStamp = 500.0;
+/// A session of layout shifts ends after a gap this long (ms)
+const SESSION_GAP: DOMHighResTimeStamp = 1000.0;
+/// A session of layout shifts lasts at most this long (ms)
+const SESSION_LENGTH: DOMHighResTimeStamp = 5000.0;
+/// Characters of text kept to name a largest contentful paint
+const LABEL_LENGTH: usize = 40;
+
+/// Text or an image painted on the page
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentfulPaint {
+    /// The text, or the image's URL
+    pub content: String,
+    pub image: bool,
+    pub rect: Rect,
+}
+
+impl ContentfulPaint {
+    /// Short description, e.g. `"Welcome to…"` or `img https://…`
+    pub fn label(&self) -> String {
+        if self.image {
+            return format!("img {}", self.content);
+        }
+        let text = self.content.trim();
+        if text.chars().count() > LABEL_LENGTH {
+            format!("\"{}\u{2026}\"", text.chars().take(LABEL_LENGTH).collect::<String>())
+        } else {
+            format!("\"{}\"", text)
+        }
+    }
+}
+
+/// The text and images of a display list, in paint order
+pub fn contentful_paints(list: &DisplayList) -> Vec<ContentfulPaint> {
+    list.iter()
+        .filter_map(|command| match command {
+            DisplayCommand::Text { text, rect, .. } => {
+                Some(ContentfulPaint { content: text.clone(), image: false, rect: *rect })
+            }
+            DisplayCommand::Image { url, rect } => {
+                Some(ContentfulPaint { content: url.to_string(), image: true, rect: *rect })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// The contentful paint with the largest area visible in `viewport`, and that area
+pub fn largest_contentful_paint(paints: &[ContentfulPaint], viewport: Rect) -> Option<(&ContentfulPaint, f32)> {
+    paints
+        .iter()
+        .map(|paint| (paint, visible_area(paint.rect, viewport)))
+        .filter(|(_, size)| *size > 0.0)
+        .fold(None, |largest, (paint, size)| match largest {
+            // The first of equally large paints stays the candidate
+            Some((_, largest_size)) if largest_size >= size => largest,
+            _ => Some((paint, size)),
+        })
+}
+
+/// Layout shift score of the contentful paints moving from `old` to `new`
+///
+/// Paints are matched by content, in order. The score is the fraction of
+/// the viewport covered by the moved paints before and after, times the
+/// farthest any moved relative to the viewport's larger side.
+pub fn layout_shift_score(old: &[ContentfulPaint], new: &[ContentfulPaint], viewport: Rect) -> f64 {
+    let mut impact_area = 0.0;
+    let mut distance: f32 = 0.0;
+    let mut matched = vec![false; new.len()];
+    for before in old {
+        let Some(index) = (0..new.len()).find(|&i| !matched[i] && new[i].content == before.content) else {
+            continue;
+        };
+        matched[index] = true;
+        let after = new[index].rect;
+        let (dx, dy) = ((after.x - before.rect.x).abs(), (after.y - before.rect.y).abs());
+        if dx < 0.5 && dy < 0.5 {
+            continue;
+        }
+        let overlap = intersection(before.rect, after).map_or(0.0, |both| visible_area(both, viewport));
+        impact_area += visible_area(before.rect, viewport) + visible_area(after, viewport) - overlap;
+        if visible_area(before.rect, viewport) > 0.0 || visible_area(after, viewport) > 0.0 {
+            distance = distance.max(dx.max(dy));
+        }
+    }
+    let viewport_area = viewport.width * viewport.height;
+    let largest_side = viewport.width.max(viewport.height);
+    if viewport_area <= 0.0 || impact_area <= 0.0 {
+        return 0.0;
+    }
+    let impact = (impact_area / viewport_area).min(1.0);
+    let distance = (distance / largest_side).min(1.0);
+    (impact * distance) as f64
+}
+
+/// Cumulative layout shift of `(start time, score)` shifts
+///
+/// Shifts are grouped into sessions that end after a second without one,
+/// or five seconds in; the worst session is the page's score.
+pub fn cumulative_layout_shift(shifts: &[(DOMHighResTimeStamp, f64)]) -> f64 {
+    let mut worst: f64 = 0.0;
+    let mut session: Option<(DOMHighResTimeStamp, DOMHighResTimeStamp, f64)> = None;
+    for &(time, score) in shifts {
+        session = match session {
+            Some((start, last, total)) if time - last < SESSION_GAP && time - start < SESSION_LENGTH => {
+                Some((start, time, total + score))
+            }
+            _ => Some((time, time, score)),
+        };
+        worst = worst.max(session.map_or(0.0, |(.., total)| total));
+    }
+    worst
+}
+
+fn intersection(a: Rect, b: Rect) -> Option<Rect> {
+    let x = a.x.max(b.x);
+    let y = a.y.max(b.y);
+    let right = (a.x + a.width).min(b.x + b.width);
+    let bottom = (a.y + a.height).min(b.y + b.height);
+    (right > x && bottom > y).then_some(Rect { x, y, width: right - x, height: bottom - y })
+}
+
+fn visible_area(rect: Rect, viewport: Rect) -> f32 {
+    intersection(rect, viewport).map_or(0.0, |visible| visible.width * visible.height)
+}
+
+/// How a metric compares with its thresholds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rating {
+    Good,
+    NeedsImprovement,
+    Poor,
+}
+
+impl Rating {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Rating::Good => "Good",
+            Rating::NeedsImprovement => "Needs improvement",
+            Rating::Poor => "Poor",
+        }
+    }
+}
+
+/// A Core Web Vitals metric
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    FirstContentfulPaint,
+    LargestContentfulPaint,
+    CumulativeLayoutShift,
+}
+
+impl Metric {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Metric::FirstContentfulPaint => "First Contentful Paint",
+            Metric::LargestContentfulPaint => "Largest Contentful Paint",
+            Metric::CumulativeLayoutShift => "Cumulative Layout Shift",
+        }
+    }
+
+    /// Values up to the first are good; from the second they are poor
+    pub fn thresholds(&self) -> (f64, f64) {
+        match self {
+            Metric::FirstContentfulPaint => (1800.0, 3000.0),
+            Metric::LargestContentfulPaint => (2500.0, 4000.0),
+            Metric::CumulativeLayoutShift => (0.1, 0.25),
+        }
+    }
+
+    /// Weight in the performance score
+    fn weight(&self) -> f64 {
+        match self {
+            Metric::FirstContentfulPaint => 10.0,
+            Metric::LargestContentfulPaint | Metric::CumulativeLayoutShift => 25.0,
+        }
+    }
+
+    pub fn rate(&self, value: f64) -> Rating {
+        let (good, poor) = self.thresholds();
+        if value <= good {
+            Rating::Good
+        } else if value < poor {
+            Rating::NeedsImprovement
+        } else {
+            Rating::Poor
+        }
+    }
+
+    /// Score from 0 to 1: full up to the good threshold, half at the poor
+    /// one and nothing at twice that
+    pub fn score(&self, value: f64) -> f64 {
+        let (good, poor) = self.thresholds();
+        if value <= good {
+            1.0
+        } else if value <= poor {
+            1.0 - 0.5 * (value - good) / (poor - good)
+        } else {
+            0.5 * (1.0 - ((value - poor) / poor).min(1.0))
+        }
+    }
+
+    /// The value as shown, in seconds for paints
+    pub fn format(&self, value: f64) -> String {
+        match self {
+            Metric::CumulativeLayoutShift => format!("{:.3}", value),
+            _ => format!("{:.2} s", value / 1000.0),
+        }
+    }
+}
+
+/// A page's Core Web Vitals, as measured so far
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WebVitals {
+    /// Milliseconds from navigation to the first text or image
+    pub first_contentful_paint: Option<DOMHighResTimeStamp>,
+    /// Milliseconds from navigation to the largest contentful paint
+    pub largest_contentful_paint: Option<DOMHighResTimeStamp>,
+    /// What the largest contentful paint painted
+    pub largest_contentful_element: Option<String>,
+    pub cumulative_layout_shift: f64,
+    /// Layout shifts counted in the cumulative score's sessions or not
+    pub layout_shifts: usize,
+}
+
+impl WebVitals {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The metrics of a page's performance timeline
+    pub fn from_performance(performance: &Performance) -> Self {
+        let first_contentful_paint = performance
+            .get_entries_by_name("first-contentful-paint")
+            .first()
+            .map(|entry| entry.start_time());
+        let largest = performance.get_entries_by_type("largest-contentful-paint").pop();
+        let element = match &largest {
+            Some(PerformanceEntry::LargestContentfulPaint { element, .. }) => Some(element.clone()),
+            _ => None,
+        };
+        let shifts = performance.get_entries_by_type("layout-shift");
+        let counted: Vec<_> = shifts
+            .iter()
+            .filter_map(|entry| match entry {
+                PerformanceEntry::LayoutShift { start_time, value, had_recent_input: false } => {
+                    Some((*start_time, *value))
+                }
+                _ => None,
+            })
+            .collect();
+        Self {
+            first_contentful_paint,
+            largest_contentful_paint: largest.map(|entry| entry.start_time()),
+            largest_contentful_element: element,
+            cumulative_layout_shift: cumulative_layout_shift(&counted),
+            layout_shifts: shifts.len(),
+        }
+    }
+
+    /// The metrics measured so far with their values
+    ///
+    /// Layout shift is measured from the first contentful paint on.
+    pub fn metrics(&self) -> Vec<(Metric, f64)> {
+        let mut metrics = Vec::new();
+        if let Some(fcp) = self.first_contentful_paint {
+            metrics.push((Metric::FirstContentfulPaint, fcp));
+        }
+        if let Some(lcp) = self.largest_contentful_paint {
+            metrics.push((Metric::LargestContentfulPaint, lcp));
+        }
+        if self.first_contentful_paint.is_some() {
+            metrics.push((Metric::CumulativeLayoutShift, self.cumulative_layout_shift));
+        }
+        metrics
+    }
+
+    /// Performance score out of 100 of the metrics measured, by their weights
+    pub fn score(&self) -> Option<u32> {
+        let metrics = self.metrics();
+        let total: f64 = metrics.iter().map(|(metric, _)| metric.weight()).sum();
+        if total == 0.0 {
+            return None;
+        }
+        let weighted: f64 = metrics.iter().map(|(metric, value)| metric.weight() * metric.score(*value)).sum();
+        Some((weighted / total * 100.0).round() as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paint(content: &str, x: f32, y: f32, width: f32, height: f32) -> ContentfulPaint {
+        ContentfulPaint { content: content.to_string(), image: false, rect: Rect { x, y, width, height } }
+    }
+
+    #[test]
+    fn test_largest_contentful_paint_in_viewport() {
+        let viewport = Rect { x: 0.0, y: 0.0, width: 800.0, height: 600.0 };
+        let paints = [
+            paint("Title", 0.0, 0.0, 400.0, 40.0),
+            paint("Intro", 0.0, 50.0, 800.0, 100.0),
+            // Mostly below the fold
+            paint("Footer", 0.0, 580.0, 800.0, 400.0),
+        ];
+        let (largest, size) = largest_contentful_paint(&paints, viewport).unwrap();
+        assert_eq!((largest.content.as_str(), size), ("Intro", 80_000.0));
+        assert_eq!(largest.label(), "\"Intro\"");
+        assert!(largest_contentful_paint(&paints[..1], Rect { y: 1000.0, ..viewport }).is_none());
+    }
+
+    #[test]
+    fn test_layout_shift_score() {
+        let viewport = Rect { x: 0.0, y: 0.0, width: 1000.0, height: 500.0 };
+        let old = [paint("Ad", 0.0, 0.0, 1000.0, 0.0), paint("Story", 0.0, 0.0, 500.0, 100.0)];
+        let new = [paint("Ad", 0.0, 0.0, 1000.0, 100.0), paint("Story", 0.0, 100.0, 500.0, 100.0)];
+        // Where the story was and is covers a fifth of the viewport, and it
+        // moved a tenth of the viewport's width
+        let score = layout_shift_score(&old, &new, viewport);
+        assert!((score - 0.02).abs() < 1e-6, "{}", score);
+        assert_eq!(layout_shift_score(&old, &old, viewport), 0.0);
+    }
+
+    #[test]
+    fn test_cumulative_layout_shift_sessions() {
+        let shifts = [(100.0, 0.05), (600.0, 0.05), (3000.0, 0.02), (3500.0, 0.2), (9000.0, 0.01)];
+        assert!((cumulative_layout_shift(&shifts) - 0.22).abs() < 1e-9);
+        assert_eq!(cumulative_layout_shift(&[]), 0.0);
+    }
+
+    #[test]
+    fn test_vitals_from_timeline() {
+        let mut performance = Performance::new();
+        assert_eq!(WebVitals::from_performance(&performance).score(), None);
+        performance.add_paint("first-paint");
+        performance.add_paint("first-contentful-paint");
+        performance.add_largest_contentful_paint(5000.0, "\"Hello\"".to_string(), String::new());
+        performance.add_layout_shift(0.3, true);
+        let vitals = WebVitals::from_performance(&performance);
+        assert_eq!(vitals.largest_contentful_element.as_deref(), Some("\"Hello\""));
+        assert_eq!(vitals.cumulative_layout_shift, 0.0);
+        assert_eq!(vitals.layout_shifts, 1);
+        // Painted at once, with no shifts counted
+        assert_eq!(vitals.score(), Some(100));
+
+        let slow = WebVitals {
+            largest_contentful_paint: Some(4000.0),
+            cumulative_layout_shift: 0.5,
+            ..vitals
+        };
+        let ratings: Vec<_> = slow.metrics().iter().map(|(metric, value)| metric.rate(*value)).collect();
+        assert_eq!(ratings, [Rating::Good, Rating::Poor, Rating::Poor]);
+        // 10 * 1 + 25 * 0.5 + 25 * 0 out of 60
+        assert_eq!(slow.score(), Some(38));
+        assert_eq!(Metric::LargestContentfulPaint.format(4000.0), "4.00 s");
+    }
+}
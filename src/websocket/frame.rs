@@ -0,0 +1,394 @@
+// WebSocket frames - encoding, decoding and message reassembly
+//
+// Frames from the server are decoded out of the read buffer as they arrive:
+// the header is validated (reserved bits, opcode, minimal lengths, control
+// frame limits) and data frames are collected until the final fragment
+// completes a message. Control frames may arrive between fragments.
+
+use rand::RngCore;
+
+use super::{CloseCode, WebSocketError, WebSocketMessage};
+
+/// Largest message (and frame) accepted from the server
+pub const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Largest payload of a control frame
+const MAX_CONTROL_PAYLOAD: usize = 125;
+
+/// WebSocket frame opcode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Opcode {
+    Continuation = 0x0,
+    Text = 0x1,
+    Binary = 0x2,
+    Close = 0x8,
+    Ping = 0x9,
+    Pong = 0xA,
+}
+
+impl Opcode {
+    /// Create from u8
+    pub(super) fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0x0 => Some(Opcode::Continuation),
+            0x1 => Some(Opcode::Text),
+            0x2 => Some(Opcode::Binary),
+            0x8 => Some(Opcode::Close),
+            0x9 => Some(Opcode::Ping),
+            0xA => Some(Opcode::Pong),
+            _ => None,
+        }
+    }
+
+    /// Close, Ping and Pong
+    pub(super) fn is_control(self) -> bool {
+        (self as u8) & 0x8 != 0
+    }
+}
+
+/// WebSocket frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Frame {
+    /// Final fragment flag
+    pub(super) fin: bool,
+    /// Opcode
+    pub(super) opcode: Opcode,
+    /// Payload data
+    pub(super) payload: Vec<u8>,
+}
+
+impl Frame {
+    /// Create a new frame
+    pub(super) fn new(opcode: Opcode, payload: Vec<u8>) -> Self {
+        Self {
+            fin: true,
+            opcode,
+            payload,
+        }
+    }
+
+    /// Encode frame to bytes
+    pub(super) fn encode(&self, mask: bool) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        // First byte: FIN + RSV + opcode
+        let mut first_byte = self.opcode as u8;
+        if self.fin {
+            first_byte |= 0x80;
+        }
+        bytes.push(first_byte);
+
+        // Second byte: MASK + payload length
+        let payload_len = self.payload.len();
+        let mut second_byte = 0u8;
+        if mask {
+            second_byte |= 0x80;
+        }
+
+        if payload_len < 126 {
+            second_byte |= payload_len as u8;
+            bytes.push(second_byte);
+        } else if payload_len < 65536 {
+            second_byte |= 126;
+            bytes.push(second_byte);
+            bytes.extend_from_slice(&(payload_len as u16).to_be_bytes());
+        } else {
+            second_byte |= 127;
+            bytes.push(second_byte);
+            bytes.extend_from_slice(&(payload_len as u64).to_be_bytes());
+        }
+
+        // Client frames are masked with a fresh random key
+        if mask {
+            let mut mask_key = [0u8; 4];
+            rand::thread_rng().fill_bytes(&mut mask_key);
+            bytes.extend_from_slice(&mask_key);
+
+            // Masked payload
+            for (i, byte) in self.payload.iter().enumerate() {
+                bytes.push(byte ^ mask_key[i % 4]);
+            }
+        } else {
+            bytes.extend_from_slice(&self.payload);
+        }
+
+        bytes
+    }
+
+    /// Decode the frame at the start of `buf`
+    ///
+    /// Returns the frame and the number of bytes it took, or `None` while
+    /// the frame is incomplete. `masked` is whether the peer must mask its
+    /// frames: clients do, servers must not.
+    pub(super) fn decode(buf: &[u8], masked: bool) -> Result<Option<(Frame, usize)>, WebSocketError> {
+        if buf.len() < 2 {
+            return Ok(None);
+        }
+
+        let fin = buf[0] & 0x80 != 0;
+        if buf[0] & 0x70 != 0 {
+            // No extension was negotiated, so no reserved bit may be set
+            return Err(WebSocketError::ProtocolError("reserved bits set".to_string()));
+        }
+        let opcode = Opcode::from_u8(buf[0] & 0x0F)
+            .ok_or_else(|| WebSocketError::ProtocolError(format!("unknown opcode {:#x}", buf[0] & 0x0F)))?;
+
+        if (buf[1] & 0x80 != 0) != masked {
+            let reason = if masked { "unmasked client frame" } else { "masked server frame" };
+            return Err(WebSocketError::ProtocolError(reason.to_string()));
+        }
+
+        let mut offset = 2;
+        let payload_len = match buf[1] & 0x7F {
+            126 => {
+                let Some(bytes) = buf.get(2..4) else {
+                    return Ok(None);
+                };
+                offset = 4;
+                let len = u16::from_be_bytes([bytes[0], bytes[1]]) as u64;
+                if len < 126 {
+                    return Err(WebSocketError::ProtocolError("non-minimal length".to_string()));
+                }
+                len
+            }
+            127 => {
+                let Some(bytes) = buf.get(2..10) else {
+                    return Ok(None);
+                };
+                offset = 10;
+                let len = u64::from_be_bytes(bytes.try_into().expect("8 bytes"));
+                if len >> 63 != 0 {
+                    return Err(WebSocketError::ProtocolError("length has the high bit set".to_string()));
+                }
+                if len < 65536 {
+                    return Err(WebSocketError::ProtocolError("non-minimal length".to_string()));
+                }
+                len
+            }
+            len => len as u64,
+        };
+
+        if opcode.is_control() {
+            if !fin {
+                return Err(WebSocketError::ProtocolError("fragmented control frame".to_string()));
+            }
+            if payload_len > MAX_CONTROL_PAYLOAD as u64 {
+                return Err(WebSocketError::ProtocolError("control frame too long".to_string()));
+            }
+        }
+        if payload_len > MAX_MESSAGE_SIZE as u64 {
+            return Err(WebSocketError::MessageTooBig);
+        }
+        let payload_len = payload_len as usize;
+
+        let mask_key = if masked {
+            let Some(key) = buf.get(offset..offset + 4) else {
+                return Ok(None);
+            };
+            offset += 4;
+            Some([key[0], key[1], key[2], key[3]])
+        } else {
+            None
+        };
+
+        let Some(data) = buf.get(offset..offset + payload_len) else {
+            return Ok(None);
+        };
+        let payload = match mask_key {
+            Some(key) => data.iter().enumerate().map(|(i, b)| b ^ key[i % 4]).collect(),
+            None => data.to_vec(),
+        };
+
+        Ok(Some((Frame { fin, opcode, payload }, offset + payload_len)))
+    }
+}
+
+/// Collects data frames into complete messages
+#[derive(Debug, Default)]
+pub(super) struct MessageAssembler {
+    /// Opcode of the message being collected (Text or Binary)
+    opcode: Option<Opcode>,
+    /// Payload received so far
+    buffer: Vec<u8>,
+}
+
+impl MessageAssembler {
+    /// Create an assembler with no message in progress
+    pub(super) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take a frame; returns the message it completes, if any
+    ///
+    /// Control frames are complete messages of their own and leave a
+    /// fragmented message in progress untouched.
+    pub(super) fn push(&mut self, frame: Frame) -> Result<Option<WebSocketMessage>, WebSocketError> {
+        match frame.opcode {
+            Opcode::Ping => return Ok(Some(WebSocketMessage::Ping(frame.payload))),
+            Opcode::Pong => return Ok(Some(WebSocketMessage::Pong(frame.payload))),
+            Opcode::Close => return parse_close(&frame.payload).map(Some),
+            Opcode::Text | Opcode::Binary => {
+                if self.opcode.is_some() {
+                    return Err(WebSocketError::ProtocolError("new message before the last one finished".to_string()));
+                }
+                self.opcode = Some(frame.opcode);
+            }
+            Opcode::Continuation => {
+                if self.opcode.is_none() {
+                    return Err(WebSocketError::ProtocolError("continuation without a message".to_string()));
+                }
+            }
+        }
+
+        if self.buffer.len() + frame.payload.len() > MAX_MESSAGE_SIZE {
+            self.reset();
+            return Err(WebSocketError::MessageTooBig);
+        }
+        self.buffer.extend_from_slice(&frame.payload);
+        if !frame.fin {
+            return Ok(None);
+        }
+
+        let payload = std::mem::take(&mut self.buffer);
+        match self.opcode.take() {
+            Some(Opcode::Text) => String::from_utf8(payload)
+                .map(|text| Some(WebSocketMessage::Text(text)))
+                .map_err(|_| WebSocketError::InvalidUtf8),
+            _ => Ok(Some(WebSocketMessage::Binary(payload))),
+        }
+    }
+
+    /// Drop any partly received message
+    pub(super) fn reset(&mut self) {
+        self.opcode = None;
+        self.buffer.clear();
+    }
+}
+
+/// Parse a Close frame's status code and reason
+fn parse_close(payload: &[u8]) -> Result<WebSocketMessage, WebSocketError> {
+    match payload.len() {
+        0 => return Ok(WebSocketMessage::Close(None, None)),
+        1 => return Err(WebSocketError::ProtocolError("close frame with a one-byte payload".to_string())),
+        _ => {}
+    }
+
+    let code = u16::from_be_bytes([payload[0], payload[1]]);
+    if !is_valid_close_code(code) {
+        return Err(WebSocketError::ProtocolError(format!("invalid close code {}", code)));
+    }
+    let reason = std::str::from_utf8(&payload[2..]).map_err(|_| WebSocketError::InvalidUtf8)?;
+    let reason = (!reason.is_empty()).then(|| reason.to_string());
+
+    Ok(WebSocketMessage::Close(CloseCode::from_u16(code), reason))
+}
+
+/// Codes an endpoint may send in a Close frame
+fn is_valid_close_code(code: u16) -> bool {
+    // 1005, 1006 and 1015 only ever describe a closure locally
+    matches!(code, 1000..=1003 | 1007..=1014 | 3000..=4999)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(buf: &[u8]) -> Result<Option<(Frame, usize)>, WebSocketError> {
+        Frame::decode(buf, false)
+    }
+
+    #[test]
+    fn test_decode_lengths_and_masking() {
+        let frame = Frame::new(Opcode::Binary, vec![7; 300]);
+        let encoded = frame.encode(false);
+        assert_eq!(encoded[1], 126);
+        assert_eq!(decode(&encoded).unwrap(), Some((frame.clone(), encoded.len())));
+        assert_eq!(decode(&encoded[..3]).unwrap(), None);
+        assert_eq!(decode(&encoded[..100]).unwrap(), None);
+
+        let large = Frame::new(Opcode::Text, vec![b'a'; 70_000]);
+        let encoded = large.encode(false);
+        assert_eq!(encoded[1], 127);
+        assert_eq!(decode(&encoded).unwrap().unwrap().1, 70_010);
+
+        // A client frame decodes back to its payload
+        let masked = frame.encode(true);
+        assert_eq!(Frame::decode(&masked, true).unwrap(), Some((frame, masked.len())));
+        assert!(matches!(decode(&masked), Err(WebSocketError::ProtocolError(_))));
+
+        // Trailing bytes belong to the next frame
+        let mut two = Frame::new(Opcode::Ping, vec![1]).encode(false);
+        two.extend_from_slice(&[0x8A, 0x00]);
+        assert_eq!(decode(&two).unwrap().unwrap().1, 3);
+    }
+
+    #[test]
+    fn test_decode_rejects_invalid_headers() {
+        let protocol_error = |buf: &[u8]| matches!(decode(buf), Err(WebSocketError::ProtocolError(_)));
+        // RSV1 set
+        assert!(protocol_error(&[0xC1, 0x00]));
+        // Reserved opcode
+        assert!(protocol_error(&[0x83, 0x00]));
+        // Fragmented ping
+        assert!(protocol_error(&[0x09, 0x00]));
+        // Control frame over 125 bytes
+        assert!(protocol_error(&[0x89, 126, 0x00, 0x7E]));
+        // Lengths that fit in a shorter form
+        assert!(protocol_error(&[0x82, 126, 0x00, 0x05]));
+        assert!(protocol_error(&[0x82, 127, 0, 0, 0, 0, 0, 0, 0x01, 0x00]));
+        assert!(protocol_error(&[0x82, 127, 0x80, 0, 0, 0, 0, 0, 0, 0]));
+        assert_eq!(decode(&[0x82, 127, 0, 0, 0, 0, 0x10, 0, 0, 0]), Err(WebSocketError::MessageTooBig));
+    }
+
+    #[test]
+    fn test_reassembles_fragmented_messages() {
+        let mut assembler = MessageAssembler::new();
+        let fragment = |opcode, fin, payload: &[u8]| Frame { fin, opcode, payload: payload.to_vec() };
+
+        // "héllo" split inside the two-byte 'é'
+        let text = "héllo".as_bytes();
+        assert_eq!(assembler.push(fragment(Opcode::Text, false, &text[..2])), Ok(None));
+        // A ping between fragments is delivered straight away
+        assert_eq!(assembler.push(fragment(Opcode::Ping, true, b"p")), Ok(Some(WebSocketMessage::Ping(b"p".to_vec()))));
+        assert_eq!(assembler.push(fragment(Opcode::Continuation, false, &text[2..4])), Ok(None));
+        assert_eq!(
+            assembler.push(fragment(Opcode::Continuation, true, &text[4..])),
+            Ok(Some(WebSocketMessage::Text("héllo".to_string())))
+        );
+
+        assert_eq!(
+            assembler.push(fragment(Opcode::Binary, true, &[1, 2])),
+            Ok(Some(WebSocketMessage::Binary(vec![1, 2])))
+        );
+        assert!(matches!(
+            assembler.push(fragment(Opcode::Continuation, true, b"x")),
+            Err(WebSocketError::ProtocolError(_))
+        ));
+        assembler.push(fragment(Opcode::Binary, false, &[1])).unwrap();
+        assert!(matches!(
+            assembler.push(fragment(Opcode::Text, true, b"x")),
+            Err(WebSocketError::ProtocolError(_))
+        ));
+
+        assembler.reset();
+        assert_eq!(assembler.push(fragment(Opcode::Text, true, &[0xFF])), Err(WebSocketError::InvalidUtf8));
+    }
+
+    #[test]
+    fn test_close_payloads() {
+        assert_eq!(parse_close(&[]), Ok(WebSocketMessage::Close(None, None)));
+        assert_eq!(
+            parse_close(b"\x03\xE8bye"),
+            Ok(WebSocketMessage::Close(Some(CloseCode::Normal), Some("bye".to_string())))
+        );
+        // Application codes keep their number
+        let private = Frame::new(Opcode::Close, CloseCode::Private(4001).as_u16().to_be_bytes().to_vec());
+        let (frame, _) = decode(&private.encode(false)).unwrap().unwrap();
+        assert_eq!(parse_close(&frame.payload), Ok(WebSocketMessage::Close(Some(CloseCode::Private(4001)), None)));
+        assert_eq!(parse_close(&[0x0B, 0xB8]), Ok(WebSocketMessage::Close(Some(CloseCode::Registered(3000)), None)));
+        assert!(matches!(parse_close(&[0x03]), Err(WebSocketError::ProtocolError(_))));
+        // 1005 is reserved for "no status received"
+        assert!(matches!(parse_close(&[0x03, 0xED]), Err(WebSocketError::ProtocolError(_))));
+        assert_eq!(parse_close(&[0x03, 0xE8, 0xC3]), Err(WebSocketError::InvalidUtf8));
+    }
+}
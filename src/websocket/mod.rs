@@ -0,0 +1,1043 @@
+// WebSocket Protocol (RFC 6455) - Phase 7 Task 5
+
+mod frame;
+mod server;
+mod transport;
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use frame::{Frame, MessageAssembler, Opcode};
+
+pub use frame::MAX_MESSAGE_SIZE;
+pub use server::{accept_upgrade, ServerCodec, UpgradeRequest};
+pub use transport::{Endpoint, Stream, HANDSHAKE_TIMEOUT};
+
+/// `bufferedAmount` above which a socket reports backpressure by default
+pub const DEFAULT_HIGH_WATER_MARK: usize = 1024 * 1024;
+
+/// Queued bytes beyond which sending data fails outright
+pub const MAX_BUFFERED_AMOUNT: usize = 16 * 1024 * 1024;
+
+/// WebSocket connection state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebSocketState {
+    Connecting,
+    Open,
+    Closing,
+    Closed,
+}
+
+/// WebSocket close code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseCode {
+    Normal,
+    GoingAway,
+    ProtocolError,
+    UnsupportedData,
+    NoStatusReceived,
+    AbnormalClosure,
+    InvalidFramePayload,
+    PolicyViolation,
+    MessageTooBig,
+    MandatoryExtension,
+    InternalError,
+    ServiceRestart,
+    TryAgainLater,
+    BadGateway,
+    TlsHandshake,
+    /// 3000-3999: registered with IANA for libraries and frameworks
+    Registered(u16),
+    /// 4000-4999: private to the application
+    Private(u16),
+}
+
+impl CloseCode {
+    /// Convert to u16
+    pub fn as_u16(self) -> u16 {
+        match self {
+            CloseCode::Normal => 1000,
+            CloseCode::GoingAway => 1001,
+            CloseCode::ProtocolError => 1002,
+            CloseCode::UnsupportedData => 1003,
+            CloseCode::NoStatusReceived => 1005,
+            CloseCode::AbnormalClosure => 1006,
+            CloseCode::InvalidFramePayload => 1007,
+            CloseCode::PolicyViolation => 1008,
+            CloseCode::MessageTooBig => 1009,
+            CloseCode::MandatoryExtension => 1010,
+            CloseCode::InternalError => 1011,
+            CloseCode::ServiceRestart => 1012,
+            CloseCode::TryAgainLater => 1013,
+            CloseCode::BadGateway => 1014,
+            CloseCode::TlsHandshake => 1015,
+            CloseCode::Registered(code) | CloseCode::Private(code) => code,
+        }
+    }
+    
+    /// Create from u16
+    pub fn from_u16(code: u16) -> Option<Self> {
+        match code {
+            1000 => Some(CloseCode::Normal),
+            1001 => Some(CloseCode::GoingAway),
+            1002 => Some(CloseCode::ProtocolError),
+            1003 => Some(CloseCode::UnsupportedData),
+            1005 => Some(CloseCode::NoStatusReceived),
+            1006 => Some(CloseCode::AbnormalClosure),
+            1007 => Some(CloseCode::InvalidFramePayload),
+            1008 => Some(CloseCode::PolicyViolation),
+            1009 => Some(CloseCode::MessageTooBig),
+            1010 => Some(CloseCode::MandatoryExtension),
+            1011 => Some(CloseCode::InternalError),
+            1012 => Some(CloseCode::ServiceRestart),
+            1013 => Some(CloseCode::TryAgainLater),
+            1014 => Some(CloseCode::BadGateway),
+            1015 => Some(CloseCode::TlsHandshake),
+            3000..=3999 => Some(CloseCode::Registered(code)),
+            4000..=4999 => Some(CloseCode::Private(code)),
+            _ => None,
+        }
+    }
+}
+
+/// WebSocket message type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebSocketMessage {
+    /// Text message (UTF-8)
+    Text(String),
+    /// Binary message
+    Binary(Vec<u8>),
+    /// Ping frame
+    Ping(Vec<u8>),
+    /// Pong frame
+    Pong(Vec<u8>),
+    /// Close frame
+    Close(Option<CloseCode>, Option<String>),
+}
+
+/// Which way a frame went
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameDirection {
+    Sent,
+    Received,
+}
+
+/// Bytes of payload kept with each logged frame
+pub const FRAME_PREVIEW_LEN: usize = 256;
+
+/// A frame as recorded for the developer tools
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameInfo {
+    pub direction: FrameDirection,
+    /// Opcode from the frame header (0x1 text, 0x2 binary, 0x8 close, ...)
+    pub opcode: u8,
+    /// Whether this is the final fragment of its message
+    pub fin: bool,
+    /// Payload length
+    pub length: usize,
+    /// Start of the payload, up to `FRAME_PREVIEW_LEN` bytes
+    pub preview: Vec<u8>,
+}
+
+impl FrameInfo {
+    fn new(direction: FrameDirection, frame: &Frame) -> Self {
+        Self {
+            direction,
+            opcode: frame.opcode as u8,
+            fin: frame.fin,
+            length: frame.payload.len(),
+            preview: frame.payload[..frame.payload.len().min(FRAME_PREVIEW_LEN)].to_vec(),
+        }
+    }
+}
+
+/// WebSocket connection
+pub struct WebSocket {
+    /// URL
+    url: String,
+    /// Connection state
+    state: WebSocketState,
+    /// Is secure (wss://)
+    secure: bool,
+    /// Incoming message queue
+    incoming_messages: VecDeque<WebSocketMessage>,
+    /// Outgoing frame queue
+    outgoing_frames: VecDeque<Vec<u8>>,
+    /// Last ping time
+    last_ping: Option<Instant>,
+    /// Ping interval
+    ping_interval: Duration,
+    /// How long the server has to answer a ping
+    pong_timeout: Duration,
+    /// When the unanswered ping was sent
+    awaiting_pong: Option<Instant>,
+    /// Bytes in `outgoing_frames` (`bufferedAmount`)
+    buffered_amount: usize,
+    /// `bufferedAmount` above which callers should stop sending
+    high_water_mark: usize,
+    /// Close code
+    close_code: Option<CloseCode>,
+    /// Close reason
+    close_reason: Option<String>,
+    /// Where the URL connects to
+    endpoint: Endpoint,
+    /// Subprotocols offered in the handshake
+    protocols: Vec<String>,
+    /// Subprotocol the server selected
+    protocol: Option<String>,
+    /// `Origin` header sent in the handshake
+    origin: Option<String>,
+    /// Connection, once the handshake succeeded
+    stream: Option<Box<dyn Stream>>,
+    /// Bytes received and not yet decoded into frames
+    read_buffer: Vec<u8>,
+    /// Fragments of the message being received
+    assembler: MessageAssembler,
+    /// Frames sent and received, while recording is on
+    frame_log: Option<Vec<FrameInfo>>,
+}
+
+impl WebSocket {
+    /// Create a new WebSocket connection
+    pub fn new(url: String) -> Result<Self, WebSocketError> {
+        Self::with_protocols(url, Vec::new())
+    }
+    
+    /// Create a connection offering subprotocols (`new WebSocket(url, protocols)`)
+    pub fn with_protocols(url: String, protocols: Vec<String>) -> Result<Self, WebSocketError> {
+        let endpoint = Endpoint::parse(&url)?;
+        
+        Ok(Self {
+            url,
+            state: WebSocketState::Connecting,
+            secure: endpoint.secure,
+            incoming_messages: VecDeque::new(),
+            outgoing_frames: VecDeque::new(),
+            last_ping: None,
+            ping_interval: Duration::from_secs(30),
+            pong_timeout: Duration::from_secs(10),
+            awaiting_pong: None,
+            buffered_amount: 0,
+            high_water_mark: DEFAULT_HIGH_WATER_MARK,
+            close_code: None,
+            close_reason: None,
+            endpoint,
+            protocols,
+            protocol: None,
+            origin: None,
+            stream: None,
+            read_buffer: Vec::new(),
+            assembler: MessageAssembler::new(),
+            frame_log: None,
+        })
+    }
+    
+    /// Send an `Origin` header with the handshake (the page's origin)
+    pub fn set_origin(&mut self, origin: String) {
+        self.origin = Some(origin);
+    }
+    
+    /// Subprotocol selected by the server
+    pub fn protocol(&self) -> Option<&str> {
+        self.protocol.as_deref()
+    }
+    
+    /// Get URL
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+    
+    /// Get state
+    pub fn state(&self) -> WebSocketState {
+        self.state
+    }
+    
+    /// Is secure
+    pub fn is_secure(&self) -> bool {
+        self.secure
+    }
+    
+    /// Connect (TCP, plus TLS for wss://) and perform the opening handshake
+    ///
+    /// Runs on the caller's tokio runtime and gives up after
+    /// `HANDSHAKE_TIMEOUT`. A failed connection ends up `Closed`.
+    pub async fn open(&mut self) -> Result<(), WebSocketError> {
+        if self.state != WebSocketState::Connecting {
+            return Err(WebSocketError::InvalidState);
+        }
+        
+        let endpoint = self.endpoint.clone();
+        let connected = tokio::time::timeout(HANDSHAKE_TIMEOUT, transport::connect(&endpoint)).await;
+        match connected {
+            Ok(Ok(stream)) => self.open_on(stream).await,
+            Ok(Err(e)) => Err(self.fail(e)),
+            Err(_) => Err(self.fail(WebSocketError::Timeout)),
+        }
+    }
+    
+    /// Perform the opening handshake over an already connected stream
+    pub async fn open_on(&mut self, mut stream: Box<dyn Stream>) -> Result<(), WebSocketError> {
+        if self.state != WebSocketState::Connecting {
+            return Err(WebSocketError::InvalidState);
+        }
+        
+        let handshake = transport::Handshake::new(self.protocols.clone());
+        let result = tokio::time::timeout(
+            HANDSHAKE_TIMEOUT,
+            handshake.perform(stream.as_mut(), &self.endpoint, self.origin.as_deref()),
+        )
+        .await
+        .unwrap_or(Err(WebSocketError::Timeout));
+        let (protocol, leftover) = result.map_err(|e| self.fail(e))?;
+        
+        self.protocol = protocol;
+        self.read_buffer = leftover;
+        self.stream = Some(stream);
+        self.state = WebSocketState::Open;
+        self.last_ping = Some(Instant::now());
+        
+        // Frames sent right behind the handshake response
+        self.process_incoming()
+    }
+    
+    /// Drop the connection after an error (RFC 6455 "fail the WebSocket connection")
+    fn fail(&mut self, error: WebSocketError) -> WebSocketError {
+        self.state = WebSocketState::Closed;
+        self.stream = None;
+        self.close_code = Some(CloseCode::AbnormalClosure);
+        error
+    }
+    
+    /// Write every queued frame to the connection
+    pub async fn flush(&mut self) -> Result<(), WebSocketError> {
+        let Some(stream) = self.stream.as_mut() else {
+            return Err(WebSocketError::NotConnected);
+        };
+        let mut result = Ok(());
+        while let Some(frame) = self.outgoing_frames.pop_front() {
+            self.buffered_amount -= frame.len();
+            if let Err(e) = stream.write_all(&frame).await {
+                result = Err(WebSocketError::Io(e.to_string()));
+                break;
+            }
+        }
+        if result.is_ok() {
+            result = stream.flush().await.map_err(|e| WebSocketError::Io(e.to_string()));
+        }
+        result.map_err(|e| self.fail(e))
+    }
+    
+    /// Wait for data from the server and decode the frames it completes
+    ///
+    /// Returns the number of bytes read; 0 means the server closed the
+    /// connection, leaving the socket `Closed`. Decoded messages are
+    /// available from `receive()`.
+    pub async fn read(&mut self) -> Result<usize, WebSocketError> {
+        let Some(stream) = self.stream.as_mut() else {
+            return Err(WebSocketError::NotConnected);
+        };
+        let mut chunk = [0u8; 16 * 1024];
+        let n = match stream.read(&mut chunk).await {
+            Ok(n) => n,
+            Err(e) => return Err(self.fail(WebSocketError::Io(e.to_string()))),
+        };
+        if n == 0 {
+            // Closed without a close frame unless one was already exchanged
+            if self.state != WebSocketState::Closed {
+                self.fail(WebSocketError::ConnectionError);
+            }
+            self.stream = None;
+            return Ok(0);
+        }
+        self.receive_bytes(&chunk[..n])?;
+        Ok(n)
+    }
+    
+    /// Take bytes received from the server and decode the frames they complete
+    ///
+    /// A protocol violation fails the connection: a close frame with the
+    /// matching status code is queued and the socket is `Closed`.
+    pub fn receive_bytes(&mut self, data: &[u8]) -> Result<(), WebSocketError> {
+        self.read_buffer.extend_from_slice(data);
+        self.process_incoming()
+    }
+    
+    /// Decode every complete frame in the read buffer
+    fn process_incoming(&mut self) -> Result<(), WebSocketError> {
+        while self.state != WebSocketState::Closed {
+            // Servers must not mask their frames
+            let (frame, consumed) = match Frame::decode(&self.read_buffer, false) {
+                Ok(Some(decoded)) => decoded,
+                Ok(None) => break,
+                Err(e) => return Err(self.fail_protocol(e)),
+            };
+            self.read_buffer.drain(..consumed);
+            if let Err(e) = self.handle_frame(frame) {
+                return Err(self.fail_protocol(e));
+            }
+        }
+        Ok(())
+    }
+    
+    /// Close the connection after the server broke the protocol
+    fn fail_protocol(&mut self, error: WebSocketError) -> WebSocketError {
+        let code = error.close_code();
+        if self.state == WebSocketState::Open {
+            let _ = self.close(code, None);
+        }
+        self.state = WebSocketState::Closed;
+        self.close_code = Some(code);
+        self.read_buffer.clear();
+        self.assembler.reset();
+        error
+    }
+    
+    /// Bytes received and not yet decoded into frames
+    pub fn buffered_bytes(&self) -> &[u8] {
+        &self.read_buffer
+    }
+    
+    /// Send text message
+    pub fn send_text(&mut self, text: String) -> Result<(), WebSocketError> {
+        if self.state != WebSocketState::Open {
+            return Err(WebSocketError::NotConnected);
+        }
+        
+        self.queue_data(Frame::new(Opcode::Text, text.into_bytes()))
+    }
+    
+    /// Send binary message
+    pub fn send_binary(&mut self, data: Vec<u8>) -> Result<(), WebSocketError> {
+        if self.state != WebSocketState::Open {
+            return Err(WebSocketError::NotConnected);
+        }
+        
+        self.queue_data(Frame::new(Opcode::Binary, data))
+    }
+    
+    /// Send ping
+    pub fn send_ping(&mut self, data: Vec<u8>) -> Result<(), WebSocketError> {
+        if self.state != WebSocketState::Open {
+            return Err(WebSocketError::NotConnected);
+        }
+        
+        self.queue(Frame::new(Opcode::Ping, data));
+        let now = Instant::now();
+        self.last_ping = Some(now);
+        self.awaiting_pong.get_or_insert(now);
+        
+        Ok(())
+    }
+    
+    /// Send pong
+    pub fn send_pong(&mut self, data: Vec<u8>) -> Result<(), WebSocketError> {
+        if self.state != WebSocketState::Open && self.state != WebSocketState::Closing {
+            return Err(WebSocketError::NotConnected);
+        }
+        
+        self.queue(Frame::new(Opcode::Pong, data));
+        
+        Ok(())
+    }
+    
+    /// Close connection
+    pub fn close(&mut self, code: CloseCode, reason: Option<String>) -> Result<(), WebSocketError> {
+        if self.state == WebSocketState::Closed {
+            return Err(WebSocketError::AlreadyClosed);
+        }
+        
+        self.state = WebSocketState::Closing;
+        self.close_code = Some(code);
+        self.close_reason = reason.clone();
+        
+        // Encode close frame
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&code.as_u16().to_be_bytes());
+        if let Some(reason_str) = reason {
+            payload.extend_from_slice(reason_str.as_bytes());
+        }
+        
+        self.queue(Frame::new(Opcode::Close, payload));
+        
+        Ok(())
+    }
+    
+    /// Queue a data frame, refusing it once the send buffer is full
+    fn queue_data(&mut self, frame: Frame) -> Result<(), WebSocketError> {
+        let encoded = frame.encode(true);
+        if self.buffered_amount + encoded.len() > MAX_BUFFERED_AMOUNT {
+            return Err(WebSocketError::BufferFull);
+        }
+        self.log_frame(FrameDirection::Sent, &frame);
+        self.buffered_amount += encoded.len();
+        self.outgoing_frames.push_back(encoded);
+        Ok(())
+    }
+    
+    /// Queue a control frame; these are never refused
+    fn queue(&mut self, frame: Frame) {
+        let encoded = frame.encode(true);
+        self.log_frame(FrameDirection::Sent, &frame);
+        self.buffered_amount += encoded.len();
+        self.outgoing_frames.push_back(encoded);
+    }
+    
+    /// Start or stop recording frames for `take_frame_log`
+    pub fn record_frames(&mut self, enabled: bool) {
+        self.frame_log = enabled.then(Vec::new);
+    }
+    
+    /// Frames sent and received since the last call, while recording
+    pub fn take_frame_log(&mut self) -> Vec<FrameInfo> {
+        self.frame_log.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+    
+    fn log_frame(&mut self, direction: FrameDirection, frame: &Frame) {
+        if let Some(log) = self.frame_log.as_mut() {
+            log.push(FrameInfo::new(direction, frame));
+        }
+    }
+    
+    /// Bytes queued and not yet written to the connection (`bufferedAmount`)
+    pub fn buffered_amount(&self) -> usize {
+        self.buffered_amount
+    }
+    
+    /// Set the `bufferedAmount` above which `is_backpressured()` reports true
+    pub fn set_high_water_mark(&mut self, bytes: usize) {
+        self.high_water_mark = bytes;
+    }
+    
+    /// Whether callers should wait for `flush()` before sending more
+    pub fn is_backpressured(&self) -> bool {
+        self.buffered_amount > self.high_water_mark
+    }
+    
+    /// Set how often keepalive pings are sent
+    pub fn set_ping_interval(&mut self, interval: Duration) {
+        self.ping_interval = interval;
+    }
+    
+    /// Set how long the server has to answer a ping before the connection is dropped
+    pub fn set_pong_timeout(&mut self, timeout: Duration) {
+        self.pong_timeout = timeout;
+    }
+    
+    /// Wait for the next message, sending keepalive pings meanwhile
+    ///
+    /// Meant to be polled in a loop by the task that owns the socket: it
+    /// writes queued frames, pings every `ping_interval` and fails the
+    /// connection with `Timeout` if a ping goes unanswered for
+    /// `pong_timeout`. Returns `None` once the connection is closed.
+    pub async fn next_message(&mut self) -> Result<Option<WebSocketMessage>, WebSocketError> {
+        loop {
+            if let Some(message) = self.receive() {
+                return Ok(Some(message));
+            }
+            if self.stream.is_none() {
+                return Ok(None);
+            }
+            
+            if let Some(sent) = self.awaiting_pong {
+                if sent.elapsed() >= self.pong_timeout {
+                    return Err(self.fail(WebSocketError::Timeout));
+                }
+            }
+            if self.state == WebSocketState::Open && self.should_ping() {
+                self.send_ping(Vec::new())?;
+            }
+            if !self.outgoing_frames.is_empty() {
+                self.flush().await?;
+            }
+            if self.state == WebSocketState::Closed {
+                // Close handshake finished and our reply is written
+                self.stream = None;
+                continue;
+            }
+            
+            let deadline = self.keepalive_deadline();
+            tokio::select! {
+                read = self.read() => {
+                    if let Err(e) = read {
+                        // Best effort to tell the server why, after a protocol error
+                        if self.stream.is_some() && !self.outgoing_frames.is_empty() {
+                            let _ = self.flush().await;
+                        }
+                        return Err(e);
+                    }
+                }
+                _ = tokio::time::sleep_until(deadline.into()) => {}
+            }
+        }
+    }
+    
+    /// When `next_message` next has to ping or check for a pong
+    fn keepalive_deadline(&self) -> Instant {
+        let next_ping = self.last_ping.unwrap_or_else(Instant::now) + self.ping_interval;
+        match self.awaiting_pong {
+            Some(sent) => next_ping.min(sent + self.pong_timeout),
+            None => next_ping,
+        }
+    }
+    
+    /// Receive message
+    pub fn receive(&mut self) -> Option<WebSocketMessage> {
+        self.incoming_messages.pop_front()
+    }
+    
+    /// Check if ping should be sent
+    pub fn should_ping(&self) -> bool {
+        if let Some(last_ping) = self.last_ping {
+            last_ping.elapsed() >= self.ping_interval
+        } else {
+            false
+        }
+    }
+    
+    /// Get next outgoing frame
+    pub fn next_outgoing_frame(&mut self) -> Option<Vec<u8>> {
+        let frame = self.outgoing_frames.pop_front()?;
+        self.buffered_amount -= frame.len();
+        Some(frame)
+    }
+    
+    /// Handle a complete, unfragmented incoming frame
+    pub fn handle_incoming_frame(&mut self, opcode: u8, payload: Vec<u8>) -> Result<(), WebSocketError> {
+        let opcode = Opcode::from_u8(opcode).ok_or(WebSocketError::InvalidFrame)?;
+        self.handle_frame(Frame::new(opcode, payload))
+    }
+    
+    /// Pass a decoded frame through reassembly and act on the message it completes
+    fn handle_frame(&mut self, frame: Frame) -> Result<(), WebSocketError> {
+        self.log_frame(FrameDirection::Received, &frame);
+        let Some(message) = self.assembler.push(frame)? else {
+            return Ok(());
+        };
+        
+        match &message {
+            WebSocketMessage::Ping(payload) if self.state == WebSocketState::Open => {
+                // Auto-respond with pong
+                self.send_pong(payload.clone())?;
+            }
+            WebSocketMessage::Pong(_) => {
+                self.awaiting_pong = None;
+            }
+            WebSocketMessage::Close(code, reason) => {
+                if self.state == WebSocketState::Open {
+                    // Echo the server's close and take its status
+                    let _ = self.close(code.unwrap_or(CloseCode::Normal), None);
+                    self.close_code = Some(code.unwrap_or(CloseCode::NoStatusReceived));
+                    self.close_reason = reason.clone();
+                }
+                self.state = WebSocketState::Closed;
+                self.assembler.reset();
+            }
+            _ => {}
+        }
+        self.incoming_messages.push_back(message);
+        
+        Ok(())
+    }
+    
+    /// Get close code
+    pub fn close_code(&self) -> Option<CloseCode> {
+        self.close_code
+    }
+    
+    /// Get close reason
+    pub fn close_reason(&self) -> Option<&str> {
+        self.close_reason.as_deref()
+    }
+}
+
+/// WebSocket error types
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebSocketError {
+    /// Invalid URL
+    InvalidUrl,
+    /// Invalid state
+    InvalidState,
+    /// Not connected
+    NotConnected,
+    /// Already closed
+    AlreadyClosed,
+    /// Invalid frame
+    InvalidFrame,
+    /// Invalid UTF-8
+    InvalidUtf8,
+    /// Connection error
+    ConnectionError,
+    /// Unsupported feature
+    UnsupportedFeature,
+    /// The server's handshake response was not acceptable
+    HandshakeFailed(String),
+    /// TLS connection for wss:// failed
+    TlsError(String),
+    /// Reading from or writing to the socket failed
+    Io(String),
+    /// Connecting or the handshake took too long
+    Timeout,
+    /// The server broke the framing rules
+    ProtocolError(String),
+    /// A message exceeded `MAX_MESSAGE_SIZE`
+    MessageTooBig,
+    /// Sending would queue more than `MAX_BUFFERED_AMOUNT` bytes
+    BufferFull,
+}
+
+impl WebSocketError {
+    /// Status code to close the connection with after this error
+    pub fn close_code(&self) -> CloseCode {
+        match self {
+            WebSocketError::InvalidUtf8 => CloseCode::InvalidFramePayload,
+            WebSocketError::MessageTooBig => CloseCode::MessageTooBig,
+            WebSocketError::InvalidFrame | WebSocketError::ProtocolError(_) => CloseCode::ProtocolError,
+            _ => CloseCode::AbnormalClosure,
+        }
+    }
+}
+
+impl std::fmt::Display for WebSocketError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            WebSocketError::InvalidUrl => write!(f, "Invalid WebSocket URL"),
+            WebSocketError::InvalidState => write!(f, "Invalid WebSocket state"),
+            WebSocketError::NotConnected => write!(f, "WebSocket not connected"),
+            WebSocketError::AlreadyClosed => write!(f, "WebSocket already closed"),
+            WebSocketError::InvalidFrame => write!(f, "Invalid WebSocket frame"),
+            WebSocketError::InvalidUtf8 => write!(f, "Invalid UTF-8 in text message"),
+            WebSocketError::ConnectionError => write!(f, "WebSocket connection error"),
+            WebSocketError::UnsupportedFeature => write!(f, "Unsupported WebSocket feature"),
+            WebSocketError::HandshakeFailed(reason) => write!(f, "WebSocket handshake failed: {}", reason),
+            WebSocketError::TlsError(msg) => write!(f, "WebSocket TLS error: {}", msg),
+            WebSocketError::Io(msg) => write!(f, "WebSocket I/O error: {}", msg),
+            WebSocketError::Timeout => write!(f, "WebSocket connection timed out"),
+            WebSocketError::ProtocolError(msg) => write!(f, "WebSocket protocol error: {}", msg),
+            WebSocketError::MessageTooBig => write!(f, "WebSocket message too big"),
+            WebSocketError::BufferFull => write!(f, "WebSocket send buffer full"),
+        }
+    }
+}
+
+impl std::error::Error for WebSocketError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::DuplexStream;
+    
+    /// Read a handshake request and answer it with 101, followed by `extra`
+    async fn accept_handshake(server: &mut DuplexStream, extra: &[u8]) -> String {
+        let mut request = Vec::new();
+        let mut byte = [0u8; 1];
+        while !request.ends_with(b"\r\n\r\n") {
+            server.read_exact(&mut byte).await.unwrap();
+            request.push(byte[0]);
+        }
+        let request = String::from_utf8(request).unwrap();
+        let key = request.lines().find_map(|l| l.strip_prefix("Sec-WebSocket-Key: ")).unwrap();
+        let response = format!(
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            transport::accept_key(key)
+        );
+        server.write_all(response.as_bytes()).await.unwrap();
+        server.write_all(extra).await.unwrap();
+        request
+    }
+    
+    /// A socket opened against an in-memory server, and that server's end
+    async fn open_socket() -> (WebSocket, DuplexStream) {
+        let (client, mut server) = tokio::io::duplex(64 * 1024);
+        let server = tokio::spawn(async move {
+            accept_handshake(&mut server, b"").await;
+            server
+        });
+        let mut ws = WebSocket::new("ws://example.com".to_string()).unwrap();
+        ws.open_on(Box::new(client)).await.unwrap();
+        (ws, server.await.unwrap())
+    }
+    
+    #[test]
+    fn test_websocket_creation() {
+        let ws = WebSocket::new("ws://example.com".to_string()).unwrap();
+        assert_eq!(ws.state(), WebSocketState::Connecting);
+        assert!(!ws.is_secure());
+    }
+    
+    #[test]
+    fn test_secure_websocket() {
+        let ws = WebSocket::new("wss://example.com".to_string()).unwrap();
+        assert!(ws.is_secure());
+    }
+    
+    #[test]
+    fn test_invalid_url() {
+        let result = WebSocket::new("http://example.com".to_string());
+        assert!(result.is_err());
+        if let Err(e) = result {
+            assert_eq!(e, WebSocketError::InvalidUrl);
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_open_connection() {
+        let (ws, _server) = open_socket().await;
+        assert_eq!(ws.state(), WebSocketState::Open);
+    }
+    
+    #[tokio::test]
+    async fn test_send_text() {
+        let (mut ws, _server) = open_socket().await;
+        
+        ws.send_text("Hello".to_string()).unwrap();
+        assert!(ws.next_outgoing_frame().is_some());
+    }
+    
+    #[tokio::test]
+    async fn test_send_binary() {
+        let (mut ws, _server) = open_socket().await;
+        
+        ws.send_binary(vec![1, 2, 3, 4]).unwrap();
+        assert!(ws.next_outgoing_frame().is_some());
+    }
+    
+    #[tokio::test]
+    async fn test_close() {
+        let (mut ws, _server) = open_socket().await;
+        
+        ws.close(CloseCode::Normal, Some("Goodbye".to_string())).unwrap();
+        assert_eq!(ws.state(), WebSocketState::Closing);
+        assert_eq!(ws.close_code(), Some(CloseCode::Normal));
+    }
+    
+    #[tokio::test]
+    async fn test_ping_pong() {
+        let (mut ws, _server) = open_socket().await;
+        
+        ws.send_ping(vec![1, 2, 3]).unwrap();
+        assert!(ws.next_outgoing_frame().is_some());
+    }
+    
+    #[tokio::test]
+    async fn test_frame_log() {
+        let (mut ws, _server) = open_socket().await;
+        ws.send_text("before".to_string()).unwrap();
+        assert!(ws.take_frame_log().is_empty());
+        
+        ws.record_frames(true);
+        ws.send_text("x".repeat(FRAME_PREVIEW_LEN + 10)).unwrap();
+        ws.receive_bytes(&[0x82, 0x02, 7, 8]).unwrap();
+        let log = ws.take_frame_log();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].direction, FrameDirection::Sent);
+        assert_eq!(log[0].length, FRAME_PREVIEW_LEN + 10);
+        assert_eq!(log[0].preview.len(), FRAME_PREVIEW_LEN);
+        assert_eq!((log[1].direction, log[1].opcode, log[1].preview.clone()), (FrameDirection::Received, 0x2, vec![7, 8]));
+        assert!(ws.take_frame_log().is_empty());
+    }
+    
+    #[test]
+    fn test_close_code_conversion() {
+        assert_eq!(CloseCode::Normal.as_u16(), 1000);
+        assert_eq!(CloseCode::from_u16(1000), Some(CloseCode::Normal));
+        assert_eq!(CloseCode::from_u16(9999), None);
+        assert_eq!(CloseCode::from_u16(3000), Some(CloseCode::Registered(3000)));
+        assert_eq!(CloseCode::from_u16(4999).map(CloseCode::as_u16), Some(4999));
+    }
+    
+    #[tokio::test]
+    async fn test_handle_incoming_text() {
+        let (mut ws, _server) = open_socket().await;
+        
+        let payload = "Hello".as_bytes().to_vec();
+        ws.handle_incoming_frame(Opcode::Text as u8, payload).unwrap();
+        
+        if let Some(WebSocketMessage::Text(text)) = ws.receive() {
+            assert_eq!(text, "Hello");
+        } else {
+            panic!("Expected text message");
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_handle_incoming_binary() {
+        let (mut ws, _server) = open_socket().await;
+        
+        let payload = vec![1, 2, 3, 4];
+        ws.handle_incoming_frame(Opcode::Binary as u8, payload.clone()).unwrap();
+        
+        if let Some(WebSocketMessage::Binary(data)) = ws.receive() {
+            assert_eq!(data, payload);
+        } else {
+            panic!("Expected binary message");
+        }
+    }
+    
+    #[test]
+    fn test_frame_encoding() {
+        let frame = Frame::new(Opcode::Text, "Hello".as_bytes().to_vec());
+        let encoded = frame.encode(false);
+        
+        // Check FIN and opcode
+        assert_eq!(encoded[0] & 0x80, 0x80); // FIN bit set
+        assert_eq!(encoded[0] & 0x0F, Opcode::Text as u8);
+    }
+    
+    #[test]
+    fn test_send_before_open() {
+        let mut ws = WebSocket::new("ws://example.com".to_string()).unwrap();
+        
+        let result = ws.send_text("Hello".to_string());
+        assert_eq!(result, Err(WebSocketError::NotConnected));
+    }
+    
+    #[tokio::test]
+    async fn test_handshake_and_socket_io() {
+        let (client, mut server) = tokio::io::duplex(64 * 1024);
+        let server_task = tokio::spawn(async move {
+            // A frame sent right behind the handshake response
+            let request = accept_handshake(&mut server, &[0x81, 0x02, b'h', b'i']).await;
+            (request, server)
+        });
+        let mut ws = WebSocket::with_protocols("ws://example.com:8080/chat".to_string(), vec!["chat".to_string()]).unwrap();
+        ws.set_origin("https://example.com".to_string());
+        ws.open_on(Box::new(client)).await.unwrap();
+        let (request, mut server) = server_task.await.unwrap();
+        
+        assert!(request.starts_with("GET /chat HTTP/1.1\r\nHost: example.com:8080\r\n"));
+        assert!(request.contains("Origin: https://example.com\r\n"));
+        assert_eq!(ws.state(), WebSocketState::Open);
+        assert_eq!(ws.protocol(), None);
+        assert!(ws.buffered_bytes().is_empty());
+        assert_eq!(ws.receive(), Some(WebSocketMessage::Text("hi".to_string())));
+        
+        ws.send_text("Hello".to_string()).unwrap();
+        ws.flush().await.unwrap();
+        let mut frame = [0u8; 11];
+        server.read_exact(&mut frame).await.unwrap();
+        assert_eq!(frame[0], 0x81);
+        // Masked, with the payload unmasked by the key
+        assert_eq!(frame[1], 0x80 | 5);
+        let unmasked: Vec<u8> = frame[6..].iter().enumerate().map(|(i, b)| b ^ frame[2 + i % 4]).collect();
+        assert_eq!(unmasked, b"Hello");
+        
+        server.write_all(&[0x8A, 0x00]).await.unwrap();
+        assert_eq!(ws.read().await, Ok(2));
+        assert_eq!(ws.receive(), Some(WebSocketMessage::Pong(Vec::new())));
+        
+        // The server going away without a close frame
+        drop(server);
+        assert_eq!(ws.read().await, Ok(0));
+        assert_eq!(ws.state(), WebSocketState::Closed);
+        assert_eq!(ws.close_code(), Some(CloseCode::AbnormalClosure));
+    }
+    
+    #[tokio::test]
+    async fn test_rejected_handshake_closes() {
+        let (client, mut server) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(async move {
+            let mut buffer = [0u8; 1024];
+            let _ = server.read(&mut buffer).await;
+            let _ = server.write_all(b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\n\r\n").await;
+        });
+        let mut ws = WebSocket::new("ws://example.com".to_string()).unwrap();
+        assert!(matches!(ws.open_on(Box::new(client)).await, Err(WebSocketError::HandshakeFailed(_))));
+        assert_eq!(ws.state(), WebSocketState::Closed);
+        assert_eq!(ws.open_on(Box::new(tokio::io::duplex(16).0)).await, Err(WebSocketError::InvalidState));
+        assert_eq!(ws.flush().await, Err(WebSocketError::NotConnected));
+    }
+    
+    #[tokio::test]
+    async fn test_fragmented_message_and_close_echo() {
+        let (mut ws, _server) = open_socket().await;
+        
+        // "Hello" in two fragments, split across reads, with a ping between
+        ws.receive_bytes(&[0x01, 0x03, b'H', b'e']).unwrap();
+        assert_eq!(ws.buffered_bytes().len(), 4);
+        ws.receive_bytes(&[b'l', 0x89, 0x00, 0x80, 0x02, b'l', b'o']).unwrap();
+        assert!(ws.buffered_bytes().is_empty());
+        assert_eq!(ws.receive(), Some(WebSocketMessage::Ping(Vec::new())));
+        assert_eq!(ws.receive(), Some(WebSocketMessage::Text("Hello".to_string())));
+        assert!(ws.next_outgoing_frame().is_some());
+        
+        // The server's close is echoed back
+        ws.receive_bytes(&[0x88, 0x02, 0x03, 0xE9]).unwrap();
+        assert_eq!(ws.receive(), Some(WebSocketMessage::Close(Some(CloseCode::GoingAway), None)));
+        assert_eq!(ws.state(), WebSocketState::Closed);
+        assert_eq!(ws.close_code(), Some(CloseCode::GoingAway));
+        let echo = ws.next_outgoing_frame().unwrap();
+        assert_eq!(echo[0], 0x88);
+        assert_eq!(echo[1], 0x80 | 2);
+    }
+    
+    #[tokio::test]
+    async fn test_protocol_violation_fails_connection() {
+        let (mut ws, _server) = open_socket().await;
+        
+        // Continuation frame with no message started
+        let result = ws.receive_bytes(&[0x80, 0x01, b'x', 0x81, 0x00]);
+        assert!(matches!(result, Err(WebSocketError::ProtocolError(_))));
+        assert_eq!(ws.state(), WebSocketState::Closed);
+        assert_eq!(ws.close_code(), Some(CloseCode::ProtocolError));
+        assert!(ws.buffered_bytes().is_empty());
+        assert!(ws.receive().is_none());
+        
+        // The close frame carries 1002
+        let close = ws.next_outgoing_frame().unwrap();
+        let code: Vec<u8> = close[6..8].iter().enumerate().map(|(i, b)| b ^ close[2 + i]).collect();
+        assert_eq!(code, 1002u16.to_be_bytes());
+        
+        let (mut ws, _server) = open_socket().await;
+        assert_eq!(ws.receive_bytes(&[0x81, 0x01, 0xFF]), Err(WebSocketError::InvalidUtf8));
+        assert_eq!(ws.close_code(), Some(CloseCode::InvalidFramePayload));
+    }
+    
+    #[tokio::test]
+    async fn test_buffered_amount_and_backpressure() {
+        let (mut ws, mut server) = open_socket().await;
+        ws.set_high_water_mark(16);
+        
+        ws.send_text("x".repeat(20)).unwrap();
+        // 2 header bytes, 4 mask bytes and the payload
+        assert_eq!(ws.buffered_amount(), 26);
+        assert!(ws.is_backpressured());
+        
+        ws.flush().await.unwrap();
+        assert_eq!(ws.buffered_amount(), 0);
+        assert!(!ws.is_backpressured());
+        let mut frame = [0u8; 26];
+        server.read_exact(&mut frame).await.unwrap();
+        
+        assert_eq!(ws.send_binary(vec![0; MAX_BUFFERED_AMOUNT]), Err(WebSocketError::BufferFull));
+        assert_eq!(ws.buffered_amount(), 0);
+        ws.send_ping(Vec::new()).unwrap();
+        assert_eq!(ws.buffered_amount(), 6);
+        ws.next_outgoing_frame().unwrap();
+        assert_eq!(ws.buffered_amount(), 0);
+    }
+    
+    #[tokio::test]
+    async fn test_keepalive_pings_and_pong_timeout() {
+        let (mut ws, mut server) = open_socket().await;
+        ws.set_ping_interval(Duration::from_millis(20));
+        ws.set_pong_timeout(Duration::from_millis(50));
+        
+        tokio::spawn(async move {
+            // Answer the first ping, then stop answering
+            let mut ping = [0u8; 6];
+            server.read_exact(&mut ping).await.unwrap();
+            assert_eq!(ping[0], 0x89);
+            server.write_all(&[0x8A, 0x00, 0x81, 0x02, b'o', b'k']).await.unwrap();
+            let mut buffer = [0u8; 64];
+            while server.read(&mut buffer).await.unwrap_or(0) > 0 {}
+        });
+        
+        assert_eq!(ws.next_message().await, Ok(Some(WebSocketMessage::Pong(Vec::new()))));
+        assert_eq!(ws.next_message().await, Ok(Some(WebSocketMessage::Text("ok".to_string()))));
+        assert_eq!(ws.next_message().await, Err(WebSocketError::Timeout));
+        assert_eq!(ws.state(), WebSocketState::Closed);
+        assert_eq!(ws.close_code(), Some(CloseCode::AbnormalClosure));
+        assert_eq!(ws.next_message().await, Ok(None));
+    }
+}
@@ -0,0 +1,133 @@
+// WebSocket server side - answering the handshake and framing for clients
+//
+// Used by endpoints the browser itself serves, such as the DevTools
+// protocol server. The caller owns the socket: this answers the client's
+// Upgrade request, unmasks the client's frames into messages and encodes
+// unmasked frames to send back.
+
+use super::frame::{Frame, MessageAssembler, Opcode};
+use super::transport::accept_key;
+use super::{CloseCode, WebSocketError, WebSocketMessage};
+
+/// A client's opening handshake, once accepted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpgradeRequest {
+    /// Requested resource, e.g. `/devtools/page/1`
+    pub resource: String,
+    /// `101 Switching Protocols` response head to send
+    pub response: String,
+}
+
+/// Check a client's Upgrade request head and build the response
+pub fn accept_upgrade(head: &str) -> Result<UpgradeRequest, WebSocketError> {
+    let fail = |reason: &str| Err(WebSocketError::HandshakeFailed(reason.to_string()));
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or("").split(' ');
+    let (Some("GET"), Some(resource)) = (request_line.next(), request_line.next()) else {
+        return fail("not a GET request");
+    };
+
+    let mut key = None;
+    let (mut upgrade, mut connection, mut version) = (false, false, false);
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "upgrade" => upgrade = value.eq_ignore_ascii_case("websocket"),
+            "connection" => connection = value.split(',').any(|token| token.trim().eq_ignore_ascii_case("upgrade")),
+            "sec-websocket-version" => version = value == "13",
+            "sec-websocket-key" => key = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    if !upgrade || !connection {
+        return fail("not an Upgrade request");
+    }
+    if !version {
+        return fail("unsupported version");
+    }
+    let Some(key) = key else {
+        return fail("missing Sec-WebSocket-Key");
+    };
+
+    Ok(UpgradeRequest {
+        resource: resource.to_string(),
+        response: format!(
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Accept: {}\r\n\r\n",
+            accept_key(&key)
+        ),
+    })
+}
+
+/// Framing for the server end of a connection
+#[derive(Debug, Default)]
+pub struct ServerCodec {
+    /// Bytes of a frame not fully received yet
+    buffer: Vec<u8>,
+    assembler: MessageAssembler,
+}
+
+impl ServerCodec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take bytes read from the client; returns the messages they complete
+    pub fn receive_bytes(&mut self, data: &[u8]) -> Result<Vec<WebSocketMessage>, WebSocketError> {
+        self.buffer.extend_from_slice(data);
+        let mut messages = Vec::new();
+        while let Some((frame, used)) = Frame::decode(&self.buffer, true)? {
+            self.buffer.drain(..used);
+            messages.extend(self.assembler.push(frame)?);
+        }
+        Ok(messages)
+    }
+
+    /// A text message, ready to write
+    pub fn text(text: &str) -> Vec<u8> {
+        Frame::new(Opcode::Text, text.as_bytes().to_vec()).encode(false)
+    }
+
+    /// Answer to a ping
+    pub fn pong(payload: Vec<u8>) -> Vec<u8> {
+        Frame::new(Opcode::Pong, payload).encode(false)
+    }
+
+    /// Close frame with a status code
+    pub fn close(code: CloseCode) -> Vec<u8> {
+        Frame::new(Opcode::Close, code.as_u16().to_be_bytes().to_vec()).encode(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_upgrade_and_frames() {
+        let head = "GET /devtools/page/1 HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+                    Connection: keep-alive, Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                    Sec-WebSocket-Version: 13\r\n\r\n";
+        let upgrade = accept_upgrade(head).unwrap();
+        assert_eq!(upgrade.resource, "/devtools/page/1");
+        assert!(upgrade.response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+        assert!(accept_upgrade("GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").is_err());
+
+        // Client frames are masked and may arrive in pieces
+        let mut codec = ServerCodec::new();
+        let frame = Frame::new(Opcode::Text, b"hello".to_vec()).encode(true);
+        assert_eq!(codec.receive_bytes(&frame[..3]), Ok(Vec::new()));
+        let mut rest = frame[3..].to_vec();
+        rest.extend(Frame::new(Opcode::Ping, Vec::new()).encode(true));
+        assert_eq!(
+            codec.receive_bytes(&rest),
+            Ok(vec![WebSocketMessage::Text("hello".to_string()), WebSocketMessage::Ping(Vec::new())])
+        );
+        // An unmasked client frame breaks the protocol
+        assert!(codec.receive_bytes(&ServerCodec::text("hi")).is_err());
+        assert_eq!(ServerCodec::text("hi"), vec![0x81, 2, b'h', b'i']);
+    }
+}
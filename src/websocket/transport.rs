@@ -0,0 +1,298 @@
+// WebSocket transport - the connection and the opening handshake
+//
+// Connections run on the caller's tokio runtime: a TCP socket, wrapped in
+// TLS (rustls with Mozilla's root certificates) for wss://, then the
+// HTTP/1.1 Upgrade handshake of RFC 6455 section 4.
+
+use super::WebSocketError;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rand::RngCore;
+use sha1::{Digest, Sha1};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{self, ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+use url::Url;
+
+/// Appended to the client's key to compute `Sec-WebSocket-Accept`
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Longest handshake response head accepted
+const MAX_RESPONSE_HEAD: usize = 16 * 1024;
+
+/// Time allowed for connecting and completing the handshake
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A byte stream a WebSocket runs over: TCP, TLS, or an in-memory pipe
+pub trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+/// Where a WebSocket URL connects to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    /// Host name or address, IPv6 addresses without brackets
+    pub host: String,
+    pub port: u16,
+    /// wss://
+    pub secure: bool,
+    /// Path and query, as sent in the request line
+    pub resource: String,
+}
+
+impl Endpoint {
+    /// Parse a ws:// or wss:// URL; fragments are not allowed
+    pub fn parse(url: &str) -> Result<Self, WebSocketError> {
+        let url = Url::parse(url).map_err(|_| WebSocketError::InvalidUrl)?;
+        let secure = match url.scheme() {
+            "ws" => false,
+            "wss" => true,
+            _ => return Err(WebSocketError::InvalidUrl),
+        };
+        if url.fragment().is_some() {
+            return Err(WebSocketError::InvalidUrl);
+        }
+        let host = url.host_str().ok_or(WebSocketError::InvalidUrl)?;
+        let host = host.trim_start_matches('[').trim_end_matches(']').to_string();
+        let port = url.port_or_known_default().unwrap_or(if secure { 443 } else { 80 });
+
+        let mut resource = url.path().to_string();
+        if let Some(query) = url.query() {
+            resource.push('?');
+            resource.push_str(query);
+        }
+        Ok(Self { host, port, secure, resource })
+    }
+
+    /// `Host` header value; the port is left out when it is the scheme's default
+    fn host_header(&self) -> String {
+        let host = if self.host.contains(':') { format!("[{}]", self.host) } else { self.host.clone() };
+        let default_port = if self.secure { 443 } else { 80 };
+        if self.port == default_port {
+            host
+        } else {
+            format!("{}:{}", host, self.port)
+        }
+    }
+}
+
+/// `Sec-WebSocket-Accept` a server must answer `key` with
+pub fn accept_key(key: &str) -> String {
+    let mut sha1 = Sha1::new();
+    sha1.update(key.as_bytes());
+    sha1.update(ACCEPT_GUID.as_bytes());
+    BASE64.encode(sha1.finalize())
+}
+
+/// Client side of the opening handshake
+pub struct Handshake {
+    /// Random `Sec-WebSocket-Key`
+    key: String,
+    /// Subprotocols offered, in order of preference
+    protocols: Vec<String>,
+}
+
+impl Handshake {
+    /// Start a handshake with a fresh random key
+    pub fn new(protocols: Vec<String>) -> Self {
+        let mut nonce = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        Self {
+            key: BASE64.encode(nonce),
+            protocols,
+        }
+    }
+
+    /// HTTP/1.1 Upgrade request
+    pub fn request(&self, endpoint: &Endpoint, origin: Option<&str>) -> String {
+        let mut request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n",
+            endpoint.resource,
+            endpoint.host_header(),
+            self.key
+        );
+        if let Some(origin) = origin {
+            request.push_str(&format!("Origin: {}\r\n", origin));
+        }
+        if !self.protocols.is_empty() {
+            request.push_str(&format!("Sec-WebSocket-Protocol: {}\r\n", self.protocols.join(", ")));
+        }
+        request.push_str("\r\n");
+        request
+    }
+
+    /// Check the server's response head (status line and headers)
+    ///
+    /// Returns the subprotocol the server selected, if any.
+    pub fn validate(&self, head: &str) -> Result<Option<String>, WebSocketError> {
+        let fail = |reason: &str| Err(WebSocketError::HandshakeFailed(reason.to_string()));
+        let mut lines = head.split("\r\n");
+        let status = lines.next().unwrap_or("");
+        let mut parts = status.splitn(3, ' ');
+        if !parts.next().unwrap_or("").starts_with("HTTP/1.1") || parts.next() != Some("101") {
+            return fail(&format!("unexpected response '{}'", status));
+        }
+
+        let headers: Vec<(String, &str)> = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim()))
+            .collect();
+        let header = |name: &str| headers.iter().find(|(n, _)| n == name).map(|(_, v)| *v);
+        let has_token = |name: &str, token: &str| {
+            header(name).is_some_and(|v| v.split(',').any(|t| t.trim().eq_ignore_ascii_case(token)))
+        };
+
+        if !has_token("upgrade", "websocket") {
+            return fail("missing 'Upgrade: websocket'");
+        }
+        if !has_token("connection", "upgrade") {
+            return fail("missing 'Connection: Upgrade'");
+        }
+        if header("sec-websocket-accept") != Some(accept_key(&self.key).as_str()) {
+            return fail("wrong Sec-WebSocket-Accept");
+        }
+        // No extensions are offered, so none may be accepted
+        if header("sec-websocket-extensions").is_some_and(|v| !v.is_empty()) {
+            return fail("server selected an extension that was not offered");
+        }
+        match header("sec-websocket-protocol").filter(|v| !v.is_empty()) {
+            Some(protocol) if self.protocols.iter().any(|p| p == protocol) => Ok(Some(protocol.to_string())),
+            Some(protocol) => fail(&format!("server selected unrequested subprotocol '{}'", protocol)),
+            None => Ok(None),
+        }
+    }
+
+    /// Send the request over `stream` and read and check the response
+    ///
+    /// Returns the selected subprotocol and any bytes the server sent after
+    /// the response head, which already belong to the first frames.
+    pub async fn perform(
+        &self,
+        stream: &mut dyn Stream,
+        endpoint: &Endpoint,
+        origin: Option<&str>,
+    ) -> Result<(Option<String>, Vec<u8>), WebSocketError> {
+        stream.write_all(self.request(endpoint, origin).as_bytes()).await.map_err(io_error)?;
+        stream.flush().await.map_err(io_error)?;
+
+        let mut buffer = Vec::new();
+        let mut chunk = [0u8; 4096];
+        let head_end = loop {
+            if let Some(end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+                break end;
+            }
+            if buffer.len() > MAX_RESPONSE_HEAD {
+                return Err(WebSocketError::HandshakeFailed("response head too long".to_string()));
+            }
+            let n = stream.read(&mut chunk).await.map_err(io_error)?;
+            if n == 0 {
+                return Err(WebSocketError::HandshakeFailed("connection closed during handshake".to_string()));
+            }
+            buffer.extend_from_slice(&chunk[..n]);
+        };
+
+        let head = String::from_utf8_lossy(&buffer[..head_end]).into_owned();
+        let protocol = self.validate(&head)?;
+        Ok((protocol, buffer.split_off(head_end + 4)))
+    }
+}
+
+/// Open a TCP connection to the endpoint, with TLS for wss://
+pub async fn connect(endpoint: &Endpoint) -> Result<Box<dyn Stream>, WebSocketError> {
+    let tcp = TcpStream::connect((endpoint.host.as_str(), endpoint.port)).await.map_err(io_error)?;
+    tcp.set_nodelay(true).map_err(io_error)?;
+    if !endpoint.secure {
+        return Ok(Box::new(tcp));
+    }
+
+    let server_name = ServerName::try_from(endpoint.host.clone()).map_err(|_| WebSocketError::InvalidUrl)?;
+    let tls = TlsConnector::from(tls_config())
+        .connect(server_name, tcp)
+        .await
+        .map_err(|e| WebSocketError::TlsError(e.to_string()))?;
+    Ok(Box::new(tls))
+}
+
+/// Client TLS settings, shared by every wss:// connection
+fn tls_config() -> Arc<ClientConfig> {
+    static CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+    CONFIG
+        .get_or_init(|| {
+            let roots = RootCertStore { roots: webpki_roots::TLS_SERVER_ROOTS.to_vec() };
+            let provider = Arc::new(rustls::crypto::ring::default_provider());
+            let mut config = ClientConfig::builder_with_provider(provider)
+                .with_safe_default_protocol_versions()
+                .expect("default TLS versions are supported")
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+            // The Upgrade handshake needs HTTP/1.1
+            config.alpn_protocols = vec![b"http/1.1".to_vec()];
+            Arc::new(config)
+        })
+        .clone()
+}
+
+fn io_error(e: std::io::Error) -> WebSocketError {
+    WebSocketError::Io(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_key() {
+        // Example from RFC 6455 section 1.3
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+        // Keys are 16 random bytes, base64 encoded
+        let (a, b) = (Handshake::new(Vec::new()), Handshake::new(Vec::new()));
+        assert_eq!(BASE64.decode(&a.key).unwrap().len(), 16);
+        assert_ne!(a.key, b.key);
+    }
+
+    #[test]
+    fn test_endpoint_parse() {
+        let endpoint = Endpoint::parse("wss://example.com/chat?room=1").unwrap();
+        assert_eq!(endpoint, Endpoint { host: "example.com".to_string(), port: 443, secure: true, resource: "/chat?room=1".to_string() });
+        assert_eq!(endpoint.host_header(), "example.com");
+
+        let endpoint = Endpoint::parse("ws://[::1]:9000").unwrap();
+        assert_eq!((endpoint.host.as_str(), endpoint.port, endpoint.resource.as_str()), ("::1", 9000, "/"));
+        assert_eq!(endpoint.host_header(), "[::1]:9000");
+
+        assert_eq!(Endpoint::parse("ws://example.com/#top"), Err(WebSocketError::InvalidUrl));
+        assert_eq!(Endpoint::parse("https://example.com/"), Err(WebSocketError::InvalidUrl));
+    }
+
+    #[test]
+    fn test_validate_response() {
+        let handshake = Handshake::new(vec!["chat".to_string(), "superchat".to_string()]);
+        let request = handshake.request(&Endpoint::parse("ws://example.com:8080/ws").unwrap(), Some("https://example.com"));
+        assert!(request.starts_with("GET /ws HTTP/1.1\r\nHost: example.com:8080\r\n"));
+        assert!(request.contains("Sec-WebSocket-Protocol: chat, superchat\r\n"));
+        assert!(request.contains("Origin: https://example.com\r\n"));
+        assert!(request.ends_with("\r\n\r\n"));
+
+        let response = |status: &str, accept: &str, extra: &str| {
+            format!("HTTP/1.1 {}\r\nupgrade: WebSocket\r\nConnection: keep-alive, Upgrade\r\nSec-WebSocket-Accept: {}{}", status, accept, extra)
+        };
+        let accept = accept_key(&handshake.key);
+        assert_eq!(handshake.validate(&response("101 Switching Protocols", &accept, "")), Ok(None));
+        assert_eq!(
+            handshake.validate(&response("101 Switching Protocols", &accept, "\r\nSec-WebSocket-Protocol: superchat")),
+            Ok(Some("superchat".to_string()))
+        );
+        for bad in [
+            response("200 OK", &accept, ""),
+            response("101 Switching Protocols", "bm9wZQ==", ""),
+            response("101 Switching Protocols", &accept, "\r\nSec-WebSocket-Protocol: other"),
+            response("101 Switching Protocols", &accept, "\r\nSec-WebSocket-Extensions: permessage-deflate"),
+        ] {
+            assert!(matches!(handshake.validate(&bad), Err(WebSocketError::HandshakeFailed(_))));
+        }
+    }
+}
@@ -0,0 +1,118 @@
+// Idle task queue - work put off until the browser has spare time
+//
+// Tasks wait, oldest first, for an idle period and run while it has time
+// left. A task given a timeout runs once the timeout passes even if no idle
+// period came, so deferred work is not starved by a busy page.
+
+use super::scheduler::IdleDeadline;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Identifies a posted task, for cancelling it
+pub type IdleTaskHandle = u64;
+
+#[derive(Debug, Clone)]
+struct IdleTask<T> {
+    handle: IdleTaskHandle,
+    task: T,
+    timeout: Option<Instant>,
+}
+
+/// Tasks waiting for idle time
+#[derive(Debug, Clone)]
+pub struct IdleTaskQueue<T> {
+    tasks: VecDeque<IdleTask<T>>,
+    next_handle: IdleTaskHandle,
+}
+
+impl<T> IdleTaskQueue<T> {
+    pub fn new() -> Self {
+        Self { tasks: VecDeque::new(), next_handle: 1 }
+    }
+
+    /// Post a task to run in an idle period
+    pub fn post(&mut self, task: T) -> IdleTaskHandle {
+        self.push(task, None)
+    }
+
+    /// Post a task that runs after `timeout` from `now` if no idle period came first
+    pub fn post_with_timeout(&mut self, task: T, timeout: Duration, now: Instant) -> IdleTaskHandle {
+        self.push(task, Some(now + timeout))
+    }
+
+    fn push(&mut self, task: T, timeout: Option<Instant>) -> IdleTaskHandle {
+        let handle = self.next_handle;
+        self.next_handle += 1;
+        self.tasks.push_back(IdleTask { handle, task, timeout });
+        handle
+    }
+
+    /// Drop a task that has yet to run, returning whether it was waiting
+    pub fn cancel(&mut self, handle: IdleTaskHandle) -> bool {
+        let before = self.tasks.len();
+        self.tasks.retain(|task| task.handle != handle);
+        self.tasks.len() != before
+    }
+
+    /// Is a task like `matches` waiting
+    pub fn contains(&self, matches: impl Fn(&T) -> bool) -> bool {
+        self.tasks.iter().any(|task| matches(&task.task))
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// The next task to run at `now`, with whether its timeout passed
+    ///
+    /// Tasks whose timeout passed come first, and run without idle time;
+    /// otherwise the oldest task runs if `deadline` leaves time for it.
+    pub fn next(&mut self, deadline: Option<&IdleDeadline>, now: Instant) -> Option<(T, bool)> {
+        let timed_out = self.tasks.iter().position(|task| task.timeout.is_some_and(|timeout| timeout <= now));
+        if let Some(index) = timed_out {
+            return self.tasks.remove(index).map(|task| (task.task, true));
+        }
+        if deadline?.time_remaining(now).is_zero() {
+            return None;
+        }
+        self.tasks.pop_front().map(|task| (task.task, false))
+    }
+}
+
+impl<T> Default for IdleTaskQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tasks_run_in_idle_time_or_on_timeout() {
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        let mut queue = IdleTaskQueue::new();
+        queue.post("evict");
+        let cancelled = queue.post("cancelled");
+        queue.post_with_timeout("urgent", ms(100), start);
+        assert!(queue.cancel(cancelled));
+        assert!(!queue.cancel(cancelled));
+        assert!(queue.contains(|task| *task == "urgent"));
+
+        // No idle time: only timed out tasks run
+        assert_eq!(queue.next(None, start + ms(50)), None);
+        let spent = IdleDeadline { deadline: start + ms(150) };
+        assert_eq!(queue.next(Some(&spent), start + ms(150)), Some(("urgent", true)));
+        assert_eq!(queue.next(Some(&spent), start + ms(150)), None);
+
+        let idle = IdleDeadline { deadline: start + ms(200) };
+        assert_eq!(queue.next(Some(&idle), start + ms(160)), Some(("evict", false)));
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,450 @@
+// Multiple top-level windows sharing one event loop
+//
+// Each window gets its own GPU process, which owns the window's surface and
+// paints the frames sent to it. The embedder keeps any
+// shared state (caches, storage) in its callback and routes events by
+// window key. Every window is exposed to screen readers through an
+// AccessKit adapter. Redraws go through the frame scheduler, so windows
+// draw on vsync and the loop sleeps while none has anything to draw.
+
+use super::scheduler::{BeginFrame, FrameReport, FrameScheduler, FrameStats, IdleDeadline};
+use super::{WindowConfig, WindowError};
+use crate::accessibility::WINDOW_NODE_ID;
+use crate::multiprocess::GpuProcess;
+use accesskit::{ActionHandler, ActionRequest, NodeBuilder, NodeClassSet, NodeId, Role, Tree, TreeUpdate};
+use accesskit_winit::Adapter;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use winit::{
+    dpi::PhysicalSize,
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop, EventLoopWindowTarget},
+    window::{CursorIcon, Window as WinitWindow, WindowBuilder, WindowId},
+};
+
+/// Identifies a window opened through the window manager
+///
+/// Keys are assigned when a window is requested, before the OS window
+/// exists, so callers can track windows they asked for.
+pub type WindowKey = u64;
+
+/// How often the loop wakes while no frame is wanted, to poll background work
+const IDLE_POLL: Duration = Duration::from_millis(250);
+
+/// Lifecycle and input events delivered to the window manager callback
+#[derive(Debug, Clone)]
+pub enum ManagedEvent {
+    /// The window and its GPU process were created
+    Opened,
+    /// An event from the OS window
+    Window(WindowEvent),
+    /// An assistive technology asked for an action (focus, click, scroll)
+    Accessibility(ActionRequest),
+    /// A frame has begun: the window should draw
+    BeginFrame(BeginFrame),
+    /// No frame is due; background work can be polled
+    Idle,
+    /// Spare time before the next frame, which the window asked for
+    IdlePeriod(IdleDeadline),
+    /// The window is about to be destroyed
+    Closed,
+}
+
+/// Accessibility action requests, queued from the platform adapter's thread
+type ActionQueue = Arc<Mutex<Vec<(WindowKey, ActionRequest)>>>;
+
+/// Queues action requests for one window until the event loop runs
+struct QueuedActions {
+    key: WindowKey,
+    queue: ActionQueue,
+}
+
+impl ActionHandler for QueuedActions {
+    fn do_action(&mut self, request: ActionRequest) {
+        if let Ok(mut queue) = self.queue.lock() {
+            queue.push((self.key, request));
+        }
+    }
+}
+
+/// Opens, closes and addresses windows from inside the event loop
+pub struct WindowControl {
+    next_key: WindowKey,
+    windows: HashMap<WindowKey, Arc<WinitWindow>>,
+    adapters: HashMap<WindowKey, Adapter>,
+    actions: ActionQueue,
+    pending_open: Vec<(WindowKey, WindowConfig)>,
+    pending_close: Vec<WindowKey>,
+    exit_requested: bool,
+    scheduler: FrameScheduler,
+    /// Frames begun whose redraw the window has yet to deliver
+    begun: HashMap<WindowKey, BeginFrame>,
+}
+
+impl WindowControl {
+    fn new() -> Self {
+        Self {
+            next_key: 1,
+            windows: HashMap::new(),
+            adapters: HashMap::new(),
+            actions: Arc::new(Mutex::new(Vec::new())),
+            pending_open: Vec::new(),
+            pending_close: Vec::new(),
+            exit_requested: false,
+            scheduler: FrameScheduler::new(Instant::now()),
+            begun: HashMap::new(),
+        }
+    }
+
+    /// Request a new window; it opens after the current event is handled
+    pub fn open_window(&mut self, config: WindowConfig) -> WindowKey {
+        let key = self.next_key;
+        self.next_key += 1;
+        self.pending_open.push((key, config));
+        key
+    }
+
+    /// Request that a window close
+    pub fn close_window(&mut self, key: WindowKey) {
+        // A window that never opened is simply dropped
+        let before = self.pending_open.len();
+        self.pending_open.retain(|(k, _)| *k != key);
+        if self.pending_open.len() == before && !self.pending_close.contains(&key) {
+            self.pending_close.push(key);
+        }
+    }
+
+    /// Close every window and stop the event loop
+    pub fn exit(&mut self) {
+        self.exit_requested = true;
+    }
+
+    /// Get an open window
+    pub fn window(&self, key: WindowKey) -> Option<&Arc<WinitWindow>> {
+        self.windows.get(&key)
+    }
+
+    /// Keys of open windows
+    pub fn keys(&self) -> Vec<WindowKey> {
+        let mut keys: Vec<WindowKey> = self.windows.keys().copied().collect();
+        keys.sort_unstable();
+        keys
+    }
+
+    /// Number of open and requested windows
+    pub fn len(&self) -> usize {
+        self.windows.len() + self.pending_open.len()
+    }
+
+    /// Are there no open or requested windows
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Ask for a frame of one window, drawn on the next vsync
+    pub fn request_redraw(&mut self, key: WindowKey) {
+        if self.windows.contains_key(&key) {
+            self.scheduler.request_frame(key, Instant::now());
+        }
+    }
+
+    /// Ask for spare time for one window, given before its next frame
+    pub fn request_idle_period(&mut self, key: WindowKey) {
+        if self.windows.contains_key(&key) {
+            self.scheduler.request_idle_period(key);
+        }
+    }
+
+    /// Account a drawn frame against its budget
+    pub fn record_frame(&mut self, report: &FrameReport) {
+        self.scheduler.record(report);
+    }
+
+    /// Frames drawn, over budget and skipped so far
+    pub fn frame_stats(&self) -> FrameStats {
+        self.scheduler.stats()
+    }
+
+    /// Change the mouse cursor shown over a window
+    pub fn set_cursor_icon(&self, key: WindowKey, icon: CursorIcon) {
+        if let Some(window) = self.windows.get(&key) {
+            window.set_cursor_icon(icon);
+        }
+    }
+
+    /// Change a window's title
+    pub fn set_title(&self, key: WindowKey, title: &str) {
+        if let Some(window) = self.windows.get(&key) {
+            window.set_title(title);
+        }
+    }
+
+    /// Send an accessibility tree update for a window
+    ///
+    /// The update is dropped while no assistive technology is listening;
+    /// the next full tree is requested when one connects.
+    pub fn update_accessibility(&self, key: WindowKey, update: impl FnOnce() -> TreeUpdate) {
+        if let Some(adapter) = self.adapters.get(&key) {
+            adapter.update_if_active(update);
+        }
+    }
+
+    fn take_actions(&self) -> Vec<(WindowKey, ActionRequest)> {
+        self.actions.lock().map(|mut queue| std::mem::take(&mut *queue)).unwrap_or_default()
+    }
+
+    fn key_for(&self, id: WindowId) -> Option<WindowKey> {
+        self.windows.iter().find(|(_, w)| w.id() == id).map(|(k, _)| *k)
+    }
+}
+
+/// Runs several top-level windows, each with its own GPU process
+pub struct WindowManager {
+    event_loop: Option<EventLoop<()>>,
+    control: WindowControl,
+}
+
+impl WindowManager {
+    /// Create a window manager with no windows
+    pub fn new() -> Result<Self, WindowError> {
+        let event_loop = EventLoop::new().map_err(|e| WindowError::Creation(e.to_string()))?;
+        Ok(Self {
+            event_loop: Some(event_loop),
+            control: WindowControl::new(),
+        })
+    }
+
+    /// Request a window to open when the event loop starts
+    pub fn open_window(&mut self, config: WindowConfig) -> WindowKey {
+        self.control.open_window(config)
+    }
+
+    /// Run the event loop until every window has closed
+    ///
+    /// The callback receives the window control, the window's key and
+    /// GPU process, and the event. Returning false closes that window; a
+    /// `CloseRequested` event closes the window once the callback returns.
+    pub fn run<F>(mut self, mut callback: F) -> Result<(), WindowError>
+    where
+        F: FnMut(&mut WindowControl, WindowKey, &mut GpuProcess, ManagedEvent) -> bool + 'static,
+    {
+        let event_loop = self.event_loop.take()
+            .ok_or(WindowError::EventLoop("Event loop already consumed".to_string()))?;
+        let mut control = self.control;
+        let mut gpus: HashMap<WindowKey, GpuProcess> = HashMap::new();
+        let mut next_idle = Instant::now() + IDLE_POLL;
+
+        event_loop
+            .run(move |event, target| {
+                match event {
+                    Event::WindowEvent { event, window_id } => {
+                        let Some(key) = control.key_for(window_id) else {
+                            return;
+                        };
+                        let close = matches!(event, WindowEvent::CloseRequested);
+                        if let (Some(adapter), Some(window)) = (control.adapters.get(&key), control.windows.get(&key)) {
+                            adapter.process_event(window, &event);
+                        }
+                        if let Some(gpu) = gpus.get_mut(&key) {
+                            if let WindowEvent::ScaleFactorChanged { scale_factor, .. } = event {
+                                let (width, height) = gpu.size();
+                                gpu.resize(width, height, scale_factor as f32);
+                            }
+                            // Redraws begin a frame, scheduled or asked for by the system
+                            let event = match event {
+                                WindowEvent::RedrawRequested => {
+                                    let begun = control.begun.remove(&key);
+                                    let now = Instant::now();
+                                    ManagedEvent::BeginFrame(
+                                        begun.unwrap_or_else(|| control.scheduler.unscheduled_frame(key, now)),
+                                    )
+                                }
+                                event => ManagedEvent::Window(event),
+                            };
+                            if !callback(&mut control, key, gpu, event) || close {
+                                control.close_window(key);
+                            }
+                        }
+                    }
+                    Event::AboutToWait => {
+                        for (key, request) in control.take_actions() {
+                            if let Some(gpu) = gpus.get_mut(&key) {
+                                callback(&mut control, key, gpu, ManagedEvent::Accessibility(request));
+                            }
+                        }
+                        let now = Instant::now();
+                        if control.scheduler.wake_time().is_none() && now >= next_idle {
+                            for key in control.keys() {
+                                if let Some(gpu) = gpus.get_mut(&key) {
+                                    callback(&mut control, key, gpu, ManagedEvent::Idle);
+                                }
+                            }
+                            next_idle = now + IDLE_POLL;
+                        }
+                        // Idle periods never hold up a frame waiting to be drawn
+                        if control.begun.is_empty() {
+                            for (key, deadline) in control.scheduler.idle_periods(now) {
+                                if let Some(gpu) = gpus.get_mut(&key) {
+                                    callback(&mut control, key, gpu, ManagedEvent::IdlePeriod(deadline));
+                                }
+                            }
+                        }
+                        let now = Instant::now();
+                        for (key, frame) in control.scheduler.begin_frames(now) {
+                            if let Some(window) = control.windows.get(&key) {
+                                control.begun.insert(key, frame);
+                                window.request_redraw();
+                            }
+                        }
+                        // Sleep until the next vsync a frame is wanted on, or the next idle poll;
+                        // idle work asked for meanwhile waits no longer than a frame
+                        let idle_wake = control.scheduler.wants_idle().then(|| now + control.scheduler.interval());
+                        let wake = control.scheduler.wake_time().or(idle_wake).unwrap_or(next_idle);
+                        target.set_control_flow(ControlFlow::WaitUntil(wake));
+                    }
+                    _ => {}
+                }
+
+                Self::apply_requests(&mut control, &mut gpus, target, &mut callback);
+                if control.is_empty() {
+                    target.exit();
+                }
+            })
+            .map_err(|e| WindowError::EventLoop(e.to_string()))
+    }
+
+    /// Open and close the windows requested while handling an event
+    fn apply_requests<F>(
+        control: &mut WindowControl,
+        gpus: &mut HashMap<WindowKey, GpuProcess>,
+        target: &EventLoopWindowTarget<()>,
+        callback: &mut F,
+    ) where
+        F: FnMut(&mut WindowControl, WindowKey, &mut GpuProcess, ManagedEvent) -> bool,
+    {
+        if control.exit_requested {
+            control.pending_open.clear();
+            control.pending_close = control.keys();
+        }
+
+        // Callbacks may request more windows, so repeat until settled
+        while !control.pending_open.is_empty() || !control.pending_close.is_empty() {
+            for (key, config) in std::mem::take(&mut control.pending_open) {
+                match Self::create(target, &config, key, &control.actions) {
+                    Ok((window, adapter)) => {
+                        // Frames follow the refresh rate of the display the window opened on
+                        let refresh = window.current_monitor().and_then(|monitor| monitor.refresh_rate_millihertz());
+                        if let Some(millihertz) = refresh {
+                            control.scheduler.set_refresh_rate(millihertz as f64 / 1000.0);
+                        }
+                        let mut gpu = GpuProcess::for_window(window.clone());
+                        control.windows.insert(key, window);
+                        control.adapters.insert(key, adapter);
+                        control.request_redraw(key);
+                        callback(control, key, &mut gpu, ManagedEvent::Opened);
+                        gpus.insert(key, gpu);
+                    }
+                    Err(e) => eprintln!("Failed to open window: {}", e),
+                }
+            }
+
+            for key in std::mem::take(&mut control.pending_close) {
+                // The GPU process stops before the window it paints is destroyed
+                if let Some(mut gpu) = gpus.remove(&key) {
+                    callback(control, key, &mut gpu, ManagedEvent::Closed);
+                }
+                // Dropping the last handle destroys the OS window
+                control.adapters.remove(&key);
+                control.windows.remove(&key);
+                control.scheduler.remove(key);
+                control.begun.remove(&key);
+            }
+        }
+    }
+
+    fn create(
+        target: &EventLoopWindowTarget<()>,
+        config: &WindowConfig,
+        key: WindowKey,
+        actions: &ActionQueue,
+    ) -> Result<(Arc<WinitWindow>, Adapter), WindowError> {
+        // The accessibility adapter must be attached before the window is shown
+        let window = WindowBuilder::new()
+            .with_title(&config.title)
+            .with_inner_size(PhysicalSize::new(config.width, config.height))
+            .with_resizable(config.resizable)
+            .with_visible(false)
+            .build(target)
+            .map_err(|e| WindowError::Creation(e.to_string()))?;
+        let title = config.title.clone();
+        let handler = QueuedActions { key, queue: actions.clone() };
+        let adapter = Adapter::with_action_handler(&window, move || initial_tree(&title), Box::new(handler));
+        window.set_visible(true);
+        Ok((Arc::new(window), adapter))
+    }
+}
+
+/// Tree announced before the embedder sends its own: an empty window
+fn initial_tree(title: &str) -> TreeUpdate {
+    let mut window = NodeBuilder::new(Role::Window);
+    window.set_name(title);
+    TreeUpdate {
+        nodes: vec![(NodeId(WINDOW_NODE_ID), window.build(&mut NodeClassSet::new()))],
+        tree: Some(Tree::new(NodeId(WINDOW_NODE_ID))),
+        focus: NodeId(WINDOW_NODE_ID),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_keys_are_assigned_on_request() {
+        let mut control = WindowControl::new();
+        assert!(control.is_empty());
+
+        let first = control.open_window(WindowConfig::default());
+        let second = control.open_window(WindowConfig::default());
+        assert_ne!(first, second);
+        assert_eq!(control.len(), 2);
+        // Not opened until the event loop creates them
+        assert!(control.window(first).is_none());
+        assert!(control.keys().is_empty());
+    }
+
+    #[test]
+    fn test_closing_a_pending_window_cancels_it() {
+        let mut control = WindowControl::new();
+        let key = control.open_window(WindowConfig::default());
+        control.close_window(key);
+        assert!(control.is_empty());
+        assert!(control.pending_close.is_empty());
+
+        // Closing an open window is deferred and not repeated
+        control.close_window(7);
+        control.close_window(7);
+        assert_eq!(control.pending_close, vec![7]);
+    }
+
+    #[test]
+    fn test_accessibility_actions_are_queued_per_window() {
+        let control = WindowControl::new();
+        let mut handler = QueuedActions { key: 3, queue: control.actions.clone() };
+        handler.do_action(ActionRequest {
+            action: accesskit::Action::Focus,
+            target: NodeId(42),
+            data: None,
+        });
+
+        let actions = control.take_actions();
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].0, 3);
+        assert_eq!(actions[0].1.target, NodeId(42));
+        assert!(control.take_actions().is_empty());
+
+        let tree = initial_tree("Browser");
+        assert_eq!(tree.nodes[0].1.name(), Some("Browser"));
+    }
+}
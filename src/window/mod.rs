@@ -1,15 +1,24 @@
 pub mod scroll;
+mod manager;
+mod scheduler;
+mod idle;
 
 use winit::{
     dpi::PhysicalSize,
     event::{Event, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
-    window::{Window as WinitWindow, WindowBuilder},
+    window::{CursorIcon, Window as WinitWindow, WindowBuilder},
 };
 use std::sync::Arc;
 use crate::renderer::Renderer;
 
-pub use scroll::ScrollState;
+pub use scroll::{ScrollAlign, ScrollBehavior, ScrollState};
+pub use manager::{ManagedEvent, WindowControl, WindowKey, WindowManager};
+pub use scheduler::{
+    BeginFrame, FramePhase, FrameReport, FrameScheduler, FrameStats, FrameTimer, IdleDeadline, DEFAULT_REFRESH_RATE,
+    MAX_IDLE_PERIOD,
+};
+pub use idle::{IdleTaskHandle, IdleTaskQueue};
 
 /// Application window with integrated renderer
 pub struct Window {
@@ -65,14 +74,27 @@ impl Window {
         self.window.inner_size()
     }
 
+    /// Device pixels per CSS pixel for the monitor the window is on
+    pub fn scale_factor(&self) -> f64 {
+        self.window.scale_factor()
+    }
+
     /// Request a redraw of the window
     pub fn request_redraw(&self) {
         self.window.request_redraw();
     }
 
+    /// Change the mouse cursor shown over the window
+    pub fn set_cursor_icon(&self, icon: CursorIcon) {
+        self.window.set_cursor_icon(icon);
+    }
+
     /// Run the event loop with renderer and callback
     /// 
-    /// The callback receives the renderer and window events
+    /// The callback receives the renderer and window events. It paints in
+    /// this process, for programs that exercise the renderer directly; the
+    /// browser's windows, opened through `WindowManager`, paint through a
+    /// GPU process.
     pub fn run_with_renderer<F>(mut self, mut callback: F) -> Result<(), WindowError>
     where
         F: FnMut(&mut Renderer, WindowEvent) -> bool + 'static,
@@ -97,6 +119,10 @@ impl Window {
                     Event::WindowEvent { event, window_id: event_window_id } 
                         if event_window_id == window_id => 
                     {
+                        if let WindowEvent::ScaleFactorChanged { scale_factor, .. } = event {
+                            renderer.set_scale_factor(scale_factor);
+                        }
+
                         // Pass event to callback; if it returns false, exit
                         if !callback(&mut renderer, event.clone()) {
                             target.exit();
@@ -119,6 +145,15 @@ impl Window {
     }
 }
 
+/// Convert a window size in device pixels to CSS pixels
+///
+/// Layout works in CSS pixels; the surface and input events use device
+/// pixels, which differ on HiDPI displays.
+pub fn css_size(size: PhysicalSize<u32>, scale_factor: f64) -> (f32, f32) {
+    let logical = size.to_logical::<f32>(scale_factor);
+    (logical.width, logical.height)
+}
+
 /// Window-related errors
 #[derive(Debug)]
 pub enum WindowError {
@@ -168,4 +203,10 @@ mod tests {
         assert_eq!(config.height, 600);
         assert!(!config.resizable);
     }
+
+    #[test]
+    fn test_css_size_divides_by_scale_factor() {
+        assert_eq!(css_size(PhysicalSize::new(2048, 1536), 2.0), (1024.0, 768.0));
+        assert_eq!(css_size(PhysicalSize::new(1024, 768), 1.0), (1024.0, 768.0));
+    }
 }
@@ -0,0 +1,425 @@
+// Frame scheduler - when windows draw, and how long their frames take
+//
+// Windows ask for a frame instead of redrawing straight away. Frames begin
+// on the display's vsync, so several requests in one refresh interval make
+// one frame, and no frame begins while nothing asked for one, letting the
+// event loop sleep. Each frame runs its work in fixed phases whose times
+// are accounted against the refresh interval. Time left before the next
+// frame is handed out as idle periods to windows that asked for one.
+
+use super::WindowKey;
+use std::collections::BTreeSet;
+use std::time::{Duration, Instant};
+
+/// Refresh rate assumed until the display reports its own
+pub const DEFAULT_REFRESH_RATE: f64 = 60.0;
+/// Longest idle period, so input arriving during one is not kept waiting
+pub const MAX_IDLE_PERIOD: Duration = Duration::from_millis(50);
+
+/// Stages of a frame, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FramePhase {
+    /// Coalesced input and other work waiting for the frame
+    Input,
+    /// `requestAnimationFrame` callbacks and animations
+    AnimationFrame,
+    Style,
+    Layout,
+    Paint,
+    /// Drawing the painted layers to the window
+    Composite,
+}
+
+impl FramePhase {
+    pub const ALL: [FramePhase; 6] = [
+        FramePhase::Input,
+        FramePhase::AnimationFrame,
+        FramePhase::Style,
+        FramePhase::Layout,
+        FramePhase::Paint,
+        FramePhase::Composite,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FramePhase::Input => "Input",
+            FramePhase::AnimationFrame => "Animation frame",
+            FramePhase::Style => "Style",
+            FramePhase::Layout => "Layout",
+            FramePhase::Paint => "Paint",
+            FramePhase::Composite => "Composite",
+        }
+    }
+}
+
+/// Signal that a window's frame has begun
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BeginFrame {
+    /// Counts frames across every window
+    pub sequence: u64,
+    /// The vsync the frame is aligned to; animations sample this time
+    pub frame_time: Instant,
+    /// When the frame should be finished to make the next vsync
+    pub deadline: Instant,
+}
+
+impl BeginFrame {
+    /// Time the frame has to do its work
+    pub fn budget(&self) -> Duration {
+        self.deadline.duration_since(self.frame_time)
+    }
+}
+
+/// The end of spare time given to idle work
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdleDeadline {
+    pub deadline: Instant,
+}
+
+impl IdleDeadline {
+    /// Time left at `now`
+    pub fn time_remaining(&self, now: Instant) -> Duration {
+        self.deadline.saturating_duration_since(now)
+    }
+}
+
+/// How long each phase of a frame took
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameReport {
+    pub frame: BeginFrame,
+    /// Time spent in each phase that ran, in phase order
+    pub phases: Vec<(FramePhase, Duration)>,
+    /// When the frame's work finished
+    pub finished: Instant,
+}
+
+impl FrameReport {
+    /// Time from the vsync to the end of the frame's work
+    pub fn total(&self) -> Duration {
+        self.finished.saturating_duration_since(self.frame.frame_time)
+    }
+
+    /// Time spent working, leaving out any wait for the frame to start
+    pub fn work(&self) -> Duration {
+        self.phases.iter().map(|(_, duration)| *duration).sum()
+    }
+
+    pub fn phase(&self, phase: FramePhase) -> Duration {
+        self.phases.iter().filter(|(p, _)| *p == phase).map(|(_, duration)| *duration).sum()
+    }
+
+    /// Did the frame miss its deadline
+    pub fn is_over_budget(&self) -> bool {
+        self.finished > self.frame.deadline
+    }
+}
+
+/// Times the phases of one frame as they run
+#[derive(Debug, Clone)]
+pub struct FrameTimer {
+    frame: BeginFrame,
+    current: Option<(FramePhase, Instant)>,
+    phases: Vec<(FramePhase, Duration)>,
+}
+
+impl FrameTimer {
+    pub fn begin(frame: BeginFrame) -> Self {
+        Self { frame, current: None, phases: Vec::new() }
+    }
+
+    /// Start `phase`, ending the one before it
+    pub fn enter(&mut self, phase: FramePhase, now: Instant) {
+        self.end_phase(now);
+        self.current = Some((phase, now));
+    }
+
+    fn end_phase(&mut self, now: Instant) {
+        if let Some((phase, start)) = self.current.take() {
+            self.phases.push((phase, now.saturating_duration_since(start)));
+        }
+    }
+
+    /// End the last phase and report the frame
+    pub fn finish(mut self, now: Instant) -> FrameReport {
+        self.end_phase(now);
+        FrameReport { frame: self.frame, phases: self.phases, finished: now }
+    }
+}
+
+/// Frame counts for budget accounting
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Frames drawn
+    pub frames: u64,
+    /// Frames that missed their deadline
+    pub over_budget: u64,
+    /// Vsyncs that passed with no frame to draw
+    pub idle_skipped: u64,
+}
+
+/// Decides when windows begin frames
+#[derive(Debug, Clone)]
+pub struct FrameScheduler {
+    interval: Duration,
+    /// A vsync every later one is a whole number of intervals from
+    timebase: Instant,
+    /// Windows waiting for their next frame
+    pending: BTreeSet<WindowKey>,
+    /// Windows waiting for an idle period
+    idle: BTreeSet<WindowKey>,
+    /// The vsync the pending windows' frame begins on
+    due: Option<Instant>,
+    /// The vsync the last frame began on
+    last_frame: Option<Instant>,
+    sequence: u64,
+    stats: FrameStats,
+}
+
+impl FrameScheduler {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            interval: refresh_interval(DEFAULT_REFRESH_RATE),
+            timebase: now,
+            pending: BTreeSet::new(),
+            idle: BTreeSet::new(),
+            due: None,
+            last_frame: None,
+            sequence: 0,
+            stats: FrameStats::default(),
+        }
+    }
+
+    /// Follow the display's refresh rate, in hertz
+    pub fn set_refresh_rate(&mut self, hz: f64) {
+        if hz > 0.0 {
+            self.interval = refresh_interval(hz);
+        }
+    }
+
+    /// Time between vsyncs
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// The first vsync at or after `now`
+    pub fn next_vsync(&self, now: Instant) -> Instant {
+        let since = now.saturating_duration_since(self.timebase).as_nanos();
+        self.vsync(since.div_ceil(self.interval.as_nanos()))
+    }
+
+    /// The last vsync at or before `now`
+    pub fn last_vsync(&self, now: Instant) -> Instant {
+        let since = now.saturating_duration_since(self.timebase).as_nanos();
+        self.vsync(since / self.interval.as_nanos())
+    }
+
+    fn vsync(&self, intervals: u128) -> Instant {
+        self.timebase + Duration::from_nanos((intervals * self.interval.as_nanos()) as u64)
+    }
+
+    /// Ask for a frame for `key` on the next vsync
+    pub fn request_frame(&mut self, key: WindowKey, now: Instant) {
+        self.pending.insert(key);
+        if self.due.is_none() {
+            // One frame per vsync, however soon after the last it is asked for
+            let earliest = self.last_frame.map_or(now, |last| now.max(last + self.interval));
+            self.due = Some(self.next_vsync(earliest));
+        }
+    }
+
+    /// Is a frame waiting for `key`
+    pub fn is_pending(&self, key: WindowKey) -> bool {
+        self.pending.contains(&key)
+    }
+
+    /// Ask for an idle period for `key`, given when no frame needs the time
+    pub fn request_idle_period(&mut self, key: WindowKey) {
+        self.idle.insert(key);
+    }
+
+    /// Is a window waiting for an idle period
+    pub fn wants_idle(&self) -> bool {
+        !self.idle.is_empty()
+    }
+
+    /// Begin an idle period at `now` for each waiting window, if there is
+    /// time before the next frame
+    ///
+    /// The period ends at the next frame's vsync, and lasts no longer than
+    /// `MAX_IDLE_PERIOD` while no frame is wanted.
+    pub fn idle_periods(&mut self, now: Instant) -> Vec<(WindowKey, IdleDeadline)> {
+        let deadline = self.due.map_or(now + MAX_IDLE_PERIOD, |due| due.min(now + MAX_IDLE_PERIOD));
+        if deadline <= now {
+            return Vec::new();
+        }
+        std::mem::take(&mut self.idle).into_iter().map(|key| (key, IdleDeadline { deadline })).collect()
+    }
+
+    /// Stop scheduling frames for a closed window
+    pub fn remove(&mut self, key: WindowKey) {
+        self.idle.remove(&key);
+        self.pending.remove(&key);
+        if self.pending.is_empty() {
+            self.due = None;
+        }
+    }
+
+    /// When the event loop must wake to begin the next frame, if any is wanted
+    pub fn wake_time(&self) -> Option<Instant> {
+        self.due
+    }
+
+    /// Begin the frames due by `now`, one per waiting window
+    pub fn begin_frames(&mut self, now: Instant) -> Vec<(WindowKey, BeginFrame)> {
+        if self.due.filter(|due| *due <= now).is_none() {
+            return Vec::new();
+        }
+        // The latest vsync, should the loop have woken late
+        let frame_time = self.last_vsync(now);
+        self.due = None;
+        self.note_idle_vsyncs(frame_time);
+        self.last_frame = Some(frame_time);
+        let deadline = frame_time + self.interval;
+        std::mem::take(&mut self.pending)
+            .into_iter()
+            .map(|key| {
+                self.sequence += 1;
+                (key, BeginFrame { sequence: self.sequence, frame_time, deadline })
+            })
+            .collect()
+    }
+
+    /// A frame for a redraw the system asked for, outside the schedule
+    pub fn unscheduled_frame(&mut self, key: WindowKey, now: Instant) -> BeginFrame {
+        self.pending.remove(&key);
+        if self.pending.is_empty() {
+            self.due = None;
+        }
+        self.sequence += 1;
+        BeginFrame { sequence: self.sequence, frame_time: now, deadline: now + self.interval }
+    }
+
+    /// Account a finished frame against its budget
+    pub fn record(&mut self, report: &FrameReport) {
+        self.stats.frames += 1;
+        if report.is_over_budget() {
+            self.stats.over_budget += 1;
+        }
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        self.stats
+    }
+
+    fn note_idle_vsyncs(&mut self, frame_time: Instant) {
+        if let Some(last) = self.last_frame {
+            let between = frame_time.saturating_duration_since(last).as_nanos() / self.interval.as_nanos();
+            self.stats.idle_skipped += (between as u64).saturating_sub(1);
+        }
+    }
+}
+
+/// Time between vsyncs at `hz`
+fn refresh_interval(hz: f64) -> Duration {
+    Duration::from_nanos((1e9 / hz).round() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_align_to_vsync() {
+        let start = Instant::now();
+        let mut scheduler = FrameScheduler::new(start);
+        scheduler.set_refresh_rate(100.0);
+        let ms = Duration::from_millis;
+        assert_eq!(scheduler.interval(), ms(10));
+        // Nothing asked for a frame, so the loop can sleep
+        assert_eq!(scheduler.wake_time(), None);
+        assert!(scheduler.begin_frames(start + ms(50)).is_empty());
+
+        // Requests within one interval share the next vsync
+        scheduler.request_frame(2, start + ms(3));
+        scheduler.request_frame(1, start + ms(7));
+        assert!(scheduler.is_pending(1));
+        assert_eq!(scheduler.wake_time(), Some(start + ms(10)));
+        assert!(scheduler.begin_frames(start + ms(9)).is_empty());
+        let frames = scheduler.begin_frames(start + ms(10));
+        let keys: Vec<_> = frames.iter().map(|(key, frame)| (*key, frame.sequence)).collect();
+        assert_eq!(keys, [(1, 1), (2, 2)]);
+        assert_eq!(frames[0].1.frame_time, start + ms(10));
+        assert_eq!(frames[0].1.budget(), ms(10));
+        assert_eq!(scheduler.wake_time(), None);
+
+        // Asking again straight away waits for the following vsync
+        scheduler.request_frame(1, start + ms(10));
+        assert_eq!(scheduler.wake_time(), Some(start + ms(20)));
+
+        // Vsyncs passed while idle are skipped, not drawn
+        scheduler.remove(1);
+        assert_eq!(scheduler.wake_time(), None);
+        scheduler.request_frame(1, start + ms(52));
+        let frames = scheduler.begin_frames(start + ms(60));
+        assert_eq!(frames[0].1.frame_time, start + ms(60));
+        assert_eq!(scheduler.stats().idle_skipped, 4);
+    }
+
+    #[test]
+    fn test_idle_periods_end_before_frames() {
+        let start = Instant::now();
+        let mut scheduler = FrameScheduler::new(start);
+        scheduler.set_refresh_rate(100.0);
+        let ms = Duration::from_millis;
+        assert!(scheduler.idle_periods(start).is_empty());
+
+        // With no frame wanted the period is capped
+        scheduler.request_idle_period(1);
+        assert!(scheduler.wants_idle());
+        let periods = scheduler.idle_periods(start + ms(3));
+        assert_eq!(periods, [(1, IdleDeadline { deadline: start + ms(53) })]);
+        assert!(!scheduler.wants_idle());
+        assert_eq!(periods[0].1.time_remaining(start + ms(50)), ms(3));
+
+        // A waiting frame ends the period at its vsync
+        scheduler.request_idle_period(1);
+        scheduler.request_frame(2, start + ms(4));
+        assert_eq!(scheduler.idle_periods(start + ms(6))[0].1.deadline, start + ms(10));
+        // Nor is there a period once the frame is due
+        scheduler.request_idle_period(1);
+        assert!(scheduler.idle_periods(start + ms(10)).is_empty());
+        assert!(scheduler.wants_idle());
+        scheduler.remove(1);
+        assert!(!scheduler.wants_idle());
+    }
+
+    #[test]
+    fn test_frame_budget_accounting() {
+        let start = Instant::now();
+        let mut scheduler = FrameScheduler::new(start);
+        let ms = Duration::from_millis;
+        let frame = BeginFrame { sequence: 1, frame_time: start, deadline: start + ms(16) };
+        let mut timer = FrameTimer::begin(frame);
+        timer.enter(FramePhase::Input, start);
+        timer.enter(FramePhase::Layout, start + ms(2));
+        timer.enter(FramePhase::Paint, start + ms(12));
+        timer.enter(FramePhase::Composite, start + ms(15));
+        let report = timer.finish(start + ms(20));
+        assert_eq!(report.phases.iter().map(|(phase, _)| *phase).collect::<Vec<_>>(), [
+            FramePhase::Input,
+            FramePhase::Layout,
+            FramePhase::Paint,
+            FramePhase::Composite,
+        ]);
+        assert_eq!(report.phase(FramePhase::Layout), ms(10));
+        assert_eq!(report.phase(FramePhase::Style), Duration::ZERO);
+        assert_eq!(report.total(), ms(20));
+        assert_eq!(report.work(), ms(20));
+        assert!(report.is_over_budget());
+
+        scheduler.record(&report);
+        let quick = FrameTimer::begin(frame).finish(start + ms(5));
+        scheduler.record(&quick);
+        assert_eq!(scheduler.stats(), FrameStats { frames: 2, over_budget: 1, idle_skipped: 0 });
+    }
+}
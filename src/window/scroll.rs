@@ -1,3 +1,108 @@
+use crate::layout::Rect;
+use crate::style::StyledNode;
+use crate::css::Value;
+use winit::keyboard::{Key, NamedKey};
+
+/// Duration of a smooth scroll animation, in seconds
+const SMOOTH_SCROLL_DURATION: f32 = 0.25;
+/// Distance scrolled by an arrow key
+const LINE_SCROLL_STEP: f32 = 40.0;
+/// Fraction of the viewport scrolled by PageUp/PageDown/Space
+const PAGE_SCROLL_FRACTION: f32 = 0.875;
+/// Fraction of fling velocity kept after one second
+const FLING_FRICTION: f32 = 0.05;
+/// Fling stops below this speed (pixels per second)
+const MIN_FLING_VELOCITY: f32 = 20.0;
+
+/// How programmatic scrolls move the viewport (CSS `scroll-behavior`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScrollBehavior {
+    /// Use the page's `scroll-behavior`
+    #[default]
+    Auto,
+    Smooth,
+    Instant,
+}
+
+impl ScrollBehavior {
+    /// Parse a `scroll-behavior` / `behavior` value
+    pub fn from_str(s: &str) -> Self {
+        match s.to_lowercase().as_str() {
+            "smooth" => ScrollBehavior::Smooth,
+            "instant" => ScrollBehavior::Instant,
+            _ => ScrollBehavior::Auto,
+        }
+    }
+
+    /// Get the keyword for this behavior
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScrollBehavior::Auto => "auto",
+            ScrollBehavior::Smooth => "smooth",
+            ScrollBehavior::Instant => "instant",
+        }
+    }
+
+    /// Read `scroll-behavior` from a styled node (usually the root element)
+    pub fn from_style(node: &StyledNode) -> Self {
+        match node.value("scroll-behavior") {
+            Some(Value::Keyword(keyword)) => Self::from_str(keyword),
+            _ => ScrollBehavior::Auto,
+        }
+    }
+}
+
+/// Where an element is aligned when scrolled into view
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollAlign {
+    Start,
+    Center,
+    End,
+    /// Scroll as little as possible; no-op if already visible
+    Nearest,
+}
+
+impl ScrollAlign {
+    /// Parse a `block`/`inline` option value
+    pub fn from_str(s: &str) -> Self {
+        match s.to_lowercase().as_str() {
+            "center" => ScrollAlign::Center,
+            "end" => ScrollAlign::End,
+            "nearest" => ScrollAlign::Nearest,
+            _ => ScrollAlign::Start,
+        }
+    }
+}
+
+/// An in-progress eased scroll between two offsets
+#[derive(Debug, Clone, Copy)]
+struct ScrollAnimation {
+    from_x: f32,
+    from_y: f32,
+    to_x: f32,
+    to_y: f32,
+    elapsed: f32,
+}
+
+impl ScrollAnimation {
+    fn position(&self) -> (f32, f32) {
+        let t = ease_out_cubic(self.elapsed / SMOOTH_SCROLL_DURATION);
+        (
+            self.from_x + (self.to_x - self.from_x) * t,
+            self.from_y + (self.to_y - self.from_y) * t,
+        )
+    }
+
+    fn finished(&self) -> bool {
+        self.elapsed >= SMOOTH_SCROLL_DURATION
+    }
+}
+
+fn ease_out_cubic(t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    1.0 - (1.0 - t).powi(3)
+}
+
 /// Scrolling state management
 #[derive(Debug, Clone, Copy)]
 pub struct ScrollState {
@@ -10,6 +115,13 @@ pub struct ScrollState {
     /// Viewport size (width, height) in pixels
     pub viewport_width: f32,
     pub viewport_height: f32,
+    /// Page `scroll-behavior`, applied to programmatic scrolls
+    pub behavior: ScrollBehavior,
+    /// Smooth scroll in progress
+    animation: Option<ScrollAnimation>,
+    /// Kinetic fling velocity in pixels per second
+    velocity_x: f32,
+    velocity_y: f32,
 }
 
 impl Default for ScrollState {
@@ -21,6 +133,10 @@ impl Default for ScrollState {
             content_height: 0.0,
             viewport_width: 800.0,
             viewport_height: 600.0,
+            behavior: ScrollBehavior::Auto,
+            animation: None,
+            velocity_x: 0.0,
+            velocity_y: 0.0,
         }
     }
 }
@@ -51,6 +167,7 @@ impl ScrollState {
 
     /// Scroll by delta amount
     pub fn scroll_by(&mut self, delta_x: f32, delta_y: f32) {
+        self.stop();
         self.offset_x += delta_x;
         self.offset_y += delta_y;
         self.clamp_scroll();
@@ -58,11 +175,160 @@ impl ScrollState {
 
     /// Scroll to absolute position
     pub fn scroll_to(&mut self, x: f32, y: f32) {
+        self.stop();
         self.offset_x = x;
         self.offset_y = y;
         self.clamp_scroll();
     }
 
+    /// Animate to an absolute position
+    pub fn smooth_scroll_to(&mut self, x: f32, y: f32) {
+        let (to_x, to_y) = self.clamp_offset(x, y);
+        self.velocity_x = 0.0;
+        self.velocity_y = 0.0;
+        self.animation = if to_x == self.offset_x && to_y == self.offset_y {
+            None
+        } else {
+            Some(ScrollAnimation {
+                from_x: self.offset_x,
+                from_y: self.offset_y,
+                to_x,
+                to_y,
+                elapsed: 0.0,
+            })
+        };
+    }
+
+    /// Animate by a delta, accumulating onto any scroll already in progress
+    pub fn smooth_scroll_by(&mut self, delta_x: f32, delta_y: f32) {
+        let (x, y) = self.target();
+        self.smooth_scroll_to(x + delta_x, y + delta_y);
+    }
+
+    /// Programmatic scroll (`window.scrollTo`, `scrollIntoView`)
+    ///
+    /// `Auto` defers to the page's `scroll-behavior`.
+    pub fn scroll_to_with_behavior(&mut self, x: f32, y: f32, behavior: ScrollBehavior) {
+        let behavior = match behavior {
+            ScrollBehavior::Auto => self.behavior,
+            other => other,
+        };
+        match behavior {
+            ScrollBehavior::Smooth => self.smooth_scroll_to(x, y),
+            _ => self.scroll_to(x, y),
+        }
+    }
+
+    /// Scroll vertically so a page rectangle is visible (`scrollIntoView`)
+    pub fn scroll_rect_into_view(&mut self, rect: Rect, align: ScrollAlign, behavior: ScrollBehavior) {
+        let top = self.offset_y;
+        let bottom = top + self.viewport_height;
+        let y = match align {
+            ScrollAlign::Start => rect.y,
+            ScrollAlign::Center => rect.y + rect.height / 2.0 - self.viewport_height / 2.0,
+            ScrollAlign::End => rect.y + rect.height - self.viewport_height,
+            ScrollAlign::Nearest if rect.y < top => rect.y,
+            ScrollAlign::Nearest if rect.y + rect.height > bottom => {
+                rect.y + rect.height - self.viewport_height
+            }
+            ScrollAlign::Nearest => return,
+        };
+        self.scroll_to_with_behavior(self.offset_x, y, behavior);
+    }
+
+    /// Mouse wheel input; deltas are eased rather than applied instantly
+    pub fn wheel(&mut self, delta_x: f32, delta_y: f32) {
+        self.smooth_scroll_by(delta_x, delta_y);
+    }
+
+    /// Touchpad pixel-precise scroll; follows the fingers directly
+    pub fn touchpad_scroll(&mut self, delta_x: f32, delta_y: f32) {
+        self.scroll_by(delta_x, delta_y);
+    }
+
+    /// Start a kinetic fling (touchpad lift-off) with a velocity in pixels per second
+    pub fn fling(&mut self, velocity_x: f32, velocity_y: f32) {
+        self.animation = None;
+        self.velocity_x = velocity_x;
+        self.velocity_y = velocity_y;
+    }
+
+    /// Offset the viewport will end at once current motion settles
+    pub fn target(&self) -> (f32, f32) {
+        match self.animation {
+            Some(ref animation) => (animation.to_x, animation.to_y),
+            None => (self.offset_x, self.offset_y),
+        }
+    }
+
+    /// Is a smooth scroll or fling in progress
+    pub fn is_animating(&self) -> bool {
+        self.animation.is_some() || self.velocity_x != 0.0 || self.velocity_y != 0.0
+    }
+
+    /// Cancel any smooth scroll or fling, leaving the current offset
+    pub fn stop(&mut self) {
+        self.animation = None;
+        self.velocity_x = 0.0;
+        self.velocity_y = 0.0;
+    }
+
+    /// Advance animations by `dt` seconds; returns true while still animating
+    pub fn tick(&mut self, dt: f32) -> bool {
+        if let Some(mut animation) = self.animation {
+            animation.elapsed += dt;
+            let (x, y) = animation.position();
+            self.offset_x = x;
+            self.offset_y = y;
+            self.animation = if animation.finished() { None } else { Some(animation) };
+        } else if self.velocity_x != 0.0 || self.velocity_y != 0.0 {
+            let (before_x, before_y) = (self.offset_x, self.offset_y);
+            self.offset_x += self.velocity_x * dt;
+            self.offset_y += self.velocity_y * dt;
+            self.clamp_scroll();
+
+            let decay = FLING_FRICTION.powf(dt);
+            // Stop at rest or when hitting an edge
+            self.velocity_x = if self.offset_x == before_x { 0.0 } else { self.velocity_x * decay };
+            self.velocity_y = if self.offset_y == before_y { 0.0 } else { self.velocity_y * decay };
+            if self.velocity_x.abs() < MIN_FLING_VELOCITY {
+                self.velocity_x = 0.0;
+            }
+            if self.velocity_y.abs() < MIN_FLING_VELOCITY {
+                self.velocity_y = 0.0;
+            }
+        }
+        self.is_animating()
+    }
+
+    /// Keyboard scrolling: arrows, PageUp/PageDown, Home/End and Space
+    ///
+    /// Returns true if the key scrolls the page.
+    pub fn handle_key(&mut self, key: &Key, shift: bool) -> bool {
+        let page = self.viewport_height * PAGE_SCROLL_FRACTION;
+        match key {
+            Key::Named(NamedKey::ArrowDown) => self.smooth_scroll_by(0.0, LINE_SCROLL_STEP),
+            Key::Named(NamedKey::ArrowUp) => self.smooth_scroll_by(0.0, -LINE_SCROLL_STEP),
+            Key::Named(NamedKey::ArrowRight) => self.smooth_scroll_by(LINE_SCROLL_STEP, 0.0),
+            Key::Named(NamedKey::ArrowLeft) => self.smooth_scroll_by(-LINE_SCROLL_STEP, 0.0),
+            Key::Named(NamedKey::PageDown) => self.smooth_scroll_by(0.0, page),
+            Key::Named(NamedKey::PageUp) => self.smooth_scroll_by(0.0, -page),
+            Key::Named(NamedKey::Space) if shift => self.smooth_scroll_by(0.0, -page),
+            Key::Named(NamedKey::Space) => self.smooth_scroll_by(0.0, page),
+            Key::Named(NamedKey::Home) => self.smooth_scroll_to(self.offset_x, 0.0),
+            Key::Named(NamedKey::End) => self.smooth_scroll_to(self.offset_x, self.content_height),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Clamp an offset to the scrollable range
+    fn clamp_offset(&self, x: f32, y: f32) -> (f32, f32) {
+        let max_scroll_x = (self.content_width - self.viewport_width).max(0.0);
+        let max_scroll_y = (self.content_height - self.viewport_height).max(0.0);
+        (x.clamp(0.0, max_scroll_x), y.clamp(0.0, max_scroll_y))
+    }
+
     /// Clamp scroll to valid range
     fn clamp_scroll(&mut self) {
         // Calculate maximum scroll (content that extends beyond viewport)
@@ -84,6 +350,14 @@ impl ScrollState {
         self.content_height > self.viewport_height
     }
 
+    /// Get horizontal scroll percentage (0.0 to 1.0)
+    pub fn scroll_percentage_x(&self) -> f32 {
+        if !self.can_scroll_x() {
+            return 0.0;
+        }
+        self.offset_x / (self.content_width - self.viewport_width)
+    }
+
     /// Get scroll percentage (0.0 to 1.0)
     pub fn scroll_percentage_y(&self) -> f32 {
         if !self.can_scroll_y() {
@@ -148,4 +422,110 @@ mod tests {
         assert_eq!(x, 50.0);
         assert_eq!(y, 100.0); // 200 - 100 offset
     }
+
+    #[test]
+    fn test_smooth_scroll_eases_to_target() {
+        let mut state = ScrollState::new(800.0, 600.0);
+        state.set_content_size(800.0, 3000.0);
+
+        state.wheel(0.0, 100.0);
+        state.wheel(0.0, 100.0); // deltas accumulate
+        assert_eq!(state.target(), (0.0, 200.0));
+        assert_eq!(state.offset_y, 0.0);
+
+        assert!(state.tick(SMOOTH_SCROLL_DURATION / 2.0));
+        // Ease-out covers most of the distance in the first half
+        assert!(state.offset_y > 100.0 && state.offset_y < 200.0);
+
+        assert!(!state.tick(SMOOTH_SCROLL_DURATION));
+        assert_eq!(state.offset_y, 200.0);
+
+        // Targets are clamped to the scrollable range
+        state.smooth_scroll_to(0.0, 10_000.0);
+        assert_eq!(state.target(), (0.0, 2400.0));
+        state.scroll_to(0.0, 50.0); // instant scroll cancels the animation
+        assert!(!state.is_animating());
+    }
+
+    #[test]
+    fn test_fling_decays_and_stops_at_edge() {
+        let mut state = ScrollState::new(800.0, 600.0);
+        state.set_content_size(800.0, 1000.0);
+
+        state.fling(0.0, 1000.0);
+        assert!(state.tick(0.1));
+        let after_first = state.offset_y;
+        assert!(after_first > 0.0);
+
+        state.tick(0.1);
+        let second_step = state.offset_y - after_first;
+        assert!(second_step < after_first); // slowing down
+
+        while state.tick(0.1) {}
+        assert!(state.offset_y <= 400.0);
+
+        state.scroll_to(0.0, 10.0);
+        state.fling(0.0, -5000.0);
+        while state.tick(0.05) {}
+        assert_eq!(state.offset_y, 0.0);
+    }
+
+    #[test]
+    fn test_keyboard_scrolling() {
+        let mut state = ScrollState::new(800.0, 400.0);
+        state.set_content_size(800.0, 2000.0);
+
+        assert!(state.handle_key(&Key::Named(NamedKey::PageDown), false));
+        assert_eq!(state.target(), (0.0, 350.0));
+        assert!(state.handle_key(&Key::Named(NamedKey::ArrowDown), false));
+        assert_eq!(state.target(), (0.0, 390.0));
+        assert!(state.handle_key(&Key::Named(NamedKey::Space), true));
+        assert_eq!(state.target(), (0.0, 40.0));
+        assert!(state.handle_key(&Key::Named(NamedKey::End), false));
+        assert_eq!(state.target(), (0.0, 1600.0));
+        assert!(!state.handle_key(&Key::Named(NamedKey::Enter), false));
+    }
+
+    #[test]
+    fn test_scroll_behavior() {
+        use crate::css::CssParser;
+        use crate::dom::Node;
+        use crate::style::style_tree;
+
+        let dom = Node::element("html".to_string(), Default::default(), vec![]);
+        let css = CssParser::parse("html { scroll-behavior: smooth; }");
+        let styled = style_tree(&dom, &css);
+
+        let mut state = ScrollState::new(800.0, 600.0);
+        state.set_content_size(800.0, 2000.0);
+        state.behavior = ScrollBehavior::from_style(&styled);
+        assert_eq!(state.behavior, ScrollBehavior::Smooth);
+
+        state.scroll_to_with_behavior(0.0, 500.0, ScrollBehavior::Auto);
+        assert!(state.is_animating());
+        state.scroll_to_with_behavior(0.0, 300.0, ScrollBehavior::Instant);
+        assert_eq!(state.offset_y, 300.0);
+        assert!(!state.is_animating());
+    }
+
+    #[test]
+    fn test_scroll_rect_into_view() {
+        let mut state = ScrollState::new(800.0, 400.0);
+        state.set_content_size(800.0, 3000.0);
+        let rect = Rect { x: 0.0, y: 1000.0, width: 100.0, height: 50.0 };
+
+        state.scroll_rect_into_view(rect, ScrollAlign::Start, ScrollBehavior::Instant);
+        assert_eq!(state.offset_y, 1000.0);
+        state.scroll_rect_into_view(rect, ScrollAlign::Center, ScrollBehavior::Instant);
+        assert_eq!(state.offset_y, 825.0);
+        state.scroll_rect_into_view(rect, ScrollAlign::End, ScrollBehavior::Instant);
+        assert_eq!(state.offset_y, 650.0);
+
+        // Already visible: nearest leaves the offset alone
+        state.scroll_rect_into_view(rect, ScrollAlign::Nearest, ScrollBehavior::Instant);
+        assert_eq!(state.offset_y, 650.0);
+        state.scroll_to(0.0, 0.0);
+        state.scroll_rect_into_view(rect, ScrollAlign::Nearest, ScrollBehavior::Instant);
+        assert_eq!(state.offset_y, 650.0);
+    }
 }